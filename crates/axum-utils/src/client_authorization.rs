@@ -38,7 +38,7 @@ use serde_json::Value;
 use thiserror::Error;
 use tower::{Service, ServiceExt};
 
-use crate::http_client_factory::HttpClientFactory;
+use crate::{client_certificate::ClientCertificate, http_client_factory::HttpClientFactory};
 
 static JWT_BEARER_CLIENT_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
 
@@ -70,6 +70,10 @@ pub enum Credentials {
         client_id: String,
         jwt: Box<Jwt<'static, HashMap<String, serde_json::Value>>>,
     },
+    ClientCertificate {
+        client_id: String,
+        certificate: Box<ClientCertificate>,
+    },
 }
 
 impl Credentials {
@@ -80,7 +84,8 @@ impl Credentials {
             Credentials::None { client_id }
             | Credentials::ClientSecretBasic { client_id, .. }
             | Credentials::ClientSecretPost { client_id, .. }
-            | Credentials::ClientAssertionJwtBearer { client_id, .. } => client_id,
+            | Credentials::ClientAssertionJwtBearer { client_id, .. }
+            | Credentials::ClientCertificate { client_id, .. } => client_id,
         }
     }
 
@@ -98,7 +103,8 @@ impl Credentials {
             Credentials::None { client_id }
             | Credentials::ClientSecretBasic { client_id, .. }
             | Credentials::ClientSecretPost { client_id, .. }
-            | Credentials::ClientAssertionJwtBearer { client_id, .. } => client_id,
+            | Credentials::ClientAssertionJwtBearer { client_id, .. }
+            | Credentials::ClientCertificate { client_id, .. } => client_id,
         };
 
         repo.oauth2_client().find_by_client_id(client_id).await
@@ -118,7 +124,11 @@ impl Credentials {
         client: &Client,
     ) -> Result<(), CredentialsVerificationError> {
         match (self, method) {
-            (Credentials::None { .. }, OAuthClientAuthenticationMethod::None) => {}
+            // Public clients may present a certificate, to get certificate-bound tokens
+            (
+                Credentials::None { .. } | Credentials::ClientCertificate { .. },
+                OAuthClientAuthenticationMethod::None,
+            ) => {}
 
             (
                 Credentials::ClientSecretPost { client_secret, .. },
@@ -180,6 +190,41 @@ impl Credentials {
                     .map_err(|_| CredentialsVerificationError::InvalidAssertionSignature)?;
             }
 
+            (
+                Credentials::ClientCertificate { certificate, .. },
+                OAuthClientAuthenticationMethod::TlsClientAuth,
+            ) => {
+                let subject_dn = client
+                    .tls_client_auth_subject_dn
+                    .as_deref()
+                    .ok_or(CredentialsVerificationError::InvalidClientConfig)?;
+
+                // The certificate must have been issued by a trusted authority for its subject
+                // to mean anything
+                if !certificate.is_trusted() || !certificate.subject_matches(subject_dn) {
+                    return Err(CredentialsVerificationError::CertificateMismatch);
+                }
+            }
+
+            (
+                Credentials::ClientCertificate { certificate, .. },
+                OAuthClientAuthenticationMethod::SelfSignedTlsClientAuth,
+            ) => {
+                // Get the client JWKS
+                let jwks = client
+                    .jwks
+                    .as_ref()
+                    .ok_or(CredentialsVerificationError::InvalidClientConfig)?;
+
+                let jwks = fetch_jwks(http_client_factory, jwks)
+                    .await
+                    .map_err(|_| CredentialsVerificationError::JwksFetchFailed)?;
+
+                if !certificate.public_key_matches(&jwks) {
+                    return Err(CredentialsVerificationError::CertificateMismatch);
+                }
+            }
+
             (_, _) => {
                 return Err(CredentialsVerificationError::AuthenticationMethodMismatch);
             }
@@ -188,7 +233,13 @@ impl Credentials {
     }
 }
 
-async fn fetch_jwks(
+/// Get the JWKS of a client, fetching it if the client registered a
+/// `jwks_uri`
+///
+/// # Errors
+///
+/// Returns an error if the JWKS could not be fetched
+pub async fn fetch_jwks(
     http_client_factory: &HttpClientFactory,
     jwks: &JwksOrJwksUri,
 ) -> Result<PublicJsonWebKeySet, BoxError> {
@@ -231,12 +282,18 @@ pub enum CredentialsVerificationError {
 
     #[error("failed to fetch jwks")]
     JwksFetchFailed,
+
+    #[error("client certificate did not match")]
+    CertificateMismatch,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ClientAuthorization<F = ()> {
     pub credentials: Credentials,
     pub form: Option<F>,
+
+    /// The TLS client certificate presented with the request, if any
+    pub certificate: Option<ClientCertificate>,
 }
 
 impl<F> ClientAuthorization<F> {
@@ -352,6 +409,9 @@ where
         // Split the request into parts so we can extract some headers
         let (mut parts, body) = req.into_parts();
 
+        // The server adds the client certificate to the extensions, if there is one
+        let certificate = parts.extensions.get::<ClientCertificate>().cloned();
+
         let header =
             TypedHeader::<Authorization<Basic>>::from_request_parts(&mut parts, state).await;
 
@@ -428,8 +488,16 @@ where
             }
 
             (None, Some(client_id), None, None, None) => {
-                // Only got a client_id in the form
-                Credentials::None { client_id }
+                if let Some(certificate) = &certificate {
+                    // Got a client_id in the form and a TLS client certificate
+                    Credentials::ClientCertificate {
+                        client_id,
+                        certificate: Box::new(certificate.clone()),
+                    }
+                } else {
+                    // Only got a client_id in the form
+                    Credentials::None { client_id }
+                }
             }
 
             (
@@ -483,7 +551,11 @@ where
             }
         };
 
-        Ok(ClientAuthorization { credentials, form })
+        Ok(ClientAuthorization {
+            credentials,
+            form,
+            certificate,
+        })
     }
 }
 
@@ -514,6 +586,38 @@ mod tests {
                     client_id: "client-id".to_owned(),
                 },
                 form: Some(serde_json::json!({"foo": "bar"})),
+                certificate: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn client_certificate_test() {
+        let certificate = ClientCertificate::from_pem(crate::client_certificate::TEST_CERTIFICATE)
+            .unwrap()
+            .trusted();
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(
+                http::header::CONTENT_TYPE,
+                mime::APPLICATION_WWW_FORM_URLENCODED.as_ref(),
+            )
+            .extension(certificate.clone())
+            .body(Full::<Bytes>::new("client_id=client-id&foo=bar".into()))
+            .unwrap();
+
+        assert_eq!(
+            ClientAuthorization::<serde_json::Value>::from_request(req, &())
+                .await
+                .unwrap(),
+            ClientAuthorization {
+                credentials: Credentials::ClientCertificate {
+                    client_id: "client-id".to_owned(),
+                    certificate: Box::new(certificate.clone()),
+                },
+                form: Some(serde_json::json!({"foo": "bar"})),
+                certificate: Some(certificate),
             }
         );
     }
@@ -543,6 +647,7 @@ mod tests {
                     client_secret: "client-secret".to_owned(),
                 },
                 form: Some(serde_json::json!({"foo": "bar"})),
+                certificate: None,
             }
         );
 
@@ -570,6 +675,7 @@ mod tests {
                     client_secret: "client-secret".to_owned(),
                 },
                 form: Some(serde_json::json!({"foo": "bar"})),
+                certificate: None,
             }
         );
 
@@ -632,6 +738,7 @@ mod tests {
                     client_secret: "client-secret".to_owned(),
                 },
                 form: Some(serde_json::json!({"foo": "bar"})),
+                certificate: None,
             }
         );
     }
@@ -0,0 +1,263 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers to handle TLS client certificates, used for mutual-TLS client
+//! authentication and certificate-bound access tokens, as defined in
+//! [RFC 8705]
+//!
+//! The certificate is extracted from the connection or from a header set by a
+//! reverse proxy by the server, which then adds it to the request extensions.
+//!
+//! [RFC 8705]: https://www.rfc-editor.org/rfc/rfc8705.html
+
+use std::str::FromStr;
+
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use mas_jose::jwk::{JsonWebKeyPublicParameters, PublicJsonWebKeySet};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use x509_cert::{
+    der::{Decode, Encode},
+    name::Name,
+    Certificate,
+};
+
+#[derive(Debug, Error)]
+pub enum ClientCertificateError {
+    #[error("invalid client certificate header")]
+    InvalidHeader,
+
+    #[error("invalid client certificate")]
+    InvalidCertificate,
+}
+
+/// A TLS client certificate presented with a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    der: Vec<u8>,
+    subject: String,
+    public_key: Vec<u8>,
+    trusted: bool,
+}
+
+impl ClientCertificate {
+    /// Decode a DER-encoded X.509 certificate
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the certificate could not be decoded
+    pub fn from_der(der: Vec<u8>) -> Result<Self, ClientCertificateError> {
+        let certificate =
+            Certificate::from_der(&der).map_err(|_| ClientCertificateError::InvalidCertificate)?;
+
+        let subject = certificate.tbs_certificate.subject.to_string();
+        let public_key = certificate
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .map_err(|_| ClientCertificateError::InvalidCertificate)?;
+
+        Ok(Self {
+            der,
+            subject,
+            public_key,
+            trusted: false,
+        })
+    }
+
+    /// Decode a PEM-encoded X.509 certificate
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the certificate could not be decoded
+    pub fn from_pem(pem: &str) -> Result<Self, ClientCertificateError> {
+        let (label, der) = pem_rfc7468::decode_vec(pem.trim().as_bytes())
+            .map_err(|_| ClientCertificateError::InvalidCertificate)?;
+
+        if label != "CERTIFICATE" {
+            return Err(ClientCertificateError::InvalidCertificate);
+        }
+
+        Self::from_der(der)
+    }
+
+    /// Decode a certificate forwarded by a reverse proxy in a header
+    ///
+    /// The value is either the DER-encoded certificate as a structured field
+    /// byte sequence, as defined in [RFC 9440], or a URL-encoded PEM
+    /// certificate, like the one nginx provides in `$ssl_client_escaped_cert`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header value or the certificate could not be
+    /// decoded
+    ///
+    /// [RFC 9440]: https://www.rfc-editor.org/rfc/rfc9440.html
+    pub fn from_header_value(value: &str) -> Result<Self, ClientCertificateError> {
+        let value = value.trim();
+
+        if let Some(encoded) = value.strip_prefix(':').and_then(|v| v.strip_suffix(':')) {
+            let der = BASE64
+                .decode(encoded.as_bytes())
+                .map_err(|_| ClientCertificateError::InvalidHeader)?;
+            return Self::from_der(der);
+        }
+
+        let pem = percent_encoding::percent_decode_str(value)
+            .decode_utf8()
+            .map_err(|_| ClientCertificateError::InvalidHeader)?;
+        Self::from_pem(&pem)
+    }
+
+    /// Mark the certificate as issued by one of the trusted certificate
+    /// authorities
+    #[must_use]
+    pub fn trusted(mut self) -> Self {
+        self.trusted = true;
+        self
+    }
+
+    /// Whether the certificate was issued by one of the trusted certificate
+    /// authorities
+    #[must_use]
+    pub fn is_trusted(&self) -> bool {
+        self.trusted
+    }
+
+    /// The DER-encoded certificate
+    #[must_use]
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The subject distinguished name of the certificate, in the RFC 4514
+    /// string format
+    #[must_use]
+    pub fn subject_dn(&self) -> &str {
+        &self.subject
+    }
+
+    /// Compute the SHA-256 thumbprint of the certificate, used in the
+    /// `x5t#S256` confirmation method of certificate-bound tokens
+    ///
+    /// The result is base64url-encoded, without padding.
+    #[must_use]
+    pub fn thumbprint_sha256(&self) -> String {
+        BASE64URL_NOPAD.encode(&Sha256::digest(&self.der))
+    }
+
+    /// Check whether the subject of the certificate matches the given
+    /// distinguished name
+    ///
+    /// Both are compared in their normalized RFC 4514 string format.
+    #[must_use]
+    pub fn subject_matches(&self, expected: &str) -> bool {
+        Name::from_str(expected).map_or_else(
+            |_| self.subject == expected,
+            |name| self.subject == name.to_string(),
+        )
+    }
+
+    /// Check whether the public key of the certificate is one of the keys of
+    /// the given JWKS
+    #[must_use]
+    pub fn public_key_matches(&self, jwks: &PublicJsonWebKeySet) -> bool {
+        let Some(params) = JsonWebKeyPublicParameters::from_public_key_der(&self.public_key) else {
+            return false;
+        };
+
+        let thumbprint = params.thumbprint_sha256();
+        jwks.iter()
+            .any(|key| key.params().thumbprint_sha256() == thumbprint)
+    }
+}
+
+/// A self-signed certificate for `CN=client.example.com,O=Example,C=FR`
+#[cfg(test)]
+pub(crate) const TEST_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBZjCCAQugAwIBAgIBATAKBggqhkjOPQQDAjA8MQswCQYDVQQGEwJGUjEQMA4G
+A1UECgwHRXhhbXBsZTEbMBkGA1UEAwwSY2xpZW50LmV4YW1wbGUuY29tMB4XDTIz
+MDEwMTAwMDAwMFoXDTMzMDEwMTAwMDAwMFowPDELMAkGA1UEBhMCRlIxEDAOBgNV
+BAoMB0V4YW1wbGUxGzAZBgNVBAMMEmNsaWVudC5leGFtcGxlLmNvbTBZMBMGByqG
+SM49AgEGCCqGSM49AwEHA0IABFdPmSxbkzqGG5c9XGZ1Z9/IxmTb4rxtnNYxB1ZD
+SEN3EggIaAf8aE0jFeM5qXomq2D38EG13qM2qvJh6MdKaFswCgYIKoZIzj0EAwID
+SQAwRgIhAPdXs9x8Ves5WiDILFCF6gIVerGu24M4AyfkK9Va7YhQAiEAkqTLFg/B
+KGZ2CMPOCfLS7lRMoFo+yMWD17QZb+/wbXM=
+-----END CERTIFICATE-----
+";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_certificate() {
+        let certificate = ClientCertificate::from_pem(TEST_CERTIFICATE).unwrap();
+        assert!(!certificate.is_trusted());
+        assert_eq!(
+            certificate.subject_dn(),
+            "CN=client.example.com,O=Example,C=FR"
+        );
+        assert_eq!(
+            certificate.thumbprint_sha256(),
+            "T2H81IkWfHDS-IbkjXHicqu3tPNAiOBTzZ2Dik6I1sI"
+        );
+
+        assert!(certificate.subject_matches("CN=client.example.com,O=Example,C=FR"));
+        assert!(!certificate.subject_matches("CN=other.example.com,O=Example,C=FR"));
+
+        assert!(ClientCertificate::from_pem("not a certificate").is_err());
+    }
+
+    #[test]
+    fn test_from_header_value() {
+        let certificate = ClientCertificate::from_pem(TEST_CERTIFICATE).unwrap();
+
+        // As a structured field byte sequence
+        let value = format!(":{}:", BASE64.encode(certificate.der()));
+        assert_eq!(
+            ClientCertificate::from_header_value(&value).unwrap(),
+            certificate
+        );
+
+        // As an URL-encoded PEM
+        let value: String =
+            url::form_urlencoded::byte_serialize(TEST_CERTIFICATE.as_bytes()).collect();
+        let value = value.replace('+', "%20");
+        assert_eq!(
+            ClientCertificate::from_header_value(&value).unwrap(),
+            certificate
+        );
+
+        assert!(ClientCertificate::from_header_value(":not base64:").is_err());
+    }
+
+    #[test]
+    fn test_public_key_matches() {
+        let certificate = ClientCertificate::from_pem(TEST_CERTIFICATE).unwrap();
+
+        let jwks: PublicJsonWebKeySet = serde_json::from_value(serde_json::json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "x": "V0-ZLFuTOoYblz1cZnVn38jGZNvivG2c1jEHVkNIQ3c",
+                "y": "EggIaAf8aE0jFeM5qXomq2D38EG13qM2qvJh6MdKaFs",
+            }]
+        }))
+        .unwrap();
+        assert!(certificate.public_key_matches(&jwks));
+
+        assert!(!certificate.public_key_matches(&PublicJsonWebKeySet::default()));
+    }
+}
@@ -146,6 +146,16 @@ impl CookieJar {
         self
     }
 
+    /// Remove a cookie from the jar
+    #[must_use]
+    pub fn remove(mut self, key: &str) -> Self {
+        let cookie = Cookie::new(key.to_owned(), "");
+        let cookie = self.options.apply(cookie);
+        self.inner = self.inner.remove(cookie);
+
+        self
+    }
+
     /// Load and deserialize a cookie from the jar
     ///
     /// Returns `None` if the cookie is not present
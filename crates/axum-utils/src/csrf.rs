@@ -111,6 +111,15 @@ pub struct ProtectedForm<T> {
     inner: T,
 }
 
+impl<T> ProtectedForm<T> {
+    /// Build a CSRF-protected form out of its parts, for forms which can't be
+    /// deserialized directly, like multipart uploads
+    #[must_use]
+    pub fn new(csrf: String, inner: T) -> Self {
+        Self { csrf, inner }
+    }
+}
+
 pub trait CsrfExt {
     /// Get the current CSRF token out of the cookie jar, generating a new one
     /// if necessary
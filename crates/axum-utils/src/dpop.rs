@@ -0,0 +1,233 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers to handle DPoP proofs sent along requests, as defined in [RFC 9449]
+//!
+//! [RFC 9449]: https://www.rfc-editor.org/rfc/rfc9449.html
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use http::{HeaderMap, HeaderName, HeaderValue};
+use mas_jose::dpop::{DPoPProof, DPoPProofError, DPoPProofOptions};
+use mas_keystore::Encrypter;
+use mas_storage::Clock;
+use thiserror::Error;
+use url::Url;
+
+/// The header in which clients send their DPoP proof
+pub static DPOP: HeaderName = HeaderName::from_static("dpop");
+
+/// The header in which the server sends a nonce to include in the next proofs
+pub static DPOP_NONCE: HeaderName = HeaderName::from_static("dpop-nonce");
+
+/// How long a nonce we generated is accepted
+const NONCE_LIFETIME: i64 = 5 * 60;
+
+/// How long after its issuance a proof is accepted
+const PROOF_MAX_AGE: i64 = 5 * 60;
+
+#[derive(Debug, Error)]
+pub enum DPoPError {
+    #[error("more than one DPoP proof was sent")]
+    MultipleProofs,
+
+    #[error("invalid DPoP header")]
+    InvalidHeader,
+
+    #[error(transparent)]
+    InvalidProof(#[from] DPoPProofError),
+
+    #[error("invalid or expired DPoP nonce")]
+    InvalidNonce,
+
+    #[error("DPoP proof was already used")]
+    Replayed,
+
+    #[error("failed to generate a DPoP nonce")]
+    NonceGeneration,
+}
+
+/// Extract the DPoP proof from the request headers, if any
+///
+/// # Errors
+///
+/// Returns an error if more than one proof was sent, or if the header is not
+/// valid UTF-8
+pub fn proof_from_headers(headers: &HeaderMap) -> Result<Option<&str>, DPoPError> {
+    let mut values = headers.get_all(&DPOP).iter();
+    let Some(value) = values.next() else {
+        return Ok(None);
+    };
+
+    if values.next().is_some() {
+        return Err(DPoPError::MultipleProofs);
+    }
+
+    let value = value.to_str().map_err(|_| DPoPError::InvalidHeader)?;
+    Ok(Some(value))
+}
+
+/// Keeps track of the DPoP proofs which were already used, so that a captured
+/// proof can't be replayed
+#[async_trait]
+pub trait DPoPReplayCache: Send + Sync {
+    /// Record the use of the proof identified by `jti`, signed by the key with
+    /// the `jkt` thumbprint. It only has to be remembered until `expires_at`,
+    /// after which the proof is rejected anyway.
+    ///
+    /// Returns `false` if the proof was already used
+    async fn record(
+        &self,
+        clock: &dyn Clock,
+        jkt: &str,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> bool;
+}
+
+/// Verify a DPoP proof, and record its use so that it can't be used again
+///
+/// # Errors
+///
+/// Returns an error if the proof is invalid, does not match the request, or
+/// was already used
+pub async fn verify_fresh_proof(
+    proof: &str,
+    options: DPoPProofOptions<'_>,
+    replay_cache: &dyn DPoPReplayCache,
+    clock: &dyn Clock,
+) -> Result<DPoPProof, DPoPError> {
+    let max_age = Duration::seconds(PROOF_MAX_AGE);
+    let proof = DPoPProof::verify(proof, &options.max_age(max_age))?;
+
+    let expires_at = proof.issued_at() + max_age;
+    if !replay_cache
+        .record(clock, proof.jkt(), proof.jti(), expires_at)
+        .await
+    {
+        return Err(DPoPError::Replayed);
+    }
+
+    Ok(proof)
+}
+
+/// Verify the DPoP proof sent with a request, if any
+///
+/// If the proof includes a nonce, it must be one previously generated by
+/// [`generate_nonce`] which is still valid. Each proof can only be used once.
+///
+/// # Errors
+///
+/// Returns an error if the proof is invalid, does not match the request, was
+/// already used or has an invalid nonce
+pub async fn verify_proof(
+    headers: &HeaderMap,
+    method: &str,
+    uri: &Url,
+    access_token: Option<&str>,
+    encrypter: &Encrypter,
+    replay_cache: &dyn DPoPReplayCache,
+    clock: &dyn Clock,
+) -> Result<Option<DPoPProof>, DPoPError> {
+    let Some(proof) = proof_from_headers(headers)? else {
+        return Ok(None);
+    };
+
+    let mut options = DPoPProofOptions::new(method, uri, clock.now());
+    if let Some(access_token) = access_token {
+        options = options.with_access_token(access_token);
+    }
+
+    let proof = verify_fresh_proof(proof, options, replay_cache, clock).await?;
+
+    if let Some(nonce) = proof.nonce() {
+        validate_nonce(encrypter, clock.now(), nonce)?;
+    }
+
+    Ok(Some(proof))
+}
+
+/// Generate a nonce which clients can include in their next DPoP proofs
+///
+/// Nonces are stateless: they are an encrypted timestamp, and are accepted
+/// for five minutes.
+///
+/// # Errors
+///
+/// Returns an error if the nonce could not be encrypted
+pub fn generate_nonce(encrypter: &Encrypter, now: DateTime<Utc>) -> Result<String, DPoPError> {
+    encrypter
+        .encrypt_to_string(&now.timestamp().to_be_bytes())
+        .map_err(|_| DPoPError::NonceGeneration)
+}
+
+/// Generate a nonce, as a value for the [`DPOP_NONCE`] header
+///
+/// # Errors
+///
+/// Returns an error if the nonce could not be encrypted
+pub fn nonce_header(encrypter: &Encrypter, now: DateTime<Utc>) -> Result<HeaderValue, DPoPError> {
+    let nonce = generate_nonce(encrypter, now)?;
+    HeaderValue::try_from(nonce).map_err(|_| DPoPError::NonceGeneration)
+}
+
+fn validate_nonce(encrypter: &Encrypter, now: DateTime<Utc>, nonce: &str) -> Result<(), DPoPError> {
+    let decrypted = encrypter
+        .decrypt_string(nonce)
+        .map_err(|_| DPoPError::InvalidNonce)?;
+    let timestamp: [u8; 8] = decrypted.try_into().map_err(|_| DPoPError::InvalidNonce)?;
+    let issued_at = Utc
+        .timestamp_opt(i64::from_be_bytes(timestamp), 0)
+        .single()
+        .ok_or(DPoPError::InvalidNonce)?;
+
+    if issued_at > now || now - issued_at > Duration::seconds(NONCE_LIFETIME) {
+        return Err(DPoPError::InvalidNonce);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nonce_lifetime() {
+        let encrypter = Encrypter::new(&[0x42; 32]);
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+        let nonce = generate_nonce(&encrypter, now).unwrap();
+        assert!(validate_nonce(&encrypter, now, &nonce).is_ok());
+        assert!(validate_nonce(&encrypter, now + Duration::minutes(4), &nonce).is_ok());
+        assert!(validate_nonce(&encrypter, now + Duration::minutes(6), &nonce).is_err());
+        assert!(validate_nonce(&encrypter, now - Duration::minutes(1), &nonce).is_err());
+        assert!(validate_nonce(&encrypter, now, "not-a-nonce").is_err());
+    }
+
+    #[test]
+    fn test_proof_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(matches!(proof_from_headers(&headers), Ok(None)));
+
+        headers.append(&DPOP, HeaderValue::from_static("a.b.c"));
+        assert!(matches!(proof_from_headers(&headers), Ok(Some("a.b.c"))));
+
+        headers.append(&DPOP, HeaderValue::from_static("d.e.f"));
+        assert!(matches!(
+            proof_from_headers(&headers),
+            Err(DPoPError::MultipleProofs)
+        ));
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Forms which can be submitted either by the browser or as JSON by the
+//! frontend
+
+use std::hash::Hash;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Form, FromRequest},
+    response::{IntoResponse, Redirect, Response},
+    BoxError, Json,
+};
+use http::{header::LOCATION, Request, StatusCode};
+use http_body::Body as HttpBody;
+use mas_templates::FormState;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A form submitted either by the browser as
+/// `application/x-www-form-urlencoded`, or by the frontend as
+/// `application/json`.
+///
+/// Forms submitted as JSON expect a reply in kind, see [`form_errors`] and
+/// [`json_redirect`].
+pub struct FormOrJson<T> {
+    inner: T,
+    json: bool,
+}
+
+impl<T> FormOrJson<T> {
+    /// Whether the form was submitted as JSON
+    #[must_use]
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Get the submitted form
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+fn is_json_content_type<B>(req: &Request<B>) -> bool {
+    let Some(content_type) = req
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+    else {
+        return false;
+    };
+
+    content_type.type_() == mime::APPLICATION
+        && (content_type.subtype() == mime::JSON
+            || content_type
+                .suffix()
+                .is_some_and(|suffix| suffix == mime::JSON))
+}
+
+#[async_trait]
+impl<S, B, T> FromRequest<S, B> for FormOrJson<T>
+where
+    T: DeserializeOwned,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        if is_json_content_type(&req) {
+            let Json(inner) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self { inner, json: true })
+        } else {
+            let Form(inner) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self { inner, json: false })
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FormErrorsResponse<'a, K: Hash + Eq> {
+    form: &'a FormState<K>,
+}
+
+/// Reply to a form submitted as JSON with the errors on the form and its
+/// fields
+#[must_use]
+pub fn form_errors<K>(status: StatusCode, state: &FormState<K>) -> Response
+where
+    K: Serialize + Hash + Eq,
+{
+    (status, Json(FormErrorsResponse { form: state })).into_response()
+}
+
+#[derive(Serialize)]
+struct RedirectResponse<'a> {
+    redirect_uri: &'a str,
+}
+
+/// Reply to a form submitted as JSON with where the frontend should go next,
+/// instead of redirecting the browser there
+#[must_use]
+pub fn json_redirect(redirect: Redirect) -> Response {
+    let response = redirect.into_response();
+    let Some(location) = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return response;
+    };
+
+    Json(RedirectResponse {
+        redirect_uri: location,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Bytes, Full};
+    use http::Method;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct TestForm {
+        username: String,
+    }
+
+    #[tokio::test]
+    async fn test_form_or_json() {
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(
+                http::header::CONTENT_TYPE,
+                mime::APPLICATION_WWW_FORM_URLENCODED.as_ref(),
+            )
+            .body(Full::<Bytes>::new("username=alice".into()))
+            .unwrap();
+        let form = FormOrJson::<TestForm>::from_request(req, &())
+            .await
+            .unwrap();
+        assert!(!form.is_json());
+        assert_eq!(form.into_inner().username, "alice");
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .header(
+                http::header::CONTENT_TYPE,
+                "application/json; charset=utf-8",
+            )
+            .body(Full::<Bytes>::new(r#"{"username": "bob"}"#.into()))
+            .unwrap();
+        let form = FormOrJson::<TestForm>::from_request(req, &())
+            .await
+            .unwrap();
+        assert!(form.is_json());
+        assert_eq!(form.into_inner().username, "bob");
+    }
+
+    #[test]
+    fn test_json_redirect() {
+        let response = json_redirect(Redirect::to("/account/"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(LOCATION).is_none());
+    }
+}
@@ -16,10 +16,13 @@
 #![allow(clippy::module_name_repetitions)]
 
 pub mod client_authorization;
+pub mod client_certificate;
 pub mod cookies;
 pub mod csrf;
+pub mod dpop;
 pub mod error_wrapper;
 pub mod fancy_error;
+pub mod form_or_json;
 pub mod http_client_factory;
 pub mod jwt;
 pub mod language_detection;
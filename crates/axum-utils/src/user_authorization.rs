@@ -25,14 +25,25 @@ use axum::{
     BoxError,
 };
 use headers::{authorization::Bearer, Authorization, Header, HeaderMapExt, HeaderName};
-use http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue, Request, StatusCode};
+use http::{
+    header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    request::Parts,
+    HeaderMap, HeaderValue, Method, Request, StatusCode,
+};
 use mas_data_model::Session;
+use mas_jose::dpop::DPoPProofOptions;
 use mas_storage::{
     oauth2::{OAuth2AccessTokenRepository, OAuth2SessionRepository},
     Clock, RepositoryAccess,
 };
 use serde::{de::DeserializeOwned, Deserialize};
 use thiserror::Error;
+use url::Url;
+
+use crate::{
+    client_certificate::ClientCertificate,
+    dpop::{proof_from_headers, verify_fresh_proof, DPoPReplayCache},
+};
 
 #[derive(Debug, Deserialize)]
 struct AuthorizedForm<F> {
@@ -47,6 +58,7 @@ struct AuthorizedForm<F> {
 enum AccessToken {
     Form(String),
     Header(String),
+    DPoP(String),
     None,
 }
 
@@ -56,7 +68,7 @@ impl AccessToken {
         repo: &mut impl RepositoryAccess<Error = E>,
     ) -> Result<(mas_data_model::AccessToken, Session), AuthorizationVerificationError<E>> {
         let token = match self {
-            AccessToken::Form(t) | AccessToken::Header(t) => t,
+            AccessToken::Form(t) | AccessToken::Header(t) | AccessToken::DPoP(t) => t,
             AccessToken::None => return Err(AuthorizationVerificationError::MissingToken),
         };
 
@@ -79,6 +91,9 @@ impl AccessToken {
 #[derive(Debug)]
 pub struct UserAuthorization<F = ()> {
     access_token: AccessToken,
+    dpop_proof: Option<String>,
+    certificate: Option<ClientCertificate>,
+    method: Method,
     form: Option<F>,
 }
 
@@ -87,14 +102,20 @@ impl<F: Send> UserAuthorization<F> {
     /// Verify a user authorization and return the session and the protected
     /// form value
     ///
+    /// The `uri` is the public URL of the protected resource, against which
+    /// DPoP proofs are checked, and the `replay_cache` keeps track of the
+    /// proofs already used.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the token is invalid, if the user session ended or
-    /// if the form is missing
+    /// Returns an error if the token is invalid, if the user session ended,
+    /// if the DPoP proof is invalid or if the form is missing
     pub async fn protected_form<E>(
         self,
         repo: &mut impl RepositoryAccess<Error = E>,
         clock: &impl Clock,
+        uri: &Url,
+        replay_cache: &dyn DPoPReplayCache,
     ) -> Result<(Session, F), AuthorizationVerificationError<E>> {
         let Some(form) = self.form else {
             return Err(AuthorizationVerificationError::MissingForm);
@@ -106,19 +127,38 @@ impl<F: Send> UserAuthorization<F> {
             return Err(AuthorizationVerificationError::InvalidToken);
         }
 
+        verify_binding(
+            &self.access_token,
+            self.dpop_proof.as_deref(),
+            self.certificate.as_ref(),
+            &self.method,
+            uri,
+            clock,
+            replay_cache,
+            &session,
+        )
+        .await?;
+
         Ok((session, form))
     }
 
     // TODO: take scopes to validate as parameter
     /// Verify a user authorization and return the session
     ///
+    /// The `uri` is the public URL of the protected resource, against which
+    /// DPoP proofs are checked, and the `replay_cache` keeps track of the
+    /// proofs already used.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the token is invalid or if the user session ended
+    /// Returns an error if the token is invalid, if the user session ended or
+    /// if the DPoP proof is invalid
     pub async fn protected<E>(
         self,
         repo: &mut impl RepositoryAccess<Error = E>,
         clock: &impl Clock,
+        uri: &Url,
+        replay_cache: &dyn DPoPReplayCache,
     ) -> Result<Session, AuthorizationVerificationError<E>> {
         let (token, session) = self.access_token.fetch(repo).await?;
 
@@ -126,10 +166,77 @@ impl<F: Send> UserAuthorization<F> {
             return Err(AuthorizationVerificationError::InvalidToken);
         }
 
+        verify_binding(
+            &self.access_token,
+            self.dpop_proof.as_deref(),
+            self.certificate.as_ref(),
+            &self.method,
+            uri,
+            clock,
+            replay_cache,
+            &session,
+        )
+        .await?;
+
         Ok(session)
     }
 }
 
+/// Check that the token was presented the way the session requires: with a
+/// valid DPoP proof for sessions bound to a DPoP key, as a bearer token
+/// otherwise, and with the same client certificate for sessions bound to one
+async fn verify_binding<E>(
+    access_token: &AccessToken,
+    dpop_proof: Option<&str>,
+    certificate: Option<&ClientCertificate>,
+    method: &Method,
+    uri: &Url,
+    clock: &impl Clock,
+    replay_cache: &dyn DPoPReplayCache,
+    session: &Session,
+) -> Result<(), AuthorizationVerificationError<E>> {
+    match (session.dpop_jkt.as_deref(), access_token) {
+        (Some(jkt), AccessToken::DPoP(token)) => {
+            let proof = dpop_proof.ok_or(AuthorizationVerificationError::InvalidDPoPProof)?;
+            let options =
+                DPoPProofOptions::new(method.as_str(), uri, clock.now()).with_access_token(token);
+            let proof = verify_fresh_proof(proof, options, replay_cache, clock)
+                .await
+                .map_err(|_| AuthorizationVerificationError::InvalidDPoPProof)?;
+
+            if proof.jkt() != jkt {
+                return Err(AuthorizationVerificationError::InvalidDPoPProof);
+            }
+        }
+        // DPoP-bound tokens can't be used as bearer tokens, and bearer tokens can't
+        // be used with the DPoP scheme
+        (Some(_), _) | (None, AccessToken::DPoP(_)) => {
+            return Err(AuthorizationVerificationError::InvalidToken);
+        }
+        (None, _) => {}
+    }
+
+    // Certificate-bound tokens can only be used along the same client certificate
+    if let Some(thumbprint) = session.certificate_thumbprint.as_deref() {
+        let presented = certificate.map(ClientCertificate::thumbprint_sha256);
+        if presented.as_deref() != Some(thumbprint) {
+            return Err(AuthorizationVerificationError::InvalidToken);
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the access token from an `Authorization` header using the `DPoP`
+/// scheme, if any
+fn dpop_token_from_headers(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("DPoP")
+        .then(|| token.trim().to_owned())
+}
+
 pub enum UserAuthorizationError {
     InvalidHeader,
     TokenInFormAndHeader,
@@ -148,6 +255,9 @@ pub enum AuthorizationVerificationError<E> {
     #[error("missing form")]
     MissingForm,
 
+    #[error("invalid DPoP proof")]
+    InvalidDPoPProof,
+
     #[error(transparent)]
     Internal(#[from] E),
 }
@@ -184,12 +294,17 @@ impl BearerError {
 
 enum WwwAuthenticate {
     #[allow(dead_code)]
-    Basic { realm: HeaderValue },
+    Basic {
+        realm: HeaderValue,
+    },
     Bearer {
         realm: Option<HeaderValue>,
         error: BearerError,
         error_description: Option<HeaderValue>,
     },
+    DPoP {
+        error: HeaderValue,
+    },
 }
 
 impl Header for WwwAuthenticate {
@@ -230,6 +345,11 @@ impl Header for WwwAuthenticate {
 
                 ("Bearer", params)
             }
+            WwwAuthenticate::DPoP { error } => {
+                let mut params = HashMap::new();
+                params.insert("error", error.clone());
+                ("DPoP", params)
+            }
         };
 
         let params = params.into_iter().map(|(k, v)| format!(" {k}={v:?}"));
@@ -283,11 +403,68 @@ where
                 });
                 (StatusCode::BAD_REQUEST, headers).into_response()
             }
+            Self::InvalidDPoPProof => {
+                let mut headers = HeaderMap::new();
+
+                headers.typed_insert(WwwAuthenticate::DPoP {
+                    error: HeaderValue::from_static("invalid_dpop_proof"),
+                });
+                (StatusCode::UNAUTHORIZED, headers).into_response()
+            }
             Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
         }
     }
 }
 
+/// The parts of the authorization which are found in the request headers
+struct HeaderAuthorization {
+    access_token: Option<AccessToken>,
+    dpop_proof: Option<String>,
+    certificate: Option<ClientCertificate>,
+    method: Method,
+}
+
+impl HeaderAuthorization {
+    async fn from_parts<S: Send + Sync>(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, UserAuthorizationError> {
+        // Take the DPoP proof, if any
+        let dpop_proof = proof_from_headers(&parts.headers)
+            .map_err(|_| UserAuthorizationError::InvalidHeader)?
+            .map(ToOwned::to_owned);
+        let method = parts.method.clone();
+
+        // The server adds the client certificate to the extensions, if there is one
+        let certificate = parts.extensions.get::<ClientCertificate>().cloned();
+
+        // Take the Authorization header, either with the DPoP or the Bearer scheme
+        let token_from_header = if let Some(token) = dpop_token_from_headers(&parts.headers) {
+            Some(AccessToken::DPoP(token))
+        } else {
+            let header =
+                TypedHeader::<Authorization<Bearer>>::from_request_parts(&mut parts, state).await;
+
+            match header {
+                Ok(header) => Some(AccessToken::Header(header.token().to_owned())),
+                Err(err) => match err.reason() {
+                    // If it's missing it is fine
+                    TypedHeaderRejectionReason::Missing => None,
+                    // If the header could not be parsed, return the error
+                    _ => return Err(UserAuthorizationError::InvalidHeader),
+                },
+            }
+        };
+
+        Ok(Self {
+            access_token: token_from_header,
+            dpop_proof,
+            certificate,
+            method,
+        })
+    }
+}
+
 #[async_trait]
 impl<S, B, F> FromRequest<S, B> for UserAuthorization<F>
 where
@@ -301,19 +478,13 @@ where
 
     async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
         let (mut parts, body) = req.into_parts();
-        let header =
-            TypedHeader::<Authorization<Bearer>>::from_request_parts(&mut parts, state).await;
-
-        // Take the Authorization header
-        let token_from_header = match header {
-            Ok(header) => Some(header.token().to_owned()),
-            Err(err) => match err.reason() {
-                // If it's missing it is fine
-                TypedHeaderRejectionReason::Missing => None,
-                // If the header could not be parsed, return the error
-                _ => return Err(UserAuthorizationError::InvalidHeader),
-            },
-        };
+
+        let HeaderAuthorization {
+            access_token: token_from_header,
+            dpop_proof,
+            certificate,
+            method,
+        } = HeaderAuthorization::from_parts(&mut parts, state).await?;
 
         let req = Request::from_parts(parts, body);
 
@@ -334,11 +505,47 @@ where
         let access_token = match (token_from_header, token_from_form) {
             // Ensure the token should not be in both the form and the access token
             (Some(_), Some(_)) => return Err(UserAuthorizationError::TokenInFormAndHeader),
-            (Some(t), None) => AccessToken::Header(t),
+            (Some(t), None) => t,
             (None, Some(t)) => AccessToken::Form(t),
             (None, None) => AccessToken::None,
         };
 
-        Ok(UserAuthorization { access_token, form })
+        Ok(UserAuthorization {
+            access_token,
+            dpop_proof,
+            certificate,
+            method,
+            form,
+        })
+    }
+}
+
+/// Like [`UserAuthorization`], but only looks for the access token in the
+/// request headers, leaving the body to other extractors, like a JSON body
+#[derive(Debug)]
+pub struct HeaderUserAuthorization(pub UserAuthorization);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for HeaderUserAuthorization
+where
+    S: Send + Sync,
+{
+    type Rejection = UserAuthorizationError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let HeaderAuthorization {
+            access_token,
+            dpop_proof,
+            certificate,
+            method,
+        } = HeaderAuthorization::from_parts(parts, state).await?;
+
+        Ok(Self(UserAuthorization {
+            access_token: access_token.unwrap_or(AccessToken::None),
+            dpop_proof,
+            certificate,
+            method,
+            form: None,
+        }))
     }
 }
@@ -0,0 +1,304 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP access log, written independently of the tracing logs
+
+use std::{io::Write, net::IpAddr, sync::Arc, time::Instant};
+
+use anyhow::Context;
+use axum::{extract::State, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::{CONTENT_LENGTH, REFERER, USER_AGENT},
+    HeaderMap, Request,
+};
+use ipnetwork::IpNetwork;
+use mas_config::{HttpAccessLogConfig, HttpAccessLogFormat};
+use mas_router::SimpleRoute;
+use mas_storage::{Clock, SystemClock};
+use tracing::warn;
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
+
+use crate::app_state::infer_client_ip;
+
+/// Writes a line for each HTTP request to the configured output
+#[derive(Clone)]
+pub struct AccessLog {
+    format: HttpAccessLogFormat,
+    writer: NonBlocking,
+    trusted_proxies: Arc<[IpNetwork]>,
+    exclude_health_checks: bool,
+    excluded_path: Option<Arc<str>>,
+    listener: Option<Arc<str>>,
+}
+
+impl AccessLog {
+    /// Open the access log output described by the configuration
+    ///
+    /// The returned [`WorkerGuard`] must be kept around for the lifetime of
+    /// the server, as the pending lines are flushed when it gets dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log file could not be opened
+    pub fn from_config(
+        config: &HttpAccessLogConfig,
+        trusted_proxies: &[IpNetwork],
+    ) -> Result<(Self, WorkerGuard), anyhow::Error> {
+        // Access logs are often kept for compliance reasons, so we'd rather slow
+        // down the requests than silently drop lines when the writer can't keep up
+        let builder = NonBlockingBuilder::default().lossy(false);
+
+        let (writer, guard) = if let Some(path) = &config.path {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("could not open the access log file {path}"))?;
+            builder.finish(file)
+        } else {
+            builder.finish(std::io::stdout())
+        };
+
+        let access_log = Self {
+            format: config.format,
+            writer,
+            trusted_proxies: trusted_proxies.into(),
+            exclude_health_checks: config.exclude_health_checks,
+            excluded_path: None,
+            listener: None,
+        };
+
+        Ok((access_log, guard))
+    }
+
+    /// Get a copy of the access log for the given listener, as the path of
+    /// the health check endpoint depends on the listener prefix
+    #[must_use]
+    pub fn for_listener(&self, name: Option<&str>, prefix: Option<&str>) -> Self {
+        let excluded_path = self.exclude_health_checks.then(|| {
+            let prefix = prefix.unwrap_or_default().trim_end_matches('/');
+            format!("{prefix}{}", mas_router::Healthcheck::PATH).into()
+        });
+
+        Self {
+            excluded_path,
+            listener: name.map(Into::into),
+            ..self.clone()
+        }
+    }
+
+    fn write(&self, entry: &Entry<'_>) {
+        let mut line = match self.format {
+            HttpAccessLogFormat::Common => entry.common(),
+            HttpAccessLogFormat::Combined => entry.combined(),
+            HttpAccessLogFormat::Json => entry.json(self.listener.as_deref()),
+        };
+        line.push('\n');
+
+        let mut writer = self.writer.clone();
+        if let Err(e) = writer.write_all(line.as_bytes()) {
+            warn!(
+                error = &e as &dyn std::error::Error,
+                "Failed to write to the access log"
+            );
+        }
+    }
+}
+
+/// Informations about a request and its response, as they appear in the
+/// access log
+struct Entry<'a> {
+    client_ip: Option<IpAddr>,
+    time: DateTime<Utc>,
+    request_line: String,
+    method: &'a str,
+    uri: &'a str,
+    protocol: String,
+    status: u16,
+    bytes: Option<u64>,
+    referer: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    duration_ms: u64,
+}
+
+/// Escape a value to be put between double quotes in a log line
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Entry<'_> {
+    fn common(&self) -> String {
+        format!(
+            r#"{client_ip} - - [{time}] "{request_line}" {status} {bytes}"#,
+            client_ip = self
+                .client_ip
+                .map_or_else(|| "-".to_owned(), |ip| ip.to_string()),
+            time = self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            request_line = escape(&self.request_line),
+            status = self.status,
+            bytes = self
+                .bytes
+                .map_or_else(|| "-".to_owned(), |bytes| bytes.to_string()),
+        )
+    }
+
+    fn combined(&self) -> String {
+        format!(
+            r#"{common} "{referer}" "{user_agent}""#,
+            common = self.common(),
+            referer = escape(self.referer.unwrap_or("-")),
+            user_agent = escape(self.user_agent.unwrap_or("-")),
+        )
+    }
+
+    fn json(&self, listener: Option<&str>) -> String {
+        serde_json::json!({
+            "time": self.time.to_rfc3339(),
+            "listener": listener,
+            "client_ip": self.client_ip,
+            "method": self.method,
+            "uri": self.uri,
+            "protocol": self.protocol,
+            "status": self.status,
+            "bytes": self.bytes,
+            "referer": self.referer,
+            "user_agent": self.user_agent,
+            "duration_ms": self.duration_ms,
+        })
+        .to_string()
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &hyper::header::HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Middleware writing an access log line for each request
+pub async fn middleware<B>(
+    State(access_log): State<AccessLog>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if access_log.excluded_path.as_deref() == Some(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let start = Instant::now();
+    let time = SystemClock::default().now();
+
+    let (parts, body) = request.into_parts();
+    let client_ip = infer_client_ip(&parts, &access_log.trusted_proxies);
+    let method = parts.method.clone();
+    let uri = parts.uri.clone();
+    let version = parts.version;
+    let referer = header_str(&parts.headers, &REFERER).map(ToOwned::to_owned);
+    let user_agent = header_str(&parts.headers, &USER_AGENT).map(ToOwned::to_owned);
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let uri = uri
+        .path_and_query()
+        .map_or_else(|| uri.path(), |path_and_query| path_and_query.as_str());
+    let protocol = format!("{version:?}");
+    let bytes = header_str(response.headers(), &CONTENT_LENGTH).and_then(|v| v.parse().ok());
+
+    let entry = Entry {
+        client_ip,
+        time,
+        request_line: format!("{method} {uri} {protocol}"),
+        method: method.as_str(),
+        uri,
+        protocol,
+        status: response.status().as_u16(),
+        bytes,
+        referer: referer.as_deref(),
+        user_agent: user_agent.as_deref(),
+        duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+    };
+
+    access_log.write(&entry);
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn entry() -> Entry<'static> {
+        Entry {
+            client_ip: Some([192, 0, 2, 1].into()),
+            time: Utc.with_ymd_and_hms(2023, 12, 6, 13, 55, 36).unwrap(),
+            request_line: "GET /login?next=%2F HTTP/1.1".to_owned(),
+            method: "GET",
+            uri: "/login?next=%2F",
+            protocol: "HTTP/1.1".to_owned(),
+            status: 200,
+            bytes: Some(2326),
+            referer: None,
+            user_agent: Some(r#"Mozilla/5.0 "quoted""#),
+            duration_ms: 12,
+        }
+    }
+
+    #[test]
+    fn test_common_format() {
+        assert_eq!(
+            entry().common(),
+            r#"192.0.2.1 - - [06/Dec/2023:13:55:36 +0000] "GET /login?next=%2F HTTP/1.1" 200 2326"#
+        );
+
+        let entry = Entry {
+            client_ip: None,
+            bytes: None,
+            ..entry()
+        };
+        assert_eq!(
+            entry.common(),
+            r#"- - - [06/Dec/2023:13:55:36 +0000] "GET /login?next=%2F HTTP/1.1" 200 -"#
+        );
+    }
+
+    #[test]
+    fn test_combined_format() {
+        assert_eq!(
+            entry().combined(),
+            r#"192.0.2.1 - - [06/Dec/2023:13:55:36 +0000] "GET /login?next=%2F HTTP/1.1" 200 2326 "-" "Mozilla/5.0 \"quoted\"""#
+        );
+    }
+
+    #[test]
+    fn test_json_format() {
+        let line: serde_json::Value = serde_json::from_str(&entry().json(Some("web"))).unwrap();
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "time": "2023-12-06T13:55:36+00:00",
+                "listener": "web",
+                "client_ip": "192.0.2.1",
+                "method": "GET",
+                "uri": "/login?next=%2F",
+                "protocol": "HTTP/1.1",
+                "status": 200,
+                "bytes": 2326,
+                "referer": null,
+                "user_agent": r#"Mozilla/5.0 "quoted""#,
+                "duration_ms": 12,
+            })
+        );
+    }
+}
@@ -19,12 +19,14 @@ use axum::{
     extract::{FromRef, FromRequestParts},
 };
 use ipnetwork::IpNetwork;
+use mas_email::MemoryMailbox;
 use mas_handlers::{
-    passwords::PasswordManager, ActivityTracker, BoundActivityTracker, CookieManager, ErrorWrapper,
-    HttpClientFactory, MatrixHomeserver, MetadataCache, SiteConfig,
+    passwords::PasswordManager, ActivityTracker, BoundActivityTracker, BoxHomeserverConnection,
+    CookieManager, ErrorWrapper, HttpClientFactory, MatrixHomeserver, RequestUriCache, SiteConfig,
 };
 use mas_i18n::Translator;
-use mas_keystore::{Encrypter, Keystore};
+use mas_keystore::{Encrypter, Keystore, RequestSigner};
+use mas_matrix::CircuitBreaker;
 use mas_policy::{Policy, PolicyFactory};
 use mas_router::UrlBuilder;
 use mas_storage::{BoxClock, BoxRepository, BoxRng, Repository, SystemClock};
@@ -42,18 +44,25 @@ pub struct AppState {
     pub pool: PgPool,
     pub templates: Templates,
     pub key_store: Keystore,
+    pub request_signer: RequestSigner,
     pub cookie_manager: CookieManager,
     pub encrypter: Encrypter,
     pub url_builder: UrlBuilder,
     pub homeserver: MatrixHomeserver,
+    pub homeserver_connection: BoxHomeserverConnection,
     pub policy_factory: Arc<PolicyFactory>,
+    #[cfg(feature = "graphql")]
     pub graphql_schema: mas_graphql::Schema,
     pub http_client_factory: HttpClientFactory,
     pub password_manager: PasswordManager,
-    pub metadata_cache: MetadataCache,
+    #[cfg(feature = "upstream-oauth2")]
+    pub metadata_cache: mas_handlers::MetadataCache,
+    pub request_uri_cache: RequestUriCache,
     pub site_config: SiteConfig,
     pub activity_tracker: ActivityTracker,
     pub trusted_proxies: Vec<IpNetwork>,
+    pub homeserver_circuit_breaker: Option<CircuitBreaker>,
+    pub dev_mailbox: Option<MemoryMailbox>,
     pub conn_acquisition_histogram: Option<Histogram<u64>>,
 }
 
@@ -105,11 +114,13 @@ impl AppState {
         Ok(())
     }
 
-    /// Init the metadata cache.
+    /// Init the metadata cache, and keep it in sync with the changes made to
+    /// the upstream providers by other instances.
     ///
     /// # Panics
     ///
     /// Panics if the metadata cache could not be initialized.
+    #[cfg(feature = "upstream-oauth2")]
     pub async fn init_metadata_cache(&self) {
         // XXX: this panics because the error is annoying to propagate
         let conn = self
@@ -132,6 +143,11 @@ impl AppState {
             )
             .await
             .expect("Failed to warm up the metadata cache");
+
+        self.metadata_cache
+            .listen_for_invalidations(&self.pool)
+            .await
+            .expect("Failed to listen to the metadata cache invalidations");
     }
 }
 
@@ -141,6 +157,7 @@ impl FromRef<AppState> for PgPool {
     }
 }
 
+#[cfg(feature = "graphql")]
 impl FromRef<AppState> for mas_graphql::Schema {
     fn from_ref(input: &AppState) -> Self {
         input.graphql_schema.clone()
@@ -165,6 +182,12 @@ impl FromRef<AppState> for Keystore {
     }
 }
 
+impl FromRef<AppState> for RequestSigner {
+    fn from_ref(input: &AppState) -> Self {
+        input.request_signer.clone()
+    }
+}
+
 impl FromRef<AppState> for Encrypter {
     fn from_ref(input: &AppState) -> Self {
         input.encrypter.clone()
@@ -183,6 +206,12 @@ impl FromRef<AppState> for MatrixHomeserver {
     }
 }
 
+impl FromRef<AppState> for BoxHomeserverConnection {
+    fn from_ref(input: &AppState) -> Self {
+        input.homeserver_connection.clone()
+    }
+}
+
 impl FromRef<AppState> for HttpClientFactory {
     fn from_ref(input: &AppState) -> Self {
         input.http_client_factory.clone()
@@ -201,12 +230,25 @@ impl FromRef<AppState> for CookieManager {
     }
 }
 
-impl FromRef<AppState> for MetadataCache {
+#[cfg(feature = "upstream-oauth2")]
+impl FromRef<AppState> for mas_handlers::MetadataCache {
     fn from_ref(input: &AppState) -> Self {
         input.metadata_cache.clone()
     }
 }
 
+impl FromRef<AppState> for RequestUriCache {
+    fn from_ref(input: &AppState) -> Self {
+        input.request_uri_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<CircuitBreaker> {
+    fn from_ref(input: &AppState) -> Self {
+        input.homeserver_circuit_breaker.clone()
+    }
+}
+
 impl FromRef<AppState> for SiteConfig {
     fn from_ref(input: &AppState) -> Self {
         input.site_config.clone()
@@ -268,7 +310,7 @@ impl FromRequestParts<AppState> for ActivityTracker {
     }
 }
 
-fn infer_client_ip(
+pub(crate) fn infer_client_ip(
     parts: &axum::http::request::Parts,
     trusted_proxies: &[IpNetwork],
 ) -> Option<IpAddr> {
@@ -0,0 +1,43 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Information about this build of the service, shown in the version endpoint,
+//! in the startup logs and in the telemetry resource
+
+use mas_handlers::BuildInfo;
+
+/// The cargo features this binary was built with
+const FEATURES: &[(&str, bool)] = &[
+    ("dist", cfg!(feature = "dist")),
+    ("docker", cfg!(feature = "docker")),
+    ("policy-cache", cfg!(feature = "policy-cache")),
+    ("native-roots", cfg!(feature = "native-roots")),
+    ("webpki-roots", cfg!(feature = "webpki-roots")),
+];
+
+/// Get the information about this build.
+///
+/// The git commit is taken from the `MAS_GIT_SHA` environment variable at
+/// build time, if it was set.
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: option_env!("MAS_GIT_SHA"),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect(),
+    }
+}
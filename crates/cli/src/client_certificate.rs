@@ -0,0 +1,190 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Extraction of the TLS client certificates, either from the TLS connection
+//! or from a header set by a reverse proxy
+
+use std::{sync::Arc, time::SystemTime};
+
+use anyhow::Context;
+use axum::{extract::State, middleware::Next, response::Response};
+use hyper::Request;
+use ipnetwork::IpNetwork;
+use mas_axum_utils::client_certificate::ClientCertificate;
+use mas_config::{HttpClientCertificateConfig, HttpClientCertificateSource};
+use mas_listener::ConnectionInfo;
+use mas_storage::{Clock, SystemClock};
+use rustls::{
+    server::{AllowAnyAuthenticatedClient, ClientCertVerified, ClientCertVerifier},
+    Certificate, DistinguishedName, RootCertStore,
+};
+use tracing::warn;
+
+/// Extracts the client certificate of each request and adds it to the request
+/// extensions, where the handlers can find it
+#[derive(Clone)]
+pub struct ClientCertificateExtractor {
+    source: HttpClientCertificateSource,
+    trusted_proxies: Arc<[IpNetwork]>,
+    verifier: Option<Arc<AllowAnyAuthenticatedClient>>,
+}
+
+impl ClientCertificateExtractor {
+    /// Build the extractor described by the configuration
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the trusted root certificates could not be loaded
+    pub fn from_config(
+        config: &HttpClientCertificateConfig,
+        trusted_proxies: &[IpNetwork],
+    ) -> Result<Self, anyhow::Error> {
+        let roots = config.load_trusted_roots()?;
+
+        // Without trusted roots, only self-signed certificates can be used
+        let verifier = if roots.is_empty() {
+            None
+        } else {
+            let mut store = RootCertStore::empty();
+            for root in roots {
+                store
+                    .add(&Certificate(root))
+                    .context("invalid trusted root certificate")?;
+            }
+
+            Some(Arc::new(AllowAnyAuthenticatedClient::new(store)))
+        };
+
+        Ok(Self {
+            source: config.source.clone(),
+            trusted_proxies: trusted_proxies.into(),
+            verifier,
+        })
+    }
+
+    /// Whether the TLS listeners should ask for client certificates during the
+    /// handshake
+    #[must_use]
+    pub fn requests_tls_certificates(&self) -> bool {
+        matches!(self.source, HttpClientCertificateSource::Tls)
+    }
+
+    /// Get the certificate chain presented by the client, leaf first
+    fn chain<B>(&self, request: &Request<B>) -> Option<Vec<Vec<u8>>> {
+        let connection_info = request.extensions().get::<ConnectionInfo>()?;
+
+        match &self.source {
+            HttpClientCertificateSource::Tls => {
+                let certificates = connection_info.get_tls_ref()?.peer_certificates.as_ref()?;
+                Some(certificates.iter().map(|c| c.0.clone()).collect())
+            }
+
+            HttpClientCertificateSource::Header { header } => {
+                // Anyone could set the header, so it is only trusted when it was
+                // set by one of our reverse proxies
+                let peer = connection_info.get_peer_addr()?.ip();
+                if !self.trusted_proxies.iter().any(|net| net.contains(peer)) {
+                    return None;
+                }
+
+                let value = request.headers().get(header)?.to_str().ok()?;
+                match ClientCertificate::from_header_value(value) {
+                    Ok(certificate) => Some(vec![certificate.der().to_vec()]),
+                    Err(e) => {
+                        warn!(
+                            error = &e as &dyn std::error::Error,
+                            "Invalid client certificate forwarded by the reverse proxy"
+                        );
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn extract<B>(&self, request: &Request<B>) -> Option<ClientCertificate> {
+        let mut chain = self.chain(request)?.into_iter().map(Certificate);
+        let leaf = chain.next()?;
+        let intermediates: Vec<_> = chain.collect();
+
+        let certificate = match ClientCertificate::from_der(leaf.0.clone()) {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Invalid client certificate"
+                );
+                return None;
+            }
+        };
+
+        // Check whether the certificate was issued by one of the trusted roots
+        let now: SystemTime = SystemClock::default().now().into();
+        let trusted = self.verifier.as_ref().is_some_and(|verifier| {
+            verifier
+                .verify_client_cert(&leaf, &intermediates, now)
+                .is_ok()
+        });
+
+        Some(if trusted {
+            certificate.trusted()
+        } else {
+            certificate
+        })
+    }
+}
+
+/// Middleware adding the client certificate to the request extensions
+pub async fn middleware<B>(
+    State(extractor): State<ClientCertificateExtractor>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if let Some(certificate) = extractor.extract(&request) {
+        request.extensions_mut().insert(certificate);
+    }
+
+    next.run(request).await
+}
+
+/// Asks clients for a certificate during the TLS handshake, without requiring
+/// one
+///
+/// The handshake still checks that the client owns the key of the
+/// certificate, but the certificate itself is only checked later on, when it
+/// is used to authenticate a client, as self-signed certificates are allowed.
+pub struct RequestClientCertificate;
+
+impl ClientCertVerifier for RequestClientCertificate {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+}
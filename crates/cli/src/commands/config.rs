@@ -27,7 +27,7 @@ use sqlx::{postgres::PgAdvisoryLock, Acquire};
 use tokio::io::AsyncWriteExt;
 use tracing::{error, info, info_span, warn};
 
-use crate::util::database_connection_from_config;
+use crate::util::{database_connection_from_config, email_sender_warnings};
 
 fn map_import_action(
     config: &mas_config::UpstreamOAuth2ImportAction,
@@ -78,6 +78,21 @@ fn map_claims_imports(
                 mas_data_model::UpsreamOAuthProviderSetEmailVerification::Import
             }
         },
+        groups: mas_data_model::UpstreamOAuthProviderGroupsImportPreference {
+            sync: match config.groups.sync {
+                mas_config::UpstreamOAuth2GroupsSyncPolicy::Ignore => {
+                    mas_data_model::UpstreamOAuthProviderGroupsSyncPolicy::Ignore
+                }
+                mas_config::UpstreamOAuth2GroupsSyncPolicy::Additive => {
+                    mas_data_model::UpstreamOAuthProviderGroupsSyncPolicy::Additive
+                }
+                mas_config::UpstreamOAuth2GroupsSyncPolicy::Authoritative => {
+                    mas_data_model::UpstreamOAuthProviderGroupsSyncPolicy::Authoritative
+                }
+            },
+            claim: config.groups.claim.clone(),
+            mapping: config.groups.mapping.clone(),
+        },
     }
 }
 
@@ -132,7 +147,12 @@ impl Options {
             SC::Check => {
                 let _span = info_span!("cli.config.check").entered();
 
-                let _config: RootConfig = root.load_config()?;
+                let config: RootConfig = root.load_config()?;
+
+                for warning in email_sender_warnings(&config.email) {
+                    warn!("{warning}");
+                }
+
                 info!(path = ?root.config, "Configuration file looks good");
             }
 
@@ -285,6 +305,7 @@ async fn sync(root: &super::Options, prune: bool, dry_run: bool) -> anyhow::Resu
                         issuer: provider.issuer,
                         human_name: provider.human_name,
                         brand_name: provider.brand_name,
+                        icon: provider.icon,
                         scope: provider.scope.parse()?,
                         token_endpoint_auth_method,
                         token_endpoint_signing_alg,
@@ -362,6 +383,12 @@ async fn sync(root: &super::Options, prune: bool, dry_run: bool) -> anyhow::Resu
                     jwks.cloned(),
                     jwks_uri.cloned(),
                     client.redirect_uris.clone(),
+                    client.post_logout_redirect_uris.clone(),
+                    client.backchannel_logout_uri.clone(),
+                    client.backchannel_logout_session_required,
+                    client.require_pushed_authorization_requests,
+                    client.tls_client_auth_subject_dn().map(ToOwned::to_owned),
+                    client.tls_client_certificate_bound_access_tokens,
                 )
                 .await?;
         }
@@ -15,8 +15,11 @@
 use anyhow::Context;
 use clap::Parser;
 use mas_config::DatabaseConfig;
-use mas_storage_pg::MIGRATOR;
-use tracing::{info_span, Instrument};
+use mas_storage_pg::{
+    applied_schema_version, check_schema_version, latest_known_schema_version, pending_migrations,
+    SchemaVersion, MIGRATOR,
+};
+use tracing::{info, info_span, warn, Instrument};
 
 use crate::util::database_connection_from_config;
 
@@ -29,21 +32,86 @@ pub(super) struct Options {
 #[derive(Parser, Debug)]
 enum Subcommand {
     /// Run database migrations
-    Migrate,
+    Migrate {
+        /// Only list the migrations which would be applied
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show the version of the database schema and the pending migrations
+    Status,
 }
 
 impl Options {
     pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
-        let _span = info_span!("cli.database.migrate").entered();
-        let config: DatabaseConfig = root.load_config()?;
-        let mut conn = database_connection_from_config(&config).await?;
-
-        // Run pending migrations
-        MIGRATOR
-            .run(&mut conn)
-            .instrument(info_span!("db.migrate"))
-            .await
-            .context("could not run migrations")?;
+        use Subcommand as SC;
+        match self.subcommand {
+            SC::Migrate { dry_run } => {
+                let _span = info_span!("cli.database.migrate").entered();
+                let config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&config).await?;
+
+                let pending = pending_migrations(&mut conn).await?;
+                if pending.is_empty() {
+                    info!("No pending migrations");
+                    return Ok(());
+                }
+
+                for migration in &pending {
+                    info!(
+                        migration.version,
+                        migration.description = %migration.description,
+                        "Pending migration"
+                    );
+                }
+
+                if dry_run {
+                    info!(count = pending.len(), "Dry run, not applying migrations");
+                    return Ok(());
+                }
+
+                // Run pending migrations
+                MIGRATOR
+                    .run(&mut conn)
+                    .instrument(info_span!("db.migrate"))
+                    .await
+                    .context("could not run migrations")?;
+
+                info!(count = pending.len(), "Migrations applied");
+            }
+
+            SC::Status => {
+                let _span = info_span!("cli.database.status").entered();
+                let config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&config).await?;
+
+                let applied = applied_schema_version(&mut conn).await?;
+                let latest_known = latest_known_schema_version();
+                info!(?applied, latest_known, "Database schema version");
+
+                match check_schema_version(&mut conn).await? {
+                    SchemaVersion::UpToDate => info!("The database schema is up to date"),
+                    SchemaVersion::Pending { count } => {
+                        for migration in pending_migrations(&mut conn).await? {
+                            info!(
+                                migration.version,
+                                migration.description = %migration.description,
+                                "Pending migration"
+                            );
+                        }
+                        warn!(
+                            count,
+                            "The database has pending migrations, apply them with `mas-cli database migrate`"
+                        );
+                    }
+                    SchemaVersion::Newer { latest_applied, .. } => warn!(
+                        latest_applied,
+                        latest_known,
+                        "The database schema is newer than what this version supports"
+                    ),
+                }
+            }
+        }
 
         Ok(())
     }
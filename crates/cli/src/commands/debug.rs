@@ -12,16 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "graphql")]
+use anyhow::Context;
+#[cfg(feature = "graphql")]
+use camino::Utf8PathBuf;
 use clap::Parser;
 use hyper::{Response, Uri};
-use mas_config::PolicyConfig;
+use mas_config::{
+    BrandingConfig, HttpConfig, MatrixConfig, PolicyConfig, ScopesConfig, SmsConfig,
+    TemplatesConfig,
+};
 use mas_handlers::HttpClientFactory;
 use mas_http::HttpServiceExt;
+use mas_router::UrlBuilder;
+use mas_templates::{SmsVerificationContext, TemplateContext};
+use rand::{distributions::Uniform, thread_rng, Rng};
 use tokio::io::AsyncWriteExt;
 use tower::{Service, ServiceExt};
+#[cfg(feature = "graphql")]
+use tracing::error;
 use tracing::{info, info_span};
 
-use crate::util::policy_factory_from_config;
+use crate::util::{policy_factory_from_config, sms_sender_from_config, templates_from_config};
 
 #[derive(Parser, Debug)]
 pub(super) struct Options {
@@ -47,6 +59,32 @@ enum Subcommand {
 
     /// Check that the policies compile
     Policy,
+
+    /// Export the GraphQL schema, in the SDL format
+    #[cfg(feature = "graphql")]
+    GraphqlSchema {
+        /// Where to write the schema
+        ///
+        /// If not specified, the schema will be written to stdout
+        #[arg(short, long)]
+        output: Option<Utf8PathBuf>,
+
+        /// Check that the schema is compatible with a previously exported one,
+        /// and fail if it has breaking changes
+        #[arg(long, value_name = "PREVIOUS_SCHEMA")]
+        check: Option<Utf8PathBuf>,
+    },
+
+    /// Send a text message with a random verification code, to check that the
+    /// SMS gateway is correctly configured
+    Sms {
+        /// Language of the message
+        #[arg(long, default_value = "en")]
+        language: String,
+
+        /// Phone number to send the message to, in the E.164 format
+        to: String,
+    },
 }
 
 fn print_headers(parts: &hyper::http::response::Parts) {
@@ -121,11 +159,80 @@ impl Options {
             SC::Policy => {
                 let _span = info_span!("cli.debug.policy").entered();
                 let config: PolicyConfig = root.load_config()?;
+                let scopes: ScopesConfig = root.load_config()?;
                 info!("Loading and compiling the policy module");
-                let policy_factory = policy_factory_from_config(&config).await?;
+                let policy_factory = policy_factory_from_config(&config, &scopes).await?;
 
                 let _instance = policy_factory.instantiate().await?;
             }
+
+            #[cfg(feature = "graphql")]
+            SC::GraphqlSchema { output, check } => {
+                let _span = info_span!("cli.debug.graphql_schema").entered();
+                let sdl = mas_graphql::schema_builder().finish().sdl();
+
+                if let Some(previous) = check {
+                    let previous = tokio::fs::read_to_string(&previous)
+                        .await
+                        .with_context(|| format!("could not read {previous}"))?;
+                    let changes = mas_graphql::breaking_changes(&previous, &sdl)
+                        .context("could not parse the previous schema")?;
+
+                    if !changes.is_empty() {
+                        for change in &changes {
+                            error!("Breaking change: {change}");
+                        }
+                        anyhow::bail!(
+                            "The GraphQL schema has {} breaking change(s)",
+                            changes.len()
+                        );
+                    }
+
+                    info!("The GraphQL schema is compatible with the previous one");
+                }
+
+                if let Some(output) = output {
+                    info!("Writing the GraphQL schema to {output:?}");
+                    let mut file = tokio::fs::File::create(output).await?;
+                    file.write_all(sdl.as_bytes()).await?;
+                } else {
+                    tokio::io::stdout().write_all(sdl.as_bytes()).await?;
+                }
+            }
+
+            SC::Sms { language, to } => {
+                let _span = info_span!("cli.debug.sms").entered();
+                let sms_config: SmsConfig = root.load_config()?;
+                let templates_config: TemplatesConfig = root.load_config()?;
+                let branding_config: BrandingConfig = root.load_config()?;
+                let matrix_config: MatrixConfig = root.load_config()?;
+                let http_config: HttpConfig = root.load_config()?;
+
+                let url_builder = UrlBuilder::new(
+                    http_config.public_base.clone(),
+                    http_config.issuer.clone(),
+                    None,
+                );
+                let templates = templates_from_config(
+                    &templates_config,
+                    &branding_config,
+                    &url_builder,
+                    &matrix_config.homeserver,
+                )
+                .await?;
+
+                let sender = sms_sender_from_config(&sms_config, &templates, &http_client_factory)?;
+
+                let language = language
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid language {language:?}"))?;
+                let code = thread_rng().sample(Uniform::<u32>::from(0..1_000_000));
+                let context =
+                    SmsVerificationContext::new(format!("{code:06}")).with_language(language);
+
+                info!("Sending a verification code to {to}");
+                sender.send_verification_code(&to, &context).await?;
+            }
         }
 
         Ok(())
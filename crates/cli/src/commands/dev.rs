@@ -0,0 +1,233 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context;
+use clap::Parser;
+use mas_config::{
+    AppConfig, CaptchaConfig, ConfigurationSection, DatabaseConnectConfig, EmailTransportConfig,
+    HttpResource,
+};
+use mas_iana::oauth::OAuthClientAuthenticationMethod;
+use mas_storage::{
+    oauth2::OAuth2ClientRepository,
+    user::{UserPasswordRepository, UserRepository},
+    Clock, RepositoryAccess, SystemClock,
+};
+use mas_storage_pg::{PgRepository, MIGRATOR};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    SeedableRng,
+};
+use sqlx::{Connection, Executor};
+use tracing::{info, info_span, warn, Instrument};
+use ulid::Ulid;
+use url::Url;
+
+use crate::util::{database_connection_from_config, password_manager_from_config};
+
+#[derive(Parser, Debug)]
+pub(super) struct Options {
+    #[command(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(Parser, Debug)]
+enum Subcommand {
+    /// Run the server with an ephemeral database, seeded with a test user and
+    /// a test client, and with settings suited for local development.
+    ///
+    /// Never use this in production.
+    Server {
+        /// Username of the test user
+        #[arg(long, default_value = "alice")]
+        username: String,
+
+        /// Password of the test user
+        #[arg(long, default_value = "password")]
+        password: String,
+
+        /// Redirect URI allowed for the test client
+        #[arg(long, default_value = "http://localhost:3000/callback")]
+        redirect_uri: Url,
+
+        /// Keep the ephemeral database when shutting down
+        #[arg(long)]
+        keep_database: bool,
+    },
+}
+
+impl Options {
+    #[allow(clippy::too_many_lines)]
+    pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
+        use Subcommand as SC;
+        let clock = SystemClock::default();
+        // XXX: we should disallow SeedableRng::from_entropy
+        let mut rng = rand_chacha::ChaChaRng::from_entropy();
+
+        match self.subcommand {
+            SC::Server {
+                username,
+                password,
+                redirect_uri,
+                keep_database,
+            } => {
+                let span = info_span!("cli.dev.server").entered();
+
+                // Use the configuration file if there is one, else generate a fresh one
+                let mut config: AppConfig = match root.load_config() {
+                    Ok(config) => config,
+                    Err(e) => {
+                        warn!(
+                            error = &*e as &dyn std::error::Error,
+                            "Could not load the configuration, generating one"
+                        );
+                        AppConfig::load_and_generate(&mut rng).await?
+                    }
+                };
+
+                // Relax the settings which get in the way when developing locally
+                config.email.transport = EmailTransportConfig::Memory;
+                config.account.verify_email_before_registration = false;
+                config.captcha = CaptchaConfig::default();
+                for listener in &mut config.http.listeners {
+                    for resource in &mut listener.resources {
+                        if let HttpResource::GraphQL { playground } = resource {
+                            *playground = true;
+                        }
+                    }
+                }
+
+                // Create a throwaway database next to the configured one
+                let database_name = format!(
+                    "mas_dev_{}",
+                    Alphanumeric.sample_string(&mut rng, 10).to_lowercase()
+                );
+                let mut admin_conn = database_connection_from_config(&config.database).await?;
+                info!(database_name, "Creating the ephemeral database");
+                admin_conn
+                    .execute(format!(r#"CREATE DATABASE "{database_name}""#).as_str())
+                    .await
+                    .context("could not create the ephemeral database")?;
+
+                config.database.options = match config.database.options {
+                    DatabaseConnectConfig::Uri { uri } => {
+                        let mut uri: Url = uri
+                            .parse()
+                            .context("could not parse database connection string")?;
+                        uri.set_path(&format!("/{database_name}"));
+                        DatabaseConnectConfig::Uri { uri: uri.into() }
+                    }
+                    DatabaseConnectConfig::Options {
+                        host,
+                        port,
+                        socket,
+                        username,
+                        password,
+                        database: _,
+                    } => DatabaseConnectConfig::Options {
+                        host,
+                        port,
+                        socket,
+                        username,
+                        password,
+                        database: Some(database_name.clone()),
+                    },
+                };
+
+                let mut conn = database_connection_from_config(&config.database).await?;
+                MIGRATOR
+                    .run(&mut conn)
+                    .instrument(info_span!("db.migrate"))
+                    .await
+                    .context("could not run migrations")?;
+
+                // Seed the test user and client
+                let password_manager = password_manager_from_config(&config.passwords).await?;
+                let encrypter = config.secrets.encrypter();
+                let client_secret = Alphanumeric.sample_string(&mut rng, 32);
+                let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let user = repo.user().add(&mut rng, &clock, username.clone()).await?;
+                let (version, hashed_password) = password_manager
+                    .hash(&mut rng, password.clone().into_bytes().into())
+                    .await?;
+                repo.user_password()
+                    .add(&mut rng, &clock, &user, version, hashed_password, None)
+                    .await?;
+
+                let encrypted_client_secret =
+                    encrypter.encrypt_to_string(client_secret.as_bytes())?;
+                repo.oauth2_client()
+                    .upsert_static(
+                        client_id,
+                        OAuthClientAuthenticationMethod::ClientSecretPost,
+                        Some(encrypted_client_secret),
+                        None,
+                        None,
+                        vec![redirect_uri.clone()],
+                        Vec::new(),
+                        None,
+                        false,
+                        false,
+                        None,
+                        false,
+                    )
+                    .await?;
+
+                repo.into_inner().commit().await?;
+                drop(conn);
+
+                let issuer = config
+                    .http
+                    .issuer
+                    .clone()
+                    .unwrap_or_else(|| config.http.public_base.clone());
+                let public_base = config.http.public_base.clone();
+
+                println!();
+                println!("Development server ready, do not use this in production!");
+                println!();
+                println!("  Issuer:          {issuer}");
+                println!("  Test user:       {username} / {password}");
+                println!("  Client ID:       {client_id}");
+                println!("  Client secret:   {client_secret}");
+                println!("  Redirect URI:    {redirect_uri}");
+                println!("  Dev mailbox:     {public_base}dev/mailbox");
+                println!("  GraphQL:         {public_base}graphql/playground");
+                println!();
+
+                span.exit();
+
+                let res = super::server::Options::dev().run_with_config(config).await;
+
+                if keep_database {
+                    info!(database_name, "Keeping the ephemeral database");
+                } else {
+                    info!(database_name, "Dropping the ephemeral database");
+                    admin_conn
+                        .execute(
+                            format!(r#"DROP DATABASE "{database_name}" WITH (FORCE)"#).as_str(),
+                        )
+                        .await
+                        .context("could not drop the ephemeral database")?;
+                }
+
+                res
+            }
+        }
+    }
+}
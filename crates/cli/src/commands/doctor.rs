@@ -0,0 +1,244 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Diagnose common problems with a deployment, printing actionable errors
+//! instead of having them show up at runtime
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use hyper::{body::Bytes, Request, StatusCode};
+use mas_config::{DatabaseConfig, RootConfig, SecretsConfig};
+use mas_handlers::HttpClientFactory;
+use mas_http::HttpServiceExt;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_router::UrlBuilder;
+use mas_storage::{Clock, SystemClock};
+use mas_storage_pg::{check_schema_version, SchemaVersion};
+use tower::{Service, ServiceExt};
+use tracing::{error, info, info_span, warn};
+
+use crate::util::{
+    database_connection_from_config, email_sender_warnings, homeserver_connection_from_config,
+    mailer_from_config, templates_from_config,
+};
+
+#[derive(Parser, Debug)]
+pub(super) struct Options {}
+
+/// Keeps track of the checks which failed, so that all of them run before
+/// reporting the problems
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, result: anyhow::Result<()>, hint: &str) {
+        match result {
+            Ok(()) => info!("{name}: OK"),
+            Err(e) => {
+                self.failures += 1;
+                error!("{name}: {e:#}");
+                error!("Hint: {hint}");
+            }
+        }
+    }
+}
+
+/// Check that the database is reachable, and that its schema is up to date
+async fn check_database(config: &DatabaseConfig) -> anyhow::Result<()> {
+    let mut conn = database_connection_from_config(config).await?;
+    match check_schema_version(&mut conn).await? {
+        SchemaVersion::UpToDate => Ok(()),
+        SchemaVersion::Pending { count } => {
+            anyhow::bail!("{count} migration(s) have not been applied yet")
+        }
+        SchemaVersion::Newer {
+            latest_known,
+            latest_applied,
+        } => anyhow::bail!(
+            "the database schema (version {latest_applied}) is newer than what this version supports (version {latest_known})"
+        ),
+    }
+}
+
+/// Check that the signing keys can be loaded, and that tokens can be signed
+/// with the default algorithm
+async fn check_keys(config: &SecretsConfig, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let key_store = config
+        .key_store()
+        .await
+        .context("could not load the signing keys")?;
+
+    // ID tokens are signed with RS256 unless the client asks otherwise
+    if key_store
+        .signer_for_alg(&JsonWebSignatureAlg::Rs256)
+        .is_none()
+    {
+        anyhow::bail!("no active RSA key is available to sign tokens with RS256");
+    }
+
+    for (kid, expires_at) in config.key_expirations() {
+        if expires_at <= now {
+            warn!("The signing key {kid:?} expired at {expires_at}, it can be removed from the configuration");
+        } else if expires_at <= now + chrono::Duration::days(7) {
+            warn!(
+                "The signing key {kid:?} expires at {expires_at}, make sure a new key is in place"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the service is reachable through its public URL, by fetching
+/// the discovery document and comparing the issuer it advertises
+async fn check_public_url(
+    url_builder: &UrlBuilder,
+    http_client_factory: &HttpClientFactory,
+) -> anyhow::Result<()> {
+    let discovery = url_builder.oidc_discovery();
+    let mut client = http_client_factory
+        .client("doctor.public_base")
+        .request_bytes_to_body()
+        .response_body_to_bytes();
+
+    let request = Request::get(discovery.as_str()).body(Bytes::new())?;
+    let response = client.ready().await?.call(request).await?;
+    if response.status() != StatusCode::OK {
+        anyhow::bail!("{discovery} replied with {}", response.status());
+    }
+
+    let metadata: serde_json::Value = serde_json::from_slice(response.body())
+        .with_context(|| format!("{discovery} did not reply with a JSON document"))?;
+    let issuer = metadata.get("issuer").and_then(serde_json::Value::as_str);
+    let expected = url_builder.oidc_issuer();
+    if issuer != Some(expected.as_str()) {
+        anyhow::bail!(
+            "{discovery} advertises the issuer {issuer:?}, expected {:?}",
+            expected.as_str()
+        );
+    }
+
+    Ok(())
+}
+
+impl Options {
+    pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
+        let _span = info_span!("cli.doctor").entered();
+        let mut report = Report::default();
+
+        // Nothing else can be checked without a valid configuration
+        let config: RootConfig = root.load_config().context(
+            "the configuration is invalid, check it against the schema with `mas-cli config check`",
+        )?;
+        info!("Configuration: OK");
+
+        for warning in email_sender_warnings(&config.email) {
+            warn!("{warning}");
+        }
+
+        let clock = SystemClock::default();
+        let now = clock.now();
+
+        report.check(
+            "Database",
+            check_database(&config.database).await,
+            "check the `database` section, then run `mas-cli database migrate` to apply pending migrations",
+        );
+
+        report.check(
+            "Signing keys",
+            check_keys(&config.secrets, now).await,
+            "check the `secrets.keys` section, new keys can be generated with `mas-cli keys generate`",
+        );
+
+        let request_signer = match config.secrets.request_signer().await {
+            Ok(request_signer) => Some(request_signer),
+            Err(e) => {
+                report.check(
+                    "Request signing keys",
+                    Err(e),
+                    "check the `secrets.request_signing_keys` section",
+                );
+                None
+            }
+        };
+
+        let url_builder = UrlBuilder::new(
+            config.http.public_base.clone(),
+            config.http.issuer.clone(),
+            None,
+        );
+
+        let templates = templates_from_config(
+            &config.templates,
+            &config.branding,
+            &url_builder,
+            &config.matrix.homeserver,
+        )
+        .await;
+        match templates {
+            Ok(templates) => {
+                let result = match mailer_from_config(&config.email, &templates) {
+                    Ok(mailer) => mailer.test_connection().await.map_err(Into::into),
+                    Err(e) => Err(e),
+                };
+                report.check(
+                    "Email transport",
+                    result,
+                    "check the `email` section, and that the SMTP server is reachable from this host",
+                );
+            }
+            Err(e) => report.check(
+                "Templates",
+                Err(e),
+                "check the `templates` section, the templates can be checked with `mas-cli templates check`",
+            ),
+        }
+
+        let http_client_factory = HttpClientFactory::new().await?;
+
+        report.check(
+            "Public URL",
+            check_public_url(&url_builder, &http_client_factory).await,
+            "check `http.public_base`, and that the reverse proxy forwards requests to a listener serving the `discovery` resource",
+        );
+
+        if let Some(request_signer) = request_signer {
+            let result = match homeserver_connection_from_config(
+                &config.matrix,
+                &request_signer,
+                &http_client_factory,
+            )
+            .await
+            {
+                Ok(conn) => conn.check().await,
+                Err(e) => Err(e),
+            };
+            report.check(
+                "Homeserver",
+                result,
+                "check that `matrix.endpoint` points to the homeserver, and that `matrix.secret` matches its configuration",
+            );
+        }
+
+        if report.failures > 0 {
+            anyhow::bail!("{} check(s) failed", report.failures);
+        }
+
+        info!("Everything looks good");
+        Ok(())
+    }
+}
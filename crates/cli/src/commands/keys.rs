@@ -0,0 +1,172 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use camino::Utf8PathBuf;
+use clap::{Parser, ValueEnum};
+use mas_config::{KeyAlgorithm, KeyConfig, SecretsConfig};
+use mas_storage::{Clock, SystemClock};
+use rand::SeedableRng;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, info_span};
+
+#[derive(Parser, Debug)]
+pub(super) struct Options {
+    #[command(subcommand)]
+    subcommand: Subcommand,
+}
+
+/// The type of a signing key
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Algorithm {
+    /// RSA, with a 2048-bit modulus
+    Rsa,
+
+    /// ECDSA with the P-256 curve
+    EcP256,
+
+    /// ECDSA with the P-384 curve
+    EcP384,
+
+    /// ECDSA with the K-256 curve
+    EcK256,
+}
+
+impl From<Algorithm> for KeyAlgorithm {
+    fn from(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Rsa => Self::Rsa,
+            Algorithm::EcP256 => Self::EcP256,
+            Algorithm::EcP384 => Self::EcP384,
+            Algorithm::EcK256 => Self::EcK256,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+enum Subcommand {
+    /// Generate new signing keys, to add to the `secrets.keys` section of the
+    /// configuration
+    Generate {
+        /// Type of key to generate. Can be repeated. Generates one key of each
+        /// type if not specified
+        #[arg(long = "algorithm", value_enum)]
+        algorithms: Vec<Algorithm>,
+
+        /// The path to the file to write the keys to
+        ///
+        /// If not specified, the keys will be written to stdout
+        #[arg(short, long)]
+        output: Option<Utf8PathBuf>,
+    },
+
+    /// Rotate the signing keys, writing an updated `secrets` section of the
+    /// configuration
+    ///
+    /// The new keys are published right away, and replace the current ones
+    /// for signing once the overlap period is over. The current keys stay
+    /// published for another overlap period, so that the tokens they signed
+    /// can still be verified.
+    Rotate {
+        /// Type of key to generate. Can be repeated. Generates one key of each
+        /// type if not specified
+        #[arg(long = "algorithm", value_enum)]
+        algorithms: Vec<Algorithm>,
+
+        /// Overlap period, in seconds. It should be longer than the time
+        /// clients cache the JWKS, and than the lifetime of the tokens
+        #[arg(long, default_value_t = 24 * 60 * 60)]
+        overlap: u32,
+
+        /// The path to the file to write the `secrets` section to
+        ///
+        /// If not specified, it will be written to stdout
+        #[arg(short, long)]
+        output: Option<Utf8PathBuf>,
+    },
+}
+
+/// Generate one key for each of the given types, or the default set of keys
+async fn generate_keys(algorithms: Vec<Algorithm>) -> anyhow::Result<Vec<KeyConfig>> {
+    let algorithms: Vec<KeyAlgorithm> = if algorithms.is_empty() {
+        KeyAlgorithm::DEFAULT.to_vec()
+    } else {
+        algorithms.into_iter().map(Into::into).collect()
+    };
+
+    // XXX: we should disallow SeedableRng::from_entropy
+    let mut rng = rand_chacha::ChaChaRng::from_entropy();
+    let mut keys = Vec::with_capacity(algorithms.len());
+    for algorithm in algorithms {
+        let key = KeyConfig::generate(&mut rng, algorithm).await?;
+        info!(kid = key.kid(), ?algorithm, "Generated key");
+        keys.push(key);
+    }
+
+    Ok(keys)
+}
+
+async fn write_output(output: Option<Utf8PathBuf>, content: &str) -> anyhow::Result<()> {
+    if let Some(output) = output {
+        info!("Writing to {output:?}");
+        let mut file = tokio::fs::File::create(output).await?;
+        file.write_all(content.as_bytes()).await?;
+    } else {
+        info!("Writing to standard output");
+        tokio::io::stdout().write_all(content.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+impl Options {
+    pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
+        use Subcommand as SC;
+        match self.subcommand {
+            SC::Generate { algorithms, output } => {
+                let _span = info_span!("cli.keys.generate").entered();
+
+                let keys = generate_keys(algorithms).await?;
+                let content = serde_yaml::to_string(&keys)?;
+                write_output(output, &content).await?;
+            }
+
+            SC::Rotate {
+                algorithms,
+                overlap,
+                output,
+            } => {
+                let _span = info_span!("cli.keys.rotate").entered();
+
+                let mut config: SecretsConfig = root.load_config()?;
+                let keys = generate_keys(algorithms).await?;
+
+                let now = SystemClock::default().now();
+                let overlap = chrono::Duration::seconds(overlap.into());
+                config.rotate_keys(keys, now, overlap);
+
+                let content = serde_yaml::to_string(&BTreeMap::from([("secrets", &config)]))?;
+                write_output(output, &content).await?;
+
+                info!(
+                    switch_at = %(now + overlap),
+                    "Replace the `secrets` section of the configuration and restart the server, then restart it again after the switch"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -14,20 +14,33 @@
 
 use anyhow::Context;
 use clap::Parser;
-use mas_config::{DatabaseConfig, PasswordsConfig};
+use mas_config::{DatabaseConfig, PasswordsConfig, SecretsConfig};
 use mas_data_model::{Device, TokenType};
+use mas_iana::oauth::OAuthClientAuthenticationMethod;
 use mas_storage::{
     compat::{CompatAccessTokenRepository, CompatSessionRepository},
-    job::{DeactivateUserJob, DeleteDeviceJob, JobRepositoryExt, ProvisionUserJob},
-    user::{UserEmailRepository, UserPasswordRepository, UserRepository},
-    RepositoryAccess, SystemClock,
+    job::{
+        DeleteDeviceJob, ForcePasswordResetJob, JobRepositoryExt, ProvisionUserJob,
+        SendCompatPasswordDeprecationEmailJob,
+    },
+    oauth2::{end_session, OAuth2ClientRepository},
+    user::UserRepository,
+    Clock, RepositoryAccess, SystemClock,
 };
 use mas_storage_pg::PgRepository;
-use rand::SeedableRng;
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    SeedableRng,
+};
 use sqlx::{types::Uuid, Acquire};
 use tracing::{info, info_span, warn};
+use ulid::Ulid;
+use url::Url;
 
-use crate::util::{database_connection_from_config, password_manager_from_config};
+use crate::util::database_connection_from_config;
+
+mod token;
+mod user;
 
 #[derive(Parser, Debug)]
 pub(super) struct Options {
@@ -37,10 +50,18 @@ pub(super) struct Options {
 
 #[derive(Parser, Debug)]
 enum Subcommand {
-    /// Mark email address as verified
+    /// Manage user accounts
+    User {
+        #[command(subcommand)]
+        subcommand: user::Subcommand,
+    },
+
+    /// Mark email address as verified. Alias of `user verify-email`
+    #[command(hide = true)]
     VerifyEmail { username: String, email: String },
 
-    /// Set a user password
+    /// Set a user password. Alias of `user set-password`
+    #[command(hide = true)]
     SetPassword { username: String, password: String },
 
     /// Issue a compatibility token
@@ -60,6 +81,38 @@ enum Subcommand {
     /// Trigger a provisioning job for all users
     ProvisionAllUsers,
 
+    /// Create a service account, for bots and bridges which need a Matrix
+    /// identity but can't log in interactively. Use
+    /// `issue-compatibility-token` to get a token for it.
+    AddServiceAccount {
+        /// Username of the service account
+        username: String,
+    },
+
+    /// Create a confidential OAuth 2.0 client, for trusted services like the
+    /// homeserver. Its credentials are printed once, as the secret can't be
+    /// retrieved afterwards.
+    AddClient {
+        /// Redirect URI the client is allowed to use. Can be repeated
+        #[arg(long = "redirect-uri")]
+        redirect_uris: Vec<Url>,
+
+        /// How the client authenticates on the token endpoint. One of
+        /// `client_secret_basic`, `client_secret_post` or `client_secret_jwt`
+        #[arg(long, default_value = "client_secret_basic")]
+        auth_method: OAuthClientAuthenticationMethod,
+    },
+
+    /// List the clients provisioned by the operator, either through the
+    /// configuration or with `add-client`
+    ListClients,
+
+    /// Delete an OAuth 2.0 client, along with all its sessions and tokens
+    DeleteClient {
+        /// ID of the client to delete
+        client_id: Ulid,
+    },
+
     /// Kill all sessions for a user
     KillSessions {
         /// User for which to kill sessions
@@ -70,7 +123,50 @@ enum Subcommand {
         dry_run: bool,
     },
 
-    /// Lock a user
+    /// Show which user, client and session an access or refresh token belongs
+    /// to
+    InspectToken {
+        /// The token, or a prefix of at least 12 characters of it
+        token: String,
+    },
+
+    /// Revoke an access or refresh token. Revoking an OAuth 2.0 refresh token
+    /// ends its whole session.
+    RevokeToken {
+        /// The token, or a prefix of at least 12 characters of it
+        token: String,
+
+        /// End the whole session the token belongs to
+        #[arg(long)]
+        session: bool,
+
+        /// Do a dry run
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Require a user to choose a new password, e.g. after a suspected
+    /// credential leak. All their sessions are ended, and they get an email
+    /// with a link to choose a new password.
+    ForcePasswordReset {
+        /// User who must reset their password
+        username: String,
+    },
+
+    /// Progressively disable password login on the compatibility layer for a
+    /// user. They are warned by email, and blocked once the grace period
+    /// configured in `passwords.compat_deprecation_grace_period` is over.
+    DeprecateCompatPassword {
+        /// User whose password login is deprecated
+        username: String,
+
+        /// Clear the deprecation instead, allowing password login again
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Lock a user. Alias of `user lock`
+    #[command(hide = true)]
     LockUser {
         /// User to lock
         username: String,
@@ -80,11 +176,25 @@ enum Subcommand {
         deactivate: bool,
     },
 
-    /// Unlock a user
+    /// Unlock a user. Alias of `user unlock`
+    #[command(hide = true)]
     UnlockUser {
         /// User to unlock
         username: String,
     },
+
+    /// Delete a user. The user can be restored until the end of the grace
+    /// period configured in `tasks.deleted_users`, after which it gets purged.
+    DeleteUser {
+        /// User to delete
+        username: String,
+    },
+
+    /// Restore a deleted user which wasn't purged yet
+    RestoreUser {
+        /// User to restore
+        username: String,
+    },
 }
 
 impl Options {
@@ -96,68 +206,18 @@ impl Options {
         let mut rng = rand_chacha::ChaChaRng::from_entropy();
 
         match self.subcommand {
-            SC::SetPassword { username, password } => {
-                let _span =
-                    info_span!("cli.manage.set_password", user.username = %username).entered();
+            SC::User { subcommand } => subcommand.run(root).await,
 
-                let database_config: DatabaseConfig = root.load_config()?;
-                let passwords_config: PasswordsConfig = root.load_config()?;
-
-                let mut conn = database_connection_from_config(&database_config).await?;
-                let password_manager = password_manager_from_config(&passwords_config).await?;
-
-                let txn = conn.begin().await?;
-                let mut repo = PgRepository::from_conn(txn);
-                let user = repo
-                    .user()
-                    .find_by_username(&username)
-                    .await?
-                    .context("User not found")?;
-
-                let password = password.into_bytes().into();
-
-                let (version, hashed_password) = password_manager.hash(&mut rng, password).await?;
-
-                repo.user_password()
-                    .add(&mut rng, &clock, &user, version, hashed_password, None)
-                    .await?;
-
-                info!(%user.id, %user.username, "Password changed");
-                repo.into_inner().commit().await?;
-
-                Ok(())
+            SC::SetPassword { username, password } => {
+                user::Subcommand::SetPassword { username, password }
+                    .run(root)
+                    .await
             }
 
             SC::VerifyEmail { username, email } => {
-                let _span = info_span!(
-                    "cli.manage.verify_email",
-                    user.username = username,
-                    user_email.email = email
-                )
-                .entered();
-
-                let database_config: DatabaseConfig = root.load_config()?;
-                let mut conn = database_connection_from_config(&database_config).await?;
-                let txn = conn.begin().await?;
-                let mut repo = PgRepository::from_conn(txn);
-
-                let user = repo
-                    .user()
-                    .find_by_username(&username)
-                    .await?
-                    .context("User not found")?;
-
-                let email = repo
-                    .user_email()
-                    .find(&user, &email)
-                    .await?
-                    .context("Email not found")?;
-                let email = repo.user_email().mark_as_verified(&clock, email).await?;
-
-                repo.into_inner().commit().await?;
-                info!(?email, "Email marked as verified");
-
-                Ok(())
+                user::Subcommand::VerifyEmail { username, email }
+                    .run(root)
+                    .await
             }
 
             SC::IssueCompatibilityToken {
@@ -233,6 +293,135 @@ impl Options {
                 Ok(())
             }
 
+            SC::AddServiceAccount { username } => {
+                let _span = info_span!("cli.manage.add_service_account", user.username = username)
+                    .entered();
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                if repo.user().exists(&username).await? {
+                    anyhow::bail!("User already exists");
+                }
+
+                let user = repo.user().add(&mut rng, &clock, username).await?;
+                let user = repo.user().set_service_account(user, true).await?;
+
+                repo.job()
+                    .schedule_job(ProvisionUserJob::new(&user))
+                    .await?;
+
+                repo.into_inner().commit().await?;
+                info!(%user.id, %user.username, "Service account created");
+
+                Ok(())
+            }
+
+            SC::AddClient {
+                redirect_uris,
+                auth_method,
+            } => {
+                let _span = info_span!("cli.manage.add_client").entered();
+
+                if !matches!(
+                    auth_method,
+                    OAuthClientAuthenticationMethod::ClientSecretBasic
+                        | OAuthClientAuthenticationMethod::ClientSecretPost
+                        | OAuthClientAuthenticationMethod::ClientSecretJwt
+                ) {
+                    anyhow::bail!("Unsupported authentication method {auth_method}");
+                }
+
+                let database_config: DatabaseConfig = root.load_config()?;
+                let secrets_config: SecretsConfig = root.load_config()?;
+                let encrypter = secrets_config.encrypter();
+
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let client_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+                let client_secret = Alphanumeric.sample_string(&mut rng, 32);
+                let encrypted_client_secret =
+                    encrypter.encrypt_to_string(client_secret.as_bytes())?;
+
+                // Clients provisioned by the operator are static, like the ones from the
+                // configuration, so that they never get cleaned up for being inactive
+                let client = repo
+                    .oauth2_client()
+                    .upsert_static(
+                        client_id,
+                        auth_method,
+                        Some(encrypted_client_secret),
+                        None,
+                        None,
+                        redirect_uris,
+                        Vec::new(),
+                        None,
+                        false,
+                        false,
+                        None,
+                        false,
+                    )
+                    .await?;
+
+                repo.into_inner().commit().await?;
+                info!(%client.id, "Client created");
+
+                println!("client_id: {}", client.client_id);
+                println!("client_secret: {client_secret}");
+
+                Ok(())
+            }
+
+            SC::ListClients => {
+                let _span = info_span!("cli.manage.list_clients").entered();
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let clients = repo.oauth2_client().all_static().await?;
+                for client in clients {
+                    let auth_method = client
+                        .token_endpoint_auth_method
+                        .as_ref()
+                        .map_or_else(|| "-".to_owned(), ToString::to_string);
+                    let redirect_uris = client
+                        .redirect_uris
+                        .iter()
+                        .map(Url::as_str)
+                        .collect::<Vec<_>>()
+                        .join(",");
+
+                    println!("{}\t{auth_method}\t{redirect_uris}", client.client_id);
+                }
+
+                Ok(())
+            }
+
+            SC::DeleteClient { client_id } => {
+                let _span =
+                    info_span!("cli.manage.delete_client", client.id = %client_id).entered();
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let client = repo
+                    .oauth2_client()
+                    .lookup(client_id)
+                    .await?
+                    .context("Client not found")?;
+
+                warn!(%client.id, "Deleting client");
+                repo.oauth2_client().delete(client).await?;
+                repo.into_inner().commit().await?;
+
+                Ok(())
+            }
+
             SC::KillSessions { username, dry_run } => {
                 let _span =
                     info_span!("cli.manage.kill_sessions", user.username = username).entered();
@@ -294,22 +483,19 @@ impl Options {
                         .lookup(id)
                         .await?
                         .context("Session not found")?;
-                    info!(%oauth2_session.id, %oauth2_session.scope, "Killing oauth2 session");
 
-                    if dry_run {
+                    // Derived sessions end along with their parent session
+                    if !oauth2_session.is_valid() {
                         continue;
                     }
 
-                    for scope in &*oauth2_session.scope {
-                        if let Some(device) = Device::from_scope_token(scope) {
-                            // Schedule a job to delete the device.
-                            repo.job()
-                                .schedule_job(DeleteDeviceJob::new(&user, &device))
-                                .await?;
-                        }
+                    info!(%oauth2_session.id, %oauth2_session.scope, "Killing oauth2 session");
+
+                    if dry_run {
+                        continue;
                     }
 
-                    repo.oauth2_session().finish(&clock, oauth2_session).await?;
+                    end_session(&mut repo, &clock, oauth2_session).await?;
                 }
 
                 let user_sessions_ids: Vec<Uuid> = sqlx::query_scalar(
@@ -351,11 +537,17 @@ impl Options {
                 Ok(())
             }
 
-            SC::LockUser {
-                username,
-                deactivate,
-            } => {
-                let _span = info_span!("cli.manage.lock_user", user.username = username).entered();
+            SC::InspectToken { token } => token::inspect(root, &token).await,
+
+            SC::RevokeToken {
+                token,
+                session,
+                dry_run,
+            } => token::revoke(root, &token, session, dry_run).await,
+
+            SC::ForcePasswordReset { username } => {
+                let _span = info_span!("cli.manage.force_password_reset", user.username = username)
+                    .entered();
                 let config: DatabaseConfig = root.load_config()?;
                 let mut conn = database_connection_from_config(&config).await?;
                 let txn = conn.begin().await?;
@@ -367,17 +559,57 @@ impl Options {
                     .await?
                     .context("User not found")?;
 
-                info!(%user.id, "Locking user");
+                if user.is_service_account {
+                    anyhow::bail!("Service accounts can't have a password");
+                }
+
+                // The flag is set right away so that the current password can't be used to
+                // log in anymore, even if the worker is not running
+                let user = repo.user().require_password_reset(&clock, user).await?;
+
+                warn!(%user.id, "Scheduling the end of all sessions and a password reset email");
+                repo.job()
+                    .schedule_job(ForcePasswordResetJob::new(&user))
+                    .await?;
+
+                repo.into_inner().commit().await?;
 
-                // Even though the deactivation job will lock the user, we lock it here in case
-                // the worker is not running, as we don't have a good way to run a job
-                // synchronously yet.
-                let user = repo.user().lock(&clock, user).await?;
+                Ok(())
+            }
+
+            SC::DeprecateCompatPassword { username, clear } => {
+                let _span = info_span!(
+                    "cli.manage.deprecate_compat_password",
+                    user.username = username
+                )
+                .entered();
+                let database_config: DatabaseConfig = root.load_config()?;
+                let passwords_config: PasswordsConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
 
-                if deactivate {
-                    warn!(%user.id, "Scheduling user deactivation");
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                if clear {
+                    let user = repo.user().clear_compat_password_deprecation(user).await?;
+                    info!(%user.id, "Cleared the password login deprecation");
+                } else if user.compat_password_deprecated() {
+                    info!(%user.id, "Password login is already deprecated for this user");
+                } else {
+                    let user = repo.user().deprecate_compat_password(&clock, user).await?;
+                    let blocked_at =
+                        clock.now() + passwords_config.compat_deprecation_grace_period();
+
+                    warn!(%user.id, %blocked_at, "Deprecating password login, sending a warning email");
                     repo.job()
-                        .schedule_job(DeactivateUserJob::new(&user, false))
+                        .schedule_job(SendCompatPasswordDeprecationEmailJob::new(
+                            &user, blocked_at,
+                        ))
                         .await?;
                 }
 
@@ -386,8 +618,45 @@ impl Options {
                 Ok(())
             }
 
-            SC::UnlockUser { username } => {
-                let _span = info_span!("cli.manage.lock_user", user.username = username).entered();
+            SC::LockUser {
+                username,
+                deactivate,
+            } => {
+                user::Subcommand::Lock {
+                    username,
+                    deactivate,
+                }
+                .run(root)
+                .await
+            }
+
+            SC::UnlockUser { username } => user::Subcommand::Unlock { username }.run(root).await,
+
+            SC::DeleteUser { username } => {
+                let _span =
+                    info_span!("cli.manage.delete_user", user.username = username).entered();
+                let config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                warn!(%user.id, "Deleting user");
+
+                repo.user().soft_delete(&clock, user).await?;
+                repo.into_inner().commit().await?;
+
+                Ok(())
+            }
+
+            SC::RestoreUser { username } => {
+                let _span =
+                    info_span!("cli.manage.restore_user", user.username = username).entered();
                 let config: DatabaseConfig = root.load_config()?;
                 let mut conn = database_connection_from_config(&config).await?;
                 let txn = conn.begin().await?;
@@ -399,9 +668,13 @@ impl Options {
                     .await?
                     .context("User not found")?;
 
-                info!(%user.id, "Unlocking user");
+                if user.is_purged() {
+                    anyhow::bail!("User was already purged and can't be restored");
+                }
+
+                info!(%user.id, "Restoring user");
 
-                repo.user().unlock(user).await?;
+                repo.user().restore(user).await?;
                 repo.into_inner().commit().await?;
 
                 Ok(())
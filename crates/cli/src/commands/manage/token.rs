@@ -0,0 +1,345 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! `manage inspect-token` and `manage revoke-token`, to find out who a
+//! leaked token belongs to and cut its access
+
+use anyhow::Context;
+use mas_config::DatabaseConfig;
+use mas_data_model::{AccessToken, CompatAccessToken, CompatRefreshToken, RefreshToken, TokenType};
+use mas_storage::{
+    compat::{CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository},
+    job::{DeleteDeviceJob, JobRepositoryExt},
+    oauth2::{
+        self, OAuth2AccessTokenRepository, OAuth2ClientRepository, OAuth2RefreshTokenRepository,
+        OAuth2SessionRepository,
+    },
+    user::UserRepository,
+    Clock, RepositoryAccess, SystemClock,
+};
+use mas_storage_pg::PgRepository;
+use sqlx::{Acquire, Postgres, Transaction};
+use tracing::{info, info_span, warn};
+use ulid::Ulid;
+
+use crate::util::database_connection_from_config;
+
+/// Tokens which aren't complete are looked up by prefix, which must be long
+/// enough not to match many tokens by mistake
+const MIN_PREFIX_LENGTH: usize = 12;
+
+enum Token {
+    AccessToken(AccessToken),
+    RefreshToken(RefreshToken),
+    CompatAccessToken(CompatAccessToken),
+    CompatRefreshToken(CompatRefreshToken),
+}
+
+impl Token {
+    fn id(&self) -> Ulid {
+        match self {
+            Self::AccessToken(t) => t.id,
+            Self::RefreshToken(t) => t.id,
+            Self::CompatAccessToken(t) => t.id,
+            Self::CompatRefreshToken(t) => t.id,
+        }
+    }
+
+    fn token_type(&self) -> TokenType {
+        match self {
+            Self::AccessToken(_) => TokenType::AccessToken,
+            Self::RefreshToken(_) => TokenType::RefreshToken,
+            Self::CompatAccessToken(_) => TokenType::CompatAccessToken,
+            Self::CompatRefreshToken(_) => TokenType::CompatRefreshToken,
+        }
+    }
+
+    fn session_id(&self) -> Ulid {
+        match self {
+            Self::AccessToken(t) => t.session_id,
+            Self::RefreshToken(t) => t.session_id,
+            Self::CompatAccessToken(t) => t.session_id,
+            Self::CompatRefreshToken(t) => t.session_id,
+        }
+    }
+
+    fn is_valid(&self, clock: &dyn Clock) -> bool {
+        match self {
+            Self::AccessToken(t) => t.is_valid(clock.now()),
+            Self::RefreshToken(t) => t.is_valid(),
+            Self::CompatAccessToken(t) => t.is_valid(clock.now()),
+            Self::CompatRefreshToken(t) => t.is_valid(),
+        }
+    }
+}
+
+/// Find a token, either from the full token or from a prefix of it
+async fn find_token(
+    repo: &mut PgRepository<Transaction<'_, Postgres>>,
+    token: &str,
+) -> anyhow::Result<Token> {
+    let found = match TokenType::check(token) {
+        Ok(TokenType::AccessToken) => repo
+            .oauth2_access_token()
+            .find_by_token(token)
+            .await?
+            .map(Token::AccessToken),
+        Ok(TokenType::RefreshToken) => repo
+            .oauth2_refresh_token()
+            .find_by_token(token)
+            .await?
+            .map(Token::RefreshToken),
+        Ok(TokenType::CompatAccessToken) => repo
+            .compat_access_token()
+            .find_by_token(token)
+            .await?
+            .map(Token::CompatAccessToken),
+        Ok(TokenType::CompatRefreshToken) => repo
+            .compat_refresh_token()
+            .find_by_token(token)
+            .await?
+            .map(Token::CompatRefreshToken),
+        Err(_) => return find_token_by_prefix(repo, token).await,
+    };
+
+    found.context("Token not found")
+}
+
+async fn find_token_by_prefix(
+    repo: &mut PgRepository<Transaction<'_, Postgres>>,
+    prefix: &str,
+) -> anyhow::Result<Token> {
+    if prefix.len() < MIN_PREFIX_LENGTH {
+        anyhow::bail!(
+            "Not a valid token, and too short to be looked up as a prefix (at least {MIN_PREFIX_LENGTH} characters are needed)"
+        );
+    }
+
+    // Fetch one more than needed from each table, to detect ambiguous prefixes
+    let mut matches: Vec<Token> = Vec::new();
+    matches.extend(
+        repo.oauth2_access_token()
+            .find_by_token_prefix(prefix, 2)
+            .await?
+            .into_iter()
+            .map(Token::AccessToken),
+    );
+    matches.extend(
+        repo.oauth2_refresh_token()
+            .find_by_token_prefix(prefix, 2)
+            .await?
+            .into_iter()
+            .map(Token::RefreshToken),
+    );
+    matches.extend(
+        repo.compat_access_token()
+            .find_by_token_prefix(prefix, 2)
+            .await?
+            .into_iter()
+            .map(Token::CompatAccessToken),
+    );
+    matches.extend(
+        repo.compat_refresh_token()
+            .find_by_token_prefix(prefix, 2)
+            .await?
+            .into_iter()
+            .map(Token::CompatRefreshToken),
+    );
+
+    let mut matches = matches.into_iter();
+    match (matches.next(), matches.next()) {
+        (None, _) => anyhow::bail!("No token starts with this prefix"),
+        (Some(token), None) => Ok(token),
+        (Some(_), Some(_)) => {
+            anyhow::bail!("Several tokens start with this prefix, use a longer one")
+        }
+    }
+}
+
+/// Print the token, and the session, user and client it belongs to
+pub(super) async fn inspect(root: &super::super::Options, token: &str) -> anyhow::Result<()> {
+    let _span = info_span!("cli.manage.inspect_token").entered();
+    let clock = SystemClock::default();
+    let database_config: DatabaseConfig = root.load_config()?;
+    let mut conn = database_connection_from_config(&database_config).await?;
+    let txn = conn.begin().await?;
+    let mut repo = PgRepository::from_conn(txn);
+
+    let token = find_token(&mut repo, token).await?;
+    info!(
+        token.id = %token.id(),
+        token.kind = %token.token_type(),
+        token.valid = token.is_valid(&clock),
+        "Found token"
+    );
+
+    if matches!(token, Token::AccessToken(_) | Token::RefreshToken(_)) {
+        let session = repo
+            .oauth2_session()
+            .lookup(token.session_id())
+            .await?
+            .context("Session not found")?;
+        let client = repo
+            .oauth2_client()
+            .lookup(session.client_id)
+            .await?
+            .context("Client not found")?;
+        let user = if let Some(user_id) = session.user_id {
+            repo.user().lookup(user_id).await?
+        } else {
+            None
+        };
+
+        info!(
+            session.id = %session.id,
+            session.valid = session.is_valid(),
+            session.scope = %session.scope,
+            session.created_at = %session.created_at,
+            "OAuth 2.0 session"
+        );
+        info!(
+            client.id = %client.id,
+            %client.client_id,
+            client.name = client.client_name.as_deref().unwrap_or("-"),
+            "Client"
+        );
+        if let Some(user) = user {
+            info!(%user.id, %user.username, "User");
+        } else {
+            info!("The session isn't tied to a user");
+        }
+    } else {
+        let session = repo
+            .compat_session()
+            .lookup(token.session_id())
+            .await?
+            .context("Session not found")?;
+        let user = repo
+            .user()
+            .lookup(session.user_id)
+            .await?
+            .context("User not found")?;
+
+        info!(
+            session.id = %session.id,
+            session.valid = session.is_valid(),
+            %session.device,
+            session.created_at = %session.created_at,
+            "Compatibility session"
+        );
+        info!(%user.id, %user.username, "User");
+    }
+
+    Ok(())
+}
+
+/// Revoke the token, or end the whole session it belongs to
+///
+/// OAuth 2.0 refresh tokens can't be revoked on their own, so revoking one
+/// always ends its session, like the revocation endpoint does.
+pub(super) async fn revoke(
+    root: &super::super::Options,
+    token: &str,
+    end_session: bool,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let _span = info_span!("cli.manage.revoke_token").entered();
+    let clock = SystemClock::default();
+    let database_config: DatabaseConfig = root.load_config()?;
+    let mut conn = database_connection_from_config(&database_config).await?;
+    let txn = conn.begin().await?;
+    let mut repo = PgRepository::from_conn(txn);
+
+    let token = find_token(&mut repo, token).await?;
+    let end_session = end_session || matches!(token, Token::RefreshToken(_));
+    let session_id = token.session_id();
+
+    if !end_session && !token.is_valid(&clock) {
+        warn!(token.id = %token.id(), "The token is not valid anymore, nothing to revoke");
+        return Ok(());
+    }
+
+    match token {
+        Token::AccessToken(access_token) if !end_session => {
+            warn!(%access_token.id, "Revoking access token");
+            repo.oauth2_access_token()
+                .revoke(&clock, access_token)
+                .await?;
+        }
+
+        Token::AccessToken(_) | Token::RefreshToken(_) => {
+            let session = repo
+                .oauth2_session()
+                .lookup(session_id)
+                .await?
+                .context("Session not found")?;
+
+            if !session.is_valid() {
+                warn!(%session.id, "The session already ended");
+                return Ok(());
+            }
+
+            warn!(%session.id, "Ending OAuth 2.0 session");
+            oauth2::end_session(&mut repo, &clock, session).await?;
+        }
+
+        Token::CompatAccessToken(access_token) if !end_session => {
+            warn!(%access_token.id, "Expiring compatibility access token");
+            repo.compat_access_token()
+                .expire(&clock, access_token)
+                .await?;
+        }
+
+        Token::CompatRefreshToken(refresh_token) if !end_session => {
+            warn!(%refresh_token.id, "Consuming compatibility refresh token");
+            repo.compat_refresh_token()
+                .consume(&clock, refresh_token)
+                .await?;
+        }
+
+        Token::CompatAccessToken(_) | Token::CompatRefreshToken(_) => {
+            let session = repo
+                .compat_session()
+                .lookup(session_id)
+                .await?
+                .context("Session not found")?;
+
+            if !session.is_valid() {
+                warn!(%session.id, "The session already ended");
+                return Ok(());
+            }
+
+            let user = repo
+                .user()
+                .lookup(session.user_id)
+                .await?
+                .context("User not found")?;
+
+            warn!(%session.id, %session.device, "Ending compatibility session");
+            repo.job()
+                .schedule_job(DeleteDeviceJob::new(&user, &session.device))
+                .await?;
+            repo.compat_session().finish(&clock, session).await?;
+        }
+    }
+
+    let txn = repo.into_inner();
+    if dry_run {
+        info!("Dry run, not saving");
+        txn.rollback().await?;
+    } else {
+        txn.commit().await?;
+    }
+
+    Ok(())
+}
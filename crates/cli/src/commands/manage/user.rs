@@ -0,0 +1,395 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `manage user` subcommands, to fix user accounts without touching the
+//! database directly
+
+use anyhow::Context;
+use clap::Parser;
+use mas_config::{AccountConfig, DatabaseConfig, PasswordsConfig};
+use mas_data_model::is_valid_username;
+use mas_storage::{
+    job::{DeactivateUserJob, JobRepositoryExt, ProvisionUserJob},
+    login_failure::user_key,
+    user::{
+        UserEmailFilter, UserEmailRepository, UserFilter, UserPasswordRepository, UserRepository,
+    },
+    Pagination, RepositoryAccess, SystemClock,
+};
+use mas_storage_pg::PgRepository;
+use rand::SeedableRng;
+use sqlx::Acquire;
+use tracing::{info, info_span, warn};
+
+use crate::util::{
+    database_connection_from_config, email_normalization_from_config, password_manager_from_config,
+};
+
+/// How many users are fetched at once by `manage user list`
+const LIST_PAGE_SIZE: usize = 100;
+
+#[derive(Parser, Debug)]
+pub(super) enum Subcommand {
+    /// Add a user
+    Add {
+        /// Username of the new user
+        username: String,
+
+        /// Set a password for the new user
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Add an email address to the new user, already verified and set as
+        /// primary
+        #[arg(long)]
+        email: Option<String>,
+    },
+
+    /// List users
+    List {
+        /// Only list users whose username or one of their email addresses
+        /// contains this text, ignoring case
+        #[arg(long)]
+        search: Option<String>,
+
+        /// Only list active users
+        #[arg(long, conflicts_with = "locked")]
+        active: bool,
+
+        /// Only list locked users
+        #[arg(long)]
+        locked: bool,
+
+        /// Maximum number of users to list. Lists all users if not set
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+
+    /// Lock a user
+    Lock {
+        /// User to lock
+        username: String,
+
+        /// Whether to deactivate the user
+        #[arg(long)]
+        deactivate: bool,
+    },
+
+    /// Unlock a user
+    Unlock {
+        /// User to unlock
+        username: String,
+    },
+
+    /// Set a user password
+    SetPassword { username: String, password: String },
+
+    /// Mark email address as verified
+    VerifyEmail { username: String, email: String },
+}
+
+impl Subcommand {
+    #[allow(clippy::too_many_lines)]
+    pub(super) async fn run(self, root: &super::super::Options) -> anyhow::Result<()> {
+        use Subcommand as SC;
+        let clock = SystemClock::default();
+        // XXX: we should disallow SeedableRng::from_entropy
+        let mut rng = rand_chacha::ChaChaRng::from_entropy();
+
+        match self {
+            SC::Add {
+                username,
+                password,
+                email,
+            } => {
+                let _span = info_span!("cli.manage.user.add", user.username = username).entered();
+
+                if !is_valid_username(&username) {
+                    anyhow::bail!("Invalid username");
+                }
+
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                if repo.user().exists(&username).await? {
+                    anyhow::bail!("User already exists");
+                }
+
+                // Normalize the email address like the server does, and make sure it isn't
+                // already verified by another user
+                let email = if let Some(email) = email {
+                    let account_config: AccountConfig = root.load_config()?;
+                    let email = email_normalization_from_config(&account_config).normalize(&email);
+                    let filter = UserEmailFilter::new().for_email(&email).verified_only();
+                    if repo.user_email().count(filter).await? > 0 {
+                        anyhow::bail!("Email address {email:?} is already in use by another user");
+                    }
+                    Some(email)
+                } else {
+                    None
+                };
+
+                let user = repo.user().add(&mut rng, &clock, username).await?;
+
+                if let Some(password) = password {
+                    let passwords_config: PasswordsConfig = root.load_config()?;
+                    let password_manager = password_manager_from_config(&passwords_config).await?;
+
+                    let (version, hashed_password) = password_manager
+                        .hash(&mut rng, password.into_bytes().into())
+                        .await?;
+
+                    repo.user_password()
+                        .add(&mut rng, &clock, &user, version, hashed_password, None)
+                        .await?;
+                }
+
+                if let Some(email) = email {
+                    let user_email = repo
+                        .user_email()
+                        .add(&mut rng, &clock, &user, email)
+                        .await?;
+                    let user_email = repo
+                        .user_email()
+                        .mark_as_verified(&clock, user_email)
+                        .await?;
+                    repo.user_email().set_as_primary(&user_email).await?;
+                }
+
+                // Load the user again, as setting the primary email changed it
+                let user = repo
+                    .user()
+                    .lookup(user.id)
+                    .await?
+                    .context("User not found")?;
+
+                repo.job()
+                    .schedule_job(ProvisionUserJob::new(&user))
+                    .await?;
+
+                repo.into_inner().commit().await?;
+                info!(%user.id, %user.username, "User added");
+
+                Ok(())
+            }
+
+            SC::List {
+                search,
+                active,
+                locked,
+                limit,
+            } => {
+                let _span = info_span!("cli.manage.user.list").entered();
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let mut filter = UserFilter::new();
+                if let Some(search) = search.as_deref() {
+                    filter = filter.matching(search);
+                }
+                if active {
+                    filter = filter.active_only();
+                }
+                if locked {
+                    filter = filter.locked_only();
+                }
+
+                let mut remaining = limit.unwrap_or(usize::MAX);
+                let mut pagination = Pagination::first(LIST_PAGE_SIZE.min(remaining));
+                while remaining > 0 {
+                    let page = repo.user().list(filter, pagination).await?;
+
+                    for user in &page.edges {
+                        let state = if user.is_deleted() {
+                            "deleted"
+                        } else if user.locked_at.is_some() {
+                            "locked"
+                        } else {
+                            "active"
+                        };
+
+                        println!(
+                            "{id}\t{username}\t{state}\t{created_at}",
+                            id = user.id,
+                            username = user.username,
+                            created_at = user.created_at.to_rfc3339(),
+                        );
+                    }
+
+                    remaining = remaining.saturating_sub(page.edges.len());
+                    match page.edges.last() {
+                        Some(last) if page.has_next_page => {
+                            pagination =
+                                Pagination::first(LIST_PAGE_SIZE.min(remaining)).after(last.id);
+                        }
+                        _ => break,
+                    }
+                }
+
+                Ok(())
+            }
+
+            SC::Lock {
+                username,
+                deactivate,
+            } => {
+                let _span = info_span!("cli.manage.user.lock", user.username = username).entered();
+                let config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                info!(%user.id, "Locking user");
+
+                // Even though the deactivation job will lock the user, we lock it here in case
+                // the worker is not running, as we don't have a good way to run a job
+                // synchronously yet.
+                let user = repo.user().lock(&clock, user).await?;
+
+                if deactivate {
+                    warn!(%user.id, "Scheduling user deactivation");
+                    repo.job()
+                        .schedule_job(DeactivateUserJob::new(&user, false))
+                        .await?;
+                }
+
+                repo.into_inner().commit().await?;
+
+                Ok(())
+            }
+
+            SC::Unlock { username } => {
+                let _span =
+                    info_span!("cli.manage.user.unlock", user.username = username).entered();
+                let config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                info!(%user.id, "Unlocking user");
+
+                // Also lift the lockout after too many failed login attempts
+                repo.login_failure().reset(&user_key(&user)).await?;
+                repo.user().unlock(user).await?;
+                repo.into_inner().commit().await?;
+
+                Ok(())
+            }
+
+            SC::SetPassword { username, password } => {
+                let _span =
+                    info_span!("cli.manage.user.set_password", user.username = %username).entered();
+
+                let database_config: DatabaseConfig = root.load_config()?;
+                let passwords_config: PasswordsConfig = root.load_config()?;
+
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let password_manager = password_manager_from_config(&passwords_config).await?;
+
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                if user.is_service_account {
+                    anyhow::bail!("Service accounts can't have a password");
+                }
+
+                let password = password.into_bytes().into();
+
+                let (version, hashed_password) = password_manager.hash(&mut rng, password).await?;
+
+                repo.user_password()
+                    .add(&mut rng, &clock, &user, version, hashed_password, None)
+                    .await?;
+
+                // Setting a new password fulfills a pending reset requirement
+                let user = repo.user().clear_password_reset(user).await?;
+
+                info!(%user.id, %user.username, "Password changed");
+                repo.into_inner().commit().await?;
+
+                Ok(())
+            }
+
+            SC::VerifyEmail { username, email } => {
+                let _span = info_span!(
+                    "cli.manage.user.verify_email",
+                    user.username = username,
+                    user_email.email = email
+                )
+                .entered();
+
+                let database_config: DatabaseConfig = root.load_config()?;
+                let mut conn = database_connection_from_config(&database_config).await?;
+                let txn = conn.begin().await?;
+                let mut repo = PgRepository::from_conn(txn);
+
+                let user = repo
+                    .user()
+                    .find_by_username(&username)
+                    .await?
+                    .context("User not found")?;
+
+                let account_config: AccountConfig = root.load_config()?;
+                let email = email_normalization_from_config(&account_config).normalize(&email);
+
+                let email = repo
+                    .user_email()
+                    .find(&user, &email)
+                    .await?
+                    .context("Email not found")?;
+
+                // A verified email address can only belong to one user
+                if email.confirmed_at.is_none() {
+                    let filter = UserEmailFilter::new()
+                        .for_email(&email.email)
+                        .verified_only();
+                    if repo.user_email().count(filter).await? > 0 {
+                        anyhow::bail!(
+                            "Email address {:?} is already in use by another user",
+                            email.email
+                        );
+                    }
+                }
+
+                let email = repo.user_email().mark_as_verified(&clock, email).await?;
+
+                repo.into_inner().commit().await?;
+                info!(?email, "Email marked as verified");
+
+                Ok(())
+            }
+        }
+    }
+}
@@ -20,6 +20,10 @@ use mas_config::ConfigurationSection;
 mod config;
 mod database;
 mod debug;
+mod dev;
+mod doctor;
+mod import;
+mod keys;
 mod manage;
 mod server;
 mod templates;
@@ -42,11 +46,23 @@ enum Subcommand {
     /// Manage the instance
     Manage(self::manage::Options),
 
+    /// Generate and rotate the signing keys
+    Keys(self::keys::Options),
+
+    /// Import users from other services
+    Import(self::import::Options),
+
+    /// Check the deployment for common problems
+    Doctor(self::doctor::Options),
+
     /// Templates-related commands
     Templates(self::templates::Options),
 
     /// Debug utilities
     Debug(self::debug::Options),
+
+    /// Local development utilities
+    Dev(self::dev::Options),
 }
 
 #[derive(Parser, Debug)]
@@ -68,8 +84,12 @@ impl Options {
             Some(S::Server(c)) => c.run(&self).await,
             Some(S::Worker(c)) => c.run(&self).await,
             Some(S::Manage(c)) => c.run(&self).await,
+            Some(S::Keys(c)) => c.run(&self).await,
+            Some(S::Import(c)) => c.run(&self).await,
+            Some(S::Doctor(c)) => c.run(&self).await,
             Some(S::Templates(c)) => c.run(&self).await,
             Some(S::Debug(c)) => c.run(&self).await,
+            Some(S::Dev(c)) => c.run(&self).await,
             None => self::server::Options::default().run(&self).await,
         }
     }
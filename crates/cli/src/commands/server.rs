@@ -12,19 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{sync::Arc, time::Duration};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::Context;
 use clap::Parser;
 use itertools::Itertools;
-use mas_config::AppConfig;
+use mas_config::{AppConfig, DeviceIdConflictPolicy};
+use mas_data_model::TermsOfService;
 use mas_handlers::{
-    ActivityTracker, CookieManager, HttpClientFactory, MatrixHomeserver, MetadataCache, SiteConfig,
+    rate_limit::{EmailThrottle, Quota},
+    ActivityTracker, AvatarStore, BoxHomeserverConnection, CookieManager, DeviceConflictPolicy,
+    DeviceNameTemplate, HttpClientFactory, LoginLockout, MatrixHomeserver, RequestUriCache,
+    SiteConfig,
 };
 use mas_listener::{server::Server, shutdown::ShutdownStream};
-use mas_matrix_synapse::SynapseConnection;
 use mas_router::UrlBuilder;
-use mas_storage_pg::MIGRATOR;
+use mas_storage_pg::{applied_schema_version, MIGRATOR};
 use rand::{
     distributions::{Alphanumeric, DistString},
     thread_rng,
@@ -33,10 +36,17 @@ use tokio::signal::unix::SignalKind;
 use tracing::{info, info_span, warn, Instrument};
 
 use crate::{
+    access_log::AccessLog,
     app_state::AppState,
+    build_info::build_info,
+    client_certificate::ClientCertificateExtractor,
     util::{
-        database_pool_from_config, mailer_from_config, password_manager_from_config,
-        policy_factory_from_config, register_sighup, templates_from_config,
+        blob_storage_from_config, breached_password_check_from_config, captcha_config_from_config,
+        check_database_schema, custom_scopes_from_config, database_pool_from_config,
+        email_normalization_from_config, homeserver_connection_from_config, mailer_from_config,
+        maintenance_mode_from_config, password_manager_from_config, policy_factory_from_config,
+        rate_limiter_from_config, refresh_token_policies_from_config, register_sighup,
+        tasks_settings_from_config, templates_from_config,
     },
 };
 
@@ -46,16 +56,50 @@ pub(super) struct Options {
     #[arg(long)]
     migrate: bool,
 
+    /// Never apply migrations, and refuse to start if some are pending. For
+    /// deployments where schema changes go through `mas-cli database migrate`
+    #[arg(long, conflicts_with = "migrate")]
+    no_migrate: bool,
+
     /// Do not start the task worker
     #[arg(long)]
     no_worker: bool,
+
+    /// Address on which to serve the Prometheus metrics, instead of the
+    /// `listen` address set in the metrics exporter config
+    #[arg(long, value_name = "ADDRESS")]
+    prometheus_listen: Option<SocketAddr>,
+
+    /// Run even if the database schema is newer than what this version
+    /// supports
+    #[arg(long)]
+    allow_newer_schema: bool,
+
+    /// Run as the development server: serve the emails kept in memory on the
+    /// `/dev/mailbox` page, which has no authentication, and trust the email
+    /// addresses given on registration. Only set by `mas-cli dev server`.
+    #[arg(skip)]
+    dev: bool,
 }
 
 impl Options {
-    #[allow(clippy::too_many_lines)]
+    /// Options for the development server
+    pub(super) fn dev() -> Self {
+        Self {
+            dev: true,
+            ..Self::default()
+        }
+    }
+
     pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
-        let span = info_span!("cli.run.init").entered();
         let config: AppConfig = root.load_config()?;
+        self.run_with_config(config).await
+    }
+
+    /// Run the server with an already loaded configuration
+    #[allow(clippy::too_many_lines)]
+    pub(super) async fn run_with_config(self, config: AppConfig) -> anyhow::Result<()> {
+        let span = info_span!("cli.run.init").entered();
 
         // Connect to the database
         info!("Connecting to the database");
@@ -70,6 +114,23 @@ impl Options {
                 .context("could not run migrations")?;
         }
 
+        let mut conn = pool.acquire().await?;
+        check_database_schema(&mut conn, self.allow_newer_schema, !self.no_migrate).await?;
+        let schema_version = applied_schema_version(&mut conn).await?;
+        drop(conn);
+
+        // Expose the metrics on their own address, if configured
+        crate::telemetry::spawn_prometheus_listener(self.prometheus_listen)?;
+
+        let build_info = build_info();
+        info!(
+            version = build_info.version,
+            git_sha = build_info.git_sha,
+            features = ?build_info.features,
+            schema_version,
+            "Starting the authentication service"
+        );
+
         // Initialize the key store
         let key_store = config
             .secrets
@@ -77,13 +138,19 @@ impl Options {
             .await
             .context("could not import keys from config")?;
 
+        let request_signer = config
+            .secrets
+            .request_signer()
+            .await
+            .context("could not import request signing keys from config")?;
+
         let encrypter = config.secrets.encrypter();
         let cookie_manager =
             CookieManager::derive_from(config.http.public_base.clone(), &config.secrets.encryption);
 
         // Load and compile the WASM policies (and fallback to the default embedded one)
         info!("Loading and compiling the policy module");
-        let policy_factory = policy_factory_from_config(&config.policy).await?;
+        let policy_factory = policy_factory_from_config(&config.policy, &config.scopes).await?;
         let policy_factory = Arc::new(policy_factory);
 
         let url_builder = UrlBuilder::new(
@@ -103,8 +170,32 @@ impl Options {
 
         let http_client_factory = HttpClientFactory::new().await?;
 
+        let conn = homeserver_connection_from_config(
+            &config.matrix,
+            &request_signer,
+            &http_client_factory,
+        )
+        .await?;
+
+        // Only report the circuit breaker state in the readiness check if configured
+        let homeserver_circuit_breaker = config
+            .matrix
+            .circuit_breaker
+            .affects_readiness
+            .then(|| conn.circuit_breaker().clone());
+
+        // The handlers which update the profile of the user call the homeserver directly
+        let homeserver_connection: BoxHomeserverConnection = Arc::new(conn.clone());
+
+        let mailer = mailer_from_config(&config.email, &templates)?;
+        // The mailbox lists every email sent, including recovery and login links,
+        // so it is only served by the development server
+        let dev_mailbox = mailer.memory_mailbox().filter(|_| self.dev);
+        if dev_mailbox.is_some() && self.no_worker {
+            warn!("Emails are kept in memory by the worker process, they won't show up on the development mailbox of this process");
+        }
+
         if !self.no_worker {
-            let mailer = mailer_from_config(&config.email, &templates)?;
             mailer.test_connection().await?;
 
             #[allow(clippy::disallowed_methods)]
@@ -112,13 +203,19 @@ impl Options {
             let worker_name = Alphanumeric.sample_string(&mut rng, 10);
 
             info!(worker_name, "Starting task worker");
-            let conn = SynapseConnection::new(
-                config.matrix.homeserver.clone(),
-                config.matrix.endpoint.clone(),
-                config.matrix.secret.clone(),
-                http_client_factory.clone(),
-            );
-            let monitor = mas_tasks::init(&worker_name, &pool, &mailer, conn).await?;
+            let settings =
+                tasks_settings_from_config(&config.tasks, &config.secrets, &config.rate_limiting);
+            let monitor = mas_tasks::init(
+                &worker_name,
+                &pool,
+                &mailer,
+                conn.clone(),
+                settings,
+                &key_store,
+                &url_builder,
+                &http_client_factory,
+            )
+            .await?;
             // TODO: grab the handle
             tokio::spawn(monitor.run());
         }
@@ -130,18 +227,119 @@ impl Options {
         let password_manager = password_manager_from_config(&config.passwords).await?;
 
         // The upstream OIDC metadata cache
-        let metadata_cache = MetadataCache::new();
-
-        let conn = SynapseConnection::new(
-            config.matrix.homeserver.clone(),
-            config.matrix.endpoint.clone(),
-            config.matrix.secret.clone(),
-            http_client_factory.clone(),
-        );
+        #[cfg(feature = "upstream-oauth2")]
+        let metadata_cache = mas_handlers::MetadataCache::new();
+
+        // The cache of request objects fetched from `request_uri`s
+        let request_uri_cache = RequestUriCache::new();
+
+        let blob_storage = blob_storage_from_config(&config.storage, &http_client_factory);
+
+        let avatar_store = if config.avatars.enabled {
+            let blob_storage = blob_storage
+                .context("avatar uploads are enabled but no blob storage is configured")?;
+            Some(AvatarStore::new(
+                blob_storage,
+                config.avatars.max_size,
+                url_builder.clone(),
+            ))
+        } else {
+            None
+        };
 
+        let rate_limiter = rate_limiter_from_config(&config.rate_limiting, &pool).await?;
         let site_config = SiteConfig {
             access_token_ttl: config.experimental.access_token_ttl,
             compat_token_ttl: config.experimental.compat_token_ttl,
+            custom_scopes: custom_scopes_from_config(&config.scopes)?.into(),
+            refresh_token_policies: refresh_token_policies_from_config(
+                &config.experimental,
+                &config.clients,
+            ),
+            avatar_store,
+            rate_limiter: rate_limiter.clone(),
+            login_rate_limit: Quota::new(
+                config.rate_limiting.login.burst,
+                config.rate_limiting.login.replenish_interval,
+            ),
+            registration_rate_limit: Quota::new(
+                config.rate_limiting.registration.burst,
+                config.rate_limiting.registration.replenish_interval,
+            ),
+            device_code_link_rate_limit: Quota::new(
+                config.rate_limiting.device_code_link.burst,
+                config.rate_limiting.device_code_link.replenish_interval,
+            ),
+            graphql_rate_limit: Quota::new(
+                config.rate_limiting.graphql.burst,
+                config.rate_limiting.graphql.replenish_interval,
+            ),
+            email_throttle: EmailThrottle::new(
+                rate_limiter,
+                Quota::new(
+                    config.rate_limiting.email.per_address.burst,
+                    config.rate_limiting.email.per_address.replenish_interval,
+                ),
+                Quota::new(
+                    config.rate_limiting.email.per_user.burst,
+                    config.rate_limiting.email.per_user.replenish_interval,
+                ),
+            ),
+            max_active_sessions_per_client: config
+                .rate_limiting
+                .tokens
+                .max_active_sessions_per_client,
+            user_token_rate_limit: config
+                .rate_limiting
+                .tokens
+                .max_tokens_per_user_per_hour
+                .map(Quota::per_hour),
+            login_lockout: config.rate_limiting.login_lockout.map(|lockout| {
+                LoginLockout::new(
+                    lockout.user_threshold,
+                    lockout.ip_threshold,
+                    lockout.base_duration,
+                    lockout.max_duration,
+                )
+            }),
+            maintenance: maintenance_mode_from_config(&config.maintenance),
+            verify_email_before_registration: config.account.verify_email_before_registration,
+            skip_email_verification: self.dev,
+            allowed_next_urls: config.account.allowed_next_urls.clone().into(),
+            email_normalization: email_normalization_from_config(&config.account),
+            email_login_links: config.account.email_login_links,
+            terms: config.account.terms.as_ref().map(|terms| TermsOfService {
+                version: terms.version.clone(),
+                url: terms.url.clone(),
+            }),
+            compat_device_name_template: config
+                .matrix
+                .device_name_template
+                .as_deref()
+                .map(DeviceNameTemplate::new)
+                .transpose()
+                .context("invalid device name template")?,
+            device_conflict_policy: match config.matrix.device_id_conflict {
+                DeviceIdConflictPolicy::Replace => DeviceConflictPolicy::Replace,
+                DeviceIdConflictPolicy::Reject => DeviceConflictPolicy::Reject,
+                DeviceIdConflictPolicy::Suffix => DeviceConflictPolicy::Suffix,
+            },
+            captcha: captcha_config_from_config(&config.captcha)?,
+            breached_password_check: breached_password_check_from_config(&config.passwords),
+            compat_password_deprecation_grace_period: config
+                .passwords
+                .compat_deprecation_grace_period(),
+            matrix_introspection_clients: config.matrix.introspection_clients.clone().into(),
+            embedding_origins: config
+                .account
+                .embedding_origins
+                .iter()
+                .map(|url| {
+                    let origin = url.origin();
+                    anyhow::ensure!(origin.is_tuple(), "invalid embedding origin {url}");
+                    Ok(origin.ascii_serialization())
+                })
+                .collect::<Result<_, _>>()?,
         };
 
         // Initialize the activity tracker
@@ -149,35 +347,86 @@ impl Options {
         let activity_tracker = ActivityTracker::new(pool.clone(), Duration::from_secs(60));
         let trusted_proxies = config.http.trusted_proxies.clone();
 
+        // Open the access log, if configured. The guard flushes the pending lines
+        // when dropped, so it has to live as long as the server
+        let (access_log, _access_log_guard) = config
+            .http
+            .access_log
+            .as_ref()
+            .map(|access_log| AccessLog::from_config(access_log, &trusted_proxies))
+            .transpose()?
+            .unzip();
+
+        // Extract the TLS client certificates, if configured
+        let client_certificate = config
+            .http
+            .client_certificate
+            .as_ref()
+            .map(|client_certificate| {
+                ClientCertificateExtractor::from_config(client_certificate, &trusted_proxies)
+            })
+            .transpose()?;
+        let request_client_certificates = client_certificate
+            .as_ref()
+            .is_some_and(ClientCertificateExtractor::requests_tls_certificates);
+
         // Explicitly the config to properly zeroize secret keys
         drop(config);
 
         // Listen for SIGHUP
-        register_sighup(&templates, &activity_tracker)?;
+        register_sighup(&templates, Some(&activity_tracker))?;
 
-        let graphql_schema = mas_handlers::graphql_schema(&pool, &policy_factory, conn);
+        #[cfg(feature = "graphql")]
+        let session_events = mas_handlers::SessionEvents::listen(&pool)
+            .await
+            .context("could not listen to the session events")?;
+
+        #[cfg(feature = "graphql")]
+        let graphql_schema = mas_handlers::graphql_schema(
+            &pool,
+            &policy_factory,
+            conn,
+            site_config.refresh_token_policies.clone(),
+            site_config.avatar_store.clone(),
+            site_config.email_normalization,
+            site_config.email_throttle.clone(),
+            site_config.terms.clone(),
+            session_events,
+            mas_handlers::GraphQLLimits {
+                max_depth: config.graphql.max_depth,
+                max_complexity: config.graphql.max_complexity,
+            },
+        );
 
         let state = {
             let mut s = AppState {
                 pool,
                 templates,
                 key_store,
+                request_signer,
+                #[cfg(feature = "upstream-oauth2")]
                 metadata_cache,
+                request_uri_cache,
                 cookie_manager,
                 encrypter,
                 url_builder,
                 homeserver,
+                homeserver_connection,
                 policy_factory,
+                #[cfg(feature = "graphql")]
                 graphql_schema,
                 http_client_factory,
                 password_manager,
                 site_config,
                 activity_tracker,
                 trusted_proxies,
+                homeserver_circuit_breaker,
+                dev_mailbox: dev_mailbox.filter(|_| !self.no_worker),
                 conn_acquisition_histogram: None,
             };
             s.init_metrics()?;
             // XXX: this might panic
+            #[cfg(feature = "upstream-oauth2")]
             s.init_metadata_cache().await;
             s
         };
@@ -192,7 +441,10 @@ impl Options {
 
                 // Load the TLS config
                 let tls_config = if let Some(tls_config) = config.tls.as_ref() {
-                    let tls_config = crate::server::build_tls_server_config(tls_config)?;
+                    let tls_config = crate::server::build_tls_server_config(
+                        tls_config,
+                        request_client_certificates,
+                    )?;
                     Some(Arc::new(tls_config))
                 } else {
                     None
@@ -204,6 +456,8 @@ impl Options {
                     &config.resources,
                     config.prefix.as_deref(),
                     config.name.as_deref(),
+                    access_log.as_ref(),
+                    client_certificate.as_ref(),
                 );
 
 
@@ -12,21 +12,37 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::SocketAddr;
+
+use anyhow::Context;
 use clap::Parser;
 use mas_config::AppConfig;
 use mas_handlers::HttpClientFactory;
-use mas_matrix_synapse::SynapseConnection;
 use mas_router::UrlBuilder;
 use rand::{
     distributions::{Alphanumeric, DistString},
     thread_rng,
 };
-use tracing::{info, info_span};
+use tracing::{info, info_span, warn};
 
-use crate::util::{database_pool_from_config, mailer_from_config, templates_from_config};
+use crate::util::{
+    check_database_schema, database_pool_from_config, homeserver_connection_from_config,
+    mailer_from_config, register_sighup, tasks_settings_from_config, templates_from_config,
+};
 
 #[derive(Parser, Debug, Default)]
-pub(super) struct Options {}
+pub(super) struct Options {
+    /// Address on which to serve the Prometheus metrics. The `listen` address
+    /// set in the metrics exporter config is only used by the server, as it
+    /// can't be bound by several processes on the same host
+    #[arg(long, value_name = "ADDRESS")]
+    prometheus_listen: Option<SocketAddr>,
+
+    /// Run even if the database schema is newer than what this version
+    /// supports
+    #[arg(long)]
+    allow_newer_schema: bool,
+}
 
 impl Options {
     pub async fn run(self, root: &super::Options) -> anyhow::Result<()> {
@@ -37,6 +53,15 @@ impl Options {
         info!("Connecting to the database");
         let pool = database_pool_from_config(&config.database).await?;
 
+        let mut conn = pool.acquire().await?;
+        check_database_schema(&mut conn, self.allow_newer_schema, true).await?;
+        drop(conn);
+
+        // Expose the metrics on their own address, if asked to
+        if let Some(address) = self.prometheus_listen {
+            crate::telemetry::spawn_prometheus_listener(Some(address))?;
+        }
+
         let url_builder = UrlBuilder::new(
             config.http.public_base.clone(),
             config.http.issuer.clone(),
@@ -54,14 +79,33 @@ impl Options {
 
         let mailer = mailer_from_config(&config.email, &templates)?;
         mailer.test_connection().await?;
+        if mailer.memory_mailbox().is_some() {
+            warn!("Emails are kept in memory by this worker, they can only be seen on the development mailbox when the worker runs in the server process");
+        }
+
+        // The key store is used to sign the back-channel logout tokens
+        let key_store = config
+            .secrets
+            .key_store()
+            .await
+            .context("could not import keys from config")?;
+
+        let request_signer = config
+            .secrets
+            .request_signer()
+            .await
+            .context("could not import request signing keys from config")?;
 
         let http_client_factory = HttpClientFactory::new().await?;
-        let conn = SynapseConnection::new(
-            config.matrix.homeserver.clone(),
-            config.matrix.endpoint.clone(),
-            config.matrix.secret.clone(),
-            http_client_factory,
-        );
+        let conn = homeserver_connection_from_config(
+            &config.matrix,
+            &request_signer,
+            &http_client_factory,
+        )
+        .await?;
+
+        let settings =
+            tasks_settings_from_config(&config.tasks, &config.secrets, &config.rate_limiting);
 
         drop(config);
 
@@ -70,7 +114,20 @@ impl Options {
         let worker_name = Alphanumeric.sample_string(&mut rng, 10);
 
         info!(worker_name, "Starting task scheduler");
-        let monitor = mas_tasks::init(&worker_name, &pool, &mailer, conn).await?;
+        let monitor = mas_tasks::init(
+            &worker_name,
+            &pool,
+            &mailer,
+            conn,
+            settings,
+            &key_store,
+            &url_builder,
+            &http_client_factory,
+        )
+        .await?;
+
+        // Listen for SIGHUP, so that emails are rendered with up-to-date templates
+        register_sighup(&templates, None)?;
 
         span.exit();
 
@@ -18,16 +18,21 @@ use std::{io::IsTerminal, sync::Arc};
 
 use anyhow::Context;
 use clap::Parser;
-use mas_config::TelemetryConfig;
+use mas_config::{DatabaseConfig, TelemetryConfig};
+use mas_storage_pg::QueryTimingLayer;
 use sentry_tracing::EventFilter;
 use tracing_subscriber::{
     filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
 };
 
-use crate::sentry_transport::HyperTransportFactory;
+use crate::{redaction::Redactor, sentry_transport::HyperTransportFactory};
 
+mod access_log;
 mod app_state;
+mod build_info;
+mod client_certificate;
 mod commands;
+mod redaction;
 mod sentry_transport;
 mod server;
 mod telemetry;
@@ -56,9 +61,6 @@ async fn try_main() -> anyhow::Result<()> {
     let output = std::io::stderr();
     let with_ansi = output.is_terminal();
     let (log_writer, _guard) = tracing_appender::non_blocking(output);
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_writer(log_writer)
-        .with_ansi(with_ansi);
     let filter_layer = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new("info"))
         .context("could not setup logging filter")?;
@@ -71,6 +73,16 @@ async fn try_main() -> anyhow::Result<()> {
     // Falling back to default.
     let telemetry_config: TelemetryConfig = opts.load_config().unwrap_or_default();
 
+    // Same goes for the database config, which has the slow query threshold
+    let database_config: DatabaseConfig = opts.load_config().unwrap_or_default();
+
+    // Redact the sensitive fields everywhere spans and events end up
+    let redactor = Redactor::new(&telemetry_config.redaction.fields);
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .fmt_fields(redactor.fmt_fields())
+        .with_writer(log_writer)
+        .with_ansi(with_ansi);
+
     // Setup Sentry
     let sentry = sentry::init((
         telemetry_config.sentry.dsn.as_deref(),
@@ -81,6 +93,14 @@ async fn try_main() -> anyhow::Result<()> {
             traces_sample_rate: 1.0,
             auto_session_tracking: true,
             session_mode: sentry::SessionMode::Request,
+            before_send: Some(Arc::new({
+                let redactor = redactor.clone();
+                move |event| redactor.sentry_event(event)
+            })),
+            before_breadcrumb: Some(Arc::new({
+                let redactor = redactor.clone();
+                move |breadcrumb| redactor.sentry_breadcrumb(breadcrumb)
+            })),
             ..Default::default()
         },
     ));
@@ -109,9 +129,15 @@ async fn try_main() -> anyhow::Result<()> {
             .with_filter(LevelFilter::INFO)
     });
 
+    // Time the database queries, and warn about the slow ones
+    let query_timing_layer = QueryTimingLayer::new(database_config.slow_query_threshold);
+
     let subscriber = Registry::default()
         .with(sentry_layer)
         .with(telemetry_layer)
+        // This must come after the OpenTelemetry layer
+        .with(redactor.opentelemetry_layer())
+        .with(query_timing_layer)
         .with(filter_layer)
         .with(fmt_layer);
     subscriber
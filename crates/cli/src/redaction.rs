@@ -0,0 +1,355 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Redaction of sensitive fields, like passwords and tokens, in the logs and
+//! traces.
+//!
+//! The list of field names to redact is kept in a single [`Redactor`], which
+//! is then plugged in each of the places spans and events are sent to: the
+//! log output, the OpenTelemetry traces and the Sentry events.
+
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use opentelemetry::KeyValue;
+use sentry::protocol::{Breadcrumb, Context as SentryContext, Event, Map, Value};
+use tracing::{field::Field, span::Attributes, Id, Subscriber};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    field::{MakeVisitor, Visit, VisitFmt, VisitOutput},
+    fmt::format::{DefaultVisitor, Writer},
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+    Layer,
+};
+
+/// What redacted values are replaced with
+const REDACTED: &str = "[redacted]";
+
+/// The fields which are always redacted
+const DEFAULT_FIELDS: &[&str] = &[
+    "access_token",
+    "authorization",
+    "client_secret",
+    "code",
+    "code_verifier",
+    "cookie",
+    "current_password",
+    "id_token",
+    "new_password",
+    "password",
+    "refresh_token",
+    "secret",
+    "set_cookie",
+    "token",
+];
+
+/// Decides which span and event fields should be redacted
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    fields: Arc<HashSet<String>>,
+}
+
+impl Redactor {
+    /// Create a redactor for the built-in list of fields, plus the given ones
+    pub fn new(extra_fields: &[String]) -> Self {
+        let fields = DEFAULT_FIELDS
+            .iter()
+            .copied()
+            .chain(extra_fields.iter().map(String::as_str))
+            .map(str::to_ascii_lowercase)
+            .collect();
+
+        Self {
+            fields: Arc::new(fields),
+        }
+    }
+
+    /// Whether the field with the given name should be redacted.
+    ///
+    /// Only the last part of dotted names is looked at, so that
+    /// `user.password` is redacted like `password` is, but `access_token.id`
+    /// isn't.
+    pub fn is_redacted(&self, name: &str) -> bool {
+        let name = name.rsplit('.').next().unwrap_or(name);
+        self.fields.contains(&name.to_ascii_lowercase())
+    }
+
+    /// A formatter for the fields of the log output, see
+    /// [`tracing_subscriber::fmt::Layer::fmt_fields`]
+    pub fn fmt_fields(&self) -> RedactingFields {
+        RedactingFields {
+            redactor: self.clone(),
+        }
+    }
+
+    /// A layer which redacts the span attributes recorded by the OpenTelemetry
+    /// layer, before they are exported.
+    ///
+    /// It must be added *after* the OpenTelemetry layer, so that it runs
+    /// after it recorded the fields.
+    pub fn opentelemetry_layer(&self) -> RedactOpenTelemetryLayer {
+        RedactOpenTelemetryLayer {
+            redactor: self.clone(),
+        }
+    }
+
+    /// Redact the fields of an event before it is sent to Sentry, see
+    /// [`sentry::ClientOptions::before_send`]
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn sentry_event(&self, mut event: Event<'static>) -> Option<Event<'static>> {
+        self.redact_map(&mut event.extra);
+
+        // The tracing integration puts the fields of the event and of its spans
+        // in the contexts
+        for context in event.contexts.values_mut() {
+            if let SentryContext::Other(map) = context {
+                self.redact_map(map);
+            }
+        }
+
+        Some(event)
+    }
+
+    /// Redact the fields of a breadcrumb before it is sent to Sentry, see
+    /// [`sentry::ClientOptions::before_breadcrumb`]
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn sentry_breadcrumb(&self, mut breadcrumb: Breadcrumb) -> Option<Breadcrumb> {
+        self.redact_map(&mut breadcrumb.data);
+        Some(breadcrumb)
+    }
+
+    fn redact_map(&self, map: &mut Map<String, Value>) {
+        for (key, value) in map.iter_mut() {
+            if self.is_redacted(key) {
+                *value = Value::from(REDACTED);
+            }
+        }
+    }
+
+    fn redact_key_values(&self, key_values: &mut [KeyValue]) {
+        for key_value in key_values {
+            if self.is_redacted(key_value.key.as_str()) {
+                key_value.value = REDACTED.into();
+            }
+        }
+    }
+}
+
+/// Stands in for a redacted value when formatting fields
+struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Formats the fields like the default formatter does, except for the
+/// redacted ones
+#[derive(Debug, Clone)]
+pub struct RedactingFields {
+    redactor: Redactor,
+}
+
+impl<'a> MakeVisitor<Writer<'a>> for RedactingFields {
+    type Visitor = RedactingVisitor<DefaultVisitor<'a>>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        RedactingVisitor {
+            inner: DefaultVisitor::new(target, true),
+            redactor: self.redactor.clone(),
+        }
+    }
+}
+
+/// A field visitor which hides the value of the redacted fields from the
+/// wrapped visitor
+pub struct RedactingVisitor<V> {
+    inner: V,
+    redactor: Redactor,
+}
+
+impl<V: Visit> Visit for RedactingVisitor<V> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.redactor.is_redacted(field.name()) {
+            self.inner.record_debug(field, &Redacted);
+        } else {
+            self.inner.record_str(field, value);
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        if self.redactor.is_redacted(field.name()) {
+            self.inner.record_debug(field, &Redacted);
+        } else {
+            self.inner.record_error(field, value);
+        }
+    }
+
+    // The other methods default to calling this one
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.redactor.is_redacted(field.name()) {
+            self.inner.record_debug(field, &Redacted);
+        } else {
+            self.inner.record_debug(field, value);
+        }
+    }
+}
+
+impl<V: VisitOutput<fmt::Result>> VisitOutput<fmt::Result> for RedactingVisitor<V> {
+    fn finish(self) -> fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for RedactingVisitor<V> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.inner.writer()
+    }
+}
+
+/// Redacts the attributes the OpenTelemetry layer recorded on spans and span
+/// events, see [`Redactor::opentelemetry_layer`]
+#[derive(Debug, Clone)]
+pub struct RedactOpenTelemetryLayer {
+    redactor: Redactor,
+}
+
+impl RedactOpenTelemetryLayer {
+    fn redact<S>(&self, span: &SpanRef<'_, S>)
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+
+        if let Some(attributes) = &mut data.builder.attributes {
+            self.redactor.redact_key_values(attributes);
+        }
+
+        for event in data.builder.events.iter_mut().flatten() {
+            self.redactor.redact_key_values(&mut event.attributes);
+        }
+    }
+}
+
+impl<S> Layer<S> for RedactOpenTelemetryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.redact(&span);
+        }
+    }
+
+    fn on_record(&self, id: &Id, _values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.redact(&span);
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.event_span(event) {
+            self.redact(&span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_is_redacted() {
+        let redactor = Redactor::new(&["Session_Key".to_owned()]);
+        assert!(redactor.is_redacted("password"));
+        assert!(redactor.is_redacted("user.password"));
+        assert!(redactor.is_redacted("Access_Token"));
+        assert!(redactor.is_redacted("session_key"));
+        assert!(!redactor.is_redacted("access_token.id"));
+        assert!(!redactor.is_redacted("http.response.status_code"));
+        assert!(!redactor.is_redacted("user.id"));
+    }
+
+    #[test]
+    fn test_fmt_fields() {
+        let redactor = Redactor::new(&[]);
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .fmt_fields(redactor.fmt_fields())
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("login", user.id = 42, user.password = "hunter2");
+            let _entered = span.enter();
+            tracing::info!(access_token = "mct_secret", client.id = "abcd", "Logged in");
+        });
+
+        let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("Logged in"));
+        assert!(logs.contains("user.id=42"));
+        assert!(logs.contains("client.id=\"abcd\""));
+        assert!(logs.contains("user.password=[redacted]"));
+        assert!(logs.contains("access_token=[redacted]"));
+        assert!(!logs.contains("hunter2"));
+        assert!(!logs.contains("mct_secret"));
+    }
+
+    #[test]
+    fn test_sentry_event() {
+        let redactor = Redactor::new(&[]);
+        let mut event = Event::new();
+        event.extra.insert("password".to_owned(), "hunter2".into());
+        event.extra.insert("user.id".to_owned(), "abcd".into());
+        let mut fields = Map::new();
+        fields.insert("refresh_token".to_owned(), "mcr_secret".into());
+        event.contexts.insert(
+            "Rust Tracing Fields".to_owned(),
+            SentryContext::Other(fields),
+        );
+
+        let event = redactor.sentry_event(event).unwrap();
+        assert_eq!(event.extra["password"], REDACTED);
+        assert_eq!(event.extra["user.id"], "abcd");
+        let SentryContext::Other(fields) = &event.contexts["Rust Tracing Fields"] else {
+            panic!("unexpected context");
+        };
+        assert_eq!(fields["refresh_token"], REDACTED);
+    }
+}
@@ -16,6 +16,7 @@ use std::{
     future::ready,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs},
     os::unix::net::UnixListener,
+    sync::Arc,
 };
 
 use anyhow::Context;
@@ -23,6 +24,7 @@ use axum::{
     body::HttpBody,
     error_handling::HandleErrorLayer,
     extract::{FromRef, MatchedPath},
+    middleware::from_fn_with_state,
     Extension, Router,
 };
 use hyper::{
@@ -31,6 +33,7 @@ use hyper::{
 };
 use listenfd::ListenFd;
 use mas_config::{HttpBindConfig, HttpResource, HttpTlsConfig, UnixOrTcp};
+use mas_handlers::{CookieManager, SiteConfig};
 use mas_listener::{unix_or_tcp::UnixOrTcpListener, ConnectionInfo};
 use mas_router::Route;
 use mas_templates::Templates;
@@ -51,7 +54,11 @@ use tower_http::{services::ServeDir, set_header::SetResponseHeaderLayer};
 use tracing::{warn, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::app_state::AppState;
+use crate::{
+    access_log::AccessLog,
+    app_state::AppState,
+    client_certificate::{ClientCertificateExtractor, RequestClientCertificate},
+};
 
 const MAS_LISTENER_NAME: Key = Key::from_static_str("mas.listener.name");
 
@@ -177,6 +184,8 @@ pub fn build_router<B>(
     resources: &[HttpResource],
     prefix: Option<&str>,
     name: Option<&str>,
+    access_log: Option<&AccessLog>,
+    client_certificate: Option<&ClientCertificateExtractor>,
 ) -> Router<(), B>
 where
     B: HttpBody + Send + 'static,
@@ -184,6 +193,8 @@ where
     <B as HttpBody>::Error: std::error::Error + Send + Sync,
 {
     let templates = Templates::from_ref(&state);
+    let cookie_manager = CookieManager::from_ref(&state);
+    let maintenance = SiteConfig::from_ref(&state).maintenance;
     let mut router = Router::new();
 
     for resource in resources {
@@ -198,11 +209,34 @@ where
                 router.merge(mas_handlers::discovery_router::<AppState, B>())
             }
             mas_config::HttpResource::Human => {
-                router.merge(mas_handlers::human_router::<AppState, B>(templates.clone()))
+                let router = router.merge(mas_handlers::human_router::<AppState, B>(
+                    templates.clone(),
+                    cookie_manager.clone(),
+                    maintenance.clone(),
+                ));
+
+                #[cfg(feature = "upstream-oauth2")]
+                let router = router.merge(mas_handlers::upstream_oauth2_router::<AppState, B>(
+                    templates.clone(),
+                    cookie_manager.clone(),
+                    maintenance.clone(),
+                ));
+
+                if let Some(mailbox) = state.dev_mailbox.clone() {
+                    router.merge(mas_handlers::dev_mailbox_router::<AppState, B>(mailbox))
+                } else {
+                    router
+                }
             }
+            #[cfg(feature = "graphql")]
             mas_config::HttpResource::GraphQL { playground } => {
                 router.merge(mas_handlers::graphql_router::<AppState, B>(*playground))
             }
+            #[cfg(not(feature = "graphql"))]
+            mas_config::HttpResource::GraphQL { .. } => {
+                warn!("The GraphQL resource is configured, but this build doesn't include it");
+                router
+            }
             mas_config::HttpResource::Assets { path } => {
                 let static_service = ServeDir::new(path)
                     .append_index_html_on_directories(false)
@@ -224,10 +258,33 @@ where
                 )
             }
             mas_config::HttpResource::OAuth => {
-                router.merge(mas_handlers::api_router::<AppState, B>())
+                router.merge(mas_handlers::api_router::<AppState, B>(maintenance.clone()))
             }
+            #[cfg(feature = "compat")]
+            mas_config::HttpResource::Compat => router
+                .merge(mas_handlers::compat_router::<AppState, B>(
+                    maintenance.clone(),
+                )),
+            #[cfg(not(feature = "compat"))]
             mas_config::HttpResource::Compat => {
-                router.merge(mas_handlers::compat_router::<AppState, B>())
+                warn!("The compat resource is configured, but this build doesn't include it");
+                router
+            }
+            #[cfg(feature = "admin-api")]
+            mas_config::HttpResource::AdminApi => router
+                .merge(mas_handlers::admin_api_router::<AppState, B>(
+                    maintenance.clone(),
+                )),
+            #[cfg(not(feature = "admin-api"))]
+            mas_config::HttpResource::AdminApi => {
+                warn!("The admin API resource is configured, but this build doesn't include it");
+                router
+            }
+            mas_config::HttpResource::Version { detailed } => {
+                router.merge(mas_handlers::version_router::<AppState, B>(
+                    crate::build_info::build_info(),
+                    *detailed,
+                ))
             }
             // TODO: do a better handler here
             mas_config::HttpResource::ConnectionInfo => router.route(
@@ -252,6 +309,20 @@ where
 
     router = router.fallback(mas_handlers::fallback);
 
+    if let Some(access_log) = access_log {
+        router = router.layer(from_fn_with_state(
+            access_log.for_listener(name, prefix),
+            crate::access_log::middleware,
+        ));
+    }
+
+    if let Some(client_certificate) = client_certificate {
+        router = router.layer(from_fn_with_state(
+            client_certificate.clone(),
+            crate::client_certificate::middleware,
+        ));
+    }
+
     router
         .layer(
             InFlightCounterLayer::new("http.server.active_requests").on_request((
@@ -283,14 +354,22 @@ where
         .with_state(state)
 }
 
-pub fn build_tls_server_config(config: &HttpTlsConfig) -> Result<ServerConfig, anyhow::Error> {
+pub fn build_tls_server_config(
+    config: &HttpTlsConfig,
+    request_client_certificates: bool,
+) -> Result<ServerConfig, anyhow::Error> {
     let (key, chain) = config.load()?;
     let key = rustls::PrivateKey(key);
     let chain = chain.into_iter().map(rustls::Certificate).collect();
 
-    let mut config = rustls::ServerConfig::builder()
-        .with_safe_defaults()
-        .with_no_client_auth()
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = if request_client_certificates {
+        builder.with_client_cert_verifier(Arc::new(RequestClientCertificate))
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    let mut config = builder
         .with_single_cert(chain, key)
         .context("failed to build TLS server config")?;
     config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
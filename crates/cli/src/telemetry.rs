@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::time::Duration;
+use std::{net::SocketAddr, time::Duration};
 
 use anyhow::Context as _;
 use hyper::{header::CONTENT_TYPE, Body, Response};
@@ -20,8 +20,9 @@ use mas_config::{
     JaegerExporterProtocolConfig, MetricsExporterConfig, Propagator, TelemetryConfig,
     TracingExporterConfig,
 };
-use opentelemetry::{global, propagation::TextMapPropagator, trace::TracerProvider as _};
+use opentelemetry::{global, propagation::TextMapPropagator, trace::TracerProvider as _, KeyValue};
 use opentelemetry_jaeger::Propagator as JaegerPropagator;
+#[cfg(feature = "otlp")]
 use opentelemetry_otlp::MetricsExporterBuilder;
 use opentelemetry_prometheus::PrometheusExporter;
 use opentelemetry_sdk::{
@@ -42,6 +43,7 @@ use url::Url;
 
 static METER_PROVIDER: OnceCell<MeterProvider> = OnceCell::const_new();
 static PROMETHEUS_REGISTRY: OnceCell<Registry> = OnceCell::const_new();
+static PROMETHEUS_LISTENER: OnceCell<(Option<SocketAddr>, String)> = OnceCell::const_new();
 
 pub async fn setup(config: &TelemetryConfig) -> anyhow::Result<Option<Tracer>> {
     global::set_error_handler(|e| tracing::error!("{}", e))?;
@@ -102,6 +104,7 @@ fn stdout_tracer_provider() -> TracerProvider {
         .build()
 }
 
+#[cfg(feature = "otlp")]
 fn otlp_tracer(endpoint: Option<&Url>) -> anyhow::Result<Tracer> {
     use opentelemetry_otlp::WithExportConfig;
 
@@ -183,10 +186,15 @@ async fn tracer(config: &TracingExporterConfig) -> anyhow::Result<Option<Tracer>
     let tracer_provider = match config {
         TracingExporterConfig::None => return Ok(None),
         TracingExporterConfig::Stdout => stdout_tracer_provider(),
+        #[cfg(feature = "otlp")]
         TracingExporterConfig::Otlp { endpoint } => {
             // The OTLP exporter already creates a tracer and installs it
             return Ok(Some(otlp_tracer(endpoint.as_ref())?));
         }
+        #[cfg(not(feature = "otlp"))]
+        TracingExporterConfig::Otlp { .. } => {
+            anyhow::bail!("The OTLP exporter is not included in this build")
+        }
         TracingExporterConfig::Jaeger(JaegerExporterProtocolConfig::UdpThriftCompact {
             agent_host,
             agent_port,
@@ -216,6 +224,7 @@ async fn tracer(config: &TracingExporterConfig) -> anyhow::Result<Option<Tracer>
     Ok(Some(tracer))
 }
 
+#[cfg(feature = "otlp")]
 fn otlp_metric_reader(endpoint: Option<&url::Url>) -> anyhow::Result<PeriodicReader> {
     use opentelemetry_otlp::WithExportConfig;
 
@@ -275,6 +284,46 @@ pub fn prometheus_service<T>() -> tower::util::ServiceFn<fn(T) -> PromServiceFut
     tower::service_fn(prometheus_service_fn as _)
 }
 
+/// Serve the Prometheus metrics on a dedicated address: the given one if any,
+/// else the one set in the configuration
+///
+/// Only one process per host can bind a given address, so the configured
+/// address is only used by the server. Other commands have to be given their
+/// own address.
+pub fn spawn_prometheus_listener(address: Option<SocketAddr>) -> anyhow::Result<()> {
+    let Some((configured_address, path)) = PROMETHEUS_LISTENER.get() else {
+        if address.is_some() {
+            anyhow::bail!("Serving the Prometheus metrics requires the Prometheus exporter to be enabled in the config");
+        }
+        return Ok(());
+    };
+
+    let Some(address) = address.or(*configured_address) else {
+        return Ok(());
+    };
+
+    if !path.starts_with('/') {
+        anyhow::bail!("The path of the Prometheus exporter must start with a slash, got {path:?}");
+    }
+
+    let listener = std::net::TcpListener::bind(address)
+        .with_context(|| format!("could not bind the Prometheus exporter to {address}"))?;
+    let router = axum::Router::new().route_service(path, prometheus_service());
+    let server = hyper::Server::from_tcp(listener)?.serve(router.into_make_service());
+
+    tracing::info!(%address, %path, "Serving Prometheus metrics");
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            tracing::error!(
+                error = &e as &dyn std::error::Error,
+                "The Prometheus exporter listener failed"
+            );
+        }
+    });
+
+    Ok(())
+}
+
 fn prometheus_metric_reader() -> anyhow::Result<PrometheusExporter> {
     let registry = Registry::new();
     PROMETHEUS_REGISTRY.set(registry.clone())?;
@@ -292,10 +341,17 @@ fn init_meter(config: &MetricsExporterConfig) -> anyhow::Result<()> {
     let meter_provider_builder = match config {
         MetricsExporterConfig::None => meter_provider_builder.with_reader(ManualReader::default()),
         MetricsExporterConfig::Stdout => meter_provider_builder.with_reader(stdout_metric_reader()),
+        #[cfg(feature = "otlp")]
         MetricsExporterConfig::Otlp { endpoint } => {
             meter_provider_builder.with_reader(otlp_metric_reader(endpoint.as_ref())?)
         }
-        MetricsExporterConfig::Prometheus => {
+        #[cfg(not(feature = "otlp"))]
+        MetricsExporterConfig::Otlp { .. } => {
+            anyhow::bail!("The OTLP exporter is not included in this build")
+        }
+        MetricsExporterConfig::Prometheus { listen, path } => {
+            PROMETHEUS_LISTENER.set((*listen, path.clone()))?;
+
             meter_provider_builder.with_reader(prometheus_metric_reader()?)
         }
     };
@@ -315,10 +371,16 @@ fn trace_config() -> opentelemetry_sdk::trace::Config {
 }
 
 fn resource() -> Resource {
-    let resource = Resource::new(vec![
+    let build_info = crate::build_info::build_info();
+    let mut attributes = vec![
         semcov::resource::SERVICE_NAME.string(env!("CARGO_PKG_NAME")),
-        semcov::resource::SERVICE_VERSION.string(env!("CARGO_PKG_VERSION")),
-    ]);
+        semcov::resource::SERVICE_VERSION.string(build_info.version),
+        KeyValue::new("mas.build.features", build_info.features.join(",")),
+    ];
+    if let Some(git_sha) = build_info.git_sha {
+        attributes.push(KeyValue::new("mas.build.git_sha", git_sha));
+    }
+    let resource = Resource::new(attributes);
 
     let detected = Resource::from_detectors(
         Duration::from_secs(5),
@@ -15,20 +15,45 @@
 use std::time::Duration;
 
 use anyhow::Context;
+use hyper::{
+    header::{HeaderName, AUTHORIZATION},
+    HeaderMap, HeaderValue,
+};
 use mas_config::{
-    BrandingConfig, DatabaseConfig, DatabaseConnectConfig, EmailConfig, EmailSmtpMode,
-    EmailTransportConfig, PasswordsConfig, PolicyConfig, TemplatesConfig,
+    AccountConfig, BlobStorageConfig, BlobStorageS3EncryptionAlgorithm, BrandingConfig,
+    CaptchaConfig, CaptchaServiceKind, ClientsConfig, DatabaseConfig, DatabaseConnectConfig,
+    EmailConfig, EmailSenderKind, EmailSmtpMode, EmailTransportConfig, ExperimentalConfig,
+    MaintenanceConfig, MatrixConfig, PasswordsConfig, PolicyConfig, RateLimitingBackendConfig,
+    RateLimitingConfig, ScopesConfig, SecretsConfig, SmsConfig, SmsTransportConfig, StorageConfig,
+    TasksConfig, TemplatesConfig,
+};
+use mas_data_model::{
+    CaptchaService, EmailNormalization, RefreshTokenLifetimes, RefreshTokenPolicies,
+    RefreshTokenPolicy,
+};
+use mas_email::{EmailKind, MailTransport, Mailbox, Mailer};
+use mas_handlers::{
+    blob_storage::{BlobStorage, S3Bucket, S3ServerSideEncryption},
+    passwords::PasswordManager,
+    rate_limit::RateLimiter,
+    ActivityTracker, BreachedPasswordCheck, CustomScope, HttpClientFactory, MaintenanceMode,
 };
-use mas_email::{MailTransport, Mailer};
-use mas_handlers::{passwords::PasswordManager, ActivityTracker};
+use mas_keystore::RequestSigner;
+use mas_matrix::CircuitBreaker;
+use mas_matrix_synapse::SynapseConnection;
 use mas_policy::PolicyFactory;
 use mas_router::UrlBuilder;
+use mas_sms::{BlackholeTransport, HttpTransport, RateLimit, SmsSender, TwilioTransport};
+use mas_storage_pg::{check_schema_version, SchemaVersion};
+use mas_tasks::{BatchingSettings, KeyExpirySettings, TasksSettings};
 use mas_templates::{SiteBranding, TemplateLoadingError, Templates};
+use oauth2_types::scope::ScopeToken;
+use opentelemetry::metrics::Unit;
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     ConnectOptions, PgConnection, PgPool,
 };
-use tracing::{error, info, log::LevelFilter};
+use tracing::{error, info, log::LevelFilter, warn};
 
 pub async fn password_manager_from_config(
     config: &PasswordsConfig,
@@ -55,6 +80,18 @@ pub async fn password_manager_from_config(
     PasswordManager::new(schemes)
 }
 
+pub fn breached_password_check_from_config(
+    config: &PasswordsConfig,
+) -> Option<BreachedPasswordCheck> {
+    let config = config.breached_passwords()?;
+    let action = match config.action {
+        mas_config::BreachedPasswordAction::Reject => mas_handlers::BreachedPasswordAction::Reject,
+        mas_config::BreachedPasswordAction::Warn => mas_handlers::BreachedPasswordAction::Warn,
+    };
+
+    Some(BreachedPasswordCheck::new(config.endpoint.clone(), action))
+}
+
 pub fn mailer_from_config(
     config: &EmailConfig,
     templates: &Templates,
@@ -63,6 +100,7 @@ pub fn mailer_from_config(
     let reply_to = config.reply_to.parse()?;
     let transport = match &config.transport {
         EmailTransportConfig::Blackhole => MailTransport::blackhole(),
+        EmailTransportConfig::Memory => MailTransport::memory(),
         EmailTransportConfig::Smtp {
             mode,
             hostname,
@@ -87,31 +125,484 @@ pub fn mailer_from_config(
         EmailTransportConfig::AwsSes => anyhow::bail!("AWS SESv2 backend has been removed"),
     };
 
-    Ok(Mailer::new(templates.clone(), transport, from, reply_to))
+    let mut mailer = Mailer::new(templates.clone(), transport, from, reply_to);
+
+    for (sender_kind, sender) in config.senders.iter() {
+        let kind = match sender_kind {
+            EmailSenderKind::Verification => EmailKind::Verification,
+            EmailSenderKind::Registration => EmailKind::Registration,
+            EmailSenderKind::PasswordReset => EmailKind::PasswordReset,
+            EmailSenderKind::LoginLink => EmailKind::LoginLink,
+            EmailSenderKind::DataExport => EmailKind::DataExport,
+            EmailSenderKind::CompatPasswordDeprecation => EmailKind::CompatPasswordDeprecation,
+        };
+
+        let from = sender.from.as_ref().unwrap_or(&config.from);
+        let reply_to = sender.reply_to.as_ref().unwrap_or(&config.reply_to);
+        let from = from
+            .parse()
+            .with_context(|| format!("invalid From address for {sender_kind} emails"))?;
+        let reply_to = reply_to
+            .parse()
+            .with_context(|| format!("invalid Reply-To address for {sender_kind} emails"))?;
+        mailer = mailer.with_sender(kind, from, reply_to);
+    }
+
+    Ok(mailer)
+}
+
+/// Parse a sender address, returning its domain
+fn check_sender_address(warnings: &mut Vec<String>, what: &str, address: &str) -> Option<String> {
+    let mailbox: Mailbox = match address.parse() {
+        Ok(mailbox) => mailbox,
+        Err(e) => {
+            warnings.push(format!("The {what} address {address:?} is invalid: {e}"));
+            return None;
+        }
+    };
+
+    let domain = mailbox.email.domain().to_ascii_lowercase();
+    if domain == "localhost" || !domain.contains('.') {
+        warnings.push(format!(
+            "The {what} address uses the domain {domain:?}, which can't have SPF or DMARC records: emails will likely be rejected or flagged as spam"
+        ));
+    }
+
+    Some(domain)
+}
+
+/// Look for sender addresses which are likely to get emails flagged as spam,
+/// because they can't pass the SPF and DMARC checks of the recipients.
+///
+/// This only looks at the configuration: the DNS records of the domains are
+/// not checked.
+pub fn email_sender_warnings(config: &EmailConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if matches!(
+        config.transport,
+        EmailTransportConfig::Blackhole | EmailTransportConfig::Memory
+    ) {
+        return warnings;
+    }
+
+    // The domain the relay authenticates as, if the SMTP username is an address
+    let relay_domain = match &config.transport {
+        EmailTransportConfig::Smtp {
+            credentials: Some(credentials),
+            ..
+        } => credentials
+            .username
+            .rsplit_once('@')
+            .map(|(_, domain)| domain.to_ascii_lowercase()),
+        _ => None,
+    };
+
+    let from_domain = check_sender_address(&mut warnings, "From", &config.from);
+    check_sender_address(&mut warnings, "Reply-To", &config.reply_to);
+
+    if let (Some(from_domain), Some(relay_domain)) = (&from_domain, &relay_domain) {
+        if from_domain != relay_domain {
+            warnings.push(format!(
+                "The From address uses the domain {from_domain:?}, but the SMTP relay authenticates as {relay_domain:?}: make sure the SPF and DKIM records of {from_domain:?} allow the relay to send emails on its behalf"
+            ));
+        }
+    }
+
+    for (name, sender) in config.senders.iter() {
+        if let Some(reply_to) = &sender.reply_to {
+            check_sender_address(
+                &mut warnings,
+                &format!("Reply-To of {name} emails"),
+                reply_to,
+            );
+        }
+
+        let Some(from) = &sender.from else { continue };
+        let Some(domain) =
+            check_sender_address(&mut warnings, &format!("From of {name} emails"), from)
+        else {
+            continue;
+        };
+
+        // Emails of a given kind sent from another domain need that domain to be
+        // aligned as well
+        let aligned_with = relay_domain.as_ref().or(from_domain.as_ref());
+        if aligned_with.is_some_and(|aligned_with| *aligned_with != domain) {
+            warnings.push(format!(
+                "The From address of {name} emails uses the domain {domain:?}, unlike the other emails: make sure its SPF and DKIM records allow the same relay to send emails on its behalf, or DMARC checks will fail"
+            ));
+        }
+    }
+
+    warnings
+}
+
+pub fn sms_sender_from_config(
+    config: &SmsConfig,
+    templates: &Templates,
+    http_client_factory: &HttpClientFactory,
+) -> Result<SmsSender, anyhow::Error> {
+    let rate_limit = RateLimit::new(
+        config.rate_limit.burst,
+        config
+            .rate_limit
+            .replenish_interval
+            .to_std()
+            .context("invalid SMS rate limit interval")?,
+    );
+
+    let sender = match &config.transport {
+        SmsTransportConfig::Blackhole => {
+            SmsSender::new(templates.clone(), BlackholeTransport, rate_limit)
+        }
+        SmsTransportConfig::Twilio {
+            account_sid,
+            auth_token,
+            from,
+        } => SmsSender::new(
+            templates.clone(),
+            TwilioTransport::new(
+                http_client_factory.clone(),
+                account_sid.clone(),
+                auth_token.clone(),
+                from.clone(),
+            ),
+            rate_limit,
+        ),
+        SmsTransportConfig::Http { url, token } => SmsSender::new(
+            templates.clone(),
+            HttpTransport::new(http_client_factory.clone(), url.clone(), token.clone()),
+            rate_limit,
+        ),
+    };
+
+    Ok(sender)
+}
+
+pub fn blob_storage_from_config(
+    config: &StorageConfig,
+    http_client_factory: &HttpClientFactory,
+) -> Option<BlobStorage> {
+    let storage = match config.blobs.as_ref()? {
+        BlobStorageConfig::Filesystem { path } => BlobStorage::filesystem(path.clone()),
+        BlobStorageConfig::S3 {
+            endpoint,
+            region,
+            bucket,
+            access_key_id,
+            secret_access_key,
+            force_path_style,
+            prefix,
+            server_side_encryption,
+        } => {
+            let mut bucket = S3Bucket::new(
+                http_client_factory.http_service("blob_storage.s3"),
+                endpoint.clone(),
+                region,
+                bucket,
+                access_key_id,
+                secret_access_key,
+            )
+            .with_path_style(*force_path_style);
+
+            if let Some(prefix) = prefix {
+                bucket = bucket.with_prefix(prefix);
+            }
+
+            if let Some(encryption) = server_side_encryption {
+                let encryption = match encryption.algorithm {
+                    BlobStorageS3EncryptionAlgorithm::Aes256 => S3ServerSideEncryption::Aes256,
+                    BlobStorageS3EncryptionAlgorithm::AwsKms => S3ServerSideEncryption::AwsKms {
+                        key_id: encryption.kms_key_id.clone(),
+                    },
+                };
+                bucket = bucket.with_server_side_encryption(encryption);
+            }
+
+            BlobStorage::s3(bucket)
+        }
+    };
+
+    Some(storage)
+}
+
+pub async fn rate_limiter_from_config(
+    config: &RateLimitingConfig,
+    pool: &PgPool,
+) -> Result<RateLimiter, anyhow::Error> {
+    let rate_limiter = match &config.backend {
+        RateLimitingBackendConfig::Memory => RateLimiter::memory(),
+        RateLimitingBackendConfig::Postgres => RateLimiter::postgres(pool.clone()),
+        RateLimitingBackendConfig::Redis { url } => RateLimiter::redis(url.as_str())
+            .await
+            .context("failed to connect to the Redis server")?,
+    };
+
+    Ok(rate_limiter)
+}
+
+/// Build the maintenance mode switch from the configuration
+///
+/// If a flag file is configured, a background task polls it and toggles the
+/// maintenance mode depending on whether it exists.
+pub async fn homeserver_connection_from_config(
+    config: &MatrixConfig,
+    request_signer: &RequestSigner,
+    http_client_factory: &HttpClientFactory,
+) -> Result<SynapseConnection, anyhow::Error> {
+    let mut headers = HeaderMap::with_capacity(config.headers.len());
+    for header in &config.headers {
+        let name = HeaderName::try_from(header.name.as_str())
+            .with_context(|| format!("invalid homeserver header name {:?}", header.name))?;
+        if name == AUTHORIZATION {
+            anyhow::bail!("the Authorization header to the homeserver can't be overridden");
+        }
+
+        let value = header.load_value().await?;
+        let mut value = HeaderValue::try_from(value)
+            .with_context(|| format!("invalid value for the homeserver header {name}"))?;
+        // Those headers are often used to carry credentials
+        value.set_sensitive(true);
+        headers.append(name, value);
+    }
+
+    let circuit_breaker = CircuitBreaker::new(
+        config.circuit_breaker.failure_threshold,
+        config.circuit_breaker.reset_timeout,
+    );
+
+    // Expose whether calls to the homeserver are currently short-circuited
+    let meter = opentelemetry::global::meter_with_version(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None,
+        None,
+    );
+    let gauge = meter
+        .i64_observable_gauge("mas.homeserver.circuit_breaker.open")
+        .with_description("Whether calls to the homeserver are currently not attempted")
+        .with_unit(Unit::new("{open}"))
+        .init();
+    let breaker = circuit_breaker.clone();
+    let res = meter.register_callback(&[gauge.as_any()], move |observer| {
+        observer.observe_i64(&gauge, i64::from(breaker.is_open()), &[]);
+    });
+    if let Err(e) = res {
+        warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to register the homeserver circuit breaker metric"
+        );
+    }
+
+    Ok(SynapseConnection::new(
+        config.homeserver.clone(),
+        config.endpoint.clone(),
+        config.secret.clone(),
+        http_client_factory.clone(),
+    )
+    .with_request_timeout(config.request_timeout)
+    .with_retries(config.max_retries, config.retry_backoff)
+    .with_circuit_breaker(circuit_breaker)
+    .with_request_signer(request_signer.clone())
+    .with_headers(headers))
+}
+
+pub fn maintenance_mode_from_config(config: &MaintenanceConfig) -> MaintenanceMode {
+    let mode = MaintenanceMode::new(config.enabled, config.retry_after, config.message.clone());
+
+    if let Some(flag_file) = config.flag_file.clone() {
+        let always_enabled = config.enabled;
+        let mode = mode.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+
+                let exists = tokio::fs::try_exists(&flag_file)
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!(?err, %flag_file, "Could not check the maintenance flag file");
+                        false
+                    });
+
+                let enabled = always_enabled || exists;
+                if enabled != mode.is_enabled() {
+                    if enabled {
+                        warn!(%flag_file, "Entering maintenance mode");
+                    } else {
+                        info!(%flag_file, "Leaving maintenance mode");
+                    }
+                    mode.set_enabled(enabled);
+                }
+            }
+        });
+    }
+
+    mode
+}
+
+pub fn email_normalization_from_config(config: &AccountConfig) -> EmailNormalization {
+    EmailNormalization {
+        lowercase: config.email_normalization.lowercase,
+        gmail_folding: config.email_normalization.gmail_folding,
+    }
+}
+
+pub fn captcha_config_from_config(
+    config: &CaptchaConfig,
+) -> Result<Option<mas_data_model::CaptchaConfig>, anyhow::Error> {
+    let Some(service) = config.service else {
+        return Ok(None);
+    };
+
+    if !cfg!(feature = "captcha") {
+        anyhow::bail!("A CAPTCHA service is configured, but this build doesn't include it");
+    }
+
+    let service = match service {
+        CaptchaServiceKind::RecaptchaV2 => CaptchaService::RecaptchaV2,
+        CaptchaServiceKind::CloudflareTurnstile => CaptchaService::CloudflareTurnstile,
+        CaptchaServiceKind::HCaptcha => CaptchaService::HCaptcha,
+    };
+
+    let site_key = config
+        .site_key
+        .clone()
+        .context("a CAPTCHA service is configured but no site key is set")?;
+    let secret_key = config
+        .secret_key
+        .clone()
+        .context("a CAPTCHA service is configured but no secret key is set")?;
+
+    Ok(Some(mas_data_model::CaptchaConfig {
+        service,
+        site_key,
+        secret_key,
+    }))
+}
+
+pub fn tasks_settings_from_config(
+    config: &TasksConfig,
+    secrets: &SecretsConfig,
+    rate_limiting: &RateLimitingConfig,
+) -> TasksSettings {
+    let key_expirations = secrets.key_expirations();
+    let key_expiry = (!key_expirations.is_empty()).then(|| KeyExpirySettings {
+        expirations: key_expirations,
+        warning_period: config.key_expiry.warning_period,
+    });
+
+    TasksSettings {
+        stale_clients_inactivity: config
+            .stale_clients
+            .enabled
+            .then_some(config.stale_clients.inactivity_period),
+        key_expiry,
+        deleted_users_grace_period: Some(config.deleted_users.grace_period),
+        login_failures_retention: rate_limiting
+            .login_lockout
+            .map(|lockout| lockout.max_duration),
+        batching: BatchingSettings {
+            min_batch_size: config.cleanup.min_batch_size.get(),
+            max_batch_size: config.cleanup.max_batch_size.get(),
+            target_batch_duration: config.cleanup.target_batch_duration,
+            max_pause: config.cleanup.max_pause,
+        },
+    }
+}
+
+pub fn custom_scopes_from_config(config: &ScopesConfig) -> Result<Vec<CustomScope>, anyhow::Error> {
+    config
+        .iter()
+        .map(|scope| {
+            let token = scope
+                .name
+                .parse::<ScopeToken>()
+                .with_context(|| format!("invalid custom scope {:?}", scope.name))?;
+
+            Ok(CustomScope {
+                token,
+                description: scope.description.clone(),
+            })
+        })
+        .collect()
+}
+
+pub fn refresh_token_policies_from_config(
+    experimental: &ExperimentalConfig,
+    clients: &ClientsConfig,
+) -> RefreshTokenPolicies {
+    let default = RefreshTokenPolicy {
+        rotation: experimental.refresh_token_rotation,
+        lifetimes: RefreshTokenLifetimes {
+            inactivity: experimental.refresh_token_inactivity_ttl,
+            absolute: experimental.refresh_token_absolute_ttl,
+        },
+        revoke_session_on_reuse: experimental.revoke_session_on_refresh_token_reuse,
+        reuse_grace_period: experimental.refresh_token_reuse_grace_period,
+    };
+
+    let clients = clients
+        .iter()
+        .filter_map(|client| {
+            let overrides = client.refresh_tokens.as_ref()?;
+            let policy = RefreshTokenPolicy {
+                rotation: overrides.rotation.unwrap_or(default.rotation),
+                lifetimes: RefreshTokenLifetimes {
+                    inactivity: overrides.inactivity_ttl.or(default.lifetimes.inactivity),
+                    absolute: overrides.absolute_ttl.or(default.lifetimes.absolute),
+                },
+                revoke_session_on_reuse: overrides
+                    .revoke_session_on_reuse
+                    .unwrap_or(default.revoke_session_on_reuse),
+                reuse_grace_period: overrides.reuse_grace_period.or(default.reuse_grace_period),
+            };
+            Some((client.client_id, policy))
+        })
+        .collect();
+
+    RefreshTokenPolicies {
+        default,
+        clients: std::sync::Arc::new(clients),
+    }
 }
 
 pub async fn policy_factory_from_config(
     config: &PolicyConfig,
+    scopes: &ScopesConfig,
 ) -> Result<PolicyFactory, anyhow::Error> {
     let policy_file = tokio::fs::File::open(&config.wasm_module)
         .await
         .context("failed to open OPA WASM policy file")?;
 
+    // Expose the custom scopes to the policy, next to the arbitrary data from the
+    // config
+    let mut data = config.data.clone().unwrap_or_default();
+    if !scopes.is_empty() {
+        if data.is_null() {
+            data = serde_json::Value::Object(serde_json::Map::new());
+        }
+
+        let serde_json::Value::Object(map) = &mut data else {
+            anyhow::bail!("policy data must be an object to declare custom scopes");
+        };
+
+        map.insert("custom_scopes".to_owned(), serde_json::to_value(scopes)?);
+    }
+
     let entrypoints = mas_policy::Entrypoints {
         register: config.register_entrypoint.clone(),
         client_registration: config.client_registration_entrypoint.clone(),
         authorization_grant: config.authorization_grant_entrypoint.clone(),
         email: config.email_entrypoint.clone(),
+        compat_login: config.compat_login_entrypoint.clone(),
         password: config.password_entrypoint.clone(),
     };
 
-    PolicyFactory::load(
-        policy_file,
-        config.data.clone().unwrap_or_default(),
-        entrypoints,
-    )
-    .await
-    .context("failed to load the policy")
+    PolicyFactory::load(policy_file, data, entrypoints)
+        .await
+        .context("failed to load the policy")
 }
 
 pub async fn templates_from_config(
@@ -196,7 +687,7 @@ fn database_connect_options_from_config(
 
     let options = options
         .log_statements(LevelFilter::Debug)
-        .log_slow_statements(LevelFilter::Warn, Duration::from_millis(100));
+        .log_slow_statements(LevelFilter::Warn, config.slow_query_threshold);
 
     Ok(options)
 }
@@ -227,16 +718,75 @@ pub async fn database_connection_from_config(
         .context("could not connect to the database")
 }
 
+/// Check that the database schema is not newer than what this binary supports
+///
+/// Running against a schema migrated by a newer version of the service, for
+/// example after a botched rollback, could silently corrupt data, so this
+/// refuses to continue unless `allow_newer_schema` is set.
+///
+/// Pending migrations only trigger a warning, unless `allow_pending` is unset,
+/// for deployments where migrations are applied separately.
+///
+/// # Errors
+///
+/// Returns an error if the schema is newer than what this binary supports, if
+/// migrations are pending and `allow_pending` is unset, or if the database
+/// could not be queried
+pub async fn check_database_schema(
+    conn: &mut PgConnection,
+    allow_newer_schema: bool,
+    allow_pending: bool,
+) -> Result<(), anyhow::Error> {
+    let version = check_schema_version(conn)
+        .await
+        .context("could not check the database schema version")?;
+
+    match version {
+        SchemaVersion::UpToDate => {}
+        SchemaVersion::Pending { count } if allow_pending => {
+            warn!(count, "The database has pending migrations");
+        }
+        SchemaVersion::Pending { count } => {
+            anyhow::bail!(
+                "The database has {count} pending migration(s). \
+                 Apply them with `mas-cli database migrate` before starting the service."
+            );
+        }
+        SchemaVersion::Newer {
+            latest_known,
+            latest_applied,
+        } if allow_newer_schema => {
+            warn!(
+                latest_known,
+                latest_applied,
+                "The database schema is newer than what this version supports, continuing anyway"
+            );
+        }
+        SchemaVersion::Newer {
+            latest_known,
+            latest_applied,
+        } => {
+            anyhow::bail!(
+                "The database schema (version {latest_applied}) is newer than what this version supports (version {latest_known}). \
+                 This usually means the service was rolled back after a newer version migrated the database. \
+                 Upgrade the service, or pass --allow-newer-schema to run anyway at the risk of corrupting data."
+            );
+        }
+    }
+
+    Ok(())
+}
+
 /// Reload templates on SIGHUP
 pub fn register_sighup(
     templates: &Templates,
-    activity_tracker: &ActivityTracker,
+    activity_tracker: Option<&ActivityTracker>,
 ) -> anyhow::Result<()> {
     #[cfg(unix)]
     {
         let mut signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
         let templates = templates.clone();
-        let activity_tracker = activity_tracker.clone();
+        let activity_tracker = activity_tracker.cloned();
 
         tokio::spawn(async move {
             loop {
@@ -245,9 +795,13 @@ pub fn register_sighup(
                     break;
                 };
 
-                info!("SIGHUP received, reloading templates & flushing activity tracker");
+                if let Some(activity_tracker) = &activity_tracker {
+                    info!("SIGHUP received, reloading templates & flushing activity tracker");
+                    activity_tracker.flush().await;
+                } else {
+                    info!("SIGHUP received, reloading templates");
+                }
 
-                activity_tracker.flush().await;
                 templates.clone().reload().await.unwrap_or_else(|err| {
                     error!(?err, "Error while reloading templates");
                 });
@@ -329,4 +883,112 @@ mod tests {
         let manager = password_manager_from_config(&config).await;
         assert!(manager.is_err());
     }
+
+    #[test]
+    fn test_email_sender_warnings() {
+        // Emails sent nowhere don't need to be delivered
+        let config: EmailConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(email_sender_warnings(&config).is_empty());
+
+        let config: EmailConfig = serde_json::from_value(serde_json::json!({
+            "transport": "smtp",
+            "mode": "tls",
+            "hostname": "smtp.example.com",
+            "username": "mas@example.com",
+            "password": "hunter2",
+            "from": "\"Example\" <noreply@example.com>",
+            "reply_to": "support@example.com",
+        }))
+        .unwrap();
+        assert!(email_sender_warnings(&config).is_empty());
+
+        let config: EmailConfig = serde_json::from_value(serde_json::json!({
+            "transport": "smtp",
+            "mode": "tls",
+            "hostname": "smtp.example.com",
+            "username": "mas@example.com",
+            "password": "hunter2",
+            "from": "\"Example\" <noreply@example.com>",
+            "reply_to": "support@localhost",
+            "senders": {
+                "password_reset": {
+                    "from": "\"Example security\" <security@example.com>",
+                },
+                "data_export": {
+                    "from": "exports@example.org",
+                },
+            },
+        }))
+        .unwrap();
+        let warnings = email_sender_warnings(&config);
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("Reply-To address uses the domain \"localhost\""));
+        assert!(warnings[1].contains("data_export emails uses the domain \"example.org\""));
+
+        // The relay authenticating as another domain than the From address
+        let config: EmailConfig = serde_json::from_value(serde_json::json!({
+            "transport": "smtp",
+            "mode": "tls",
+            "hostname": "smtp.example.com",
+            "username": "mas@mail.example.net",
+            "password": "hunter2",
+            "from": "noreply@example.com",
+            "reply_to": "noreply@example.com",
+        }))
+        .unwrap();
+        let warnings = email_sender_warnings(&config);
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].contains("authenticates as \"mail.example.net\""));
+    }
+
+    #[test]
+    fn test_refresh_token_policies_from_config() {
+        let experimental: ExperimentalConfig = serde_json::from_value(serde_json::json!({
+            "refresh_token_inactivity_ttl": 3600,
+            "revoke_session_on_refresh_token_reuse": true,
+            "refresh_token_reuse_grace_period": 30,
+        }))
+        .unwrap();
+        let clients: ClientsConfig = serde_json::from_value(serde_json::json!([{
+            "client_id": "01H3Z6S4NN0P6XMDF0E4FHVRFA",
+            "client_auth_method": "none",
+        }, {
+            "client_id": "01H3Z6S4NN0P6XMDF0E4FHVRFB",
+            "client_auth_method": "none",
+            "refresh_tokens": {
+                "rotation": false,
+                "absolute_ttl": 86400,
+                "reuse_grace_period": 10,
+            },
+        }]))
+        .unwrap();
+
+        let policies = refresh_token_policies_from_config(&experimental, &clients);
+        assert!(policies.default.rotation);
+        assert!(policies.default.revoke_session_on_reuse);
+        assert_eq!(
+            policies.default.lifetimes.inactivity,
+            Some(chrono::Duration::hours(1))
+        );
+        assert_eq!(policies.default.lifetimes.absolute, None);
+        assert_eq!(
+            policies.default.reuse_grace_period,
+            Some(chrono::Duration::seconds(30))
+        );
+
+        // Only clients with overrides are listed
+        assert_eq!(policies.clients.len(), 1);
+        let policy = policies.for_client("01H3Z6S4NN0P6XMDF0E4FHVRFB".parse().unwrap());
+        assert!(!policy.rotation);
+        assert!(policy.revoke_session_on_reuse);
+        assert_eq!(
+            policy.lifetimes.inactivity,
+            Some(chrono::Duration::hours(1))
+        );
+        assert_eq!(policy.lifetimes.absolute, Some(chrono::Duration::days(1)));
+        assert_eq!(
+            policy.reuse_grace_period,
+            Some(chrono::Duration::seconds(10))
+        );
+    }
 }
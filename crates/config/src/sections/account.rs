@@ -0,0 +1,178 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::ConfigurationSection;
+
+fn default_true() -> bool {
+    true
+}
+
+/// How email addresses are normalized before being stored and compared
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EmailNormalizationConfig {
+    /// Whether the local part of email addresses should be lowercased. The
+    /// domain is always lowercased.
+    #[serde(default = "default_true")]
+    pub lowercase: bool,
+
+    /// Whether the dots and the `+` suffix should be removed from the local
+    /// part of Gmail addresses.
+    ///
+    /// Gmail ignores them when delivering emails, so without this, the same
+    /// mailbox could be used for multiple accounts.
+    #[serde(default)]
+    pub gmail_folding: bool,
+}
+
+impl Default for EmailNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            gmail_folding: false,
+        }
+    }
+}
+
+/// The terms of service users have to accept
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TermsConfig {
+    /// Version of the terms of service.
+    ///
+    /// Changing it makes every user accept the terms again on their next
+    /// login.
+    pub version: String,
+
+    /// URL where the terms of service can be read
+    pub url: Url,
+}
+
+/// Configuration related to the user accounts
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AccountConfig {
+    /// Whether the email address should be verified before the account gets
+    /// created during password-based registration.
+    ///
+    /// When enabled, the registration form first asks for an email address
+    /// and sends a verification code to it. The username and password are
+    /// only asked for once the code was entered, so that no account exists
+    /// with an email address the user doesn't own.
+    #[serde(default)]
+    pub verify_email_before_registration: bool,
+
+    /// URL prefixes to which users can be sent back after logging in or out,
+    /// through the `next` parameter of the login and logout pages.
+    ///
+    /// A URL is allowed if it has the same scheme, host and port as one of
+    /// the prefixes, and its path starts with the path of that prefix.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_next_urls: Vec<Url>,
+
+    /// How email addresses are normalized before being stored and compared.
+    ///
+    /// A verified email address can only belong to one user, once normalized.
+    #[serde(default)]
+    pub email_normalization: EmailNormalizationConfig,
+
+    /// Whether users can sign in with a single-use link sent to one of their
+    /// verified email addresses.
+    ///
+    /// When enabled, the login page offers to send such a link. It expires
+    /// after 15 minutes.
+    #[serde(default)]
+    pub email_login_links: bool,
+
+    /// Terms of service users have to accept to register and to log in.
+    ///
+    /// Acceptances are recorded per version: when the version changes, users
+    /// are asked to accept the new terms before finishing their next login.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terms: Option<TermsConfig>,
+
+    /// Origins of the clients allowed to embed the account management pages
+    /// in a frame, by loading them with `?embedded=true`.
+    ///
+    /// Embedded pages are rendered without the navigation and footer, and
+    /// notify the embedding page through `postMessage` when the user
+    /// completes an action. Without any origin, the pages can't be embedded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedding_origins: Vec<Url>,
+}
+
+#[async_trait]
+impl ConfigurationSection for AccountConfig {
+    fn path() -> &'static str {
+        "account"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  account:
+                    verify_email_before_registration: true
+                    allowed_next_urls:
+                      - https://app.example.com/
+                    email_normalization:
+                      gmail_folding: true
+                    email_login_links: true
+                    terms:
+                      version: "2023-12"
+                      url: https://example.com/terms
+                "#,
+            )?;
+
+            let config = AccountConfig::load_from_file("config.yaml")?;
+
+            assert!(config.verify_email_before_registration);
+            assert_eq!(
+                config.allowed_next_urls,
+                vec![Url::parse("https://app.example.com/").unwrap()]
+            );
+            assert!(config.email_normalization.lowercase);
+            assert!(config.email_normalization.gmail_folding);
+            assert!(config.email_login_links);
+            let terms = config.terms.unwrap();
+            assert_eq!(terms.version, "2023-12");
+            assert_eq!(terms.url, Url::parse("https://example.com/terms").unwrap());
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,91 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ConfigurationSection;
+
+const fn default_max_size() -> usize {
+    1024 * 1024
+}
+
+/// Configuration section for avatars uploaded by users
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AvatarsConfig {
+    /// Whether users can upload an avatar. Uploaded avatars are saved in the
+    /// blob storage configured in the `storage` section.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Maximum size of an uploaded avatar, in bytes
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+impl Default for AvatarsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size: default_max_size(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for AvatarsConfig {
+    fn path() -> &'static str {
+        "avatars"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  avatars:
+                    enabled: true
+                "#,
+            )?;
+
+            let config = AvatarsConfig::load_from_file("config.yaml")?;
+
+            assert!(config.enabled);
+            assert_eq!(config.max_size, 1024 * 1024);
+
+            Ok(())
+        });
+    }
+}
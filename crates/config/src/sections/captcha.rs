@@ -0,0 +1,108 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ConfigurationSection;
+
+/// Which service should be used for CAPTCHA protection
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub enum CaptchaServiceKind {
+    /// Use Google's reCAPTCHA v2 API
+    #[serde(rename = "recaptcha_v2")]
+    RecaptchaV2,
+
+    /// Use Cloudflare Turnstile
+    #[serde(rename = "cloudflare_turnstile")]
+    CloudflareTurnstile,
+
+    /// Use hCaptcha
+    #[serde(rename = "hcaptcha")]
+    HCaptcha,
+}
+
+/// Configuration section to protect the registration and login forms with
+/// CAPTCHA challenges
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CaptchaConfig {
+    /// Which service should be used for CAPTCHA protection. The forms are not
+    /// protected if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<CaptchaServiceKind>,
+
+    /// The site key given by the CAPTCHA service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site_key: Option<String>,
+
+    /// The secret key given by the CAPTCHA service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_key: Option<String>,
+}
+
+#[async_trait]
+impl ConfigurationSection for CaptchaConfig {
+    fn path() -> &'static str {
+        "captcha"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  captcha:
+                    service: hcaptcha
+                    site_key: 10000000-ffff-ffff-ffff-000000000001
+                    secret_key: 0x0000000000000000000000000000000000000000
+                "#,
+            )?;
+
+            let config = CaptchaConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.service, Some(CaptchaServiceKind::HCaptcha));
+            assert_eq!(
+                config.site_key.as_deref(),
+                Some("10000000-ffff-ffff-ffff-000000000001")
+            );
+            assert_eq!(
+                config.secret_key.as_deref(),
+                Some("0x0000000000000000000000000000000000000000")
+            );
+
+            Ok(())
+        });
+    }
+}
@@ -15,12 +15,13 @@
 use std::ops::{Deref, DerefMut};
 
 use async_trait::async_trait;
+use chrono::Duration;
 use mas_iana::oauth::OAuthClientAuthenticationMethod;
 use mas_jose::jwk::PublicJsonWebKeySet;
 use rand::Rng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_with::skip_serializing_none;
+use serde_with::{serde_as, skip_serializing_none};
 use thiserror::Error;
 use ulid::Ulid;
 use url::Url;
@@ -71,6 +72,18 @@ pub enum ClientAuthMethodConfig {
     /// `client_secret_basic`: a `client_assertion` sent in the request body and
     /// signed by an asymmetric key
     PrivateKeyJwt(JwksOrJwksUri),
+
+    /// `tls_client_auth`: a TLS client certificate issued by one of the
+    /// trusted certificate authorities
+    TlsClientAuth {
+        /// The expected subject distinguished name of the certificate, in the
+        /// RFC 4514 string format
+        tls_client_auth_subject_dn: String,
+    },
+
+    /// `self_signed_tls_client_auth`: a self-signed TLS client certificate,
+    /// with a public key from the client JWKS
+    SelfSignedTlsClientAuth(JwksOrJwksUri),
 }
 
 /// An OAuth 2.0 client configuration
@@ -92,6 +105,72 @@ pub struct ClientConfig {
     /// List of allowed redirect URIs
     #[serde(default)]
     pub redirect_uris: Vec<Url>,
+
+    /// List of allowed URIs to redirect to after an RP-initiated logout
+    #[serde(default)]
+    pub post_logout_redirect_uris: Vec<Url>,
+
+    /// URI to which logout tokens are sent when a session of this client ends,
+    /// as defined by OpenID Connect Back-Channel Logout
+    #[serde(default)]
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the logout tokens sent to the `backchannel_logout_uri` must
+    /// include the `sid` claim. Defaults to `false`.
+    #[serde(default)]
+    pub backchannel_logout_session_required: bool,
+
+    /// Whether this client must use pushed authorization requests to start an
+    /// authorization flow. Defaults to `false`.
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+
+    /// Whether the access tokens issued to this client are bound to the TLS
+    /// client certificate it used on the token endpoint. Defaults to `false`.
+    #[serde(default)]
+    pub tls_client_certificate_bound_access_tokens: bool,
+
+    /// Overrides of the refresh token behavior configured in the
+    /// `experimental` section, for this client
+    #[serde(default)]
+    pub refresh_tokens: Option<ClientRefreshTokensConfig>,
+}
+
+/// Overrides of the refresh token behavior for a client. Options which are
+/// not set use the server-wide value.
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ClientRefreshTokensConfig {
+    /// Whether refresh tokens are replaced by new ones each time they are used
+    #[serde(default)]
+    pub rotation: Option<bool>,
+
+    /// How long a session can go unused before its refresh tokens expire, in
+    /// seconds
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub inactivity_ttl: Option<Duration>,
+
+    /// How long after a session started its refresh tokens expire, in seconds,
+    /// regardless of its activity
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub absolute_ttl: Option<Duration>,
+
+    /// Whether using a refresh token which was already used ends the whole
+    /// session
+    #[serde(default)]
+    pub revoke_session_on_reuse: Option<bool>,
+
+    /// How long after being used a refresh token can still be used once more,
+    /// in seconds
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub reuse_grace_period: Option<Duration>,
 }
 
 #[derive(Debug, Error)]
@@ -127,6 +206,12 @@ impl ClientConfig {
             ClientAuthMethodConfig::PrivateKeyJwt(_) => {
                 OAuthClientAuthenticationMethod::PrivateKeyJwt
             }
+            ClientAuthMethodConfig::TlsClientAuth { .. } => {
+                OAuthClientAuthenticationMethod::TlsClientAuth
+            }
+            ClientAuthMethodConfig::SelfSignedTlsClientAuth(_) => {
+                OAuthClientAuthenticationMethod::SelfSignedTlsClientAuth
+            }
         }
     }
 
@@ -134,7 +219,21 @@ impl ClientConfig {
     #[must_use]
     pub fn jwks(&self) -> Option<&PublicJsonWebKeySet> {
         match &self.client_auth_method {
-            ClientAuthMethodConfig::PrivateKeyJwt(JwksOrJwksUri::Jwks(jwks)) => Some(jwks),
+            ClientAuthMethodConfig::PrivateKeyJwt(JwksOrJwksUri::Jwks(jwks))
+            | ClientAuthMethodConfig::SelfSignedTlsClientAuth(JwksOrJwksUri::Jwks(jwks)) => {
+                Some(jwks)
+            }
+            _ => None,
+        }
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    pub fn tls_client_auth_subject_dn(&self) -> Option<&str> {
+        match &self.client_auth_method {
+            ClientAuthMethodConfig::TlsClientAuth {
+                tls_client_auth_subject_dn,
+            } => Some(tls_client_auth_subject_dn),
             _ => None,
         }
     }
@@ -143,7 +242,8 @@ impl ClientConfig {
     #[must_use]
     pub fn jwks_uri(&self) -> Option<&Url> {
         match &self.client_auth_method {
-            ClientAuthMethodConfig::PrivateKeyJwt(JwksOrJwksUri::JwksUri(jwks_uri)) => {
+            ClientAuthMethodConfig::PrivateKeyJwt(JwksOrJwksUri::JwksUri(jwks_uri))
+            | ClientAuthMethodConfig::SelfSignedTlsClientAuth(JwksOrJwksUri::JwksUri(jwks_uri)) => {
                 Some(jwks_uri)
             }
             _ => None,
@@ -207,6 +307,9 @@ mod tests {
                       client_auth_method: none
                       redirect_uris:
                         - https://exemple.fr/callback
+                      post_logout_redirect_uris:
+                        - https://exemple.fr/logged-out
+                      backchannel_logout_uri: https://exemple.fr/backchannel-logout
 
                     - client_id: 01GFWR32NCQ12B8Z0J8CPXRRB6
                       client_auth_method: client_secret_basic
@@ -237,12 +340,17 @@ mod tests {
                           use: "sig"
                           e: "AQAB"
                           n: "0hukqytPwrj1RbMYhYoepCi3CN5k7DwYkTe_Cmb7cP9_qv4ok78KdvFXt5AnQxCRwBD7-qTNkkfMWO2RxUMBdQD0ED6tsSb1n5dp0XY8dSWiBDCX8f6Hr-KolOpvMLZKRy01HdAWcM6RoL9ikbjYHUEW1C8IJnw3MzVHkpKFDL354aptdNLaAdTCBvKzU9WpXo10g-5ctzSlWWjQuecLMQ4G1mNdsR1LHhUENEnOvgT8cDkX0fJzLbEbyBYkdMgKggyVPEB1bg6evG4fTKawgnf0IDSPxIU-wdS9wdSP9ZCJJPLi5CEp-6t6rE_sb2dGcnzjCGlembC57VwpkUvyMw"
+
+                    - client_id: 01GFWR5EXP2E8T7DZ4NW1TMQ0V
+                      client_auth_method: tls_client_auth
+                      tls_client_auth_subject_dn: CN=client.example.com,O=Example
+                      tls_client_certificate_bound_access_tokens: true
                 "#,
             )?;
 
             let config = ClientsConfig::load_from_file("config.yaml")?;
 
-            assert_eq!(config.0.len(), 5);
+            assert_eq!(config.0.len(), 6);
 
             assert_eq!(
                 config.0[0].client_id,
@@ -252,6 +360,15 @@ mod tests {
                 config.0[0].redirect_uris,
                 vec!["https://exemple.fr/callback".parse().unwrap()]
             );
+            assert_eq!(
+                config.0[0].post_logout_redirect_uris,
+                vec!["https://exemple.fr/logged-out".parse().unwrap()]
+            );
+            assert_eq!(
+                config.0[0].backchannel_logout_uri,
+                Some("https://exemple.fr/backchannel-logout".parse().unwrap())
+            );
+            assert!(!config.0[0].backchannel_logout_session_required);
 
             assert_eq!(
                 config.0[1].client_id,
@@ -259,6 +376,16 @@ mod tests {
             );
             assert_eq!(config.0[1].redirect_uris, Vec::new());
 
+            assert_eq!(
+                config.0[5].client_auth_method(),
+                OAuthClientAuthenticationMethod::TlsClientAuth
+            );
+            assert_eq!(
+                config.0[5].tls_client_auth_subject_dn(),
+                Some("CN=client.example.com,O=Example")
+            );
+            assert!(config.0[5].tls_client_certificate_bound_access_tokens);
+
             Ok(())
         });
     }
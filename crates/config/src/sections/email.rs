@@ -53,6 +53,10 @@ pub enum EmailTransportConfig {
     /// Don't send emails anywhere
     Blackhole,
 
+    /// Keep the emails in memory. They are only listed on the `/dev/mailbox`
+    /// page of the development server, and are lost on restart
+    Memory,
+
     /// Send emails via an SMTP relay
     Smtp {
         /// Connection mode to the relay
@@ -98,6 +102,113 @@ fn default_sendmail_command() -> String {
     "sendmail".to_owned()
 }
 
+/// Addresses to send a kind of email from, instead of the default ones
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct EmailSenderConfig {
+    /// Email address to use as From. Defaults to the global `from` address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "crate::schema::mailbox")]
+    pub from: Option<String>,
+
+    /// Email address to use as Reply-To. Defaults to the global `reply_to`
+    /// address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "crate::schema::mailbox")]
+    pub reply_to: Option<String>,
+}
+
+/// The kinds of email which can be sent from their own address
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailSenderKind {
+    /// Emails with a code to verify an email address
+    Verification,
+
+    /// Emails with a code to verify the email address of a new account
+    Registration,
+
+    /// Emails with a link to reset a password
+    PasswordReset,
+
+    /// Emails with a link to log in
+    LoginLink,
+
+    /// Emails telling that a data export is ready to download
+    DataExport,
+
+    /// Emails warning that password login on the compatibility layer is
+    /// being disabled
+    CompatPasswordDeprecation,
+}
+
+impl std::fmt::Display for EmailSenderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The name of the field in the `senders` section
+        let name = match self {
+            Self::Verification => "verification",
+            Self::Registration => "registration",
+            Self::PasswordReset => "password_reset",
+            Self::LoginLink => "login_link",
+            Self::DataExport => "data_export",
+            Self::CompatPasswordDeprecation => "compat_password_deprecation",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Addresses to send each kind of email from
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct EmailSendersConfig {
+    /// Emails with a code to verify an email address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<EmailSenderConfig>,
+
+    /// Emails with a code to verify the email address of a new account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration: Option<EmailSenderConfig>,
+
+    /// Emails with a link to reset a password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_reset: Option<EmailSenderConfig>,
+
+    /// Emails with a link to log in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login_link: Option<EmailSenderConfig>,
+
+    /// Emails telling that a data export is ready to download
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_export: Option<EmailSenderConfig>,
+
+    /// Emails warning that password login on the compatibility layer is
+    /// being disabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compat_password_deprecation: Option<EmailSenderConfig>,
+}
+
+impl EmailSendersConfig {
+    /// Whether no kind of email has its own sender
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// The kinds of email which have their own sender
+    pub fn iter(&self) -> impl Iterator<Item = (EmailSenderKind, &EmailSenderConfig)> {
+        [
+            (EmailSenderKind::Verification, &self.verification),
+            (EmailSenderKind::Registration, &self.registration),
+            (EmailSenderKind::PasswordReset, &self.password_reset),
+            (EmailSenderKind::LoginLink, &self.login_link),
+            (EmailSenderKind::DataExport, &self.data_export),
+            (
+                EmailSenderKind::CompatPasswordDeprecation,
+                &self.compat_password_deprecation,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(kind, sender)| Some((kind, sender.as_ref()?)))
+    }
+}
+
 /// Configuration related to sending emails
 #[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct EmailConfig {
@@ -111,6 +222,11 @@ pub struct EmailConfig {
     #[schemars(schema_with = "crate::schema::mailbox")]
     pub reply_to: String,
 
+    /// Addresses to use for some kinds of emails instead of `from` and
+    /// `reply_to`, e.g. to send password resets from a dedicated address
+    #[serde(default, skip_serializing_if = "EmailSendersConfig::is_empty")]
+    pub senders: EmailSendersConfig,
+
     /// What backend should be used when sending emails
     #[serde(flatten, default)]
     pub transport: EmailTransportConfig,
@@ -121,6 +237,7 @@ impl Default for EmailConfig {
         Self {
             from: default_email(),
             reply_to: default_email(),
+            senders: EmailSendersConfig::default(),
             transport: EmailTransportConfig::Blackhole,
         }
     }
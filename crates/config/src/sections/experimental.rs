@@ -25,6 +25,10 @@ fn default_token_ttl() -> Duration {
     Duration::minutes(5)
 }
 
+fn default_true() -> bool {
+    true
+}
+
 /// Configuration sections for experimental options
 ///
 /// Do not change these options unless you know what you are doing.
@@ -43,6 +47,42 @@ pub struct ExperimentalConfig {
     #[serde(default = "default_token_ttl")]
     #[serde_as(as = "serde_with::DurationSeconds<i64>")]
     pub compat_token_ttl: Duration,
+
+    /// How long a session can go unused before its refresh tokens expire, in
+    /// seconds. Once expired, the user has to authenticate again. Defaults to
+    /// no expiry.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub refresh_token_inactivity_ttl: Option<Duration>,
+
+    /// How long after a session started its refresh tokens expire, in seconds,
+    /// regardless of its activity. Defaults to no expiry.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub refresh_token_absolute_ttl: Option<Duration>,
+
+    /// Whether refresh tokens are replaced by new ones each time they are
+    /// used. Defaults to `true`.
+    #[serde(default = "default_true")]
+    pub refresh_token_rotation: bool,
+
+    /// Whether using a refresh token which was already used ends the whole
+    /// session, as it likely leaked. Only applies when refresh tokens are
+    /// rotated. Defaults to `false`.
+    #[serde(default)]
+    pub revoke_session_on_refresh_token_reuse: bool,
+
+    /// How long after being used a refresh token can still be used once more,
+    /// in seconds, in which case the same tokens as the first time are
+    /// returned. This helps clients which refresh twice on flaky networks.
+    /// Only applies when refresh tokens are rotated. Defaults to no grace
+    /// period.
+    #[schemars(with = "Option<u64>")]
+    #[serde(default)]
+    #[serde_as(as = "Option<serde_with::DurationSeconds<i64>>")]
+    pub refresh_token_reuse_grace_period: Option<Duration>,
 }
 
 impl Default for ExperimentalConfig {
@@ -50,6 +90,11 @@ impl Default for ExperimentalConfig {
         Self {
             access_token_ttl: default_token_ttl(),
             compat_token_ttl: default_token_ttl(),
+            refresh_token_inactivity_ttl: None,
+            refresh_token_absolute_ttl: None,
+            refresh_token_rotation: true,
+            revoke_session_on_refresh_token_reuse: false,
+            refresh_token_reuse_grace_period: None,
         }
     }
 }
@@ -0,0 +1,100 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::ConfigurationSection;
+
+const fn default_max_depth() -> usize {
+    16
+}
+
+const fn default_max_complexity() -> usize {
+    2000
+}
+
+/// Configuration section for the GraphQL API
+///
+/// The per-caller rate limit on this API is configured in the
+/// `rate_limiting` section.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct GraphQLConfig {
+    /// Maximum depth of a query. Queries nesting fields deeper than this are
+    /// rejected before being executed.
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+
+    /// Maximum complexity of a query. Each field counts for one, and the
+    /// fields under a paginated list count once per requested item, so that
+    /// nested lists quickly add up.
+    #[serde(default = "default_max_complexity")]
+    pub max_complexity: usize,
+}
+
+impl Default for GraphQLConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            max_complexity: default_max_complexity(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for GraphQLConfig {
+    fn path() -> &'static str {
+        "graphql"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r"
+                  graphql:
+                    max_depth: 8
+                ",
+            )?;
+
+            let config = GraphQLConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.max_depth, 8);
+            assert_eq!(config.max_complexity, 2000);
+
+            Ok(())
+        });
+    }
+}
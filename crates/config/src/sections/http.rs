@@ -280,6 +280,9 @@ pub enum Resource {
     /// Matrix compatibility API
     Compat,
 
+    /// Administrative API (/api/admin/v1/)
+    AdminApi,
+
     /// Static files
     Assets {
         /// Path to the directory to serve.
@@ -288,6 +291,14 @@ pub enum Resource {
         path: Utf8PathBuf,
     },
 
+    /// Version endpoint (/api/version)
+    Version {
+        /// Also show the git commit, the enabled build features and the
+        /// database schema version
+        #[serde(default)]
+        detailed: bool,
+    },
+
     /// Mount a "/connection-info" handler which helps debugging informations on
     /// the upstream connection
     #[serde(rename = "connection-info")]
@@ -328,6 +339,117 @@ pub struct ListenerConfig {
     pub tls: Option<TlsConfig>,
 }
 
+/// Format of the lines written to the HTTP access log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// NCSA Common Log Format
+    #[default]
+    Common,
+
+    /// NCSA Combined Log Format, which adds the referer and the user agent to
+    /// the common format
+    Combined,
+
+    /// One JSON object per line
+    Json,
+}
+
+fn default_exclude_health_checks() -> bool {
+    true
+}
+
+/// Configuration of the HTTP access log
+///
+/// This log is written independently of the tracing logs
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AccessLogConfig {
+    /// Format of the log lines
+    #[serde(default)]
+    pub format: AccessLogFormat,
+
+    /// Path to the file the log lines are appended to. Logs to the standard
+    /// output if not set
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub path: Option<Utf8PathBuf>,
+
+    /// Whether to leave out the requests to the health check endpoint
+    #[serde(default = "default_exclude_health_checks")]
+    pub exclude_health_checks: bool,
+}
+
+fn default_client_certificate_header() -> String {
+    "Client-Cert".to_owned()
+}
+
+/// How TLS client certificates are conveyed to the service
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum ClientCertificateSource {
+    /// Client certificates are requested during the TLS handshake, by the
+    /// listeners which have TLS configured
+    Tls,
+
+    /// TLS is terminated by a reverse proxy, which forwards the client
+    /// certificate in a header. The header is only trusted on requests coming
+    /// from the `trusted_proxies`
+    Header {
+        /// Name of the header. Its value is either the DER-encoded certificate
+        /// as a structured field byte sequence, as defined by RFC 9440, or a
+        /// URL-encoded PEM certificate
+        #[serde(default = "default_client_certificate_header")]
+        header: String,
+    },
+}
+
+/// Configuration of TLS client certificates, used for mutual-TLS client
+/// authentication and certificate-bound access tokens
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClientCertificateConfig {
+    /// Where the client certificates come from
+    #[serde(flatten)]
+    pub source: ClientCertificateSource,
+
+    /// PEM-encoded certificates of the authorities which issue the
+    /// certificates of clients using the `tls_client_auth` method
+    #[serde(default)]
+    pub trusted_roots: Vec<CertificateOrFile>,
+}
+
+impl ClientCertificateConfig {
+    /// Load the trusted root certificates, as DER
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a certificate file could not be read, or if a
+    /// certificate could not be decoded as PEM
+    pub fn load_trusted_roots(&self) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        let mut roots = Vec::new();
+
+        for root in &self.trusted_roots {
+            let pem = match root {
+                CertificateOrFile::Certificate(pem) => Cow::Borrowed(pem.as_str()),
+                CertificateOrFile::CertificateFile(path) => {
+                    Cow::Owned(std::fs::read_to_string(path)?)
+                }
+            };
+
+            let mut reader = Cursor::new(pem.as_bytes());
+            let certificates = rustls_pemfile::certs(&mut reader)?;
+
+            if certificates.is_empty() {
+                bail!("Trusted root certificate is empty (or invalid)")
+            }
+
+            roots.extend(certificates);
+        }
+
+        Ok(roots)
+    }
+}
+
 /// Configuration related to the web server
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct HttpConfig {
@@ -345,6 +467,15 @@ pub struct HttpConfig {
 
     /// OIDC issuer URL. Defaults to `public_base` if not set.
     pub issuer: Option<Url>,
+
+    /// If set, writes an access log of the HTTP requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_log: Option<AccessLogConfig>,
+
+    /// If set, accepts TLS client certificates, for mutual-TLS client
+    /// authentication and certificate-bound access tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_certificate: Option<ClientCertificateConfig>,
 }
 
 impl Default for HttpConfig {
@@ -385,6 +516,8 @@ impl Default for HttpConfig {
             trusted_proxies: default_trusted_proxies(),
             issuer: Some(default_public_base()),
             public_base: default_public_base(),
+            access_log: None,
+            client_certificate: None,
         }
     }
 }
@@ -0,0 +1,121 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use camino::Utf8PathBuf;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::ConfigurationSection;
+
+fn default_retry_after() -> Duration {
+    Duration::minutes(5)
+}
+
+/// Configuration related to the maintenance mode
+///
+/// While in maintenance mode, existing tokens keep working, but interactive
+/// flows show a maintenance page and requests which would write to the
+/// database are rejected.
+#[serde_as]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MaintenanceConfig {
+    /// Whether the service starts in maintenance mode
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Path to a file which puts the service in maintenance mode while it
+    /// exists. This lets operators toggle the maintenance mode without
+    /// restarting the service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub flag_file: Option<Utf8PathBuf>,
+
+    /// How long clients should wait before retrying, in seconds. Defaults to
+    /// 5 minutes.
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde(default = "default_retry_after")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub retry_after: Duration,
+
+    /// A message displayed to users on the maintenance page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flag_file: None,
+            retry_after: default_retry_after(),
+            message: None,
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for MaintenanceConfig {
+    fn path() -> &'static str {
+        "maintenance"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  maintenance:
+                    flag_file: /var/lib/mas/maintenance
+                    retry_after: 60
+                    message: Back in a minute!
+                "#,
+            )?;
+
+            let config = MaintenanceConfig::load_from_file("config.yaml")?;
+
+            assert!(!config.enabled);
+            assert_eq!(
+                config.flag_file.as_ref().map(Utf8PathBuf::as_str),
+                Some("/var/lib/mas/maintenance")
+            );
+            assert_eq!(config.retry_after, Duration::minutes(1));
+            assert_eq!(config.message.as_deref(), Some("Back in a minute!"));
+
+            Ok(())
+        });
+    }
+}
@@ -12,7 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
+use anyhow::{bail, Context};
 use async_trait::async_trait;
+use camino::Utf8PathBuf;
 use rand::{
     distributions::{Alphanumeric, DistString},
     Rng,
@@ -20,6 +24,7 @@ use rand::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+use ulid::Ulid;
 use url::Url;
 
 use super::ConfigurationSection;
@@ -32,6 +37,126 @@ fn default_endpoint() -> Url {
     Url::parse("http://localhost:8008/").unwrap()
 }
 
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+fn default_max_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_reset_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// Configuration of the circuit breaker wrapping calls to the homeserver
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CircuitBreakerConfig {
+    /// Number of consecutive failed calls after which calls to the homeserver
+    /// are not attempted anymore
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// Time to wait, in seconds, before trying to call the homeserver again
+    /// after the circuit opened
+    #[schemars(with = "u64")]
+    #[serde(default = "default_reset_timeout")]
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    pub reset_timeout: Duration,
+
+    /// Whether the service should report as not ready while the circuit is
+    /// open
+    #[serde(default)]
+    pub affects_readiness: bool,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            reset_timeout: default_reset_timeout(),
+            affects_readiness: false,
+        }
+    }
+}
+
+/// What to do when a new session asks for a device ID which is already used by
+/// an active session of the same user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceIdConflictPolicy {
+    /// End the existing sessions and replace the device on the homeserver
+    #[default]
+    Replace,
+
+    /// Refuse to start the new session
+    Reject,
+
+    /// Give the new session a different device ID, derived from the requested
+    /// one
+    Suffix,
+}
+
+/// An extra header sent on every call to the homeserver
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HeaderConfig {
+    /// Name of the header
+    pub name: String,
+
+    /// Value of the header. If a `secret` or `secret_file` is set, the
+    /// `{{ secret }}` placeholder is replaced by it, for example
+    /// `Bearer {{ secret }}`
+    pub value: String,
+
+    /// Secret to put in the value of the header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// File from which to read the secret to put in the value of the header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub secret_file: Option<Utf8PathBuf>,
+}
+
+impl HeaderConfig {
+    /// Load the value of the header, reading the secret from its file if
+    /// needed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if both `secret` and `secret_file` are set, or if the
+    /// secret file could not be read
+    pub async fn load_value(&self) -> anyhow::Result<String> {
+        let secret = match (&self.secret, &self.secret_file) {
+            (None, None) => return Ok(self.value.clone()),
+            (Some(_), Some(_)) => {
+                bail!(
+                    "Cannot set both `secret` and `secret_file` on header {}",
+                    self.name
+                )
+            }
+            (Some(secret), None) => secret.clone(),
+            (None, Some(path)) => tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read the secret file {path}"))?
+                // Files usually end with a newline, which isn't part of the secret
+                .trim_end()
+                .to_owned(),
+        };
+
+        Ok(self.value.replace("{{ secret }}", &secret))
+    }
+}
+
 /// Configuration related to the Matrix homeserver
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -46,6 +171,54 @@ pub struct MatrixConfig {
     /// The base URL of the homeserver's client API
     #[serde(default = "default_endpoint")]
     pub endpoint: Url,
+
+    /// Timeout, in seconds, of each call to the homeserver
+    #[schemars(with = "u64")]
+    #[serde(default = "default_request_timeout")]
+    #[serde_as(as = "serde_with::DurationSeconds<u64>")]
+    pub request_timeout: Duration,
+
+    /// How many times a call to the homeserver is retried if it failed
+    /// because of a transient error
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Delay, in milliseconds, before the first retry. It doubles on every
+    /// subsequent retry
+    #[schemars(with = "u64")]
+    #[serde(default = "default_retry_backoff")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub retry_backoff: Duration,
+
+    /// Circuit breaker configuration
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+
+    /// Template used to name the devices of compatibility sessions when the
+    /// client did not supply an `initial_device_display_name`.
+    ///
+    /// It has access to the `client` and `platform` variables, guessed from
+    /// the `User-Agent` header, and to the raw `user_agent`. For example:
+    /// `{{ client }}{% if platform %} on {{ platform }}{% endif %}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_name_template: Option<String>,
+
+    /// What to do when a new session asks for a device ID which is already
+    /// used by an active session of the same user
+    #[serde(default)]
+    pub device_id_conflict: DeviceIdConflictPolicy,
+
+    /// List of client IDs, usually the one used by the homeserver, which get
+    /// Matrix-specific claims (`mxid`, `device_id`, `device_name` and
+    /// `session_kind`) when introspecting tokens
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(with = "Vec<String>")]
+    pub introspection_clients: Vec<Ulid>,
+
+    /// Extra headers to send on every call to the homeserver, for example
+    /// when it sits behind an authenticating proxy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<HeaderConfig>,
 }
 
 #[async_trait]
@@ -62,6 +235,14 @@ impl ConfigurationSection for MatrixConfig {
             homeserver: default_homeserver(),
             secret: Alphanumeric.sample_string(&mut rng, 32),
             endpoint: default_endpoint(),
+            request_timeout: default_request_timeout(),
+            max_retries: default_max_retries(),
+            retry_backoff: default_retry_backoff(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            device_name_template: None,
+            device_id_conflict: DeviceIdConflictPolicy::default(),
+            introspection_clients: Vec::new(),
+            headers: Vec::new(),
         })
     }
 
@@ -70,6 +251,14 @@ impl ConfigurationSection for MatrixConfig {
             homeserver: default_homeserver(),
             secret: "test".to_owned(),
             endpoint: default_endpoint(),
+            request_timeout: default_request_timeout(),
+            max_retries: default_max_retries(),
+            retry_backoff: default_retry_backoff(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            device_name_template: None,
+            device_id_conflict: DeviceIdConflictPolicy::default(),
+            introspection_clients: Vec::new(),
+            headers: Vec::new(),
         }
     }
 }
@@ -85,17 +274,65 @@ mod tests {
         Jail::expect_with(|jail| {
             jail.create_file(
                 "config.yaml",
-                r"
+                r#"
                     matrix:
                       homeserver: matrix.org
                       secret: test
-                ",
+                      max_retries: 0
+                      circuit_breaker:
+                        failure_threshold: 3
+                        affects_readiness: true
+                      device_name_template: "{{ client }} on {{ platform }}"
+                      device_id_conflict: suffix
+                      headers:
+                        - name: X-Tenant
+                          value: example
+                        - name: Proxy-Authorization
+                          value: "Bearer {{ secret }}"
+                          secret_file: proxy-token
+                "#,
             )?;
+            jail.create_file("proxy-token", "hunter2\n")?;
 
             let config = MatrixConfig::load_from_file("config.yaml")?;
 
             assert_eq!(config.homeserver, "matrix.org".to_owned());
             assert_eq!(config.secret, "test".to_owned());
+            assert_eq!(config.request_timeout, Duration::from_secs(10));
+            assert_eq!(config.max_retries, 0);
+            assert_eq!(config.circuit_breaker.failure_threshold, 3);
+            assert_eq!(
+                config.circuit_breaker.reset_timeout,
+                Duration::from_secs(30)
+            );
+            assert!(config.circuit_breaker.affects_readiness);
+            assert_eq!(
+                config.device_name_template.as_deref(),
+                Some("{{ client }} on {{ platform }}")
+            );
+            assert_eq!(config.device_id_conflict, DeviceIdConflictPolicy::Suffix);
+
+            let headers = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    let mut headers = Vec::new();
+                    for header in &config.headers {
+                        headers.push((header.name.clone(), header.load_value().await?));
+                    }
+                    anyhow::Ok(headers)
+                })
+                .unwrap();
+            assert_eq!(
+                headers,
+                vec![
+                    ("X-Tenant".to_owned(), "example".to_owned()),
+                    (
+                        "Proxy-Authorization".to_owned(),
+                        "Bearer hunter2".to_owned()
+                    ),
+                ]
+            );
 
             Ok(())
         });
@@ -17,42 +17,82 @@ use rand::Rng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+mod account;
+mod avatars;
 mod branding;
+mod captcha;
 mod clients;
 mod database;
 mod email;
 mod experimental;
+mod graphql;
 mod http;
+mod maintenance;
 mod matrix;
 mod passwords;
 mod policy;
+mod rate_limiting;
+mod scopes;
 mod secrets;
+mod sms;
+mod storage;
+mod tasks;
 mod telemetry;
 mod templates;
 mod upstream_oauth2;
 
 pub use self::{
+    account::{AccountConfig, EmailNormalizationConfig, TermsConfig},
+    avatars::AvatarsConfig,
     branding::BrandingConfig,
-    clients::{ClientAuthMethodConfig, ClientConfig, ClientsConfig},
+    captcha::{CaptchaConfig, CaptchaServiceKind},
+    clients::{ClientAuthMethodConfig, ClientConfig, ClientRefreshTokensConfig, ClientsConfig},
     database::{ConnectConfig as DatabaseConnectConfig, DatabaseConfig},
-    email::{EmailConfig, EmailSmtpMode, EmailTransportConfig},
+    email::{
+        EmailConfig, EmailSenderConfig, EmailSenderKind, EmailSendersConfig, EmailSmtpMode,
+        EmailTransportConfig,
+    },
     experimental::ExperimentalConfig,
+    graphql::GraphQLConfig,
     http::{
-        BindConfig as HttpBindConfig, HttpConfig, ListenerConfig as HttpListenerConfig,
-        Resource as HttpResource, TlsConfig as HttpTlsConfig, UnixOrTcp,
+        AccessLogConfig as HttpAccessLogConfig, AccessLogFormat as HttpAccessLogFormat,
+        BindConfig as HttpBindConfig, ClientCertificateConfig as HttpClientCertificateConfig,
+        ClientCertificateSource as HttpClientCertificateSource, HttpConfig,
+        ListenerConfig as HttpListenerConfig, Resource as HttpResource, TlsConfig as HttpTlsConfig,
+        UnixOrTcp,
+    },
+    maintenance::MaintenanceConfig,
+    matrix::{
+        CircuitBreakerConfig as MatrixCircuitBreakerConfig, DeviceIdConflictPolicy,
+        HeaderConfig as MatrixHeaderConfig, MatrixConfig,
+    },
+    passwords::{
+        Algorithm as PasswordAlgorithm, BreachedPasswordAction, BreachedPasswordsConfig,
+        PasswordsConfig,
     },
-    matrix::MatrixConfig,
-    passwords::{Algorithm as PasswordAlgorithm, PasswordsConfig},
     policy::PolicyConfig,
-    secrets::SecretsConfig,
+    rate_limiting::{
+        EmailQuotaConfig, LoginLockoutConfig, RateLimitQuotaConfig, RateLimitingBackendConfig,
+        RateLimitingConfig, TokenQuotaConfig,
+    },
+    scopes::{ScopeConfig, ScopesConfig},
+    secrets::{KeyAlgorithm, KeyConfig, SecretsConfig},
+    sms::{SmsConfig, SmsTransportConfig},
+    storage::{
+        BlobStorageConfig, BlobStorageS3EncryptionAlgorithm, BlobStorageS3EncryptionConfig,
+        StorageConfig,
+    },
+    tasks::{DeletedUsersConfig, KeyExpiryConfig, StaleClientsConfig, TasksConfig},
     telemetry::{
         JaegerExporterProtocolConfig, MetricsConfig, MetricsExporterConfig, Propagator,
-        TelemetryConfig, TracingConfig, TracingExporterConfig,
+        RedactionConfig, TelemetryConfig, TracingConfig, TracingExporterConfig,
     },
     templates::TemplatesConfig,
     upstream_oauth2::{
         ClaimsImports as UpstreamOAuth2ClaimsImports, DiscoveryMode as UpstreamOAuth2DiscoveryMode,
         EmailImportPreference as UpstreamOAuth2EmailImportPreference,
+        GroupsImportPreference as UpstreamOAuth2GroupsImportPreference,
+        GroupsSyncPolicy as UpstreamOAuth2GroupsSyncPolicy,
         ImportAction as UpstreamOAuth2ImportAction,
         ImportPreference as UpstreamOAuth2ImportPreference, PkceMethod as UpstreamOAuth2PkceMethod,
         SetEmailVerification as UpstreamOAuth2SetEmailVerification, UpstreamOAuth2Config,
@@ -87,6 +127,10 @@ pub struct RootConfig {
     #[serde(default)]
     pub email: EmailConfig,
 
+    /// Configuration related to sending text messages
+    #[serde(default)]
+    pub sms: SmsConfig,
+
     /// Application secrets
     pub secrets: SecretsConfig,
 
@@ -101,6 +145,10 @@ pub struct RootConfig {
     #[serde(default)]
     pub policy: PolicyConfig,
 
+    /// List of custom scopes clients can request
+    #[serde(default)]
+    pub scopes: ScopesConfig,
+
     /// Configuration related to upstream OAuth providers
     #[serde(default)]
     pub upstream_oauth2: UpstreamOAuth2Config,
@@ -109,6 +157,38 @@ pub struct RootConfig {
     #[serde(default)]
     pub branding: BrandingConfig,
 
+    /// Configuration section to setup CAPTCHA protection on a few operations
+    #[serde(default)]
+    pub captcha: CaptchaConfig,
+
+    /// Configuration section for avatars uploaded by users
+    #[serde(default)]
+    pub avatars: AvatarsConfig,
+
+    /// Configuration related to the storage of files
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Configuration related to the GraphQL API
+    #[serde(default)]
+    pub graphql: GraphQLConfig,
+
+    /// Configuration related to rate limiting
+    #[serde(default)]
+    pub rate_limiting: RateLimitingConfig,
+
+    /// Configuration related to the maintenance mode
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    /// Configuration related to the user accounts
+    #[serde(default)]
+    pub account: AccountConfig,
+
+    /// Configuration related to the background tasks
+    #[serde(default)]
+    pub tasks: TasksConfig,
+
     /// Experimental configuration options
     #[serde(default)]
     pub experimental: ExperimentalConfig,
@@ -131,12 +211,22 @@ impl ConfigurationSection for RootConfig {
             telemetry: TelemetryConfig::generate(&mut rng).await?,
             templates: TemplatesConfig::generate(&mut rng).await?,
             email: EmailConfig::generate(&mut rng).await?,
+            sms: SmsConfig::generate(&mut rng).await?,
             passwords: PasswordsConfig::generate(&mut rng).await?,
             secrets: SecretsConfig::generate(&mut rng).await?,
             matrix: MatrixConfig::generate(&mut rng).await?,
             policy: PolicyConfig::generate(&mut rng).await?,
+            scopes: ScopesConfig::generate(&mut rng).await?,
             upstream_oauth2: UpstreamOAuth2Config::generate(&mut rng).await?,
             branding: BrandingConfig::generate(&mut rng).await?,
+            captcha: CaptchaConfig::generate(&mut rng).await?,
+            avatars: AvatarsConfig::generate(&mut rng).await?,
+            storage: StorageConfig::generate(&mut rng).await?,
+            graphql: GraphQLConfig::generate(&mut rng).await?,
+            rate_limiting: RateLimitingConfig::generate(&mut rng).await?,
+            maintenance: MaintenanceConfig::generate(&mut rng).await?,
+            account: AccountConfig::generate(&mut rng).await?,
+            tasks: TasksConfig::generate(&mut rng).await?,
             experimental: ExperimentalConfig::generate(&mut rng).await?,
         })
     }
@@ -150,11 +240,21 @@ impl ConfigurationSection for RootConfig {
             templates: TemplatesConfig::test(),
             passwords: PasswordsConfig::test(),
             email: EmailConfig::test(),
+            sms: SmsConfig::test(),
             secrets: SecretsConfig::test(),
             matrix: MatrixConfig::test(),
             policy: PolicyConfig::test(),
+            scopes: ScopesConfig::test(),
             upstream_oauth2: UpstreamOAuth2Config::test(),
             branding: BrandingConfig::test(),
+            captcha: CaptchaConfig::test(),
+            avatars: AvatarsConfig::test(),
+            storage: StorageConfig::test(),
+            graphql: GraphQLConfig::test(),
+            rate_limiting: RateLimitingConfig::test(),
+            maintenance: MaintenanceConfig::test(),
+            account: AccountConfig::test(),
+            tasks: TasksConfig::test(),
             experimental: ExperimentalConfig::test(),
         }
     }
@@ -186,9 +286,36 @@ pub struct AppConfig {
     #[serde(default)]
     pub policy: PolicyConfig,
 
+    #[serde(default)]
+    pub scopes: ScopesConfig,
+
     #[serde(default)]
     pub branding: BrandingConfig,
 
+    #[serde(default)]
+    pub captcha: CaptchaConfig,
+
+    #[serde(default)]
+    pub avatars: AvatarsConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    #[serde(default)]
+    pub graphql: GraphQLConfig,
+
+    #[serde(default)]
+    pub rate_limiting: RateLimitingConfig,
+
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+
+    #[serde(default)]
+    pub account: AccountConfig,
+
+    #[serde(default)]
+    pub tasks: TasksConfig,
+
     #[serde(default)]
     pub experimental: ExperimentalConfig,
 }
@@ -212,7 +339,16 @@ impl ConfigurationSection for AppConfig {
             secrets: SecretsConfig::generate(&mut rng).await?,
             matrix: MatrixConfig::generate(&mut rng).await?,
             policy: PolicyConfig::generate(&mut rng).await?,
+            scopes: ScopesConfig::generate(&mut rng).await?,
             branding: BrandingConfig::generate(&mut rng).await?,
+            captcha: CaptchaConfig::generate(&mut rng).await?,
+            avatars: AvatarsConfig::generate(&mut rng).await?,
+            storage: StorageConfig::generate(&mut rng).await?,
+            graphql: GraphQLConfig::generate(&mut rng).await?,
+            rate_limiting: RateLimitingConfig::generate(&mut rng).await?,
+            maintenance: MaintenanceConfig::generate(&mut rng).await?,
+            account: AccountConfig::generate(&mut rng).await?,
+            tasks: TasksConfig::generate(&mut rng).await?,
             experimental: ExperimentalConfig::generate(&mut rng).await?,
         })
     }
@@ -227,7 +363,16 @@ impl ConfigurationSection for AppConfig {
             secrets: SecretsConfig::test(),
             matrix: MatrixConfig::test(),
             policy: PolicyConfig::test(),
+            scopes: ScopesConfig::test(),
             branding: BrandingConfig::test(),
+            captcha: CaptchaConfig::test(),
+            avatars: AvatarsConfig::test(),
+            storage: StorageConfig::test(),
+            graphql: GraphQLConfig::test(),
+            rate_limiting: RateLimitingConfig::test(),
+            maintenance: MaintenanceConfig::test(),
+            account: AccountConfig::test(),
+            tasks: TasksConfig::test(),
             experimental: ExperimentalConfig::test(),
         }
     }
@@ -17,9 +17,12 @@ use std::borrow::Cow;
 use anyhow::bail;
 use async_trait::async_trait;
 use camino::Utf8PathBuf;
+use chrono::Duration;
 use rand::Rng;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use url::Url;
 
 use crate::ConfigurationSection;
 
@@ -35,7 +38,41 @@ fn default_enabled() -> bool {
     true
 }
 
+fn default_breached_passwords_endpoint() -> Url {
+    Url::parse("https://api.pwnedpasswords.com/range/").unwrap()
+}
+
+fn default_compat_deprecation_grace_period() -> Duration {
+    Duration::days(14)
+}
+
+/// What to do when a user picks a password which appeared in a data breach
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BreachedPasswordAction {
+    /// Refuse the password
+    #[default]
+    Reject,
+
+    /// Accept the password, but log a warning
+    Warn,
+}
+
+/// Check of new passwords against known data breaches
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BreachedPasswordsConfig {
+    /// What to do when a password appeared in a data breach
+    #[serde(default)]
+    pub action: BreachedPasswordAction,
+
+    /// Base URL of a Have I Been Pwned compatible range API. Defaults to the
+    /// public API, but can point to a local mirror
+    #[serde(default = "default_breached_passwords_endpoint")]
+    pub endpoint: Url,
+}
+
 /// User password hashing config
+#[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PasswordsConfig {
     /// Whether password-based authentication is enabled
@@ -44,6 +81,19 @@ pub struct PasswordsConfig {
 
     #[serde(default = "default_schemes")]
     schemes: Vec<HashingScheme>,
+
+    /// Check new passwords against known data breaches, using the k-anonymity
+    /// range API of Have I Been Pwned. Disabled by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    breached_passwords: Option<BreachedPasswordsConfig>,
+
+    /// Number of seconds during which users whose password login on the
+    /// compatibility layer is being deprecated can still use it, after being
+    /// warned by email. Defaults to 14 days.
+    #[schemars(with = "u64")]
+    #[serde(default = "default_compat_deprecation_grace_period")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    compat_deprecation_grace_period: Duration,
 }
 
 impl Default for PasswordsConfig {
@@ -51,6 +101,8 @@ impl Default for PasswordsConfig {
         Self {
             enabled: default_enabled(),
             schemes: default_schemes(),
+            breached_passwords: None,
+            compat_deprecation_grace_period: default_compat_deprecation_grace_period(),
         }
     }
 }
@@ -80,6 +132,19 @@ impl PasswordsConfig {
         self.enabled
     }
 
+    /// How new passwords are checked against known data breaches, if enabled
+    #[must_use]
+    pub fn breached_passwords(&self) -> Option<&BreachedPasswordsConfig> {
+        self.breached_passwords.as_ref()
+    }
+
+    /// For how long users whose password login on the compatibility layer is
+    /// being deprecated can still use it
+    #[must_use]
+    pub fn compat_deprecation_grace_period(&self) -> Duration {
+        self.compat_deprecation_grace_period
+    }
+
     /// Load the password hashing schemes defined by the config
     ///
     /// # Errors
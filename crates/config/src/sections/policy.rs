@@ -56,6 +56,10 @@ fn default_email_endpoint() -> String {
     "email/violation".to_owned()
 }
 
+fn default_compat_login_endpoint() -> String {
+    "compat_login/violation".to_owned()
+}
+
 /// Application secrets
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -85,6 +89,10 @@ pub struct PolicyConfig {
     #[serde(default = "default_email_endpoint")]
     pub email_entrypoint: String,
 
+    /// Entrypoint to use when logging in through the compatibility layer
+    #[serde(default = "default_compat_login_endpoint")]
+    pub compat_login_entrypoint: String,
+
     /// Arbitrary data to pass to the policy
     #[serde(default)]
     pub data: Option<serde_json::Value>,
@@ -99,6 +107,7 @@ impl Default for PolicyConfig {
             authorization_grant_entrypoint: default_authorization_grant_endpoint(),
             password_entrypoint: default_password_endpoint(),
             email_entrypoint: default_email_endpoint(),
+            compat_login_entrypoint: default_compat_login_endpoint(),
             data: None,
         }
     }
@@ -0,0 +1,362 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::num::NonZeroU32;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use url::Url;
+
+use super::ConfigurationSection;
+
+/// Where the state of the rate limiters is stored
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateLimitingBackendConfig {
+    /// Keep the state in memory. Each replica of the service enforces the
+    /// limits on its own.
+    #[default]
+    Memory,
+
+    /// Store the state in the database, so that the limits are shared between
+    /// replicas
+    Postgres,
+
+    /// Store the state in a Redis server, so that the limits are shared
+    /// between replicas
+    Redis {
+        /// URL of the Redis server, e.g. `redis://localhost:6379/0`
+        url: Url,
+    },
+}
+
+/// A rate limit, as a bucket of tokens: each request takes a token, and the
+/// bucket gets a new token at a fixed interval
+#[serde_as]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RateLimitQuotaConfig {
+    /// How many requests can be made at once
+    pub burst: NonZeroU32,
+
+    /// How often a new request is allowed, in seconds
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub replenish_interval: Duration,
+}
+
+fn default_login_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(5).unwrap(),
+        replenish_interval: Duration::seconds(20),
+    }
+}
+
+fn default_registration_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(10).unwrap(),
+        replenish_interval: Duration::minutes(6),
+    }
+}
+
+fn default_device_code_link_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(10).unwrap(),
+        replenish_interval: Duration::minutes(1),
+    }
+}
+
+fn default_graphql_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(100).unwrap(),
+        replenish_interval: Duration::seconds(1),
+    }
+}
+
+/// Quotas on the tokens issued by the token endpoint, protecting against
+/// clients which keep on starting new sessions or asking for new tokens
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct TokenQuotaConfig {
+    /// Maximum number of active sessions a single client can have. The token
+    /// endpoint refuses to start new sessions for a client above this number.
+    ///
+    /// Not limited by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_active_sessions_per_client: Option<NonZeroU32>,
+
+    /// Maximum number of tokens issued to a single user per hour, across all
+    /// clients. The whole hourly allowance can be used at once, and it
+    /// then replenishes evenly over the hour.
+    ///
+    /// Not limited by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens_per_user_per_hour: Option<NonZeroU32>,
+}
+
+fn default_email_address_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(1).unwrap(),
+        replenish_interval: Duration::minutes(1),
+    }
+}
+
+fn default_email_user_quota() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(10).unwrap(),
+        replenish_interval: Duration::minutes(6),
+    }
+}
+
+/// Quotas on the emails sent to verify an address or recover an account,
+/// protecting against the abuse of those endpoints to flood a mailbox
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct EmailQuotaConfig {
+    /// Rate limit of the verification and recovery emails sent to a single
+    /// address. This acts as a cooldown before another email can be
+    /// requested.
+    #[serde(default = "default_email_address_quota")]
+    pub per_address: RateLimitQuotaConfig,
+
+    /// Rate limit of the verification emails sent on behalf of a single user,
+    /// across all their addresses
+    #[serde(default = "default_email_user_quota")]
+    pub per_user: RateLimitQuotaConfig,
+}
+
+impl Default for EmailQuotaConfig {
+    fn default() -> Self {
+        Self {
+            per_address: default_email_address_quota(),
+            per_user: default_email_user_quota(),
+        }
+    }
+}
+
+fn default_lockout_user_threshold() -> NonZeroU32 {
+    NonZeroU32::new(10).unwrap()
+}
+
+fn default_lockout_ip_threshold() -> NonZeroU32 {
+    NonZeroU32::new(50).unwrap()
+}
+
+fn default_lockout_base_duration() -> Duration {
+    Duration::minutes(1)
+}
+
+fn default_lockout_max_duration() -> Duration {
+    Duration::days(1)
+}
+
+/// Temporary lockout of the users and IP addresses after too many failed
+/// login attempts, protecting against password guessing and credential
+/// stuffing
+#[serde_as]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct LoginLockoutConfig {
+    /// How many failed attempts on a single account before it gets locked out
+    #[serde(default = "default_lockout_user_threshold")]
+    pub user_threshold: NonZeroU32,
+
+    /// How many failed attempts from a single IP address, on any account,
+    /// before it gets locked out
+    #[serde(default = "default_lockout_ip_threshold")]
+    pub ip_threshold: NonZeroU32,
+
+    /// How long the first lockout lasts, in seconds. Each further failure
+    /// doubles it.
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    #[serde(default = "default_lockout_base_duration")]
+    pub base_duration: Duration,
+
+    /// The maximum duration of a lockout, in seconds. Failed attempts are
+    /// forgotten once no new failure happened for that long.
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    #[serde(default = "default_lockout_max_duration")]
+    pub max_duration: Duration,
+}
+
+impl Default for LoginLockoutConfig {
+    fn default() -> Self {
+        Self {
+            user_threshold: default_lockout_user_threshold(),
+            ip_threshold: default_lockout_ip_threshold(),
+            base_duration: default_lockout_base_duration(),
+            max_duration: default_lockout_max_duration(),
+        }
+    }
+}
+
+/// Configuration related to rate limiting
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RateLimitingConfig {
+    /// Where the state of the rate limiters is stored
+    #[serde(default)]
+    pub backend: RateLimitingBackendConfig,
+
+    /// Rate limit of password login attempts, per IP address
+    #[serde(default = "default_login_quota")]
+    pub login: RateLimitQuotaConfig,
+
+    /// Rate limit of registration attempts, per IP address
+    #[serde(default = "default_registration_quota")]
+    pub registration: RateLimitQuotaConfig,
+
+    /// Rate limit of the device code lookups by user code, per IP address.
+    /// This protects the user codes, which are short, against guessing.
+    #[serde(default = "default_device_code_link_quota")]
+    pub device_code_link: RateLimitQuotaConfig,
+
+    /// Rate limit of the GraphQL requests, per user, or per IP address for
+    /// anonymous requests
+    #[serde(default = "default_graphql_quota")]
+    pub graphql: RateLimitQuotaConfig,
+
+    /// Quotas on the tokens issued to clients
+    #[serde(default)]
+    pub tokens: TokenQuotaConfig,
+
+    /// Quotas on the verification and recovery emails, to prevent abusing
+    /// them to flood a mailbox
+    #[serde(default)]
+    pub email: EmailQuotaConfig,
+
+    /// Temporary lockout after too many failed login attempts. The failures
+    /// are tracked in the database, whatever the backend of the rate limiters.
+    ///
+    /// Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login_lockout: Option<LoginLockoutConfig>,
+}
+
+impl Default for RateLimitingConfig {
+    fn default() -> Self {
+        Self {
+            backend: RateLimitingBackendConfig::default(),
+            login: default_login_quota(),
+            registration: default_registration_quota(),
+            device_code_link: default_device_code_link_quota(),
+            graphql: default_graphql_quota(),
+            tokens: TokenQuotaConfig::default(),
+            email: EmailQuotaConfig::default(),
+            login_lockout: None,
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for RateLimitingConfig {
+    fn path() -> &'static str {
+        "rate_limiting"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  rate_limiting:
+                    backend:
+                      type: redis
+                      url: redis://localhost:6379/0
+                    login:
+                      burst: 3
+                      replenish_interval: 60
+                    registration:
+                      burst: 2
+                      replenish_interval: 3600
+                    device_code_link:
+                      burst: 5
+                      replenish_interval: 120
+                    graphql:
+                      burst: 20
+                      replenish_interval: 2
+                    tokens:
+                      max_active_sessions_per_client: 1000
+                    email:
+                      per_address:
+                        burst: 2
+                        replenish_interval: 300
+                    login_lockout:
+                      user_threshold: 5
+                      max_duration: 3600
+                "#,
+            )?;
+
+            let config = RateLimitingConfig::load_from_file("config.yaml")?;
+
+            let RateLimitingBackendConfig::Redis { url } = config.backend else {
+                panic!("expected the Redis backend");
+            };
+            assert_eq!(url.as_str(), "redis://localhost:6379/0");
+            assert_eq!(config.login.burst.get(), 3);
+            assert_eq!(config.login.replenish_interval, Duration::minutes(1));
+            assert_eq!(config.registration.burst.get(), 2);
+            assert_eq!(config.registration.replenish_interval, Duration::hours(1));
+            assert_eq!(config.device_code_link.burst.get(), 5);
+            assert_eq!(
+                config.device_code_link.replenish_interval,
+                Duration::minutes(2)
+            );
+            assert_eq!(config.graphql.burst.get(), 20);
+            assert_eq!(config.graphql.replenish_interval, Duration::seconds(2));
+            assert_eq!(
+                config
+                    .tokens
+                    .max_active_sessions_per_client
+                    .map(NonZeroU32::get),
+                Some(1000)
+            );
+            assert!(config.tokens.max_tokens_per_user_per_hour.is_none());
+
+            assert_eq!(config.email.per_address.burst.get(), 2);
+            assert_eq!(
+                config.email.per_address.replenish_interval,
+                Duration::minutes(5)
+            );
+            assert_eq!(config.email.per_user.burst.get(), 10);
+
+            let lockout = config.login_lockout.expect("expected the login lockout");
+            assert_eq!(lockout.user_threshold.get(), 5);
+            assert_eq!(lockout.ip_threshold.get(), 50);
+            assert_eq!(lockout.base_duration, Duration::minutes(1));
+            assert_eq!(lockout.max_duration, Duration::hours(1));
+
+            Ok(())
+        });
+    }
+}
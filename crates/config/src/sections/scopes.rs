@@ -0,0 +1,142 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::{Deref, DerefMut};
+
+use async_trait::async_trait;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use ulid::Ulid;
+
+use super::ConfigurationSection;
+
+fn default_user_consentable() -> bool {
+    true
+}
+
+/// A custom scope which clients can request, on top of the ones built into
+/// the service
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScopeConfig {
+    /// The scope token, as requested by clients
+    pub name: String,
+
+    /// Human-readable description of what the scope grants, shown to users on
+    /// the consent screen
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Whether users can grant this scope to clients, through the
+    /// authorization code and device code grants or a token exchange. If
+    /// `false`, the scope can only be obtained through the client credentials
+    /// grant.
+    #[serde(default = "default_user_consentable")]
+    pub user_consentable: bool,
+
+    /// List of client IDs allowed to request this scope. If not set, any
+    /// client can request it.
+    #[serde(default)]
+    #[schemars(with = "Option<Vec<String>>")]
+    pub clients: Option<Vec<Ulid>>,
+}
+
+/// List of custom scopes
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct ScopesConfig(Vec<ScopeConfig>);
+
+impl Deref for ScopesConfig {
+    type Target = Vec<ScopeConfig>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ScopesConfig {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for ScopesConfig {
+    fn path() -> &'static str {
+        "scopes"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  scopes:
+                    - name: "urn:example:bridge"
+                      description: "Act as the bridge bot"
+                      user_consentable: false
+                      clients:
+                        - 01GFWR28C4KNE04WG3HKXB7C9R
+
+                    - name: "urn:example:read"
+                "#,
+            )?;
+
+            let config = ScopesConfig::load_from_file("config.yaml")?;
+
+            assert_eq!(config.0.len(), 2);
+
+            assert_eq!(config.0[0].name, "urn:example:bridge");
+            assert_eq!(
+                config.0[0].description.as_deref(),
+                Some("Act as the bridge bot")
+            );
+            assert!(!config.0[0].user_consentable);
+            assert_eq!(
+                config.0[0].clients,
+                Some(vec![Ulid::from_str("01GFWR28C4KNE04WG3HKXB7C9R").unwrap()])
+            );
+
+            assert_eq!(config.0[1].name, "urn:example:read");
+            assert_eq!(config.0[1].description, None);
+            assert!(config.0[1].user_consentable);
+            assert_eq!(config.0[1].clients, None);
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::num::NonZeroU32;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{ConfigurationSection, RateLimitQuotaConfig};
+
+/// What backend should be used when sending text messages
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "transport", rename_all = "snake_case")]
+pub enum SmsTransportConfig {
+    /// Don't send text messages anywhere
+    #[default]
+    Blackhole,
+
+    /// Send text messages through the Twilio Programmable Messaging API
+    Twilio {
+        /// The SID of the Twilio account
+        account_sid: String,
+
+        /// The authentication token of the Twilio account
+        auth_token: String,
+
+        /// The phone number to send the messages from, in the E.164 format
+        from: String,
+    },
+
+    /// Send text messages by calling an HTTP endpoint, with a JSON object
+    /// with the `to` and `body` fields
+    Http {
+        /// URL of the endpoint
+        url: Url,
+
+        /// Bearer token to authenticate with on the endpoint
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+}
+
+fn default_rate_limit() -> RateLimitQuotaConfig {
+    RateLimitQuotaConfig {
+        burst: NonZeroU32::new(3).unwrap(),
+        replenish_interval: Duration::minutes(10),
+    }
+}
+
+/// Configuration related to sending text messages
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SmsConfig {
+    /// What backend should be used when sending text messages
+    #[serde(flatten, default)]
+    pub transport: SmsTransportConfig,
+
+    /// Rate limit of the text messages sent to a single phone number
+    #[serde(default = "default_rate_limit")]
+    pub rate_limit: RateLimitQuotaConfig,
+}
+
+impl Default for SmsConfig {
+    fn default() -> Self {
+        Self {
+            transport: SmsTransportConfig::default(),
+            rate_limit: default_rate_limit(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigurationSection for SmsConfig {
+    fn path() -> &'static str {
+        "sms"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Jail;
+
+    use super::*;
+
+    #[test]
+    fn load_config() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "config.yaml",
+                r#"
+                  sms:
+                    transport: twilio
+                    account_sid: AC0123456789abcdef
+                    auth_token: secret
+                    from: "+15555550100"
+                    rate_limit:
+                      burst: 1
+                      replenish_interval: 60
+                "#,
+            )?;
+
+            let config = SmsConfig::load_from_file("config.yaml")?;
+
+            let SmsTransportConfig::Twilio {
+                account_sid, from, ..
+            } = config.transport
+            else {
+                panic!("expected the Twilio transport");
+            };
+            assert_eq!(account_sid, "AC0123456789abcdef");
+            assert_eq!(from, "+15555550100");
+            assert_eq!(config.rate_limit.burst.get(), 1);
+            assert_eq!(config.rate_limit.replenish_interval, Duration::minutes(1));
+
+            Ok(())
+        });
+    }
+}
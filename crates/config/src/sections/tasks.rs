@@ -0,0 +1,203 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::num::NonZeroUsize;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use crate::ConfigurationSection;
+
+fn default_stale_clients_inactivity() -> Duration {
+    Duration::days(90)
+}
+
+/// Configuration of the garbage collection of stale dynamically-registered
+/// clients
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct StaleClientsConfig {
+    /// Whether to delete dynamically-registered clients which have no active
+    /// session and no recent activity. Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Number of seconds without any activity after which a client is
+    /// considered stale. Defaults to 90 days.
+    #[schemars(with = "u64", range(min = 86400))]
+    #[serde(default = "default_stale_clients_inactivity")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub inactivity_period: Duration,
+}
+
+impl Default for StaleClientsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            inactivity_period: default_stale_clients_inactivity(),
+        }
+    }
+}
+
+fn default_key_expiry_warning_period() -> Duration {
+    Duration::days(30)
+}
+
+/// Configuration of the monitoring of the signing keys expiration
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct KeyExpiryConfig {
+    /// Number of seconds before the expiration of a signing key from which
+    /// the worker starts warning about it. Defaults to 30 days.
+    #[schemars(with = "u64")]
+    #[serde(default = "default_key_expiry_warning_period")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub warning_period: Duration,
+}
+
+impl Default for KeyExpiryConfig {
+    fn default() -> Self {
+        Self {
+            warning_period: default_key_expiry_warning_period(),
+        }
+    }
+}
+
+fn default_deleted_users_grace_period() -> Duration {
+    Duration::days(30)
+}
+
+/// Configuration of the purge of soft-deleted users
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct DeletedUsersConfig {
+    /// Number of seconds during which a deleted user can still be restored,
+    /// before its data is erased and it is deactivated on the homeserver.
+    /// Defaults to 30 days.
+    #[schemars(with = "u64")]
+    #[serde(default = "default_deleted_users_grace_period")]
+    #[serde_as(as = "serde_with::DurationSeconds<i64>")]
+    pub grace_period: Duration,
+}
+
+impl Default for DeletedUsersConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: default_deleted_users_grace_period(),
+        }
+    }
+}
+
+fn default_cleanup_min_batch_size() -> NonZeroUsize {
+    NonZeroUsize::new(10).unwrap()
+}
+
+fn default_cleanup_max_batch_size() -> NonZeroUsize {
+    NonZeroUsize::new(1000).unwrap()
+}
+
+fn default_cleanup_target_batch_duration() -> std::time::Duration {
+    std::time::Duration::from_millis(500)
+}
+
+fn default_cleanup_max_pause() -> std::time::Duration {
+    std::time::Duration::from_secs(10)
+}
+
+/// Configuration of the pace of the cleanup jobs
+///
+/// Rows are deleted in batches. The size of the batches and the pause between
+/// them adapt to how long each batch takes and to how busy the database
+/// connection pool is, within the limits set here.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, JsonSchema, Serialize)]
+pub struct CleanupConfig {
+    /// Smallest number of rows deleted in a single batch. Defaults to 10.
+    #[schemars(with = "u32", range(min = 1))]
+    #[serde(default = "default_cleanup_min_batch_size")]
+    pub min_batch_size: NonZeroUsize,
+
+    /// Largest number of rows deleted in a single batch. Defaults to 1000.
+    #[schemars(with = "u32", range(min = 1))]
+    #[serde(default = "default_cleanup_max_batch_size")]
+    pub max_batch_size: NonZeroUsize,
+
+    /// Number of milliseconds a single batch should take. Batches taking
+    /// longer than this get smaller, and batches taking much less get larger.
+    /// Defaults to 500 milliseconds.
+    #[schemars(with = "u64", range(min = 1))]
+    #[serde(default = "default_cleanup_target_batch_duration")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub target_batch_duration: std::time::Duration,
+
+    /// Maximum number of milliseconds to wait between two batches when the
+    /// database is busy. Defaults to 10 seconds.
+    #[schemars(with = "u64")]
+    #[serde(default = "default_cleanup_max_pause")]
+    #[serde_as(as = "serde_with::DurationMilliSeconds<u64>")]
+    pub max_pause: std::time::Duration,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            min_batch_size: default_cleanup_min_batch_size(),
+            max_batch_size: default_cleanup_max_batch_size(),
+            target_batch_duration: default_cleanup_target_batch_duration(),
+            max_pause: default_cleanup_max_pause(),
+        }
+    }
+}
+
+/// Configuration related to the background tasks run by the worker
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema, Serialize)]
+pub struct TasksConfig {
+    /// Garbage collection of stale dynamically-registered clients
+    #[serde(default)]
+    pub stale_clients: StaleClientsConfig,
+
+    /// Monitoring of the signing keys expiration
+    #[serde(default)]
+    pub key_expiry: KeyExpiryConfig,
+
+    /// Purge of the soft-deleted users
+    #[serde(default)]
+    pub deleted_users: DeletedUsersConfig,
+
+    /// Pace of the cleanup jobs
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+}
+
+#[async_trait]
+impl ConfigurationSection for TasksConfig {
+    fn path() -> &'static str {
+        "tasks"
+    }
+
+    async fn generate<R>(_rng: R) -> anyhow::Result<Self>
+    where
+        R: Rng + Send,
+    {
+        Ok(Self::default())
+    }
+
+    fn test() -> Self {
+        Self::default()
+    }
+}
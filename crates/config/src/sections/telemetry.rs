@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::num::NonZeroU16;
+use std::{net::SocketAddr, num::NonZeroU16};
 
 use async_trait::async_trait;
 use rand::Rng;
@@ -50,6 +50,14 @@ fn otlp_endpoint_example() -> &'static str {
     "https://localhost:4317"
 }
 
+fn prometheus_listen_example() -> &'static str {
+    "0.0.0.0:9100"
+}
+
+fn default_prometheus_path() -> String {
+    "/metrics".to_owned()
+}
+
 fn zipkin_collector_endpoint_example() -> &'static str {
     "http://127.0.0.1:9411/api/v2/spans"
 }
@@ -237,9 +245,23 @@ pub enum MetricsExporterConfig {
         endpoint: Option<Url>,
     },
 
-    /// Export metrics via Prometheus. An HTTP listener with the `prometheus`
-    /// resource must be setup to expose the Promethes metrics.
-    Prometheus,
+    /// Export metrics via Prometheus. They are exposed on the HTTP listeners
+    /// with the `prometheus` resource, and on a dedicated address if `listen`
+    /// is set.
+    Prometheus {
+        /// Address on which to expose the metrics for scraping, without
+        /// having to set up an HTTP listener with the `prometheus` resource.
+        ///
+        /// Only `mas-cli server` binds this address. `mas-cli worker` serves
+        /// the metrics only when given its own `--prometheus-listen` address.
+        #[schemars(with = "Option<String>", example = "prometheus_listen_example")]
+        #[serde(default)]
+        listen: Option<SocketAddr>,
+
+        /// Path on which the metrics are exposed on the `listen` address
+        #[serde(default = "default_prometheus_path")]
+        path: String,
+    },
 }
 
 impl Default for MetricsExporterConfig {
@@ -269,6 +291,17 @@ pub struct SentryConfig {
     pub dsn: Option<String>,
 }
 
+/// Configuration related to the redaction of sensitive fields in the logs
+/// and traces
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RedactionConfig {
+    /// Names of span and event fields to redact, on top of the built-in ones
+    /// like `password` or `access_token`. Names are matched ignoring case,
+    /// against the last part of dotted names.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+}
+
 /// Configuration related to sending monitoring data
 #[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
 pub struct TelemetryConfig {
@@ -283,6 +316,11 @@ pub struct TelemetryConfig {
     /// Configuration related to the Sentry integration
     #[serde(default)]
     pub sentry: SentryConfig,
+
+    /// Configuration related to the redaction of sensitive fields in the logs
+    /// and traces
+    #[serde(default)]
+    pub redaction: RedactionConfig,
 }
 
 #[async_trait]
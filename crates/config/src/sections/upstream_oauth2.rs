@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::ops::Deref;
+use std::{collections::BTreeMap, ops::Deref};
 
 use async_trait::async_trait;
 use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
@@ -177,6 +177,46 @@ pub struct EmailImportPreference {
     pub set_email_verification: SetEmailVerification,
 }
 
+/// How the groups of the user should be synchronised with the upstream
+/// provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupsSyncPolicy {
+    /// Don't import the groups
+    #[default]
+    Ignore,
+
+    /// Add the groups claimed by the upstream provider to the user, but never
+    /// remove any
+    Additive,
+
+    /// Make the groups of the user match exactly the ones claimed by the
+    /// upstream provider, removing the others
+    Authoritative,
+}
+
+/// What should be done with the groups attribute
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
+pub struct GroupsImportPreference {
+    /// How to synchronise the groups on each login
+    #[serde(default)]
+    pub sync: GroupsSyncPolicy,
+
+    /// The claim holding the groups, either as a list of strings or as a
+    /// single string
+    ///
+    /// If not provided, the `groups` claim is used
+    #[serde(default)]
+    pub claim: Option<String>,
+
+    /// Map the groups claimed by the upstream provider to MAS group names
+    ///
+    /// If not empty, only the groups listed here are imported
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mapping: BTreeMap<String, String>,
+}
+
 /// How claims should be imported
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, JsonSchema)]
 pub struct ClaimsImports {
@@ -196,6 +236,11 @@ pub struct ClaimsImports {
     /// `email_verified` claims
     #[serde(default)]
     pub email: EmailImportPreference,
+
+    /// Import the groups of the user, like `groups`, `roles` or Active
+    /// Directory group DNs, on each login
+    #[serde(default)]
+    pub groups: GroupsImportPreference,
 }
 
 /// How to discover the provider's configuration
@@ -261,6 +306,12 @@ pub struct Provider {
     ///  - `twitter`
     pub brand_name: Option<String>,
 
+    /// An icon for the provider, as a Matrix Content (`mxc://`) URI. It is
+    /// shown to clients using the legacy Matrix login API, alongside the
+    /// brand identifier.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
     /// The client ID to use when authenticating with the provider
     pub client_id: String,
 
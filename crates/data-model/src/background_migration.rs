@@ -0,0 +1,53 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// A data migration which runs in the background, in batches, while the
+/// service is running
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackgroundMigration {
+    /// The name under which the migration was registered
+    pub name: String,
+
+    /// The position of the last processed row, as understood by the migration
+    pub cursor: Option<String>,
+
+    /// How many rows were processed so far
+    pub processed_rows: u64,
+
+    /// When the migration was registered
+    pub created_at: DateTime<Utc>,
+
+    /// When the last batch ran
+    pub updated_at: Option<DateTime<Utc>>,
+
+    /// When the migration finished processing all the rows
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl BackgroundMigration {
+    /// Whether the migration finished processing all the rows
+    #[must_use]
+    pub fn is_completed(&self) -> bool {
+        self.completed_at.is_some()
+    }
+
+    /// Whether the migration did not process any batch yet
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        self.updated_at.is_none()
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+
+/// A service which shows CAPTCHA challenges and verifies their responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CaptchaService {
+    /// Google reCAPTCHA v2
+    #[serde(rename = "recaptcha_v2")]
+    RecaptchaV2,
+
+    /// Cloudflare Turnstile
+    #[serde(rename = "cloudflare_turnstile")]
+    CloudflareTurnstile,
+
+    /// hCaptcha
+    #[serde(rename = "hcaptcha")]
+    HCaptcha,
+}
+
+impl CaptchaService {
+    /// The name of the form field in which the widget of this service puts
+    /// the response to the challenge
+    #[must_use]
+    pub const fn response_field(self) -> &'static str {
+        match self {
+            Self::RecaptchaV2 => "g-recaptcha-response",
+            Self::CloudflareTurnstile => "cf-turnstile-response",
+            Self::HCaptcha => "h-captcha-response",
+        }
+    }
+
+    /// The endpoint on which responses to the challenges are verified
+    #[must_use]
+    pub const fn verify_url(self) -> &'static str {
+        match self {
+            Self::RecaptchaV2 => "https://www.google.com/recaptcha/api/siteverify",
+            Self::CloudflareTurnstile => {
+                "https://challenges.cloudflare.com/turnstile/v0/siteverify"
+            }
+            Self::HCaptcha => "https://api.hcaptcha.com/siteverify",
+        }
+    }
+}
+
+/// How the registration and login forms are protected by CAPTCHA challenges
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaptchaConfig {
+    /// The service showing and verifying the challenges
+    pub service: CaptchaService,
+
+    /// The public key of the site, used by the widget shown to users
+    pub site_key: String,
+
+    /// The secret key of the site, used to verify the responses
+    #[serde(skip_serializing)]
+    pub secret_key: String,
+}
@@ -16,8 +16,11 @@
 
 use thiserror::Error;
 
+pub(crate) mod background_migration;
+pub(crate) mod captcha;
 pub(crate) mod compat;
 pub(crate) mod oauth2;
+pub(crate) mod session_events;
 pub(crate) mod tokens;
 pub(crate) mod upstream_oauth2;
 pub(crate) mod users;
@@ -28,26 +31,36 @@ pub(crate) mod users;
 pub struct InvalidTransitionError;
 
 pub use self::{
+    background_migration::BackgroundMigration,
+    captcha::{CaptchaConfig, CaptchaService},
     compat::{
         CompatAccessToken, CompatRefreshToken, CompatRefreshTokenState, CompatSession,
         CompatSessionState, CompatSsoLogin, CompatSsoLoginState, Device,
     },
     oauth2::{
-        AuthorizationCode, AuthorizationGrant, AuthorizationGrantStage, Client,
-        InvalidRedirectUriError, JwksOrJwksUri, Pkce, Session, SessionState,
+        AuthorizationCode, AuthorizationGrant, AuthorizationGrantStage, Client, DeviceCodeGrant,
+        DeviceCodeGrantState, InvalidRedirectUriError, JwksOrJwksUri, Pkce,
+        PushedAuthorizationRequest, Session, SessionState,
     },
+    session_events::{SessionEvent, SessionEventKind, SessionEventTarget},
     tokens::{
-        AccessToken, AccessTokenState, RefreshToken, RefreshTokenState, TokenFormatError, TokenType,
+        AccessToken, AccessTokenState, RefreshToken, RefreshTokenLifetimes, RefreshTokenPolicies,
+        RefreshTokenPolicy, RefreshTokenState, TokenFormatError, TokenType,
     },
     upstream_oauth2::{
         UpsreamOAuthProviderSetEmailVerification, UpstreamOAuthAuthorizationSession,
         UpstreamOAuthAuthorizationSessionState, UpstreamOAuthLink, UpstreamOAuthProvider,
         UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderGroupsImportPreference, UpstreamOAuthProviderGroupsSyncPolicy,
         UpstreamOAuthProviderImportAction, UpstreamOAuthProviderImportPreference,
         UpstreamOAuthProviderPkceMode, UpstreamOAuthProviderSubjectPreference,
     },
     users::{
-        Authentication, AuthenticationMethod, BrowserSession, Password, User, UserEmail,
-        UserEmailVerification, UserEmailVerificationState,
+        is_valid_username, Authentication, AuthenticationMethod, BrowserSession,
+        EmailNormalization, Password, SignInSession, TermsOfService, User, UserDataExport,
+        UserEmail, UserEmailVerification, UserEmailVerificationState, UserLoginLink,
+        UserRecoveryCode, UserRecoveryTicket, UserRegistration, UserSignInNotification,
+        UserTermsAcceptance, WebauthnCredential, ACR_PASSWORD, ACR_UPSTREAM_OAUTH2, ACR_WEBAUTHN,
+        SUPPORTED_ACR_VALUES,
     },
 };
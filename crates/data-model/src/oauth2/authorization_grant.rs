@@ -18,7 +18,7 @@ use chrono::{DateTime, Duration, Utc};
 use mas_iana::oauth::PkceCodeChallengeMethod;
 use oauth2_types::{
     pkce::{CodeChallengeError, CodeChallengeMethodExt},
-    requests::ResponseMode,
+    requests::{ClaimsRequest, ResponseMode},
     scope::{Scope, OPENID, PROFILE},
 };
 use rand::{
@@ -30,7 +30,7 @@ use ulid::Ulid;
 use url::Url;
 
 use super::session::Session;
-use crate::InvalidTransitionError;
+use crate::{Authentication, InvalidTransitionError};
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Pkce {
@@ -161,10 +161,14 @@ pub struct AuthorizationGrant {
     pub state: Option<String>,
     pub nonce: Option<String>,
     pub max_age: Option<NonZeroU32>,
+    pub acr_values: Vec<String>,
+    pub claims: Option<ClaimsRequest>,
     pub response_mode: ResponseMode,
     pub response_type_id_token: bool,
     pub created_at: DateTime<Utc>,
     pub requires_consent: bool,
+    pub requires_reauthentication: bool,
+    pub resource: Option<Url>,
 }
 
 impl std::ops::Deref for AuthorizationGrant {
@@ -182,6 +186,22 @@ impl AuthorizationGrant {
         self.created_at - Duration::seconds(max_age.unwrap_or(3600 * 24 * 365))
     }
 
+    /// Whether the given authentication is recent and strong enough to
+    /// fulfill this grant
+    ///
+    /// If the client asked for ACR values which the authentication doesn't
+    /// satisfy, or explicitly asked for the user to authenticate again, only
+    /// an authentication done after the grant was started is accepted, so that
+    /// the user gets a chance to authenticate again.
+    #[must_use]
+    pub fn accepts_authentication(&self, authentication: &Authentication) -> bool {
+        let fresh = authentication.created_at > self.created_at;
+        authentication.created_at > self.max_auth_time()
+            && (fresh
+                || (!self.requires_reauthentication
+                    && authentication.satisfies_acr_values(&self.acr_values)))
+    }
+
     /// Mark the authorization grant as exchanged.
     ///
     /// # Errors
@@ -241,10 +261,64 @@ impl AuthorizationGrant {
             state: Some(Alphanumeric.sample_string(rng, 10)),
             nonce: Some(Alphanumeric.sample_string(rng, 10)),
             max_age: None,
+            acr_values: Vec::new(),
+            claims: None,
             response_mode: ResponseMode::Query,
             response_type_id_token: false,
             created_at: now,
             requires_consent: false,
+            requires_reauthentication: false,
+            resource: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{AuthenticationMethod, ACR_PASSWORD, ACR_UPSTREAM_OAUTH2};
+
+    #[test]
+    fn accepts_authentication() {
+        let now = DateTime::default() + Duration::days(1);
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let mut grant = AuthorizationGrant::sample(now, &mut rng);
+
+        let password = Authentication {
+            id: Ulid::nil(),
+            created_at: now - Duration::hours(1),
+            authentication_method: AuthenticationMethod::Password {
+                user_password_id: Ulid::nil(),
+            },
+        };
+        assert!(grant.accepts_authentication(&password));
+
+        // Too old for the requested max_age
+        grant.max_age = NonZeroU32::new(60);
+        assert!(!grant.accepts_authentication(&password));
+        grant.max_age = None;
+
+        // The requested ACR is satisfied
+        grant.acr_values = vec![ACR_PASSWORD.to_owned()];
+        assert!(grant.accepts_authentication(&password));
+
+        // The requested ACR isn't satisfied, so the user has to authenticate again
+        grant.acr_values = vec![ACR_UPSTREAM_OAUTH2.to_owned()];
+        assert!(!grant.accepts_authentication(&password));
+
+        // … unless they did so after the grant was started
+        let fresh = Authentication {
+            created_at: now + Duration::minutes(1),
+            ..password.clone()
+        };
+        assert!(grant.accepts_authentication(&fresh));
+        grant.acr_values = Vec::new();
+
+        // The client asked for the user to authenticate again
+        grant.requires_reauthentication = true;
+        assert!(!grant.accepts_authentication(&password));
+        assert!(grant.accepts_authentication(&fresh));
+    }
+}
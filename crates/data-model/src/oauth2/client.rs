@@ -14,11 +14,13 @@
 
 use chrono::{DateTime, Utc};
 use mas_iana::{
-    jose::JsonWebSignatureAlg,
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
     oauth::{OAuthAuthorizationEndpointResponseType, OAuthClientAuthenticationMethod},
 };
 use mas_jose::jwk::PublicJsonWebKeySet;
-use oauth2_types::{oidc::ApplicationType, requests::GrantType};
+use oauth2_types::{
+    oidc::ApplicationType, registration::DEFAULT_ENCRYPTION_ENC_ALGORITHM, requests::GrantType,
+};
 use rand::RngCore;
 use serde::Serialize;
 use thiserror::Error;
@@ -94,9 +96,57 @@ pub struct Client {
     /// and client_secret_jwt authentication methods
     pub token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
 
+    /// JWS alg algorithm that MUST be used for signing request objects sent
+    /// to the authorization endpoint. If not set, any supported algorithm
+    /// may be used
+    pub request_object_signing_alg: Option<JsonWebSignatureAlg>,
+
+    /// `request_uri` values pre-registered by the client. Request objects are
+    /// only fetched from those
+    pub request_uris: Vec<Url>,
+
+    /// JWE alg algorithm REQUIRED for encrypting the ID Token issued to this
+    /// Client. If not set, the ID Token is not encrypted
+    pub id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// JWE enc algorithm REQUIRED for encrypting the ID Token issued to this
+    /// Client
+    pub id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+
+    /// JWE alg algorithm REQUIRED for encrypting UserInfo Responses. If not
+    /// set, the UserInfo Response is not encrypted
+    pub userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+
+    /// JWE enc algorithm REQUIRED for encrypting UserInfo Responses
+    pub userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+
     /// URI using the https scheme that a third party can use to initiate a
     /// login by the RP
     pub initiate_login_uri: Option<Url>,
+
+    /// Array of URLs to which the End-User can be redirected after an
+    /// RP-initiated logout
+    pub post_logout_redirect_uris: Vec<Url>,
+
+    /// URL to which logout tokens are sent when a session of this client
+    /// ends, as defined by OpenID Connect Back-Channel Logout
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the logout tokens sent to the `backchannel_logout_uri` must
+    /// include the `sid` claim
+    pub backchannel_logout_session_required: bool,
+
+    /// Whether the client must use pushed authorization requests to start an
+    /// authorization flow
+    pub require_pushed_authorization_requests: bool,
+
+    /// Expected subject distinguished name of the certificate the client
+    /// authenticates with, when using the `tls_client_auth` method
+    pub tls_client_auth_subject_dn: Option<String>,
+
+    /// Whether the access tokens issued to this client are bound to the
+    /// certificate it used on the token endpoint
+    pub tls_client_certificate_bound_access_tokens: bool,
 }
 
 #[derive(Debug, Error)]
@@ -134,6 +184,45 @@ impl Client {
         }
     }
 
+    /// Returns `true` if the given URL is a registered
+    /// `post_logout_redirect_uri` of this client
+    #[must_use]
+    pub fn has_post_logout_redirect_uri(&self, uri: &Url) -> bool {
+        uri_matches_one_of(uri, &self.post_logout_redirect_uris)
+    }
+
+    /// The `alg` and `enc` algorithms used to encrypt the ID tokens issued to
+    /// this client, if they have to be encrypted
+    #[must_use]
+    pub fn id_token_encrypted_response(
+        &self,
+    ) -> Option<(&JsonWebEncryptionAlg, &JsonWebEncryptionEnc)> {
+        self.id_token_encrypted_response_alg.as_ref().map(|alg| {
+            (
+                alg,
+                self.id_token_encrypted_response_enc
+                    .as_ref()
+                    .unwrap_or(DEFAULT_ENCRYPTION_ENC_ALGORITHM),
+            )
+        })
+    }
+
+    /// The `alg` and `enc` algorithms used to encrypt the user info responses
+    /// sent to this client, if they have to be encrypted
+    #[must_use]
+    pub fn userinfo_encrypted_response(
+        &self,
+    ) -> Option<(&JsonWebEncryptionAlg, &JsonWebEncryptionEnc)> {
+        self.userinfo_encrypted_response_alg.as_ref().map(|alg| {
+            (
+                alg,
+                self.userinfo_encrypted_response_enc
+                    .as_ref()
+                    .unwrap_or(DEFAULT_ENCRYPTION_ENC_ALGORITHM),
+            )
+        })
+    }
+
     #[doc(hidden)]
     pub fn samples(now: DateTime<Utc>, rng: &mut impl RngCore) -> Vec<Client> {
         vec![
@@ -158,11 +247,28 @@ impl Client {
                 initiate_login_uri: Some(
                     Url::parse("https://client1.example.com/initiate-login").unwrap(),
                 ),
+                post_logout_redirect_uris: vec![Url::parse(
+                    "https://client1.example.com/logged-out",
+                )
+                .unwrap()],
+                backchannel_logout_uri: Some(
+                    Url::parse("https://client1.example.com/backchannel-logout").unwrap(),
+                ),
+                backchannel_logout_session_required: true,
                 token_endpoint_auth_method: Some(OAuthClientAuthenticationMethod::None),
                 token_endpoint_auth_signing_alg: None,
+                request_object_signing_alg: None,
+                request_uris: Vec::new(),
+                id_token_encrypted_response_alg: None,
+                id_token_encrypted_response_enc: None,
+                userinfo_encrypted_response_alg: None,
+                userinfo_encrypted_response_enc: None,
                 id_token_signed_response_alg: None,
                 userinfo_signed_response_alg: None,
                 jwks: None,
+                require_pushed_authorization_requests: false,
+                tls_client_auth_subject_dn: None,
+                tls_client_certificate_bound_access_tokens: false,
             },
             // Another client without any URIs set
             Self {
@@ -180,11 +286,23 @@ impl Client {
                 tos_uri: None,
                 policy_uri: None,
                 initiate_login_uri: None,
+                post_logout_redirect_uris: Vec::new(),
+                backchannel_logout_uri: None,
+                backchannel_logout_session_required: false,
                 token_endpoint_auth_method: None,
                 token_endpoint_auth_signing_alg: None,
+                request_object_signing_alg: None,
+                request_uris: Vec::new(),
+                id_token_encrypted_response_alg: None,
+                id_token_encrypted_response_enc: None,
+                userinfo_encrypted_response_alg: None,
+                userinfo_encrypted_response_enc: None,
                 id_token_signed_response_alg: None,
                 userinfo_signed_response_alg: None,
                 jwks: None,
+                require_pushed_authorization_requests: false,
+                tls_client_auth_subject_dn: None,
+                tls_client_certificate_bound_access_tokens: false,
             },
         ]
     }
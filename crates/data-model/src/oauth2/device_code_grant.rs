@@ -0,0 +1,255 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use oauth2_types::scope::Scope;
+use serde::Serialize;
+use ulid::Ulid;
+
+use super::session::Session;
+use crate::{BrowserSession, InvalidTransitionError};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum DeviceCodeGrantState {
+    /// The device code grant is waiting for the user to approve or reject it
+    #[default]
+    Pending,
+
+    /// The user approved the device code grant, and the device can now
+    /// exchange it for tokens
+    Fulfilled {
+        browser_session_id: Ulid,
+        fulfilled_at: DateTime<Utc>,
+    },
+
+    /// The user rejected the device code grant
+    Rejected {
+        browser_session_id: Ulid,
+        rejected_at: DateTime<Utc>,
+    },
+
+    /// The device exchanged the device code grant for tokens
+    Exchanged {
+        browser_session_id: Ulid,
+        session_id: Ulid,
+        fulfilled_at: DateTime<Utc>,
+        exchanged_at: DateTime<Utc>,
+    },
+}
+
+impl DeviceCodeGrantState {
+    /// Returns `true` if the device code grant state is [`Pending`].
+    ///
+    /// [`Pending`]: DeviceCodeGrantState::Pending
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Pending)
+    }
+
+    /// Returns `true` if the device code grant state is [`Fulfilled`].
+    ///
+    /// [`Fulfilled`]: DeviceCodeGrantState::Fulfilled
+    #[must_use]
+    pub fn is_fulfilled(&self) -> bool {
+        matches!(self, Self::Fulfilled { .. })
+    }
+
+    /// Returns `true` if the device code grant state is [`Rejected`].
+    ///
+    /// [`Rejected`]: DeviceCodeGrantState::Rejected
+    #[must_use]
+    pub fn is_rejected(&self) -> bool {
+        matches!(self, Self::Rejected { .. })
+    }
+
+    /// Returns `true` if the device code grant state is [`Exchanged`].
+    ///
+    /// [`Exchanged`]: DeviceCodeGrantState::Exchanged
+    #[must_use]
+    pub fn is_exchanged(&self) -> bool {
+        matches!(self, Self::Exchanged { .. })
+    }
+
+    /// Transition the device code grant from [`Pending`] to [`Fulfilled`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant state is not [`Pending`].
+    ///
+    /// [`Pending`]: DeviceCodeGrantState::Pending
+    /// [`Fulfilled`]: DeviceCodeGrantState::Fulfilled
+    pub fn fulfill(
+        self,
+        browser_session: &BrowserSession,
+        fulfilled_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Pending => Ok(Self::Fulfilled {
+                browser_session_id: browser_session.id,
+                fulfilled_at,
+            }),
+            _ => Err(InvalidTransitionError),
+        }
+    }
+
+    /// Transition the device code grant from [`Pending`] to [`Rejected`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant state is not [`Pending`].
+    ///
+    /// [`Pending`]: DeviceCodeGrantState::Pending
+    /// [`Rejected`]: DeviceCodeGrantState::Rejected
+    pub fn reject(
+        self,
+        browser_session: &BrowserSession,
+        rejected_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Pending => Ok(Self::Rejected {
+                browser_session_id: browser_session.id,
+                rejected_at,
+            }),
+            _ => Err(InvalidTransitionError),
+        }
+    }
+
+    /// Transition the device code grant from [`Fulfilled`] to [`Exchanged`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant state is not [`Fulfilled`].
+    ///
+    /// [`Fulfilled`]: DeviceCodeGrantState::Fulfilled
+    /// [`Exchanged`]: DeviceCodeGrantState::Exchanged
+    pub fn exchange(
+        self,
+        session: &Session,
+        exchanged_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Fulfilled {
+                browser_session_id,
+                fulfilled_at,
+            } => Ok(Self::Exchanged {
+                browser_session_id,
+                session_id: session.id,
+                fulfilled_at,
+                exchanged_at,
+            }),
+            _ => Err(InvalidTransitionError),
+        }
+    }
+}
+
+/// A grant issued by the device authorization endpoint, which a user has to
+/// approve from another device before the requesting device can get tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceCodeGrant {
+    pub id: Ulid,
+    #[serde(flatten)]
+    pub state: DeviceCodeGrantState,
+
+    /// The client which requested the device code grant
+    pub client_id: Ulid,
+
+    /// The scope requested by the client
+    pub scope: Scope,
+
+    /// The short code the user types on the verification page
+    pub user_code: String,
+
+    /// The opaque code the device uses to poll the token endpoint
+    pub device_code: String,
+
+    pub created_at: DateTime<Utc>,
+
+    /// After this time, the device code grant can't be approved or exchanged
+    /// anymore
+    pub expires_at: DateTime<Utc>,
+
+    /// The IP address of the device which requested the grant
+    pub ip_address: Option<IpAddr>,
+
+    /// The user agent of the device which requested the grant
+    pub user_agent: Option<String>,
+}
+
+impl std::ops::Deref for DeviceCodeGrant {
+    type Target = DeviceCodeGrantState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl DeviceCodeGrant {
+    /// Returns `true` if the device code grant can't be used anymore because
+    /// it is past its expiration time.
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Mark the device code grant as fulfilled by the given browser session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant is not [`Pending`].
+    ///
+    /// [`Pending`]: DeviceCodeGrantState::Pending
+    pub fn fulfill(
+        mut self,
+        browser_session: &BrowserSession,
+        fulfilled_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        self.state = self.state.fulfill(browser_session, fulfilled_at)?;
+        Ok(self)
+    }
+
+    /// Mark the device code grant as rejected by the given browser session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant is not [`Pending`].
+    ///
+    /// [`Pending`]: DeviceCodeGrantState::Pending
+    pub fn reject(
+        mut self,
+        browser_session: &BrowserSession,
+        rejected_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        self.state = self.state.reject(browser_session, rejected_at)?;
+        Ok(self)
+    }
+
+    /// Mark the device code grant as exchanged for the given session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device code grant is not [`Fulfilled`].
+    ///
+    /// [`Fulfilled`]: DeviceCodeGrantState::Fulfilled
+    pub fn exchange(
+        mut self,
+        session: &Session,
+        exchanged_at: DateTime<Utc>,
+    ) -> Result<Self, InvalidTransitionError> {
+        self.state = self.state.exchange(session, exchanged_at)?;
+        Ok(self)
+    }
+}
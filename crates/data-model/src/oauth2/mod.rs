@@ -14,10 +14,14 @@
 
 mod authorization_grant;
 mod client;
+mod device_code_grant;
+mod pushed_authorization_request;
 mod session;
 
 pub use self::{
     authorization_grant::{AuthorizationCode, AuthorizationGrant, AuthorizationGrantStage, Pkce},
     client::{Client, InvalidRedirectUriError, JwksOrJwksUri},
+    device_code_grant::{DeviceCodeGrant, DeviceCodeGrantState},
+    pushed_authorization_request::PushedAuthorizationRequest,
     session::{Session, SessionState},
 };
@@ -0,0 +1,119 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use ulid::Ulid;
+
+use crate::InvalidTransitionError;
+
+/// An authorization request which a client pushed to the pushed authorization
+/// request endpoint, as per RFC 9126. The client then starts the
+/// authorization flow by referring to it with a `request_uri`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PushedAuthorizationRequest {
+    pub id: Ulid,
+    pub client_id: Ulid,
+
+    /// The parameters of the authorization request, without the client
+    /// credentials
+    pub parameters: BTreeMap<String, String>,
+
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+
+    /// When the request was used to start an authorization flow. A pushed
+    /// request can only be used once.
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl PushedAuthorizationRequest {
+    /// The prefix of the `request_uri` given to clients
+    pub const REQUEST_URI_PREFIX: &'static str = "urn:ietf:params:oauth:request_uri:";
+
+    /// The `request_uri` the client uses to refer to this request
+    #[must_use]
+    pub fn request_uri(&self) -> String {
+        format!("{}{}", Self::REQUEST_URI_PREFIX, self.id)
+    }
+
+    /// Extract the ID of a pushed request from a `request_uri`, if it is one
+    /// we issued
+    #[must_use]
+    pub fn id_from_request_uri(request_uri: &str) -> Option<Ulid> {
+        request_uri
+            .strip_prefix(Self::REQUEST_URI_PREFIX)?
+            .parse()
+            .ok()
+    }
+
+    /// Returns `true` if the request can still be used to start an
+    /// authorization flow
+    #[must_use]
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.consumed_at.is_none() && now < self.expires_at
+    }
+
+    /// Mark the request as used
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request was already used
+    pub fn consume(mut self, consumed_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        if self.consumed_at.is_some() {
+            return Err(InvalidTransitionError);
+        }
+
+        self.consumed_at = Some(consumed_at);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_uri_roundtrip() {
+        let request = PushedAuthorizationRequest {
+            id: Ulid::nil(),
+            client_id: Ulid::nil(),
+            parameters: BTreeMap::new(),
+            created_at: DateTime::default(),
+            expires_at: DateTime::default(),
+            consumed_at: None,
+        };
+
+        let request_uri = request.request_uri();
+        assert_eq!(
+            request_uri,
+            "urn:ietf:params:oauth:request_uri:00000000000000000000000000"
+        );
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri(&request_uri),
+            Some(request.id)
+        );
+
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri("https://example.com/request"),
+            None
+        );
+        assert_eq!(
+            PushedAuthorizationRequest::id_from_request_uri("urn:ietf:params:oauth:request_uri:x"),
+            None
+        );
+    }
+}
@@ -15,9 +15,10 @@
 use std::net::IpAddr;
 
 use chrono::{DateTime, Utc};
-use oauth2_types::scope::Scope;
+use oauth2_types::{requests::ClaimsRequest, scope::Scope};
 use serde::Serialize;
 use ulid::Ulid;
+use url::Url;
 
 use crate::InvalidTransitionError;
 
@@ -77,6 +78,12 @@ pub struct Session {
     pub scope: Scope,
     pub last_active_at: Option<DateTime<Utc>>,
     pub last_active_ip: Option<IpAddr>,
+    pub dpop_jkt: Option<String>,
+    pub certificate_thumbprint: Option<String>,
+    pub parent_session_id: Option<Ulid>,
+    pub resource: Option<Url>,
+    pub claims: Option<ClaimsRequest>,
+    pub human_name: Option<String>,
 }
 
 impl std::ops::Deref for Session {
@@ -101,4 +108,24 @@ impl Session {
         self.state = self.state.finish(finished_at)?;
         Ok(self)
     }
+
+    /// Returns `true` if the tokens of this session are bound to a DPoP key.
+    #[must_use]
+    pub fn is_dpop_bound(&self) -> bool {
+        self.dpop_jkt.is_some()
+    }
+
+    /// Returns `true` if the tokens of this session are bound to a client
+    /// certificate.
+    #[must_use]
+    pub fn is_certificate_bound(&self) -> bool {
+        self.certificate_thumbprint.is_some()
+    }
+
+    /// Returns `true` if this session was derived from another one through
+    /// the token exchange grant.
+    #[must_use]
+    pub fn is_derived(&self) -> bool {
+        self.parent_session_id.is_some()
+    }
 }
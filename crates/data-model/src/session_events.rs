@@ -0,0 +1,50 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use ulid::Ulid;
+
+/// What happened to a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventKind {
+    /// The session was started
+    Created,
+
+    /// The session was ended
+    Ended,
+
+    /// The user authenticated again in the session
+    AuthenticationAdded,
+}
+
+/// The session an event happened to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEventTarget {
+    /// A browser session
+    Browser(Ulid),
+
+    /// An OAuth 2.0 session
+    OAuth2(Ulid),
+
+    /// A compatibility session
+    Compat(Ulid),
+}
+
+/// Something which happened to one of the sessions of a user, broadcasted so
+/// that clients can be updated live
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionEvent {
+    pub kind: SessionEventKind,
+    pub session: SessionEventTarget,
+    pub user_id: Ulid,
+}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use chrono::{DateTime, Utc};
+use std::{collections::HashMap, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
 use crc::{Crc, CRC_32_ISO_HDLC};
 use mas_iana::oauth::OAuthTokenTypeHint;
 use rand::{distributions::Alphanumeric, Rng, RngCore};
@@ -117,6 +119,13 @@ pub enum RefreshTokenState {
     Valid,
     Consumed {
         consumed_at: DateTime<Utc>,
+
+        /// The refresh token issued in place of this one, if it is known
+        next_refresh_token_id: Option<Ulid>,
+
+        /// When this refresh token was accepted again during the reuse grace
+        /// period, which can only happen once
+        grace_used_at: Option<DateTime<Utc>>,
     },
 }
 
@@ -126,13 +135,43 @@ impl RefreshTokenState {
     /// # Errors
     ///
     /// Returns an error if the refresh token is already consumed.
-    fn consume(self, consumed_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+    fn consume(
+        self,
+        consumed_at: DateTime<Utc>,
+        next_refresh_token_id: Ulid,
+    ) -> Result<Self, InvalidTransitionError> {
         match self {
-            Self::Valid => Ok(Self::Consumed { consumed_at }),
+            Self::Valid => Ok(Self::Consumed {
+                consumed_at,
+                next_refresh_token_id: Some(next_refresh_token_id),
+                grace_used_at: None,
+            }),
             Self::Consumed { .. } => Err(InvalidTransitionError),
         }
     }
 
+    /// Record that the consumed refresh token was accepted again during the
+    /// reuse grace period, returning a new state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the refresh token is not consumed, or if it was
+    /// already accepted again.
+    fn use_grace(self, used_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        match self {
+            Self::Consumed {
+                consumed_at,
+                next_refresh_token_id,
+                grace_used_at: None,
+            } => Ok(Self::Consumed {
+                consumed_at,
+                next_refresh_token_id,
+                grace_used_at: Some(used_at),
+            }),
+            Self::Valid | Self::Consumed { .. } => Err(InvalidTransitionError),
+        }
+    }
+
     /// Returns `true` if the refresh token state is [`Valid`].
     ///
     /// [`Valid`]: RefreshTokenState::Valid
@@ -176,13 +215,151 @@ impl RefreshToken {
 
     /// Consumes the refresh token and returns the consumed token.
     ///
+    /// # Parameters
+    ///
+    /// * `consumed_at` - The time at which the refresh token was consumed
+    /// * `next_refresh_token` - The refresh token issued in place of this one
+    ///
     /// # Errors
     ///
     /// Returns an error if the refresh token is already consumed.
-    pub fn consume(mut self, consumed_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
-        self.state = self.state.consume(consumed_at)?;
+    pub fn consume(
+        mut self,
+        consumed_at: DateTime<Utc>,
+        next_refresh_token: &RefreshToken,
+    ) -> Result<Self, InvalidTransitionError> {
+        self.state = self.state.consume(consumed_at, next_refresh_token.id)?;
+        Ok(self)
+    }
+
+    /// Record that the consumed refresh token was accepted again during the
+    /// reuse grace period, and return the updated token.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the refresh token is not consumed, or if it was
+    /// already accepted again.
+    pub fn use_grace(mut self, used_at: DateTime<Utc>) -> Result<Self, InvalidTransitionError> {
+        self.state = self.state.use_grace(used_at)?;
         Ok(self)
     }
+
+    /// The refresh token issued in place of this one, if this one was
+    /// consumed less than `grace_period` before `now` and wasn't accepted
+    /// again already
+    #[must_use]
+    pub fn next_refresh_token_id_in_grace(
+        &self,
+        now: DateTime<Utc>,
+        grace_period: Duration,
+    ) -> Option<Ulid> {
+        match self.state {
+            RefreshTokenState::Consumed {
+                consumed_at,
+                next_refresh_token_id: Some(next_refresh_token_id),
+                grace_used_at: None,
+            } if now < consumed_at + grace_period => Some(next_refresh_token_id),
+            _ => None,
+        }
+    }
+}
+
+/// How long refresh tokens can be used for, on top of being single-use
+///
+/// Once a refresh token expires, the session it belongs to can't be refreshed
+/// anymore, forcing the user to authenticate again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshTokenLifetimes {
+    /// Refresh tokens expire if the session wasn't used for that long
+    pub inactivity: Option<Duration>,
+
+    /// Refresh tokens expire once the session is that old, whatever its
+    /// activity
+    pub absolute: Option<Duration>,
+}
+
+impl RefreshTokenLifetimes {
+    /// When the refresh tokens of a session started at `created_at` and last
+    /// used at `last_active_at` stop being valid, if ever
+    #[must_use]
+    pub fn expires_at(
+        &self,
+        created_at: DateTime<Utc>,
+        last_active_at: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let inactivity = self.inactivity.map(|ttl| last_active_at + ttl);
+        let absolute = self.absolute.map(|ttl| created_at + ttl);
+
+        match (inactivity, absolute) {
+            (Some(inactivity), Some(absolute)) => Some(inactivity.min(absolute)),
+            (inactivity, absolute) => inactivity.or(absolute),
+        }
+    }
+
+    /// Whether the refresh tokens of a session started at `created_at` and
+    /// last used at `last_active_at` expired
+    #[must_use]
+    pub fn is_expired(
+        &self,
+        now: DateTime<Utc>,
+        created_at: DateTime<Utc>,
+        last_active_at: DateTime<Utc>,
+    ) -> bool {
+        self.expires_at(created_at, last_active_at)
+            .is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+/// How refresh tokens behave when they are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshTokenPolicy {
+    /// Whether a refresh token is consumed when used, a new one being issued
+    /// in its place. Otherwise, the same refresh token can be used again.
+    pub rotation: bool,
+
+    /// When refresh tokens expire
+    pub lifetimes: RefreshTokenLifetimes,
+
+    /// Whether using a refresh token which was already consumed ends the
+    /// whole session it belongs to
+    pub revoke_session_on_reuse: bool,
+
+    /// How long after being consumed a refresh token can still be used once,
+    /// in which case the tokens issued in its place are returned again. This
+    /// lets clients which lost the response to a refresh retry it.
+    pub reuse_grace_period: Option<Duration>,
+}
+
+impl Default for RefreshTokenPolicy {
+    fn default() -> Self {
+        Self {
+            rotation: true,
+            lifetimes: RefreshTokenLifetimes::default(),
+            revoke_session_on_reuse: false,
+            reuse_grace_period: None,
+        }
+    }
+}
+
+/// The refresh token policy of the server, with per-client overrides
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RefreshTokenPolicies {
+    /// The policy of clients without an override
+    pub default: RefreshTokenPolicy,
+
+    /// The policies of clients which override the default one
+    pub clients: Arc<HashMap<Ulid, RefreshTokenPolicy>>,
+}
+
+impl RefreshTokenPolicies {
+    /// The refresh token policy which applies to the given client
+    #[must_use]
+    pub fn for_client(&self, client_id: Ulid) -> RefreshTokenPolicy {
+        self.clients
+            .get(&client_id)
+            .copied()
+            .unwrap_or(self.default)
+    }
 }
 
 /// Type of token to generate or validate
@@ -375,6 +552,7 @@ pub enum TokenFormatError {
 mod tests {
     use std::collections::HashSet;
 
+    use chrono::TimeZone;
     use rand::thread_rng;
 
     use super::*;
@@ -434,4 +612,108 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_refresh_token_lifetimes() {
+        let created_at = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let last_active_at = created_at + Duration::days(10);
+
+        let lifetimes = RefreshTokenLifetimes::default();
+        assert_eq!(lifetimes.expires_at(created_at, last_active_at), None);
+        assert!(!lifetimes.is_expired(
+            created_at + Duration::days(1000),
+            created_at,
+            last_active_at
+        ));
+
+        let lifetimes = RefreshTokenLifetimes {
+            inactivity: Some(Duration::days(7)),
+            absolute: None,
+        };
+        assert_eq!(
+            lifetimes.expires_at(created_at, last_active_at),
+            Some(created_at + Duration::days(17))
+        );
+        assert!(!lifetimes.is_expired(created_at + Duration::days(16), created_at, last_active_at));
+        assert!(lifetimes.is_expired(created_at + Duration::days(17), created_at, last_active_at));
+
+        // The earliest of the two wins
+        let lifetimes = RefreshTokenLifetimes {
+            inactivity: Some(Duration::days(7)),
+            absolute: Some(Duration::days(15)),
+        };
+        assert_eq!(
+            lifetimes.expires_at(created_at, last_active_at),
+            Some(created_at + Duration::days(15))
+        );
+    }
+
+    #[test]
+    fn test_refresh_token_policies() {
+        let client_id = Ulid::from_parts(1, 1);
+        let other_client_id = Ulid::from_parts(2, 2);
+        let overridden = RefreshTokenPolicy {
+            rotation: false,
+            lifetimes: RefreshTokenLifetimes {
+                inactivity: Some(Duration::days(7)),
+                absolute: None,
+            },
+            revoke_session_on_reuse: true,
+            reuse_grace_period: None,
+        };
+
+        let policies = RefreshTokenPolicies {
+            default: RefreshTokenPolicy::default(),
+            clients: Arc::new(HashMap::from([(client_id, overridden)])),
+        };
+
+        assert_eq!(policies.for_client(client_id), overridden);
+        assert_eq!(
+            policies.for_client(other_client_id),
+            RefreshTokenPolicy::default()
+        );
+    }
+
+    #[test]
+    fn test_refresh_token_reuse_grace() {
+        let created_at = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        let token = RefreshToken {
+            id: Ulid::from_parts(1, 1),
+            state: RefreshTokenState::Valid,
+            refresh_token: "mar_abc".to_owned(),
+            session_id: Ulid::from_parts(2, 2),
+            created_at,
+            access_token_id: None,
+        };
+        let next_token = RefreshToken {
+            id: Ulid::from_parts(3, 3),
+            ..token.clone()
+        };
+        let grace_period = Duration::seconds(30);
+
+        // Valid tokens don't need a grace period
+        assert_eq!(
+            token.next_refresh_token_id_in_grace(created_at, grace_period),
+            None
+        );
+
+        let consumed_at = created_at + Duration::minutes(1);
+        let token = token.consume(consumed_at, &next_token).unwrap();
+        assert_eq!(
+            token.next_refresh_token_id_in_grace(consumed_at + Duration::seconds(29), grace_period),
+            Some(next_token.id)
+        );
+        assert_eq!(
+            token.next_refresh_token_id_in_grace(consumed_at + Duration::seconds(30), grace_period),
+            None
+        );
+
+        // The grace period can only be used once
+        let token = token.use_grace(consumed_at).unwrap();
+        assert_eq!(
+            token.next_refresh_token_id_in_grace(consumed_at, grace_period),
+            None
+        );
+        assert!(token.use_grace(consumed_at).is_err());
+    }
 }
@@ -21,6 +21,8 @@ pub use self::{
     provider::{
         ClaimsImports as UpstreamOAuthProviderClaimsImports,
         DiscoveryMode as UpstreamOAuthProviderDiscoveryMode,
+        GroupsImportPreference as UpstreamOAuthProviderGroupsImportPreference,
+        GroupsSyncPolicy as UpstreamOAuthProviderGroupsSyncPolicy,
         ImportAction as UpstreamOAuthProviderImportAction,
         ImportPreference as UpstreamOAuthProviderImportPreference,
         PkceMode as UpstreamOAuthProviderPkceMode,
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
 use oauth2_types::scope::Scope;
@@ -130,6 +132,7 @@ pub struct UpstreamOAuthProvider {
     pub issuer: String,
     pub human_name: Option<String>,
     pub brand_name: Option<String>,
+    pub icon: Option<String>,
     pub discovery_mode: DiscoveryMode,
     pub pkce_mode: PkceMode,
     pub jwks_uri_override: Option<Url>,
@@ -186,6 +189,9 @@ pub struct ClaimsImports {
 
     #[serde(default)]
     pub verify_email: SetEmailVerification,
+
+    #[serde(default)]
+    pub groups: GroupsImportPreference,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -253,3 +259,52 @@ impl ImportAction {
         }
     }
 }
+
+/// How to synchronise the groups of a user with the ones claimed by the
+/// upstream provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupsSyncPolicy {
+    /// Don't import the groups
+    #[default]
+    Ignore,
+
+    /// Add the claimed groups to the user, but never remove any
+    Additive,
+
+    /// Make the groups of the user match exactly the claimed groups
+    Authoritative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub struct GroupsImportPreference {
+    #[serde(default)]
+    pub sync: GroupsSyncPolicy,
+
+    /// The claim holding the groups, defaults to `groups`
+    #[serde(default)]
+    pub claim: Option<String>,
+
+    /// Map upstream group values to MAS group names. When not empty, only the
+    /// groups listed here are imported
+    #[serde(default)]
+    pub mapping: BTreeMap<String, String>,
+}
+
+impl GroupsImportPreference {
+    #[must_use]
+    pub fn claim(&self) -> &str {
+        self.claim.as_deref().unwrap_or("groups")
+    }
+
+    /// Get the MAS group name for an upstream group value, if it should be
+    /// imported
+    #[must_use]
+    pub fn map<'a>(&'a self, upstream_group: &'a str) -> Option<&'a str> {
+        if self.mapping.is_empty() {
+            Some(upstream_group)
+        } else {
+            self.mapping.get(upstream_group).map(String::as_str)
+        }
+    }
+}
@@ -18,6 +18,39 @@ use chrono::{DateTime, Duration, Utc};
 use rand::{Rng, SeedableRng};
 use serde::Serialize;
 use ulid::Ulid;
+use url::Url;
+
+fn valid_username_character(c: char) -> bool {
+    c.is_ascii_lowercase()
+        || c.is_ascii_digit()
+        || c == '='
+        || c == '_'
+        || c == '-'
+        || c == '.'
+        || c == '/'
+        || c == '+'
+}
+
+/// Check that a username is a valid Matrix localpart, which doesn't start
+/// with an underscore
+#[must_use]
+pub fn is_valid_username(username: &str) -> bool {
+    if username.is_empty() || username.len() > 255 {
+        return false;
+    }
+
+    // Should not start with an underscore
+    if username.get(0..1) == Some("_") {
+        return false;
+    }
+
+    // Should only contain valid characters
+    if !username.chars().all(valid_username_character) {
+        return false;
+    }
+
+    true
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct User {
@@ -28,13 +61,62 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub locked_at: Option<DateTime<Utc>>,
     pub can_request_admin: bool,
+    pub is_service_account: bool,
+    pub locale: Option<String>,
+    pub password_reset_required_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub purged_at: Option<DateTime<Utc>>,
+    pub compat_password_deprecated_at: Option<DateTime<Utc>>,
 }
 
 impl User {
-    /// Returns `true` unless the user is locked.
+    /// Returns `true` unless the user is locked or deleted.
     #[must_use]
     pub fn is_valid(&self) -> bool {
-        self.locked_at.is_none()
+        self.locked_at.is_none() && self.deleted_at.is_none()
+    }
+
+    /// Returns `true` if the user was deleted, even if it can still be
+    /// restored.
+    #[must_use]
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// Returns `true` if the data of the deleted user was erased, meaning it
+    /// can no longer be restored.
+    #[must_use]
+    pub fn is_purged(&self) -> bool {
+        self.purged_at.is_some()
+    }
+
+    /// Returns `true` if the user is allowed to log in interactively, i.e. it
+    /// is not a service account.
+    #[must_use]
+    pub fn can_login_interactively(&self) -> bool {
+        !self.is_service_account
+    }
+
+    /// Returns `true` if an administrator required the user to reset their
+    /// password.
+    #[must_use]
+    pub fn password_reset_required(&self) -> bool {
+        self.password_reset_required_at.is_some()
+    }
+
+    /// Returns `true` if the password login of the user on the compatibility
+    /// layer is being deprecated, either in its warning period or already
+    /// blocked.
+    #[must_use]
+    pub fn compat_password_deprecated(&self) -> bool {
+        self.compat_password_deprecated_at.is_some()
+    }
+
+    /// Returns `true` if the given email address is the primary one of the
+    /// user, which is the one shared with clients and used to contact them.
+    #[must_use]
+    pub fn is_primary_email(&self, user_email: &UserEmail) -> bool {
+        self.primary_user_email_id == Some(user_email.id)
     }
 }
 
@@ -50,6 +132,12 @@ impl User {
             created_at: now,
             locked_at: None,
             can_request_admin: false,
+            is_service_account: false,
+            locale: None,
+            password_reset_required_at: None,
+            deleted_at: None,
+            purged_at: None,
+            compat_password_deprecated_at: None,
         }]
     }
 }
@@ -70,13 +158,74 @@ pub struct Authentication {
     pub authentication_method: AuthenticationMethod,
 }
 
+impl Authentication {
+    /// Whether this authentication satisfies one of the given Authentication
+    /// Context Class References. An empty list is always satisfied.
+    #[must_use]
+    pub fn satisfies_acr_values(&self, acr_values: &[String]) -> bool {
+        acr_values.is_empty()
+            || self
+                .authentication_method
+                .acr()
+                .is_some_and(|acr| acr_values.iter().any(|value| value == acr))
+    }
+}
+
+/// The Authentication Context Class Reference of an authentication done with
+/// a password
+pub const ACR_PASSWORD: &str = "urn:matrix-authentication-service:acr:password";
+
+/// The Authentication Context Class Reference of an authentication done
+/// through an upstream OAuth 2.0 provider
+pub const ACR_UPSTREAM_OAUTH2: &str = "urn:matrix-authentication-service:acr:upstream_oauth2";
+
+/// The Authentication Context Class Reference of an authentication done with
+/// a WebAuthn credential, e.g. a passkey
+pub const ACR_WEBAUTHN: &str = "urn:matrix-authentication-service:acr:webauthn";
+
+/// All the Authentication Context Class References which can be requested by
+/// clients
+pub const SUPPORTED_ACR_VALUES: [&str; 3] = [ACR_PASSWORD, ACR_UPSTREAM_OAUTH2, ACR_WEBAUTHN];
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum AuthenticationMethod {
     Password { user_password_id: Ulid },
     UpstreamOAuth2 { upstream_oauth2_session_id: Ulid },
+    Webauthn { webauthn_credential_id: Ulid },
+    RecoveryCode { user_recovery_code_id: Ulid },
+    LoginLink { user_login_link_id: Ulid },
     Unknown,
 }
 
+impl AuthenticationMethod {
+    /// The Authentication Context Class Reference satisfied by this method, as
+    /// emitted in the `acr` claim
+    #[must_use]
+    pub fn acr(&self) -> Option<&'static str> {
+        match self {
+            Self::Password { .. } => Some(ACR_PASSWORD),
+            Self::UpstreamOAuth2 { .. } => Some(ACR_UPSTREAM_OAUTH2),
+            Self::Webauthn { .. } => Some(ACR_WEBAUTHN),
+            Self::RecoveryCode { .. } | Self::LoginLink { .. } | Self::Unknown => None,
+        }
+    }
+
+    /// The Authentication Method References of this method, as defined in
+    /// [RFC8176], emitted in the `amr` claim
+    ///
+    /// [RFC8176]: https://www.rfc-editor.org/rfc/rfc8176
+    #[must_use]
+    pub fn amr(&self) -> &'static [&'static str] {
+        match self {
+            Self::Password { .. } => &["pwd"],
+            Self::UpstreamOAuth2 { .. } => &["fed"],
+            Self::Webauthn { .. } => &["hwk", "user"],
+            Self::RecoveryCode { .. } | Self::LoginLink { .. } => &["otp"],
+            Self::Unknown => &[],
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct BrowserSession {
     pub id: Ulid,
@@ -122,6 +271,15 @@ pub struct UserEmail {
     pub confirmed_at: Option<DateTime<Utc>>,
 }
 
+impl UserEmail {
+    /// Returns `true` if the user proved they own this email address. Only
+    /// verified addresses can become primary.
+    #[must_use]
+    pub fn is_verified(&self) -> bool {
+        self.confirmed_at.is_some()
+    }
+}
+
 impl UserEmail {
     #[must_use]
     pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
@@ -144,6 +302,59 @@ impl UserEmail {
     }
 }
 
+/// How email addresses are normalized before being stored and compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmailNormalization {
+    /// Whether the local part of the address should be lowercased. The domain
+    /// is always lowercased.
+    pub lowercase: bool,
+
+    /// Whether the dots and the `+` suffix should be removed from the local
+    /// part of Gmail addresses, as Gmail ignores them when delivering
+    pub gmail_folding: bool,
+}
+
+impl Default for EmailNormalization {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            gmail_folding: false,
+        }
+    }
+}
+
+impl EmailNormalization {
+    /// Normalize an email address
+    ///
+    /// Addresses which don't have a domain part are only trimmed.
+    #[must_use]
+    pub fn normalize(&self, email: &str) -> String {
+        let email = email.trim();
+        let Some((local, domain)) = email.rsplit_once('@') else {
+            return email.to_owned();
+        };
+
+        let mut domain = domain.to_lowercase();
+        let mut local = if self.lowercase {
+            local.to_lowercase()
+        } else {
+            local.to_owned()
+        };
+
+        if self.gmail_folding && (domain == "gmail.com" || domain == "googlemail.com") {
+            if let Some((base, _suffix)) = local.split_once('+') {
+                local = base.to_owned();
+            }
+            local.retain(|c| c != '.');
+            // Gmail addresses are case-insensitive anyway
+            local = local.to_lowercase();
+            domain = "gmail.com".to_owned();
+        }
+
+        format!("{local}@{domain}")
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum UserEmailVerificationState {
     AlreadyUsed { when: DateTime<Utc> },
@@ -207,3 +418,403 @@ impl UserEmailVerification {
             .collect()
     }
 }
+
+/// A registration which started by verifying an email address, before any
+/// account exists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserRegistration {
+    pub id: Ulid,
+    pub email: String,
+    pub code: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl UserRegistration {
+    /// Returns `true` if the registration can still make progress, i.e. it
+    /// didn't expire and no account was created from it yet
+    #[must_use]
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.completed_at.is_none() && now < self.expires_at
+    }
+
+    /// Returns `true` if the email address was verified
+    #[must_use]
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
+        vec![
+            Self {
+                id: Ulid::from_datetime_with_source(now.into(), rng),
+                email: "alice@example.com".to_owned(),
+                code: "123456".to_owned(),
+                created_at: now - Duration::minutes(5),
+                expires_at: now + Duration::minutes(55),
+                verified_at: None,
+                completed_at: None,
+            },
+            Self {
+                id: Ulid::from_datetime_with_source(now.into(), rng),
+                email: "bob@example.com".to_owned(),
+                code: "654321".to_owned(),
+                created_at: now - Duration::minutes(5),
+                expires_at: now + Duration::minutes(55),
+                verified_at: Some(now - Duration::minutes(1)),
+                completed_at: None,
+            },
+        ]
+    }
+}
+
+/// The session which was started by a sign-in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SignInSession {
+    /// A browser session, from an interactive login
+    Browser(Ulid),
+
+    /// A compatibility session, from a password login
+    Compat(Ulid),
+}
+
+/// A notification of a new sign-in, shown on the other sessions of the user
+/// until one of them acknowledges it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserSignInNotification {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub session: SignInSession,
+    pub created_at: DateTime<Utc>,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+}
+
+impl UserSignInNotification {
+    /// Returns `true` if the notification was acknowledged by the user
+    #[must_use]
+    pub fn is_acknowledged(&self) -> bool {
+        self.acknowledged_at.is_some()
+    }
+}
+
+/// A single-use, time-limited ticket sent by email to let a user choose a new
+/// password
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserRecoveryTicket {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub ticket: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl UserRecoveryTicket {
+    /// Returns `true` if the ticket can still be used to reset the password
+    #[must_use]
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.consumed_at.is_none() && now < self.expires_at
+    }
+}
+
+impl UserRecoveryTicket {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
+        User::samples(now, rng)
+            .into_iter()
+            .map(|user| Self {
+                id: Ulid::from_datetime_with_source(now.into(), rng),
+                user_id: user.id,
+                ticket: "Cohbeequ5yahng5siebaiVeh3xae3ooC".to_owned(),
+                created_at: now,
+                expires_at: now + Duration::hours(1),
+                consumed_at: None,
+            })
+            .collect()
+    }
+}
+
+/// A single-use, time-limited link sent by email to let a user sign in
+/// without a password
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserLoginLink {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub user_email_id: Ulid,
+    pub token: String,
+
+    /// Where to send the user once they are logged in, serialized as the
+    /// query parameters of the login page were
+    #[serde(skip_serializing)]
+    pub post_auth_action: Option<serde_json::Value>,
+
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl UserLoginLink {
+    /// Returns `true` if the link can still be used to sign in
+    #[must_use]
+    pub fn is_valid(&self, now: DateTime<Utc>) -> bool {
+        self.consumed_at.is_none() && now < self.expires_at
+    }
+}
+
+impl UserLoginLink {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
+        User::samples(now, rng)
+            .into_iter()
+            .map(|user| Self {
+                id: Ulid::from_datetime_with_source(now.into(), rng),
+                user_id: user.id,
+                user_email_id: Ulid::from_datetime_with_source(now.into(), rng),
+                token: "eeNg1ohsh6ieWu6aiph4Ohp8ahgh3ieD".to_owned(),
+                post_auth_action: None,
+                created_at: now,
+                expires_at: now + Duration::minutes(15),
+                consumed_at: None,
+            })
+            .collect()
+    }
+}
+
+/// A WebAuthn credential, e.g. a passkey, registered by a user to sign in
+/// without a password
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WebauthnCredential {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub name: String,
+
+    /// The credential ID, as sent by the authenticator, in URL-safe base64
+    pub credential_id: String,
+
+    /// The serialized public key and counters of the credential, opaque to
+    /// everything but the WebAuthn implementation
+    #[serde(skip_serializing)]
+    pub passkey: serde_json::Value,
+
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl WebauthnCredential {
+    #[doc(hidden)]
+    #[must_use]
+    pub fn samples(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self> {
+        User::samples(now, rng)
+            .into_iter()
+            .flat_map(|user| {
+                [
+                    Self {
+                        id: Ulid::from_datetime_with_source(now.into(), rng),
+                        user_id: user.id,
+                        name: "Laptop".to_owned(),
+                        credential_id: "dGhpcyBpcyBhIGNyZWRlbnRpYWwgaWQ".to_owned(),
+                        passkey: serde_json::Value::Null,
+                        created_at: now - Duration::days(30),
+                        last_used_at: Some(now - Duration::hours(2)),
+                    },
+                    Self {
+                        id: Ulid::from_datetime_with_source(now.into(), rng),
+                        user_id: user.id,
+                        name: "Security key".to_owned(),
+                        credential_id: "YW5vdGhlciBjcmVkZW50aWFsIGlk".to_owned(),
+                        passkey: serde_json::Value::Null,
+                        created_at: now,
+                        last_used_at: None,
+                    },
+                ]
+            })
+            .collect()
+    }
+}
+
+/// A one-time recovery code, which can be used to sign in when the user lost
+/// access to their passkeys.
+///
+/// Only a hash of the code is stored, the plain code is shown once to the user
+/// when it is generated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserRecoveryCode {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub created_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl UserRecoveryCode {
+    /// How many recovery codes are generated at once
+    pub const COUNT: usize = 10;
+
+    /// Generate a new plain recovery code, formatted as three groups of four
+    /// characters, e.g. `4kxt-m2pa-9hzc`
+    #[must_use]
+    pub fn generate(rng: &mut impl Rng) -> String {
+        // Lowercase letters and digits, without the ones which are easily
+        // confused with each other
+        const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+        (0..3)
+            .map(|_| {
+                (0..4)
+                    .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// Hash a plain recovery code, so that it can be stored or looked up.
+    ///
+    /// The code is normalized first, so that the case, dashes and whitespace
+    /// typed by the user don't matter. Recovery codes have enough entropy for
+    /// a fast hash to be sufficient.
+    #[must_use]
+    pub fn hash(code: &str) -> String {
+        use sha2::{Digest, Sha256};
+
+        let normalized: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        Sha256::digest(normalized.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn is_consumed(&self) -> bool {
+        self.consumed_at.is_some()
+    }
+}
+
+/// The terms of service users have to accept, as configured by the operator
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TermsOfService {
+    /// Identifies the current version of the terms. Users who only accepted
+    /// another version have to accept them again.
+    pub version: String,
+
+    /// Where the terms can be read
+    pub url: Url,
+}
+
+impl TermsOfService {
+    /// Returns `true` if the acceptance is for the current version of the
+    /// terms
+    #[must_use]
+    pub fn is_accepted(&self, acceptance: Option<&UserTermsAcceptance>) -> bool {
+        acceptance.is_some_and(|acceptance| acceptance.version == self.version)
+    }
+}
+
+/// A user accepted a version of the terms of service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserTermsAcceptance {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub version: String,
+    pub url: Url,
+    pub accepted_at: DateTime<Utc>,
+}
+
+/// An export of all the data held about a user, which they can download
+/// through a secret, time-limited link once it is ready
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UserDataExport {
+    pub id: Ulid,
+    pub user_id: Ulid,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl UserDataExport {
+    /// Returns `true` if the export is ready and can still be downloaded
+    #[must_use]
+    pub fn is_available(&self, now: DateTime<Utc>) -> bool {
+        self.completed_at.is_some() && self.expires_at.is_some_and(|expires_at| now < expires_at)
+    }
+
+    /// Returns `true` if the export was requested but isn't ready yet
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        self.completed_at.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_email() {
+        let normalization = EmailNormalization {
+            lowercase: false,
+            gmail_folding: false,
+        };
+        assert_eq!(
+            normalization.normalize(" John.Doe@Example.COM "),
+            "John.Doe@example.com"
+        );
+        assert_eq!(normalization.normalize("not-an-email"), "not-an-email");
+
+        let normalization = EmailNormalization::default();
+        assert_eq!(
+            normalization.normalize("John.Doe+mas@Example.COM"),
+            "john.doe+mas@example.com"
+        );
+        assert_eq!(
+            normalization.normalize("John.Doe+mas@gmail.com"),
+            "john.doe+mas@gmail.com"
+        );
+
+        let normalization = EmailNormalization {
+            lowercase: false,
+            gmail_folding: true,
+        };
+        assert_eq!(
+            normalization.normalize("John.Doe+mas@GMail.com"),
+            "johndoe@gmail.com"
+        );
+        assert_eq!(
+            normalization.normalize("john.doe@googlemail.com"),
+            "johndoe@gmail.com"
+        );
+        // Other domains are left alone
+        assert_eq!(
+            normalization.normalize("John.Doe+mas@example.com"),
+            "John.Doe+mas@example.com"
+        );
+    }
+
+    #[test]
+    fn recovery_code() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let code = UserRecoveryCode::generate(&mut rng);
+        assert_eq!(code.len(), 14);
+        assert_eq!(code.split('-').count(), 3);
+        assert_ne!(code, UserRecoveryCode::generate(&mut rng));
+
+        // The hash doesn't depend on how the code was typed
+        let hash = UserRecoveryCode::hash(&code);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(UserRecoveryCode::hash(&code.to_uppercase()), hash);
+        assert_eq!(UserRecoveryCode::hash(&code.replace('-', " ")), hash);
+        assert_eq!(UserRecoveryCode::hash(&code.replace('-', "")), hash);
+        assert_ne!(UserRecoveryCode::hash("aaaa-bbbb-cccc"), hash);
+    }
+}
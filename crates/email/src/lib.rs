@@ -25,6 +25,6 @@ pub use lettre::{
 pub use mas_templates::EmailVerificationContext;
 
 pub use self::{
-    mailer::Mailer,
-    transport::{SmtpMode, Transport as MailTransport},
+    mailer::{EmailKind, Mailer},
+    transport::{Mailbox as MemoryMailbox, SentEmail, SmtpMode, Transport as MailTransport},
 };
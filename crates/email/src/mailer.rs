@@ -14,22 +14,58 @@
 
 //! Send emails to users
 
+use std::collections::HashMap;
+
 use lettre::{
     message::{Mailbox, MessageBuilder, MultiPart},
     AsyncTransport, Message,
 };
-use mas_templates::{EmailVerificationContext, Templates, WithLanguage};
+use mas_templates::{
+    EmailCompatPasswordDeprecationContext, EmailDataExportContext, EmailLoginLinkContext,
+    EmailPasswordResetContext, EmailRegistrationContext, EmailVerificationContext, Templates,
+    WithLanguage,
+};
 use thiserror::Error;
 
 use crate::MailTransport;
 
+/// The kinds of emails sent to users, which can each be sent from their own
+/// address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmailKind {
+    /// Code to verify an email address
+    Verification,
+
+    /// Code to verify the email address of a new account
+    Registration,
+
+    /// Link to reset a password
+    PasswordReset,
+
+    /// Link to log in
+    LoginLink,
+
+    /// Notification that a data export is ready
+    DataExport,
+
+    /// Warning that password login on the compatibility layer is being
+    /// disabled
+    CompatPasswordDeprecation,
+}
+
+#[derive(Clone)]
+struct Sender {
+    from: Mailbox,
+    reply_to: Mailbox,
+}
+
 /// Helps sending mails to users
 #[derive(Clone)]
 pub struct Mailer {
     templates: Templates,
     transport: MailTransport,
-    from: Mailbox,
-    reply_to: Mailbox,
+    default_sender: Sender,
+    senders: HashMap<EmailKind, Sender>,
 }
 
 #[derive(Debug, Error)]
@@ -52,15 +88,23 @@ impl Mailer {
         Self {
             templates,
             transport,
-            from,
-            reply_to,
+            default_sender: Sender { from, reply_to },
+            senders: HashMap::new(),
         }
     }
 
-    fn base_message(&self) -> MessageBuilder {
+    /// Send a kind of email from other addresses than the default ones
+    #[must_use]
+    pub fn with_sender(mut self, kind: EmailKind, from: Mailbox, reply_to: Mailbox) -> Self {
+        self.senders.insert(kind, Sender { from, reply_to });
+        self
+    }
+
+    fn base_message(&self, kind: EmailKind) -> MessageBuilder {
+        let sender = self.senders.get(&kind).unwrap_or(&self.default_sender);
         Message::builder()
-            .from(self.from.clone())
-            .reply_to(self.reply_to.clone())
+            .from(sender.from.clone())
+            .reply_to(sender.reply_to.clone())
     }
 
     fn prepare_verification_email(
@@ -77,7 +121,7 @@ impl Mailer {
         let subject = self.templates.render_email_verification_subject(context)?;
 
         let message = self
-            .base_message()
+            .base_message(EmailKind::Verification)
             .subject(subject.trim())
             .to(to)
             .multipart(multipart)?;
@@ -112,6 +156,259 @@ impl Mailer {
         Ok(())
     }
 
+    fn prepare_registration_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailRegistrationContext>,
+    ) -> Result<Message, Error> {
+        let plain = self.templates.render_email_registration_txt(context)?;
+
+        let html = self.templates.render_email_registration_html(context)?;
+
+        let multipart = MultiPart::alternative_plain_html(plain, html);
+
+        let subject = self.templates.render_email_registration_subject(context)?;
+
+        let message = self
+            .base_message(EmailKind::Registration)
+            .subject(subject.trim())
+            .to(to)
+            .multipart(multipart)?;
+
+        Ok(message)
+    }
+
+    /// Send the verification code of a registration, before the account
+    /// exists
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the email failed rendering or failed sending
+    #[tracing::instrument(
+        name = "email.registration.send",
+        skip_all,
+        fields(
+            email.to = %to,
+            email.language = %context.language(),
+            user_registration.id = %context.registration().id,
+        ),
+        err,
+    )]
+    pub async fn send_registration_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailRegistrationContext>,
+    ) -> Result<(), Error> {
+        let message = self.prepare_registration_email(to, context)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    fn prepare_password_reset_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailPasswordResetContext>,
+    ) -> Result<Message, Error> {
+        let plain = self.templates.render_email_password_reset_txt(context)?;
+
+        let html = self.templates.render_email_password_reset_html(context)?;
+
+        let multipart = MultiPart::alternative_plain_html(plain, html);
+
+        let subject = self
+            .templates
+            .render_email_password_reset_subject(context)?;
+
+        let message = self
+            .base_message(EmailKind::PasswordReset)
+            .subject(subject.trim())
+            .to(to)
+            .multipart(multipart)?;
+
+        Ok(message)
+    }
+
+    /// Send a link to choose a new password to a user who must reset it
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the email failed rendering or failed sending
+    #[tracing::instrument(
+        name = "email.password_reset.send",
+        skip_all,
+        fields(
+            email.to = %to,
+            email.language = %context.language(),
+            user.id = %context.user().id,
+            user_recovery_ticket.id = %context.ticket().id,
+        ),
+        err,
+    )]
+    pub async fn send_password_reset_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailPasswordResetContext>,
+    ) -> Result<(), Error> {
+        let message = self.prepare_password_reset_email(to, context)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    fn prepare_login_link_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailLoginLinkContext>,
+    ) -> Result<Message, Error> {
+        let plain = self.templates.render_email_login_link_txt(context)?;
+
+        let html = self.templates.render_email_login_link_html(context)?;
+
+        let multipart = MultiPart::alternative_plain_html(plain, html);
+
+        let subject = self.templates.render_email_login_link_subject(context)?;
+
+        let message = self
+            .base_message(EmailKind::LoginLink)
+            .subject(subject.trim())
+            .to(to)
+            .multipart(multipart)?;
+
+        Ok(message)
+    }
+
+    /// Send a link to sign in without a password to a user
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the email failed rendering or failed sending
+    #[tracing::instrument(
+        name = "email.login_link.send",
+        skip_all,
+        fields(
+            email.to = %to,
+            email.language = %context.language(),
+            user.id = %context.user().id,
+            user_login_link.id = %context.login_link().id,
+        ),
+        err,
+    )]
+    pub async fn send_login_link_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailLoginLinkContext>,
+    ) -> Result<(), Error> {
+        let message = self.prepare_login_link_email(to, context)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    fn prepare_data_export_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailDataExportContext>,
+    ) -> Result<Message, Error> {
+        let plain = self.templates.render_email_data_export_txt(context)?;
+
+        let html = self.templates.render_email_data_export_html(context)?;
+
+        let multipart = MultiPart::alternative_plain_html(plain, html);
+
+        let subject = self.templates.render_email_data_export_subject(context)?;
+
+        let message = self
+            .base_message(EmailKind::DataExport)
+            .subject(subject.trim())
+            .to(to)
+            .multipart(multipart)?;
+
+        Ok(message)
+    }
+
+    /// Send the link to download their data export to a user
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the email failed rendering or failed sending
+    #[tracing::instrument(
+        name = "email.data_export.send",
+        skip_all,
+        fields(
+            email.to = %to,
+            email.language = %context.language(),
+            user.id = %context.user().id,
+        ),
+        err,
+    )]
+    pub async fn send_data_export_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailDataExportContext>,
+    ) -> Result<(), Error> {
+        let message = self.prepare_data_export_email(to, context)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    fn prepare_compat_password_deprecation_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailCompatPasswordDeprecationContext>,
+    ) -> Result<Message, Error> {
+        let plain = self
+            .templates
+            .render_email_compat_password_deprecation_txt(context)?;
+
+        let html = self
+            .templates
+            .render_email_compat_password_deprecation_html(context)?;
+
+        let multipart = MultiPart::alternative_plain_html(plain, html);
+
+        let subject = self
+            .templates
+            .render_email_compat_password_deprecation_subject(context)?;
+
+        let message = self
+            .base_message(EmailKind::CompatPasswordDeprecation)
+            .subject(subject.trim())
+            .to(to)
+            .multipart(multipart)?;
+
+        Ok(message)
+    }
+
+    /// Warn a user that they will soon no longer be able to log in with their
+    /// password on the compatibility layer
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the email failed rendering or failed sending
+    #[tracing::instrument(
+        name = "email.compat_password_deprecation.send",
+        skip_all,
+        fields(
+            email.to = %to,
+            email.language = %context.language(),
+            user.id = %context.user().id,
+        ),
+        err,
+    )]
+    pub async fn send_compat_password_deprecation_email(
+        &self,
+        to: Mailbox,
+        context: &WithLanguage<EmailCompatPasswordDeprecationContext>,
+    ) -> Result<(), Error> {
+        let message = self.prepare_compat_password_deprecation_email(to, context)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+
+    /// Get the emails recorded by the transport, if it keeps them in memory
+    #[must_use]
+    pub fn memory_mailbox(&self) -> Option<crate::MemoryMailbox> {
+        self.transport.mailbox()
+    }
+
     /// Test the connetion to the mail server
     ///
     /// # Errors
@@ -14,7 +14,12 @@
 
 //! Email transport backends
 
-use std::{ffi::OsString, num::NonZeroU16, sync::Arc};
+use std::{
+    ffi::OsString,
+    num::NonZeroU16,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
 
 use async_trait::async_trait;
 use lettre::{
@@ -46,10 +51,81 @@ pub struct Transport {
 
 enum TransportInner {
     Blackhole,
+    Memory(Mailbox),
     Smtp(AsyncSmtpTransport<Tokio1Executor>),
     Sendmail(AsyncSendmailTransport<Tokio1Executor>),
 }
 
+/// An email recorded by the in-memory transport
+#[derive(Debug, Clone)]
+pub struct SentEmail {
+    /// When the email was sent
+    pub sent_at: SystemTime,
+
+    /// The sender of the email, from the envelope
+    pub from: Option<String>,
+
+    /// The recipients of the email, from the envelope
+    pub to: Vec<String>,
+
+    /// The full message, as it would have been sent on the wire
+    pub message: String,
+}
+
+impl SentEmail {
+    /// Get the value of a header of the message, if it is present
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        // The headers end at the first empty line
+        self.message
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                key.eq_ignore_ascii_case(name).then(|| value.trim())
+            })
+    }
+
+    /// Get the subject of the message, if it has one
+    #[must_use]
+    pub fn subject(&self) -> Option<&str> {
+        self.header("Subject")
+    }
+}
+
+/// The emails recorded by the in-memory transport.
+///
+/// Clones share the same list of emails.
+#[derive(Debug, Clone, Default)]
+pub struct Mailbox {
+    emails: Arc<Mutex<Vec<SentEmail>>>,
+}
+
+impl Mailbox {
+    /// Get the emails sent so far, from the oldest to the most recent
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock was poisoned
+    #[must_use]
+    pub fn emails(&self) -> Vec<SentEmail> {
+        self.emails.lock().unwrap().clone()
+    }
+
+    /// Forget all the emails sent so far
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock was poisoned
+    pub fn clear(&self) {
+        self.emails.lock().unwrap().clear();
+    }
+
+    fn push(&self, email: SentEmail) {
+        self.emails.lock().unwrap().push(email);
+    }
+}
+
 impl Transport {
     fn new(inner: TransportInner) -> Self {
         let inner = Arc::new(inner);
@@ -62,6 +138,22 @@ impl Transport {
         Self::new(TransportInner::Blackhole)
     }
 
+    /// Construct a transport which keeps the emails in memory instead of
+    /// sending them, for tests and local development
+    #[must_use]
+    pub fn memory() -> Self {
+        Self::new(TransportInner::Memory(Mailbox::default()))
+    }
+
+    /// Get the emails recorded by this transport, if it is an in-memory one
+    #[must_use]
+    pub fn mailbox(&self) -> Option<Mailbox> {
+        match self.inner.as_ref() {
+            TransportInner::Memory(mailbox) => Some(mailbox.clone()),
+            _ => None,
+        }
+    }
+
     /// Construct a SMTP transport
     ///
     /// # Errors
@@ -111,7 +203,8 @@ impl Transport {
             TransportInner::Smtp(t) => {
                 t.test_connection().await?;
             }
-            TransportInner::Blackhole | TransportInner::Sendmail(_) => {}
+            TransportInner::Blackhole | TransportInner::Memory(_) | TransportInner::Sendmail(_) => {
+            }
         }
 
         Ok(())
@@ -143,6 +236,14 @@ impl AsyncTransport for Transport {
                     "An email was supposed to be sent but no email backend is configured"
                 );
             }
+            TransportInner::Memory(mailbox) => {
+                mailbox.push(SentEmail {
+                    sent_at: SystemTime::now(),
+                    from: envelope.from().map(ToString::to_string),
+                    to: envelope.to().iter().map(ToString::to_string).collect(),
+                    message: String::from_utf8_lossy(email).into_owned(),
+                });
+            }
             TransportInner::Smtp(t) => {
                 t.send_raw(envelope, email).await?;
             }
@@ -15,28 +15,31 @@
 #![deny(clippy::future_not_send)]
 #![allow(clippy::module_name_repetitions, clippy::unused_async)]
 
-use async_graphql::EmptySubscription;
 use mas_data_model::{BrowserSession, Session, User};
 use ulid::Ulid;
 
 mod model;
 mod mutations;
 mod query;
+mod schema_diff;
 mod state;
+mod subscriptions;
 
 pub use self::{
     model::{CreationEvent, Node},
     mutations::Mutation,
     query::Query,
-    state::{BoxState, State},
+    schema_diff::{breaking_changes, BreakingChange},
+    state::{AvatarStore, BoxState, EmailThrottle, State},
+    subscriptions::Subscription,
 };
 
-pub type Schema = async_graphql::Schema<Query, Mutation, EmptySubscription>;
-pub type SchemaBuilder = async_graphql::SchemaBuilder<Query, Mutation, EmptySubscription>;
+pub type Schema = async_graphql::Schema<Query, Mutation, Subscription>;
+pub type SchemaBuilder = async_graphql::SchemaBuilder<Query, Mutation, Subscription>;
 
 #[must_use]
 pub fn schema_builder() -> SchemaBuilder {
-    async_graphql::Schema::build(Query::new(), Mutation::new(), EmptySubscription)
+    async_graphql::Schema::build(Query::new(), Mutation::new(), Subscription::new())
         .register_output_type::<Node>()
         .register_output_type::<CreationEvent>()
 }
@@ -95,6 +98,12 @@ impl OwnerId for mas_data_model::UpstreamOAuthLink {
     }
 }
 
+impl OwnerId for mas_data_model::UserSignInNotification {
+    fn owner_id(&self) -> Option<Ulid> {
+        Some(self.user_id)
+    }
+}
+
 /// A dumb wrapper around a `Ulid` to implement `OwnerId` for it.
 pub struct UserId(Ulid);
 
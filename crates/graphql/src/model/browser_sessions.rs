@@ -14,9 +14,12 @@
 
 use async_graphql::{Context, Description, Object, ID};
 use chrono::{DateTime, Utc};
-use mas_storage::{user::BrowserSessionRepository, RepositoryAccess};
+use mas_storage::{
+    user::{BrowserSessionRepository, UserSignInNotificationRepository},
+    RepositoryAccess,
+};
 
-use super::{NodeType, SessionState, User};
+use super::{NodeType, SessionState, SignInNotification, User};
 use crate::state::ContextExt;
 
 /// A browser session represents a logged in user in a browser.
@@ -59,6 +62,25 @@ impl BrowserSession {
         Ok(last_authentication.map(Authentication))
     }
 
+    /// The sign-ins of the user which happened after this session started and
+    /// were not acknowledged yet, oldest first.
+    async fn unacknowledged_sign_ins(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<SignInNotification>, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        let notifications = repo
+            .user_sign_in_notification()
+            .list_unacknowledged(&self.0)
+            .await?;
+
+        repo.cancel().await?;
+
+        Ok(notifications.into_iter().map(SignInNotification).collect())
+    }
+
     /// When the object was created.
     pub async fn created_at(&self) -> DateTime<Utc> {
         self.0.created_at
@@ -93,6 +93,12 @@ impl CompatSession {
         self.session.device.as_str()
     }
 
+    /// A human-readable name for the session, supplied by the client when
+    /// logging in, derived from its user agent, or set by the user.
+    async fn human_name(&self) -> Option<&str> {
+        self.session.human_name.as_deref()
+    }
+
     /// When the object was created.
     pub async fn created_at(&self) -> DateTime<Utc> {
         self.session.created_at
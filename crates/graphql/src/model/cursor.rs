@@ -40,3 +40,19 @@ impl NodeCursor {
 }
 
 pub type Cursor = OpaqueCursor<NodeCursor>;
+
+/// Complexity of a paginated list: the fields under it count once per
+/// requested item
+#[must_use]
+pub fn connection_complexity(
+    child_complexity: usize,
+    first: Option<i32>,
+    last: Option<i32>,
+) -> usize {
+    let count = first
+        .or(last)
+        .and_then(|count| usize::try_from(count).ok())
+        .unwrap_or_default();
+
+    child_complexity.saturating_mul(count.max(1))
+}
@@ -21,6 +21,9 @@ mod cursor;
 mod matrix;
 mod node;
 mod oauth;
+mod session_events;
+mod sign_in_notifications;
+mod statistics;
 mod upstream_oauth;
 mod users;
 mod viewer;
@@ -28,11 +31,14 @@ mod viewer;
 pub use self::{
     browser_sessions::{Authentication, BrowserSession},
     compat_sessions::{CompatSession, CompatSsoLogin},
-    cursor::{Cursor, NodeCursor},
+    cursor::{connection_complexity, Cursor, NodeCursor},
     node::{Node, NodeType},
-    oauth::{OAuth2Client, OAuth2Consent, OAuth2Session},
+    oauth::{OAuth2AuthorizationGrantFunnel, OAuth2Client, OAuth2Consent, OAuth2Session},
+    session_events::SessionEvent,
+    sign_in_notifications::{SignInNotification, SignInSession},
+    statistics::DailyStatistics,
     upstream_oauth::{UpstreamOAuth2Link, UpstreamOAuth2Provider},
-    users::{User, UserEmail},
+    users::{User, UserEmail, UserState},
     viewer::{Anonymous, Viewer, ViewerSession},
 };
 
@@ -51,6 +57,7 @@ pub enum CreationEvent {
     UpstreamOAuth2Provider(Box<UpstreamOAuth2Provider>),
     UpstreamOAuth2Link(Box<UpstreamOAuth2Link>),
     OAuth2Session(Box<OAuth2Session>),
+    SignInNotification(Box<SignInNotification>),
 }
 
 pub struct PreloadedTotalCount(pub Option<usize>);
@@ -19,7 +19,7 @@ use ulid::Ulid;
 
 use super::{
     Anonymous, Authentication, BrowserSession, CompatSession, CompatSsoLogin, OAuth2Client,
-    OAuth2Session, UpstreamOAuth2Link, UpstreamOAuth2Provider, User, UserEmail,
+    OAuth2Session, SignInNotification, UpstreamOAuth2Link, UpstreamOAuth2Provider, User, UserEmail,
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -30,6 +30,7 @@ pub enum NodeType {
     CompatSsoLogin,
     OAuth2Client,
     OAuth2Session,
+    SignInNotification,
     UpstreamOAuth2Provider,
     UpstreamOAuth2Link,
     User,
@@ -54,6 +55,7 @@ impl NodeType {
             NodeType::CompatSsoLogin => "compat_sso_login",
             NodeType::OAuth2Client => "oauth2_client",
             NodeType::OAuth2Session => "oauth2_session",
+            NodeType::SignInNotification => "sign_in_notification",
             NodeType::UpstreamOAuth2Provider => "upstream_oauth2_provider",
             NodeType::UpstreamOAuth2Link => "upstream_oauth2_link",
             NodeType::User => "user",
@@ -69,6 +71,7 @@ impl NodeType {
             "compat_sso_login" => Some(NodeType::CompatSsoLogin),
             "oauth2_client" => Some(NodeType::OAuth2Client),
             "oauth2_session" => Some(NodeType::OAuth2Session),
+            "sign_in_notification" => Some(NodeType::SignInNotification),
             "upstream_oauth2_provider" => Some(NodeType::UpstreamOAuth2Provider),
             "upstream_oauth2_link" => Some(NodeType::UpstreamOAuth2Link),
             "user" => Some(NodeType::User),
@@ -123,6 +126,7 @@ pub enum Node {
     CompatSsoLogin(Box<CompatSsoLogin>),
     OAuth2Client(Box<OAuth2Client>),
     OAuth2Session(Box<OAuth2Session>),
+    SignInNotification(Box<SignInNotification>),
     UpstreamOAuth2Provider(Box<UpstreamOAuth2Provider>),
     UpstreamOAuth2Link(Box<UpstreamOAuth2Link>),
     User(Box<User>),
@@ -54,6 +54,11 @@ impl OAuth2Session {
         self.0.scope.to_string()
     }
 
+    /// A human-readable name the user gave to the session.
+    pub async fn human_name(&self) -> Option<&str> {
+        self.0.human_name.as_deref()
+    }
+
     /// When the object was created.
     pub async fn created_at(&self) -> DateTime<Utc> {
         self.0.created_at
@@ -127,6 +132,23 @@ impl OAuth2Session {
     pub async fn last_active_at(&self) -> Option<DateTime<Utc>> {
         self.0.last_active_at
     }
+
+    /// When the session will expire if it isn't used anymore, according to
+    /// the refresh token lifetimes configured on the server for its client.
+    pub async fn expires_at(&self, ctx: &Context<'_>) -> Option<DateTime<Utc>> {
+        // Sessions without a user are client credentials sessions, which don't
+        // get refresh tokens
+        if !self.0.is_valid() || self.0.user_id.is_none() {
+            return None;
+        }
+
+        let last_active_at = self.0.last_active_at.unwrap_or(self.0.created_at);
+        ctx.state()
+            .refresh_token_policies()
+            .for_client(self.0.client_id)
+            .lifetimes
+            .expires_at(self.0.created_at, last_active_at)
+    }
 }
 
 /// The application type advertised by the client.
@@ -228,3 +250,55 @@ impl OAuth2Consent {
         Ok(OAuth2Client(client))
     }
 }
+
+/// How far the authorization grants of a client went in the authorization
+/// flow.
+#[derive(Description)]
+pub struct OAuth2AuthorizationGrantFunnel(pub mas_storage::oauth2::AuthorizationGrantFunnel);
+
+#[Object(use_type_description)]
+impl OAuth2AuthorizationGrantFunnel {
+    /// OAuth 2.0 client which started the authorization grants.
+    pub async fn client(&self, ctx: &Context<'_>) -> Result<OAuth2Client, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+        let client = repo
+            .oauth2_client()
+            .lookup(self.0.client_id)
+            .await?
+            .context("Could not load client")?;
+        repo.cancel().await?;
+
+        Ok(OAuth2Client(client))
+    }
+
+    /// Number of authorization grants started.
+    pub async fn started(&self) -> u64 {
+        self.0.started
+    }
+
+    /// Number of authorization grants which sent the user to a login page.
+    pub async fn login_shown(&self) -> u64 {
+        self.0.login_shown
+    }
+
+    /// Number of authorization grants which showed the consent screen.
+    pub async fn consent_shown(&self) -> u64 {
+        self.0.consent_shown
+    }
+
+    /// Number of authorization grants which were completed.
+    pub async fn completed(&self) -> u64 {
+        self.0.completed
+    }
+
+    /// Number of authorization grants which were abandoned by the user.
+    pub async fn abandoned(&self) -> u64 {
+        self.0.abandoned
+    }
+
+    /// Number of authorization grants which ended with an error.
+    pub async fn errored(&self) -> u64 {
+        self.0.errored
+    }
+}
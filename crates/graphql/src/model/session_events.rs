@@ -0,0 +1,98 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_graphql::{Context, Description, Enum, Object, ID};
+use mas_data_model::SessionEventTarget;
+use mas_storage::{
+    compat::CompatSessionRepository, oauth2::OAuth2SessionRepository,
+    user::BrowserSessionRepository, RepositoryAccess,
+};
+
+use super::{BrowserSession, CompatSession, Node, NodeType, OAuth2Session};
+use crate::state::ContextExt;
+
+/// What happened to a session
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum SessionEventKind {
+    /// The session was started
+    Created,
+
+    /// The session was ended
+    Ended,
+
+    /// The user authenticated again in the session
+    AuthenticationAdded,
+}
+
+impl From<mas_data_model::SessionEventKind> for SessionEventKind {
+    fn from(kind: mas_data_model::SessionEventKind) -> Self {
+        match kind {
+            mas_data_model::SessionEventKind::Created => Self::Created,
+            mas_data_model::SessionEventKind::Ended => Self::Ended,
+            mas_data_model::SessionEventKind::AuthenticationAdded => Self::AuthenticationAdded,
+        }
+    }
+}
+
+/// Something which happened to one of the sessions of the user
+#[derive(Description)]
+pub struct SessionEvent(pub mas_data_model::SessionEvent);
+
+#[Object(use_type_description)]
+impl SessionEvent {
+    /// What happened to the session
+    async fn kind(&self) -> SessionEventKind {
+        self.0.kind.into()
+    }
+
+    /// ID of the session
+    async fn session_id(&self) -> ID {
+        match self.0.session {
+            SessionEventTarget::Browser(id) => NodeType::BrowserSession.id(id),
+            SessionEventTarget::OAuth2(id) => NodeType::OAuth2Session.id(id),
+            SessionEventTarget::Compat(id) => NodeType::CompatSession.id(id),
+        }
+    }
+
+    /// The session, in its current state. Is `null` if it does not exist
+    /// anymore.
+    async fn session(&self, ctx: &Context<'_>) -> Result<Option<Node>, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        let node = match self.0.session {
+            SessionEventTarget::Browser(id) => repo
+                .browser_session()
+                .lookup(id)
+                .await?
+                .map(|session| Node::BrowserSession(Box::new(BrowserSession(session)))),
+
+            SessionEventTarget::OAuth2(id) => repo
+                .oauth2_session()
+                .lookup(id)
+                .await?
+                .map(|session| Node::OAuth2Session(Box::new(OAuth2Session(session)))),
+
+            SessionEventTarget::Compat(id) => repo
+                .compat_session()
+                .lookup(id)
+                .await?
+                .map(|session| Node::CompatSession(Box::new(CompatSession::new(session)))),
+        };
+
+        repo.cancel().await?;
+
+        Ok(node)
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context as _;
+use async_graphql::{Context, Description, Object, Union, ID};
+use chrono::{DateTime, Utc};
+use mas_storage::{compat::CompatSessionRepository, user::BrowserSessionRepository};
+
+use super::{BrowserSession, CompatSession, NodeType};
+use crate::state::ContextExt;
+
+/// A notification of a new sign-in of the user, shown in their other sessions
+/// until it is acknowledged.
+#[derive(Description)]
+pub struct SignInNotification(pub mas_data_model::UserSignInNotification);
+
+impl From<mas_data_model::UserSignInNotification> for SignInNotification {
+    fn from(v: mas_data_model::UserSignInNotification) -> Self {
+        Self(v)
+    }
+}
+
+/// The session started by a sign-in.
+#[derive(Union)]
+pub enum SignInSession {
+    BrowserSession(Box<BrowserSession>),
+    CompatSession(Box<CompatSession>),
+}
+
+#[Object(use_type_description)]
+impl SignInNotification {
+    /// ID of the object.
+    pub async fn id(&self) -> ID {
+        NodeType::SignInNotification.id(self.0.id)
+    }
+
+    /// When the object was created.
+    pub async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    /// The session started by the sign-in, which can be ended if the sign-in
+    /// wasn't expected.
+    async fn session(&self, ctx: &Context<'_>) -> Result<SignInSession, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        let session = match self.0.session {
+            mas_data_model::SignInSession::Browser(id) => {
+                let session = repo
+                    .browser_session()
+                    .lookup(id)
+                    .await?
+                    .context("Could not load browser session")?;
+
+                SignInSession::BrowserSession(Box::new(BrowserSession(session)))
+            }
+
+            mas_data_model::SignInSession::Compat(id) => {
+                let session = repo
+                    .compat_session()
+                    .lookup(id)
+                    .await?
+                    .context("Could not load compat session")?;
+
+                SignInSession::CompatSession(Box::new(CompatSession::new(session)))
+            }
+        };
+
+        repo.cancel().await?;
+
+        Ok(session)
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_graphql::{Description, Object};
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+
+/// Usage statistics of a single day.
+#[derive(Description)]
+pub struct DailyStatistics(pub mas_storage::statistics::DailyStatistics);
+
+#[Object(use_type_description)]
+impl DailyStatistics {
+    /// The start of the day, at midnight UTC.
+    pub async fn day(&self) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&self.0.day.and_time(NaiveTime::MIN))
+    }
+
+    /// Number of users created on that day.
+    pub async fn new_users(&self) -> u64 {
+        self.0.new_users
+    }
+
+    /// Number of users with a session active on that day.
+    pub async fn active_users(&self) -> u64 {
+        self.0.active_users
+    }
+
+    /// Number of OAuth 2.0 and compatibility sessions active on that day.
+    pub async fn active_sessions(&self) -> u64 {
+        self.0.active_sessions
+    }
+
+    /// Number of OAuth 2.0 refresh tokens and compatibility access tokens
+    /// issued on that day.
+    pub async fn tokens_issued(&self) -> u64 {
+        self.0.tokens_issued
+    }
+
+    /// When the statistics were last computed. The statistics of the current
+    /// day are updated every hour.
+    pub async fn computed_at(&self) -> DateTime<Utc> {
+        self.0.computed_at
+    }
+}
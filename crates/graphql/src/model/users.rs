@@ -17,18 +17,23 @@ use async_graphql::{
     Context, Description, Enum, Object, Union, ID,
 };
 use chrono::{DateTime, Utc};
-use mas_data_model::Device;
+use mas_data_model::{Device, UserTermsAcceptance};
 use mas_storage::{
     app_session::AppSessionFilter,
     compat::{CompatSessionFilter, CompatSsoLoginFilter, CompatSsoLoginRepository},
+    login_failure::user_key,
     oauth2::{OAuth2SessionFilter, OAuth2SessionRepository},
     upstream_oauth2::{UpstreamOAuthLinkFilter, UpstreamOAuthLinkRepository},
-    user::{BrowserSessionFilter, BrowserSessionRepository, UserEmailFilter, UserEmailRepository},
+    user::{
+        BrowserSessionFilter, BrowserSessionRepository, UserEmailFilter, UserEmailRepository,
+        UserRecoveryCodeRepository, UserTermsRepository,
+    },
     Pagination, RepositoryAccess,
 };
 
 use super::{
     compat_sessions::{CompatSessionType, CompatSsoLogin},
+    connection_complexity,
     matrix::MatrixUser,
     BrowserSession, CompatSession, Cursor, NodeCursor, NodeType, OAuth2Session,
     PreloadedTotalCount, SessionState, UpstreamOAuth2Link,
@@ -51,6 +56,24 @@ impl From<mas_data_model::BrowserSession> for User {
     }
 }
 
+impl User {
+    /// Lookup the acceptance of the current version of the terms of service
+    async fn terms_acceptance(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<UserTermsAcceptance>, async_graphql::Error> {
+        let state = ctx.state();
+        let Some(terms) = state.terms() else {
+            return Ok(None);
+        };
+
+        let mut repo = state.repository().await?;
+        let acceptance = repo.user_terms().find(&self.0, &terms.version).await?;
+        repo.cancel().await?;
+        Ok(acceptance)
+    }
+}
+
 #[Object(use_type_description)]
 impl User {
     /// ID of the object.
@@ -73,11 +96,76 @@ impl User {
         self.0.locked_at
     }
 
+    /// Until when the user is temporarily locked out after too many failed
+    /// login attempts.
+    pub async fn login_locked_until(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<DateTime<Utc>>, async_graphql::Error> {
+        let state = ctx.state();
+        let clock = state.clock();
+        let mut repo = state.repository().await?;
+
+        let locked_until = repo
+            .login_failure()
+            .locked_until(&clock, &user_key(&self.0))
+            .await?;
+        repo.cancel().await?;
+        Ok(locked_until)
+    }
+
+    /// When the user was deleted. Deleted users can be restored until they
+    /// are purged.
+    pub async fn deleted_at(&self) -> Option<DateTime<Utc>> {
+        self.0.deleted_at
+    }
+
+    /// When the data of the deleted user was erased.
+    pub async fn purged_at(&self) -> Option<DateTime<Utc>> {
+        self.0.purged_at
+    }
+
+    /// When an administrator required the user to choose a new password.
+    pub async fn password_reset_required_at(&self) -> Option<DateTime<Utc>> {
+        self.0.password_reset_required_at
+    }
+
+    /// Whether the user accepted the current version of the terms of
+    /// service. Always true if no terms of service are configured.
+    pub async fn terms_accepted(&self, ctx: &Context<'_>) -> Result<bool, async_graphql::Error> {
+        let Some(terms) = ctx.state().terms() else {
+            return Ok(true);
+        };
+
+        let acceptance = self.terms_acceptance(ctx).await?;
+        Ok(terms.is_accepted(acceptance.as_ref()))
+    }
+
+    /// When the user accepted the current version of the terms of service.
+    pub async fn terms_accepted_at(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Option<DateTime<Utc>>, async_graphql::Error> {
+        let acceptance = self.terms_acceptance(ctx).await?;
+        Ok(acceptance.map(|acceptance| acceptance.accepted_at))
+    }
+
     /// Whether the user can request admin privileges.
     pub async fn can_request_admin(&self) -> bool {
         self.0.can_request_admin
     }
 
+    /// Whether the user is a service account, which can't log in
+    /// interactively.
+    pub async fn is_service_account(&self) -> bool {
+        self.0.is_service_account
+    }
+
+    /// The preferred language of the user, as a BCP 47 language tag.
+    pub async fn locale(&self) -> Option<&str> {
+        self.0.locale.as_deref()
+    }
+
     /// Access to the user's Matrix account information.
     async fn matrix(&self, ctx: &Context<'_>) -> Result<MatrixUser, async_graphql::Error> {
         let state = ctx.state();
@@ -98,7 +186,21 @@ impl User {
         Ok(user_email)
     }
 
+    /// Number of recovery codes of the user which were not used yet.
+    async fn recovery_codes_remaining(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<usize, async_graphql::Error> {
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        let count = repo.user_recovery_code().count_remaining(&self.0).await?;
+        repo.cancel().await?;
+        Ok(count)
+    }
+
     /// Get the list of compatibility SSO logins, chronologically sorted
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn compat_sso_logins(
         &self,
         ctx: &Context<'_>,
@@ -160,6 +262,7 @@ impl User {
 
     /// Get the list of compatibility sessions, chronologically sorted
     #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn compat_sessions(
         &self,
         ctx: &Context<'_>,
@@ -170,6 +273,12 @@ impl User {
         #[graphql(name = "type", desc = "List only sessions with the given type.")]
         type_param: Option<CompatSessionType>,
 
+        #[graphql(desc = "List only sessions last active after the given time.")]
+        last_active_after: Option<DateTime<Utc>>,
+
+        #[graphql(desc = "List only sessions last active before the given time.")]
+        last_active_before: Option<DateTime<Utc>>,
+
         #[graphql(desc = "Returns the elements in the list that come after the cursor.")]
         after: Option<String>,
         #[graphql(desc = "Returns the elements in the list that come before the cursor.")]
@@ -206,6 +315,14 @@ impl User {
                     Some(CompatSessionType::Unknown) => filter.unknown_only(),
                     None => filter,
                 };
+                let filter = match last_active_after {
+                    Some(last_active_after) => filter.with_last_active_after(last_active_after),
+                    None => filter,
+                };
+                let filter = match last_active_before {
+                    Some(last_active_before) => filter.with_last_active_before(last_active_before),
+                    None => filter,
+                };
 
                 let page = repo.compat_session().list(filter, pagination).await?;
 
@@ -239,6 +356,7 @@ impl User {
     }
 
     /// Get the list of active browser sessions, chronologically sorted
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn browser_sessions(
         &self,
         ctx: &Context<'_>,
@@ -246,6 +364,12 @@ impl User {
         #[graphql(name = "state", desc = "List only sessions in the given state.")]
         state_param: Option<SessionState>,
 
+        #[graphql(desc = "List only sessions last active after the given time.")]
+        last_active_after: Option<DateTime<Utc>>,
+
+        #[graphql(desc = "List only sessions last active before the given time.")]
+        last_active_before: Option<DateTime<Utc>>,
+
         #[graphql(desc = "Returns the elements in the list that come after the cursor.")]
         after: Option<String>,
         #[graphql(desc = "Returns the elements in the list that come before the cursor.")]
@@ -276,6 +400,14 @@ impl User {
                     Some(SessionState::Finished) => filter.finished_only(),
                     None => filter,
                 };
+                let filter = match last_active_after {
+                    Some(last_active_after) => filter.with_last_active_after(last_active_after),
+                    None => filter,
+                };
+                let filter = match last_active_before {
+                    Some(last_active_before) => filter.with_last_active_before(last_active_before),
+                    None => filter,
+                };
 
                 let page = repo.browser_session().list(filter, pagination).await?;
 
@@ -307,6 +439,7 @@ impl User {
     }
 
     /// Get the list of emails, chronologically sorted
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn emails(
         &self,
         ctx: &Context<'_>,
@@ -377,6 +510,7 @@ impl User {
 
     /// Get the list of OAuth 2.0 sessions, chronologically sorted
     #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn oauth2_sessions(
         &self,
         ctx: &Context<'_>,
@@ -386,6 +520,12 @@ impl User {
 
         #[graphql(desc = "List only sessions for the given client.")] client: Option<ID>,
 
+        #[graphql(desc = "List only sessions last active after the given time.")]
+        last_active_after: Option<DateTime<Utc>>,
+
+        #[graphql(desc = "List only sessions last active before the given time.")]
+        last_active_before: Option<DateTime<Utc>>,
+
         #[graphql(desc = "Returns the elements in the list that come after the cursor.")]
         after: Option<String>,
         #[graphql(desc = "Returns the elements in the list that come before the cursor.")]
@@ -437,6 +577,15 @@ impl User {
                     None => filter,
                 };
 
+                let filter = match last_active_after {
+                    Some(last_active_after) => filter.with_last_active_after(last_active_after),
+                    None => filter,
+                };
+                let filter = match last_active_before {
+                    Some(last_active_before) => filter.with_last_active_before(last_active_before),
+                    None => filter,
+                };
+
                 let page = repo.oauth2_session().list(filter, pagination).await?;
 
                 let count = if ctx.look_ahead().field("totalCount").exists() {
@@ -467,6 +616,7 @@ impl User {
     }
 
     /// Get the list of upstream OAuth 2.0 links
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn upstream_oauth2_links(
         &self,
         ctx: &Context<'_>,
@@ -534,6 +684,7 @@ impl User {
     /// Get the list of both compat and OAuth 2.0 sessions, chronologically
     /// sorted
     #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn app_sessions(
         &self,
         ctx: &Context<'_>,
@@ -667,3 +818,13 @@ pub enum UserEmailState {
     /// The email address has been confirmed.
     Confirmed,
 }
+
+/// The state of a user account.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum UserState {
+    /// The user can use their account.
+    Active,
+
+    /// The user was locked out of their account by an administrator.
+    Locked,
+}
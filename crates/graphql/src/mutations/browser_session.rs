@@ -13,10 +13,15 @@
 // limitations under the License.
 
 use async_graphql::{Context, Enum, InputObject, Object, ID};
-use mas_storage::RepositoryAccess;
+use mas_storage::{
+    job::{JobRepositoryExt, SendBackchannelLogoutJob},
+    oauth2::OAuth2SessionFilter,
+    user::UserSignInNotificationRepository,
+    Pagination, RepositoryAccess,
+};
 
 use crate::{
-    model::{BrowserSession, NodeType},
+    model::{BrowserSession, NodeType, SignInNotification},
     state::ContextExt,
 };
 
@@ -67,6 +72,48 @@ impl EndBrowserSessionPayload {
     }
 }
 
+/// The input of the `acknowledgeSignIn` mutation.
+#[derive(InputObject)]
+pub struct AcknowledgeSignInInput {
+    /// The ID of the sign-in notification to acknowledge.
+    sign_in_notification_id: ID,
+}
+
+/// The payload of the `acknowledgeSignIn` mutation.
+pub enum AcknowledgeSignInPayload {
+    NotFound,
+    Acknowledged(Box<mas_data_model::UserSignInNotification>),
+}
+
+/// The status of the `acknowledgeSignIn` mutation.
+#[derive(Enum, Copy, Clone, PartialEq, Eq, Debug)]
+enum AcknowledgeSignInStatus {
+    /// The sign-in was acknowledged.
+    Acknowledged,
+
+    /// The sign-in notification was not found.
+    NotFound,
+}
+
+#[Object]
+impl AcknowledgeSignInPayload {
+    /// The status of the mutation.
+    async fn status(&self) -> AcknowledgeSignInStatus {
+        match self {
+            Self::Acknowledged(_) => AcknowledgeSignInStatus::Acknowledged,
+            Self::NotFound => AcknowledgeSignInStatus::NotFound,
+        }
+    }
+
+    /// Returns the acknowledged sign-in notification.
+    async fn sign_in_notification(&self) -> Option<SignInNotification> {
+        match self {
+            Self::Acknowledged(notification) => Some(SignInNotification(*notification.clone())),
+            Self::NotFound => None,
+        }
+    }
+}
+
 #[Object]
 impl BrowserSessionMutations {
     async fn end_browser_session(
@@ -92,10 +139,74 @@ impl BrowserSessionMutations {
             return Ok(EndBrowserSessionPayload::NotFound);
         }
 
+        // Notify the clients which had sessions attached to this browser session
+        let filter = OAuth2SessionFilter::new()
+            .for_browser_session(&session)
+            .active_only();
+        let mut pagination = Pagination::first(100);
+        loop {
+            let page = repo.oauth2_session().list(filter, pagination).await?;
+
+            for oauth2_session in &page.edges {
+                repo.job()
+                    .schedule_job(SendBackchannelLogoutJob::new(oauth2_session))
+                    .await?;
+            }
+
+            match page.edges.last() {
+                Some(last) if page.has_next_page => pagination = pagination.after(last.id),
+                _ => break,
+            }
+        }
+
         let session = repo.browser_session().finish(&clock, session).await?;
 
         repo.save().await?;
 
         Ok(EndBrowserSessionPayload::Ended(Box::new(session)))
     }
+
+    /// Acknowledge a new sign-in of the user, so that it stops being shown in
+    /// their other sessions.
+    async fn acknowledge_sign_in(
+        &self,
+        ctx: &Context<'_>,
+        input: AcknowledgeSignInInput,
+    ) -> Result<AcknowledgeSignInPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let notification_id =
+            NodeType::SignInNotification.extract_ulid(&input.sign_in_notification_id)?;
+        let requester = ctx.requester();
+
+        let mut repo = state.repository().await?;
+        let clock = state.clock();
+
+        let notification = repo
+            .user_sign_in_notification()
+            .lookup(notification_id)
+            .await?;
+
+        let Some(notification) = notification else {
+            return Ok(AcknowledgeSignInPayload::NotFound);
+        };
+
+        if !requester.is_owner_or_admin(&notification) {
+            return Ok(AcknowledgeSignInPayload::NotFound);
+        }
+
+        // Acknowledging twice is a no-op
+        let notification = if notification.is_acknowledged() {
+            notification
+        } else {
+            repo.user_sign_in_notification()
+                .acknowledge(&clock, notification)
+                .await?
+        };
+
+        repo.save().await?;
+
+        Ok(AcknowledgeSignInPayload::Acknowledged(Box::new(
+            notification,
+        )))
+    }
 }
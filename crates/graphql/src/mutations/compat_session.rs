@@ -16,7 +16,7 @@ use anyhow::Context as _;
 use async_graphql::{Context, Enum, InputObject, Object, ID};
 use mas_storage::{
     compat::CompatSessionRepository,
-    job::{DeleteDeviceJob, JobRepositoryExt},
+    job::{DeleteDeviceJob, JobRepositoryExt, ProvisionDeviceJob},
     RepositoryAccess,
 };
 
@@ -72,6 +72,56 @@ impl EndCompatSessionPayload {
     }
 }
 
+/// The input of the `setCompatSessionName` mutation.
+#[derive(InputObject)]
+pub struct SetCompatSessionNameInput {
+    /// The ID of the session to rename.
+    compat_session_id: ID,
+
+    /// The new name of the session. If `None`, the name will be removed.
+    human_name: Option<String>,
+}
+
+/// The payload of the `setCompatSessionName` mutation.
+pub enum SetCompatSessionNamePayload {
+    NotFound,
+    Invalid,
+    Updated(mas_data_model::CompatSession),
+}
+
+/// The status of the `setCompatSessionName` mutation.
+#[derive(Enum, Copy, Clone, PartialEq, Eq, Debug)]
+enum SetCompatSessionNameStatus {
+    /// The session was renamed.
+    Updated,
+
+    /// The session was not found.
+    NotFound,
+
+    /// The name is invalid.
+    Invalid,
+}
+
+#[Object]
+impl SetCompatSessionNamePayload {
+    /// The status of the mutation.
+    async fn status(&self) -> SetCompatSessionNameStatus {
+        match self {
+            Self::Updated(_) => SetCompatSessionNameStatus::Updated,
+            Self::NotFound => SetCompatSessionNameStatus::NotFound,
+            Self::Invalid => SetCompatSessionNameStatus::Invalid,
+        }
+    }
+
+    /// The session with the new name.
+    async fn compat_session(&self) -> Option<CompatSession> {
+        match self {
+            Self::Updated(session) => Some(CompatSession::new(session.clone())),
+            Self::NotFound | Self::Invalid => None,
+        }
+    }
+}
+
 #[Object]
 impl CompatSessionMutations {
     async fn end_compat_session(
@@ -112,4 +162,59 @@ impl CompatSessionMutations {
 
         Ok(EndCompatSessionPayload::Ended(session))
     }
+    /// Rename a compatibility session, for example to "Work laptop". The name
+    /// is also used as the display name of the device on the homeserver.
+    async fn set_compat_session_name(
+        &self,
+        ctx: &Context<'_>,
+        input: SetCompatSessionNameInput,
+    ) -> Result<SetCompatSessionNamePayload, async_graphql::Error> {
+        let state = ctx.state();
+        let compat_session_id = NodeType::CompatSession.extract_ulid(&input.compat_session_id)?;
+        let requester = ctx.requester();
+
+        if input
+            .human_name
+            .as_ref()
+            .is_some_and(|name| name.is_empty() || name.len() > 256)
+        {
+            return Ok(SetCompatSessionNamePayload::Invalid);
+        }
+
+        let mut repo = state.repository().await?;
+
+        let session = repo.compat_session().lookup(compat_session_id).await?;
+        let Some(session) = session else {
+            return Ok(SetCompatSessionNamePayload::NotFound);
+        };
+
+        if !requester.is_owner_or_admin(&session) {
+            return Ok(SetCompatSessionNamePayload::NotFound);
+        }
+
+        let session = repo
+            .compat_session()
+            .set_human_name(session, input.human_name)
+            .await?;
+
+        // Rename the device on the homeserver as well
+        if let Some(human_name) = &session.human_name {
+            let user = repo
+                .user()
+                .lookup(session.user_id)
+                .await?
+                .context("Could not load user")?;
+
+            repo.job()
+                .schedule_job(
+                    ProvisionDeviceJob::new(&user, &session.device)
+                        .set_display_name(human_name.clone()),
+                )
+                .await?;
+        }
+
+        repo.save().await?;
+
+        Ok(SetCompatSessionNamePayload::Updated(session))
+    }
 }
@@ -12,8 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io::Read;
+
 use anyhow::Context as _;
-use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
+use async_graphql::{Context, Description, Enum, InputObject, Object, Upload, ID};
+use ulid::Ulid;
 
 use crate::{
     model::{NodeType, User},
@@ -71,6 +74,96 @@ impl SetDisplayNamePayload {
     }
 }
 
+/// The input for the `uploadAvatar` mutation
+#[derive(InputObject)]
+struct UploadAvatarInput {
+    /// The ID of the user to set the avatar of
+    user_id: ID,
+
+    /// The image to use as avatar
+    file: Upload,
+}
+
+/// The status of the `uploadAvatar` mutation
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum UploadAvatarStatus {
+    /// The avatar was uploaded and set
+    Uploaded,
+    /// The file is larger than what the server accepts
+    TooLarge,
+    /// The file is not a PNG, JPEG, GIF or WebP image
+    InvalidType,
+    /// Avatar uploads are disabled on this server
+    Disabled,
+}
+
+/// The payload of the `uploadAvatar` mutation
+#[derive(Description)]
+enum UploadAvatarPayload {
+    Uploaded(User),
+    TooLarge,
+    InvalidType,
+    Disabled,
+}
+
+#[Object(use_type_description)]
+impl UploadAvatarPayload {
+    /// Status of the operation
+    async fn status(&self) -> UploadAvatarStatus {
+        match self {
+            UploadAvatarPayload::Uploaded(_) => UploadAvatarStatus::Uploaded,
+            UploadAvatarPayload::TooLarge => UploadAvatarStatus::TooLarge,
+            UploadAvatarPayload::InvalidType => UploadAvatarStatus::InvalidType,
+            UploadAvatarPayload::Disabled => UploadAvatarStatus::Disabled,
+        }
+    }
+
+    /// The user that was updated
+    async fn user(&self) -> Option<&User> {
+        match self {
+            UploadAvatarPayload::Uploaded(user) => Some(user),
+            _ => None,
+        }
+    }
+}
+
+/// The input for the `removeAvatar` mutation
+#[derive(InputObject)]
+struct RemoveAvatarInput {
+    /// The ID of the user to remove the avatar of
+    user_id: ID,
+}
+
+/// The status of the `removeAvatar` mutation
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+pub enum RemoveAvatarStatus {
+    /// The avatar was removed
+    Removed,
+}
+
+/// The payload of the `removeAvatar` mutation
+#[derive(Description)]
+enum RemoveAvatarPayload {
+    Removed(User),
+}
+
+#[Object(use_type_description)]
+impl RemoveAvatarPayload {
+    /// Status of the operation
+    async fn status(&self) -> RemoveAvatarStatus {
+        match self {
+            RemoveAvatarPayload::Removed(_) => RemoveAvatarStatus::Removed,
+        }
+    }
+
+    /// The user that was updated
+    async fn user(&self) -> Option<&User> {
+        match self {
+            RemoveAvatarPayload::Removed(user) => Some(user),
+        }
+    }
+}
+
 #[Object]
 impl MatrixMutations {
     /// Set the display name of a user
@@ -119,4 +212,98 @@ impl MatrixMutations {
 
         Ok(SetDisplayNamePayload::Set(User(user.clone())))
     }
+
+    /// Upload an image and set it as the avatar of a user
+    async fn upload_avatar(
+        &self,
+        ctx: &Context<'_>,
+        input: UploadAvatarInput,
+    ) -> Result<UploadAvatarPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let id = NodeType::User.extract_ulid(&input.user_id)?;
+        let requester = ctx.requester();
+
+        if !requester.is_owner_or_admin(&UserId(id)) {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let Some(store) = state.avatar_store() else {
+            return Ok(UploadAvatarPayload::Disabled);
+        };
+
+        let max_size = store.max_size();
+        let upload = input.file.value(ctx)?;
+        if upload.size()? > max_size as u64 {
+            return Ok(UploadAvatarPayload::TooLarge);
+        }
+
+        let mut content = Vec::new();
+        upload
+            .into_read()
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut content)
+            .context("Failed to read the uploaded file")?;
+
+        if content.len() > max_size {
+            return Ok(UploadAvatarPayload::TooLarge);
+        }
+
+        let mut repo = state.repository().await?;
+        let user = repo
+            .user()
+            .lookup(id)
+            .await?
+            .context("Failed to lookup user")?;
+        repo.cancel().await?;
+
+        let clock = state.clock();
+        let mut rng = state.rng();
+        let avatar_id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+        let Some(avatar_url) = store
+            .save(avatar_id, content)
+            .await
+            .context("Failed to save avatar")?
+        else {
+            return Ok(UploadAvatarPayload::InvalidType);
+        };
+
+        let conn = state.homeserver_connection();
+        let mxid = conn.mxid(&user.username);
+        conn.set_avatar_url(&mxid, avatar_url.as_str())
+            .await
+            .context("Failed to set avatar")?;
+
+        Ok(UploadAvatarPayload::Uploaded(User(user)))
+    }
+
+    /// Remove the avatar of a user
+    async fn remove_avatar(
+        &self,
+        ctx: &Context<'_>,
+        input: RemoveAvatarInput,
+    ) -> Result<RemoveAvatarPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let id = NodeType::User.extract_ulid(&input.user_id)?;
+        let requester = ctx.requester();
+
+        if !requester.is_owner_or_admin(&UserId(id)) {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+        let user = repo
+            .user()
+            .lookup(id)
+            .await?
+            .context("Failed to lookup user")?;
+        repo.cancel().await?;
+
+        let conn = state.homeserver_connection();
+        let mxid = conn.mxid(&user.username);
+        conn.unset_avatar_url(&mxid)
+            .await
+            .context("Failed to remove avatar")?;
+
+        Ok(RemoveAvatarPayload::Removed(User(user)))
+    }
 }
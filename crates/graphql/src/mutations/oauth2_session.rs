@@ -12,24 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
 use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
 use chrono::Duration;
 use mas_data_model::{Device, TokenType};
 use mas_storage::{
-    job::{DeleteDeviceJob, JobRepositoryExt, ProvisionDeviceJob},
+    job::{JobRepositoryExt, ProvisionDeviceJob},
     oauth2::{
-        OAuth2AccessTokenRepository, OAuth2ClientRepository, OAuth2RefreshTokenRepository,
-        OAuth2SessionRepository,
+        end_session, OAuth2AccessTokenRepository, OAuth2ClientRepository,
+        OAuth2RefreshTokenRepository, OAuth2SessionFilter, OAuth2SessionRepository,
     },
     user::UserRepository,
-    RepositoryAccess,
+    Pagination, RepositoryAccess,
 };
 use oauth2_types::scope::Scope;
+use ulid::Ulid;
 
 use crate::{
-    model::{NodeType, OAuth2Session},
+    model::{NodeType, OAuth2Client, OAuth2Session},
     state::ContextExt,
+    UserId,
 };
 
 #[derive(Default)]
@@ -118,6 +122,112 @@ impl EndOAuth2SessionPayload {
     }
 }
 
+/// The input of the `endOauth2ClientSessions` mutation.
+#[derive(InputObject)]
+pub struct EndOAuth2ClientSessionsInput {
+    /// The ID of the user whose sessions should be ended.
+    user_id: ID,
+
+    /// The ID of the client to revoke access from.
+    oauth2_client_id: ID,
+}
+
+/// The payload of the `endOauth2ClientSessions` mutation.
+pub enum EndOAuth2ClientSessionsPayload {
+    NotFound,
+    Ended {
+        client: mas_data_model::Client,
+        count: usize,
+    },
+}
+
+/// The status of the `endOauth2ClientSessions` mutation.
+#[derive(Enum, Copy, Clone, PartialEq, Eq, Debug)]
+enum EndOAuth2ClientSessionsStatus {
+    /// The sessions of the client were ended.
+    Ended,
+
+    /// The user or the client was not found.
+    NotFound,
+}
+
+#[Object]
+impl EndOAuth2ClientSessionsPayload {
+    /// The status of the mutation.
+    async fn status(&self) -> EndOAuth2ClientSessionsStatus {
+        match self {
+            Self::Ended { .. } => EndOAuth2ClientSessionsStatus::Ended,
+            Self::NotFound => EndOAuth2ClientSessionsStatus::NotFound,
+        }
+    }
+
+    /// The client which lost access to the account.
+    async fn oauth2_client(&self) -> Option<OAuth2Client> {
+        match self {
+            Self::Ended { client, .. } => Some(OAuth2Client(client.clone())),
+            Self::NotFound => None,
+        }
+    }
+
+    /// The number of sessions which were ended.
+    async fn ended_sessions(&self) -> usize {
+        match self {
+            Self::Ended { count, .. } => *count,
+            Self::NotFound => 0,
+        }
+    }
+}
+
+/// The input of the `setOauth2SessionName` mutation.
+#[derive(InputObject)]
+pub struct SetOAuth2SessionNameInput {
+    /// The ID of the session to rename.
+    oauth2_session_id: ID,
+
+    /// The new name of the session. If `None`, the name will be removed.
+    human_name: Option<String>,
+}
+
+/// The payload of the `setOauth2SessionName` mutation.
+pub enum SetOAuth2SessionNamePayload {
+    NotFound,
+    Invalid,
+    Updated(mas_data_model::Session),
+}
+
+/// The status of the `setOauth2SessionName` mutation.
+#[derive(Enum, Copy, Clone, PartialEq, Eq, Debug)]
+enum SetOAuth2SessionNameStatus {
+    /// The session was renamed.
+    Updated,
+
+    /// The session was not found.
+    NotFound,
+
+    /// The name is invalid.
+    Invalid,
+}
+
+#[Object]
+impl SetOAuth2SessionNamePayload {
+    /// The status of the mutation.
+    async fn status(&self) -> SetOAuth2SessionNameStatus {
+        match self {
+            Self::Updated(_) => SetOAuth2SessionNameStatus::Updated,
+            Self::NotFound => SetOAuth2SessionNameStatus::NotFound,
+            Self::Invalid => SetOAuth2SessionNameStatus::Invalid,
+        }
+    }
+
+    /// The session with the new name.
+    async fn oauth2_session(&self) -> Option<OAuth2Session> {
+        match self {
+            Self::Updated(session) => Some(OAuth2Session(session.clone())),
+            Self::NotFound | Self::Invalid => None,
+        }
+    }
+}
+
 #[Object]
 impl OAuth2SessionMutations {
     /// Create a new arbitrary OAuth 2.0 Session.
@@ -229,33 +339,136 @@ impl OAuth2SessionMutations {
             return Ok(EndOAuth2SessionPayload::NotFound);
         }
 
-        if let Some(user_id) = session.user_id {
+        let session = end_session(&mut repo, &clock, session).await?;
+
+        repo.save().await?;
+
+        Ok(EndOAuth2SessionPayload::Ended(session))
+    }
+
+    /// End all the active sessions of a user with a client, revoking the
+    /// access this client has to their account.
+    async fn end_oauth2_client_sessions(
+        &self,
+        ctx: &Context<'_>,
+        input: EndOAuth2ClientSessionsInput,
+    ) -> Result<EndOAuth2ClientSessionsPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let client_id = NodeType::OAuth2Client.extract_ulid(&input.oauth2_client_id)?;
+        let requester = ctx.requester();
+
+        if !requester.is_owner_or_admin(&UserId(user_id)) {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+        let clock = state.clock();
+
+        let Some(user) = repo.user().lookup(user_id).await? else {
+            return Ok(EndOAuth2ClientSessionsPayload::NotFound);
+        };
+
+        let Some(client) = repo.oauth2_client().lookup(client_id).await? else {
+            return Ok(EndOAuth2ClientSessionsPayload::NotFound);
+        };
+
+        // Collect the sessions first, as ending them changes what the filter
+        // matches
+        let filter = OAuth2SessionFilter::new()
+            .for_user(&user)
+            .for_client(&client)
+            .active_only();
+        let mut sessions = Vec::new();
+        let mut pagination = Pagination::first(100);
+        loop {
+            let page = repo.oauth2_session().list(filter, pagination).await?;
+            let has_next_page = page.has_next_page;
+            let last = page.edges.last().map(|session| session.id);
+            sessions.extend(page.edges);
+
+            match last {
+                Some(last) if has_next_page => pagination = pagination.after(last),
+                _ => break,
+            }
+        }
+
+        // Derived sessions end along with their parent session, so they are
+        // skipped if it is one of them
+        let count = sessions.len();
+        let ids: HashSet<Ulid> = sessions.iter().map(|session| session.id).collect();
+        for session in sessions {
+            if session
+                .parent_session_id
+                .is_some_and(|parent_id| ids.contains(&parent_id))
+            {
+                continue;
+            }
+
+            end_session(&mut repo, &clock, session).await?;
+        }
+
+        repo.save().await?;
+
+        Ok(EndOAuth2ClientSessionsPayload::Ended { client, count })
+    }
+    /// Rename an OAuth 2.0 session, for example to "Work laptop". The name is
+    /// also used as the display name of its device on the homeserver.
+    async fn set_oauth2_session_name(
+        &self,
+        ctx: &Context<'_>,
+        input: SetOAuth2SessionNameInput,
+    ) -> Result<SetOAuth2SessionNamePayload, async_graphql::Error> {
+        let state = ctx.state();
+        let oauth2_session_id = NodeType::OAuth2Session.extract_ulid(&input.oauth2_session_id)?;
+        let requester = ctx.requester();
+
+        if input
+            .human_name
+            .as_ref()
+            .is_some_and(|name| name.is_empty() || name.len() > 256)
+        {
+            return Ok(SetOAuth2SessionNamePayload::Invalid);
+        }
+
+        let mut repo = state.repository().await?;
+
+        let session = repo.oauth2_session().lookup(oauth2_session_id).await?;
+        let Some(session) = session else {
+            return Ok(SetOAuth2SessionNamePayload::NotFound);
+        };
+
+        if !requester.is_owner_or_admin(&session) {
+            return Ok(SetOAuth2SessionNamePayload::NotFound);
+        }
+
+        let session = repo
+            .oauth2_session()
+            .set_human_name(session, input.human_name)
+            .await?;
+
+        // Rename the devices of the session on the homeserver as well
+        if let (Some(user_id), Some(human_name)) = (session.user_id, &session.human_name) {
             let user = repo
                 .user()
                 .lookup(user_id)
                 .await?
                 .context("Could not load user")?;
 
-            // Scan the scopes of the session to find if there is any device that should be
-            // deleted from the Matrix server.
-            // TODO: this should be moved in a higher level "end oauth session" method.
-            // XXX: this might not be the right semantic, but it's the best we
-            // can do for now, since we're not explicitly storing devices for OAuth2
-            // sessions.
             for scope in &*session.scope {
                 if let Some(device) = Device::from_scope_token(scope) {
-                    // Schedule a job to delete the device.
                     repo.job()
-                        .schedule_job(DeleteDeviceJob::new(&user, &device))
+                        .schedule_job(
+                            ProvisionDeviceJob::new(&user, &device)
+                                .set_display_name(human_name.clone()),
+                        )
                         .await?;
                 }
             }
         }
 
-        let session = repo.oauth2_session().finish(&clock, session).await?;
-
         repo.save().await?;
 
-        Ok(EndOAuth2SessionPayload::Ended(session))
+        Ok(SetOAuth2SessionNamePayload::Updated(session))
     }
 }
@@ -14,9 +14,14 @@
 
 use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
+use mas_data_model::{is_valid_username, UserRecoveryCode};
+use mas_i18n::DataLocale;
 use mas_storage::{
-    job::{DeactivateUserJob, JobRepositoryExt, ProvisionUserJob},
-    user::UserRepository,
+    job::{
+        DeactivateUserJob, EndUserSessionsJob, ForcePasswordResetJob, JobRepositoryExt,
+        ProvisionUserJob,
+    },
+    user::{UserRecoveryCodeRepository, UserRepository},
 };
 use tracing::info;
 
@@ -36,6 +41,10 @@ pub struct UserMutations {
 struct AddUserInput {
     /// The username of the user to add.
     username: String,
+
+    /// Whether the user is a service account, which can't log in
+    /// interactively. Defaults to `false`.
+    service_account: Option<bool>,
 }
 
 /// The status of the `addUser` mutation.
@@ -128,6 +137,156 @@ impl LockUserPayload {
     }
 }
 
+/// The input for the `deleteUser` mutation.
+#[derive(InputObject)]
+struct DeleteUserInput {
+    /// The ID of the user to delete.
+    user_id: ID,
+
+    /// Deactivate the user on the homeserver right away, instead of when it
+    /// gets purged. The user can still be restored, but not on the
+    /// homeserver.
+    deactivate: Option<bool>,
+}
+
+/// The status of the `deleteUser` mutation.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum DeleteUserStatus {
+    /// The user was deleted.
+    Deleted,
+
+    /// The user was not found.
+    NotFound,
+}
+
+/// The payload for the `deleteUser` mutation.
+#[derive(Description)]
+enum DeleteUserPayload {
+    /// The user was deleted.
+    Deleted(mas_data_model::User),
+
+    /// The user was not found.
+    NotFound,
+}
+
+#[Object(use_type_description)]
+impl DeleteUserPayload {
+    /// Status of the operation
+    async fn status(&self) -> DeleteUserStatus {
+        match self {
+            Self::Deleted(_) => DeleteUserStatus::Deleted,
+            Self::NotFound => DeleteUserStatus::NotFound,
+        }
+    }
+
+    /// The user that was deleted.
+    async fn user(&self) -> Option<User> {
+        match self {
+            Self::Deleted(user) => Some(User(user.clone())),
+            Self::NotFound => None,
+        }
+    }
+}
+
+/// The input for the `restoreUser` mutation.
+#[derive(InputObject)]
+struct RestoreUserInput {
+    /// The ID of the user to restore.
+    user_id: ID,
+}
+
+/// The status of the `restoreUser` mutation.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum RestoreUserStatus {
+    /// The user was restored.
+    Restored,
+
+    /// The user was already purged, and can't be restored anymore.
+    Purged,
+
+    /// The user was not found.
+    NotFound,
+}
+
+/// The payload for the `restoreUser` mutation.
+#[derive(Description)]
+enum RestoreUserPayload {
+    /// The user was restored.
+    Restored(mas_data_model::User),
+
+    /// The user was already purged.
+    Purged(mas_data_model::User),
+
+    /// The user was not found.
+    NotFound,
+}
+
+#[Object(use_type_description)]
+impl RestoreUserPayload {
+    /// Status of the operation
+    async fn status(&self) -> RestoreUserStatus {
+        match self {
+            Self::Restored(_) => RestoreUserStatus::Restored,
+            Self::Purged(_) => RestoreUserStatus::Purged,
+            Self::NotFound => RestoreUserStatus::NotFound,
+        }
+    }
+
+    /// The user that was restored.
+    async fn user(&self) -> Option<User> {
+        match self {
+            Self::Restored(user) | Self::Purged(user) => Some(User(user.clone())),
+            Self::NotFound => None,
+        }
+    }
+}
+
+/// The input for the `forcePasswordReset` mutation.
+#[derive(InputObject)]
+struct ForcePasswordResetInput {
+    /// The ID of the user who must reset their password.
+    user_id: ID,
+}
+
+/// The status of the `forcePasswordReset` mutation.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum ForcePasswordResetStatus {
+    /// The user must now reset their password.
+    Required,
+
+    /// The user was not found.
+    NotFound,
+}
+
+/// The payload for the `forcePasswordReset` mutation.
+#[derive(Description)]
+enum ForcePasswordResetPayload {
+    /// The user must now reset their password.
+    Required(mas_data_model::User),
+
+    /// The user was not found.
+    NotFound,
+}
+
+#[Object(use_type_description)]
+impl ForcePasswordResetPayload {
+    /// Status of the operation
+    async fn status(&self) -> ForcePasswordResetStatus {
+        match self {
+            Self::Required(_) => ForcePasswordResetStatus::Required,
+            Self::NotFound => ForcePasswordResetStatus::NotFound,
+        }
+    }
+
+    /// The user who must reset their password.
+    async fn user(&self) -> Option<User> {
+        match self {
+            Self::Required(user) => Some(User(user.clone())),
+            Self::NotFound => None,
+        }
+    }
+}
+
 /// The input for the `setCanRequestAdmin` mutation.
 #[derive(InputObject)]
 struct SetCanRequestAdminInput {
@@ -187,34 +346,107 @@ impl AllowUserCrossSigningResetPayload {
     }
 }
 
-fn valid_username_character(c: char) -> bool {
-    c.is_ascii_lowercase()
-        || c.is_ascii_digit()
-        || c == '='
-        || c == '_'
-        || c == '-'
-        || c == '.'
-        || c == '/'
-        || c == '+'
+/// The input for the `setPreferredLanguage` mutation.
+#[derive(InputObject)]
+struct SetPreferredLanguageInput {
+    /// The ID of the user to update.
+    user_id: ID,
+
+    /// The preferred language of the user, as a BCP 47 language tag.
+    locale: String,
+}
+
+/// The status of the `setPreferredLanguage` mutation.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum SetPreferredLanguageStatus {
+    /// The preferred language was updated.
+    Updated,
+
+    /// The user was not found.
+    NotFound,
+
+    /// The language tag is invalid.
+    Invalid,
+}
+
+/// The payload for the `setPreferredLanguage` mutation.
+#[derive(Description)]
+enum SetPreferredLanguagePayload {
+    Updated(mas_data_model::User),
+    NotFound,
+    Invalid,
 }
 
-// XXX: this should probably be moved somewhere else
-fn username_valid(username: &str) -> bool {
-    if username.is_empty() || username.len() > 255 {
-        return false;
+#[Object(use_type_description)]
+impl SetPreferredLanguagePayload {
+    /// Status of the operation
+    async fn status(&self) -> SetPreferredLanguageStatus {
+        match self {
+            Self::Updated(_) => SetPreferredLanguageStatus::Updated,
+            Self::NotFound => SetPreferredLanguageStatus::NotFound,
+            Self::Invalid => SetPreferredLanguageStatus::Invalid,
+        }
     }
 
-    // Should not start with an underscore
-    if username.get(0..1) == Some("_") {
-        return false;
+    /// The user that was updated.
+    async fn user(&self) -> Option<User> {
+        match self {
+            Self::Updated(user) => Some(User(user.clone())),
+            Self::NotFound | Self::Invalid => None,
+        }
     }
+}
 
-    // Should only contain valid characters
-    if !username.chars().all(valid_username_character) {
-        return false;
+/// The input for the `regenerateRecoveryCodes` mutation.
+#[derive(InputObject)]
+struct RegenerateRecoveryCodesInput {
+    /// The ID of the user to regenerate the recovery codes for.
+    user_id: ID,
+}
+
+/// The status of the `regenerateRecoveryCodes` mutation.
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+enum RegenerateRecoveryCodesStatus {
+    /// New recovery codes were generated.
+    Regenerated,
+
+    /// The user was not found.
+    NotFound,
+}
+
+/// The payload for the `regenerateRecoveryCodes` mutation.
+#[derive(Description)]
+enum RegenerateRecoveryCodesPayload {
+    Regenerated(mas_data_model::User, Vec<String>),
+    NotFound,
+}
+
+#[Object(use_type_description)]
+impl RegenerateRecoveryCodesPayload {
+    /// Status of the operation
+    async fn status(&self) -> RegenerateRecoveryCodesStatus {
+        match self {
+            Self::Regenerated(..) => RegenerateRecoveryCodesStatus::Regenerated,
+            Self::NotFound => RegenerateRecoveryCodesStatus::NotFound,
+        }
     }
 
-    true
+    /// The user whose recovery codes were regenerated.
+    async fn user(&self) -> Option<User> {
+        match self {
+            Self::Regenerated(user, _) => Some(User(user.clone())),
+            Self::NotFound => None,
+        }
+    }
+
+    /// The new recovery codes. They replace the previous ones, and can't be
+    /// retrieved again later.
+    async fn codes(&self) -> Option<&[String]> {
+        match self {
+            Self::Regenerated(_, codes) => Some(codes),
+            Self::NotFound => None,
+        }
+    }
 }
 
 #[Object]
@@ -241,11 +473,15 @@ impl UserMutations {
         }
 
         // Do some basic check on the username
-        if !username_valid(&input.username) {
+        if !is_valid_username(&input.username) {
             return Ok(AddUserPayload::Invalid);
         }
 
-        let user = repo.user().add(&mut rng, &clock, input.username).await?;
+        let mut user = repo.user().add(&mut rng, &clock, input.username).await?;
+
+        if input.service_account.unwrap_or(false) {
+            user = repo.user().set_service_account(user, true).await?;
+        }
 
         repo.job()
             .schedule_job(ProvisionUserJob::new(&user))
@@ -294,6 +530,125 @@ impl UserMutations {
         Ok(LockUserPayload::Locked(user))
     }
 
+    /// Delete a user. The user can't be used anymore, but can be restored
+    /// until the end of the grace period, after which its data is erased.
+    /// This is only available to administrators.
+    async fn delete_user(
+        &self,
+        ctx: &Context<'_>,
+        input: DeleteUserInput,
+    ) -> Result<DeleteUserPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let requester = ctx.requester();
+
+        if !requester.is_admin() {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let user = repo.user().lookup(user_id).await?;
+
+        let Some(user) = user else {
+            return Ok(DeleteUserPayload::NotFound);
+        };
+
+        info!("Deleting user {}", user.id);
+        let user = repo.user().soft_delete(&state.clock(), user).await?;
+
+        // End the sessions of the user, which revokes their tokens
+        if input.deactivate.unwrap_or(false) {
+            info!("Scheduling deactivation of user {}", user.id);
+            repo.job()
+                .schedule_job(DeactivateUserJob::new(&user, false))
+                .await?;
+        } else {
+            repo.job()
+                .schedule_job(EndUserSessionsJob::new(&user))
+                .await?;
+        }
+
+        repo.save().await?;
+
+        Ok(DeleteUserPayload::Deleted(user))
+    }
+
+    /// Restore a deleted user, as long as it wasn't purged yet. This is only
+    /// available to administrators.
+    async fn restore_user(
+        &self,
+        ctx: &Context<'_>,
+        input: RestoreUserInput,
+    ) -> Result<RestoreUserPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let requester = ctx.requester();
+
+        if !requester.is_admin() {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let user = repo.user().lookup(user_id).await?;
+
+        let Some(user) = user else {
+            return Ok(RestoreUserPayload::NotFound);
+        };
+
+        if user.is_purged() {
+            return Ok(RestoreUserPayload::Purged(user));
+        }
+
+        info!("Restoring user {}", user.id);
+        let user = repo.user().restore(user).await?;
+
+        repo.save().await?;
+
+        Ok(RestoreUserPayload::Restored(user))
+    }
+
+    /// Require a user to choose a new password, e.g. after a suspected
+    /// credential leak. All their sessions are ended, and they get an email
+    /// with a link to choose a new password. This is only available to
+    /// administrators.
+    async fn force_password_reset(
+        &self,
+        ctx: &Context<'_>,
+        input: ForcePasswordResetInput,
+    ) -> Result<ForcePasswordResetPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let requester = ctx.requester();
+
+        if !requester.is_admin() {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let user = repo.user().lookup(user_id).await?;
+
+        let Some(user) = user else {
+            return Ok(ForcePasswordResetPayload::NotFound);
+        };
+
+        let user = repo
+            .user()
+            .require_password_reset(&state.clock(), user)
+            .await?;
+
+        info!("Scheduling forced password reset of user {}", user.id);
+        repo.job()
+            .schedule_job(ForcePasswordResetJob::new(&user))
+            .await?;
+
+        repo.save().await?;
+
+        Ok(ForcePasswordResetPayload::Required(user))
+    }
+
     /// Set whether a user can request admin. This is only available to
     /// administrators.
     async fn set_can_request_admin(
@@ -327,6 +682,40 @@ impl UserMutations {
         Ok(SetCanRequestAdminPayload::Updated(user))
     }
 
+    /// Set the preferred language of a user, used for the emails sent to them
+    /// and the pages they see.
+    async fn set_preferred_language(
+        &self,
+        ctx: &Context<'_>,
+        input: SetPreferredLanguageInput,
+    ) -> Result<SetPreferredLanguagePayload, async_graphql::Error> {
+        let state = ctx.state();
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let requester = ctx.requester();
+
+        if !requester.is_owner_or_admin(&UserId(user_id)) {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        // Normalize the language tag
+        let Ok(locale) = input.locale.parse::<DataLocale>() else {
+            return Ok(SetPreferredLanguagePayload::Invalid);
+        };
+
+        let mut repo = state.repository().await?;
+        let user = repo.user().lookup(user_id).await?;
+
+        let Some(user) = user else {
+            return Ok(SetPreferredLanguagePayload::NotFound);
+        };
+
+        let user = repo.user().set_locale(user, locale.to_string()).await?;
+
+        repo.save().await?;
+
+        Ok(SetPreferredLanguagePayload::Updated(user))
+    }
+
     /// Temporarily allow user to reset their cross-signing keys.
     async fn allow_user_cross_signing_reset(
         &self,
@@ -358,4 +747,48 @@ impl UserMutations {
 
         Ok(AllowUserCrossSigningResetPayload::Allowed(user))
     }
+
+    /// Generate a new set of recovery codes for a user, invalidating the
+    /// previous ones. This is only available to the user themselves, as the
+    /// codes are returned in the response.
+    async fn regenerate_recovery_codes(
+        &self,
+        ctx: &Context<'_>,
+        input: RegenerateRecoveryCodesInput,
+    ) -> Result<RegenerateRecoveryCodesPayload, async_graphql::Error> {
+        let state = ctx.state();
+        let user_id = NodeType::User.extract_ulid(&input.user_id)?;
+        let requester = ctx.requester();
+
+        if requester.user().map(|user| user.id) != Some(user_id) {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let mut repo = state.repository().await?;
+        let user = repo.user().lookup(user_id).await?;
+
+        let Some(user) = user else {
+            return Ok(RegenerateRecoveryCodesPayload::NotFound);
+        };
+
+        let clock = state.clock();
+        let mut rng = state.rng();
+        let codes: Vec<String> = (0..UserRecoveryCode::COUNT)
+            .map(|_| UserRecoveryCode::generate(&mut rng))
+            .collect();
+        let hashes = codes
+            .iter()
+            .map(|code| UserRecoveryCode::hash(code))
+            .collect();
+
+        repo.user_recovery_code()
+            .replace(&mut rng, &clock, &user, hashes)
+            .await?;
+
+        repo.save().await?;
+
+        info!("Regenerated recovery codes of user {}", user.id);
+
+        Ok(RegenerateRecoveryCodesPayload::Regenerated(user, codes))
+    }
 }
@@ -16,7 +16,7 @@ use anyhow::Context as _;
 use async_graphql::{Context, Description, Enum, InputObject, Object, ID};
 use mas_storage::{
     job::{JobRepositoryExt, ProvisionUserJob, VerifyEmailJob},
-    user::{UserEmailRepository, UserRepository},
+    user::{UserEmailFilter, UserEmailInUseError, UserEmailRepository, UserRepository},
     RepositoryAccess,
 };
 
@@ -58,6 +58,10 @@ pub enum AddEmailStatus {
     Invalid,
     /// The email address is not allowed by the policy
     Denied,
+    /// The email address is already in use by another account
+    InUse,
+    /// Too many emails were sent recently, try again later
+    RateLimited,
 }
 
 /// The payload of the `addEmail` mutation
@@ -69,6 +73,10 @@ enum AddEmailPayload {
     Denied {
         violations: Vec<mas_policy::Violation>,
     },
+    InUse,
+    RateLimited {
+        retry_after: u64,
+    },
 }
 
 #[Object(use_type_description)]
@@ -80,6 +88,8 @@ impl AddEmailPayload {
             AddEmailPayload::Exists(_) => AddEmailStatus::Exists,
             AddEmailPayload::Invalid => AddEmailStatus::Invalid,
             AddEmailPayload::Denied { .. } => AddEmailStatus::Denied,
+            AddEmailPayload::InUse => AddEmailStatus::InUse,
+            AddEmailPayload::RateLimited { .. } => AddEmailStatus::RateLimited,
         }
     }
 
@@ -89,7 +99,10 @@ impl AddEmailPayload {
             AddEmailPayload::Added(email) | AddEmailPayload::Exists(email) => {
                 Some(UserEmail(email.clone()))
             }
-            AddEmailPayload::Invalid | AddEmailPayload::Denied { .. } => None,
+            AddEmailPayload::Invalid
+            | AddEmailPayload::Denied { .. }
+            | AddEmailPayload::InUse
+            | AddEmailPayload::RateLimited { .. } => None,
         }
     }
 
@@ -100,7 +113,10 @@ impl AddEmailPayload {
 
         let user_id = match self {
             AddEmailPayload::Added(email) | AddEmailPayload::Exists(email) => email.user_id,
-            AddEmailPayload::Invalid | AddEmailPayload::Denied { .. } => return Ok(None),
+            AddEmailPayload::Invalid
+            | AddEmailPayload::Denied { .. }
+            | AddEmailPayload::InUse
+            | AddEmailPayload::RateLimited { .. } => return Ok(None),
         };
 
         let user = repo
@@ -121,6 +137,16 @@ impl AddEmailPayload {
         let messages = violations.iter().map(|v| v.msg.clone()).collect();
         Some(messages)
     }
+
+    /// How many seconds to wait before trying again, if the rate limit was
+    /// exceeded
+    async fn retry_after(&self) -> Option<u64> {
+        let AddEmailPayload::RateLimited { retry_after } = self else {
+            return None;
+        };
+
+        Some(*retry_after)
+    }
 }
 
 /// The input for the `sendVerificationEmail` mutation
@@ -137,6 +163,8 @@ enum SendVerificationEmailStatus {
     Sent,
     /// The email address is already verified
     AlreadyVerified,
+    /// Too many emails were sent recently, try again later
+    RateLimited,
 }
 
 /// The payload of the `sendVerificationEmail` mutation
@@ -144,6 +172,10 @@ enum SendVerificationEmailStatus {
 enum SendVerificationEmailPayload {
     Sent(mas_data_model::UserEmail),
     AlreadyVerified(mas_data_model::UserEmail),
+    RateLimited {
+        email: mas_data_model::UserEmail,
+        retry_after: u64,
+    },
 }
 
 #[Object(use_type_description)]
@@ -155,6 +187,9 @@ impl SendVerificationEmailPayload {
             SendVerificationEmailPayload::AlreadyVerified(_) => {
                 SendVerificationEmailStatus::AlreadyVerified
             }
+            SendVerificationEmailPayload::RateLimited { .. } => {
+                SendVerificationEmailStatus::RateLimited
+            }
         }
     }
 
@@ -162,7 +197,8 @@ impl SendVerificationEmailPayload {
     async fn email(&self) -> UserEmail {
         match self {
             SendVerificationEmailPayload::Sent(email)
-            | SendVerificationEmailPayload::AlreadyVerified(email) => UserEmail(email.clone()),
+            | SendVerificationEmailPayload::AlreadyVerified(email)
+            | SendVerificationEmailPayload::RateLimited { email, .. } => UserEmail(email.clone()),
         }
     }
 
@@ -173,7 +209,8 @@ impl SendVerificationEmailPayload {
 
         let user_id = match self {
             SendVerificationEmailPayload::Sent(email)
-            | SendVerificationEmailPayload::AlreadyVerified(email) => email.user_id,
+            | SendVerificationEmailPayload::AlreadyVerified(email)
+            | SendVerificationEmailPayload::RateLimited { email, .. } => email.user_id,
         };
 
         let user = repo
@@ -184,6 +221,16 @@ impl SendVerificationEmailPayload {
 
         Ok(User(user))
     }
+
+    /// How many seconds to wait before trying again, if the rate limit was
+    /// exceeded
+    async fn retry_after(&self) -> Option<u64> {
+        let SendVerificationEmailPayload::RateLimited { retry_after, .. } = self else {
+            return None;
+        };
+
+        Some(*retry_after)
+    }
 }
 
 /// The input for the `verifyEmail` mutation
@@ -204,6 +251,8 @@ enum VerifyEmailStatus {
     AlreadyVerified,
     /// The verification code is invalid
     InvalidCode,
+    /// The email address is already verified by another account
+    InUse,
 }
 
 /// The payload of the `verifyEmail` mutation
@@ -212,6 +261,7 @@ enum VerifyEmailPayload {
     Verified(mas_data_model::UserEmail),
     AlreadyVerified(mas_data_model::UserEmail),
     InvalidCode,
+    InUse,
 }
 
 #[Object(use_type_description)]
@@ -222,6 +272,7 @@ impl VerifyEmailPayload {
             VerifyEmailPayload::Verified(_) => VerifyEmailStatus::Verified,
             VerifyEmailPayload::AlreadyVerified(_) => VerifyEmailStatus::AlreadyVerified,
             VerifyEmailPayload::InvalidCode => VerifyEmailStatus::InvalidCode,
+            VerifyEmailPayload::InUse => VerifyEmailStatus::InUse,
         }
     }
 
@@ -231,7 +282,7 @@ impl VerifyEmailPayload {
             VerifyEmailPayload::Verified(email) | VerifyEmailPayload::AlreadyVerified(email) => {
                 Some(UserEmail(email.clone()))
             }
-            VerifyEmailPayload::InvalidCode => None,
+            VerifyEmailPayload::InvalidCode | VerifyEmailPayload::InUse => None,
         }
     }
 
@@ -244,7 +295,7 @@ impl VerifyEmailPayload {
             VerifyEmailPayload::Verified(email) | VerifyEmailPayload::AlreadyVerified(email) => {
                 email.user_id
             }
-            VerifyEmailPayload::InvalidCode => return Ok(None),
+            VerifyEmailPayload::InvalidCode | VerifyEmailPayload::InUse => return Ok(None),
         };
 
         let user = repo
@@ -410,14 +461,16 @@ impl UserEmailMutations {
         // XXX: this logic should be extracted somewhere else, since most of it is
         // duplicated in mas_handlers
 
+        let email = state.email_normalization().normalize(&input.email);
+
         // Validate the email address
-        if input.email.parse::<lettre::Address>().is_err() {
+        if email.parse::<lettre::Address>().is_err() {
             return Ok(AddEmailPayload::Invalid);
         }
 
         if !skip_policy_check {
             let mut policy = state.policy().await?;
-            let res = policy.evaluate_email(&input.email).await?;
+            let res = policy.evaluate_email(&email).await?;
             if !res.valid() {
                 return Ok(AddEmailPayload::Denied {
                     violations: res.violations,
@@ -426,16 +479,25 @@ impl UserEmailMutations {
         }
 
         // Find an existing email address
-        let existing_user_email = repo.user_email().find(&user, &input.email).await?;
+        let existing_user_email = repo.user_email().find(&user, &email).await?;
         let (added, mut user_email) = if let Some(user_email) = existing_user_email {
             (false, user_email)
         } else {
             let clock = state.clock();
             let mut rng = state.rng();
 
+            // A verified email address can only belong to one user
+            let in_use = repo
+                .user_email()
+                .count(UserEmailFilter::new().for_email(&email).verified_only())
+                .await?;
+            if in_use > 0 {
+                return Ok(AddEmailPayload::InUse);
+            }
+
             let user_email = repo
                 .user_email()
-                .add(&mut rng, &clock, &user, input.email)
+                .add(&mut rng, &clock, &user, email)
                 .await?;
 
             (true, user_email)
@@ -444,11 +506,27 @@ impl UserEmailMutations {
         // Schedule a job to verify the email address if needed
         if user_email.confirmed_at.is_none() {
             if skip_verification {
-                user_email = repo
+                user_email = match repo
                     .user_email()
                     .mark_as_verified(&state.clock(), user_email)
-                    .await?;
+                    .await
+                {
+                    Ok(user_email) => user_email,
+                    Err(e) if UserEmailInUseError::is_cause_of(&e) => {
+                        return Ok(AddEmailPayload::InUse);
+                    }
+                    Err(e) => return Err(e.into()),
+                };
             } else {
+                // Returning early rolls back the address which was just added
+                if let Err(retry_after) = state
+                    .email_throttle()
+                    .check(&state.clock(), &user, &user_email.email)
+                    .await
+                {
+                    return Ok(AddEmailPayload::RateLimited { retry_after });
+                }
+
                 // TODO: figure out the locale
                 repo.job()
                     .schedule_job(VerifyEmailJob::new(&user_email))
@@ -491,6 +569,23 @@ impl UserEmailMutations {
         // Schedule a job to verify the email address if needed
         let needs_verification = user_email.confirmed_at.is_none();
         if needs_verification {
+            let user = repo
+                .user()
+                .lookup(user_email.user_id)
+                .await?
+                .context("User not found")?;
+
+            if let Err(retry_after) = state
+                .email_throttle()
+                .check(&state.clock(), &user, &user_email.email)
+                .await
+            {
+                return Ok(SendVerificationEmailPayload::RateLimited {
+                    email: user_email,
+                    retry_after,
+                });
+            }
+
             // TODO: figure out the locale
             repo.job()
                 .schedule_job(VerifyEmailJob::new(&user_email))
@@ -550,6 +645,19 @@ impl UserEmailMutations {
             return Ok(VerifyEmailPayload::InvalidCode);
         };
 
+        // Another user might have verified the same address in the meantime
+        let in_use = repo
+            .user_email()
+            .count(
+                UserEmailFilter::new()
+                    .for_email(&user_email.email)
+                    .verified_only(),
+            )
+            .await?;
+        if in_use > 0 {
+            return Ok(VerifyEmailPayload::InUse);
+        }
+
         repo.user_email()
             .consume_verification_code(&clock, verification)
             .await?;
@@ -565,10 +673,14 @@ impl UserEmailMutations {
             repo.user_email().set_as_primary(&user_email).await?;
         }
 
-        let user_email = repo
-            .user_email()
-            .mark_as_verified(&clock, user_email)
-            .await?;
+        let user_email = match repo.user_email().mark_as_verified(&clock, user_email).await {
+            Ok(user_email) => user_email,
+            // Another user verified the same address concurrently
+            Err(e) if UserEmailInUseError::is_cause_of(&e) => {
+                return Ok(VerifyEmailPayload::InUse);
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         repo.job()
             .schedule_job(ProvisionUserJob::new(&user))
@@ -657,6 +769,11 @@ impl UserEmailMutations {
             .await?
             .context("Failed to load user")?;
 
+        // Update the order of the 3PIDs on the homeserver
+        repo.job()
+            .schedule_job(ProvisionUserJob::new(&user))
+            .await?;
+
         repo.save().await?;
 
         Ok(SetPrimaryEmailPayload::Set(user))
@@ -0,0 +1,74 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_graphql::{Context, Object};
+use chrono::{DateTime, Utc};
+use mas_storage::{
+    oauth2::OAuth2AuthorizationGrantRepository, statistics::StatisticsRepository, RepositoryAccess,
+};
+
+use crate::{
+    model::{DailyStatistics, OAuth2AuthorizationGrantFunnel},
+    state::ContextExt,
+};
+
+#[derive(Default)]
+pub struct AnalyticsQuery;
+
+#[Object]
+impl AnalyticsQuery {
+    /// Get how far the authorization grants started since the given date went
+    /// in the authorization flow, per client.
+    ///
+    /// This is only available to administrators.
+    async fn oauth2_authorization_grant_funnel(
+        &self,
+        ctx: &Context<'_>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<OAuth2AuthorizationGrantFunnel>, async_graphql::Error> {
+        let requester = ctx.requester();
+        if !requester.is_admin() {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        }
+
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+        let funnel = repo.oauth2_authorization_grant().funnel(since).await?;
+        repo.cancel().await?;
+
+        Ok(funnel
+            .into_iter()
+            .map(OAuth2AuthorizationGrantFunnel)
+            .collect())
+    }
+
+    /// Get the usage statistics of each day since the given date, oldest
+    /// first.
+    ///
+    /// This is only available to administrators.
+    async fn daily_statistics(
+        &self,
+        ctx: &Context<'_>,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DailyStatistics>, async_graphql::Error> {
+        ctx.require_admin()?;
+
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+        let statistics = repo.statistics().list(since.date_naive()).await?;
+        repo.cancel().await?;
+
+        Ok(statistics.into_iter().map(DailyStatistics).collect())
+    }
+}
@@ -21,15 +21,27 @@ use crate::{
     UserId,
 };
 
+mod analytics;
 mod session;
 mod upstream_oauth;
+mod user;
 mod viewer;
 
-use self::{session::SessionQuery, upstream_oauth::UpstreamOAuthQuery, viewer::ViewerQuery};
+use self::{
+    analytics::AnalyticsQuery, session::SessionQuery, upstream_oauth::UpstreamOAuthQuery,
+    user::UserQuery, viewer::ViewerQuery,
+};
 
 /// The query root of the GraphQL interface.
 #[derive(Default, MergedObject)]
-pub struct Query(BaseQuery, UpstreamOAuthQuery, SessionQuery, ViewerQuery);
+pub struct Query(
+    BaseQuery,
+    UpstreamOAuthQuery,
+    UserQuery,
+    SessionQuery,
+    ViewerQuery,
+    AnalyticsQuery,
+);
 
 impl Query {
     #[must_use]
@@ -188,7 +200,8 @@ impl BaseQuery {
             NodeType::Authentication
             | NodeType::CompatSession
             | NodeType::CompatSsoLogin
-            | NodeType::OAuth2Session => None,
+            | NodeType::OAuth2Session
+            | NodeType::SignInNotification => None,
 
             NodeType::UpstreamOAuth2Provider => UpstreamOAuthQuery
                 .upstream_oauth2_provider(ctx, id)
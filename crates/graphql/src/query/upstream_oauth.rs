@@ -20,8 +20,8 @@ use mas_storage::{upstream_oauth2::UpstreamOAuthProviderFilter, Pagination, Repo
 
 use crate::{
     model::{
-        Cursor, NodeCursor, NodeType, PreloadedTotalCount, UpstreamOAuth2Link,
-        UpstreamOAuth2Provider,
+        connection_complexity, Cursor, NodeCursor, NodeType, PreloadedTotalCount,
+        UpstreamOAuth2Link, UpstreamOAuth2Provider,
     },
     state::ContextExt,
 };
@@ -77,6 +77,7 @@ impl UpstreamOAuthQuery {
     }
 
     /// Get a list of upstream OAuth 2.0 providers.
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
     async fn upstream_oauth2_providers(
         &self,
         ctx: &Context<'_>,
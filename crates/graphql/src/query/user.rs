@@ -0,0 +1,130 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_graphql::{
+    connection::{query, Connection, Edge, OpaqueCursor},
+    Context, Object,
+};
+use chrono::{DateTime, Utc};
+use mas_storage::{user::UserFilter, Pagination, RepositoryAccess};
+
+use crate::{
+    model::{
+        connection_complexity, Cursor, NodeCursor, NodeType, PreloadedTotalCount, User, UserState,
+    },
+    state::ContextExt,
+};
+
+#[derive(Default)]
+pub struct UserQuery;
+
+#[Object]
+impl UserQuery {
+    /// Get a list of users, chronologically sorted.
+    ///
+    /// Only available for administrators.
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "connection_complexity(child_complexity, first, last)")]
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+
+        #[graphql(name = "state", desc = "List only users in the given state.")]
+        state_param: Option<UserState>,
+
+        #[graphql(desc = "Search for users by username or email address.")] search: Option<String>,
+
+        #[graphql(desc = "List only users whose account was created after the given time.")]
+        created_after: Option<DateTime<Utc>>,
+
+        #[graphql(desc = "List only users whose account was created before the given time.")]
+        created_before: Option<DateTime<Utc>>,
+
+        #[graphql(desc = "Returns the elements in the list that come after the cursor.")]
+        after: Option<String>,
+        #[graphql(desc = "Returns the elements in the list that come before the cursor.")]
+        before: Option<String>,
+        #[graphql(desc = "Returns the first *n* elements from the list.")] first: Option<i32>,
+        #[graphql(desc = "Returns the last *n* elements from the list.")] last: Option<i32>,
+    ) -> Result<Connection<Cursor, User, PreloadedTotalCount>, async_graphql::Error> {
+        ctx.require_admin()?;
+
+        let state = ctx.state();
+        let mut repo = state.repository().await?;
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let after_id = after
+                    .map(|x: OpaqueCursor<NodeCursor>| x.extract_for_type(NodeType::User))
+                    .transpose()?;
+                let before_id = before
+                    .map(|x: OpaqueCursor<NodeCursor>| x.extract_for_type(NodeType::User))
+                    .transpose()?;
+                let pagination = Pagination::try_new(before_id, after_id, first, last)?;
+
+                let filter = UserFilter::new();
+
+                let filter = match state_param {
+                    Some(UserState::Active) => filter.active_only(),
+                    Some(UserState::Locked) => filter.locked_only(),
+                    None => filter,
+                };
+
+                let filter = match search.as_deref() {
+                    Some(search) => filter.matching(search),
+                    None => filter,
+                };
+
+                let filter = match created_after {
+                    Some(created_after) => filter.with_created_after(created_after),
+                    None => filter,
+                };
+
+                let filter = match created_before {
+                    Some(created_before) => filter.with_created_before(created_before),
+                    None => filter,
+                };
+
+                let page = repo.user().list(filter, pagination).await?;
+
+                // Preload the total count if requested
+                let count = if ctx.look_ahead().field("totalCount").exists() {
+                    Some(repo.user().count(filter).await?)
+                } else {
+                    None
+                };
+
+                repo.cancel().await?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page.has_previous_page,
+                    page.has_next_page,
+                    PreloadedTotalCount(count),
+                );
+                connection.edges.extend(
+                    page.edges.into_iter().map(|u| {
+                        Edge::new(OpaqueCursor(NodeCursor(NodeType::User, u.id)), User(u))
+                    }),
+                );
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+}
@@ -0,0 +1,576 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detect changes between two versions of the GraphQL schema which would
+//! break existing clients
+
+use std::collections::HashMap;
+
+use async_graphql::parser::{
+    parse_schema,
+    types::{
+        BaseType, FieldDefinition, InputValueDefinition, Type, TypeKind, TypeSystemDefinition,
+    },
+    Positioned,
+};
+
+/// A change in the schema which can break existing clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakingChange {
+    /// A type was removed
+    TypeRemoved { name: String },
+
+    /// A type changed kind, e.g. from an object to an interface
+    TypeKindChanged { name: String },
+
+    /// A field was removed from an object, interface or input object
+    FieldRemoved { type_name: String, field: String },
+
+    /// The type of a field changed in an incompatible way
+    FieldTypeChanged {
+        type_name: String,
+        field: String,
+        previous: String,
+        current: String,
+    },
+
+    /// An argument was removed from a field
+    ArgumentRemoved {
+        type_name: String,
+        field: String,
+        argument: String,
+    },
+
+    /// The type of an argument changed in an incompatible way
+    ArgumentTypeChanged {
+        type_name: String,
+        field: String,
+        argument: String,
+        previous: String,
+        current: String,
+    },
+
+    /// A required argument was added to a field
+    RequiredArgumentAdded {
+        type_name: String,
+        field: String,
+        argument: String,
+    },
+
+    /// A required field was added to an input object
+    RequiredInputFieldAdded { type_name: String, field: String },
+
+    /// A value was removed from an enum
+    EnumValueRemoved { type_name: String, value: String },
+
+    /// A member was removed from a union
+    UnionMemberRemoved { type_name: String, member: String },
+
+    /// An object or interface doesn't implement an interface anymore
+    InterfaceRemoved {
+        type_name: String,
+        interface: String,
+    },
+}
+
+impl std::fmt::Display for BreakingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TypeRemoved { name } => write!(f, "type `{name}` was removed"),
+            Self::TypeKindChanged { name } => write!(f, "type `{name}` changed kind"),
+            Self::FieldRemoved { type_name, field } => {
+                write!(f, "field `{type_name}.{field}` was removed")
+            }
+            Self::FieldTypeChanged {
+                type_name,
+                field,
+                previous,
+                current,
+            } => write!(
+                f,
+                "field `{type_name}.{field}` changed type from `{previous}` to `{current}`"
+            ),
+            Self::ArgumentRemoved {
+                type_name,
+                field,
+                argument,
+            } => write!(
+                f,
+                "argument `{argument}` of field `{type_name}.{field}` was removed"
+            ),
+            Self::ArgumentTypeChanged {
+                type_name,
+                field,
+                argument,
+                previous,
+                current,
+            } => write!(
+                f,
+                "argument `{argument}` of field `{type_name}.{field}` changed type from \
+                 `{previous}` to `{current}`"
+            ),
+            Self::RequiredArgumentAdded {
+                type_name,
+                field,
+                argument,
+            } => write!(
+                f,
+                "required argument `{argument}` was added to field `{type_name}.{field}`"
+            ),
+            Self::RequiredInputFieldAdded { type_name, field } => {
+                write!(f, "required input field `{type_name}.{field}` was added")
+            }
+            Self::EnumValueRemoved { type_name, value } => {
+                write!(f, "value `{value}` was removed from enum `{type_name}`")
+            }
+            Self::UnionMemberRemoved { type_name, member } => {
+                write!(f, "member `{member}` was removed from union `{type_name}`")
+            }
+            Self::InterfaceRemoved {
+                type_name,
+                interface,
+            } => write!(
+                f,
+                "type `{type_name}` does not implement interface `{interface}` anymore"
+            ),
+        }
+    }
+}
+
+/// Whether a value of type `current` can be used where a value of type
+/// `previous` was expected. This is the case for output types, which can only
+/// become stricter.
+fn output_compatible(previous: &Type, current: &Type) -> bool {
+    (previous.nullable || !current.nullable)
+        && match (&previous.base, &current.base) {
+            (BaseType::Named(previous), BaseType::Named(current)) => previous == current,
+            (BaseType::List(previous), BaseType::List(current)) => {
+                output_compatible(previous, current)
+            }
+            _ => false,
+        }
+}
+
+/// Whether a value of type `previous` can be passed where a value of type
+/// `current` is expected. This is the case for input types, which can only
+/// become looser.
+fn input_compatible(previous: &Type, current: &Type) -> bool {
+    (current.nullable || !previous.nullable)
+        && match (&previous.base, &current.base) {
+            (BaseType::Named(previous), BaseType::Named(current)) => previous == current,
+            (BaseType::List(previous), BaseType::List(current)) => {
+                input_compatible(previous, current)
+            }
+            _ => false,
+        }
+}
+
+fn is_required(input: &InputValueDefinition) -> bool {
+    !input.ty.node.nullable && input.default_value.is_none()
+}
+
+fn find<'a, T>(
+    items: &'a [Positioned<T>],
+    name: impl Fn(&T) -> &str,
+    needle: &str,
+) -> Option<&'a T> {
+    items
+        .iter()
+        .map(|item| &item.node)
+        .find(|item| name(item) == needle)
+}
+
+fn diff_fields(
+    type_name: &str,
+    previous: &[Positioned<FieldDefinition>],
+    current: &[Positioned<FieldDefinition>],
+    changes: &mut Vec<BreakingChange>,
+) {
+    for previous_field in previous.iter().map(|f| &f.node) {
+        let field = previous_field.name.node.as_str();
+        let Some(current_field) = find(current, |f| f.name.node.as_str(), field) else {
+            changes.push(BreakingChange::FieldRemoved {
+                type_name: type_name.to_owned(),
+                field: field.to_owned(),
+            });
+            continue;
+        };
+
+        if !output_compatible(&previous_field.ty.node, &current_field.ty.node) {
+            changes.push(BreakingChange::FieldTypeChanged {
+                type_name: type_name.to_owned(),
+                field: field.to_owned(),
+                previous: previous_field.ty.node.to_string(),
+                current: current_field.ty.node.to_string(),
+            });
+        }
+
+        for previous_argument in previous_field.arguments.iter().map(|a| &a.node) {
+            let argument = previous_argument.name.node.as_str();
+            let Some(current_argument) =
+                find(&current_field.arguments, |a| a.name.node.as_str(), argument)
+            else {
+                changes.push(BreakingChange::ArgumentRemoved {
+                    type_name: type_name.to_owned(),
+                    field: field.to_owned(),
+                    argument: argument.to_owned(),
+                });
+                continue;
+            };
+
+            if !input_compatible(&previous_argument.ty.node, &current_argument.ty.node) {
+                changes.push(BreakingChange::ArgumentTypeChanged {
+                    type_name: type_name.to_owned(),
+                    field: field.to_owned(),
+                    argument: argument.to_owned(),
+                    previous: previous_argument.ty.node.to_string(),
+                    current: current_argument.ty.node.to_string(),
+                });
+            }
+        }
+
+        for current_argument in current_field.arguments.iter().map(|a| &a.node) {
+            let argument = current_argument.name.node.as_str();
+            if is_required(current_argument)
+                && find(
+                    &previous_field.arguments,
+                    |a| a.name.node.as_str(),
+                    argument,
+                )
+                .is_none()
+            {
+                changes.push(BreakingChange::RequiredArgumentAdded {
+                    type_name: type_name.to_owned(),
+                    field: field.to_owned(),
+                    argument: argument.to_owned(),
+                });
+            }
+        }
+    }
+}
+
+fn diff_interfaces(
+    type_name: &str,
+    previous: &[Positioned<async_graphql::Name>],
+    current: &[Positioned<async_graphql::Name>],
+    changes: &mut Vec<BreakingChange>,
+) {
+    for interface in previous {
+        if !current.iter().any(|i| i.node == interface.node) {
+            changes.push(BreakingChange::InterfaceRemoved {
+                type_name: type_name.to_owned(),
+                interface: interface.node.to_string(),
+            });
+        }
+    }
+}
+
+fn diff_types(name: &str, previous: &TypeKind, current: &TypeKind) -> Vec<BreakingChange> {
+    let mut changes = Vec::new();
+
+    match (previous, current) {
+        (TypeKind::Scalar, TypeKind::Scalar) => {}
+
+        (TypeKind::Object(previous), TypeKind::Object(current)) => {
+            diff_interfaces(
+                name,
+                &previous.implements,
+                &current.implements,
+                &mut changes,
+            );
+            diff_fields(name, &previous.fields, &current.fields, &mut changes);
+        }
+
+        (TypeKind::Interface(previous), TypeKind::Interface(current)) => {
+            diff_interfaces(
+                name,
+                &previous.implements,
+                &current.implements,
+                &mut changes,
+            );
+            diff_fields(name, &previous.fields, &current.fields, &mut changes);
+        }
+
+        (TypeKind::Union(previous), TypeKind::Union(current)) => {
+            for member in &previous.members {
+                if !current.members.iter().any(|m| m.node == member.node) {
+                    changes.push(BreakingChange::UnionMemberRemoved {
+                        type_name: name.to_owned(),
+                        member: member.node.to_string(),
+                    });
+                }
+            }
+        }
+
+        (TypeKind::Enum(previous), TypeKind::Enum(current)) => {
+            for value in &previous.values {
+                if !current
+                    .values
+                    .iter()
+                    .any(|v| v.node.value.node == value.node.value.node)
+                {
+                    changes.push(BreakingChange::EnumValueRemoved {
+                        type_name: name.to_owned(),
+                        value: value.node.value.node.to_string(),
+                    });
+                }
+            }
+        }
+
+        (TypeKind::InputObject(previous), TypeKind::InputObject(current)) => {
+            for previous_field in previous.fields.iter().map(|f| &f.node) {
+                let field = previous_field.name.node.as_str();
+                let Some(current_field) = find(&current.fields, |f| f.name.node.as_str(), field)
+                else {
+                    changes.push(BreakingChange::FieldRemoved {
+                        type_name: name.to_owned(),
+                        field: field.to_owned(),
+                    });
+                    continue;
+                };
+
+                if !input_compatible(&previous_field.ty.node, &current_field.ty.node) {
+                    changes.push(BreakingChange::FieldTypeChanged {
+                        type_name: name.to_owned(),
+                        field: field.to_owned(),
+                        previous: previous_field.ty.node.to_string(),
+                        current: current_field.ty.node.to_string(),
+                    });
+                }
+            }
+
+            for current_field in current.fields.iter().map(|f| &f.node) {
+                let field = current_field.name.node.as_str();
+                if is_required(current_field)
+                    && find(&previous.fields, |f| f.name.node.as_str(), field).is_none()
+                {
+                    changes.push(BreakingChange::RequiredInputFieldAdded {
+                        type_name: name.to_owned(),
+                        field: field.to_owned(),
+                    });
+                }
+            }
+        }
+
+        _ => changes.push(BreakingChange::TypeKindChanged {
+            name: name.to_owned(),
+        }),
+    }
+
+    changes
+}
+
+fn types(document: &async_graphql::parser::types::ServiceDocument) -> HashMap<&str, &TypeKind> {
+    document
+        .definitions
+        .iter()
+        .filter_map(|definition| match definition {
+            TypeSystemDefinition::Type(ty) => Some((ty.node.name.node.as_str(), &ty.node.kind)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// List the changes from the `previous` schema to the `current` one which
+/// would break existing clients. Both schemas are given in the SDL format.
+///
+/// Adding types, fields, optional arguments or enum values is considered
+/// compatible.
+///
+/// # Errors
+///
+/// Returns an error if one of the schemas could not be parsed
+pub fn breaking_changes(
+    previous: &str,
+    current: &str,
+) -> Result<Vec<BreakingChange>, async_graphql::parser::Error> {
+    let previous = parse_schema(previous)?;
+    let current = parse_schema(current)?;
+    let previous_types = types(&previous);
+    let current_types = types(&current);
+
+    let mut names: Vec<&str> = previous_types.keys().copied().collect();
+    names.sort_unstable();
+
+    let mut changes = Vec::new();
+    for name in names {
+        let previous = previous_types[name];
+        if let Some(current) = current_types.get(name) {
+            changes.extend(diff_types(name, previous, current));
+        } else {
+            changes.push(BreakingChange::TypeRemoved {
+                name: name.to_owned(),
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r"
+        type Query {
+            user(id: ID!): User
+            users(first: Int, after: String): [User!]!
+        }
+
+        interface Node {
+            id: ID!
+        }
+
+        type User implements Node {
+            id: ID!
+            username: String!
+            email: String
+            state: UserState!
+        }
+
+        enum UserState {
+            ACTIVE
+            LOCKED
+        }
+
+        input AddUserInput {
+            username: String!
+            admin: Boolean
+        }
+    ";
+
+    fn changes(current: &str) -> Vec<String> {
+        breaking_changes(SCHEMA, current)
+            .unwrap()
+            .into_iter()
+            .map(|change| change.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn compatible_changes() {
+        assert!(changes(SCHEMA).is_empty());
+
+        // Adding things, making outputs stricter and inputs looser is fine
+        let current = r"
+            type Query {
+                user(id: ID, includeLocked: Boolean): User
+                users(first: Int, after: String): [User!]!
+                viewer: User
+            }
+
+            interface Node {
+                id: ID!
+            }
+
+            type User implements Node {
+                id: ID!
+                username: String!
+                email: String!
+                state: UserState!
+                createdAt: String!
+            }
+
+            enum UserState {
+                ACTIVE
+                LOCKED
+                DEACTIVATED
+            }
+
+            input AddUserInput {
+                username: String!
+                admin: Boolean
+                skipEmail: Boolean! = false
+            }
+
+            type Session {
+                id: ID!
+            }
+        ";
+        assert_eq!(changes(current), Vec::<String>::new());
+    }
+
+    #[test]
+    fn breaking_changes_are_detected() {
+        let current = r"
+            type Query {
+                user(id: ID!, server: String!): User
+                users(first: String): [User]!
+            }
+
+            type Node {
+                id: ID!
+            }
+
+            type User {
+                id: ID!
+                email: String
+                state: UserState!
+            }
+
+            enum UserState {
+                ACTIVE
+            }
+
+            input AddUserInput {
+                username: String!
+                admin: Boolean!
+                password: String!
+            }
+        ";
+
+        assert_eq!(
+            changes(current),
+            vec![
+                "field `AddUserInput.admin` changed type from `Boolean` to `Boolean!`",
+                "required input field `AddUserInput.password` was added",
+                "type `Node` changed kind",
+                "required argument `server` was added to field `Query.user`",
+                "field `Query.users` changed type from `[User!]!` to `[User]!`",
+                "argument `first` of field `Query.users` changed type from `Int` to `String`",
+                "argument `after` of field `Query.users` was removed",
+                "type `User` does not implement interface `Node` anymore",
+                "field `User.username` was removed",
+                "value `LOCKED` was removed from enum `UserState`",
+            ]
+        );
+    }
+
+    /// The schema exported in `frontend/schema.graphql` is what the frontend
+    /// and third-party consumers build against. This makes sure it is kept up
+    /// to date, and that it only changes in a compatible way unless done
+    /// deliberately.
+    #[test]
+    fn schema_snapshot() {
+        let snapshot = include_str!("../../../frontend/schema.graphql");
+        let current = crate::schema_builder().finish().sdl();
+
+        let changes = breaking_changes(snapshot, &current).unwrap();
+        assert!(
+            changes.is_empty(),
+            "The GraphQL schema changed in an incompatible way:\n{}\n\
+             If this is deliberate, regenerate the schema with `misc/update.sh`",
+            changes
+                .iter()
+                .map(|change| format!("  - {change}"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+
+        assert!(
+            snapshot.trim_end() == current.trim_end(),
+            "The GraphQL schema is out of date, regenerate it with `misc/update.sh`",
+        );
+    }
+}
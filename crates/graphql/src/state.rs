@@ -12,9 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use mas_data_model::{
+    EmailNormalization, RefreshTokenPolicies, SessionEvent, TermsOfService, User,
+};
 use mas_matrix::HomeserverConnection;
 use mas_policy::Policy;
-use mas_storage::{BoxClock, BoxRepository, BoxRng, RepositoryError};
+use mas_storage::{BoxClock, BoxRepository, BoxRng, Clock, RepositoryError};
+use tokio::sync::broadcast;
+use ulid::Ulid;
+use url::Url;
 
 use crate::Requester;
 
@@ -25,6 +31,31 @@ pub trait State {
     fn homeserver_connection(&self) -> &dyn HomeserverConnection<Error = anyhow::Error>;
     fn clock(&self) -> BoxClock;
     fn rng(&self) -> BoxRng;
+    fn refresh_token_policies(&self) -> &RefreshTokenPolicies;
+    fn avatar_store(&self) -> Option<&dyn AvatarStore>;
+    fn email_normalization(&self) -> EmailNormalization;
+    fn email_throttle(&self) -> &dyn EmailThrottle;
+    fn terms(&self) -> Option<&TermsOfService>;
+    fn session_events(&self) -> broadcast::Receiver<SessionEvent>;
+}
+
+/// Throttles the emails sent on behalf of users
+#[async_trait::async_trait]
+pub trait EmailThrottle: Send + Sync {
+    /// Check if an email can be sent to the given address on behalf of the
+    /// user, returning how many seconds to wait before retrying if not
+    async fn check(&self, clock: &dyn Clock, user: &User, email: &str) -> Result<(), u64>;
+}
+
+/// Where the avatars uploaded by users are kept
+#[async_trait::async_trait]
+pub trait AvatarStore: Send + Sync {
+    /// The maximum size of an avatar, in bytes
+    fn max_size(&self) -> usize;
+
+    /// Save an avatar, returning the URL from which it is served, or `None`
+    /// if the content is not an image format accepted as avatar
+    async fn save(&self, id: Ulid, content: Vec<u8>) -> anyhow::Result<Option<Url>>;
 }
 
 pub type BoxState = Box<dyn State + Send + Sync + 'static>;
@@ -33,6 +64,10 @@ pub trait ContextExt {
     fn state(&self) -> &BoxState;
 
     fn requester(&self) -> &Requester;
+
+    /// Make sure the requester has administrative privileges, for queries
+    /// and mutations which are only available to administrators
+    fn require_admin(&self) -> Result<(), async_graphql::Error>;
 }
 
 impl ContextExt for async_graphql::Context<'_> {
@@ -43,4 +78,12 @@ impl ContextExt for async_graphql::Context<'_> {
     fn requester(&self) -> &Requester {
         self.data_unchecked()
     }
+
+    fn require_admin(&self) -> Result<(), async_graphql::Error> {
+        if self.requester().is_admin() {
+            Ok(())
+        } else {
+            Err(async_graphql::Error::new("Unauthorized"))
+        }
+    }
 }
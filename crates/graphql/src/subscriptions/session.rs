@@ -0,0 +1,55 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_graphql::{Context, Subscription};
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{model::SessionEvent, state::ContextExt};
+
+#[derive(Default)]
+pub struct SessionSubscription;
+
+#[Subscription]
+impl SessionSubscription {
+    /// Get the events which happen to the sessions of the current user: when
+    /// they start, end, or when the user authenticates again in one of them.
+    async fn session_events(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<impl Stream<Item = SessionEvent>, async_graphql::Error> {
+        let Some(user) = ctx.requester().user() else {
+            return Err(async_graphql::Error::new("Unauthorized"));
+        };
+
+        let user_id = user.id;
+        let receiver = ctx.state().session_events();
+
+        Ok(stream::unfold(receiver, move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.user_id == user_id => {
+                        return Some((SessionEvent(event), receiver));
+                    }
+
+                    // Events of other users, or which we missed because the
+                    // client is too slow to consume them
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }))
+    }
+}
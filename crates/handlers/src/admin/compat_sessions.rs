@@ -0,0 +1,181 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use mas_axum_utils::user_authorization::HeaderUserAuthorization;
+use mas_data_model::CompatSession;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    compat::CompatSessionFilter,
+    job::{DeleteDeviceJob, JobRepositoryExt},
+    BoxClock, BoxRepository, Clock, RepositoryAccess,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use ulid::Ulid;
+
+use super::{authenticate, load_user, model, pagination, RouteError, SessionState};
+use crate::{BoundActivityTracker, SiteConfig};
+
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct ListParams {
+    /// Only return the sessions of this user
+    #[schemars(with = "Option<String>")]
+    user: Option<Ulid>,
+
+    /// Only return sessions in this state
+    state: Option<SessionState>,
+
+    /// Return the sessions after this cursor
+    #[schemars(with = "Option<String>")]
+    after: Option<Ulid>,
+
+    /// How many sessions to return, up to 100. Defaults to 10.
+    first: Option<usize>,
+}
+
+/// End a compatibility session, deleting its device on the homeserver
+pub(super) async fn end_session(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    session: CompatSession,
+) -> Result<CompatSession, RouteError> {
+    let user = load_user(repo, session.user_id).await?;
+
+    repo.job()
+        .schedule_job(DeleteDeviceJob::new(&user, &session.device))
+        .await?;
+
+    let session = repo.compat_session().finish(clock, session).await?;
+
+    Ok(session)
+}
+
+#[tracing::instrument(name = "handlers.admin.compat_sessions.list", skip_all, err)]
+pub(crate) async fn list(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Query(params): Query<ListParams>,
+) -> Result<Json<model::Page<model::CompatSession>>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminCompatSessions);
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let pagination = pagination(params.after, params.first)?;
+
+    let user = match params.user {
+        Some(id) => Some(load_user(&mut repo, id).await?),
+        None => None,
+    };
+
+    let mut filter = CompatSessionFilter::new();
+    if let Some(user) = &user {
+        filter = filter.for_user(user);
+    }
+    filter = match params.state {
+        Some(SessionState::Active) => filter.active_only(),
+        Some(SessionState::Finished) => filter.finished_only(),
+        None => filter,
+    };
+
+    let page = repo
+        .compat_session()
+        .list(filter, pagination)
+        .await?
+        .map(|(session, _)| session);
+
+    Ok(Json(model::Page::from_storage(page, |session| session.id)))
+}
+
+#[tracing::instrument(name = "handlers.admin.compat_sessions.get", fields(compat_session.id = %id), skip_all, err)]
+pub(crate) async fn get(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+) -> Result<Json<model::CompatSession>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminCompatSession(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let session = repo
+        .compat_session()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::CompatSessionNotFound(id))?;
+
+    Ok(Json(session.into()))
+}
+
+#[tracing::instrument(name = "handlers.admin.compat_sessions.finish", fields(compat_session.id = %id), skip_all, err)]
+pub(crate) async fn finish(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+) -> Result<Json<model::CompatSession>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminFinishCompatSession(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let session = repo
+        .compat_session()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::CompatSessionNotFound(id))?;
+
+    if session.is_finished() {
+        return Err(RouteError::SessionFinished);
+    }
+
+    let session = end_session(&mut repo, &clock, session).await?;
+
+    repo.save().await?;
+
+    Ok(Json(session.into()))
+}
@@ -0,0 +1,198 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Administrative REST API, mounted under `/api/admin/v1/`
+//!
+//! Every endpoint requires an access token with the `urn:mas:admin` scope.
+//! The API is described by an OpenAPI document served under
+//! `/api/admin/v1/openapi.json`.
+
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use hyper::StatusCode;
+use mas_axum_utils::{
+    sentry::SentryEventID,
+    user_authorization::{AuthorizationVerificationError, UserAuthorization},
+};
+use mas_data_model::{Session, User};
+use mas_storage::{BoxClock, BoxRepository, Pagination, RepositoryAccess, RepositoryError};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ulid::Ulid;
+use url::Url;
+
+use crate::{impl_from_error_for_route, BoundActivityTracker, SiteConfig};
+
+pub(crate) mod compat_sessions;
+mod model;
+pub(crate) mod oauth2_clients;
+pub(crate) mod oauth2_sessions;
+pub(crate) mod openapi;
+#[cfg(test)]
+mod tests;
+pub(crate) mod tokens;
+pub(crate) mod users;
+
+/// The scope the access tokens need to use this API
+const ADMIN_SCOPE: &str = "urn:mas:admin";
+
+/// How many items are returned in a page if the request doesn't say
+const DEFAULT_PAGE_SIZE: usize = 10;
+
+/// How many items can be requested in a single page
+const MAX_PAGE_SIZE: usize = 100;
+
+/// The state of the sessions to list
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SessionState {
+    Active,
+    Finished,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("Missing or invalid access token")]
+    Unauthorized(#[from] AuthorizationVerificationError<RepositoryError>),
+
+    #[error("The access token does not have the urn:mas:admin scope")]
+    MissingScope,
+
+    #[error("The page size must be between 1 and 100")]
+    InvalidPageSize,
+
+    #[error("Invalid username")]
+    InvalidUsername,
+
+    #[error("User {0} not found")]
+    UserNotFound(Ulid),
+
+    #[error("User {0:?} already exists")]
+    UserExists(String),
+
+    #[error("OAuth 2.0 session {0} not found")]
+    OAuth2SessionNotFound(Ulid),
+
+    #[error("Compatibility session {0} not found")]
+    CompatSessionNotFound(Ulid),
+
+    #[error("OAuth 2.0 client {0} not found")]
+    OAuth2ClientNotFound(Ulid),
+
+    #[error("The session is already finished")]
+    SessionFinished,
+
+    #[error("Unknown or expired token")]
+    UnknownToken,
+}
+
+impl_from_error_for_route!(RepositoryError);
+
+/// The body of the responses of failed requests
+#[derive(Serialize, JsonSchema)]
+struct ErrorResponse {
+    /// A description of the error
+    error: String,
+}
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> Response {
+        let event_id = sentry::capture_error(&self);
+        let status = match self {
+            Self::Internal(_) | Self::Unauthorized(AuthorizationVerificationError::Internal(_)) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::MissingScope => StatusCode::FORBIDDEN,
+            Self::InvalidPageSize | Self::InvalidUsername | Self::UnknownToken => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::UserNotFound(_)
+            | Self::OAuth2SessionNotFound(_)
+            | Self::CompatSessionNotFound(_)
+            | Self::OAuth2ClientNotFound(_) => StatusCode::NOT_FOUND,
+            Self::UserExists(_) | Self::SessionFinished => StatusCode::CONFLICT,
+        };
+
+        let body = ErrorResponse {
+            error: self.to_string(),
+        };
+
+        (status, SentryEventID::from(event_id), Json(body)).into_response()
+    }
+}
+
+/// Check that the request carries a valid access token with the admin scope,
+/// and return the session it belongs to
+///
+/// The `uri` is the public URL of the endpoint, against which DPoP proofs are
+/// checked.
+async fn authenticate(
+    repo: &mut BoxRepository,
+    clock: &BoxClock,
+    activity_tracker: &BoundActivityTracker,
+    user_authorization: UserAuthorization,
+    uri: &Url,
+    site_config: &SiteConfig,
+) -> Result<Session, RouteError> {
+    let session = user_authorization
+        .protected(repo, clock, uri, &site_config.rate_limiter)
+        .await?;
+
+    if !session.scope.contains(ADMIN_SCOPE) {
+        return Err(RouteError::MissingScope);
+    }
+
+    // Tokens issued to a user stop working once the user is locked or deleted
+    if let Some(user_id) = session.user_id {
+        let user = repo.user().lookup(user_id).await?;
+        if !user.as_ref().is_some_and(User::is_valid) {
+            return Err(AuthorizationVerificationError::InvalidToken.into());
+        }
+    }
+
+    activity_tracker
+        .record_oauth2_session(clock, &session)
+        .await;
+
+    Ok(session)
+}
+
+/// Build the pagination of a list request
+fn pagination(after: Option<Ulid>, first: Option<usize>) -> Result<Pagination, RouteError> {
+    let first = first.unwrap_or(DEFAULT_PAGE_SIZE);
+    if first == 0 || first > MAX_PAGE_SIZE {
+        return Err(RouteError::InvalidPageSize);
+    }
+
+    let pagination = Pagination::first(first);
+    Ok(match after {
+        Some(after) => pagination.after(after),
+        None => pagination,
+    })
+}
+
+/// Load the user with the given ID
+async fn load_user(repo: &mut BoxRepository, id: Ulid) -> Result<User, RouteError> {
+    repo.user()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::UserNotFound(id))
+}
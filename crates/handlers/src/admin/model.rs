@@ -0,0 +1,255 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The objects returned by the administrative API
+
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use mas_data_model::{CompatSessionState, SessionState};
+use schemars::JsonSchema;
+use serde::Serialize;
+use ulid::Ulid;
+use url::Url;
+
+/// A page of items, in chronological order
+#[derive(Serialize, JsonSchema)]
+pub struct Page<T> {
+    /// The items in this page
+    pub data: Vec<T>,
+
+    /// The cursor to pass as the `after` parameter to get the next page, if
+    /// there is one
+    #[schemars(with = "Option<String>")]
+    pub next_cursor: Option<Ulid>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from a page loaded from the repository, whose items are
+    /// identified by `id`
+    pub fn from_storage<U>(page: mas_storage::Page<U>, id: impl Fn(&U) -> Ulid) -> Self
+    where
+        T: From<U>,
+    {
+        let next_cursor = page.edges.last().filter(|_| page.has_next_page).map(id);
+
+        Self {
+            data: page.edges.into_iter().map(T::from).collect(),
+            next_cursor,
+        }
+    }
+}
+
+/// A user
+#[derive(Serialize, JsonSchema)]
+pub struct User {
+    /// The ID of the user
+    #[schemars(with = "String")]
+    pub id: Ulid,
+
+    /// The username, which is the localpart of their Matrix ID
+    pub username: String,
+
+    /// When the user was created
+    pub created_at: DateTime<Utc>,
+
+    /// When the user was locked. Locked users can't log in.
+    pub locked_at: Option<DateTime<Utc>>,
+
+    /// When the user was deleted
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    /// Whether the user can request admin privileges
+    pub can_request_admin: bool,
+
+    /// Whether the user is a service account, which can't log in
+    /// interactively
+    pub is_service_account: bool,
+}
+
+impl From<mas_data_model::User> for User {
+    fn from(user: mas_data_model::User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            created_at: user.created_at,
+            locked_at: user.locked_at,
+            deleted_at: user.deleted_at,
+            can_request_admin: user.can_request_admin,
+            is_service_account: user.is_service_account,
+        }
+    }
+}
+
+/// An OAuth 2.0 session
+#[derive(Serialize, JsonSchema)]
+pub struct OAuth2Session {
+    /// The ID of the session
+    #[schemars(with = "String")]
+    pub id: Ulid,
+
+    /// When the session was started
+    pub created_at: DateTime<Utc>,
+
+    /// When the session ended, if it did
+    pub finished_at: Option<DateTime<Utc>>,
+
+    /// The ID of the user this session is for, if any. Sessions obtained
+    /// with the client credentials grant have no user.
+    #[schemars(with = "Option<String>")]
+    pub user_id: Option<Ulid>,
+
+    /// The ID of the client which started the session
+    #[schemars(with = "String")]
+    pub client_id: Ulid,
+
+    /// The scope granted to the session
+    pub scope: String,
+
+    /// When the session was last active
+    pub last_active_at: Option<DateTime<Utc>>,
+
+    /// The IP address the session was last active from
+    pub last_active_ip: Option<IpAddr>,
+
+    /// The name the user gave to the session
+    pub human_name: Option<String>,
+}
+
+impl From<mas_data_model::Session> for OAuth2Session {
+    fn from(session: mas_data_model::Session) -> Self {
+        let finished_at = match session.state {
+            SessionState::Valid => None,
+            SessionState::Finished { finished_at } => Some(finished_at),
+        };
+
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            finished_at,
+            user_id: session.user_id,
+            client_id: session.client_id,
+            scope: session.scope.to_string(),
+            last_active_at: session.last_active_at,
+            last_active_ip: session.last_active_ip,
+            human_name: session.human_name,
+        }
+    }
+}
+
+/// A session started through the Matrix client-server login API
+#[derive(Serialize, JsonSchema)]
+pub struct CompatSession {
+    /// The ID of the session
+    #[schemars(with = "String")]
+    pub id: Ulid,
+
+    /// When the session was started
+    pub created_at: DateTime<Utc>,
+
+    /// When the session ended, if it did
+    pub finished_at: Option<DateTime<Utc>>,
+
+    /// The ID of the user this session is for
+    #[schemars(with = "String")]
+    pub user_id: Ulid,
+
+    /// The Matrix device ID of the session
+    pub device_id: String,
+
+    /// When the session was last active
+    pub last_active_at: Option<DateTime<Utc>>,
+
+    /// The IP address the session was last active from
+    pub last_active_ip: Option<IpAddr>,
+
+    /// The name the user gave to the session
+    pub human_name: Option<String>,
+}
+
+impl From<mas_data_model::CompatSession> for CompatSession {
+    fn from(session: mas_data_model::CompatSession) -> Self {
+        let finished_at = match session.state {
+            CompatSessionState::Valid => None,
+            CompatSessionState::Finished { finished_at } => Some(finished_at),
+        };
+
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            finished_at,
+            user_id: session.user_id,
+            device_id: session.device.as_str().to_owned(),
+            last_active_at: session.last_active_at,
+            last_active_ip: session.last_active_ip,
+            human_name: session.human_name,
+        }
+    }
+}
+
+/// An OAuth 2.0 client
+#[derive(Serialize, JsonSchema)]
+pub struct OAuth2Client {
+    /// The ID of the client
+    #[schemars(with = "String")]
+    pub id: Ulid,
+
+    /// The client identifier, used by the client in OAuth 2.0 requests
+    pub client_id: String,
+
+    /// The name of the client
+    pub client_name: Option<String>,
+
+    /// The URL of the home page of the client
+    pub client_uri: Option<Url>,
+
+    /// The redirect URIs the client registered
+    pub redirect_uris: Vec<Url>,
+
+    /// E-mail addresses of the people responsible for the client
+    pub contacts: Vec<String>,
+}
+
+impl From<mas_data_model::Client> for OAuth2Client {
+    fn from(client: mas_data_model::Client) -> Self {
+        Self {
+            id: client.id,
+            client_id: client.client_id,
+            client_name: client.client_name,
+            client_uri: client.client_uri,
+            redirect_uris: client.redirect_uris,
+            contacts: client.contacts,
+        }
+    }
+}
+
+/// The session a revoked token belonged to
+#[derive(Serialize, JsonSchema)]
+#[serde(tag = "session_type", rename_all = "snake_case")]
+pub enum RevokedSession {
+    /// The token belonged to an OAuth 2.0 session
+    #[serde(rename = "oauth2")]
+    OAuth2 {
+        /// The ID of the session, which is now finished
+        #[schemars(with = "String")]
+        session_id: Ulid,
+    },
+
+    /// The token belonged to a compatibility session
+    Compat {
+        /// The ID of the session, which is now finished
+        #[schemars(with = "String")]
+        session_id: Ulid,
+    },
+}
@@ -0,0 +1,55 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use mas_axum_utils::user_authorization::HeaderUserAuthorization;
+use mas_router::UrlBuilder;
+use mas_storage::{BoxClock, BoxRepository, RepositoryAccess};
+use ulid::Ulid;
+
+use super::{authenticate, model, RouteError};
+use crate::{BoundActivityTracker, SiteConfig};
+
+#[tracing::instrument(name = "handlers.admin.oauth2_clients.get", fields(oauth2_client.id = %id), skip_all, err)]
+pub(crate) async fn get(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+) -> Result<Json<model::OAuth2Client>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminOAuth2Client(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let client = repo
+        .oauth2_client()
+        .lookup(id)
+        .await?
+        .ok_or(RouteError::OAuth2ClientNotFound(id))?;
+
+    Ok(Json(client.into()))
+}
@@ -0,0 +1,281 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The OpenAPI document describing the administrative API
+
+use axum::{extract::State, response::IntoResponse, Json};
+use mas_router::UrlBuilder;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use super::{compat_sessions, model, oauth2_sessions, tokens, users, ErrorResponse, ADMIN_SCOPE};
+
+/// Describe the fields of `T` as query parameters
+fn query_parameters<T: JsonSchema>(gen: &mut SchemaGenerator) -> Vec<Value> {
+    let schema = gen.root_schema_for::<T>();
+    let properties = schema
+        .schema
+        .object
+        .map(|object| object.properties)
+        .unwrap_or_default();
+
+    properties
+        .into_iter()
+        .map(|(name, schema)| {
+            let mut schema = serde_json::to_value(schema).unwrap_or_default();
+            let description = schema
+                .as_object_mut()
+                .and_then(|schema| schema.remove("description"));
+            json!({
+                "name": name,
+                "in": "query",
+                "required": false,
+                "description": description,
+                "schema": schema,
+            })
+        })
+        .collect()
+}
+
+/// The `id` path parameter
+fn id_parameter(description: &str) -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string", "format": "ulid" },
+    })
+}
+
+/// A JSON request body of type `T`
+fn request_body<T: JsonSchema>(gen: &mut SchemaGenerator, required: bool) -> Value {
+    json!({
+        "required": required,
+        "content": {
+            "application/json": { "schema": gen.subschema_for::<T>() },
+        },
+    })
+}
+
+/// The responses of an operation replying with a `T` and the given status
+/// code on success
+fn responses<T: JsonSchema>(gen: &mut SchemaGenerator, status: &str) -> Value {
+    let error = json!({
+        "content": {
+            "application/json": { "schema": gen.subschema_for::<ErrorResponse>() },
+        },
+    });
+
+    let mut responses = Map::new();
+    responses.insert(
+        status.to_owned(),
+        json!({
+            "description": "Success",
+            "content": {
+                "application/json": { "schema": gen.subschema_for::<T>() },
+            },
+        }),
+    );
+    for (status, description) in [
+        ("400", "Invalid request"),
+        ("401", "Missing or invalid access token"),
+        ("403", "The access token does not have the admin scope"),
+        ("404", "Not found"),
+        ("409", "Conflicts with the current state"),
+    ] {
+        let mut error = error.clone();
+        error["description"] = description.into();
+        responses.insert(status.to_owned(), error);
+    }
+
+    Value::Object(responses)
+}
+
+/// Build the OpenAPI document, with the API served from `server`
+fn spec(server: &str) -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+
+    let mut paths = Map::new();
+    paths.insert(
+        "/users".to_owned(),
+        json!({
+            "get": {
+                "summary": "List users",
+                "operationId": "listUsers",
+                "parameters": query_parameters::<users::ListParams>(&mut gen),
+                "responses": responses::<model::Page<model::User>>(&mut gen, "200"),
+            },
+            "post": {
+                "summary": "Create a user",
+                "operationId": "addUser",
+                "requestBody": request_body::<users::AddUserRequest>(&mut gen, true),
+                "responses": responses::<model::User>(&mut gen, "201"),
+            },
+        }),
+    );
+    paths.insert(
+        "/users/{id}".to_owned(),
+        json!({
+            "get": {
+                "summary": "Get a user",
+                "operationId": "getUser",
+                "parameters": [id_parameter("The ID of the user")],
+                "responses": responses::<model::User>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/users/{id}/lock".to_owned(),
+        json!({
+            "post": {
+                "summary": "Lock a user, optionally deactivating them on the homeserver",
+                "operationId": "lockUser",
+                "parameters": [id_parameter("The ID of the user")],
+                "requestBody": request_body::<users::LockUserRequest>(&mut gen, false),
+                "responses": responses::<model::User>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/users/{id}/unlock".to_owned(),
+        json!({
+            "post": {
+                "summary": "Unlock a user",
+                "operationId": "unlockUser",
+                "parameters": [id_parameter("The ID of the user")],
+                "responses": responses::<model::User>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/oauth2-sessions".to_owned(),
+        json!({
+            "get": {
+                "summary": "List OAuth 2.0 sessions",
+                "operationId": "listOAuth2Sessions",
+                "parameters": query_parameters::<oauth2_sessions::ListParams>(&mut gen),
+                "responses": responses::<model::Page<model::OAuth2Session>>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/oauth2-sessions/{id}".to_owned(),
+        json!({
+            "get": {
+                "summary": "Get an OAuth 2.0 session",
+                "operationId": "getOAuth2Session",
+                "parameters": [id_parameter("The ID of the session")],
+                "responses": responses::<model::OAuth2Session>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/oauth2-sessions/{id}/finish".to_owned(),
+        json!({
+            "post": {
+                "summary": "Finish an OAuth 2.0 session",
+                "operationId": "finishOAuth2Session",
+                "parameters": [id_parameter("The ID of the session")],
+                "responses": responses::<model::OAuth2Session>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/compat-sessions".to_owned(),
+        json!({
+            "get": {
+                "summary": "List compatibility sessions",
+                "operationId": "listCompatSessions",
+                "parameters": query_parameters::<compat_sessions::ListParams>(&mut gen),
+                "responses": responses::<model::Page<model::CompatSession>>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/compat-sessions/{id}".to_owned(),
+        json!({
+            "get": {
+                "summary": "Get a compatibility session",
+                "operationId": "getCompatSession",
+                "parameters": [id_parameter("The ID of the session")],
+                "responses": responses::<model::CompatSession>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/compat-sessions/{id}/finish".to_owned(),
+        json!({
+            "post": {
+                "summary": "Finish a compatibility session",
+                "operationId": "finishCompatSession",
+                "parameters": [id_parameter("The ID of the session")],
+                "responses": responses::<model::CompatSession>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/oauth2-clients/{id}".to_owned(),
+        json!({
+            "get": {
+                "summary": "Get an OAuth 2.0 client",
+                "operationId": "getOAuth2Client",
+                "parameters": [id_parameter("The ID of the client")],
+                "responses": responses::<model::OAuth2Client>(&mut gen, "200"),
+            },
+        }),
+    );
+    paths.insert(
+        "/tokens/revoke".to_owned(),
+        json!({
+            "post": {
+                "summary": "Revoke a token, finishing the session it belongs to",
+                "operationId": "revokeToken",
+                "requestBody": request_body::<tokens::RevokeTokenRequest>(&mut gen, true),
+                "responses": responses::<model::RevokedSession>(&mut gen, "200"),
+            },
+        }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Matrix Authentication Service administrative API",
+            "version": "1",
+        },
+        "servers": [{ "url": server }],
+        "paths": paths,
+        "components": {
+            "schemas": gen.definitions(),
+            "securitySchemes": {
+                "token": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": format!("An access token with the `{ADMIN_SCOPE}` scope"),
+                },
+            },
+        },
+        "security": [{ "token": [] }],
+    })
+}
+
+#[tracing::instrument(name = "handlers.admin.openapi.get", skip_all)]
+pub(crate) async fn get(State(url_builder): State<UrlBuilder>) -> impl IntoResponse {
+    let base = url_builder.absolute_url_for(&mas_router::AdminApiSpec);
+    let server = base.as_str().trim_end_matches("/openapi.json");
+    Json(spec(server))
+}
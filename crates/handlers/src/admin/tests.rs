@@ -0,0 +1,273 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::Duration;
+use hyper::{Request, StatusCode};
+use mas_data_model::TokenType;
+use mas_router::SimpleRoute;
+use mas_storage::{
+    compat::{CompatAccessTokenRepository, CompatSessionRepository},
+    RepositoryAccess,
+};
+use oauth2_types::scope::{ScopeToken, OPENID};
+use sqlx::PgPool;
+
+use crate::test_utils::{
+    create_test_client, create_test_user, init_tracing, start_oauth_session, RequestBuilderExt,
+    ResponseExt, TestState,
+};
+
+const ADMIN: ScopeToken = ScopeToken::from_static("urn:mas:admin");
+
+/// Get an access token with the admin scope, for an administrator
+async fn admin_token(state: &TestState) -> String {
+    let client = create_test_client(state).await;
+    let admin = create_test_user(state, "admin").await;
+    let token = start_oauth_session(state, &client, &admin, [ADMIN].into_iter().collect()).await;
+    token.access_token
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_authentication(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let client = create_test_client(&state).await;
+    let user = create_test_user(&state, "alice").await;
+
+    // Without a token, the request is rejected
+    let request = Request::get(mas_router::AdminUsers::PATH).empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::UNAUTHORIZED);
+
+    // With a token without the admin scope, it is forbidden
+    let token = start_oauth_session(&state, &client, &user, [OPENID].into_iter().collect()).await;
+    let request = Request::get(mas_router::AdminUsers::PATH)
+        .bearer(&token.access_token)
+        .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::FORBIDDEN);
+    let body: serde_json::Value = response.json();
+    assert!(body["error"].is_string());
+
+    // The OpenAPI document doesn't need a token
+    let request = Request::get(mas_router::AdminApiSpec::PATH).empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let spec: serde_json::Value = response.json();
+    assert_eq!(
+        spec["servers"][0]["url"],
+        "https://example.com/api/admin/v1"
+    );
+    assert!(spec["paths"]["/users/{id}/lock"]["post"].is_object());
+    assert!(spec["components"]["schemas"]["User"].is_object());
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_users(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let token = admin_token(&state).await;
+
+    // Make sure the users are listed in order
+    state.clock.advance(Duration::minutes(1));
+
+    // Create a user
+    let request = Request::post(mas_router::AdminUsers::PATH)
+        .bearer(&token)
+        .json(serde_json::json!({ "username": "alice" }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::CREATED);
+    let alice: serde_json::Value = response.json();
+    assert_eq!(alice["username"], "alice");
+    assert!(alice["locked_at"].is_null());
+    let alice_id = alice["id"].as_str().unwrap().to_owned();
+
+    // It can't be created twice
+    let request = Request::post(mas_router::AdminUsers::PATH)
+        .bearer(&token)
+        .json(serde_json::json!({ "username": "alice" }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::CONFLICT);
+
+    // Invalid usernames are refused
+    let request = Request::post(mas_router::AdminUsers::PATH)
+        .bearer(&token)
+        .json(serde_json::json!({ "username": "Not Valid" }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+
+    // List the users, one at a time
+    let request = Request::get(format!("{}?first=1", mas_router::AdminUsers::PATH))
+        .bearer(&token)
+        .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let page: serde_json::Value = response.json();
+    assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    assert_eq!(page["data"][0]["username"], "admin");
+    let cursor = page["next_cursor"].as_str().unwrap();
+
+    let request = Request::get(format!(
+        "{}?first=1&after={cursor}",
+        mas_router::AdminUsers::PATH
+    ))
+    .bearer(&token)
+    .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let page: serde_json::Value = response.json();
+    assert_eq!(page["data"][0]["username"], "alice");
+    assert!(page["next_cursor"].is_null());
+
+    // Lock the user
+    let request = Request::post(format!("{}/{alice_id}/lock", mas_router::AdminUsers::PATH))
+        .bearer(&token)
+        .json(serde_json::json!({ "deactivate": false }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let alice: serde_json::Value = response.json();
+    assert!(alice["locked_at"].is_string());
+
+    // Only the admin is still active
+    let request = Request::get(format!("{}?state=active", mas_router::AdminUsers::PATH))
+        .bearer(&token)
+        .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let page: serde_json::Value = response.json();
+    assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    assert_eq!(page["data"][0]["username"], "admin");
+
+    // Unlock the user, without a body
+    let request = Request::post(format!(
+        "{}/{alice_id}/unlock",
+        mas_router::AdminUsers::PATH
+    ))
+    .bearer(&token)
+    .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+
+    let request = Request::get(format!("{}/{alice_id}", mas_router::AdminUsers::PATH))
+        .bearer(&token)
+        .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let alice: serde_json::Value = response.json();
+    assert!(alice["locked_at"].is_null());
+
+    // Unknown users are not found
+    let request = Request::get(format!(
+        "{}/{}",
+        mas_router::AdminUsers::PATH,
+        ulid::Ulid::nil()
+    ))
+    .bearer(&token)
+    .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_sessions(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let token = admin_token(&state).await;
+    let client = create_test_client(&state).await;
+    let user = create_test_user(&state, "alice").await;
+    let session_token =
+        start_oauth_session(&state, &client, &user, [OPENID].into_iter().collect()).await;
+
+    // List the sessions of the user
+    let request = Request::get(format!(
+        "{}?user={}&state=active",
+        mas_router::AdminOAuth2Sessions::PATH,
+        user.id
+    ))
+    .bearer(&token)
+    .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let page: serde_json::Value = response.json();
+    assert_eq!(page["data"].as_array().unwrap().len(), 1);
+    assert_eq!(page["data"][0]["client_id"], client.id.to_string());
+    let session_id = page["data"][0]["id"].as_str().unwrap().to_owned();
+    assert_eq!(session_id, session_token.session_id.to_string());
+
+    // Finish it
+    let finish = format!(
+        "{}/{session_id}/finish",
+        mas_router::AdminOAuth2Sessions::PATH
+    );
+    let request = Request::post(&finish).bearer(&token).empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let session: serde_json::Value = response.json();
+    assert!(session["finished_at"].is_string());
+
+    // It can't be finished twice
+    let request = Request::post(&finish).bearer(&token).empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::CONFLICT);
+
+    // Start a compatibility session, and revoke its access token
+    let mut repo = state.repository().await.unwrap();
+    let mut rng = state.rng();
+    let device = mas_data_model::Device::generate(&mut rng);
+    let compat_session = repo
+        .compat_session()
+        .add(&mut rng, &state.clock, &user, device, false)
+        .await
+        .unwrap();
+    let compat_token = repo
+        .compat_access_token()
+        .add(
+            &mut rng,
+            &state.clock,
+            &compat_session,
+            TokenType::CompatAccessToken.generate(&mut rng),
+            None,
+        )
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    let request = Request::post(mas_router::AdminRevokeToken::PATH)
+        .bearer(&token)
+        .json(serde_json::json!({ "token": compat_token.token }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let revoked: serde_json::Value = response.json();
+    assert_eq!(revoked["session_type"], "compat");
+    assert_eq!(revoked["session_id"], compat_session.id.to_string());
+
+    // The token can't be revoked again
+    let request = Request::post(mas_router::AdminRevokeToken::PATH)
+        .bearer(&token)
+        .json(serde_json::json!({ "token": compat_token.token }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::BAD_REQUEST);
+
+    let request = Request::get(format!(
+        "{}?user={}&state=finished",
+        mas_router::AdminCompatSessions::PATH,
+        user.id
+    ))
+    .bearer(&token)
+    .empty();
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let page: serde_json::Value = response.json();
+    assert_eq!(page["data"][0]["id"], compat_session.id.to_string());
+}
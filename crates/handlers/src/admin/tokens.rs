@@ -0,0 +1,129 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{extract::State, Json};
+use mas_axum_utils::user_authorization::HeaderUserAuthorization;
+use mas_data_model::TokenType;
+use mas_router::UrlBuilder;
+use mas_storage::{oauth2, BoxClock, BoxRepository, Clock, RepositoryAccess};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use ulid::Ulid;
+
+use super::{authenticate, compat_sessions, model, RouteError};
+use crate::{BoundActivityTracker, SiteConfig};
+
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct RevokeTokenRequest {
+    /// The access or refresh token to revoke. Both OAuth 2.0 and
+    /// compatibility tokens are accepted.
+    token: String,
+}
+
+/// Find the ID of the session a valid token belongs to
+async fn find_session_id(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    token_type: TokenType,
+    token: &str,
+) -> Result<Option<Ulid>, RouteError> {
+    let session_id = match token_type {
+        TokenType::AccessToken => repo
+            .oauth2_access_token()
+            .find_by_token(token)
+            .await?
+            .filter(|access_token| access_token.is_valid(clock.now()))
+            .map(|access_token| access_token.session_id),
+        TokenType::RefreshToken => repo
+            .oauth2_refresh_token()
+            .find_by_token(token)
+            .await?
+            .filter(|refresh_token| refresh_token.is_valid())
+            .map(|refresh_token| refresh_token.session_id),
+        TokenType::CompatAccessToken => repo
+            .compat_access_token()
+            .find_by_token(token)
+            .await?
+            .filter(|access_token| access_token.is_valid(clock.now()))
+            .map(|access_token| access_token.session_id),
+        TokenType::CompatRefreshToken => repo
+            .compat_refresh_token()
+            .find_by_token(token)
+            .await?
+            .filter(|refresh_token| refresh_token.is_valid())
+            .map(|refresh_token| refresh_token.session_id),
+    };
+
+    Ok(session_id)
+}
+
+#[tracing::instrument(name = "handlers.admin.tokens.revoke", skip_all, err)]
+pub(crate) async fn revoke(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Json(request): Json<RevokeTokenRequest>,
+) -> Result<Json<model::RevokedSession>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminRevokeToken);
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let token_type = TokenType::check(&request.token).map_err(|_| RouteError::UnknownToken)?;
+    let session_id = find_session_id(&mut repo, &clock, token_type, &request.token)
+        .await?
+        .ok_or(RouteError::UnknownToken)?;
+
+    let revoked = match token_type {
+        TokenType::AccessToken | TokenType::RefreshToken => {
+            let session = repo
+                .oauth2_session()
+                .lookup(session_id)
+                .await?
+                .filter(|session| session.is_valid())
+                .ok_or(RouteError::UnknownToken)?;
+
+            let session = oauth2::end_session(&mut repo, &clock, session).await?;
+            model::RevokedSession::OAuth2 {
+                session_id: session.id,
+            }
+        }
+        TokenType::CompatAccessToken | TokenType::CompatRefreshToken => {
+            let session = repo
+                .compat_session()
+                .lookup(session_id)
+                .await?
+                .filter(|session| session.is_valid())
+                .ok_or(RouteError::UnknownToken)?;
+
+            let session = compat_sessions::end_session(&mut repo, &clock, session).await?;
+            model::RevokedSession::Compat {
+                session_id: session.id,
+            }
+        }
+    };
+
+    repo.save().await?;
+
+    Ok(Json(revoked))
+}
@@ -0,0 +1,256 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use hyper::StatusCode;
+use mas_axum_utils::user_authorization::HeaderUserAuthorization;
+use mas_data_model::is_valid_username;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{DeactivateUserJob, JobRepositoryExt, ProvisionUserJob},
+    user::UserFilter,
+    BoxClock, BoxRepository, BoxRng, RepositoryAccess,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::info;
+use ulid::Ulid;
+
+use super::{authenticate, load_user, model, pagination, RouteError};
+use crate::{BoundActivityTracker, SiteConfig};
+
+/// The state of the users to list
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UserState {
+    Active,
+    Locked,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct ListParams {
+    /// Only return users whose username or one of their email addresses
+    /// contains this text, ignoring case
+    search: Option<String>,
+
+    /// Only return users in this state
+    state: Option<UserState>,
+
+    /// Return the users after this cursor
+    #[schemars(with = "Option<String>")]
+    after: Option<Ulid>,
+
+    /// How many users to return, up to 100. Defaults to 10.
+    first: Option<usize>,
+}
+
+#[tracing::instrument(name = "handlers.admin.users.list", skip_all, err)]
+pub(crate) async fn list(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Query(params): Query<ListParams>,
+) -> Result<Json<model::Page<model::User>>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminUsers);
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let pagination = pagination(params.after, params.first)?;
+
+    let mut filter = UserFilter::new();
+    if let Some(search) = params.search.as_deref() {
+        filter = filter.matching(search);
+    }
+    filter = match params.state {
+        Some(UserState::Active) => filter.active_only(),
+        Some(UserState::Locked) => filter.locked_only(),
+        None => filter,
+    };
+
+    let page = repo.user().list(filter, pagination).await?;
+
+    Ok(Json(model::Page::from_storage(page, |user| user.id)))
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub(crate) struct AddUserRequest {
+    /// The username of the user to add
+    username: String,
+
+    /// Whether the user is a service account, which can't log in
+    /// interactively. Defaults to `false`.
+    #[serde(default)]
+    service_account: bool,
+}
+
+#[tracing::instrument(name = "handlers.admin.users.add", skip_all, err)]
+pub(crate) async fn add(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Json(request): Json<AddUserRequest>,
+) -> Result<impl IntoResponse, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminUsers);
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    if !is_valid_username(&request.username) {
+        return Err(RouteError::InvalidUsername);
+    }
+
+    if repo.user().exists(&request.username).await? {
+        return Err(RouteError::UserExists(request.username));
+    }
+
+    let mut user = repo.user().add(&mut rng, &clock, request.username).await?;
+
+    if request.service_account {
+        user = repo.user().set_service_account(user, true).await?;
+    }
+
+    repo.job()
+        .schedule_job(ProvisionUserJob::new(&user))
+        .await?;
+
+    repo.save().await?;
+
+    Ok((StatusCode::CREATED, Json(model::User::from(user))))
+}
+
+#[tracing::instrument(name = "handlers.admin.users.get", fields(user.id = %id), skip_all, err)]
+pub(crate) async fn get(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+) -> Result<Json<model::User>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminUser(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let user = load_user(&mut repo, id).await?;
+
+    Ok(Json(user.into()))
+}
+
+#[derive(Deserialize, JsonSchema, Default)]
+pub(crate) struct LockUserRequest {
+    /// Also deactivate the user on the homeserver, which can't be undone.
+    /// Defaults to `false`.
+    #[serde(default)]
+    deactivate: bool,
+}
+
+#[tracing::instrument(name = "handlers.admin.users.lock", fields(user.id = %id), skip_all, err)]
+pub(crate) async fn lock(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+    request: Option<Json<LockUserRequest>>,
+) -> Result<Json<model::User>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminLockUser(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let Json(request) = request.unwrap_or_default();
+    let user = load_user(&mut repo, id).await?;
+
+    let user = repo.user().lock(&clock, user).await?;
+
+    if request.deactivate {
+        info!("Scheduling deactivation of user {}", user.id);
+        repo.job()
+            .schedule_job(DeactivateUserJob::new(&user, true))
+            .await?;
+    }
+
+    repo.save().await?;
+
+    Ok(Json(user.into()))
+}
+
+#[tracing::instrument(name = "handlers.admin.users.unlock", fields(user.id = %id), skip_all, err)]
+pub(crate) async fn unlock(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    HeaderUserAuthorization(user_authorization): HeaderUserAuthorization,
+    Path(id): Path<Ulid>,
+) -> Result<Json<model::User>, RouteError> {
+    let uri = url_builder.absolute_url_for(&mas_router::AdminUnlockUser(id));
+    authenticate(
+        &mut repo,
+        &clock,
+        &activity_tracker,
+        user_authorization,
+        &uri,
+        &site_config,
+    )
+    .await?;
+
+    let user = load_user(&mut repo, id).await?;
+    let user = repo.user().unlock(user).await?;
+
+    repo.save().await?;
+
+    Ok(Json(user.into()))
+}
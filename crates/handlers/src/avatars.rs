@@ -0,0 +1,247 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage and serving of the avatars users upload
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use hyper::{
+    header::{CACHE_CONTROL, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+    StatusCode,
+};
+use mas_axum_utils::sentry::SentryEventID;
+use mas_router::UrlBuilder;
+use thiserror::Error;
+use ulid::Ulid;
+use url::Url;
+
+use crate::{
+    blob_storage::{BlobStorage, BlobStorageError},
+    impl_from_error_for_route, SiteConfig,
+};
+
+/// The image formats accepted as avatars, with the extension used to store
+/// them
+const FORMATS: [(&str, &str); 4] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+];
+
+/// Guess the content type of an image from its first bytes. We don't trust
+/// the content type sent by the client, as the file is served back as-is.
+fn image_content_type(content: &[u8]) -> Option<&'static str> {
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if content.starts_with(b"\xFF\xD8\xFF") {
+        Some("image/jpeg")
+    } else if content.starts_with(b"GIF87a") || content.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if content.len() >= 12 && &content[..4] == b"RIFF" && &content[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// Stores the avatars uploaded by users in the blob storage, under the
+/// `avatars/` prefix
+#[derive(Debug, Clone)]
+pub struct AvatarStore {
+    blob_storage: BlobStorage,
+    max_size: usize,
+    url_builder: UrlBuilder,
+}
+
+impl AvatarStore {
+    /// Create a new avatar store, saving avatars in the given blob storage
+    #[must_use]
+    pub fn new(blob_storage: BlobStorage, max_size: usize, url_builder: UrlBuilder) -> Self {
+        Self {
+            blob_storage,
+            max_size,
+            url_builder,
+        }
+    }
+
+    /// The maximum size of an avatar, in bytes
+    #[must_use]
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Save an avatar, returning the URL from which it is served, or [`None`]
+    /// if the content is not a PNG, JPEG, GIF or WebP image
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the avatar could not be written to the blob
+    /// storage
+    pub async fn save(&self, id: Ulid, content: Vec<u8>) -> Result<Option<Url>, BlobStorageError> {
+        let Some(content_type) = image_content_type(&content) else {
+            return Ok(None);
+        };
+
+        let (_, extension) = FORMATS
+            .iter()
+            .find(|(t, _)| *t == content_type)
+            .expect("all the sniffed formats are accepted");
+
+        let filename = format!("{id}.{extension}");
+        self.blob_storage
+            .put(&format!("avatars/{filename}"), content_type, content)
+            .await?;
+
+        Ok(Some(self.url_builder.avatar(filename)))
+    }
+}
+
+#[cfg(feature = "graphql")]
+#[axum::async_trait]
+impl mas_graphql::AvatarStore for AvatarStore {
+    fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    async fn save(&self, id: Ulid, content: Vec<u8>) -> anyhow::Result<Option<Url>> {
+        Ok(AvatarStore::save(self, id, content).await?)
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error("Avatar not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+}
+
+impl_from_error_for_route!(BlobStorageError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+        let response = match self {
+            Self::NotFound => (StatusCode::NOT_FOUND, "Avatar not found").into_response(),
+            Self::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+#[tracing::instrument(
+    name = "handlers.avatars.get",
+    fields(avatar.filename = %filename),
+    skip_all,
+    err,
+)]
+pub(crate) async fn get(
+    State(site_config): State<SiteConfig>,
+    Path(filename): Path<String>,
+) -> Result<impl IntoResponse, RouteError> {
+    let store = site_config.avatar_store.ok_or(RouteError::NotFound)?;
+
+    // Only serve files named like the ones we save
+    let (id, extension) = filename.split_once('.').ok_or(RouteError::NotFound)?;
+    Ulid::from_string(id).map_err(|_| RouteError::NotFound)?;
+    let (content_type, _) = FORMATS
+        .iter()
+        .find(|(_, e)| *e == extension)
+        .ok_or(RouteError::NotFound)?;
+
+    let content = store
+        .blob_storage
+        .get(&format!("avatars/{filename}"))
+        .await?
+        .ok_or(RouteError::NotFound)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, *content_type),
+            // Avatars are never modified, a new one gets a new ID
+            (CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            (X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        ],
+        content,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::CONTENT_TYPE, Request, StatusCode};
+    use mas_storage::Clock;
+    use sqlx::PgPool;
+
+    use super::AvatarStore;
+    use crate::{
+        blob_storage::BlobStorage,
+        test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState},
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_avatars(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+
+        let id = ulid::Ulid::from_datetime_with_source(state.clock.now().into(), &mut state.rng());
+        let path = std::env::temp_dir().join(format!("mas-avatars-{id}"));
+        let store = AvatarStore::new(
+            BlobStorage::filesystem(path.clone().try_into().unwrap()),
+            1024,
+            state.url_builder.clone(),
+        );
+
+        // Uploads are disabled, so nothing is served
+        let request = Request::get(format!("/avatars/{id}.png")).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        state.site_config.avatar_store = Some(store.clone());
+
+        // A GIF header keeps the body valid UTF-8 for the test client
+        let content = b"GIF89ahello".to_vec();
+
+        // Files which aren't images are refused
+        assert!(store.save(id, b"hello".to_vec()).await.unwrap().is_none());
+
+        let url = store.save(id, content).await.unwrap().unwrap();
+        assert_eq!(
+            url.as_str(),
+            format!("https://example.com/avatars/{id}.gif")
+        );
+
+        let request = Request::get(url.path()).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.assert_header_value(CONTENT_TYPE, "image/gif");
+        assert_eq!(response.body(), "GIF89ahello");
+
+        // Same file with another extension isn't served
+        let request = Request::get(format!("/avatars/{id}.png")).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // Neither are files not named after an ID
+        let request = Request::get("/avatars/..%2Fpasswd").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Storage of blobs, like the avatars uploaded by users, either on the local
+//! filesystem or in an S3-compatible object storage
+
+use std::sync::Arc;
+
+use camino::Utf8PathBuf;
+use hyper::StatusCode;
+use thiserror::Error;
+use tower::BoxError;
+
+mod filesystem;
+mod s3;
+
+use self::filesystem::Filesystem;
+pub use self::s3::{S3Bucket, S3ServerSideEncryption};
+
+/// Errors which can happen when storing, reading or deleting a blob
+#[derive(Debug, Error)]
+pub enum BlobStorageError {
+    #[error("Invalid blob key {0:?}")]
+    InvalidKey(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("Request to the object storage failed")]
+    Http(#[source] BoxError),
+
+    #[error("Object storage replied with unexpected status {0}")]
+    UnexpectedStatus(StatusCode),
+}
+
+/// A wrapper around the supported blob storage backends
+#[derive(Debug, Clone)]
+pub struct BlobStorage {
+    inner: Arc<BlobStorageInner>,
+}
+
+#[derive(Debug)]
+enum BlobStorageInner {
+    Filesystem(Filesystem),
+    S3(S3Bucket),
+}
+
+impl BlobStorage {
+    fn new(inner: BlobStorageInner) -> Self {
+        let inner = Arc::new(inner);
+        Self { inner }
+    }
+
+    /// Construct a blob storage saving blobs in a directory
+    #[must_use]
+    pub fn filesystem(root: Utf8PathBuf) -> Self {
+        Self::new(BlobStorageInner::Filesystem(Filesystem::new(root)))
+    }
+
+    /// Construct a blob storage saving blobs in an S3 bucket
+    #[must_use]
+    pub fn s3(bucket: S3Bucket) -> Self {
+        Self::new(BlobStorageInner::S3(bucket))
+    }
+
+    /// Store a blob under the given key, replacing any existing blob
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is invalid or if the backend failed to
+    /// store the blob
+    #[tracing::instrument(
+        name = "blob_storage.put",
+        skip(self, content),
+        fields(blob.size = content.len()),
+        err,
+    )]
+    pub async fn put(
+        &self,
+        key: &str,
+        content_type: &str,
+        content: Vec<u8>,
+    ) -> Result<(), BlobStorageError> {
+        validate_key(key)?;
+        match self.inner.as_ref() {
+            BlobStorageInner::Filesystem(fs) => fs.put(key, content).await,
+            BlobStorageInner::S3(bucket) => bucket.put(key, content_type, content).await,
+        }
+    }
+
+    /// Get the blob stored under the given key, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is invalid or if the backend failed to
+    /// read the blob
+    #[tracing::instrument(name = "blob_storage.get", skip(self), err)]
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BlobStorageError> {
+        validate_key(key)?;
+        match self.inner.as_ref() {
+            BlobStorageInner::Filesystem(fs) => fs.get(key).await,
+            BlobStorageInner::S3(bucket) => bucket.get(key).await,
+        }
+    }
+
+    /// Delete the blob stored under the given key. Deleting a blob which
+    /// doesn't exist is not an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is invalid or if the backend failed to
+    /// delete the blob
+    #[tracing::instrument(name = "blob_storage.delete", skip(self), err)]
+    pub async fn delete(&self, key: &str) -> Result<(), BlobStorageError> {
+        validate_key(key)?;
+        match self.inner.as_ref() {
+            BlobStorageInner::Filesystem(fs) => fs.delete(key).await,
+            BlobStorageInner::S3(bucket) => bucket.delete(key).await,
+        }
+    }
+}
+
+/// Check that a key is made of non-empty `/`-separated segments of
+/// alphanumeric characters, `-`, `_` and `.`, without `.` or `..` segments.
+///
+/// This makes sure a key can never escape the directory of the filesystem
+/// backend and doesn't need any escaping in S3 object keys.
+fn validate_key(key: &str) -> Result<(), BlobStorageError> {
+    let valid = key.split('/').all(|segment| {
+        !segment.is_empty()
+            && segment != "."
+            && segment != ".."
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    });
+
+    if valid {
+        Ok(())
+    } else {
+        Err(BlobStorageError::InvalidKey(key.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        assert!(validate_key("avatars/01HG3ZC1TBE6YBJXSBH5S8VSM2.png").is_ok());
+        assert!(validate_key("exports/2023-11-29/export_1.tar.gz").is_ok());
+
+        assert!(validate_key("").is_err());
+        assert!(validate_key("/avatars").is_err());
+        assert!(validate_key("avatars/").is_err());
+        assert!(validate_key("avatars//a.png").is_err());
+        assert!(validate_key("avatars/../passwd").is_err());
+        assert!(validate_key("./passwd").is_err());
+        assert!(validate_key("avatars/a b.png").is_err());
+        assert!(validate_key("avatars\\a.png").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filesystem() {
+        let root = std::env::temp_dir().join(format!("mas-blobs-{}", std::process::id()));
+        let storage = BlobStorage::filesystem(root.clone().try_into().unwrap());
+
+        assert_eq!(storage.get("a/b.txt").await.unwrap(), None);
+
+        storage
+            .put("a/b.txt", "text/plain", b"hello".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get("a/b.txt").await.unwrap().as_deref(),
+            Some(&b"hello"[..])
+        );
+
+        storage.delete("a/b.txt").await.unwrap();
+        assert_eq!(storage.get("a/b.txt").await.unwrap(), None);
+
+        // Deleting twice is fine
+        storage.delete("a/b.txt").await.unwrap();
+
+        assert!(matches!(
+            storage.get("../b.txt").await,
+            Err(BlobStorageError::InvalidKey(_))
+        ));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
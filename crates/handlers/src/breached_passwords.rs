@@ -0,0 +1,156 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Check new passwords against the Have I Been Pwned "range" API.
+//!
+//! Only the first 5 characters of the SHA-1 hash of the password are sent, and
+//! the API answers with the suffixes of all the breached hashes starting with
+//! them, so the password itself never leaves the service.
+
+use hyper::{body::Bytes, header::HeaderName, Request, StatusCode};
+use mas_axum_utils::http_client_factory::HttpClientFactory;
+use mas_http::HttpServiceExt;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+use tower::{Service, ServiceExt};
+use url::Url;
+
+/// What to do when a user picks a password which appeared in a breach
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreachedPasswordAction {
+    /// Refuse the password
+    Reject,
+
+    /// Accept the password, but log a warning
+    Warn,
+}
+
+#[derive(Debug, Error)]
+#[error("failed to check the password against the breached passwords API")]
+pub(crate) struct Error(#[source] anyhow::Error);
+
+/// Checks passwords against a Have I Been Pwned compatible range API
+#[derive(Debug, Clone)]
+pub struct BreachedPasswordCheck {
+    endpoint: Url,
+    action: BreachedPasswordAction,
+}
+
+impl BreachedPasswordCheck {
+    /// Create a new [`BreachedPasswordCheck`] calling the range API at the
+    /// given endpoint, like `https://api.pwnedpasswords.com/range/`
+    #[must_use]
+    pub fn new(endpoint: Url, action: BreachedPasswordAction) -> Self {
+        Self { endpoint, action }
+    }
+
+    /// Check if the password appeared in a breach, and returns `true` if it
+    /// should be refused.
+    ///
+    /// If the API can't be reached, the password is accepted and a warning is
+    /// logged, so that an outage of the API doesn't prevent users from
+    /// registering or changing their password.
+    pub(crate) async fn should_reject(
+        &self,
+        http_client_factory: &HttpClientFactory,
+        password: &str,
+    ) -> bool {
+        match self.is_breached(http_client_factory, password).await {
+            Ok(false) => false,
+            Ok(true) => {
+                tracing::warn!(action = ?self.action, "Password appeared in a data breach");
+                self.action == BreachedPasswordAction::Reject
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Could not check if the password appeared in a data breach"
+                );
+                false
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "handlers.breached_passwords.check", skip_all, err)]
+    async fn is_breached(
+        &self,
+        http_client_factory: &HttpClientFactory,
+        password: &str,
+    ) -> Result<bool, Error> {
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+
+        let url = self.endpoint.join(prefix).map_err(|e| Error(e.into()))?;
+
+        // Ask for padding, so that the size of the response doesn't leak the
+        // prefix
+        let request = Request::get(url.as_str())
+            .header(HeaderName::from_static("add-padding"), "true")
+            .body(Bytes::new())
+            .map_err(|e| Error(e.into()))?;
+
+        let mut client = http_client_factory
+            .client("breached_passwords.range")
+            .request_bytes_to_body()
+            .response_body_to_bytes();
+
+        let response = client
+            .ready()
+            .await
+            .map_err(|e| Error(e.into()))?
+            .call(request)
+            .await
+            .map_err(|e| Error(e.into()))?;
+
+        if response.status() != StatusCode::OK {
+            return Err(Error(anyhow::anyhow!(
+                "unexpected status code {}",
+                response.status()
+            )));
+        }
+
+        let body = std::str::from_utf8(response.body()).map_err(|e| Error(e.into()))?;
+        Ok(range_contains(body, suffix))
+    }
+}
+
+/// Look for a hash suffix in a range API response, made of `SUFFIX:COUNT`
+/// lines. Padding entries have a count of 0 and are ignored.
+fn range_contains(body: &str, suffix: &str) -> bool {
+    body.lines().any(|line| {
+        let Some((line_suffix, count)) = line.trim().split_once(':') else {
+            return false;
+        };
+
+        line_suffix.eq_ignore_ascii_case(suffix) && count.parse().map_or(false, |c: u64| c > 0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_contains() {
+        let body = "0018A45C4D1DEF81644B54AB7F969B88D65:10\r\n\
+                    1E4C9B93F3F0682250B6CF8331B7EE68FD8:3861493\r\n\
+                    011053FD0102E94D6AE2F8B83D76FAF94F6:0\r\n";
+
+        assert!(range_contains(body, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"));
+        assert!(range_contains(body, "1e4c9b93f3f0682250b6cf8331b7ee68fd8"));
+        // Padding entries don't count
+        assert!(!range_contains(body, "011053FD0102E94D6AE2F8B83D76FAF94F6"));
+        assert!(!range_contains(body, "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"));
+    }
+}
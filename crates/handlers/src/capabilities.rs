@@ -0,0 +1,173 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Describes which optional features this deployment supports, so that
+//! clients can feature-detect instead of relying on version checks
+
+use axum::{extract::State, response::IntoResponse, Json};
+use mas_axum_utils::FancyError;
+use mas_data_model::UpstreamOAuthProvider;
+use mas_storage::BoxRepository;
+use serde::Serialize;
+use ulid::Ulid;
+
+use crate::{passwords::PasswordManager, SiteConfig};
+
+#[derive(Serialize, Debug)]
+struct UpstreamProvider {
+    id: Ulid,
+    issuer: String,
+    human_name: Option<String>,
+    brand_name: Option<String>,
+}
+
+impl From<UpstreamOAuthProvider> for UpstreamProvider {
+    fn from(provider: UpstreamOAuthProvider) -> Self {
+        Self {
+            id: provider.id,
+            issuer: provider.issuer,
+            human_name: provider.human_name,
+            brand_name: provider.brand_name,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct Capabilities {
+    /// Whether users can log in with a password
+    password_login: bool,
+
+    /// Whether users can create an account with a password
+    password_registration: bool,
+
+    /// Whether the OAuth 2.0 device authorization grant is available
+    device_code_grant: bool,
+
+    /// Whether the Matrix compatibility layer supports SSO login
+    compat_sso: bool,
+
+    /// Whether users can upload an avatar from their account page
+    avatar_upload: bool,
+
+    /// The second factor authentication methods users can set up
+    second_factor_methods: Vec<&'static str>,
+
+    /// The upstream providers users can log in with
+    upstream_providers: Vec<UpstreamProvider>,
+}
+
+#[tracing::instrument(name = "handlers.capabilities.get", skip_all, err)]
+pub(crate) async fn get(
+    State(password_manager): State<PasswordManager>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+) -> Result<impl IntoResponse, FancyError> {
+    let upstream_providers = crate::upstream_providers(&mut repo)
+        .await?
+        .into_iter()
+        .map(UpstreamProvider::from)
+        .collect();
+
+    let capabilities = Capabilities {
+        password_login: password_manager.is_enabled(),
+        password_registration: password_manager.is_enabled(),
+        device_code_grant: true,
+        compat_sso: true,
+        avatar_upload: site_config.avatar_store.is_some(),
+        // No second factor is supported yet
+        second_factor_methods: Vec::new(),
+        upstream_providers,
+    };
+
+    Ok(Json(capabilities))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_router::SimpleRoute;
+    use mas_storage::{
+        upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
+        RepositoryAccess,
+    };
+    use oauth2_types::scope::{Scope, OPENID};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_get_capabilities(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let request = Request::get(mas_router::Capabilities::PATH).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let capabilities: serde_json::Value = response.json();
+        assert_eq!(capabilities["password_login"], true);
+        assert_eq!(capabilities["password_registration"], true);
+        assert_eq!(capabilities["device_code_grant"], true);
+        assert_eq!(capabilities["avatar_upload"], false);
+        assert_eq!(capabilities["upstream_providers"], serde_json::json!([]));
+
+        // Add an upstream provider, it should show up
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://example.com/".to_owned(),
+                    human_name: Some("Example Ltd.".to_owned()),
+                    brand_name: None,
+                    scope: Scope::from_iter([OPENID]),
+                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    token_endpoint_signing_alg: None,
+                    client_id: "client".to_owned(),
+                    encrypted_client_secret: None,
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    authorization_endpoint_override: None,
+                    token_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+                    pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+                },
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get(mas_router::Capabilities::PATH).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let capabilities: serde_json::Value = response.json();
+        assert_eq!(
+            capabilities["upstream_providers"],
+            serde_json::json!([{
+                "id": provider.id,
+                "issuer": "https://example.com/",
+                "human_name": "Example Ltd.",
+                "brand_name": null,
+            }])
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::IpAddr;
+
+use mas_axum_utils::http_client_factory::HttpClientFactory;
+use mas_data_model::{CaptchaConfig, CaptchaService};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// The response to a CAPTCHA challenge, as submitted along with a form.
+///
+/// Each service puts the response in a differently-named field, so all of them
+/// are accepted, and the one matching the configured service is used.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct CaptchaForm {
+    #[serde(rename = "g-recaptcha-response")]
+    recaptcha_response: Option<String>,
+
+    #[serde(rename = "cf-turnstile-response")]
+    turnstile_response: Option<String>,
+
+    #[serde(rename = "h-captcha-response")]
+    hcaptcha_response: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    #[error("the form has no response to the CAPTCHA challenge")]
+    MissingResponse,
+
+    #[error("the response to the CAPTCHA challenge was rejected: {error_codes:?}")]
+    Rejected { error_codes: Vec<String> },
+
+    #[error("failed to verify the response to the CAPTCHA challenge")]
+    Call(#[source] anyhow::Error),
+}
+
+impl Error {
+    /// Returns `true` if the user failed the challenge, as opposed to the
+    /// service not being reachable
+    pub(crate) fn is_user_error(&self) -> bool {
+        matches!(self, Self::MissingResponse | Self::Rejected { .. })
+    }
+}
+
+#[cfg(feature = "captcha")]
+#[derive(serde::Serialize)]
+struct VerifyRequest<'a> {
+    secret: &'a str,
+    response: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remoteip: Option<IpAddr>,
+
+    /// Only used by hCaptcha, to check that the response was given for this
+    /// site
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sitekey: Option<&'a str>,
+}
+
+#[cfg(feature = "captcha")]
+#[derive(Deserialize)]
+struct VerifyResponse {
+    success: bool,
+
+    #[serde(default, rename = "error-codes")]
+    error_codes: Vec<String>,
+}
+
+impl CaptchaForm {
+    fn response(&self, service: CaptchaService) -> Option<&str> {
+        let response = match service {
+            CaptchaService::RecaptchaV2 => &self.recaptcha_response,
+            CaptchaService::CloudflareTurnstile => &self.turnstile_response,
+            CaptchaService::HCaptcha => &self.hcaptcha_response,
+        };
+
+        response.as_deref().filter(|response| !response.is_empty())
+    }
+
+    /// Verify the response to the challenge with the configured service.
+    ///
+    /// Always succeeds if no CAPTCHA service is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the form has no response, if the service rejected
+    /// it, or if the service could not be reached
+    #[tracing::instrument(name = "handlers.captcha.verify", skip_all, err)]
+    pub(crate) async fn verify(
+        &self,
+        http_client_factory: &HttpClientFactory,
+        remote_ip: Option<IpAddr>,
+        config: Option<&CaptchaConfig>,
+    ) -> Result<(), Error> {
+        let Some(config) = config else {
+            return Ok(());
+        };
+
+        let response = self
+            .response(config.service)
+            .ok_or(Error::MissingResponse)?;
+
+        call_service(http_client_factory, remote_ip, config, response).await
+    }
+}
+
+#[cfg(feature = "captcha")]
+async fn call_service(
+    http_client_factory: &HttpClientFactory,
+    remote_ip: Option<IpAddr>,
+    config: &CaptchaConfig,
+    response: &str,
+) -> Result<(), Error> {
+    use hyper::Request;
+    use mas_http::HttpServiceExt;
+    use tower::{Service, ServiceExt};
+
+    let request = Request::post(config.service.verify_url())
+        .body(VerifyRequest {
+            secret: &config.secret_key,
+            response,
+            remoteip: remote_ip,
+            sitekey: matches!(config.service, CaptchaService::HCaptcha)
+                .then_some(config.site_key.as_str()),
+        })
+        .map_err(|e| Error::Call(e.into()))?;
+
+    let mut client = http_client_factory
+        .client("captcha.verify")
+        .request_bytes_to_body()
+        .form_urlencoded_request()
+        .response_body_to_bytes()
+        .json_response::<VerifyResponse>();
+
+    let response = client
+        .ready()
+        .await
+        .map_err(|e| Error::Call(e.into()))?
+        .call(request)
+        .await
+        .map_err(|e| Error::Call(e.into()))?;
+
+    let response = response.into_body();
+    if !response.success {
+        return Err(Error::Rejected {
+            error_codes: response.error_codes,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "captcha"))]
+#[allow(clippy::unused_async)]
+async fn call_service(
+    _http_client_factory: &HttpClientFactory,
+    _remote_ip: Option<IpAddr>,
+    _config: &CaptchaConfig,
+    _response: &str,
+) -> Result<(), Error> {
+    // A CAPTCHA service is configured, but this build can't verify the
+    // responses: fail closed instead of letting everyone through
+    Err(Error::Call(anyhow::anyhow!(
+        "this build doesn't include CAPTCHA verification"
+    )))
+}
@@ -0,0 +1,163 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use minijinja::{context, Environment};
+
+/// A template used to derive the name of a compat device from the client
+/// metadata, when the client didn't supply an `initial_device_display_name`
+///
+/// The template has access to the `client` and `platform` variables, guessed
+/// from the `User-Agent` header, as well as the raw `user_agent`. They are all
+/// undefined if the client didn't send the header.
+#[derive(Debug, Clone)]
+pub struct DeviceNameTemplate {
+    source: Arc<str>,
+}
+
+impl DeviceNameTemplate {
+    /// Parse a device name template
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template is not valid
+    pub fn new(source: &str) -> Result<Self, minijinja::Error> {
+        // Compile it once, so that syntax errors are caught on startup
+        Environment::new().template_from_str(source)?;
+
+        Ok(Self {
+            source: source.into(),
+        })
+    }
+
+    /// Render the template for the given `User-Agent`
+    ///
+    /// Returns [`None`] if the template rendered to an empty string, or if it
+    /// failed to render.
+    #[must_use]
+    pub fn render(&self, user_agent: Option<&str>) -> Option<String> {
+        let ctx = context! {
+            client => user_agent.and_then(guess_client),
+            platform => user_agent.and_then(guess_platform),
+            user_agent => user_agent,
+        };
+
+        match Environment::new().render_str(&self.source, ctx) {
+            Ok(name) => {
+                let name = name.trim();
+                (!name.is_empty()).then(|| name.to_owned())
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Failed to render the device name template"
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Guess the name of the client from its `User-Agent`
+///
+/// Matrix clients usually put their name as the first product of the header,
+/// e.g. `Element/1.5.28 (Android 13; ...)`. Browsers all pretend to be
+/// `Mozilla`, so we look for the browser name further in the header instead.
+fn guess_client(user_agent: &str) -> Option<&str> {
+    // Order matters, as each browser also pretends to be the ones after it
+    const BROWSERS: [(&str, &str); 4] = [
+        ("Firefox/", "Firefox"),
+        ("Edg/", "Edge"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ];
+
+    let product = user_agent.split(['/', ' ']).next()?;
+
+    if product == "Mozilla" {
+        BROWSERS
+            .iter()
+            .find(|(needle, _)| user_agent.contains(needle))
+            .map(|(_, name)| *name)
+    } else if product.is_empty() {
+        None
+    } else {
+        Some(product)
+    }
+}
+
+/// Guess the platform the client runs on from its `User-Agent`
+fn guess_platform(user_agent: &str) -> Option<&'static str> {
+    // Order matters: Android UAs mention Linux, and iOS UAs mention Mac OS X
+    const PLATFORMS: [(&str, &str); 8] = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("iOS", "iOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Macintosh", "macOS"),
+        ("Linux", "Linux"),
+    ];
+
+    PLATFORMS
+        .iter()
+        .find(|(needle, _)| user_agent.contains(needle))
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_from_user_agent() {
+        let element_android = "Element/1.6.5 (Google Pixel 7; Android 14; UP1A.231005.007; Flavour GooglePlay; MatrixAndroidSdk2 1.6.5)";
+        assert_eq!(guess_client(element_android), Some("Element"));
+        assert_eq!(guess_platform(element_android), Some("Android"));
+
+        let element_ios = "Element/1.11.6 (iPhone; iOS 17.1.2; Scale/3.00)";
+        assert_eq!(guess_client(element_ios), Some("Element"));
+        assert_eq!(guess_platform(element_ios), Some("iOS"));
+
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0";
+        assert_eq!(guess_client(firefox), Some("Firefox"));
+        assert_eq!(guess_platform(firefox), Some("Linux"));
+
+        let chrome = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+        assert_eq!(guess_client(chrome), Some("Chrome"));
+        assert_eq!(guess_platform(chrome), Some("macOS"));
+
+        assert_eq!(guess_client(""), None);
+        assert_eq!(guess_platform("curl/8.4.0"), None);
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert!(DeviceNameTemplate::new("{{ client ").is_err());
+
+        let template = DeviceNameTemplate::new(
+            "{% if client %}{{ client }}{% if platform %} on {{ platform }}{% endif %}{% endif %}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            template.render(Some("Element/1.11.6 (iPhone; iOS 17.1.2; Scale/3.00)")),
+            Some("Element on iOS".to_owned())
+        );
+        assert_eq!(template.render(Some("curl/8.4.0")), Some("curl".to_owned()));
+        assert_eq!(template.render(None), None);
+    }
+}
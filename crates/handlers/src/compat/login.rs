@@ -12,17 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use axum::{extract::State, response::IntoResponse, Json};
+use axum::{extract::State, response::IntoResponse, Json, TypedHeader};
 use chrono::Duration;
-use hyper::StatusCode;
+use headers::UserAgent;
+use hyper::{header::RETRY_AFTER, StatusCode};
 use mas_axum_utils::sentry::SentryEventID;
-use mas_data_model::{CompatSession, CompatSsoLoginState, Device, TokenType, User};
+use mas_data_model::{
+    CompatSession, CompatSsoLoginState, Device, TokenType, UpstreamOAuthProvider, User,
+};
+use mas_policy::Policy;
 use mas_storage::{
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
     },
-    job::{JobRepositoryExt, ProvisionDeviceJob},
+    job::{JobRepositoryExt, NotifyNewSignInJob, ProvisionDeviceJob},
     user::{UserPasswordRepository, UserRepository},
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
@@ -34,7 +38,10 @@ use zeroize::Zeroizing;
 
 use super::{MatrixError, MatrixHomeserver};
 use crate::{
-    impl_from_error_for_route, passwords::PasswordManager, site_config::SiteConfig,
+    device_conflict::{claim_device, DeviceConflictError},
+    impl_from_error_for_route,
+    passwords::PasswordManager,
+    site_config::SiteConfig,
     BoundActivityTracker,
 };
 
@@ -58,10 +65,25 @@ enum LoginType {
     },
 }
 
+/// An upstream provider, as advertised in the `m.login.sso` flow (MSC2858)
+#[skip_serializing_none]
 #[derive(Debug, Serialize)]
 struct SsoIdentityProvider {
-    id: &'static str,
-    name: &'static str,
+    id: String,
+    name: String,
+    icon: Option<String>,
+    brand: Option<String>,
+}
+
+impl From<UpstreamOAuthProvider> for SsoIdentityProvider {
+    fn from(provider: UpstreamOAuthProvider) -> Self {
+        Self {
+            id: provider.id.to_string(),
+            name: provider.human_name.unwrap_or(provider.issuer),
+            icon: provider.icon,
+            brand: provider.brand_name,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -69,30 +91,33 @@ struct LoginTypes {
     flows: Vec<LoginType>,
 }
 
-#[tracing::instrument(name = "handlers.compat.login.get", skip_all)]
-pub(crate) async fn get(State(password_manager): State<PasswordManager>) -> impl IntoResponse {
+#[tracing::instrument(name = "handlers.compat.login.get", skip_all, err)]
+pub(crate) async fn get(
+    State(password_manager): State<PasswordManager>,
+    mut repo: BoxRepository,
+) -> Result<impl IntoResponse, RouteError> {
+    // Let clients show a provider picker, and send users directly to the
+    // provider they chose through `/login/sso/redirect/:idp`
+    let identity_providers = crate::upstream_providers(&mut repo)
+        .await?
+        .into_iter()
+        .map(SsoIdentityProvider::from)
+        .collect();
+
+    let sso = LoginType::Sso {
+        identity_providers,
+        delegated_oidc_compatibility: true,
+    };
+
     let flows = if password_manager.is_enabled() {
-        vec![
-            LoginType::Password,
-            LoginType::Sso {
-                identity_providers: vec![],
-                delegated_oidc_compatibility: true,
-            },
-            LoginType::Token,
-        ]
+        vec![LoginType::Password, sso, LoginType::Token]
     } else {
-        vec![
-            LoginType::Sso {
-                identity_providers: vec![],
-                delegated_oidc_compatibility: true,
-            },
-            LoginType::Token,
-        ]
+        vec![sso, LoginType::Token]
     };
 
     let res = LoginTypes { flows };
 
-    Json(res)
+    Ok(Json(res))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -102,6 +127,12 @@ pub struct RequestBody {
 
     #[serde(default)]
     refresh_token: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial_device_display_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    device_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -162,19 +193,63 @@ pub enum RouteError {
     #[error("password verification failed")]
     PasswordVerificationFailed(#[source] anyhow::Error),
 
+    #[error("user must reset their password")]
+    PasswordResetRequired,
+
+    #[error("password login is deprecated for this user")]
+    PasswordLoginDeprecated,
+
+    #[error("too many failed login attempts")]
+    LoginLocked,
+
+    #[error("too many login attempts, retry in {retry_after}s")]
+    RateLimited { retry_after: u64 },
+
     #[error("login took too long")]
     LoginTookTooLong,
 
     #[error("invalid login token")]
     InvalidLoginToken,
+
+    #[error("invalid device ID")]
+    InvalidDeviceId,
+
+    #[error("device ID is already in use")]
+    DeviceInUse,
 }
 
 impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_policy::EvaluationError);
+
+impl From<DeviceConflictError> for RouteError {
+    fn from(e: DeviceConflictError) -> Self {
+        match e {
+            DeviceConflictError::InUse(_) => Self::DeviceInUse,
+            DeviceConflictError::Internal(e) => Self::Internal(e),
+        }
+    }
+}
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         let event_id = sentry::capture_error(&self);
         let response = match self {
+            // The Matrix spec has a dedicated field to tell clients when to retry
+            Self::RateLimited { retry_after } => {
+                let body = serde_json::json!({
+                    "errcode": "M_LIMIT_EXCEEDED",
+                    "error": "Too many login attempts",
+                    "retry_after_ms": retry_after * 1000,
+                });
+
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    SentryEventID::from(event_id),
+                    [(RETRY_AFTER, retry_after.to_string())],
+                    Json(body),
+                )
+                    .into_response();
+            }
             Self::Internal(_) | Self::SessionNotFound => MatrixError {
                 errcode: "M_UNKNOWN",
                 error: "Internal server error",
@@ -192,6 +267,22 @@ impl IntoResponse for RouteError {
                     status: StatusCode::FORBIDDEN,
                 }
             }
+            Self::PasswordResetRequired => MatrixError {
+                errcode: "M_FORBIDDEN",
+                error: "Password reset required",
+                status: StatusCode::FORBIDDEN,
+            },
+            Self::PasswordLoginDeprecated => MatrixError {
+                errcode: "M_FORBIDDEN",
+                error: "Password login is no longer allowed for this account, \
+                        sign in through the browser (SSO) instead",
+                status: StatusCode::FORBIDDEN,
+            },
+            Self::LoginLocked => MatrixError {
+                errcode: "M_LIMIT_EXCEEDED",
+                error: "Too many failed login attempts",
+                status: StatusCode::TOO_MANY_REQUESTS,
+            },
             Self::LoginTookTooLong => MatrixError {
                 errcode: "M_UNAUTHORIZED",
                 error: "Login token expired",
@@ -202,6 +293,16 @@ impl IntoResponse for RouteError {
                 error: "Invalid login token",
                 status: StatusCode::FORBIDDEN,
             },
+            Self::InvalidDeviceId => MatrixError {
+                errcode: "M_INVALID_PARAM",
+                error: "Invalid device ID",
+                status: StatusCode::BAD_REQUEST,
+            },
+            Self::DeviceInUse => MatrixError {
+                errcode: "M_FORBIDDEN",
+                error: "Device ID is already in use",
+                status: StatusCode::FORBIDDEN,
+            },
         };
 
         (SentryEventID::from(event_id), response).into_response()
@@ -214,11 +315,29 @@ pub(crate) async fn post(
     clock: BoxClock,
     State(password_manager): State<PasswordManager>,
     mut repo: BoxRepository,
+    mut policy: Policy,
     activity_tracker: BoundActivityTracker,
     State(homeserver): State<MatrixHomeserver>,
     State(site_config): State<SiteConfig>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(input): Json<RequestBody>,
 ) -> Result<impl IntoResponse, RouteError> {
+    // Name the device after what the client asked for, or derive a name from
+    // its user agent if the operator configured a template for it
+    let device_name = input
+        .initial_device_display_name
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| {
+            let template = site_config.compat_device_name_template.as_ref()?;
+            template.render(user_agent.as_ref().map(|ua| ua.as_str()))
+        });
+
+    let requested_device = input
+        .device_id
+        .map(Device::try_from)
+        .transpose()
+        .map_err(|_| RouteError::InvalidDeviceId)?;
+
     let (session, user) = match (password_manager.is_enabled(), input.credentials) {
         (
             true,
@@ -227,18 +346,79 @@ pub(crate) async fn post(
                 password,
             },
         ) => {
-            user_password_login(
+            // Limit the number of attempts from a single IP address
+            if let Some(ip) = activity_tracker.ip() {
+                site_config
+                    .rate_limiter
+                    .check(
+                        &clock,
+                        &format!("login:ip:{ip}"),
+                        site_config.login_rate_limit,
+                    )
+                    .await
+                    .map_err(|e| RouteError::RateLimited {
+                        retry_after: e.retry_after(clock.now()),
+                    })?;
+            }
+
+            // Refuse the attempt without looking at the password if the account or
+            // the IP address is locked out after too many failures
+            if let Some(lockout) = &site_config.login_lockout {
+                if lockout
+                    .locked_until(&mut repo, &clock, &user, activity_tracker.ip())
+                    .await?
+                    .is_some()
+                {
+                    return Err(RouteError::LoginLocked);
+                }
+            }
+
+            let res = user_password_login(
                 &mut rng,
                 &clock,
                 &password_manager,
                 &mut repo,
-                user,
+                &mut policy,
+                &site_config,
+                user.clone(),
                 password,
+                device_name,
+                requested_device,
             )
-            .await?
+            .await;
+
+            match (res, &site_config.login_lockout) {
+                (Ok((session, user)), Some(lockout)) => {
+                    lockout.reset(&mut repo, &user).await?;
+                    (session, user)
+                }
+
+                (Ok(res), None) => res,
+
+                // Count the failure towards the lockout of the user and the IP
+                // address
+                (
+                    Err(
+                        e @ (RouteError::UserNotFound
+                        | RouteError::NoPassword
+                        | RouteError::PasswordVerificationFailed(_)),
+                    ),
+                    Some(lockout),
+                ) => {
+                    lockout
+                        .record_failure(&mut repo, &clock, &user, activity_tracker.ip())
+                        .await?;
+                    repo.save().await?;
+                    return Err(e);
+                }
+
+                (Err(e), _) => return Err(e),
+            }
         }
 
-        (_, Credentials::Token { token }) => token_login(&mut repo, &clock, &token).await?,
+        (_, Credentials::Token { token }) => {
+            token_login(&mut repo, &clock, &token, device_name).await?
+        }
 
         _ => {
             return Err(RouteError::Unsupported);
@@ -290,6 +470,7 @@ async fn token_login(
     repo: &mut BoxRepository,
     clock: &dyn Clock,
     token: &str,
+    device_name: Option<String>,
 ) -> Result<(CompatSession, User), RouteError> {
     let login = repo
         .compat_sso_login()
@@ -350,6 +531,23 @@ async fn token_login(
 
     repo.compat_sso_login().exchange(clock, login).await?;
 
+    // The device was provisioned when the SSO login completed, before we knew
+    // its name, so provision it again with the name
+    let session = if let Some(device_name) = device_name {
+        repo.job()
+            .schedule_job(
+                ProvisionDeviceJob::new(&user, &session.device)
+                    .set_display_name(device_name.clone()),
+            )
+            .await?;
+
+        repo.compat_session()
+            .set_human_name(session, Some(device_name))
+            .await?
+    } else {
+        session
+    };
+
     Ok((session, user))
 }
 
@@ -358,8 +556,12 @@ async fn user_password_login(
     clock: &impl Clock,
     password_manager: &PasswordManager,
     repo: &mut BoxRepository,
+    policy: &mut Policy,
+    site_config: &SiteConfig,
     username: String,
     password: String,
+    device_name: Option<String>,
+    requested_device: Option<Device>,
 ) -> Result<(CompatSession, User), RouteError> {
     // Find the user
     let user = repo
@@ -367,6 +569,7 @@ async fn user_password_login(
         .find_by_username(&username)
         .await?
         .filter(mas_data_model::User::is_valid)
+        .filter(mas_data_model::User::can_login_interactively)
         .ok_or(RouteError::UserNotFound)?;
 
     // Lookup its password
@@ -389,6 +592,25 @@ async fn user_password_login(
         .await
         .map_err(RouteError::PasswordVerificationFailed)?;
 
+    // The password may have leaked: the user has to choose a new one through the
+    // web interface before using it again
+    if user.password_reset_required() {
+        return Err(RouteError::PasswordResetRequired);
+    }
+
+    // Operators can progressively move users off password logins on the
+    // compatibility layer: the policy decides when they get blocked
+    let res = policy
+        .evaluate_compat_password_login(
+            clock.now(),
+            &user,
+            site_config.compat_password_deprecation_grace_period,
+        )
+        .await?;
+    if !res.valid() {
+        return Err(RouteError::PasswordLoginDeprecated);
+    }
+
     if let Some((version, hashed_password)) = new_password_hash {
         // Save the upgraded password if needed
         repo.user_password()
@@ -404,27 +626,76 @@ async fn user_password_login(
     }
 
     // Now that the user credentials have been verified, start a new compat session
-    let device = Device::generate(&mut rng);
-    repo.job()
-        .schedule_job(ProvisionDeviceJob::new(&user, &device))
+    // Clients can ask to reuse a device ID, which may still be in use by
+    // another session
+    let (device, replaces_existing) = if let Some(device) = requested_device {
+        let claim = claim_device(
+            &mut rng,
+            clock,
+            repo,
+            site_config.device_conflict_policy,
+            &user,
+            device,
+        )
         .await?;
+        (claim.device, claim.replaces_existing)
+    } else {
+        (Device::generate(&mut rng), false)
+    };
 
-    let session = repo
+    let mut job = ProvisionDeviceJob::new(&user, &device);
+    if let Some(device_name) = &device_name {
+        job = job.set_display_name(device_name.clone());
+    }
+    if replaces_existing {
+        job = job.replace_existing();
+    }
+    repo.job().schedule_job(job).await?;
+
+    let mut session = repo
         .compat_session()
         .add(&mut rng, clock, &user, device, false)
         .await?;
 
+    if device_name.is_some() {
+        session = repo
+            .compat_session()
+            .set_human_name(session, device_name)
+            .await?;
+    }
+
+    // Let the other sessions of the user know about this sign-in
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_compat_session(&session))
+        .await?;
+
     Ok((session, user))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::num::NonZeroU32;
+
     use hyper::Request;
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{
+        app_session::{AppSessionFilter, AppSessionRepository},
+        upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
+    };
+    use oauth2_types::scope::OPENID;
     use rand::distributions::{Alphanumeric, DistString};
     use sqlx::PgPool;
+    use ulid::Ulid;
 
     use super::*;
-    use crate::test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState};
+    use crate::{
+        test_utils::{capture_logs, init_tracing, RequestBuilderExt, ResponseExt, TestState},
+        DeviceConflictPolicy, DeviceNameTemplate, LoginLockout,
+    };
 
     /// Test that the server advertises the right login flows.
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
@@ -457,6 +728,89 @@ mod tests {
         );
     }
 
+    /// Test that the upstream providers are advertised in the SSO flow, and
+    /// that clients can send users directly to one of them
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_identity_providers(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let mut rng = state.rng();
+
+        let mut repo = state.repository().await.unwrap();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &state.clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://example.com/".to_owned(),
+                    human_name: Some("Example Ltd.".to_owned()),
+                    brand_name: Some("google".to_owned()),
+                    icon: Some("mxc://example.com/icon".to_owned()),
+                    scope: [OPENID].into_iter().collect(),
+                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    token_endpoint_signing_alg: None,
+                    client_id: "client".to_owned(),
+                    encrypted_client_secret: None,
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    authorization_endpoint_override: None,
+                    token_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+                    pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+                },
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/_matrix/client/v3/login").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+
+        assert_eq!(
+            body["flows"][1],
+            serde_json::json!({
+                "type": "m.login.sso",
+                "identity_providers": [
+                    {
+                        "id": provider.id.to_string(),
+                        "name": "Example Ltd.",
+                        "icon": "mxc://example.com/icon",
+                        "brand": "google",
+                    }
+                ],
+                "org.matrix.msc3824.delegated_oidc_compatibility": true,
+            })
+        );
+
+        // Picking the provider sends the user to it, to then complete the login
+        let request = Request::get(format!(
+            "/_matrix/client/v3/login/sso/redirect/{}?redirectUrl=https://client.example.com/",
+            provider.id
+        ))
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = response.location();
+        let prefix = format!("https://example.com/upstream/authorize/{}?", provider.id);
+        assert!(location.starts_with(&prefix), "{location}");
+        assert!(
+            location.contains("kind=continue_compat_sso_login"),
+            "{location}"
+        );
+
+        // Unknown providers are rejected
+        let request = Request::get(format!(
+            "/_matrix/client/v3/login/sso/redirect/{}?redirectUrl=https://client.example.com/",
+            Ulid::nil()
+        ))
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
     /// Test that the server doesn't allow login with a password if the password
     /// manager is disabled
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
@@ -621,6 +975,267 @@ mod tests {
         assert_eq!(body, old_body);
     }
 
+    /// Test that logging in and refreshing the session doesn't log the
+    /// password nor the tokens, even at the most verbose level
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_does_not_log_credentials(pool: PgPool) {
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("correct horse battery staple".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let (_guard, logs) = capture_logs();
+
+        let request = Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+            "type": "m.login.password",
+            "identifier": {
+                "type": "m.id.user",
+                "user": "alice",
+            },
+            "password": "correct horse battery staple",
+            "refresh_token": true,
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+        let refresh_token = body.refresh_token.unwrap();
+
+        let request = Request::post("/_matrix/client/v3/refresh").json(serde_json::json!({
+            "refresh_token": refresh_token,
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let refreshed: serde_json::Value = response.json();
+
+        let logs = logs.contents();
+        // Make sure the capture actually worked
+        assert!(logs.contains("handlers.compat.login.post"));
+        assert!(!logs.contains("correct horse battery staple"));
+        assert!(!logs.contains(&body.access_token));
+        assert!(!logs.contains(&refresh_token));
+        assert!(!logs.contains(refreshed["access_token"].as_str().unwrap()));
+        assert!(!logs.contains(refreshed["refresh_token"].as_str().unwrap()));
+    }
+
+    /// Test that too many failed attempts temporarily lock out the user
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_lockout(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.login_lockout = Some(LoginLockout::new(
+            NonZeroU32::new(2).unwrap(),
+            NonZeroU32::new(100).unwrap(),
+            Duration::minutes(1),
+            Duration::hours(1),
+        ));
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let login = |password: &str| {
+            Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+                "type": "m.login.password",
+                "identifier": {
+                    "type": "m.id.user",
+                    "user": "alice",
+                },
+                "password": password,
+            }))
+        };
+
+        // Two failures lock the user out
+        for _ in 0..2 {
+            let response = state.request(login("wrongpassword")).await;
+            response.assert_status(StatusCode::FORBIDDEN);
+        }
+
+        // Even the right password is refused during the lockout
+        let response = state.request(login("password")).await;
+        response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_LIMIT_EXCEEDED");
+
+        // Once the lockout is over, the user can log in again
+        state.clock.advance(Duration::minutes(2));
+        let response = state.request(login("password")).await;
+        response.assert_status(StatusCode::OK);
+
+        // Which resets the counter: a single failure doesn't lock the user out
+        let response = state.request(login("wrongpassword")).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let response = state.request(login("password")).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    /// Test that service accounts can't login with a password, even if they
+    /// have one.
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_service_account_password_login(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "bot".to_owned())
+            .await
+            .unwrap();
+        let user = repo.user().set_service_account(user, true).await.unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let request = Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+            "type": "m.login.password",
+            "identifier": {
+                "type": "m.id.user",
+                "user": "bot",
+            },
+            "password": "password",
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_UNAUTHORIZED");
+    }
+
+    /// Test that users whose password login is deprecated can still use it
+    /// during the grace period, but not after.
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_deprecated_password_login(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.compat_password_deprecation_grace_period = Duration::days(1);
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.user()
+            .deprecate_compat_password(&state.clock, user)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let login = || {
+            Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+                "type": "m.login.password",
+                "identifier": {
+                    "type": "m.id.user",
+                    "user": "alice",
+                },
+                "password": "password",
+            }))
+        };
+
+        // The user was only warned, they can still log in
+        let response = state.request(login()).await;
+        response.assert_status(StatusCode::OK);
+
+        // Once the grace period is over, the login is refused
+        state.clock.advance(Duration::days(1));
+        let response = state.request(login()).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_FORBIDDEN");
+    }
+
     /// Test the response of an unsupported login flow.
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
     async fn test_unsupported_login(pool: PgPool) {
@@ -708,6 +1323,224 @@ mod tests {
         assert_eq!(body["errcode"], "M_UNAUTHORIZED");
     }
 
+    /// Test that compat devices are named after the
+    /// `initial_device_display_name`, or from the user agent with the
+    /// configured template
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_device_name(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.compat_device_name_template = Some(
+            DeviceNameTemplate::new("{{ client }}{% if platform %} on {{ platform }}{% endif %}")
+                .unwrap(),
+        );
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let user_agent = "Element/1.11.6 (iPhone; iOS 17.1.2; Scale/3.00)";
+
+        // The name supplied by the client takes precedence
+        let request = Request::post("/_matrix/client/v3/login")
+            .header("User-Agent", user_agent)
+            .json(serde_json::json!({
+                "type": "m.login.password",
+                "identifier": {
+                    "type": "m.id.user",
+                    "user": "alice",
+                },
+                "password": "password",
+                "initial_device_display_name": "Alice's phone",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .compat_session()
+            .find_by_device(&user, &body.device_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.human_name.as_deref(), Some("Alice's phone"));
+        repo.cancel().await.unwrap();
+
+        // Without one, the name is derived from the user agent
+        let request = Request::post("/_matrix/client/v3/login")
+            .header("User-Agent", user_agent)
+            .json(serde_json::json!({
+                "type": "m.login.password",
+                "identifier": {
+                    "type": "m.id.user",
+                    "user": "alice",
+                },
+                "password": "password",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .compat_session()
+            .find_by_device(&user, &body.device_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.human_name.as_deref(), Some("Element on iOS"));
+        repo.cancel().await.unwrap();
+
+        // Sessions created through the SSO login get their name when the
+        // token is exchanged
+        let (device, token) = get_login_token(&state, &user).await;
+        let request = Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+            "type": "m.login.token",
+            "token": token,
+            "initial_device_display_name": "Alice's laptop",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .compat_session()
+            .find_by_device(&user, &device)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.human_name.as_deref(), Some("Alice's laptop"));
+        repo.cancel().await.unwrap();
+    }
+
+    /// Test what happens when a client logs in with a device ID which is
+    /// already in use, with the different policies
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_device_id_conflict(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(
+                &mut state.rng(),
+                Zeroizing::new("password".to_owned().into_bytes()),
+            )
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let login = |device_id: &str| {
+            Request::post("/_matrix/client/v3/login").json(serde_json::json!({
+                "type": "m.login.password",
+                "identifier": {
+                    "type": "m.id.user",
+                    "user": "alice",
+                },
+                "password": "password",
+                "device_id": device_id,
+            }))
+        };
+
+        let device = Device::try_from("ABCDEF".to_owned()).unwrap();
+        let active_sessions = AppSessionFilter::new()
+            .for_user(&user)
+            .for_device(&device)
+            .active_only();
+
+        // Invalid device IDs are rejected
+        let response = state.request(login("not a device")).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_INVALID_PARAM");
+
+        // The first login gets the device it asked for
+        let response = state.request(login("ABCDEF")).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+        assert_eq!(body.device_id, device);
+
+        // By default, logging in again with the same device replaces the
+        // previous session
+        let response = state.request(login("ABCDEF")).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+        assert_eq!(body.device_id, device);
+
+        let mut repo = state.repository().await.unwrap();
+        assert_eq!(repo.app_session().count(active_sessions).await.unwrap(), 1);
+        repo.cancel().await.unwrap();
+
+        // The new session can be rejected instead
+        state.site_config.device_conflict_policy = DeviceConflictPolicy::Reject;
+        let response = state.request(login("ABCDEF")).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["errcode"], "M_FORBIDDEN");
+
+        // Or get a different device
+        state.site_config.device_conflict_policy = DeviceConflictPolicy::Suffix;
+        let response = state.request(login("ABCDEF")).await;
+        response.assert_status(StatusCode::OK);
+        let body: ResponseBody = response.json();
+        assert!(body.device_id.as_str().starts_with("ABCDEF-"));
+        assert_eq!(body.device_id.as_str().len(), 11);
+
+        // In both cases, the original session is left untouched
+        let mut repo = state.repository().await.unwrap();
+        assert_eq!(repo.app_session().count(active_sessions).await.unwrap(), 1);
+        repo.cancel().await.unwrap();
+    }
+
     /// Get a login token for a user.
     /// Returns the device and the token.
     ///
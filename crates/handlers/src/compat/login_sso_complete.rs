@@ -31,13 +31,14 @@ use mas_router::{CompatLoginSsoAction, PostAuthAction, UrlBuilder};
 use mas_storage::{
     compat::{CompatSessionRepository, CompatSsoLoginRepository},
     job::{JobRepositoryExt, ProvisionDeviceJob},
+    user::UserTermsRepository,
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use mas_templates::{CompatSsoContext, ErrorContext, TemplateContext, Templates};
 use serde::{Deserialize, Serialize};
 use ulid::Ulid;
 
-use crate::PreferredLanguage;
+use crate::{PreferredLanguage, SiteConfig};
 
 #[derive(Serialize)]
 struct AllParams<'s> {
@@ -66,6 +67,7 @@ pub async fn get(
     mut repo: BoxRepository,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     cookie_jar: CookieJar,
     Path(id): Path<Ulid>,
     Query(params): Query<Params>,
@@ -96,6 +98,19 @@ pub async fn get(
         return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
     }
 
+    // The user has to accept the current terms of service first
+    if let Some(terms) = &site_config.terms {
+        let acceptance = repo
+            .user_terms()
+            .find(&session.user, &terms.version)
+            .await?;
+        if !terms.is_accepted(acceptance.as_ref()) {
+            let destination =
+                mas_router::AcceptTerms::and_then(PostAuthAction::continue_compat_sso_login(id));
+            return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
+        }
+    }
+
     let login = repo
         .compat_sso_login()
         .lookup(id)
@@ -136,6 +151,7 @@ pub async fn post(
     PreferredLanguage(locale): PreferredLanguage,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     cookie_jar: CookieJar,
     Path(id): Path<Ulid>,
     Query(params): Query<Params>,
@@ -167,6 +183,19 @@ pub async fn post(
         return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
     }
 
+    // The user has to accept the current terms of service first
+    if let Some(terms) = &site_config.terms {
+        let acceptance = repo
+            .user_terms()
+            .find(&session.user, &terms.version)
+            .await?;
+        if !terms.is_accepted(acceptance.as_ref()) {
+            let destination =
+                mas_router::AcceptTerms::and_then(PostAuthAction::continue_compat_sso_login(id));
+            return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
+        }
+    }
+
     let login = repo
         .compat_sso_login()
         .lookup(id)
@@ -14,17 +14,25 @@
 // limitations under the License.
 
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
 };
 use hyper::StatusCode;
 use mas_axum_utils::sentry::SentryEventID;
-use mas_router::{CompatLoginSsoAction, CompatLoginSsoComplete, UrlBuilder};
-use mas_storage::{compat::CompatSsoLoginRepository, BoxClock, BoxRepository, BoxRng};
+use mas_data_model::CompatSsoLogin;
+use mas_router::{
+    CompatLoginSsoAction, CompatLoginSsoComplete, PostAuthAction, UpstreamOAuth2Authorize,
+    UrlBuilder,
+};
+use mas_storage::{
+    compat::CompatSsoLoginRepository, upstream_oauth2::UpstreamOAuthProviderRepository, BoxClock,
+    BoxRepository, BoxRng,
+};
 use rand::distributions::{Alphanumeric, DistString};
 use serde::Deserialize;
 use serde_with::serde;
 use thiserror::Error;
+use ulid::Ulid;
 use url::Url;
 
 use crate::impl_from_error_for_route;
@@ -46,6 +54,9 @@ pub enum RouteError {
 
     #[error("invalid redirect_url")]
     InvalidRedirectUrl,
+
+    #[error("unknown identity provider")]
+    UnknownIdentityProvider,
 }
 
 impl_from_error_for_route!(mas_storage::RepositoryError);
@@ -53,25 +64,23 @@ impl_from_error_for_route!(mas_storage::RepositoryError);
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         let event_id = sentry::capture_error(&self);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            SentryEventID::from(event_id),
-            format!("{self}"),
-        )
-            .into_response()
+        let status = match self {
+            Self::UnknownIdentityProvider => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, SentryEventID::from(event_id), format!("{self}")).into_response()
     }
 }
 
-#[tracing::instrument(name = "handlers.compat.login_sso_redirect.get", skip_all, err)]
-pub async fn get(
-    mut rng: BoxRng,
-    clock: BoxClock,
-    mut repo: BoxRepository,
-    State(url_builder): State<UrlBuilder>,
-    Query(params): Query<Params>,
-) -> Result<impl IntoResponse, RouteError> {
+/// Validate the `redirectUrl` parameter and start a compat SSO login with it
+async fn start_login(
+    rng: &mut BoxRng,
+    clock: &BoxClock,
+    repo: &mut BoxRepository,
+    redirect_url: Option<String>,
+) -> Result<CompatSsoLogin, RouteError> {
     // Check the redirectUrl parameter
-    let redirect_url = params.redirect_url.ok_or(RouteError::MissingRedirectUrl)?;
+    let redirect_url = redirect_url.ok_or(RouteError::MissingRedirectUrl)?;
     let redirect_url = Url::parse(&redirect_url).map_err(|_| RouteError::InvalidRedirectUrl)?;
 
     // Do not allow URLs with username or passwords in them
@@ -84,13 +93,64 @@ pub async fn get(
         return Err(RouteError::InvalidRedirectUrl);
     }
 
-    let token = Alphanumeric.sample_string(&mut rng, 32);
+    let token = Alphanumeric.sample_string(rng, 32);
     let login = repo
         .compat_sso_login()
-        .add(&mut rng, &clock, token, redirect_url)
+        .add(rng, clock, token, redirect_url)
         .await?;
 
+    Ok(login)
+}
+
+#[tracing::instrument(name = "handlers.compat.login_sso_redirect.get", skip_all, err)]
+pub async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(url_builder): State<UrlBuilder>,
+    Query(params): Query<Params>,
+) -> Result<impl IntoResponse, RouteError> {
+    let login = start_login(&mut rng, &clock, &mut repo, params.redirect_url).await?;
+
     repo.save().await?;
 
     Ok(url_builder.absolute_redirect(&CompatLoginSsoComplete::new(login.id, params.action)))
 }
+
+/// Start a compat SSO login, sending the user directly to the upstream
+/// provider they picked from the `identity_providers` advertised in the
+/// `m.login.sso` flow
+#[tracing::instrument(
+    name = "handlers.compat.login_sso_redirect.get_idp",
+    skip_all,
+    fields(upstream_oauth_provider.id = idp),
+    err
+)]
+pub async fn get_idp(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(url_builder): State<UrlBuilder>,
+    Path((_version, idp)): Path<(String, String)>,
+    Query(params): Query<Params>,
+) -> Result<impl IntoResponse, RouteError> {
+    let provider_id: Ulid = idp
+        .parse()
+        .map_err(|_| RouteError::UnknownIdentityProvider)?;
+    let provider = repo
+        .upstream_oauth_provider()
+        .lookup(provider_id)
+        .await?
+        .filter(|_| cfg!(feature = "upstream-oauth2"))
+        .ok_or(RouteError::UnknownIdentityProvider)?;
+
+    let login = start_login(&mut rng, &clock, &mut repo, params.redirect_url).await?;
+
+    repo.save().await?;
+
+    // Once the user is back from the provider, they will be asked to confirm
+    // the login on the usual completion page
+    let destination = UpstreamOAuth2Authorize::new(provider.id)
+        .and_then(PostAuthAction::continue_compat_sso_login(login.id));
+    Ok(url_builder.absolute_redirect(&destination))
+}
@@ -12,14 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "compat")]
 use axum::{response::IntoResponse, Json};
+#[cfg(feature = "compat")]
 use hyper::StatusCode;
+#[cfg(feature = "compat")]
 use serde::Serialize;
 
+pub(crate) mod device_name;
+#[cfg(feature = "compat")]
 pub(crate) mod login;
+#[cfg(feature = "compat")]
 pub(crate) mod login_sso_complete;
+#[cfg(feature = "compat")]
 pub(crate) mod login_sso_redirect;
+#[cfg(feature = "compat")]
 pub(crate) mod logout;
+#[cfg(feature = "compat")]
 pub(crate) mod refresh;
 
 #[derive(Debug, Clone)]
@@ -38,14 +47,16 @@ impl std::fmt::Display for MatrixHomeserver {
     }
 }
 
+#[cfg(feature = "compat")]
 #[derive(Debug, Serialize)]
-struct MatrixError {
-    errcode: &'static str,
-    error: &'static str,
+pub(crate) struct MatrixError {
+    pub errcode: &'static str,
+    pub error: &'static str,
     #[serde(skip)]
-    status: StatusCode,
+    pub status: StatusCode,
 }
 
+#[cfg(feature = "compat")]
 impl IntoResponse for MatrixError {
     fn into_response(self) -> axum::response::Response {
         (self.status, Json(self)).into_response()
@@ -19,6 +19,7 @@ use mas_axum_utils::sentry::SentryEventID;
 use mas_data_model::{TokenFormatError, TokenType};
 use mas_storage::{
     compat::{CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository},
+    job::{DeleteDeviceJob, JobRepositoryExt},
     BoxClock, BoxRepository, BoxRng, Clock,
 };
 use serde::{Deserialize, Serialize};
@@ -44,6 +45,9 @@ pub enum RouteError {
     #[error("refresh token already consumed")]
     RefreshTokenConsumed,
 
+    #[error("refresh token expired")]
+    RefreshTokenExpired,
+
     #[error("invalid session")]
     InvalidSession,
 
@@ -60,7 +64,10 @@ impl IntoResponse for RouteError {
                 error: "Internal error",
                 status: StatusCode::INTERNAL_SERVER_ERROR,
             },
-            Self::InvalidToken | Self::InvalidSession | Self::RefreshTokenConsumed => MatrixError {
+            Self::InvalidToken
+            | Self::InvalidSession
+            | Self::RefreshTokenConsumed
+            | Self::RefreshTokenExpired => MatrixError {
                 errcode: "M_UNKNOWN_TOKEN",
                 error: "Invalid refresh token",
                 status: StatusCode::UNAUTHORIZED,
@@ -123,6 +130,35 @@ pub(crate) async fn post(
         return Err(RouteError::InvalidSession);
     }
 
+    // Refreshing counts as activity, so the refresh token itself is the latest
+    // sign of activity we might know about
+    let last_active_at = session
+        .last_active_at
+        .unwrap_or(session.created_at)
+        .max(refresh_token.created_at);
+    if site_config
+        .refresh_token_policies
+        .default
+        .lifetimes
+        .is_expired(clock.now(), session.created_at, last_active_at)
+    {
+        // End the session and delete the device, so that the user has to log in
+        // again
+        let user = repo
+            .user()
+            .lookup(session.user_id)
+            .await?
+            .ok_or(RouteError::UnknownSession)?;
+
+        repo.job()
+            .schedule_job(DeleteDeviceJob::new(&user, &session.device))
+            .await?;
+        repo.compat_session().finish(&clock, session).await?;
+        repo.save().await?;
+
+        return Err(RouteError::RefreshTokenExpired);
+    }
+
     activity_tracker
         .record_compat_session(&clock, &session)
         .await;
@@ -0,0 +1,222 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Handling of new sessions asking for a device ID which is already in use
+
+use mas_data_model::{Device, User};
+use mas_storage::{
+    app_session::{AppSession, AppSessionFilter, AppSessionRepository},
+    compat::CompatSessionRepository,
+    oauth2::{schedule_backchannel_logout, OAuth2SessionRepository},
+    BoxRepository, Clock, Pagination, RepositoryAccess,
+};
+use oauth2_types::scope::Scope;
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    RngCore,
+};
+use thiserror::Error;
+
+use crate::impl_from_error_for_route;
+
+/// Length of the random suffix added to device IDs with the
+/// [`DeviceConflictPolicy::Suffix`] policy
+const SUFFIX_LENGTH: usize = 4;
+
+/// What to do when a new session asks for a device ID which is already used by
+/// an active session of the same user
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DeviceConflictPolicy {
+    /// End the existing sessions and replace the device on the homeserver
+    #[default]
+    Replace,
+
+    /// Refuse to start the new session
+    Reject,
+
+    /// Give the new session a different device ID, derived from the requested
+    /// one
+    Suffix,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum DeviceConflictError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("device {0} is already in use")]
+    InUse(Device),
+}
+
+impl_from_error_for_route!(DeviceConflictError: mas_storage::RepositoryError);
+
+/// The device a new session can use
+#[derive(Debug)]
+pub(crate) struct DeviceClaim {
+    /// The device to use, which might be different from the requested one
+    pub device: Device,
+
+    /// Whether the device was used by sessions which were ended, in which case
+    /// it has to be replaced on the homeserver
+    pub replaces_existing: bool,
+}
+
+/// Claim a device for a new session of the given user, applying the given
+/// policy if it is already used by an active session
+///
+/// With the [`DeviceConflictPolicy::Replace`] policy, the sessions using the
+/// device are ended, and the caller is responsible for replacing the device on
+/// the homeserver.
+pub(crate) async fn claim_device(
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    repo: &mut BoxRepository,
+    policy: DeviceConflictPolicy,
+    user: &User,
+    device: Device,
+) -> Result<DeviceClaim, DeviceConflictError> {
+    if !is_in_use(repo, user, &device).await? {
+        return Ok(DeviceClaim {
+            device,
+            replaces_existing: false,
+        });
+    }
+
+    match policy {
+        DeviceConflictPolicy::Reject => Err(DeviceConflictError::InUse(device)),
+
+        DeviceConflictPolicy::Suffix => loop {
+            let suffix = Alphanumeric.sample_string(rng, SUFFIX_LENGTH);
+            let Ok(candidate) = Device::try_from(format!("{device}-{suffix}")) else {
+                unreachable!()
+            };
+
+            if !is_in_use(repo, user, &candidate).await? {
+                tracing::info!(
+                    requested = %device,
+                    device = %candidate,
+                    "Requested device is in use, using a suffixed one instead"
+                );
+
+                return Ok(DeviceClaim {
+                    device: candidate,
+                    replaces_existing: false,
+                });
+            }
+        },
+
+        DeviceConflictPolicy::Replace => {
+            end_sessions_using_device(repo, clock, user, &device).await?;
+
+            Ok(DeviceClaim {
+                device,
+                replaces_existing: true,
+            })
+        }
+    }
+}
+
+/// Claim all the devices requested in an OAuth 2.0 scope, returning the scope
+/// the session should be started with, and the devices which need to be
+/// replaced on the homeserver
+pub(crate) async fn claim_devices_in_scope(
+    rng: &mut (dyn RngCore + Send),
+    clock: &dyn Clock,
+    repo: &mut BoxRepository,
+    policy: DeviceConflictPolicy,
+    user: &User,
+    scope: &Scope,
+) -> Result<(Scope, Vec<Device>), DeviceConflictError> {
+    let mut tokens = Vec::new();
+    let mut replaced = Vec::new();
+
+    for token in scope.iter() {
+        let Some(device) = Device::from_scope_token(token) else {
+            tokens.push(token.clone());
+            continue;
+        };
+
+        let claim = claim_device(rng, clock, repo, policy, user, device).await?;
+        tokens.push(claim.device.to_scope_token());
+        if claim.replaces_existing {
+            replaced.push(claim.device);
+        }
+    }
+
+    Ok((tokens.into_iter().collect(), replaced))
+}
+
+/// Check whether the device is used by an active session of the user
+async fn is_in_use(
+    repo: &mut BoxRepository,
+    user: &User,
+    device: &Device,
+) -> Result<bool, mas_storage::RepositoryError> {
+    let filter = AppSessionFilter::new()
+        .for_user(user)
+        .for_device(device)
+        .active_only();
+
+    Ok(repo.app_session().count(filter).await? > 0)
+}
+
+/// End all the active sessions of the user using this device
+async fn end_sessions_using_device(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    user: &User,
+    device: &Device,
+) -> Result<(), mas_storage::RepositoryError> {
+    let filter = AppSessionFilter::new()
+        .for_user(user)
+        .for_device(device)
+        .active_only();
+
+    loop {
+        let page = repo
+            .app_session()
+            .list(filter, Pagination::first(100))
+            .await?;
+
+        for session in page.edges {
+            match session {
+                AppSession::Compat(compat_session) => {
+                    tracing::info!(
+                        %compat_session.id,
+                        %device,
+                        "Ending compat session using the replaced device"
+                    );
+                    repo.compat_session().finish(clock, *compat_session).await?;
+                }
+                AppSession::OAuth2(oauth2_session) => {
+                    tracing::info!(
+                        %oauth2_session.id,
+                        %device,
+                        "Ending OAuth 2.0 session using the replaced device"
+                    );
+                    schedule_backchannel_logout(repo, &oauth2_session).await?;
+                    repo.oauth2_session().finish(clock, *oauth2_session).await?;
+                }
+            }
+        }
+
+        // Finished sessions don't match the filter anymore, so we can fetch the
+        // first page again until there is nothing left
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(())
+}
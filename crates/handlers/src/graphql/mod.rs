@@ -17,21 +17,27 @@ use std::sync::Arc;
 use async_graphql::{
     extensions::{ApolloTracing, Tracing},
     http::{playground_source, GraphQLPlaygroundConfig, MultipartOptions},
+    parser::types::OperationType,
 };
 use axum::{
     async_trait,
     extract::{BodyStream, RawQuery, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{
+        sse::{Event, KeepAlive},
+        Html, IntoResponse, Response, Sse,
+    },
     Json, TypedHeader,
 };
-use futures_util::TryStreamExt;
+use futures_util::{StreamExt, TryStreamExt};
 use headers::{authorization::Bearer, Authorization, ContentType, HeaderValue};
-use hyper::header::CACHE_CONTROL;
+use hyper::header::{CACHE_CONTROL, RETRY_AFTER};
 use mas_axum_utils::{
     cookies::CookieJar, sentry::SentryEventID, FancyError, SessionInfo, SessionInfoExt,
 };
-use mas_data_model::User;
+use mas_data_model::{
+    EmailNormalization, RefreshTokenPolicies, SessionEvent, TermsOfService, User,
+};
 use mas_graphql::{Requester, Schema};
 use mas_matrix::HomeserverConnection;
 use mas_policy::{InstantiateError, Policy, PolicyFactory};
@@ -42,17 +48,58 @@ use mas_storage_pg::PgRepository;
 use rand::{thread_rng, SeedableRng};
 use rand_chacha::ChaChaRng;
 use sqlx::PgPool;
+use tokio::sync::broadcast;
 use tracing::{info_span, Instrument};
 
-use crate::{impl_from_error_for_route, BoundActivityTracker};
+use crate::{
+    impl_from_error_for_route, rate_limit::EmailThrottle, AvatarStore, BoundActivityTracker,
+    BoxHomeserverConnection, SiteConfig,
+};
 
+mod session_events;
 #[cfg(test)]
 mod tests;
 
+pub use self::session_events::SessionEvents;
+
+/// Limits on the size of the GraphQL queries, to prevent deeply nested or
+/// expensive queries from overloading the database
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Maximum depth of a query
+    pub max_depth: usize,
+
+    /// Maximum complexity of a query
+    pub max_complexity: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 16,
+            max_complexity: 2000,
+        }
+    }
+}
+
+impl Limits {
+    pub(crate) fn apply(self, builder: mas_graphql::SchemaBuilder) -> mas_graphql::SchemaBuilder {
+        builder
+            .limit_depth(self.max_depth)
+            .limit_complexity(self.max_complexity)
+    }
+}
+
 struct GraphQLState {
     pool: PgPool,
-    homeserver_connection: Arc<dyn HomeserverConnection<Error = anyhow::Error>>,
+    homeserver_connection: BoxHomeserverConnection,
     policy_factory: Arc<PolicyFactory>,
+    refresh_token_policies: RefreshTokenPolicies,
+    avatar_store: Option<AvatarStore>,
+    email_normalization: EmailNormalization,
+    email_throttle: EmailThrottle,
+    terms: Option<TermsOfService>,
+    session_events: SessionEvents,
 }
 
 #[async_trait]
@@ -85,22 +132,63 @@ impl mas_graphql::State for GraphQLState {
         let rng = ChaChaRng::from_rng(rng).expect("Failed to seed rng");
         Box::new(rng)
     }
+
+    fn refresh_token_policies(&self) -> &RefreshTokenPolicies {
+        &self.refresh_token_policies
+    }
+
+    fn avatar_store(&self) -> Option<&dyn mas_graphql::AvatarStore> {
+        self.avatar_store
+            .as_ref()
+            .map(|store| store as &dyn mas_graphql::AvatarStore)
+    }
+
+    fn email_normalization(&self) -> EmailNormalization {
+        self.email_normalization
+    }
+
+    fn email_throttle(&self) -> &dyn mas_graphql::EmailThrottle {
+        &self.email_throttle
+    }
+
+    fn terms(&self) -> Option<&TermsOfService> {
+        self.terms.as_ref()
+    }
+
+    fn session_events(&self) -> broadcast::Receiver<SessionEvent> {
+        self.session_events.subscribe()
+    }
 }
 
 #[must_use]
+#[allow(clippy::too_many_arguments)]
 pub fn schema(
     pool: &PgPool,
     policy_factory: &Arc<PolicyFactory>,
     homeserver_connection: impl HomeserverConnection<Error = anyhow::Error> + 'static,
+    refresh_token_policies: RefreshTokenPolicies,
+    avatar_store: Option<AvatarStore>,
+    email_normalization: EmailNormalization,
+    email_throttle: EmailThrottle,
+    terms: Option<TermsOfService>,
+    session_events: SessionEvents,
+    limits: Limits,
 ) -> Schema {
     let state = GraphQLState {
         pool: pool.clone(),
         policy_factory: Arc::clone(policy_factory),
         homeserver_connection: Arc::new(homeserver_connection),
+        refresh_token_policies,
+        avatar_store,
+        email_normalization,
+        email_throttle,
+        terms,
+        session_events,
     };
     let state: mas_graphql::BoxState = Box::new(state);
 
-    mas_graphql::schema_builder()
+    limits
+        .apply(mas_graphql::schema_builder())
         .extension(Tracing)
         .extension(ApolloTracing)
         .data(state)
@@ -140,6 +228,12 @@ pub enum RouteError {
 
     #[error(transparent)]
     ParseRequest(#[from] async_graphql::ParseRequestError),
+
+    #[error("The service is in maintenance mode")]
+    Maintenance { retry_after: chrono::Duration },
+
+    #[error("Rate limit exceeded")]
+    RateLimited { retry_after: u64 },
 }
 
 impl_from_error_for_route!(mas_storage::RepositoryError);
@@ -184,6 +278,26 @@ impl IntoResponse for RouteError {
                 )
                     .into_response()
             }
+
+            Self::Maintenance { retry_after } => {
+                let error = async_graphql::Error::new("The service is in maintenance mode");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(RETRY_AFTER, retry_after.num_seconds().to_string())],
+                    Json(serde_json::json!({"errors": [error]})),
+                )
+                    .into_response()
+            }
+
+            Self::RateLimited { retry_after } => {
+                let error = async_graphql::Error::new("Too many requests");
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(RETRY_AFTER, retry_after.to_string())],
+                    Json(serde_json::json!({"errors": [error]})),
+                )
+                    .into_response()
+            }
         };
 
         (SentryEventID::from(event_id), response).into_response()
@@ -253,8 +367,63 @@ async fn get_requester(
     Ok(requester)
 }
 
+/// Returns `true` if the request contains a mutation operation
+fn has_mutation(request: &mut async_graphql::Request) -> bool {
+    request.parsed_query().map_or(false, |document| {
+        document
+            .operations
+            .iter()
+            .any(|(_name, operation)| operation.node.ty == OperationType::Mutation)
+    })
+}
+
+/// Reject the request if it contains mutations while in maintenance mode
+fn check_maintenance(
+    site_config: &SiteConfig,
+    request: &mut async_graphql::Request,
+) -> Result<(), RouteError> {
+    if site_config.maintenance.is_enabled() && has_mutation(request) {
+        return Err(RouteError::Maintenance {
+            retry_after: site_config.maintenance.retry_after(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Take a token from the GraphQL request budget of the requester. Requests
+/// made on behalf of a user share the same budget, whichever session they
+/// come from, and anonymous requests are limited per IP address.
+async fn check_rate_limit(
+    site_config: &SiteConfig,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    requester: &Requester,
+) -> Result<(), RouteError> {
+    let key = match requester {
+        Requester::BrowserSession(session) => format!("graphql:user:{}", session.user.id),
+        Requester::OAuth2Session(_, Some(user)) => format!("graphql:user:{}", user.id),
+        Requester::OAuth2Session(session, None) => {
+            format!("graphql:client:{}", session.client_id)
+        }
+        Requester::Anonymous => match activity_tracker.ip() {
+            Some(ip) => format!("graphql:ip:{ip}"),
+            None => return Ok(()),
+        },
+    };
+
+    site_config
+        .rate_limiter
+        .check(clock, &key, site_config.graphql_rate_limit)
+        .await
+        .map_err(|e| RouteError::RateLimited {
+            retry_after: e.retry_after(clock.now()),
+        })
+}
+
 pub async fn post(
     State(schema): State<Schema>,
+    State(site_config): State<SiteConfig>,
     clock: BoxClock,
     repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
@@ -268,10 +437,11 @@ pub async fn post(
         .map(|TypedHeader(Authorization(bearer))| bearer.token());
     let (session_info, _cookie_jar) = cookie_jar.session_info();
     let requester = get_requester(&clock, &activity_tracker, repo, session_info, token).await?;
+    check_rate_limit(&site_config, &clock, &activity_tracker, &requester).await?;
 
     let content_type = content_type.map(|TypedHeader(h)| h.to_string());
 
-    let request = async_graphql::http::receive_body(
+    let mut request = async_graphql::http::receive_body(
         content_type,
         body.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
             .into_async_read(),
@@ -280,6 +450,8 @@ pub async fn post(
     .await?
     .data(requester); // XXX: this should probably return another error response?
 
+    check_maintenance(&site_config, &mut request)?;
+
     let span = span_for_graphql_request(&request);
     let response = schema.execute(request).instrument(span).await;
 
@@ -296,6 +468,7 @@ pub async fn post(
 
 pub async fn get(
     State(schema): State<Schema>,
+    State(site_config): State<SiteConfig>,
     clock: BoxClock,
     repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
@@ -308,10 +481,17 @@ pub async fn get(
         .map(|TypedHeader(Authorization(bearer))| bearer.token());
     let (session_info, _cookie_jar) = cookie_jar.session_info();
     let requester = get_requester(&clock, &activity_tracker, repo, session_info, token).await?;
+    if let Err(e) = check_rate_limit(&site_config, &clock, &activity_tracker, &requester).await {
+        return Ok(e.into_response());
+    }
 
-    let request =
+    let mut request =
         async_graphql::http::parse_query_string(&query.unwrap_or_default())?.data(requester);
 
+    if let Err(e) = check_maintenance(&site_config, &mut request) {
+        return Ok(e.into_response());
+    }
+
     let span = span_for_graphql_request(&request);
     let response = schema.execute(request).instrument(span).await;
 
@@ -323,7 +503,48 @@ pub async fn get(
 
     let headers = response.http_headers.clone();
 
-    Ok((headers, cache_control, Json(response)))
+    Ok((headers, cache_control, Json(response)).into_response())
+}
+
+/// Execute a request, usually a subscription, and stream its responses as
+/// server-sent events, following the "distinct connections" mode of the
+/// GraphQL over SSE protocol
+pub async fn stream(
+    State(schema): State<Schema>,
+    State(site_config): State<SiteConfig>,
+    clock: BoxClock,
+    repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response, FancyError> {
+    let token = authorization
+        .as_ref()
+        .map(|TypedHeader(Authorization(bearer))| bearer.token());
+    let (session_info, _cookie_jar) = cookie_jar.session_info();
+    let requester = get_requester(&clock, &activity_tracker, repo, session_info, token).await?;
+    if let Err(e) = check_rate_limit(&site_config, &clock, &activity_tracker, &requester).await {
+        return Ok(e.into_response());
+    }
+
+    let mut request =
+        async_graphql::http::parse_query_string(&query.unwrap_or_default())?.data(requester);
+
+    if let Err(e) = check_maintenance(&site_config, &mut request) {
+        return Ok(e.into_response());
+    }
+
+    let responses = schema
+        .execute_stream(request)
+        .map(|response| Event::default().event("next").json_data(response))
+        .chain(futures_util::stream::once(async {
+            Ok::<_, axum::Error>(Event::default().event("complete").data(""))
+        }));
+
+    Ok(Sse::new(responses)
+        .keep_alive(KeepAlive::default())
+        .into_response())
 }
 
 pub async fn playground() -> impl IntoResponse {
@@ -0,0 +1,73 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use mas_data_model::SessionEvent;
+use mas_storage_pg::{session_events::PgSessionEventListener, DatabaseError};
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+
+/// How many events are kept for subscribers which are lagging behind
+const CAPACITY: usize = 256;
+
+/// Broadcasts the events which happen to the sessions of users to the GraphQL
+/// subscriptions
+#[derive(Debug, Clone)]
+pub struct SessionEvents {
+    sender: broadcast::Sender<SessionEvent>,
+}
+
+impl SessionEvents {
+    /// Start listening to the session events from the database, and
+    /// broadcasting them in a background task
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection to the database could not be
+    /// established
+    pub async fn listen(pool: &PgPool) -> Result<Self, DatabaseError> {
+        let mut listener = PgSessionEventListener::connect(pool).await?;
+        let (sender, _) = broadcast::channel(CAPACITY);
+
+        let events = Self { sender };
+        let sender = events.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    // This only fails if nobody is subscribed
+                    Ok(event) => {
+                        let _ = sender.send(event);
+                    }
+
+                    Err(e) => {
+                        tracing::error!(
+                            error = &e as &dyn std::error::Error,
+                            "Failed to receive session events, retrying in 5 seconds"
+                        );
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        });
+
+        Ok(events)
+    }
+
+    /// Subscribe to the session events of all users
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.sender.subscribe()
+    }
+}
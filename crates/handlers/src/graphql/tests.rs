@@ -12,14 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::num::NonZeroU32;
+
 use axum::http::Request;
-use hyper::StatusCode;
-use mas_data_model::{AccessToken, Client, TokenType, User};
+use chrono::Duration;
+use futures_util::{FutureExt, StreamExt};
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_graphql::Requester;
 use mas_router::SimpleRoute;
-use mas_storage::{
-    oauth2::{OAuth2AccessTokenRepository, OAuth2ClientRepository},
-    RepositoryAccess,
-};
+use mas_storage::{oauth2::OAuth2AccessTokenRepository, RepositoryAccess};
 use oauth2_types::{
     registration::ClientRegistrationResponse,
     requests::AccessTokenResponse,
@@ -28,95 +29,14 @@ use oauth2_types::{
 use sqlx::PgPool;
 
 use crate::{
+    rate_limit::Quota,
     test_utils,
-    test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState},
+    test_utils::{
+        create_test_client, create_test_user, init_tracing, start_oauth_session, RequestBuilderExt,
+        ResponseExt, TestState,
+    },
 };
 
-async fn create_test_client(state: &TestState) -> Client {
-    let mut repo = state.repository().await.unwrap();
-    let mut rng = state.rng();
-
-    let client = repo
-        .oauth2_client()
-        .add(
-            &mut rng,
-            &state.clock,
-            vec![],
-            None,
-            None,
-            vec![],
-            vec![],
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-        .await
-        .unwrap();
-
-    repo.save().await.unwrap();
-
-    client
-}
-
-async fn create_test_user<U: Into<String> + Send>(state: &TestState, username: U) -> User {
-    let username = username.into();
-    let mut repo = state.repository().await.unwrap();
-    let mut rng = state.rng();
-
-    let user = repo
-        .user()
-        .add(&mut rng, &state.clock, username)
-        .await
-        .unwrap();
-
-    repo.save().await.unwrap();
-
-    user
-}
-
-async fn start_oauth_session(
-    state: &TestState,
-    client: &Client,
-    user: &User,
-    scope: Scope,
-) -> AccessToken {
-    let mut repo = state.repository().await.unwrap();
-    let mut rng = state.rng();
-
-    let browser_session = repo
-        .browser_session()
-        .add(&mut rng, &state.clock, user, None)
-        .await
-        .unwrap();
-
-    let session = repo
-        .oauth2_session()
-        .add_from_browser_session(&mut rng, &state.clock, client, &browser_session, scope)
-        .await
-        .unwrap();
-
-    let access_token_str = TokenType::AccessToken.generate(&mut rng);
-
-    let access_token = repo
-        .oauth2_access_token()
-        .add(&mut rng, &state.clock, &session, access_token_str, None)
-        .await
-        .unwrap();
-
-    repo.save().await.unwrap();
-
-    access_token
-}
-
 const GRAPHQL: ScopeToken = ScopeToken::from_static("urn:mas:graphql:*");
 const ADMIN: ScopeToken = ScopeToken::from_static("urn:mas:admin");
 
@@ -568,3 +488,405 @@ async fn test_oauth2_client_credentials(pool: PgPool) {
         .unwrap();
     assert!(token.is_some());
 }
+
+/// Test that users can rename their own sessions, and only those.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_set_oauth2_session_name(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+
+    let client = create_test_client(&state).await;
+    let alice = create_test_user(&state, "alice").await;
+    let bob = create_test_user(&state, "bob").await;
+    let access_token =
+        start_oauth_session(&state, &client, &alice, Scope::from_iter([GRAPHQL])).await;
+    let bob_token = start_oauth_session(&state, &client, &bob, Scope::from_iter([GRAPHQL])).await;
+
+    let rename = |session_id: String, human_name: Option<&str>| {
+        Request::post("/graphql")
+            .bearer(&access_token.access_token)
+            .json(serde_json::json!({
+                "query": r"
+                    mutation SetName($id: ID!, $name: String) {
+                        setOauth2SessionName(input: {oauth2SessionId: $id, humanName: $name}) {
+                            status
+                            oauth2Session {
+                                humanName
+                            }
+                        }
+                    }
+                ",
+                "variables": {
+                    "id": session_id,
+                    "name": human_name,
+                },
+            }))
+    };
+
+    let own_session = format!("oauth2_session:{}", access_token.session_id);
+    let response = state
+        .request(rename(own_session.clone(), Some("Work laptop")))
+        .await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({
+            "setOauth2SessionName": {
+                "status": "UPDATED",
+                "oauth2Session": {
+                    "humanName": "Work laptop",
+                },
+            },
+        })
+    );
+
+    let mut repo = state.repository().await.unwrap();
+    let session = repo
+        .oauth2_session()
+        .lookup(access_token.session_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.human_name.as_deref(), Some("Work laptop"));
+    repo.cancel().await.unwrap();
+
+    // Empty names are refused
+    let response = state.request(rename(own_session, Some(""))).await;
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.data["setOauth2SessionName"]["status"], "INVALID");
+
+    // Other users' sessions can't be renamed
+    let other_session = format!("oauth2_session:{}", bob_token.session_id);
+    let response = state.request(rename(other_session, Some("Mine"))).await;
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.data["setOauth2SessionName"]["status"], "NOT_FOUND");
+}
+
+/// Test that users can revoke the access of a client to their account, and
+/// only to theirs.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_end_oauth2_client_sessions(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+
+    let client = create_test_client(&state).await;
+    let other_client = create_test_client(&state).await;
+    let alice = create_test_user(&state, "alice").await;
+    let bob = create_test_user(&state, "bob").await;
+    let access_token =
+        start_oauth_session(&state, &client, &alice, Scope::from_iter([GRAPHQL])).await;
+    let first =
+        start_oauth_session(&state, &other_client, &alice, Scope::from_iter([OPENID])).await;
+    let second =
+        start_oauth_session(&state, &other_client, &alice, Scope::from_iter([OPENID])).await;
+    let bob_token =
+        start_oauth_session(&state, &other_client, &bob, Scope::from_iter([OPENID])).await;
+
+    let revoke = |user_id: String| {
+        Request::post("/graphql")
+            .bearer(&access_token.access_token)
+            .json(serde_json::json!({
+                "query": r"
+                    mutation Revoke($userId: ID!, $clientId: ID!) {
+                        endOauth2ClientSessions(input: {userId: $userId, oauth2ClientId: $clientId}) {
+                            status
+                            endedSessions
+                            oauth2Client {
+                                id
+                            }
+                        }
+                    }
+                ",
+                "variables": {
+                    "userId": user_id,
+                    "clientId": format!("oauth2_client:{}", other_client.id),
+                },
+            }))
+    };
+
+    let response = state.request(revoke(format!("user:{}", alice.id))).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({
+            "endOauth2ClientSessions": {
+                "status": "ENDED",
+                "endedSessions": 2,
+                "oauth2Client": {
+                    "id": format!("oauth2_client:{}", other_client.id),
+                },
+            },
+        })
+    );
+
+    // Only the sessions of alice with this client were ended
+    let mut repo = state.repository().await.unwrap();
+    for (session_id, finished) in [
+        (first.session_id, true),
+        (second.session_id, true),
+        (access_token.session_id, false),
+        (bob_token.session_id, false),
+    ] {
+        let session = repo
+            .oauth2_session()
+            .lookup(session_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.is_finished(), finished);
+    }
+    repo.cancel().await.unwrap();
+
+    // Revoking again doesn't end anything
+    let response = state.request(revoke(format!("user:{}", alice.id))).await;
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.data["endOauth2ClientSessions"]["endedSessions"], 0);
+
+    // Other users' sessions can't be ended
+    let response = state.request(revoke(format!("user:{}", bob.id))).await;
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.errors.len(), 1);
+}
+
+/// Test that administrators can list and search users, and that regular users
+/// can't.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_admin_list_users(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+
+    let client = create_test_client(&state).await;
+    let alice = create_test_user(&state, "alice").await;
+    // Users are sorted by ID, so make sure they were created at different times
+    state.clock.advance(Duration::minutes(1));
+    let bob = create_test_user(&state, "bob").await;
+
+    let access_token =
+        start_oauth_session(&state, &client, &alice, Scope::from_iter([GRAPHQL])).await;
+    let access_token_admin =
+        start_oauth_session(&state, &client, &alice, Scope::from_iter([GRAPHQL, ADMIN])).await;
+
+    let list = |token: &str, search: Option<&str>| {
+        Request::post("/graphql")
+            .bearer(token)
+            .json(serde_json::json!({
+                "query": r"
+                    query ListUsers($search: String) {
+                        users(search: $search, first: 10) {
+                            totalCount
+                            nodes {
+                                id
+                                username
+                            }
+                        }
+                    }
+                ",
+                "variables": {
+                    "search": search,
+                },
+            }))
+    };
+
+    // Regular users can't list users
+    let response = state.request(list(&access_token.access_token, None)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.errors.len(), 1);
+
+    let response = state
+        .request(list(&access_token_admin.access_token, None))
+        .await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(
+        response.data,
+        serde_json::json!({
+            "users": {
+                "totalCount": 2,
+                "nodes": [
+                    {
+                        "id": format!("user:{}", alice.id),
+                        "username": "alice",
+                    },
+                    {
+                        "id": format!("user:{}", bob.id),
+                        "username": "bob",
+                    },
+                ],
+            },
+        })
+    );
+
+    let response = state
+        .request(list(&access_token_admin.access_token, Some("BO")))
+        .await;
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_eq!(response.data["users"]["totalCount"], 1);
+    assert_eq!(response.data["users"]["nodes"][0]["username"], "bob");
+}
+
+/// Test that users are notified of what happens to their sessions, and only
+/// to theirs.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_session_events_subscription(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+
+    let alice = create_test_user(&state, "alice").await;
+    let bob = create_test_user(&state, "bob").await;
+
+    let mut repo = state.repository().await.unwrap();
+    let mut rng = state.rng();
+    let viewer = repo
+        .browser_session()
+        .add(&mut rng, &state.clock, &alice, None)
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    let request = async_graphql::Request::new(
+        r"
+            subscription {
+                sessionEvents {
+                    kind
+                    sessionId
+                    session {
+                        __typename
+                    }
+                }
+            }
+        ",
+    )
+    .data(Requester::BrowserSession(viewer));
+    let mut stream = state.graphql_schema.execute_stream(request);
+
+    // Poll the stream once, so that the subscription is started before the
+    // sessions are created
+    assert!(stream.next().now_or_never().is_none());
+
+    let mut repo = state.repository().await.unwrap();
+    repo.browser_session()
+        .add(&mut rng, &state.clock, &bob, None)
+        .await
+        .unwrap();
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &state.clock, &alice, None)
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    let mut repo = state.repository().await.unwrap();
+    repo.browser_session()
+        .finish(&state.clock, session.clone())
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+
+    // The session of bob is not part of the events
+    for kind in ["CREATED", "ENDED"] {
+        let response = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({
+                "sessionEvents": {
+                    "kind": kind,
+                    "sessionId": format!("browser_session:{}", session.id),
+                    "session": {
+                        "__typename": "BrowserSession",
+                    },
+                },
+            })
+        );
+    }
+}
+
+/// Test that queries which are too deep or too complex are rejected before
+/// being executed.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_query_limits(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+
+    let client = create_test_client(&state).await;
+    let user = create_test_user(&state, "alice").await;
+    let access_token =
+        start_oauth_session(&state, &client, &user, Scope::from_iter([GRAPHQL])).await;
+    let access_token = access_token.access_token;
+
+    // Nests `levels` times the sessions of the user of each session
+    let nested_sessions = |levels: usize, first: usize| {
+        let query = format!(
+            "query {{ viewer {{ ... on User {{ {} id {} }} }} }}",
+            format!("browserSessions(first: {first}) {{ edges {{ node {{ user {{ ").repeat(levels),
+            "} } } } ".repeat(levels),
+        );
+        Request::post("/graphql")
+            .bearer(&access_token)
+            .json(serde_json::json!({ "query": query }))
+    };
+
+    let response = state.request(nested_sessions(3, 1)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+    // Too deep
+    let response = state.request(nested_sessions(4, 1)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.errors.len(), 1);
+    assert!(response.data.is_null());
+
+    // Not too deep, but requesting too many items
+    let response = state.request(nested_sessions(2, 100)).await;
+    response.assert_status(StatusCode::OK);
+    let response: GraphQLResponse = response.json();
+    assert_eq!(response.errors.len(), 1);
+    assert!(response.data.is_null());
+}
+
+/// Test that the GraphQL requests of a user are rate limited.
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_rate_limit(pool: PgPool) {
+    init_tracing();
+    let mut state = TestState::from_pool(pool).await.unwrap();
+    state.site_config.graphql_rate_limit =
+        Quota::new(NonZeroU32::new(2).unwrap(), Duration::hours(1));
+
+    let client = create_test_client(&state).await;
+    let alice = create_test_user(&state, "alice").await;
+    let bob = create_test_user(&state, "bob").await;
+    let alice_token =
+        start_oauth_session(&state, &client, &alice, Scope::from_iter([GRAPHQL])).await;
+    let bob_token = start_oauth_session(&state, &client, &bob, Scope::from_iter([GRAPHQL])).await;
+
+    let request = |token: &str| {
+        Request::post("/graphql")
+            .bearer(token)
+            .json(serde_json::json!({ "query": "query { viewer { __typename } }" }))
+    };
+
+    for _ in 0..2 {
+        let response = state.request(request(&alice_token.access_token)).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    let response = state.request(request(&alice_token.access_token)).await;
+    response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    response.assert_header_value(RETRY_AFTER, "3600");
+
+    // Other users have their own budget
+    let response = state.request(request(&bob_token.access_token)).await;
+    response.assert_status(StatusCode::OK);
+}
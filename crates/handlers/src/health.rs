@@ -12,12 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use axum::{extract::State, response::IntoResponse};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use hyper::StatusCode;
 use mas_axum_utils::FancyError;
+use mas_matrix::CircuitBreaker;
 use sqlx::PgPool;
 use tracing::{info_span, Instrument};
 
-pub async fn get(State(pool): State<PgPool>) -> Result<impl IntoResponse, FancyError> {
+pub async fn get(
+    State(pool): State<PgPool>,
+    State(circuit_breaker): State<Option<CircuitBreaker>>,
+) -> Result<Response, FancyError> {
+    // Only set if the homeserver availability should affect the readiness
+    if circuit_breaker
+        .as_ref()
+        .is_some_and(CircuitBreaker::is_open)
+    {
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, "homeserver unavailable").into_response());
+    }
+
     let mut conn = pool.acquire().await?;
 
     sqlx::query("SELECT $1")
@@ -26,12 +42,14 @@ pub async fn get(State(pool): State<PgPool>) -> Result<impl IntoResponse, FancyE
         .instrument(info_span!("DB health"))
         .await?;
 
-    Ok("ok")
+    Ok("ok".into_response())
 }
 
 #[cfg(test)]
 mod tests {
-    use hyper::{Request, StatusCode};
+    use std::time::Duration;
+
+    use hyper::Request;
 
     use super::*;
     use crate::test_utils::{RequestBuilderExt, ResponseExt, TestState};
@@ -45,4 +63,27 @@ mod tests {
         response.assert_status(StatusCode::OK);
         assert_eq!(response.body(), "ok");
     }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_get_health_homeserver_unavailable(pool: PgPool) {
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        state.homeserver_circuit_breaker = Some(breaker.clone());
+
+        let request = Request::get("/health").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // Once the circuit opens, the service is not ready anymore
+        breaker.record_failure();
+        let request = Request::get("/health").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+
+        // And it is ready again once the homeserver responds
+        breaker.record_success();
+        let request = Request::get("/health").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+    }
 }
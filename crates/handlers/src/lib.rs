@@ -23,17 +23,17 @@
     clippy::let_with_type_underscore,
 )]
 
-use std::{convert::Infallible, time::Duration};
+use std::{convert::Infallible, sync::Arc, time::Duration};
 
 use axum::{
-    body::{Bytes, HttpBody},
+    body::HttpBody,
     extract::{FromRef, FromRequestParts, OriginalUri, RawQuery, State},
     http::Method,
+    middleware::from_fn_with_state,
     response::{Html, IntoResponse},
     routing::{get, on, post, MethodFilter},
-    Router,
+    Extension, Router,
 };
-use headers::HeaderName;
 use hyper::{
     header::{
         ACCEPT, ACCEPT_LANGUAGE, AUTHORIZATION, CONTENT_LANGUAGE, CONTENT_LENGTH, CONTENT_TYPE,
@@ -41,30 +41,54 @@ use hyper::{
     StatusCode, Version,
 };
 use mas_axum_utils::{cookies::CookieJar, FancyError};
+use mas_email::MemoryMailbox;
 use mas_http::CorsLayerExt;
-use mas_keystore::{Encrypter, Keystore};
+use mas_keystore::{Encrypter, Keystore, RequestSigner};
+use mas_matrix::{CircuitBreaker, HomeserverConnection};
 use mas_policy::Policy;
 use mas_router::{Route, UrlBuilder};
-use mas_storage::{BoxClock, BoxRepository, BoxRng};
+use mas_storage::{
+    upstream_oauth2::UpstreamOAuthProviderRepository, BoxClock, BoxRepository, BoxRng,
+    RepositoryAccess,
+};
 use mas_templates::{ErrorContext, NotFoundContext, TemplateContext, Templates};
 use passwords::PasswordManager;
 use sqlx::PgPool;
 use tower::util::AndThenLayer;
 use tower_http::cors::{Any, CorsLayer};
 
+#[cfg(feature = "admin-api")]
+mod admin;
+mod avatars;
+pub mod blob_storage;
+mod breached_passwords;
+mod capabilities;
+mod captcha;
 mod compat;
+mod device_conflict;
+#[cfg(feature = "graphql")]
 mod graphql;
 mod health;
+mod login_lockout;
+mod maintenance;
+#[cfg(test)]
+mod no_js;
 mod oauth2;
 pub mod passwords;
+pub mod rate_limit;
+mod request_signing_keys;
+#[cfg(feature = "upstream-oauth2")]
 pub mod upstream_oauth2;
+mod version;
 mod views;
+#[cfg(feature = "webauthn")]
+mod webauthn;
 
 mod activity_tracker;
 mod preferred_language;
 mod site_config;
-#[cfg(test)]
-mod test_utils;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 /// Implement `From<E>` for `RouteError`, for "internal server error" kind of
 /// errors.
@@ -86,31 +110,66 @@ pub use mas_axum_utils::{
     cookies::CookieManager, http_client_factory::HttpClientFactory, ErrorWrapper,
 };
 
+#[cfg(feature = "graphql")]
+pub use self::graphql::{schema as graphql_schema, Limits as GraphQLLimits, SessionEvents};
+#[cfg(feature = "upstream-oauth2")]
+pub use self::upstream_oauth2::cache::MetadataCache;
 pub use self::{
     activity_tracker::{ActivityTracker, Bound as BoundActivityTracker},
-    compat::MatrixHomeserver,
-    graphql::schema as graphql_schema,
+    avatars::AvatarStore,
+    breached_passwords::{BreachedPasswordAction, BreachedPasswordCheck},
+    compat::{device_name::DeviceNameTemplate, MatrixHomeserver},
+    device_conflict::DeviceConflictPolicy,
+    login_lockout::LoginLockout,
+    maintenance::MaintenanceMode,
+    oauth2::authorization::request_object::RequestUriCache,
     preferred_language::PreferredLanguage,
-    site_config::SiteConfig,
-    upstream_oauth2::cache::MetadataCache,
+    site_config::{CustomScope, SiteConfig},
+    version::BuildInfo,
 };
 
+/// The connection to the homeserver, shared by the handlers which need to
+/// call it directly instead of going through a job
+pub type BoxHomeserverConnection = Arc<dyn HomeserverConnection<Error = anyhow::Error>>;
+
 pub fn healthcheck_router<S, B>() -> Router<S, B>
 where
     B: HttpBody + Send + 'static,
     S: Clone + Send + Sync + 'static,
     PgPool: FromRef<S>,
+    Option<CircuitBreaker>: FromRef<S>,
 {
     Router::new().route(mas_router::Healthcheck::route(), get(self::health::get))
 }
 
+/// Serve the version of the service, along with build details and the
+/// database schema version if `detailed` is set
+pub fn version_router<S, B>(build_info: BuildInfo, detailed: bool) -> Router<S, B>
+where
+    B: HttpBody + Send + 'static,
+    S: Clone + Send + Sync + 'static,
+    PgPool: FromRef<S>,
+{
+    let handler = if detailed {
+        get(self::version::get_detailed)
+    } else {
+        get(self::version::get)
+    };
+
+    Router::new()
+        .route(mas_router::Version::route(), handler)
+        .layer(Extension(build_info))
+}
+
+#[cfg(feature = "graphql")]
 pub fn graphql_router<S, B>(playground: bool) -> Router<S, B>
 where
     B: HttpBody + Send + 'static,
-    <B as HttpBody>::Data: Into<Bytes>,
+    <B as HttpBody>::Data: Into<axum::body::Bytes>,
     <B as HttpBody>::Error: std::error::Error + Send + Sync,
     S: Clone + Send + Sync + 'static,
     mas_graphql::Schema: FromRef<S>,
+    SiteConfig: FromRef<S>,
     BoundActivityTracker: FromRequestParts<S>,
     BoxRepository: FromRequestParts<S>,
     BoxClock: FromRequestParts<S>,
@@ -122,6 +181,10 @@ where
             mas_router::GraphQL::route(),
             get(self::graphql::get).post(self::graphql::post),
         )
+        .route(
+            mas_router::GraphQLStream::route(),
+            get(self::graphql::stream),
+        )
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -150,6 +213,7 @@ where
     B: HttpBody + Send + 'static,
     S: Clone + Send + Sync + 'static,
     Keystore: FromRef<S>,
+    RequestSigner: FromRef<S>,
     UrlBuilder: FromRef<S>,
     BoxClock: FromRequestParts<S>,
     BoxRng: FromRequestParts<S>,
@@ -163,6 +227,10 @@ where
             mas_router::Webfinger::route(),
             get(self::oauth2::webfinger::get),
         )
+        .route(
+            mas_router::RequestSigningKeys::route(),
+            get(self::request_signing_keys::get),
+        )
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -178,7 +246,7 @@ where
         )
 }
 
-pub fn api_router<S, B>() -> Router<S, B>
+pub fn api_router<S, B>(maintenance: MaintenanceMode) -> Router<S, B>
 where
     B: HttpBody + Send + 'static,
     <B as HttpBody>::Data: Send,
@@ -192,12 +260,52 @@ where
     Encrypter: FromRef<S>,
     HttpClientFactory: FromRef<S>,
     SiteConfig: FromRef<S>,
+    PasswordManager: FromRef<S>,
     BoxClock: FromRequestParts<S>,
     BoxRng: FromRequestParts<S>,
     Policy: FromRequestParts<S>,
 {
+    // Those routes write to the database, so they are unavailable while in
+    // maintenance mode
+    let mutating = Router::new()
+        .route(
+            mas_router::OAuth2Revocation::route(),
+            post(self::oauth2::revoke::post),
+        )
+        .route(
+            mas_router::OAuth2TokenEndpoint::route(),
+            post(self::oauth2::token::post),
+        )
+        .route(
+            mas_router::OAuth2RegistrationEndpoint::route(),
+            post(self::oauth2::registration::post),
+        )
+        .route(
+            mas_router::OAuth2ClientConfigurationEndpoint::route(),
+            get(self::oauth2::registration::get)
+                .put(self::oauth2::registration::put)
+                .delete(self::oauth2::registration::delete),
+        )
+        .route(
+            mas_router::OAuth2DeviceAuthorizationEndpoint::route(),
+            post(self::oauth2::device::authorize::post),
+        )
+        .route(
+            mas_router::OAuth2PushedAuthorizationRequestEndpoint::route(),
+            post(self::oauth2::par::post),
+        )
+        .route_layer(from_fn_with_state(
+            maintenance,
+            self::maintenance::api_guard,
+        ));
+
     // All those routes are API-like, with a common CORS layer
     Router::new()
+        .route(
+            mas_router::Capabilities::route(),
+            get(self::capabilities::get),
+        )
+        .route(mas_router::Avatar::route(), get(self::avatars::get))
         .route(
             mas_router::OAuth2Keys::route(),
             get(self::oauth2::keys::get),
@@ -213,18 +321,7 @@ where
             mas_router::OAuth2Introspection::route(),
             post(self::oauth2::introspection::post),
         )
-        .route(
-            mas_router::OAuth2Revocation::route(),
-            post(self::oauth2::revoke::post),
-        )
-        .route(
-            mas_router::OAuth2TokenEndpoint::route(),
-            post(self::oauth2::token::post),
-        )
-        .route(
-            mas_router::OAuth2RegistrationEndpoint::route(),
-            post(self::oauth2::registration::post),
-        )
+        .merge(mutating)
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
@@ -240,8 +337,88 @@ where
         )
 }
 
+#[cfg(feature = "admin-api")]
+pub fn admin_api_router<S, B>(maintenance: MaintenanceMode) -> Router<S, B>
+where
+    B: HttpBody + Send + 'static,
+    <B as HttpBody>::Data: Send,
+    <B as HttpBody>::Error: std::error::Error + Send + Sync,
+    S: Clone + Send + Sync + 'static,
+    UrlBuilder: FromRef<S>,
+    SiteConfig: FromRef<S>,
+    BoxRepository: FromRequestParts<S>,
+    BoundActivityTracker: FromRequestParts<S>,
+    BoxClock: FromRequestParts<S>,
+    BoxRng: FromRequestParts<S>,
+{
+    // Those routes write to the database, so they are unavailable while in
+    // maintenance mode
+    let mutating = Router::new()
+        .route(
+            mas_router::AdminUsers::route(),
+            post(self::admin::users::add),
+        )
+        .route(
+            mas_router::AdminLockUser::route(),
+            post(self::admin::users::lock),
+        )
+        .route(
+            mas_router::AdminUnlockUser::route(),
+            post(self::admin::users::unlock),
+        )
+        .route(
+            mas_router::AdminFinishOAuth2Session::route(),
+            post(self::admin::oauth2_sessions::finish),
+        )
+        .route(
+            mas_router::AdminFinishCompatSession::route(),
+            post(self::admin::compat_sessions::finish),
+        )
+        .route(
+            mas_router::AdminRevokeToken::route(),
+            post(self::admin::tokens::revoke),
+        )
+        .route_layer(from_fn_with_state(
+            maintenance,
+            self::maintenance::api_guard,
+        ));
+
+    Router::new()
+        .route(
+            mas_router::AdminApiSpec::route(),
+            get(self::admin::openapi::get),
+        )
+        .route(
+            mas_router::AdminUsers::route(),
+            get(self::admin::users::list),
+        )
+        .route(mas_router::AdminUser::route(), get(self::admin::users::get))
+        .route(
+            mas_router::AdminOAuth2Sessions::route(),
+            get(self::admin::oauth2_sessions::list),
+        )
+        .route(
+            mas_router::AdminOAuth2Session::route(),
+            get(self::admin::oauth2_sessions::get),
+        )
+        .route(
+            mas_router::AdminCompatSessions::route(),
+            get(self::admin::compat_sessions::list),
+        )
+        .route(
+            mas_router::AdminCompatSession::route(),
+            get(self::admin::compat_sessions::get),
+        )
+        .route(
+            mas_router::AdminOAuth2Client::route(),
+            get(self::admin::oauth2_clients::get),
+        )
+        .merge(mutating)
+}
+
+#[cfg(feature = "compat")]
 #[allow(clippy::trait_duplication_in_bounds)]
-pub fn compat_router<S, B>() -> Router<S, B>
+pub fn compat_router<S, B>(maintenance: MaintenanceMode) -> Router<S, B>
 where
     B: HttpBody + Send + 'static,
     <B as HttpBody>::Data: Send,
@@ -256,10 +433,12 @@ where
     BoxClock: FromRequestParts<S>,
     BoxRng: FromRequestParts<S>,
 {
-    Router::new()
+    // Those routes write to the database, so they are unavailable while in
+    // maintenance mode
+    let mutating = Router::new()
         .route(
             mas_router::CompatLogin::route(),
-            get(self::compat::login::get).post(self::compat::login::post),
+            post(self::compat::login::post),
         )
         .route(
             mas_router::CompatLogout::route(),
@@ -269,13 +448,24 @@ where
             mas_router::CompatRefresh::route(),
             post(self::compat::refresh::post),
         )
+        .route_layer(from_fn_with_state(
+            maintenance,
+            self::maintenance::compat_guard,
+        ));
+
+    Router::new()
+        .route(
+            mas_router::CompatLogin::route(),
+            get(self::compat::login::get),
+        )
+        .merge(mutating)
         .route(
             mas_router::CompatLoginSsoRedirect::route(),
             get(self::compat::login_sso_redirect::get),
         )
         .route(
             mas_router::CompatLoginSsoRedirectIdp::route(),
-            get(self::compat::login_sso_redirect::get),
+            get(self::compat::login_sso_redirect::get_idp),
         )
         .route(
             mas_router::CompatLoginSsoRedirectSlash::route(),
@@ -291,17 +481,21 @@ where
                     ACCEPT_LANGUAGE,
                     CONTENT_LANGUAGE,
                     CONTENT_TYPE,
-                    HeaderName::from_static("x-requested-with"),
+                    headers::HeaderName::from_static("x-requested-with"),
                 ])
                 .max_age(Duration::from_secs(60 * 60)),
         )
 }
 
 #[allow(clippy::too_many_lines)]
-pub fn human_router<S, B>(templates: Templates) -> Router<S, B>
+pub fn human_router<S, B>(
+    templates: Templates,
+    cookie_manager: CookieManager,
+    maintenance: MaintenanceMode,
+) -> Router<S, B>
 where
     B: HttpBody + Send + 'static,
-    <B as HttpBody>::Data: Send,
+    <B as HttpBody>::Data: Into<axum::body::Bytes> + Send,
     <B as HttpBody>::Error: std::error::Error + Send + Sync,
     S: Clone + Send + Sync + 'static,
     UrlBuilder: FromRef<S>,
@@ -314,12 +508,14 @@ where
     Keystore: FromRef<S>,
     HttpClientFactory: FromRef<S>,
     PasswordManager: FromRef<S>,
-    MetadataCache: FromRef<S>,
+    RequestUriCache: FromRef<S>,
+    SiteConfig: FromRef<S>,
+    BoxHomeserverConnection: FromRef<S>,
     BoxClock: FromRequestParts<S>,
     BoxRng: FromRequestParts<S>,
     Policy: FromRequestParts<S>,
 {
-    Router::new()
+    let router = Router::new()
         // XXX: hard-coded redirect from /account to /account/
         .route(
             "/account",
@@ -349,23 +545,90 @@ where
             }),
         )
         .route(mas_router::Index::route(), get(self::views::index::get))
+        .route(mas_router::CsrfToken::route(), get(self::views::csrf::get))
         .route(
             mas_router::Login::route(),
             get(self::views::login::get).post(self::views::login::post),
         )
+        .route(
+            mas_router::LoginRecoveryCode::route(),
+            get(self::views::recovery_code_login::get).post(self::views::recovery_code_login::post),
+        )
+        .route(
+            mas_router::LoginLink::route(),
+            get(self::views::login_link::get).post(self::views::login_link::post),
+        )
+        .route(
+            mas_router::LoginLinkFinish::route(),
+            get(self::views::login_link::finish_get).post(self::views::login_link::finish_post),
+        )
         .route(mas_router::Logout::route(), post(self::views::logout::post))
         .route(
             mas_router::Reauth::route(),
             get(self::views::reauth::get).post(self::views::reauth::post),
         )
+        .route(
+            mas_router::AcceptTerms::route(),
+            get(self::views::terms::get).post(self::views::terms::post),
+        )
         .route(
             mas_router::Register::route(),
             get(self::views::register::get).post(self::views::register::post),
         )
+        .route(
+            mas_router::RegisterVerifyEmail::route(),
+            get(self::views::register::verify::get).post(self::views::register::verify::post),
+        )
+        .route(
+            mas_router::RecoveryStart::route(),
+            get(self::views::recovery::start::get).post(self::views::recovery::start::post),
+        )
+        .route(
+            mas_router::RecoveryFinish::route(),
+            get(self::views::recovery::finish::get).post(self::views::recovery::finish::post),
+        )
         .route(
             mas_router::AccountPassword::route(),
             get(self::views::account::password::get).post(self::views::account::password::post),
         )
+        .route(
+            mas_router::AccountRecoveryCodes::route(),
+            get(self::views::account::recovery_codes::get)
+                .post(self::views::account::recovery_codes::post),
+        )
+        .route(
+            mas_router::AccountProfile::route(),
+            get(self::views::account::profile::get).post(self::views::account::profile::post),
+        )
+        .route(
+            mas_router::AccountProfileAvatar::route(),
+            post(self::views::account::profile::upload_avatar),
+        )
+        .route(
+            mas_router::AccountDeactivate::route(),
+            get(self::views::account::deactivate::get).post(self::views::account::deactivate::post),
+        )
+        .route(
+            mas_router::AccountDataExport::route(),
+            get(self::views::account::data_export::get)
+                .post(self::views::account::data_export::post),
+        )
+        .route(
+            mas_router::AccountDataExportDownload::route(),
+            get(self::views::account::data_export::download),
+        )
+        .route(
+            mas_router::AccountEmails::route(),
+            get(self::views::account::emails::index::get),
+        )
+        .route(
+            mas_router::AccountEmailSetPrimary::route(),
+            post(self::views::account::emails::index::set_primary),
+        )
+        .route(
+            mas_router::AccountEmailRemove::route(),
+            post(self::views::account::emails::index::remove),
+        )
         .route(
             mas_router::AccountVerifyEmail::route(),
             get(self::views::account::emails::verify::get)
@@ -389,9 +652,88 @@ where
             get(self::oauth2::consent::get).post(self::oauth2::consent::post),
         )
         .route(
-            mas_router::CompatLoginSsoComplete::route(),
-            get(self::compat::login_sso_complete::get).post(self::compat::login_sso_complete::post),
+            mas_router::DeviceCodeLink::route(),
+            get(self::oauth2::device::link::get),
+        )
+        .route(
+            mas_router::DeviceCodeConsent::route(),
+            get(self::oauth2::device::consent::get).post(self::oauth2::device::consent::post),
+        )
+        .route(
+            mas_router::OidcEndSession::route(),
+            get(self::oauth2::end_session::get).post(self::oauth2::end_session::post),
+        );
+
+    #[cfg(feature = "webauthn")]
+    let router = router
+        .route(
+            mas_router::LoginWebauthn::route(),
+            post(self::views::webauthn::login),
+        )
+        .route(
+            mas_router::LoginWebauthnChallenge::route(),
+            post(self::views::webauthn::login_challenge),
         )
+        .route(
+            mas_router::ReauthWebauthn::route(),
+            post(self::views::webauthn::reauth),
+        )
+        .route(
+            mas_router::ReauthWebauthnChallenge::route(),
+            post(self::views::webauthn::reauth_challenge),
+        )
+        .route(
+            mas_router::AccountWebauthn::route(),
+            get(self::views::account::webauthn::get).post(self::views::account::webauthn::post),
+        )
+        .route(
+            mas_router::AccountWebauthnChallenge::route(),
+            post(self::views::account::webauthn::challenge),
+        )
+        .route(
+            mas_router::AccountWebauthnRemove::route(),
+            post(self::views::account::webauthn::remove),
+        );
+
+    #[cfg(feature = "compat")]
+    let router = router.route(
+        mas_router::CompatLoginSsoComplete::route(),
+        get(self::compat::login_sso_complete::get).post(self::compat::login_sso_complete::post),
+    );
+
+    human_layers(router, templates, cookie_manager, maintenance)
+}
+
+/// Sign in with the upstream OAuth 2.0 providers
+#[cfg(feature = "upstream-oauth2")]
+pub fn upstream_oauth2_router<S, B>(
+    templates: Templates,
+    cookie_manager: CookieManager,
+    maintenance: MaintenanceMode,
+) -> Router<S, B>
+where
+    B: HttpBody + Send + 'static,
+    <B as HttpBody>::Data: Into<axum::body::Bytes> + Send,
+    <B as HttpBody>::Error: std::error::Error + Send + Sync,
+    S: Clone + Send + Sync + 'static,
+    UrlBuilder: FromRef<S>,
+    PreferredLanguage: FromRequestParts<S>,
+    BoxRepository: FromRequestParts<S>,
+    CookieJar: FromRequestParts<S>,
+    BoundActivityTracker: FromRequestParts<S>,
+    Encrypter: FromRef<S>,
+    Templates: FromRef<S>,
+    Keystore: FromRef<S>,
+    HttpClientFactory: FromRef<S>,
+    PasswordManager: FromRef<S>,
+    MetadataCache: FromRef<S>,
+    SiteConfig: FromRef<S>,
+    BoxHomeserverConnection: FromRef<S>,
+    BoxClock: FromRequestParts<S>,
+    BoxRng: FromRequestParts<S>,
+    Policy: FromRequestParts<S>,
+{
+    let router = Router::new()
         .route(
             mas_router::UpstreamOAuth2Authorize::route(),
             get(self::upstream_oauth2::authorize::get),
@@ -403,7 +745,32 @@ where
         .route(
             mas_router::UpstreamOAuth2Link::route(),
             get(self::upstream_oauth2::link::get).post(self::upstream_oauth2::link::post),
-        )
+        );
+
+    human_layers(router, templates, cookie_manager, maintenance)
+}
+
+/// Put the pages meant for humans behind the maintenance mode, and render
+/// the server errors as HTML pages
+fn human_layers<S, B>(
+    router: Router<S, B>,
+    templates: Templates,
+    cookie_manager: CookieManager,
+    maintenance: MaintenanceMode,
+) -> Router<S, B>
+where
+    B: HttpBody + Send + 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .route_layer(from_fn_with_state(
+            self::maintenance::HumanGuardState {
+                mode: maintenance,
+                templates: templates.clone(),
+                cookie_manager,
+            },
+            self::maintenance::human_guard,
+        ))
         .layer(AndThenLayer::new(
             move |response: axum::response::Response| async move {
                 if response.status().is_server_error() {
@@ -424,6 +791,22 @@ where
         ))
 }
 
+/// Serve the page listing the emails kept by the in-memory mail transport
+pub fn dev_mailbox_router<S, B>(mailbox: MemoryMailbox) -> Router<S, B>
+where
+    B: HttpBody + Send + 'static,
+    S: Clone + Send + Sync + 'static,
+    PreferredLanguage: FromRequestParts<S>,
+    Templates: FromRef<S>,
+{
+    Router::new()
+        .route(
+            mas_router::DevMailbox::route(),
+            get(self::views::dev_mailbox::get),
+        )
+        .layer(Extension(mailbox))
+}
+
 /// The fallback handler for all routes that don't match anything else.
 ///
 /// # Errors
@@ -443,3 +826,16 @@ pub async fn fallback(
 
     Ok((StatusCode::NOT_FOUND, Html(res)))
 }
+
+/// Load the upstream providers users can sign in with. There are none if this
+/// build doesn't include the upstream OAuth 2.0 support, even if some are
+/// configured.
+async fn upstream_providers(
+    repo: &mut BoxRepository,
+) -> Result<Vec<mas_data_model::UpstreamOAuthProvider>, mas_storage::RepositoryError> {
+    if !cfg!(feature = "upstream-oauth2") {
+        return Ok(Vec::new());
+    }
+
+    repo.upstream_oauth_provider().all().await
+}
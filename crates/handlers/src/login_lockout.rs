@@ -0,0 +1,191 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Temporary lockout of the users and IP addresses after too many failed
+//! login attempts.
+//!
+//! Each failure beyond the threshold doubles the duration of the lockout, up
+//! to a maximum. A successful login or a password reset lifts the lockout of
+//! the user, so that the legitimate owner of an account can always unlock it
+//! through their email address.
+
+use std::{net::IpAddr, num::NonZeroU32};
+
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::User;
+use mas_storage::{
+    login_failure::{ip_key, user_key},
+    user::UserRepository,
+    BoxRepository, Clock, RepositoryAccess, RepositoryError,
+};
+
+/// Thresholds and durations of the login lockout
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLockout {
+    user_threshold: NonZeroU32,
+    ip_threshold: NonZeroU32,
+    base_duration: Duration,
+    max_duration: Duration,
+}
+
+impl LoginLockout {
+    /// Create a new [`LoginLockout`], locking out users after
+    /// `user_threshold` failures and IP addresses after `ip_threshold`
+    /// failures, for `base_duration` doubling with each further failure, up to
+    /// `max_duration`
+    #[must_use]
+    pub const fn new(
+        user_threshold: NonZeroU32,
+        ip_threshold: NonZeroU32,
+        base_duration: Duration,
+        max_duration: Duration,
+    ) -> Self {
+        Self {
+            user_threshold,
+            ip_threshold,
+            base_duration,
+            max_duration,
+        }
+    }
+
+    /// The counters a login attempt on this username from this IP address
+    /// affects, with their threshold. Unknown usernames don't get a counter,
+    /// to avoid filling the database during credential stuffing attacks.
+    async fn keys(
+        &self,
+        repo: &mut BoxRepository,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<Vec<(String, NonZeroU32)>, RepositoryError> {
+        let mut keys = Vec::with_capacity(2);
+        if let Some(ip) = ip {
+            keys.push((ip_key(ip), self.ip_threshold));
+        }
+
+        if let Some(user) = repo.user().find_by_username(username).await? {
+            keys.push((user_key(&user), self.user_threshold));
+        }
+
+        Ok(keys)
+    }
+
+    /// Check if a login attempt on this username from this IP address should
+    /// be refused, and returns when the lockout ends if so
+    pub(crate) async fn locked_until(
+        &self,
+        repo: &mut BoxRepository,
+        clock: &dyn Clock,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<Option<DateTime<Utc>>, RepositoryError> {
+        let mut locked_until = None;
+        for (key, _) in self.keys(repo, username, ip).await? {
+            let until = repo.login_failure().locked_until(clock, &key).await?;
+            locked_until = locked_until.max(until);
+        }
+
+        Ok(locked_until)
+    }
+
+    /// Record a failed login attempt, locking out the user or the IP address
+    /// if they went over their threshold
+    pub(crate) async fn record_failure(
+        &self,
+        repo: &mut BoxRepository,
+        clock: &dyn Clock,
+        username: &str,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RepositoryError> {
+        for (key, threshold) in self.keys(repo, username, ip).await? {
+            let attempts = repo
+                .login_failure()
+                .record(clock, &key, self.max_duration)
+                .await?;
+
+            if let Some(duration) = self.lockout_duration(attempts, threshold) {
+                let locked_until = clock.now() + duration;
+                tracing::warn!(
+                    key,
+                    attempts,
+                    %locked_until,
+                    "Too many failed login attempts, locking out"
+                );
+                repo.login_failure().lock(&key, locked_until).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Forget the failed login attempts on a user account, after a successful
+    /// login or a password reset
+    pub(crate) async fn reset(
+        &self,
+        repo: &mut BoxRepository,
+        user: &User,
+    ) -> Result<(), RepositoryError> {
+        repo.login_failure().reset(&user_key(user)).await
+    }
+
+    /// How long to lock out a counter after `attempts` failures, if it went
+    /// over its threshold
+    fn lockout_duration(&self, attempts: u32, threshold: NonZeroU32) -> Option<Duration> {
+        let exponent = attempts.checked_sub(threshold.get())?;
+        let factor = 1_i32 << exponent.min(30);
+        let duration = self
+            .base_duration
+            .checked_mul(factor)
+            .unwrap_or(self.max_duration);
+        Some(duration.min(self.max_duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_duration() {
+        let threshold = NonZeroU32::new(3).unwrap();
+        let lockout = LoginLockout::new(
+            threshold,
+            threshold,
+            Duration::minutes(1),
+            Duration::hours(1),
+        );
+
+        assert_eq!(lockout.lockout_duration(1, threshold), None);
+        assert_eq!(lockout.lockout_duration(2, threshold), None);
+        assert_eq!(
+            lockout.lockout_duration(3, threshold),
+            Some(Duration::minutes(1))
+        );
+        assert_eq!(
+            lockout.lockout_duration(4, threshold),
+            Some(Duration::minutes(2))
+        );
+        assert_eq!(
+            lockout.lockout_duration(6, threshold),
+            Some(Duration::minutes(8))
+        );
+        // Capped to the maximum duration, even with a lot of attempts
+        assert_eq!(
+            lockout.lockout_duration(10, threshold),
+            Some(Duration::hours(1))
+        );
+        assert_eq!(
+            lockout.lockout_duration(u32::MAX, threshold),
+            Some(Duration::hours(1))
+        );
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Maintenance mode, during which existing tokens keep working but anything
+//! which would write to the database is rejected
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::{FromRef, State},
+    http::Request,
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::Duration;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_axum_utils::{cookies::CookieManager, FancyError};
+use mas_i18n::Translator;
+use mas_templates::{MaintenanceContext, TemplateContext, Templates};
+use oauth2_types::errors::{ClientError, ClientErrorCode};
+
+#[cfg(feature = "compat")]
+use crate::compat::MatrixError;
+use crate::PreferredLanguage;
+
+/// The maintenance mode of the service, which can be toggled at runtime
+#[derive(Debug, Clone)]
+pub struct MaintenanceMode {
+    enabled: Arc<AtomicBool>,
+    retry_after: Duration,
+    message: Option<Arc<str>>,
+}
+
+impl Default for MaintenanceMode {
+    fn default() -> Self {
+        Self::new(false, Duration::minutes(5), None)
+    }
+}
+
+impl MaintenanceMode {
+    /// Create a new maintenance mode switch
+    #[must_use]
+    pub fn new(enabled: bool, retry_after: Duration, message: Option<String>) -> Self {
+        Self {
+            enabled: Arc::new(AtomicBool::new(enabled)),
+            retry_after,
+            message: message.map(Into::into),
+        }
+    }
+
+    /// Returns `true` if the service is currently in maintenance mode
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enter or leave the maintenance mode. This affects all the clones of
+    /// this switch.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// How long clients should wait before retrying
+    #[must_use]
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+
+    /// The message displayed to users on the maintenance page, if any
+    #[must_use]
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    fn retry_after_header(&self) -> [(hyper::header::HeaderName, String); 1] {
+        [(RETRY_AFTER, self.retry_after.num_seconds().to_string())]
+    }
+}
+
+/// Middleware rejecting OAuth 2.0 API requests while in maintenance mode
+pub(crate) async fn api_guard<B>(
+    State(mode): State<MaintenanceMode>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !mode.is_enabled() {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        mode.retry_after_header(),
+        Json(
+            ClientError::from(ClientErrorCode::TemporarilyUnavailable)
+                .with_description("The service is in maintenance mode".to_owned()),
+        ),
+    )
+        .into_response()
+}
+
+/// Middleware rejecting Matrix compatibility API requests while in
+/// maintenance mode
+#[cfg(feature = "compat")]
+pub(crate) async fn compat_guard<B>(
+    State(mode): State<MaintenanceMode>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !mode.is_enabled() {
+        return next.run(request).await;
+    }
+
+    (
+        mode.retry_after_header(),
+        MatrixError {
+            errcode: "M_UNKNOWN",
+            error: "The service is in maintenance mode",
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        },
+    )
+        .into_response()
+}
+
+/// State of the [`human_guard`] middleware
+#[derive(Clone)]
+pub(crate) struct HumanGuardState {
+    pub mode: MaintenanceMode,
+    pub templates: Templates,
+    pub cookie_manager: CookieManager,
+}
+
+impl FromRef<HumanGuardState> for Arc<Translator> {
+    fn from_ref(input: &HumanGuardState) -> Self {
+        input.templates.translator()
+    }
+}
+
+impl FromRef<HumanGuardState> for CookieManager {
+    fn from_ref(input: &HumanGuardState) -> Self {
+        input.cookie_manager.clone()
+    }
+}
+
+/// Middleware showing the maintenance page on interactive routes while in
+/// maintenance mode
+pub(crate) async fn human_guard<B>(
+    State(state): State<HumanGuardState>,
+    PreferredLanguage(locale): PreferredLanguage,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, FancyError> {
+    if !state.mode.is_enabled() {
+        return Ok(next.run(request).await);
+    }
+
+    let ctx =
+        MaintenanceContext::new(state.mode.message().map(ToOwned::to_owned)).with_language(locale);
+    let content = state.templates.render_maintenance(&ctx)?;
+
+    Ok((
+        StatusCode::SERVICE_UNAVAILABLE,
+        state.mode.retry_after_header(),
+        Html(content),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::RETRY_AFTER, Request, StatusCode};
+    use mas_router::{Route, SimpleRoute};
+    use oauth2_types::{
+        errors::{ClientError, ClientErrorCode},
+        registration::ClientRegistrationResponse,
+        requests::IntrospectionResponse,
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_maintenance_mode(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Get a pair of tokens and a client able to introspect them before entering
+        // maintenance mode
+        state.create_user("alice", "password").await;
+        let cookies = CookieHelper::new();
+        state.login(&cookies, "alice", "password").await;
+        let client_id = state.register_client("https://example.com/callback").await;
+        let tokens = state
+            .run_authorization_code_flow(
+                &cookies,
+                &client_id,
+                "https://example.com/callback",
+                "openid",
+            )
+            .await;
+        let refresh_token = tokens.refresh_token.unwrap();
+
+        let request = Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(json!({
+            "contacts": ["hello@introspecting.com"],
+            "client_uri": "https://introspecting.com/",
+            "grant_types": [],
+            "token_endpoint_auth_method": "client_secret_basic",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse {
+            client_id: introspecting_client_id,
+            client_secret: introspecting_client_secret,
+            ..
+        } = response.json();
+        let introspecting_client_secret = introspecting_client_secret.unwrap();
+
+        state.site_config.maintenance.set_enabled(true);
+
+        // Existing tokens can still be introspected
+        let request = Request::post(mas_router::OAuth2Introspection::PATH)
+            .basic_auth(&introspecting_client_id, &introspecting_client_secret)
+            .form(json!({ "token": tokens.access_token }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: IntrospectionResponse = response.json();
+        assert!(response.active);
+
+        // But they can't be refreshed
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(json!({
+            "grant_type": "refresh_token",
+            "refresh_token": refresh_token,
+            "client_id": client_id,
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_header_value(RETRY_AFTER, "300");
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::TemporarilyUnavailable);
+
+        // Logging in through the Matrix compatibility API is rejected
+        let request = Request::post(mas_router::CompatLogin::PATH).json(json!({
+            "type": "m.login.password",
+            "identifier": { "type": "m.id.user", "user": "alice" },
+            "password": "password",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_header_value(RETRY_AFTER, "300");
+        let error: serde_json::Value = response.json();
+        assert_eq!(error["errcode"], "M_UNKNOWN");
+
+        // Interactive pages show the maintenance page
+        let request = cookies.with_cookies(Request::get(mas_router::Login::route()).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_header_value(RETRY_AFTER, "300");
+        assert!(response.body().contains("Down for maintenance"));
+
+        // GraphQL queries still work, but mutations are rejected
+        let request = cookies.with_cookies(Request::post("/graphql").json(json!({
+            "query": "query { viewer { __typename } }",
+        })));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let request = cookies.with_cookies(Request::post("/graphql").json(json!({
+            "query": r#"mutation { addUser(input: {username: "bob"}) { status } }"#,
+        })));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        response.assert_header_value(RETRY_AFTER, "300");
+
+        // Leaving maintenance mode makes everything available again
+        state.site_config.maintenance.set_enabled(false);
+
+        let request = Request::get(mas_router::Login::route()).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(json!({
+            "grant_type": "refresh_token",
+            "refresh_token": refresh_token,
+            "client_id": client_id,
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Check that every interactive flow can be completed by a browser with
+//! JavaScript disabled, which accessibility tools and the Tor Browser users
+//! rely on.
+
+use hyper::{Request, StatusCode};
+use mas_data_model::UserEmail;
+use mas_router::{Route, SimpleRoute};
+use mas_storage::{
+    user::{UserEmailRepository, UserRepository},
+    RepositoryAccess,
+};
+use oauth2_types::{
+    registration::ClientRegistrationResponse,
+    requests::{AccessTokenResponse, DeviceAuthorizationResponse},
+};
+use sqlx::PgPool;
+
+use crate::test_utils::{
+    init_tracing, CookieHelper, NoJsBrowser, RequestBuilderExt, ResponseExt, TestState,
+};
+
+/// Add a known verification code for the given email address, as the email
+/// with the code is sent by a job which doesn't run in tests
+async fn add_verification_code(state: &TestState, user_email: &UserEmail) -> String {
+    let mut repo = state.repository().await.unwrap();
+    let verification = repo
+        .user_email()
+        .add_verification_code(
+            &mut state.rng(),
+            &state.clock,
+            user_email,
+            chrono::Duration::hours(8),
+            "123456".to_owned(),
+        )
+        .await
+        .unwrap();
+    repo.save().await.unwrap();
+    verification.code
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_login(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let cookies = CookieHelper::new();
+    state.create_user("john", "hunter2").await;
+
+    let mut browser = NoJsBrowser::new(&state, &cookies);
+    browser.visit(mas_router::Login::route()).await;
+    browser
+        .submit(&[("username", "john"), ("password", "hunter2")])
+        .await;
+
+    assert_eq!(browser.location(), "/");
+    browser.page().assert_status(StatusCode::OK);
+    assert!(browser.page().body().contains("john"));
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_register_and_verify_email(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let cookies = CookieHelper::new();
+
+    let mut browser = NoJsBrowser::new(&state, &cookies);
+    browser.visit("/register").await;
+    browser
+        .submit(&[
+            ("email", "john@example.com"),
+            ("username", "john"),
+            ("password", "correcthorsebatterystaple"),
+            ("password_confirm", "correcthorsebatterystaple"),
+        ])
+        .await;
+    assert!(browser.location().starts_with("/verify-email/"));
+
+    let mut repo = state.repository().await.unwrap();
+    let user = repo.user().find_by_username("john").await.unwrap().unwrap();
+    let user_email = repo
+        .user_email()
+        .find(&user, "john@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    repo.cancel().await.unwrap();
+
+    let code = add_verification_code(&state, &user_email).await;
+    browser.submit(&[("code", code.as_str())]).await;
+
+    // The account management app points to the pages working without
+    // JavaScript
+    assert_eq!(browser.location(), "/account/");
+    browser.page().assert_status(StatusCode::OK);
+    assert!(browser.page().body().contains("/account/emails"));
+
+    let mut repo = state.repository().await.unwrap();
+    let user_email = repo
+        .user_email()
+        .lookup(user_email.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user_email.confirmed_at.is_some());
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_add_and_verify_email(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let cookies = CookieHelper::new();
+    let user = state.create_user("john", "hunter2").await;
+
+    let mut browser = NoJsBrowser::new(&state, &cookies);
+    browser.visit(mas_router::Login::route()).await;
+    browser
+        .submit(&[("username", "john"), ("password", "hunter2")])
+        .await;
+
+    browser.visit(mas_router::AccountAddEmail::route()).await;
+    browser.submit(&[("email", "john@example.com")]).await;
+    assert!(browser.location().starts_with("/verify-email/"));
+
+    let mut repo = state.repository().await.unwrap();
+    let user_email = repo
+        .user_email()
+        .find(&user, "john@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    repo.cancel().await.unwrap();
+
+    let code = add_verification_code(&state, &user_email).await;
+    browser.submit(&[("code", code.as_str())]).await;
+    assert_eq!(browser.location(), "/account/");
+
+    let mut repo = state.repository().await.unwrap();
+    let user_email = repo
+        .user_email()
+        .lookup(user_email.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user_email.confirmed_at.is_some());
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_authorization_code_consent(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let cookies = CookieHelper::new();
+    state.create_user("john", "hunter2").await;
+
+    let redirect_uri = "https://example.com/callback";
+    let client_id = state.register_client(redirect_uri).await;
+
+    // Use the form_post response mode, which relies on JavaScript to send the
+    // user back to the client automatically
+    let query = serde_urlencoded::to_string([
+        ("response_type", "code"),
+        ("response_mode", "form_post"),
+        ("client_id", client_id.as_str()),
+        ("redirect_uri", redirect_uri),
+        ("scope", "openid"),
+        ("state", "state"),
+    ])
+    .unwrap();
+
+    let mut browser = NoJsBrowser::new(&state, &cookies);
+    browser
+        .visit(&format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .await;
+    browser
+        .submit(&[("username", "john"), ("password", "hunter2")])
+        .await;
+    assert!(browser.location().starts_with("/consent/"));
+
+    browser.submit(&[]).await;
+    browser.page().assert_status(StatusCode::OK);
+
+    browser.submit(&[]).await;
+    assert_eq!(browser.location(), redirect_uri);
+    assert_eq!(browser.param("state").as_deref(), Some("state"));
+    let code = browser.param("code").unwrap();
+
+    let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+        "grant_type": "authorization_code",
+        "code": code,
+        "redirect_uri": redirect_uri,
+        "client_id": client_id,
+    }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let AccessTokenResponse { access_token, .. } = response.json();
+    assert!(state.is_access_token_valid(&access_token).await);
+}
+
+#[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+async fn test_device_code(pool: PgPool) {
+    init_tracing();
+    let state = TestState::from_pool(pool).await.unwrap();
+    let cookies = CookieHelper::new();
+    state.create_user("john", "hunter2").await;
+
+    let request =
+        Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+            "client_uri": "https://example.com/",
+            "contacts": ["contact@example.com"],
+            "token_endpoint_auth_method": "none",
+            "grant_types": ["urn:ietf:params:oauth:grant-type:device_code"],
+            "response_types": [],
+        }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::CREATED);
+    let ClientRegistrationResponse { client_id, .. } = response.json();
+
+    let request = Request::post(mas_router::OAuth2DeviceAuthorizationEndpoint::PATH).form(
+        serde_json::json!({
+            "client_id": client_id,
+            "scope": "openid",
+        }),
+    );
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let device_grant: DeviceAuthorizationResponse = response.json();
+
+    // The user types the code on the link page, signs in and approves the grant
+    let mut browser = NoJsBrowser::new(&state, &cookies);
+    browser.visit(mas_router::DeviceCodeLink::route()).await;
+    browser
+        .submit(&[("code", device_grant.user_code.as_str())])
+        .await;
+    browser
+        .submit(&[("username", "john"), ("password", "hunter2")])
+        .await;
+    assert!(browser.location().starts_with("/device/"));
+
+    browser.submit(&[("action", "consent")]).await;
+    browser.page().assert_status(StatusCode::OK);
+
+    let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+        "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+        "device_code": device_grant.device_code,
+        "client_id": client_id,
+    }));
+    let response = state.request(request).await;
+    response.assert_status(StatusCode::OK);
+    let AccessTokenResponse { access_token, .. } = response.json();
+    assert!(state.is_access_token_valid(&access_token).await);
+}
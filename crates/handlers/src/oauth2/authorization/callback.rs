@@ -17,13 +17,111 @@
 use std::collections::HashMap;
 
 use axum::response::{Html, IntoResponse, Redirect, Response};
-use mas_data_model::AuthorizationGrant;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{AuthorizationGrant, Client};
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::jwt::{JsonWebSignatureHeader, Jwt, JwtSignatureError};
+use mas_keystore::Keystore;
+use mas_router::UrlBuilder;
+use mas_storage::Clock;
 use mas_templates::{FormPostContext, Templates};
 use oauth2_types::requests::ResponseMode;
 use serde::Serialize;
 use thiserror::Error;
 use url::Url;
 
+/// How long the signed authorization responses are valid for
+const SIGNED_RESPONSE_TTL_MINUTES: i64 = 10;
+
+/// Pick the algorithm used to sign authorization responses.
+///
+/// This is RS256, the default in JARM, if there is a key for it, or any other
+/// algorithm we have a key for.
+pub(crate) fn response_signing_alg(key_store: &Keystore) -> Option<JsonWebSignatureAlg> {
+    if key_store
+        .signing_key_for_algorithm(&JsonWebSignatureAlg::Rs256)
+        .is_some()
+    {
+        return Some(JsonWebSignatureAlg::Rs256);
+    }
+
+    key_store.available_signing_algorithms().into_iter().next()
+}
+
+/// Signs the authorization responses sent with one of the JWT response modes,
+/// as defined by JARM
+#[derive(Clone)]
+pub struct ResponseSigner {
+    key_store: Keystore,
+    alg: Option<JsonWebSignatureAlg>,
+    issuer: String,
+    client_id: String,
+    now: DateTime<Utc>,
+}
+
+impl std::fmt::Debug for ResponseSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseSigner")
+            .field("alg", &self.alg)
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResponseSigner {
+    pub fn new(
+        key_store: &Keystore,
+        url_builder: &UrlBuilder,
+        client: &Client,
+        clock: &impl Clock,
+    ) -> Self {
+        Self {
+            key_store: key_store.clone(),
+            alg: response_signing_alg(key_store),
+            issuer: url_builder.oidc_issuer().to_string(),
+            client_id: client.client_id.clone(),
+            now: clock.now(),
+        }
+    }
+
+    fn sign<T: Serialize>(&self, params: T) -> Result<String, CallbackDestinationError> {
+        #[derive(Serialize)]
+        struct Claims<'a, T> {
+            iss: &'a str,
+            aud: &'a str,
+            exp: i64,
+
+            #[serde(flatten)]
+            params: T,
+        }
+
+        let alg = self
+            .alg
+            .clone()
+            .ok_or(CallbackDestinationError::NoSigningKey)?;
+        let (kid, signer) = self
+            .key_store
+            .signer_for_alg(&alg)
+            .ok_or(CallbackDestinationError::NoSigningKey)?;
+
+        let mut header = JsonWebSignatureHeader::new(alg);
+        if let Some(kid) = kid {
+            header = header.with_kid(kid);
+        }
+
+        let claims = Claims {
+            iss: &self.issuer,
+            aud: &self.client_id,
+            exp: (self.now + Duration::minutes(SIGNED_RESPONSE_TTL_MINUTES)).timestamp(),
+            params,
+        };
+
+        let jwt = Jwt::sign(header, claims, &signer)?;
+        Ok(jwt.into_string())
+    }
+}
+
 #[derive(Debug, Clone)]
 enum CallbackDestinationMode {
     Query {
@@ -38,6 +136,9 @@ pub struct CallbackDestination {
     mode: CallbackDestinationMode,
     safe_redirect_uri: Url,
     state: Option<String>,
+
+    /// Set if the response parameters have to be wrapped in a signed JWT
+    response_signer: Option<ResponseSigner>,
 }
 
 #[derive(Debug, Error)]
@@ -59,32 +160,39 @@ pub enum CallbackDestinationError {
 
     #[error("Failed to serialize parameters query string")]
     ParamsSerialization(#[from] serde_urlencoded::ser::Error),
-}
 
-impl TryFrom<&AuthorizationGrant> for CallbackDestination {
-    type Error = IntoCallbackDestinationError;
+    #[error("No key available to sign the authorization response")]
+    NoSigningKey,
+
+    #[error("Failed to sign the authorization response")]
+    ResponseSignature(#[from] JwtSignatureError),
+}
 
-    fn try_from(value: &AuthorizationGrant) -> Result<Self, Self::Error> {
+impl CallbackDestination {
+    pub fn from_grant(
+        grant: &AuthorizationGrant,
+        response_signer: ResponseSigner,
+    ) -> Result<Self, IntoCallbackDestinationError> {
         Self::try_new(
-            &value.response_mode,
-            value.redirect_uri.clone(),
-            value.state.clone(),
+            &grant.response_mode,
+            grant.redirect_uri.clone(),
+            grant.state.clone(),
+            response_signer,
         )
     }
-}
 
-impl CallbackDestination {
     pub fn try_new(
         mode: &ResponseMode,
         mut redirect_uri: Url,
         state: Option<String>,
+        response_signer: ResponseSigner,
     ) -> Result<Self, IntoCallbackDestinationError> {
         if redirect_uri.fragment().is_some() {
             return Err(IntoCallbackDestinationError::RedirectUriFragmentNotAllowed);
         }
 
-        let mode = match mode {
-            ResponseMode::Query => {
+        let (mode, signed) = match mode {
+            ResponseMode::Query | ResponseMode::QueryJwt => {
                 let existing_params = redirect_uri
                     .query()
                     .map(serde_urlencoded::from_str)
@@ -94,17 +202,34 @@ impl CallbackDestination {
                 // Remove the query from the URL
                 redirect_uri.set_query(None);
 
-                CallbackDestinationMode::Query { existing_params }
+                (
+                    CallbackDestinationMode::Query { existing_params },
+                    *mode == ResponseMode::QueryJwt,
+                )
             }
-            ResponseMode::Fragment => CallbackDestinationMode::Fragment,
-            ResponseMode::FormPost => CallbackDestinationMode::FormPost,
+            ResponseMode::Fragment => (CallbackDestinationMode::Fragment, false),
+            ResponseMode::FragmentJwt => (CallbackDestinationMode::Fragment, true),
+            ResponseMode::FormPost => (CallbackDestinationMode::FormPost, false),
+            ResponseMode::FormPostJwt => (CallbackDestinationMode::FormPost, true),
             _ => return Err(IntoCallbackDestinationError::UnsupportedResponseMode),
         };
 
+        let response_signer = if signed {
+            // We can't sign anything without a key
+            if response_signer.alg.is_none() {
+                return Err(IntoCallbackDestinationError::UnsupportedResponseMode);
+            }
+
+            Some(response_signer)
+        } else {
+            None
+        };
+
         Ok(Self {
             mode,
             safe_redirect_uri: redirect_uri,
             state,
+            response_signer,
         })
     }
 
@@ -112,6 +237,52 @@ impl CallbackDestination {
         self,
         templates: &Templates,
         params: T,
+    ) -> Result<Response, CallbackDestinationError> {
+        #[derive(Serialize)]
+        struct SignedResponse {
+            response: String,
+        }
+
+        #[derive(Serialize)]
+        struct WithState<T> {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            state: Option<String>,
+
+            #[serde(flatten)]
+            params: T,
+        }
+
+        if let Some(response_signer) = &self.response_signer {
+            // With JARM, the state and the parameters all go in the signed JWT
+            let response = response_signer.sign(WithState {
+                state: self.state,
+                params,
+            })?;
+
+            Self::deliver(
+                self.mode,
+                self.safe_redirect_uri,
+                templates,
+                None,
+                SignedResponse { response },
+            )
+        } else {
+            Self::deliver(
+                self.mode,
+                self.safe_redirect_uri,
+                templates,
+                self.state,
+                params,
+            )
+        }
+    }
+
+    fn deliver<T: Serialize>(
+        mode: CallbackDestinationMode,
+        mut redirect_uri: Url,
+        templates: &Templates,
+        state: Option<String>,
+        params: T,
     ) -> Result<Response, CallbackDestinationError> {
         #[derive(Serialize)]
         struct AllParams<'s, T> {
@@ -125,10 +296,7 @@ impl CallbackDestination {
             params: T,
         }
 
-        let mut redirect_uri = self.safe_redirect_uri;
-        let state = self.state;
-
-        match self.mode {
+        match mode {
             CallbackDestinationMode::Query { existing_params } => {
                 let merged = AllParams {
                     existing: Some(&existing_params),
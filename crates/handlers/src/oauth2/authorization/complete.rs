@@ -12,30 +12,45 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
 use axum::{
     extract::{Path, State},
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
-use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt, sentry::SentryEventID, SessionInfoExt};
-use mas_data_model::{AuthorizationGrant, BrowserSession, Client, Device};
+use mas_axum_utils::{
+    cookies::CookieJar, csrf::CsrfExt, http_client_factory::HttpClientFactory,
+    sentry::SentryEventID, SessionInfoExt,
+};
+use mas_data_model::{AuthorizationGrant, BrowserSession, Client, Device, TermsOfService};
 use mas_keystore::Keystore;
 use mas_policy::{EvaluationResult, Policy};
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
+    job::{JobRepositoryExt, ProvisionDeviceJob},
     oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository, OAuth2SessionRepository},
-    user::BrowserSessionRepository,
+    user::{BrowserSessionRepository, UserGroupRepository, UserTermsRepository},
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use mas_templates::{PolicyViolationContext, TemplateContext, Templates};
-use oauth2_types::requests::AuthorizationResponse;
+use oauth2_types::{
+    errors::{ClientError, ClientErrorCode},
+    requests::AuthorizationResponse,
+};
 use thiserror::Error;
 use tracing::warn;
 use ulid::Ulid;
 
-use super::callback::CallbackDestination;
+use super::{
+    callback::{CallbackDestination, ResponseSigner},
+    funnel::{self, FunnelStep},
+};
 use crate::{
-    impl_from_error_for_route, oauth2::generate_id_token, BoundActivityTracker, PreferredLanguage,
+    device_conflict::{claim_devices_in_scope, DeviceConflictError, DeviceConflictPolicy},
+    impl_from_error_for_route,
+    oauth2::{encrypt_id_token, generate_id_token, requested_id_token_claims},
+    BoundActivityTracker, PreferredLanguage, SiteConfig,
 };
 
 #[derive(Debug, Error)]
@@ -95,6 +110,8 @@ pub(crate) async fn get(
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
     State(key_store): State<Keystore>,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(site_config): State<SiteConfig>,
     policy: Policy,
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
@@ -111,12 +128,17 @@ pub(crate) async fn get(
         .await?
         .ok_or(RouteError::NotFound)?;
 
-    let callback_destination = CallbackDestination::try_from(&grant)?;
     let continue_grant = PostAuthAction::continue_grant(grant.id);
 
     let Some(session) = maybe_session else {
         // If there is no session, redirect to the login screen, redirecting here after
         // logout
+        repo.oauth2_authorization_grant()
+            .record_login_shown(&clock, &grant)
+            .await?;
+        repo.save().await?;
+        funnel::record(grant.client_id, FunnelStep::LoginShown);
+
         return Ok((
             cookie_jar,
             url_builder.redirect(&mas_router::Login::and_then(continue_grant)),
@@ -134,6 +156,11 @@ pub(crate) async fn get(
         .await?
         .ok_or(RouteError::NoSuchClient)?;
 
+    let callback_destination = CallbackDestination::from_grant(
+        &grant,
+        ResponseSigner::new(&key_store, &url_builder, &client, &clock),
+    )?;
+
     match complete(
         &mut rng,
         &clock,
@@ -142,9 +169,13 @@ pub(crate) async fn get(
         key_store,
         policy,
         &url_builder,
+        &http_client_factory,
+        site_config.device_conflict_policy,
+        site_config.terms.as_ref(),
         grant,
         &client,
         &session,
+        true,
     )
     .await
     {
@@ -157,6 +188,11 @@ pub(crate) async fn get(
             url_builder.redirect(&mas_router::Reauth::and_then(continue_grant)),
         )
             .into_response()),
+        Err(GrantCompletionError::RequiresTermsAcceptance) => Ok((
+            cookie_jar,
+            url_builder.redirect(&mas_router::AcceptTerms::and_then(continue_grant)),
+        )
+            .into_response()),
         Err(GrantCompletionError::RequiresConsent) => {
             let next = mas_router::Consent(grant_id);
             Ok((cookie_jar, url_builder.redirect(&next)).into_response())
@@ -174,6 +210,16 @@ pub(crate) async fn get(
 
             Ok((cookie_jar, Html(content)).into_response())
         }
+        Err(GrantCompletionError::DeviceInUse) => {
+            let res = callback_destination
+                .go(
+                    &templates,
+                    ClientError::from(ClientErrorCode::InvalidScope)
+                        .with_description("A requested device is already in use".to_owned()),
+                )
+                .await?;
+            Ok((cookie_jar, res).into_response())
+        }
         Err(GrantCompletionError::NotPending) => Err(RouteError::NotPending),
         Err(GrantCompletionError::Internal(e)) => Err(RouteError::Internal(e)),
     }
@@ -190,11 +236,26 @@ pub enum GrantCompletionError {
     #[error("user needs to reauthenticate")]
     RequiresReauth,
 
+    #[error("user needs to accept the terms of service")]
+    RequiresTermsAcceptance,
+
     #[error("client lacks consent")]
     RequiresConsent,
 
     #[error("denied by the policy")]
     PolicyViolation(AuthorizationGrant, EvaluationResult),
+
+    #[error("a requested device is already in use")]
+    DeviceInUse,
+}
+
+impl From<DeviceConflictError> for GrantCompletionError {
+    fn from(e: DeviceConflictError) -> Self {
+        match e {
+            DeviceConflictError::InUse(_) => Self::DeviceInUse,
+            DeviceConflictError::Internal(e) => Self::Internal(e),
+        }
+    }
 }
 
 impl_from_error_for_route!(GrantCompletionError: mas_storage::RepositoryError);
@@ -202,7 +263,15 @@ impl_from_error_for_route!(GrantCompletionError: super::callback::IntoCallbackDe
 impl_from_error_for_route!(GrantCompletionError: mas_policy::LoadError);
 impl_from_error_for_route!(GrantCompletionError: mas_policy::EvaluationError);
 impl_from_error_for_route!(GrantCompletionError: super::super::IdTokenSignatureError);
-
+impl_from_error_for_route!(GrantCompletionError: super::super::ResponseEncryptionError);
+
+/// Try to complete the given authorization grant for the given browser
+/// session
+///
+/// If `interactive` is true, the caller will show the reauthentication, terms
+/// of service or consent page when they are needed, and the grant is recorded
+/// as such.
+/// Otherwise, needing one of those pages is recorded as an error.
 pub(crate) async fn complete(
     rng: &mut (impl rand::RngCore + rand::CryptoRng + Send),
     clock: &impl Clock,
@@ -211,33 +280,73 @@ pub(crate) async fn complete(
     key_store: Keystore,
     mut policy: Policy,
     url_builder: &UrlBuilder,
+    http_client_factory: &HttpClientFactory,
+    device_conflict_policy: DeviceConflictPolicy,
+    terms: Option<&TermsOfService>,
     grant: AuthorizationGrant,
     client: &Client,
     browser_session: &BrowserSession,
+    interactive: bool,
 ) -> Result<AuthorizationResponse, GrantCompletionError> {
     // Verify that the grant is in a pending stage
     if !grant.stage.is_pending() {
         return Err(GrantCompletionError::NotPending);
     }
 
-    // Check if the authentication is fresh enough
+    // Check if the authentication is fresh and strong enough
     let authentication = repo
         .browser_session()
         .get_last_authentication(browser_session)
         .await?;
-    let authentication = authentication.filter(|auth| auth.created_at > grant.max_auth_time());
+    let authentication = authentication.filter(|auth| grant.accepts_authentication(auth));
 
     let Some(valid_authentication) = authentication else {
+        if interactive {
+            repo.oauth2_authorization_grant()
+                .record_login_shown(clock, &grant)
+                .await?;
+            funnel::record(grant.client_id, FunnelStep::LoginShown);
+        } else {
+            repo.oauth2_authorization_grant()
+                .record_error(clock, &grant)
+                .await?;
+            funnel::record(grant.client_id, FunnelStep::Errored);
+        }
         repo.save().await?;
         return Err(GrantCompletionError::RequiresReauth);
     };
 
+    // Check that the user accepted the current terms of service
+    if let Some(terms) = terms {
+        let acceptance = repo
+            .user_terms()
+            .find(&browser_session.user, &terms.version)
+            .await?;
+
+        if !terms.is_accepted(acceptance.as_ref()) {
+            if !interactive {
+                repo.oauth2_authorization_grant()
+                    .record_error(clock, &grant)
+                    .await?;
+                funnel::record(grant.client_id, FunnelStep::Errored);
+            }
+            repo.save().await?;
+            return Err(GrantCompletionError::RequiresTermsAcceptance);
+        }
+    }
+
     // Run through the policy
+    let groups = repo.user_group().list(&browser_session.user).await?;
     let res = policy
-        .evaluate_authorization_grant(&grant, client, &browser_session.user)
+        .evaluate_authorization_grant(&grant, client, &browser_session.user, &groups)
         .await?;
 
-    if !res.valid() {
+    if !res.valid_ignoring_claims() {
+        repo.oauth2_authorization_grant()
+            .record_error(clock, &grant)
+            .await?;
+        repo.save().await?;
+        funnel::record(grant.client_id, FunnelStep::Errored);
         return Err(GrantCompletionError::PolicyViolation(grant, res));
     }
 
@@ -254,16 +363,60 @@ pub(crate) async fn complete(
 
     // Check if the client lacks consent *or* if consent was explicitly asked
     if lacks_consent || grant.requires_consent {
+        // The consent page records when it is actually shown
+        if !interactive {
+            repo.oauth2_authorization_grant()
+                .record_error(clock, &grant)
+                .await?;
+            funnel::record(grant.client_id, FunnelStep::Errored);
+        }
         repo.save().await?;
         return Err(GrantCompletionError::RequiresConsent);
     }
 
+    // Apply the configured policy if one of the requested devices is already
+    // used by another session
+    let (scope, replaced_devices) = claim_devices_in_scope(
+        rng,
+        clock,
+        &mut repo,
+        device_conflict_policy,
+        &browser_session.user,
+        &grant.scope,
+    )
+    .await?;
+
+    for device in &replaced_devices {
+        repo.job()
+            .schedule_job(ProvisionDeviceJob::new(&browser_session.user, device).replace_existing())
+            .await?;
+    }
+
     // All good, let's start the session
     let session = repo
         .oauth2_session()
-        .add_from_browser_session(rng, clock, client, browser_session, grant.scope.clone())
+        .add_from_browser_session(rng, clock, client, browser_session, scope)
         .await?;
 
+    // Record the intended audience of the tokens, if the client asked for one
+    let session = if let Some(resource) = grant.resource.clone() {
+        repo.oauth2_session()
+            .set_resource(session, resource)
+            .await?
+    } else {
+        session
+    };
+
+    // Record the claims the client asked for, leaving out the ones the policy
+    // doesn't allow it to get
+    let session = if let Some(mut claims) = grant.claims.clone() {
+        let denied_claims: HashSet<&str> = res.denied_claims().collect();
+        claims.retain(|claim| !denied_claims.contains(claim));
+        repo.oauth2_session().set_claims(session, claims).await?
+    } else {
+        session
+    };
+
     let grant = repo
         .oauth2_authorization_grant()
         .fulfill(clock, &session, grant)
@@ -274,17 +427,22 @@ pub(crate) async fn complete(
 
     // Did they request an ID token?
     if grant.response_type_id_token {
-        params.id_token = Some(generate_id_token(
+        let user_claims =
+            requested_id_token_claims(&mut repo, &session, &browser_session.user).await?;
+        let id_token = generate_id_token(
             rng,
             clock,
             url_builder,
             &key_store,
             client,
-            &grant,
+            Some(&grant),
             browser_session,
             None,
             Some(&valid_authentication),
-        )?);
+            user_claims,
+        )?;
+
+        params.id_token = Some(encrypt_id_token(rng, http_client_factory, client, id_token).await?);
     }
 
     // Did they request an auth code?
@@ -298,5 +456,7 @@ pub(crate) async fn complete(
         .record_oauth2_session(clock, &session)
         .await;
 
+    funnel::record(client.id, FunnelStep::Completed);
+
     Ok(params)
 }
@@ -0,0 +1,89 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics about how far authorization grants go in the authorization flow
+
+use std::sync::OnceLock;
+
+use opentelemetry::{
+    metrics::{Counter, Unit},
+    Key,
+};
+use ulid::Ulid;
+
+const STEP: Key = Key::from_static_str("step");
+const CLIENT_ID: Key = Key::from_static_str("client.id");
+
+/// A step of the authorization flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FunnelStep {
+    /// The authorization grant was created
+    Started,
+
+    /// The user was sent to a login, registration or reauthentication page
+    LoginShown,
+
+    /// The user was shown the consent screen
+    ConsentShown,
+
+    /// The grant was fulfilled and the user sent back to the client
+    Completed,
+
+    /// The grant ended with an error sent back to the client, or was denied
+    Errored,
+}
+
+impl FunnelStep {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::LoginShown => "login_shown",
+            Self::ConsentShown => "consent_shown",
+            Self::Completed => "completed",
+            Self::Errored => "errored",
+        }
+    }
+}
+
+fn counter() -> &'static Counter<u64> {
+    static COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        let meter = opentelemetry::global::meter_with_version(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            Some(opentelemetry_semantic_conventions::SCHEMA_URL),
+            None,
+        );
+
+        meter
+            .u64_counter("mas.oauth2.authorization_grants")
+            .with_description(
+                "Number of times authorization grants reached a step of the authorization flow",
+            )
+            .with_unit(Unit::new("{grants}"))
+            .init()
+    })
+}
+
+/// Record that an authorization grant of the given client reached the given
+/// step
+pub(crate) fn record(client_id: Ulid, step: FunnelStep) {
+    counter().add(
+        1,
+        &[
+            STEP.string(step.as_str()),
+            CLIENT_ID.string(client_id.to_string()),
+        ],
+    );
+}
@@ -1,4 +1,4 @@
-// Copyright 2021, 2022 The Matrix.org Foundation C.I.C.
+// Copyright 2021-2023 The Matrix.org Foundation C.I.C.
 //
 // Licensed under the Apache License, Version 2.0 (the "License");
 // you may not use this file except in compliance with the License.
@@ -12,18 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{collections::BTreeMap, sync::Arc};
+
 use axum::{
     extract::{Form, State},
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
-use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt, sentry::SentryEventID, SessionInfoExt};
-use mas_data_model::{AuthorizationCode, Pkce};
-use mas_keystore::Keystore;
+use mas_axum_utils::{
+    cookies::CookieJar, csrf::CsrfExt, http_client_factory::HttpClientFactory,
+    sentry::SentryEventID, SessionInfoExt,
+};
+use mas_data_model::{AuthorizationCode, Pkce, PushedAuthorizationRequest};
+use mas_keystore::{Encrypter, Keystore};
 use mas_policy::Policy;
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
-    oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository},
+    oauth2::{
+        OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
+        OAuth2PushedAuthorizationRequestRepository,
+    },
     BoxClock, BoxRepository, BoxRng,
 };
 use mas_templates::{PolicyViolationContext, TemplateContext, Templates};
@@ -37,12 +45,23 @@ use rand::{distributions::Alphanumeric, Rng};
 use serde::Deserialize;
 use thiserror::Error;
 use tracing::warn;
+use url::form_urlencoded;
 
-use self::{callback::CallbackDestination, complete::GrantCompletionError};
-use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage};
+use self::{
+    callback::{CallbackDestination, ResponseSigner},
+    complete::GrantCompletionError,
+    funnel::FunnelStep,
+    request_object::{RequestObjectError, RequestUriCache},
+};
+use super::resource_is_valid;
+use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage, SiteConfig};
 
 mod callback;
 pub mod complete;
+pub(crate) mod funnel;
+pub mod request_object;
+
+pub(crate) use self::callback::response_signing_alg;
 
 #[derive(Debug, Error)]
 pub enum RouteError {
@@ -55,6 +74,15 @@ pub enum RouteError {
     #[error("invalid response mode")]
     InvalidResponseMode,
 
+    #[error("invalid authorization request")]
+    InvalidRequest(#[source] serde_urlencoded::de::Error),
+
+    #[error("invalid or expired request_uri")]
+    InvalidRequestUri,
+
+    #[error("invalid request object")]
+    InvalidRequestObject(#[from] RequestObjectError),
+
     #[error("invalid parameters")]
     IntoCallbackDestination(#[from] self::callback::IntoCallbackDestinationError),
 
@@ -76,6 +104,19 @@ impl IntoResponse for RouteError {
             RouteError::InvalidResponseMode => {
                 (StatusCode::BAD_REQUEST, "invalid response mode").into_response()
             }
+            RouteError::InvalidRequest(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid authorization request ({e})"),
+            )
+                .into_response(),
+            RouteError::InvalidRequestUri => {
+                (StatusCode::BAD_REQUEST, "invalid or expired request_uri").into_response()
+            }
+            RouteError::InvalidRequestObject(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid request object ({e})"),
+            )
+                .into_response(),
             RouteError::IntoCallbackDestination(e) => {
                 (StatusCode::BAD_REQUEST, e.to_string()).into_response()
             }
@@ -105,6 +146,28 @@ pub(crate) struct Params {
     pkce: Option<pkce::AuthorizationRequest>,
 }
 
+impl Params {
+    /// Parse the parameters of an authorization request made by the given
+    /// client, either from the query string or from a pushed authorization
+    /// request
+    pub(crate) fn parse(
+        client_id: &str,
+        parameters: &BTreeMap<String, String>,
+    ) -> Result<Self, serde_urlencoded::de::Error> {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("client_id", client_id)
+            .extend_pairs(parameters.iter().filter(|(key, _)| *key != "client_id"))
+            .finish();
+
+        serde_urlencoded::from_str(&query)
+    }
+
+    /// The redirect URI the client asked for, if any
+    pub(crate) fn redirect_uri(&self) -> &Option<url::Url> {
+        &self.auth.redirect_uri
+    }
+}
+
 /// Given a list of response types and an optional user-defined response mode,
 /// figure out what response mode must be used, and emit an error if the
 /// suggested response mode isn't allowed for the given response types.
@@ -117,21 +180,28 @@ fn resolve_response_mode(
     // If the response type includes either "token" or "id_token", the default
     // response mode is "fragment" and the response mode "query" must not be
     // used
+    // The same goes for the "jwt" response mode, which resolves to either
+    // "fragment.jwt" or "query.jwt"
     if response_type.has_token() || response_type.has_id_token() {
         match suggested_response_mode {
             None => Ok(M::Fragment),
-            Some(M::Query) => Err(RouteError::InvalidResponseMode),
+            Some(M::Jwt) => Ok(M::FragmentJwt),
+            Some(M::Query | M::QueryJwt) => Err(RouteError::InvalidResponseMode),
             Some(mode) => Ok(mode),
         }
     } else {
         // In other cases, all response modes are allowed, defaulting to "query"
-        Ok(suggested_response_mode.unwrap_or(M::Query))
+        match suggested_response_mode {
+            None => Ok(M::Query),
+            Some(M::Jwt) => Ok(M::QueryJwt),
+            Some(mode) => Ok(mode),
+        }
     }
 }
 
 #[tracing::instrument(
     name = "handlers.oauth2.authorization.get",
-    fields(client.id = %params.auth.client_id),
+    fields(client.id = tracing::field::Empty),
     skip_all,
     err,
 )]
@@ -143,19 +213,93 @@ pub(crate) async fn get(
     State(templates): State<Templates>,
     State(key_store): State<Keystore>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(encrypter): State<Encrypter>,
+    State(request_uri_cache): State<RequestUriCache>,
     policy: Policy,
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
     cookie_jar: CookieJar,
-    Form(params): Form<Params>,
+    Form(raw_params): Form<BTreeMap<String, String>>,
 ) -> Result<Response, RouteError> {
     // First, figure out what client it is
+    let client_id = raw_params
+        .get("client_id")
+        .ok_or(RouteError::ClientNotFound)?;
+    tracing::Span::current().record("client.id", client_id.as_str());
+
     let client = repo
         .oauth2_client()
-        .find_by_client_id(&params.auth.client_id)
+        .find_by_client_id(client_id)
         .await?
         .ok_or(RouteError::ClientNotFound)?;
 
+    // If the request refers to a pushed authorization request, the parameters
+    // are the ones which were pushed, and it can only be used once
+    let pushed_request_id = raw_params
+        .get("request_uri")
+        .and_then(|request_uri| PushedAuthorizationRequest::id_from_request_uri(request_uri));
+    let pushed_request = if let Some(id) = pushed_request_id {
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(id)
+            .await?
+            .filter(|request| request.client_id == client.id && request.is_valid(clock.now()))
+            .ok_or(RouteError::InvalidRequestUri)?;
+
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, request)
+            .await?;
+
+        Some(request)
+    } else {
+        None
+    };
+    let request_was_pushed = pushed_request.is_some();
+
+    // Else, the parameters might be in a request object, passed either by value
+    // or by reference
+    let request_object = if request_was_pushed {
+        None
+    } else if let Some(request) = raw_params.get("request") {
+        Some(Arc::from(request.as_str()))
+    } else if let Some(request_uri) = raw_params.get("request_uri") {
+        let request = request_uri_cache
+            .get(&http_client_factory, &clock, &client, request_uri)
+            .await?;
+        Some(request)
+    } else {
+        None
+    };
+
+    let request_object_parameters = if let Some(request) = request_object {
+        let parameters = self::request_object::verify(
+            &http_client_factory,
+            &encrypter,
+            &url_builder,
+            &clock,
+            &client,
+            &request,
+        )
+        .await?;
+        Some(parameters)
+    } else {
+        None
+    };
+
+    let parameters = if let Some(request) = &pushed_request {
+        &request.parameters
+    } else if let Some(parameters) = &request_object_parameters {
+        parameters
+    } else {
+        &raw_params
+    };
+
+    let params =
+        Params::parse(&client.client_id, parameters).map_err(RouteError::InvalidRequest)?;
+
     // And resolve the redirect_uri and response_mode
     let redirect_uri = client
         .resolve_redirect_uri(&params.auth.redirect_uri)?
@@ -168,6 +312,7 @@ pub(crate) async fn get(
         &response_mode,
         redirect_uri.clone(),
         params.auth.state.clone(),
+        ResponseSigner::new(&key_store, &url_builder, &client, &clock),
     )?;
 
     // Get the session info from the cookie
@@ -182,22 +327,13 @@ pub(crate) async fn get(
             let maybe_session = session_info.load_session(&mut repo).await?;
             let prompt = params.auth.prompt.as_deref().unwrap_or_default();
 
-            // Check if the request/request_uri/registration params are used. If so, reply
-            // with the right error since we don't support them.
-            if params.auth.request.is_some() {
-                return Ok(callback_destination
-                    .go(
-                        &templates,
-                        ClientError::from(ClientErrorCode::RequestNotSupported),
-                    )
-                    .await?);
-            }
-
-            if params.auth.request_uri.is_some() {
+            // Some clients are required to push their authorization requests first
+            if client.require_pushed_authorization_requests && !request_was_pushed {
                 return Ok(callback_destination
                     .go(
                         &templates,
-                        ClientError::from(ClientErrorCode::RequestUriNotSupported),
+                        ClientError::from(ClientErrorCode::InvalidRequest)
+                            .with_description("pushed authorization request required".to_owned()),
                     )
                     .await?);
             }
@@ -224,6 +360,8 @@ pub(crate) async fn get(
                     .await?);
             }
 
+            // Check if the registration param is used. If so, reply with the right error
+            // since we don't support it.
             if params.auth.registration.is_some() {
                 return Ok(callback_destination
                     .go(
@@ -233,6 +371,33 @@ pub(crate) async fn get(
                     .await?);
             }
 
+            // Check that the resource indicator, if any, is acceptable
+            if params
+                .auth
+                .resource
+                .as_ref()
+                .is_some_and(|resource| !resource_is_valid(resource))
+            {
+                return Ok(callback_destination
+                    .go(
+                        &templates,
+                        ClientError::from(ClientErrorCode::InvalidTarget),
+                    )
+                    .await?);
+            }
+
+            // prompt=none can't be combined with other values
+            if prompt.contains(&Prompt::None) && prompt.len() > 1 {
+                return Ok(callback_destination
+                    .go(
+                        &templates,
+                        ClientError::from(ClientErrorCode::InvalidRequest).with_description(
+                            "prompt=none can't be combined with other values".to_owned(),
+                        ),
+                    )
+                    .await?);
+            }
+
             // Fail early if prompt=none and there is no active session
             if prompt.contains(&Prompt::None) && maybe_session.is_none() {
                 return Ok(callback_destination
@@ -283,6 +448,17 @@ pub(crate) async fn get(
             };
 
             let requires_consent = prompt.contains(&Prompt::Consent);
+            let requires_reauthentication = prompt.contains(&Prompt::Login);
+
+            // The ACR values are sent as a set, sort them to get a stable order
+            let mut acr_values: Vec<String> = params
+                .auth
+                .acr_values
+                .clone()
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            acr_values.sort();
 
             let grant = repo
                 .oauth2_authorization_grant()
@@ -296,11 +472,16 @@ pub(crate) async fn get(
                     params.auth.state.clone(),
                     params.auth.nonce,
                     params.auth.max_age,
+                    acr_values,
+                    params.auth.claims,
                     response_mode,
                     response_type.has_id_token(),
                     requires_consent,
+                    requires_reauthentication,
+                    params.auth.resource,
                 )
                 .await?;
+            funnel::record(grant.client_id, FunnelStep::Started);
             let continue_grant = PostAuthAction::continue_grant(grant.id);
 
             let res = match maybe_session {
@@ -311,14 +492,22 @@ pub(crate) async fn get(
                 }
                 None if prompt.contains(&Prompt::Create) => {
                     // Client asked for a registration, show the registration prompt
+                    repo.oauth2_authorization_grant()
+                        .record_login_shown(&clock, &grant)
+                        .await?;
                     repo.save().await?;
+                    funnel::record(grant.client_id, FunnelStep::LoginShown);
 
                     url_builder.redirect(&mas_router::Register::and_then(continue_grant))
                         .into_response()
                 }
                 None => {
                     // Other cases where we don't have a session, ask for a login
+                    repo.oauth2_authorization_grant()
+                        .record_login_shown(&clock, &grant)
+                        .await?;
                     repo.save().await?;
+                    funnel::record(grant.client_id, FunnelStep::LoginShown);
 
                     url_builder.redirect(&mas_router::Login::and_then(continue_grant))
                         .into_response()
@@ -330,7 +519,11 @@ pub(crate) async fn get(
                         || prompt.contains(&Prompt::SelectAccount) =>
                 {
                     // TODO: better pages here
+                    repo.oauth2_authorization_grant()
+                        .record_login_shown(&clock, &grant)
+                        .await?;
                     repo.save().await?;
+                    funnel::record(grant.client_id, FunnelStep::LoginShown);
 
                     activity_tracker.record_browser_session(&clock, &session).await;
 
@@ -351,9 +544,13 @@ pub(crate) async fn get(
                         key_store,
                         policy,
                         &url_builder,
+                        &http_client_factory,
+                        site_config.device_conflict_policy,
+                        site_config.terms.as_ref(),
                         grant,
                         &client,
                         &user_session,
+                        false,
                     )
                     .await
                     {
@@ -367,6 +564,14 @@ pub(crate) async fn get(
                                 .await?
                         }
                         Err(GrantCompletionError::RequiresReauth) => {
+                            callback_destination
+                                .go(
+                                    &templates,
+                                    ClientError::from(ClientErrorCode::LoginRequired),
+                                )
+                                .await?
+                        }
+                        Err(GrantCompletionError::RequiresTermsAcceptance) => {
                             callback_destination
                                 .go(
                                     &templates,
@@ -379,6 +584,17 @@ pub(crate) async fn get(
                                 .go(&templates, ClientError::from(ClientErrorCode::AccessDenied))
                                 .await?
                         }
+                        Err(GrantCompletionError::DeviceInUse) => {
+                            callback_destination
+                                .go(
+                                    &templates,
+                                    ClientError::from(ClientErrorCode::InvalidScope)
+                                        .with_description(
+                                            "A requested device is already in use".to_owned(),
+                                        ),
+                                )
+                                .await?
+                        }
                         Err(GrantCompletionError::Internal(e)) => {
                             return Err(RouteError::Internal(e))
                         }
@@ -401,9 +617,13 @@ pub(crate) async fn get(
                         key_store,
                         policy,
                         &url_builder,
+                        &http_client_factory,
+                        site_config.device_conflict_policy,
+                        site_config.terms.as_ref(),
                         grant,
                         &client,
                         &user_session,
+                        true,
                     )
                     .await
                     {
@@ -426,6 +646,21 @@ pub(crate) async fn get(
                             url_builder.redirect(&mas_router::Reauth::and_then(continue_grant))
                                 .into_response()
                         }
+                        Err(GrantCompletionError::RequiresTermsAcceptance) => {
+                            url_builder.redirect(&mas_router::AcceptTerms::and_then(continue_grant))
+                                .into_response()
+                        }
+                        Err(GrantCompletionError::DeviceInUse) => {
+                            callback_destination
+                                .go(
+                                    &templates,
+                                    ClientError::from(ClientErrorCode::InvalidScope)
+                                        .with_description(
+                                            "A requested device is already in use".to_owned(),
+                                        ),
+                                )
+                                .await?
+                        }
                         Err(GrantCompletionError::Internal(e)) => {
                             return Err(RouteError::Internal(e))
                         }
@@ -454,3 +689,443 @@ pub(crate) async fn get(
 
     Ok((cookie_jar, response).into_response())
 }
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use mas_jose::{
+        jwa::SymmetricKey,
+        jwt::{JsonWebSignatureHeader, Jwt},
+    };
+    use mas_storage::{user::UserEmailRepository, RepositoryAccess};
+    use oauth2_types::registration::ClientRegistrationResponse;
+    use sqlx::PgPool;
+    use ulid::Ulid;
+    use url::Url;
+
+    use crate::{
+        test_utils::{init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+        CustomScope,
+    };
+
+    const REDIRECT_URI: &str = "https://example.com/callback";
+
+    fn callback_error(location: &str) -> Option<String> {
+        let url = Url::parse(location).ok()?;
+        url.query_pairs()
+            .find(|(key, _)| key == "error")
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Start an authorization request with a logged in user, and follow the
+    /// redirects until a page is shown, returning its content
+    async fn consent_page(
+        state: &TestState,
+        cookies: &CookieHelper,
+        client_id: &str,
+        scope: &str,
+    ) -> String {
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", scope),
+            ("state", "state"),
+        ])
+        .unwrap();
+        let mut location = format!("{}?{query}", mas_router::OAuth2AuthorizationEndpoint::PATH);
+
+        loop {
+            assert!(!location.starts_with(REDIRECT_URI), "{location}");
+            let request = cookies.with_cookies(Request::get(&location).empty());
+            let response = state.request(request).await;
+            cookies.save_cookies(&response);
+            if response.status() == StatusCode::OK {
+                return response.body().to_owned();
+            }
+
+            response.assert_status(StatusCode::SEE_OTHER);
+            location = response.location().to_owned();
+        }
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_custom_scopes(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+            "custom_scopes": [
+                {"name": "urn:example:read", "user_consentable": true},
+                {"name": "urn:example:bot", "user_consentable": false},
+                {
+                    "name": "urn:example:restricted",
+                    "user_consentable": true,
+                    "clients": [Ulid::nil().to_string()],
+                },
+            ]
+        }))
+        .await
+        .unwrap();
+        state.site_config.custom_scopes = vec![CustomScope {
+            token: "urn:example:read".parse().unwrap(),
+            description: Some("Read your example data".to_owned()),
+        }]
+        .into();
+
+        let client_id = state.register_client(REDIRECT_URI).await;
+        let cookies = CookieHelper::new();
+        state.create_user("john", "hunter2").await;
+        state.login(&cookies, "john", "hunter2").await;
+
+        // Declared custom scopes are shown with their description on the consent
+        // page, and end up in the tokens
+        let page = consent_page(&state, &cookies, &client_id, "openid urn:example:read").await;
+        assert!(page.contains("Read your example data"));
+
+        let response = state
+            .run_authorization_code_flow(
+                &cookies,
+                &client_id,
+                REDIRECT_URI,
+                "openid urn:example:read",
+            )
+            .await;
+        assert_eq!(
+            response.scope,
+            Some("openid urn:example:read".parse().unwrap())
+        );
+
+        // Unknown scopes, scopes users can't grant and scopes this client is not
+        // allowed to request are denied by the policy
+        for scope in [
+            "openid urn:example:write",
+            "openid urn:example:bot",
+            "openid urn:example:restricted",
+        ] {
+            let page = consent_page(&state, &cookies, &client_id, scope).await;
+            assert!(
+                page.contains("The authorization request was denied"),
+                "{scope}"
+            );
+        }
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_prompt(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = state.register_client(REDIRECT_URI).await;
+        let cookies = CookieHelper::new();
+
+        // Start an authorization request with the given prompt, and return where
+        // it redirected to
+        let authorize_with_prompt = |prompt: &'static str| {
+            let query = serde_urlencoded::to_string([
+                ("response_type", "code"),
+                ("client_id", client_id.as_str()),
+                ("redirect_uri", REDIRECT_URI),
+                ("scope", "openid"),
+                ("state", "state"),
+                ("prompt", prompt),
+            ])
+            .unwrap();
+
+            let request = Request::get(format!(
+                "{}?{query}",
+                mas_router::OAuth2AuthorizationEndpoint::PATH
+            ))
+            .empty();
+            let request = cookies.with_cookies(request);
+
+            let state = &state;
+            async move {
+                let response = state.request(request).await;
+                response.assert_status(StatusCode::SEE_OTHER);
+                response.location().to_owned()
+            }
+        };
+
+        // Without a session, prompt=none fails right away
+        let location = authorize_with_prompt("none").await;
+        assert_eq!(callback_error(&location).as_deref(), Some("login_required"));
+
+        // prompt=none can't be combined with other values
+        let location = authorize_with_prompt("none login").await;
+        assert_eq!(
+            callback_error(&location).as_deref(),
+            Some("invalid_request")
+        );
+
+        // prompt=create goes to the registration page, and prompt=login to the
+        // login page
+        let location = authorize_with_prompt("create").await;
+        assert!(location.starts_with("/register?"), "{location}");
+        let location = authorize_with_prompt("login").await;
+        assert!(location.starts_with("/login?"), "{location}");
+
+        state.create_user("john", "hunter2").await;
+        state.login(&cookies, "john", "hunter2").await;
+
+        // The user never consented to this client, so prompt=none fails
+        let location = authorize_with_prompt("none").await;
+        assert_eq!(
+            callback_error(&location).as_deref(),
+            Some("consent_required")
+        );
+
+        // Once the user consented, prompt=none goes straight back to the client
+        state
+            .run_authorization_code_flow(&cookies, &client_id, REDIRECT_URI, "openid")
+            .await;
+        let location = authorize_with_prompt("none").await;
+        assert!(location.starts_with(REDIRECT_URI), "{location}");
+        assert_eq!(callback_error(&location), None);
+
+        // prompt=login asks the user to authenticate again, even with a session
+        let location = authorize_with_prompt("login").await;
+        assert!(location.starts_with("/reauth?"), "{location}");
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_claims_parameter(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = state.register_client(REDIRECT_URI).await;
+        let cookies = CookieHelper::new();
+
+        let user = state.create_user("john", "hunter2").await;
+        let mut repo = state.repository().await.unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "john@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        let user_email = repo
+            .user_email()
+            .mark_as_verified(&state.clock, user_email)
+            .await
+            .unwrap();
+        repo.user_email().set_as_primary(&user_email).await.unwrap();
+        repo.save().await.unwrap();
+
+        state.login(&cookies, "john", "hunter2").await;
+
+        // Ask for the email in the ID token and from the userinfo endpoint,
+        // without the `email` scope, along with a claim the policy denies
+        let claims = serde_json::json!({
+            "id_token": {
+                "email": {"essential": true},
+                "phone_number": null,
+            },
+            "userinfo": {
+                "email_verified": null,
+            },
+        })
+        .to_string();
+        let tokens = state
+            .run_authorization_code_flow_with_params(
+                &cookies,
+                &client_id,
+                REDIRECT_URI,
+                "openid",
+                &[("claims", &claims)],
+            )
+            .await;
+
+        let id_token = tokens.id_token.unwrap();
+        let id_token = Jwt::<serde_json::Value>::try_from(id_token.as_str()).unwrap();
+        let id_token = id_token.payload();
+        assert_eq!(id_token["email"], "john@example.com");
+        assert!(id_token.get("email_verified").is_none());
+        assert!(id_token.get("phone_number").is_none());
+
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .bearer(&tokens.access_token)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let userinfo: serde_json::Value = response.json();
+        assert_eq!(userinfo["email_verified"], true);
+        assert!(userinfo.get("email").is_none());
+    }
+
+    /// Sign a request object with the given client secret
+    fn sign_request_object(
+        client_id: &str,
+        client_secret: &str,
+        alg: JsonWebSignatureAlg,
+    ) -> String {
+        let claims = serde_json::json!({
+            "iss": client_id,
+            "aud": "https://example.com/",
+            "client_id": client_id,
+            "response_type": "code",
+            "redirect_uri": REDIRECT_URI,
+            "scope": "openid",
+            "state": "state",
+        });
+
+        let key = SymmetricKey::new_for_alg(client_secret.as_bytes().to_vec(), &alg).unwrap();
+        Jwt::sign(JsonWebSignatureHeader::new(alg), claims, &key)
+            .unwrap()
+            .into_string()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_request_object(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": [REDIRECT_URI],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "request_object_signing_alg": "HS256",
+                "request_uris": ["https://client.example.com/request.jwt"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.unwrap();
+
+        let authorize_with = |params: Vec<(&'static str, String)>| {
+            let query = serde_urlencoded::to_string(params).unwrap();
+            Request::get(format!(
+                "{}?{query}",
+                mas_router::OAuth2AuthorizationEndpoint::PATH
+            ))
+            .empty()
+        };
+
+        // A valid request object, passed by value, goes to the login page
+        let jwt = sign_request_object(&client_id, &client_secret, JsonWebSignatureAlg::Hs256);
+        let request = authorize_with(vec![
+            ("client_id", client_id.clone()),
+            ("request", jwt.clone()),
+        ]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert!(response.location().starts_with("/login"));
+
+        // Same thing when passed by reference
+        let request_uri: Url = "https://client.example.com/request.jwt".parse().unwrap();
+        state
+            .request_uri_cache
+            .insert(&state.clock, request_uri.clone(), &jwt)
+            .await;
+        let request = authorize_with(vec![
+            ("client_id", client_id.clone()),
+            ("request_uri", request_uri.to_string()),
+        ]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert!(response.location().starts_with("/login"));
+
+        // Only the registered request_uris are used, even if the request object
+        // is valid
+        let request_uri: Url = "https://client.example.com/other.jwt".parse().unwrap();
+        state
+            .request_uri_cache
+            .insert(&state.clock, request_uri.clone(), &jwt)
+            .await;
+        let request = authorize_with(vec![
+            ("client_id", client_id.clone()),
+            ("request_uri", request_uri.to_string()),
+        ]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // The fragment is part of the registered URI
+        let request = authorize_with(vec![
+            ("client_id", client_id.clone()),
+            (
+                "request_uri",
+                "https://client.example.com/request.jwt#v2".to_owned(),
+            ),
+        ]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Request objects can't be fetched over plain HTTP
+        let request = authorize_with(vec![
+            ("client_id", client_id.clone()),
+            (
+                "request_uri",
+                "http://client.example.com/request.jwt".to_owned(),
+            ),
+        ]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Signed with the wrong secret
+        let jwt = sign_request_object(&client_id, "not the secret", JsonWebSignatureAlg::Hs256);
+        let request = authorize_with(vec![("client_id", client_id.clone()), ("request", jwt)]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Signed with an algorithm the client did not register
+        let jwt = sign_request_object(&client_id, &client_secret, JsonWebSignatureAlg::Hs512);
+        let request = authorize_with(vec![("client_id", client_id.clone()), ("request", jwt)]);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_jarm(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let client_id = state.register_client(REDIRECT_URI).await;
+
+        // Errors are also sent back as signed responses, which makes it easy to
+        // get one without logging in
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("response_mode", "jwt"),
+            ("client_id", client_id.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", "openid urn:example:write"),
+            ("state", "state"),
+        ])
+        .unwrap();
+
+        let request = Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        // The "jwt" response mode resolves to "query.jwt" for the code flow
+        let location = Url::parse(response.location()).unwrap();
+        assert!(location.as_str().starts_with(REDIRECT_URI));
+        assert_eq!(callback_error(location.as_str()), None);
+        let (_, jwt) = location
+            .query_pairs()
+            .find(|(key, _)| key == "response")
+            .unwrap();
+
+        let jwt = Jwt::<serde_json::Value>::try_from(jwt.as_ref()).unwrap();
+        jwt.verify_with_jwks(&state.key_store.public_jwks())
+            .unwrap();
+
+        let claims = jwt.payload();
+        assert_eq!(claims["iss"], "https://example.com/");
+        assert_eq!(claims["aud"], client_id.as_str());
+        assert_eq!(claims["state"], "state");
+        assert_eq!(claims["error"], "invalid_scope");
+    }
+}
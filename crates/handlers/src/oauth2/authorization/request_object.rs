@@ -0,0 +1,304 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request objects, as defined by RFC 9101 (JWT-Secured Authorization Request)
+//!
+//! Only signed request objects are supported. Encrypted ones (JWE) would need
+//! the server to publish encryption keys, which it doesn't, so they are
+//! rejected.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use hyper::{body::HttpBody, Request};
+use mas_axum_utils::{client_authorization::fetch_jwks, http_client_factory::HttpClientFactory};
+use mas_data_model::Client;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{
+    claims::{self, ClaimError, TimeOptions},
+    jwt::{Jwt, JwtDecodeError},
+};
+use mas_keystore::{DecryptError, Encrypter};
+use mas_router::UrlBuilder;
+use mas_storage::Clock;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tower::{BoxError, Service, ServiceExt};
+use url::Url;
+
+/// How long a request object fetched from a `request_uri` is kept in cache
+const REQUEST_URI_CACHE_TTL_SECONDS: i64 = 5 * 60;
+
+/// How many request objects are kept in cache at most
+const REQUEST_URI_CACHE_MAX_ENTRIES: usize = 1000;
+
+/// The maximum size of a request object fetched from a `request_uri`
+const REQUEST_URI_MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+pub enum RequestObjectError {
+    #[error("encrypted request objects are not supported, sign them instead")]
+    Encrypted,
+
+    #[error("could not decode the request object")]
+    Decode(#[from] JwtDecodeError),
+
+    #[error("request object signed with {0}, which is not the algorithm registered by the client")]
+    UnexpectedAlgorithm(JsonWebSignatureAlg),
+
+    #[error("the client has no key to verify the request object with")]
+    MissingKey,
+
+    #[error("could not decrypt the client secret")]
+    ClientSecretDecryption(#[from] DecryptError),
+
+    #[error("could not fetch the client JWKS")]
+    JwksFetch(#[source] BoxError),
+
+    #[error("invalid request object signature")]
+    InvalidSignature,
+
+    #[error("invalid request object claims")]
+    InvalidClaims(#[from] ClaimError),
+
+    #[error("the client_id in the request object does not match the client")]
+    ClientIdMismatch,
+
+    #[error("invalid request_uri")]
+    InvalidRequestUri(#[from] url::ParseError),
+
+    #[error("request_uri must use the https scheme")]
+    InsecureRequestUri,
+
+    #[error("request_uri is not one of the request_uris registered by the client")]
+    UnregisteredRequestUri,
+
+    #[error("could not fetch the request object from the request_uri")]
+    RequestUriFetch(#[source] BoxError),
+}
+
+/// Verify the request object sent by a client, and get the authorization
+/// request parameters out of it
+///
+/// The parameters in the request object are the only ones considered, the
+/// ones passed alongside it are ignored.
+pub(crate) async fn verify(
+    http_client_factory: &HttpClientFactory,
+    encrypter: &Encrypter,
+    url_builder: &UrlBuilder,
+    clock: &dyn Clock,
+    client: &Client,
+    request: &str,
+) -> Result<BTreeMap<String, String>, RequestObjectError> {
+    // Encrypted request objects are JWEs, which have five parts instead of three
+    if request.split('.').count() == 5 {
+        return Err(RequestObjectError::Encrypted);
+    }
+
+    let jwt = Jwt::<HashMap<String, Value>>::try_from(request)?;
+
+    let alg = jwt.header().alg();
+    if let Some(expected) = &client.request_object_signing_alg {
+        if alg != expected {
+            return Err(RequestObjectError::UnexpectedAlgorithm(alg.clone()));
+        }
+    }
+
+    // Request objects signed with an HMAC use the client secret as key, the
+    // other ones are signed with one of the keys in the client JWKS. Unsigned
+    // request objects never pass either of those checks.
+    if matches!(
+        alg,
+        JsonWebSignatureAlg::Hs256 | JsonWebSignatureAlg::Hs384 | JsonWebSignatureAlg::Hs512
+    ) {
+        let encrypted_client_secret = client
+            .encrypted_client_secret
+            .as_deref()
+            .ok_or(RequestObjectError::MissingKey)?;
+        let client_secret = encrypter.decrypt_string(encrypted_client_secret)?;
+
+        jwt.verify_with_shared_secret(client_secret)
+            .map_err(|_| RequestObjectError::InvalidSignature)?;
+    } else {
+        let jwks = client.jwks.as_ref().ok_or(RequestObjectError::MissingKey)?;
+        let jwks = fetch_jwks(http_client_factory, jwks)
+            .await
+            .map_err(RequestObjectError::JwksFetch)?;
+
+        jwt.verify_with_jwks(&jwks)
+            .map_err(|_| RequestObjectError::InvalidSignature)?;
+    }
+
+    let (_header, mut claims) = jwt.into_parts();
+
+    // The JWT claims are all optional, but have to be valid if present
+    let issuer = url_builder.oidc_issuer().to_string();
+    let time_options = TimeOptions::new(clock.now());
+    claims::ISS.extract_optional_with_options(&mut claims, client.client_id.as_str())?;
+    claims::AUD.extract_optional_with_options(&mut claims, &issuer)?;
+    claims::EXP.extract_optional_with_options(&mut claims, &time_options)?;
+    claims::NBF.extract_optional_with_options(&mut claims, &time_options)?;
+    claims.remove("iat");
+    claims.remove("jti");
+
+    if let Some(client_id) = claims.remove("client_id") {
+        if client_id.as_str() != Some(client.client_id.as_str()) {
+            return Err(RequestObjectError::ClientIdMismatch);
+        }
+    }
+
+    // The rest of the claims are the authorization request parameters. Request
+    // objects can't reference other request objects.
+    let parameters = claims
+        .into_iter()
+        .filter(|(key, _)| key != "request" && key != "request_uri")
+        .filter_map(|(key, value)| match value {
+            Value::Null => None,
+            Value::String(value) => Some((key, value)),
+            value => Some((key, value.to_string())),
+        })
+        .collect();
+
+    Ok(parameters)
+}
+
+struct CachedRequestObject {
+    fetched_at: DateTime<Utc>,
+    request: Arc<str>,
+}
+
+/// A cache of the request objects fetched from the `request_uri` sent by
+/// clients
+#[derive(Clone, Default)]
+pub struct RequestUriCache {
+    cache: Arc<RwLock<HashMap<Url, CachedRequestObject>>>,
+}
+
+impl RequestUriCache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the request object referenced by a `request_uri`, fetching it if it
+    /// is not in the cache yet or if the cached one expired
+    ///
+    /// Only the `request_uri` values registered by the client are fetched. They
+    /// are compared with their fragment, which clients change to bust the
+    /// cache.
+    pub(crate) async fn get(
+        &self,
+        http_client_factory: &HttpClientFactory,
+        clock: &dyn Clock,
+        client: &Client,
+        request_uri: &str,
+    ) -> Result<Arc<str>, RequestObjectError> {
+        let request_uri: Url = request_uri.parse()?;
+        if request_uri.scheme() != "https" {
+            return Err(RequestObjectError::InsecureRequestUri);
+        }
+
+        if !client.request_uris.contains(&request_uri) {
+            return Err(RequestObjectError::UnregisteredRequestUri);
+        }
+
+        let now = clock.now();
+        let ttl = Duration::seconds(REQUEST_URI_CACHE_TTL_SECONDS);
+
+        if let Some(cached) = self.cache.read().await.get(&request_uri) {
+            if now < cached.fetched_at + ttl {
+                return Ok(cached.request.clone());
+            }
+        }
+
+        let request: Arc<str> = fetch_request_uri(http_client_factory, &request_uri)
+            .await
+            .map_err(RequestObjectError::RequestUriFetch)?
+            .into();
+
+        let mut cache = self.cache.write().await;
+        cache.retain(|_, cached| now < cached.fetched_at + ttl);
+
+        // Make room for the new entry by evicting the oldest one
+        if cache.len() >= REQUEST_URI_CACHE_MAX_ENTRIES {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, cached)| cached.fetched_at)
+                .map(|(request_uri, _)| request_uri.clone());
+            if let Some(oldest) = oldest {
+                cache.remove(&oldest);
+            }
+        }
+
+        cache.insert(
+            request_uri,
+            CachedRequestObject {
+                fetched_at: now,
+                request: request.clone(),
+            },
+        );
+
+        Ok(request)
+    }
+
+    /// Put a request object in the cache, as if it was fetched from the given
+    /// `request_uri`
+    #[cfg(test)]
+    pub(crate) async fn insert(&self, clock: &dyn Clock, request_uri: Url, request: &str) {
+        self.cache.write().await.insert(
+            request_uri,
+            CachedRequestObject {
+                fetched_at: clock.now(),
+                request: request.into(),
+            },
+        );
+    }
+}
+
+async fn fetch_request_uri(
+    http_client_factory: &HttpClientFactory,
+    request_uri: &Url,
+) -> Result<String, BoxError> {
+    // The fragment is only there for clients to bust our cache
+    let mut request_uri = request_uri.clone();
+    request_uri.set_fragment(None);
+
+    let request = Request::get(request_uri.as_str()).body(mas_http::EmptyBody::new())?;
+
+    let mut client = http_client_factory.client("client.fetch_request_uri");
+    let response = client.ready().await?.call(request).await?;
+
+    if !response.status().is_success() {
+        return Err(format!("unexpected status code {}", response.status()).into());
+    }
+
+    // Read the body chunk by chunk, to stop as soon as it gets too big
+    let mut body = std::pin::pin!(response.into_body());
+    let mut request = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if request.len() + chunk.len() > REQUEST_URI_MAX_RESPONSE_SIZE {
+            return Err("the request object is too large".into());
+        }
+        request.extend_from_slice(&chunk);
+    }
+
+    let request = String::from_utf8(request)?;
+
+    Ok(request.trim().to_owned())
+}
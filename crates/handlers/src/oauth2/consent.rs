@@ -13,13 +13,14 @@
 // limitations under the License.
 
 use axum::{
-    extract::{Form, Path, State},
+    extract::{Path, State},
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
 use mas_axum_utils::{
     cookies::CookieJar,
     csrf::{CsrfExt, ProtectedForm},
+    form_or_json::{json_redirect, FormOrJson},
     sentry::SentryEventID,
     SessionInfoExt,
 };
@@ -28,13 +29,15 @@ use mas_policy::Policy;
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{
     oauth2::{OAuth2AuthorizationGrantRepository, OAuth2ClientRepository},
+    user::UserGroupRepository,
     BoxClock, BoxRepository, BoxRng,
 };
 use mas_templates::{ConsentContext, PolicyViolationContext, TemplateContext, Templates};
 use thiserror::Error;
 use ulid::Ulid;
 
-use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage};
+use super::authorization::funnel::{self, FunnelStep};
+use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage, SiteConfig};
 
 #[derive(Debug, Error)]
 pub enum RouteError {
@@ -85,6 +88,7 @@ pub(crate) async fn get(
     PreferredLanguage(locale): PreferredLanguage,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     mut policy: Policy,
     mut repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
@@ -118,12 +122,26 @@ pub(crate) async fn get(
 
         let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
 
+        let groups = repo.user_group().list(&session.user).await?;
         let res = policy
-            .evaluate_authorization_grant(&grant, &client, &session.user)
+            .evaluate_authorization_grant(&grant, &client, &session.user, &groups)
             .await?;
 
-        if res.valid() {
+        if res.valid_ignoring_claims() {
+            repo.oauth2_authorization_grant()
+                .record_consent_shown(&clock, &grant)
+                .await?;
+            repo.save().await?;
+            funnel::record(grant.client_id, FunnelStep::ConsentShown);
+
+            let scope_descriptions = site_config
+                .custom_scopes
+                .iter()
+                .filter_map(|scope| Some((scope.token.to_string(), scope.description.clone()?)))
+                .collect();
+
             let ctx = ConsentContext::new(grant, client)
+                .with_scope_descriptions(scope_descriptions)
                 .with_session(session)
                 .with_csrf(csrf_token.form_value())
                 .with_language(locale);
@@ -132,6 +150,12 @@ pub(crate) async fn get(
 
             Ok((cookie_jar, Html(content)).into_response())
         } else {
+            repo.oauth2_authorization_grant()
+                .record_error(&clock, &grant)
+                .await?;
+            repo.save().await?;
+            funnel::record(grant.client_id, FunnelStep::Errored);
+
             let ctx = PolicyViolationContext::new(grant, client)
                 .with_session(session)
                 .with_csrf(csrf_token.form_value())
@@ -142,6 +166,12 @@ pub(crate) async fn get(
             Ok((cookie_jar, Html(content)).into_response())
         }
     } else {
+        repo.oauth2_authorization_grant()
+            .record_login_shown(&clock, &grant)
+            .await?;
+        repo.save().await?;
+        funnel::record(grant.client_id, FunnelStep::LoginShown);
+
         let login = mas_router::Login::and_continue_grant(grant_id);
         Ok((cookie_jar, url_builder.redirect(&login)).into_response())
     }
@@ -162,9 +192,11 @@ pub(crate) async fn post(
     cookie_jar: CookieJar,
     State(url_builder): State<UrlBuilder>,
     Path(grant_id): Path<Ulid>,
-    Form(form): Form<ProtectedForm<()>>,
+    form: FormOrJson<ProtectedForm<()>>,
 ) -> Result<Response, RouteError> {
-    cookie_jar.verify_form(&clock, form)?;
+    // The frontend submits the form as JSON, and expects JSON replies
+    let json = form.is_json();
+    cookie_jar.verify_form(&clock, form.into_inner())?;
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
@@ -178,8 +210,12 @@ pub(crate) async fn post(
     let next = PostAuthAction::continue_grant(grant_id);
 
     let Some(session) = maybe_session else {
-        let login = mas_router::Login::and_then(next);
-        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+        let login = url_builder.redirect(&mas_router::Login::and_then(next));
+        if json {
+            return Ok((cookie_jar, json_redirect(login)).into_response());
+        }
+
+        return Ok((cookie_jar, login).into_response());
     };
 
     activity_tracker
@@ -192,11 +228,18 @@ pub(crate) async fn post(
         .await?
         .ok_or(RouteError::NoSuchClient)?;
 
+    let groups = repo.user_group().list(&session.user).await?;
     let res = policy
-        .evaluate_authorization_grant(&grant, &client, &session.user)
+        .evaluate_authorization_grant(&grant, &client, &session.user, &groups)
         .await?;
 
-    if !res.valid() {
+    if !res.valid_ignoring_claims() {
+        repo.oauth2_authorization_grant()
+            .record_error(&clock, &grant)
+            .await?;
+        repo.save().await?;
+        funnel::record(grant.client_id, FunnelStep::Errored);
+
         return Err(RouteError::PolicyViolation);
     }
 
@@ -224,5 +267,10 @@ pub(crate) async fn post(
 
     repo.save().await?;
 
-    Ok((cookie_jar, next.go_next(&url_builder)).into_response())
+    let reply = next.go_next(&url_builder);
+    if json {
+        return Ok((cookie_jar, json_redirect(reply)).into_response());
+    }
+
+    Ok((cookie_jar, reply).into_response())
 }
@@ -0,0 +1,189 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{extract::State, response::IntoResponse, Json, TypedHeader};
+use chrono::Duration;
+use headers::{CacheControl, HeaderMap, HeaderMapExt, Pragma, UserAgent};
+use hyper::StatusCode;
+use mas_axum_utils::{
+    client_authorization::{ClientAuthorization, CredentialsVerificationError},
+    http_client_factory::HttpClientFactory,
+    sentry::SentryEventID,
+};
+use mas_keystore::Encrypter;
+use mas_router::UrlBuilder;
+use mas_storage::{oauth2::OAuth2DeviceCodeGrantRepository, BoxClock, BoxRepository, BoxRng};
+use oauth2_types::{
+    errors::{ClientError, ClientErrorCode},
+    requests::{
+        DeviceAuthorizationRequest, DeviceAuthorizationResponse, GrantType,
+        DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS,
+    },
+    scope::ScopeToken,
+};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    Rng,
+};
+use thiserror::Error;
+
+use crate::{impl_from_error_for_route, BoundActivityTracker};
+
+/// How long the device and user codes are valid for
+const DEVICE_CODE_LIFETIME_MINUTES: i64 = 20;
+
+/// The characters used to generate user codes. Vowels are left out to avoid
+/// forming words, and so are characters which could be mistaken for digits.
+const USER_CODE_CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("bad request")]
+    BadRequest,
+
+    #[error("client not found")]
+    ClientNotFound,
+
+    #[error("client not allowed")]
+    ClientNotAllowed,
+
+    #[error("could not verify client credentials")]
+    ClientCredentialsVerification(#[from] CredentialsVerificationError),
+
+    #[error("client is unauthorized")]
+    UnauthorizedClient,
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+        let response = match self {
+            Self::Internal(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ClientError::from(ClientErrorCode::ServerError)),
+            ),
+            Self::BadRequest => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidRequest)),
+            ),
+            Self::ClientNotFound | Self::ClientCredentialsVerification(_) => (
+                StatusCode::UNAUTHORIZED,
+                Json(ClientError::from(ClientErrorCode::InvalidClient)),
+            ),
+            Self::ClientNotAllowed | Self::UnauthorizedClient => (
+                StatusCode::UNAUTHORIZED,
+                Json(ClientError::from(ClientErrorCode::UnauthorizedClient)),
+            ),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+#[tracing::instrument(
+    name = "handlers.oauth2.device.authorize.post",
+    fields(client.id = client_authorization.client_id()),
+    skip_all,
+    err,
+)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    State(url_builder): State<UrlBuilder>,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(encrypter): State<Encrypter>,
+    client_authorization: ClientAuthorization<DeviceAuthorizationRequest>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = client_authorization
+        .credentials
+        .fetch(&mut repo)
+        .await?
+        .ok_or(RouteError::ClientNotFound)?;
+
+    let method = client
+        .token_endpoint_auth_method
+        .as_ref()
+        .ok_or(RouteError::ClientNotAllowed)?;
+
+    client_authorization
+        .credentials
+        .verify(&http_client_factory, &encrypter, method, &client)
+        .await?;
+
+    let form = client_authorization.form.ok_or(RouteError::BadRequest)?;
+
+    // Check that the client is allowed to use this grant type
+    if !client.grant_types.contains(&GrantType::DeviceCode) {
+        return Err(RouteError::UnauthorizedClient);
+    }
+
+    // Default to an empty scope if none is provided
+    let scope = form
+        .scope
+        .unwrap_or_else(|| std::iter::empty::<ScopeToken>().collect());
+
+    let expires_in = Duration::minutes(DEVICE_CODE_LIFETIME_MINUTES);
+
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    let ip_address = activity_tracker.ip();
+
+    let device_code = Alphanumeric.sample_string(&mut rng, 32);
+    let user_code: String = (0..8)
+        .map(|_| char::from(USER_CODE_CHARSET[rng.gen_range(0..USER_CODE_CHARSET.len())]))
+        .collect();
+
+    let device_code = repo
+        .oauth2_device_code_grant()
+        .add(
+            &mut rng,
+            &clock,
+            &client,
+            scope,
+            device_code,
+            user_code,
+            expires_in,
+            ip_address,
+            user_agent,
+        )
+        .await?;
+
+    repo.save().await?;
+
+    let response = DeviceAuthorizationResponse {
+        device_code: device_code.device_code,
+        user_code: device_code.user_code.clone(),
+        verification_uri: url_builder.device_code_link(),
+        verification_uri_complete: Some(
+            url_builder.device_code_link_with_code(device_code.user_code),
+        ),
+        expires_in,
+        interval: Some(Duration::seconds(
+            DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS,
+        )),
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.typed_insert(CacheControl::new().with_no_store());
+    headers.typed_insert(Pragma::no_cache());
+
+    Ok((headers, Json(response)))
+}
@@ -0,0 +1,223 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context;
+use axum::{
+    extract::{Form, Path, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_policy::Policy;
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_storage::{
+    oauth2::{OAuth2ClientRepository, OAuth2DeviceCodeGrantRepository},
+    user::{UserGroupRepository, UserTermsRepository},
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{DeviceConsentContext, TemplateContext, Templates};
+use serde::Deserialize;
+use ulid::Ulid;
+
+use crate::{BoundActivityTracker, PreferredLanguage, SiteConfig};
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    Consent,
+    Reject,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct ConsentForm {
+    action: Action,
+}
+
+#[tracing::instrument(name = "handlers.oauth2.device.consent.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Path(grant_id): Path<Ulid>,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login =
+            mas_router::Login::and_then(PostAuthAction::continue_device_code_grant(grant_id));
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // The user has to accept the current terms of service first
+    if let Some(terms) = &site_config.terms {
+        let acceptance = repo
+            .user_terms()
+            .find(&session.user, &terms.version)
+            .await?;
+        if !terms.is_accepted(acceptance.as_ref()) {
+            let destination = mas_router::AcceptTerms::and_then(
+                PostAuthAction::continue_device_code_grant(grant_id),
+            );
+            return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
+        }
+    }
+
+    let grant = repo
+        .oauth2_device_code_grant()
+        .lookup(grant_id)
+        .await?
+        .context("Device grant not found")?;
+
+    if grant.is_expired(clock.now()) {
+        return Err(FancyError::from(anyhow::anyhow!("Grant is expired")));
+    }
+
+    let client = repo
+        .oauth2_client()
+        .lookup(grant.client_id)
+        .await?
+        .context("Client not found")?;
+
+    // Evaluate the policy
+    let groups = repo.user_group().list(&session.user).await?;
+    let res = policy
+        .evaluate_device_code_grant(&grant, &client, &session.user, &groups)
+        .await?;
+    if !res.valid() {
+        return Err(FancyError::from(anyhow::anyhow!(
+            "Policy denied the request: {res}"
+        )));
+    }
+
+    let scope_descriptions = site_config
+        .custom_scopes
+        .iter()
+        .filter_map(|scope| Some((scope.token.to_string(), scope.description.clone()?)))
+        .collect();
+
+    let ctx = DeviceConsentContext::new(grant, client)
+        .with_scope_descriptions(scope_descriptions)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let rendered = templates.render_device_consent(&ctx)?;
+
+    Ok((cookie_jar, Html(rendered)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.oauth2.device.consent.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Path(grant_id): Path<Ulid>,
+    Form(form): Form<ProtectedForm<ConsentForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login =
+            mas_router::Login::and_then(PostAuthAction::continue_device_code_grant(grant_id));
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let grant = repo
+        .oauth2_device_code_grant()
+        .lookup(grant_id)
+        .await?
+        .context("Device grant not found")?;
+
+    if grant.is_expired(clock.now()) {
+        return Err(FancyError::from(anyhow::anyhow!("Grant is expired")));
+    }
+
+    let client = repo
+        .oauth2_client()
+        .lookup(grant.client_id)
+        .await?
+        .context("Client not found")?;
+
+    // Evaluate the policy
+    let groups = repo.user_group().list(&session.user).await?;
+    let res = policy
+        .evaluate_device_code_grant(&grant, &client, &session.user, &groups)
+        .await?;
+    if !res.valid() {
+        return Err(FancyError::from(anyhow::anyhow!(
+            "Policy denied the request: {res}"
+        )));
+    }
+
+    let grant = if grant.is_pending() {
+        match form.action {
+            Action::Consent => {
+                repo.oauth2_device_code_grant()
+                    .fulfill(&clock, grant, &session)
+                    .await?
+            }
+            Action::Reject => {
+                repo.oauth2_device_code_grant()
+                    .reject(&clock, grant, &session)
+                    .await?
+            }
+        }
+    } else {
+        // XXX: In case we're not pending, let's just return the grant as-is
+        // since it might just be a form resubmission, and feedback is nice enough
+        grant
+    };
+
+    repo.save().await?;
+
+    let ctx = DeviceConsentContext::new(grant, client)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let rendered = templates.render_device_consent(&ctx)?;
+
+    Ok((cookie_jar, Html(rendered)).into_response())
+}
@@ -0,0 +1,129 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse},
+};
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt, FancyError, SessionInfoExt};
+use mas_router::UrlBuilder;
+use mas_storage::{oauth2::OAuth2DeviceCodeGrantRepository, BoxClock, BoxRepository, BoxRng};
+use mas_templates::{
+    DeviceLinkContext, DeviceLinkFormField, FieldError, FormError, FormState, TemplateContext,
+    Templates,
+};
+
+use crate::{BoundActivityTracker, PreferredLanguage, SiteConfig};
+
+/// Normalise a user code as typed by the user, so that `bcdf-ghjk` and
+/// `BCDF GHJK` both match `BCDFGHJK`
+fn normalize_user_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[tracing::instrument(name = "handlers.oauth2.device.link.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Query(query): Query<mas_router::DeviceCodeLink>,
+) -> Result<impl IntoResponse, FancyError> {
+    let mut form_state = FormState::from_form(&query);
+    let mut retry_after = None;
+
+    // If we have a code in the query, find the grant and redirect to the consent
+    // page
+    if let Some(code) = query.code() {
+        // Limit the number of lookups from a single IP address, as user codes are
+        // short enough to be guessed otherwise
+        if let Some(ip) = activity_tracker.ip() {
+            if let Err(e) = site_config
+                .rate_limiter
+                .check(
+                    &clock,
+                    &format!("device_code_link:ip:{ip}"),
+                    site_config.device_code_link_rate_limit,
+                )
+                .await
+            {
+                retry_after = Some(e.retry_after(clock.now()));
+            }
+        }
+
+        if retry_after.is_some() {
+            form_state.add_error_on_form(FormError::RateLimitExceeded);
+        } else {
+            let code = normalize_user_code(code);
+            let grant = repo
+                .oauth2_device_code_grant()
+                .find_by_user_code(&code)
+                .await?
+                // Only pending and unexpired grants can be linked
+                .filter(|grant| grant.is_pending() && !grant.is_expired(clock.now()));
+
+            if let Some(grant) = grant {
+                let destination = mas_router::DeviceCodeConsent::new(grant.id);
+                return Ok((cookie_jar, url_builder.redirect(&destination)).into_response());
+            }
+
+            form_state.add_error_on_field(DeviceLinkFormField::Code, FieldError::Invalid);
+        }
+    }
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let ctx = DeviceLinkContext::default()
+        .with_form_state(form_state)
+        .maybe_with_session(maybe_session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_device_link(&ctx)?;
+
+    if let Some(retry_after) = retry_after {
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.to_string())],
+            cookie_jar,
+            Html(content),
+        )
+            .into_response());
+    }
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_user_code() {
+        assert_eq!(normalize_user_code("BCDFGHJK"), "BCDFGHJK");
+        assert_eq!(normalize_user_code("bcdf-ghjk"), "BCDFGHJK");
+        assert_eq!(normalize_user_code(" BCDF GHJK "), "BCDFGHJK");
+    }
+}
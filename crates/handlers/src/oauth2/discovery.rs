@@ -13,11 +13,18 @@
 // limitations under the License.
 
 use axum::{extract::State, response::IntoResponse, Json};
-use mas_iana::oauth::{
-    OAuthAuthorizationEndpointResponseType, OAuthClientAuthenticationMethod,
-    PkceCodeChallengeMethod,
+use mas_data_model::SUPPORTED_ACR_VALUES;
+use mas_iana::{
+    jose::JsonWebSignatureAlg,
+    oauth::{
+        OAuthAuthorizationEndpointResponseType, OAuthClientAuthenticationMethod,
+        PkceCodeChallengeMethod,
+    },
+};
+use mas_jose::jwa::{
+    SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS, SUPPORTED_ENCRYPTION_ALGORITHMS,
+    SUPPORTED_SIGNING_ALGORITHMS,
 };
-use mas_jose::jwa::SUPPORTED_SIGNING_ALGORITHMS;
 use mas_keystore::Keystore;
 use mas_router::UrlBuilder;
 use oauth2_types::{
@@ -27,6 +34,8 @@ use oauth2_types::{
 };
 use serde::Serialize;
 
+use super::authorization::response_signing_alg;
+
 #[derive(Debug, Serialize)]
 struct DiscoveryResponse {
     #[serde(flatten)]
@@ -52,6 +61,8 @@ pub(crate) async fn get(
         OAuthClientAuthenticationMethod::ClientSecretPost,
         OAuthClientAuthenticationMethod::ClientSecretJwt,
         OAuthClientAuthenticationMethod::PrivateKeyJwt,
+        OAuthClientAuthenticationMethod::TlsClientAuth,
+        OAuthClientAuthenticationMethod::SelfSignedTlsClientAuth,
         OAuthClientAuthenticationMethod::None,
     ]);
 
@@ -70,6 +81,10 @@ pub(crate) async fn get(
     let revocation_endpoint = Some(url_builder.oauth_revocation_endpoint());
     let userinfo_endpoint = Some(url_builder.oidc_userinfo_endpoint());
     let registration_endpoint = Some(url_builder.oauth_registration_endpoint());
+    let device_authorization_endpoint = Some(url_builder.oauth_device_authorization_endpoint());
+    let pushed_authorization_request_endpoint =
+        Some(url_builder.oauth_pushed_authorization_request_endpoint());
+    let end_session_endpoint = Some(url_builder.oidc_end_session_endpoint());
 
     let scopes_supported = Some(vec![scope::OPENID.to_string(), scope::EMAIL.to_string()]);
 
@@ -79,16 +94,31 @@ pub(crate) async fn get(
         OAuthAuthorizationEndpointResponseType::CodeIdToken.into(),
     ]);
 
-    let response_modes_supported = Some(vec![
+    // Authorization responses can only be signed (JARM) if we have a key for it
+    let authorization_signing_alg = response_signing_alg(&key_store);
+
+    let mut response_modes_supported = vec![
         ResponseMode::FormPost,
         ResponseMode::Query,
         ResponseMode::Fragment,
-    ]);
+    ];
+    if authorization_signing_alg.is_some() {
+        response_modes_supported.extend([
+            ResponseMode::Jwt,
+            ResponseMode::FormPostJwt,
+            ResponseMode::QueryJwt,
+            ResponseMode::FragmentJwt,
+        ]);
+    }
+    let response_modes_supported = Some(response_modes_supported);
+    let authorization_signing_alg_values_supported = authorization_signing_alg.map(|alg| vec![alg]);
 
     let grant_types_supported = Some(vec![
         GrantType::AuthorizationCode,
         GrantType::RefreshToken,
         GrantType::ClientCredentials,
+        GrantType::DeviceCode,
+        GrantType::TokenExchange,
     ]);
 
     let token_endpoint_auth_methods_supported = client_auth_methods_supported.clone();
@@ -114,6 +144,20 @@ pub(crate) async fn get(
     let id_token_signing_alg_values_supported = jwt_signing_alg_values_supported.clone();
     let userinfo_signing_alg_values_supported = jwt_signing_alg_values_supported;
 
+    // ID tokens and userinfo responses can be encrypted to the client keys
+    let id_token_encryption_alg_values_supported = Some(SUPPORTED_ENCRYPTION_ALGORITHMS.to_vec());
+    let id_token_encryption_enc_values_supported =
+        Some(SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS.to_vec());
+    let userinfo_encryption_alg_values_supported = id_token_encryption_alg_values_supported.clone();
+    let userinfo_encryption_enc_values_supported = id_token_encryption_enc_values_supported.clone();
+
+    let acr_values_supported = Some(
+        SUPPORTED_ACR_VALUES
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    );
+
     let display_values_supported = Some(vec![Display::Page]);
 
     let claim_types_supported = Some(vec![ClaimType::Normal]);
@@ -126,16 +170,51 @@ pub(crate) async fn get(
         "exp".to_owned(),
         "nonce".to_owned(),
         "auth_time".to_owned(),
+        "acr".to_owned(),
+        "amr".to_owned(),
         "at_hash".to_owned(),
         "c_hash".to_owned(),
+        "sid".to_owned(),
+        "username".to_owned(),
+        "email".to_owned(),
+        "email_verified".to_owned(),
     ]);
 
-    let claims_parameter_supported = Some(false);
-    let request_parameter_supported = Some(false);
-    let request_uri_parameter_supported = Some(false);
+    let claims_parameter_supported = Some(true);
+
+    // Request objects are verified with `mas-jose`, and are only fetched from
+    // the `request_uri` values the client registered. Encrypted request objects
+    // are not supported, so no `request_object_encryption_*` is advertised.
+    let request_parameter_supported = Some(true);
+    let request_uri_parameter_supported = Some(true);
+    let require_request_uri_registration = Some(true);
+    let request_object_signing_alg_values_supported = Some(SUPPORTED_SIGNING_ALGORITHMS.to_vec());
+
+    // Logout tokens include the `sid` claim, which matches the one in ID tokens
+    let backchannel_logout_supported = Some(true);
+    let backchannel_logout_session_supported = Some(true);
+
+    // Pushed authorization requests are only required for some clients
+    let require_pushed_authorization_requests = Some(false);
 
     let prompt_values_supported = Some(vec![Prompt::None, Prompt::Login, Prompt::Create]);
 
+    let dpop_signing_alg_values_supported = Some(vec![
+        JsonWebSignatureAlg::Rs256,
+        JsonWebSignatureAlg::Rs384,
+        JsonWebSignatureAlg::Rs512,
+        JsonWebSignatureAlg::Ps256,
+        JsonWebSignatureAlg::Ps384,
+        JsonWebSignatureAlg::Ps512,
+        JsonWebSignatureAlg::Es256,
+        JsonWebSignatureAlg::Es384,
+        JsonWebSignatureAlg::Es256K,
+    ]);
+
+    // Access tokens can be bound to the client certificate, for the clients
+    // which opted into it
+    let tls_client_certificate_bound_access_tokens = Some(true);
+
     let standard = ProviderMetadata {
         issuer,
         authorization_endpoint,
@@ -156,16 +235,32 @@ pub(crate) async fn get(
         introspection_endpoint_auth_signing_alg_values_supported,
         code_challenge_methods_supported,
         userinfo_endpoint,
+        acr_values_supported,
         subject_types_supported,
         id_token_signing_alg_values_supported,
+        id_token_encryption_alg_values_supported,
+        id_token_encryption_enc_values_supported,
         userinfo_signing_alg_values_supported,
+        userinfo_encryption_alg_values_supported,
+        userinfo_encryption_enc_values_supported,
         display_values_supported,
         claim_types_supported,
         claims_supported,
         claims_parameter_supported,
+        request_object_signing_alg_values_supported,
         request_parameter_supported,
         request_uri_parameter_supported,
+        require_request_uri_registration,
         prompt_values_supported,
+        device_authorization_endpoint,
+        pushed_authorization_request_endpoint,
+        require_pushed_authorization_requests,
+        dpop_signing_alg_values_supported,
+        tls_client_certificate_bound_access_tokens,
+        end_session_endpoint,
+        backchannel_logout_supported,
+        backchannel_logout_session_supported,
+        authorization_signing_alg_values_supported,
         ..ProviderMetadata::default()
     };
 
@@ -0,0 +1,496 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use hyper::StatusCode;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    sentry::SentryEventID,
+    SessionInfoExt,
+};
+use mas_data_model::{BrowserSession, Client};
+use mas_jose::{claims, jwt::Jwt};
+use mas_keystore::Keystore;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    oauth2::{end_session, OAuth2SessionFilter},
+    BoxClock, BoxRepository, BoxRng, Clock, Pagination, RepositoryAccess,
+};
+use mas_templates::{EndSessionContext, TemplateContext, Templates};
+use oauth2_types::oidc::RpInitiatedLogoutRequest;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{impl_from_error_for_route, BoundActivityTracker, PreferredLanguage};
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error(transparent)]
+    Csrf(#[from] mas_axum_utils::csrf::CsrfError),
+
+    #[error("invalid id_token_hint")]
+    InvalidIdTokenHint,
+
+    #[error("client_id does not match the id_token_hint audience")]
+    ClientMismatch,
+
+    #[error("could not find client")]
+    ClientNotFound,
+
+    #[error("post_logout_redirect_uri requires a client_id or an id_token_hint")]
+    MissingClient,
+
+    #[error("post_logout_redirect_uri is not registered for this client")]
+    UnknownPostLogoutRedirectUri,
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+impl_from_error_for_route!(mas_templates::TemplateError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> Response {
+        let event_id = sentry::capture_error(&self);
+        // TODO: better error pages
+        let response = match self {
+            Self::Internal(_) | Self::Csrf(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+            }
+            Self::InvalidIdTokenHint
+            | Self::ClientMismatch
+            | Self::ClientNotFound
+            | Self::MissingClient
+            | Self::UnknownPostLogoutRedirectUri => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+/// The body of a `POST` request to the end session endpoint
+///
+/// It can either come from the confirmation page we render, in which case it
+/// is CSRF-protected, or directly from a relying party.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum EndSessionForm {
+    Confirmed(ProtectedForm<RpInitiatedLogoutRequest>),
+    Request(RpInitiatedLogoutRequest),
+}
+
+/// A validated RP-initiated logout request
+struct ValidatedRequest {
+    /// The `sub` claim of the `id_token_hint`, if one was provided
+    subject: Option<String>,
+
+    /// The client which initiated the logout, if it could be determined
+    client: Option<Client>,
+
+    /// Where to redirect the user after the logout
+    post_logout_redirect_uri: Option<url::Url>,
+
+    /// The state to pass back to the client
+    state: Option<String>,
+}
+
+/// Validate the `id_token_hint`, the `client_id` and the
+/// `post_logout_redirect_uri` of the request against what we know
+async fn validate_request(
+    repo: &mut BoxRepository,
+    key_store: &Keystore,
+    url_builder: &UrlBuilder,
+    params: RpInitiatedLogoutRequest,
+) -> Result<ValidatedRequest, RouteError> {
+    let mut client_id = params.client_id;
+    let mut subject = None;
+
+    if let Some(id_token_hint) = params.id_token_hint.as_deref() {
+        let jwt = Jwt::<HashMap<String, Value>>::try_from(id_token_hint)
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        jwt.verify_with_jwks(&key_store.public_jwks())
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let (_header, mut claims) = jwt.into_parts();
+
+        // The ID token might have expired already, which is expected here, so we
+        // only check the issuer, subject and audience
+        claims::ISS
+            .extract_required_with_options(&mut claims, url_builder.oidc_issuer().as_str())
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let sub = claims::SUB
+            .extract_required(&mut claims)
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        let aud = claims::AUD
+            .extract_required(&mut claims)
+            .map_err(|_| RouteError::InvalidIdTokenHint)?;
+
+        match &client_id {
+            Some(client_id) if !aud.contains(client_id) => {
+                return Err(RouteError::ClientMismatch);
+            }
+            Some(_) => {}
+            None => client_id = aud.first().cloned(),
+        }
+
+        subject = Some(sub);
+    }
+
+    let client = if let Some(client_id) = client_id {
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await?
+            .ok_or(RouteError::ClientNotFound)?;
+        Some(client)
+    } else {
+        None
+    };
+
+    if let Some(uri) = &params.post_logout_redirect_uri {
+        let client = client.as_ref().ok_or(RouteError::MissingClient)?;
+        if !client.has_post_logout_redirect_uri(uri) {
+            return Err(RouteError::UnknownPostLogoutRedirectUri);
+        }
+    }
+
+    Ok(ValidatedRequest {
+        subject,
+        client,
+        post_logout_redirect_uri: params.post_logout_redirect_uri,
+        state: params.state,
+    })
+}
+
+/// End the given browser session, along with all the OAuth 2.0 sessions which
+/// were started from it
+async fn end_browser_session(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    session: BrowserSession,
+) -> Result<(), RouteError> {
+    let filter = OAuth2SessionFilter::new()
+        .for_browser_session(&session)
+        .active_only();
+
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.oauth2_session().list(filter, pagination).await?;
+
+        for oauth2_session in page.edges {
+            pagination = pagination.after(oauth2_session.id);
+
+            // Derived sessions end along with their parent session, which was
+            // started from the same browser session
+            if oauth2_session.is_derived() {
+                continue;
+            }
+
+            end_session(repo, clock, oauth2_session).await?;
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    repo.browser_session().finish(clock, session).await?;
+
+    Ok(())
+}
+
+/// Redirect the user after the logout, either back to the client or to the
+/// login page
+fn redirect(url_builder: &UrlBuilder, request: ValidatedRequest) -> Response {
+    if let Some(mut uri) = request.post_logout_redirect_uri {
+        if let Some(state) = &request.state {
+            uri.query_pairs_mut().append_pair("state", state);
+        }
+
+        Redirect::to(uri.as_str()).into_response()
+    } else {
+        url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    locale: mas_i18n::DataLocale,
+    templates: Templates,
+    key_store: Keystore,
+    url_builder: UrlBuilder,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    params: RpInitiatedLogoutRequest,
+    confirmed: bool,
+) -> Result<Response, RouteError> {
+    let request = validate_request(&mut repo, &key_store, &url_builder, params).await?;
+
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+    let Some(session) = session_info.load_session(&mut repo).await? else {
+        // There is no session to end, so we can just redirect
+        return Ok((cookie_jar, redirect(&url_builder, request)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // We end the session right away if the user confirmed it, or if the client
+    // gave us an ID token issued for the current user. Otherwise, we ask the
+    // user for confirmation.
+    if confirmed || request.subject.as_deref() == Some(session.user.sub.as_str()) {
+        end_browser_session(&mut repo, &clock, session).await?;
+        repo.save().await?;
+
+        let cookie_jar = cookie_jar.update_session_info(&session_info.mark_session_ended());
+        return Ok((cookie_jar, redirect(&url_builder, request)).into_response());
+    }
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let ctx = EndSessionContext::new(
+        request.client,
+        request.post_logout_redirect_uri,
+        request.state,
+    )
+    .with_session(session)
+    .with_csrf(csrf_token.form_value())
+    .with_language(locale);
+
+    let content = templates.render_end_session(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.oauth2.end_session.get", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get(
+    rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Query(params): Query<RpInitiatedLogoutRequest>,
+) -> Result<Response, RouteError> {
+    handle(
+        rng,
+        clock,
+        locale,
+        templates,
+        key_store,
+        url_builder,
+        repo,
+        activity_tracker,
+        cookie_jar,
+        params,
+        false,
+    )
+    .await
+}
+
+#[tracing::instrument(name = "handlers.oauth2.end_session.post", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn post(
+    rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Form(form): Form<EndSessionForm>,
+) -> Result<Response, RouteError> {
+    let (params, confirmed) = match form {
+        EndSessionForm::Confirmed(form) => (cookie_jar.verify_form(&clock, form)?, true),
+        EndSessionForm::Request(params) => (params, false),
+    };
+
+    handle(
+        rng,
+        clock,
+        locale,
+        templates,
+        key_store,
+        url_builder,
+        repo,
+        activity_tracker,
+        cookie_jar,
+        params,
+        confirmed,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_router::SimpleRoute;
+    use oauth2_types::registration::ClientRegistrationResponse;
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    /// Register a client with a `post_logout_redirect_uri`, log in and run an
+    /// authorization code flow. Returns the client ID and the token response.
+    async fn setup(
+        state: &TestState,
+        cookies: &CookieHelper,
+    ) -> (String, oauth2_types::requests::AccessTokenResponse) {
+        state.create_user("john", "hunter2").await;
+        state.login(cookies, "john", "hunter2").await;
+
+        let request = Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(json!({
+            "contacts": ["hello@example.com"],
+            "client_uri": "https://example.com/",
+            "redirect_uris": ["https://example.com/callback"],
+            "post_logout_redirect_uris": ["https://example.com/logged-out"],
+            "response_types": ["code"],
+            "grant_types": ["authorization_code", "refresh_token"],
+            "token_endpoint_auth_method": "none",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let tokens = state
+            .run_authorization_code_flow(
+                cookies,
+                &client_id,
+                "https://example.com/callback",
+                "openid",
+            )
+            .await;
+
+        (client_id, tokens)
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_end_session_with_id_token_hint(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let (_client_id, tokens) = setup(&state, &cookies).await;
+        let id_token = tokens.id_token.unwrap();
+
+        // An unregistered post_logout_redirect_uri is rejected
+        let query = serde_urlencoded::to_string([
+            ("id_token_hint", id_token.as_str()),
+            ("post_logout_redirect_uri", "https://example.com/elsewhere"),
+        ])
+        .unwrap();
+        let request = Request::get(format!("{}?{query}", mas_router::OidcEndSession::PATH)).empty();
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(state.is_access_token_valid(&tokens.access_token).await);
+
+        // So is a tampered ID token
+        let query =
+            serde_urlencoded::to_string([("id_token_hint", format!("{id_token}x"))]).unwrap();
+        let request = Request::get(format!("{}?{query}", mas_router::OidcEndSession::PATH)).empty();
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(state.is_access_token_valid(&tokens.access_token).await);
+
+        // A valid ID token for the current user logs them out right away
+        let query = serde_urlencoded::to_string([
+            ("id_token_hint", id_token.as_str()),
+            ("post_logout_redirect_uri", "https://example.com/logged-out"),
+            ("state", "abc"),
+        ])
+        .unwrap();
+        let request = Request::get(format!("{}?{query}", mas_router::OidcEndSession::PATH)).empty();
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "https://example.com/logged-out?state=abc");
+
+        // The OAuth 2.0 session ended along with the browser session
+        assert!(!state.is_access_token_valid(&tokens.access_token).await);
+
+        // Doing it again just redirects, as there is no session to end anymore
+        let request = Request::get(format!("{}?{query}", mas_router::OidcEndSession::PATH)).empty();
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "https://example.com/logged-out?state=abc");
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_end_session_confirmation(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        let (client_id, tokens) = setup(&state, &cookies).await;
+
+        // Without an ID token hint, the user is asked for confirmation
+        let query = serde_urlencoded::to_string([
+            ("client_id", client_id.as_str()),
+            ("post_logout_redirect_uri", "https://example.com/logged-out"),
+        ])
+        .unwrap();
+        let request = Request::get(format!("{}?{query}", mas_router::OidcEndSession::PATH)).empty();
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token();
+        assert!(state.is_access_token_valid(&tokens.access_token).await);
+
+        // A POST without the CSRF token isn't enough either
+        let request = Request::post(mas_router::OidcEndSession::PATH).form(json!({
+            "client_id": client_id,
+            "post_logout_redirect_uri": "https://example.com/logged-out",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::OK);
+        assert!(state.is_access_token_valid(&tokens.access_token).await);
+
+        // Confirming through the form ends the sessions
+        let request = Request::post(mas_router::OidcEndSession::PATH).form(json!({
+            "csrf": csrf_token,
+            "client_id": client_id,
+            "post_logout_redirect_uri": "https://example.com/logged-out",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "https://example.com/logged-out");
+        assert!(!state.is_access_token_valid(&tokens.access_token).await);
+    }
+}
@@ -13,29 +13,32 @@
 // limitations under the License.
 
 use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
 use hyper::StatusCode;
 use mas_axum_utils::{
     client_authorization::{ClientAuthorization, CredentialsVerificationError},
     http_client_factory::HttpClientFactory,
     sentry::SentryEventID,
 };
-use mas_data_model::{TokenFormatError, TokenType};
+use mas_data_model::{CompatSession, Device, Session, TokenFormatError, TokenType};
 use mas_iana::oauth::{OAuthClientAuthenticationMethod, OAuthTokenTypeHint};
 use mas_keystore::Encrypter;
 use mas_storage::{
     compat::{CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository},
     oauth2::{OAuth2AccessTokenRepository, OAuth2RefreshTokenRepository, OAuth2SessionRepository},
-    user::UserRepository,
+    user::{BrowserSessionRepository, UserRepository},
     BoxClock, BoxRepository, Clock,
 };
 use oauth2_types::{
     errors::{ClientError, ClientErrorCode},
-    requests::{IntrospectionRequest, IntrospectionResponse},
+    requests::{IntrospectionRequest, IntrospectionResponse, TokenConfirmation},
     scope::ScopeToken,
 };
+use serde::Serialize;
+use serde_with::skip_serializing_none;
 use thiserror::Error;
 
-use crate::{impl_from_error_for_route, ActivityTracker};
+use crate::{impl_from_error_for_route, ActivityTracker, MatrixHomeserver, SiteConfig};
 
 #[derive(Debug, Error)]
 pub enum RouteError {
@@ -161,8 +164,109 @@ const INACTIVE: IntrospectionResponse = IntrospectionResponse {
     aud: None,
     iss: None,
     jti: None,
+    cnf: None,
+    auth_time: None,
+    acr: None,
+    amr: None,
 };
 
+/// The confirmation of the key or certificate the tokens of a session are bound
+/// to, if any
+fn token_confirmation(session: &Session) -> Option<TokenConfirmation> {
+    if !session.is_dpop_bound() && !session.is_certificate_bound() {
+        return None;
+    }
+
+    Some(TokenConfirmation {
+        jkt: session.dpop_jkt.clone(),
+        x5t_s256: session.certificate_thumbprint.clone(),
+    })
+}
+
+/// The time, the Authentication Context Class Reference and the Authentication
+/// Method References of the last authentication of the browser session an
+/// OAuth 2.0 session was started from, if any
+async fn last_authentication(
+    repo: &mut BoxRepository,
+    session: &Session,
+) -> Result<(Option<DateTime<Utc>>, Option<String>, Option<Vec<String>>), RouteError> {
+    let Some(user_session_id) = session.user_session_id else {
+        return Ok((None, None, None));
+    };
+
+    let Some(browser_session) = repo.browser_session().lookup(user_session_id).await? else {
+        return Ok((None, None, None));
+    };
+
+    let Some(authentication) = repo
+        .browser_session()
+        .get_last_authentication(&browser_session)
+        .await?
+    else {
+        return Ok((None, None, None));
+    };
+
+    let method = &authentication.authentication_method;
+    let acr = method.acr().map(ToOwned::to_owned);
+    let amr = method.amr();
+    let amr = (!amr.is_empty()).then(|| amr.iter().map(ToString::to_string).collect());
+
+    Ok((Some(authentication.created_at), acr, amr))
+}
+
+/// Matrix-specific claims, added to the response for the clients allowed to
+/// see them, so that the homeserver doesn't have to derive them from the
+/// username and the scope
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct MatrixClaims {
+    /// The Matrix ID of the user, if the session has one
+    mxid: Option<String>,
+
+    /// The device ID of the session, if it has one
+    device_id: Option<String>,
+
+    /// The name the user gave to the session, which the homeserver can use as
+    /// the display name of the device
+    device_name: Option<String>,
+
+    /// Either `oauth2` or `compat`
+    session_kind: &'static str,
+}
+
+impl MatrixClaims {
+    fn oauth2(homeserver: &MatrixHomeserver, username: Option<&str>, session: &Session) -> Self {
+        Self {
+            mxid: username.map(|username| format!("@{username}:{homeserver}")),
+            device_id: session
+                .scope
+                .iter()
+                .find_map(Device::from_scope_token)
+                .map(|device| device.as_str().to_owned()),
+            device_name: session.human_name.clone(),
+            session_kind: "oauth2",
+        }
+    }
+
+    fn compat(homeserver: &MatrixHomeserver, username: &str, session: &CompatSession) -> Self {
+        Self {
+            mxid: Some(format!("@{username}:{homeserver}")),
+            device_id: Some(session.device.as_str().to_owned()),
+            device_name: session.human_name.clone(),
+            session_kind: "compat",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Response {
+    #[serde(flatten)]
+    response: IntrospectionResponse,
+
+    #[serde(flatten)]
+    matrix: Option<MatrixClaims>,
+}
+
 const API_SCOPE: ScopeToken = ScopeToken::from_static("urn:matrix:org.matrix.msc2967.client:api:*");
 const SYNAPSE_ADMIN_SCOPE: ScopeToken = ScopeToken::from_static("urn:synapse:admin:*");
 
@@ -179,6 +283,8 @@ pub(crate) async fn post(
     mut repo: BoxRepository,
     activity_tracker: ActivityTracker,
     State(encrypter): State<Encrypter>,
+    State(site_config): State<SiteConfig>,
+    State(homeserver): State<MatrixHomeserver>,
     client_authorization: ClientAuthorization<IntrospectionRequest>,
 ) -> Result<impl IntoResponse, RouteError> {
     let client = client_authorization
@@ -215,7 +321,12 @@ pub(crate) async fn post(
     // XXX: we should get the IP from the client introspecting the token
     let ip = None;
 
-    let reply = match token_type {
+    // Only some clients, usually the homeserver, get the Matrix-specific claims
+    let with_matrix_claims = site_config
+        .matrix_introspection_clients
+        .contains(&client.id);
+
+    let (reply, matrix) = match token_type {
         TokenType::AccessToken => {
             let access_token = repo
                 .oauth2_access_token()
@@ -259,7 +370,12 @@ pub(crate) async fn post(
                 .record_oauth2_session(&clock, &session, ip)
                 .await;
 
-            IntrospectionResponse {
+            let cnf = token_confirmation(&session);
+            let (auth_time, acr, amr) = last_authentication(&mut repo, &session).await?;
+            let matrix = with_matrix_claims
+                .then(|| MatrixClaims::oauth2(&homeserver, username.as_deref(), &session));
+
+            let response = IntrospectionResponse {
                 active: true,
                 scope: Some(session.scope),
                 client_id: Some(session.client_id.to_string()),
@@ -269,10 +385,16 @@ pub(crate) async fn post(
                 iat: Some(access_token.created_at),
                 nbf: Some(access_token.created_at),
                 sub,
-                aud: None,
+                aud: session.resource.map(String::from),
                 iss: None,
                 jti: Some(access_token.jti()),
-            }
+                cnf,
+                auth_time,
+                acr,
+                amr,
+            };
+
+            (response, matrix)
         }
 
         TokenType::RefreshToken => {
@@ -318,7 +440,12 @@ pub(crate) async fn post(
                 .record_oauth2_session(&clock, &session, ip)
                 .await;
 
-            IntrospectionResponse {
+            let cnf = token_confirmation(&session);
+            let (auth_time, acr, amr) = last_authentication(&mut repo, &session).await?;
+            let matrix = with_matrix_claims
+                .then(|| MatrixClaims::oauth2(&homeserver, username.as_deref(), &session));
+
+            let response = IntrospectionResponse {
                 active: true,
                 scope: Some(session.scope),
                 client_id: Some(session.client_id.to_string()),
@@ -328,10 +455,16 @@ pub(crate) async fn post(
                 iat: Some(refresh_token.created_at),
                 nbf: Some(refresh_token.created_at),
                 sub,
-                aud: None,
+                aud: session.resource.map(String::from),
                 iss: None,
                 jti: Some(refresh_token.jti()),
-            }
+                cnf,
+                auth_time,
+                acr,
+                amr,
+            };
+
+            (response, matrix)
         }
 
         TokenType::CompatAccessToken => {
@@ -377,7 +510,10 @@ pub(crate) async fn post(
                 .record_compat_session(&clock, &session, ip)
                 .await;
 
-            IntrospectionResponse {
+            let matrix = with_matrix_claims
+                .then(|| MatrixClaims::compat(&homeserver, &user.username, &session));
+
+            let response = IntrospectionResponse {
                 active: true,
                 scope: Some(scope),
                 client_id: Some("legacy".into()),
@@ -390,7 +526,13 @@ pub(crate) async fn post(
                 aud: None,
                 iss: None,
                 jti: None,
-            }
+                cnf: None,
+                auth_time: None,
+                acr: None,
+                amr: None,
+            };
+
+            (response, matrix)
         }
 
         TokenType::CompatRefreshToken => {
@@ -436,7 +578,10 @@ pub(crate) async fn post(
                 .record_compat_session(&clock, &session, ip)
                 .await;
 
-            IntrospectionResponse {
+            let matrix = with_matrix_claims
+                .then(|| MatrixClaims::compat(&homeserver, &user.username, &session));
+
+            let response = IntrospectionResponse {
                 active: true,
                 scope: Some(scope),
                 client_id: Some("legacy".into()),
@@ -449,11 +594,20 @@ pub(crate) async fn post(
                 aud: None,
                 iss: None,
                 jti: None,
-            }
+                cnf: None,
+                auth_time: None,
+                acr: None,
+                amr: None,
+            };
+
+            (response, matrix)
         }
     };
 
-    Ok(Json(reply))
+    Ok(Json(Response {
+        response: reply,
+        matrix,
+    }))
 }
 
 #[cfg(test)]
@@ -827,4 +981,100 @@ mod tests {
         let response: IntrospectionResponse = response.json();
         assert!(response.active);
     }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_introspect_matrix_claims(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision two clients which will be used to do introspection requests,
+        // only one of them being allowed to see the Matrix-specific claims
+        let mut introspecting_clients = Vec::new();
+        for name in ["homeserver", "other"] {
+            let request = Request::post(OAuth2RegistrationEndpoint::PATH).json(json!({
+                "contacts": [format!("hello@{name}.com")],
+                "client_uri": format!("https://{name}.com/"),
+                "grant_types": [],
+                "token_endpoint_auth_method": "client_secret_basic",
+            }));
+
+            let response = state.request(request).await;
+            response.assert_status(StatusCode::CREATED);
+            let client: ClientRegistrationResponse = response.json();
+            introspecting_clients.push((client.client_id, client.client_secret.unwrap()));
+        }
+
+        state.site_config.matrix_introspection_clients =
+            vec![introspecting_clients[0].0.parse().unwrap()].into();
+
+        // Provision a user with a password, so that we can use the password flow
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let (version, hashed_password) = state
+            .password_manager
+            .hash(&mut state.rng(), Zeroizing::new(b"password".to_vec()))
+            .await
+            .unwrap();
+
+        repo.user_password()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                version,
+                hashed_password,
+                None,
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let request = Request::post("/_matrix/client/v3/login").json(json!({
+            "type": "m.login.password",
+            "identifier": {
+                "type": "m.id.user",
+                "user": "alice",
+            },
+            "password": "password",
+            "initial_device_display_name": "Work laptop",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        let access_token = response["access_token"].as_str().unwrap();
+        let device_id = response["device_id"].as_str().unwrap();
+
+        let (client_id, client_secret) = &introspecting_clients[0];
+        let request = Request::post(OAuth2Introspection::PATH)
+            .basic_auth(client_id, client_secret)
+            .form(json!({ "token": access_token }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        assert_eq!(response["active"], true);
+        assert_eq!(response["mxid"], "@alice:example.com");
+        assert_eq!(response["device_id"], device_id);
+        assert_eq!(response["device_name"], "Work laptop");
+        assert_eq!(response["session_kind"], "compat");
+
+        // The other client doesn't get them
+        let (client_id, client_secret) = &introspecting_clients[1];
+        let request = Request::post(OAuth2Introspection::PATH)
+            .basic_auth(client_id, client_secret)
+            .form(json!({ "token": access_token }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        assert_eq!(response["active"], true);
+        assert!(response.get("mxid").is_none());
+        assert!(response.get("device_id").is_none());
+        assert!(response.get("device_name").is_none());
+        assert!(response.get("session_kind").is_none());
+    }
 }
@@ -15,26 +15,34 @@
 use std::collections::HashMap;
 
 use chrono::Duration;
+use mas_axum_utils::{client_authorization::fetch_jwks, http_client_factory::HttpClientFactory};
 use mas_data_model::{
     AccessToken, Authentication, AuthorizationGrant, BrowserSession, Client, RefreshToken, Session,
-    TokenType,
+    TokenType, User,
 };
-use mas_iana::jose::JsonWebSignatureAlg;
+use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg};
 use mas_jose::{
     claims::{self, hash_token},
     constraints::Constrainable,
+    jwe::{self, JsonWebEncryptionHeader},
     jwt::{JsonWebSignatureHeader, Jwt},
 };
 use mas_keystore::Keystore;
 use mas_router::UrlBuilder;
-use mas_storage::{Clock, RepositoryAccess};
+use mas_storage::{user::UserEmailRepository, Clock, RepositoryAccess};
+use serde_json::Value;
 use thiserror::Error;
+use tower::BoxError;
+use url::Url;
 
 pub mod authorization;
 pub mod consent;
+pub mod device;
 pub mod discovery;
+pub mod end_session;
 pub mod introspection;
 pub mod keys;
+pub mod par;
 pub mod registration;
 pub mod revoke;
 pub mod token;
@@ -52,31 +60,145 @@ pub(crate) enum IdTokenSignatureError {
     TokenHash(#[from] mas_jose::claims::TokenHashError),
 }
 
+#[derive(Debug, Error)]
+pub(crate) enum ResponseEncryptionError {
+    #[error("The client has no JWKS to encrypt responses to")]
+    NoJwks,
+
+    #[error("Failed to fetch the client JWKS")]
+    FetchJwks(#[source] BoxError),
+
+    #[error("No key of the client JWKS is suitable for {alg}")]
+    NoSuitableKey { alg: JsonWebEncryptionAlg },
+
+    #[error(transparent)]
+    Encryption(#[from] jwe::JweEncryptionError),
+}
+
+/// Encrypt a response for the given client as a JWE, using one of the keys of
+/// its JWKS
+pub(crate) async fn encrypt_for_client(
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+    http_client_factory: &HttpClientFactory,
+    client: &Client,
+    (alg, enc): (&JsonWebEncryptionAlg, &JsonWebEncryptionEnc),
+    cty: Option<&str>,
+    payload: &[u8],
+) -> Result<String, ResponseEncryptionError> {
+    let jwks = client
+        .jwks
+        .as_ref()
+        .ok_or(ResponseEncryptionError::NoJwks)?;
+    let jwks = fetch_jwks(http_client_factory, jwks)
+        .await
+        .map_err(ResponseEncryptionError::FetchJwks)?;
+
+    let key = jwe::find_encryption_key(&jwks, alg)
+        .ok_or_else(|| ResponseEncryptionError::NoSuitableKey { alg: alg.clone() })?;
+
+    let mut header = JsonWebEncryptionHeader::new(alg.clone(), enc.clone());
+    if let Some(cty) = cty {
+        header = header.with_cty(cty.to_owned());
+    }
+
+    Ok(jwe::encrypt(rng, header, key, payload)?)
+}
+
+/// Encrypt an ID token if the client asked for it, by nesting it in a JWE
+pub(crate) async fn encrypt_id_token(
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+    http_client_factory: &HttpClientFactory,
+    client: &Client,
+    id_token: String,
+) -> Result<String, ResponseEncryptionError> {
+    let Some(encrypted_response) = client.id_token_encrypted_response() else {
+        return Ok(id_token);
+    };
+
+    encrypt_for_client(
+        rng,
+        http_client_factory,
+        client,
+        encrypted_response,
+        Some("JWT"),
+        id_token.as_bytes(),
+    )
+    .await
+}
+
+/// Gather the claims about the user that the client asked to get in the ID
+/// token with the `claims` parameter
+pub(crate) async fn requested_id_token_claims<R: RepositoryAccess>(
+    repo: &mut R,
+    session: &Session,
+    user: &User,
+) -> Result<HashMap<String, Value>, R::Error> {
+    let mut claims = HashMap::new();
+    let Some(requested) = &session.claims else {
+        return Ok(claims);
+    };
+
+    if requested.requests_id_token_claim("username") {
+        claims.insert("username".to_owned(), user.username.clone().into());
+    }
+
+    let wants_email = requested.requests_id_token_claim("email");
+    let wants_email_verified = requested.requests_id_token_claim("email_verified");
+    if wants_email || wants_email_verified {
+        if let Some(user_email) = repo.user_email().get_primary(user).await? {
+            if wants_email_verified {
+                claims.insert("email_verified".to_owned(), user_email.is_verified().into());
+            }
+
+            if wants_email {
+                claims.insert("email".to_owned(), user_email.email.into());
+            }
+        }
+    }
+
+    Ok(claims)
+}
+
 pub(crate) fn generate_id_token(
     rng: &mut (impl rand::RngCore + rand::CryptoRng),
     clock: &impl Clock,
     url_builder: &UrlBuilder,
     key_store: &Keystore,
     client: &Client,
-    grant: &AuthorizationGrant,
+    grant: Option<&AuthorizationGrant>,
     browser_session: &BrowserSession,
     access_token: Option<&AccessToken>,
     last_authentication: Option<&Authentication>,
+    user_claims: HashMap<String, Value>,
 ) -> Result<String, IdTokenSignatureError> {
-    let mut claims = HashMap::new();
+    let mut claims = user_claims;
     let now = clock.now();
     claims::ISS.insert(&mut claims, url_builder.oidc_issuer().to_string())?;
     claims::SUB.insert(&mut claims, &browser_session.user.sub)?;
     claims::AUD.insert(&mut claims, client.client_id.clone())?;
     claims::IAT.insert(&mut claims, now)?;
     claims::EXP.insert(&mut claims, now + Duration::hours(1))?;
+    claims::SID.insert(&mut claims, browser_session.id.to_string())?;
 
-    if let Some(ref nonce) = grant.nonce {
+    if let Some(nonce) = grant.and_then(|grant| grant.nonce.as_ref()) {
         claims::NONCE.insert(&mut claims, nonce.clone())?;
     }
 
     if let Some(last_authentication) = last_authentication {
         claims::AUTH_TIME.insert(&mut claims, last_authentication.created_at)?;
+
+        let method = &last_authentication.authentication_method;
+        if let Some(acr) = method.acr() {
+            claims::ACR.insert(&mut claims, acr.to_owned())?;
+        }
+
+        let amr = method.amr();
+        if !amr.is_empty() {
+            claims::AMR.insert(
+                &mut claims,
+                amr.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            )?;
+        }
     }
 
     let alg = client
@@ -91,7 +213,7 @@ pub(crate) fn generate_id_token(
         claims::AT_HASH.insert(&mut claims, hash_token(&alg, &access_token.access_token)?)?;
     }
 
-    if let Some(ref code) = grant.code {
+    if let Some(code) = grant.and_then(|grant| grant.code.as_ref()) {
         claims::C_HASH.insert(&mut claims, hash_token(&alg, &code.code)?)?;
     }
 
@@ -125,3 +247,11 @@ pub(crate) async fn generate_token_pair<R: RepositoryAccess>(
 
     Ok((access_token, refresh_token))
 }
+
+/// Check that a resource indicator is acceptable: it must be an absolute URI,
+/// which [`Url`] always is, and must not have a fragment
+///
+/// See [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2)
+pub(crate) fn resource_is_valid(resource: &Url) -> bool {
+    resource.fragment().is_none()
+}
@@ -0,0 +1,361 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::Duration;
+use hyper::StatusCode;
+use mas_axum_utils::{
+    client_authorization::{ClientAuthorization, CredentialsVerificationError},
+    http_client_factory::HttpClientFactory,
+    sentry::SentryEventID,
+};
+use mas_keystore::Encrypter;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    oauth2::OAuth2PushedAuthorizationRequestRepository, BoxClock, BoxRepository, BoxRng,
+};
+use oauth2_types::{
+    errors::{ClientError, ClientErrorCode},
+    requests::PushedAuthorizationResponse,
+};
+use thiserror::Error;
+
+use super::authorization::{request_object::RequestObjectError, Params};
+use crate::impl_from_error_for_route;
+
+/// How long a pushed authorization request can be used for
+const PUSHED_AUTHORIZATION_REQUEST_LIFETIME_SECONDS: i64 = 60;
+
+#[derive(Debug, Error)]
+pub(crate) enum RouteError {
+    #[error(transparent)]
+    Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+
+    #[error("bad request")]
+    BadRequest,
+
+    #[error("invalid authorization request")]
+    InvalidRequest(#[from] serde_urlencoded::de::Error),
+
+    #[error("invalid redirect uri")]
+    InvalidRedirectUri(#[from] mas_data_model::InvalidRedirectUriError),
+
+    #[error("invalid request object")]
+    InvalidRequestObject(#[from] RequestObjectError),
+
+    #[error("client not found")]
+    ClientNotFound,
+
+    #[error("client not allowed")]
+    ClientNotAllowed,
+
+    #[error("could not verify client credentials")]
+    ClientCredentialsVerification(#[from] CredentialsVerificationError),
+}
+
+impl_from_error_for_route!(mas_storage::RepositoryError);
+
+impl IntoResponse for RouteError {
+    fn into_response(self) -> axum::response::Response {
+        let event_id = sentry::capture_error(&self);
+        let response = match self {
+            Self::Internal(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ClientError::from(ClientErrorCode::ServerError)),
+            ),
+            Self::BadRequest | Self::InvalidRequest(_) | Self::InvalidRedirectUri(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidRequest)),
+            ),
+            Self::InvalidRequestObject(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidRequestObject)),
+            ),
+            Self::ClientNotFound | Self::ClientCredentialsVerification(_) => (
+                StatusCode::UNAUTHORIZED,
+                Json(ClientError::from(ClientErrorCode::InvalidClient)),
+            ),
+            Self::ClientNotAllowed => (
+                StatusCode::UNAUTHORIZED,
+                Json(ClientError::from(ClientErrorCode::UnauthorizedClient)),
+            ),
+        };
+
+        (SentryEventID::from(event_id), response).into_response()
+    }
+}
+
+#[tracing::instrument(
+    name = "handlers.oauth2.par.post",
+    fields(client.id = client_authorization.client_id()),
+    skip_all,
+    err,
+)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(encrypter): State<Encrypter>,
+    State(url_builder): State<UrlBuilder>,
+    client_authorization: ClientAuthorization<BTreeMap<String, String>>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = client_authorization
+        .credentials
+        .fetch(&mut repo)
+        .await?
+        .ok_or(RouteError::ClientNotFound)?;
+
+    let method = client
+        .token_endpoint_auth_method
+        .as_ref()
+        .ok_or(RouteError::ClientNotAllowed)?;
+
+    client_authorization
+        .credentials
+        .verify(&http_client_factory, &encrypter, method, &client)
+        .await?;
+
+    let parameters = client_authorization.form.ok_or(RouteError::BadRequest)?;
+
+    // A pushed request can't itself refer to another request
+    if parameters.contains_key("request_uri") {
+        return Err(RouteError::BadRequest);
+    }
+
+    // If the parameters are in a request object, verify it now and store the
+    // parameters it contains
+    let parameters = if let Some(request) = parameters.get("request") {
+        super::authorization::request_object::verify(
+            &http_client_factory,
+            &encrypter,
+            &url_builder,
+            &clock,
+            &client,
+            request,
+        )
+        .await?
+    } else {
+        parameters
+    };
+
+    // Validate the request the same way the authorization endpoint would, so
+    // that the client gets the error now rather than the user later
+    let params = Params::parse(&client.client_id, &parameters)?;
+    client.resolve_redirect_uri(params.redirect_uri())?;
+
+    let expires_in = Duration::seconds(PUSHED_AUTHORIZATION_REQUEST_LIFETIME_SECONDS);
+    let request = repo
+        .oauth2_pushed_authorization_request()
+        .add(&mut rng, &clock, &client, parameters, expires_in)
+        .await?;
+
+    repo.save().await?;
+
+    let response = PushedAuthorizationResponse {
+        request_uri: request.request_uri(),
+        expires_in,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::PushedAuthorizationRequest;
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{oauth2::OAuth2ClientRepository, Clock, RepositoryAccess};
+    use oauth2_types::{
+        errors::{ClientError, ClientErrorCode},
+        requests::PushedAuthorizationResponse,
+    };
+    use sqlx::PgPool;
+    use ulid::Ulid;
+    use url::Url;
+
+    use crate::test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState};
+
+    const REDIRECT_URI: &str = "https://example.com/callback";
+
+    fn authorize_with_request_uri(client_id: &str, request_uri: &str) -> hyper::Request<String> {
+        let query =
+            serde_urlencoded::to_string([("client_id", client_id), ("request_uri", request_uri)])
+                .unwrap();
+
+        Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_pushed_authorization_request(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = state.register_client(REDIRECT_URI).await;
+
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": REDIRECT_URI,
+                "scope": "openid",
+                "state": "state",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: PushedAuthorizationResponse = response.json();
+        assert!(response
+            .request_uri
+            .starts_with(PushedAuthorizationRequest::REQUEST_URI_PREFIX));
+        assert_eq!(response.expires_in.num_seconds(), 60);
+
+        // The client can then use it to start the authorization flow
+        let request = authorize_with_request_uri(&client_id, &response.request_uri);
+        let response_ = state.request(request).await;
+        response_.assert_status(StatusCode::SEE_OTHER);
+        assert!(response_.location().starts_with("/login"));
+
+        // But only once
+        let request = authorize_with_request_uri(&client_id, &response.request_uri);
+        let response_ = state.request(request).await;
+        response_.assert_status(StatusCode::BAD_REQUEST);
+
+        // Other clients can't use it either
+        let other_client_id = state.register_client(REDIRECT_URI).await;
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: PushedAuthorizationResponse = response.json();
+
+        let request = authorize_with_request_uri(&other_client_id, &response.request_uri);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_invalid_pushed_authorization_request(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let client_id = state.register_client(REDIRECT_URI).await;
+
+        // The redirect URI must be registered
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "redirect_uri": "https://example.com/other",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidRequest);
+
+        // Pushed requests can't refer to other requests
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client_id,
+                "response_type": "code",
+                "scope": "openid",
+                "request_uri": "https://example.com/request",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Unknown clients are rejected
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": Ulid::nil().to_string(),
+                "response_type": "code",
+                "scope": "openid",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_require_pushed_authorization_requests(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_datetime_with_source(state.clock.now().into(), &mut state.rng()),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec![REDIRECT_URI.parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                true,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Passing the parameters directly is not allowed
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &client.client_id),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", "openid"),
+            ("state", "state"),
+        ])
+        .unwrap();
+        let request = Request::get(format!(
+            "{}?{query}",
+            mas_router::OAuth2AuthorizationEndpoint::PATH
+        ))
+        .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let location = Url::parse(response.location()).unwrap();
+        assert!(location
+            .query_pairs()
+            .any(|(key, value)| key == "error" && value == "invalid_request"));
+
+        // Pushing them first works
+        let request = Request::post(mas_router::OAuth2PushedAuthorizationRequestEndpoint::PATH)
+            .form(serde_json::json!({
+                "client_id": client.client_id,
+                "response_type": "code",
+                "scope": "openid",
+                "state": "state",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: PushedAuthorizationResponse = response.json();
+
+        let request = authorize_with_request_uri(&client.client_id, &response.request_uri);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert!(response.location().starts_with("/login"));
+    }
+}
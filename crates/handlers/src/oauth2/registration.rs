@@ -12,23 +12,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use axum::{extract::State, response::IntoResponse, Json};
-use hyper::StatusCode;
+use std::string::FromUtf8Error;
+
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json, TypedHeader,
+};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use headers::{authorization::Bearer, Authorization};
+use hyper::{header::WWW_AUTHENTICATE, StatusCode};
 use mas_axum_utils::sentry::SentryEventID;
-use mas_iana::oauth::OAuthClientAuthenticationMethod;
-use mas_keystore::Encrypter;
+use mas_data_model::{Client, JwksOrJwksUri};
+use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_jose::{
+    jwa::{
+        SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS, SUPPORTED_ENCRYPTION_ALGORITHMS,
+        SUPPORTED_SIGNING_ALGORITHMS,
+    },
+    jwe::find_encryption_key,
+};
+use mas_keystore::{DecryptError, Encrypter, Keystore};
 use mas_policy::{Policy, Violation};
+use mas_router::UrlBuilder;
 use mas_storage::{oauth2::OAuth2ClientRepository, BoxClock, BoxRepository, BoxRng};
 use oauth2_types::{
     errors::{ClientError, ClientErrorCode},
     registration::{
         ClientMetadata, ClientMetadataVerificationError, ClientRegistrationResponse, Localized,
+        VerifiedClientMetadata,
     },
 };
 use psl::Psl;
-use rand::distributions::{Alphanumeric, DistString};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    RngCore,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use tracing::info;
+use ulid::Ulid;
 use url::Url;
 
 use crate::impl_from_error_for_route;
@@ -47,14 +71,43 @@ pub(crate) enum RouteError {
     #[error("{0} is a public suffix, not a valid domain")]
     UrlIsPublicSuffix(&'static str),
 
+    #[error("request_uris must be https URLs on a domain name")]
+    InsecureRequestUri,
+
+    #[error("{field} {alg} is not supported by this server")]
+    UnsupportedSigningAlgorithm {
+        field: &'static str,
+        alg: JsonWebSignatureAlg,
+    },
+
+    #[error("{field} {alg} is not supported by this server")]
+    UnsupportedEncryptionAlgorithm { field: &'static str, alg: String },
+
+    #[error("{field} needs a key in the client JWKS to encrypt responses with")]
+    NoEncryptionKey { field: &'static str },
+
     #[error("denied by the policy: {0:?}")]
     PolicyDenied(Vec<Violation>),
+
+    #[error("missing registration access token")]
+    MissingAuthorization,
+
+    #[error("invalid registration access token")]
+    InvalidAuthorization,
+
+    #[error("client_id in the request does not match the registered client")]
+    ClientIdMismatch,
+
+    #[error("client_secret in the request does not match the registered client")]
+    ClientSecretMismatch,
 }
 
 impl_from_error_for_route!(mas_storage::RepositoryError);
 impl_from_error_for_route!(mas_policy::LoadError);
 impl_from_error_for_route!(mas_policy::EvaluationError);
 impl_from_error_for_route!(mas_keystore::aead::Error);
+impl_from_error_for_route!(DecryptError);
+impl_from_error_for_route!(FromUtf8Error);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
@@ -128,6 +181,20 @@ impl IntoResponse for RouteError {
             )
                 .into_response(),
 
+            // This error happens if the client asked for its tokens to be signed or encrypted
+            // with an algorithm we don't support, or for which no key is available
+            e @ (Self::InsecureRequestUri
+            | Self::UnsupportedSigningAlgorithm { .. }
+            | Self::UnsupportedEncryptionAlgorithm { .. }
+            | Self::NoEncryptionKey { .. }) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidClientMetadata)
+                        .with_description(e.to_string()),
+                ),
+            )
+                .into_response(),
+
             // For policy violations, we return an `invalid_client_metadata` error with the details
             // of the violations in most cases. If a violation includes `redirect_uri` in the
             // message, we return an `invalid_redirect_uri` error instead.
@@ -151,6 +218,26 @@ impl IntoResponse for RouteError {
                 )
                     .into_response()
             }
+
+            // Errors on the registration access token are reported as described in RFC 6750
+            Self::MissingAuthorization => {
+                (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response()
+            }
+
+            Self::InvalidAuthorization => (
+                StatusCode::UNAUTHORIZED,
+                [(WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#)],
+            )
+                .into_response(),
+
+            e @ (Self::ClientIdMismatch | Self::ClientSecretMismatch) => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest)
+                        .with_description(e.to_string()),
+                ),
+            )
+                .into_response(),
         };
 
         (SentryEventID::from(event_id), response).into_response()
@@ -187,20 +274,13 @@ fn localised_url_has_public_suffix(url: &Localized<Url>) -> bool {
     url.iter().any(|(_lang, url)| host_is_public_suffix(url))
 }
 
-#[tracing::instrument(name = "handlers.oauth2.registration.post", skip_all, err)]
-pub(crate) async fn post(
-    mut rng: BoxRng,
-    clock: BoxClock,
-    mut repo: BoxRepository,
-    mut policy: Policy,
-    State(encrypter): State<Encrypter>,
-    body: Result<Json<ClientMetadata>, axum::extract::rejection::JsonRejection>,
-) -> Result<impl IntoResponse, RouteError> {
-    // Propagate any JSON extraction error
-    let Json(body) = body?;
-
-    info!(?body, "Client registration");
-
+/// Validate the client metadata sent by a client, either on registration or
+/// when updating its registration
+async fn validate_metadata(
+    body: ClientMetadata,
+    key_store: &Keystore,
+    policy: &mut Policy,
+) -> Result<VerifiedClientMetadata, RouteError> {
     // Validate the body
     let metadata = body.validate()?;
 
@@ -242,24 +322,303 @@ pub(crate) async fn post(
         }
     }
 
+    for post_logout_redirect_uri in metadata.post_logout_redirect_uris.iter().flatten() {
+        if host_is_public_suffix(post_logout_redirect_uri) {
+            return Err(RouteError::UrlIsPublicSuffix("post_logout_redirect_uri"));
+        }
+    }
+
+    if let Some(backchannel_logout_uri) = &metadata.backchannel_logout_uri {
+        if host_is_public_suffix(backchannel_logout_uri) {
+            return Err(RouteError::UrlIsPublicSuffix("backchannel_logout_uri"));
+        }
+    }
+
+    // The authorization endpoint fetches request objects from those, so they
+    // have to point to a domain name, not to an IP address in our network, over
+    // a secure connection
+    for request_uri in metadata.request_uris.iter().flatten() {
+        if request_uri.scheme() != "https"
+            || !matches!(request_uri.host(), Some(url::Host::Domain(_)))
+        {
+            return Err(RouteError::InsecureRequestUri);
+        }
+
+        if host_is_public_suffix(request_uri) {
+            return Err(RouteError::UrlIsPublicSuffix("request_uri"));
+        }
+    }
+
+    // Make sure we can sign the ID tokens and userinfo responses with the
+    // algorithms the client asked for
+    if let Some(alg) = &metadata.id_token_signed_response_alg {
+        if key_store.signing_key_for_algorithm(alg).is_none() {
+            return Err(RouteError::UnsupportedSigningAlgorithm {
+                field: "id_token_signed_response_alg",
+                alg: alg.clone(),
+            });
+        }
+    }
+
+    if let Some(alg) = &metadata.userinfo_signed_response_alg {
+        if key_store.signing_key_for_algorithm(alg).is_none() {
+            return Err(RouteError::UnsupportedSigningAlgorithm {
+                field: "userinfo_signed_response_alg",
+                alg: alg.clone(),
+            });
+        }
+    }
+
+    // Request objects are verified with `mas-jose`, which doesn't accept
+    // unsigned ones
+    if let Some(alg) = &metadata.request_object_signing_alg {
+        if !SUPPORTED_SIGNING_ALGORITHMS.contains(alg) {
+            return Err(RouteError::UnsupportedSigningAlgorithm {
+                field: "request_object_signing_alg",
+                alg: alg.clone(),
+            });
+        }
+    }
+
+    // Make sure we can encrypt the ID tokens and userinfo responses with the
+    // algorithms the client asked for, and that it gave us keys to encrypt them
+    // to. Keys behind a `jwks_uri` are only checked when encrypting.
+    for (alg_field, enc_field, encrypted_response) in [
+        (
+            "id_token_encrypted_response_alg",
+            "id_token_encrypted_response_enc",
+            metadata.id_token_encrypted_response(),
+        ),
+        (
+            "userinfo_encrypted_response_alg",
+            "userinfo_encrypted_response_enc",
+            metadata.userinfo_encrypted_response(),
+        ),
+    ] {
+        let Some((alg, enc)) = encrypted_response else {
+            continue;
+        };
+
+        if !SUPPORTED_ENCRYPTION_ALGORITHMS.contains(alg) {
+            return Err(RouteError::UnsupportedEncryptionAlgorithm {
+                field: alg_field,
+                alg: alg.to_string(),
+            });
+        }
+
+        if !SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS.contains(enc) {
+            return Err(RouteError::UnsupportedEncryptionAlgorithm {
+                field: enc_field,
+                alg: enc.to_string(),
+            });
+        }
+
+        let has_key = match (&metadata.jwks, &metadata.jwks_uri) {
+            (Some(jwks), _) => find_encryption_key(jwks, alg).is_some(),
+            (None, Some(_)) => true,
+            (None, None) => false,
+        };
+
+        if !has_key {
+            return Err(RouteError::NoEncryptionKey { field: alg_field });
+        }
+    }
+
     let res = policy.evaluate_client_registration(&metadata).await?;
     if !res.valid() {
         return Err(RouteError::PolicyDenied(res.violations));
     }
 
-    let (client_secret, encrypted_client_secret) = match metadata.token_endpoint_auth_method {
+    Ok(metadata)
+}
+
+/// Whether the given authentication method needs a client secret
+fn requires_client_secret(method: Option<&OAuthClientAuthenticationMethod>) -> bool {
+    matches!(
+        method,
         Some(
             OAuthClientAuthenticationMethod::ClientSecretJwt
-            | OAuthClientAuthenticationMethod::ClientSecretPost
-            | OAuthClientAuthenticationMethod::ClientSecretBasic,
-        ) => {
+                | OAuthClientAuthenticationMethod::ClientSecretPost
+                | OAuthClientAuthenticationMethod::ClientSecretBasic,
+        )
+    )
+}
+
+/// Generate a random client secret, returning it along with its encrypted
+/// form
+fn generate_client_secret(
+    rng: &mut impl RngCore,
+    encrypter: &Encrypter,
+) -> Result<(String, String), RouteError> {
+    let client_secret = Alphanumeric.sample_string(rng, 20);
+    let encrypted_client_secret = encrypter.encrypt_to_string(client_secret.as_bytes())?;
+    Ok((client_secret, encrypted_client_secret))
+}
+
+/// Hash a registration access token. Only the hash is stored, so that a
+/// database leak doesn't allow managing the clients
+fn hash_registration_access_token(token: &str) -> String {
+    Base64UrlUnpadded::encode_string(&Sha256::digest(token.as_bytes()))
+}
+
+/// Generate a new registration access token for the given client and store
+/// its hash
+async fn issue_registration_access_token(
+    rng: &mut (impl RngCore + Send),
+    repo: &mut BoxRepository,
+    client: &Client,
+) -> Result<String, RouteError> {
+    let token = Alphanumeric.sample_string(rng, 32);
+    repo.oauth2_client()
+        .set_registration_access_token_hash(client, &hash_registration_access_token(&token))
+        .await?;
+    Ok(token)
+}
+
+/// Load the client identified by `id`, checking that the request is
+/// authenticated with its registration access token
+async fn authenticate_client(
+    repo: &mut BoxRepository,
+    id: Ulid,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<Client, RouteError> {
+    let TypedHeader(authorization) = authorization.ok_or(RouteError::MissingAuthorization)?;
+    let hash = hash_registration_access_token(authorization.token());
+
+    repo.oauth2_client()
+        .find_by_registration_access_token_hash(&hash)
+        .await?
+        .filter(|client| client.id == id)
+        .ok_or(RouteError::InvalidAuthorization)
+}
+
+/// Get the client secret of a client, in clear
+fn decrypt_client_secret(
+    encrypter: &Encrypter,
+    client: &Client,
+) -> Result<Option<String>, RouteError> {
+    client
+        .encrypted_client_secret
+        .as_deref()
+        .map(|encrypted_client_secret| {
+            let decrypted = encrypter.decrypt_string(encrypted_client_secret)?;
+            let decrypted = String::from_utf8(decrypted)?;
+            Ok::<_, RouteError>(decrypted)
+        })
+        .transpose()
+}
+
+/// Rebuild the client metadata from a registered client
+fn client_metadata(client: &Client) -> ClientMetadata {
+    let (jwks, jwks_uri) = match &client.jwks {
+        Some(JwksOrJwksUri::Jwks(jwks)) => (Some(jwks.clone()), None),
+        Some(JwksOrJwksUri::JwksUri(jwks_uri)) => (None, Some(jwks_uri.clone())),
+        None => (None, None),
+    };
+
+    ClientMetadata {
+        redirect_uris: Some(client.redirect_uris.clone()),
+        grant_types: Some(client.grant_types.clone()),
+        application_type: client.application_type,
+        contacts: Some(client.contacts.clone()).filter(|c| !c.is_empty()),
+        client_name: client.client_name.clone().map(|v| Localized::new(v, [])),
+        logo_uri: client.logo_uri.clone().map(|v| Localized::new(v, [])),
+        client_uri: client.client_uri.clone().map(|v| Localized::new(v, [])),
+        policy_uri: client.policy_uri.clone().map(|v| Localized::new(v, [])),
+        tos_uri: client.tos_uri.clone().map(|v| Localized::new(v, [])),
+        jwks_uri,
+        jwks,
+        token_endpoint_auth_method: client.token_endpoint_auth_method.clone(),
+        token_endpoint_auth_signing_alg: client.token_endpoint_auth_signing_alg.clone(),
+        request_object_signing_alg: client.request_object_signing_alg.clone(),
+        request_uris: Some(client.request_uris.clone()).filter(|u| !u.is_empty()),
+        id_token_signed_response_alg: client.id_token_signed_response_alg.clone(),
+        userinfo_signed_response_alg: client.userinfo_signed_response_alg.clone(),
+        id_token_encrypted_response_alg: client.id_token_encrypted_response_alg.clone(),
+        id_token_encrypted_response_enc: client.id_token_encrypted_response_enc.clone(),
+        userinfo_encrypted_response_alg: client.userinfo_encrypted_response_alg.clone(),
+        userinfo_encrypted_response_enc: client.userinfo_encrypted_response_enc.clone(),
+        initiate_login_uri: client.initiate_login_uri.clone(),
+        post_logout_redirect_uris: Some(client.post_logout_redirect_uris.clone())
+            .filter(|u| !u.is_empty()),
+        backchannel_logout_uri: client.backchannel_logout_uri.clone(),
+        backchannel_logout_session_required: Some(client.backchannel_logout_session_required),
+        require_pushed_authorization_requests: Some(client.require_pushed_authorization_requests),
+        ..ClientMetadata::default()
+    }
+}
+
+/// The response of the client configuration endpoint, as defined in RFC 7592.
+/// It has the registered client metadata alongside its credentials
+#[derive(Serialize)]
+struct ClientConfigurationResponse {
+    #[serde(flatten)]
+    credentials: ClientRegistrationResponse,
+
+    #[serde(flatten)]
+    metadata: ClientMetadata,
+}
+
+impl ClientConfigurationResponse {
+    fn new(url_builder: &UrlBuilder, client: &Client, client_secret: Option<String>) -> Self {
+        Self {
+            credentials: ClientRegistrationResponse {
+                client_id: client.client_id.clone(),
+                client_secret,
+                client_id_issued_at: Some(client.id.datetime().into()),
+                client_secret_expires_at: None,
+                // The registration access token is not rotated, and is only returned
+                // once on registration
+                registration_access_token: None,
+                registration_client_uri: Some(
+                    url_builder.oauth_client_configuration_endpoint(client.id),
+                ),
+            },
+            metadata: client_metadata(client),
+        }
+    }
+}
+
+/// The body of a client update request, which has the full client metadata
+/// along with the client credentials
+#[derive(Deserialize)]
+pub(crate) struct ClientUpdateRequest {
+    client_id: String,
+
+    #[serde(default)]
+    client_secret: Option<String>,
+
+    #[serde(flatten)]
+    metadata: ClientMetadata,
+}
+
+#[tracing::instrument(name = "handlers.oauth2.registration.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+    State(encrypter): State<Encrypter>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    body: Result<Json<ClientMetadata>, axum::extract::rejection::JsonRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    // Propagate any JSON extraction error
+    let Json(body) = body?;
+
+    info!(?body, "Client registration");
+
+    let metadata = validate_metadata(body, &key_store, &mut policy).await?;
+
+    let (client_secret, encrypted_client_secret) =
+        if requires_client_secret(metadata.token_endpoint_auth_method.as_ref()) {
             // Let's generate a random client secret
-            let client_secret = Alphanumeric.sample_string(&mut rng, 20);
-            let encrypted_client_secret = encrypter.encrypt_to_string(client_secret.as_bytes())?;
+            let (client_secret, encrypted_client_secret) =
+                generate_client_secret(&mut rng, &encrypter)?;
             (Some(client_secret), Some(encrypted_client_secret))
-        }
-        _ => (None, None),
-    };
+        } else {
+            (None, None)
+        };
 
     let client = repo
         .oauth2_client()
@@ -287,32 +646,196 @@ pub(crate) async fn post(
             metadata.userinfo_signed_response_alg.clone(),
             metadata.token_endpoint_auth_method.clone(),
             metadata.token_endpoint_auth_signing_alg.clone(),
+            metadata.request_object_signing_alg.clone(),
+            metadata.request_uris.clone().unwrap_or_default(),
+            metadata.id_token_encrypted_response_alg.clone(),
+            metadata.id_token_encrypted_response_enc.clone(),
+            metadata.userinfo_encrypted_response_alg.clone(),
+            metadata.userinfo_encrypted_response_enc.clone(),
             metadata.initiate_login_uri.clone(),
+            metadata
+                .post_logout_redirect_uris
+                .clone()
+                .unwrap_or_default(),
+            metadata.backchannel_logout_uri.clone(),
+            metadata.backchannel_logout_session_required(),
         )
         .await?;
 
+    let registration_access_token =
+        issue_registration_access_token(&mut rng, &mut repo, &client).await?;
+
     repo.save().await?;
 
     let response = ClientRegistrationResponse {
+        registration_client_uri: Some(url_builder.oauth_client_configuration_endpoint(client.id)),
         client_id: client.client_id,
         client_secret,
         // XXX: we should have a `created_at` field on the clients
         client_id_issued_at: Some(client.id.datetime().into()),
         client_secret_expires_at: None,
+        registration_access_token: Some(registration_access_token),
     };
 
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+#[tracing::instrument(
+    name = "handlers.oauth2.registration.get",
+    fields(client.id = %client_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn get(
+    mut repo: BoxRepository,
+    State(encrypter): State<Encrypter>,
+    State(url_builder): State<UrlBuilder>,
+    Path(client_id): Path<Ulid>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = authenticate_client(&mut repo, client_id, authorization).await?;
+    let client_secret = decrypt_client_secret(&encrypter, &client)?;
+
+    let response = ClientConfigurationResponse::new(&url_builder, &client, client_secret);
+
+    Ok(Json(response))
+}
+
+#[tracing::instrument(
+    name = "handlers.oauth2.registration.put",
+    fields(client.id = %client_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn put(
+    mut rng: BoxRng,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+    State(encrypter): State<Encrypter>,
+    State(key_store): State<Keystore>,
+    State(url_builder): State<UrlBuilder>,
+    Path(client_id): Path<Ulid>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    body: Result<Json<ClientUpdateRequest>, axum::extract::rejection::JsonRejection>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = authenticate_client(&mut repo, client_id, authorization).await?;
+
+    // Propagate any JSON extraction error
+    let Json(body) = body?;
+
+    info!(?body.metadata, "Client registration update");
+
+    if body.client_id != client.client_id {
+        return Err(RouteError::ClientIdMismatch);
+    }
+
+    let current_client_secret = decrypt_client_secret(&encrypter, &client)?;
+    if body.client_secret.is_some() && body.client_secret != current_client_secret {
+        return Err(RouteError::ClientSecretMismatch);
+    }
+
+    // The whole metadata goes through the same validation as on registration
+    let metadata = validate_metadata(body.metadata, &key_store, &mut policy).await?;
+
+    // Keep the current client secret if the client still needs one, and
+    // generate one if it switched to a secret-based authentication method
+    let (client_secret, encrypted_client_secret) =
+        if requires_client_secret(metadata.token_endpoint_auth_method.as_ref()) {
+            match (
+                current_client_secret,
+                client.encrypted_client_secret.clone(),
+            ) {
+                (Some(client_secret), Some(encrypted_client_secret)) => {
+                    (Some(client_secret), Some(encrypted_client_secret))
+                }
+                _ => {
+                    let (client_secret, encrypted_client_secret) =
+                        generate_client_secret(&mut rng, &encrypter)?;
+                    (Some(client_secret), Some(encrypted_client_secret))
+                }
+            }
+        } else {
+            (None, None)
+        };
+
+    let client = repo
+        .oauth2_client()
+        .update(
+            client,
+            metadata.redirect_uris().to_vec(),
+            encrypted_client_secret,
+            metadata.application_type,
+            metadata.grant_types().to_vec(),
+            metadata.contacts.clone().unwrap_or_default(),
+            metadata
+                .client_name
+                .clone()
+                .map(Localized::to_non_localized),
+            metadata.logo_uri.clone().map(Localized::to_non_localized),
+            metadata.client_uri.clone().map(Localized::to_non_localized),
+            metadata.policy_uri.clone().map(Localized::to_non_localized),
+            metadata.tos_uri.clone().map(Localized::to_non_localized),
+            metadata.jwks_uri.clone(),
+            metadata.jwks.clone(),
+            metadata.id_token_signed_response_alg.clone(),
+            metadata.userinfo_signed_response_alg.clone(),
+            metadata.token_endpoint_auth_method.clone(),
+            metadata.token_endpoint_auth_signing_alg.clone(),
+            metadata.request_object_signing_alg.clone(),
+            metadata.request_uris.clone().unwrap_or_default(),
+            metadata.id_token_encrypted_response_alg.clone(),
+            metadata.id_token_encrypted_response_enc.clone(),
+            metadata.userinfo_encrypted_response_alg.clone(),
+            metadata.userinfo_encrypted_response_enc.clone(),
+            metadata.initiate_login_uri.clone(),
+            metadata
+                .post_logout_redirect_uris
+                .clone()
+                .unwrap_or_default(),
+            metadata.backchannel_logout_uri.clone(),
+            metadata.backchannel_logout_session_required(),
+        )
+        .await?;
+
+    repo.save().await?;
+
+    let response = ClientConfigurationResponse::new(&url_builder, &client, client_secret);
+
+    Ok(Json(response))
+}
+
+#[tracing::instrument(
+    name = "handlers.oauth2.registration.delete",
+    fields(client.id = %client_id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn delete(
+    mut repo: BoxRepository,
+    Path(client_id): Path<Ulid>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<impl IntoResponse, RouteError> {
+    let client = authenticate_client(&mut repo, client_id, authorization).await?;
+
+    info!(client.id = %client.id, "Deleting client registration");
+
+    repo.oauth2_client().delete(client).await?;
+
+    repo.save().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[cfg(test)]
 mod tests {
-    use hyper::{Request, StatusCode};
-    use mas_router::SimpleRoute;
+    use hyper::{header::WWW_AUTHENTICATE, Request, StatusCode};
+    use mas_router::{Route, SimpleRoute};
     use oauth2_types::{
         errors::{ClientError, ClientErrorCode},
         registration::ClientRegistrationResponse,
     };
     use sqlx::PgPool;
+    use ulid::Ulid;
     use url::Url;
 
     use crate::{
@@ -435,6 +958,114 @@ mod tests {
             response.error_description.unwrap(),
             "client_uri is not using a valid domain"
         );
+
+        // Asking for an ID token signing algorithm we don't have a key for
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "id_token_signed_response_alg": "ES384",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "id_token_signed_response_alg ES384 is not supported by this server"
+        );
+
+        // Asking for unsigned request objects
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "request_object_signing_alg": "none",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "request_object_signing_alg none is not supported by this server"
+        );
+
+        // Asking for request objects to be fetched from an IP address
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "request_uris": ["https://169.254.169.254/request.jwt"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "request_uris must be https URLs on a domain name"
+        );
+
+        // Asking for ID tokens encrypted with an unsupported algorithm
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "jwks_uri": "https://example.com/jwks.json",
+                "id_token_encrypted_response_alg": "RSA1_5",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "id_token_encrypted_response_alg RSA1_5 is not supported by this server"
+        );
+
+        // Asking for encrypted userinfo responses without any key to encrypt them to
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "userinfo_encrypted_response_alg": "RSA-OAEP-256",
+                "userinfo_encrypted_response_enc": "A256GCM",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+        assert_eq!(
+            response.error_description.unwrap(),
+            "userinfo_encrypted_response_alg needs a key in the client JWKS to encrypt responses \
+             with"
+        );
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
@@ -475,5 +1106,172 @@ mod tests {
         response.assert_status(StatusCode::CREATED);
         let response: ClientRegistrationResponse = response.json();
         assert!(response.client_secret.is_some());
+
+        // Asking for an ID token signing algorithm for which we have a key
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+                "id_token_signed_response_alg": "ES256",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_client_configuration(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Register a client which uses a client secret
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "client_secret_basic",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.unwrap();
+        let token = response.registration_access_token.unwrap();
+        let id: Ulid = client_id.parse().unwrap();
+        let endpoint = mas_router::OAuth2ClientConfigurationEndpoint(id);
+        assert_eq!(
+            response.registration_client_uri.unwrap().path(),
+            endpoint.path()
+        );
+        let path = endpoint.path_and_query();
+
+        // Requests without or with a wrong token are rejected
+        let request = Request::get(&path).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        response.assert_header_value(WWW_AUTHENTICATE, "Bearer");
+
+        let request = Request::get(&path).bearer("not-the-token").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        response.assert_header_value(WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#);
+
+        // The token of another client can't be used to access this one
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/"],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+                "token_endpoint_auth_method": "none",
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: ClientRegistrationResponse = response.json();
+        let other_token = response.registration_access_token.unwrap();
+
+        let request = Request::get(&path).bearer(&other_token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // Read the client configuration
+        let request = Request::get(&path).bearer(&token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        assert_eq!(response["client_id"], client_id);
+        assert_eq!(response["client_secret"], client_secret);
+        assert_eq!(
+            response["token_endpoint_auth_method"],
+            "client_secret_basic"
+        );
+        assert_eq!(
+            response["redirect_uris"],
+            serde_json::json!(["https://example.com/"])
+        );
+        assert!(response.get("registration_access_token").is_none());
+
+        // The client_id in the body must match the client
+        let request = Request::put(&path).bearer(&token).json(serde_json::json!({
+            "client_id": "01FSHN9AG0MKGTBNZ16RDR3PVY",
+            "redirect_uris": ["https://example.com/"],
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidRequest);
+
+        // The metadata goes through the same validation as on registration
+        let request = Request::put(&path).bearer(&token).json(serde_json::json!({
+            "client_id": client_id,
+            "client_uri": "https://github.io/",
+            "redirect_uris": ["https://example.com/"],
+            "response_types": ["code"],
+            "grant_types": ["authorization_code"],
+            "token_endpoint_auth_method": "client_secret_basic",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let response: ClientError = response.json();
+        assert_eq!(response.error, ClientErrorCode::InvalidClientMetadata);
+
+        // Update the client, keeping the same client secret
+        let request = Request::put(&path).bearer(&token).json(serde_json::json!({
+            "client_id": client_id,
+            "client_secret": client_secret,
+            "client_name": "Updated client",
+            "client_uri": "https://example.com/",
+            "redirect_uris": ["https://example.com/callback"],
+            "response_types": ["code"],
+            "grant_types": ["authorization_code"],
+            "token_endpoint_auth_method": "client_secret_basic",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        assert_eq!(response["client_secret"], client_secret);
+        assert_eq!(response["client_name"], "Updated client");
+
+        // Switching to a public client drops the client secret
+        let request = Request::put(&path).bearer(&token).json(serde_json::json!({
+            "client_id": client_id,
+            "client_uri": "https://example.com/",
+            "redirect_uris": ["https://example.com/callback"],
+            "response_types": ["code"],
+            "grant_types": ["authorization_code"],
+            "token_endpoint_auth_method": "none",
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let request = Request::get(&path).bearer(&token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: serde_json::Value = response.json();
+        assert!(response.get("client_secret").is_none());
+        assert!(response.get("client_name").is_none());
+        assert_eq!(response["token_endpoint_auth_method"], "none");
+        assert_eq!(
+            response["redirect_uris"],
+            serde_json::json!(["https://example.com/callback"])
+        );
+
+        // Delete the client, after which the token can't be used anymore
+        let request = Request::delete(&path).bearer(&token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::NO_CONTENT);
+
+        let request = Request::get(&path).bearer(&token).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
     }
 }
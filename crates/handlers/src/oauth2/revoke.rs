@@ -19,13 +19,10 @@ use mas_axum_utils::{
     http_client_factory::HttpClientFactory,
     sentry::SentryEventID,
 };
-use mas_data_model::{Device, TokenType};
+use mas_data_model::TokenType;
 use mas_iana::oauth::OAuthTokenTypeHint;
 use mas_keystore::Encrypter;
-use mas_storage::{
-    job::{DeleteDeviceJob, JobRepositoryExt},
-    BoxClock, BoxRepository, RepositoryAccess,
-};
+use mas_storage::{oauth2::end_session, BoxClock, BoxRepository, RepositoryAccess};
 use oauth2_types::{
     errors::{ClientError, ClientErrorCode},
     requests::RevocationRequest,
@@ -207,34 +204,9 @@ pub(crate) async fn post(
         .record_oauth2_session(&clock, &session)
         .await;
 
-    // If the session is associated with a user, make sure we schedule a device
-    // deletion job for all the devices associated with the session.
-    if let Some(user_id) = session.user_id {
-        // Fetch the user
-        let user = repo
-            .user()
-            .lookup(user_id)
-            .await?
-            .ok_or(RouteError::UnknownToken)?;
-
-        // Scan the scopes of the session to find if there is any device that should be
-        // deleted from the Matrix server.
-        // TODO: this should be moved in a higher level "end oauth session" method.
-        // XXX: this might not be the right semantic, but it's the best we
-        // can do for now, since we're not explicitly storing devices for OAuth2
-        // sessions.
-        for scope in &*session.scope {
-            if let Some(device) = Device::from_scope_token(scope) {
-                // Schedule a job to delete the device.
-                repo.job()
-                    .schedule_job(DeleteDeviceJob::new(&user, &device))
-                    .await?;
-            }
-        }
-    }
-
-    // Now that we checked everything, we can end the session.
-    repo.oauth2_session().finish(&clock, session).await?;
+    // Now that we checked everything, we can end the session, deleting its
+    // devices and letting the client know through the back-channel
+    end_session(&mut repo, &clock, session).await?;
 
     repo.save().await?;
 
@@ -461,4 +433,131 @@ mod tests {
 
         assert!(!state.is_access_token_valid(&access_token).await);
     }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_revoke_token_of_other_client(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Register two confidential clients
+        let mut credentials = Vec::new();
+        for _ in 0..2 {
+            let request = Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(
+                serde_json::json!({
+                    "client_uri": "https://example.com/",
+                    "redirect_uris": ["https://example.com/callback"],
+                    "contacts": ["contact@example.com"],
+                    "token_endpoint_auth_method": "client_secret_post",
+                    "response_types": ["code"],
+                    "grant_types": ["authorization_code", "refresh_token"],
+                }),
+            );
+
+            let response = state.request(request).await;
+            response.assert_status(StatusCode::CREATED);
+
+            let client_registration: ClientRegistrationResponse = response.json();
+            credentials.push((
+                client_registration.client_id,
+                client_registration.client_secret.unwrap(),
+            ));
+        }
+
+        let (client_id, client_secret) = credentials[0].clone();
+        let (other_client_id, other_client_secret) = credentials[1].clone();
+
+        // Create a session for the first client
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (AccessToken { access_token, .. }, RefreshToken { refresh_token, .. }) =
+            generate_token_pair(
+                &mut state.rng(),
+                &state.clock,
+                &mut repo,
+                &session,
+                Duration::minutes(5),
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // The other client can't revoke the tokens, whatever their type
+        for (token, hint) in [
+            (&access_token, "access_token"),
+            (&refresh_token, "refresh_token"),
+        ] {
+            let request =
+                Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+                    "token": token,
+                    "token_type_hint": hint,
+                    "client_id": other_client_id,
+                    "client_secret": other_client_secret,
+                }));
+
+            let response = state.request(request).await;
+            response.assert_status(StatusCode::UNAUTHORIZED);
+            let error: ClientError = response.json();
+            assert_eq!(error.error, ClientErrorCode::UnauthorizedClient);
+
+            assert!(state.is_access_token_valid(&access_token).await);
+        }
+
+        // Bad credentials are rejected as well
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": access_token,
+            "client_id": client_id,
+            "client_secret": other_client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+        let error: ClientError = response.json();
+        assert_eq!(error.error, ClientErrorCode::InvalidClient);
+
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // The client which owns the tokens can revoke them, without a hint
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": refresh_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        assert!(!state.is_access_token_valid(&access_token).await);
+    }
 }
@@ -11,28 +11,36 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::{collections::HashMap, num::NonZeroU32, sync::OnceLock};
+use std::{collections::HashMap, sync::OnceLock};
 
 use axum::{extract::State, response::IntoResponse, Json};
 use chrono::{DateTime, Duration, Utc};
-use headers::{CacheControl, HeaderMap, HeaderMapExt, Pragma};
-use hyper::StatusCode;
+use headers::{CacheControl, HeaderMap, HeaderMapExt, HeaderValue, Pragma};
+use hyper::{header::RETRY_AFTER, StatusCode};
 use mas_axum_utils::{
     client_authorization::{ClientAuthorization, CredentialsVerificationError},
+    dpop::{self, DPoPError, DPOP_NONCE},
     http_client_factory::HttpClientFactory,
     sentry::SentryEventID,
 };
-use mas_data_model::{AuthorizationGrantStage, Client, Device, TokenType};
+use mas_data_model::{
+    AccessToken, AuthorizationGrantStage, Client, Device, DeviceCodeGrantState, RefreshToken,
+    RefreshTokenPolicy, Session, TokenType, User,
+};
+use mas_iana::oauth::OAuthAccessTokenType;
+use mas_jose::dpop::DPoPProof;
 use mas_keystore::{Encrypter, Keystore};
-use mas_oidc_client::types::scope::ScopeToken;
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
     job::{JobRepositoryExt, ProvisionDeviceJob},
     oauth2::{
-        OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository,
-        OAuth2RefreshTokenRepository, OAuth2SessionRepository,
+        end_session, schedule_backchannel_logout, OAuth2AccessTokenRepository,
+        OAuth2AuthorizationGrantRepository, OAuth2DeviceCodeGrantRepository,
+        OAuth2RefreshTokenRepository, OAuth2SessionFilter, OAuth2SessionRepository,
     },
-    user::BrowserSessionRepository,
+    user::{BrowserSessionRepository, UserGroupRepository},
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use oauth2_types::{
@@ -40,19 +48,33 @@ use oauth2_types::{
     pkce::CodeChallengeError,
     requests::{
         AccessTokenRequest, AccessTokenResponse, AuthorizationCodeGrant, ClientCredentialsGrant,
-        GrantType, RefreshTokenGrant,
+        DeviceCodeGrant, GrantType, RefreshTokenGrant, TokenExchangeGrant, TokenTypeIdentifier,
+        DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS,
     },
-    scope,
+    scope::{self, ScopeToken},
+};
+use opentelemetry::{
+    metrics::{Counter, Unit},
+    Key,
 };
 use serde::Serialize;
 use serde_with::{serde_as, skip_serializing_none};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, info, warn};
 use ulid::Ulid;
 use url::Url;
 
-use super::{generate_id_token, generate_token_pair};
-use crate::{impl_from_error_for_route, site_config::SiteConfig, BoundActivityTracker};
+use super::{
+    encrypt_id_token, generate_id_token, generate_token_pair, requested_id_token_claims,
+    resource_is_valid,
+};
+use crate::{
+    device_conflict::{claim_devices_in_scope, DeviceConflictError},
+    impl_from_error_for_route,
+    rate_limit::Quota,
+    site_config::SiteConfig,
+    BoundActivityTracker,
+};
 
 #[serde_as]
 #[skip_serializing_none]
@@ -103,6 +125,9 @@ pub(crate) enum RouteError {
     #[error("refresh token {0} is invalid")]
     RefreshTokenInvalid(Ulid),
 
+    #[error("refresh token {0} expired")]
+    RefreshTokenExpired(Ulid),
+
     #[error("session {0} is invalid")]
     SessionInvalid(Ulid),
 
@@ -115,6 +140,18 @@ pub(crate) enum RouteError {
     #[error("unsupported grant type")]
     UnsupportedGrantType,
 
+    #[error("unsupported token type")]
+    UnsupportedTokenType,
+
+    #[error("requested scope is not part of the scope of the subject token")]
+    ScopeNotGranted,
+
+    #[error("a requested device is already in use")]
+    DeviceInUse,
+
+    #[error("requested resource is invalid or was not granted")]
+    InvalidTarget,
+
     #[error("unauthorized client")]
     UnauthorizedClient,
 
@@ -123,12 +160,71 @@ pub(crate) enum RouteError {
 
     #[error("failed to load oauth session")]
     NoSuchOAuthSession,
+
+    #[error("device code grant {0} is still pending")]
+    PendingDeviceCode(Ulid),
+
+    #[error("device code grant {0} was rejected")]
+    RejectedDeviceCode(Ulid),
+
+    #[error("device code grant {0} expired")]
+    ExpiredDeviceCode(Ulid),
+
+    #[error("device code grant {0} is polled too often")]
+    SlowDown(Ulid),
+
+    #[error("invalid DPoP proof")]
+    InvalidDPoPProof(#[source] DPoPError),
+
+    #[error("DPoP proof is missing or does not match the session key")]
+    DPoPKeyMismatch,
+
+    #[error("a fresh DPoP nonce is required")]
+    UseDPoPNonce(HeaderValue),
+
+    #[error("client certificate is missing")]
+    MissingClientCertificate,
+
+    #[error("client certificate does not match the session certificate")]
+    CertificateMismatch,
+
+    #[error("client {0} has too many active sessions")]
+    TooManyClientSessions(Ulid),
+
+    #[error("user {user_id} was issued too many tokens, retry in {retry_after}s")]
+    UserTokenQuotaExceeded { user_id: Ulid, retry_after: u64 },
 }
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
         let event_id = sentry::capture_error(&self);
 
+        // The client must retry with the nonce we provide
+        if let Self::UseDPoPNonce(nonce) = self {
+            let mut headers = HeaderMap::new();
+            headers.insert(&DPOP_NONCE, nonce);
+            let response = (
+                StatusCode::BAD_REQUEST,
+                headers,
+                Json(ClientError::from(ClientErrorCode::UseDpopNonce)),
+            );
+            return (SentryEventID::from(event_id), response).into_response();
+        }
+
+        // Tell the client when it can get tokens again
+        if let Self::UserTokenQuotaExceeded { retry_after, .. } = self {
+            let response = (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                Json(
+                    ClientError::from(ClientErrorCode::TemporarilyUnavailable).with_description(
+                        "Too many tokens were issued for this user, retry later".to_owned(),
+                    ),
+                ),
+            );
+            return (SentryEventID::from(event_id), response).into_response();
+        }
+
         let response = match self {
             Self::Internal(_) | Self::NoSuchBrowserSession | Self::NoSuchOAuthSession => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -168,6 +264,7 @@ impl IntoResponse for RouteError {
             Self::InvalidGrant
             | Self::RefreshTokenNotFound
             | Self::RefreshTokenInvalid(_)
+            | Self::RefreshTokenExpired(_)
             | Self::SessionInvalid(_)
             | Self::ClientIDMismatch { .. }
             | Self::GrantNotFound => (
@@ -178,6 +275,71 @@ impl IntoResponse for RouteError {
                 StatusCode::BAD_REQUEST,
                 Json(ClientError::from(ClientErrorCode::UnsupportedGrantType)),
             ),
+            Self::UnsupportedTokenType => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest)
+                        .with_description("Only access tokens can be exchanged".to_owned()),
+                ),
+            ),
+            Self::ScopeNotGranted => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidScope)),
+            ),
+            Self::DeviceInUse => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidScope)
+                        .with_description("A requested device is already in use".to_owned()),
+                ),
+            ),
+            Self::InvalidTarget => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidTarget)),
+            ),
+            Self::PendingDeviceCode(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::AuthorizationPending)),
+            ),
+            Self::RejectedDeviceCode(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::AccessDenied)),
+            ),
+            Self::ExpiredDeviceCode(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::ExpiredToken)),
+            ),
+            Self::SlowDown(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::SlowDown)),
+            ),
+            Self::InvalidDPoPProof(_) | Self::DPoPKeyMismatch | Self::UseDPoPNonce(_) => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidDpopProof)),
+            ),
+            Self::MissingClientCertificate => (
+                StatusCode::BAD_REQUEST,
+                Json(
+                    ClientError::from(ClientErrorCode::InvalidRequest).with_description(
+                        "This client requires a TLS client certificate".to_owned(),
+                    ),
+                ),
+            ),
+            Self::CertificateMismatch => (
+                StatusCode::BAD_REQUEST,
+                Json(ClientError::from(ClientErrorCode::InvalidGrant)),
+            ),
+            Self::TooManyClientSessions(_) => (
+                StatusCode::FORBIDDEN,
+                Json(
+                    ClientError::from(ClientErrorCode::AccessDenied)
+                        .with_description("This client has too many active sessions".to_owned()),
+                ),
+            ),
+            Self::UserTokenQuotaExceeded { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(ClientError::from(ClientErrorCode::TemporarilyUnavailable)),
+            ),
         };
 
         (SentryEventID::from(event_id), response).into_response()
@@ -187,6 +349,16 @@ impl IntoResponse for RouteError {
 impl_from_error_for_route!(mas_storage::RepositoryError);
 impl_from_error_for_route!(mas_policy::EvaluationError);
 impl_from_error_for_route!(super::IdTokenSignatureError);
+impl_from_error_for_route!(super::ResponseEncryptionError);
+
+impl From<DeviceConflictError> for RouteError {
+    fn from(e: DeviceConflictError) -> Self {
+        match e {
+            DeviceConflictError::InUse(_) => Self::DeviceInUse,
+            DeviceConflictError::Internal(e) => Self::Internal(e),
+        }
+    }
+}
 
 #[tracing::instrument(
     name = "handlers.oauth2.token.post",
@@ -205,6 +377,7 @@ pub(crate) async fn post(
     State(site_config): State<SiteConfig>,
     State(encrypter): State<Encrypter>,
     policy: Policy,
+    request_headers: HeaderMap,
     client_authorization: ClientAuthorization<AccessTokenRequest>,
 ) -> Result<impl IntoResponse, RouteError> {
     let client = client_authorization
@@ -225,7 +398,42 @@ pub(crate) async fn post(
 
     let form = client_authorization.form.ok_or(RouteError::BadRequest)?;
 
-    let (reply, repo) = match form {
+    // If the client sent a DPoP proof, the tokens we issue will be bound to its key
+    let dpop_proof = match dpop::verify_proof(
+        &request_headers,
+        "POST",
+        &url_builder.oauth_token_endpoint(),
+        None,
+        &encrypter,
+        &site_config.rate_limiter,
+        &clock,
+    )
+    .await
+    {
+        Ok(proof) => proof,
+        Err(DPoPError::InvalidNonce) => {
+            let nonce = dpop::nonce_header(&encrypter, clock.now())
+                .map_err(|e| RouteError::Internal(Box::new(e)))?;
+            return Err(RouteError::UseDPoPNonce(nonce));
+        }
+        Err(e) => return Err(RouteError::InvalidDPoPProof(e)),
+    };
+    let dpop_jkt = dpop_proof.as_ref().map(DPoPProof::jkt);
+
+    // Clients using certificate-bound access tokens must present their
+    // certificate, so that the tokens we issue are bound to it
+    let certificate_thumbprint = if client.tls_client_certificate_bound_access_tokens {
+        let certificate = client_authorization
+            .certificate
+            .as_ref()
+            .ok_or(RouteError::MissingClientCertificate)?;
+        Some(certificate.thumbprint_sha256())
+    } else {
+        None
+    };
+    let certificate_thumbprint = certificate_thumbprint.as_deref();
+
+    let (mut reply, repo) = match form {
         AccessTokenRequest::AuthorizationCode(grant) => {
             authorization_code_grant(
                 &mut rng,
@@ -236,6 +444,8 @@ pub(crate) async fn post(
                 &key_store,
                 &url_builder,
                 &site_config,
+                dpop_jkt,
+                certificate_thumbprint,
                 repo,
             )
             .await?
@@ -248,6 +458,8 @@ pub(crate) async fn post(
                 &grant,
                 &client,
                 &site_config,
+                dpop_jkt,
+                certificate_thumbprint,
                 repo,
             )
             .await?
@@ -260,6 +472,39 @@ pub(crate) async fn post(
                 &grant,
                 &client,
                 &site_config,
+                dpop_jkt,
+                certificate_thumbprint,
+                repo,
+                policy,
+            )
+            .await?
+        }
+        AccessTokenRequest::DeviceCode(grant) => {
+            device_code_grant(
+                &mut rng,
+                &clock,
+                &activity_tracker,
+                &grant,
+                &client,
+                &key_store,
+                &url_builder,
+                &site_config,
+                dpop_jkt,
+                certificate_thumbprint,
+                repo,
+            )
+            .await?
+        }
+        AccessTokenRequest::TokenExchange(grant) => {
+            token_exchange_grant(
+                &mut rng,
+                &clock,
+                &activity_tracker,
+                &grant,
+                &client,
+                &site_config,
+                dpop_jkt,
+                certificate_thumbprint,
                 repo,
                 policy,
             )
@@ -270,15 +515,157 @@ pub(crate) async fn post(
         }
     };
 
+    // Encrypt the ID token if the client asked for it. This happens before
+    // saving, so that no tokens are issued if it fails
+    if let Some(id_token) = reply.id_token.take() {
+        reply.id_token =
+            Some(encrypt_id_token(&mut rng, &http_client_factory, &client, id_token).await?);
+    }
+
     repo.save().await?;
 
     let mut headers = HeaderMap::new();
     headers.typed_insert(CacheControl::new().with_no_store());
     headers.typed_insert(Pragma::no_cache());
 
+    // Give DPoP clients a nonce they can use in their next proofs
+    if dpop_proof.is_some() {
+        let nonce = dpop::nonce_header(&encrypter, clock.now())
+            .map_err(|e| RouteError::Internal(Box::new(e)))?;
+        headers.insert(&DPOP_NONCE, nonce);
+    }
+
     Ok((headers, Json(reply)))
 }
 
+/// Bind a freshly started session to the DPoP key of the client, if it sent
+/// a proof
+async fn bind_dpop_key(
+    repo: &mut BoxRepository,
+    session: Session,
+    dpop_jkt: Option<&str>,
+) -> Result<Session, RouteError> {
+    let Some(jkt) = dpop_jkt else {
+        return Ok(session);
+    };
+
+    let session = repo
+        .oauth2_session()
+        .bind_dpop_key(session, jkt.to_owned())
+        .await?;
+    Ok(session)
+}
+
+/// Bind a freshly started session to the client certificate, if the client
+/// uses certificate-bound access tokens
+async fn bind_certificate(
+    repo: &mut BoxRepository,
+    session: Session,
+    certificate_thumbprint: Option<&str>,
+) -> Result<Session, RouteError> {
+    let Some(thumbprint) = certificate_thumbprint else {
+        return Ok(session);
+    };
+
+    let session = repo
+        .oauth2_session()
+        .bind_certificate(session, thumbprint.to_owned())
+        .await?;
+    Ok(session)
+}
+
+const QUOTA: Key = Key::from_static_str("quota");
+const CLIENT_ID: Key = Key::from_static_str("client.id");
+
+fn quota_rejections_counter() -> &'static Counter<u64> {
+    static COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        let meter = opentelemetry::global::meter_with_version(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            Some(opentelemetry_semantic_conventions::SCHEMA_URL),
+            None,
+        );
+
+        meter
+            .u64_counter("mas.oauth2.token_quota.rejections")
+            .with_description("Number of token requests rejected because of a quota")
+            .with_unit(Unit::new("{requests}"))
+            .init()
+    })
+}
+
+/// Enforce the token quotas before issuing tokens for a session.
+///
+/// `started` is `true` when the session gets its first tokens, in which case
+/// it counts towards the active sessions of the client.
+async fn check_quotas(
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    site_config: &SiteConfig,
+    client: &Client,
+    session: &Session,
+    started: bool,
+) -> Result<(), RouteError> {
+    if let (true, Some(max)) = (started, site_config.max_active_sessions_per_client) {
+        // The session is already in the database at this point, so it is part
+        // of the count
+        let filter = OAuth2SessionFilter::new().for_client(client).active_only();
+        let active = repo.oauth2_session().count(filter).await?;
+        if active > usize::try_from(max.get()).unwrap_or(usize::MAX) {
+            warn!(client.id = %client.id, active, "Client has too many active sessions");
+            quota_rejections_counter().add(
+                1,
+                &[
+                    QUOTA.string("client_sessions"),
+                    CLIENT_ID.string(client.id.to_string()),
+                ],
+            );
+            return Err(RouteError::TooManyClientSessions(client.id));
+        }
+    }
+
+    if let (Some(quota), Some(user_id)) = (site_config.user_token_rate_limit, session.user_id) {
+        let key = format!("token-issuance:user:{user_id}");
+        if let Err(e) = site_config.rate_limiter.check(clock, &key, quota).await {
+            warn!(user.id = %user_id, client.id = %client.id, "User was issued too many tokens");
+            quota_rejections_counter().add(
+                1,
+                &[
+                    QUOTA.string("user_tokens"),
+                    CLIENT_ID.string(client.id.to_string()),
+                ],
+            );
+            return Err(RouteError::UserTokenQuotaExceeded {
+                user_id,
+                retry_after: e.retry_after(clock.now()),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the resource indicator sent to the token endpoint, if any, is
+/// the one the session was started with
+fn check_resource(session: &Session, requested: Option<&Url>) -> Result<(), RouteError> {
+    match requested {
+        Some(resource) if session.resource.as_ref() != Some(resource) => {
+            Err(RouteError::InvalidTarget)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The type of the access tokens issued for the given session
+fn access_token_type(session: &Session) -> OAuthAccessTokenType {
+    if session.is_dpop_bound() {
+        OAuthAccessTokenType::DPoP
+    } else {
+        OAuthAccessTokenType::Bearer
+    }
+}
+
 #[allow(clippy::too_many_lines)] // TODO: refactor some parts out
 async fn authorization_code_grant(
     mut rng: &mut BoxRng,
@@ -289,6 +676,8 @@ async fn authorization_code_grant(
     key_store: &Keystore,
     url_builder: &UrlBuilder,
     site_config: &SiteConfig,
+    dpop_jkt: Option<&str>,
+    certificate_thumbprint: Option<&str>,
     mut repo: BoxRepository,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
@@ -324,6 +713,7 @@ async fn authorization_code_grant(
                     .lookup(session_id)
                     .await?
                     .ok_or(RouteError::NoSuchOAuthSession)?;
+                schedule_backchannel_logout(&mut repo, &session).await?;
                 repo.oauth2_session().finish(clock, session).await?;
                 repo.save().await?;
             }
@@ -360,6 +750,8 @@ async fn authorization_code_grant(
         return Err(RouteError::UnauthorizedClient);
     }
 
+    check_resource(&session, grant.resource.as_ref())?;
+
     match (code.pkce.as_ref(), grant.code_verifier.as_ref()) {
         (None, None) => {}
         // We have a challenge but no verifier (or vice-versa)? Bad request.
@@ -386,27 +778,36 @@ async fn authorization_code_grant(
         .get_last_authentication(&browser_session)
         .await?;
 
+    check_quotas(clock, &mut repo, site_config, client, &session, true).await?;
+
+    let session = bind_dpop_key(&mut repo, session, dpop_jkt).await?;
+    let session = bind_certificate(&mut repo, session, certificate_thumbprint).await?;
+
     let ttl = site_config.access_token_ttl;
     let (access_token, refresh_token) =
         generate_token_pair(&mut rng, clock, &mut repo, &session, ttl).await?;
 
     let id_token = if session.scope.contains(&scope::OPENID) {
+        let user_claims =
+            requested_id_token_claims(&mut repo, &session, &browser_session.user).await?;
         Some(generate_id_token(
             &mut rng,
             clock,
             url_builder,
             key_store,
             client,
-            &authz_grant,
+            Some(&authz_grant),
             &browser_session,
             Some(&access_token),
             last_authentication.as_ref(),
+            user_claims,
         )?)
     } else {
         None
     };
 
     let mut params = AccessTokenResponse::new(access_token.access_token)
+        .with_token_type(access_token_type(&session))
         .with_expires_in(ttl)
         .with_refresh_token(refresh_token.refresh_token)
         .with_scope(session.scope.clone());
@@ -450,6 +851,8 @@ async fn refresh_token_grant(
     grant: &RefreshTokenGrant,
     client: &Client,
     site_config: &SiteConfig,
+    dpop_jkt: Option<&str>,
+    certificate_thumbprint: Option<&str>,
     mut repo: BoxRepository,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
     // Check that the client is allowed to use this grant type
@@ -457,6 +860,8 @@ async fn refresh_token_grant(
         return Err(RouteError::UnauthorizedClient);
     }
 
+    let policy = site_config.refresh_token_policies.for_client(client.id);
+
     let refresh_token = repo
         .oauth2_refresh_token()
         .find_by_token(&grant.refresh_token)
@@ -469,7 +874,45 @@ async fn refresh_token_grant(
         .await?
         .ok_or(RouteError::NoSuchOAuthSession)?;
 
-    if !refresh_token.is_valid() {
+    if client.id != session.client_id {
+        // As per https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+        return Err(RouteError::ClientIDMismatch {
+            expected: session.client_id,
+            actual: client.id,
+        });
+    }
+
+    // The tokens issued when this refresh token was consumed, if the client is
+    // retrying within the reuse grace period
+    let replayed = if refresh_token.is_valid() {
+        None
+    } else {
+        replayed_token_pair(clock, &mut repo, &policy, &refresh_token).await?
+    };
+
+    if !refresh_token.is_valid() && replayed.is_none() {
+        // The refresh token was already used, which means either the client
+        // misbehaves or the token leaked. Keep a record of it, and end the
+        // session if configured to do so.
+        let revoke = policy.revoke_session_on_reuse && session.is_valid();
+        warn!(
+            refresh_token.id = %refresh_token.id,
+            session.id = %session.id,
+            client.id = %client.id,
+            session_revoked = revoke,
+            "Refresh token reuse detected"
+        );
+
+        repo.oauth2_refresh_token()
+            .record_reuse(rng, clock, &refresh_token, revoke)
+            .await?;
+
+        if revoke {
+            end_session(&mut repo, clock, session).await?;
+        }
+
+        repo.save().await?;
+
         return Err(RouteError::RefreshTokenInvalid(refresh_token.id));
     }
 
@@ -477,28 +920,98 @@ async fn refresh_token_grant(
         return Err(RouteError::SessionInvalid(session.id));
     }
 
-    if client.id != session.client_id {
-        // As per https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
-        return Err(RouteError::ClientIDMismatch {
-            expected: session.client_id,
-            actual: client.id,
-        });
+    // Sessions bound to a DPoP key can only be refreshed with a proof from that key
+    if let Some(session_jkt) = &session.dpop_jkt {
+        if dpop_jkt != Some(session_jkt.as_str()) {
+            return Err(RouteError::DPoPKeyMismatch);
+        }
+    }
+
+    // Same goes for sessions bound to a client certificate
+    if let Some(session_thumbprint) = &session.certificate_thumbprint {
+        if certificate_thumbprint != Some(session_thumbprint.as_str()) {
+            return Err(RouteError::CertificateMismatch);
+        }
+    }
+
+    check_resource(&session, grant.resource.as_ref())?;
+
+    // Refreshing counts as activity, so the refresh token itself is the latest
+    // sign of activity we might know about
+    let last_active_at = session
+        .last_active_at
+        .unwrap_or(session.created_at)
+        .max(refresh_token.created_at);
+    if policy
+        .lifetimes
+        .is_expired(clock.now(), session.created_at, last_active_at)
+    {
+        // End the session, so that the user sees it as finished and has to
+        // authenticate again
+        end_session(&mut repo, clock, session).await?;
+        repo.save().await?;
+
+        return Err(RouteError::RefreshTokenExpired(refresh_token.id));
+    }
+
+    if let Some((access_token, next_refresh_token)) = replayed {
+        // Return the same tokens as the first time, which the client most
+        // likely never received. This only works once.
+        info!(
+            refresh_token.id = %refresh_token.id,
+            session.id = %session.id,
+            client.id = %client.id,
+            "Refresh token used again within the grace period"
+        );
+        repo.oauth2_refresh_token()
+            .use_grace(clock, refresh_token)
+            .await?;
+
+        let mut params = AccessTokenResponse::new(access_token.access_token)
+            .with_token_type(access_token_type(&session))
+            .with_refresh_token(next_refresh_token.refresh_token)
+            .with_scope(session.scope);
+        if let Some(expires_at) = access_token.expires_at {
+            params = params.with_expires_in(expires_at - clock.now());
+        }
+
+        return Ok((params, repo));
     }
 
+    check_quotas(clock, &mut repo, site_config, client, &session, false).await?;
+
     activity_tracker
         .record_oauth2_session(clock, &session)
         .await;
 
     let ttl = site_config.access_token_ttl;
-    let (new_access_token, new_refresh_token) =
-        generate_token_pair(rng, clock, &mut repo, &session, ttl).await?;
+    let previous_access_token_id = refresh_token.access_token_id;
+    let (new_access_token, new_refresh_token) = if policy.rotation {
+        let (new_access_token, new_refresh_token) =
+            generate_token_pair(rng, clock, &mut repo, &session, ttl).await?;
 
-    let refresh_token = repo
-        .oauth2_refresh_token()
-        .consume(clock, refresh_token)
-        .await?;
+        repo.oauth2_refresh_token()
+            .consume(clock, refresh_token, &new_refresh_token)
+            .await?;
+
+        (new_access_token, new_refresh_token)
+    } else {
+        // Keep the same refresh token, only issuing a new access token
+        let access_token_str = TokenType::AccessToken.generate(rng);
+        let new_access_token = repo
+            .oauth2_access_token()
+            .add(rng, clock, &session, access_token_str, Some(ttl))
+            .await?;
+
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .set_access_token(refresh_token, &new_access_token)
+            .await?;
+
+        (new_access_token, refresh_token)
+    };
 
-    if let Some(access_token_id) = refresh_token.access_token_id {
+    if let Some(access_token_id) = previous_access_token_id {
         let access_token = repo.oauth2_access_token().lookup(access_token_id).await?;
         if let Some(access_token) = access_token {
             repo.oauth2_access_token()
@@ -508,6 +1021,7 @@ async fn refresh_token_grant(
     }
 
     let params = AccessTokenResponse::new(new_access_token.access_token)
+        .with_token_type(access_token_type(&session))
         .with_expires_in(ttl)
         .with_refresh_token(new_refresh_token.refresh_token)
         .with_scope(session.scope);
@@ -515,6 +1029,51 @@ async fn refresh_token_grant(
     Ok((params, repo))
 }
 
+/// The tokens issued in place of a consumed refresh token, if it is used again
+/// within the reuse grace period and the client didn't use them yet
+async fn replayed_token_pair(
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    policy: &RefreshTokenPolicy,
+    refresh_token: &RefreshToken,
+) -> Result<Option<(AccessToken, RefreshToken)>, RouteError> {
+    let Some(grace_period) = policy.reuse_grace_period else {
+        return Ok(None);
+    };
+
+    let Some(next_refresh_token_id) =
+        refresh_token.next_refresh_token_id_in_grace(clock.now(), grace_period)
+    else {
+        return Ok(None);
+    };
+
+    // If the new refresh token was already used, the client did get it, so
+    // this is a genuine reuse
+    let Some(next_refresh_token) = repo
+        .oauth2_refresh_token()
+        .lookup(next_refresh_token_id)
+        .await?
+        .filter(|token| token.is_valid())
+    else {
+        return Ok(None);
+    };
+
+    let Some(access_token_id) = next_refresh_token.access_token_id else {
+        return Ok(None);
+    };
+
+    let Some(access_token) = repo
+        .oauth2_access_token()
+        .lookup(access_token_id)
+        .await?
+        .filter(|token| token.is_valid(clock.now()))
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some((access_token, next_refresh_token)))
+}
+
 async fn client_credentials_grant(
     rng: &mut BoxRng,
     clock: &impl Clock,
@@ -522,6 +1081,8 @@ async fn client_credentials_grant(
     grant: &ClientCredentialsGrant,
     client: &Client,
     site_config: &SiteConfig,
+    dpop_jkt: Option<&str>,
+    certificate_thumbprint: Option<&str>,
     mut repo: BoxRepository,
     mut policy: Policy,
 ) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
@@ -536,6 +1097,14 @@ async fn client_credentials_grant(
         .clone()
         .unwrap_or_else(|| std::iter::empty::<ScopeToken>().collect());
 
+    if grant
+        .resource
+        .as_ref()
+        .is_some_and(|resource| !resource_is_valid(resource))
+    {
+        return Err(RouteError::InvalidTarget);
+    }
+
     // Make the request go through the policy engine
     let res = policy
         .evaluate_client_credentials_grant(&scope, client)
@@ -549,6 +1118,18 @@ async fn client_credentials_grant(
         .oauth2_session()
         .add_from_client_credentials(rng, clock, client, scope)
         .await?;
+    let session = if let Some(resource) = grant.resource.clone() {
+        repo.oauth2_session()
+            .set_resource(session, resource)
+            .await?
+    } else {
+        session
+    };
+
+    check_quotas(clock, &mut repo, site_config, client, &session, true).await?;
+
+    let session = bind_dpop_key(&mut repo, session, dpop_jkt).await?;
+    let session = bind_certificate(&mut repo, session, certificate_thumbprint).await?;
 
     let ttl = site_config.access_token_ttl;
     let access_token_str = TokenType::AccessToken.generate(rng);
@@ -558,7 +1139,9 @@ async fn client_credentials_grant(
         .add(rng, clock, &session, access_token_str, Some(ttl))
         .await?;
 
-    let mut params = AccessTokenResponse::new(access_token.access_token).with_expires_in(ttl);
+    let mut params = AccessTokenResponse::new(access_token.access_token)
+        .with_token_type(access_token_type(&session))
+        .with_expires_in(ttl);
 
     // XXX: there is a potential (but unlikely) race here, where the activity for
     // the session is recorded before the transaction is committed. We would have to
@@ -575,43 +1158,381 @@ async fn client_credentials_grant(
     Ok((params, repo))
 }
 
-#[cfg(test)]
-mod tests {
-    use hyper::Request;
-    use mas_data_model::{AccessToken, AuthorizationCode, RefreshToken};
-    use mas_router::SimpleRoute;
-    use oauth2_types::{
-        registration::ClientRegistrationResponse,
-        requests::ResponseMode,
-        scope::{Scope, OPENID},
-    };
-    use sqlx::PgPool;
+#[allow(clippy::too_many_arguments)]
+async fn device_code_grant(
+    mut rng: &mut BoxRng,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    grant: &DeviceCodeGrant,
+    client: &Client,
+    key_store: &Keystore,
+    url_builder: &UrlBuilder,
+    site_config: &SiteConfig,
+    dpop_jkt: Option<&str>,
+    certificate_thumbprint: Option<&str>,
+    mut repo: BoxRepository,
+) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
+    // Check that the client is allowed to use this grant type
+    if !client.grant_types.contains(&GrantType::DeviceCode) {
+        return Err(RouteError::UnauthorizedClient);
+    }
 
-    use super::*;
-    use crate::test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState};
+    let grant = repo
+        .oauth2_device_code_grant()
+        .find_by_device_code(&grant.device_code)
+        .await?
+        .ok_or(RouteError::GrantNotFound)?;
 
-    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
-    async fn test_auth_code_grant(pool: PgPool) {
-        init_tracing();
-        let state = TestState::from_pool(pool).await.unwrap();
+    // Check that the client match
+    if client.id != grant.client_id {
+        return Err(RouteError::ClientIDMismatch {
+            expected: grant.client_id,
+            actual: client.id,
+        });
+    }
 
-        // Provision a client
-        let request =
-            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
-                "client_uri": "https://example.com/",
-                "redirect_uris": ["https://example.com/callback"],
-                "contacts": ["contact@example.com"],
-                "token_endpoint_auth_method": "none",
-                "response_types": ["code"],
-                "grant_types": ["authorization_code"],
-            }));
+    if grant.is_expired(clock.now()) {
+        return Err(RouteError::ExpiredDeviceCode(grant.id));
+    }
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::CREATED);
+    // Clients must wait for the polling interval between two requests
+    let quota = Quota::new(
+        NonZeroU32::new(1).unwrap(),
+        Duration::seconds(DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS),
+    );
+    if site_config
+        .rate_limiter
+        .check(clock, &format!("device_code:poll:{}", grant.id), quota)
+        .await
+        .is_err()
+    {
+        return Err(RouteError::SlowDown(grant.id));
+    }
 
-        let ClientRegistrationResponse { client_id, .. } = response.json();
+    let browser_session_id = match &grant.state {
+        DeviceCodeGrantState::Pending => {
+            return Err(RouteError::PendingDeviceCode(grant.id));
+        }
+        DeviceCodeGrantState::Rejected { .. } => {
+            return Err(RouteError::RejectedDeviceCode(grant.id));
+        }
+        DeviceCodeGrantState::Exchanged { exchanged_at, .. } => {
+            debug!(%exchanged_at, "Device code was already exchanged");
+            return Err(RouteError::InvalidGrant);
+        }
+        DeviceCodeGrantState::Fulfilled {
+            browser_session_id, ..
+        } => *browser_session_id,
+    };
 
-        // Let's provision a user and create a session for them. This part is hard to
+    let browser_session = repo
+        .browser_session()
+        .lookup(browser_session_id)
+        .await?
+        .ok_or(RouteError::NoSuchBrowserSession)?;
+
+    // The user might have logged out since they approved the grant
+    if !browser_session.active() {
+        return Err(RouteError::InvalidGrant);
+    }
+
+    let last_authentication = repo
+        .browser_session()
+        .get_last_authentication(&browser_session)
+        .await?;
+
+    // Apply the configured policy if one of the requested devices is already
+    // used by another session
+    let (scope, replaced_devices) = claim_devices_in_scope(
+        rng,
+        clock,
+        &mut repo,
+        site_config.device_conflict_policy,
+        &browser_session.user,
+        &grant.scope,
+    )
+    .await?;
+
+    // Start the session
+    let session = repo
+        .oauth2_session()
+        .add_from_browser_session(rng, clock, client, &browser_session, scope)
+        .await?;
+
+    check_quotas(clock, &mut repo, site_config, client, &session, true).await?;
+
+    let session = bind_dpop_key(&mut repo, session, dpop_jkt).await?;
+    let session = bind_certificate(&mut repo, session, certificate_thumbprint).await?;
+
+    let ttl = site_config.access_token_ttl;
+    let (access_token, refresh_token) =
+        generate_token_pair(&mut rng, clock, &mut repo, &session, ttl).await?;
+
+    let id_token = if session.scope.contains(&scope::OPENID) {
+        Some(generate_id_token(
+            &mut rng,
+            clock,
+            url_builder,
+            key_store,
+            client,
+            None,
+            &browser_session,
+            Some(&access_token),
+            last_authentication.as_ref(),
+            HashMap::new(),
+        )?)
+    } else {
+        None
+    };
+
+    let mut params = AccessTokenResponse::new(access_token.access_token)
+        .with_token_type(access_token_type(&session))
+        .with_expires_in(ttl)
+        .with_refresh_token(refresh_token.refresh_token)
+        .with_scope(session.scope.clone());
+
+    if let Some(id_token) = id_token {
+        params = params.with_id_token(id_token);
+    }
+
+    // Look for device to provision
+    for scope in &*session.scope {
+        if let Some(device) = Device::from_scope_token(scope) {
+            let mut job = ProvisionDeviceJob::new(&browser_session.user, &device);
+            if replaced_devices.contains(&device) {
+                job = job.replace_existing();
+            }
+            repo.job().schedule_job(job).await?;
+        }
+    }
+
+    repo.oauth2_device_code_grant()
+        .exchange(clock, grant, &session)
+        .await?;
+
+    // XXX: there is a potential (but unlikely) race here, where the activity for
+    // the session is recorded before the transaction is committed. We would have to
+    // save the repository here to fix that.
+    activity_tracker
+        .record_oauth2_session(clock, &session)
+        .await;
+
+    Ok((params, repo))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn token_exchange_grant(
+    rng: &mut BoxRng,
+    clock: &impl Clock,
+    activity_tracker: &BoundActivityTracker,
+    grant: &TokenExchangeGrant,
+    client: &Client,
+    site_config: &SiteConfig,
+    dpop_jkt: Option<&str>,
+    certificate_thumbprint: Option<&str>,
+    mut repo: BoxRepository,
+    mut policy: Policy,
+) -> Result<(AccessTokenResponse, BoxRepository), RouteError> {
+    // Check that the client is allowed to use this grant type
+    if !client.grant_types.contains(&GrantType::TokenExchange) {
+        return Err(RouteError::UnauthorizedClient);
+    }
+
+    // We only support exchanging an access token for another access token, and
+    // don't support delegation through actor tokens
+    if grant.subject_token_type != TokenTypeIdentifier::AccessToken
+        || grant
+            .requested_token_type
+            .as_ref()
+            .is_some_and(|t| *t != TokenTypeIdentifier::AccessToken)
+    {
+        return Err(RouteError::UnsupportedTokenType);
+    }
+
+    if grant.actor_token.is_some() || grant.actor_token_type.is_some() {
+        return Err(RouteError::BadRequest);
+    }
+
+    let subject_token = repo
+        .oauth2_access_token()
+        .find_by_token(&grant.subject_token)
+        .await?
+        .ok_or(RouteError::InvalidGrant)?;
+
+    if !subject_token.is_valid(clock.now()) {
+        return Err(RouteError::InvalidGrant);
+    }
+
+    let parent = repo
+        .oauth2_session()
+        .lookup(subject_token.session_id)
+        .await?
+        .ok_or(RouteError::NoSuchOAuthSession)?;
+
+    if !parent.is_valid() {
+        return Err(RouteError::SessionInvalid(parent.id));
+    }
+
+    // The client exchanging the token can't prove possession of the key a
+    // sender-constrained token is bound to
+    if parent.is_dpop_bound() {
+        debug!("Refusing to exchange a DPoP-bound access token");
+        return Err(RouteError::InvalidGrant);
+    }
+
+    if parent.is_certificate_bound() {
+        debug!("Refusing to exchange a certificate-bound access token");
+        return Err(RouteError::InvalidGrant);
+    }
+
+    // Only tokens issued on behalf of a user can be exchanged
+    let user_id = parent.user_id.ok_or(RouteError::InvalidGrant)?;
+    let user = repo
+        .user()
+        .lookup(user_id)
+        .await?
+        .filter(User::is_valid)
+        .ok_or(RouteError::InvalidGrant)?;
+
+    // The new token can't have more scopes than the one it is derived from
+    let scope = grant.scope.clone().unwrap_or_else(|| parent.scope.clone());
+    if !scope.iter().all(|token| parent.scope.contains(token)) {
+        return Err(RouteError::ScopeNotGranted);
+    }
+
+    // Make the request go through the policy engine
+    let groups = repo.user_group().list(&user).await?;
+    let res = policy
+        .evaluate_token_exchange(&scope, client, &user, &groups)
+        .await?;
+    if !res.valid() {
+        return Err(RouteError::DeniedByPolicy(res.violations));
+    }
+
+    // Start the derived session
+    let session = repo
+        .oauth2_session()
+        .add_from_token_exchange(rng, clock, client, &parent, scope)
+        .await?;
+
+    check_quotas(clock, &mut repo, site_config, client, &session, true).await?;
+
+    let session = bind_dpop_key(&mut repo, session, dpop_jkt).await?;
+    let session = bind_certificate(&mut repo, session, certificate_thumbprint).await?;
+
+    let ttl = site_config.access_token_ttl;
+    let access_token_str = TokenType::AccessToken.generate(rng);
+
+    let access_token = repo
+        .oauth2_access_token()
+        .add(rng, clock, &session, access_token_str, Some(ttl))
+        .await?;
+
+    let mut params = AccessTokenResponse::new(access_token.access_token)
+        .with_token_type(access_token_type(&session))
+        .with_issued_token_type(TokenTypeIdentifier::AccessToken)
+        .with_expires_in(ttl);
+
+    // XXX: there is a potential (but unlikely) race here, where the activity for
+    // the session is recorded before the transaction is committed. We would have to
+    // save the repository here to fix that.
+    activity_tracker
+        .record_oauth2_session(clock, &session)
+        .await;
+
+    if !session.scope.is_empty() {
+        // We only return the scope if it's not empty
+        params = params.with_scope(session.scope);
+    }
+
+    Ok((params, repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroU32, sync::Arc};
+
+    use hyper::Request;
+    use mas_axum_utils::client_certificate::ClientCertificate;
+    use mas_data_model::{
+        AccessToken, AuthorizationCode, RefreshToken, RefreshTokenLifetimes, RefreshTokenPolicy,
+    };
+    use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+    use mas_jose::{
+        dpop::{access_token_hash, DPOP_JWT_TYPE},
+        jwk::{JsonWebKey, JsonWebKeyPublicParameters},
+        jwt::{JsonWebSignatureHeader, Jwt},
+    };
+    use mas_keystore::PrivateKey;
+    use mas_router::SimpleRoute;
+    use oauth2_types::{
+        registration::ClientRegistrationResponse,
+        requests::{DeviceAuthorizationResponse, IntrospectionResponse, ResponseMode},
+        scope::{Scope, OPENID},
+    };
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{
+        rate_limit::Quota,
+        test_utils::{init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+    };
+
+    /// Sign a DPoP proof for the given request with the given key
+    fn dpop_proof(
+        state: &TestState,
+        key: &PrivateKey,
+        method: &str,
+        uri: &Url,
+        access_token: Option<&str>,
+        nonce: Option<&str>,
+    ) -> String {
+        let alg = JsonWebSignatureAlg::Es256;
+        let header = JsonWebSignatureHeader::new(alg.clone())
+            .with_typ(DPOP_JWT_TYPE.to_owned())
+            .with_jwk(JsonWebKey::new(JsonWebKeyPublicParameters::from(key)));
+
+        let jti = Ulid::from_datetime_with_source(state.clock.now().into(), &mut state.rng());
+        let mut claims = serde_json::Map::new();
+        claims.insert("jti".to_owned(), jti.to_string().into());
+        claims.insert("htm".to_owned(), method.into());
+        claims.insert("htu".to_owned(), uri.as_str().into());
+        claims.insert("iat".to_owned(), state.clock.now().timestamp().into());
+        if let Some(access_token) = access_token {
+            claims.insert("ath".to_owned(), access_token_hash(access_token).into());
+        }
+        if let Some(nonce) = nonce {
+            claims.insert("nonce".to_owned(), nonce.into());
+        }
+
+        let signer = key.signing_key_for_alg(&alg).unwrap();
+        Jwt::sign_with_rng(&mut state.rng(), header, claims, &signer)
+            .unwrap()
+            .into_string()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_auth_code_grant(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        // Let's provision a user and create a session for them. This part is hard to
         // test with just HTTP requests, so we'll use the repository directly.
         let mut repo = state.repository().await.unwrap();
 
@@ -652,9 +1573,13 @@ mod tests {
                 Some("state".to_owned()),
                 Some("nonce".to_owned()),
                 None,
+                Vec::new(),
+                None,
                 ResponseMode::Query,
                 false,
                 false,
+                false,
+                None,
             )
             .await
             .unwrap();
@@ -752,9 +1677,13 @@ mod tests {
                 Some("state".to_owned()),
                 Some("nonce".to_owned()),
                 None,
+                Vec::new(),
+                None,
                 ResponseMode::Query,
                 false,
                 false,
+                false,
+                None,
             )
             .await
             .unwrap();
@@ -798,6 +1727,32 @@ mod tests {
         assert_eq!(error, ClientErrorCode::InvalidGrant);
     }
 
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_auth_code_grant_through_browser(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        state.create_user("john", "hunter2").await;
+        state.login(&cookies, "john", "hunter2").await;
+
+        let redirect_uri = "https://example.com/callback";
+        let client_id = state.register_client(redirect_uri).await;
+
+        // The first time, the user is asked for consent
+        let response = state
+            .run_authorization_code_flow(&cookies, &client_id, redirect_uri, "openid")
+            .await;
+        assert!(state.is_access_token_valid(&response.access_token).await);
+        assert!(response.id_token.is_some());
+
+        // The second time, the consent was already given
+        let response = state
+            .run_authorization_code_flow(&cookies, &client_id, redirect_uri, "openid")
+            .await;
+        assert!(state.is_access_token_valid(&response.access_token).await);
+    }
+
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
     async fn test_refresh_token_grant(pool: PgPool) {
         init_tracing();
@@ -922,130 +1877,1304 @@ mod tests {
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
-    async fn test_client_credentials(pool: PgPool) {
+    async fn test_dpop_bound_session(pool: PgPool) {
         init_tracing();
         let state = TestState::from_pool(pool).await.unwrap();
+        let key = PrivateKey::generate_ec_p256(&mut state.rng());
+        let jkt = JsonWebKeyPublicParameters::from(&key).thumbprint_sha256();
+        let token_endpoint = state.url_builder.oauth_token_endpoint();
+        let userinfo_endpoint = state.url_builder.oidc_userinfo_endpoint();
 
         // Provision a client
         let request =
             Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
                 "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
                 "contacts": ["contact@example.com"],
-                "token_endpoint_auth_method": "client_secret_post",
-                "grant_types": ["client_credentials"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
             }));
 
         let response = state.request(request).await;
         response.assert_status(StatusCode::CREATED);
 
-        let response: ClientRegistrationResponse = response.json();
-        let client_id = response.client_id;
-        let client_secret = response.client_secret.expect("to have a client secret");
-
-        // Call the token endpoint with an empty scope
-        let request =
-            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
-                "client_id": client_id,
-                "client_secret": client_secret,
-            }));
+        let ClientRegistrationResponse { client_id, .. } = response.json();
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        // Provision a user and a session bound to the DPoP key
+        let mut repo = state.repository().await.unwrap();
 
-        let response: AccessTokenResponse = response.json();
-        assert!(response.refresh_token.is_none());
-        assert!(response.expires_in.is_some());
-        assert!(response.scope.is_none());
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
 
-        // Revoke the token
-        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
-        }));
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
 
-        // We should be allowed to ask for the GraphQL API scope
-        let request =
-            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
-                "client_id": client_id,
-                "client_secret": client_secret,
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .bind_dpop_key(session, jkt)
+            .await
+            .unwrap();
+
+        let (AccessToken { access_token, .. }, RefreshToken { refresh_token, .. }) =
+            generate_token_pair(
+                &mut state.rng(),
+                &state.clock,
+                &mut repo,
+                &session,
+                Duration::minutes(5),
+            )
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // The token can't be used as a bearer token
+        assert!(!state.is_access_token_valid(&access_token).await);
+
+        // It works with a DPoP proof for the userinfo endpoint
+        let proof = dpop_proof(
+            &state,
+            &key,
+            "GET",
+            &userinfo_endpoint,
+            Some(&access_token),
+            None,
+        );
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", &proof)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // But the same proof can't be used twice
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", proof)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // But not with a proof for another endpoint
+        let proof = dpop_proof(
+            &state,
+            &key,
+            "POST",
+            &token_endpoint,
+            Some(&access_token),
+            None,
+        );
+        let request = Request::get(mas_router::OidcUserinfo::PATH)
+            .header("Authorization", format!("DPoP {access_token}"))
+            .header("DPoP", proof)
+            .empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // Refreshing without a proof fails
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidDpopProof);
+
+        // Refreshing with a proof with an unknown nonce asks for a fresh one
+        let proof = dpop_proof(
+            &state,
+            &key,
+            "POST",
+            &token_endpoint,
+            None,
+            Some("not-a-nonce"),
+        );
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let nonce = response
+            .headers()
+            .get("DPoP-Nonce")
+            .expect("to have a nonce")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::UseDpopNonce);
+
+        // Refreshing with a valid proof and nonce works
+        let proof = dpop_proof(&state, &key, "POST", &token_endpoint, None, Some(&nonce));
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", &proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.headers().contains_key("DPoP-Nonce"));
+        let response: AccessTokenResponse = response.json();
+        assert_eq!(response.token_type, OAuthAccessTokenType::DPoP);
+
+        // Replaying the proof with the new refresh token fails
+        let request = Request::post(mas_router::OAuth2TokenEndpoint::PATH)
+            .header("DPoP", proof)
+            .form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": response.refresh_token,
+                "client_id": client.client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidDpopProof);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_inactivity_expiry(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.refresh_token_policies.default = RefreshTokenPolicy {
+            lifetimes: RefreshTokenLifetimes {
+                inactivity: Some(Duration::days(7)),
+                absolute: None,
+            },
+            ..RefreshTokenPolicy::default()
+        };
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Using the refresh token within the inactivity window works
+        state.clock.advance(Duration::days(6));
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        let refresh_token = response.refresh_token.expect("to have a refresh token");
+
+        // Leaving the new refresh token unused for too long expires it
+        state.clock.advance(Duration::days(8));
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+
+        // The session should have been ended
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_reuse_revokes_session(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.refresh_token_policies.default = RefreshTokenPolicy {
+            revoke_session_on_reuse: true,
+            ..RefreshTokenPolicy::default()
+        };
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Use the refresh token once
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        let new_refresh_token = response.refresh_token.expect("to have a refresh token");
+        assert_ne!(new_refresh_token, refresh_token);
+
+        // Replaying it fails, and ends the session
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+        repo.save().await.unwrap();
+
+        // So the new refresh token doesn't work anymore either
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": new_refresh_token,
+                "client_id": client.client_id,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_reuse_grace_period(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.refresh_token_policies.default = RefreshTokenPolicy {
+            revoke_session_on_reuse: true,
+            reuse_grace_period: Some(Duration::seconds(30)),
+            ..RefreshTokenPolicy::default()
+        };
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        let refresh = |refresh_token: &str| {
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }))
+        };
+
+        // Use the refresh token once
+        let response = state.request(refresh(&refresh_token)).await;
+        response.assert_status(StatusCode::OK);
+        let first: AccessTokenResponse = response.json();
+        let new_refresh_token = first
+            .refresh_token
+            .clone()
+            .expect("to have a refresh token");
+
+        // Replaying it shortly after gives the same tokens back
+        state.clock.advance(Duration::seconds(10));
+        let response = state.request(refresh(&refresh_token)).await;
+        response.assert_status(StatusCode::OK);
+        let second: AccessTokenResponse = response.json();
+        assert_eq!(second.access_token, first.access_token);
+        assert_eq!(second.refresh_token, first.refresh_token);
+        assert!(state.is_access_token_valid(&second.access_token).await);
+
+        // But only once, after which it is treated as a reuse
+        let response = state.request(refresh(&refresh_token)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+
+        let mut repo = state.repository().await.unwrap();
+        let session = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(session.is_finished());
+        repo.save().await.unwrap();
+
+        // The new refresh token doesn't work anymore either
+        let response = state.request(refresh(&new_refresh_token)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_reuse_after_grace_period(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.refresh_token_policies.default = RefreshTokenPolicy {
+            reuse_grace_period: Some(Duration::seconds(30)),
+            ..RefreshTokenPolicy::default()
+        };
+
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        let refresh = |refresh_token: &str| {
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }))
+        };
+
+        let response = state.request(refresh(&refresh_token)).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        let second_refresh_token = response.refresh_token.expect("to have a refresh token");
+
+        let response = state.request(refresh(&second_refresh_token)).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        let third_refresh_token = response.refresh_token.expect("to have a refresh token");
+
+        // Once the client used the new refresh token, the old one can't be
+        // replayed anymore, even within the grace period
+        let response = state.request(refresh(&refresh_token)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Nor can a refresh token be replayed after the grace period
+        let response = state.request(refresh(&third_refresh_token)).await;
+        response.assert_status(StatusCode::OK);
+
+        state.clock.advance(Duration::seconds(31));
+        let response = state.request(refresh(&third_refresh_token)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_refresh_token_without_rotation(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Disable rotation for this client only
+        state.site_config.refresh_token_policies.clients = Arc::new(HashMap::from([(
+            client.id,
+            RefreshTokenPolicy {
+                rotation: false,
+                ..RefreshTokenPolicy::default()
+            },
+        )]));
+
+        // The same refresh token can be used multiple times
+        let mut previous_access_token = None;
+        for _ in 0..2 {
+            let request =
+                Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                    "grant_type": "refresh_token",
+                    "refresh_token": refresh_token,
+                    "client_id": client.client_id,
+                }));
+
+            let response = state.request(request).await;
+            response.assert_status(StatusCode::OK);
+            let response: AccessTokenResponse = response.json();
+            assert_eq!(
+                response.refresh_token.as_deref(),
+                Some(refresh_token.as_str())
+            );
+            assert_ne!(Some(&response.access_token), previous_access_token.as_ref());
+
+            // The previous access token got revoked
+            if let Some(previous_access_token) = previous_access_token {
+                let mut repo = state.repository().await.unwrap();
+                let access_token = repo
+                    .oauth2_access_token()
+                    .find_by_token(&previous_access_token)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert!(!access_token.is_valid(state.clock.now()));
+                repo.save().await.unwrap();
+            }
+
+            previous_access_token = Some(response.access_token);
+        }
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_client_credentials(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["client_credentials"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        // Call the token endpoint with an empty scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert!(response.scope.is_none());
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // We should be allowed to ask for the GraphQL API scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
                 "scope": "urn:mas:graphql:*"
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert_eq!(response.scope, Some("urn:mas:graphql:*".parse().unwrap()));
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        // We should be NOT allowed to ask for the MAS admin scope
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "scope": "urn:mas:admin"
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        // Now, if we add the client to the admin list in the policy, it should work
+        let state = {
+            let mut state = state;
+            state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
+                "admin_clients": [client_id]
+            }))
+            .await
+            .unwrap();
+            state
+        };
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "scope": "urn:mas:admin"
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response: AccessTokenResponse = response.json();
+        assert!(response.refresh_token.is_none());
+        assert!(response.expires_in.is_some());
+        assert_eq!(response.scope, Some("urn:mas:admin".parse().unwrap()));
+
+        // Revoke the token
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": response.access_token,
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_client_sessions_quota(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.max_active_sessions_per_client = NonZeroU32::new(2);
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["client_credentials"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        let token_request = || {
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+            }))
+        };
+
+        // The first two sessions can be started
+        let mut access_tokens = Vec::new();
+        for _ in 0..2 {
+            let response = state.request(token_request()).await;
+            response.assert_status(StatusCode::OK);
+            let response: AccessTokenResponse = response.json();
+            access_tokens.push(response.access_token);
+        }
+
+        // The third one is refused
+        let response = state.request(token_request()).await;
+        response.assert_status(StatusCode::FORBIDDEN);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::AccessDenied);
+
+        // Revoking a token ends its session, which makes room for a new one
+        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
+            "token": access_tokens[0],
+            "client_id": client_id,
+            "client_secret": client_secret,
+        }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let response = state.request(token_request()).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_user_token_quota(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        state.site_config.user_token_rate_limit =
+            Some(Quota::per_hour(NonZeroU32::new(2).unwrap()));
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "redirect_uris": ["https://example.com/callback"],
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .find_by_client_id(&client_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut state.rng(),
+                &state.clock,
+                &client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        let (_, RefreshToken { refresh_token, .. }) = generate_token_pair(
+            &mut state.rng(),
+            &state.clock,
+            &mut repo,
+            &session,
+            Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Keep the same refresh token, to focus on the quota
+        state.site_config.refresh_token_policies.clients = Arc::new(HashMap::from([(
+            client.id,
+            RefreshTokenPolicy {
+                rotation: false,
+                ..RefreshTokenPolicy::default()
+            },
+        )]));
+
+        let token_request = || {
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "refresh_token",
+                "refresh_token": refresh_token,
+                "client_id": client.client_id,
+            }))
+        };
+
+        for _ in 0..2 {
+            let response = state.request(token_request()).await;
+            response.assert_status(StatusCode::OK);
+        }
+
+        // The hourly quota is used up, and replenishes every 30 minutes
+        let response = state.request(token_request()).await;
+        response.assert_status(StatusCode::TOO_MANY_REQUESTS);
+        response.assert_header_value(RETRY_AFTER, "1800");
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::TemporarilyUnavailable);
+
+        state.clock.advance(Duration::minutes(30));
+        let response = state.request(token_request()).await;
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_resource_indicator(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["client_credentials"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        // Resource indicators can't have a fragment
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "resource": "https://api.example.com/#fragment",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidTarget);
+
+        // Ask for a token for a specific resource
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "resource": "https://api.example.com/",
+            }));
 
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
         let response: AccessTokenResponse = response.json();
-        assert!(response.refresh_token.is_none());
-        assert!(response.expires_in.is_some());
-        assert_eq!(response.scope, Some("urn:mas:graphql:*".parse().unwrap()));
 
-        // Revoke the token
-        let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
-        }));
+        // The resource is the audience of the token
+        let request =
+            Request::post(mas_router::OAuth2Introspection::PATH).form(serde_json::json!({
+                "token": response.access_token,
+                "client_id": client_id,
+                "client_secret": client_secret,
+            }));
 
         let response = state.request(request).await;
         response.assert_status(StatusCode::OK);
+        let response: IntrospectionResponse = response.json();
+        assert!(response.active);
+        assert_eq!(response.aud.as_deref(), Some("https://api.example.com/"));
+    }
 
-        // We should be NOT allowed to ask for the MAS admin scope
+    /// A self-signed certificate for `CN=client.example.com,O=Example,C=FR`
+    const CLIENT_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBZjCCAQugAwIBAgIBATAKBggqhkjOPQQDAjA8MQswCQYDVQQGEwJGUjEQMA4G
+A1UECgwHRXhhbXBsZTEbMBkGA1UEAwwSY2xpZW50LmV4YW1wbGUuY29tMB4XDTIz
+MDEwMTAwMDAwMFoXDTMzMDEwMTAwMDAwMFowPDELMAkGA1UEBhMCRlIxEDAOBgNV
+BAoMB0V4YW1wbGUxGzAZBgNVBAMMEmNsaWVudC5leGFtcGxlLmNvbTBZMBMGByqG
+SM49AgEGCCqGSM49AwEHA0IABFdPmSxbkzqGG5c9XGZ1Z9/IxmTb4rxtnNYxB1ZD
+SEN3EggIaAf8aE0jFeM5qXomq2D38EG13qM2qvJh6MdKaFswCgYIKoZIzj0EAwID
+SQAwRgIhAPdXs9x8Ves5WiDILFCF6gIVerGu24M4AyfkK9Va7YhQAiEAkqTLFg/B
+KGZ2CMPOCfLS7lRMoFo+yMWD17QZb+/wbXM=
+-----END CERTIFICATE-----
+";
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_certificate_bound_access_tokens(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let certificate = ClientCertificate::from_pem(CLIENT_CERTIFICATE).unwrap();
+
+        // Provision a static client which uses certificate-bound access tokens
+        let mut repo = state.repository().await.unwrap();
+        let client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_datetime_with_source(state.clock.now().into(), &mut state.rng()),
+                OAuthClientAuthenticationMethod::ClientSecretPost,
+                Some(state.encrypter.encrypt_to_string(b"secret").unwrap()),
+                None,
+                None,
+                Vec::new(),
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                true,
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Without a certificate, the client can't get a token
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
                 "grant_type": "client_credentials",
-                "client_id": client_id,
-                "client_secret": client_secret,
-                "scope": "urn:mas:admin"
+                "client_id": client.client_id,
+                "client_secret": "secret",
             }));
 
         let response = state.request(request).await;
-        response.assert_status(StatusCode::FORBIDDEN);
-
+        response.assert_status(StatusCode::BAD_REQUEST);
         let ClientError { error, .. } = response.json();
-        assert_eq!(error, ClientErrorCode::InvalidScope);
+        assert_eq!(error, ClientErrorCode::InvalidRequest);
+
+        // With one, the token is bound to it
+        let mut request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client.client_id,
+                "client_secret": "secret",
+            }));
+        request.extensions_mut().insert(certificate.clone());
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: AccessTokenResponse = response.json();
+        assert_eq!(response.token_type, OAuthAccessTokenType::Bearer);
+
+        let request =
+            Request::post(mas_router::OAuth2Introspection::PATH).form(serde_json::json!({
+                "token": response.access_token,
+                "client_id": client.client_id,
+                "client_secret": "secret",
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let response: IntrospectionResponse = response.json();
+        assert!(response.active);
+        let cnf = response.cnf.expect("to have a confirmation");
+        assert_eq!(
+            cnf.x5t_s256.as_deref(),
+            Some(certificate.thumbprint_sha256().as_str())
+        );
+        assert_eq!(cnf.jkt, None);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_token_exchange(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        state.create_user("john", "hunter2").await;
+        state.login(&cookies, "john", "hunter2").await;
+
+        // Get a token for the user through a regular client
+        let redirect_uri = "https://example.com/callback";
+        let user_client_id = state.register_client(redirect_uri).await;
+        let subject = state
+            .run_authorization_code_flow(
+                &cookies,
+                &user_client_id,
+                redirect_uri,
+                "openid urn:matrix:org.matrix.msc2967.client:api:*",
+            )
+            .await;
+
+        // Provision the client which will exchange the token
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "client_secret_post",
+                "grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let response: ClientRegistrationResponse = response.json();
+        let client_id = response.client_id;
+        let client_secret = response.client_secret.expect("to have a client secret");
+
+        let exchange_request = |scope: &str| {
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+                "client_id": client_id,
+                "client_secret": client_secret,
+                "subject_token": subject.access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+                "scope": scope,
+            }))
+        };
+
+        // The client is not allowed to exchange tokens by the policy yet
+        let response = state.request(exchange_request("openid")).await;
+        response.assert_status(StatusCode::FORBIDDEN);
 
-        // Now, if we add the client to the admin list in the policy, it should work
         let state = {
             let mut state = state;
             state.policy_factory = crate::test_utils::policy_factory(serde_json::json!({
-                "admin_clients": [client_id]
+                "token_exchange_clients": [client_id]
             }))
             .await
             .unwrap();
             state
         };
 
+        // It can't get scopes which were not granted to the subject token
+        let response = state.request(exchange_request("openid email")).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidScope);
+
+        // Only access tokens can be exchanged
         let request =
             Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
-                "grant_type": "client_credentials",
+                "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
                 "client_id": client_id,
                 "client_secret": client_secret,
-                "scope": "urn:mas:admin"
+                "subject_token": subject.access_token,
+                "subject_token_type": "urn:ietf:params:oauth:token-type:id_token",
             }));
-
         let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidRequest);
+
+        // Now downscope the token
+        let response = state.request(exchange_request("openid")).await;
         response.assert_status(StatusCode::OK);
 
         let response: AccessTokenResponse = response.json();
         assert!(response.refresh_token.is_none());
         assert!(response.expires_in.is_some());
-        assert_eq!(response.scope, Some("urn:mas:admin".parse().unwrap()));
-
-        // Revoke the token
+        assert_eq!(response.scope, Some(Scope::from_iter([OPENID])));
+        assert_eq!(
+            response.issued_token_type,
+            Some(TokenTypeIdentifier::AccessToken)
+        );
+        assert!(state.is_access_token_valid(&response.access_token).await);
+
+        // Revoking the subject token ends the derived session as well
         let request = Request::post(mas_router::OAuth2Revocation::PATH).form(serde_json::json!({
-            "token": response.access_token,
-            "client_id": client_id,
-            "client_secret": client_secret,
+            "token": subject.access_token,
+            "client_id": user_client_id,
         }));
+        let revoke_response = state.request(request).await;
+        revoke_response.assert_status(StatusCode::OK);
 
-        let response = state.request(request).await;
-        response.assert_status(StatusCode::OK);
+        assert!(!state.is_access_token_valid(&response.access_token).await);
+
+        // And the subject token can't be exchanged anymore
+        let response = state.request(exchange_request("openid")).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
     }
 
     #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
@@ -1086,4 +3215,200 @@ mod tests {
         let ClientError { error, .. } = response.json();
         assert_eq!(error, ClientErrorCode::UnsupportedGrantType);
     }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_device_code_grant(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Provision a client
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "client_uri": "https://example.com/",
+                "contacts": ["contact@example.com"],
+                "token_endpoint_auth_method": "none",
+                "grant_types": ["urn:ietf:params:oauth:grant-type:device_code", "refresh_token"],
+                "response_types": [],
+            }));
+
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+
+        let ClientRegistrationResponse { client_id, .. } = response.json();
+
+        // Start a device code grant
+        let request = Request::post(mas_router::OAuth2DeviceAuthorizationEndpoint::PATH).form(
+            serde_json::json!({
+                "client_id": client_id,
+                "scope": "openid",
+            }),
+        );
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let device_grant: DeviceAuthorizationResponse = response.json();
+        assert_eq!(device_grant.user_code.len(), 8);
+        assert!(device_grant.verification_uri_complete.is_some());
+
+        // Poll the token endpoint, it should be pending
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::AuthorizationPending);
+
+        // Polling again right away is too fast
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::SlowDown);
+
+        // Let's provision a user and approve the grant. This part is hard to test
+        // with just HTTP requests, so we'll use the repository directly.
+        let mut repo = state.repository().await.unwrap();
+
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "alice".to_owned())
+            .await
+            .unwrap();
+
+        let browser_session = repo
+            .browser_session()
+            .add(&mut state.rng(), &state.clock, &user, None)
+            .await
+            .unwrap();
+
+        let grant = repo
+            .oauth2_device_code_grant()
+            .find_by_user_code(&device_grant.user_code)
+            .await
+            .unwrap()
+            .unwrap();
+
+        repo.oauth2_device_code_grant()
+            .fulfill(&state.clock, grant, &browser_session)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        // Now we should be able to exchange the device code, once the polling
+        // interval elapsed
+        state.clock.advance(device_grant.interval.unwrap());
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let AccessTokenResponse {
+            access_token,
+            refresh_token,
+            id_token,
+            ..
+        } = response.json();
+        assert!(refresh_token.is_some());
+        assert!(id_token.is_some());
+
+        // Check that the token is valid
+        assert!(state.is_access_token_valid(&access_token).await);
+
+        // The device code can't be used twice
+        state.clock.advance(device_grant.interval.unwrap());
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::InvalidGrant);
+
+        // Start another grant, which the user will reject
+        let request = Request::post(mas_router::OAuth2DeviceAuthorizationEndpoint::PATH).form(
+            serde_json::json!({
+                "client_id": client_id,
+                "scope": "openid",
+            }),
+        );
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let device_grant: DeviceAuthorizationResponse = response.json();
+
+        let mut repo = state.repository().await.unwrap();
+        let grant = repo
+            .oauth2_device_code_grant()
+            .find_by_device_code(&device_grant.device_code)
+            .await
+            .unwrap()
+            .unwrap();
+
+        repo.oauth2_device_code_grant()
+            .reject(&state.clock, grant, &browser_session)
+            .await
+            .unwrap();
+
+        repo.save().await.unwrap();
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::AccessDenied);
+
+        // Grants expire after a while
+        let request = Request::post(mas_router::OAuth2DeviceAuthorizationEndpoint::PATH).form(
+            serde_json::json!({
+                "client_id": client_id,
+                "scope": "openid",
+            }),
+        );
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+
+        let device_grant: DeviceAuthorizationResponse = response.json();
+
+        state
+            .clock
+            .advance(device_grant.expires_in + Duration::seconds(1));
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+                "device_code": device_grant.device_code,
+                "client_id": client_id,
+            }));
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        let ClientError { error, .. } = response.json();
+        assert_eq!(error, ClientErrorCode::ExpiredToken);
+    }
 }
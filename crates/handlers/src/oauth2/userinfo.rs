@@ -17,12 +17,14 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use hyper::StatusCode;
+use hyper::{header::CONTENT_TYPE, StatusCode};
 use mas_axum_utils::{
+    http_client_factory::HttpClientFactory,
     jwt::JwtResponse,
     sentry::SentryEventID,
     user_authorization::{AuthorizationVerificationError, UserAuthorization},
 };
+use mas_data_model::UserEmail;
 use mas_jose::{
     constraints::Constrainable,
     jwt::{JsonWebSignatureHeader, Jwt},
@@ -37,7 +39,8 @@ use serde::Serialize;
 use serde_with::skip_serializing_none;
 use thiserror::Error;
 
-use crate::{impl_from_error_for_route, BoundActivityTracker};
+use super::encrypt_for_client;
+use crate::{impl_from_error_for_route, BoundActivityTracker, SiteConfig};
 
 #[skip_serializing_none]
 #[derive(Serialize)]
@@ -82,6 +85,8 @@ pub enum RouteError {
 impl_from_error_for_route!(mas_storage::RepositoryError);
 impl_from_error_for_route!(mas_keystore::WrongAlgorithmError);
 impl_from_error_for_route!(mas_jose::jwt::JwtSignatureError);
+impl_from_error_for_route!(super::ResponseEncryptionError);
+impl_from_error_for_route!(serde_json::Error);
 
 impl IntoResponse for RouteError {
     fn into_response(self) -> axum::response::Response {
@@ -107,9 +112,18 @@ pub async fn get(
     activity_tracker: BoundActivityTracker,
     mut repo: BoxRepository,
     State(key_store): State<Keystore>,
+    State(http_client_factory): State<HttpClientFactory>,
+    State(site_config): State<SiteConfig>,
     user_authorization: UserAuthorization,
 ) -> Result<Response, RouteError> {
-    let session = user_authorization.protected(&mut repo, &clock).await?;
+    let session = user_authorization
+        .protected(
+            &mut repo,
+            &clock,
+            &url_builder.oidc_userinfo_endpoint(),
+            &site_config.rate_limiter,
+        )
+        .await?;
 
     // This endpoint requires the `openid` scope.
     if !session.scope.contains("openid") {
@@ -131,7 +145,19 @@ pub async fn get(
         .await?
         .ok_or(RouteError::NoSuchUser)?;
 
-    let user_email = if session.scope.contains(&scope::EMAIL) {
+    // The email claims are returned either with the `email` scope, or if the
+    // client asked for them with the `claims` parameter
+    let requested = |claim| {
+        session
+            .claims
+            .as_ref()
+            .is_some_and(|claims| claims.requests_userinfo_claim(claim))
+    };
+    let email_scope = session.scope.contains(&scope::EMAIL);
+    let wants_email = email_scope || requested("email");
+    let wants_email_verified = email_scope || requested("email_verified");
+
+    let user_email = if wants_email || wants_email_verified {
         repo.user_email().get_primary(&user).await?
     } else {
         None
@@ -140,8 +166,11 @@ pub async fn get(
     let user_info = UserInfo {
         sub: user.sub.clone(),
         username: user.username.clone(),
-        email_verified: user_email.as_ref().map(|u| u.confirmed_at.is_some()),
-        email: user_email.map(|u| u.email),
+        email_verified: user_email
+            .as_ref()
+            .filter(|_| wants_email_verified)
+            .map(UserEmail::is_verified),
+        email: user_email.filter(|_| wants_email).map(|u| u.email),
     };
 
     let client = repo
@@ -150,7 +179,7 @@ pub async fn get(
         .await?
         .ok_or(RouteError::NoSuchClient)?;
 
-    if let Some(alg) = client.userinfo_signed_response_alg {
+    if let Some(alg) = client.userinfo_signed_response_alg.clone() {
         let key = key_store
             .signing_key_for_algorithm(&alg)
             .ok_or(RouteError::InvalidSigningKey)?;
@@ -161,13 +190,48 @@ pub async fn get(
 
         let user_info = SignedUserInfo {
             iss: url_builder.oidc_issuer().to_string(),
-            aud: client.client_id,
+            aud: client.client_id.clone(),
             user_info,
         };
 
         let token = Jwt::sign_with_rng(&mut rng, header, user_info, &signer)?;
+
+        // Responses which are both signed and encrypted are nested JWTs
+        if let Some(encrypted_response) = client.userinfo_encrypted_response() {
+            let token = encrypt_for_client(
+                &mut rng,
+                &http_client_factory,
+                &client,
+                encrypted_response,
+                Some("JWT"),
+                token.into_string().as_bytes(),
+            )
+            .await?;
+
+            return Ok(encrypted_jwt_response(token));
+        }
+
         Ok(JwtResponse(token).into_response())
+    } else if let Some(encrypted_response) = client.userinfo_encrypted_response() {
+        // Responses which are only encrypted have the JSON user info as payload
+        let payload = serde_json::to_vec(&user_info)?;
+        let token = encrypt_for_client(
+            &mut rng,
+            &http_client_factory,
+            &client,
+            encrypted_response,
+            None,
+            &payload,
+        )
+        .await?;
+
+        Ok(encrypted_jwt_response(token))
     } else {
         Ok(Json(user_info).into_response())
     }
 }
+
+/// Build a response with an encrypted JWT, as an `application/jwt` document
+fn encrypted_jwt_response(token: String) -> Response {
+    ([(CONTENT_TYPE, "application/jwt")], token).into_response()
+}
@@ -190,9 +190,12 @@ impl PasswordManager {
     ) -> Result<Option<(SchemeVersion, String)>, anyhow::Error> {
         let inner = self.get_inner()?;
 
-        // If the current scheme isn't the default one, we also hash with the default
-        // one so that
-        let new_hash_fut: OptionFuture<_> = (scheme != inner.current_version)
+        // If the current scheme isn't the default one, or if the password was hashed
+        // with weaker parameters than the current ones, we also hash with the default
+        // one so that the upgraded hash can be saved
+        let needs_upgrade =
+            scheme != inner.current_version || inner.current_hasher.needs_rehash(&hashed_password);
+        let new_hash_fut: OptionFuture<_> = needs_upgrade
             .then(|| self.hash(rng, password.clone()))
             .into();
 
@@ -248,6 +251,10 @@ impl Hasher {
         self.algorithm
             .verify_blocking(hashed_password, password, self.pepper.as_deref())
     }
+
+    fn needs_rehash(&self, hashed_password: &str) -> bool {
+        self.algorithm.needs_rehash(hashed_password)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -353,6 +360,40 @@ impl Algorithm {
 
         Ok(())
     }
+
+    /// Returns `true` if the hash was made with weaker parameters than the
+    /// ones this algorithm currently uses, or if they can't be parsed
+    fn needs_rehash(self, hashed_password: &str) -> bool {
+        match self {
+            Algorithm::Bcrypt { cost } => hashed_password
+                .parse::<bcrypt::HashParts>()
+                .map_or(true, |parts| parts.get_cost() < cost),
+
+            Algorithm::Argon2id => {
+                let Ok(hashed_password) = PasswordHash::new(hashed_password) else {
+                    return true;
+                };
+
+                let current = argon2::Params::default();
+                argon2::Params::try_from(&hashed_password).map_or(true, |params| {
+                    hashed_password.algorithm != argon2::Algorithm::Argon2id.ident()
+                        || params.m_cost() < current.m_cost()
+                        || params.t_cost() < current.t_cost()
+                        || params.p_cost() < current.p_cost()
+                })
+            }
+
+            Algorithm::Pbkdf2 => {
+                let Ok(hashed_password) = PasswordHash::new(hashed_password) else {
+                    return true;
+                };
+
+                pbkdf2::Params::try_from(&hashed_password).map_or(true, |params| {
+                    params.rounds < pbkdf2::Params::default().rounds
+                })
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -454,6 +495,67 @@ mod tests {
         assert!(alg.verify_blocking(&hash, password, Some(pepper)).is_err());
     }
 
+    #[test]
+    fn needs_rehash() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let password = b"hunter2";
+
+        let weak = Algorithm::Bcrypt { cost: 4 };
+        let strong = Algorithm::Bcrypt { cost: 5 };
+        let hash = weak
+            .hash_blocking(&mut rng, password, None)
+            .expect("Couldn't hash password");
+        assert!(!weak.needs_rehash(&hash));
+        assert!(strong.needs_rehash(&hash));
+
+        let hash = Algorithm::Argon2id
+            .hash_blocking(&mut rng, password, None)
+            .expect("Couldn't hash password");
+        assert!(!Algorithm::Argon2id.needs_rehash(&hash));
+        assert!(Algorithm::Argon2id
+            .needs_rehash("$argon2id$v=19$m=1024,t=1,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNo"));
+
+        let hash = Algorithm::Pbkdf2
+            .hash_blocking(&mut rng, password, None)
+            .expect("Couldn't hash password");
+        assert!(!Algorithm::Pbkdf2.needs_rehash(&hash));
+
+        // Garbage is always re-hashed
+        assert!(weak.needs_rehash("not-a-hash"));
+        assert!(Algorithm::Argon2id.needs_rehash("not-a-hash"));
+        assert!(Algorithm::Pbkdf2.needs_rehash("not-a-hash"));
+    }
+
+    #[tokio::test]
+    async fn verify_and_upgrade_parameters() {
+        // Changing the parameters of the current scheme re-hashes the password,
+        // keeping the same version
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let password = Zeroizing::new(b"hunter2".to_vec());
+
+        let manager = PasswordManager::new([(1, Hasher::bcrypt(4, None))]).unwrap();
+        let (version, hash) = manager
+            .hash(&mut rng, password.clone())
+            .await
+            .expect("Failed to hash");
+
+        let manager = PasswordManager::new([(1, Hasher::bcrypt(5, None))]).unwrap();
+        let (new_version, new_hash) = manager
+            .verify_and_upgrade(&mut rng, version, password.clone(), hash)
+            .await
+            .expect("Failed to verify")
+            .expect("Password should have been upgraded");
+
+        assert_eq!(new_version, 1);
+
+        // The new hash doesn't need another upgrade
+        let res = manager
+            .verify_and_upgrade(&mut rng, new_version, password, new_hash)
+            .await
+            .expect("Failed to verify");
+        assert!(res.is_none());
+    }
+
     #[tokio::test]
     async fn hash_verify_and_upgrade() {
         // Tests the whole password manager, by hashing a password and upgrading it
@@ -20,30 +20,86 @@ use axum::{
     http::request::Parts,
     TypedHeader,
 };
-use mas_axum_utils::language_detection::AcceptLanguage;
+use mas_axum_utils::{
+    cookies::{CookieJar, CookieManager},
+    language_detection::AcceptLanguage,
+};
+use mas_data_model::User;
 use mas_i18n::{DataLocale, Translator};
+use mas_storage::{BoxRepository, RepositoryAccess, RepositoryError};
+
+/// Name of the cookie holding the preferred language of the logged-in user
+static COOKIE_NAME: &str = "language";
 
 pub struct PreferredLanguage(pub DataLocale);
 
+/// Remember the preferred language of a user who just logged in
+///
+/// If the user doesn't have a preferred language yet, it is inferred from the
+/// language of the current request and saved. The preferred language is then
+/// stored in a cookie, so that it is honored on subsequent requests, even if
+/// they don't have an `Accept-Language` header.
+///
+/// # Errors
+///
+/// Returns an error if the preferred language could not be saved
+pub async fn remember_locale(
+    repo: &mut BoxRepository,
+    locale: &DataLocale,
+    user: User,
+    cookie_jar: CookieJar,
+) -> Result<(User, CookieJar), RepositoryError> {
+    let user = if user.locale.is_none() {
+        repo.user().set_locale(user, locale.to_string()).await?
+    } else {
+        user
+    };
+
+    let cookie_jar = save_locale(cookie_jar, &user);
+    Ok((user, cookie_jar))
+}
+
+/// Save the preferred language of the user in a cookie, if they have one
+#[must_use]
+pub fn save_locale(cookie_jar: CookieJar, user: &User) -> CookieJar {
+    match &user.locale {
+        Some(locale) => cookie_jar.save(COOKIE_NAME, locale, true),
+        None => cookie_jar,
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for PreferredLanguage
 where
     S: Send + Sync,
     Arc<Translator>: FromRef<S>,
+    CookieManager: FromRef<S>,
 {
     type Rejection = Infallible;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let translator: Arc<Translator> = FromRef::from_ref(state);
+        let cookie_jar = CookieJar::from_request_parts(parts, state).await?;
         let accept_language: Option<TypedHeader<AcceptLanguage>> =
             FromRequestParts::from_request_parts(parts, state).await?;
         let supported_language = translator.available_locales();
 
-        let locale = accept_language
-            .and_then(|TypedHeader(accept_language)| {
-                accept_language.iter().find_map(|lang| {
-                    let locale: DataLocale = lang.into();
-                    supported_language.contains(&&locale).then_some(locale)
+        // The language saved in the cookie takes precedence over the one
+        // advertised by the browser
+        let saved_language = cookie_jar
+            .load::<String>(COOKIE_NAME)
+            .ok()
+            .flatten()
+            .and_then(|locale| locale.parse::<DataLocale>().ok())
+            .filter(|locale| supported_language.contains(&locale));
+
+        let locale = saved_language
+            .or_else(|| {
+                accept_language.and_then(|TypedHeader(accept_language)| {
+                    accept_language.iter().find_map(|lang| {
+                        let locale: DataLocale = lang.into();
+                        supported_language.contains(&&locale).then_some(locale)
+                    })
                 })
             })
             .unwrap_or("en".parse().unwrap());
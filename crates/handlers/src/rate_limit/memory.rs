@@ -0,0 +1,53 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+
+use super::Quota;
+
+/// Above this number of buckets, the full ones get cleaned up
+const CLEANUP_THRESHOLD: usize = 10_000;
+
+/// Keeps the buckets in memory, as the time at which each of them will be full
+/// again
+#[derive(Debug, Default)]
+pub(super) struct Memory {
+    buckets: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Memory {
+    pub fn take(&self, now: DateTime<Utc>, key: &str, quota: Quota) -> Option<DateTime<Utc>> {
+        let capacity = i32::try_from(quota.burst.get()).unwrap_or(i32::MAX);
+        let mut buckets = self.buckets.lock().expect("lock poisoned");
+
+        if buckets.len() > CLEANUP_THRESHOLD {
+            // Full buckets are the same as missing ones
+            buckets.retain(|_, full_at| *full_at > now);
+        }
+
+        // Taking a token pushes back the time at which the bucket is full by one
+        // interval. The bucket is empty if that would be more than `capacity`
+        // intervals in the future.
+        let full_at = buckets.get(key).copied().unwrap_or(now).max(now);
+        let new_full_at = full_at + quota.replenish_interval;
+        let limit = now + quota.replenish_interval * capacity;
+        if new_full_at > limit {
+            return Some(new_full_at - quota.replenish_interval * capacity);
+        }
+
+        buckets.insert(key.to_owned(), new_full_at);
+        None
+    }
+}
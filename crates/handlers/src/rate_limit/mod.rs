@@ -0,0 +1,390 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Rate limiting of requests, using token buckets kept in memory or shared
+//! between replicas in the database or in Redis
+
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, OnceLock},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use mas_axum_utils::dpop::DPoPReplayCache;
+use mas_data_model::User;
+use mas_storage::{Clock, RepositoryAccess, RepositoryTransaction};
+use mas_storage_pg::PgRepository;
+use opentelemetry::{
+    metrics::{Counter, Unit},
+    Key,
+};
+use sqlx::PgPool;
+use thiserror::Error;
+
+mod memory;
+mod redis;
+
+use self::{memory::Memory, redis::Redis};
+
+/// A rate limit, as a bucket of tokens: each request takes a token, and the
+/// bucket gets a new token every `replenish_interval`, up to `burst` tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    burst: NonZeroU32,
+    replenish_interval: Duration,
+}
+
+impl Quota {
+    /// Create a new quota, allowing `burst` requests at once and a new request
+    /// every `replenish_interval`
+    #[must_use]
+    pub const fn new(burst: NonZeroU32, replenish_interval: Duration) -> Self {
+        Self {
+            burst,
+            replenish_interval,
+        }
+    }
+
+    /// Create a new quota allowing `count` requests per hour. The whole hourly
+    /// allowance can be used at once, and it then replenishes evenly over the
+    /// hour.
+    #[must_use]
+    pub fn per_hour(count: NonZeroU32) -> Self {
+        let replenish_interval =
+            Duration::hours(1) / i32::try_from(count.get()).unwrap_or(i32::MAX);
+        Self::new(count, replenish_interval)
+    }
+}
+
+/// The request was rejected because the rate limit was exceeded
+#[derive(Debug, Error)]
+#[error("Rate limit exceeded, retry at {retry_at}")]
+pub struct RateLimited {
+    retry_at: DateTime<Utc>,
+}
+
+impl RateLimited {
+    /// How long to wait before the request would be allowed, rounded up to
+    /// the second, for use in a `Retry-After` header
+    #[must_use]
+    pub fn retry_after(&self, now: DateTime<Utc>) -> u64 {
+        let millis = (self.retry_at - now).num_milliseconds();
+        u64::try_from(millis).unwrap_or(0).div_ceil(1000)
+    }
+}
+
+const LIMITER: Key = Key::from_static_str("limiter");
+
+fn rejections_counter() -> &'static Counter<u64> {
+    static COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        let meter = opentelemetry::global::meter_with_version(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            Some(opentelemetry_semantic_conventions::SCHEMA_URL),
+            None,
+        );
+
+        meter
+            .u64_counter("mas.rate_limit.rejections")
+            .with_description("Number of requests rejected by a rate limiter")
+            .with_unit(Unit::new("{requests}"))
+            .init()
+    })
+}
+
+/// A wrapper around the supported rate limiting backends
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    inner: Arc<RateLimiterInner>,
+}
+
+#[derive(Debug)]
+enum RateLimiterInner {
+    Memory(Memory),
+    Postgres(PgPool),
+    Redis(Redis),
+}
+
+impl RateLimiter {
+    fn new(inner: RateLimiterInner) -> Self {
+        let inner = Arc::new(inner);
+        Self { inner }
+    }
+
+    /// Construct a rate limiter keeping its state in memory. Each replica of
+    /// the service then enforces the limits on its own.
+    #[must_use]
+    pub fn memory() -> Self {
+        Self::new(RateLimiterInner::Memory(Memory::default()))
+    }
+
+    /// Construct a rate limiter keeping its state in the database
+    #[must_use]
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::new(RateLimiterInner::Postgres(pool))
+    }
+
+    /// Construct a rate limiter keeping its state in a Redis server
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is invalid or if the connection to the
+    /// Redis server failed
+    pub async fn redis(url: &str) -> Result<Self, ::redis::RedisError> {
+        Ok(Self::new(RateLimiterInner::Redis(
+            Redis::connect(url).await?,
+        )))
+    }
+
+    /// Take a token from the bucket identified by `key`
+    ///
+    /// If the backend fails, the request is let through: a broken rate
+    /// limiter should not take the whole service down.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bucket is empty
+    #[tracing::instrument(name = "rate_limit.check", skip(self, clock, quota))]
+    pub async fn check(
+        &self,
+        clock: &dyn Clock,
+        key: &str,
+        quota: Quota,
+    ) -> Result<(), RateLimited> {
+        let now = clock.now();
+        let res = match self.inner.as_ref() {
+            RateLimiterInner::Memory(memory) => Ok(memory.take(now, key, quota)),
+            RateLimiterInner::Postgres(pool) => take_postgres(pool, clock, key, quota).await,
+            RateLimiterInner::Redis(redis) => redis.take(now, key, quota).await,
+        };
+
+        match res {
+            Ok(None) => Ok(()),
+            Ok(Some(retry_at)) => {
+                tracing::info!(%retry_at, "Rate limit exceeded");
+                // Keys look like `login:ip:127.0.0.1`, the first segment names
+                // the limiter without leaking the address or user in the metric
+                let limiter = key.split(':').next().unwrap_or(key).to_owned();
+                rejections_counter().add(1, &[LIMITER.string(limiter)]);
+                Err(RateLimited { retry_at })
+            }
+            Err(e) => {
+                tracing::error!("Failed to check rate limit, letting the request through: {e}");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Throttles the verification and recovery emails, both per address and per
+/// user, so that those endpoints can't be used to flood a mailbox
+#[derive(Debug, Clone)]
+pub struct EmailThrottle {
+    limiter: RateLimiter,
+    per_address: Quota,
+    per_user: Quota,
+}
+
+impl EmailThrottle {
+    /// Create a new [`EmailThrottle`], keeping its state in the given rate
+    /// limiter
+    #[must_use]
+    pub fn new(limiter: RateLimiter, per_address: Quota, per_user: Quota) -> Self {
+        Self {
+            limiter,
+            per_address,
+            per_user,
+        }
+    }
+
+    /// Check if an email can be sent to the given address, on behalf of the
+    /// given user if there is one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if too many emails were sent recently to this address
+    /// or on behalf of this user
+    pub async fn check(
+        &self,
+        clock: &dyn Clock,
+        user: Option<&User>,
+        email: &str,
+    ) -> Result<(), RateLimited> {
+        self.limiter
+            .check(clock, &format!("email:address:{email}"), self.per_address)
+            .await?;
+
+        if let Some(user) = user {
+            self.limiter
+                .check(clock, &format!("email:user:{}", user.id), self.per_user)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphql")]
+#[axum::async_trait]
+impl mas_graphql::EmailThrottle for EmailThrottle {
+    async fn check(&self, clock: &dyn Clock, user: &User, email: &str) -> Result<(), u64> {
+        EmailThrottle::check(self, clock, Some(user), email)
+            .await
+            .map_err(|e| e.retry_after(clock.now()))
+    }
+}
+
+/// Used DPoP proofs are remembered as buckets holding a single token, which
+/// only get full again once the proof expired. Like other limits, proofs are
+/// let through if the backend fails.
+#[axum::async_trait]
+impl DPoPReplayCache for RateLimiter {
+    async fn record(
+        &self,
+        clock: &dyn Clock,
+        jkt: &str,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> bool {
+        let replenish_interval = (expires_at - clock.now()).max(Duration::seconds(1));
+        let quota = Quota::new(NonZeroU32::new(1).unwrap(), replenish_interval);
+        self.check(clock, &format!("dpop:{jkt}:{jti}"), quota)
+            .await
+            .is_ok()
+    }
+}
+
+async fn take_postgres(
+    pool: &PgPool,
+    clock: &dyn Clock,
+    key: &str,
+    quota: Quota,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let mut repo = PgRepository::from_pool(pool).await?;
+    let retry_at = repo
+        .rate_limit()
+        .take(clock, key, quota.burst.get(), quota.replenish_interval)
+        .await?;
+    repo.save().await?;
+    Ok(retry_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_storage::clock::MockClock;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory() {
+        let clock = MockClock::default();
+        let limiter = RateLimiter::memory();
+        let quota = Quota::new(NonZeroU32::new(2).unwrap(), Duration::seconds(10));
+
+        limiter.check(&clock, "a", quota).await.unwrap();
+        limiter.check(&clock, "a", quota).await.unwrap();
+        let err = limiter.check(&clock, "a", quota).await.unwrap_err();
+        assert_eq!(err.retry_after(clock.now()), 10);
+
+        // Other buckets are independent
+        limiter.check(&clock, "b", quota).await.unwrap();
+
+        clock.advance(Duration::seconds(5));
+        let err = limiter.check(&clock, "a", quota).await.unwrap_err();
+        assert_eq!(err.retry_after(clock.now()), 5);
+
+        clock.advance(Duration::seconds(5));
+        limiter.check(&clock, "a", quota).await.unwrap();
+        limiter.check(&clock, "a", quota).await.unwrap_err();
+
+        // Once full again, the whole burst is available
+        clock.advance(Duration::seconds(20));
+        limiter.check(&clock, "a", quota).await.unwrap();
+        limiter.check(&clock, "a", quota).await.unwrap();
+        limiter.check(&clock, "a", quota).await.unwrap_err();
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_postgres(pool: PgPool) {
+        let clock = MockClock::default();
+        let limiter = RateLimiter::postgres(pool);
+        let quota = Quota::new(NonZeroU32::new(2).unwrap(), Duration::seconds(10));
+
+        limiter.check(&clock, "a", quota).await.unwrap();
+        limiter.check(&clock, "a", quota).await.unwrap();
+        let err = limiter.check(&clock, "a", quota).await.unwrap_err();
+        assert_eq!(err.retry_after(clock.now()), 10);
+
+        clock.advance(Duration::seconds(10));
+        limiter.check(&clock, "a", quota).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dpop_replay_cache() {
+        let clock = MockClock::default();
+        let limiter = RateLimiter::memory();
+        let expires_at = clock.now() + Duration::minutes(5);
+
+        assert!(limiter.record(&clock, "key", "proof", expires_at).await);
+        assert!(!limiter.record(&clock, "key", "proof", expires_at).await);
+
+        // Proofs are tracked per key
+        assert!(limiter.record(&clock, "other", "proof", expires_at).await);
+
+        // Proofs are forgotten once they expired
+        clock.advance(Duration::minutes(5));
+        assert!(limiter.record(&clock, "key", "proof", expires_at).await);
+    }
+
+    #[tokio::test]
+    async fn test_email_throttle() {
+        let clock = MockClock::default();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let throttle = EmailThrottle::new(
+            RateLimiter::memory(),
+            Quota::new(NonZeroU32::new(1).unwrap(), Duration::seconds(60)),
+            Quota::new(NonZeroU32::new(2).unwrap(), Duration::seconds(600)),
+        );
+        let user = User::samples(clock.now(), &mut rng).remove(0);
+
+        // The same address can't get two emails in a row
+        throttle
+            .check(&clock, Some(&user), "alice@example.com")
+            .await
+            .unwrap();
+        let err = throttle
+            .check(&clock, Some(&user), "alice@example.com")
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after(clock.now()), 60);
+
+        // The user can only send a few emails, across all addresses
+        throttle
+            .check(&clock, Some(&user), "bob@example.com")
+            .await
+            .unwrap();
+        let err = throttle
+            .check(&clock, Some(&user), "carol@example.com")
+            .await
+            .unwrap_err();
+        assert_eq!(err.retry_after(clock.now()), 300);
+
+        // Without a user, only the address is limited
+        throttle
+            .check(&clock, None, "dave@example.com")
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use redis::{aio::ConnectionManager, Client, RedisError, Script};
+
+use super::Quota;
+
+/// Atomically take a token from a bucket.
+///
+/// Like the other backends, the bucket is stored as the time at which it will
+/// be full again, in milliseconds since the epoch. The key expires once the
+/// bucket is full, as a full bucket is the same as a missing one.
+///
+/// Returns 0 if a token was taken, or the time at which the next token will be
+/// available otherwise.
+const TAKE_SCRIPT: &str = r"
+local now = tonumber(ARGV[1])
+local interval = tonumber(ARGV[2])
+local capacity = tonumber(ARGV[3])
+
+local full_at = tonumber(redis.call('GET', KEYS[1]) or now)
+if full_at < now then
+  full_at = now
+end
+
+local new_full_at = full_at + interval
+if new_full_at > now + interval * capacity then
+  return new_full_at - interval * capacity
+end
+
+redis.call('SET', KEYS[1], new_full_at, 'PX', new_full_at - now)
+return 0
+";
+
+/// Keeps the buckets in a Redis server
+pub(super) struct Redis {
+    connection: ConnectionManager,
+    script: Script,
+}
+
+impl std::fmt::Debug for Redis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Redis").finish_non_exhaustive()
+    }
+}
+
+impl Redis {
+    pub async fn connect(url: &str) -> Result<Self, RedisError> {
+        let client = Client::open(url)?;
+        let connection = ConnectionManager::new(client).await?;
+        Ok(Self {
+            connection,
+            script: Script::new(TAKE_SCRIPT),
+        })
+    }
+
+    pub async fn take(
+        &self,
+        now: DateTime<Utc>,
+        key: &str,
+        quota: Quota,
+    ) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+        let interval = quota.replenish_interval.num_milliseconds().max(1);
+
+        // The connection manager is cheap to clone, and reconnects on its own
+        let mut connection = self.connection.clone();
+        let retry_at: i64 = self
+            .script
+            .key(format!("mas:rate_limit:{key}"))
+            .arg(now.timestamp_millis())
+            .arg(interval)
+            .arg(quota.burst.get())
+            .invoke_async(&mut connection)
+            .await?;
+
+        if retry_at == 0 {
+            return Ok(None);
+        }
+
+        let retry_at = Utc
+            .timestamp_millis_opt(retry_at)
+            .single()
+            .unwrap_or_else(|| now + Duration::milliseconds(interval));
+        Ok(Some(retry_at))
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Publishes the keys used to sign outbound requests, so that their receivers
+//! can verify the signatures
+
+use axum::{extract::State, response::IntoResponse, Json};
+use mas_keystore::RequestSigner;
+
+#[tracing::instrument(name = "handlers.request_signing_keys.get", skip_all)]
+pub(crate) async fn get(State(request_signer): State<RequestSigner>) -> impl IntoResponse {
+    let jwks = request_signer.public_jwks();
+    Json(jwks)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_keystore::{RequestSigner, RequestSigningKey};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{init_tracing, RequestBuilderExt, ResponseExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_request_signing_keys(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+
+        // Nothing is published by default
+        let request = Request::get("/.well-known/mas-request-signing-keys.json").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let jwks: serde_json::Value = response.json();
+        assert_eq!(jwks, serde_json::json!({ "keys": [] }));
+
+        let active = RequestSigningKey::from_bytes("active", &[0x42; 32]);
+        let next = RequestSigningKey::from_bytes("next", &[0x43; 32]);
+        state.request_signer =
+            RequestSigner::new(vec![active]).with_published_only_keys(vec![next]);
+
+        let request = Request::get("/.well-known/mas-request-signing-keys.json").empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let jwks: serde_json::Value = response.json();
+        let keys = jwks["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["kid"], "active");
+        assert_eq!(keys[0]["kty"], "OKP");
+        assert_eq!(keys[0]["crv"], "Ed25519");
+        assert_eq!(keys[0]["alg"], "EdDSA");
+        assert_eq!(keys[1]["kid"], "next");
+    }
+}
@@ -12,20 +12,226 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{num::NonZeroU32, sync::Arc};
+
 use chrono::Duration;
+use mas_data_model::{CaptchaConfig, EmailNormalization, RefreshTokenPolicies, TermsOfService};
+use oauth2_types::scope::ScopeToken;
+use ulid::Ulid;
+use url::Url;
+
+use crate::{
+    rate_limit::{EmailThrottle, Quota, RateLimiter},
+    AvatarStore, BreachedPasswordCheck, DeviceConflictPolicy, DeviceNameTemplate, LoginLockout,
+    MaintenanceMode,
+};
+
+/// A scope declared by the operator, on top of the ones built into MAS.
+///
+/// Which grants and clients can request it is enforced by the policy, which
+/// gets the whole registry as `data.custom_scopes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomScope {
+    /// The scope token
+    pub token: ScopeToken,
+
+    /// Description shown to users on the consent screen
+    pub description: Option<String>,
+}
 
 /// Random site configuration we don't now where to put yet.
 #[derive(Debug, Clone)]
 pub struct SiteConfig {
     pub access_token_ttl: Duration,
     pub compat_token_ttl: Duration,
+
+    /// How refresh tokens behave, with per-client overrides
+    pub refresh_token_policies: RefreshTokenPolicies,
+
+    /// Scopes declared by the operator, which clients can request
+    pub custom_scopes: Arc<[CustomScope]>,
+
+    /// Where avatars uploaded by users are stored, if uploads are enabled
+    pub avatar_store: Option<AvatarStore>,
+
+    /// Rate limiter shared by the handlers
+    pub rate_limiter: RateLimiter,
+
+    /// Rate limit of password login attempts, per IP address
+    pub login_rate_limit: Quota,
+
+    /// Rate limit of registration attempts, per IP address
+    pub registration_rate_limit: Quota,
+
+    /// Rate limit of the device code lookups by user code, per IP address
+    pub device_code_link_rate_limit: Quota,
+
+    /// Rate limit of the GraphQL requests, per user, or per IP address for
+    /// anonymous requests
+    pub graphql_rate_limit: Quota,
+
+    /// Throttle of the verification and recovery emails
+    pub email_throttle: EmailThrottle,
+
+    /// Maximum number of active sessions a single client can have, if limited
+    pub max_active_sessions_per_client: Option<NonZeroU32>,
+
+    /// Rate limit of the tokens issued to a single user, if limited
+    pub user_token_rate_limit: Option<Quota>,
+
+    /// Temporary lockout after too many failed login attempts, if enabled
+    pub login_lockout: Option<LoginLockout>,
+
+    /// Whether the service is in maintenance mode
+    pub maintenance: MaintenanceMode,
+
+    /// Whether the email address is verified before the account gets created
+    /// on registration
+    pub verify_email_before_registration: bool,
+
+    /// Whether the email address given on registration is trusted without
+    /// being verified. Only set by the development server
+    pub skip_email_verification: bool,
+
+    /// URL prefixes users can be sent back to after logging in or out
+    pub allowed_next_urls: Arc<[Url]>,
+
+    /// How email addresses are normalized before being stored and compared
+    pub email_normalization: EmailNormalization,
+
+    /// Whether users can sign in with a link sent to their email address
+    pub email_login_links: bool,
+
+    /// Terms of service users have to accept, if any
+    pub terms: Option<TermsOfService>,
+
+    /// Template used to name compat devices when the client didn't supply a
+    /// name
+    pub compat_device_name_template: Option<DeviceNameTemplate>,
+
+    /// What to do when a new session asks for a device ID which is already
+    /// used by an active session of the same user
+    pub device_conflict_policy: DeviceConflictPolicy,
+
+    /// CAPTCHA protecting the registration and login forms, if enabled
+    pub captcha: Option<CaptchaConfig>,
+
+    /// Check of new passwords against known data breaches, if enabled
+    pub breached_password_check: Option<BreachedPasswordCheck>,
+
+    /// For how long users whose password login on the compatibility layer is
+    /// being deprecated can still use it
+    pub compat_password_deprecation_grace_period: Duration,
+
+    /// Clients which get Matrix-specific claims when introspecting tokens
+    pub matrix_introspection_clients: Arc<[Ulid]>,
+
+    /// Serialized origins allowed to embed the account management pages
+    pub embedding_origins: Arc<[String]>,
+}
+
+impl SiteConfig {
+    /// Returns `true` if users can be sent to the given URL after logging in
+    /// or out
+    #[must_use]
+    pub fn is_next_url_allowed(&self, url: &Url) -> bool {
+        self.allowed_next_urls
+            .iter()
+            .any(|prefix| is_url_prefix(prefix, url))
+    }
+}
+
+/// Check that `url` is on the same origin as `prefix`, under its path
+fn is_url_prefix(prefix: &Url, url: &Url) -> bool {
+    if prefix.scheme() != url.scheme()
+        || prefix.host() != url.host()
+        || prefix.port_or_known_default() != url.port_or_known_default()
+        || !url.username().is_empty()
+        || url.password().is_some()
+    {
+        return false;
+    }
+
+    let prefix_path = prefix.path();
+    let path = url.path();
+    match path.strip_prefix(prefix_path) {
+        // Don't let `/app` match `/application`
+        Some(rest) => prefix_path.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
 }
 
 impl Default for SiteConfig {
     fn default() -> Self {
+        let rate_limiter = RateLimiter::memory();
         Self {
             access_token_ttl: Duration::minutes(5),
             compat_token_ttl: Duration::minutes(5),
+            refresh_token_policies: RefreshTokenPolicies::default(),
+            custom_scopes: Arc::new([]),
+            avatar_store: None,
+            rate_limiter: rate_limiter.clone(),
+            login_rate_limit: Quota::new(NonZeroU32::new(5).unwrap(), Duration::seconds(20)),
+            registration_rate_limit: Quota::new(NonZeroU32::new(10).unwrap(), Duration::minutes(6)),
+            device_code_link_rate_limit: Quota::new(
+                NonZeroU32::new(10).unwrap(),
+                Duration::minutes(1),
+            ),
+            graphql_rate_limit: Quota::new(NonZeroU32::new(100).unwrap(), Duration::seconds(1)),
+            email_throttle: EmailThrottle::new(
+                rate_limiter,
+                Quota::new(NonZeroU32::new(1).unwrap(), Duration::minutes(1)),
+                Quota::new(NonZeroU32::new(10).unwrap(), Duration::minutes(6)),
+            ),
+            max_active_sessions_per_client: None,
+            user_token_rate_limit: None,
+            login_lockout: None,
+            maintenance: MaintenanceMode::default(),
+            verify_email_before_registration: false,
+            skip_email_verification: false,
+            allowed_next_urls: Arc::new([]),
+            email_normalization: EmailNormalization::default(),
+            email_login_links: false,
+            terms: None,
+            compat_device_name_template: None,
+            device_conflict_policy: DeviceConflictPolicy::default(),
+            captcha: None,
+            breached_password_check: None,
+            compat_password_deprecation_grace_period: Duration::days(14),
+            matrix_introspection_clients: Arc::new([]),
+            embedding_origins: Arc::new([]),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_url_allowed() {
+        let site_config = SiteConfig {
+            allowed_next_urls: vec![
+                Url::parse("https://app.example.com/").unwrap(),
+                Url::parse("https://example.com/app").unwrap(),
+            ]
+            .into(),
+            ..SiteConfig::default()
+        };
+
+        let allowed = |url: &str| site_config.is_next_url_allowed(&Url::parse(url).unwrap());
+
+        assert!(allowed("https://app.example.com/"));
+        assert!(allowed("https://app.example.com/#/room/!abc:example.com"));
+        assert!(allowed("https://app.example.com:443/foo?bar=baz"));
+        assert!(allowed("https://example.com/app"));
+        assert!(allowed("https://example.com/app/settings"));
+
+        assert!(!allowed("http://app.example.com/"));
+        assert!(!allowed("https://app.example.com:8443/"));
+        assert!(!allowed("https://app.example.com.evil.com/"));
+        assert!(!allowed("https://user@app.example.com/"));
+        assert!(!allowed("https://example.com/application"));
+        assert!(!allowed("https://example.com/"));
+    }
+}
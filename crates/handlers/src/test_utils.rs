@@ -12,6 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Helpers to exercise the full HTTP router against a real database.
+//!
+//! This module is always available to the crate's own tests, and is exported
+//! behind the `test-utils` feature so that downstream integrators can write
+//! integration tests against the service's behaviour without a live
+//! deployment. The [`TestState`] holds everything the router needs in memory,
+//! except for the database, which is typically provided by the
+//! `#[sqlx::test]` macro. It loads the templates, translations, frontend
+//! manifest and policy from the workspace, so those need to be built first.
+//!
+//! Outside of this repository, the crate can be used as a git dependency with
+//! the `test-utils` feature. The assets are then loaded from a directory laid
+//! out like the workspace, passed to [`TestState::from_pool_with_assets`] and
+//! [`policy_factory_with_assets`].
+
 use std::{
     convert::Infallible,
     sync::{Arc, Mutex, RwLock},
@@ -24,11 +39,12 @@ use axum::{
     extract::{FromRef, FromRequestParts},
     response::{IntoResponse, IntoResponseParts},
 };
+use camino::{Utf8Path, Utf8PathBuf};
 use cookie_store::{CookieStore, RawCookie};
 use futures_util::future::BoxFuture;
 use headers::{Authorization, ContentType, HeaderMapExt, HeaderName, HeaderValue};
 use hyper::{
-    header::{CONTENT_TYPE, COOKIE, SET_COOKIE},
+    header::{CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE},
     Request, Response, StatusCode,
 };
 use mas_axum_utils::{
@@ -36,52 +52,131 @@ use mas_axum_utils::{
     http_client_factory::HttpClientFactory,
     ErrorWrapper,
 };
+use mas_data_model::{
+    AccessToken, Client, EmailNormalization, RefreshTokenPolicies, SessionEvent, TermsOfService,
+    TokenType, User,
+};
 use mas_i18n::Translator;
-use mas_keystore::{Encrypter, JsonWebKey, JsonWebKeySet, Keystore, PrivateKey};
-use mas_matrix::{HomeserverConnection, MockHomeserverConnection};
+use mas_keystore::{Encrypter, JsonWebKey, JsonWebKeySet, Keystore, PrivateKey, RequestSigner};
+use mas_matrix::{CircuitBreaker, HomeserverConnection, MockHomeserverConnection};
 use mas_policy::{InstantiateError, Policy, PolicyFactory};
-use mas_router::{SimpleRoute, UrlBuilder};
-use mas_storage::{clock::MockClock, BoxClock, BoxRepository, BoxRng, Repository};
+use mas_router::{Route, SimpleRoute, UrlBuilder};
+use mas_storage::{
+    clock::MockClock, BoxClock, BoxRepository, BoxRng, Repository, RepositoryAccess,
+};
 use mas_storage_pg::{DatabaseError, PgRepository};
 use mas_templates::{SiteBranding, Templates};
+use oauth2_types::{
+    registration::ClientRegistrationResponse, requests::AccessTokenResponse, scope::Scope,
+};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
 use serde::{de::DeserializeOwned, Serialize};
 use sqlx::PgPool;
+use tokio::sync::broadcast;
 use tower::{Layer, Service, ServiceExt};
 use url::Url;
+use zeroize::Zeroizing;
 
 use crate::{
+    oauth2::authorization::request_object::RequestUriCache,
     passwords::{Hasher, PasswordManager},
+    rate_limit::EmailThrottle,
     site_config::SiteConfig,
     upstream_oauth2::cache::MetadataCache,
-    ActivityTracker, BoundActivityTracker, MatrixHomeserver,
+    ActivityTracker, AvatarStore, BoundActivityTracker, BoxHomeserverConnection, GraphQLLimits,
+    MatrixHomeserver, SessionEvents,
 };
 
+/// Install a tracing subscriber which writes to the test output.
 // This might fail if it's not the first time it's being called, which is fine,
 // so we ignore the result
 #[allow(unused_must_use)]
-pub(crate) fn init_tracing() {
+pub fn init_tracing() {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
         .with_test_writer()
         .try_init();
 }
 
-pub(crate) async fn policy_factory(
-    data: serde_json::Value,
-) -> Result<Arc<PolicyFactory>, anyhow::Error> {
-    let workspace_root = camino::Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+/// Logs captured by [`capture_logs`]
+#[derive(Clone, Default)]
+pub struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    /// Everything logged so far
+    #[must_use]
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Capture everything logged on the current thread, down to the `TRACE`
+/// level, until the returned guard is dropped.
+///
+/// This is used to check that no credentials end up in the logs. Tests run on
+/// a single-threaded runtime, so this captures what the handlers log, but not
+/// what runs on the blocking thread pool, like password hashing.
+#[must_use]
+pub fn capture_logs() -> (tracing::subscriber::DefaultGuard, CapturedLogs) {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_ansi(false)
+        // Log the spans as well, as they can record credentials in their fields
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .with_writer(move || writer.clone())
+        .finish();
+    let guard = tracing::subscriber::set_default(subscriber);
+    (guard, logs)
+}
+
+/// The root of the workspace, from which the assets are loaded by default
+fn workspace_root() -> Utf8PathBuf {
+    Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
         .join("..")
-        .join("..");
+}
 
-    let file = tokio::fs::File::open(workspace_root.join("policies").join("policy.wasm")).await?;
+/// Load the policy from the workspace, with the given data
+///
+/// # Errors
+///
+/// Returns an error if the policy could not be loaded
+pub async fn policy_factory(data: serde_json::Value) -> Result<Arc<PolicyFactory>, anyhow::Error> {
+    policy_factory_with_assets(&workspace_root(), data).await
+}
+
+/// Load the policy from `policies/policy.wasm` under the given assets
+/// directory, with the given data
+///
+/// # Errors
+///
+/// Returns an error if the policy could not be loaded
+pub async fn policy_factory_with_assets(
+    assets: &Utf8Path,
+    data: serde_json::Value,
+) -> Result<Arc<PolicyFactory>, anyhow::Error> {
+    let file = tokio::fs::File::open(assets.join("policies").join("policy.wasm")).await?;
 
     let entrypoints = mas_policy::Entrypoints {
         register: "register/violation".to_owned(),
         client_registration: "client_registration/violation".to_owned(),
         authorization_grant: "authorization_grant/violation".to_owned(),
         email: "email/violation".to_owned(),
+        compat_login: "compat_login/violation".to_owned(),
         password: "password/violation".to_owned(),
     };
 
@@ -90,13 +185,17 @@ pub(crate) async fn policy_factory(
     Ok(policy_factory)
 }
 
+/// The state of the application, with a mock clock, a seeded RNG and a mock
+/// homeserver connection
 #[derive(Clone)]
-pub(crate) struct TestState {
+pub struct TestState {
     pub pool: PgPool,
     pub templates: Templates,
     pub key_store: Keystore,
+    pub request_signer: RequestSigner,
     pub cookie_manager: CookieManager,
     pub metadata_cache: MetadataCache,
+    pub request_uri_cache: RequestUriCache,
     pub encrypter: Encrypter,
     pub url_builder: UrlBuilder,
     pub homeserver: MatrixHomeserver,
@@ -106,26 +205,50 @@ pub(crate) struct TestState {
     pub password_manager: PasswordManager,
     pub site_config: SiteConfig,
     pub activity_tracker: ActivityTracker,
+    pub homeserver_circuit_breaker: Option<CircuitBreaker>,
+    /// Shares its state with the connection used by the GraphQL API, to
+    /// inspect the calls made to the homeserver
+    pub homeserver_connection: MockHomeserverConnection,
     pub clock: Arc<MockClock>,
     pub rng: Arc<Mutex<ChaChaRng>>,
 }
 
 impl TestState {
-    /// Create a new test state from the given database pool
+    /// Create a new test state from the given database pool, loading the
+    /// assets from the workspace
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the templates or the policy could not be loaded
     pub async fn from_pool(pool: PgPool) -> Result<Self, anyhow::Error> {
-        let workspace_root = camino::Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
-            .join("..")
-            .join("..");
+        Self::from_pool_with_assets(pool, &workspace_root()).await
+    }
 
+    /// Create a new test state from the given database pool, loading the
+    /// assets from a directory laid out like the workspace: the `templates`
+    /// and `translations` directories, the frontend manifest at
+    /// `frontend/dist/manifest.json` and the policy at `policies/policy.wasm`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the templates or the policy could not be loaded
+    ///
+    /// # Panics
+    ///
+    /// Panics if the test keys could not be loaded
+    pub async fn from_pool_with_assets(
+        pool: PgPool,
+        assets: &Utf8Path,
+    ) -> Result<Self, anyhow::Error> {
         let url_builder = UrlBuilder::new("https://example.com/".parse()?, None, None);
 
         let site_branding = SiteBranding::new("example.com").with_service_name("Example");
 
         let templates = Templates::load(
-            workspace_root.join("templates"),
+            assets.join("templates"),
             url_builder.clone(),
-            workspace_root.join("frontend/dist/manifest.json"),
-            workspace_root.join("translations"),
+            assets.join("frontend/dist/manifest.json"),
+            assets.join("translations"),
             site_branding,
         )
         .await?;
@@ -135,7 +258,12 @@ impl TestState {
             PrivateKey::load_pem(include_str!("../../keystore/tests/keys/rsa.pkcs1.pem")).unwrap();
         let rsa = JsonWebKey::new(rsa).with_kid("test-rsa");
 
-        let jwks = JsonWebKeySet::new(vec![rsa]);
+        let ec_p256 =
+            PrivateKey::load_pem(include_str!("../../keystore/tests/keys/ec-p256.sec1.pem"))
+                .unwrap();
+        let ec_p256 = JsonWebKey::new(ec_p256).with_kid("test-ec-p256");
+
+        let jwks = JsonWebKeySet::new(vec![rsa, ec_p256]);
         let key_store = Keystore::new(jwks);
 
         let encrypter = Encrypter::new(&[0x42; 32]);
@@ -143,12 +271,13 @@ impl TestState {
             CookieManager::derive_from("https://example.com".parse()?, &[0x42; 32]);
 
         let metadata_cache = MetadataCache::new();
+        let request_uri_cache = RequestUriCache::new();
 
         let password_manager = PasswordManager::new([(1, Hasher::argon2id(None))])?;
 
         let homeserver = MatrixHomeserver::new("example.com".to_owned());
 
-        let policy_factory = policy_factory(serde_json::json!({})).await?;
+        let policy_factory = policy_factory_with_assets(assets, serde_json::json!({})).await?;
 
         let homeserver_connection = MockHomeserverConnection::new("example.com");
 
@@ -162,13 +291,22 @@ impl TestState {
         let graphql_state = TestGraphQLState {
             pool: pool.clone(),
             policy_factory: Arc::clone(&policy_factory),
-            homeserver_connection,
+            homeserver_connection: homeserver_connection.clone(),
             rng: Arc::clone(&rng),
             clock: Arc::clone(&clock),
+            refresh_token_policies: site_config.refresh_token_policies.clone(),
+            avatar_store: site_config.avatar_store.clone(),
+            email_normalization: site_config.email_normalization,
+            email_throttle: site_config.email_throttle.clone(),
+            terms: site_config.terms.clone(),
+            session_events: SessionEvents::listen(&pool).await?,
         };
         let state: mas_graphql::BoxState = Box::new(graphql_state);
 
-        let graphql_schema = mas_graphql::schema_builder().data(state).finish();
+        let graphql_schema = GraphQLLimits::default()
+            .apply(mas_graphql::schema_builder())
+            .data(state)
+            .finish();
 
         let activity_tracker =
             ActivityTracker::new(pool.clone(), std::time::Duration::from_secs(1));
@@ -177,8 +315,10 @@ impl TestState {
             pool,
             templates,
             key_store,
+            request_signer: RequestSigner::default(),
             cookie_manager,
             metadata_cache,
+            request_uri_cache,
             encrypter,
             url_builder,
             homeserver,
@@ -188,11 +328,18 @@ impl TestState {
             password_manager,
             site_config,
             activity_tracker,
+            homeserver_circuit_breaker: None,
+            homeserver_connection,
             clock,
             rng,
         })
     }
 
+    /// Run a request through the full router
+    ///
+    /// # Panics
+    ///
+    /// Panics if the response body is not valid UTF-8
     pub async fn request<B>(&self, request: Request<B>) -> Response<String>
     where
         B: HttpBody + Send + 'static,
@@ -201,11 +348,22 @@ impl TestState {
         B::Error: std::error::Error + Send + Sync,
         B::Data: Send,
     {
+        let maintenance = self.site_config.maintenance.clone();
         let app = crate::healthcheck_router()
             .merge(crate::discovery_router())
-            .merge(crate::api_router())
-            .merge(crate::compat_router())
-            .merge(crate::human_router(self.templates.clone()))
+            .merge(crate::api_router(maintenance.clone()))
+            .merge(crate::admin_api_router(maintenance.clone()))
+            .merge(crate::compat_router(maintenance.clone()))
+            .merge(crate::human_router(
+                self.templates.clone(),
+                self.cookie_manager.clone(),
+                maintenance.clone(),
+            ))
+            .merge(crate::upstream_oauth2_router(
+                self.templates.clone(),
+                self.cookie_manager.clone(),
+                maintenance,
+            ))
             .merge(crate::graphql_router(false))
             .with_state(self.clone());
 
@@ -231,6 +389,11 @@ impl TestState {
         Response::from_parts(parts, body)
     }
 
+    /// Get a repository backed by the test database
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a connection could not be acquired
     pub async fn repository(&self) -> Result<BoxRepository, DatabaseError> {
         let repo = PgRepository::from_pool(&self.pool).await?;
         Ok(repo
@@ -243,6 +406,7 @@ impl TestState {
     /// # Panics
     ///
     /// Panics if the RNG is already locked.
+    #[must_use]
     pub fn rng(&self) -> ChaChaRng {
         let mut parent_rng = self.rng.try_lock().expect("Failed to lock RNG");
         ChaChaRng::from_rng(&mut *parent_rng).unwrap()
@@ -269,9 +433,283 @@ impl TestState {
     }
 
     /// Get an empty cookie jar
+    #[must_use]
     pub fn cookie_jar(&self) -> CookieJar {
         self.cookie_manager.cookie_jar()
     }
+
+    /// Provision a user with the given username and password
+    ///
+    /// # Panics
+    ///
+    /// Panics if the user could not be created
+    pub async fn create_user(&self, username: &str, password: &str) -> User {
+        let mut rng = self.rng();
+        let mut repo = self.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut rng, &self.clock, username.to_owned())
+            .await
+            .unwrap();
+        let (version, hash) = self
+            .password_manager
+            .hash(&mut rng, Zeroizing::new(password.as_bytes().to_vec()))
+            .await
+            .unwrap();
+        repo.user_password()
+            .add(&mut rng, &self.clock, &user, version, hash, None)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+        user
+    }
+
+    /// Log in with a password through the login form, saving the session
+    /// cookie in the given [`CookieHelper`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the login failed
+    pub async fn login(&self, cookies: &CookieHelper, username: &str, password: &str) {
+        let request = cookies.with_cookies(Request::get(mas_router::Login::route()).empty());
+        let response = self.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token();
+
+        let request = Request::post(mas_router::Login::route()).form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": username,
+            "password": password,
+        }));
+        let response = self.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+    }
+
+    /// Register a public client through the dynamic client registration
+    /// endpoint, which can use the authorization code grant with the given
+    /// redirect URI. Returns the client ID.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the registration failed
+    pub async fn register_client(&self, redirect_uri: &str) -> String {
+        let request =
+            Request::post(mas_router::OAuth2RegistrationEndpoint::PATH).json(serde_json::json!({
+                "contacts": ["hello@example.com"],
+                "client_uri": "https://example.com/",
+                "redirect_uris": [redirect_uri],
+                "response_types": ["code"],
+                "grant_types": ["authorization_code", "refresh_token"],
+                "token_endpoint_auth_method": "none",
+            }));
+
+        let response = self.request(request).await;
+        response.assert_status(StatusCode::CREATED);
+        let response: ClientRegistrationResponse = response.json();
+        response.client_id
+    }
+
+    /// Run a full authorization code flow for the user logged in the given
+    /// [`CookieHelper`], giving consent if asked to, and exchange the code for
+    /// tokens
+    ///
+    /// # Panics
+    ///
+    /// Panics if any step of the flow failed
+    pub async fn run_authorization_code_flow(
+        &self,
+        cookies: &CookieHelper,
+        client_id: &str,
+        redirect_uri: &str,
+        scope: &str,
+    ) -> AccessTokenResponse {
+        self.run_authorization_code_flow_with_params(cookies, client_id, redirect_uri, scope, &[])
+            .await
+    }
+
+    /// Same as [`Self::run_authorization_code_flow`], with additional
+    /// parameters in the authorization request
+    ///
+    /// # Panics
+    ///
+    /// Panics if any step of the flow failed
+    pub async fn run_authorization_code_flow_with_params(
+        &self,
+        cookies: &CookieHelper,
+        client_id: &str,
+        redirect_uri: &str,
+        scope: &str,
+        params: &[(&str, &str)],
+    ) -> AccessTokenResponse {
+        let mut query = vec![
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("scope", scope),
+            ("state", "state"),
+        ];
+        query.extend_from_slice(params);
+        let query = serde_urlencoded::to_string(query).unwrap();
+        let mut location = format!("{}?{query}", mas_router::OAuth2AuthorizationEndpoint::PATH);
+
+        // Follow the redirects until we get back to the client, giving consent on the
+        // way if needed
+        let callback = loop {
+            if location.starts_with(redirect_uri) {
+                break Url::parse(&location).unwrap();
+            }
+
+            let request = cookies.with_cookies(Request::get(&location).empty());
+            let response = self.request(request).await;
+            cookies.save_cookies(&response);
+
+            let response =
+                if response.status() == StatusCode::OK && location.starts_with("/consent/") {
+                    let csrf_token = response.csrf_token();
+                    let request = Request::post(&location).form(serde_json::json!({
+                        "csrf": csrf_token,
+                    }));
+                    let response = self.request(cookies.with_cookies(request)).await;
+                    cookies.save_cookies(&response);
+                    response
+                } else {
+                    response
+                };
+
+            response.assert_status(StatusCode::SEE_OTHER);
+            location = response.location().to_owned();
+        };
+
+        let code = callback
+            .query_pairs()
+            .find(|(key, _)| key == "code")
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_else(|| panic!("No code in the callback URL: {callback}"));
+
+        let request =
+            Request::post(mas_router::OAuth2TokenEndpoint::PATH).form(serde_json::json!({
+                "grant_type": "authorization_code",
+                "code": code,
+                "redirect_uri": redirect_uri,
+                "client_id": client_id,
+            }));
+        let response = self.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.json()
+    }
+}
+
+/// Create an OAuth 2.0 client with no redirect URI nor grant type, to attach
+/// sessions to
+///
+/// # Panics
+///
+/// Panics if the client could not be created
+pub async fn create_test_client(state: &TestState) -> Client {
+    let mut repo = state.repository().await.unwrap();
+    let mut rng = state.rng();
+
+    let client = repo
+        .oauth2_client()
+        .add(
+            &mut rng,
+            &state.clock,
+            vec![],
+            None,
+            None,
+            vec![],
+            vec![],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Vec::new(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Vec::new(),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+
+    repo.save().await.unwrap();
+
+    client
+}
+
+/// Create a user without any password nor email address
+///
+/// # Panics
+///
+/// Panics if the user could not be created
+pub async fn create_test_user<U: Into<String> + Send>(state: &TestState, username: U) -> User {
+    let username = username.into();
+    let mut repo = state.repository().await.unwrap();
+    let mut rng = state.rng();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &state.clock, username)
+        .await
+        .unwrap();
+
+    repo.save().await.unwrap();
+
+    user
+}
+
+/// Start an OAuth 2.0 session for the user with the given scope, and get an
+/// access token for it
+///
+/// # Panics
+///
+/// Panics if the session or the token could not be created
+pub async fn start_oauth_session(
+    state: &TestState,
+    client: &Client,
+    user: &User,
+    scope: Scope,
+) -> AccessToken {
+    let mut repo = state.repository().await.unwrap();
+    let mut rng = state.rng();
+
+    let browser_session = repo
+        .browser_session()
+        .add(&mut rng, &state.clock, user, None)
+        .await
+        .unwrap();
+
+    let session = repo
+        .oauth2_session()
+        .add_from_browser_session(&mut rng, &state.clock, client, &browser_session, scope)
+        .await
+        .unwrap();
+
+    let access_token_str = TokenType::AccessToken.generate(&mut rng);
+
+    let access_token = repo
+        .oauth2_access_token()
+        .add(&mut rng, &state.clock, &session, access_token_str, None)
+        .await
+        .unwrap();
+
+    repo.save().await.unwrap();
+
+    access_token
 }
 
 struct TestGraphQLState {
@@ -280,6 +718,12 @@ struct TestGraphQLState {
     policy_factory: Arc<PolicyFactory>,
     clock: Arc<MockClock>,
     rng: Arc<Mutex<ChaChaRng>>,
+    refresh_token_policies: RefreshTokenPolicies,
+    avatar_store: Option<AvatarStore>,
+    email_normalization: EmailNormalization,
+    email_throttle: EmailThrottle,
+    terms: Option<TermsOfService>,
+    session_events: SessionEvents,
 }
 
 #[async_trait]
@@ -311,6 +755,32 @@ impl mas_graphql::State for TestGraphQLState {
         let rng = ChaChaRng::from_rng(&mut *parent_rng).expect("Failed to seed RNG");
         Box::new(rng)
     }
+
+    fn refresh_token_policies(&self) -> &RefreshTokenPolicies {
+        &self.refresh_token_policies
+    }
+
+    fn avatar_store(&self) -> Option<&dyn mas_graphql::AvatarStore> {
+        self.avatar_store
+            .as_ref()
+            .map(|store| store as &dyn mas_graphql::AvatarStore)
+    }
+
+    fn email_normalization(&self) -> EmailNormalization {
+        self.email_normalization
+    }
+
+    fn email_throttle(&self) -> &dyn mas_graphql::EmailThrottle {
+        &self.email_throttle
+    }
+
+    fn terms(&self) -> Option<&TermsOfService> {
+        self.terms.as_ref()
+    }
+
+    fn session_events(&self) -> broadcast::Receiver<SessionEvent> {
+        self.session_events.subscribe()
+    }
 }
 
 impl FromRef<TestState> for PgPool {
@@ -343,6 +813,12 @@ impl FromRef<TestState> for Keystore {
     }
 }
 
+impl FromRef<TestState> for RequestSigner {
+    fn from_ref(input: &TestState) -> Self {
+        input.request_signer.clone()
+    }
+}
+
 impl FromRef<TestState> for Encrypter {
     fn from_ref(input: &TestState) -> Self {
         input.encrypter.clone()
@@ -361,6 +837,12 @@ impl FromRef<TestState> for MatrixHomeserver {
     }
 }
 
+impl FromRef<TestState> for BoxHomeserverConnection {
+    fn from_ref(input: &TestState) -> Self {
+        Arc::new(input.homeserver_connection.clone())
+    }
+}
+
 impl FromRef<TestState> for HttpClientFactory {
     fn from_ref(input: &TestState) -> Self {
         input.http_client_factory.clone()
@@ -385,12 +867,24 @@ impl FromRef<TestState> for MetadataCache {
     }
 }
 
+impl FromRef<TestState> for RequestUriCache {
+    fn from_ref(input: &TestState) -> Self {
+        input.request_uri_cache.clone()
+    }
+}
+
 impl FromRef<TestState> for SiteConfig {
     fn from_ref(input: &TestState) -> Self {
         input.site_config.clone()
     }
 }
 
+impl FromRef<TestState> for Option<CircuitBreaker> {
+    fn from_ref(input: &TestState) -> Self {
+        input.homeserver_circuit_breaker.clone()
+    }
+}
+
 #[async_trait]
 impl FromRequestParts<TestState> for ActivityTracker {
     type Rejection = Infallible;
@@ -470,7 +964,8 @@ impl FromRequestParts<TestState> for Policy {
     }
 }
 
-pub(crate) trait RequestBuilderExt {
+/// Extension trait to build requests in tests
+pub trait RequestBuilderExt {
     /// Builds the request with the given JSON value as body.
     fn json<T: Serialize>(self, body: T) -> hyper::Request<String>;
 
@@ -525,7 +1020,8 @@ impl RequestBuilderExt for hyper::http::request::Builder {
     }
 }
 
-pub(crate) trait ResponseExt {
+/// Extension trait to make assertions on responses in tests
+pub trait ResponseExt {
     /// Asserts that the response has the given status code.
     ///
     /// # Panics
@@ -548,6 +1044,20 @@ pub(crate) trait ResponseExt {
     /// Panics if the response is missing the `Content-Type: application/json`,
     /// or if the body is not valid JSON.
     fn json<T: DeserializeOwned>(&self) -> T;
+
+    /// Get the target of a redirection response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the response has no valid `Location` header.
+    fn location(&self) -> &str;
+
+    /// Get the CSRF token from a rendered HTML form.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the body has no CSRF token.
+    fn csrf_token(&self) -> &str;
 }
 
 impl ResponseExt for Response<String> {
@@ -584,6 +1094,320 @@ impl ResponseExt for Response<String> {
         self.assert_header_value(CONTENT_TYPE, "application/json");
         serde_json::from_str(self.body()).expect("JSON deserialization failed")
     }
+
+    #[track_caller]
+    fn location(&self) -> &str {
+        self.headers()
+            .get(LOCATION)
+            .expect("Missing location header")
+            .to_str()
+            .expect("Invalid location header")
+    }
+
+    #[track_caller]
+    fn csrf_token(&self) -> &str {
+        self.body()
+            .split("name=\"csrf\" value=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .expect("No CSRF token in the response body")
+    }
+}
+
+/// A browser with JavaScript disabled, which can only follow redirects and
+/// fill and submit the HTML forms of the pages it visits.
+///
+/// It is used to check that the interactive flows can be completed without
+/// JavaScript, like they would be with some accessibility tools or with the
+/// Tor Browser in its safest mode.
+pub struct NoJsBrowser<'a> {
+    state: &'a TestState,
+    cookies: CookieHelper,
+    location: String,
+    page: Response<String>,
+    posted: Vec<(String, String)>,
+}
+
+impl<'a> NoJsBrowser<'a> {
+    /// Create a browser sharing the cookies of the given [`CookieHelper`]
+    #[must_use]
+    pub fn new(state: &'a TestState, cookies: &CookieHelper) -> Self {
+        Self {
+            state,
+            cookies: cookies.clone(),
+            location: String::new(),
+            page: Response::new(String::new()),
+            posted: Vec::new(),
+        }
+    }
+
+    /// The current location, which is an absolute URL if the browser left the
+    /// service
+    #[must_use]
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// The response of the current page
+    #[must_use]
+    pub fn page(&self) -> &Response<String> {
+        &self.page
+    }
+
+    /// Get a parameter the browser left the service with, either from the
+    /// query of the current location, or from the form it posted there
+    #[must_use]
+    pub fn param(&self, name: &str) -> Option<String> {
+        if let Some((_, value)) = self.posted.iter().find(|(key, _)| key == name) {
+            return Some(value.clone());
+        }
+
+        let url = Url::parse("https://example.com/")
+            .ok()?
+            .join(&self.location)
+            .ok()?;
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Visit the given URI, following the redirects within the service
+    pub async fn visit(&mut self, uri: &str) {
+        self.navigate(Request::get(uri).empty()).await;
+    }
+
+    /// Fill and submit a form of the current page, then follow the redirects
+    /// within the service.
+    ///
+    /// The form is the first visible one which has all the given fields.
+    /// Like in a real browser, the other fields keep the value they were
+    /// rendered with, and hidden fields can't be changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no form of the page matches, or if it can't be submitted
+    /// without JavaScript.
+    pub async fn submit(&mut self, fields: &[(&str, &str)]) {
+        let form = HtmlForm::parse_all(self.page.body())
+            .into_iter()
+            .find(|form| form.accepts(fields))
+            .unwrap_or_else(|| {
+                panic!(
+                    "No form with the fields {fields:?} on {}. Body: {}",
+                    self.location,
+                    self.page.body()
+                )
+            });
+
+        assert!(
+            form.submittable,
+            "The form on {} has no submit button. Body: {}",
+            self.location,
+            self.page.body()
+        );
+
+        let mut values: Vec<(String, String)> = form
+            .inputs
+            .iter()
+            .filter(|input| fields.iter().all(|(name, _)| *name != input.name))
+            .filter_map(|input| Some((input.name.clone(), input.value.clone()?)))
+            .collect();
+        values.extend(
+            fields
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned())),
+        );
+
+        let action = form.action.unwrap_or_else(|| self.location.clone());
+
+        if !form.post {
+            let path = action.split_once('?').map_or(&*action, |(path, _)| path);
+            let query = serde_urlencoded::to_string(&values).unwrap();
+            let uri = format!("{path}?{query}");
+            if uri.starts_with('/') {
+                self.navigate(Request::get(uri).empty()).await;
+            } else {
+                self.leave(uri, Vec::new());
+            }
+        } else if action.starts_with('/') {
+            self.navigate(Request::post(action).form(values)).await;
+        } else {
+            self.leave(action, values);
+        }
+    }
+
+    fn leave(&mut self, location: String, posted: Vec<(String, String)>) {
+        self.location = location;
+        self.page = Response::new(String::new());
+        self.posted = posted;
+    }
+
+    async fn navigate(&mut self, mut request: Request<String>) {
+        self.posted.clear();
+
+        loop {
+            self.location = request.uri().to_string();
+            let response = self.state.request(self.cookies.with_cookies(request)).await;
+            self.cookies.save_cookies(&response);
+
+            if !matches!(response.status(), StatusCode::FOUND | StatusCode::SEE_OTHER) {
+                self.page = response;
+                return;
+            }
+
+            let location = response.location().to_owned();
+            if !location.starts_with('/') {
+                self.location = location;
+                self.page = response;
+                return;
+            }
+
+            request = Request::get(location).empty();
+        }
+    }
+}
+
+/// A form as rendered in an HTML page
+struct HtmlForm {
+    post: bool,
+    action: Option<String>,
+    inputs: Vec<HtmlInput>,
+    submittable: bool,
+}
+
+struct HtmlInput {
+    name: String,
+    /// The value submitted with the form, if any. Unchecked checkboxes don't
+    /// submit anything.
+    value: Option<String>,
+    hidden: bool,
+}
+
+impl HtmlForm {
+    /// Find the forms of a page which are visible to browsers without
+    /// JavaScript
+    fn parse_all(html: &str) -> Vec<Self> {
+        html.split("<form")
+            .skip(1)
+            .filter_map(|chunk| {
+                let (tag, rest) = chunk.split_once('>')?;
+                let body = rest.split("</form>").next().unwrap_or(rest);
+                let attributes = html_attributes(tag);
+                let attribute = |name: &str| {
+                    attributes
+                        .iter()
+                        .find(|(key, _)| *key == name)
+                        .map(|(_, value)| value.clone().unwrap_or_default())
+                };
+
+                // Forms enhanced with WebAuthn can only be used with JavaScript
+                if attribute("hidden").is_some() || attribute("data-webauthn").is_some() {
+                    return None;
+                }
+
+                let post =
+                    attribute("method").is_some_and(|method| method.eq_ignore_ascii_case("post"));
+                let action = attribute("action").filter(|action| !action.is_empty());
+
+                let inputs = body
+                    .split("<input")
+                    .skip(1)
+                    .filter_map(|chunk| HtmlInput::parse(chunk.split('>').next()?))
+                    .collect();
+
+                let submittable = body.contains("type=\"submit\"")
+                    || body.split("<button").skip(1).any(|chunk| {
+                        chunk.split('>').next().is_some_and(|tag| {
+                            !html_attributes(tag).iter().any(|(key, value)| {
+                                *key == "type" && value.as_deref() != Some("submit")
+                            })
+                        })
+                    });
+
+                Some(Self {
+                    post,
+                    action,
+                    inputs,
+                    submittable,
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the form can be filled with the given fields
+    fn accepts(&self, fields: &[(&str, &str)]) -> bool {
+        fields.iter().all(|(name, value)| {
+            self.inputs.iter().any(|input| {
+                input.name == *name && (!input.hidden || input.value.as_deref() == Some(*value))
+            })
+        })
+    }
+}
+
+impl HtmlInput {
+    fn parse(tag: &str) -> Option<Self> {
+        let attributes = html_attributes(tag);
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.clone().unwrap_or_default())
+        };
+
+        let name = attribute("name").filter(|name| !name.is_empty())?;
+        let kind = attribute("type").unwrap_or_default();
+        let value = match &*kind {
+            "submit" | "button" => return None,
+            "checkbox" | "radio" => {
+                attribute("checked").map(|_| attribute("value").unwrap_or_else(|| "on".to_owned()))
+            }
+            _ => Some(attribute("value").unwrap_or_default()),
+        };
+
+        Some(Self {
+            name,
+            value,
+            hidden: kind == "hidden",
+        })
+    }
+}
+
+/// Parse the attributes of an HTML tag, unescaping their values
+fn html_attributes(tag: &str) -> Vec<(&str, Option<String>)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    while let Some(start) = rest.find(|c: char| !c.is_whitespace() && c != '/') {
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(value) = rest.strip_prefix('=') {
+            let (value, remaining) = if let Some(value) = value.strip_prefix('"') {
+                value.split_once('"').unwrap_or((value, ""))
+            } else {
+                value.split_once(char::is_whitespace).unwrap_or((value, ""))
+            };
+            attributes.push((name, Some(html_unescape(value))));
+            rest = remaining;
+        } else {
+            attributes.push((name, None));
+        }
+    }
+    attributes
+}
+
+/// Undo the escaping done by the templates
+fn html_unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#x2f;", "/")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
 
 /// A helper for storing and retrieving cookies in tests.
@@ -593,11 +1417,17 @@ pub struct CookieHelper {
 }
 
 impl CookieHelper {
+    /// Create a new, empty cookie store
+    #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Inject the cookies from the store into the request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the request URI or the stored cookies are invalid.
     pub fn with_cookies<B>(&self, mut request: Request<B>) -> Request<B> {
         let url = Url::options()
             .base_url(Some(&"https://example.com/".parse().unwrap()))
@@ -619,6 +1449,10 @@ impl CookieHelper {
     }
 
     /// Save the cookies from the response into the store.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the response has an invalid `Set-Cookie` header.
     pub fn save_cookies<B>(&self, response: &Response<B>) {
         let url = "https://example.com/".parse().unwrap();
         let mut store = self.store.write().unwrap();
@@ -640,6 +1474,7 @@ impl CookieHelper {
         );
     }
 
+    /// Save the cookies set by the given response parts into the store.
     pub fn import(&self, res: impl IntoResponseParts) {
         let response = (res, "").into_response();
         self.save_cookies(&response);
@@ -21,7 +21,12 @@ use mas_http::HttpService;
 use mas_iana::oauth::PkceCodeChallengeMethod;
 use mas_oidc_client::error::DiscoveryError;
 use mas_storage::{upstream_oauth2::UpstreamOAuthProviderRepository, RepositoryAccess};
+use mas_storage_pg::{
+    cache_invalidation::{CacheInvalidation, PgCacheInvalidationListener},
+    DatabaseError,
+};
 use oauth2_types::oidc::VerifiedProviderMetadata;
+use sqlx::PgPool;
 use tokio::sync::RwLock;
 use url::Url;
 
@@ -249,6 +254,52 @@ impl MetadataCache {
         Ok(metadata)
     }
 
+    /// Drop the metadata cached for the given issuer, so that it gets fetched
+    /// again the next time it is needed
+    pub async fn invalidate(&self, issuer: &str) {
+        self.cache.write().await.remove(issuer);
+        self.insecure_cache.write().await.remove(issuer);
+    }
+
+    /// Drop the cached metadata of the upstream providers changed by any
+    /// instance, as they are notified by the database.
+    ///
+    /// This spawns a background task listening to the invalidations.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection to the database could not be
+    /// established
+    pub async fn listen_for_invalidations(
+        &self,
+        pool: &PgPool,
+    ) -> Result<tokio::task::JoinHandle<()>, DatabaseError> {
+        let mut listener = PgCacheInvalidationListener::connect(pool).await?;
+
+        let cache = self.clone();
+        Ok(tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(CacheInvalidation::UpstreamOAuthProvider { issuer }) => {
+                        tracing::info!(
+                            %issuer,
+                            "Dropping the cached metadata of a changed provider"
+                        );
+                        cache.invalidate(&issuer).await;
+                    }
+
+                    Err(e) => {
+                        tracing::error!(
+                            error = &e as &dyn std::error::Error,
+                            "Failed to receive cache invalidations, retrying in 5 seconds"
+                        );
+                        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        }))
+    }
+
     #[tracing::instrument(name = "metadata_cache.refresh_all", skip_all)]
     async fn refresh_all(&self, http_service: &HttpService) {
         // Grab all the keys first to avoid locking the cache for too long
@@ -414,6 +465,14 @@ mod tests {
         // Calling refresh should refresh all the known valid issuers
         cache.refresh_all(&service).await;
         assert_eq!(calls.load(Ordering::SeqCst), 6);
+
+        // Once invalidated, the issuer is fetched again
+        cache.invalidate("https://valid.example.com/").await;
+        cache
+            .get(&service, "https://valid.example.com/", true)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 7);
     }
 
     #[tokio::test]
@@ -493,6 +552,7 @@ mod tests {
             issuer: "https://valid.example.com/".to_owned(),
             human_name: Some("Example Ltd.".to_owned()),
             brand_name: None,
+            icon: None,
             discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
             pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
             jwks_uri_override: None,
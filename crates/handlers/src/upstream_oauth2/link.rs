@@ -24,15 +24,20 @@ use mas_axum_utils::{
     sentry::SentryEventID,
     FancyError, SessionInfoExt,
 };
-use mas_data_model::User;
+use mas_data_model::{
+    UpstreamOAuthAuthorizationSession, UpstreamOAuthProviderGroupsSyncPolicy, User,
+};
 use mas_jose::jwt::Jwt;
 use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
-    job::{JobRepositoryExt, ProvisionUserJob},
+    job::{JobRepositoryExt, NotifyNewSignInJob, ProvisionUserJob},
     upstream_oauth2::{UpstreamOAuthLinkRepository, UpstreamOAuthSessionRepository},
-    user::{BrowserSessionRepository, UserEmailRepository, UserRepository},
-    BoxClock, BoxRepository, BoxRng, RepositoryAccess,
+    user::{
+        BrowserSessionRepository, UserEmailFilter, UserEmailRepository, UserGroupRepository,
+        UserRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
 use mas_templates::{
     ErrorContext, FieldError, FormError, TemplateContext, Templates, ToFormState,
@@ -45,7 +50,10 @@ use tracing::warn;
 use ulid::Ulid;
 
 use super::{template::environment, UpstreamSessionsCookie};
-use crate::{impl_from_error_for_route, views::shared::OptionalPostAuthAction, PreferredLanguage};
+use crate::{
+    impl_from_error_for_route, preferred_language::remember_locale,
+    views::shared::OptionalPostAuthAction, PreferredLanguage, SiteConfig,
+};
 
 const DEFAULT_LOCALPART_TEMPLATE: &str = "{{ user.preferred_username }}";
 const DEFAULT_DISPLAYNAME_TEMPLATE: &str = "{{ user.name }}";
@@ -160,6 +168,74 @@ fn render_attribute_template(
     }
 }
 
+/// Synchronise the groups of the user with the ones the upstream provider
+/// claims in the `id_token`, according to the provider's sync policy.
+///
+/// With the authoritative policy, a missing claim removes all the groups of
+/// the user.
+async fn sync_groups(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    provider_id: Ulid,
+    user: &User,
+    upstream_session: &UpstreamOAuthAuthorizationSession,
+) -> Result<(), RouteError> {
+    let provider = repo
+        .upstream_oauth_provider()
+        .lookup(provider_id)
+        .await?
+        .ok_or(RouteError::ProviderNotFound)?;
+
+    let preference = &provider.claims_imports.groups;
+    if preference.sync == UpstreamOAuthProviderGroupsSyncPolicy::Ignore {
+        return Ok(());
+    }
+
+    let Some(id_token) = upstream_session.id_token() else {
+        return Ok(());
+    };
+
+    let (_header, payload) = Jwt::<'_, serde_json::Value>::try_from(id_token)?.into_parts();
+
+    // The claim can either be a list of groups or a single group
+    let claimed: Vec<&str> = match payload.get(preference.claim()) {
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .collect(),
+        Some(serde_json::Value::String(value)) => vec![value.as_str()],
+        _ => Vec::new(),
+    };
+
+    let mut groups: Vec<String> = claimed
+        .into_iter()
+        .filter_map(|group| preference.map(group))
+        .map(ToOwned::to_owned)
+        .collect();
+    groups.sort_unstable();
+    groups.dedup();
+
+    if !groups.is_empty() {
+        repo.user_group().add(clock, user, &groups).await?;
+    }
+
+    if preference.sync == UpstreamOAuthProviderGroupsSyncPolicy::Authoritative {
+        let stale: Vec<String> = repo
+            .user_group()
+            .list(user)
+            .await?
+            .into_iter()
+            .filter(|group| groups.binary_search(group).is_err())
+            .collect();
+
+        if !stale.is_empty() {
+            repo.user_group().remove(user, &stale).await?;
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "lowercase", tag = "action")]
 pub(crate) enum FormData {
@@ -245,6 +321,15 @@ pub(crate) async fn get(
                 .authenticate_with_upstream(&mut rng, &clock, &session, &upstream_session)
                 .await?;
 
+            sync_groups(
+                &mut repo,
+                &clock,
+                link.provider_id,
+                &session.user,
+                &upstream_session,
+            )
+            .await?;
+
             cookie_jar = cookie_jar.set_session(&session);
 
             repo.save().await?;
@@ -289,8 +374,14 @@ pub(crate) async fn get(
                 .lookup(user_id)
                 .await?
                 .filter(mas_data_model::User::is_valid)
+                .filter(mas_data_model::User::can_login_interactively)
                 .ok_or(RouteError::UserNotFound)?;
 
+            // Remember the language the user logged in with
+            let (user, new_cookie_jar) =
+                remember_locale(&mut repo, &locale, user, cookie_jar).await?;
+            cookie_jar = new_cookie_jar;
+
             let session = repo
                 .browser_session()
                 .add(&mut rng, &clock, &user, user_agent)
@@ -305,6 +396,20 @@ pub(crate) async fn get(
                 .authenticate_with_upstream(&mut rng, &clock, &session, &upstream_session)
                 .await?;
 
+            sync_groups(
+                &mut repo,
+                &clock,
+                link.provider_id,
+                &user,
+                &upstream_session,
+            )
+            .await?;
+
+            // Let the other sessions of the user know about this sign-in
+            repo.job()
+                .schedule_job(NotifyNewSignInJob::for_browser_session(&session))
+                .await?;
+
             cookie_jar = sessions_cookie
                 .consume_link(link_id)?
                 .save(cookie_jar, &clock);
@@ -473,6 +578,7 @@ pub(crate) async fn post(
     PreferredLanguage(locale): PreferredLanguage,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     Path(link_id): Path<Ulid>,
     Form(form): Form<ProtectedForm<FormData>>,
 ) -> Result<Response, RouteError> {
@@ -515,7 +621,7 @@ pub(crate) async fn post(
     let maybe_user_session = user_session_info.load_session(&mut repo).await?;
     let form_state = form.to_form_state();
 
-    let session = match (maybe_user_session, link.user_id, form) {
+    let mut session = match (maybe_user_session, link.user_id, form) {
         (Some(session), None, FormData::Link) => {
             // The user is already logged in, the link is not linked to any user, and the
             // user asked to link their account.
@@ -743,16 +849,26 @@ pub(crate) async fn post(
 
             // If we have an email, add it to the user
             if let Some(email) = email {
+                let email = site_config.email_normalization.normalize(&email);
+
+                // A verified email address can only belong to one user
+                let in_use = repo
+                    .user_email()
+                    .count(UserEmailFilter::new().for_email(&email).verified_only())
+                    .await?
+                    > 0;
+
                 let user_email = repo
                     .user_email()
                     .add(&mut rng, &clock, &user, email)
                     .await?;
                 // Mark the email as verified according to the policy and whether the provider
                 // claims it is, and make it the primary email.
-                if provider
-                    .claims_imports
-                    .verify_email
-                    .should_mark_as_verified(provider_email_verified)
+                if !in_use
+                    && provider
+                        .claims_imports
+                        .verify_email
+                        .should_mark_as_verified(provider_email_verified)
                 {
                     let user_email = repo
                         .user_email()
@@ -784,6 +900,20 @@ pub(crate) async fn post(
         .authenticate_with_upstream(&mut rng, &clock, &session, &upstream_session)
         .await?;
 
+    sync_groups(
+        &mut repo,
+        &clock,
+        link.provider_id,
+        &session.user,
+        &upstream_session,
+    )
+    .await?;
+
+    // Remember the language the user logged in with
+    let (user, cookie_jar) =
+        remember_locale(&mut repo, &locale, session.user.clone(), cookie_jar).await?;
+    session.user = user;
+
     let cookie_jar = sessions_cookie
         .consume_link(link_id)?
         .save(cookie_jar, &clock);
@@ -798,12 +928,13 @@ pub(crate) async fn post(
 mod tests {
     use hyper::{header::CONTENT_TYPE, Request, StatusCode};
     use mas_data_model::{
-        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderImportPreference,
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderGroupsImportPreference,
+        UpstreamOAuthProviderGroupsSyncPolicy, UpstreamOAuthProviderImportPreference,
     };
     use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
     use mas_jose::jwt::{JsonWebSignatureHeader, Jwt};
     use mas_router::Route;
-    use mas_storage::upstream_oauth2::UpstreamOAuthProviderParams;
+    use mas_storage::{upstream_oauth2::UpstreamOAuthProviderParams, user::UserGroupRepository};
     use oauth2_types::scope::{Scope, OPENID};
     use sqlx::PgPool;
 
@@ -828,6 +959,18 @@ mod tests {
                 action: mas_data_model::UpstreamOAuthProviderImportAction::Force,
                 template: None,
             },
+            groups: UpstreamOAuthProviderGroupsImportPreference {
+                sync: UpstreamOAuthProviderGroupsSyncPolicy::Authoritative,
+                claim: Some("roles".to_owned()),
+                mapping: [
+                    (
+                        "CN=Admins,DC=example,DC=com".to_owned(),
+                        "admins".to_owned(),
+                    ),
+                    ("CN=Staff,DC=example,DC=com".to_owned(), "staff".to_owned()),
+                ]
+                .into(),
+            },
             ..UpstreamOAuthProviderClaimsImports::default()
         };
 
@@ -835,6 +978,10 @@ mod tests {
             "preferred_username": "john",
             "email": "john@example.com",
             "email_verified": true,
+            "roles": [
+                "CN=Staff,DC=example,DC=com",
+                "CN=Unmapped,DC=example,DC=com",
+            ],
         });
 
         // Grab a key to sign the id_token
@@ -863,6 +1010,7 @@ mod tests {
                     issuer: "https://example.com/".to_owned(),
                     human_name: Some("Example Ltd.".to_owned()),
                     brand_name: None,
+                    icon: None,
                     scope: Scope::from_iter([OPENID]),
                     token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
                     token_endpoint_signing_alg: None,
@@ -970,5 +1118,9 @@ mod tests {
 
         assert_eq!(email.email, "john@example.com");
         assert!(email.confirmed_at.is_some());
+
+        // Only the mapped groups were imported
+        let groups = repo.user_group().list(&user).await.unwrap();
+        assert_eq!(groups, vec!["staff".to_owned()]);
     }
 }
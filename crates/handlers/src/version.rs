@@ -0,0 +1,134 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reports which build of the service is running, to help with bug reports and
+//! auditing deployments
+
+use axum::{extract::State, response::IntoResponse, Extension, Json};
+use mas_axum_utils::FancyError;
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Information about the running build of the service
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildInfo {
+    /// The version of the service
+    pub version: &'static str,
+
+    /// The git commit the service was built from, if known
+    pub git_sha: Option<&'static str>,
+
+    /// The cargo features the service was built with
+    pub features: Vec<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+struct Version {
+    version: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+struct SchemaVersion {
+    /// The latest migration applied to the database
+    applied: Option<i64>,
+
+    /// The latest migration embedded in this build
+    latest_known: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct DetailedVersion {
+    #[serde(flatten)]
+    build: BuildInfo,
+
+    schema_version: SchemaVersion,
+}
+
+#[tracing::instrument(name = "handlers.version.get", skip_all)]
+pub(crate) async fn get(Extension(build_info): Extension<BuildInfo>) -> impl IntoResponse {
+    Json(Version {
+        version: build_info.version,
+    })
+}
+
+#[tracing::instrument(name = "handlers.version.get_detailed", skip_all, err)]
+pub(crate) async fn get_detailed(
+    Extension(build_info): Extension<BuildInfo>,
+    State(pool): State<PgPool>,
+) -> Result<impl IntoResponse, FancyError> {
+    let mut conn = pool.acquire().await?;
+    let applied = mas_storage_pg::applied_schema_version(&mut conn).await?;
+
+    Ok(Json(DetailedVersion {
+        build: build_info,
+        schema_version: SchemaVersion {
+            applied,
+            latest_known: mas_storage_pg::latest_known_schema_version(),
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_router::SimpleRoute;
+    use sqlx::PgPool;
+    use tower::{Service, ServiceExt};
+
+    use super::*;
+    use crate::test_utils::{init_tracing, RequestBuilderExt, TestState};
+
+    fn build_info() -> BuildInfo {
+        BuildInfo {
+            version: "1.2.3",
+            git_sha: Some("0123456789abcdef"),
+            features: vec!["native-roots"],
+        }
+    }
+
+    async fn request(state: &TestState, detailed: bool) -> serde_json::Value {
+        let app = crate::version_router(build_info(), detailed).with_state(state.clone());
+        let request = Request::get(mas_router::Version::PATH).empty();
+        let response = app
+            .ready_oneshot()
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_get_version(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        // Only the version is shown by default
+        let version = request(&state, false).await;
+        assert_eq!(version, serde_json::json!({ "version": "1.2.3" }));
+
+        let version = request(&state, true).await;
+        assert_eq!(version["version"], "1.2.3");
+        assert_eq!(version["git_sha"], "0123456789abcdef");
+        assert_eq!(version["features"], serde_json::json!(["native-roots"]));
+        assert_eq!(
+            version["schema_version"]["applied"],
+            mas_storage_pg::latest_known_schema_version()
+        );
+    }
+}
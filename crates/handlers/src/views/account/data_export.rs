@@ -0,0 +1,172 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Let users download a copy of the data held about them
+
+use axum::{
+    extract::{Form, Path, State},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use hyper::{
+    header::{CACHE_CONTROL, CONTENT_DISPOSITION},
+    StatusCode,
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_router::{AccountDataExportDownload, UrlBuilder};
+use mas_storage::{
+    job::{ExportUserDataJob, JobRepositoryExt},
+    user::UserDataExportRepository,
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{AccountDataExportContext, TemplateContext, Templates};
+use rand::distributions::{Alphanumeric, DistString};
+use tracing::info;
+
+use crate::{BoundActivityTracker, PreferredLanguage};
+
+#[tracing::instrument(name = "handlers.views.account_data_export.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let export = repo.user_data_export().latest(&session.user).await?;
+    let download_link = export
+        .as_ref()
+        .filter(|export| export.is_available(clock.now()))
+        .map(|export| {
+            url_builder.absolute_url_for(&AccountDataExportDownload::new(export.token.clone()))
+        });
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let ctx = AccountDataExportContext::new(export, download_link)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_account_data_export(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+/// Ask for a new export of the data of the user.
+///
+/// The archive is gathered by a job, which emails the user a download link
+/// once it is ready.
+#[tracing::instrument(name = "handlers.views.account_data_export.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    // Don't start another export while one is being prepared
+    let latest = repo.user_data_export().latest(&session.user).await?;
+    if latest.is_some_and(|export| export.is_pending()) {
+        return Ok((
+            cookie_jar,
+            url_builder.redirect(&mas_router::AccountDataExport),
+        )
+            .into_response());
+    }
+
+    let token = Alphanumeric.sample_string(&mut rng, 32);
+    let export = repo
+        .user_data_export()
+        .add(&mut rng, &clock, &session.user, token)
+        .await?;
+
+    info!(user.id = %session.user.id, user_data_export.id = %export.id, "User asked for a data export");
+
+    repo.job()
+        .schedule_job(ExportUserDataJob::new(&export).with_language(locale.to_string()))
+        .await?;
+
+    repo.save().await?;
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::AccountDataExport),
+    )
+        .into_response())
+}
+
+/// Download a finished export, with the secret token sent to the user by
+/// email.
+#[tracing::instrument(name = "handlers.views.account_data_export.download", skip_all, err)]
+pub(crate) async fn download(
+    clock: BoxClock,
+    mut repo: BoxRepository,
+    Path(token): Path<String>,
+) -> Result<Response, FancyError> {
+    let Some(export) = repo.user_data_export().find_by_token(&token).await? else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    if !export.is_available(clock.now()) {
+        return Ok(StatusCode::GONE.into_response());
+    }
+
+    let Some(archive) = repo.user_data_export().archive(&export).await? else {
+        return Ok(StatusCode::GONE.into_response());
+    };
+
+    Ok((
+        [
+            (CACHE_CONTROL, "no-store"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"mas-export.json\"",
+            ),
+        ],
+        Json(archive),
+    )
+        .into_response())
+}
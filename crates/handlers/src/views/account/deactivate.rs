@@ -0,0 +1,209 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::BrowserSession;
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{DeactivateUserJob, JobRepositoryExt},
+    user::{BrowserSessionRepository, UserPasswordRepository, UserRepository},
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{
+    AccountDeactivateContext, AccountDeactivateFormField, FieldError, FormError, FormState,
+    TemplateContext, Templates,
+};
+use serde::Deserialize;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::{passwords::PasswordManager, BoundActivityTracker, PreferredLanguage};
+
+#[derive(Deserialize)]
+pub(crate) struct DeactivateForm {
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+}
+
+/// Whether the user has to enter their password to deactivate their account
+async fn requires_password(
+    password_manager: &PasswordManager,
+    repo: &mut BoxRepository,
+    session: &BrowserSession,
+) -> Result<bool, FancyError> {
+    if !password_manager.is_enabled() {
+        return Ok(false);
+    }
+
+    let user_password = repo.user_password().active(&session.user).await?;
+    Ok(user_password.is_some())
+}
+
+#[tracing::instrument(name = "handlers.views.account_deactivate.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(password_manager): State<PasswordManager>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let password = requires_password(&password_manager, &mut repo, &session).await?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let content = render(
+        locale,
+        &templates,
+        session,
+        AccountDeactivateContext::new(password),
+        csrf_token.form_value(),
+    )?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+fn render(
+    locale: DataLocale,
+    templates: &Templates,
+    session: BrowserSession,
+    ctx: AccountDeactivateContext,
+    csrf_token: String,
+) -> Result<String, FancyError> {
+    let ctx = ctx
+        .with_session(session)
+        .with_csrf(csrf_token)
+        .with_language(locale);
+
+    let content = templates.render_account_deactivate(&ctx)?;
+    Ok(content)
+}
+
+/// Deactivate the account of the user.
+///
+/// The user is soft-deleted right away, and a job ends all their sessions and
+/// deactivates them on the homeserver. Their personal data is erased once the
+/// deleted users grace period is over.
+#[tracing::instrument(name = "handlers.views.account_deactivate.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(password_manager): State<PasswordManager>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<DeactivateForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let password = requires_password(&password_manager, &mut repo, &session).await?;
+
+    let mut state = FormState::default();
+
+    if form.username.is_empty() {
+        state.add_error_on_field(AccountDeactivateFormField::Username, FieldError::Required);
+    } else if form.username != session.user.username {
+        state.add_error_on_field(AccountDeactivateFormField::Username, FieldError::Invalid);
+    }
+
+    if password {
+        if form.password.is_empty() {
+            state.add_error_on_field(AccountDeactivateFormField::Password, FieldError::Required);
+        } else {
+            let user_password = repo.user_password().active(&session.user).await?;
+            let verified = match user_password {
+                Some(user_password) => password_manager
+                    .verify(
+                        user_password.version,
+                        Zeroizing::new(form.password.into_bytes()),
+                        user_password.hashed_password,
+                    )
+                    .await
+                    .is_ok(),
+                None => false,
+            };
+
+            if !verified {
+                state.add_error_on_form(FormError::InvalidCredentials);
+            }
+        }
+    }
+
+    if !state.is_valid() {
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let content = render(
+            locale,
+            &templates,
+            session,
+            AccountDeactivateContext::new(password).with_form_state(state),
+            csrf_token.form_value(),
+        )?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    info!(user.id = %session.user.id, "User deactivated their account");
+    let user = repo
+        .user()
+        .soft_delete(&clock, session.user.clone())
+        .await?;
+    repo.browser_session().finish(&clock, session).await?;
+
+    // End the other sessions and deactivate the user on the homeserver. Its
+    // data there is only erased once the user gets purged
+    repo.job()
+        .schedule_job(DeactivateUserJob::new(&user, false))
+        .await?;
+
+    repo.save().await?;
+
+    let login = mas_router::Login::default();
+    Ok((cookie_jar, url_builder.redirect(&login)).into_response())
+}
@@ -16,6 +16,7 @@ use axum::{
     extract::{Form, Query, State},
     response::{Html, IntoResponse, Response},
 };
+use hyper::{header::RETRY_AFTER, StatusCode};
 use mas_axum_utils::{
     cookies::CookieJar,
     csrf::{CsrfExt, ProtectedForm},
@@ -25,13 +26,17 @@ use mas_policy::Policy;
 use mas_router::UrlBuilder;
 use mas_storage::{
     job::{JobRepositoryExt, VerifyEmailJob},
-    user::UserEmailRepository,
+    user::{UserEmailFilter, UserEmailRepository},
     BoxClock, BoxRepository, BoxRng,
 };
-use mas_templates::{EmailAddContext, ErrorContext, TemplateContext, Templates};
+use mas_templates::{
+    EmailAddContext, ErrorContext, FormError, FormState, TemplateContext, Templates,
+};
 use serde::Deserialize;
 
-use crate::{views::shared::OptionalPostAuthAction, BoundActivityTracker, PreferredLanguage};
+use crate::{
+    views::shared::OptionalPostAuthAction, BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
 
 #[derive(Deserialize, Debug)]
 pub struct EmailForm {
@@ -81,7 +86,9 @@ pub(crate) async fn post(
     PreferredLanguage(locale): PreferredLanguage,
     mut policy: Policy,
     cookie_jar: CookieJar,
+    State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     activity_tracker: BoundActivityTracker,
     Query(query): Query<OptionalPostAuthAction>,
     Form(form): Form<ProtectedForm<EmailForm>>,
@@ -98,34 +105,75 @@ pub(crate) async fn post(
 
     // XXX: we really should show human readable errors on the form here
 
+    let email = site_config.email_normalization.normalize(&form.email);
+
     // Validate the email address
-    if form.email.parse::<lettre::Address>().is_err() {
+    if email.parse::<lettre::Address>().is_err() {
         return Err(anyhow::anyhow!("Invalid email address").into());
     }
 
     // Run the email policy
-    let res = policy.evaluate_email(&form.email).await?;
+    let res = policy.evaluate_email(&email).await?;
     if !res.valid() {
         return Err(FancyError::new(
             ErrorContext::new()
-                .with_description(format!("Email address {:?} denied by policy", form.email))
+                .with_description(format!("Email address {email:?} denied by policy"))
                 .with_details(format!("{res}")),
         ));
     }
 
     // Find an existing email address
-    let existing_user_email = repo.user_email().find(&session.user, &form.email).await?;
+    let existing_user_email = repo.user_email().find(&session.user, &email).await?;
     let user_email = if let Some(user_email) = existing_user_email {
         user_email
     } else {
+        // A verified email address can only belong to one user
+        let in_use = repo
+            .user_email()
+            .count(UserEmailFilter::new().for_email(&email).verified_only())
+            .await?
+            > 0;
+        if in_use {
+            return Err(FancyError::new(ErrorContext::new().with_description(
+                format!("Email address {email:?} is already in use"),
+            )));
+        }
+
         repo.user_email()
-            .add(&mut rng, &clock, &session.user, form.email)
+            .add(&mut rng, &clock, &session.user, email)
             .await?
     };
 
     // If the email was not confirmed, send a confirmation email & redirect to the
     // verify page
     let next = if user_email.confirmed_at.is_none() {
+        // Don't let this form be used to flood a mailbox. Returning early rolls
+        // back the address which was just added.
+        if let Err(e) = site_config
+            .email_throttle
+            .check(&clock, Some(&session.user), &user_email.email)
+            .await
+        {
+            let retry_after = e.retry_after(clock.now());
+            let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+            let form_state =
+                FormState::default().with_error_on_form(FormError::EmailCooldown { retry_after });
+            let ctx = EmailAddContext::with_form_state(form_state)
+                .with_session(session)
+                .with_csrf(csrf_token.form_value())
+                .with_language(locale);
+
+            let content = templates.render_account_add_email(&ctx)?;
+
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.to_string())],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+
         repo.job()
             .schedule_job(VerifyEmailJob::new(&user_email).with_language(locale.to_string()))
             .await?;
@@ -0,0 +1,284 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context;
+use axum::{
+    extract::{Form, Path, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{JobRepositoryExt, ProvisionUserJob},
+    user::UserEmailRepository,
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{AccountEmailsContext, TemplateContext, Templates};
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{BoundActivityTracker, PreferredLanguage};
+
+#[tracing::instrument(name = "handlers.views.account_emails.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageEmails);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let emails = repo.user_email().all(&session.user).await?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let ctx = AccountEmailsContext::new(emails)
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_account_emails(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+/// Make one of the email addresses of the user the primary one.
+///
+/// Addresses which were not verified yet send the user to the verification
+/// page instead, so that the primary address is always a verified one.
+#[tracing::instrument(
+    name = "handlers.views.account_emails.set_primary",
+    fields(user_email.id = %id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn set_primary(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Path(id): Path<Ulid>,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageEmails);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let user_email = repo
+        .user_email()
+        .lookup(id)
+        .await?
+        .filter(|user_email| user_email.user_id == session.user.id)
+        .context("User email not found")?;
+
+    if !user_email.is_verified() {
+        let verify = mas_router::AccountVerifyEmail::new(user_email.id)
+            .and_then(mas_router::PostAuthAction::ManageEmails);
+        return Ok((cookie_jar, url_builder.redirect(&verify)).into_response());
+    }
+
+    if !session.user.is_primary_email(&user_email) {
+        info!(user.id = %session.user.id, "User changed their primary email address");
+        repo.user_email().set_as_primary(&user_email).await?;
+
+        // The homeserver gets the primary address first in the list of 3PIDs
+        repo.job()
+            .schedule_job(ProvisionUserJob::new(&session.user))
+            .await?;
+    }
+
+    repo.save().await?;
+
+    Ok((cookie_jar, url_builder.redirect(&mas_router::AccountEmails)).into_response())
+}
+
+/// Remove one of the email addresses of the user, other than the primary one.
+#[tracing::instrument(
+    name = "handlers.views.account_emails.remove",
+    fields(user_email.id = %id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn remove(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Path(id): Path<Ulid>,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageEmails);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let user_email = repo
+        .user_email()
+        .lookup(id)
+        .await?
+        .filter(|user_email| user_email.user_id == session.user.id)
+        .context("User email not found")?;
+
+    if session.user.is_primary_email(&user_email) {
+        return Err(anyhow::anyhow!("The primary email address can't be removed").into());
+    }
+
+    let verified = user_email.is_verified();
+    repo.user_email().remove(user_email).await?;
+
+    if verified {
+        repo.job()
+            .schedule_job(ProvisionUserJob::new(&session.user))
+            .await?;
+    }
+
+    repo.save().await?;
+
+    Ok((cookie_jar, url_builder.redirect(&mas_router::AccountEmails)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use mas_router::SimpleRoute;
+    use mas_storage::{
+        user::{UserEmailRepository, UserRepository},
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_change_primary_email(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        let user = state.create_user("john", "hunter2").await;
+        let mut repo = state.repository().await.unwrap();
+        let old_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "john@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        let old_email = repo
+            .user_email()
+            .mark_as_verified(&state.clock, old_email)
+            .await
+            .unwrap();
+        repo.user_email().set_as_primary(&old_email).await.unwrap();
+        let new_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "john@example.org".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        state.login(&cookies, "john", "hunter2").await;
+
+        let request = cookies.with_cookies(Request::get(mas_router::AccountEmails::PATH).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("john@example.com"));
+        assert!(response.body().contains("john@example.org"));
+        let csrf_token = response.csrf_token().to_owned();
+
+        // The new address has to be verified before it can become primary
+        let request = Request::post(format!("/account/emails/{}/primary", new_email.id))
+            .form(serde_json::json!({ "csrf": csrf_token }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert!(response
+            .location()
+            .starts_with(&format!("/verify-email/{}", new_email.id)));
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+        assert!(user.is_primary_email(&old_email));
+        let new_email = repo
+            .user_email()
+            .mark_as_verified(&state.clock, new_email)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::post(format!("/account/emails/{}/primary", new_email.id))
+            .form(serde_json::json!({ "csrf": csrf_token }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert_eq!(response.location(), mas_router::AccountEmails::PATH);
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+        assert!(user.is_primary_email(&new_email));
+        repo.save().await.unwrap();
+
+        // The primary address can't be removed, but the old one can
+        let request = Request::post(format!("/account/emails/{}/remove", new_email.id))
+            .form(serde_json::json!({ "csrf": csrf_token }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let request = Request::post(format!("/account/emails/{}/remove", old_email.id))
+            .form(serde_json::json!({ "csrf": csrf_token }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let mut repo = state.repository().await.unwrap();
+        let emails = repo.user_email().all(&user).await.unwrap();
+        assert_eq!(emails, vec![new_email]);
+    }
+}
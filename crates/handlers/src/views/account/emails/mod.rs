@@ -13,4 +13,5 @@
 // limitations under the License.
 
 pub mod add;
+pub mod index;
 pub mod verify;
@@ -25,7 +25,7 @@ use mas_axum_utils::{
 use mas_router::UrlBuilder;
 use mas_storage::{
     job::{JobRepositoryExt, ProvisionUserJob},
-    user::UserEmailRepository,
+    user::{UserEmailFilter, UserEmailInUseError, UserEmailRepository},
     BoxClock, BoxRepository, BoxRng, RepositoryAccess,
 };
 use mas_templates::{EmailVerificationPageContext, TemplateContext, Templates};
@@ -136,6 +136,21 @@ pub(crate) async fn post(
         .await?
         .context("Invalid code")?;
 
+    // Another user might have verified the same address in the meantime
+    if user_email.confirmed_at.is_none()
+        && repo
+            .user_email()
+            .count(
+                UserEmailFilter::new()
+                    .for_email(&user_email.email)
+                    .verified_only(),
+            )
+            .await?
+            > 0
+    {
+        return Err(anyhow::anyhow!("Email address is already in use").into());
+    }
+
     // TODO: display nice errors if the code was already consumed or expired
     repo.user_email()
         .consume_verification_code(&clock, verification)
@@ -145,9 +160,14 @@ pub(crate) async fn post(
         repo.user_email().set_as_primary(&user_email).await?;
     }
 
-    repo.user_email()
-        .mark_as_verified(&clock, user_email)
-        .await?;
+    match repo.user_email().mark_as_verified(&clock, user_email).await {
+        Ok(_) => {}
+        // Another user verified the same address concurrently
+        Err(e) if UserEmailInUseError::is_cause_of(&e) => {
+            return Err(anyhow::anyhow!("Email address is already in use").into());
+        }
+        Err(e) => return Err(e.into()),
+    }
 
     repo.job()
         .schedule_job(ProvisionUserJob::new(&session.user))
@@ -12,5 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod data_export;
+pub mod deactivate;
 pub mod emails;
 pub mod password;
+pub mod profile;
+pub mod recovery_codes;
+#[cfg(feature = "webauthn")]
+pub mod webauthn;
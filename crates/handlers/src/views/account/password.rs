@@ -36,7 +36,10 @@ use rand::Rng;
 use serde::Deserialize;
 use zeroize::Zeroizing;
 
-use crate::{passwords::PasswordManager, BoundActivityTracker, PreferredLanguage};
+use crate::{
+    passwords::PasswordManager, BoundActivityTracker, HttpClientFactory, PreferredLanguage,
+    SiteConfig,
+};
 
 #[derive(Deserialize)]
 pub struct ChangeForm {
@@ -109,6 +112,8 @@ pub(crate) async fn post(
     State(templates): State<Templates>,
     activity_tracker: BoundActivityTracker,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(http_client_factory): State<HttpClientFactory>,
     mut policy: Policy,
     mut repo: BoxRepository,
     cookie_jar: CookieJar,
@@ -143,6 +148,16 @@ pub(crate) async fn post(
         return Err(anyhow::anyhow!("Password policy violation: {res}").into());
     }
 
+    if let Some(check) = &site_config.breached_password_check {
+        // TODO: display nice form errors
+        if check
+            .should_reject(&http_client_factory, &form.new_password)
+            .await
+        {
+            return Err(anyhow::anyhow!("Password appeared in a data breach").into());
+        }
+    }
+
     let password = Zeroizing::new(form.current_password.into_bytes());
     let new_password = Zeroizing::new(form.new_password.into_bytes());
     let new_password_confirm = Zeroizing::new(form.new_password_confirm.into_bytes());
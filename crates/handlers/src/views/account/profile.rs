@@ -0,0 +1,426 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Let users set the display name and avatar of their Matrix profile
+
+use axum::{
+    extract::{Form, Multipart, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::BrowserSession;
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{BoxClock, BoxRepository, BoxRng};
+use mas_templates::{
+    AccountProfileContext, AccountProfileFormField, FieldError, FormState, TemplateContext,
+    Templates,
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{BoundActivityTracker, BoxHomeserverConnection, PreferredLanguage, SiteConfig};
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    SetDisplayName,
+    RemoveAvatar,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct ProfileForm {
+    action: Action,
+
+    /// The new display name. Leaving it empty removes the display name
+    #[serde(default)]
+    display_name: String,
+}
+
+#[tracing::instrument(name = "handlers.views.account_profile.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(homeserver): State<BoxHomeserverConnection>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let content = render(
+        locale,
+        &templates,
+        &site_config,
+        &homeserver,
+        session,
+        FormState::default(),
+        csrf_token.form_value(),
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    templates: &Templates,
+    site_config: &SiteConfig,
+    homeserver: &BoxHomeserverConnection,
+    session: BrowserSession,
+    form: FormState<AccountProfileFormField>,
+    csrf_token: String,
+) -> Result<String, FancyError> {
+    // Still let users set their profile if the homeserver can't tell us what
+    // it currently is
+    let mxid = homeserver.mxid(&session.user.username);
+    let (display_name, avatar_url) = match homeserver.query_user(&mxid).await {
+        Ok(user) => (user.displayname, user.avatar_url),
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to query the profile of the user");
+            (None, None)
+        }
+    };
+
+    let ctx =
+        AccountProfileContext::new(display_name, avatar_url, site_config.avatar_store.is_some())
+            .with_form_state(form)
+            .with_session(session)
+            .with_csrf(csrf_token)
+            .with_language(locale);
+
+    let content = templates.render_account_profile(&ctx)?;
+    Ok(content)
+}
+
+#[tracing::instrument(name = "handlers.views.account_profile.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(homeserver): State<BoxHomeserverConnection>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<ProfileForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let mxid = homeserver.mxid(&session.user.username);
+
+    match form.action {
+        Action::SetDisplayName => {
+            let display_name = form.display_name.trim();
+            if display_name.len() > 256 {
+                let state = FormState::from_form(&form)
+                    .with_error_on_field(AccountProfileFormField::DisplayName, FieldError::Invalid);
+                let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+                let content = render(
+                    locale,
+                    &templates,
+                    &site_config,
+                    &homeserver,
+                    session,
+                    state,
+                    csrf_token.form_value(),
+                )
+                .await?;
+
+                return Ok((cookie_jar, Html(content)).into_response());
+            }
+
+            if display_name.is_empty() {
+                homeserver.unset_displayname(&mxid).await?;
+            } else {
+                homeserver.set_displayname(&mxid, display_name).await?;
+            }
+        }
+
+        Action::RemoveAvatar => {
+            homeserver.unset_avatar_url(&mxid).await?;
+        }
+    }
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::AccountProfile),
+    )
+        .into_response())
+}
+
+/// Upload a new avatar, sent as a `multipart/form-data` form with the CSRF
+/// token in the `csrf` field and the image in the `avatar` field
+#[tracing::instrument(name = "handlers.views.account_profile.upload_avatar", skip_all, err)]
+pub(crate) async fn upload_avatar(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(homeserver): State<BoxHomeserverConnection>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    mut multipart: Multipart,
+) -> Result<Response, FancyError> {
+    let store = site_config
+        .avatar_store
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Avatar uploads are disabled"))?;
+    let max_size = store.max_size();
+
+    let mut csrf = None;
+    let mut avatar = None;
+    let mut too_large = false;
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name() {
+            Some("csrf") => csrf = Some(field.text().await?),
+            Some("avatar") => {
+                // Stop reading as soon as the file gets larger than allowed
+                let mut content = Vec::new();
+                while let Some(chunk) = field.chunk().await? {
+                    if content.len() + chunk.len() > max_size {
+                        too_large = true;
+                        break;
+                    }
+                    content.extend_from_slice(&chunk);
+                }
+                avatar = Some(content);
+
+                if too_large {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let csrf = csrf.ok_or_else(|| anyhow::anyhow!("Missing CSRF token"))?;
+    let avatar = cookie_jar.verify_form(&clock, ProtectedForm::new(csrf, avatar))?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::default();
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let avatar_url = match avatar {
+        Some(content) if !too_large && !content.is_empty() => {
+            let id = Ulid::from_datetime_with_source(clock.now().into(), &mut rng);
+            store.save(id, content).await?
+        }
+        _ => None,
+    };
+
+    let Some(avatar_url) = avatar_url else {
+        let error = if too_large {
+            FieldError::TooLarge
+        } else {
+            FieldError::Invalid
+        };
+        let state =
+            FormState::default().with_error_on_field(AccountProfileFormField::Avatar, error);
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let content = render(
+            locale,
+            &templates,
+            &site_config,
+            &homeserver,
+            session,
+            state,
+            csrf_token.form_value(),
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    let mxid = homeserver.mxid(&session.user.username);
+    homeserver
+        .set_avatar_url(&mxid, avatar_url.as_str())
+        .await?;
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::AccountProfile),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::CONTENT_TYPE, Request, StatusCode};
+    use mas_matrix::{HomeserverConnection, ProvisionRequest};
+    use mas_router::SimpleRoute;
+    use mas_storage::Clock;
+    use sqlx::PgPool;
+
+    use crate::{
+        blob_storage::BlobStorage,
+        test_utils::{init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+        AvatarStore,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_profile(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        let id = ulid::Ulid::from_datetime_with_source(state.clock.now().into(), &mut state.rng());
+        let path = std::env::temp_dir().join(format!("mas-profile-{id}"));
+        state.site_config.avatar_store = Some(AvatarStore::new(
+            BlobStorage::filesystem(path.clone().try_into().unwrap()),
+            16,
+            state.url_builder.clone(),
+        ));
+
+        let user = state.create_user("john", "hunter2").await;
+        let conn = &state.homeserver_connection;
+        let mxid = conn.mxid(&user.username);
+        conn.provision_user(&ProvisionRequest::new(&mxid, &user.sub))
+            .await
+            .unwrap();
+
+        state.login(&cookies, "john", "hunter2").await;
+
+        let request = cookies.with_cookies(Request::get(mas_router::AccountProfile::PATH).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // Set the display name
+        let request = Request::post(mas_router::AccountProfile::PATH).form(serde_json::json!({
+            "csrf": csrf_token,
+            "action": "set_display_name",
+            "display_name": "John Doe",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        assert_eq!(response.location(), mas_router::AccountProfile::PATH);
+
+        let profile = conn.query_user(&mxid).await.unwrap();
+        assert_eq!(profile.displayname.as_deref(), Some("John Doe"));
+
+        let request = cookies.with_cookies(Request::get(mas_router::AccountProfile::PATH).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("John Doe"));
+
+        // Display names longer than 256 bytes are refused
+        let request = Request::post(mas_router::AccountProfile::PATH).form(serde_json::json!({
+            "csrf": csrf_token,
+            "action": "set_display_name",
+            "display_name": "a".repeat(257),
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::OK);
+        let profile = conn.query_user(&mxid).await.unwrap();
+        assert_eq!(profile.displayname.as_deref(), Some("John Doe"));
+
+        // An empty display name removes it
+        let request = Request::post(mas_router::AccountProfile::PATH).form(serde_json::json!({
+            "csrf": csrf_token,
+            "action": "set_display_name",
+            "display_name": "",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        let profile = conn.query_user(&mxid).await.unwrap();
+        assert_eq!(profile.displayname, None);
+
+        // Upload an avatar
+        let upload = |content: &str| {
+            let body = format!(
+                "--boundary\r\n\
+                 Content-Disposition: form-data; name=\"csrf\"\r\n\r\n\
+                 {csrf_token}\r\n\
+                 --boundary\r\n\
+                 Content-Disposition: form-data; name=\"avatar\"; filename=\"avatar\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n\
+                 {content}\r\n\
+                 --boundary--\r\n"
+            );
+            let request = Request::post(mas_router::AccountProfileAvatar::PATH)
+                .header(CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+                .body(body)
+                .unwrap();
+            cookies.with_cookies(request)
+        };
+
+        // Files which aren't images are refused
+        let response = state.request(upload("hello")).await;
+        response.assert_status(StatusCode::OK);
+
+        // So are files larger than the limit
+        let response = state.request(upload("GIF89a and a bit more")).await;
+        response.assert_status(StatusCode::OK);
+
+        let profile = conn.query_user(&mxid).await.unwrap();
+        assert_eq!(profile.avatar_url, None);
+
+        let response = state.request(upload("GIF89ahello")).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let profile = conn.query_user(&mxid).await.unwrap();
+        let avatar_url = profile.avatar_url.unwrap();
+        assert!(avatar_url.starts_with("https://example.com/avatars/"));
+        assert!(avatar_url.ends_with(".gif"));
+
+        // Remove it
+        let request = Request::post(mas_router::AccountProfile::PATH).form(serde_json::json!({
+            "csrf": csrf_token,
+            "action": "remove_avatar",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let profile = conn.query_user(&mxid).await.unwrap();
+        assert_eq!(profile.avatar_url, None);
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
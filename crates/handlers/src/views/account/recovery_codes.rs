@@ -0,0 +1,161 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{BrowserSession, User, UserRecoveryCode};
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    user::UserRecoveryCodeRepository, BoxClock, BoxRepository, BoxRng, RepositoryAccess,
+    RepositoryError,
+};
+use mas_templates::{AccountRecoveryCodesContext, TemplateContext, Templates};
+
+use crate::{BoundActivityTracker, PreferredLanguage};
+
+/// Generate a new set of recovery codes for the user, replacing the previous
+/// ones.
+///
+/// Returns the plain codes, which should be shown to the user once and never
+/// again, as only their hashes are saved.
+pub(crate) async fn regenerate(
+    rng: &mut BoxRng,
+    clock: &BoxClock,
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<Vec<String>, RepositoryError> {
+    let codes: Vec<String> = (0..UserRecoveryCode::COUNT)
+        .map(|_| UserRecoveryCode::generate(rng))
+        .collect();
+    let hashes = codes
+        .iter()
+        .map(|code| UserRecoveryCode::hash(code))
+        .collect();
+
+    repo.user_recovery_code()
+        .replace(rng, clock, user, hashes)
+        .await?;
+
+    Ok(codes)
+}
+
+#[tracing::instrument(name = "handlers.views.account_recovery_codes.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageRecoveryCodes);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let remaining = repo
+        .user_recovery_code()
+        .count_remaining(&session.user)
+        .await?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let content = render(
+        locale,
+        &templates,
+        session,
+        AccountRecoveryCodesContext::new(remaining),
+        csrf_token.form_value(),
+    )?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+/// Render the recovery codes page. This is also used right after the first
+/// passkey of the user is registered, to show the codes generated for them.
+pub(crate) fn render(
+    locale: DataLocale,
+    templates: &Templates,
+    session: BrowserSession,
+    ctx: AccountRecoveryCodesContext,
+    csrf_token: String,
+) -> Result<String, FancyError> {
+    let ctx = ctx
+        .with_session(session)
+        .with_csrf(csrf_token)
+        .with_language(locale);
+
+    let content = templates.render_account_recovery_codes(&ctx)?;
+    Ok(content)
+}
+
+/// Generate a new set of recovery codes, invalidating the previous ones
+#[tracing::instrument(name = "handlers.views.account_recovery_codes.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageRecoveryCodes);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let codes = regenerate(&mut rng, &clock, &mut repo, &session.user).await?;
+
+    repo.save().await?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let content = render(
+        locale,
+        &templates,
+        session,
+        AccountRecoveryCodesContext::default().with_codes(codes),
+        csrf_token.form_value(),
+    )?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
@@ -0,0 +1,330 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context;
+use axum::{
+    extract::{Form, Path, State},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use hyper::StatusCode;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::BrowserSession;
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    user::{UserRecoveryCodeRepository, WebauthnCredentialRepository},
+    BoxClock, BoxRepository, BoxRng,
+};
+use mas_templates::{
+    AccountRecoveryCodesContext, AccountWebauthnContext, AccountWebauthnFormField, FieldError,
+    FormError, FormState, TemplateContext, Templates, ToFormState,
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+use webauthn_rs::prelude::{RegisterPublicKeyCredential, Uuid};
+
+use super::recovery_codes::regenerate;
+use crate::{
+    webauthn::{encode_credential_id, passkeys, relying_party, WebauthnCeremony},
+    BoundActivityTracker, PreferredLanguage,
+};
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RegisterForm {
+    name: String,
+
+    /// The credential created by the browser, serialized as JSON
+    #[serde(skip_serializing)]
+    credential: String,
+}
+
+impl ToFormState for RegisterForm {
+    type Field = AccountWebauthnFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.account_webauthn.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageWebauthn);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let content = render(
+        locale,
+        &templates,
+        &mut repo,
+        session,
+        FormState::default(),
+        csrf_token.form_value(),
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    templates: &Templates,
+    repo: &mut BoxRepository,
+    session: BrowserSession,
+    form: FormState<AccountWebauthnFormField>,
+    csrf_token: String,
+) -> Result<String, FancyError> {
+    let credentials = repo.webauthn_credential().all(&session.user).await?;
+
+    let ctx = AccountWebauthnContext::new(credentials)
+        .with_form_state(form)
+        .with_session(session)
+        .with_csrf(csrf_token)
+        .with_language(locale);
+
+    let content = templates.render_account_webauthn(&ctx)?;
+    Ok(content)
+}
+
+/// Start registering a new credential: replies with the options to pass to
+/// `navigator.credentials.create()`
+#[tracing::instrument(name = "handlers.views.account_webauthn.challenge", skip_all, err)]
+pub(crate) async fn challenge(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        return Ok((cookie_jar, StatusCode::UNAUTHORIZED).into_response());
+    };
+
+    let credentials = repo.webauthn_credential().all(&session.user).await?;
+    let exclude_credentials = passkeys(&credentials)?
+        .iter()
+        .map(|passkey| passkey.cred_id().clone())
+        .collect();
+
+    let webauthn = relying_party(&url_builder)?;
+    let (challenge, state) = webauthn.start_passkey_registration(
+        Uuid::from_u128(session.user.id.0),
+        &session.user.username,
+        &session.user.username,
+        Some(exclude_credentials),
+    )?;
+
+    let cookie_jar =
+        WebauthnCeremony::registration(&session.user, state).save(cookie_jar, &clock, None);
+
+    Ok((cookie_jar, Json(challenge)).into_response())
+}
+
+/// Finish registering a new credential, with the response of the browser
+#[tracing::instrument(name = "handlers.views.account_webauthn.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<RegisterForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageWebauthn);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // The challenge can only be answered once
+    let (ceremony, cookie_jar) = WebauthnCeremony::take(cookie_jar, &clock, None);
+
+    let mut state = form.to_form_state();
+    let name = form.name.trim();
+    if name.is_empty() {
+        state.add_error_on_field(AccountWebauthnFormField::Name, FieldError::Required);
+    }
+
+    // Check the response of the browser against the challenge it was given
+    let passkey = match ceremony {
+        Some(WebauthnCeremony::Registration {
+            user_id,
+            state: registration,
+        }) if user_id == session.user.id => {
+            let webauthn = relying_party(&url_builder)?;
+            serde_json::from_str::<RegisterPublicKeyCredential>(&form.credential)
+                .map_err(anyhow::Error::from)
+                .and_then(|credential| {
+                    Ok(webauthn.finish_passkey_registration(&credential, &registration)?)
+                })
+                .map_err(|e| {
+                    tracing::warn!(error = %e, "Failed to register WebAuthn credential");
+                })
+                .ok()
+        }
+        _ => None,
+    };
+
+    // A credential can only be registered once
+    let passkey = if let Some(passkey) = passkey {
+        let credential_id = encode_credential_id(passkey.cred_id())?;
+        let exists = repo
+            .webauthn_credential()
+            .find_by_credential_id(&credential_id)
+            .await?
+            .is_some();
+        (!exists).then_some((credential_id, passkey))
+    } else {
+        None
+    };
+
+    if passkey.is_none() {
+        state.add_error_on_form(FormError::WebauthnFailed);
+    }
+
+    let Some((credential_id, passkey)) = passkey.filter(|_| state.is_valid()) else {
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let content = render(
+            locale,
+            &templates,
+            &mut repo,
+            session,
+            state,
+            csrf_token.form_value(),
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    repo.webauthn_credential()
+        .add(
+            &mut rng,
+            &clock,
+            &session.user,
+            name.to_owned(),
+            credential_id,
+            serde_json::to_value(&passkey)?,
+        )
+        .await?;
+
+    // Give the user recovery codes when they enroll their first passkey, so
+    // that they can still sign in if they lose it
+    let remaining = repo
+        .user_recovery_code()
+        .count_remaining(&session.user)
+        .await?;
+    if remaining == 0 {
+        let codes = regenerate(&mut rng, &clock, &mut repo, &session.user).await?;
+
+        repo.save().await?;
+
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let content = super::recovery_codes::render(
+            locale,
+            &templates,
+            session,
+            AccountRecoveryCodesContext::default().with_codes(codes),
+            csrf_token.form_value(),
+        )?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    repo.save().await?;
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::AccountWebauthn),
+    )
+        .into_response())
+}
+
+/// Remove one of the credentials of the user
+#[tracing::instrument(
+    name = "handlers.views.account_webauthn.remove",
+    fields(webauthn_credential.id = %id),
+    skip_all,
+    err,
+)]
+pub(crate) async fn remove(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    cookie_jar: CookieJar,
+    Path(id): Path<Ulid>,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        let login = mas_router::Login::and_then(mas_router::PostAuthAction::ManageWebauthn);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let credential = repo
+        .webauthn_credential()
+        .lookup(id)
+        .await?
+        .filter(|credential| credential.user_id == session.user.id)
+        .context("WebAuthn credential not found")?;
+
+    repo.webauthn_credential().remove(credential).await?;
+
+    repo.save().await?;
+
+    Ok((
+        cookie_jar,
+        url_builder.redirect(&mas_router::AccountWebauthn),
+    )
+        .into_response())
+}
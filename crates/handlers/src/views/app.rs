@@ -16,12 +16,21 @@ use axum::{
     extract::{Query, State},
     response::{Html, IntoResponse},
 };
+use hyper::header::CONTENT_SECURITY_POLICY;
 use mas_axum_utils::{cookies::CookieJar, FancyError, SessionInfoExt};
 use mas_router::{PostAuthAction, UrlBuilder};
 use mas_storage::{BoxClock, BoxRepository};
 use mas_templates::{AppContext, TemplateContext, Templates};
+use serde::Deserialize;
 
-use crate::{BoundActivityTracker, PreferredLanguage};
+use crate::{preferred_language::save_locale, BoundActivityTracker, PreferredLanguage, SiteConfig};
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct DisplayParams {
+    /// Whether the app is embedded in a frame by a client
+    #[serde(default)]
+    embedded: bool,
+}
 
 #[tracing::instrument(name = "handlers.views.app.get", skip_all, err)]
 pub async fn get(
@@ -29,7 +38,9 @@ pub async fn get(
     State(templates): State<Templates>,
     activity_tracker: BoundActivityTracker,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     action: Option<Query<mas_router::AccountAction>>,
+    Query(display): Query<DisplayParams>,
     mut repo: BoxRepository,
     clock: BoxClock,
     cookie_jar: CookieJar,
@@ -53,8 +64,83 @@ pub async fn get(
         .record_browser_session(&clock, &session)
         .await;
 
-    let ctx = AppContext::from_url_builder(&url_builder).with_language(locale);
+    // The preferred language might have been changed from another browser, so
+    // make sure the cookie is up to date
+    let cookie_jar = save_locale(cookie_jar, &session.user);
+    let locale = session
+        .user
+        .locale
+        .as_deref()
+        .and_then(|locale| locale.parse().ok())
+        .unwrap_or(locale);
+
+    // The app can only be framed by the allowed origins, and only when asked
+    // to be rendered for it
+    let embedding_origins = &site_config.embedding_origins;
+    let embedded = display.embedded && !embedding_origins.is_empty();
+    let ctx = AppContext::from_url_builder(&url_builder);
+    let (ctx, frame_ancestors) = if embedded {
+        (
+            ctx.with_embedding_origins(embedding_origins.to_vec()),
+            embedding_origins.join(" "),
+        )
+    } else {
+        (ctx, "'none'".to_owned())
+    };
+
+    let ctx = ctx.with_language(locale);
     let content = templates.render_app(&ctx)?;
 
-    Ok((cookie_jar, Html(content)).into_response())
+    Ok((
+        cookie_jar,
+        [(
+            CONTENT_SECURITY_POLICY,
+            format!("frame-ancestors {frame_ancestors}"),
+        )],
+        Html(content),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::CONTENT_SECURITY_POLICY, Request, StatusCode};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_embedded(pool: PgPool) {
+        init_tracing();
+        let mut state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        state.create_user("john", "hunter2").await;
+        state.login(&cookies, "john", "hunter2").await;
+
+        // Embedding isn't allowed by default
+        let request = cookies.with_cookies(Request::get("/account/?embedded=true").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.assert_header_value(CONTENT_SECURITY_POLICY, "frame-ancestors 'none'");
+
+        state.site_config.embedding_origins = vec!["https://client.example.com".to_owned()].into();
+
+        // Only when asked for
+        let request = cookies.with_cookies(Request::get("/account/sessions").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.assert_header_value(CONTENT_SECURITY_POLICY, "frame-ancestors 'none'");
+
+        let request = cookies.with_cookies(Request::get("/account/sessions?embedded=true").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        response.assert_header_value(
+            CONTENT_SECURITY_POLICY,
+            "frame-ancestors https://client.example.com",
+        );
+        assert!(response.body().contains(r#"\"embedded\":true"#));
+    }
 }
@@ -0,0 +1,44 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hands out CSRF tokens to the frontend, so that it can submit the login,
+//! registration, reauthentication and consent forms as JSON
+
+use axum::{response::IntoResponse, Json};
+use hyper::header::CACHE_CONTROL;
+use mas_axum_utils::{cookies::CookieJar, csrf::CsrfExt};
+use mas_storage::{BoxClock, BoxRng};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct CsrfTokenResponse {
+    csrf: String,
+}
+
+#[tracing::instrument(name = "handlers.views.csrf.get", skip_all)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    cookie_jar: CookieJar,
+) -> impl IntoResponse {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    (
+        cookie_jar,
+        [(CACHE_CONTROL, "no-store")],
+        Json(CsrfTokenResponse {
+            csrf: csrf_token.form_value(),
+        }),
+    )
+}
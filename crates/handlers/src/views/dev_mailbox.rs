@@ -0,0 +1,102 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lists the emails kept by the in-memory mail transport, to help developing
+//! email-dependent flows locally
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use mas_axum_utils::FancyError;
+use mas_email::MemoryMailbox;
+use mas_templates::{DevMailboxContext, DevMailboxEmail, TemplateContext, Templates};
+
+use crate::PreferredLanguage;
+
+#[tracing::instrument(name = "handlers.views.dev_mailbox.get", skip_all, err)]
+pub(crate) async fn get(
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    Extension(mailbox): Extension<MemoryMailbox>,
+) -> Result<impl IntoResponse, FancyError> {
+    let emails = mailbox
+        .emails()
+        .into_iter()
+        .rev()
+        .map(|email| DevMailboxEmail {
+            sent_at: DateTime::<Utc>::from(email.sent_at),
+            subject: email.subject().map(ToOwned::to_owned),
+            from: email.from,
+            to: email.to,
+            message: email.message,
+        })
+        .collect();
+
+    let ctx = DevMailboxContext::new(emails).with_language(locale);
+    let content = templates.render_dev_mailbox(&ctx)?;
+
+    Ok(Html(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Request, StatusCode};
+    use lettre::{AsyncTransport, Message};
+    use mas_email::MailTransport;
+    use mas_router::SimpleRoute;
+    use sqlx::PgPool;
+    use tower::{Service, ServiceExt};
+
+    use crate::test_utils::{init_tracing, RequestBuilderExt, TestState};
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_dev_mailbox(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+
+        let transport = MailTransport::memory();
+        let mailbox = transport.mailbox().unwrap();
+        let message = Message::builder()
+            .from("root@localhost".parse().unwrap())
+            .to("alice@example.com".parse().unwrap())
+            .subject("Your code")
+            .body("Your code is 123456".to_owned())
+            .unwrap();
+        transport.send(message).await.unwrap();
+
+        let emails = mailbox.emails();
+        assert_eq!(emails.len(), 1);
+        assert_eq!(emails[0].subject(), Some("Your code"));
+        assert_eq!(emails[0].to, vec!["alice@example.com".to_owned()]);
+
+        let app = crate::dev_mailbox_router(mailbox).with_state(state);
+        let request = Request::get(mas_router::DevMailbox::PATH).empty();
+        let response = app
+            .ready_oneshot()
+            .await
+            .unwrap()
+            .call(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("Your code is 123456"));
+        assert!(body.contains("alice@example.com"));
+    }
+}
@@ -13,22 +13,24 @@
 // limitations under the License.
 
 use axum::{
-    extract::{Form, Query, State},
-    response::{Html, IntoResponse, Response},
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
     TypedHeader,
 };
 use headers::UserAgent;
-use hyper::StatusCode;
+use hyper::{header::RETRY_AFTER, StatusCode};
 use mas_axum_utils::{
     cookies::CookieJar,
     csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    form_or_json::{form_errors, json_redirect, FormOrJson},
+    http_client_factory::HttpClientFactory,
     FancyError, SessionInfoExt,
 };
 use mas_data_model::BrowserSession;
 use mas_i18n::DataLocale;
 use mas_router::{UpstreamOAuth2Authorize, UrlBuilder};
 use mas_storage::{
-    upstream_oauth2::UpstreamOAuthProviderRepository,
+    job::{JobRepositoryExt, NotifyNewSignInJob, SendPasswordResetEmailJob},
     user::{BrowserSessionRepository, UserPasswordRepository, UserRepository},
     BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
 };
@@ -39,13 +41,19 @@ use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
 use zeroize::Zeroizing;
 
-use super::shared::OptionalPostAuthAction;
-use crate::{passwords::PasswordManager, BoundActivityTracker, PreferredLanguage};
+use super::shared::{NextUrl, OptionalPostAuthAction};
+use crate::{
+    captcha::CaptchaForm, passwords::PasswordManager, preferred_language::remember_locale,
+    BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct LoginForm {
     username: String,
     password: String,
+
+    #[serde(flatten, skip_serializing)]
+    captcha: CaptchaForm,
 }
 
 impl ToFormState for LoginForm {
@@ -60,9 +68,11 @@ pub(crate) async fn get(
     State(password_manager): State<PasswordManager>,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     mut repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
     Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
     cookie_jar: CookieJar,
 ) -> Result<Response, FancyError> {
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
@@ -75,11 +85,11 @@ pub(crate) async fn get(
             .record_browser_session(&clock, &session)
             .await;
 
-        let reply = query.go_next(&url_builder);
+        let reply = go_next(&query, &next, &url_builder, &site_config);
         return Ok((cookie_jar, reply).into_response());
     };
 
-    let providers = repo.upstream_oauth_provider().all().await?;
+    let providers = crate::upstream_providers(&mut repo).await?;
 
     // If password-based login is disabled, and there is only one upstream provider,
     // we can directly start an authorization flow
@@ -98,8 +108,10 @@ pub(crate) async fn get(
     let content = render(
         locale,
         LoginContext::default()
+            .with_captcha(site_config.captcha.clone())
             // XXX: we might want to have a site-wide config in the templates context instead?
             .with_password_login(password_manager.is_enabled())
+            .with_login_link(site_config.email_login_links)
             .with_upstream_providers(providers),
         query,
         csrf_token,
@@ -119,12 +131,15 @@ pub(crate) async fn post(
     State(password_manager): State<PasswordManager>,
     State(templates): State<Templates>,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(http_client_factory): State<HttpClientFactory>,
     mut repo: BoxRepository,
     activity_tracker: BoundActivityTracker,
     Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
     cookie_jar: CookieJar,
     user_agent: Option<TypedHeader<UserAgent>>,
-    Form(form): Form<ProtectedForm<LoginForm>>,
+    form: FormOrJson<ProtectedForm<LoginForm>>,
 ) -> Result<Response, FancyError> {
     let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
     if !password_manager.is_enabled() {
@@ -132,7 +147,9 @@ pub(crate) async fn post(
         return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
     }
 
-    let form = cookie_jar.verify_form(&clock, form)?;
+    // The frontend submits the form as JSON, and expects JSON replies
+    let json = form.is_json();
+    let form = cookie_jar.verify_form(&clock, form.into_inner())?;
 
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
 
@@ -152,11 +169,17 @@ pub(crate) async fn post(
     };
 
     if !state.is_valid() {
-        let providers = repo.upstream_oauth_provider().all().await?;
+        if json {
+            return Ok((cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response());
+        }
+
+        let providers = crate::upstream_providers(&mut repo).await?;
         let content = render(
             locale,
             LoginContext::default()
+                .with_captcha(site_config.captcha.clone())
                 .with_form_state(state)
+                .with_login_link(site_config.email_login_links)
                 .with_upstream_providers(providers),
             query,
             csrf_token,
@@ -168,18 +191,154 @@ pub(crate) async fn post(
         return Ok((cookie_jar, Html(content)).into_response());
     }
 
+    // Limit the number of attempts from a single IP address
+    if let Some(ip) = activity_tracker.ip() {
+        if let Err(e) = site_config
+            .rate_limiter
+            .check(
+                &clock,
+                &format!("login:ip:{ip}"),
+                site_config.login_rate_limit,
+            )
+            .await
+        {
+            let state = state.with_error_on_form(FormError::RateLimitExceeded);
+            let retry_after = e.retry_after(clock.now()).to_string();
+            if json {
+                return Ok((
+                    [(RETRY_AFTER, retry_after)],
+                    cookie_jar,
+                    form_errors(StatusCode::TOO_MANY_REQUESTS, &state),
+                )
+                    .into_response());
+            }
+
+            let content = render(
+                locale,
+                LoginContext::default()
+                    .with_captcha(site_config.captcha.clone())
+                    .with_form_state(state)
+                    .with_login_link(site_config.email_login_links),
+                query,
+                csrf_token,
+                &mut repo,
+                &templates,
+            )
+            .await?;
+
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after)],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+    }
+
+    // Check the CAPTCHA before looking at the credentials
+    if let Err(e) = form
+        .captcha
+        .verify(
+            &http_client_factory,
+            activity_tracker.ip(),
+            site_config.captcha.as_ref(),
+        )
+        .await
+    {
+        if !e.is_user_error() {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Could not verify the CAPTCHA"
+            );
+        }
+
+        let state = state.with_error_on_form(FormError::Captcha);
+        if json {
+            return Ok((cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response());
+        }
+
+        let content = render(
+            locale,
+            LoginContext::default()
+                .with_captcha(site_config.captcha.clone())
+                .with_form_state(state)
+                .with_login_link(site_config.email_login_links),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    // Refuse the attempt without looking at the password if the account or the
+    // IP address is locked out after too many failures
+    if let Some(lockout) = &site_config.login_lockout {
+        if let Some(locked_until) = lockout
+            .locked_until(&mut repo, &clock, &form.username, activity_tracker.ip())
+            .await?
+        {
+            let state = state.with_error_on_form(FormError::LoginLocked);
+            let retry_after = (locked_until - clock.now())
+                .num_seconds()
+                .max(1)
+                .to_string();
+            if json {
+                return Ok((
+                    [(RETRY_AFTER, retry_after)],
+                    cookie_jar,
+                    form_errors(StatusCode::TOO_MANY_REQUESTS, &state),
+                )
+                    .into_response());
+            }
+
+            let content = render(
+                locale,
+                LoginContext::default()
+                    .with_captcha(site_config.captcha.clone())
+                    .with_form_state(state)
+                    .with_login_link(site_config.email_login_links),
+                query,
+                csrf_token,
+                &mut repo,
+                &templates,
+            )
+            .await?;
+
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after)],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+    }
+
     match login(
         password_manager,
         &mut repo,
         rng,
         &clock,
+        &locale,
         &form.username,
         &form.password,
         user_agent,
     )
     .await
     {
-        Ok(session_info) => {
+        Ok(mut session_info) => {
+            let (user, cookie_jar) =
+                remember_locale(&mut repo, &locale, session_info.user, cookie_jar).await?;
+            session_info.user = user;
+
+            if let Some(lockout) = &site_config.login_lockout {
+                lockout.reset(&mut repo, &session_info.user).await?;
+            }
+
             repo.save().await?;
 
             activity_tracker
@@ -187,15 +346,45 @@ pub(crate) async fn post(
                 .await;
 
             let cookie_jar = cookie_jar.set_session(&session_info);
-            let reply = query.go_next(&url_builder);
+            let reply = go_next(&query, &next, &url_builder, &site_config);
+            if json {
+                return Ok((cookie_jar, json_redirect(reply)).into_response());
+            }
+
             Ok((cookie_jar, reply).into_response())
         }
         Err(e) => {
+            // The password reset link has to be sent even though the login failed
+            let reset_required = matches!(e, FormError::PasswordResetRequired);
+
+            // Count the failure towards the lockout of the user and the IP address
+            let lockout = site_config
+                .login_lockout
+                .filter(|_| matches!(e, FormError::InvalidCredentials));
+            if let Some(lockout) = &lockout {
+                lockout
+                    .record_failure(&mut repo, &clock, &form.username, activity_tracker.ip())
+                    .await?;
+            }
+
             let state = state.with_error_on_form(e);
 
+            if json {
+                if reset_required || lockout.is_some() {
+                    repo.save().await?;
+                }
+
+                return Ok(
+                    (cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response()
+                );
+            }
+
             let content = render(
                 locale,
-                LoginContext::default().with_form_state(state),
+                LoginContext::default()
+                    .with_captcha(site_config.captcha.clone())
+                    .with_form_state(state)
+                    .with_login_link(site_config.email_login_links),
                 query,
                 csrf_token,
                 &mut repo,
@@ -203,17 +392,40 @@ pub(crate) async fn post(
             )
             .await?;
 
+            if reset_required || lockout.is_some() {
+                repo.save().await?;
+            }
+
             Ok((cookie_jar, Html(content)).into_response())
         }
     }
 }
 
+/// Where to send the user once they are logged in: the post auth action takes
+/// precedence over the `next` URL
+pub(super) fn go_next(
+    query: &OptionalPostAuthAction,
+    next: &NextUrl,
+    url_builder: &UrlBuilder,
+    site_config: &SiteConfig,
+) -> Redirect {
+    if query.post_auth_action.is_none() {
+        if let Some(reply) = next.redirect(site_config) {
+            return reply;
+        }
+    }
+
+    query.go_next(url_builder)
+}
+
 // TODO: move that logic elsewhere?
+#[allow(clippy::too_many_arguments)]
 async fn login(
     password_manager: PasswordManager,
     repo: &mut impl RepositoryAccess,
     mut rng: impl Rng + CryptoRng + Send,
     clock: &impl Clock,
+    locale: &DataLocale,
     username: &str,
     password: &str,
     user_agent: Option<String>,
@@ -226,6 +438,7 @@ async fn login(
         .await
         .map_err(|_e| FormError::Internal)?
         .filter(mas_data_model::User::is_valid)
+        .filter(mas_data_model::User::can_login_interactively)
         .ok_or(FormError::InvalidCredentials)?;
 
     // And its password
@@ -249,6 +462,17 @@ async fn login(
         .await
         .map_err(|_| FormError::InvalidCredentials)?;
 
+    // The user must choose a new password: send them a fresh link to do so
+    // instead of starting a session
+    if user.password_reset_required() {
+        repo.job()
+            .schedule_job(SendPasswordResetEmailJob::new(&user).with_language(locale.to_string()))
+            .await
+            .map_err(|_| FormError::Internal)?;
+
+        return Err(FormError::PasswordResetRequired);
+    }
+
     let user_password = if let Some((version, new_password_hash)) = new_password_hash {
         // Save the upgraded password
         repo.user_password()
@@ -279,10 +503,16 @@ async fn login(
         .await
         .map_err(|_| FormError::Internal)?;
 
+    // Let the other sessions of the user know about this sign-in
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_browser_session(&user_session))
+        .await
+        .map_err(|_| FormError::Internal)?;
+
     Ok(user_session)
 }
 
-async fn render(
+pub(super) async fn render(
     locale: DataLocale,
     ctx: LoginContext,
     action: OptionalPostAuthAction,
@@ -353,6 +583,7 @@ mod test {
                     issuer: "https://first.com/".to_owned(),
                     human_name: Some("First Ltd.".to_owned()),
                     brand_name: None,
+                    icon: None,
                     scope: [OPENID].into_iter().collect(),
                     token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
                     token_endpoint_signing_alg: None,
@@ -387,6 +618,7 @@ mod test {
                     issuer: "https://second.com/".to_owned(),
                     human_name: None,
                     brand_name: None,
+                    icon: None,
                     scope: [OPENID].into_iter().collect(),
                     token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
                     token_endpoint_signing_alg: None,
@@ -481,4 +713,149 @@ mod test {
         response.assert_header_value(CONTENT_TYPE, "text/html; charset=utf-8");
         assert!(response.body().contains("john"));
     }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_next_url(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.allowed_next_urls =
+                vec!["https://app.example.com/".parse().unwrap()].into();
+            state
+        };
+        let cookies = CookieHelper::new();
+        state.create_user("john", "hunter2").await;
+
+        let next = "https://app.example.com/#/home";
+        let uri = format!(
+            "/login?{}",
+            serde_urlencoded::to_string([("next", next)]).unwrap()
+        );
+
+        // Log in with the next parameter, which sends the user back there
+        let request = cookies.with_cookies(Request::get(&uri).empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token();
+
+        let request = Request::post(&uri).form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "hunter2",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, next);
+
+        // Once logged in, the login page directly redirects there
+        let request = cookies.with_cookies(Request::get(&uri).empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, next);
+
+        // URLs outside of the allow-list are ignored
+        let request = cookies
+            .with_cookies(Request::get("/login?next=https%3A%2F%2Fevil.example.com%2F").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/");
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_json_login(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        state.create_user("john", "hunter2").await;
+
+        // The frontend gets a CSRF token without rendering the login page
+        let request = cookies.with_cookies(Request::get("/csrf-token").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        let csrf_token = body["csrf"].as_str().unwrap();
+
+        // Errors on the form are reported as JSON
+        let request = Request::post("/login").json(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(
+            body["form"]["fields"]["password"]["errors"][0]["kind"],
+            "required"
+        );
+
+        let request = Request::post("/login").json(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "wrong",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["form"]["errors"][0]["kind"], "invalid_credentials");
+
+        // And on success, the frontend is told where to go next
+        let request = Request::post("/login").json(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "hunter2",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["redirect_uri"], "/");
+
+        let request = cookies.with_cookies(Request::get("/").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("john"));
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_captcha(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.captcha = Some(mas_data_model::CaptchaConfig {
+                service: mas_data_model::CaptchaService::HCaptcha,
+                site_key: "10000000-ffff-ffff-ffff-000000000001".to_owned(),
+                secret_key: "0x0000000000000000000000000000000000000000".to_owned(),
+            });
+            state
+        };
+        let cookies = CookieHelper::new();
+        state.create_user("john", "hunter2").await;
+
+        // The login page shows the CAPTCHA widget
+        let request = cookies.with_cookies(Request::get("/login").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response
+            .body()
+            .contains(r#"data-sitekey="10000000-ffff-ffff-ffff-000000000001""#));
+        let csrf_token = response.csrf_token();
+
+        // Valid credentials are not enough without solving the challenge
+        let request = Request::post("/login").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "password": "hunter2",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response
+            .body()
+            .contains("The CAPTCHA challenge was not solved"));
+    }
 }
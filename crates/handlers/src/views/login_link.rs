@@ -0,0 +1,530 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sign in with a single-use link sent by email, without a password
+
+use axum::{
+    extract::{Form, Path, Query, State},
+    response::{Html, IntoResponse, Response},
+    TypedHeader,
+};
+use chrono::Duration;
+use headers::UserAgent;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{User, UserLoginLink};
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{JobRepositoryExt, NotifyNewSignInJob, SendLoginLinkEmailJob},
+    user::{
+        BrowserSessionRepository, UserEmailFilter, UserEmailRepository, UserLoginLinkRepository,
+        UserRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock, Pagination, RepositoryAccess,
+};
+use mas_templates::{
+    EmptyContext, FieldError, FormError, LoginLinkContext, LoginLinkFinishContext,
+    LoginLinkFormField, TemplateContext, Templates, ToFormState,
+};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    login::go_next,
+    shared::{NextUrl, OptionalPostAuthAction},
+};
+use crate::{
+    preferred_language::remember_locale, BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
+
+/// How long a login link can be used after it was sent
+const LOGIN_LINK_TTL_MINUTES: i64 = 15;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct LoginLinkForm {
+    email: String,
+}
+
+impl ToFormState for LoginLinkForm {
+    type Field = LoginLinkFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.login_link.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    if !site_config.email_login_links {
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok(url_builder.redirect(&login).into_response());
+    }
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    if let Some(session) = maybe_session {
+        activity_tracker
+            .record_browser_session(&clock, &session)
+            .await;
+
+        let reply = go_next(&query, &next, &url_builder, &site_config);
+        return Ok((cookie_jar, reply).into_response());
+    };
+
+    let content = render(
+        locale,
+        LoginLinkContext::default(),
+        query,
+        csrf_token,
+        &mut repo,
+        &templates,
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.login_link.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<LoginLinkForm>>,
+) -> Result<Response, FancyError> {
+    if !site_config.email_login_links {
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok(url_builder.redirect(&login).into_response());
+    }
+
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    // Validate the form
+    let mut state = form.to_form_state();
+
+    let email = site_config.email_normalization.normalize(&form.email);
+    if email.is_empty() {
+        state.add_error_on_field(LoginLinkFormField::Email, FieldError::Required);
+    }
+
+    if !state.is_valid() {
+        let content = render(
+            locale,
+            LoginLinkContext::default().with_form_state(state),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    // Each request sends an email, so they share the rate limit of the
+    // password login, both per IP address and per email address
+    let keys = activity_tracker
+        .ip()
+        .map(|ip| format!("login-link:ip:{ip}"))
+        .into_iter()
+        .chain(std::iter::once(format!("login-link:email:{email}")));
+    for key in keys {
+        if let Err(e) = site_config
+            .rate_limiter
+            .check(&clock, &key, site_config.login_rate_limit)
+            .await
+        {
+            let state = state.with_error_on_form(FormError::RateLimitExceeded);
+            let content = render(
+                locale,
+                LoginLinkContext::default().with_form_state(state),
+                query,
+                csrf_token,
+                &mut repo,
+                &templates,
+            )
+            .await?;
+
+            let retry_after = e.retry_after(clock.now()).to_string();
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after)],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+    }
+
+    // A verified email address belongs to at most one user
+    let filter = UserEmailFilter::new().for_email(&email).verified_only();
+    let user_email = repo
+        .user_email()
+        .list(filter, Pagination::first(1))
+        .await?
+        .edges
+        .into_iter()
+        .next();
+
+    let user = if let Some(user_email) = &user_email {
+        repo.user()
+            .lookup(user_email.user_id)
+            .await?
+            .filter(User::is_valid)
+            .filter(User::can_login_interactively)
+    } else {
+        None
+    };
+
+    // The same page is shown whether the address belongs to an account or
+    // not, so that it can't be used to find out who has an account
+    if let (Some(user_email), Some(_user)) = (user_email, user) {
+        let post_auth_action = query
+            .post_auth_action
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?;
+
+        let token = Alphanumeric.sample_string(&mut rng, 32);
+        let login_link = repo
+            .user_login_link()
+            .add(
+                &mut rng,
+                &clock,
+                &user_email,
+                token,
+                post_auth_action,
+                Duration::minutes(LOGIN_LINK_TTL_MINUTES),
+            )
+            .await?;
+
+        repo.job()
+            .schedule_job(SendLoginLinkEmailJob::new(&login_link).with_language(locale.to_string()))
+            .await?;
+
+        repo.save().await?;
+    } else {
+        tracing::info!("No user found for this email address, not sending a login link");
+    }
+
+    let content = render(
+        locale,
+        LoginLinkContext::default().with_form_state(state).sent(),
+        query,
+        csrf_token,
+        &mut repo,
+        &templates,
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: LoginLinkContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_login_link(&ctx)?;
+    Ok(content)
+}
+
+/// Lookup a login link which can still be used, along with the user it
+/// belongs to
+async fn load_login_link(
+    repo: &mut BoxRepository,
+    clock: &impl Clock,
+    token: &str,
+) -> Result<Option<(UserLoginLink, User)>, FancyError> {
+    let Some(login_link) = repo.user_login_link().find_by_token(token).await? else {
+        return Ok(None);
+    };
+
+    if !login_link.is_valid(clock.now()) {
+        return Ok(None);
+    }
+
+    let Some(user) = repo
+        .user()
+        .lookup(login_link.user_id)
+        .await?
+        .filter(User::is_valid)
+        .filter(User::can_login_interactively)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some((login_link, user)))
+}
+
+fn render_expired(
+    locale: DataLocale,
+    templates: &Templates,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let ctx = EmptyContext.with_language(locale);
+    let content = templates.render_login_link_expired(&ctx)?;
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+/// Show a confirmation page instead of signing in straight away, so that
+/// email clients and link scanners fetching the link don't use it up
+#[tracing::instrument(name = "handlers.views.login_link.finish_get", skip_all, err)]
+pub(crate) async fn finish_get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Path(token): Path<String>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    if !site_config.email_login_links {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    let Some((_login_link, user)) = load_login_link(&mut repo, &clock, &token).await? else {
+        return render_expired(locale, &templates, cookie_jar);
+    };
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let ctx = LoginLinkFinishContext::new(user)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_login_link_finish(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.login_link.finish_post", skip_all, err)]
+pub(crate) async fn finish_post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    Path(token): Path<String>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    if !site_config.email_login_links {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    cookie_jar.verify_form(&clock, form)?;
+
+    let Some((login_link, user)) = load_login_link(&mut repo, &clock, &token).await? else {
+        return render_expired(locale, &templates, cookie_jar);
+    };
+
+    // Resume where the user was when they asked for the link
+    let action: OptionalPostAuthAction = login_link
+        .post_auth_action
+        .clone()
+        .map(serde_json::from_value)
+        .transpose()
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Invalid post auth action"
+            );
+            None
+        })
+        .unwrap_or_default();
+
+    let login_link = repo.user_login_link().consume(&clock, login_link).await?;
+
+    // Start a new session, authenticated by the login link
+    let mut user_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_login_link(&mut rng, &clock, &user_session, &login_link)
+        .await?;
+
+    // Let the other sessions of the user know about this sign-in
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_browser_session(&user_session))
+        .await?;
+
+    let (user, cookie_jar) =
+        remember_locale(&mut repo, &locale, user_session.user, cookie_jar).await?;
+    user_session.user = user;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &user_session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&user_session);
+    let reply = action.go_next(&url_builder);
+    Ok((cookie_jar, reply).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_storage::{user::UserEmailRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_link(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.email_login_links = true;
+            state
+        };
+        let cookies = CookieHelper::new();
+
+        let user = state.create_user("john", "hunter2").await;
+        let mut repo = state.repository().await.unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "john@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.user_email()
+            .mark_as_verified(&state.clock, user_email)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/login/link?kind=change_password").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // An unknown address gets the same answer, but no link
+        let request = Request::post("/login/link?kind=change_password").form(serde_json::json!({
+            "csrf": csrf_token,
+            "email": "alice@example.com",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_login_links")
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        let request = Request::post("/login/link?kind=change_password").form(serde_json::json!({
+            "csrf": csrf_token,
+            "email": "John@Example.com",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        let token: String = sqlx::query_scalar("SELECT token FROM user_login_links")
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+
+        // Opening the link only asks for confirmation
+        let request = Request::get(format!("/login/link/{token}")).empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("john"));
+        let csrf_token = response.csrf_token().to_owned();
+
+        // Confirming signs in, and resumes where the user was
+        let request = Request::post(format!("/login/link/{token}")).form(serde_json::json!({
+            "csrf": csrf_token,
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/change-password");
+
+        // The link can't be used again
+        let request = Request::post(format!("/login/link/{token}")).form(serde_json::json!({
+            "csrf": csrf_token,
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("Link expired"));
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use axum::{
-    extract::{Form, State},
+    extract::{Form, Query, State},
     response::IntoResponse,
 };
 use mas_axum_utils::{
@@ -22,9 +22,15 @@ use mas_axum_utils::{
     FancyError, SessionInfoExt,
 };
 use mas_router::{PostAuthAction, UrlBuilder};
-use mas_storage::{user::BrowserSessionRepository, BoxClock, BoxRepository};
+use mas_storage::{
+    job::{JobRepositoryExt, SendBackchannelLogoutJob},
+    oauth2::OAuth2SessionFilter,
+    user::BrowserSessionRepository,
+    BoxClock, BoxRepository, Pagination,
+};
 
-use crate::BoundActivityTracker;
+use super::shared::NextUrl;
+use crate::{BoundActivityTracker, SiteConfig};
 
 #[tracing::instrument(name = "handlers.views.logout.post", skip_all, err)]
 pub(crate) async fn post(
@@ -32,7 +38,9 @@ pub(crate) async fn post(
     mut repo: BoxRepository,
     cookie_jar: CookieJar,
     State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
     activity_tracker: BoundActivityTracker,
+    Query(next): Query<NextUrl>,
     Form(form): Form<ProtectedForm<Option<PostAuthAction>>>,
 ) -> Result<impl IntoResponse, FancyError> {
     let form = cookie_jar.verify_form(&clock, form)?;
@@ -46,6 +54,26 @@ pub(crate) async fn post(
             .record_browser_session(&clock, &session)
             .await;
 
+        // Notify the clients which had sessions attached to this browser session
+        let filter = OAuth2SessionFilter::new()
+            .for_browser_session(&session)
+            .active_only();
+        let mut pagination = Pagination::first(100);
+        loop {
+            let page = repo.oauth2_session().list(filter, pagination).await?;
+
+            for oauth2_session in &page.edges {
+                repo.job()
+                    .schedule_job(SendBackchannelLogoutJob::new(oauth2_session))
+                    .await?;
+            }
+
+            match page.edges.last() {
+                Some(last) if page.has_next_page => pagination = pagination.after(last.id),
+                _ => break,
+            }
+        }
+
         repo.browser_session().finish(&clock, session).await?;
         cookie_jar = cookie_jar.update_session_info(&session_info.mark_session_ended());
     }
@@ -54,6 +82,8 @@ pub(crate) async fn post(
 
     let destination = if let Some(action) = form {
         action.go_next(&url_builder)
+    } else if let Some(reply) = next.redirect(&site_config) {
+        reply
     } else {
         url_builder.redirect(&mas_router::Login::default())
     };
@@ -14,9 +14,17 @@
 
 pub mod account;
 pub mod app;
+pub mod csrf;
+pub mod dev_mailbox;
 pub mod index;
 pub mod login;
+pub mod login_link;
 pub mod logout;
 pub mod reauth;
+pub mod recovery;
+pub mod recovery_code_login;
 pub mod register;
 pub mod shared;
+pub mod terms;
+#[cfg(feature = "webauthn")]
+pub mod webauthn;
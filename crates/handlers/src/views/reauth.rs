@@ -14,21 +14,24 @@
 
 use anyhow::Context;
 use axum::{
-    extract::{Form, Query, State},
+    extract::{Query, State},
     response::{Html, IntoResponse, Response},
 };
 use hyper::StatusCode;
 use mas_axum_utils::{
     cookies::CookieJar,
     csrf::{CsrfExt, ProtectedForm},
+    form_or_json::{form_errors, json_redirect, FormOrJson},
     FancyError, SessionInfoExt,
 };
 use mas_router::UrlBuilder;
 use mas_storage::{
-    user::{BrowserSessionRepository, UserPasswordRepository},
+    user::{BrowserSessionRepository, UserPasswordRepository, WebauthnCredentialRepository},
     BoxClock, BoxRepository, BoxRng,
 };
-use mas_templates::{ReauthContext, TemplateContext, Templates};
+use mas_templates::{
+    FormError, FormState, ReauthContext, ReauthFormField, TemplateContext, Templates,
+};
 use serde::Deserialize;
 use zeroize::Zeroizing;
 
@@ -53,13 +56,6 @@ pub(crate) async fn get(
     Query(query): Query<OptionalPostAuthAction>,
     cookie_jar: CookieJar,
 ) -> Result<Response, FancyError> {
-    if !password_manager.is_enabled() {
-        // XXX: do something better here
-        return Ok(url_builder
-            .redirect(&mas_router::Account::default())
-            .into_response());
-    }
-
     let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
@@ -72,11 +68,31 @@ pub(crate) async fn get(
         return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
     };
 
+    // The user can reauthenticate either with their password or with one of
+    // their WebAuthn credentials, if passkeys are part of this build
+    let webauthn = cfg!(feature = "webauthn")
+        && !repo
+            .webauthn_credential()
+            .all(&session.user)
+            .await?
+            .is_empty();
+
+    if !password_manager.is_enabled() && !webauthn {
+        // XXX: do something better here
+        return Ok((
+            cookie_jar,
+            url_builder.redirect(&mas_router::Account::default()),
+        )
+            .into_response());
+    }
+
     activity_tracker
         .record_browser_session(&clock, &session)
         .await;
 
-    let ctx = ReauthContext::default();
+    let ctx = ReauthContext::default()
+        .with_password_login(password_manager.is_enabled())
+        .with_webauthn(webauthn);
     let next = query.load_context(&mut repo).await?;
     let ctx = if let Some(next) = next {
         ctx.with_post_action(next)
@@ -102,14 +118,16 @@ pub(crate) async fn post(
     mut repo: BoxRepository,
     Query(query): Query<OptionalPostAuthAction>,
     cookie_jar: CookieJar,
-    Form(form): Form<ProtectedForm<ReauthForm>>,
+    form: FormOrJson<ProtectedForm<ReauthForm>>,
 ) -> Result<Response, FancyError> {
     if !password_manager.is_enabled() {
         // XXX: do something better here
         return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
     }
 
-    let form = cookie_jar.verify_form(&clock, form)?;
+    // The frontend submits the form as JSON, and expects JSON replies
+    let json = form.is_json();
+    let form = cookie_jar.verify_form(&clock, form.into_inner())?;
 
     let (session_info, cookie_jar) = cookie_jar.session_info();
 
@@ -140,7 +158,22 @@ pub(crate) async fn post(
             password,
             user_password.hashed_password.clone(),
         )
-        .await?;
+        .await;
+
+    let new_password_hash = match new_password_hash {
+        Ok(new_password_hash) => new_password_hash,
+        Err(_) if json => {
+            let state = FormState::<ReauthFormField>::default()
+                .with_error_on_form(FormError::InvalidCredentials);
+            return Ok((cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // TODO: display a nice error, and send a new recovery link
+    if session.user.password_reset_required() {
+        return Err(anyhow::anyhow!("Password reset required").into());
+    }
 
     let user_password = if let Some((version, new_password_hash)) = new_password_hash {
         // Save the upgraded password
@@ -167,5 +200,9 @@ pub(crate) async fn post(
     repo.save().await?;
 
     let reply = query.go_next(&url_builder);
+    if json {
+        return Ok((cookie_jar, json_redirect(reply)).into_response());
+    }
+
     Ok((cookie_jar, reply).into_response())
 }
@@ -0,0 +1,259 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, Path, State},
+    response::{Html, IntoResponse, Response},
+    TypedHeader,
+};
+use headers::UserAgent;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{User, UserRecoveryTicket};
+use mas_i18n::DataLocale;
+use mas_policy::Policy;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{EndUserSessionsJob, JobRepositoryExt, NotifyNewSignInJob},
+    user::{
+        BrowserSessionRepository, UserPasswordRepository, UserRecoveryRepository, UserRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
+};
+use mas_templates::{
+    EmptyContext, FieldError, FormError, RecoveryFinishContext, RecoveryFinishFormField,
+    TemplateContext, Templates, ToFormState,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    passwords::PasswordManager, BoundActivityTracker, HttpClientFactory, PreferredLanguage,
+    SiteConfig,
+};
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RecoveryFinishForm {
+    new_password: String,
+    new_password_confirm: String,
+}
+
+impl ToFormState for RecoveryFinishForm {
+    type Field = RecoveryFinishFormField;
+}
+
+/// Lookup a ticket which can still be used, along with the user it belongs to
+async fn load_ticket(
+    repo: &mut BoxRepository,
+    clock: &impl Clock,
+    ticket: &str,
+) -> Result<Option<(UserRecoveryTicket, User)>, FancyError> {
+    let Some(ticket) = repo.user_recovery().find_ticket(ticket).await? else {
+        return Ok(None);
+    };
+
+    if !ticket.is_valid(clock.now()) {
+        return Ok(None);
+    }
+
+    let Some(user) = repo
+        .user()
+        .lookup(ticket.user_id)
+        .await?
+        .filter(User::is_valid)
+        .filter(User::can_login_interactively)
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some((ticket, user)))
+}
+
+fn render_expired(
+    locale: DataLocale,
+    templates: &Templates,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let ctx = EmptyContext.with_language(locale);
+    let content = templates.render_recovery_expired(&ctx)?;
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_finish.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(password_manager): State<PasswordManager>,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    Path(ticket): Path<String>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    if !password_manager.is_enabled() {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    let Some((_ticket, user)) = load_ticket(&mut repo, &clock, &ticket).await? else {
+        return render_expired(locale, &templates, cookie_jar);
+    };
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let ctx = RecoveryFinishContext::new(user)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_recovery_finish(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_finish.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(password_manager): State<PasswordManager>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(http_client_factory): State<HttpClientFactory>,
+    activity_tracker: BoundActivityTracker,
+    mut policy: Policy,
+    mut repo: BoxRepository,
+    Path(ticket): Path<String>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Form(form): Form<ProtectedForm<RecoveryFinishForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    if !password_manager.is_enabled() {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let Some((ticket, user)) = load_ticket(&mut repo, &clock, &ticket).await? else {
+        return render_expired(locale, &templates, cookie_jar);
+    };
+
+    // Validate the form
+    let state = {
+        let mut state = form.to_form_state();
+
+        if form.new_password.is_empty() {
+            state.add_error_on_field(RecoveryFinishFormField::NewPassword, FieldError::Required);
+        }
+
+        if form.new_password_confirm.is_empty() {
+            state.add_error_on_field(
+                RecoveryFinishFormField::NewPasswordConfirm,
+                FieldError::Required,
+            );
+        }
+
+        if form.new_password != form.new_password_confirm {
+            state.add_error_on_form(FormError::PasswordMismatch);
+        }
+
+        let res = policy.evaluate_password(&form.new_password).await?;
+        if !res.valid() {
+            state.add_error_on_field(
+                RecoveryFinishFormField::NewPassword,
+                FieldError::Policy {
+                    message: res.to_string(),
+                },
+            );
+        }
+
+        if let Some(check) = &site_config.breached_password_check {
+            if state.is_valid()
+                && check
+                    .should_reject(&http_client_factory, &form.new_password)
+                    .await
+            {
+                state
+                    .add_error_on_field(RecoveryFinishFormField::NewPassword, FieldError::Breached);
+            }
+        }
+
+        state
+    };
+
+    if !state.is_valid() {
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let ctx = RecoveryFinishContext::new(user)
+            .with_form_state(state)
+            .with_csrf(csrf_token.form_value())
+            .with_language(locale);
+
+        let content = templates.render_recovery_finish(&ctx)?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    let new_password = Zeroizing::new(form.new_password.into_bytes());
+    let (version, hashed_password) = password_manager.hash(&mut rng, new_password).await?;
+    let user_password = repo
+        .user_password()
+        .add(&mut rng, &clock, &user, version, hashed_password, None)
+        .await?;
+
+    repo.user_recovery().consume_ticket(&clock, ticket).await?;
+    let user = repo.user().clear_password_reset(user).await?;
+
+    // Resetting the password through the email is the way out of a lockout
+    if let Some(lockout) = &site_config.login_lockout {
+        lockout.reset(&mut repo, &user).await?;
+    }
+
+    // Sign the user in with their new password
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_password(&mut rng, &clock, &session, &user_password)
+        .await?;
+
+    // Sign the user out everywhere else, as whoever knew the old password
+    // might still be signed in
+    repo.job()
+        .schedule_job(EndUserSessionsJob::new(&user).keep_sessions_since(session.created_at))
+        .await?;
+
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_browser_session(&session))
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&session);
+    let reply = url_builder.redirect(&mas_router::Account::default());
+    Ok((cookie_jar, reply).into_response())
+}
@@ -0,0 +1,402 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, State},
+    response::{Html, IntoResponse, Response},
+};
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError,
+};
+use mas_data_model::User;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{JobRepositoryExt, SendPasswordResetEmailJob},
+    user::{UserEmailFilter, UserEmailRepository, UserRepository},
+    BoxClock, BoxRepository, BoxRng, Clock, Pagination,
+};
+use mas_templates::{
+    FieldError, FormError, RecoveryStartContext, RecoveryStartFormField, TemplateContext,
+    Templates, ToFormState,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{passwords::PasswordManager, BoundActivityTracker, PreferredLanguage, SiteConfig};
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RecoveryStartForm {
+    email: String,
+}
+
+impl ToFormState for RecoveryStartForm {
+    type Field = RecoveryStartFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_start.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(password_manager): State<PasswordManager>,
+    State(url_builder): State<UrlBuilder>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    if !password_manager.is_enabled() {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let ctx = RecoveryStartContext::default()
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_recovery_start(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_start.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(password_manager): State<PasswordManager>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<RecoveryStartForm>>,
+) -> Result<Response, FancyError> {
+    if !password_manager.is_enabled() {
+        return Ok(url_builder
+            .redirect(&mas_router::Login::default())
+            .into_response());
+    }
+
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    // Validate the form
+    let mut state = form.to_form_state();
+
+    let email = site_config.email_normalization.normalize(&form.email);
+    if email.is_empty() {
+        state.add_error_on_field(RecoveryStartFormField::Email, FieldError::Required);
+    }
+
+    if !state.is_valid() {
+        let ctx = RecoveryStartContext::default()
+            .with_form_state(state)
+            .with_csrf(csrf_token.form_value())
+            .with_language(locale);
+
+        let content = templates.render_recovery_start(&ctx)?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    // Each request sends an email, so they share the rate limit of the
+    // password login per IP address, and the email throttle per address
+    let ip_check = match activity_tracker.ip() {
+        Some(ip) => site_config
+            .rate_limiter
+            .check(
+                &clock,
+                &format!("recovery:ip:{ip}"),
+                site_config.login_rate_limit,
+            )
+            .await
+            .map_err(|e| (e.retry_after(clock.now()), FormError::RateLimitExceeded)),
+        None => Ok(()),
+    };
+
+    let check = match ip_check {
+        Ok(()) => site_config
+            .email_throttle
+            .check(&clock, None, &email)
+            .await
+            .map_err(|e| {
+                let retry_after = e.retry_after(clock.now());
+                (retry_after, FormError::EmailCooldown { retry_after })
+            }),
+        Err(e) => Err(e),
+    };
+
+    if let Err((retry_after, error)) = check {
+        let state = state.with_error_on_form(error);
+        let ctx = RecoveryStartContext::default()
+            .with_form_state(state)
+            .with_csrf(csrf_token.form_value())
+            .with_language(locale);
+
+        let content = templates.render_recovery_start(&ctx)?;
+
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.to_string())],
+            cookie_jar,
+            Html(content),
+        )
+            .into_response());
+    }
+
+    // A verified email address belongs to at most one user
+    let filter = UserEmailFilter::new().for_email(&email).verified_only();
+    let user_email = repo
+        .user_email()
+        .list(filter, Pagination::first(1))
+        .await?
+        .edges
+        .into_iter()
+        .next();
+
+    let user = if let Some(user_email) = &user_email {
+        repo.user()
+            .lookup(user_email.user_id)
+            .await?
+            .filter(User::is_valid)
+            .filter(User::can_login_interactively)
+    } else {
+        None
+    };
+
+    // The same page is shown whether the address belongs to an account or
+    // not, so that it can't be used to find out who has an account
+    if let (Some(user_email), Some(_user)) = (user_email, user) {
+        repo.job()
+            .schedule_job(
+                SendPasswordResetEmailJob::requested_for(&user_email)
+                    .with_language(locale.to_string()),
+            )
+            .await?;
+
+        repo.save().await?;
+    } else {
+        tracing::info!("No user found for this email address, not sending a recovery link");
+    }
+
+    let ctx = RecoveryStartContext::default()
+        .with_form_state(state)
+        .sent()
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_recovery_start(&ctx)?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_storage::{
+        user::{UserEmailRepository, UserRecoveryRepository, UserRepository},
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    async fn count_jobs(state: &TestState, job_type: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM apalis.jobs WHERE job_type = $1")
+            .bind(job_type)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_recovery(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        let user = state.create_user("john", "hunter2").await;
+        let mut repo = state.repository().await.unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "john@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.user_email()
+            .mark_as_verified(&state.clock, user_email)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/recover").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // An unknown address gets the same answer, but no email
+        let request = Request::post("/recover").form(serde_json::json!({
+            "csrf": csrf_token,
+            "email": "alice@example.com",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+        assert_eq!(count_jobs(&state, "send-password-reset-email").await, 0);
+
+        let request = Request::post("/recover").form(serde_json::json!({
+            "csrf": csrf_token,
+            "email": "John@Example.com",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert_eq!(count_jobs(&state, "send-password-reset-email").await, 1);
+
+        // The ticket itself is created when the email is sent
+        let mut repo = state.repository().await.unwrap();
+        repo.user_recovery()
+            .add_ticket(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "secret-ticket".to_owned(),
+                Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/recover/secret-ticket").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        let request = Request::post("/recover/secret-ticket").form(serde_json::json!({
+            "csrf": csrf_token,
+            "new_password": "correct horse battery staple",
+            "new_password_confirm": "correct horse battery staple",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/account/");
+
+        // All the other sessions get signed out
+        assert_eq!(count_jobs(&state, "end-user-sessions").await, 1);
+
+        // The ticket can't be used again
+        let request = Request::post("/recover/secret-ticket").form(serde_json::json!({
+            "csrf": csrf_token,
+            "new_password": "hunter3",
+            "new_password_confirm": "hunter3",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("Link expired"));
+    }
+
+    /// Test that service accounts can't recover their account by email, as
+    /// they can't sign in interactively anyway
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_recovery_service_account(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        let mut repo = state.repository().await.unwrap();
+        let user = repo
+            .user()
+            .add(&mut state.rng(), &state.clock, "bot".to_owned())
+            .await
+            .unwrap();
+        let user = repo.user().set_service_account(user, true).await.unwrap();
+        let user_email = repo
+            .user_email()
+            .add(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "bot@example.com".to_owned(),
+            )
+            .await
+            .unwrap();
+        repo.user_email()
+            .mark_as_verified(&state.clock, user_email)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/recover").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // The answer is the same as for any other address, but no email is sent
+        let request = Request::post("/recover").form(serde_json::json!({
+            "csrf": csrf_token,
+            "email": "bot@example.com",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert_eq!(count_jobs(&state, "send-password-reset-email").await, 0);
+
+        // A ticket issued before the account became a service account can't
+        // be used either
+        let mut repo = state.repository().await.unwrap();
+        repo.user_recovery()
+            .add_ticket(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                "bot-ticket".to_owned(),
+                Duration::hours(1),
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/recover/bot-ticket").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("Link expired"));
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sign in with a one-time recovery code, as a fallback for users who lost
+//! access to their passkeys
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+    TypedHeader,
+};
+use headers::UserAgent;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{User, UserRecoveryCode};
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{JobRepositoryExt, NotifyNewSignInJob},
+    user::{BrowserSessionRepository, UserRecoveryCodeRepository, UserRepository},
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess,
+};
+use mas_templates::{
+    FieldError, FormError, RecoveryCodeLoginContext, RecoveryCodeLoginFormField, TemplateContext,
+    Templates, ToFormState,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    login::go_next,
+    shared::{NextUrl, OptionalPostAuthAction},
+};
+use crate::{
+    preferred_language::remember_locale, BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct RecoveryCodeLoginForm {
+    username: String,
+    code: String,
+}
+
+impl ToFormState for RecoveryCodeLoginForm {
+    type Field = RecoveryCodeLoginFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_code_login.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    if let Some(session) = maybe_session {
+        activity_tracker
+            .record_browser_session(&clock, &session)
+            .await;
+
+        let reply = go_next(&query, &next, &url_builder, &site_config);
+        return Ok((cookie_jar, reply).into_response());
+    };
+
+    let content = render(
+        locale,
+        RecoveryCodeLoginContext::default(),
+        query,
+        csrf_token,
+        &mut repo,
+        &templates,
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.recovery_code_login.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Form(form): Form<ProtectedForm<RecoveryCodeLoginForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    // Validate the form
+    let mut state = form.to_form_state();
+
+    if form.username.is_empty() {
+        state.add_error_on_field(RecoveryCodeLoginFormField::Username, FieldError::Required);
+    }
+
+    if form.code.trim().is_empty() {
+        state.add_error_on_field(RecoveryCodeLoginFormField::Code, FieldError::Required);
+    }
+
+    if !state.is_valid() {
+        let content = render(
+            locale,
+            RecoveryCodeLoginContext::default().with_form_state(state),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    // Recovery codes share the rate limit of the password login, as they can
+    // be guessed the same way
+    if let Some(ip) = activity_tracker.ip() {
+        if let Err(e) = site_config
+            .rate_limiter
+            .check(
+                &clock,
+                &format!("login:ip:{ip}"),
+                site_config.login_rate_limit,
+            )
+            .await
+        {
+            let state = state.with_error_on_form(FormError::RateLimitExceeded);
+            let content = render(
+                locale,
+                RecoveryCodeLoginContext::default().with_form_state(state),
+                query,
+                csrf_token,
+                &mut repo,
+                &templates,
+            )
+            .await?;
+
+            let retry_after = e.retry_after(clock.now()).to_string();
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after)],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+    }
+
+    let user = repo
+        .user()
+        .find_by_username(&form.username)
+        .await?
+        .filter(User::is_valid)
+        .filter(User::can_login_interactively);
+
+    // Consuming the code makes sure it can't be used again
+    let code = if let Some(user) = &user {
+        repo.user_recovery_code()
+            .consume(&clock, user, &UserRecoveryCode::hash(&form.code))
+            .await?
+    } else {
+        None
+    };
+
+    let (Some(user), Some(code)) = (user, code) else {
+        let state = state.with_error_on_form(FormError::InvalidCredentials);
+        let content = render(
+            locale,
+            RecoveryCodeLoginContext::default().with_form_state(state),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    // Start a new session, authenticated by the recovery code
+    let mut user_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_recovery_code(&mut rng, &clock, &user_session, &code)
+        .await?;
+
+    // Let the other sessions of the user know about this sign-in
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_browser_session(&user_session))
+        .await?;
+
+    let (user, cookie_jar) =
+        remember_locale(&mut repo, &locale, user_session.user, cookie_jar).await?;
+    user_session.user = user;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &user_session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&user_session);
+    let reply = go_next(&query, &next, &url_builder, &site_config);
+    Ok((cookie_jar, reply).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: RecoveryCodeLoginContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_recovery_code_login(&ctx)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{Request, StatusCode};
+    use mas_data_model::UserRecoveryCode;
+    use mas_storage::{user::UserRecoveryCodeRepository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_recovery_code_login(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+
+        let user = state.create_user("john", "hunter2").await;
+
+        let mut repo = state.repository().await.unwrap();
+        repo.user_recovery_code()
+            .replace(
+                &mut state.rng(),
+                &state.clock,
+                &user,
+                vec![UserRecoveryCode::hash("aaaa-bbbb-cccc")],
+            )
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        let request = Request::get("/login/recovery-code").empty();
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // A wrong code doesn't log in
+        let request = Request::post("/login/recovery-code").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "code": "dddd-eeee-ffff",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // The right one does, even typed differently
+        let request = Request::post("/login/recovery-code").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+            "code": "AAAABBBBCCCC",
+        }));
+        let request = cookies.with_cookies(request);
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        let mut repo = state.repository().await.unwrap();
+        assert_eq!(
+            repo.user_recovery_code()
+                .count_remaining(&user)
+                .await
+                .unwrap(),
+            0
+        );
+        repo.cancel().await.unwrap();
+    }
+}
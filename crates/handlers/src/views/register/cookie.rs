@@ -0,0 +1,102 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use mas_axum_utils::cookies::CookieJar;
+use mas_data_model::UserRegistration;
+use mas_router::PostAuthAction;
+use mas_storage::Clock;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use super::REGISTRATION_MAX_AGE_SECS;
+
+/// Name of the cookie
+static COOKIE_NAME: &str = "user-registration";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Payload {
+    registration: Ulid,
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Payload {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        let Ok(ts) = self.registration.timestamp_ms().try_into() else {
+            return true;
+        };
+        let Some(when) = NaiveDateTime::from_timestamp_millis(ts) else {
+            return true;
+        };
+        let when = Utc.from_utc_datetime(&when);
+        now - when > Duration::seconds(REGISTRATION_MAX_AGE_SECS)
+    }
+}
+
+/// Remembers the registrations in progress, when the email address gets
+/// verified before the account is created.
+///
+/// Registrations are kept by the action the user will continue with once
+/// registered, which is carried in the URL of each page, so that registering
+/// in two tabs to continue different authorization grants doesn't have one
+/// registration replace the other.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct UserRegistrationCookie(Vec<Payload>);
+
+impl UserRegistrationCookie {
+    /// Load the registration cookie
+    pub fn load(cookie_jar: &CookieJar) -> Self {
+        match cookie_jar.load(COOKIE_NAME) {
+            Ok(Some(registrations)) => registrations,
+            Ok(None) => Self::default(),
+            Err(e) => {
+                tracing::warn!("Invalid user registration cookie: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the registration cookie in the cookie jar, forgetting about the
+    /// expired registrations
+    pub fn save<C: Clock>(mut self, cookie_jar: CookieJar, clock: &C) -> CookieJar {
+        let now = clock.now();
+        self.0.retain(|payload| !payload.expired(now));
+        cookie_jar.save(COOKIE_NAME, &self, false)
+    }
+
+    /// Remember a new registration, replacing the one previously started for
+    /// the same action
+    #[must_use]
+    pub fn add(
+        mut self,
+        registration: &UserRegistration,
+        post_auth_action: Option<&PostAuthAction>,
+    ) -> Self {
+        self.0
+            .retain(|payload| payload.post_auth_action.as_ref() != post_auth_action);
+        self.0.push(Payload {
+            registration: registration.id,
+            post_auth_action: post_auth_action.cloned(),
+        });
+        self
+    }
+
+    /// The ID of the registration in progress for the given action
+    pub fn registration_id(&self, post_auth_action: Option<&PostAuthAction>) -> Option<Ulid> {
+        self.0
+            .iter()
+            .find(|payload| payload.post_auth_action.as_ref() == post_auth_action)
+            .map(|payload| payload.registration)
+    }
+}
@@ -0,0 +1,892 @@
+// Copyright 2021, 2022 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Response},
+    TypedHeader,
+};
+use chrono::Duration;
+use headers::UserAgent;
+use hyper::{header::RETRY_AFTER, StatusCode};
+use lettre::Address;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    form_or_json::{form_errors, json_redirect, FormOrJson},
+    http_client_factory::HttpClientFactory,
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::UserRegistration;
+use mas_i18n::DataLocale;
+use mas_policy::{Policy, Violation};
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_storage::{
+    job::{JobRepositoryExt, ProvisionUserJob, SendRegistrationCodeJob, VerifyEmailJob},
+    user::{
+        BrowserSessionRepository, UserEmailFilter, UserEmailRepository, UserPasswordRepository,
+        UserRegistrationRepository, UserRepository, UserTermsRepository,
+    },
+    BoxClock, BoxRepository, BoxRng, Clock, RepositoryAccess, RepositoryError,
+};
+use mas_templates::{
+    FieldError, FormError, FormState, RegisterContext, RegisterFormField, TemplateContext,
+    Templates, ToFormState,
+};
+use rand::{distributions::Uniform, Rng};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use self::cookie::UserRegistrationCookie;
+use super::shared::OptionalPostAuthAction;
+use crate::{
+    captcha::CaptchaForm, passwords::PasswordManager, preferred_language::remember_locale,
+    BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
+
+mod cookie;
+pub(crate) mod verify;
+
+/// How long the user has to verify their email address and finish the
+/// registration, when the email gets verified first
+static REGISTRATION_MAX_AGE_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct RegisterForm {
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    password_confirm: String,
+    #[serde(default)]
+    accept_terms: Option<String>,
+
+    #[serde(flatten, skip_serializing)]
+    captcha: CaptchaForm,
+}
+
+impl ToFormState for RegisterForm {
+    type Field = RegisterFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.register.get", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(password_manager): State<PasswordManager>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    if maybe_session.is_some() {
+        let reply = query.go_next(&url_builder);
+        return Ok((cookie_jar, reply).into_response());
+    }
+
+    if !password_manager.is_enabled() {
+        // If password-based login is disabled, redirect to the login page here
+        return Ok(url_builder
+            .redirect(&mas_router::Login::from(query.post_auth_action))
+            .into_response());
+    }
+
+    let ctx = if site_config.verify_email_before_registration {
+        match load_registration(
+            &clock,
+            &mut repo,
+            &cookie_jar,
+            query.post_auth_action.as_ref(),
+        )
+        .await?
+        {
+            Some(registration) if registration.is_verified() => {
+                RegisterContext::default().with_verified_email(registration.email)
+            }
+            _ => RegisterContext::default().with_email_only(),
+        }
+    } else {
+        RegisterContext::default()
+    };
+    let ctx = ctx
+        .with_captcha(site_config.captcha.clone())
+        .with_terms(site_config.terms.clone());
+
+    let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.register.post", skip_all, err)]
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(password_manager): State<PasswordManager>,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    State(http_client_factory): State<HttpClientFactory>,
+    mut policy: Policy,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    form: FormOrJson<ProtectedForm<RegisterForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    if !password_manager.is_enabled() {
+        return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response());
+    }
+
+    // The frontend submits the form as JSON, and expects JSON replies
+    let json = form.is_json();
+    let form = cookie_jar.verify_form(&clock, form.into_inner())?;
+
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let registration = if site_config.verify_email_before_registration {
+        load_registration(
+            &clock,
+            &mut repo,
+            &cookie_jar,
+            query.post_auth_action.as_ref(),
+        )
+        .await?
+        .filter(UserRegistration::is_verified)
+    } else {
+        None
+    };
+
+    // Limit the number of attempts from a single IP address
+    if let Some(ip) = activity_tracker.ip() {
+        if let Err(e) = site_config
+            .rate_limiter
+            .check(
+                &clock,
+                &format!("registration:ip:{ip}"),
+                site_config.registration_rate_limit,
+            )
+            .await
+        {
+            let state = form
+                .to_form_state()
+                .with_error_on_form(FormError::RateLimitExceeded);
+            let retry_after = e.retry_after(clock.now()).to_string();
+            if json {
+                return Ok((
+                    [(RETRY_AFTER, retry_after)],
+                    cookie_jar,
+                    form_errors(StatusCode::TOO_MANY_REQUESTS, &state),
+                )
+                    .into_response());
+            }
+
+            let ctx = RegisterContext::default()
+                .with_form_state(state)
+                .with_terms(site_config.terms.clone());
+            let ctx = match &registration {
+                Some(registration) => ctx.with_verified_email(registration.email.clone()),
+                None if site_config.verify_email_before_registration => ctx
+                    .with_email_only()
+                    .with_captcha(site_config.captcha.clone()),
+                None => ctx.with_captcha(site_config.captcha.clone()),
+            };
+            let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+            return Ok((
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after)],
+                cookie_jar,
+                Html(content),
+            )
+                .into_response());
+        }
+    }
+
+    if site_config.verify_email_before_registration && registration.is_none() {
+        // This is the first step of the registration, which only asks for
+        // the email address and sends a code to it
+        let email = site_config.email_normalization.normalize(&form.email);
+        let mut state = form.to_form_state();
+        validate_email(&mut state, &email);
+        check_email_in_use(&mut state, &mut repo, &email).await?;
+        verify_captcha(
+            &mut state,
+            &form.captcha,
+            &http_client_factory,
+            &activity_tracker,
+            &site_config,
+        )
+        .await;
+
+        if state.is_valid() {
+            let res = policy.evaluate_register("", "", &email).await?;
+            // Only the email address was submitted, so only look at the
+            // violations on it
+            add_policy_violations(
+                &mut state,
+                res.violations
+                    .into_iter()
+                    .filter(|violation| violation.field.as_deref() == Some("email")),
+            );
+        }
+
+        if !state.is_valid() {
+            if json {
+                return Ok(
+                    (cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response()
+                );
+            }
+
+            let ctx = RegisterContext::default()
+                .with_email_only()
+                .with_captcha(site_config.captcha.clone())
+                .with_form_state(state);
+            let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+            return Ok((cookie_jar, Html(content)).into_response());
+        }
+
+        let range = Uniform::<u32>::from(0..1_000_000);
+        let code = format!("{:06}", rng.sample(range));
+
+        let registration = repo
+            .user_registration()
+            .add(
+                &mut rng,
+                &clock,
+                email,
+                code,
+                Duration::seconds(REGISTRATION_MAX_AGE_SECS),
+            )
+            .await?;
+
+        repo.job()
+            .schedule_job(
+                SendRegistrationCodeJob::new(&registration).with_language(locale.to_string()),
+            )
+            .await?;
+
+        repo.save().await?;
+
+        let cookie_jar = UserRegistrationCookie::load(&cookie_jar)
+            .add(&registration, query.post_auth_action.as_ref())
+            .save(cookie_jar, &clock);
+        let next = mas_router::RegisterVerifyEmail::default().and_maybe(query.post_auth_action);
+        let next = url_builder.redirect(&next);
+        if json {
+            return Ok((cookie_jar, json_redirect(next)).into_response());
+        }
+
+        return Ok((cookie_jar, next).into_response());
+    }
+
+    // If the email address was already verified, use it instead of the one in
+    // the form
+    let email = match &registration {
+        Some(registration) => registration.email.clone(),
+        None => site_config.email_normalization.normalize(&form.email),
+    };
+
+    // Validate the form
+    let state = {
+        let mut state = form.to_form_state();
+
+        if form.username.is_empty() {
+            state.add_error_on_field(RegisterFormField::Username, FieldError::Required);
+        } else if repo.user().exists(&form.username).await? {
+            state.add_error_on_field(RegisterFormField::Username, FieldError::Exists);
+        }
+
+        validate_email(&mut state, &email);
+        check_email_in_use(&mut state, &mut repo, &email).await?;
+
+        if form.password.is_empty() {
+            state.add_error_on_field(RegisterFormField::Password, FieldError::Required);
+        }
+
+        if form.password_confirm.is_empty() {
+            state.add_error_on_field(RegisterFormField::PasswordConfirm, FieldError::Required);
+        }
+
+        if form.password != form.password_confirm {
+            state.add_error_on_form(FormError::PasswordMismatch);
+            state.add_error_on_field(RegisterFormField::Password, FieldError::Unspecified);
+            state.add_error_on_field(RegisterFormField::PasswordConfirm, FieldError::Unspecified);
+        }
+
+        let res = policy
+            .evaluate_register(&form.username, &form.password, &email)
+            .await?;
+
+        add_policy_violations(&mut state, res.violations);
+
+        if site_config.terms.is_some() && form.accept_terms.is_none() {
+            state.add_error_on_field(RegisterFormField::AcceptTerms, FieldError::Required);
+        }
+
+        // The CAPTCHA was already solved in the first step if the email
+        // address was verified before
+        if registration.is_none() {
+            verify_captcha(
+                &mut state,
+                &form.captcha,
+                &http_client_factory,
+                &activity_tracker,
+                &site_config,
+            )
+            .await;
+        }
+
+        // Only call the breached passwords API once everything else is valid
+        if let Some(check) = &site_config.breached_password_check {
+            if state.is_valid()
+                && check
+                    .should_reject(&http_client_factory, &form.password)
+                    .await
+            {
+                state.add_error_on_field(RegisterFormField::Password, FieldError::Breached);
+            }
+        }
+
+        state
+    };
+
+    if !state.is_valid() {
+        if json {
+            return Ok((cookie_jar, form_errors(StatusCode::BAD_REQUEST, &state)).into_response());
+        }
+
+        let ctx = RegisterContext::default()
+            .with_form_state(state)
+            .with_terms(site_config.terms.clone());
+        let ctx = if let Some(registration) = registration {
+            ctx.with_verified_email(registration.email)
+        } else {
+            ctx.with_captcha(site_config.captcha.clone())
+        };
+        let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    let user = repo.user().add(&mut rng, &clock, form.username).await?;
+    // Remember the language the user registered with
+    let (user, cookie_jar) = remember_locale(&mut repo, &locale, user, cookie_jar).await?;
+    let password = Zeroizing::new(form.password.into_bytes());
+    let (version, hashed_password) = password_manager.hash(&mut rng, password).await?;
+    let user_password = repo
+        .user_password()
+        .add(&mut rng, &clock, &user, version, hashed_password, None)
+        .await?;
+
+    let user_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &user, email)
+        .await?;
+
+    if let Some(terms) = &site_config.terms {
+        repo.user_terms()
+            .accept(&mut rng, &clock, &user, terms)
+            .await?;
+    }
+
+    let next = if let Some(registration) = registration {
+        // The email address was verified before the account got created
+        let user_email = repo
+            .user_email()
+            .mark_as_verified(&clock, user_email)
+            .await?;
+        repo.user_email().set_as_primary(&user_email).await?;
+
+        repo.user_registration()
+            .complete(&clock, registration)
+            .await?;
+
+        query.go_next(&url_builder)
+    } else if site_config.skip_email_verification {
+        // The development server trusts the address without sending a code
+        let user_email = repo
+            .user_email()
+            .mark_as_verified(&clock, user_email)
+            .await?;
+        repo.user_email().set_as_primary(&user_email).await?;
+
+        query.go_next(&url_builder)
+    } else {
+        repo.job()
+            .schedule_job(VerifyEmailJob::new(&user_email).with_language(locale.to_string()))
+            .await?;
+
+        let next =
+            mas_router::AccountVerifyEmail::new(user_email.id).and_maybe(query.post_auth_action);
+        url_builder.redirect(&next)
+    };
+
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_password(&mut rng, &clock, &session, &user_password)
+        .await?;
+
+    repo.job()
+        .schedule_job(ProvisionUserJob::new(&user))
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&session);
+    if json {
+        return Ok((cookie_jar, json_redirect(next)).into_response());
+    }
+
+    Ok((cookie_jar, next).into_response())
+}
+
+fn validate_email(state: &mut FormState<RegisterFormField>, email: &str) {
+    if email.is_empty() {
+        state.add_error_on_field(RegisterFormField::Email, FieldError::Required);
+    } else if Address::from_str(email).is_err() {
+        state.add_error_on_field(RegisterFormField::Email, FieldError::Invalid);
+    }
+}
+
+/// A verified email address can only belong to one user
+async fn check_email_in_use(
+    state: &mut FormState<RegisterFormField>,
+    repo: &mut BoxRepository,
+    email: &str,
+) -> Result<(), RepositoryError> {
+    let filter = UserEmailFilter::new().for_email(email).verified_only();
+    if repo.user_email().count(filter).await? > 0 {
+        state.add_error_on_field(RegisterFormField::Email, FieldError::Exists);
+    }
+
+    Ok(())
+}
+
+/// Check the response to the CAPTCHA challenge, if the form is protected by
+/// one
+async fn verify_captcha(
+    state: &mut FormState<RegisterFormField>,
+    captcha: &CaptchaForm,
+    http_client_factory: &HttpClientFactory,
+    activity_tracker: &BoundActivityTracker,
+    site_config: &SiteConfig,
+) {
+    if let Err(e) = captcha
+        .verify(
+            http_client_factory,
+            activity_tracker.ip(),
+            site_config.captcha.as_ref(),
+        )
+        .await
+    {
+        if !e.is_user_error() {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Could not verify the CAPTCHA"
+            );
+        }
+
+        state.add_error_on_form(FormError::Captcha);
+    }
+}
+
+fn add_policy_violations(
+    state: &mut FormState<RegisterFormField>,
+    violations: impl IntoIterator<Item = Violation>,
+) {
+    for violation in violations {
+        match violation.field.as_deref() {
+            Some("email") => state.add_error_on_field(
+                RegisterFormField::Email,
+                FieldError::Policy {
+                    message: violation.msg,
+                },
+            ),
+            Some("username") => state.add_error_on_field(
+                RegisterFormField::Username,
+                FieldError::Policy {
+                    message: violation.msg,
+                },
+            ),
+            Some("password") => state.add_error_on_field(
+                RegisterFormField::Password,
+                FieldError::Policy {
+                    message: violation.msg,
+                },
+            ),
+            _ => state.add_error_on_form(FormError::Policy {
+                message: violation.msg,
+            }),
+        }
+    }
+}
+
+/// Load the registration in progress for the given action from the cookie, if
+/// it is still valid
+async fn load_registration(
+    clock: &impl Clock,
+    repo: &mut BoxRepository,
+    cookie_jar: &CookieJar,
+    post_auth_action: Option<&PostAuthAction>,
+) -> Result<Option<UserRegistration>, FancyError> {
+    let Some(registration_id) =
+        UserRegistrationCookie::load(cookie_jar).registration_id(post_auth_action)
+    else {
+        return Ok(None);
+    };
+
+    let registration = repo
+        .user_registration()
+        .lookup(registration_id)
+        .await?
+        .filter(|registration| registration.is_valid(clock.now()));
+
+    Ok(registration)
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: RegisterContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_register(&ctx)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::LOCATION, Request, StatusCode};
+    use mas_data_model::TermsOfService;
+    use mas_router::Route;
+    use mas_storage::{
+        user::{UserEmailRepository, UserRepository, UserTermsRepository},
+        RepositoryAccess,
+    };
+    use sqlx::PgPool;
+
+    use crate::{
+        passwords::PasswordManager,
+        test_utils::{init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState},
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_password_disabled(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.password_manager = PasswordManager::disabled();
+            state
+        };
+
+        let request = Request::get(&*mas_router::Register::default().path_and_query()).empty();
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/login");
+
+        let request = Request::post(&*mas_router::Register::default().path_and_query()).form(
+            serde_json::json!({
+                "csrf": "abc",
+                "username": "john",
+                "email": "john@example.com",
+                "password": "hunter2",
+                "password_confirm": "hunter2",
+            }),
+        );
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_verify_email_before_registration(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.verify_email_before_registration = true;
+            state
+        };
+        let cookies = CookieHelper::new();
+
+        // The registration form first only asks for the email address
+        let request = cookies.with_cookies(Request::get("/register").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(!response.body().contains("name=\"username\""));
+        let csrf = response.csrf_token().to_owned();
+
+        let request = Request::post("/register").form(serde_json::json!({
+            "csrf": csrf,
+            "email": "john@example.com",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/register/verify");
+
+        // No account exists yet
+        let mut repo = state.repository().await.unwrap();
+        assert!(!repo.user().exists("john").await.unwrap());
+        repo.cancel().await.unwrap();
+
+        let code: String = sqlx::query_scalar("SELECT code FROM user_registrations")
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+
+        let request = cookies.with_cookies(Request::get("/register/verify").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("john@example.com"));
+        let csrf = response.csrf_token().to_owned();
+
+        // A wrong code shows the form again
+        let request = Request::post("/register/verify").form(serde_json::json!({
+            "csrf": csrf,
+            "code": "wrong",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        let request = Request::post("/register/verify").form(serde_json::json!({
+            "csrf": csrf,
+            "code": code,
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/register");
+
+        // Now the rest of the form is shown, with the verified email address
+        let request = cookies.with_cookies(Request::get("/register").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("name=\"username\""));
+        assert!(response.body().contains("john@example.com"));
+        let csrf = response.csrf_token().to_owned();
+
+        let request = Request::post("/register").form(serde_json::json!({
+            "csrf": csrf,
+            "username": "john",
+            "password": "correcthorsebatterystaple",
+            "password_confirm": "correcthorsebatterystaple",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/");
+
+        // The account was created with the email address already verified
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().find_by_username("john").await.unwrap().unwrap();
+        let user_email_id = user.primary_user_email_id.unwrap();
+        let user_email = repo
+            .user_email()
+            .lookup(user_email_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user_email.email, "john@example.com");
+        assert!(user_email.confirmed_at.is_some());
+    }
+
+    /// Registrations started in different tabs, to continue different
+    /// actions, don't replace each other
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_skip_email_verification(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.skip_email_verification = true;
+            state
+        };
+        let cookies = CookieHelper::new();
+
+        let request = cookies.with_cookies(Request::get("/register").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        let csrf = response.csrf_token().to_owned();
+
+        let request = Request::post("/register").form(serde_json::json!({
+            "csrf": csrf,
+            "username": "john",
+            "email": "john@example.com",
+            "password": "correcthorsebatterystaple",
+            "password_confirm": "correcthorsebatterystaple",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+        response.assert_header_value(LOCATION, "/");
+
+        // The email address was marked as verified right away
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().find_by_username("john").await.unwrap().unwrap();
+        let user_email_id = user.primary_user_email_id.unwrap();
+        let user_email = repo
+            .user_email()
+            .lookup(user_email_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user_email.email, "john@example.com");
+        assert!(user_email.confirmed_at.is_some());
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_concurrent_registrations(pool: PgPool) {
+        init_tracing();
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.verify_email_before_registration = true;
+            state
+        };
+        let cookies = CookieHelper::new();
+
+        let request = cookies.with_cookies(Request::get("/register").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        let csrf = response.csrf_token().to_owned();
+
+        for (params, email) in [
+            ("?kind=change_password", "alice@example.com"),
+            ("?kind=manage_emails", "bob@example.com"),
+        ] {
+            let request = Request::post(format!("/register{params}")).form(serde_json::json!({
+                "csrf": csrf,
+                "email": email,
+            }));
+            let response = state.request(cookies.with_cookies(request)).await;
+            cookies.save_cookies(&response);
+            response.assert_status(StatusCode::SEE_OTHER);
+            response.assert_header_value(LOCATION, &format!("/register/verify{params}"));
+        }
+
+        // Each tab still sees its own registration
+        let request =
+            cookies.with_cookies(Request::get("/register/verify?kind=change_password").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("alice@example.com"));
+        assert!(!response.body().contains("bob@example.com"));
+
+        let request =
+            cookies.with_cookies(Request::get("/register/verify?kind=manage_emails").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("bob@example.com"));
+        assert!(!response.body().contains("alice@example.com"));
+
+        // There is nothing to verify without any action
+        let request = cookies.with_cookies(Request::get("/register/verify").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::SEE_OTHER);
+    }
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_accept_terms(pool: PgPool) {
+        init_tracing();
+        let terms = TermsOfService {
+            version: "2023-12".to_owned(),
+            url: "https://example.com/terms".parse().unwrap(),
+        };
+        let state = {
+            let mut state = TestState::from_pool(pool).await.unwrap();
+            state.site_config.terms = Some(terms.clone());
+            state
+        };
+        let cookies = CookieHelper::new();
+
+        let request = cookies.with_cookies(Request::get("/register").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        assert!(response.body().contains("name=\"accept_terms\""));
+        assert!(response.body().contains("https://example.com/terms"));
+        let csrf = response.csrf_token().to_owned();
+
+        // The account isn't created if the terms weren't accepted
+        let request = Request::post("/register").form(serde_json::json!({
+            "csrf": csrf,
+            "username": "john",
+            "email": "john@example.com",
+            "password": "correcthorsebatterystaple",
+            "password_confirm": "correcthorsebatterystaple",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        let mut repo = state.repository().await.unwrap();
+        assert!(!repo.user().exists("john").await.unwrap());
+        repo.cancel().await.unwrap();
+
+        let request = Request::post("/register").form(serde_json::json!({
+            "csrf": csrf,
+            "username": "john",
+            "email": "john@example.com",
+            "password": "correcthorsebatterystaple",
+            "password_confirm": "correcthorsebatterystaple",
+            "accept_terms": "on",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::SEE_OTHER);
+
+        // The acceptance of the current version was recorded
+        let mut repo = state.repository().await.unwrap();
+        let user = repo.user().find_by_username("john").await.unwrap().unwrap();
+        let acceptance = repo.user_terms().find(&user, &terms.version).await.unwrap();
+        assert!(terms.is_accepted(acceptance.as_ref()));
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{
+    user::UserRegistrationRepository, BoxClock, BoxRepository, BoxRng, RepositoryAccess,
+};
+use mas_templates::{
+    EmailVerificationFormField, FieldError, FormState, RegisterVerifyContext, TemplateContext,
+    Templates, ToFormState,
+};
+use serde::{Deserialize, Serialize};
+
+use super::load_registration;
+use crate::{views::shared::OptionalPostAuthAction, PreferredLanguage, SiteConfig};
+
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct CodeForm {
+    code: String,
+}
+
+impl ToFormState for CodeForm {
+    type Field = EmailVerificationFormField;
+}
+
+#[tracing::instrument(name = "handlers.views.register_verify_email.get", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    if maybe_session.is_some() {
+        let reply = query.go_next(&url_builder);
+        return Ok((cookie_jar, reply).into_response());
+    }
+
+    let registration = if site_config.verify_email_before_registration {
+        load_registration(
+            &clock,
+            &mut repo,
+            &cookie_jar,
+            query.post_auth_action.as_ref(),
+        )
+        .await?
+    } else {
+        None
+    };
+
+    // Go back to the registration form if there is nothing to verify
+    let Some(registration) = registration.filter(|r| !r.is_verified()) else {
+        let register = mas_router::Register::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&register)).into_response());
+    };
+
+    let ctx = RegisterVerifyContext::new(registration.email);
+    let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.register_verify_email.post", skip_all, err)]
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<CodeForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+
+    let registration = if site_config.verify_email_before_registration {
+        load_registration(
+            &clock,
+            &mut repo,
+            &cookie_jar,
+            query.post_auth_action.as_ref(),
+        )
+        .await?
+    } else {
+        None
+    };
+
+    let register = mas_router::Register::from(query.post_auth_action.clone());
+
+    let Some(registration) = registration else {
+        return Ok((cookie_jar, url_builder.redirect(&register)).into_response());
+    };
+
+    if registration.is_verified() {
+        return Ok((cookie_jar, url_builder.redirect(&register)).into_response());
+    }
+
+    if form.code.trim() != registration.code {
+        let state = form
+            .to_form_state()
+            .with_error_on_field(EmailVerificationFormField::Code, FieldError::Invalid);
+        let ctx = RegisterVerifyContext::new(registration.email).with_form_state(state);
+        let content = render(locale, ctx, query, csrf_token, &mut repo, &templates).await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    repo.user_registration()
+        .verify(&clock, registration)
+        .await?;
+
+    repo.save().await?;
+
+    Ok((cookie_jar, url_builder.redirect(&register)).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: RegisterVerifyContext,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut impl RepositoryAccess,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx.with_csrf(csrf_token.form_value()).with_language(locale);
+
+    let content = templates.render_register_verify(&ctx)?;
+    Ok(content)
+}
@@ -16,12 +16,15 @@ use anyhow::Context;
 use mas_router::{PostAuthAction, Route, UrlBuilder};
 use mas_storage::{
     compat::CompatSsoLoginRepository,
-    oauth2::OAuth2AuthorizationGrantRepository,
+    oauth2::{OAuth2AuthorizationGrantRepository, OAuth2DeviceCodeGrantRepository},
     upstream_oauth2::{UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository},
     RepositoryAccess,
 };
 use mas_templates::{PostAuthContext, PostAuthContextInner};
 use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::SiteConfig;
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub(crate) struct OptionalPostAuthAction {
@@ -75,6 +78,12 @@ impl OptionalPostAuthAction {
 
             PostAuthAction::ChangePassword => PostAuthContextInner::ChangePassword,
 
+            PostAuthAction::ManageEmails => PostAuthContextInner::ManageEmails,
+
+            PostAuthAction::ManageWebauthn => PostAuthContextInner::ManageWebauthn,
+
+            PostAuthAction::ManageRecoveryCodes => PostAuthContextInner::ManageRecoveryCodes,
+
             PostAuthAction::LinkUpstream { id } => {
                 let link = repo
                     .upstream_oauth_link()
@@ -94,6 +103,16 @@ impl OptionalPostAuthAction {
             }
 
             PostAuthAction::ManageAccount { .. } => PostAuthContextInner::ManageAccount,
+
+            PostAuthAction::ContinueDeviceCodeGrant { id } => {
+                let grant = repo
+                    .oauth2_device_code_grant()
+                    .lookup(id)
+                    .await?
+                    .context("Failed to load device code grant")?;
+                let grant = Box::new(grant);
+                PostAuthContextInner::ContinueDeviceCodeGrant { grant }
+            }
         };
 
         Ok(Some(PostAuthContext {
@@ -102,3 +121,29 @@ impl OptionalPostAuthAction {
         }))
     }
 }
+
+/// The `next` query parameter of the login and logout pages, which sends the
+/// user back to another first-party web application
+#[derive(Deserialize, Default, Debug, Clone)]
+pub(crate) struct NextUrl {
+    next: Option<String>,
+}
+
+impl NextUrl {
+    /// Get a redirection to the `next` URL, if it is set and allowed by the
+    /// site configuration
+    pub fn redirect(&self, site_config: &SiteConfig) -> Option<axum::response::Redirect> {
+        let next = self.next.as_deref()?;
+        let Ok(url) = Url::parse(next) else {
+            tracing::warn!(next, "Invalid next URL");
+            return None;
+        };
+
+        if !site_config.is_next_url_allowed(&url) {
+            tracing::warn!(%url, "Next URL is not in the allow-list");
+            return None;
+        }
+
+        Some(axum::response::Redirect::to(url.as_str()))
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, CsrfToken, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::BrowserSession;
+use mas_i18n::DataLocale;
+use mas_router::UrlBuilder;
+use mas_storage::{user::UserTermsRepository, BoxClock, BoxRepository, BoxRng};
+use mas_templates::{
+    AcceptTermsContext, AcceptTermsFormField, FieldError, FormState, TemplateContext, Templates,
+};
+use serde::Deserialize;
+
+use super::shared::OptionalPostAuthAction;
+use crate::{BoundActivityTracker, PreferredLanguage, SiteConfig};
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct AcceptTermsForm {
+    #[serde(default)]
+    accept_terms: Option<String>,
+}
+
+#[tracing::instrument(name = "handlers.views.terms.get", skip_all, err)]
+pub(crate) async fn get(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+) -> Result<Response, FancyError> {
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        // If there is no session, redirect to the login screen, keeping the
+        // PostAuthAction
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    // Nothing to accept if no terms are configured or if the user already
+    // accepted the current version
+    let Some(terms) = site_config.terms else {
+        return Ok((cookie_jar, query.go_next(&url_builder)).into_response());
+    };
+
+    let acceptance = repo
+        .user_terms()
+        .find(&session.user, &terms.version)
+        .await?;
+    if terms.is_accepted(acceptance.as_ref()) {
+        return Ok((cookie_jar, query.go_next(&url_builder)).into_response());
+    }
+
+    let ctx = AcceptTermsContext::new(terms);
+    let content = render(
+        locale, ctx, session, query, csrf_token, &mut repo, &templates,
+    )
+    .await?;
+
+    Ok((cookie_jar, Html(content)).into_response())
+}
+
+#[tracing::instrument(name = "handlers.views.terms.post", skip_all, err)]
+pub(crate) async fn post(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<AcceptTermsForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        // If there is no session, redirect to the login screen, keeping the
+        // PostAuthAction
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    let Some(terms) = site_config.terms else {
+        return Ok((cookie_jar, query.go_next(&url_builder)).into_response());
+    };
+
+    if form.accept_terms.is_none() {
+        let mut state = FormState::default();
+        state.add_error_on_field(AcceptTermsFormField::AcceptTerms, FieldError::Required);
+
+        let ctx = AcceptTermsContext::new(terms).with_form_state(state);
+        let content = render(
+            locale, ctx, session, query, csrf_token, &mut repo, &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    }
+
+    repo.user_terms()
+        .accept(&mut rng, &clock, &session.user, &terms)
+        .await?;
+
+    repo.save().await?;
+
+    Ok((cookie_jar, query.go_next(&url_builder)).into_response())
+}
+
+async fn render(
+    locale: DataLocale,
+    ctx: AcceptTermsContext,
+    session: BrowserSession,
+    action: OptionalPostAuthAction,
+    csrf_token: CsrfToken,
+    repo: &mut BoxRepository,
+    templates: &Templates,
+) -> Result<String, FancyError> {
+    let next = action.load_context(repo).await?;
+    let ctx = if let Some(next) = next {
+        ctx.with_post_action(next)
+    } else {
+        ctx
+    };
+    let ctx = ctx
+        .with_session(session)
+        .with_csrf(csrf_token.form_value())
+        .with_language(locale);
+
+    let content = templates.render_accept_terms(&ctx)?;
+    Ok(content)
+}
@@ -0,0 +1,388 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Authentication with a WebAuthn credential, e.g. a passkey, on the login and
+//! reauthentication screens
+
+use axum::{
+    extract::{Form, Query, State},
+    response::{Html, IntoResponse, Response},
+    Json, TypedHeader,
+};
+use headers::UserAgent;
+use hyper::StatusCode;
+use mas_axum_utils::{
+    cookies::CookieJar,
+    csrf::{CsrfExt, ProtectedForm},
+    FancyError, SessionInfoExt,
+};
+use mas_data_model::{User, WebauthnCredential};
+use mas_router::UrlBuilder;
+use mas_storage::{
+    job::{JobRepositoryExt, NotifyNewSignInJob},
+    user::{BrowserSessionRepository, UserRepository, WebauthnCredentialRepository},
+    BoxClock, BoxRepository, BoxRng, Clock,
+};
+use mas_templates::{FormError, FormState, LoginContext, Templates};
+use serde::Deserialize;
+use webauthn_rs::prelude::{Passkey, PublicKeyCredential};
+
+use super::{
+    login::{go_next, render},
+    shared::{NextUrl, OptionalPostAuthAction},
+};
+use crate::{
+    passwords::PasswordManager,
+    preferred_language::remember_locale,
+    webauthn::{encode_credential_id, passkeys, relying_party, WebauthnCeremony},
+    BoundActivityTracker, PreferredLanguage, SiteConfig,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct LoginChallengeForm {
+    username: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WebauthnForm {
+    /// The assertion made by the browser, serialized as JSON
+    credential: String,
+}
+
+/// Check the response of the browser against the authentication challenge it
+/// was given, and record the use of the credential.
+///
+/// Returns `None` if the response is invalid, or if the credential doesn't
+/// belong to the user who was challenged.
+async fn verify(
+    url_builder: &UrlBuilder,
+    repo: &mut BoxRepository,
+    clock: &impl Clock,
+    ceremony: Option<WebauthnCeremony>,
+    credential: &str,
+) -> Result<Option<WebauthnCredential>, FancyError> {
+    let Some(WebauthnCeremony::Authentication { user_id, state }) = ceremony else {
+        return Ok(None);
+    };
+
+    let webauthn = relying_party(url_builder)?;
+    let result = serde_json::from_str::<PublicKeyCredential>(credential)
+        .map_err(anyhow::Error::from)
+        .and_then(|credential| Ok(webauthn.finish_passkey_authentication(&credential, &state)?));
+
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to authenticate with a WebAuthn credential");
+            return Ok(None);
+        }
+    };
+
+    let credential_id = encode_credential_id(result.cred_id())?;
+    let Some(credential) = repo
+        .webauthn_credential()
+        .find_by_credential_id(&credential_id)
+        .await?
+        .filter(|credential| credential.user_id == user_id)
+    else {
+        return Ok(None);
+    };
+
+    // Keep track of the signature counter, to detect cloned authenticators
+    let mut passkey: Passkey = serde_json::from_value(credential.passkey.clone())?;
+    passkey.update_credential(&result);
+    let credential = repo
+        .webauthn_credential()
+        .record_use(clock, credential, serde_json::to_value(&passkey)?)
+        .await?;
+
+    Ok(Some(credential))
+}
+
+/// Start authenticating a user on the login screen: replies with the options
+/// to pass to `navigator.credentials.get()`
+#[tracing::instrument(name = "handlers.views.webauthn.login_challenge", skip_all, err)]
+pub(crate) async fn login_challenge(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<LoginChallengeForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    let user = repo
+        .user()
+        .find_by_username(&form.username)
+        .await?
+        .filter(User::is_valid)
+        .filter(User::can_login_interactively);
+
+    let credentials = if let Some(user) = &user {
+        repo.webauthn_credential().all(user).await?
+    } else {
+        Vec::new()
+    };
+
+    let (Some(user), false) = (user, credentials.is_empty()) else {
+        return Ok((cookie_jar, StatusCode::NOT_FOUND).into_response());
+    };
+
+    let webauthn = relying_party(&url_builder)?;
+    let (challenge, state) = webauthn.start_passkey_authentication(&passkeys(&credentials)?)?;
+
+    let cookie_jar = WebauthnCeremony::authentication(&user, state).save(
+        cookie_jar,
+        &clock,
+        query.post_auth_action.as_ref(),
+    );
+
+    Ok((cookie_jar, Json(challenge)).into_response())
+}
+
+/// Finish authenticating a user on the login screen, and start a new browser
+/// session
+#[tracing::instrument(name = "handlers.views.webauthn.login", skip_all, err)]
+pub(crate) async fn login(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    PreferredLanguage(locale): PreferredLanguage,
+    State(password_manager): State<PasswordManager>,
+    State(templates): State<Templates>,
+    State(url_builder): State<UrlBuilder>,
+    State(site_config): State<SiteConfig>,
+    mut repo: BoxRepository,
+    activity_tracker: BoundActivityTracker,
+    Query(query): Query<OptionalPostAuthAction>,
+    Query(next): Query<NextUrl>,
+    cookie_jar: CookieJar,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Form(form): Form<ProtectedForm<WebauthnForm>>,
+) -> Result<Response, FancyError> {
+    let user_agent = user_agent.map(|ua| ua.as_str().to_owned());
+    let form = cookie_jar.verify_form(&clock, form)?;
+
+    // The challenge can only be answered once
+    let (ceremony, cookie_jar) =
+        WebauthnCeremony::take(cookie_jar, &clock, query.post_auth_action.as_ref());
+
+    let credential = verify(&url_builder, &mut repo, &clock, ceremony, &form.credential).await?;
+
+    let user = if let Some(credential) = &credential {
+        repo.user()
+            .lookup(credential.user_id)
+            .await?
+            .filter(User::is_valid)
+            .filter(User::can_login_interactively)
+    } else {
+        None
+    };
+
+    let (Some(credential), Some(user)) = (credential, user) else {
+        let (csrf_token, cookie_jar) = cookie_jar.csrf_token(&clock, &mut rng);
+        let providers = crate::upstream_providers(&mut repo).await?;
+        let content = render(
+            locale,
+            LoginContext::default()
+                .with_password_login(password_manager.is_enabled())
+                .with_upstream_providers(providers)
+                .with_captcha(site_config.captcha.clone())
+                .with_form_state(
+                    FormState::default().with_error_on_form(FormError::WebauthnFailed),
+                ),
+            query,
+            csrf_token,
+            &mut repo,
+            &templates,
+        )
+        .await?;
+
+        return Ok((cookie_jar, Html(content)).into_response());
+    };
+
+    // Start a new session, authenticated by the credential
+    let mut user_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, user_agent)
+        .await?;
+
+    repo.browser_session()
+        .authenticate_with_webauthn(&mut rng, &clock, &user_session, &credential)
+        .await?;
+
+    // Let the other sessions of the user know about this sign-in
+    repo.job()
+        .schedule_job(NotifyNewSignInJob::for_browser_session(&user_session))
+        .await?;
+
+    let (user, cookie_jar) =
+        remember_locale(&mut repo, &locale, user_session.user, cookie_jar).await?;
+    user_session.user = user;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &user_session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&user_session);
+    let reply = go_next(&query, &next, &url_builder, &site_config);
+    Ok((cookie_jar, reply).into_response())
+}
+
+/// Start reauthenticating the current user: replies with the options to pass
+/// to `navigator.credentials.get()`
+#[tracing::instrument(name = "handlers.views.webauthn.reauth_challenge", skip_all, err)]
+pub(crate) async fn reauth_challenge(
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<()>>,
+) -> Result<Response, FancyError> {
+    cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        return Ok((cookie_jar, StatusCode::UNAUTHORIZED).into_response());
+    };
+
+    let credentials = repo.webauthn_credential().all(&session.user).await?;
+    if credentials.is_empty() {
+        return Ok((cookie_jar, StatusCode::NOT_FOUND).into_response());
+    }
+
+    let webauthn = relying_party(&url_builder)?;
+    let (challenge, state) = webauthn.start_passkey_authentication(&passkeys(&credentials)?)?;
+
+    let cookie_jar = WebauthnCeremony::authentication(&session.user, state).save(
+        cookie_jar,
+        &clock,
+        query.post_auth_action.as_ref(),
+    );
+
+    Ok((cookie_jar, Json(challenge)).into_response())
+}
+
+/// Finish reauthenticating the current user
+#[tracing::instrument(name = "handlers.views.webauthn.reauth", skip_all, err)]
+pub(crate) async fn reauth(
+    mut rng: BoxRng,
+    clock: BoxClock,
+    State(url_builder): State<UrlBuilder>,
+    activity_tracker: BoundActivityTracker,
+    mut repo: BoxRepository,
+    Query(query): Query<OptionalPostAuthAction>,
+    cookie_jar: CookieJar,
+    Form(form): Form<ProtectedForm<WebauthnForm>>,
+) -> Result<Response, FancyError> {
+    let form = cookie_jar.verify_form(&clock, form)?;
+    let (session_info, cookie_jar) = cookie_jar.session_info();
+
+    let maybe_session = session_info.load_session(&mut repo).await?;
+
+    let Some(session) = maybe_session else {
+        // If there is no session, redirect to the login screen, keeping the
+        // PostAuthAction
+        let login = mas_router::Login::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&login)).into_response());
+    };
+
+    // The challenge can only be answered once
+    let (ceremony, cookie_jar) =
+        WebauthnCeremony::take(cookie_jar, &clock, query.post_auth_action.as_ref());
+
+    let credential = verify(&url_builder, &mut repo, &clock, ceremony, &form.credential)
+        .await?
+        .filter(|credential| credential.user_id == session.user.id);
+
+    // TODO: display a nice error
+    let Some(credential) = credential else {
+        let reauth = mas_router::Reauth::from(query.post_auth_action);
+        return Ok((cookie_jar, url_builder.redirect(&reauth)).into_response());
+    };
+
+    // Mark the session as authenticated by the credential
+    repo.browser_session()
+        .authenticate_with_webauthn(&mut rng, &clock, &session, &credential)
+        .await?;
+
+    repo.save().await?;
+
+    activity_tracker
+        .record_browser_session(&clock, &session)
+        .await;
+
+    let cookie_jar = cookie_jar.set_session(&session);
+    let reply = query.go_next(&url_builder);
+    Ok((cookie_jar, reply).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{Request, StatusCode};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{
+        init_tracing, CookieHelper, RequestBuilderExt, ResponseExt, TestState,
+    };
+
+    #[sqlx::test(migrator = "mas_storage_pg::MIGRATOR")]
+    async fn test_login_without_passkey(pool: PgPool) {
+        init_tracing();
+        let state = TestState::from_pool(pool).await.unwrap();
+        let cookies = CookieHelper::new();
+        state.create_user("john", "hunter2").await;
+
+        let request = cookies.with_cookies(Request::get("/login").empty());
+        let response = state.request(request).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+        let csrf_token = response.csrf_token().to_owned();
+
+        // Users without passkeys can't get a challenge
+        let request = Request::post("/login/webauthn/challenge").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "john",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // Neither can unknown users
+        let request = Request::post("/login/webauthn/challenge").form(serde_json::json!({
+            "csrf": csrf_token,
+            "username": "alice",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        response.assert_status(StatusCode::NOT_FOUND);
+
+        // Answering without a pending challenge fails and doesn't log in
+        let request = Request::post("/login/webauthn").form(serde_json::json!({
+            "csrf": csrf_token,
+            "credential": "{}",
+        }));
+        let response = state.request(cookies.with_cookies(request)).await;
+        cookies.save_cookies(&response);
+        response.assert_status(StatusCode::OK);
+
+        let request = cookies.with_cookies(Request::get("/").empty());
+        let response = state.request(request).await;
+        response.assert_status(StatusCode::OK);
+        assert!(!response.body().contains("john"));
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers shared by the WebAuthn registration and authentication ceremonies
+
+use chrono::{DateTime, Duration, Utc};
+use mas_axum_utils::cookies::CookieJar;
+use mas_data_model::{User, WebauthnCredential};
+use mas_router::{PostAuthAction, UrlBuilder};
+use mas_storage::Clock;
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+use webauthn_rs::prelude::{
+    CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration, Webauthn, WebauthnBuilder,
+    WebauthnError,
+};
+
+/// Name of the cookie
+static COOKIE_NAME: &str = "webauthn-ceremony";
+
+/// Ceremonies expire after 5 minutes, like the challenges sent to the browser
+static CEREMONY_MAX_AGE_SECS: i64 = 60 * 5;
+
+/// How many ceremonies are kept at once, as their state makes the cookie grow
+/// quickly
+const MAX_CEREMONIES: usize = 3;
+
+/// Build the WebAuthn relying party. Credentials are bound to the host of the
+/// public base URL of the service.
+pub(crate) fn relying_party(url_builder: &UrlBuilder) -> Result<Webauthn, WebauthnError> {
+    let origin = url_builder.absolute_url_for(&mas_router::Index);
+    let rp_id = origin.host_str().ok_or(WebauthnError::Configuration)?;
+
+    WebauthnBuilder::new(rp_id, &origin)?.rp_name(rp_id).build()
+}
+
+/// The credential ID sent by the authenticator, in the URL-safe base64 form
+/// stored in the database
+pub(crate) fn encode_credential_id(id: &CredentialID) -> Result<String, serde_json::Error> {
+    serde_json::from_value(serde_json::to_value(id)?)
+}
+
+/// Load the WebAuthn passkeys of a user from their stored credentials
+pub(crate) fn passkeys(
+    credentials: &[WebauthnCredential],
+) -> Result<Vec<Passkey>, serde_json::Error> {
+    credentials
+        .iter()
+        .map(|credential| serde_json::from_value(credential.passkey.clone()))
+        .collect()
+}
+
+/// Remembers the state of a WebAuthn ceremony in progress, between the
+/// challenge sent to the browser and its response
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum WebauthnCeremony {
+    /// A user is registering a new credential
+    Registration {
+        user_id: Ulid,
+        state: PasskeyRegistration,
+    },
+
+    /// A user is authenticating with one of their credentials
+    Authentication {
+        user_id: Ulid,
+        state: PasskeyAuthentication,
+    },
+}
+
+impl WebauthnCeremony {
+    /// Start registering a new credential for the given user
+    pub fn registration(user: &User, state: PasskeyRegistration) -> Self {
+        Self::Registration {
+            user_id: user.id,
+            state,
+        }
+    }
+
+    /// Start authenticating the given user
+    pub fn authentication(user: &User, state: PasskeyAuthentication) -> Self {
+        Self::Authentication {
+            user_id: user.id,
+            state,
+        }
+    }
+
+    fn load_all(cookie_jar: &CookieJar) -> Vec<PendingCeremony> {
+        match cookie_jar.load(COOKIE_NAME) {
+            Ok(Some(ceremonies)) => ceremonies,
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                tracing::warn!("Invalid WebAuthn ceremony cookie: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn save_all(ceremonies: &[PendingCeremony], cookie_jar: CookieJar) -> CookieJar {
+        if ceremonies.is_empty() {
+            cookie_jar.remove(COOKIE_NAME)
+        } else {
+            cookie_jar.save(COOKIE_NAME, &ceremonies, false)
+        }
+    }
+
+    /// Save the ceremony in the cookie jar.
+    ///
+    /// Ceremonies are kept by the action the user will continue with once
+    /// authenticated, which is carried in the URL of each page. This way, a
+    /// user going through two authorization grants in different tabs doesn't
+    /// have one ceremony replace the other.
+    pub fn save<C: Clock>(
+        self,
+        cookie_jar: CookieJar,
+        clock: &C,
+        post_auth_action: Option<&PostAuthAction>,
+    ) -> CookieJar {
+        let now = clock.now();
+        let mut ceremonies = Self::load_all(&cookie_jar);
+        ceremonies.retain(|pending| {
+            !pending.expired(now) && pending.post_auth_action.as_ref() != post_auth_action
+        });
+
+        // Drop the oldest ones if there are too many
+        let excess = (ceremonies.len() + 1).saturating_sub(MAX_CEREMONIES);
+        ceremonies.drain(..excess);
+
+        ceremonies.push(PendingCeremony {
+            started_at: now,
+            post_auth_action: post_auth_action.cloned(),
+            ceremony: self,
+        });
+
+        Self::save_all(&ceremonies, cookie_jar)
+    }
+
+    /// Take the ceremony started for the given action out of the cookie jar,
+    /// so that a challenge can't be answered twice
+    pub fn take<C: Clock>(
+        cookie_jar: CookieJar,
+        clock: &C,
+        post_auth_action: Option<&PostAuthAction>,
+    ) -> (Option<Self>, CookieJar) {
+        let now = clock.now();
+        let mut ceremonies = Self::load_all(&cookie_jar);
+        ceremonies.retain(|pending| !pending.expired(now));
+
+        let ceremony = ceremonies
+            .iter()
+            .position(|pending| pending.post_auth_action.as_ref() == post_auth_action)
+            .map(|index| ceremonies.remove(index).ceremony);
+
+        (ceremony, Self::save_all(&ceremonies, cookie_jar))
+    }
+}
+
+/// A ceremony saved in the cookie, with the action it was started for
+#[derive(Serialize, Deserialize)]
+struct PendingCeremony {
+    started_at: DateTime<Utc>,
+    post_auth_action: Option<PostAuthAction>,
+    ceremony: WebauthnCeremony,
+}
+
+impl PendingCeremony {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.started_at > Duration::seconds(CEREMONY_MAX_AGE_SECS)
+    }
+}
@@ -504,6 +504,8 @@ mod oidc_core {
 
     pub const AUTH_TIME: Claim<Timestamp> = Claim::new("auth_time");
     pub const NONCE: Claim<String, Equality<str>> = Claim::new("nonce");
+    pub const ACR: Claim<String> = Claim::new("acr");
+    pub const AMR: Claim<Vec<String>> = Claim::new("amr");
     pub const AT_HASH: Claim<String, TokenHash> = Claim::new("at_hash");
     pub const C_HASH: Claim<String, TokenHash> = Claim::new("c_hash");
 
@@ -533,7 +535,31 @@ mod oidc_core {
     pub const UPDATED_AT: Claim<Timestamp> = Claim::new("updated_at");
 }
 
-pub use self::{oidc_core::*, rfc7519::*};
+/// Claims defined in RFC9449 sec. 4.2 and sec. 6.1
+/// <https://www.rfc-editor.org/rfc/rfc9449.html#section-4.2>
+/// <https://www.rfc-editor.org/rfc/rfc9449.html#section-6.1>
+mod rfc9449 {
+    use super::{Claim, Equality};
+
+    pub const HTM: Claim<String, Equality<str>> = Claim::new("htm");
+    pub const HTU: Claim<String> = Claim::new("htu");
+    pub const ATH: Claim<String, Equality<str>> = Claim::new("ath");
+}
+
+/// Claims defined in OIDC.BackChannel sec. 2.4
+/// <https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken>
+mod oidc_backchannel {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use super::Claim;
+
+    pub const SID: Claim<String> = Claim::new("sid");
+    pub const EVENTS: Claim<HashMap<String, Value>> = Claim::new("events");
+}
+
+pub use self::{oidc_backchannel::*, oidc_core::*, rfc7519::*, rfc9449::*};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,350 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Utilities to validate DPoP proofs, as defined in [RFC 9449]
+//!
+//! [RFC 9449]: https://www.rfc-editor.org/rfc/rfc9449.html
+
+use std::collections::HashMap;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    claims::{self, ClaimError, TimeOptions},
+    jwa::{AsymmetricKeyFromJwkError, AsymmetricVerifyingKey},
+    jwt::{Jwt, JwtDecodeError, JwtVerificationError},
+};
+
+/// The `typ` header value of DPoP proofs
+pub const DPOP_JWT_TYPE: &str = "dpop+jwt";
+
+/// How long after its issuance a DPoP proof is accepted
+const DEFAULT_MAX_AGE: i64 = 5 * 60;
+
+#[derive(Debug, Error)]
+pub enum DPoPProofError {
+    #[error("failed to decode DPoP proof")]
+    Decode(#[from] JwtDecodeError),
+
+    #[error("invalid DPoP proof type")]
+    InvalidType,
+
+    #[error("DPoP proof has no embedded public key")]
+    MissingKey,
+
+    #[error("DPoP proof key is not suitable for its algorithm")]
+    UnsuitableKey(#[from] AsymmetricKeyFromJwkError),
+
+    #[error("invalid DPoP proof signature")]
+    Signature(#[from] JwtVerificationError),
+
+    #[error("invalid claim in DPoP proof")]
+    Claim(#[from] ClaimError),
+
+    #[error("DPoP proof is too old")]
+    Expired,
+
+    #[error("DPoP proof does not match the request")]
+    RequestMismatch,
+}
+
+/// Options used to validate a DPoP proof against the HTTP request it was sent
+/// with
+#[derive(Debug, Clone)]
+pub struct DPoPProofOptions<'a> {
+    method: &'a str,
+    uri: &'a Url,
+    now: DateTime<Utc>,
+    max_age: Duration,
+    access_token: Option<&'a str>,
+}
+
+impl<'a> DPoPProofOptions<'a> {
+    /// Creates options to validate a proof sent with a request of the given
+    /// method to the given URI
+    #[must_use]
+    pub fn new(method: &'a str, uri: &'a Url, now: DateTime<Utc>) -> Self {
+        Self {
+            method,
+            uri,
+            now,
+            max_age: Duration::seconds(DEFAULT_MAX_AGE),
+            access_token: None,
+        }
+    }
+
+    /// Set how long after its issuance a proof is accepted
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Require the proof to be bound to the given access token, through the
+    /// `ath` claim
+    #[must_use]
+    pub fn with_access_token(mut self, access_token: &'a str) -> Self {
+        self.access_token = Some(access_token);
+        self
+    }
+}
+
+/// A validated DPoP proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DPoPProof {
+    jkt: String,
+    jti: String,
+    issued_at: DateTime<Utc>,
+    nonce: Option<String>,
+}
+
+impl DPoPProof {
+    /// Parse and validate a DPoP proof
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the proof could not be decoded, if its signature
+    /// is invalid, or if it does not match the request described by the
+    /// options.
+    pub fn verify(proof: &str, options: &DPoPProofOptions<'_>) -> Result<Self, DPoPProofError> {
+        let jwt: Jwt<'_, HashMap<String, Value>> = Jwt::try_from(proof)?;
+
+        if jwt.header().typ() != Some(DPOP_JWT_TYPE) {
+            return Err(DPoPProofError::InvalidType);
+        }
+
+        let jwk = jwt.header().jwk().ok_or(DPoPProofError::MissingKey)?;
+        let key = AsymmetricVerifyingKey::from_jwk_and_alg(jwk.params(), jwt.header().alg())?;
+        jwt.verify(&key)?;
+
+        let jkt = jwk.params().thumbprint_sha256();
+
+        let (_header, mut claims) = jwt.into_parts();
+
+        let jti = claims::JTI.extract_required(&mut claims)?;
+        claims::HTM.extract_required_with_options(&mut claims, options.method)?;
+
+        let htu = claims::HTU.extract_required(&mut claims)?;
+        let htu = Url::parse(&htu).map_err(|_| DPoPProofError::RequestMismatch)?;
+        if strip_query_and_fragment(&htu) != strip_query_and_fragment(options.uri) {
+            return Err(DPoPProofError::RequestMismatch);
+        }
+
+        let time_options = TimeOptions::new(options.now);
+        let issued_at = claims::IAT.extract_required_with_options(&mut claims, time_options)?;
+        if *issued_at + options.max_age < options.now {
+            return Err(DPoPProofError::Expired);
+        }
+
+        if let Some(access_token) = options.access_token {
+            let ath = access_token_hash(access_token);
+            claims::ATH.extract_required_with_options(&mut claims, ath.as_str())?;
+        }
+
+        // The nonce is checked by the caller, as it is server-specific
+        let nonce = claims
+            .remove("nonce")
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|_| ClaimError::InvalidClaim("nonce"))?;
+
+        Ok(Self {
+            jkt,
+            jti,
+            issued_at: *issued_at,
+            nonce,
+        })
+    }
+
+    /// The JWK SHA-256 Thumbprint of the key used to sign this proof
+    #[must_use]
+    pub fn jkt(&self) -> &str {
+        &self.jkt
+    }
+
+    /// The unique identifier of this proof
+    #[must_use]
+    pub fn jti(&self) -> &str {
+        &self.jti
+    }
+
+    /// When this proof was issued
+    #[must_use]
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        self.issued_at
+    }
+
+    /// The server-provided nonce included in this proof, if any
+    #[must_use]
+    pub fn nonce(&self) -> Option<&str> {
+        self.nonce.as_deref()
+    }
+}
+
+/// Compute the value of the `ath` claim for the given access token
+#[must_use]
+pub fn access_token_hash(access_token: &str) -> String {
+    let digest = Sha256::digest(access_token.as_bytes());
+    Base64UrlUnpadded::encode_string(&digest)
+}
+
+fn strip_query_and_fragment(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{
+        jwa::AsymmetricSigningKey,
+        jwk::{JsonWebKey, JsonWebKeyPublicParameters},
+        jwt::JsonWebSignatureHeader,
+    };
+
+    fn sign_proof(claims: Value, typ: &str) -> (String, String) {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let secret = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+        let params = JsonWebKeyPublicParameters::from(secret.public_key());
+        let jkt = params.thumbprint_sha256();
+
+        let header = JsonWebSignatureHeader::new(JsonWebSignatureAlg::Es256)
+            .with_typ(typ.to_owned())
+            .with_jwk(JsonWebKey::new(params));
+        let claims: HashMap<String, Value> = serde_json::from_value(claims).unwrap();
+        let key = AsymmetricSigningKey::es256(secret);
+        let jwt = Jwt::sign_with_rng(&mut rng, header, claims, &key).unwrap();
+
+        (jwt.into_string(), jkt)
+    }
+
+    #[test]
+    fn verify_valid_proof() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let uri = Url::parse("https://example.com/oauth2/token").unwrap();
+
+        let (proof, jkt) = sign_proof(
+            serde_json::json!({
+                "jti": "abcdef",
+                "htm": "POST",
+                "htu": "https://example.com/oauth2/token?foo=bar",
+                "iat": 1_700_000_000 - 30,
+                "nonce": "server-nonce",
+            }),
+            DPOP_JWT_TYPE,
+        );
+
+        let options = DPoPProofOptions::new("POST", &uri, now);
+        let proof = DPoPProof::verify(&proof, &options).unwrap();
+        assert_eq!(proof.jkt(), jkt);
+        assert_eq!(proof.jti(), "abcdef");
+        assert_eq!(proof.nonce(), Some("server-nonce"));
+    }
+
+    #[test]
+    fn verify_access_token_hash() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let uri = Url::parse("https://example.com/oauth2/userinfo").unwrap();
+
+        let (proof, _jkt) = sign_proof(
+            serde_json::json!({
+                "jti": "abcdef",
+                "htm": "GET",
+                "htu": "https://example.com/oauth2/userinfo",
+                "iat": 1_700_000_000,
+                "ath": access_token_hash("some-token"),
+            }),
+            DPOP_JWT_TYPE,
+        );
+
+        let options = DPoPProofOptions::new("GET", &uri, now).with_access_token("some-token");
+        DPoPProof::verify(&proof, &options).unwrap();
+
+        let options = DPoPProofOptions::new("GET", &uri, now).with_access_token("other-token");
+        assert!(matches!(
+            DPoPProof::verify(&proof, &options),
+            Err(DPoPProofError::Claim(_))
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_proofs() {
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let uri = Url::parse("https://example.com/oauth2/token").unwrap();
+        let options = DPoPProofOptions::new("POST", &uri, now);
+
+        let claims = serde_json::json!({
+            "jti": "abcdef",
+            "htm": "POST",
+            "htu": "https://example.com/oauth2/token",
+            "iat": 1_700_000_000,
+        });
+
+        // Wrong type
+        let (proof, _) = sign_proof(claims.clone(), "JWT");
+        assert!(matches!(
+            DPoPProof::verify(&proof, &options),
+            Err(DPoPProofError::InvalidType)
+        ));
+
+        // Wrong method
+        let options_get = DPoPProofOptions::new("GET", &uri, now);
+        let (proof, _) = sign_proof(claims, DPOP_JWT_TYPE);
+        assert!(matches!(
+            DPoPProof::verify(&proof, &options_get),
+            Err(DPoPProofError::Claim(_))
+        ));
+
+        // Wrong URI
+        let (proof, _) = sign_proof(
+            serde_json::json!({
+                "jti": "abcdef",
+                "htm": "POST",
+                "htu": "https://example.com/oauth2/userinfo",
+                "iat": 1_700_000_000,
+            }),
+            DPOP_JWT_TYPE,
+        );
+        assert!(matches!(
+            DPoPProof::verify(&proof, &options),
+            Err(DPoPProofError::RequestMismatch)
+        ));
+
+        // Too old
+        let (proof, _) = sign_proof(
+            serde_json::json!({
+                "jti": "abcdef",
+                "htm": "POST",
+                "htu": "https://example.com/oauth2/token",
+                "iat": 1_700_000_000 - 3600,
+            }),
+            DPOP_JWT_TYPE,
+        );
+        assert!(matches!(
+            DPoPProof::verify(&proof, &options),
+            Err(DPoPProofError::Expired)
+        ));
+    }
+}
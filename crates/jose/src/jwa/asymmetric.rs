@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use digest::Digest;
 use mas_iana::jose::{JsonWebKeyEcEllipticCurve, JsonWebSignatureAlg};
 use sha2::{Sha256, Sha384, Sha512};
@@ -42,6 +44,18 @@ pub enum AsymmetricKeyFromJwkError {
     KeyNotSuitable { alg: JsonWebSignatureAlg },
 }
 
+/// A backend holding a private key outside of the process, like a hardware
+/// security module, to which signing operations are delegated
+pub trait SigningBackend: Send + Sync {
+    /// Sign the message with the given algorithm, returning the signature as
+    /// it should be encoded in a JWS
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backend failed to sign the message
+    fn sign(&self, alg: &JsonWebSignatureAlg, msg: &[u8]) -> Result<Vec<u8>, signature::Error>;
+}
+
 /// An enum of all supported asymmetric signature algorithms verifying keys
 #[non_exhaustive]
 pub enum AsymmetricSigningKey {
@@ -54,6 +68,10 @@ pub enum AsymmetricSigningKey {
     Es256(super::Es256SigningKey),
     Es384(super::Es384SigningKey),
     Es256K(super::Es256KSigningKey),
+    External {
+        alg: JsonWebSignatureAlg,
+        backend: Arc<dyn SigningBackend>,
+    },
 }
 
 impl AsymmetricSigningKey {
@@ -129,6 +147,13 @@ impl AsymmetricSigningKey {
         Self::Es256K(ecdsa::SigningKey::from(key))
     }
 
+    /// Create a new signing key for the given algorithm, delegating the
+    /// signing operations to the given backend.
+    #[must_use]
+    pub fn external(alg: JsonWebSignatureAlg, backend: Arc<dyn SigningBackend>) -> Self {
+        Self::External { alg, backend }
+    }
+
     /// Create a new signing key for the given algorithm from the given private
     /// JWK parameters.
     ///
@@ -281,6 +306,10 @@ impl signature::RandomizedSigner<Signature> for AsymmetricSigningKey {
                 let signature: ecdsa::Signature<_> = key.try_sign_with_rng(rng, msg)?;
                 Ok(Signature::from_signature(&signature))
             }
+            Self::External { alg, backend } => {
+                let signature = backend.sign(alg, msg)?;
+                Ok(Signature::new(signature))
+            }
         }
     }
 }
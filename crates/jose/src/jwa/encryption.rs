@@ -0,0 +1,373 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Content encryption algorithms, as defined in RFC 7518 section 5
+
+use aes_gcm::{
+    aead::{AeadInPlace, KeyInit},
+    Aes128Gcm, Aes256Gcm, Nonce, Tag,
+};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use digest::Mac;
+use mas_iana::jose::JsonWebEncryptionEnc;
+use sha2::{Sha256, Sha512};
+use signature::rand_core::CryptoRngCore;
+use thiserror::Error;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+#[derive(Debug, Error)]
+pub enum ContentEncryptionError {
+    #[error("Unsupported content encryption algorithm {enc}")]
+    UnsupportedAlgorithm { enc: JsonWebEncryptionEnc },
+
+    #[error("Invalid content encryption key length")]
+    InvalidKeyLength,
+
+    #[error("Invalid initialization vector length")]
+    InvalidIvLength,
+
+    #[error("Content encryption failed")]
+    Encryption,
+
+    #[error("Content decryption failed")]
+    Decryption,
+}
+
+/// The result of the encryption of some content
+pub struct EncryptedContent {
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+/// A content encryption algorithm, used to encrypt the payload of a JWE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ContentEncryptionAlgorithm {
+    A128CbcHs256,
+    A256CbcHs512,
+    A128Gcm,
+    A256Gcm,
+}
+
+impl TryFrom<&JsonWebEncryptionEnc> for ContentEncryptionAlgorithm {
+    type Error = ContentEncryptionError;
+
+    fn try_from(enc: &JsonWebEncryptionEnc) -> Result<Self, Self::Error> {
+        match enc {
+            JsonWebEncryptionEnc::A128CbcHs256 => Ok(Self::A128CbcHs256),
+            JsonWebEncryptionEnc::A256CbcHs512 => Ok(Self::A256CbcHs512),
+            JsonWebEncryptionEnc::A128Gcm => Ok(Self::A128Gcm),
+            JsonWebEncryptionEnc::A256Gcm => Ok(Self::A256Gcm),
+            enc => Err(ContentEncryptionError::UnsupportedAlgorithm { enc: enc.clone() }),
+        }
+    }
+}
+
+impl ContentEncryptionAlgorithm {
+    /// The length in bytes of the content encryption keys of this algorithm
+    #[must_use]
+    pub const fn key_len(self) -> usize {
+        match self {
+            Self::A128CbcHs256 | Self::A256Gcm => 32,
+            Self::A256CbcHs512 => 64,
+            Self::A128Gcm => 16,
+        }
+    }
+
+    const fn iv_len(self) -> usize {
+        match self {
+            Self::A128CbcHs256 | Self::A256CbcHs512 => 16,
+            Self::A128Gcm | Self::A256Gcm => 12,
+        }
+    }
+
+    /// Generate a new random content encryption key for this algorithm
+    pub fn generate_key(self, rng: &mut impl CryptoRngCore) -> Vec<u8> {
+        let mut key = vec![0; self.key_len()];
+        rng.fill_bytes(&mut key);
+        key
+    }
+
+    /// Encrypt and authenticate the plaintext, along with the additional
+    /// authenticated data, with a random initialization vector
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key doesn't have the right length for this
+    /// algorithm
+    pub fn encrypt(
+        self,
+        rng: &mut impl CryptoRngCore,
+        key: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<EncryptedContent, ContentEncryptionError> {
+        if key.len() != self.key_len() {
+            return Err(ContentEncryptionError::InvalidKeyLength);
+        }
+
+        let mut iv = vec![0; self.iv_len()];
+        rng.fill_bytes(&mut iv);
+
+        let (ciphertext, tag) = match self {
+            Self::A128CbcHs256 => {
+                let (mac_key, enc_key) = key.split_at(16);
+                let ciphertext = Aes128CbcEnc::new_from_slices(enc_key, &iv)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?
+                    .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+                let tag = cbc_hmac_tag::<::hmac::Hmac<Sha256>>(mac_key, aad, &iv, &ciphertext)?;
+                (ciphertext, tag)
+            }
+
+            Self::A256CbcHs512 => {
+                let (mac_key, enc_key) = key.split_at(32);
+                let ciphertext = Aes256CbcEnc::new_from_slices(enc_key, &iv)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?
+                    .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+                let tag = cbc_hmac_tag::<::hmac::Hmac<Sha512>>(mac_key, aad, &iv, &ciphertext)?;
+                (ciphertext, tag)
+            }
+
+            Self::A128Gcm => {
+                let cipher = Aes128Gcm::new_from_slice(key)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?;
+                let mut buffer = plaintext.to_vec();
+                let tag = cipher
+                    .encrypt_in_place_detached(Nonce::from_slice(&iv), aad, &mut buffer)
+                    .map_err(|_| ContentEncryptionError::Encryption)?;
+                (buffer, tag.to_vec())
+            }
+
+            Self::A256Gcm => {
+                let cipher = Aes256Gcm::new_from_slice(key)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?;
+                let mut buffer = plaintext.to_vec();
+                let tag = cipher
+                    .encrypt_in_place_detached(Nonce::from_slice(&iv), aad, &mut buffer)
+                    .map_err(|_| ContentEncryptionError::Encryption)?;
+                (buffer, tag.to_vec())
+            }
+        };
+
+        Ok(EncryptedContent {
+            iv,
+            ciphertext,
+            tag,
+        })
+    }
+
+    /// Check the authentication tag and decrypt the ciphertext
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key or initialization vector don't have the
+    /// right length for this algorithm, or if the authentication tag is
+    /// invalid
+    pub fn decrypt(
+        self,
+        key: &[u8],
+        aad: &[u8],
+        content: &EncryptedContent,
+    ) -> Result<Vec<u8>, ContentEncryptionError> {
+        if key.len() != self.key_len() {
+            return Err(ContentEncryptionError::InvalidKeyLength);
+        }
+
+        if content.iv.len() != self.iv_len() {
+            return Err(ContentEncryptionError::InvalidIvLength);
+        }
+
+        let EncryptedContent {
+            iv,
+            ciphertext,
+            tag,
+        } = content;
+
+        match self {
+            Self::A128CbcHs256 => {
+                let (mac_key, enc_key) = key.split_at(16);
+                cbc_hmac_verify::<::hmac::Hmac<Sha256>>(mac_key, aad, iv, ciphertext, tag)?;
+                Aes128CbcDec::new_from_slices(enc_key, iv)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+                    .map_err(|_| ContentEncryptionError::Decryption)
+            }
+
+            Self::A256CbcHs512 => {
+                let (mac_key, enc_key) = key.split_at(32);
+                cbc_hmac_verify::<::hmac::Hmac<Sha512>>(mac_key, aad, iv, ciphertext, tag)?;
+                Aes256CbcDec::new_from_slices(enc_key, iv)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?
+                    .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+                    .map_err(|_| ContentEncryptionError::Decryption)
+            }
+
+            Self::A128Gcm => {
+                let cipher = Aes128Gcm::new_from_slice(key)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?;
+                gcm_decrypt(&cipher, iv, aad, ciphertext, tag)
+            }
+
+            Self::A256Gcm => {
+                let cipher = Aes256Gcm::new_from_slice(key)
+                    .map_err(|_| ContentEncryptionError::InvalidKeyLength)?;
+                gcm_decrypt(&cipher, iv, aad, ciphertext, tag)
+            }
+        }
+    }
+}
+
+/// Feed the MAC with the data authenticated by the `AES_CBC_HMAC_SHA2`
+/// algorithms, as described in RFC 7518 section 5.2.2.1
+fn cbc_hmac<M: Mac + KeyInit>(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<M, ContentEncryptionError> {
+    let aad_bits = u64::try_from(aad.len())
+        .ok()
+        .and_then(|len| len.checked_mul(8))
+        .ok_or(ContentEncryptionError::Encryption)?;
+
+    let mut mac = <M as Mac>::new_from_slice(mac_key)
+        .map_err(|_| ContentEncryptionError::InvalidKeyLength)?;
+    mac.update(aad);
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(&aad_bits.to_be_bytes());
+    Ok(mac)
+}
+
+fn cbc_hmac_tag<M: Mac + KeyInit>(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, ContentEncryptionError> {
+    let mac = cbc_hmac::<M>(mac_key, aad, iv, ciphertext)?
+        .finalize()
+        .into_bytes();
+    // The tag is the first half of the MAC, which is as long as the MAC key
+    Ok(mac[..mac_key.len()].to_vec())
+}
+
+fn cbc_hmac_verify<M: Mac + KeyInit>(
+    mac_key: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<(), ContentEncryptionError> {
+    if tag.len() != mac_key.len() {
+        return Err(ContentEncryptionError::Decryption);
+    }
+
+    cbc_hmac::<M>(mac_key, aad, iv, ciphertext)?
+        .verify_truncated_left(tag)
+        .map_err(|_| ContentEncryptionError::Decryption)
+}
+
+fn gcm_decrypt<C: AeadInPlace>(
+    cipher: &C,
+    iv: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, ContentEncryptionError> {
+    if tag.len() != 16 {
+        return Err(ContentEncryptionError::Decryption);
+    }
+
+    let mut buffer = ciphertext.to_vec();
+    cipher
+        .decrypt_in_place_detached(
+            Nonce::from_slice(iv),
+            aad,
+            &mut buffer,
+            Tag::from_slice(tag),
+        )
+        .map_err(|_| ContentEncryptionError::Decryption)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let algorithms = [
+            ContentEncryptionAlgorithm::A128CbcHs256,
+            ContentEncryptionAlgorithm::A256CbcHs512,
+            ContentEncryptionAlgorithm::A128Gcm,
+            ContentEncryptionAlgorithm::A256Gcm,
+        ];
+
+        for alg in algorithms {
+            let key = alg.generate_key(&mut rng);
+            let content = alg.encrypt(&mut rng, &key, b"aad", b"hello world").unwrap();
+            assert_ne!(content.ciphertext, b"hello world");
+
+            let plaintext = alg.decrypt(&key, b"aad", &content).unwrap();
+            assert_eq!(plaintext, b"hello world");
+
+            // Tampering with the authenticated data is detected
+            assert!(alg.decrypt(&key, b"other aad", &content).is_err());
+        }
+    }
+
+    #[test]
+    fn test_a128cbc_hs256_vector() {
+        // Test vector from RFC 7518 appendix B.1
+        let key: Vec<u8> = (0x00..=0x1f).collect();
+        let plaintext = b"A cipher system must not be required to be secret, and it must be able to fall into the hands of the enemy without inconvenience";
+        let aad = b"The second principle of Auguste Kerckhoffs";
+        let iv = vec![
+            0x1a, 0xf3, 0x8c, 0x2d, 0xc2, 0xb9, 0x6f, 0xfd, 0xd8, 0x66, 0x94, 0x09, 0x23, 0x41,
+            0xbc, 0x04,
+        ];
+
+        let ciphertext = Aes128CbcEnc::new_from_slices(&key[16..], &iv)
+            .unwrap()
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+        let tag = cbc_hmac_tag::<::hmac::Hmac<Sha256>>(&key[..16], aad, &iv, &ciphertext).unwrap();
+
+        assert_eq!(
+            tag,
+            [
+                0x65, 0x2c, 0x3f, 0xa3, 0x6b, 0x0a, 0x7c, 0x5b, 0x32, 0x19, 0xfa, 0xb3, 0xa3, 0x0b,
+                0xc1, 0xc4,
+            ]
+        );
+
+        let content = EncryptedContent {
+            iv,
+            ciphertext,
+            tag,
+        };
+        let decrypted = ContentEncryptionAlgorithm::A128CbcHs256
+            .decrypt(&key, aad, &content)
+            .unwrap();
+        assert_eq!(decrypted, plaintext);
+    }
+}
@@ -12,16 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use mas_iana::jose::JsonWebSignatureAlg;
+use mas_iana::jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg};
 use sha2::{Sha256, Sha384, Sha512};
 
 mod asymmetric;
+mod encryption;
 pub(crate) mod hmac;
 mod signature;
 mod symmetric;
 
 pub use self::{
-    asymmetric::{AsymmetricKeyFromJwkError, AsymmetricSigningKey, AsymmetricVerifyingKey},
+    asymmetric::{
+        AsymmetricKeyFromJwkError, AsymmetricSigningKey, AsymmetricVerifyingKey, SigningBackend,
+    },
+    encryption::{ContentEncryptionAlgorithm, ContentEncryptionError, EncryptedContent},
     symmetric::{InvalidAlgorithm, SymmetricKey},
 };
 
@@ -65,3 +69,22 @@ pub const SUPPORTED_SIGNING_ALGORITHMS: [JsonWebSignatureAlg; 12] = [
     JsonWebSignatureAlg::Es384,
     JsonWebSignatureAlg::Es256K,
 ];
+
+/// All the key management algorithms supported by this crate, to encrypt
+/// JWEs.
+pub const SUPPORTED_ENCRYPTION_ALGORITHMS: [JsonWebEncryptionAlg; 5] = [
+    JsonWebEncryptionAlg::RsaOaep,
+    JsonWebEncryptionAlg::RsaOaep256,
+    JsonWebEncryptionAlg::EcdhEs,
+    JsonWebEncryptionAlg::EcdhEsA128Kw,
+    JsonWebEncryptionAlg::EcdhEsA256Kw,
+];
+
+/// All the content encryption algorithms supported by this crate, to encrypt
+/// JWEs.
+pub const SUPPORTED_CONTENT_ENCRYPTION_ALGORITHMS: [JsonWebEncryptionEnc; 4] = [
+    JsonWebEncryptionEnc::A128CbcHs256,
+    JsonWebEncryptionEnc::A256CbcHs512,
+    JsonWebEncryptionEnc::A128Gcm,
+    JsonWebEncryptionEnc::A256Gcm,
+];
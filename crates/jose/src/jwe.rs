@@ -0,0 +1,685 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! JSON Web Encryption, in its compact serialization
+//!
+//! Ref: <https://www.rfc-editor.org/rfc/rfc7516.html>
+
+use aes_kw::{KekAes128, KekAes256};
+use base64ct::{Base64UrlUnpadded, Encoding};
+use digest::{typenum::Unsigned, Digest};
+use elliptic_curve::{
+    ecdh::EphemeralSecret,
+    sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    AffinePoint, CurveArithmetic, PublicKey, SecretKey,
+};
+use generic_array::GenericArray;
+use mas_iana::jose::{
+    JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebKeyEcEllipticCurve, JsonWebKeyUse,
+};
+use rsa::Oaep;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use sha1::Sha1;
+use sha2::Sha256;
+use signature::rand_core::CryptoRngCore;
+use thiserror::Error;
+
+use crate::{
+    constraints::Constrainable,
+    jwa::{ContentEncryptionAlgorithm, ContentEncryptionError, EncryptedContent},
+    jwk::{
+        public_parameters::EcPublicParameters, JsonWebKeyPublicParameters, JwkEcCurve,
+        PrivateJsonWebKey, PublicJsonWebKey, PublicJsonWebKeySet,
+    },
+};
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JsonWebEncryptionHeader {
+    alg: JsonWebEncryptionAlg,
+
+    enc: JsonWebEncryptionEnc,
+
+    #[serde(default)]
+    epk: Option<Box<PublicJsonWebKey>>,
+
+    #[serde(default)]
+    kid: Option<String>,
+
+    #[serde(default)]
+    typ: Option<String>,
+
+    #[serde(default)]
+    cty: Option<String>,
+}
+
+impl JsonWebEncryptionHeader {
+    #[must_use]
+    pub fn new(alg: JsonWebEncryptionAlg, enc: JsonWebEncryptionEnc) -> Self {
+        Self {
+            alg,
+            enc,
+            epk: None,
+            kid: None,
+            typ: None,
+            cty: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn alg(&self) -> &JsonWebEncryptionAlg {
+        &self.alg
+    }
+
+    #[must_use]
+    pub const fn enc(&self) -> &JsonWebEncryptionEnc {
+        &self.enc
+    }
+
+    #[must_use]
+    pub fn kid(&self) -> Option<&str> {
+        self.kid.as_deref()
+    }
+
+    #[must_use]
+    pub fn with_kid(mut self, kid: impl Into<String>) -> Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    #[must_use]
+    pub fn typ(&self) -> Option<&str> {
+        self.typ.as_deref()
+    }
+
+    #[must_use]
+    pub fn with_typ(mut self, typ: String) -> Self {
+        self.typ = Some(typ);
+        self
+    }
+
+    #[must_use]
+    pub fn cty(&self) -> Option<&str> {
+        self.cty.as_deref()
+    }
+
+    /// Set the content type of the payload. Nested JWTs must set it to `JWT`
+    #[must_use]
+    pub fn with_cty(mut self, cty: String) -> Self {
+        self.cty = Some(cty);
+        self
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum JweEncryptionError {
+    #[error("Unsupported key management algorithm {alg}")]
+    UnsupportedAlgorithm { alg: JsonWebEncryptionAlg },
+
+    #[error("Key not suitable for algorithm {alg}")]
+    KeyNotSuitable { alg: JsonWebEncryptionAlg },
+
+    #[error("Invalid RSA key")]
+    Rsa {
+        #[from]
+        inner: rsa::errors::Error,
+    },
+
+    #[error("Invalid Elliptic Curve key")]
+    EllipticCurve {
+        #[from]
+        inner: elliptic_curve::Error,
+    },
+
+    #[error("Failed to wrap the content encryption key")]
+    KeyWrap,
+
+    #[error(transparent)]
+    ContentEncryption {
+        #[from]
+        inner: ContentEncryptionError,
+    },
+
+    #[error("Failed to serialize the JWE header")]
+    SerializeHeader {
+        #[from]
+        inner: serde_json::Error,
+    },
+}
+
+#[derive(Debug, Error)]
+pub enum JweDecryptionError {
+    #[error("JWE must have 5 parts separated by dots")]
+    InvalidFormat,
+
+    #[error("Failed to decode JWE part")]
+    Decode {
+        #[from]
+        inner: base64ct::Error,
+    },
+
+    #[error("Failed to deserialize JWE header")]
+    DeserializeHeader {
+        #[from]
+        inner: serde_json::Error,
+    },
+
+    #[error("Unsupported key management algorithm {alg}")]
+    UnsupportedAlgorithm { alg: JsonWebEncryptionAlg },
+
+    #[error("Key not suitable for algorithm {alg}")]
+    KeyNotSuitable { alg: JsonWebEncryptionAlg },
+
+    #[error("Missing ephemeral public key in the JWE header")]
+    MissingEphemeralKey,
+
+    #[error("Invalid Elliptic Curve key")]
+    EllipticCurve {
+        #[from]
+        inner: elliptic_curve::Error,
+    },
+
+    #[error("Failed to decrypt the content encryption key")]
+    KeyDecryption,
+
+    #[error(transparent)]
+    ContentEncryption {
+        #[from]
+        inner: ContentEncryptionError,
+    },
+}
+
+/// Find a key in a JWKS suitable to encrypt content with the given key
+/// management algorithm
+///
+/// Keys explicitly meant for encryption are preferred over the ones with no
+/// `use` set.
+#[must_use]
+pub fn find_encryption_key<'a>(
+    jwks: &'a PublicJsonWebKeySet,
+    alg: &JsonWebEncryptionAlg,
+) -> Option<&'a PublicJsonWebKey> {
+    let mut candidates = jwks.iter().filter(|key| {
+        // The `alg` of a key is parsed as a signature algorithm, so compare
+        // them by their names
+        let alg_matches = key
+            .alg()
+            .map_or(true, |key_alg| key_alg.to_string() == alg.to_string());
+
+        let params_match = match alg {
+            JsonWebEncryptionAlg::RsaOaep | JsonWebEncryptionAlg::RsaOaep256 => {
+                key.params().rsa().is_some()
+            }
+            JsonWebEncryptionAlg::EcdhEs
+            | JsonWebEncryptionAlg::EcdhEsA128Kw
+            | JsonWebEncryptionAlg::EcdhEsA256Kw => key.params().ec().is_some_and(|params| {
+                matches!(
+                    params.crv,
+                    JsonWebKeyEcEllipticCurve::P256 | JsonWebKeyEcEllipticCurve::P384
+                )
+            }),
+            _ => false,
+        };
+
+        alg_matches && params_match && key.use_() != Some(&JsonWebKeyUse::Sig)
+    });
+
+    let first = candidates.next()?;
+    if first.use_() == Some(&JsonWebKeyUse::Enc) {
+        return Some(first);
+    }
+
+    candidates
+        .find(|key| key.use_() == Some(&JsonWebKeyUse::Enc))
+        .or(Some(first))
+}
+
+/// Encrypt a payload for the given public key, returning the JWE in its
+/// compact serialization
+///
+/// The `kid` of the key, if any, is set in the header.
+///
+/// # Errors
+///
+/// Returns an error if the algorithms are not supported, or if the key is not
+/// suitable for the key management algorithm
+pub fn encrypt(
+    rng: &mut impl CryptoRngCore,
+    mut header: JsonWebEncryptionHeader,
+    key: &PublicJsonWebKey,
+    payload: &[u8],
+) -> Result<String, JweEncryptionError> {
+    let enc = ContentEncryptionAlgorithm::try_from(&header.enc)?;
+
+    let (cek, encrypted_key) = match &header.alg {
+        JsonWebEncryptionAlg::RsaOaep | JsonWebEncryptionAlg::RsaOaep256 => {
+            let params = key
+                .params()
+                .rsa()
+                .ok_or_else(|| JweEncryptionError::KeyNotSuitable {
+                    alg: header.alg.clone(),
+                })?;
+            let public_key = rsa::RsaPublicKey::try_from(params)?;
+
+            let cek = enc.generate_key(rng);
+            let encrypted_key = if header.alg == JsonWebEncryptionAlg::RsaOaep {
+                public_key.encrypt(rng, Oaep::new::<Sha1>(), &cek)?
+            } else {
+                public_key.encrypt(rng, Oaep::new::<Sha256>(), &cek)?
+            };
+
+            (cek, encrypted_key)
+        }
+
+        JsonWebEncryptionAlg::EcdhEs
+        | JsonWebEncryptionAlg::EcdhEsA128Kw
+        | JsonWebEncryptionAlg::EcdhEsA256Kw => {
+            let params = key
+                .params()
+                .ec()
+                .ok_or_else(|| JweEncryptionError::KeyNotSuitable {
+                    alg: header.alg.clone(),
+                })?;
+
+            let (shared_secret, epk) = match params.crv {
+                JsonWebKeyEcEllipticCurve::P256 => ecdh_ephemeral::<p256::NistP256>(rng, params)?,
+                JsonWebKeyEcEllipticCurve::P384 => ecdh_ephemeral::<p384::NistP384>(rng, params)?,
+                _ => {
+                    return Err(JweEncryptionError::KeyNotSuitable {
+                        alg: header.alg.clone(),
+                    })
+                }
+            };
+            header.epk = Some(Box::new(PublicJsonWebKey::new(epk)));
+
+            match &header.alg {
+                // The shared secret is directly used to derive the content encryption key
+                JsonWebEncryptionAlg::EcdhEs => {
+                    let cek = concat_kdf(
+                        &shared_secret,
+                        &header.enc.to_string(),
+                        b"",
+                        b"",
+                        enc.key_len(),
+                    );
+                    (cek, Vec::new())
+                }
+
+                JsonWebEncryptionAlg::EcdhEsA128Kw => {
+                    let kek = concat_kdf(&shared_secret, &header.alg.to_string(), b"", b"", 16);
+                    let cek = enc.generate_key(rng);
+                    let encrypted_key = KekAes128::new(GenericArray::from_slice(&kek))
+                        .wrap_vec(&cek)
+                        .map_err(|_| JweEncryptionError::KeyWrap)?;
+                    (cek, encrypted_key)
+                }
+
+                _ => {
+                    let kek = concat_kdf(&shared_secret, &header.alg.to_string(), b"", b"", 32);
+                    let cek = enc.generate_key(rng);
+                    let encrypted_key = KekAes256::new(GenericArray::from_slice(&kek))
+                        .wrap_vec(&cek)
+                        .map_err(|_| JweEncryptionError::KeyWrap)?;
+                    (cek, encrypted_key)
+                }
+            }
+        }
+
+        alg => {
+            return Err(JweEncryptionError::UnsupportedAlgorithm { alg: alg.clone() });
+        }
+    };
+
+    if let Some(kid) = key.kid() {
+        header.kid = Some(kid.to_owned());
+    }
+
+    // The encoded protected header is the additional authenticated data
+    let header = Base64UrlUnpadded::encode_string(&serde_json::to_vec(&header)?);
+    let EncryptedContent {
+        iv,
+        ciphertext,
+        tag,
+    } = enc.encrypt(rng, &cek, header.as_bytes(), payload)?;
+
+    Ok(format!(
+        "{header}.{}.{}.{}.{}",
+        Base64UrlUnpadded::encode_string(&encrypted_key),
+        Base64UrlUnpadded::encode_string(&iv),
+        Base64UrlUnpadded::encode_string(&ciphertext),
+        Base64UrlUnpadded::encode_string(&tag),
+    ))
+}
+
+/// Decrypt a JWE in its compact serialization with the given private key
+///
+/// Returns the header and the decrypted payload.
+///
+/// # Errors
+///
+/// Returns an error if the JWE is malformed, if the algorithms are not
+/// supported, or if the JWE could not be decrypted with the key
+pub fn decrypt(
+    serialized: &str,
+    key: &PrivateJsonWebKey,
+) -> Result<(JsonWebEncryptionHeader, Vec<u8>), JweDecryptionError> {
+    let mut parts = serialized.split('.');
+    let (Some(encoded_header), Some(encrypted_key), Some(iv), Some(ciphertext), Some(tag), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return Err(JweDecryptionError::InvalidFormat);
+    };
+
+    let header: JsonWebEncryptionHeader =
+        serde_json::from_slice(&Base64UrlUnpadded::decode_vec(encoded_header)?)?;
+    let encrypted_key = Base64UrlUnpadded::decode_vec(encrypted_key)?;
+    let content = EncryptedContent {
+        iv: Base64UrlUnpadded::decode_vec(iv)?,
+        ciphertext: Base64UrlUnpadded::decode_vec(ciphertext)?,
+        tag: Base64UrlUnpadded::decode_vec(tag)?,
+    };
+
+    let enc = ContentEncryptionAlgorithm::try_from(&header.enc)?;
+    let not_suitable = || JweDecryptionError::KeyNotSuitable {
+        alg: header.alg.clone(),
+    };
+
+    let cek = match &header.alg {
+        JsonWebEncryptionAlg::RsaOaep | JsonWebEncryptionAlg::RsaOaep256 => {
+            let params = key.params().rsa().ok_or_else(not_suitable)?;
+            let private_key = rsa::RsaPrivateKey::try_from(params).map_err(|_| not_suitable())?;
+
+            let res = if header.alg == JsonWebEncryptionAlg::RsaOaep {
+                private_key.decrypt(Oaep::new::<Sha1>(), &encrypted_key)
+            } else {
+                private_key.decrypt(Oaep::new::<Sha256>(), &encrypted_key)
+            };
+
+            res.map_err(|_| JweDecryptionError::KeyDecryption)?
+        }
+
+        JsonWebEncryptionAlg::EcdhEs
+        | JsonWebEncryptionAlg::EcdhEsA128Kw
+        | JsonWebEncryptionAlg::EcdhEsA256Kw => {
+            let params = key.params().ec().ok_or_else(not_suitable)?;
+            let epk = header
+                .epk
+                .as_ref()
+                .and_then(|epk| epk.params().ec())
+                .ok_or(JweDecryptionError::MissingEphemeralKey)?;
+
+            let shared_secret = match params.crv {
+                JsonWebKeyEcEllipticCurve::P256 => {
+                    let secret_key = SecretKey::<p256::NistP256>::try_from(params)?;
+                    ecdh_static(&secret_key, epk)?
+                }
+                JsonWebKeyEcEllipticCurve::P384 => {
+                    let secret_key = SecretKey::<p384::NistP384>::try_from(params)?;
+                    ecdh_static(&secret_key, epk)?
+                }
+                _ => return Err(not_suitable()),
+            };
+
+            match &header.alg {
+                JsonWebEncryptionAlg::EcdhEs => concat_kdf(
+                    &shared_secret,
+                    &header.enc.to_string(),
+                    b"",
+                    b"",
+                    enc.key_len(),
+                ),
+
+                JsonWebEncryptionAlg::EcdhEsA128Kw => {
+                    let kek = concat_kdf(&shared_secret, &header.alg.to_string(), b"", b"", 16);
+                    KekAes128::new(GenericArray::from_slice(&kek))
+                        .unwrap_vec(&encrypted_key)
+                        .map_err(|_| JweDecryptionError::KeyDecryption)?
+                }
+
+                _ => {
+                    let kek = concat_kdf(&shared_secret, &header.alg.to_string(), b"", b"", 32);
+                    KekAes256::new(GenericArray::from_slice(&kek))
+                        .unwrap_vec(&encrypted_key)
+                        .map_err(|_| JweDecryptionError::KeyDecryption)?
+                }
+            }
+        }
+
+        alg => {
+            return Err(JweDecryptionError::UnsupportedAlgorithm { alg: alg.clone() });
+        }
+    };
+
+    let payload = enc.decrypt(&cek, encoded_header.as_bytes(), &content)?;
+
+    Ok((header, payload))
+}
+
+/// Generate an ephemeral key pair, and compute the shared secret with the
+/// given public key
+///
+/// Returns the shared secret and the public parameters of the ephemeral key.
+fn ecdh_ephemeral<C>(
+    rng: &mut impl CryptoRngCore,
+    params: &EcPublicParameters,
+) -> Result<(Vec<u8>, JsonWebKeyPublicParameters), elliptic_curve::Error>
+where
+    C: CurveArithmetic + JwkEcCurve,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    C::FieldBytesSize: ModulusSize + Unsigned,
+{
+    let peer = PublicKey::<C>::try_from(params)?;
+    let secret = EphemeralSecret::<C>::random(rng);
+    let shared_secret = secret.diffie_hellman(&peer);
+
+    Ok((
+        shared_secret.raw_secret_bytes().to_vec(),
+        secret.public_key().into(),
+    ))
+}
+
+/// Compute the shared secret between a static private key and the ephemeral
+/// public key of the sender
+fn ecdh_static<C>(
+    secret_key: &SecretKey<C>,
+    epk: &EcPublicParameters,
+) -> Result<Vec<u8>, elliptic_curve::Error>
+where
+    C: CurveArithmetic,
+    AffinePoint<C>: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    C::FieldBytesSize: ModulusSize + Unsigned,
+{
+    let peer = PublicKey::<C>::try_from(epk)?;
+    let shared_secret =
+        elliptic_curve::ecdh::diffie_hellman(secret_key.to_nonzero_scalar(), peer.as_affine());
+    Ok(shared_secret.raw_secret_bytes().to_vec())
+}
+
+/// Derive a key from the ECDH shared secret, with the Concat KDF defined in
+/// NIST SP 800-56A, as described in RFC 7518 section 4.6.2
+fn concat_kdf(
+    shared_secret: &[u8],
+    algorithm_id: &str,
+    apu: &[u8],
+    apv: &[u8],
+    key_len: usize,
+) -> Vec<u8> {
+    // Lengths are prefixed as 32-bit big-endian integers
+    fn length(data: &[u8]) -> [u8; 4] {
+        u32::try_from(data.len()).unwrap_or(u32::MAX).to_be_bytes()
+    }
+
+    let key_bits = u32::try_from(key_len * 8).unwrap_or(u32::MAX);
+    let mut output = Vec::with_capacity(key_len + 32);
+    let mut counter: u32 = 1;
+    while output.len() < key_len {
+        let digest = Sha256::new()
+            .chain_update(counter.to_be_bytes())
+            .chain_update(shared_secret)
+            .chain_update(length(algorithm_id.as_bytes()))
+            .chain_update(algorithm_id.as_bytes())
+            .chain_update(length(apu))
+            .chain_update(apu)
+            .chain_update(length(apv))
+            .chain_update(apv)
+            .chain_update(key_bits.to_be_bytes())
+            .finalize();
+        output.extend_from_slice(&digest);
+        counter += 1;
+    }
+
+    output.truncate(key_len);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_iana::jose::JsonWebSignatureAlg;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::jwk::JsonWebKeySet;
+
+    fn private_jwks() -> crate::jwk::PrivateJsonWebKeySet {
+        serde_json::from_str(include_str!("../tests/keys/jwks.priv.json")).unwrap()
+    }
+
+    fn rsa_key() -> PrivateJsonWebKey {
+        private_jwks()
+            .iter()
+            .find(|key| key.params().rsa().is_some())
+            .unwrap()
+            .clone()
+    }
+
+    fn ec_key() -> PrivateJsonWebKey {
+        private_jwks()
+            .iter()
+            .find(|key| {
+                key.params()
+                    .ec()
+                    .is_some_and(|params| params.crv == JsonWebKeyEcEllipticCurve::P256)
+            })
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_concat_kdf() {
+        // Test vector from RFC 7518 appendix C
+        let shared_secret = [
+            158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49,
+            110, 163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+        ];
+        let key = concat_kdf(&shared_secret, "A128GCM", b"Alice", b"Bob", 16);
+        assert_eq!(
+            Base64UrlUnpadded::encode_string(&key),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let cases = [
+            (rsa_key(), JsonWebEncryptionAlg::RsaOaep),
+            (rsa_key(), JsonWebEncryptionAlg::RsaOaep256),
+            (ec_key(), JsonWebEncryptionAlg::EcdhEs),
+            (ec_key(), JsonWebEncryptionAlg::EcdhEsA128Kw),
+            (ec_key(), JsonWebEncryptionAlg::EcdhEsA256Kw),
+        ];
+
+        for (private_key, alg) in cases {
+            let public_key = PublicJsonWebKey::try_from(private_key.clone()).unwrap();
+
+            for enc in [
+                JsonWebEncryptionEnc::A128CbcHs256,
+                JsonWebEncryptionEnc::A256Gcm,
+            ] {
+                let header = JsonWebEncryptionHeader::new(alg.clone(), enc.clone())
+                    .with_cty("JWT".to_owned());
+                let jwe = encrypt(&mut rng, header, &public_key, b"hello").unwrap();
+                assert_eq!(jwe.split('.').count(), 5);
+
+                let (header, payload) = decrypt(&jwe, &private_key).unwrap();
+                assert_eq!(header.alg(), &alg);
+                assert_eq!(header.enc(), &enc);
+                assert_eq!(header.cty(), Some("JWT"));
+                assert_eq!(header.kid(), public_key.kid());
+                assert_eq!(payload, b"hello");
+
+                // Tampering with the header is detected
+                let (_, rest) = jwe.split_once('.').unwrap();
+                let header = Base64UrlUnpadded::encode_string(
+                    &serde_json::to_vec(&header.with_typ("other".to_owned())).unwrap(),
+                );
+                assert!(decrypt(&format!("{header}.{rest}"), &private_key).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_encryption_key() {
+        let rsa_sig = PublicJsonWebKey::try_from(rsa_key())
+            .unwrap()
+            .with_use(JsonWebKeyUse::Sig)
+            .with_kid("rsa-sig");
+        let rsa_any = PublicJsonWebKey::try_from(rsa_key())
+            .unwrap()
+            .with_kid("rsa-any");
+        let rsa_enc = PublicJsonWebKey::try_from(rsa_key())
+            .unwrap()
+            .with_use(JsonWebKeyUse::Enc)
+            .with_kid("rsa-enc");
+        let rsa_ps256 = PublicJsonWebKey::try_from(rsa_key())
+            .unwrap()
+            .with_alg(JsonWebSignatureAlg::Ps256)
+            .with_kid("rsa-ps256");
+        let ec = PublicJsonWebKey::try_from(ec_key()).unwrap();
+
+        let jwks = JsonWebKeySet::new(vec![rsa_sig.clone(), rsa_any.clone(), ec.clone()]);
+        assert_eq!(
+            find_encryption_key(&jwks, &JsonWebEncryptionAlg::RsaOaep),
+            Some(&rsa_any)
+        );
+        assert_eq!(
+            find_encryption_key(&jwks, &JsonWebEncryptionAlg::EcdhEs),
+            Some(&ec)
+        );
+        assert_eq!(
+            find_encryption_key(&jwks, &JsonWebEncryptionAlg::A128Kw),
+            None
+        );
+
+        // Keys meant for encryption are preferred
+        let jwks = JsonWebKeySet::new(vec![rsa_any, rsa_enc.clone()]);
+        assert_eq!(
+            find_encryption_key(&jwks, &JsonWebEncryptionAlg::RsaOaep256),
+            Some(&rsa_enc)
+        );
+
+        // Keys with a different algorithm are skipped
+        let jwks = JsonWebKeySet::new(vec![rsa_sig, rsa_ps256]);
+        assert_eq!(
+            find_encryption_key(&jwks, &JsonWebEncryptionAlg::RsaOaep),
+            None
+        );
+    }
+}
@@ -33,7 +33,8 @@ pub(crate) mod public_parameters;
 
 use self::private_parameters::SymetricKeyError;
 pub use self::{
-    private_parameters::JsonWebKeyPrivateParameters, public_parameters::JsonWebKeyPublicParameters,
+    private_parameters::JsonWebKeyPrivateParameters,
+    public_parameters::{JsonWebKeyPublicParameters, OkpPublicParameters},
 };
 
 pub trait ParametersInfo {
@@ -43,7 +44,7 @@ pub trait ParametersInfo {
 
 /// An utilitary trait to figure out the [`JsonWebKeyEcEllipticCurve`] value for
 /// elliptic curves
-trait JwkEcCurve {
+pub(crate) trait JwkEcCurve {
     const CRV: JsonWebKeyEcEllipticCurve;
 }
 
@@ -556,4 +557,22 @@ mod tests {
         // 8th is P-521, but we don't support it yet
         keys.next().unwrap().params().ec().unwrap();
     }
+
+    #[test]
+    fn rfc7638_thumbprint() {
+        // Example from RFC 7638 section 3.1
+        let jwk = serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
+
+        let jwk: PublicJsonWebKey = serde_json::from_value(jwk).unwrap();
+        assert_eq!(
+            jwk.params().thumbprint_sha256(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
 }
@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use base64ct::{Base64UrlUnpadded, Encoding};
 use mas_iana::jose::{
     JsonWebKeyEcEllipticCurve, JsonWebKeyOkpEllipticCurve, JsonWebKeyType, JsonWebSignatureAlg,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use super::ParametersInfo;
 use crate::base64::Base64UrlNoPad;
@@ -58,6 +60,64 @@ impl JsonWebKeyPublicParameters {
             _ => None,
         }
     }
+
+    /// Compute the SHA-256 JWK Thumbprint of this key, as defined in RFC 7638
+    ///
+    /// The result is base64url-encoded, without padding.
+    #[must_use]
+    pub fn thumbprint_sha256(&self) -> String {
+        // The canonical form only has the required members of the key, in
+        // lexicographic order and without any whitespace
+        let canonical = match self {
+            Self::Rsa(params) => format!(
+                r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+                params.e.encode(),
+                params.n.encode(),
+            ),
+            Self::Ec(params) => format!(
+                r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+                params.crv,
+                params.x.encode(),
+                params.y.encode(),
+            ),
+            Self::Okp(params) => format!(
+                r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#,
+                params.crv,
+                params.x.encode(),
+            ),
+        };
+
+        let digest = Sha256::digest(canonical.as_bytes());
+        Base64UrlUnpadded::encode_string(&digest)
+    }
+
+    /// Decode a public key from a DER-encoded `SubjectPublicKeyInfo`, like
+    /// the one found in X.509 certificates
+    ///
+    /// Returns `None` if the key could not be decoded, or is of an unsupported
+    /// type.
+    #[must_use]
+    pub fn from_public_key_der(der: &[u8]) -> Option<Self> {
+        use rsa::pkcs8::DecodePublicKey;
+
+        if let Ok(key) = rsa::RsaPublicKey::from_public_key_der(der) {
+            return Some(key.into());
+        }
+
+        if let Ok(key) = p256::PublicKey::from_public_key_der(der) {
+            return Some(key.into());
+        }
+
+        if let Ok(key) = p384::PublicKey::from_public_key_der(der) {
+            return Some(key.into());
+        }
+
+        if let Ok(key) = k256::PublicKey::from_public_key_der(der) {
+            return Some(key.into());
+        }
+
+        None
+    }
 }
 
 impl ParametersInfo for JsonWebKeyPublicParameters {
@@ -308,3 +368,23 @@ mod ec_impls {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs8::EncodePublicKey;
+
+    use super::*;
+
+    #[test]
+    fn test_from_public_key_der() {
+        let key = p256::SecretKey::from_slice(&[0x42; 32])
+            .unwrap()
+            .public_key();
+        let der = key.to_public_key_der().unwrap();
+
+        let params = JsonWebKeyPublicParameters::from_public_key_der(der.as_bytes()).unwrap();
+        assert_eq!(params, JsonWebKeyPublicParameters::from(key));
+
+        assert!(JsonWebKeyPublicParameters::from_public_key_der(b"not a key").is_none());
+    }
+}
@@ -18,8 +18,11 @@
 mod base64;
 pub mod claims;
 pub mod constraints;
+pub mod dpop;
 pub mod jwa;
+pub mod jwe;
 pub mod jwk;
 pub mod jwt;
+pub mod logout;
 
 pub use self::base64::Base64;
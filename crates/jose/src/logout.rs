@@ -0,0 +1,225 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Utilities to build logout tokens, as defined in [OpenID Connect Back-Channel
+//! Logout]
+//!
+//! [OpenID Connect Back-Channel Logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use mas_iana::jose::JsonWebSignatureAlg;
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    CryptoRng, RngCore,
+};
+use serde_json::Value;
+use signature::{RandomizedSigner, SignatureEncoding};
+use thiserror::Error;
+
+use crate::{
+    claims::{self, ClaimError},
+    jwt::{JsonWebSignatureHeader, Jwt, JwtSignatureError},
+};
+
+/// The `typ` header value of logout tokens
+pub const LOGOUT_JWT_TYPE: &str = "logout+jwt";
+
+/// The event member which identifies a JWT as a logout token
+pub const BACKCHANNEL_LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+
+/// How long a logout token is valid for
+const DEFAULT_TTL: i64 = 2 * 60;
+
+#[derive(Debug, Error)]
+pub enum LogoutTokenError {
+    #[error("a logout token must have a subject or a session ID")]
+    MissingSubjectAndSession,
+
+    #[error(transparent)]
+    Claim(#[from] ClaimError),
+
+    #[error(transparent)]
+    Signature(#[from] JwtSignatureError),
+}
+
+/// A logout token, sent to a client to notify it that a session ended
+#[derive(Debug, Clone)]
+pub struct LogoutToken {
+    issuer: String,
+    audience: String,
+    issued_at: DateTime<Utc>,
+    ttl: Duration,
+    subject: Option<String>,
+    session_id: Option<String>,
+}
+
+impl LogoutToken {
+    /// Creates a logout token issued by `issuer` for the client `audience`
+    #[must_use]
+    pub fn new(
+        issuer: impl Into<String>,
+        audience: impl Into<String>,
+        issued_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            issuer: issuer.into(),
+            audience: audience.into(),
+            issued_at,
+            ttl: Duration::seconds(DEFAULT_TTL),
+            subject: None,
+            session_id: None,
+        }
+    }
+
+    /// Set the subject of the user who logged out
+    #[must_use]
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// Set the ID of the session which ended, as found in the `sid` claim of
+    /// ID tokens
+    #[must_use]
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Set how long the logout token is valid for
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sign the logout token with the given key
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if neither a subject nor a session ID were set, or if
+    /// the token could not be signed
+    pub fn sign_with_rng<R, K, S>(
+        self,
+        rng: &mut R,
+        alg: JsonWebSignatureAlg,
+        kid: Option<String>,
+        key: &K,
+    ) -> Result<Jwt<'static, HashMap<String, Value>>, LogoutTokenError>
+    where
+        R: RngCore + CryptoRng,
+        K: RandomizedSigner<S>,
+        S: SignatureEncoding,
+    {
+        if self.subject.is_none() && self.session_id.is_none() {
+            return Err(LogoutTokenError::MissingSubjectAndSession);
+        }
+
+        let jti = Alphanumeric.sample_string(rng, 32);
+
+        let mut claims = HashMap::new();
+        claims::ISS.insert(&mut claims, self.issuer)?;
+        claims::AUD.insert(&mut claims, self.audience)?;
+        claims::IAT.insert(&mut claims, self.issued_at)?;
+        claims::EXP.insert(&mut claims, self.issued_at + self.ttl)?;
+        claims::JTI.insert(&mut claims, jti)?;
+        claims::EVENTS.insert(
+            &mut claims,
+            HashMap::from([(
+                BACKCHANNEL_LOGOUT_EVENT.to_owned(),
+                Value::Object(serde_json::Map::new()),
+            )]),
+        )?;
+
+        if let Some(subject) = self.subject {
+            claims::SUB.insert(&mut claims, subject)?;
+        }
+
+        if let Some(session_id) = self.session_id {
+            claims::SID.insert(&mut claims, session_id)?;
+        }
+
+        let mut header = JsonWebSignatureHeader::new(alg).with_typ(LOGOUT_JWT_TYPE.to_owned());
+        if let Some(kid) = kid {
+            header = header.with_kid(kid);
+        }
+
+        Ok(Jwt::sign_with_rng(rng, header, claims, key)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::jwa::AsymmetricSigningKey;
+
+    #[test]
+    fn sign_logout_token() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let secret = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+        let key = AsymmetricSigningKey::es256(secret);
+
+        let jwt = LogoutToken::new("https://example.com/", "client", now)
+            .with_subject("subject")
+            .with_session_id("session")
+            .sign_with_rng(
+                &mut rng,
+                JsonWebSignatureAlg::Es256,
+                Some("kid".to_owned()),
+                &key,
+            )
+            .unwrap();
+
+        assert_eq!(jwt.header().typ(), Some(LOGOUT_JWT_TYPE));
+        assert_eq!(jwt.header().kid(), Some("kid"));
+
+        let claims = jwt.payload();
+        assert_eq!(claims["iss"], "https://example.com/");
+        assert_eq!(claims["aud"], "client");
+        assert_eq!(claims["sub"], "subject");
+        assert_eq!(claims["sid"], "session");
+        assert_eq!(claims["iat"], 1_700_000_000);
+        assert_eq!(claims["exp"], 1_700_000_000 + DEFAULT_TTL);
+        assert_eq!(
+            claims["events"],
+            serde_json::json!({ BACKCHANNEL_LOGOUT_EVENT: {} })
+        );
+        assert!(claims.contains_key("jti"));
+        assert!(!claims.contains_key("nonce"));
+    }
+
+    #[test]
+    fn reject_missing_subject_and_session() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(42);
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let secret = elliptic_curve::SecretKey::<p256::NistP256>::random(&mut rng);
+        let key = AsymmetricSigningKey::es256(secret);
+
+        let res = LogoutToken::new("https://example.com/", "client", now).sign_with_rng(
+            &mut rng,
+            JsonWebSignatureAlg::Es256,
+            None,
+            &key,
+        );
+        assert!(matches!(
+            res,
+            Err(LogoutTokenError::MissingSubjectAndSession)
+        ));
+    }
+}
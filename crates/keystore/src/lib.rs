@@ -16,12 +16,14 @@
 
 use std::{ops::Deref, sync::Arc};
 
+use chrono::{DateTime, Utc};
 use der::{zeroize::Zeroizing, Decode, Encode, EncodePem};
 use elliptic_curve::{pkcs8::EncodePrivateKey, sec1::ToEncodedPoint};
-use mas_iana::jose::{JsonWebKeyType, JsonWebSignatureAlg};
+use mas_iana::jose::{JsonWebKeyType, JsonWebKeyUse, JsonWebSignatureAlg};
 pub use mas_jose::jwk::{JsonWebKey, JsonWebKeySet};
 use mas_jose::{
-    jwa::{AsymmetricSigningKey, AsymmetricVerifyingKey},
+    constraints::{Constraint, ConstraintSet},
+    jwa::{AsymmetricSigningKey, AsymmetricVerifyingKey, SigningBackend},
     jwk::{JsonWebKeyPublicParameters, ParametersInfo, PublicJsonWebKeySet},
 };
 use pem_rfc7468::PemLabel;
@@ -32,10 +34,16 @@ use rsa::BigUint;
 use thiserror::Error;
 
 mod encrypter;
+mod pkcs11;
+mod request_signer;
 
 pub use aead;
 
-pub use self::encrypter::{DecryptError, Encrypter};
+pub use self::{
+    encrypter::{DecryptError, Encrypter},
+    pkcs11::Pkcs11Error,
+    request_signer::{RequestSigner, RequestSigningKey, SIGNATURE_HEADER},
+};
 
 /// Error type used when a key could not be loaded
 #[derive(Debug, Error)]
@@ -127,12 +135,39 @@ impl LoadError {
 
 /// A single private key
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PrivateKey {
     Rsa(Box<rsa::RsaPrivateKey>),
     EcP256(Box<elliptic_curve::SecretKey<p256::NistP256>>),
     EcP384(Box<elliptic_curve::SecretKey<p384::NistP384>>),
     EcK256(Box<elliptic_curve::SecretKey<k256::Secp256k1>>),
+    External(ExternalKey),
+}
+
+/// A private key held outside of the process, like in a hardware security
+/// module. Only its public part is known, and signing operations are delegated
+/// to a [`SigningBackend`].
+#[derive(Clone)]
+pub struct ExternalKey {
+    public: JsonWebKeyPublicParameters,
+    backend: Arc<dyn SigningBackend>,
+}
+
+impl std::fmt::Debug for ExternalKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExternalKey")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ExternalKey {
+    /// Create an external key out of its public parameters and the backend
+    /// holding the private part
+    #[must_use]
+    pub fn new(public: JsonWebKeyPublicParameters, backend: Arc<dyn SigningBackend>) -> Self {
+        Self { public, backend }
+    }
 }
 
 /// Error returned when the key can't be used for the requested algorithm
@@ -194,13 +229,15 @@ impl PrivateKey {
     ///
     /// # Errors
     ///
-    /// Returns an error if the encoding failed
+    /// Returns an error if the encoding failed, or if the key is held outside
+    /// of the process
     pub fn to_der(&self) -> Result<Zeroizing<Vec<u8>>, pkcs1::Error> {
         let der = match self {
             PrivateKey::Rsa(key) => key.to_pkcs1_der()?.to_bytes(),
             PrivateKey::EcP256(key) => to_sec1_der(key)?,
             PrivateKey::EcP384(key) => to_sec1_der(key)?,
             PrivateKey::EcK256(key) => to_sec1_der(key)?,
+            PrivateKey::External(_) => return Err(pkcs1::Error::Crypto),
         };
 
         Ok(der)
@@ -210,13 +247,15 @@ impl PrivateKey {
     ///
     /// # Errors
     ///
-    /// Returns an error if the encoding failed
+    /// Returns an error if the encoding failed, or if the key is held outside
+    /// of the process
     pub fn to_pkcs8_der(&self) -> Result<Zeroizing<Vec<u8>>, pkcs8::Error> {
         let der = match self {
             PrivateKey::Rsa(key) => key.to_pkcs8_der()?,
             PrivateKey::EcP256(key) => key.to_pkcs8_der()?,
             PrivateKey::EcP384(key) => key.to_pkcs8_der()?,
             PrivateKey::EcK256(key) => key.to_pkcs8_der()?,
+            PrivateKey::External(_) => return Err(pkcs8::Error::KeyMalformed),
         };
 
         Ok(der.to_bytes())
@@ -229,7 +268,8 @@ impl PrivateKey {
     ///
     /// # Errors
     ///
-    /// Returns an error if the encoding failed
+    /// Returns an error if the encoding failed, or if the key is held outside
+    /// of the process
     pub fn to_pem(
         &self,
         line_ending: pem_rfc7468::LineEnding,
@@ -239,6 +279,7 @@ impl PrivateKey {
             PrivateKey::EcP256(key) => to_sec1_pem(key, line_ending)?,
             PrivateKey::EcP384(key) => to_sec1_pem(key, line_ending)?,
             PrivateKey::EcK256(key) => to_sec1_pem(key, line_ending)?,
+            PrivateKey::External(_) => return Err(pkcs1::Error::Crypto),
         };
 
         Ok(pem)
@@ -452,6 +493,11 @@ impl PrivateKey {
                 AsymmetricVerifyingKey::es256k(key.public_key())
             }
 
+            (Self::External(key), alg) => {
+                AsymmetricVerifyingKey::from_jwk_and_alg(&key.public, alg)
+                    .map_err(|_| WrongAlgorithmError)?
+            }
+
             _ => return Err(WrongAlgorithmError),
         };
 
@@ -494,6 +540,10 @@ impl PrivateKey {
                 AsymmetricSigningKey::es256k(*key.clone())
             }
 
+            (Self::External(key), alg) if key.public.possible_algs().contains(alg) => {
+                AsymmetricSigningKey::external(alg.clone(), key.backend.clone())
+            }
+
             _ => return Err(WrongAlgorithmError),
         };
 
@@ -578,6 +628,7 @@ impl From<&PrivateKey> for JsonWebKeyPublicParameters {
             PrivateKey::EcP256(key) => key.public_key().into(),
             PrivateKey::EcP384(key) => key.public_key().into(),
             PrivateKey::EcK256(key) => key.public_key().into(),
+            PrivateKey::External(key) => key.public.clone(),
         }
     }
 }
@@ -589,6 +640,7 @@ impl ParametersInfo for PrivateKey {
             PrivateKey::EcP256(_) | PrivateKey::EcP384(_) | PrivateKey::EcK256(_) => {
                 JsonWebKeyType::Ec
             }
+            PrivateKey::External(key) => key.public.kty(),
         }
     }
 
@@ -605,16 +657,48 @@ impl ParametersInfo for PrivateKey {
             PrivateKey::EcP256(_) => &[JsonWebSignatureAlg::Es256],
             PrivateKey::EcP384(_) => &[JsonWebSignatureAlg::Es384],
             PrivateKey::EcK256(_) => &[JsonWebSignatureAlg::Es256K],
+            PrivateKey::External(key) => key.public.possible_algs(),
         }
     }
 }
 
+/// The period during which a key can be used for signing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyValidity {
+    /// The key should not be used for signing before this time
+    pub not_before: Option<DateTime<Utc>>,
+
+    /// The key should not be used for signing from this time on
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl KeyValidity {
+    /// Whether a key with this validity can be used for signing at the given
+    /// time
+    #[must_use]
+    pub fn contains(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.map_or(true, |not_before| not_before <= now)
+            && self.expires_at.map_or(true, |expires_at| now < expires_at)
+    }
+}
+
 /// A structure to store a list of [`PrivateKey`]. The keys are held in an
 /// [`Arc`] to ensure they are only loaded once in memory and allow cheap
 /// cloning
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Keystore {
+    /// The keys usable for signing, for each period between two key validity
+    /// boundaries, sorted by the start of the period. The first period has no
+    /// start.
+    periods: Arc<Vec<(Option<DateTime<Utc>>, JsonWebKeySet<PrivateKey>)>>,
     keys: Arc<JsonWebKeySet<PrivateKey>>,
+    published_only: Arc<JsonWebKeySet<PrivateKey>>,
+}
+
+impl Default for Keystore {
+    fn default() -> Self {
+        Self::new(JsonWebKeySet::default())
+    }
 }
 
 impl Keystore {
@@ -640,7 +724,104 @@ impl Keystore {
     #[must_use]
     pub fn new(keys: JsonWebKeySet<PrivateKey>) -> Self {
         let keys = Arc::new(keys);
-        Self { keys }
+        Self {
+            periods: Arc::new(vec![(None, JsonWebKeySet::clone(&keys))]),
+            keys,
+            published_only: Arc::default(),
+        }
+    }
+
+    /// Create a keystore out of a list of keys, each of them only being used
+    /// for signing during its validity period.
+    ///
+    /// All the keys are published in the public JWKS, regardless of their
+    /// validity, so that clients can know about them ahead of their activation
+    /// and still verify tokens signed with them after they expired.
+    #[must_use]
+    pub fn with_validity(keys: Vec<(JsonWebKey<PrivateKey>, KeyValidity)>) -> Self {
+        let mut boundaries: Vec<DateTime<Utc>> = keys
+            .iter()
+            .flat_map(|(_, validity)| [validity.not_before, validity.expires_at])
+            .flatten()
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        // Each period starts at a boundary, and the keys active during the whole
+        // period are the ones active at its start. Before the first boundary, only
+        // the keys without a `not_before` are active.
+        let active_at = |now: DateTime<Utc>| -> JsonWebKeySet<PrivateKey> {
+            keys.iter()
+                .filter(|(_, validity)| validity.contains(now))
+                .map(|(key, _)| key.clone())
+                .collect()
+        };
+        let first: JsonWebKeySet<PrivateKey> = keys
+            .iter()
+            .filter(|(_, validity)| validity.not_before.is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+        let periods = std::iter::once((None, first))
+            .chain(
+                boundaries
+                    .into_iter()
+                    .map(|start| (Some(start), active_at(start))),
+            )
+            .collect();
+
+        let keys = keys.into_iter().map(|(key, _)| key).collect();
+
+        Self {
+            periods: Arc::new(periods),
+            keys: Arc::new(keys),
+            published_only: Arc::default(),
+        }
+    }
+
+    /// Add keys which should be advertised in the public JWKS, but never used
+    /// for signing.
+    ///
+    /// This is used to publish keys ahead of their activation, so that clients
+    /// caching the JWKS already know about them when they start being used, as
+    /// well as keeping retired keys around so that previously signed tokens
+    /// can still be verified.
+    #[must_use]
+    pub fn with_published_only_keys(mut self, keys: JsonWebKeySet<PrivateKey>) -> Self {
+        self.published_only = Arc::new(keys);
+        self
+    }
+
+    /// Get the keys which can be used for signing at the given time
+    #[must_use]
+    pub fn keys_at(&self, now: DateTime<Utc>) -> &JsonWebKeySet<PrivateKey> {
+        let index = self
+            .periods
+            .partition_point(|(start, _)| start.map_or(true, |start| start <= now));
+        // The first period has no start, so the partition point is at least 1
+        &self.periods[index - 1].1
+    }
+
+    /// Find a key suitable for signing with the given algorithm, and get a
+    /// signer out of it, along with the key ID, if it has one
+    ///
+    /// Only keys valid at the current time are considered. Keys which match the
+    /// algorithm but can't actually be used with it are skipped. Returns `None`
+    /// if no suitable key was found.
+    #[must_use]
+    pub fn signer_for_alg(
+        &self,
+        alg: &JsonWebSignatureAlg,
+    ) -> Option<(Option<&str>, AsymmetricSigningKey)> {
+        let constraints =
+            ConstraintSet::new([Constraint::alg(alg), Constraint::use_(&JsonWebKeyUse::Sig)]);
+
+        self.keys_at(Utc::now())
+            .find_keys(&constraints)
+            .into_iter()
+            .find_map(|key| {
+                let signer = key.params().signing_key_for_alg(alg).ok()?;
+                Some((key.kid(), signer))
+            })
     }
 
     /// Get the public JSON Web Key Set for the keys stored in this [`Keystore`]
@@ -648,6 +829,7 @@ impl Keystore {
     pub fn public_jwks(&self) -> PublicJsonWebKeySet {
         self.keys
             .iter()
+            .chain(self.published_only.iter())
             .map(|key| {
                 key.cloned_map(|params: &PrivateKey| JsonWebKeyPublicParameters::from(params))
             })
@@ -658,7 +840,8 @@ impl Keystore {
 impl Deref for Keystore {
     type Target = JsonWebKeySet<PrivateKey>;
 
+    /// Get the keys which can be used for signing right now
     fn deref(&self) -> &Self::Target {
-        &self.keys
+        self.keys_at(Utc::now())
     }
 }
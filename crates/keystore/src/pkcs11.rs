@@ -0,0 +1,286 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keys held in a PKCS#11 token, like a hardware security module or a
+//! YubiHSM through its PKCS#11 module
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use cryptoki::{
+    context::{CInitializeArgs, Pkcs11},
+    mechanism::{
+        rsa::{PkcsMgfType, PkcsPssParams},
+        Mechanism, MechanismType,
+    },
+    object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle},
+    session::{Session, UserType},
+    types::AuthPin,
+};
+use der::{asn1::OctetStringRef, Decode};
+use elliptic_curve::PublicKey;
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::{jwa::SigningBackend, jwk::JsonWebKeyPublicParameters};
+use pkcs8::{AssociatedOid, ObjectIdentifier};
+use rsa::BigUint;
+use sha2::{Digest, Sha256, Sha384};
+use thiserror::Error;
+
+use crate::{ExternalKey, PrivateKey};
+
+/// Error type used when a key could not be loaded from a PKCS#11 token
+#[derive(Debug, Error)]
+pub enum Pkcs11Error {
+    #[error("Failed to call the PKCS#11 module")]
+    Pkcs11(#[from] cryptoki::error::Error),
+
+    #[error("Could not find a token labelled {label:?}")]
+    TokenNotFound { label: String },
+
+    #[error("Could not find a key labelled {label:?} in the token")]
+    KeyNotFound { label: String },
+
+    #[error("Unsupported key type {key_type}")]
+    UnsupportedKeyType { key_type: KeyType },
+
+    #[error("Unknown elliptic curve OID {oid}")]
+    UnknownEllipticCurveOid { oid: ObjectIdentifier },
+
+    #[error("Invalid public key parameters")]
+    InvalidPublicKey,
+}
+
+/// The PKCS#11 modules loaded so far, by path. A module can only be
+/// initialized once per process, so it is shared between the keys of the same
+/// module.
+fn module(path: &Path) -> Result<Pkcs11, Pkcs11Error> {
+    static MODULES: OnceLock<Mutex<HashMap<PathBuf, Pkcs11>>> = OnceLock::new();
+
+    let mut modules = MODULES.get_or_init(Mutex::default).lock().unwrap();
+    if let Some(module) = modules.get(path) {
+        return Ok(module.clone());
+    }
+
+    let module = Pkcs11::new(path)?;
+    module.initialize(CInitializeArgs::OsThreads)?;
+    modules.insert(path.to_owned(), module.clone());
+    Ok(module)
+}
+
+/// A private key held in a PKCS#11 token
+struct Pkcs11Backend {
+    /// Sessions can't be shared between threads, so signing operations are
+    /// serialized
+    session: Mutex<Session>,
+    key: ObjectHandle,
+}
+
+impl SigningBackend for Pkcs11Backend {
+    fn sign(&self, alg: &JsonWebSignatureAlg, msg: &[u8]) -> Result<Vec<u8>, signature::Error> {
+        // ECDSA is done on a digest computed locally, as the combined
+        // hash-and-sign mechanisms are not supported by all tokens
+        let (mechanism, data) = match alg {
+            JsonWebSignatureAlg::Rs256 => (Mechanism::Sha256RsaPkcs, msg.to_vec()),
+            JsonWebSignatureAlg::Rs384 => (Mechanism::Sha384RsaPkcs, msg.to_vec()),
+            JsonWebSignatureAlg::Rs512 => (Mechanism::Sha512RsaPkcs, msg.to_vec()),
+            JsonWebSignatureAlg::Ps256 => (
+                Mechanism::Sha256RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA256,
+                    mgf: PkcsMgfType::MGF1_SHA256,
+                    s_len: 32.into(),
+                }),
+                msg.to_vec(),
+            ),
+            JsonWebSignatureAlg::Ps384 => (
+                Mechanism::Sha384RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA384,
+                    mgf: PkcsMgfType::MGF1_SHA384,
+                    s_len: 48.into(),
+                }),
+                msg.to_vec(),
+            ),
+            JsonWebSignatureAlg::Ps512 => (
+                Mechanism::Sha512RsaPkcsPss(PkcsPssParams {
+                    hash_alg: MechanismType::SHA512,
+                    mgf: PkcsMgfType::MGF1_SHA512,
+                    s_len: 64.into(),
+                }),
+                msg.to_vec(),
+            ),
+            JsonWebSignatureAlg::Es256 | JsonWebSignatureAlg::Es256K => {
+                (Mechanism::Ecdsa, Sha256::digest(msg).to_vec())
+            }
+            JsonWebSignatureAlg::Es384 => (Mechanism::Ecdsa, Sha384::digest(msg).to_vec()),
+            _ => return Err(signature::Error::new()),
+        };
+
+        // CKM_ECDSA returns the raw concatenation of r and s, which is what JWS
+        // expects
+        let session = self.session.lock().unwrap();
+        session
+            .sign(&mechanism, self.key, &data)
+            .map_err(signature::Error::from_source)
+    }
+}
+
+/// Find a single object of the given class and label in the token
+fn find_object(
+    session: &Session,
+    class: ObjectClass,
+    label: &str,
+) -> Result<ObjectHandle, Pkcs11Error> {
+    session
+        .find_objects(&[
+            Attribute::Class(class),
+            Attribute::Label(label.as_bytes().to_vec()),
+        ])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Pkcs11Error::KeyNotFound {
+            label: label.to_owned(),
+        })
+}
+
+/// Read the public parameters of the key out of its public key object
+fn public_parameters(
+    session: &Session,
+    public_key: ObjectHandle,
+) -> Result<JsonWebKeyPublicParameters, Pkcs11Error> {
+    let attributes = session.get_attributes(
+        public_key,
+        &[
+            AttributeType::KeyType,
+            AttributeType::Modulus,
+            AttributeType::PublicExponent,
+            AttributeType::EcParams,
+            AttributeType::EcPoint,
+        ],
+    )?;
+
+    let mut key_type = None;
+    let mut modulus = None;
+    let mut public_exponent = None;
+    let mut ec_params = None;
+    let mut ec_point = None;
+    for attribute in attributes {
+        match attribute {
+            Attribute::KeyType(value) => key_type = Some(value),
+            Attribute::Modulus(value) => modulus = Some(value),
+            Attribute::PublicExponent(value) => public_exponent = Some(value),
+            Attribute::EcParams(value) => ec_params = Some(value),
+            Attribute::EcPoint(value) => ec_point = Some(value),
+            _ => {}
+        }
+    }
+
+    match key_type.ok_or(Pkcs11Error::InvalidPublicKey)? {
+        KeyType::RSA => {
+            let modulus = modulus.ok_or(Pkcs11Error::InvalidPublicKey)?;
+            let public_exponent = public_exponent.ok_or(Pkcs11Error::InvalidPublicKey)?;
+            let key = rsa::RsaPublicKey::new(
+                BigUint::from_bytes_be(&modulus),
+                BigUint::from_bytes_be(&public_exponent),
+            )
+            .map_err(|_| Pkcs11Error::InvalidPublicKey)?;
+            Ok(key.into())
+        }
+
+        KeyType::EC => {
+            let ec_params = ec_params.ok_or(Pkcs11Error::InvalidPublicKey)?;
+            let ec_point = ec_point.ok_or(Pkcs11Error::InvalidPublicKey)?;
+            let oid = ObjectIdentifier::from_der(&ec_params)
+                .map_err(|_| Pkcs11Error::InvalidPublicKey)?;
+
+            // The point should be wrapped in a DER octet string, but some
+            // tokens return it raw
+            let point = OctetStringRef::from_der(&ec_point)
+                .map_or(ec_point.as_slice(), OctetStringRef::as_bytes);
+
+            let params = match oid {
+                p256::NistP256::OID => PublicKey::<p256::NistP256>::from_sec1_bytes(point)
+                    .map_err(|_| Pkcs11Error::InvalidPublicKey)?
+                    .into(),
+                p384::NistP384::OID => PublicKey::<p384::NistP384>::from_sec1_bytes(point)
+                    .map_err(|_| Pkcs11Error::InvalidPublicKey)?
+                    .into(),
+                k256::Secp256k1::OID => PublicKey::<k256::Secp256k1>::from_sec1_bytes(point)
+                    .map_err(|_| Pkcs11Error::InvalidPublicKey)?
+                    .into(),
+                oid => return Err(Pkcs11Error::UnknownEllipticCurveOid { oid }),
+            };
+            Ok(params)
+        }
+
+        key_type => Err(Pkcs11Error::UnsupportedKeyType { key_type }),
+    }
+}
+
+impl PrivateKey {
+    /// Load a key held in a PKCS#11 token
+    ///
+    /// The private key never leaves the token: signing operations are
+    /// delegated to it. The token must also hold the matching public key, with
+    /// the same label.
+    ///
+    /// # Parameters
+    ///
+    /// * `module_path`: Path to the PKCS#11 module (shared library) to load
+    /// * `token_label`: Label of the token holding the key
+    /// * `key_label`: Label of the key in the token
+    /// * `pin`: The user PIN to log in to the token, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the module could not be loaded, if the token or the
+    /// key could not be found, or if the key is not supported
+    pub fn load_pkcs11(
+        module_path: &Path,
+        token_label: &str,
+        key_label: &str,
+        pin: Option<&str>,
+    ) -> Result<Self, Pkcs11Error> {
+        let pkcs11 = module(module_path)?;
+
+        let slot = pkcs11
+            .get_slots_with_token()?
+            .into_iter()
+            .find(|slot| {
+                pkcs11
+                    .get_token_info(*slot)
+                    .is_ok_and(|info| info.label().trim_end() == token_label)
+            })
+            .ok_or_else(|| Pkcs11Error::TokenNotFound {
+                label: token_label.to_owned(),
+            })?;
+
+        let session = pkcs11.open_ro_session(slot)?;
+        if let Some(pin) = pin {
+            session.login(UserType::User, Some(&AuthPin::new(pin.to_owned())))?;
+        }
+
+        let public_key = find_object(&session, ObjectClass::PUBLIC_KEY, key_label)?;
+        let public = public_parameters(&session, public_key)?;
+        let key = find_object(&session, ObjectClass::PRIVATE_KEY, key_label)?;
+
+        let backend = Pkcs11Backend {
+            session: Mutex::new(session),
+            key,
+        };
+
+        Ok(Self::External(ExternalKey::new(public, Arc::new(backend))))
+    }
+}
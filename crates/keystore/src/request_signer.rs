@@ -0,0 +1,341 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sign outbound HTTP requests with Ed25519 keys, so that their receivers can
+//! authenticate them using the published public keys, without any shared
+//! secret.
+//!
+//! The signature is carried in the [`SIGNATURE_HEADER`] header, formatted as
+//! `t=<timestamp>,kid=<key ID>,sig=<signature>`, where the signature is the
+//! base64url-encoded Ed25519 signature of the following payload:
+//!
+//! ```text
+//! <timestamp>\n<method>\n<path and query>\n<body>
+//! ```
+
+use std::sync::Arc;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{TimeZone, Utc};
+use der::Decode;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use mas_iana::jose::{JsonWebKeyOkpEllipticCurve, JsonWebKeyUse, JsonWebSignatureAlg};
+use mas_jose::{
+    jwk::{JsonWebKeyPublicParameters, OkpPublicParameters, PublicJsonWebKeySet},
+    Base64,
+};
+use pem_rfc7468::PemLabel;
+
+use crate::{JsonWebKey, KeyValidity, LoadError};
+
+/// Name of the header carrying the signature of a request
+pub const SIGNATURE_HEADER: &str = "x-mas-signature";
+
+/// An Ed25519 key used to sign outbound requests
+#[derive(Clone)]
+pub struct RequestSigningKey {
+    kid: String,
+    key: SigningKey,
+}
+
+impl std::fmt::Debug for RequestSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestSigningKey")
+            .field("kid", &self.kid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RequestSigningKey {
+    /// Create a signing key out of its 32-byte secret
+    #[must_use]
+    pub fn from_bytes(kid: impl Into<String>, secret: &[u8; 32]) -> Self {
+        Self {
+            kid: kid.into(),
+            key: SigningKey::from_bytes(secret),
+        }
+    }
+
+    /// Generate a new random signing key
+    #[must_use]
+    pub fn generate(kid: impl Into<String>, mut rng: impl rand::CryptoRng + rand::RngCore) -> Self {
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+        Self::from_bytes(kid, &secret)
+    }
+
+    /// Load an unencrypted PKCS#8 key, either PEM or DER encoded
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is encrypted, not an Ed25519 key, or could
+    /// not be decoded
+    pub fn load(kid: impl Into<String>, bytes: &[u8]) -> Result<Self, LoadError> {
+        let kid = kid.into();
+        if let Ok(pem) = std::str::from_utf8(bytes) {
+            match Self::load_pem(kid.clone(), pem) {
+                Ok(s) => return Ok(s),
+                // If there was an error loading the document as PEM, ignore it and continue by
+                // trying to load it as DER
+                Err(LoadError::Pem { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Self::load_der(kid, bytes)
+    }
+
+    /// Load an encrypted PKCS#8 key, either PEM or DER encoded, and decrypt
+    /// it with the given password
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not encrypted, could not be decrypted,
+    /// is not an Ed25519 key, or could not be decoded
+    pub fn load_encrypted(
+        kid: impl Into<String>,
+        bytes: &[u8],
+        password: impl AsRef<[u8]>,
+    ) -> Result<Self, LoadError> {
+        let kid = kid.into();
+        let der = match std::str::from_utf8(bytes)
+            .map(|pem| pem_rfc7468::decode_vec(pem.as_bytes()))
+        {
+            Ok(Ok((pkcs8::EncryptedPrivateKeyInfo::PEM_LABEL, doc))) => doc,
+            Ok(Ok((pkcs8::PrivateKeyInfo::PEM_LABEL, _))) => return Err(LoadError::Unencrypted),
+            Ok(Ok((label, _))) => {
+                return Err(LoadError::UnsupportedPemLabel {
+                    label: label.to_owned(),
+                })
+            }
+            // Not a PEM document, assume it is DER
+            Ok(Err(_)) | Err(_) => bytes.to_vec(),
+        };
+
+        let info = pkcs8::EncryptedPrivateKeyInfo::from_der(&der)?;
+        let decrypted = info.decrypt(password)?;
+        Self::load_der(kid, decrypted.as_bytes()).map_err(|inner| LoadError::InEncrypted {
+            inner: Box::new(inner),
+        })
+    }
+
+    /// Load an unencrypted PKCS#8 key from a PEM document
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document is not a valid PEM document, the key
+    /// is encrypted, not an Ed25519 key, or could not be decoded
+    pub fn load_pem(kid: impl Into<String>, pem: &str) -> Result<Self, LoadError> {
+        let (label, doc) = pem_rfc7468::decode_vec(pem.as_bytes())?;
+
+        match label {
+            pkcs8::PrivateKeyInfo::PEM_LABEL => Self::load_der(kid, &doc),
+            pkcs8::EncryptedPrivateKeyInfo::PEM_LABEL => Err(LoadError::Encrypted),
+            label => Err(LoadError::UnsupportedPemLabel {
+                label: label.to_owned(),
+            }),
+        }
+    }
+
+    /// Load an unencrypted PKCS#8 key from DER-encoded bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key is not an Ed25519 key, or could not be
+    /// decoded
+    pub fn load_der(kid: impl Into<String>, der: &[u8]) -> Result<Self, LoadError> {
+        let info = pkcs8::PrivateKeyInfo::from_der(der)?;
+        if info.algorithm.oid != ed25519_dalek::pkcs8::ALGORITHM_OID {
+            return Err(LoadError::UnknownAlgorithmOid {
+                oid: info.algorithm.oid,
+            });
+        }
+
+        let key = SigningKey::try_from(info)?;
+        Ok(Self {
+            kid: kid.into(),
+            key,
+        })
+    }
+
+    /// Get the ID of this key
+    #[must_use]
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    /// Encode this key as an unencrypted PKCS#8 PEM document
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key could not be encoded
+    pub fn to_pem(
+        &self,
+        line_ending: pem_rfc7468::LineEnding,
+    ) -> Result<der::zeroize::Zeroizing<String>, pkcs8::Error> {
+        use ed25519_dalek::pkcs8::EncodePrivateKey;
+        self.key.to_pkcs8_pem(line_ending)
+    }
+
+    fn public_jwk(&self) -> JsonWebKey<JsonWebKeyPublicParameters> {
+        let x = Base64::new(self.key.verifying_key().to_bytes().to_vec());
+        let parameters = JsonWebKeyPublicParameters::Okp(OkpPublicParameters::new(
+            JsonWebKeyOkpEllipticCurve::Ed25519,
+            x,
+        ));
+
+        JsonWebKey::new(parameters)
+            .with_kid(self.kid.clone())
+            .with_use(JsonWebKeyUse::Sig)
+            .with_alg(JsonWebSignatureAlg::EdDsa)
+    }
+}
+
+/// Build the payload which is signed for a request
+fn signed_payload(timestamp: u64, method: &str, path_and_query: &str, body: &[u8]) -> Vec<u8> {
+    let mut payload = format!("{timestamp}\n{method}\n{path_and_query}\n").into_bytes();
+    payload.extend_from_slice(body);
+    payload
+}
+
+/// Signs outbound requests with the first of its keys valid at the time of
+/// the request, and publishes all of them. The keys are held in an [`Arc`] to
+/// allow cheap cloning.
+#[derive(Clone, Default, Debug)]
+pub struct RequestSigner {
+    keys: Arc<Vec<(RequestSigningKey, KeyValidity)>>,
+    published_only: Arc<Vec<RequestSigningKey>>,
+}
+
+impl RequestSigner {
+    /// Create a request signer out of a list of keys. The first one is used
+    /// for signing, the others are only published.
+    #[must_use]
+    pub fn new(keys: Vec<RequestSigningKey>) -> Self {
+        Self::with_validity(
+            keys.into_iter()
+                .map(|key| (key, KeyValidity::default()))
+                .collect(),
+        )
+    }
+
+    /// Create a request signer out of a list of keys, each of them only being
+    /// used for signing requests made during its validity period. All of them
+    /// are published.
+    #[must_use]
+    pub fn with_validity(keys: Vec<(RequestSigningKey, KeyValidity)>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+            published_only: Arc::default(),
+        }
+    }
+
+    /// Add keys which should be published, but never used for signing.
+    ///
+    /// This is used to publish keys ahead of their activation, so that
+    /// receivers already know about them when they start being used, as well
+    /// as keeping retired keys around while requests signed with them might
+    /// still be in flight.
+    #[must_use]
+    pub fn with_published_only_keys(mut self, keys: Vec<RequestSigningKey>) -> Self {
+        self.published_only = Arc::new(keys);
+        self
+    }
+
+    /// Whether this signer has a key to sign requests with
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Sign a request, returning the value of the [`SIGNATURE_HEADER`] header
+    /// to add to it
+    ///
+    /// Returns `None` if there is no key valid at `timestamp` to sign with.
+    #[must_use]
+    pub fn sign(
+        &self,
+        timestamp: u64,
+        method: &str,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Option<String> {
+        let now = i64::try_from(timestamp)
+            .ok()
+            .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())?;
+        let (key, _) = self
+            .keys
+            .iter()
+            .find(|(_, validity)| validity.contains(now))?;
+        let payload = signed_payload(timestamp, method, path_and_query, body);
+        let signature = key.key.sign(&payload);
+        let signature = Base64UrlUnpadded::encode_string(&signature.to_bytes());
+        Some(format!("t={timestamp},kid={},sig={signature}", key.kid))
+    }
+
+    /// Verify the value of a [`SIGNATURE_HEADER`] header against the
+    /// published keys, returning the timestamp of the signature if it is
+    /// valid
+    ///
+    /// It is up to the caller to check that the timestamp is recent enough.
+    #[must_use]
+    pub fn verify(
+        &self,
+        header: &str,
+        method: &str,
+        path_and_query: &str,
+        body: &[u8],
+    ) -> Option<u64> {
+        let mut timestamp = None;
+        let mut kid = None;
+        let mut signature = None;
+        for part in header.split(',') {
+            match part.trim().split_once('=')? {
+                ("t", value) => timestamp = Some(value.parse().ok()?),
+                ("kid", value) => kid = Some(value),
+                ("sig", value) => signature = Some(value),
+                _ => {}
+            }
+        }
+
+        let (timestamp, kid, signature) = (timestamp?, kid?, signature?);
+        let signature = Base64UrlUnpadded::decode_vec(signature).ok()?;
+        let signature = Signature::from_slice(&signature).ok()?;
+        let key = self
+            .keys
+            .iter()
+            .map(|(key, _)| key)
+            .chain(self.published_only.iter())
+            .find(|key| key.kid == kid)?;
+
+        let payload = signed_payload(timestamp, method, path_and_query, body);
+        key.key
+            .verifying_key()
+            .verify(&payload, &signature)
+            .ok()
+            .map(|()| timestamp)
+    }
+
+    /// Get the public keys receivers can use to verify signatures, as a JSON
+    /// Web Key Set
+    #[must_use]
+    pub fn public_jwks(&self) -> PublicJsonWebKeySet {
+        self.keys
+            .iter()
+            .map(|(key, _)| key)
+            .chain(self.published_only.iter())
+            .map(RequestSigningKey::public_jwk)
+            .collect()
+    }
+}
@@ -12,13 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use der::pem::LineEnding;
 use mas_iana::jose::JsonWebSignatureAlg;
 use mas_jose::{
+    constraints::Constrainable,
     jwk::ParametersInfo,
     jwt::{JsonWebSignatureHeader, Jwt},
 };
-use mas_keystore::{JsonWebKey, JsonWebKeySet, Keystore, PrivateKey};
+use mas_keystore::{
+    JsonWebKey, JsonWebKeySet, KeyValidity, Keystore, PrivateKey, RequestSigner, RequestSigningKey,
+};
 use rand::SeedableRng;
 
 static PASSWORD: &str = "hunter2";
@@ -212,3 +216,242 @@ fn generate_sign_and_verify() {
         token.verify_with_jwks(&jwks).unwrap();
     }
 }
+
+#[test]
+fn published_only_keys() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let active = PrivateKey::generate_ec_p256(&mut rng);
+    let next = PrivateKey::generate_ec_p384(&mut rng);
+
+    let keyset = Keystore::new(JsonWebKeySet::new(vec![
+        JsonWebKey::new(active).with_kid("active")
+    ]))
+    .with_published_only_keys(JsonWebKeySet::new(vec![
+        JsonWebKey::new(next).with_kid("next")
+    ]));
+
+    // Both keys are advertised in the public JWKS
+    let jwks = keyset.public_jwks();
+    let kids: Vec<_> = jwks.iter().filter_map(|key| key.kid()).collect();
+    assert_eq!(kids, vec!["active", "next"]);
+
+    // But only the active one can be used for signing
+    assert!(keyset
+        .signing_key_for_algorithm(&JsonWebSignatureAlg::Es256)
+        .is_some());
+    assert!(keyset
+        .signing_key_for_algorithm(&JsonWebSignatureAlg::Es384)
+        .is_none());
+    assert_eq!(
+        keyset.available_signing_algorithms(),
+        vec![JsonWebSignatureAlg::Es256]
+    );
+}
+
+#[test]
+fn key_validity_switchover() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let old = PrivateKey::generate_ec_p256(&mut rng);
+    let new = PrivateKey::generate_ec_p256(&mut rng);
+    let far = PrivateKey::generate_ec_p256(&mut rng);
+
+    let rotation = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let far_future = rotation + Duration::days(365);
+
+    let keyset = Keystore::with_validity(vec![
+        (
+            JsonWebKey::new(old).with_kid("old"),
+            KeyValidity {
+                not_before: None,
+                expires_at: Some(rotation),
+            },
+        ),
+        (
+            JsonWebKey::new(new).with_kid("new"),
+            KeyValidity {
+                not_before: Some(rotation),
+                expires_at: None,
+            },
+        ),
+        (
+            JsonWebKey::new(far).with_kid("far"),
+            KeyValidity {
+                not_before: Some(far_future),
+                expires_at: None,
+            },
+        ),
+    ]);
+
+    let kids_at = |now: DateTime<Utc>| -> Vec<&str> {
+        keyset
+            .keys_at(now)
+            .iter()
+            .filter_map(|key| key.kid())
+            .collect()
+    };
+
+    // The old key is used until the rotation, and the new one from then on
+    assert_eq!(kids_at(rotation - Duration::days(1)), vec!["old"]);
+    assert_eq!(kids_at(rotation - Duration::seconds(1)), vec!["old"]);
+    assert_eq!(kids_at(rotation), vec!["new"]);
+    assert_eq!(kids_at(rotation + Duration::days(1)), vec!["new"]);
+    assert_eq!(kids_at(far_future), vec!["new", "far"]);
+
+    // All the keys are advertised in the public JWKS, regardless of their validity
+    let jwks = keyset.public_jwks();
+    let kids: Vec<_> = jwks.iter().filter_map(|key| key.kid()).collect();
+    assert_eq!(kids, vec!["old", "new", "far"]);
+}
+
+#[test]
+fn signer_for_alg() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+
+    let ec_p256 = PrivateKey::generate_ec_p256(&mut rng);
+    let ec_p384 = PrivateKey::generate_ec_p384(&mut rng);
+
+    let keyset = Keystore::new(JsonWebKeySet::new(vec![
+        JsonWebKey::new(ec_p256).with_kid("p256"),
+        JsonWebKey::new(ec_p384),
+    ]));
+
+    // The key ID is returned alongside the signer, if there is one
+    let (kid, _signer) = keyset.signer_for_alg(&JsonWebSignatureAlg::Es256).unwrap();
+    assert_eq!(kid, Some("p256"));
+
+    let (kid, _signer) = keyset.signer_for_alg(&JsonWebSignatureAlg::Es384).unwrap();
+    assert_eq!(kid, None);
+
+    // There is no key for that algorithm
+    assert!(keyset.signer_for_alg(&JsonWebSignatureAlg::Rs256).is_none());
+}
+
+#[test]
+fn load_request_signing_keys() {
+    let pem = include_bytes!("./keys/ed25519.pkcs8.pem");
+    let der = include_bytes!("./keys/ed25519.pkcs8.der");
+    let encrypted_pem = include_bytes!("./keys/ed25519.pkcs8.encrypted.pem");
+    let encrypted_der = include_bytes!("./keys/ed25519.pkcs8.encrypted.der");
+
+    let key = RequestSigningKey::load("pem", pem).unwrap();
+    let from_der = RequestSigningKey::load("der", der).unwrap();
+    let from_encrypted_pem =
+        RequestSigningKey::load_encrypted("encrypted-pem", encrypted_pem, PASSWORD).unwrap();
+    let from_encrypted_der =
+        RequestSigningKey::load_encrypted("encrypted-der", encrypted_der, PASSWORD).unwrap();
+
+    // All of them are the same key, so they produce the same signatures
+    let signature = RequestSigner::new(vec![key]).sign(0, "GET", "/", b"");
+    for key in [from_der, from_encrypted_pem, from_encrypted_der] {
+        let kid = key.kid().to_owned();
+        let other = RequestSigner::new(vec![key]).sign(0, "GET", "/", b"");
+        assert_eq!(
+            signature
+                .as_deref()
+                .map(|s| s.replace("kid=pem", &format!("kid={kid}"))),
+            other
+        );
+    }
+
+    // Other kind of keys are rejected
+    let ec = include_bytes!("./keys/ec-p256.pkcs8.pem");
+    assert!(RequestSigningKey::load("ec", ec).is_err());
+    assert!(RequestSigningKey::load_encrypted("pem", pem, PASSWORD)
+        .unwrap_err()
+        .is_unencrypted());
+}
+
+#[test]
+fn request_signer() {
+    let rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let mut rng2 = rand_chacha::ChaCha8Rng::seed_from_u64(43);
+    let active = RequestSigningKey::generate("active", rng);
+    let retired = RequestSigningKey::generate("retired", &mut rng2);
+    let unknown = RequestSigningKey::generate("unknown", &mut rng2);
+
+    // Without keys, nothing is signed
+    let signer = RequestSigner::default();
+    assert!(!signer.is_enabled());
+    assert!(signer.sign(1_700_000_000, "POST", "/hook", b"{}").is_none());
+
+    let signer = RequestSigner::new(vec![active]).with_published_only_keys(vec![retired.clone()]);
+    assert!(signer.is_enabled());
+
+    // Both keys are advertised, as Ed25519 signing keys
+    let jwks = signer.public_jwks();
+    let kids: Vec<_> = jwks.iter().filter_map(|key| key.kid()).collect();
+    assert_eq!(kids, vec!["active", "retired"]);
+    assert!(jwks
+        .iter()
+        .all(|key| key.alg() == Some(&JsonWebSignatureAlg::EdDsa)));
+
+    let header = signer
+        .sign(1_700_000_000, "POST", "/hook?a=b", b"{}")
+        .unwrap();
+    assert!(header.starts_with("t=1700000000,kid=active,sig="));
+    assert_eq!(
+        signer.verify(&header, "POST", "/hook?a=b", b"{}"),
+        Some(1_700_000_000)
+    );
+
+    // Any change in the request invalidates the signature
+    assert!(signer.verify(&header, "PUT", "/hook?a=b", b"{}").is_none());
+    assert!(signer.verify(&header, "POST", "/hook", b"{}").is_none());
+    assert!(signer
+        .verify(&header, "POST", "/hook?a=b", b"{ }")
+        .is_none());
+    let tampered = header.replace("t=1700000000", "t=1700000001");
+    assert!(signer
+        .verify(&tampered, "POST", "/hook?a=b", b"{}")
+        .is_none());
+
+    // Signatures made with a retired key are still accepted, but not the ones
+    // made with keys which are not published
+    let header = RequestSigner::new(vec![retired])
+        .sign(1_700_000_000, "POST", "/hook", b"")
+        .unwrap();
+    assert!(signer.verify(&header, "POST", "/hook", b"").is_some());
+    let header = RequestSigner::new(vec![unknown])
+        .sign(1_700_000_000, "POST", "/hook", b"")
+        .unwrap();
+    assert!(signer.verify(&header, "POST", "/hook", b"").is_none());
+}
+
+#[test]
+fn request_signer_validity() {
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let old = RequestSigningKey::generate("old", &mut rng);
+    let new = RequestSigningKey::generate("new", &mut rng);
+
+    let rotation = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let signer = RequestSigner::with_validity(vec![
+        (
+            old,
+            KeyValidity {
+                not_before: None,
+                expires_at: Some(rotation),
+            },
+        ),
+        (
+            new,
+            KeyValidity {
+                not_before: Some(rotation),
+                expires_at: None,
+            },
+        ),
+    ]);
+
+    // The key is picked based on the timestamp of the request
+    let timestamp = u64::try_from(rotation.timestamp()).unwrap();
+    let header = signer.sign(timestamp - 1, "POST", "/hook", b"").unwrap();
+    assert!(header.contains(",kid=old,"));
+    let header = signer.sign(timestamp, "POST", "/hook", b"").unwrap();
+    assert!(header.contains(",kid=new,"));
+
+    // Both keys are published
+    let jwks = signer.public_jwks();
+    let kids: Vec<_> = jwks.iter().filter_map(|key| key.kid()).collect();
+    assert_eq!(kids, vec!["old", "new"]);
+}
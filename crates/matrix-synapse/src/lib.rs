@@ -12,21 +12,69 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use http::{header::AUTHORIZATION, request::Builder, Method, Request, StatusCode};
+use std::{
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use http::{
+    header::AUTHORIZATION, request::Builder, uri::PathAndQuery, HeaderMap, HeaderValue, Method,
+    Request, StatusCode,
+};
 use mas_axum_utils::http_client_factory::HttpClientFactory;
-use mas_http::{EmptyBody, HttpServiceExt};
-use mas_matrix::{HomeserverConnection, MatrixUser, ProvisionRequest};
+use mas_http::HttpServiceExt;
+use mas_keystore::{RequestSigner, SIGNATURE_HEADER};
+use mas_matrix::{CircuitBreaker, HomeserverConnection, MatrixUser, ProvisionRequest};
 use serde::{Deserialize, Serialize};
 use tower::{Service, ServiceExt};
 use url::Url;
 
 static SYNAPSE_AUTH_PROVIDER: &str = "oauth-delegated";
 
+/// The error of a single attempt at calling the homeserver
+enum CallError {
+    /// The homeserver could not be reached, timed out, or failed to handle
+    /// the request: the call can be retried
+    Transient(anyhow::Error),
+
+    /// The homeserver handled the request, but not in the way we expected:
+    /// retrying won't help
+    Permanent(anyhow::Error),
+}
+
+impl CallError {
+    fn transient(error: impl Into<anyhow::Error>) -> Self {
+        Self::Transient(error.into())
+    }
+
+    fn permanent(error: impl Into<anyhow::Error>) -> Self {
+        Self::Permanent(error.into())
+    }
+
+    /// Classify a response with an unexpected status code
+    fn unexpected_status(message: &str, status: StatusCode) -> Self {
+        let error = anyhow::anyhow!("{message}: {status}");
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Self::Transient(error)
+        } else {
+            Self::Permanent(error)
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SynapseConnection {
     homeserver: String,
     endpoint: Url,
     access_token: String,
     http_client_factory: HttpClientFactory,
+    request_timeout: Duration,
+    max_retries: u32,
+    retry_backoff: Duration,
+    circuit_breaker: CircuitBreaker,
+    request_signer: RequestSigner,
+    headers: HeaderMap,
 }
 
 impl SynapseConnection {
@@ -42,18 +90,163 @@ impl SynapseConnection {
             endpoint,
             access_token,
             http_client_factory,
+            request_timeout: Duration::from_secs(10),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(500),
+            circuit_breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            request_signer: RequestSigner::default(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Set the signer used to sign the requests made to the homeserver, so
+    /// that it can check they come from this service
+    #[must_use]
+    pub fn with_request_signer(mut self, request_signer: RequestSigner) -> Self {
+        self.request_signer = request_signer;
+        self
+    }
+
+    /// Set extra headers to send on every call to the homeserver, for example
+    /// when it sits behind an authenticating proxy
+    #[must_use]
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Set how long a single call to the homeserver can take
+    #[must_use]
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Set how many times a failed call is retried, and how long to wait
+    /// before the first retry. The wait doubles after each retry.
+    #[must_use]
+    pub fn with_retries(mut self, max_retries: u32, retry_backoff: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Set the circuit breaker used to stop calling the homeserver when it is
+    /// down
+    #[must_use]
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// Get the circuit breaker used to stop calling the homeserver when it is
+    /// down
+    #[must_use]
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.circuit_breaker
+    }
+
+    /// Call the homeserver, retrying transient failures, timing out each
+    /// attempt and going through the circuit breaker
+    async fn call<T, F, Fut>(&self, attempt: F) -> Result<T, anyhow::Error>
+    where
+        F: Fn() -> Fut + Send + Sync,
+        Fut: Future<Output = Result<T, CallError>> + Send,
+    {
+        let mut retries = 0;
+        loop {
+            if !self.circuit_breaker.allow() {
+                return Err(anyhow::anyhow!(
+                    "Not calling Synapse, as it failed too many times recently"
+                ));
+            }
+
+            let res = tokio::time::timeout(self.request_timeout, attempt())
+                .await
+                .unwrap_or_else(|_| {
+                    Err(CallError::Transient(anyhow::anyhow!(
+                        "Call to Synapse timed out"
+                    )))
+                });
+
+            let error = match res {
+                Ok(value) => {
+                    self.circuit_breaker.record_success();
+                    return Ok(value);
+                }
+
+                // Synapse answered, so it is up
+                Err(CallError::Permanent(error)) => {
+                    self.circuit_breaker.record_success();
+                    return Err(error);
+                }
+
+                Err(CallError::Transient(error)) => {
+                    self.circuit_breaker.record_failure();
+                    error
+                }
+            };
+
+            if retries >= self.max_retries {
+                return Err(error);
+            }
+
+            let backoff = self.retry_backoff * 2_u32.saturating_pow(retries);
+            tracing::warn!(
+                error = &*error as &dyn std::error::Error,
+                retries,
+                "Call to Synapse failed, retrying in {backoff:?}"
+            );
+            tokio::time::sleep(backoff).await;
+            retries += 1;
+        }
+    }
+
+    /// Get a function which adds a signature to a request, if a request signing
+    /// key is configured. It is meant to be applied once the body of the
+    /// request is serialized.
+    fn sign(&self) -> impl Fn(Request<Bytes>) -> Request<Bytes> + Clone {
+        let request_signer = self.request_signer.clone();
+        move |mut request: Request<Bytes>| {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default();
+            let path_and_query = request
+                .uri()
+                .path_and_query()
+                .map_or("/", PathAndQuery::as_str);
+
+            let signature = request_signer
+                .sign(
+                    timestamp,
+                    request.method().as_str(),
+                    path_and_query,
+                    request.body(),
+                )
+                .and_then(|signature| HeaderValue::try_from(signature).ok());
+
+            if let Some(signature) = signature {
+                request.headers_mut().insert(SIGNATURE_HEADER, signature);
+            }
+
+            request
         }
     }
 
     fn builder(&self, url: &str) -> Builder {
-        Request::builder()
-            .uri(
-                self.endpoint
-                    .join(url)
-                    .map(String::from)
-                    .unwrap_or_default(),
-            )
-            .header(AUTHORIZATION, format!("Bearer {}", self.access_token))
+        let mut builder = Request::builder().uri(
+            self.endpoint
+                .join(url)
+                .map(String::from)
+                .unwrap_or_default(),
+        );
+
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(self.headers.clone());
+        }
+
+        builder.header(AUTHORIZATION, format!("Bearer {}", self.access_token))
     }
 
     #[must_use]
@@ -75,6 +268,56 @@ impl SynapseConnection {
     pub fn delete(&self, url: &str) -> Builder {
         self.builder(url).method(Method::DELETE)
     }
+
+    /// Check that the homeserver can be reached, and accepts the shared
+    /// secret on its admin API
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the homeserver could not be reached, or refused the
+    /// shared secret
+    #[tracing::instrument(
+        name = "homeserver.check",
+        skip_all,
+        fields(matrix.homeserver = self.homeserver),
+        err(Display),
+    )]
+    pub async fn check(&self) -> Result<(), anyhow::Error> {
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.check")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .response_body_to_bytes();
+
+            let request = self
+                .get("_synapse/admin/v1/username_available?username=mas")
+                .body(Bytes::new())
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            match response.status() {
+                // Whether the username is available or not, the secret was accepted
+                StatusCode::OK | StatusCode::BAD_REQUEST => Ok(()),
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(CallError::permanent(
+                    anyhow::anyhow!("Synapse refused the shared secret: {}", response.status()),
+                )),
+                code => Err(CallError::unexpected_status(
+                    "Failed to check the connection to Synapse",
+                    code,
+                )),
+            }
+        })
+        .await
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -120,11 +363,21 @@ struct SynapseDevice<'a> {
     device_id: &'a str,
 }
 
+#[derive(Serialize)]
+struct SynapseUpdateDeviceRequest<'a> {
+    display_name: &'a str,
+}
+
 #[derive(Serialize)]
 struct SetDisplayNameRequest<'a> {
     displayname: &'a str,
 }
 
+#[derive(Serialize)]
+struct SetAvatarUrlRequest<'a> {
+    avatar_url: &'a str,
+}
+
 #[derive(Serialize)]
 struct SynapseDeactivateUserRequest {
     erase: bool,
@@ -151,28 +404,43 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn query_user(&self, mxid: &str) -> Result<MatrixUser, Self::Error> {
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.query_user")
-            .response_body_to_bytes()
-            .json_response();
-
-        let request = self
-            .get(&format!("_synapse/admin/v2/users/{mxid}"))
-            .body(EmptyBody::new())?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::OK {
-            return Err(anyhow::anyhow!("Failed to query user from Synapse"));
-        }
-
-        let body: SynapseUser = response.into_body();
-
-        Ok(MatrixUser {
-            displayname: body.display_name,
-            avatar_url: body.avatar_url,
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.query_user")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .response_body_to_bytes()
+                .json_response();
+
+            let request = self
+                .get(&format!("_synapse/admin/v2/users/{mxid}"))
+                .body(Bytes::new())
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to query user from Synapse",
+                    response.status(),
+                ));
+            }
+
+            let body: SynapseUser = response.into_body();
+
+            Ok(MatrixUser {
+                displayname: body.display_name,
+                avatar_url: body.avatar_url,
+            })
         })
+        .await
     }
 
     #[tracing::instrument(
@@ -214,29 +482,41 @@ impl HomeserverConnection for SynapseConnection {
                 );
             });
 
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.provision_user")
-            .request_bytes_to_body()
-            .json_request();
-
-        let request = self
-            .put(&format!(
-                "_synapse/admin/v2/users/{mxid}",
-                mxid = request.mxid()
-            ))
-            .body(body)?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        match response.status() {
-            StatusCode::CREATED => Ok(true),
-            StatusCode::OK => Ok(false),
-            code => Err(anyhow::anyhow!(
-                "Failed to provision user in Synapse: {}",
-                code
-            )),
-        }
+        let body = &body;
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.provision_user")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .put(&format!(
+                    "_synapse/admin/v2/users/{mxid}",
+                    mxid = request.mxid()
+                ))
+                .body(body)
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            match response.status() {
+                StatusCode::CREATED => Ok(true),
+                StatusCode::OK => Ok(false),
+                code => Err(CallError::unexpected_status(
+                    "Failed to provision user in Synapse",
+                    code,
+                )),
+            }
+        })
+        .await
     }
 
     #[tracing::instrument(
@@ -250,23 +530,88 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn create_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.create_device")
-            .request_bytes_to_body()
-            .json_request();
-
-        let request = self
-            .post(&format!("_synapse/admin/v2/users/{mxid}/devices"))
-            .body(SynapseDevice { device_id })?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::CREATED {
-            return Err(anyhow::anyhow!("Failed to create device in Synapse"));
-        }
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.create_device")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .post(&format!("_synapse/admin/v2/users/{mxid}/devices"))
+                .body(SynapseDevice { device_id })
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::CREATED {
+                return Err(CallError::unexpected_status(
+                    "Failed to create device in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
+    }
 
-        Ok(())
+    #[tracing::instrument(
+        name = "homeserver.update_device_display_name",
+        skip_all,
+        fields(
+            matrix.homeserver = self.homeserver,
+            matrix.mxid = mxid,
+            matrix.device_id = device_id,
+        ),
+        err(Display),
+    )]
+    async fn update_device_display_name(
+        &self,
+        mxid: &str,
+        device_id: &str,
+        display_name: &str,
+    ) -> Result<(), Self::Error> {
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.update_device_display_name")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .put(&format!(
+                    "_synapse/admin/v2/users/{mxid}/devices/{device_id}"
+                ))
+                .body(SynapseUpdateDeviceRequest { display_name })
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to update device display name in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
     }
 
     #[tracing::instrument(
@@ -280,21 +625,113 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn delete_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
-        let mut client = self.http_client_factory.client("homeserver.delete_device");
-
-        let request = self
-            .delete(&format!(
-                "_synapse/admin/v2/users/{mxid}/devices/{device_id}"
-            ))
-            .body(EmptyBody::new())?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::OK {
-            return Err(anyhow::anyhow!("Failed to delete device in Synapse"));
-        }
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.delete_device")
+                .request_bytes_to_body()
+                .map_request(self.sign());
+
+            let request = self
+                .delete(&format!(
+                    "_synapse/admin/v2/users/{mxid}/devices/{device_id}"
+                ))
+                .body(Bytes::new())
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to delete device in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
+    }
 
-        Ok(())
+    #[tracing::instrument(
+        name = "homeserver.replace_device",
+        skip_all,
+        fields(
+            matrix.homeserver = self.homeserver,
+            matrix.mxid = mxid,
+            matrix.device_id = device_id,
+        ),
+        err(Display),
+    )]
+    async fn replace_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
+        // Both steps are retried together, so that a transient failure never
+        // leaves the device deleted without being created again
+        self.call(move || async move {
+            let mut delete_client = self
+                .http_client_factory
+                .client("homeserver.replace_device")
+                .request_bytes_to_body()
+                .map_request(self.sign());
+
+            let request = self
+                .delete(&format!(
+                    "_synapse/admin/v2/users/{mxid}/devices/{device_id}"
+                ))
+                .body(Bytes::new())
+                .map_err(CallError::permanent)?;
+
+            let response = delete_client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            // Synapse also answers with a 200 if the device didn't exist
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to delete device in Synapse",
+                    response.status(),
+                ));
+            }
+
+            let mut create_client = self
+                .http_client_factory
+                .client("homeserver.replace_device")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .post(&format!("_synapse/admin/v2/users/{mxid}/devices"))
+                .body(SynapseDevice { device_id })
+                .map_err(CallError::permanent)?;
+
+            let response = create_client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::CREATED {
+                return Err(CallError::unexpected_status(
+                    "Failed to create device in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
     }
 
     #[tracing::instrument(
@@ -308,23 +745,37 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn delete_user(&self, mxid: &str, erase: bool) -> Result<(), Self::Error> {
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.delete_user")
-            .request_bytes_to_body()
-            .json_request();
-
-        let request = self
-            .post(&format!("_synapse/admin/v1/deactivate/{mxid}"))
-            .body(SynapseDeactivateUserRequest { erase })?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::OK {
-            return Err(anyhow::anyhow!("Failed to delete user in Synapse"));
-        }
-
-        Ok(())
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.delete_user")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .post(&format!("_synapse/admin/v1/deactivate/{mxid}"))
+                .body(SynapseDeactivateUserRequest { erase })
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to delete user in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
     }
 
     #[tracing::instrument(
@@ -338,23 +789,37 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn set_displayname(&self, mxid: &str, displayname: &str) -> Result<(), Self::Error> {
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.set_displayname")
-            .request_bytes_to_body()
-            .json_request();
-
-        let request = self
-            .put(&format!("_matrix/client/v3/profile/{mxid}/displayname"))
-            .body(SetDisplayNameRequest { displayname })?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::OK {
-            return Err(anyhow::anyhow!("Failed to set displayname in Synapse"));
-        }
-
-        Ok(())
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.set_displayname")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .put(&format!("_matrix/client/v3/profile/{mxid}/displayname"))
+                .body(SetDisplayNameRequest { displayname })
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to set displayname in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
     }
 
     #[tracing::instrument(
@@ -370,6 +835,63 @@ impl HomeserverConnection for SynapseConnection {
         self.set_displayname(mxid, "").await
     }
 
+    #[tracing::instrument(
+        name = "homeserver.set_avatar_url",
+        skip_all,
+        fields(
+            matrix.homeserver = self.homeserver,
+            matrix.mxid = mxid,
+            matrix.avatar_url = avatar_url,
+        ),
+        err(Display),
+    )]
+    async fn set_avatar_url(&self, mxid: &str, avatar_url: &str) -> Result<(), Self::Error> {
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.set_avatar_url")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .put(&format!("_matrix/client/v3/profile/{mxid}/avatar_url"))
+                .body(SetAvatarUrlRequest { avatar_url })
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to set avatar in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    #[tracing::instrument(
+        name = "homeserver.unset_avatar_url",
+        skip_all,
+        fields(
+            matrix.homeserver = self.homeserver,
+            matrix.mxid = mxid,
+        ),
+        err(Display),
+    )]
+    async fn unset_avatar_url(&self, mxid: &str) -> Result<(), Self::Error> {
+        self.set_avatar_url(mxid, "").await
+    }
+
     #[tracing::instrument(
         name = "homeserver.allow_cross_signing_reset",
         skip_all,
@@ -380,26 +902,38 @@ impl HomeserverConnection for SynapseConnection {
         err(Display),
     )]
     async fn allow_cross_signing_reset(&self, mxid: &str) -> Result<(), Self::Error> {
-        let mut client = self
-            .http_client_factory
-            .client("homeserver.allow_cross_signing_reset")
-            .request_bytes_to_body()
-            .json_request();
-
-        let request = self
-            .post(&format!(
-                "_synapse/admin/v1/users/{mxid}/_allow_cross_signing_replacement_without_uia"
-            ))
-            .body(SynapseAllowCrossSigningResetRequest {})?;
-
-        let response = client.ready().await?.call(request).await?;
-
-        if response.status() != StatusCode::OK {
-            return Err(anyhow::anyhow!(
-                "Failed to allow cross signing reset in Synapse"
-            ));
-        }
-
-        Ok(())
+        self.call(move || async move {
+            let mut client = self
+                .http_client_factory
+                .client("homeserver.allow_cross_signing_reset")
+                .request_bytes_to_body()
+                .map_request(self.sign())
+                .json_request();
+
+            let request = self
+                .post(&format!(
+                    "_synapse/admin/v1/users/{mxid}/_allow_cross_signing_replacement_without_uia"
+                ))
+                .body(SynapseAllowCrossSigningResetRequest {})
+                .map_err(CallError::permanent)?;
+
+            let response = client
+                .ready()
+                .await
+                .map_err(CallError::transient)?
+                .call(request)
+                .await
+                .map_err(CallError::transient)?;
+
+            if response.status() != StatusCode::OK {
+                return Err(CallError::unexpected_status(
+                    "Failed to allow cross signing reset in Synapse",
+                    response.status(),
+                ));
+            }
+
+            Ok(())
+        })
+        .await
     }
 }
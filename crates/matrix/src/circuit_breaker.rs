@@ -0,0 +1,166 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Calls go through, and we count how many failed in a row
+    Closed { consecutive_failures: u32 },
+
+    /// Calls are rejected straight away, until the reset timeout elapsed
+    Open { since: Instant },
+
+    /// The reset timeout elapsed, and a single call is let through to check
+    /// whether the homeserver is back
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Inner {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<State>,
+}
+
+/// A circuit breaker, to stop calling the homeserver for a while after too
+/// many calls failed in a row.
+///
+/// This is cheap to clone, and clones share their state.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    inner: Arc<Inner>,
+}
+
+impl CircuitBreaker {
+    /// Create a new [`CircuitBreaker`], which opens after `failure_threshold`
+    /// calls failed in a row, and lets a call through again after
+    /// `reset_timeout`
+    #[must_use]
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                failure_threshold: failure_threshold.max(1),
+                reset_timeout,
+                state: Mutex::new(State::Closed {
+                    consecutive_failures: 0,
+                }),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // The state is always left consistent, so it is fine to ignore poisoning
+        self.inner
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Check whether a call to the homeserver should be attempted
+    #[must_use]
+    pub fn allow(&self) -> bool {
+        let mut state = self.state();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { since } if since.elapsed() >= self.inner.reset_timeout => {
+                *state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => false,
+        }
+    }
+
+    /// Record that a call to the homeserver went through
+    pub fn record_success(&self) {
+        *self.state() = State::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    /// Record that a call to the homeserver failed
+    pub fn record_failure(&self) {
+        let mut state = self.state();
+        *state = match *state {
+            State::Closed {
+                consecutive_failures,
+            } if consecutive_failures + 1 < self.inner.failure_threshold => State::Closed {
+                consecutive_failures: consecutive_failures + 1,
+            },
+            State::Closed { .. } | State::HalfOpen => State::Open {
+                since: Instant::now(),
+            },
+            // Calls which were started before the circuit opened don't extend it
+            open @ State::Open { .. } => open,
+        };
+    }
+
+    /// Whether the circuit is currently open, meaning that calls to the
+    /// homeserver are not attempted
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        !matches!(*self.state(), State::Closed { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+
+        // A success resets the count
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn half_open_after_reset_timeout() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        // Only one call goes through once the timeout elapsed
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        // If it fails, the circuit opens again
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        // If it succeeds, the circuit closes
+        assert!(breaker.allow());
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+        assert!(breaker.allow());
+    }
+}
@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod circuit_breaker;
 mod mock;
 
-pub use self::mock::HomeserverConnection as MockHomeserverConnection;
+pub use self::{
+    circuit_breaker::CircuitBreaker,
+    mock::{Call as MockHomeserverCall, HomeserverConnection as MockHomeserverConnection},
+};
 
 #[derive(Debug)]
 pub struct MatrixUser {
@@ -245,6 +249,43 @@ pub trait HomeserverConnection: Send + Sync {
     /// not be deleted.
     async fn delete_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error>;
 
+    /// Replace a device of a user on the homeserver, deleting the existing
+    /// one, with all its associated data, before creating a fresh one with the
+    /// same ID.
+    ///
+    /// Both steps happen in a single call, so that a concurrent creation or
+    /// deletion of the device can't be interleaved between them.
+    ///
+    /// # Parameters
+    ///
+    /// * `mxid` - The Matrix ID of the user to replace a device for.
+    /// * `device_id` - The device ID to replace.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the homeserver is unreachable or the device could
+    /// not be replaced.
+    async fn replace_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error>;
+
+    /// Update the display name of a device of a user on the homeserver.
+    ///
+    /// # Parameters
+    ///
+    /// * `mxid` - The Matrix ID of the user the device belongs to.
+    /// * `device_id` - The ID of the device to update.
+    /// * `display_name` - The new display name of the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the homeserver is unreachable or the device could
+    /// not be updated.
+    async fn update_device_display_name(
+        &self,
+        mxid: &str,
+        device_id: &str,
+        display_name: &str,
+    ) -> Result<(), Self::Error>;
+
     /// Delete a user on the homeserver.
     ///
     /// # Parameters
@@ -283,6 +324,31 @@ pub trait HomeserverConnection: Send + Sync {
     /// could not be unset.
     async fn unset_displayname(&self, mxid: &str) -> Result<(), Self::Error>;
 
+    /// Set the avatar of a user on the homeserver.
+    ///
+    /// # Parameters
+    ///
+    /// * `mxid` - The Matrix ID of the user to set the avatar for.
+    /// * `avatar_url` - The URL of the avatar to set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the homeserver is unreachable or the avatar could
+    /// not be set.
+    async fn set_avatar_url(&self, mxid: &str, avatar_url: &str) -> Result<(), Self::Error>;
+
+    /// Unset the avatar of a user on the homeserver.
+    ///
+    /// # Parameters
+    ///
+    /// * `mxid` - The Matrix ID of the user to unset the avatar for.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the homeserver is unreachable or the avatar could
+    /// not be unset.
+    async fn unset_avatar_url(&self, mxid: &str) -> Result<(), Self::Error>;
+
     /// Temporarily allow a user to reset their cross-signing keys.
     ///
     /// # Parameters
@@ -320,6 +386,21 @@ impl<T: HomeserverConnection + Send + Sync + ?Sized> HomeserverConnection for &T
         (**self).delete_device(mxid, device_id).await
     }
 
+    async fn replace_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
+        (**self).replace_device(mxid, device_id).await
+    }
+
+    async fn update_device_display_name(
+        &self,
+        mxid: &str,
+        device_id: &str,
+        display_name: &str,
+    ) -> Result<(), Self::Error> {
+        (**self)
+            .update_device_display_name(mxid, device_id, display_name)
+            .await
+    }
+
     async fn delete_user(&self, mxid: &str, erase: bool) -> Result<(), Self::Error> {
         (**self).delete_user(mxid, erase).await
     }
@@ -332,6 +413,14 @@ impl<T: HomeserverConnection + Send + Sync + ?Sized> HomeserverConnection for &T
         (**self).unset_displayname(mxid).await
     }
 
+    async fn set_avatar_url(&self, mxid: &str, avatar_url: &str) -> Result<(), Self::Error> {
+        (**self).set_avatar_url(mxid, avatar_url).await
+    }
+
+    async fn unset_avatar_url(&self, mxid: &str) -> Result<(), Self::Error> {
+        (**self).unset_avatar_url(mxid).await
+    }
+
     async fn allow_cross_signing_reset(&self, mxid: &str) -> Result<(), Self::Error> {
         (**self).allow_cross_signing_reset(mxid).await
     }
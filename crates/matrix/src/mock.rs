@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashMap, HashSet};
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
 use async_trait::async_trait;
@@ -24,16 +24,110 @@ struct MockUser {
     sub: String,
     avatar_url: Option<String>,
     displayname: Option<String>,
-    devices: HashSet<String>,
+    devices: HashMap<String, Option<String>>,
     emails: Option<Vec<String>>,
     cross_signing_reset_allowed: bool,
 }
 
-/// A mock implementation of a [`HomeserverConnection`], which never fails and
-/// doesn't do anything.
+/// A call made to the mock [`HomeserverConnection`] to change the state of
+/// the homeserver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    /// A user was provisioned
+    ProvisionUser {
+        /// The Matrix ID of the user
+        mxid: String,
+    },
+
+    /// A device was created
+    CreateDevice {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The ID of the device
+        device_id: String,
+    },
+
+    /// A device was deleted
+    DeleteDevice {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The ID of the device
+        device_id: String,
+    },
+
+    /// A device was replaced
+    ReplaceDevice {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The ID of the device
+        device_id: String,
+    },
+
+    /// A device was renamed
+    UpdateDeviceDisplayName {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The ID of the device
+        device_id: String,
+        /// The new display name of the device
+        display_name: String,
+    },
+
+    /// A user was deleted
+    DeleteUser {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// Whether the user's data was erased
+        erase: bool,
+    },
+
+    /// The display name of a user was set
+    SetDisplayname {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The new display name
+        displayname: String,
+    },
+
+    /// The display name of a user was unset
+    UnsetDisplayname {
+        /// The Matrix ID of the user
+        mxid: String,
+    },
+
+    /// The avatar of a user was set
+    SetAvatarUrl {
+        /// The Matrix ID of the user
+        mxid: String,
+        /// The URL of the new avatar
+        avatar_url: String,
+    },
+
+    /// The avatar of a user was unset
+    UnsetAvatarUrl {
+        /// The Matrix ID of the user
+        mxid: String,
+    },
+
+    /// A user was allowed to reset their cross-signing keys
+    AllowCrossSigningReset {
+        /// The Matrix ID of the user
+        mxid: String,
+    },
+}
+
+/// A mock implementation of a [`HomeserverConnection`], which keeps the state
+/// of the users in memory and records the calls made to it.
+///
+/// Clones share the same state, so a clone can be kept around to inspect the
+/// calls made through another one.
+///
+/// [`HomeserverConnection`]: crate::HomeserverConnection
+#[derive(Clone)]
 pub struct HomeserverConnection {
     homeserver: String,
-    users: RwLock<HashMap<String, MockUser>>,
+    users: Arc<RwLock<HashMap<String, MockUser>>>,
+    calls: Arc<RwLock<Vec<Call>>>,
 }
 
 impl HomeserverConnection {
@@ -44,9 +138,30 @@ impl HomeserverConnection {
     {
         Self {
             homeserver: homeserver.into(),
-            users: RwLock::new(HashMap::new()),
+            users: Arc::default(),
+            calls: Arc::default(),
         }
     }
+
+    /// Get the calls made to change the state of the homeserver, in the order
+    /// they were made, including the ones which failed
+    pub async fn calls(&self) -> Vec<Call> {
+        self.calls.read().await.clone()
+    }
+
+    /// Get the IDs of the devices of a user, or [`None`] if the user doesn't
+    /// exist
+    pub async fn devices(&self, mxid: &str) -> Option<Vec<String>> {
+        let users = self.users.read().await;
+        let user = users.get(mxid)?;
+        let mut devices: Vec<String> = user.devices.keys().cloned().collect();
+        devices.sort();
+        Some(devices)
+    }
+
+    async fn record(&self, call: Call) {
+        self.calls.write().await.push(call);
+    }
 }
 
 #[async_trait]
@@ -67,13 +182,18 @@ impl crate::HomeserverConnection for HomeserverConnection {
     }
 
     async fn provision_user(&self, request: &ProvisionRequest) -> Result<bool, Self::Error> {
+        self.record(Call::ProvisionUser {
+            mxid: request.mxid().to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let inserted = !users.contains_key(request.mxid());
         let user = users.entry(request.mxid().to_owned()).or_insert(MockUser {
             sub: request.sub().to_owned(),
             avatar_url: None,
             displayname: None,
-            devices: HashSet::new(),
+            devices: HashMap::new(),
             emails: None,
             cross_signing_reset_allowed: false,
         });
@@ -99,20 +219,75 @@ impl crate::HomeserverConnection for HomeserverConnection {
     }
 
     async fn create_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
+        self.record(Call::CreateDevice {
+            mxid: mxid.to_owned(),
+            device_id: device_id.to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
-        user.devices.insert(device_id.to_owned());
+        user.devices.entry(device_id.to_owned()).or_default();
         Ok(())
     }
 
     async fn delete_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
+        self.record(Call::DeleteDevice {
+            mxid: mxid.to_owned(),
+            device_id: device_id.to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
         user.devices.remove(device_id);
         Ok(())
     }
 
+    async fn replace_device(&self, mxid: &str, device_id: &str) -> Result<(), Self::Error> {
+        self.record(Call::ReplaceDevice {
+            mxid: mxid.to_owned(),
+            device_id: device_id.to_owned(),
+        })
+        .await;
+
+        let mut users = self.users.write().await;
+        let user = users.get_mut(mxid).context("User not found")?;
+        // Dropping the old entry also drops its display name
+        user.devices.insert(device_id.to_owned(), None);
+        Ok(())
+    }
+
+    async fn update_device_display_name(
+        &self,
+        mxid: &str,
+        device_id: &str,
+        display_name: &str,
+    ) -> Result<(), Self::Error> {
+        self.record(Call::UpdateDeviceDisplayName {
+            mxid: mxid.to_owned(),
+            device_id: device_id.to_owned(),
+            display_name: display_name.to_owned(),
+        })
+        .await;
+
+        let mut users = self.users.write().await;
+        let user = users.get_mut(mxid).context("User not found")?;
+        let device = user
+            .devices
+            .get_mut(device_id)
+            .context("Device not found")?;
+        *device = Some(display_name.to_owned());
+        Ok(())
+    }
+
     async fn delete_user(&self, mxid: &str, erase: bool) -> Result<(), Self::Error> {
+        self.record(Call::DeleteUser {
+            mxid: mxid.to_owned(),
+            erase,
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
         user.devices.clear();
@@ -126,6 +301,12 @@ impl crate::HomeserverConnection for HomeserverConnection {
     }
 
     async fn set_displayname(&self, mxid: &str, displayname: &str) -> Result<(), Self::Error> {
+        self.record(Call::SetDisplayname {
+            mxid: mxid.to_owned(),
+            displayname: displayname.to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
         user.displayname = Some(displayname.to_owned());
@@ -133,13 +314,48 @@ impl crate::HomeserverConnection for HomeserverConnection {
     }
 
     async fn unset_displayname(&self, mxid: &str) -> Result<(), Self::Error> {
+        self.record(Call::UnsetDisplayname {
+            mxid: mxid.to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
         user.displayname = None;
         Ok(())
     }
 
+    async fn set_avatar_url(&self, mxid: &str, avatar_url: &str) -> Result<(), Self::Error> {
+        self.record(Call::SetAvatarUrl {
+            mxid: mxid.to_owned(),
+            avatar_url: avatar_url.to_owned(),
+        })
+        .await;
+
+        let mut users = self.users.write().await;
+        let user = users.get_mut(mxid).context("User not found")?;
+        user.avatar_url = Some(avatar_url.to_owned());
+        Ok(())
+    }
+
+    async fn unset_avatar_url(&self, mxid: &str) -> Result<(), Self::Error> {
+        self.record(Call::UnsetAvatarUrl {
+            mxid: mxid.to_owned(),
+        })
+        .await;
+
+        let mut users = self.users.write().await;
+        let user = users.get_mut(mxid).context("User not found")?;
+        user.avatar_url = None;
+        Ok(())
+    }
+
     async fn allow_cross_signing_reset(&self, mxid: &str) -> Result<(), Self::Error> {
+        self.record(Call::AllowCrossSigningReset {
+            mxid: mxid.to_owned(),
+        })
+        .await;
+
         let mut users = self.users.write().await;
         let user = users.get_mut(mxid).context("User not found")?;
         user.cross_signing_reset_allowed = true;
@@ -164,6 +380,7 @@ mod tests {
         assert!(conn.query_user(mxid).await.is_err());
         assert!(conn.create_device(mxid, device).await.is_err());
         assert!(conn.delete_device(mxid, device).await.is_err());
+        assert!(conn.replace_device(mxid, device).await.is_err());
 
         let request = ProvisionRequest::new("@test:example.org", "test")
             .set_displayname("Test User".into())
@@ -189,6 +406,20 @@ mod tests {
         let user = conn.query_user(mxid).await.unwrap();
         assert_eq!(user.displayname, None);
 
+        // Change the avatar, then unset it
+        assert!(conn
+            .set_avatar_url(mxid, "mxc://example.org/0987654321")
+            .await
+            .is_ok());
+
+        let user = conn.query_user(mxid).await.unwrap();
+        assert_eq!(user.avatar_url, Some("mxc://example.org/0987654321".into()));
+
+        assert!(conn.unset_avatar_url(mxid).await.is_ok());
+
+        let user = conn.query_user(mxid).await.unwrap();
+        assert_eq!(user.avatar_url, None);
+
         // Deleting a non-existent device should not fail
         assert!(conn.delete_device(mxid, device).await.is_ok());
 
@@ -197,8 +428,48 @@ mod tests {
         // Create the same device again
         assert!(conn.create_device(mxid, device).await.is_ok());
 
-        // XXX: there is no API to query devices yet in the trait
+        // Name the device
+        assert!(conn
+            .update_device_display_name(mxid, device, "Element on Android")
+            .await
+            .is_ok());
+        // Naming an unknown device fails
+        assert!(conn
+            .update_device_display_name(mxid, "UNKNOWN", "Element on Android")
+            .await
+            .is_err());
+
+        // Replace the device, which works whether it exists or not
+        assert!(conn.replace_device(mxid, device).await.is_ok());
+        assert!(conn.replace_device(mxid, "OTHER").await.is_ok());
+
+        assert_eq!(
+            conn.devices(mxid).await,
+            Some(vec!["OTHER".to_owned(), device.to_owned()])
+        );
+
         // Delete the device
         assert!(conn.delete_device(mxid, device).await.is_ok());
+        assert_eq!(conn.devices(mxid).await, Some(vec!["OTHER".to_owned()]));
+
+        // Clones share the recorded calls, which include the failed ones
+        let calls = conn.clone().calls().await;
+        assert_eq!(
+            calls.first(),
+            Some(&Call::CreateDevice {
+                mxid: mxid.to_owned(),
+                device_id: device.to_owned(),
+            })
+        );
+        assert!(calls.contains(&Call::ProvisionUser {
+            mxid: mxid.to_owned()
+        }));
+        assert_eq!(
+            calls.last(),
+            Some(&Call::DeleteDevice {
+                mxid: mxid.to_owned(),
+                device_id: device.to_owned(),
+            })
+        );
     }
 }
@@ -275,6 +275,29 @@ pub enum ClientErrorCode {
     /// From [RFC7009](https://www.rfc-editor.org/rfc/rfc7009#section-2.2.1).
     UnsupportedTokenType,
 
+    /// `invalid_dpop_proof`
+    ///
+    /// The DPoP proof sent with the request is invalid.
+    ///
+    /// From [RFC9449](https://www.rfc-editor.org/rfc/rfc9449#section-5).
+    InvalidDpopProof,
+
+    /// `use_dpop_nonce`
+    ///
+    /// The authorization server requires the DPoP proof to include a nonce it
+    /// provided. The nonce to use is given in the `DPoP-Nonce` header of the
+    /// response.
+    ///
+    /// From [RFC9449](https://www.rfc-editor.org/rfc/rfc9449#section-8).
+    UseDpopNonce,
+
+    /// `invalid_target`
+    ///
+    /// The requested resource is invalid, missing, unknown, or malformed.
+    ///
+    /// From [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2).
+    InvalidTarget,
+
     /// Another error code.
     #[display("{0}")]
     Unknown(String),
@@ -366,6 +389,13 @@ impl ClientErrorCode {
             ClientErrorCode::UnsupportedTokenType => {
                 "The authorization server does not support the revocation of the presented token type."
             },
+            ClientErrorCode::InvalidDpopProof => "The DPoP proof is invalid.",
+            ClientErrorCode::UseDpopNonce => {
+                "The authorization server requires a nonce in the DPoP proof."
+            }
+            ClientErrorCode::InvalidTarget => {
+                "The requested resource is invalid, missing, unknown, or malformed."
+            }
             ClientErrorCode::Unknown(_) => "",
         }
     }
@@ -461,6 +491,18 @@ mod tests {
             serde_json::to_string(&ClientErrorCode::InvalidClientMetadata).unwrap(),
             "\"invalid_client_metadata\""
         );
+        assert_eq!(
+            serde_json::to_string(&ClientErrorCode::InvalidDpopProof).unwrap(),
+            "\"invalid_dpop_proof\""
+        );
+        assert_eq!(
+            serde_json::to_string(&ClientErrorCode::UseDpopNonce).unwrap(),
+            "\"use_dpop_nonce\""
+        );
+        assert_eq!(
+            serde_json::to_string(&ClientErrorCode::InvalidTarget).unwrap(),
+            "\"invalid_target\""
+        );
 
         assert_eq!(
             serde_json::to_string(&ClientErrorCode::Unknown("unknown_error_code".to_owned()))
@@ -555,6 +597,18 @@ mod tests {
             serde_json::from_str::<ClientErrorCode>("\"invalid_client_metadata\"").unwrap(),
             ClientErrorCode::InvalidClientMetadata
         );
+        assert_eq!(
+            serde_json::from_str::<ClientErrorCode>("\"invalid_dpop_proof\"").unwrap(),
+            ClientErrorCode::InvalidDpopProof
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientErrorCode>("\"use_dpop_nonce\"").unwrap(),
+            ClientErrorCode::UseDpopNonce
+        );
+        assert_eq!(
+            serde_json::from_str::<ClientErrorCode>("\"invalid_target\"").unwrap(),
+            ClientErrorCode::InvalidTarget
+        );
 
         assert_eq!(
             serde_json::from_str::<ClientErrorCode>("\"unknown_error_code\"").unwrap(),
@@ -479,6 +479,40 @@ pub struct ProviderMetadata {
     ///
     /// [RP-Initiated Logout endpoint]: https://openid.net/specs/openid-connect-rpinitiated-1_0.html
     pub end_session_endpoint: Option<Url>,
+
+    /// Boolean value specifying whether the OP supports [back-channel logout].
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [back-channel logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+    pub backchannel_logout_supported: Option<bool>,
+
+    /// Boolean value specifying whether the OP can pass a `sid` claim in the
+    /// logout token to identify the RP session with the OP.
+    ///
+    /// Defaults to `false`.
+    pub backchannel_logout_session_supported: Option<bool>,
+
+    /// JSON array containing a list of the JWS `alg` values supported by the
+    /// authorization server for [DPoP] proof JWTs.
+    ///
+    /// [DPoP]: https://www.rfc-editor.org/rfc/rfc9449.html
+    pub dpop_signing_alg_values_supported: Option<Vec<JsonWebSignatureAlg>>,
+
+    /// Boolean value indicating server support for [mutual-TLS client
+    /// certificate-bound access tokens].
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [mutual-TLS client certificate-bound access tokens]: https://www.rfc-editor.org/rfc/rfc8705.html#section-3
+    pub tls_client_certificate_bound_access_tokens: Option<bool>,
+
+    /// JSON array containing a list of the JWS signing algorithms (`alg`
+    /// values) supported by the authorization server to sign the [JARM]
+    /// authorization responses.
+    ///
+    /// [JARM]: https://openid.net/specs/oauth-v2-jarm.html
+    pub authorization_signing_alg_values_supported: Option<Vec<JsonWebSignatureAlg>>,
 }
 
 impl ProviderMetadata {
@@ -754,6 +788,23 @@ impl ProviderMetadata {
         self.require_request_uri_registration.unwrap_or(false)
     }
 
+    /// Boolean value specifying whether the OP supports back-channel logout.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn backchannel_logout_supported(&self) -> bool {
+        self.backchannel_logout_supported.unwrap_or(false)
+    }
+
+    /// Boolean value specifying whether the OP can pass a `sid` claim in the
+    /// logout token.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn backchannel_logout_session_supported(&self) -> bool {
+        self.backchannel_logout_session_supported.unwrap_or(false)
+    }
+
     /// Indicates where authorization request needs to be protected as Request
     /// Object and provided through either `request` or `request_uri` parameter.
     ///
@@ -771,6 +822,16 @@ impl ProviderMetadata {
     pub fn require_pushed_authorization_requests(&self) -> bool {
         self.require_pushed_authorization_requests.unwrap_or(false)
     }
+
+    /// Indicates whether the authorization server supports mutual-TLS client
+    /// certificate-bound access tokens.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn tls_client_certificate_bound_access_tokens(&self) -> bool {
+        self.tls_client_certificate_bound_access_tokens
+            .unwrap_or(false)
+    }
 }
 
 /// The verified authorization server metadata.
@@ -127,54 +127,61 @@ pub struct ClientMetadataSerdeHelper {
     introspection_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
     introspection_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
     post_logout_redirect_uris: Option<Vec<Url>>,
+    backchannel_logout_uri: Option<Url>,
+    backchannel_logout_session_required: Option<bool>,
     #[serde(flatten)]
     extra: ClientMetadataLocalizedFields,
 }
 
 impl From<VerifiedClientMetadata> for ClientMetadataSerdeHelper {
     fn from(metadata: VerifiedClientMetadata) -> Self {
-        let VerifiedClientMetadata {
-            inner:
-                ClientMetadata {
-                    redirect_uris,
-                    response_types,
-                    grant_types,
-                    application_type,
-                    contacts,
-                    client_name,
-                    logo_uri,
-                    client_uri,
-                    policy_uri,
-                    tos_uri,
-                    jwks_uri,
-                    jwks,
-                    software_id,
-                    software_version,
-                    sector_identifier_uri,
-                    subject_type,
-                    token_endpoint_auth_method,
-                    token_endpoint_auth_signing_alg,
-                    id_token_signed_response_alg,
-                    id_token_encrypted_response_alg,
-                    id_token_encrypted_response_enc,
-                    userinfo_signed_response_alg,
-                    userinfo_encrypted_response_alg,
-                    userinfo_encrypted_response_enc,
-                    request_object_signing_alg,
-                    request_object_encryption_alg,
-                    request_object_encryption_enc,
-                    default_max_age,
-                    require_auth_time,
-                    default_acr_values,
-                    initiate_login_uri,
-                    request_uris,
-                    require_signed_request_object,
-                    require_pushed_authorization_requests,
-                    introspection_signed_response_alg,
-                    introspection_encrypted_response_alg,
-                    introspection_encrypted_response_enc,
-                    post_logout_redirect_uris,
-                },
+        metadata.inner.into()
+    }
+}
+
+impl From<ClientMetadata> for ClientMetadataSerdeHelper {
+    fn from(metadata: ClientMetadata) -> Self {
+        let ClientMetadata {
+            redirect_uris,
+            response_types,
+            grant_types,
+            application_type,
+            contacts,
+            client_name,
+            logo_uri,
+            client_uri,
+            policy_uri,
+            tos_uri,
+            jwks_uri,
+            jwks,
+            software_id,
+            software_version,
+            sector_identifier_uri,
+            subject_type,
+            token_endpoint_auth_method,
+            token_endpoint_auth_signing_alg,
+            id_token_signed_response_alg,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_signed_response_alg,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
+            request_object_signing_alg,
+            request_object_encryption_alg,
+            request_object_encryption_enc,
+            default_max_age,
+            require_auth_time,
+            default_acr_values,
+            initiate_login_uri,
+            request_uris,
+            require_signed_request_object,
+            require_pushed_authorization_requests,
+            introspection_signed_response_alg,
+            introspection_encrypted_response_alg,
+            introspection_encrypted_response_enc,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
         } = metadata;
 
         ClientMetadataSerdeHelper {
@@ -211,6 +218,8 @@ impl From<VerifiedClientMetadata> for ClientMetadataSerdeHelper {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
             extra: ClientMetadataLocalizedFields {
                 client_name,
                 logo_uri,
@@ -258,6 +267,8 @@ impl From<ClientMetadataSerdeHelper> for ClientMetadata {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
             extra:
                 ClientMetadataLocalizedFields {
                     client_name,
@@ -307,6 +318,8 @@ impl From<ClientMetadataSerdeHelper> for ClientMetadata {
             introspection_encrypted_response_alg,
             introspection_encrypted_response_enc,
             post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
         }
     }
 }
@@ -126,8 +126,8 @@ impl<T> From<(T, HashMap<LanguageTag, T>)> for Localized<T> {
 /// All the fields with a default value are accessible via methods.
 ///
 /// [IANA registry]: https://www.iana.org/assignments/oauth-parameters/oauth-parameters.xhtml#client-metadata
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Default)]
-#[serde(from = "ClientMetadataSerdeHelper")]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+#[serde(from = "ClientMetadataSerdeHelper", into = "ClientMetadataSerdeHelper")]
 pub struct ClientMetadata {
     /// Array of redirection URIs for use in redirect-based flows such as the
     /// [authorization code flow].
@@ -436,6 +436,19 @@ pub struct ClientMetadata {
     ///
     /// [RP-Initiated Logout endpoint]: https://openid.net/specs/openid-connect-rpinitiated-1_0.html
     pub post_logout_redirect_uris: Option<Vec<Url>>,
+
+    /// URL that will cause the client to log itself out when sent a logout
+    /// token by the provider, as defined by [OpenID Connect Back-Channel
+    /// Logout].
+    ///
+    /// [OpenID Connect Back-Channel Logout]: https://openid.net/specs/openid-connect-backchannel-1_0.html
+    pub backchannel_logout_uri: Option<Url>,
+
+    /// Whether the client requires the `sid` claim to be included in the logout
+    /// tokens sent to its `backchannel_logout_uri`.
+    ///
+    /// Defaults to `false`.
+    pub backchannel_logout_session_required: Option<bool>,
 }
 
 impl ClientMetadata {
@@ -568,6 +581,14 @@ impl ClientMetadata {
             )?;
         }
 
+        if let Some(url) = &self.backchannel_logout_uri {
+            if url.fragment().is_some() {
+                return Err(
+                    ClientMetadataVerificationError::BackchannelLogoutUriWithFragment(url.clone()),
+                );
+            }
+        }
+
         Ok(VerifiedClientMetadata { inner: self })
     }
 
@@ -742,6 +763,15 @@ impl ClientMetadata {
             .unwrap_or_default()
     }
 
+    /// Whether the client requires the `sid` claim to be included in the
+    /// logout tokens sent to its `backchannel_logout_uri`.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn backchannel_logout_session_required(&self) -> bool {
+        self.backchannel_logout_session_required.unwrap_or_default()
+    }
+
     /// [JWE] `alg` and `enc` algorithms for encrypting responses of the
     /// [introspection endpoint].
     ///
@@ -845,6 +875,10 @@ pub enum ClientMetadataVerificationError {
     #[error("redirect URI with fragment: {0}")]
     RedirectUriWithFragment(Url),
 
+    /// The back-channel logout URI contains a fragment, which is not allowed.
+    #[error("backchannel_logout_uri with fragment: {0}")]
+    BackchannelLogoutUriWithFragment(Url),
+
     /// The given response type is not compatible with the grant types.
     #[error("'{0}' response type not compatible with grant types")]
     IncoherentResponseType(ResponseType),
@@ -905,6 +939,18 @@ pub struct ClientRegistrationResponse {
     #[serde(default)]
     #[serde_as(as = "Option<TimestampSeconds<i64>>")]
     pub client_secret_expires_at: Option<DateTime<Utc>>,
+
+    /// A token that can be used at the `registration_client_uri` to read,
+    /// update or delete the client registration, as defined in [RFC 7592].
+    ///
+    /// [RFC 7592]: https://www.rfc-editor.org/rfc/rfc7592
+    #[serde(default)]
+    pub registration_access_token: Option<String>,
+
+    /// Location of the client configuration endpoint where the
+    /// `registration_access_token` can be used.
+    #[serde(default)]
+    pub registration_client_uri: Option<Url>,
 }
 
 #[cfg(test)]
@@ -1392,6 +1438,24 @@ mod tests {
         metadata.validate().unwrap();
     }
 
+    #[test]
+    fn validate_backchannel_logout_uri() {
+        let mut metadata = valid_client_metadata();
+
+        // Err - URL with a fragment
+        let logout_uri = Url::parse("https://localhost/logout#fragment").unwrap();
+        metadata.backchannel_logout_uri = Some(logout_uri.clone());
+        let url = assert_matches!(
+            metadata.clone().validate(),
+            Err(ClientMetadataVerificationError::BackchannelLogoutUriWithFragment(url)) => url
+        );
+        assert_eq!(url, logout_uri);
+
+        // Ok - URL without a fragment
+        metadata.backchannel_logout_uri = Some(Url::parse("https://localhost/logout").unwrap());
+        metadata.validate().unwrap();
+    }
+
     #[test]
     fn validate_introspection_encrypted_response() {
         let mut metadata = valid_client_metadata();
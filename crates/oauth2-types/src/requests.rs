@@ -16,7 +16,12 @@
 //!
 //! [OAuth 2.0]: https://oauth.net/2/
 
-use std::{collections::HashSet, fmt, hash::Hash, num::NonZeroU32};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    num::NonZeroU32,
+};
 
 use chrono::{DateTime, Duration, Utc};
 use language_tags::LanguageTag;
@@ -24,8 +29,8 @@ use mas_iana::oauth::{OAuthAccessTokenType, OAuthTokenTypeHint};
 use parse_display::{Display, FromStr};
 use serde::{Deserialize, Serialize};
 use serde_with::{
-    formats::SpaceSeparator, serde_as, skip_serializing_none, DeserializeFromStr, DisplayFromStr,
-    DurationSeconds, SerializeDisplay, StringWithSeparator, TimestampSeconds,
+    formats::SpaceSeparator, json::JsonString, serde_as, skip_serializing_none, DeserializeFromStr,
+    DisplayFromStr, DurationSeconds, SerializeDisplay, StringWithSeparator, TimestampSeconds,
 };
 use url::Url;
 
@@ -70,6 +75,35 @@ pub enum ResponseMode {
     /// Defined in [OAuth 2.0 Form Post Response Mode](https://openid.net/specs/oauth-v2-form-post-response-mode-1_0.html).
     FormPost,
 
+    /// Authorization Response parameters are encoded in a signed JWT, sent
+    /// with the default response mode for the requested response type.
+    ///
+    /// Defined in [JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)](https://openid.net/specs/oauth-v2-jarm.html).
+    #[display("jwt")]
+    Jwt,
+
+    /// Authorization Response parameters are encoded in a signed JWT, sent in
+    /// the `response` parameter of the query string added to the
+    /// `redirect_uri`.
+    ///
+    /// Defined in [JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)](https://openid.net/specs/oauth-v2-jarm.html).
+    #[display("query.jwt")]
+    QueryJwt,
+
+    /// Authorization Response parameters are encoded in a signed JWT, sent in
+    /// the `response` parameter of the fragment added to the `redirect_uri`.
+    ///
+    /// Defined in [JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)](https://openid.net/specs/oauth-v2-jarm.html).
+    #[display("fragment.jwt")]
+    FragmentJwt,
+
+    /// Authorization Response parameters are encoded in a signed JWT, sent in
+    /// the `response` parameter of an auto-submitted HTML form.
+    ///
+    /// Defined in [JWT Secured Authorization Response Mode for OAuth 2.0 (JARM)](https://openid.net/specs/oauth-v2-jarm.html).
+    #[display("form_post.jwt")]
+    FormPostJwt,
+
     /// An unknown value.
     #[display("{0}")]
     Unknown(String),
@@ -255,6 +289,12 @@ pub struct AuthorizationRequest {
     #[serde(default)]
     pub acr_values: Option<HashSet<String>>,
 
+    /// Specific claims the client would like to get in the ID token or from
+    /// the UserInfo endpoint.
+    #[serde_as(as = "Option<JsonString>")]
+    #[serde(default)]
+    pub claims: Option<ClaimsRequest>,
+
     /// A JWT that contains the request's parameter values, called a [Request
     /// Object].
     ///
@@ -273,6 +313,14 @@ pub struct AuthorizationRequest {
     ///
     /// [Self-Issued OpenID Provider]: https://openid.net/specs/openid-connect-core-1_0.html#SelfIssued
     pub registration: Option<String>,
+
+    /// The URI of the target service where the client intends to use the
+    /// requested access token.
+    ///
+    /// Only a single resource indicator is supported.
+    ///
+    /// From [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2).
+    pub resource: Option<Url>,
 }
 
 impl AuthorizationRequest {
@@ -294,9 +342,11 @@ impl AuthorizationRequest {
             id_token_hint: None,
             login_hint: None,
             acr_values: None,
+            claims: None,
             request: None,
             request_uri: None,
             registration: None,
+            resource: None,
         }
     }
 }
@@ -314,13 +364,84 @@ impl fmt::Debug for AuthorizationRequest {
             .field("ui_locales", &self.ui_locales)
             .field("login_hint", &self.login_hint)
             .field("acr_values", &self.acr_values)
+            .field("claims", &self.claims)
             .field("request", &self.request)
             .field("request_uri", &self.request_uri)
             .field("registration", &self.registration)
+            .field("resource", &self.resource)
             .finish_non_exhaustive()
     }
 }
 
+/// How a client requests an individual claim.
+///
+/// Defined in [OpenID Connect Core 1.0](https://openid.net/specs/openid-connect-core-1_0.html#IndividualClaimsRequests).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub struct IndividualClaimRequest {
+    /// Whether the claim is necessary for the client to work properly.
+    #[serde(default)]
+    pub essential: bool,
+
+    /// A specific value the claim is requested to have.
+    pub value: Option<serde_json::Value>,
+
+    /// A set of values the claim is requested to have, in order of preference.
+    pub values: Option<Vec<serde_json::Value>>,
+}
+
+/// The claims a client requests through the `claims` parameter.
+///
+/// Defined in [OpenID Connect Core 1.0](https://openid.net/specs/openid-connect-core-1_0.html#ClaimsParameter).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub struct ClaimsRequest {
+    /// Claims requested to be returned from the UserInfo endpoint.
+    pub userinfo: Option<HashMap<String, Option<IndividualClaimRequest>>>,
+
+    /// Claims requested to be returned in the ID token.
+    pub id_token: Option<HashMap<String, Option<IndividualClaimRequest>>>,
+}
+
+impl ClaimsRequest {
+    /// Whether the given claim was requested for the UserInfo endpoint.
+    #[must_use]
+    pub fn requests_userinfo_claim(&self, claim: &str) -> bool {
+        self.userinfo
+            .as_ref()
+            .is_some_and(|claims| claims.contains_key(claim))
+    }
+
+    /// Whether the given claim was requested in the ID token.
+    #[must_use]
+    pub fn requests_id_token_claim(&self, claim: &str) -> bool {
+        self.id_token
+            .as_ref()
+            .is_some_and(|claims| claims.contains_key(claim))
+    }
+
+    /// The names of all the requested claims, without duplicates.
+    pub fn claim_names(&self) -> impl Iterator<Item = &str> {
+        let mut names: Vec<&str> = self
+            .userinfo
+            .iter()
+            .chain(self.id_token.iter())
+            .flat_map(HashMap::keys)
+            .map(String::as_str)
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names.into_iter()
+    }
+
+    /// Remove the claims for which the predicate returns `false`.
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        for claims in self.userinfo.iter_mut().chain(self.id_token.iter_mut()) {
+            claims.retain(|name, _| f(name));
+        }
+    }
+}
+
 /// A successful response from the [Authorization Endpoint].
 ///
 /// [Authorization Endpoint]: https://www.rfc-editor.org/rfc/rfc6749.html#section-3.1
@@ -376,32 +497,32 @@ pub const DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS: i64 = 5;
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct DeviceAuthorizationResponse {
     /// The device verification code.
-    device_code: String,
+    pub device_code: String,
 
     /// The end-user verification code.
-    user_code: String,
+    pub user_code: String,
 
     /// The end-user verification URI on the authorization server.
     ///
     /// The URI should be short and easy to remember as end users will be asked
     /// to manually type it into their user agent.
-    verification_uri: Url,
+    pub verification_uri: Url,
 
     /// A verification URI that includes the `user_code` (or other information
     /// with the same function as the `user_code`), which is designed for
     /// non-textual transmission.
-    verification_uri_complete: Option<Url>,
+    pub verification_uri_complete: Option<Url>,
 
     /// The lifetime of the `device_code` and `user_code`.
     #[serde_as(as = "DurationSeconds<i64>")]
-    expires_in: Duration,
+    pub expires_in: Duration,
 
     /// The minimum amount of time in seconds that the client should wait
     /// between polling requests to the token endpoint.
     ///
     /// Defaults to [`DEFAULT_DEVICE_AUTHORIZATION_INTERVAL_SECONDS`].
     #[serde_as(as = "Option<DurationSeconds<i64>>")]
-    interval: Option<Duration>,
+    pub interval: Option<Duration>,
 }
 
 impl DeviceAuthorizationResponse {
@@ -447,12 +568,22 @@ pub struct AuthorizationCodeGrant {
     /// authorization endpoint.
     // TODO: move this somehow in the pkce module
     pub code_verifier: Option<String>,
+
+    /// The URI of the target service where the client intends to use the
+    /// requested access token.
+    ///
+    /// This must match the resource indicator sent to the authorization
+    /// endpoint, if any.
+    ///
+    /// From [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2).
+    pub resource: Option<Url>,
 }
 
 impl fmt::Debug for AuthorizationCodeGrant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("AuthorizationCodeGrant")
             .field("redirect_uri", &self.redirect_uri)
+            .field("resource", &self.resource)
             .finish_non_exhaustive()
     }
 }
@@ -473,12 +604,22 @@ pub struct RefreshTokenGrant {
     /// the resource owner, and if omitted is treated as equal to the scope
     /// originally granted by the resource owner.
     pub scope: Option<Scope>,
+
+    /// The URI of the target service where the client intends to use the
+    /// requested access token.
+    ///
+    /// This must match the resource indicator the session was created with,
+    /// if any.
+    ///
+    /// From [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2).
+    pub resource: Option<Url>,
 }
 
 impl fmt::Debug for RefreshTokenGrant {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RefreshTokenGrant")
             .field("scope", &self.scope)
+            .field("resource", &self.resource)
             .finish_non_exhaustive()
     }
 }
@@ -491,6 +632,12 @@ impl fmt::Debug for RefreshTokenGrant {
 pub struct ClientCredentialsGrant {
     /// The scope of the access request.
     pub scope: Option<Scope>,
+
+    /// The URI of the target service where the client intends to use the
+    /// requested access token.
+    ///
+    /// From [RFC8707](https://www.rfc-editor.org/rfc/rfc8707#section-2).
+    pub resource: Option<Url>,
 }
 
 /// A request to the [Token Endpoint] for the [Device Authorization] grant type.
@@ -500,7 +647,7 @@ pub struct ClientCredentialsGrant {
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct DeviceCodeGrant {
     /// The device verification code, from the device authorization response.
-    pub device_code: Option<Scope>,
+    pub device_code: String,
 }
 
 impl fmt::Debug for DeviceCodeGrant {
@@ -509,6 +656,90 @@ impl fmt::Debug for DeviceCodeGrant {
     }
 }
 
+/// A [token type identifier], as used in the [Token Exchange] grant type.
+///
+/// [token type identifier]: https://www.rfc-editor.org/rfc/rfc8693#section-3
+/// [Token Exchange]: https://www.rfc-editor.org/rfc/rfc8693
+#[derive(
+    Debug,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Clone,
+    Display,
+    FromStr,
+    SerializeDisplay,
+    DeserializeFromStr,
+)]
+#[non_exhaustive]
+pub enum TokenTypeIdentifier {
+    /// An OAuth 2.0 access token.
+    #[display("urn:ietf:params:oauth:token-type:access_token")]
+    AccessToken,
+
+    /// An OAuth 2.0 refresh token.
+    #[display("urn:ietf:params:oauth:token-type:refresh_token")]
+    RefreshToken,
+
+    /// An OpenID Connect ID Token.
+    #[display("urn:ietf:params:oauth:token-type:id_token")]
+    IdToken,
+
+    /// A JSON Web Token.
+    #[display("urn:ietf:params:oauth:token-type:jwt")]
+    Jwt,
+
+    /// An unknown value.
+    #[display("{0}")]
+    Unknown(String),
+}
+
+/// A request to the [Token Endpoint] for the [Token Exchange] grant type.
+///
+/// [Token Endpoint]: https://www.rfc-editor.org/rfc/rfc6749#section-3.2
+/// [Token Exchange]: https://www.rfc-editor.org/rfc/rfc8693
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TokenExchangeGrant {
+    /// The token that represents the identity of the party on behalf of whom
+    /// the request is being made.
+    pub subject_token: String,
+
+    /// The type of the `subject_token`.
+    pub subject_token_type: TokenTypeIdentifier,
+
+    /// A token that represents the identity of the acting party.
+    pub actor_token: Option<String>,
+
+    /// The type of the `actor_token`.
+    pub actor_token_type: Option<TokenTypeIdentifier>,
+
+    /// The type of the requested security token.
+    ///
+    /// If omitted, the authorization server picks the type, usually an access
+    /// token.
+    pub requested_token_type: Option<TokenTypeIdentifier>,
+
+    /// The scope of the requested security token.
+    ///
+    /// The requested scope must not include any scope not granted to the
+    /// subject token, and if omitted is treated as equal to it.
+    pub scope: Option<Scope>,
+}
+
+impl fmt::Debug for TokenExchangeGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenExchangeGrant")
+            .field("subject_token_type", &self.subject_token_type)
+            .field("actor_token_type", &self.actor_token_type)
+            .field("requested_token_type", &self.requested_token_type)
+            .field("scope", &self.scope)
+            .finish_non_exhaustive()
+    }
+}
+
 /// All possible values for the `grant_type` parameter.
 #[derive(
     Debug,
@@ -552,6 +783,10 @@ pub enum GrantType {
     /// [`urn:openid:params:grant-type:ciba`](https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html)
     #[display("urn:openid:params:grant-type:ciba")]
     ClientInitiatedBackchannelAuthentication,
+
+    /// [`urn:ietf:params:oauth:grant-type:token-exchange`](https://www.rfc-editor.org/rfc/rfc8693)
+    #[display("urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange,
 }
 
 /// An enum representing the possible requests to the [Token Endpoint].
@@ -573,6 +808,10 @@ pub enum AccessTokenRequest {
     #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
     DeviceCode(DeviceCodeGrant),
 
+    /// A request to exchange a token for another one.
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange(TokenExchangeGrant),
+
     /// An unsupported request.
     #[serde(skip_serializing, other)]
     Unsupported,
@@ -604,6 +843,12 @@ pub struct AccessTokenResponse {
 
     /// The scope of the access token.
     pub scope: Option<Scope>,
+
+    /// The type of the issued token, in response to a [Token Exchange]
+    /// request.
+    ///
+    /// [Token Exchange]: https://www.rfc-editor.org/rfc/rfc8693#section-2.2.1
+    pub issued_token_type: Option<TokenTypeIdentifier>,
 }
 
 impl AccessTokenResponse {
@@ -617,9 +862,17 @@ impl AccessTokenResponse {
             token_type: OAuthAccessTokenType::Bearer,
             expires_in: None,
             scope: None,
+            issued_token_type: None,
         }
     }
 
+    /// Sets the type of the access token of an `AccessTokenResponse`.
+    #[must_use]
+    pub fn with_token_type(mut self, token_type: OAuthAccessTokenType) -> Self {
+        self.token_type = token_type;
+        self
+    }
+
     /// Adds a refresh token to an `AccessTokenResponse`.
     #[must_use]
     pub fn with_refresh_token(mut self, refresh_token: String) -> Self {
@@ -647,6 +900,13 @@ impl AccessTokenResponse {
         self.expires_in = Some(expires_in);
         self
     }
+
+    /// Sets the type of the issued token of an `AccessTokenResponse`.
+    #[must_use]
+    pub fn with_issued_token_type(mut self, issued_token_type: TokenTypeIdentifier) -> Self {
+        self.issued_token_type = Some(issued_token_type);
+        self
+    }
 }
 
 impl fmt::Debug for AccessTokenResponse {
@@ -655,6 +915,7 @@ impl fmt::Debug for AccessTokenResponse {
             .field("token_type", &self.token_type)
             .field("expires_in", &self.expires_in)
             .field("scope", &self.scope)
+            .field("issued_token_type", &self.issued_token_type)
             .finish_non_exhaustive()
     }
 }
@@ -726,6 +987,43 @@ pub struct IntrospectionResponse {
 
     /// String identifier for the token.
     pub jti: Option<String>,
+
+    /// Confirmation of the key the token is bound to, if any.
+    pub cnf: Option<TokenConfirmation>,
+
+    /// Time when the user last authenticated.
+    ///
+    /// Defined in [RFC9470](https://www.rfc-editor.org/rfc/rfc9470#section-6.2).
+    #[serde_as(as = "Option<TimestampSeconds>")]
+    pub auth_time: Option<DateTime<Utc>>,
+
+    /// Authentication Context Class Reference satisfied by the last
+    /// authentication of the user.
+    ///
+    /// Defined in [RFC9470](https://www.rfc-editor.org/rfc/rfc9470#section-6.2).
+    pub acr: Option<String>,
+
+    /// Authentication Method References used in the last authentication of
+    /// the user.
+    pub amr: Option<Vec<String>>,
+}
+
+/// The confirmation method of a sender-constrained token.
+///
+/// Defined in [RFC7800](https://www.rfc-editor.org/rfc/rfc7800#section-3.1).
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct TokenConfirmation {
+    /// The JWK SHA-256 Thumbprint of the DPoP key the token is bound to.
+    ///
+    /// Defined in [RFC9449](https://www.rfc-editor.org/rfc/rfc9449#section-6.1).
+    pub jkt: Option<String>,
+
+    /// The SHA-256 thumbprint of the client certificate the token is bound to.
+    ///
+    /// Defined in [RFC8705](https://www.rfc-editor.org/rfc/rfc8705#section-3.1).
+    #[serde(rename = "x5t#S256")]
+    pub x5t_s256: Option<String>,
 }
 
 /// A request to the [Revocation Endpoint].
@@ -774,6 +1072,64 @@ mod tests {
     use super::*;
     use crate::{scope::OPENID, test_utils::assert_serde_json};
 
+    #[test]
+    fn parse_response_mode() {
+        for (mode, expected) in [
+            ("query", ResponseMode::Query),
+            ("fragment", ResponseMode::Fragment),
+            ("form_post", ResponseMode::FormPost),
+            ("jwt", ResponseMode::Jwt),
+            ("query.jwt", ResponseMode::QueryJwt),
+            ("fragment.jwt", ResponseMode::FragmentJwt),
+            ("form_post.jwt", ResponseMode::FormPostJwt),
+            (
+                "web_message",
+                ResponseMode::Unknown("web_message".to_owned()),
+            ),
+        ] {
+            let parsed: ResponseMode = mode.parse().unwrap();
+            assert_eq!(parsed, expected);
+            assert_eq!(parsed.to_string(), mode);
+        }
+    }
+
+    #[test]
+    fn claims_request() {
+        let mut claims: ClaimsRequest = serde_json::from_value(json!({
+            "userinfo": {
+                "email": {"essential": true},
+                "email_verified": null,
+            },
+            "id_token": {
+                "email": null,
+                "acr": {"values": ["urn:mas:acr:password"]},
+            },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            claims.userinfo.as_ref().unwrap()["email"],
+            Some(IndividualClaimRequest {
+                essential: true,
+                ..IndividualClaimRequest::default()
+            })
+        );
+        assert!(claims.requests_userinfo_claim("email_verified"));
+        assert!(!claims.requests_id_token_claim("email_verified"));
+        assert_eq!(
+            claims.claim_names().collect::<Vec<_>>(),
+            ["acr", "email", "email_verified"]
+        );
+
+        claims.retain(|name| name != "email");
+        assert!(!claims.requests_userinfo_claim("email"));
+        assert!(!claims.requests_id_token_claim("email"));
+        assert_eq!(
+            claims.claim_names().collect::<Vec<_>>(),
+            ["acr", "email_verified"]
+        );
+    }
+
     #[test]
     fn serde_refresh_token_grant() {
         let expected = json!({
@@ -790,6 +1146,7 @@ mod tests {
         let req = AccessTokenRequest::RefreshToken(RefreshTokenGrant {
             refresh_token: "abcd".into(),
             scope,
+            resource: None,
         });
 
         assert_serde_json(&req, expected);
@@ -801,17 +1158,79 @@ mod tests {
             "grant_type": "authorization_code",
             "code": "abcd",
             "redirect_uri": "https://example.com/redirect",
+            "resource": "https://api.example.com/",
         });
 
         let req = AccessTokenRequest::AuthorizationCode(AuthorizationCodeGrant {
             code: "abcd".into(),
             redirect_uri: Some("https://example.com/redirect".parse().unwrap()),
             code_verifier: None,
+            resource: Some("https://api.example.com/".parse().unwrap()),
+        });
+
+        assert_serde_json(&req, expected);
+    }
+
+    #[test]
+    fn serde_device_code_grant() {
+        let expected = json!({
+            "grant_type": "urn:ietf:params:oauth:grant-type:device_code",
+            "device_code": "abcd",
+        });
+
+        let req = AccessTokenRequest::DeviceCode(DeviceCodeGrant {
+            device_code: "abcd".into(),
         });
 
         assert_serde_json(&req, expected);
     }
 
+    #[test]
+    fn serde_token_exchange_grant() {
+        let expected = json!({
+            "grant_type": "urn:ietf:params:oauth:grant-type:token-exchange",
+            "subject_token": "abcd",
+            "subject_token_type": "urn:ietf:params:oauth:token-type:access_token",
+            "scope": "openid",
+        });
+
+        let req = AccessTokenRequest::TokenExchange(TokenExchangeGrant {
+            subject_token: "abcd".into(),
+            subject_token_type: TokenTypeIdentifier::AccessToken,
+            actor_token: None,
+            actor_token_type: None,
+            requested_token_type: None,
+            scope: Some("openid".parse().unwrap()),
+        });
+
+        assert_serde_json(&req, expected);
+    }
+
+    #[test]
+    fn serde_device_authorization_response() {
+        let expected = json!({
+            "device_code": "abcd",
+            "user_code": "WDJB-MJHT",
+            "verification_uri": "https://example.com/link",
+            "verification_uri_complete": "https://example.com/link?code=WDJB-MJHT",
+            "expires_in": 1800,
+            "interval": 5,
+        });
+
+        let res = DeviceAuthorizationResponse {
+            device_code: "abcd".into(),
+            user_code: "WDJB-MJHT".into(),
+            verification_uri: "https://example.com/link".parse().unwrap(),
+            verification_uri_complete: Some(
+                "https://example.com/link?code=WDJB-MJHT".parse().unwrap(),
+            ),
+            expires_in: Duration::minutes(30),
+            interval: Some(Duration::seconds(5)),
+        };
+
+        assert_serde_json(&res, expected);
+    }
+
     #[test]
     fn serialize_grant_type() {
         assert_eq!(
@@ -842,6 +1261,10 @@ mod tests {
             serde_json::to_string(&GrantType::ClientInitiatedBackchannelAuthentication).unwrap(),
             "\"urn:openid:params:grant-type:ciba\""
         );
+        assert_eq!(
+            serde_json::to_string(&GrantType::TokenExchange).unwrap(),
+            "\"urn:ietf:params:oauth:grant-type:token-exchange\""
+        );
     }
 
     #[test]
@@ -875,6 +1298,13 @@ mod tests {
             serde_json::from_str::<GrantType>("\"urn:openid:params:grant-type:ciba\"").unwrap(),
             GrantType::ClientInitiatedBackchannelAuthentication
         );
+        assert_eq!(
+            serde_json::from_str::<GrantType>(
+                "\"urn:ietf:params:oauth:grant-type:token-exchange\""
+            )
+            .unwrap(),
+            GrantType::TokenExchange
+        );
     }
 
     #[test]
@@ -67,7 +67,10 @@ pub async fn access_token_with_client_credentials(
         http_service,
         client_credentials,
         token_endpoint,
-        AccessTokenRequest::ClientCredentials(ClientCredentialsGrant { scope }),
+        AccessTokenRequest::ClientCredentials(ClientCredentialsGrant {
+            scope,
+            resource: None,
+        }),
         now,
         rng,
     )
@@ -82,6 +82,10 @@ async fn pass_introspect_token() {
                 aud: Some(CLIENT_ID.to_owned()),
                 iss: Some(issuer.to_string()),
                 jti: None,
+                cnf: None,
+                auth_time: None,
+                acr: None,
+                amr: None,
             }),
         )
         .mount(&mock_server)
@@ -72,6 +72,8 @@ async fn pass_register_client_none() {
                 client_secret: None,
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
@@ -103,6 +105,8 @@ async fn pass_register_client_client_secret_basic() {
                 client_secret: Some(CLIENT_SECRET.to_owned()),
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
@@ -134,6 +138,8 @@ async fn pass_register_client_client_secret_post() {
                 client_secret: Some(CLIENT_SECRET.to_owned()),
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
@@ -166,6 +172,8 @@ async fn pass_register_client_client_secret_jwt() {
                 client_secret: Some(CLIENT_SECRET.to_owned()),
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
@@ -202,6 +210,8 @@ async fn pass_register_client_private_key_jwt() {
                 client_secret: None,
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
@@ -245,6 +255,8 @@ async fn fail_register_client_missing_secret() {
                 client_secret: None,
                 client_id_issued_at: None,
                 client_secret_expires_at: None,
+                registration_access_token: None,
+                registration_client_uri: None,
             }),
         )
         .mount(&mock_server)
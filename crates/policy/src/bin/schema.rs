@@ -15,7 +15,8 @@
 use std::path::{Path, PathBuf};
 
 use mas_policy::model::{
-    AuthorizationGrantInput, ClientRegistrationInput, EmailInput, PasswordInput, RegisterInput,
+    AuthorizationGrantInput, ClientRegistrationInput, CompatLoginInput, EmailInput, PasswordInput,
+    RegisterInput,
 };
 use schemars::{gen::SchemaSettings, JsonSchema};
 
@@ -54,4 +55,5 @@ fn main() {
     write_schema::<AuthorizationGrantInput>(output_root, "authorization_grant_input.json");
     write_schema::<EmailInput>(output_root, "email_input.json");
     write_schema::<PasswordInput>(output_root, "password_input.json");
+    write_schema::<CompatLoginInput>(output_root, "compat_login_input.json");
 }
@@ -14,7 +14,8 @@
 
 pub mod model;
 
-use mas_data_model::{AuthorizationGrant, Client, User};
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{AuthorizationGrant, Client, DeviceCodeGrant, User};
 use oauth2_types::{registration::VerifiedClientMetadata, scope::Scope};
 use opa_wasm::Runtime;
 use thiserror::Error;
@@ -22,10 +23,11 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 use wasmtime::{Config, Engine, Module, Store};
 
 use self::model::{
-    AuthorizationGrantInput, ClientRegistrationInput, EmailInput, PasswordInput, RegisterInput,
+    AuthorizationGrantInput, ClientRegistrationInput, CompatLoginInput, EmailInput, PasswordInput,
+    RegisterInput,
 };
 pub use self::model::{EvaluationResult, Violation};
-use crate::model::GrantType;
+use crate::model::{CompatLoginType, GrantType};
 
 #[derive(Debug, Error)]
 pub enum LoadError {
@@ -69,16 +71,18 @@ pub struct Entrypoints {
     pub authorization_grant: String,
     pub email: String,
     pub password: String,
+    pub compat_login: String,
 }
 
 impl Entrypoints {
-    fn all(&self) -> [&str; 5] {
+    fn all(&self) -> [&str; 6] {
         [
             self.register.as_str(),
             self.client_registration.as_str(),
             self.authorization_grant.as_str(),
             self.email.as_str(),
             self.password.as_str(),
+            self.compat_login.as_str(),
         ]
     }
 }
@@ -307,11 +311,18 @@ impl Policy {
         authorization_grant: &AuthorizationGrant,
         client: &Client,
         user: &User,
+        groups: &[String],
     ) -> Result<EvaluationResult, EvaluationError> {
         let input = AuthorizationGrantInput {
             user: Some(user),
+            groups,
             client,
             scope: &authorization_grant.scope,
+            claims: authorization_grant
+                .claims
+                .as_ref()
+                .map(|claims| claims.claim_names().collect())
+                .unwrap_or_default(),
             grant_type: GrantType::AuthorizationCode,
         };
 
@@ -327,6 +338,45 @@ impl Policy {
         Ok(res)
     }
 
+    #[tracing::instrument(
+        name = "policy.evaluate.device_code_grant",
+        skip_all,
+        fields(
+            input.device_code_grant.id = %device_code_grant.id,
+            input.scope = %device_code_grant.scope,
+            input.client.id = %client.id,
+            input.user.id = %user.id,
+        ),
+        err,
+    )]
+    pub async fn evaluate_device_code_grant(
+        &mut self,
+        device_code_grant: &DeviceCodeGrant,
+        client: &Client,
+        user: &User,
+        groups: &[String],
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = AuthorizationGrantInput {
+            user: Some(user),
+            groups,
+            client,
+            scope: &device_code_grant.scope,
+            claims: Vec::new(),
+            grant_type: GrantType::DeviceCode,
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(
+                &mut self.store,
+                &self.entrypoints.authorization_grant,
+                &input,
+            )
+            .await?;
+
+        Ok(res)
+    }
+
     #[tracing::instrument(
         name = "policy.evaluate.client_credentials_grant",
         skip_all,
@@ -343,8 +393,10 @@ impl Policy {
     ) -> Result<EvaluationResult, EvaluationError> {
         let input = AuthorizationGrantInput {
             user: None,
+            groups: &[],
             client,
             scope,
+            claims: Vec::new(),
             grant_type: GrantType::ClientCredentials,
         };
 
@@ -359,6 +411,75 @@ impl Policy {
 
         Ok(res)
     }
+
+    #[tracing::instrument(
+        name = "policy.evaluate.token_exchange",
+        skip_all,
+        fields(
+            input.scope = %scope,
+            input.client.id = %client.id,
+            input.user.id = %user.id,
+        ),
+        err,
+    )]
+    pub async fn evaluate_token_exchange(
+        &mut self,
+        scope: &Scope,
+        client: &Client,
+        user: &User,
+        groups: &[String],
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = AuthorizationGrantInput {
+            user: Some(user),
+            groups,
+            client,
+            scope,
+            claims: Vec::new(),
+            grant_type: GrantType::TokenExchange,
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(
+                &mut self.store,
+                &self.entrypoints.authorization_grant,
+                &input,
+            )
+            .await?;
+
+        Ok(res)
+    }
+
+    #[tracing::instrument(
+        name = "policy.evaluate.compat_password_login",
+        skip_all,
+        fields(
+            input.user.id = %user.id,
+        ),
+        err,
+    )]
+    pub async fn evaluate_compat_password_login(
+        &mut self,
+        now: DateTime<Utc>,
+        user: &User,
+        grace_period: Duration,
+    ) -> Result<EvaluationResult, EvaluationError> {
+        let input = CompatLoginInput {
+            user,
+            login_type: CompatLoginType::Password,
+            deprecated_for: user
+                .compat_password_deprecated_at
+                .map(|deprecated_at| (now - deprecated_at).num_seconds()),
+            grace_period: grace_period.num_seconds(),
+        };
+
+        let [res]: [EvaluationResult; 1] = self
+            .instance
+            .evaluate(&mut self.store, &self.entrypoints.compat_login, &input)
+            .await?;
+
+        Ok(res)
+    }
 }
 
 #[cfg(test)]
@@ -387,6 +508,7 @@ mod tests {
             authorization_grant: "authorization_grant/violation".to_owned(),
             email: "email/violation".to_owned(),
             password: "password/violation".to_owned(),
+            compat_login: "compat_login/violation".to_owned(),
         };
 
         let factory = PolicyFactory::load(file, data, entrypoints).await.unwrap();
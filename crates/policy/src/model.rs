@@ -29,6 +29,14 @@ pub struct Violation {
     pub field: Option<String>,
 }
 
+impl Violation {
+    /// The requested claim this violation is about, if any
+    #[must_use]
+    pub fn claim(&self) -> Option<&str> {
+        self.field.as_deref()?.strip_prefix("claims.")
+    }
+}
+
 /// The result of a policy evaluation.
 #[derive(Deserialize, Debug)]
 pub struct EvaluationResult {
@@ -57,6 +65,21 @@ impl EvaluationResult {
     pub fn valid(&self) -> bool {
         self.violations.is_empty()
     }
+
+    /// Returns true if the policy evaluation was successful, not counting the
+    /// violations on requested claims.
+    ///
+    /// Claims the client isn't allowed to get are left out of what it
+    /// receives, instead of denying the whole grant.
+    #[must_use]
+    pub fn valid_ignoring_claims(&self) -> bool {
+        self.violations.iter().all(|v| v.claim().is_some())
+    }
+
+    /// The requested claims the client isn't allowed to get
+    pub fn denied_claims(&self) -> impl Iterator<Item = &str> {
+        self.violations.iter().filter_map(Violation::claim)
+    }
 }
 
 /// Input for the user registration policy.
@@ -98,6 +121,10 @@ pub struct ClientRegistrationInput<'a> {
 pub enum GrantType {
     AuthorizationCode,
     ClientCredentials,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange,
 }
 
 /// Input for the authorization grant policy.
@@ -111,6 +138,9 @@ pub struct AuthorizationGrantInput<'a> {
     )]
     pub user: Option<&'a User>,
 
+    /// The groups the user belongs to
+    pub groups: &'a [String],
+
     #[cfg_attr(
         feature = "jsonschema",
         schemars(with = "std::collections::HashMap<String, serde_json::Value>")
@@ -120,6 +150,9 @@ pub struct AuthorizationGrantInput<'a> {
     #[cfg_attr(feature = "jsonschema", schemars(with = "String"))]
     pub scope: &'a Scope,
 
+    /// The claims requested with the `claims` parameter
+    pub claims: Vec<&'a str>,
+
     pub grant_type: GrantType,
 }
 
@@ -138,3 +171,34 @@ pub struct EmailInput<'a> {
 pub struct PasswordInput<'a> {
     pub password: &'a str,
 }
+
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub enum CompatLoginType {
+    #[serde(rename = "m.login.password")]
+    Password,
+    #[serde(rename = "m.login.token")]
+    Token,
+}
+
+/// Input for the compatibility layer login policy.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "jsonschema", derive(schemars::JsonSchema))]
+pub struct CompatLoginInput<'a> {
+    #[cfg_attr(
+        feature = "jsonschema",
+        schemars(with = "std::collections::HashMap<String, serde_json::Value>")
+    )]
+    pub user: &'a User,
+
+    pub login_type: CompatLoginType,
+
+    /// For how many seconds the password login of the user has been
+    /// deprecated, if it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated_for: Option<i64>,
+
+    /// For how many seconds deprecated password logins are still allowed
+    pub grace_period: i64,
+}
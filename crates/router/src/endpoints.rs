@@ -18,7 +18,7 @@ use ulid::Ulid;
 pub use crate::traits::*;
 use crate::UrlBuilder;
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case", tag = "kind")]
 pub enum PostAuthAction {
     ContinueAuthorizationGrant {
@@ -28,6 +28,9 @@ pub enum PostAuthAction {
         id: Ulid,
     },
     ChangePassword,
+    ManageEmails,
+    ManageWebauthn,
+    ManageRecoveryCodes,
     LinkUpstream {
         id: Ulid,
     },
@@ -35,6 +38,9 @@ pub enum PostAuthAction {
         #[serde(flatten)]
         action: Option<AccountAction>,
     },
+    ContinueDeviceCodeGrant {
+        id: Ulid,
+    },
 }
 
 impl PostAuthAction {
@@ -58,6 +64,11 @@ impl PostAuthAction {
         PostAuthAction::ManageAccount { action }
     }
 
+    #[must_use]
+    pub const fn continue_device_code_grant(id: Ulid) -> Self {
+        PostAuthAction::ContinueDeviceCodeGrant { id }
+    }
+
     pub fn go_next(&self, url_builder: &UrlBuilder) -> axum::response::Redirect {
         match self {
             Self::ContinueAuthorizationGrant { id } => {
@@ -67,10 +78,16 @@ impl PostAuthAction {
                 url_builder.redirect(&CompatLoginSsoComplete::new(*id, None))
             }
             Self::ChangePassword => url_builder.redirect(&AccountPassword),
+            Self::ManageEmails => url_builder.redirect(&AccountEmails),
+            Self::ManageWebauthn => url_builder.redirect(&AccountWebauthn),
+            Self::ManageRecoveryCodes => url_builder.redirect(&AccountRecoveryCodes),
             Self::LinkUpstream { id } => url_builder.redirect(&UpstreamOAuth2Link::new(*id)),
             Self::ManageAccount { action } => url_builder.redirect(&Account {
                 action: action.clone(),
             }),
+            Self::ContinueDeviceCodeGrant { id } => {
+                url_builder.redirect(&DeviceCodeConsent::new(*id))
+            }
         }
     }
 }
@@ -98,6 +115,14 @@ impl SimpleRoute for ChangePasswordDiscovery {
     const PATH: &'static str = "/.well-known/change-password";
 }
 
+/// `GET /.well-known/mas-request-signing-keys.json`
+#[derive(Default, Debug, Clone)]
+pub struct RequestSigningKeys;
+
+impl SimpleRoute for RequestSigningKeys {
+    const PATH: &'static str = "/.well-known/mas-request-signing-keys.json";
+}
+
 /// `GET /oauth2/keys.json`
 #[derive(Default, Debug, Clone)]
 pub struct OAuth2Keys;
@@ -146,6 +171,45 @@ impl SimpleRoute for OAuth2RegistrationEndpoint {
     const PATH: &'static str = "/oauth2/registration";
 }
 
+/// `GET|PUT|DELETE /oauth2/registration/:client_id`
+#[derive(Debug, Clone)]
+pub struct OAuth2ClientConfigurationEndpoint(pub Ulid);
+
+impl Route for OAuth2ClientConfigurationEndpoint {
+    type Query = ();
+    fn route() -> &'static str {
+        "/oauth2/registration/:client_id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/oauth2/registration/{}", self.0).into()
+    }
+}
+
+/// `POST /oauth2/device`
+#[derive(Default, Debug, Clone)]
+pub struct OAuth2DeviceAuthorizationEndpoint;
+
+impl SimpleRoute for OAuth2DeviceAuthorizationEndpoint {
+    const PATH: &'static str = "/oauth2/device";
+}
+
+/// `POST /oauth2/par`
+#[derive(Default, Debug, Clone)]
+pub struct OAuth2PushedAuthorizationRequestEndpoint;
+
+impl SimpleRoute for OAuth2PushedAuthorizationRequestEndpoint {
+    const PATH: &'static str = "/oauth2/par";
+}
+
+/// `GET|POST /oauth2/logout`
+#[derive(Default, Debug, Clone)]
+pub struct OidcEndSession;
+
+impl SimpleRoute for OidcEndSession {
+    const PATH: &'static str = "/oauth2/logout";
+}
+
 /// `GET /authorize`
 #[derive(Default, Debug, Clone)]
 pub struct OAuth2AuthorizationEndpoint;
@@ -170,6 +234,38 @@ impl SimpleRoute for Healthcheck {
     const PATH: &'static str = "/health";
 }
 
+/// `GET /api/capabilities`
+#[derive(Default, Debug, Clone)]
+pub struct Capabilities;
+
+impl SimpleRoute for Capabilities {
+    const PATH: &'static str = "/api/capabilities";
+}
+
+/// `GET /dev/mailbox`
+#[derive(Default, Debug, Clone)]
+pub struct DevMailbox;
+
+impl SimpleRoute for DevMailbox {
+    const PATH: &'static str = "/dev/mailbox";
+}
+
+/// `GET /api/version`
+#[derive(Default, Debug, Clone)]
+pub struct Version;
+
+impl SimpleRoute for Version {
+    const PATH: &'static str = "/api/version";
+}
+
+/// `GET /csrf-token`
+#[derive(Default, Debug, Clone)]
+pub struct CsrfToken;
+
+impl SimpleRoute for CsrfToken {
+    const PATH: &'static str = "/csrf-token";
+}
+
 /// `GET|POST /login`
 #[derive(Default, Debug, Clone)]
 pub struct Login {
@@ -237,6 +333,94 @@ impl From<Option<PostAuthAction>> for Login {
     }
 }
 
+/// `POST /login/webauthn`
+#[derive(Default, Debug, Clone)]
+pub struct LoginWebauthn;
+
+impl SimpleRoute for LoginWebauthn {
+    const PATH: &'static str = "/login/webauthn";
+}
+
+/// `POST /login/webauthn/challenge`
+#[derive(Default, Debug, Clone)]
+pub struct LoginWebauthnChallenge;
+
+impl SimpleRoute for LoginWebauthnChallenge {
+    const PATH: &'static str = "/login/webauthn/challenge";
+}
+
+/// `GET|POST /login/recovery-code`
+#[derive(Default, Debug, Clone)]
+pub struct LoginRecoveryCode {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Route for LoginRecoveryCode {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/login/recovery-code"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl From<Option<PostAuthAction>> for LoginRecoveryCode {
+    fn from(post_auth_action: Option<PostAuthAction>) -> Self {
+        Self { post_auth_action }
+    }
+}
+
+/// `GET|POST /login/link`
+#[derive(Default, Debug, Clone)]
+pub struct LoginLink {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl Route for LoginLink {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/login/link"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl From<Option<PostAuthAction>> for LoginLink {
+    fn from(post_auth_action: Option<PostAuthAction>) -> Self {
+        Self { post_auth_action }
+    }
+}
+
+/// `GET|POST /login/link/:token`
+#[derive(Debug, Clone)]
+pub struct LoginLinkFinish {
+    token: String,
+}
+
+impl LoginLinkFinish {
+    #[must_use]
+    pub fn new(token: String) -> Self {
+        Self { token }
+    }
+}
+
+impl Route for LoginLinkFinish {
+    type Query = ();
+    fn route() -> &'static str {
+        "/login/link/:token"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/login/link/{}", self.token).into()
+    }
+}
+
 /// `POST /logout`
 #[derive(Default, Debug, Clone)]
 pub struct Logout;
@@ -298,6 +482,68 @@ impl From<Option<PostAuthAction>> for Reauth {
     }
 }
 
+/// `GET|POST /terms`
+#[derive(Default, Debug, Clone)]
+pub struct AcceptTerms {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl AcceptTerms {
+    #[must_use]
+    pub fn and_then(action: PostAuthAction) -> Self {
+        Self {
+            post_auth_action: Some(action),
+        }
+    }
+
+    /// Get a reference to the post auth action.
+    #[must_use]
+    pub fn post_auth_action(&self) -> Option<&PostAuthAction> {
+        self.post_auth_action.as_ref()
+    }
+
+    pub fn go_next(&self, url_builder: &UrlBuilder) -> axum::response::Redirect {
+        match &self.post_auth_action {
+            Some(action) => action.go_next(url_builder),
+            None => url_builder.redirect(&Index),
+        }
+    }
+}
+
+impl Route for AcceptTerms {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/terms"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
+impl From<Option<PostAuthAction>> for AcceptTerms {
+    fn from(post_auth_action: Option<PostAuthAction>) -> Self {
+        Self { post_auth_action }
+    }
+}
+
+/// `POST /reauth/webauthn`
+#[derive(Default, Debug, Clone)]
+pub struct ReauthWebauthn;
+
+impl SimpleRoute for ReauthWebauthn {
+    const PATH: &'static str = "/reauth/webauthn";
+}
+
+/// `POST /reauth/webauthn/challenge`
+#[derive(Default, Debug, Clone)]
+pub struct ReauthWebauthnChallenge;
+
+impl SimpleRoute for ReauthWebauthnChallenge {
+    const PATH: &'static str = "/reauth/webauthn/challenge";
+}
+
 /// `GET|POST /register`
 #[derive(Default, Debug, Clone)]
 pub struct Register {
@@ -358,6 +604,32 @@ impl From<Option<PostAuthAction>> for Register {
     }
 }
 
+/// `GET|POST /register/verify`
+#[derive(Default, Debug, Clone)]
+pub struct RegisterVerifyEmail {
+    post_auth_action: Option<PostAuthAction>,
+}
+
+impl RegisterVerifyEmail {
+    #[must_use]
+    pub fn and_maybe(mut self, action: Option<PostAuthAction>) -> Self {
+        self.post_auth_action = action;
+        self
+    }
+}
+
+impl Route for RegisterVerifyEmail {
+    type Query = PostAuthAction;
+
+    fn route() -> &'static str {
+        "/register/verify"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.post_auth_action.as_ref()
+    }
+}
+
 /// `GET|POST /verify-email/:id`
 #[derive(Debug, Clone)]
 pub struct AccountVerifyEmail {
@@ -402,6 +674,38 @@ impl Route for AccountVerifyEmail {
     }
 }
 
+/// `GET|POST /recover`
+#[derive(Default, Debug, Clone)]
+pub struct RecoveryStart;
+
+impl SimpleRoute for RecoveryStart {
+    const PATH: &'static str = "/recover";
+}
+
+/// `GET|POST /recover/:ticket`
+#[derive(Debug, Clone)]
+pub struct RecoveryFinish {
+    ticket: String,
+}
+
+impl RecoveryFinish {
+    #[must_use]
+    pub fn new(ticket: String) -> Self {
+        Self { ticket }
+    }
+}
+
+impl Route for RecoveryFinish {
+    type Query = ();
+    fn route() -> &'static str {
+        "/recover/:ticket"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/recover/{}", self.ticket).into()
+    }
+}
+
 /// `GET /add-email`
 #[derive(Default, Debug, Clone)]
 pub struct AccountAddEmail {
@@ -428,7 +732,7 @@ impl AccountAddEmail {
 }
 
 /// Actions parameters as defined by MSC2965
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "action")]
 pub enum AccountAction {
     #[serde(rename = "org.matrix.profile")]
@@ -486,6 +790,166 @@ impl SimpleRoute for AccountPassword {
     const PATH: &'static str = "/change-password";
 }
 
+/// `GET|POST /account/webauthn`
+#[derive(Default, Debug, Clone)]
+pub struct AccountWebauthn;
+
+impl SimpleRoute for AccountWebauthn {
+    const PATH: &'static str = "/account/webauthn";
+}
+
+/// `POST /account/webauthn/challenge`
+#[derive(Default, Debug, Clone)]
+pub struct AccountWebauthnChallenge;
+
+impl SimpleRoute for AccountWebauthnChallenge {
+    const PATH: &'static str = "/account/webauthn/challenge";
+}
+
+/// `POST /account/webauthn/:id/remove`
+#[derive(Debug, Clone)]
+pub struct AccountWebauthnRemove {
+    id: Ulid,
+}
+
+impl AccountWebauthnRemove {
+    #[must_use]
+    pub fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for AccountWebauthnRemove {
+    type Query = ();
+    fn route() -> &'static str {
+        "/account/webauthn/:id/remove"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/account/webauthn/{}/remove", self.id).into()
+    }
+}
+
+/// `GET /account/emails`
+#[derive(Default, Debug, Clone)]
+pub struct AccountEmails;
+
+impl SimpleRoute for AccountEmails {
+    const PATH: &'static str = "/account/emails";
+}
+
+/// `POST /account/emails/:id/primary`
+#[derive(Debug, Clone)]
+pub struct AccountEmailSetPrimary {
+    id: Ulid,
+}
+
+impl AccountEmailSetPrimary {
+    #[must_use]
+    pub fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for AccountEmailSetPrimary {
+    type Query = ();
+    fn route() -> &'static str {
+        "/account/emails/:id/primary"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/account/emails/{}/primary", self.id).into()
+    }
+}
+
+/// `POST /account/emails/:id/remove`
+#[derive(Debug, Clone)]
+pub struct AccountEmailRemove {
+    id: Ulid,
+}
+
+impl AccountEmailRemove {
+    #[must_use]
+    pub fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for AccountEmailRemove {
+    type Query = ();
+    fn route() -> &'static str {
+        "/account/emails/:id/remove"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/account/emails/{}/remove", self.id).into()
+    }
+}
+
+/// `GET|POST /account/recovery-codes`
+#[derive(Default, Debug, Clone)]
+pub struct AccountRecoveryCodes;
+
+impl SimpleRoute for AccountRecoveryCodes {
+    const PATH: &'static str = "/account/recovery-codes";
+}
+
+/// `GET|POST /account/profile`
+#[derive(Default, Debug, Clone)]
+pub struct AccountProfile;
+
+impl SimpleRoute for AccountProfile {
+    const PATH: &'static str = "/account/profile";
+}
+
+/// `POST /account/profile/avatar`
+#[derive(Default, Debug, Clone)]
+pub struct AccountProfileAvatar;
+
+impl SimpleRoute for AccountProfileAvatar {
+    const PATH: &'static str = "/account/profile/avatar";
+}
+
+/// `GET|POST /account/deactivate`
+#[derive(Default, Debug, Clone)]
+pub struct AccountDeactivate;
+
+impl SimpleRoute for AccountDeactivate {
+    const PATH: &'static str = "/account/deactivate";
+}
+
+/// `GET|POST /account/export`
+#[derive(Default, Debug, Clone)]
+pub struct AccountDataExport;
+
+impl SimpleRoute for AccountDataExport {
+    const PATH: &'static str = "/account/export";
+}
+
+/// `GET /account/export/:token`
+#[derive(Debug, Clone)]
+pub struct AccountDataExportDownload {
+    token: String,
+}
+
+impl AccountDataExportDownload {
+    #[must_use]
+    pub fn new(token: String) -> Self {
+        Self { token }
+    }
+}
+
+impl Route for AccountDataExportDownload {
+    type Query = ();
+    fn route() -> &'static str {
+        "/account/export/:token"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/account/export/{}", self.token).into()
+    }
+}
+
 /// `GET /authorize/:grant_id`
 #[derive(Debug, Clone)]
 pub struct ContinueAuthorizationGrant(pub Ulid);
@@ -516,6 +980,62 @@ impl Route for Consent {
     }
 }
 
+/// `GET /link`
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCodeLink {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+}
+
+impl DeviceCodeLink {
+    /// Pre-fill the verification page with the given user code
+    #[must_use]
+    pub fn with_code(code: String) -> Self {
+        Self { code: Some(code) }
+    }
+
+    /// The user code given in the query parameters, if any
+    #[must_use]
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+}
+
+impl Route for DeviceCodeLink {
+    type Query = DeviceCodeLink;
+    fn route() -> &'static str {
+        "/link"
+    }
+
+    fn query(&self) -> Option<&Self::Query> {
+        self.code.as_ref().map(|_| self)
+    }
+}
+
+/// `GET|POST /device/:device_code_id`
+#[derive(Debug, Clone)]
+pub struct DeviceCodeConsent {
+    id: Ulid,
+}
+
+impl DeviceCodeConsent {
+    #[must_use]
+    pub fn new(id: Ulid) -> Self {
+        Self { id }
+    }
+}
+
+impl Route for DeviceCodeConsent {
+    type Query = ();
+    fn route() -> &'static str {
+        "/device/:device_code_id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/device/{}", self.id).into()
+    }
+}
+
 /// `GET|POST /_matrix/client/v3/login`
 pub struct CompatLogin;
 
@@ -712,6 +1232,30 @@ impl Route for StaticAsset {
     }
 }
 
+/// `GET /avatars/:filename`
+#[derive(Debug, Clone)]
+pub struct Avatar {
+    filename: String,
+}
+
+impl Avatar {
+    #[must_use]
+    pub fn new(filename: String) -> Self {
+        Self { filename }
+    }
+}
+
+impl Route for Avatar {
+    type Query = ();
+    fn route() -> &'static str {
+        "/avatars/:filename"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/avatars/{}", self.filename).into()
+    }
+}
+
 /// `GET|POST /graphql`
 pub struct GraphQL;
 
@@ -719,9 +1263,171 @@ impl SimpleRoute for GraphQL {
     const PATH: &'static str = "/graphql";
 }
 
+/// `GET /graphql/stream`
+pub struct GraphQLStream;
+
+impl SimpleRoute for GraphQLStream {
+    const PATH: &'static str = "/graphql/stream";
+}
+
 /// `GET /graphql/playground`
 pub struct GraphQLPlayground;
 
 impl SimpleRoute for GraphQLPlayground {
     const PATH: &'static str = "/graphql/playground";
 }
+
+/// `GET /api/admin/v1/openapi.json`
+pub struct AdminApiSpec;
+
+impl SimpleRoute for AdminApiSpec {
+    const PATH: &'static str = "/api/admin/v1/openapi.json";
+}
+
+/// `GET|POST /api/admin/v1/users`
+pub struct AdminUsers;
+
+impl SimpleRoute for AdminUsers {
+    const PATH: &'static str = "/api/admin/v1/users";
+}
+
+/// `GET /api/admin/v1/users/:id`
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub Ulid);
+
+impl Route for AdminUser {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/users/:id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/users/{}", self.0).into()
+    }
+}
+
+/// `POST /api/admin/v1/users/:id/lock`
+#[derive(Debug, Clone)]
+pub struct AdminLockUser(pub Ulid);
+
+impl Route for AdminLockUser {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/users/:id/lock"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/users/{}/lock", self.0).into()
+    }
+}
+
+/// `POST /api/admin/v1/users/:id/unlock`
+#[derive(Debug, Clone)]
+pub struct AdminUnlockUser(pub Ulid);
+
+impl Route for AdminUnlockUser {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/users/:id/unlock"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/users/{}/unlock", self.0).into()
+    }
+}
+
+/// `GET /api/admin/v1/oauth2-sessions`
+pub struct AdminOAuth2Sessions;
+
+impl SimpleRoute for AdminOAuth2Sessions {
+    const PATH: &'static str = "/api/admin/v1/oauth2-sessions";
+}
+
+/// `GET /api/admin/v1/oauth2-sessions/:id`
+#[derive(Debug, Clone)]
+pub struct AdminOAuth2Session(pub Ulid);
+
+impl Route for AdminOAuth2Session {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/oauth2-sessions/:id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/oauth2-sessions/{}", self.0).into()
+    }
+}
+
+/// `POST /api/admin/v1/oauth2-sessions/:id/finish`
+#[derive(Debug, Clone)]
+pub struct AdminFinishOAuth2Session(pub Ulid);
+
+impl Route for AdminFinishOAuth2Session {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/oauth2-sessions/:id/finish"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/oauth2-sessions/{}/finish", self.0).into()
+    }
+}
+
+/// `GET /api/admin/v1/compat-sessions`
+pub struct AdminCompatSessions;
+
+impl SimpleRoute for AdminCompatSessions {
+    const PATH: &'static str = "/api/admin/v1/compat-sessions";
+}
+
+/// `GET /api/admin/v1/compat-sessions/:id`
+#[derive(Debug, Clone)]
+pub struct AdminCompatSession(pub Ulid);
+
+impl Route for AdminCompatSession {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/compat-sessions/:id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/compat-sessions/{}", self.0).into()
+    }
+}
+
+/// `POST /api/admin/v1/compat-sessions/:id/finish`
+#[derive(Debug, Clone)]
+pub struct AdminFinishCompatSession(pub Ulid);
+
+impl Route for AdminFinishCompatSession {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/compat-sessions/:id/finish"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/compat-sessions/{}/finish", self.0).into()
+    }
+}
+
+/// `GET /api/admin/v1/oauth2-clients/:id`
+#[derive(Debug, Clone)]
+pub struct AdminOAuth2Client(pub Ulid);
+
+impl Route for AdminOAuth2Client {
+    type Query = ();
+    fn route() -> &'static str {
+        "/api/admin/v1/oauth2-clients/:id"
+    }
+
+    fn path(&self) -> std::borrow::Cow<'static, str> {
+        format!("/api/admin/v1/oauth2-clients/{}", self.0).into()
+    }
+}
+
+/// `POST /api/admin/v1/tokens/revoke`
+pub struct AdminRevokeToken;
+
+impl SimpleRoute for AdminRevokeToken {
+    const PATH: &'static str = "/api/admin/v1/tokens/revoke";
+}
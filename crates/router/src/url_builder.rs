@@ -28,7 +28,9 @@ pub struct UrlBuilder {
 }
 
 impl UrlBuilder {
-    fn absolute_url_for<U>(&self, destination: &U) -> Url
+    /// Create an absolute URL for a route
+    #[must_use]
+    pub fn absolute_url_for<U>(&self, destination: &U) -> Url
     where
         U: Route,
     {
@@ -148,12 +150,51 @@ impl UrlBuilder {
         self.absolute_url_for(&crate::endpoints::OAuth2Revocation)
     }
 
+    /// OAuth 2.0 device authorization endpoint
+    #[must_use]
+    pub fn oauth_device_authorization_endpoint(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::OAuth2DeviceAuthorizationEndpoint)
+    }
+
+    /// OAuth 2.0 pushed authorization request endpoint
+    #[must_use]
+    pub fn oauth_pushed_authorization_request_endpoint(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::OAuth2PushedAuthorizationRequestEndpoint)
+    }
+
+    /// OpenID Connect RP-initiated logout endpoint
+    #[must_use]
+    pub fn oidc_end_session_endpoint(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::OidcEndSession)
+    }
+
+    /// Device code grant verification page, where users type the code shown
+    /// on their device
+    #[must_use]
+    pub fn device_code_link(&self) -> Url {
+        self.absolute_url_for(&crate::endpoints::DeviceCodeLink::default())
+    }
+
+    /// Device code grant verification page, with the user code already filled
+    /// in
+    #[must_use]
+    pub fn device_code_link_with_code(&self, code: String) -> Url {
+        self.absolute_url_for(&crate::endpoints::DeviceCodeLink::with_code(code))
+    }
+
     /// OAuth 2.0 client registration endpoint
     #[must_use]
     pub fn oauth_registration_endpoint(&self) -> Url {
         self.absolute_url_for(&crate::endpoints::OAuth2RegistrationEndpoint)
     }
 
+    /// OAuth 2.0 client configuration endpoint, used to manage a dynamically
+    /// registered client
+    #[must_use]
+    pub fn oauth_client_configuration_endpoint(&self, id: Ulid) -> Url {
+        self.absolute_url_for(&crate::endpoints::OAuth2ClientConfigurationEndpoint(id))
+    }
+
     // OIDC userinfo endpoint
     #[must_use]
     pub fn oidc_userinfo_endpoint(&self) -> Url {
@@ -178,6 +219,12 @@ impl UrlBuilder {
         &self.assets_base
     }
 
+    /// URL of an avatar uploaded by a user
+    #[must_use]
+    pub fn avatar(&self, filename: String) -> Url {
+        self.absolute_url_for(&crate::endpoints::Avatar::new(filename))
+    }
+
     /// GraphQL endpoint
     #[must_use]
     pub fn graphql_endpoint(&self) -> Url {
@@ -0,0 +1,31 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helps sending text messages to users, with different SMS gateways
+
+#![deny(missing_docs)]
+
+mod rate_limit;
+mod sender;
+mod transport;
+
+pub use mas_templates::SmsVerificationContext;
+
+pub use self::{
+    rate_limit::RateLimit,
+    sender::{Error, SmsSender},
+    transport::{
+        BlackholeTransport, Error as TransportError, HttpTransport, SmsTransport, TwilioTransport,
+    },
+};
@@ -0,0 +1,108 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rate limiting of the messages sent to a single phone number
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU32,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A rate limit on the messages sent to a single phone number, as a bucket of
+/// tokens: each message takes a token, and the bucket gets a new token every
+/// `replenish_interval`, up to `burst` tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    burst: NonZeroU32,
+    replenish_interval: Duration,
+}
+
+impl RateLimit {
+    /// Create a new rate limit, allowing `burst` messages at once and a new
+    /// message every `replenish_interval`
+    #[must_use]
+    pub const fn new(burst: NonZeroU32, replenish_interval: Duration) -> Self {
+        Self {
+            burst,
+            replenish_interval,
+        }
+    }
+}
+
+/// Keeps track of the messages sent to each phone number, in memory
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    limit: RateLimit,
+
+    /// The time at which each bucket will be full again
+    buckets: Mutex<HashMap<String, Instant>>,
+}
+
+impl Limiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Take a token from the bucket of the given phone number, returning the
+    /// time at which a message will be allowed again if it is empty
+    pub(crate) fn take(&self, now: Instant, to: &str) -> Result<(), Instant> {
+        let interval = self.limit.replenish_interval;
+        let capacity = interval * self.limit.burst.get();
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Forget about the buckets which are full again
+        buckets.retain(|_, full_at| *full_at > now);
+
+        let full_at = buckets.get(to).copied().unwrap_or(now).max(now);
+        let new_full_at = full_at + interval;
+        if new_full_at - now > capacity {
+            return Err(new_full_at - capacity);
+        }
+
+        buckets.insert(to.to_owned(), new_full_at);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter() {
+        let limiter = Limiter::new(RateLimit::new(
+            NonZeroU32::new(2).unwrap(),
+            Duration::from_secs(60),
+        ));
+        let now = Instant::now();
+
+        limiter.take(now, "+15550001").unwrap();
+        limiter.take(now, "+15550001").unwrap();
+        let retry_at = limiter.take(now, "+15550001").unwrap_err();
+        assert_eq!(retry_at, now + Duration::from_secs(60));
+
+        // Other phone numbers are independent
+        limiter.take(now, "+15550002").unwrap();
+
+        let now = now + Duration::from_secs(60);
+        limiter.take(now, "+15550001").unwrap();
+        limiter.take(now, "+15550001").unwrap_err();
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Send text messages to users
+
+use std::{sync::Arc, time::Instant};
+
+use mas_templates::{SmsVerificationContext, Templates, WithLanguage};
+use thiserror::Error;
+
+use crate::{
+    rate_limit::{Limiter, RateLimit},
+    SmsTransport,
+};
+
+/// Helps sending text messages to users
+#[derive(Clone)]
+pub struct SmsSender {
+    templates: Templates,
+    transport: Arc<dyn SmsTransport>,
+    limiter: Arc<Limiter>,
+}
+
+/// An error which happened while sending a text message
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The phone number is not in the E.164 format
+    #[error("Phone number {0:?} is not in the E.164 format")]
+    InvalidPhoneNumber(String),
+
+    /// Too many messages were sent to this phone number recently
+    #[error("Too many messages were sent to this phone number recently")]
+    RateLimited {
+        /// When a message will be allowed again
+        retry_at: Instant,
+    },
+
+    /// The message failed rendering
+    #[error(transparent)]
+    Templates(#[from] mas_templates::TemplateError),
+
+    /// The message failed sending
+    #[error(transparent)]
+    Transport(#[from] crate::TransportError),
+}
+
+/// Whether the phone number is in the E.164 format, a `+` followed by up to 15
+/// digits
+fn is_e164(phone_number: &str) -> bool {
+    let Some(digits) = phone_number.strip_prefix('+') else {
+        return false;
+    };
+
+    (2..=15).contains(&digits.len())
+        && !digits.starts_with('0')
+        && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl SmsSender {
+    /// Constructs a new [`SmsSender`], sending at most as many messages to a
+    /// single phone number as allowed by the given [`RateLimit`]
+    #[must_use]
+    pub fn new(
+        templates: Templates,
+        transport: impl SmsTransport + 'static,
+        rate_limit: RateLimit,
+    ) -> Self {
+        Self {
+            templates,
+            transport: Arc::new(transport),
+            limiter: Arc::new(Limiter::new(rate_limit)),
+        }
+    }
+
+    /// Send a verification code to a phone number
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the phone number is invalid, if too many messages
+    /// were sent to it recently, or if the message failed rendering or failed
+    /// sending
+    #[tracing::instrument(
+        name = "sms.verification.send",
+        skip_all,
+        fields(
+            sms.language = %context.language(),
+        ),
+        err,
+    )]
+    pub async fn send_verification_code(
+        &self,
+        to: &str,
+        context: &WithLanguage<SmsVerificationContext>,
+    ) -> Result<(), Error> {
+        if !is_e164(to) {
+            return Err(Error::InvalidPhoneNumber(to.to_owned()));
+        }
+
+        let body = self.templates.render_sms_verification(context)?;
+
+        self.limiter
+            .take(Instant::now(), to)
+            .map_err(|retry_at| Error::RateLimited { retry_at })?;
+
+        self.transport.send(to, body.trim()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_e164() {
+        assert!(is_e164("+33612345678"));
+        assert!(is_e164("+15555550100"));
+        assert!(!is_e164("0612345678"));
+        assert!(!is_e164("+0612345678"));
+        assert!(!is_e164("+33 6 12 34 56 78"));
+        assert!(!is_e164("+1234567890123456"));
+        assert!(!is_e164("+"));
+    }
+}
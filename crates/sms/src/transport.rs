@@ -0,0 +1,206 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SMS gateway backends
+
+use async_trait::async_trait;
+use headers::{Authorization, HeaderMapExt};
+use http::{Request, StatusCode};
+use mas_axum_utils::http_client_factory::HttpClientFactory;
+use mas_http::HttpServiceExt;
+use serde::Serialize;
+use thiserror::Error;
+use tower::{BoxError, Service, ServiceExt};
+use url::Url;
+
+/// The base URL of the Twilio REST API
+const TWILIO_API_BASE: &str = "https://api.twilio.com/2010-04-01/";
+
+/// An error which happened while sending a text message
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The request to the gateway could not be built
+    #[error("Failed to build the request to the SMS gateway")]
+    Request(#[from] http::Error),
+
+    /// The gateway could not be reached
+    #[error("Failed to call the SMS gateway")]
+    Call(#[source] BoxError),
+
+    /// The gateway rejected the message
+    #[error("The SMS gateway responded with status {0}")]
+    UnexpectedStatus(StatusCode),
+}
+
+/// A backend able to send text messages to phone numbers
+#[async_trait]
+pub trait SmsTransport: Send + Sync {
+    /// Send a text message to a phone number, in the E.164 format
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be handed over to the gateway
+    async fn send(&self, to: &str, body: &str) -> Result<(), Error>;
+}
+
+/// A transport which drops all the messages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlackholeTransport;
+
+#[async_trait]
+impl SmsTransport for BlackholeTransport {
+    async fn send(&self, _to: &str, _body: &str) -> Result<(), Error> {
+        tracing::warn!("A text message was supposed to be sent but no SMS backend is configured");
+        Ok(())
+    }
+}
+
+/// A transport sending messages through the Twilio Programmable Messaging API
+#[derive(Debug, Clone)]
+pub struct TwilioTransport {
+    http_client_factory: HttpClientFactory,
+    account_sid: String,
+    auth_token: String,
+    from: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct TwilioMessage<'a> {
+    to: &'a str,
+    from: &'a str,
+    body: &'a str,
+}
+
+impl TwilioTransport {
+    /// Constructs a new Twilio transport, sending messages from the given
+    /// phone number
+    #[must_use]
+    pub fn new(
+        http_client_factory: HttpClientFactory,
+        account_sid: String,
+        auth_token: String,
+        from: String,
+    ) -> Self {
+        Self {
+            http_client_factory,
+            account_sid,
+            auth_token,
+            from,
+        }
+    }
+}
+
+#[async_trait]
+impl SmsTransport for TwilioTransport {
+    #[tracing::instrument(name = "sms.twilio.send", skip_all, err)]
+    async fn send(&self, to: &str, body: &str) -> Result<(), Error> {
+        let uri = format!(
+            "{TWILIO_API_BASE}Accounts/{account_sid}/Messages.json",
+            account_sid = self.account_sid,
+        );
+
+        let mut request = Request::post(uri).body(TwilioMessage {
+            to,
+            from: &self.from,
+            body,
+        })?;
+        request
+            .headers_mut()
+            .typed_insert(Authorization::basic(&self.account_sid, &self.auth_token));
+
+        let mut client = self
+            .http_client_factory
+            .client("sms.twilio.send")
+            .request_bytes_to_body()
+            .form_urlencoded_request();
+
+        let response = client
+            .ready()
+            .await
+            .map_err(|e| Error::Call(e.into()))?
+            .call(request)
+            .await
+            .map_err(|e| Error::Call(e.into()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::UnexpectedStatus(response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// A transport sending messages to a generic HTTP endpoint, as a JSON object
+/// with the `to` and `body` fields
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    http_client_factory: HttpClientFactory,
+    url: Url,
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct HttpMessage<'a> {
+    to: &'a str,
+    body: &'a str,
+}
+
+impl HttpTransport {
+    /// Constructs a new HTTP transport, sending messages to the given URL,
+    /// optionally authenticating with a bearer token
+    #[must_use]
+    pub fn new(http_client_factory: HttpClientFactory, url: Url, token: Option<String>) -> Self {
+        Self {
+            http_client_factory,
+            url,
+            token,
+        }
+    }
+}
+
+#[async_trait]
+impl SmsTransport for HttpTransport {
+    #[tracing::instrument(name = "sms.http.send", skip_all, fields(url.full = %self.url), err)]
+    async fn send(&self, to: &str, body: &str) -> Result<(), Error> {
+        let mut request = Request::post(self.url.as_str()).body(HttpMessage { to, body })?;
+
+        if let Some(token) = &self.token {
+            // A token which isn't a valid header value can't be sent
+            let authorization =
+                Authorization::bearer(token).map_err(|e| Error::Call(Box::new(e)))?;
+            request.headers_mut().typed_insert(authorization);
+        }
+
+        let mut client = self
+            .http_client_factory
+            .client("sms.http.send")
+            .request_bytes_to_body()
+            .json_request();
+
+        let response = client
+            .ready()
+            .await
+            .map_err(|e| Error::Call(e.into()))?
+            .call(request)
+            .await
+            .map_err(|e| Error::Call(e.into()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::UnexpectedStatus(response.status()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Keeps an audit record of the dynamically-registered clients which got
+-- garbage-collected because they had no activity for a while
+CREATE TABLE "oauth2_client_deletions" (
+  "oauth2_client_id" UUID NOT NULL
+    CONSTRAINT "oauth2_client_deletions_pkey"
+    PRIMARY KEY,
+
+  "client_name" TEXT,
+  "redirect_uris" TEXT[] NOT NULL,
+
+  -- When the client was last seen active, if ever
+  "last_active_at" TIMESTAMP WITH TIME ZONE,
+
+  "deleted_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
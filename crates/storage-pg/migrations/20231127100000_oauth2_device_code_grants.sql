@@ -0,0 +1,58 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Device code grants, as per RFC 8628. A device requests one, and a user
+-- approves or rejects it from another device using the user code.
+CREATE TABLE "oauth2_device_code_grants" (
+  "oauth2_device_code_grant_id" UUID NOT NULL
+    CONSTRAINT "oauth2_device_code_grants_pkey"
+    PRIMARY KEY,
+
+  "oauth2_client_id" UUID NOT NULL
+    CONSTRAINT "oauth2_device_code_grants_oauth2_client_id_fkey"
+    REFERENCES "oauth2_clients" ("oauth2_client_id"),
+
+  "scope" TEXT NOT NULL,
+
+  "device_code" TEXT NOT NULL
+    CONSTRAINT "oauth2_device_code_grants_device_code_unique"
+    UNIQUE,
+
+  "user_code" TEXT NOT NULL
+    CONSTRAINT "oauth2_device_code_grants_user_code_unique"
+    UNIQUE,
+
+  -- The browser session which approved or rejected the grant
+  "user_session_id" UUID
+    CONSTRAINT "oauth2_device_code_grants_user_session_id_fkey"
+    REFERENCES "user_sessions" ("user_session_id"),
+
+  -- The session created when the grant was exchanged
+  "oauth2_session_id" UUID
+    CONSTRAINT "oauth2_device_code_grants_oauth2_session_id_fkey"
+    REFERENCES "oauth2_sessions" ("oauth2_session_id"),
+
+  "ip_address" INET,
+  "user_agent" TEXT,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "fulfilled_at" TIMESTAMP WITH TIME ZONE,
+  "rejected_at" TIMESTAMP WITH TIME ZONE,
+  "exchanged_at" TIMESTAMP WITH TIME ZONE
+);
+
+-- This adds a column to the oauth2_clients to allow them to use the device code flow
+ALTER TABLE oauth2_clients
+    ADD COLUMN grant_type_device_code boolean NOT NULL DEFAULT false;
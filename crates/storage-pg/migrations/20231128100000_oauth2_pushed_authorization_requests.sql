@@ -0,0 +1,37 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Pushed authorization requests, as per RFC 9126. A client pushes the
+-- parameters of an authorization request, and then refers to them with a
+-- `request_uri` when redirecting the user to the authorization endpoint.
+CREATE TABLE "oauth2_pushed_authorization_requests" (
+  "oauth2_pushed_authorization_request_id" UUID NOT NULL
+    CONSTRAINT "oauth2_pushed_authorization_requests_pkey"
+    PRIMARY KEY,
+
+  "oauth2_client_id" UUID NOT NULL
+    CONSTRAINT "oauth2_pushed_authorization_requests_oauth2_client_id_fkey"
+    REFERENCES "oauth2_clients" ("oauth2_client_id"),
+
+  "parameters" JSONB NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "consumed_at" TIMESTAMP WITH TIME ZONE
+);
+
+-- This adds a column to the oauth2_clients to force them to push their
+-- authorization requests
+ALTER TABLE oauth2_clients
+    ADD COLUMN require_pushed_authorization_requests boolean NOT NULL DEFAULT false;
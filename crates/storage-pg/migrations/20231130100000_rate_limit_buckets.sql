@@ -0,0 +1,28 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- State of the rate limiting token buckets, shared between all the replicas.
+-- Each bucket only stores the time at which it will be full again, from which
+-- the number of available tokens can be derived.
+CREATE TABLE "rate_limit_buckets" (
+  "bucket_key" TEXT NOT NULL
+    CONSTRAINT "rate_limit_buckets_pkey"
+    PRIMARY KEY,
+
+  "full_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+-- Used to clean up the buckets which are full again
+CREATE INDEX "rate_limit_buckets_full_at_idx"
+  ON "rate_limit_buckets" ("full_at");
@@ -0,0 +1,27 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Sessions created through the token exchange grant are derived from another
+-- session, and get finished alongside it
+ALTER TABLE "oauth2_sessions"
+  ADD COLUMN "parent_oauth2_session_id" UUID
+    REFERENCES "oauth2_sessions" ("oauth2_session_id");
+
+CREATE INDEX "oauth2_sessions_parent_oauth2_session_id_idx"
+  ON "oauth2_sessions" ("parent_oauth2_session_id");
+
+-- This adds a column to the oauth2_clients to allow them to use the token
+-- exchange grant
+ALTER TABLE oauth2_clients
+    ADD COLUMN grant_type_token_exchange boolean NOT NULL DEFAULT false;
@@ -0,0 +1,31 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- State of the data migrations which run in the background, in batches, while
+-- the service is running. They get registered by inserting a row in this table
+-- from a regular migration, and are then picked up by the workers.
+CREATE TABLE "background_migrations" (
+  "name" TEXT NOT NULL
+    CONSTRAINT "background_migrations_pkey"
+    PRIMARY KEY,
+
+  -- Opaque position of the last processed row, interpreted by the migration
+  "cursor" TEXT,
+
+  "processed_rows" BIGINT NOT NULL DEFAULT 0,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+  "updated_at" TIMESTAMP WITH TIME ZONE,
+  "completed_at" TIMESTAMP WITH TIME ZONE
+);
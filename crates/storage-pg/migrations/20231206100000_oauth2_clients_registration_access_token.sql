@@ -0,0 +1,20 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Stores the hash of the registration access token handed out to dynamically
+-- registered clients, which lets them manage their own registration (RFC 7592)
+ALTER TABLE "oauth2_clients"
+    ADD COLUMN "registration_access_token_hash" TEXT
+        CONSTRAINT "oauth2_clients_registration_access_token_hash_unique"
+        UNIQUE;
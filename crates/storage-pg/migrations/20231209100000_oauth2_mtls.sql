@@ -0,0 +1,25 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Clients authenticating with `tls_client_auth` are identified by the subject
+-- of their certificate, and clients can ask for their access tokens to be
+-- bound to their certificate
+ALTER TABLE "oauth2_clients"
+  ADD COLUMN "tls_client_auth_subject_dn" TEXT,
+  ADD COLUMN "tls_client_certificate_bound_access_tokens" BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Sessions can be bound to a client certificate, in which case all the tokens
+-- issued for them are sender-constrained to this certificate
+ALTER TABLE "oauth2_sessions"
+  ADD COLUMN "certificate_thumbprint" TEXT;
@@ -0,0 +1,44 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Notifications of new sign-ins, shown on the other sessions of the user until
+-- one of them acknowledges it. A sign-in is either a new browser session, or a
+-- new compatibility session from a password login.
+CREATE TABLE "user_sign_in_notifications" (
+  "user_sign_in_notification_id" UUID NOT NULL
+    CONSTRAINT "user_sign_in_notifications_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_sign_in_notifications_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  "user_session_id" UUID
+    CONSTRAINT "user_sign_in_notifications_user_session_id_fkey"
+    REFERENCES "user_sessions" ("user_session_id"),
+
+  "compat_session_id" UUID
+    CONSTRAINT "user_sign_in_notifications_compat_session_id_fkey"
+    REFERENCES "compat_sessions" ("compat_session_id"),
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "acknowledged_at" TIMESTAMP WITH TIME ZONE,
+
+  CONSTRAINT "user_sign_in_notifications_session_check"
+    CHECK (("user_session_id" IS NULL) <> ("compat_session_id" IS NULL))
+);
+
+CREATE INDEX "user_sign_in_notifications_user_id_idx"
+  ON "user_sign_in_notifications" ("user_id")
+  WHERE "acknowledged_at" IS NULL;
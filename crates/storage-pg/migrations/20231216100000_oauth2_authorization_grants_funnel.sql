@@ -0,0 +1,24 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Track how far authorization grants go in the authorization flow, to find
+-- out where users drop off
+ALTER TABLE "oauth2_authorization_grants"
+  ADD COLUMN "login_shown_at" TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN "consent_shown_at" TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN "errored_at" TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN "abandoned_at" TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX "oauth2_authorization_grants_created_at_idx"
+  ON "oauth2_authorization_grants" ("created_at");
@@ -0,0 +1,30 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Keeps an audit record of the refresh tokens which were used again after
+-- being consumed, which usually means they leaked
+CREATE TABLE "oauth2_refresh_token_reuses" (
+  "oauth2_refresh_token_reuse_id" UUID NOT NULL
+    CONSTRAINT "oauth2_refresh_token_reuses_pkey"
+    PRIMARY KEY,
+
+  -- Refresh tokens and sessions get cleaned up, so those are not foreign keys
+  "oauth2_refresh_token_id" UUID NOT NULL,
+  "oauth2_session_id" UUID NOT NULL,
+
+  -- Whether the session was ended because of the reuse
+  "session_revoked" BOOLEAN NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
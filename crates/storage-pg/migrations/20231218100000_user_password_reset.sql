@@ -0,0 +1,37 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Set by an administrator to force the user to pick a new password, e.g. after
+-- a suspected credential leak
+ALTER TABLE "users"
+  ADD COLUMN "password_reset_required_at" TIMESTAMP WITH TIME ZONE;
+
+-- Single-use tickets sent by email to let a user choose a new password
+CREATE TABLE "user_recovery_tickets" (
+  "user_recovery_ticket_id" UUID NOT NULL
+    CONSTRAINT "user_recovery_tickets_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_recovery_tickets_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  "ticket" TEXT NOT NULL
+    CONSTRAINT "user_recovery_tickets_ticket_unique"
+    UNIQUE,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "consumed_at" TIMESTAMP WITH TIME ZONE
+);
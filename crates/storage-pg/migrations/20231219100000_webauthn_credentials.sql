@@ -0,0 +1,46 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- WebAuthn credentials (passkeys, security keys) registered by users to sign in
+-- without a password
+CREATE TABLE "webauthn_credentials" (
+  "webauthn_credential_id" UUID NOT NULL
+    CONSTRAINT "webauthn_credentials_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "webauthn_credentials_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The credential ID sent by the authenticator, in URL-safe base64
+  "credential_id" TEXT NOT NULL
+    CONSTRAINT "webauthn_credentials_credential_id_unique"
+    UNIQUE,
+
+  "name" TEXT NOT NULL,
+
+  -- The public key and signature counter, as serialized by the WebAuthn library
+  "passkey" JSONB NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "last_used_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "webauthn_credentials_user_id_idx"
+  ON "webauthn_credentials" ("user_id");
+
+ALTER TABLE "user_session_authentications"
+  ADD COLUMN "webauthn_credential_id" UUID
+    REFERENCES "webauthn_credentials" ("webauthn_credential_id")
+    ON DELETE SET NULL;
@@ -0,0 +1,41 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- One-time recovery codes, which users can sign in with when they lost access
+-- to their passkeys. Only a hash of the codes is stored.
+CREATE TABLE "user_recovery_codes" (
+  "user_recovery_code_id" UUID NOT NULL
+    CONSTRAINT "user_recovery_codes_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_recovery_codes_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- SHA-256 of the normalized code, hex-encoded
+  "code_hash" TEXT NOT NULL,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  -- When the code was used to sign in. Codes can only be used once
+  "consumed_at" TIMESTAMP WITH TIME ZONE,
+
+  CONSTRAINT "user_recovery_codes_user_id_code_hash_unique"
+    UNIQUE ("user_id", "code_hash")
+);
+
+ALTER TABLE "user_session_authentications"
+  ADD COLUMN "user_recovery_code_id" UUID
+    REFERENCES "user_recovery_codes" ("user_recovery_code_id")
+    ON DELETE SET NULL;
@@ -0,0 +1,25 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Deleted users are kept for a grace period, during which they can be
+-- restored. Once purged, only a tombstone of the user is kept, so that its
+-- username can never be reused.
+ALTER TABLE "users"
+  ADD COLUMN "deleted_at" TIMESTAMP WITH TIME ZONE,
+  ADD COLUMN "purged_at" TIMESTAMP WITH TIME ZONE;
+
+-- Used by the job looking for users to purge
+CREATE INDEX "users_deleted_at_idx"
+  ON "users" ("deleted_at")
+  WHERE "deleted_at" IS NOT NULL AND "purged_at" IS NULL;
@@ -0,0 +1,48 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Single-use links sent by email to let a user sign in without a password
+CREATE TABLE "user_login_links" (
+  "user_login_link_id" UUID NOT NULL
+    CONSTRAINT "user_login_links_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_login_links_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The email address the link was sent to. Removing the address revokes the
+  -- links which were sent to it
+  "user_email_id" UUID NOT NULL
+    CONSTRAINT "user_login_links_user_email_id_fkey"
+    REFERENCES "user_emails" ("user_email_id")
+    ON DELETE CASCADE,
+
+  "token" TEXT NOT NULL
+    CONSTRAINT "user_login_links_token_unique"
+    UNIQUE,
+
+  -- Where to send the user once they are logged in, so that e.g. an
+  -- authorization grant started in the browser resumes after the link is used
+  "post_auth_action" JSONB,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "expires_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "consumed_at" TIMESTAMP WITH TIME ZONE
+);
+
+ALTER TABLE "user_session_authentications"
+  ADD COLUMN "user_login_link_id" UUID
+    REFERENCES "user_login_links" ("user_login_link_id")
+    ON DELETE SET NULL;
@@ -0,0 +1,31 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Counters of failed login attempts, per user and per IP address, used to
+-- temporarily lock them out after too many failures
+CREATE TABLE "login_failures" (
+  "failure_key" TEXT NOT NULL
+    CONSTRAINT "login_failures_pkey"
+    PRIMARY KEY,
+
+  "failed_attempts" INTEGER NOT NULL,
+
+  "last_failed_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  "locked_until" TIMESTAMP WITH TIME ZONE
+);
+
+-- Used to clean up the counters which are not relevant anymore
+CREATE INDEX "login_failures_last_failed_at_idx"
+  ON "login_failures" ("last_failed_at");
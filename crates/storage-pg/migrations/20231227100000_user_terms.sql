@@ -0,0 +1,33 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Versions of the terms of service the users accepted
+CREATE TABLE "user_terms" (
+  "user_terms_id" UUID NOT NULL
+    CONSTRAINT "user_terms_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_terms_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  "version" TEXT NOT NULL,
+
+  "url" TEXT NOT NULL,
+
+  "accepted_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+
+  CONSTRAINT "user_terms_user_id_version_unique"
+    UNIQUE ("user_id", "version")
+);
@@ -0,0 +1,40 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Exports of all the data held about a user, requested by the user
+CREATE TABLE "user_data_exports" (
+  "user_data_export_id" UUID NOT NULL
+    CONSTRAINT "user_data_exports_pkey"
+    PRIMARY KEY,
+
+  "user_id" UUID NOT NULL
+    CONSTRAINT "user_data_exports_user_id_fkey"
+    REFERENCES "users" ("user_id"),
+
+  -- The secret part of the download link
+  "token" TEXT NOT NULL
+    CONSTRAINT "user_data_exports_token_unique"
+    UNIQUE,
+
+  -- The exported data, set once the export is done, and cleared once it
+  -- expired
+  "archive" JSONB,
+
+  "created_at" TIMESTAMP WITH TIME ZONE NOT NULL,
+  "completed_at" TIMESTAMP WITH TIME ZONE,
+  "expires_at" TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX "user_data_exports_user_id_created_at_idx"
+  ON "user_data_exports" ("user_id", "created_at");
@@ -0,0 +1,30 @@
+-- Copyright 2023 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Usage statistics, computed for each day by a scheduled job
+CREATE TABLE "daily_statistics" (
+  -- The day, in UTC
+  "day" DATE NOT NULL
+    CONSTRAINT "daily_statistics_pkey"
+    PRIMARY KEY,
+
+  "new_users" BIGINT NOT NULL,
+  "active_users" BIGINT NOT NULL,
+  "active_sessions" BIGINT NOT NULL,
+  "tokens_issued" BIGINT NOT NULL,
+
+  -- When the statistics were last computed. They are recomputed during the
+  -- day, and one last time once it ended
+  "computed_at" TIMESTAMP WITH TIME ZONE NOT NULL
+);
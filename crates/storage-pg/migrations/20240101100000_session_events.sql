@@ -0,0 +1,79 @@
+-- Copyright 2024 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Notify the `mas::session_events` channel when sessions are started or ended,
+-- so that clients can be updated live
+CREATE FUNCTION "notify_session_event"() RETURNS TRIGGER AS $$
+  DECLARE
+    "kind" TEXT;
+  BEGIN
+    -- Sessions of OAuth 2.0 clients acting on their own behalf have no user
+    IF NEW."user_id" IS NULL THEN
+      RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+      "kind" := 'created';
+    ELSIF OLD."finished_at" IS NULL AND NEW."finished_at" IS NOT NULL THEN
+      "kind" := 'ended';
+    ELSE
+      RETURN NULL;
+    END IF;
+
+    -- The first argument is the type of session, the second the name of the
+    -- column holding its ID
+    PERFORM pg_notify('mas::session_events', json_build_object(
+      'kind', "kind",
+      'session_type', TG_ARGV[0],
+      'session_id', to_jsonb(NEW) ->> TG_ARGV[1],
+      'user_id', NEW."user_id"
+    )::TEXT);
+
+    RETURN NULL;
+  END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER "user_sessions_notify"
+  AFTER INSERT OR UPDATE OF "finished_at" ON "user_sessions"
+  FOR EACH ROW EXECUTE PROCEDURE "notify_session_event"('browser', 'user_session_id');
+
+CREATE TRIGGER "oauth2_sessions_notify"
+  AFTER INSERT OR UPDATE OF "finished_at" ON "oauth2_sessions"
+  FOR EACH ROW EXECUTE PROCEDURE "notify_session_event"('oauth2', 'oauth2_session_id');
+
+CREATE TRIGGER "compat_sessions_notify"
+  AFTER INSERT OR UPDATE OF "finished_at" ON "compat_sessions"
+  FOR EACH ROW EXECUTE PROCEDURE "notify_session_event"('compat', 'compat_session_id');
+
+-- Same, when a user authenticates again in a browser session
+CREATE FUNCTION "notify_session_authentication"() RETURNS TRIGGER AS $$
+  BEGIN
+    PERFORM pg_notify('mas::session_events', json_build_object(
+      'kind', 'authentication_added',
+      'session_type', 'browser',
+      'session_id', NEW."user_session_id",
+      'user_id', (
+        SELECT "user_id"
+        FROM "user_sessions"
+        WHERE "user_session_id" = NEW."user_session_id"
+      )
+    )::TEXT);
+
+    RETURN NULL;
+  END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER "user_session_authentications_notify"
+  AFTER INSERT ON "user_session_authentications"
+  FOR EACH ROW EXECUTE PROCEDURE "notify_session_authentication"();
@@ -0,0 +1,45 @@
+-- Copyright 2024 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Send the type of the inserted jobs in the notifications, so that only the
+-- workers of that type wake up
+CREATE OR REPLACE FUNCTION apalis.notify_new_jobs() RETURNS TRIGGER AS $$
+  BEGIN
+    PERFORM pg_notify('apalis::job', NEW."job_type");
+    RETURN NULL;
+  END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER "notify_workers" ON apalis.jobs;
+CREATE TRIGGER "notify_workers"
+  AFTER INSERT ON apalis.jobs
+  FOR EACH ROW EXECUTE PROCEDURE apalis.notify_new_jobs();
+
+-- Notify the `mas::cache_invalidation` channel when an upstream provider is
+-- changed or removed, so that all the instances drop the metadata they cached
+-- for its issuer
+CREATE FUNCTION "notify_upstream_oauth_provider_invalidation"() RETURNS TRIGGER AS $$
+  BEGIN
+    PERFORM pg_notify('mas::cache_invalidation', json_build_object(
+      'kind', 'upstream_oauth_provider',
+      'issuer', OLD."issuer"
+    )::TEXT);
+
+    RETURN NULL;
+  END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER "upstream_oauth_providers_invalidate"
+  AFTER UPDATE OR DELETE ON "upstream_oauth_providers"
+  FOR EACH ROW EXECUTE PROCEDURE "notify_upstream_oauth_provider_invalidation"();
@@ -0,0 +1,41 @@
+-- Copyright 2024 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Normalize the existing addresses like the default `email_normalization`
+-- settings do: surrounding whitespace is removed, and the address lowercased
+UPDATE "user_emails"
+    SET "email" = LOWER(TRIM("email"))
+    WHERE "email" <> LOWER(TRIM("email"));
+
+-- The same address might have been verified more than once, either by
+-- different users or with a different case. Only the one verified first stays
+-- verified.
+UPDATE "user_emails"
+    SET "confirmed_at" = NULL
+    FROM (
+        SELECT "user_email_id"
+             , ROW_NUMBER() OVER (
+                   PARTITION BY "email"
+                   ORDER BY "confirmed_at", "user_email_id"
+               ) AS "rank"
+          FROM "user_emails"
+         WHERE "confirmed_at" IS NOT NULL
+    ) AS "ranked"
+    WHERE "user_emails"."user_email_id" = "ranked"."user_email_id"
+      AND "ranked"."rank" > 1;
+
+-- A verified email address can only belong to one user
+CREATE UNIQUE INDEX "user_emails_email_verified_unique"
+    ON "user_emails" ("email")
+    WHERE "confirmed_at" IS NOT NULL;
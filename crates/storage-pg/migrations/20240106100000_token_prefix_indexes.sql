@@ -0,0 +1,27 @@
+-- Copyright 2024 The Matrix.org Foundation C.I.C.
+--
+-- Licensed under the Apache License, Version 2.0 (the "License");
+-- you may not use this file except in compliance with the License.
+-- You may obtain a copy of the License at
+--
+--     http://www.apache.org/licenses/LICENSE-2.0
+--
+-- Unless required by applicable law or agreed to in writing, software
+-- distributed under the License is distributed on an "AS IS" BASIS,
+-- WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+-- See the License for the specific language governing permissions and
+-- limitations under the License.
+
+-- Allow looking up tokens by prefix with `LIKE 'prefix%'`, which the indexes
+-- on the token columns can't serve unless the database uses the C collation
+CREATE INDEX "oauth2_access_tokens_access_token_prefix"
+    ON "oauth2_access_tokens" ("access_token" text_pattern_ops);
+
+CREATE INDEX "oauth2_refresh_tokens_refresh_token_prefix"
+    ON "oauth2_refresh_tokens" ("refresh_token" text_pattern_ops);
+
+CREATE INDEX "compat_access_tokens_access_token_prefix"
+    ON "compat_access_tokens" ("access_token" text_pattern_ops);
+
+CREATE INDEX "compat_refresh_tokens_refresh_token_prefix"
+    ON "compat_refresh_tokens" ("refresh_token" text_pattern_ops);
@@ -25,7 +25,7 @@ use sea_query::{
     Alias, ColumnRef, CommonTableExpression, Expr, PgFunc, PostgresQueryBuilder, Query, UnionType,
 };
 use sea_query_binder::SqlxBinder;
-use sqlx::PgConnection;
+use sqlx::{types::Json, PgConnection};
 use ulid::Ulid;
 use uuid::Uuid;
 
@@ -56,7 +56,9 @@ mod priv_ {
     use std::net::IpAddr;
 
     use chrono::{DateTime, Utc};
+    use oauth2_types::requests::ClaimsRequest;
     use sea_query::enum_def;
+    use sqlx::types::Json;
     use uuid::Uuid;
 
     #[derive(sqlx::FromRow)]
@@ -70,11 +72,17 @@ mod priv_ {
         pub(super) user_id: Option<Uuid>,
         pub(super) scope_list: Option<Vec<String>>,
         pub(super) device_id: Option<String>,
+        pub(super) human_name: Option<String>,
         pub(super) created_at: DateTime<Utc>,
         pub(super) finished_at: Option<DateTime<Utc>>,
         pub(super) is_synapse_admin: Option<bool>,
         pub(super) last_active_at: Option<DateTime<Utc>>,
         pub(super) last_active_ip: Option<IpAddr>,
+        pub(super) dpop_jkt: Option<String>,
+        pub(super) certificate_thumbprint: Option<String>,
+        pub(super) parent_oauth2_session_id: Option<Uuid>,
+        pub(super) resource: Option<String>,
+        pub(super) claims: Option<Json<ClaimsRequest>>,
     }
 }
 
@@ -95,11 +103,17 @@ impl TryFrom<AppSessionLookup> for AppSession {
             user_id,
             scope_list,
             device_id,
+            human_name,
             created_at,
             finished_at,
             is_synapse_admin,
             last_active_at,
             last_active_ip,
+            dpop_jkt,
+            certificate_thumbprint,
+            parent_oauth2_session_id,
+            resource,
+            claims,
         } = value;
 
         match (
@@ -140,6 +154,7 @@ impl TryFrom<AppSessionLookup> for AppSession {
                     state,
                     user_id: user_id.into(),
                     device,
+                    human_name,
                     created_at,
                     is_synapse_admin,
                     last_active_at,
@@ -169,6 +184,16 @@ impl TryFrom<AppSessionLookup> for AppSession {
                         .source(e)
                 })?;
 
+                let resource = resource
+                    .map(|resource| resource.parse())
+                    .transpose()
+                    .map_err(|e| {
+                        DatabaseInconsistencyError::on("oauth2_sessions")
+                            .column("resource")
+                            .row(id)
+                            .source(e)
+                    })?;
+
                 let state = match value.finished_at {
                     None => SessionState::Valid,
                     Some(finished_at) => SessionState::Finished { finished_at },
@@ -184,6 +209,12 @@ impl TryFrom<AppSessionLookup> for AppSession {
                     scope,
                     last_active_at,
                     last_active_ip,
+                    dpop_jkt,
+                    certificate_thumbprint,
+                    parent_session_id: parent_oauth2_session_id.map(Ulid::from),
+                    resource,
+                    claims: claims.map(|Json(claims)| claims),
+                    human_name,
                 };
 
                 Ok(AppSession::OAuth2(Box::new(session)))
@@ -241,6 +272,10 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 AppSessionLookupIden::ScopeList,
             )
             .expr_as(Expr::cust("NULL"), AppSessionLookupIden::DeviceId)
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::HumanName)),
+                AppSessionLookupIden::HumanName,
+            )
             .expr_as(
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::CreatedAt)),
                 AppSessionLookupIden::CreatedAt,
@@ -258,6 +293,26 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveIp)),
                 AppSessionLookupIden::LastActiveIp,
             )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::DpopJkt)),
+                AppSessionLookupIden::DpopJkt,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::CertificateThumbprint)),
+                AppSessionLookupIden::CertificateThumbprint,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ParentOAuth2SessionId)),
+                AppSessionLookupIden::ParentOauth2SessionId,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::Resource)),
+                AppSessionLookupIden::Resource,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::Claims)),
+                AppSessionLookupIden::Claims,
+            )
             .from(OAuth2Sessions::Table)
             .and_where_option(filter.user().map(|user| {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::UserId)).eq(Uuid::from(user.id))
@@ -298,6 +353,10 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 Expr::col((CompatSessions::Table, CompatSessions::DeviceId)),
                 AppSessionLookupIden::DeviceId,
             )
+            .expr_as(
+                Expr::col((CompatSessions::Table, CompatSessions::HumanName)),
+                AppSessionLookupIden::HumanName,
+            )
             .expr_as(
                 Expr::col((CompatSessions::Table, CompatSessions::CreatedAt)),
                 AppSessionLookupIden::CreatedAt,
@@ -318,6 +377,17 @@ impl<'c> AppSessionRepository for PgAppSessionRepository<'c> {
                 Expr::col((CompatSessions::Table, CompatSessions::LastActiveIp)),
                 AppSessionLookupIden::LastActiveIp,
             )
+            .expr_as(Expr::cust("NULL"), AppSessionLookupIden::DpopJkt)
+            .expr_as(
+                Expr::cust("NULL"),
+                AppSessionLookupIden::CertificateThumbprint,
+            )
+            .expr_as(
+                Expr::cust("NULL"),
+                AppSessionLookupIden::ParentOauth2SessionId,
+            )
+            .expr_as(Expr::cust("NULL"), AppSessionLookupIden::Resource)
+            .expr_as(Expr::cust("NULL"), AppSessionLookupIden::Claims)
             .from(CompatSessions::Table)
             .and_where_option(filter.user().map(|user| {
                 Expr::col((CompatSessions::Table, CompatSessions::UserId)).eq(Uuid::from(user.id))
@@ -565,7 +635,16 @@ mod tests {
                 None,
                 None,
                 None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
                 Some("https://example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
             )
             .await
             .unwrap();
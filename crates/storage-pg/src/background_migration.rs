@@ -0,0 +1,412 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module containing the PostgreSQL implementation of the
+//! [`BackgroundMigrationRepository`], along with the data migrations it runs.
+//!
+//! # Changing the schema of big tables
+//!
+//! Regular migrations run in a single transaction before the service starts,
+//! which is not an option when a change needs to rewrite every row of a big
+//! table. Such changes are split in three steps, spread over at least two
+//! releases:
+//!
+//!  1. **Expand**: a regular migration adds the new columns or tables next to
+//!     the old ones, and registers a background migration by inserting a row in
+//!     the `background_migrations` table. From this release on, the code writes
+//!     both the old and the new representation.
+//!  2. **Backfill**: the workers pick up the background migration and run it in
+//!     small batches through a `Backfill` implementation, which fills the new
+//!     representation for the rows written before the expand step. Each batch
+//!     records its progress, so that it can be interrupted and resumed at any
+//!     time.
+//!  3. **Contract**: once the code only reads the new representation, a regular
+//!     migration in a later release drops the old one. That migration must
+//!     refuse to run if the backfill didn't complete:
+//!
+//! ```sql
+//! DO $$
+//! BEGIN
+//!   IF EXISTS (
+//!     SELECT 1 FROM background_migrations
+//!     WHERE name = 'my_backfill' AND completed_at IS NULL
+//!   ) THEN
+//!     RAISE EXCEPTION 'The my_backfill background migration did not complete';
+//!   END IF;
+//! END $$;
+//! ```
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::BackgroundMigration;
+use mas_storage::{background_migration::BackgroundMigrationRepository, Clock};
+use sqlx::PgConnection;
+
+use crate::{DatabaseError, DatabaseInconsistencyError, ExecuteExt};
+
+/// The outcome of a single batch of a [`Backfill`]
+pub(crate) struct BackfillBatch {
+    /// How many rows were processed in this batch
+    pub processed: usize,
+
+    /// Where the next batch should start from, or `None` if there are no rows
+    /// left to process
+    pub next_cursor: Option<String>,
+}
+
+/// A data migration which can be run in batches in the background
+#[async_trait]
+pub(crate) trait Backfill: Send + Sync {
+    /// The name under which the migration gets registered in the
+    /// `background_migrations` table
+    fn name(&self) -> &'static str;
+
+    /// Process up to `batch_size` rows, starting after `cursor`
+    ///
+    /// The cursor is opaque to the framework: it is whatever the previous
+    /// batch returned, or `None` for the first batch.
+    async fn run_batch(
+        &self,
+        conn: &mut PgConnection,
+        cursor: Option<&str>,
+        batch_size: usize,
+    ) -> Result<BackfillBatch, DatabaseError>;
+}
+
+/// The background migrations known by this version of the service
+///
+/// Migrations registered in the database but not listed here, e.g. because
+/// they were introduced by a newer version, are left untouched.
+const BACKFILLS: &[&dyn Backfill] = &[];
+
+/// An implementation of [`BackgroundMigrationRepository`] for a PostgreSQL
+/// connection
+pub struct PgBackgroundMigrationRepository<'c> {
+    conn: &'c mut PgConnection,
+    backfills: &'c [&'c dyn Backfill],
+}
+
+impl<'c> PgBackgroundMigrationRepository<'c> {
+    /// Create a new [`PgBackgroundMigrationRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self {
+            conn,
+            backfills: BACKFILLS,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_backfills(conn: &'c mut PgConnection, backfills: &'c [&'c dyn Backfill]) -> Self {
+        Self { conn, backfills }
+    }
+}
+
+struct BackgroundMigrationLookup {
+    name: String,
+    cursor: Option<String>,
+    processed_rows: i64,
+    created_at: DateTime<Utc>,
+    updated_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<BackgroundMigrationLookup> for BackgroundMigration {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: BackgroundMigrationLookup) -> Result<Self, Self::Error> {
+        let processed_rows = u64::try_from(value.processed_rows).map_err(|e| {
+            DatabaseInconsistencyError::on("background_migrations")
+                .column("processed_rows")
+                .source(e)
+        })?;
+
+        Ok(BackgroundMigration {
+            name: value.name,
+            cursor: value.cursor,
+            processed_rows,
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+            completed_at: value.completed_at,
+        })
+    }
+}
+
+#[async_trait]
+impl<'c> BackgroundMigrationRepository for PgBackgroundMigrationRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.background_migration.list",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn list(&mut self) -> Result<Vec<BackgroundMigration>, Self::Error> {
+        let res = sqlx::query_as!(
+            BackgroundMigrationLookup,
+            r#"
+                SELECT name
+                     , cursor
+                     , processed_rows
+                     , created_at
+                     , updated_at
+                     , completed_at
+                FROM background_migrations
+                ORDER BY created_at, name
+            "#,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        let res: Result<Vec<_>, _> = res.into_iter().map(TryInto::try_into).collect();
+        Ok(res?)
+    }
+
+    #[tracing::instrument(
+        name = "db.background_migration.run_next_batch",
+        skip_all,
+        fields(
+            db.statement,
+            background_migration.name,
+            background_migration.batch_size = batch_size,
+        ),
+        err,
+    )]
+    async fn run_next_batch(
+        &mut self,
+        clock: &dyn Clock,
+        batch_size: usize,
+    ) -> Result<Option<BackgroundMigration>, Self::Error> {
+        let names: Vec<String> = self
+            .backfills
+            .iter()
+            .map(|backfill| backfill.name().to_owned())
+            .collect();
+
+        // Lock the migration row for the duration of the transaction, so that
+        // concurrent workers don't process the same batch twice
+        let res = sqlx::query_as!(
+            BackgroundMigrationLookup,
+            r#"
+                SELECT name
+                     , cursor
+                     , processed_rows
+                     , created_at
+                     , updated_at
+                     , completed_at
+                FROM background_migrations
+                WHERE completed_at IS NULL
+                  AND name = ANY($1)
+                ORDER BY created_at, name
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            "#,
+            &names[..],
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else {
+            return Ok(None);
+        };
+
+        tracing::Span::current().record("background_migration.name", res.name.as_str());
+
+        let backfill = self
+            .backfills
+            .iter()
+            .find(|backfill| backfill.name() == res.name)
+            .ok_or_else(|| {
+                DatabaseInconsistencyError::on("background_migrations").column("name")
+            })?;
+
+        let batch = backfill
+            .run_batch(&mut *self.conn, res.cursor.as_deref(), batch_size)
+            .await?;
+
+        let processed = i64::try_from(batch.processed).unwrap_or(i64::MAX);
+        let now = clock.now();
+        let completed_at = batch.next_cursor.is_none().then_some(now);
+
+        let res = sqlx::query_as!(
+            BackgroundMigrationLookup,
+            r#"
+                UPDATE background_migrations
+                SET cursor = COALESCE($2, cursor)
+                  , processed_rows = processed_rows + $3
+                  , updated_at = $4
+                  , completed_at = $5
+                WHERE name = $1
+                RETURNING name
+                        , cursor
+                        , processed_rows
+                        , created_at
+                        , updated_at
+                        , completed_at
+            "#,
+            res.name,
+            batch.next_cursor,
+            processed,
+            now,
+            completed_at,
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        Ok(Some(res.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use chrono::Duration;
+    use mas_storage::{clock::MockClock, Clock, Repository, RepositoryAccess};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::{PgConnection, PgPool};
+    use ulid::Ulid;
+    use uuid::Uuid;
+
+    use super::{Backfill, BackfillBatch, PgBackgroundMigrationRepository};
+    use crate::{DatabaseError, PgRepository};
+
+    /// A backfill which walks through the users table without changing it
+    struct WalkUsers;
+
+    #[async_trait]
+    impl Backfill for WalkUsers {
+        fn name(&self) -> &'static str {
+            "walk_users"
+        }
+
+        async fn run_batch(
+            &self,
+            conn: &mut PgConnection,
+            cursor: Option<&str>,
+            batch_size: usize,
+        ) -> Result<BackfillBatch, DatabaseError> {
+            let after = cursor
+                .map(str::parse::<Ulid>)
+                .transpose()
+                .map_err(DatabaseError::to_invalid_operation)?
+                .unwrap_or(Ulid::nil());
+
+            let ids: Vec<Uuid> = sqlx::query_scalar(
+                r"
+                    SELECT user_id FROM users
+                    WHERE user_id > $1
+                    ORDER BY user_id
+                    LIMIT $2
+                ",
+            )
+            .bind(Uuid::from(after))
+            .bind(i64::try_from(batch_size).unwrap_or(i64::MAX))
+            .fetch_all(&mut *conn)
+            .await?;
+
+            let next_cursor = if ids.len() < batch_size {
+                None
+            } else {
+                ids.last().map(|id| Ulid::from(*id).to_string())
+            };
+
+            Ok(BackfillBatch {
+                processed: ids.len(),
+                next_cursor,
+            })
+        }
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_background_migration_repo(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap();
+
+        // Nothing is registered at first
+        assert!(repo.background_migration().list().await.unwrap().is_empty());
+        assert!(repo
+            .background_migration()
+            .run_next_batch(&clock, 2)
+            .await
+            .unwrap()
+            .is_none());
+
+        for username in ["alice", "bob", "charlie"] {
+            repo.user()
+                .add(&mut rng, &clock, username.to_owned())
+                .await
+                .unwrap();
+        }
+
+        // Register the migration, like a schema migration would
+        sqlx::query("INSERT INTO background_migrations (name, created_at) VALUES ($1, $2)")
+            .bind("walk_users")
+            .bind(clock.now())
+            .execute(&mut **repo)
+            .await
+            .unwrap();
+
+        // Migrations unknown to this version are not picked up
+        assert!(repo
+            .background_migration()
+            .run_next_batch(&clock, 2)
+            .await
+            .unwrap()
+            .is_none());
+
+        let backfills: &[&dyn Backfill] = &[&WalkUsers];
+
+        let migration = PgBackgroundMigrationRepository::with_backfills(&mut repo, backfills)
+            .run_next_batch(&clock, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(migration.name, "walk_users");
+        assert_eq!(migration.processed_rows, 2);
+        assert!(migration.cursor.is_some());
+        assert!(!migration.is_pending());
+        assert!(!migration.is_completed());
+
+        clock.advance(Duration::minutes(1));
+        let migration = PgBackgroundMigrationRepository::with_backfills(&mut repo, backfills)
+            .run_next_batch(&clock, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(migration.processed_rows, 3);
+        assert_eq!(migration.completed_at, Some(clock.now()));
+
+        // Completed migrations are not picked up again
+        assert!(
+            PgBackgroundMigrationRepository::with_backfills(&mut repo, backfills)
+                .run_next_batch(&clock, 2)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let migrations = repo.background_migration().list().await.unwrap();
+        assert_eq!(migrations, vec![migration]);
+
+        repo.boxed().save().await.unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A listener for the invalidations of the caches kept in memory by each
+//! instance, sent through PostgreSQL notifications by triggers on the tables
+//! they mirror
+
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+
+use crate::DatabaseError;
+
+/// The channel on which the cache invalidations are notified
+const CHANNEL: &str = "mas::cache_invalidation";
+
+/// Something cached in memory which changed in the database
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CacheInvalidation {
+    /// An upstream OAuth 2.0 provider was changed or removed. The metadata
+    /// discovered for its issuer should be fetched again.
+    UpstreamOAuthProvider {
+        /// The issuer the provider had before the change
+        issuer: String,
+    },
+}
+
+/// Listens to the invalidations of the caches, whichever instance made the
+/// change
+///
+/// Invalidations are only sent once the transaction which caused them is
+/// committed. They may be lost if the connection to the database drops.
+pub struct PgCacheInvalidationListener {
+    listener: PgListener,
+}
+
+impl PgCacheInvalidationListener {
+    /// Start listening to the cache invalidations, on a dedicated connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection could not be established
+    pub async fn connect(pool: &PgPool) -> Result<Self, DatabaseError> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(CHANNEL).await?;
+        Ok(Self { listener })
+    }
+
+    /// Wait for the next cache invalidation
+    ///
+    /// If the connection dropped, this tries to reconnect first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection dropped and could not be
+    /// re-established
+    pub async fn recv(&mut self) -> Result<CacheInvalidation, DatabaseError> {
+        loop {
+            let notification = self.listener.recv().await?;
+            if let Ok(invalidation) = serde_json::from_str(notification.payload()) {
+                return Ok(invalidation);
+            }
+
+            tracing::warn!(
+                payload = notification.payload(),
+                "Ignoring invalid cache invalidation"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_data_model::{
+        UpstreamOAuthProviderClaimsImports, UpstreamOAuthProviderDiscoveryMode,
+        UpstreamOAuthProviderPkceMode,
+    };
+    use mas_iana::oauth::OAuthClientAuthenticationMethod;
+    use mas_storage::{
+        clock::MockClock,
+        upstream_oauth2::{UpstreamOAuthProviderParams, UpstreamOAuthProviderRepository},
+        Repository, RepositoryAccess,
+    };
+    use oauth2_types::scope::{Scope, OPENID};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+
+    use super::{CacheInvalidation, PgCacheInvalidationListener};
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_cache_invalidation(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut listener = PgCacheInvalidationListener::connect(&pool).await.unwrap();
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let provider = repo
+            .upstream_oauth_provider()
+            .add(
+                &mut rng,
+                &clock,
+                UpstreamOAuthProviderParams {
+                    issuer: "https://example.com/".to_owned(),
+                    human_name: None,
+                    brand_name: None,
+                    icon: None,
+                    scope: Scope::from_iter([OPENID]),
+                    token_endpoint_auth_method: OAuthClientAuthenticationMethod::None,
+                    token_endpoint_signing_alg: None,
+                    client_id: "client-id".to_owned(),
+                    encrypted_client_secret: None,
+                    claims_imports: UpstreamOAuthProviderClaimsImports::default(),
+                    token_endpoint_override: None,
+                    authorization_endpoint_override: None,
+                    jwks_uri_override: None,
+                    discovery_mode: UpstreamOAuthProviderDiscoveryMode::Oidc,
+                    pkce_mode: UpstreamOAuthProviderPkceMode::Auto,
+                },
+            )
+            .await
+            .unwrap();
+        repo.upstream_oauth_provider()
+            .delete(provider)
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        // Adding a provider doesn't invalidate anything, removing it does
+        assert_eq!(
+            listener.recv().await.unwrap(),
+            CacheInvalidation::UpstreamOAuthProvider {
+                issuer: "https://example.com/".to_owned(),
+            }
+        );
+    }
+}
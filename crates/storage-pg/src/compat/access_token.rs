@@ -131,6 +131,46 @@ impl<'c> CompatAccessTokenRepository for PgCompatAccessTokenRepository<'c> {
         Ok(Some(res.into()))
     }
 
+    #[tracing::instrument(
+        name = "db.compat_access_token.find_by_token_prefix",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatAccessToken>, Self::Error> {
+        let pattern = format!("{}%", crate::escape_like(prefix));
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let res = sqlx::query_as!(
+            CompatAccessTokenLookup,
+            r#"
+                SELECT compat_access_token_id
+                     , access_token
+                     , created_at
+                     , expires_at
+                     , compat_session_id
+
+                FROM compat_access_tokens
+
+                WHERE access_token LIKE $1
+
+                LIMIT $2
+            "#,
+            pattern,
+            limit,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
     #[tracing::instrument(
         name = "db.compat_access_token.add",
         skip_all,
@@ -142,6 +142,34 @@ mod tests {
         assert!(session_lookup.is_valid());
         assert!(!session_lookup.is_finished());
 
+        // Sessions don't have a name by default
+        assert_eq!(session_lookup.human_name, None);
+
+        // Name the session
+        let session = repo
+            .compat_session()
+            .set_human_name(session, Some("Element on Android".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(session.human_name.as_deref(), Some("Element on Android"));
+
+        // Check that it is retrieved on lookup and in lists
+        let session_lookup = repo
+            .compat_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("compat session not found");
+        assert_eq!(
+            session_lookup.human_name.as_deref(),
+            Some("Element on Android")
+        );
+        let full_list = repo.compat_session().list(all, pagination).await.unwrap();
+        assert_eq!(
+            full_list.edges[0].0.human_name.as_deref(),
+            Some("Element on Android")
+        );
+
         // Finish the session
         let session = repo.compat_session().finish(&clock, session).await.unwrap();
         assert!(!session.is_valid());
@@ -383,6 +411,31 @@ mod tests {
         // Token is not valid anymore
         assert!(!token.is_valid(clock.now()));
 
+        // Looking up via a prefix of the token works
+        let tokens = repo
+            .compat_access_token()
+            .find_by_token_prefix("second_", 10)
+            .await
+            .unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].id, token.id);
+
+        // The limit is respected
+        let tokens = repo
+            .compat_access_token()
+            .find_by_token_prefix("", 1)
+            .await
+            .unwrap();
+        assert_eq!(tokens.len(), 1);
+
+        // Wildcards in the prefix are matched literally
+        let tokens = repo
+            .compat_access_token()
+            .find_by_token_prefix("%_access", 10)
+            .await
+            .unwrap();
+        assert!(tokens.is_empty());
+
         repo.save().await.unwrap();
     }
 
@@ -142,6 +142,47 @@ impl<'c> CompatRefreshTokenRepository for PgCompatRefreshTokenRepository<'c> {
         Ok(Some(res.into()))
     }
 
+    #[tracing::instrument(
+        name = "db.compat_refresh_token.find_by_token_prefix",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatRefreshToken>, Self::Error> {
+        let pattern = format!("{}%", crate::escape_like(prefix));
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let res = sqlx::query_as!(
+            CompatRefreshTokenLookup,
+            r#"
+                SELECT compat_refresh_token_id
+                     , refresh_token
+                     , created_at
+                     , consumed_at
+                     , compat_session_id
+                     , compat_access_token_id
+
+                FROM compat_refresh_tokens
+
+                WHERE refresh_token LIKE $1
+
+                LIMIT $2
+            "#,
+            pattern,
+            limit,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
     #[tracing::instrument(
         name = "db.compat_refresh_token.add",
         skip_all,
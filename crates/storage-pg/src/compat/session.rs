@@ -54,6 +54,7 @@ impl<'c> PgCompatSessionRepository<'c> {
 struct CompatSessionLookup {
     compat_session_id: Uuid,
     device_id: String,
+    human_name: Option<String>,
     user_id: Uuid,
     created_at: DateTime<Utc>,
     finished_at: Option<DateTime<Utc>>,
@@ -84,6 +85,7 @@ impl TryFrom<CompatSessionLookup> for CompatSession {
             state,
             user_id: value.user_id.into(),
             device,
+            human_name: value.human_name,
             created_at: value.created_at,
             is_synapse_admin: value.is_synapse_admin,
             last_active_at: value.last_active_at,
@@ -99,6 +101,7 @@ impl TryFrom<CompatSessionLookup> for CompatSession {
 struct CompatSessionAndSsoLoginLookup {
     compat_session_id: Uuid,
     device_id: String,
+    human_name: Option<String>,
     user_id: Uuid,
     created_at: DateTime<Utc>,
     finished_at: Option<DateTime<Utc>>,
@@ -135,6 +138,7 @@ impl TryFrom<CompatSessionAndSsoLoginLookup> for (CompatSession, Option<CompatSs
             state,
             user_id: value.user_id.into(),
             device,
+            human_name: value.human_name,
             created_at: value.created_at,
             is_synapse_admin: value.is_synapse_admin,
             last_active_at: value.last_active_at,
@@ -213,6 +217,7 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
             r#"
                 SELECT compat_session_id
                      , device_id
+                     , human_name
                      , user_id
                      , created_at
                      , finished_at
@@ -253,6 +258,7 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
             r#"
                 SELECT compat_session_id
                      , device_id
+                     , human_name
                      , user_id
                      , created_at
                      , finished_at
@@ -262,6 +268,9 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
                 FROM compat_sessions
                 WHERE user_id = $1
                   AND device_id = $2
+                ORDER BY (finished_at IS NULL) DESC
+                       , created_at DESC
+                LIMIT 1
             "#,
             Uuid::from(user.id),
             device.as_str(),
@@ -319,6 +328,7 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
             state: CompatSessionState::default(),
             user_id: user.id,
             device,
+            human_name: None,
             created_at,
             is_synapse_admin,
             last_active_at: None,
@@ -366,6 +376,41 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
         Ok(compat_session)
     }
 
+    #[tracing::instrument(
+        name = "db.compat_session.set_human_name",
+        skip_all,
+        fields(
+            db.statement,
+            %compat_session.id,
+            compat_session.human_name = human_name,
+        ),
+        err,
+    )]
+    async fn set_human_name(
+        &mut self,
+        mut compat_session: CompatSession,
+        human_name: Option<String>,
+    ) -> Result<CompatSession, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE compat_sessions
+                SET human_name = $2
+                WHERE compat_session_id = $1
+            "#,
+            Uuid::from(compat_session.id),
+            human_name.as_deref(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        compat_session.human_name = human_name;
+
+        Ok(compat_session)
+    }
+
     #[tracing::instrument(
         name = "db.compat_session.list",
         skip_all,
@@ -388,6 +433,10 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
                 Expr::col((CompatSessions::Table, CompatSessions::DeviceId)),
                 CompatSessionAndSsoLoginLookupIden::DeviceId,
             )
+            .expr_as(
+                Expr::col((CompatSessions::Table, CompatSessions::HumanName)),
+                CompatSessionAndSsoLoginLookupIden::HumanName,
+            )
             .expr_as(
                 Expr::col((CompatSessions::Table, CompatSessions::UserId)),
                 CompatSessionAndSsoLoginLookupIden::UserId,
@@ -452,6 +501,14 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
                     Expr::col((CompatSessions::Table, CompatSessions::FinishedAt)).is_not_null()
                 }
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                    .gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                    .lt(last_active_before)
+            }))
             .and_where_option(filter.auth_type().map(|auth_type| {
                 if auth_type.is_sso_login() {
                     Expr::col((CompatSsoLogins::Table, CompatSsoLogins::CompatSsoLoginId))
@@ -498,6 +555,14 @@ impl<'c> CompatSessionRepository for PgCompatSessionRepository<'c> {
                     Expr::col((CompatSessions::Table, CompatSessions::FinishedAt)).is_not_null()
                 }
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                    .gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((CompatSessions::Table, CompatSessions::LastActiveAt))
+                    .lt(last_active_before)
+            }))
             .and_where_option(filter.auth_type().map(|auth_type| {
                 // Check if it is an SSO login by checking if there is a SSO login for the
                 // session.
@@ -35,6 +35,12 @@ pub enum Users {
     CreatedAt,
     LockedAt,
     CanRequestAdmin,
+    IsServiceAccount,
+    Locale,
+    PasswordResetRequiredAt,
+    DeletedAt,
+    PurgedAt,
+    CompatPasswordDeprecatedAt,
 }
 
 #[derive(sea_query::Iden)]
@@ -53,6 +59,7 @@ pub enum CompatSessions {
     CompatSessionId,
     UserId,
     DeviceId,
+    HumanName,
     CreatedAt,
     FinishedAt,
     IsSynapseAdmin,
@@ -87,6 +94,13 @@ pub enum OAuth2Sessions {
     FinishedAt,
     LastActiveAt,
     LastActiveIp,
+    DpopJkt,
+    CertificateThumbprint,
+    #[iden = "parent_oauth2_session_id"]
+    ParentOAuth2SessionId,
+    Resource,
+    Claims,
+    HumanName,
 }
 
 #[derive(sea_query::Iden)]
@@ -98,6 +112,7 @@ pub enum UpstreamOAuthProviders {
     Issuer,
     HumanName,
     BrandName,
+    Icon,
     Scope,
     ClientId,
     EncryptedClientSecret,
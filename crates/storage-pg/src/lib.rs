@@ -170,9 +170,15 @@
 use sqlx::migrate::Migrator;
 
 pub mod app_session;
+pub mod background_migration;
+pub mod cache_invalidation;
 pub mod compat;
 pub mod job;
+pub mod login_failure;
 pub mod oauth2;
+pub mod rate_limit;
+pub mod session_events;
+pub mod statistics;
 pub mod upstream_oauth2;
 pub mod user;
 
@@ -180,10 +186,19 @@ mod errors;
 pub(crate) mod iden;
 pub(crate) mod pagination;
 pub(crate) mod repository;
+mod schema_version;
 pub(crate) mod tracing;
 
 pub(crate) use self::errors::DatabaseInconsistencyError;
-pub use self::{errors::DatabaseError, repository::PgRepository, tracing::ExecuteExt};
+pub use self::{
+    errors::DatabaseError,
+    repository::PgRepository,
+    schema_version::{
+        applied_schema_version, check_schema_version, latest_known_schema_version,
+        pending_migrations, SchemaVersion,
+    },
+    tracing::{ExecuteExt, QueryTimingLayer},
+};
 
 /// Embedded migrations, allowing them to run on startup
 pub static MIGRATOR: Migrator = {
@@ -196,3 +211,10 @@ pub static MIGRATOR: Migrator = {
     m.ignore_missing = true;
     m
 };
+
+/// Escape the wildcards of a `LIKE` pattern, so that they are matched literally
+pub(crate) fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
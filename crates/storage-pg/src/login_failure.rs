@@ -0,0 +1,289 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A module containing the PostgreSQL implementation of the
+//! [`LoginFailureRepository`].
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_storage::{login_failure::LoginFailureRepository, Clock};
+use sqlx::PgConnection;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`LoginFailureRepository`] for a PostgreSQL
+/// connection
+pub struct PgLoginFailureRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgLoginFailureRepository<'c> {
+    /// Create a new [`PgLoginFailureRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl<'c> LoginFailureRepository for PgLoginFailureRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.login_failure.locked_until",
+        skip_all,
+        fields(
+            db.statement,
+            login_failure.key = key,
+        ),
+        err,
+    )]
+    async fn locked_until(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+                SELECT locked_until AS "locked_until!"
+                FROM login_failures
+                WHERE failure_key = $1
+                  AND locked_until > $2
+            "#,
+            key,
+            clock.now(),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(locked_until)
+    }
+
+    #[tracing::instrument(
+        name = "db.login_failure.record",
+        skip_all,
+        fields(
+            db.statement,
+            login_failure.key = key,
+        ),
+        err,
+    )]
+    async fn record(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        forget_after: Duration,
+    ) -> Result<u32, Self::Error> {
+        let now = clock.now();
+
+        // The counter starts over if the last failure is too old
+        let failed_attempts = sqlx::query_scalar!(
+            r#"
+                INSERT INTO login_failures AS counter
+                    (failure_key, failed_attempts, last_failed_at)
+                VALUES ($1, 1, $2)
+                ON CONFLICT (failure_key) DO UPDATE
+                SET failed_attempts = CASE
+                        WHEN counter.last_failed_at < $3 THEN 1
+                        ELSE counter.failed_attempts + 1
+                    END
+                  , last_failed_at = $2
+                RETURNING failed_attempts
+            "#,
+            key,
+            now,
+            now - forget_after,
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        Ok(u32::try_from(failed_attempts).unwrap_or(u32::MAX))
+    }
+
+    #[tracing::instrument(
+        name = "db.login_failure.lock",
+        skip_all,
+        fields(
+            db.statement,
+            login_failure.key = key,
+            %locked_until,
+        ),
+        err,
+    )]
+    async fn lock(&mut self, key: &str, locked_until: DateTime<Utc>) -> Result<(), Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE login_failures
+                SET locked_until = $2
+                WHERE failure_key = $1
+            "#,
+            key,
+            locked_until,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.login_failure.reset",
+        skip_all,
+        fields(
+            db.statement,
+            login_failure.key = key,
+        ),
+        err,
+    )]
+    async fn reset(&mut self, key: &str) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                DELETE FROM login_failures
+                WHERE failure_key = $1
+            "#,
+            key,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.login_failure.cleanup",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn cleanup(
+        &mut self,
+        clock: &dyn Clock,
+        forget_after: Duration,
+    ) -> Result<usize, Self::Error> {
+        let now = clock.now();
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM login_failures
+                WHERE last_failed_at < $1
+                  AND (locked_until IS NULL OR locked_until < $2)
+            "#,
+            now - forget_after,
+            now,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use mas_storage::{clock::MockClock, Clock, Repository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_login_failure_repo(pool: PgPool) {
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let forget_after = Duration::hours(1);
+
+        // Failures are counted per key
+        for expected in 1..=3 {
+            let count = repo
+                .login_failure()
+                .record(&clock, "test", forget_after)
+                .await
+                .unwrap();
+            assert_eq!(count, expected);
+        }
+        let count = repo
+            .login_failure()
+            .record(&clock, "other", forget_after)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Nothing is locked yet
+        let locked_until = repo
+            .login_failure()
+            .locked_until(&clock, "test")
+            .await
+            .unwrap();
+        assert_eq!(locked_until, None);
+
+        // Lock the key for a minute
+        let until = clock.now() + Duration::minutes(1);
+        repo.login_failure().lock("test", until).await.unwrap();
+        let locked_until = repo
+            .login_failure()
+            .locked_until(&clock, "test")
+            .await
+            .unwrap();
+        assert_eq!(locked_until, Some(until));
+
+        // The lockout ends by itself
+        clock.advance(Duration::minutes(2));
+        let locked_until = repo
+            .login_failure()
+            .locked_until(&clock, "test")
+            .await
+            .unwrap();
+        assert_eq!(locked_until, None);
+
+        // Failures are forgotten after a while
+        clock.advance(Duration::hours(2));
+        let count = repo
+            .login_failure()
+            .record(&clock, "test", forget_after)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Only the stale counter gets cleaned up
+        assert_eq!(
+            repo.login_failure()
+                .cleanup(&clock, forget_after)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Resetting lifts the lockout
+        repo.login_failure()
+            .lock("test", clock.now() + Duration::minutes(1))
+            .await
+            .unwrap();
+        repo.login_failure().reset("test").await.unwrap();
+        let locked_until = repo
+            .login_failure()
+            .locked_until(&clock, "test")
+            .await
+            .unwrap();
+        assert_eq!(locked_until, None);
+
+        repo.save().await.unwrap();
+    }
+}
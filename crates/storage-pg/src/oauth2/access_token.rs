@@ -129,6 +129,47 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
         Ok(Some(res.into()))
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_access_token.find_by_token_prefix",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AccessToken>, Self::Error> {
+        let pattern = format!("{}%", crate::escape_like(prefix));
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let res = sqlx::query_as!(
+            OAuth2AccessTokenLookup,
+            r#"
+                SELECT oauth2_access_token_id
+                     , access_token
+                     , created_at
+                     , expires_at
+                     , revoked_at
+                     , oauth2_session_id
+
+                FROM oauth2_access_tokens
+
+                WHERE access_token LIKE $1
+
+                LIMIT $2
+            "#,
+            pattern,
+            limit,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_access_token.add",
         skip_all,
@@ -206,15 +247,26 @@ impl<'c> OAuth2AccessTokenRepository for PgOAuth2AccessTokenRepository<'c> {
             .map_err(DatabaseError::to_invalid_operation)
     }
 
-    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+    async fn cleanup_expired(
+        &mut self,
+        clock: &dyn Clock,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
         // Cleanup token which expired more than 15 minutes ago
         let threshold = clock.now() - Duration::minutes(15);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
         let res = sqlx::query!(
             r#"
                 DELETE FROM oauth2_access_tokens
-                WHERE expires_at < $1
+                WHERE oauth2_access_token_id IN (
+                    SELECT oauth2_access_token_id
+                    FROM oauth2_access_tokens
+                    WHERE expires_at < $1
+                    LIMIT $2
+                )
             "#,
             threshold,
+            limit,
         )
         .execute(&mut *self.conn)
         .await?;
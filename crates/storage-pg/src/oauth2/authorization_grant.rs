@@ -20,10 +20,16 @@ use mas_data_model::{
     AuthorizationCode, AuthorizationGrant, AuthorizationGrantStage, Client, Pkce, Session,
 };
 use mas_iana::oauth::PkceCodeChallengeMethod;
-use mas_storage::{oauth2::OAuth2AuthorizationGrantRepository, Clock};
-use oauth2_types::{requests::ResponseMode, scope::Scope};
+use mas_storage::{
+    oauth2::{AuthorizationGrantFunnel, OAuth2AuthorizationGrantRepository},
+    Clock,
+};
+use oauth2_types::{
+    requests::{ClaimsRequest, ResponseMode},
+    scope::Scope,
+};
 use rand::RngCore;
-use sqlx::PgConnection;
+use sqlx::{types::Json, PgConnection};
 use ulid::Ulid;
 use url::Url;
 use uuid::Uuid;
@@ -57,16 +63,46 @@ struct GrantLookup {
     redirect_uri: String,
     response_mode: String,
     max_age: Option<i32>,
+    acr_values: Vec<String>,
+    claims: Option<Json<ClaimsRequest>>,
     response_type_code: bool,
     response_type_id_token: bool,
     authorization_code: Option<String>,
     code_challenge: Option<String>,
     code_challenge_method: Option<String>,
     requires_consent: bool,
+    requires_reauthentication: bool,
+    resource: Option<String>,
     oauth2_client_id: Uuid,
     oauth2_session_id: Option<Uuid>,
 }
 
+struct FunnelLookup {
+    oauth2_client_id: Uuid,
+    started: i64,
+    login_shown: i64,
+    consent_shown: i64,
+    completed: i64,
+    abandoned: i64,
+    errored: i64,
+}
+
+impl From<FunnelLookup> for AuthorizationGrantFunnel {
+    fn from(value: FunnelLookup) -> Self {
+        // Counts can't be negative
+        let count = |count: i64| u64::try_from(count).unwrap_or_default();
+        AuthorizationGrantFunnel {
+            client_id: value.oauth2_client_id.into(),
+            started: count(value.started),
+            login_shown: count(value.login_shown),
+            consent_shown: count(value.consent_shown),
+            completed: count(value.completed),
+            abandoned: count(value.abandoned),
+            errored: count(value.errored),
+        }
+    }
+}
+
 impl TryFrom<GrantLookup> for AuthorizationGrant {
     type Error = DatabaseInconsistencyError;
 
@@ -179,6 +215,17 @@ impl TryFrom<GrantLookup> for AuthorizationGrant {
                     .source(e)
             })?;
 
+        let resource = value
+            .resource
+            .map(|resource| resource.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_authorization_grants")
+                    .column("resource")
+                    .row(id)
+                    .source(e)
+            })?;
+
         Ok(AuthorizationGrant {
             id,
             stage,
@@ -188,11 +235,15 @@ impl TryFrom<GrantLookup> for AuthorizationGrant {
             state: value.state,
             nonce: value.nonce,
             max_age,
+            acr_values: value.acr_values,
+            claims: value.claims.map(|Json(claims)| claims),
             response_mode,
             redirect_uri,
             created_at: value.created_at,
             response_type_id_token: value.response_type_id_token,
             requires_consent: value.requires_consent,
+            requires_reauthentication: value.requires_reauthentication,
+            resource,
         })
     }
 }
@@ -223,9 +274,13 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
         state: Option<String>,
         nonce: Option<String>,
         max_age: Option<NonZeroU32>,
+        acr_values: Vec<String>,
+        claims: Option<ClaimsRequest>,
         response_mode: ResponseMode,
         response_type_id_token: bool,
         requires_consent: bool,
+        requires_reauthentication: bool,
+        resource: Option<Url>,
     ) -> Result<AuthorizationGrant, Self::Error> {
         let code_challenge = code
             .as_ref()
@@ -260,10 +315,15 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
                      response_type_id_token,
                      authorization_code,
                      requires_consent,
+                     requires_reauthentication,
+                     resource,
+                     acr_values,
+                     claims,
                      created_at
                 )
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+                     $18, $19)
             "#,
             Uuid::from(id),
             Uuid::from(client.id),
@@ -279,6 +339,10 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
             response_type_id_token,
             code_str,
             requires_consent,
+            requires_reauthentication,
+            resource.as_ref().map(Url::as_str),
+            &acr_values,
+            claims.as_ref().map(Json) as _,
             created_at,
         )
         .execute(&mut *self.conn)
@@ -294,10 +358,14 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
             state,
             nonce,
             max_age,
+            acr_values,
+            claims,
             response_mode,
             created_at,
             response_type_id_token,
             requires_consent,
+            requires_reauthentication,
+            resource,
         })
     }
 
@@ -325,6 +393,8 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
                      , response_mode
                      , nonce
                      , max_age
+                     , acr_values
+                     , claims as "claims: Json<ClaimsRequest>"
                      , oauth2_client_id
                      , authorization_code
                      , response_type_code
@@ -332,6 +402,8 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
                      , code_challenge
                      , code_challenge_method
                      , requires_consent
+                     , requires_reauthentication
+                     , resource
                      , oauth2_session_id
                 FROM
                     oauth2_authorization_grants
@@ -374,6 +446,8 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
                      , response_mode
                      , nonce
                      , max_age
+                     , acr_values
+                     , claims as "claims: Json<ClaimsRequest>"
                      , oauth2_client_id
                      , authorization_code
                      , response_type_code
@@ -381,6 +455,8 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
                      , code_challenge
                      , code_challenge_method
                      , requires_consent
+                     , requires_reauthentication
+                     , resource
                      , oauth2_session_id
                 FROM
                     oauth2_authorization_grants
@@ -508,4 +584,167 @@ impl<'c> OAuth2AuthorizationGrantRepository for PgOAuth2AuthorizationGrantReposi
 
         Ok(grant)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.record_login_shown",
+        skip_all,
+        fields(
+            db.statement,
+            %grant.id,
+            client.id = %grant.client_id,
+        ),
+        err,
+    )]
+    async fn record_login_shown(
+        &mut self,
+        clock: &dyn Clock,
+        grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                UPDATE oauth2_authorization_grants
+                SET login_shown_at = COALESCE(login_shown_at, $2)
+                WHERE oauth2_authorization_grant_id = $1
+            "#,
+            Uuid::from(grant.id),
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.record_consent_shown",
+        skip_all,
+        fields(
+            db.statement,
+            %grant.id,
+            client.id = %grant.client_id,
+        ),
+        err,
+    )]
+    async fn record_consent_shown(
+        &mut self,
+        clock: &dyn Clock,
+        grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                UPDATE oauth2_authorization_grants
+                SET consent_shown_at = COALESCE(consent_shown_at, $2)
+                WHERE oauth2_authorization_grant_id = $1
+            "#,
+            Uuid::from(grant.id),
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.record_error",
+        skip_all,
+        fields(
+            db.statement,
+            %grant.id,
+            client.id = %grant.client_id,
+        ),
+        err,
+    )]
+    async fn record_error(
+        &mut self,
+        clock: &dyn Clock,
+        grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                UPDATE oauth2_authorization_grants
+                SET errored_at = COALESCE(errored_at, $2)
+                WHERE oauth2_authorization_grant_id = $1
+            "#,
+            Uuid::from(grant.id),
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.mark_abandoned",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn mark_abandoned(
+        &mut self,
+        clock: &dyn Clock,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<Ulid>, Self::Error> {
+        let res = sqlx::query_scalar!(
+            r#"
+                UPDATE oauth2_authorization_grants
+                SET abandoned_at = $1
+                WHERE fulfilled_at IS NULL
+                  AND cancelled_at IS NULL
+                  AND errored_at IS NULL
+                  AND abandoned_at IS NULL
+                  AND created_at < $2
+                RETURNING oauth2_client_id
+            "#,
+            clock.now(),
+            started_before,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Ulid::from).collect())
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_authorization_grant.funnel",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn funnel(
+        &mut self,
+        started_since: DateTime<Utc>,
+    ) -> Result<Vec<AuthorizationGrantFunnel>, Self::Error> {
+        let res = sqlx::query_as!(
+            FunnelLookup,
+            r#"
+                SELECT oauth2_client_id
+                     , COUNT(*) AS "started!"
+                     , COUNT(login_shown_at) AS "login_shown!"
+                     , COUNT(consent_shown_at) AS "consent_shown!"
+                     , COUNT(fulfilled_at) AS "completed!"
+                     , COUNT(abandoned_at) AS "abandoned!"
+                     , COUNT(errored_at) AS "errored!"
+                FROM oauth2_authorization_grants
+                WHERE created_at >= $1
+                GROUP BY oauth2_client_id
+                ORDER BY oauth2_client_id
+            "#,
+            started_since,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
 }
@@ -19,9 +19,10 @@ use std::{
 };
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{Client, JwksOrJwksUri, User};
 use mas_iana::{
-    jose::JsonWebSignatureAlg,
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
     oauth::{OAuthAuthorizationEndpointResponseType, OAuthClientAuthenticationMethod},
 };
 use mas_jose::jwk::PublicJsonWebKeySet;
@@ -64,6 +65,8 @@ struct OAuth2ClientLookup {
     grant_type_authorization_code: bool,
     grant_type_refresh_token: bool,
     grant_type_client_credentials: bool,
+    grant_type_device_code: bool,
+    grant_type_token_exchange: bool,
     contacts: Vec<String>,
     client_name: Option<String>,
     logo_uri: Option<String>,
@@ -76,7 +79,19 @@ struct OAuth2ClientLookup {
     userinfo_signed_response_alg: Option<String>,
     token_endpoint_auth_method: Option<String>,
     token_endpoint_auth_signing_alg: Option<String>,
+    request_object_signing_alg: Option<String>,
+    request_uris: Vec<String>,
+    id_token_encrypted_response_alg: Option<String>,
+    id_token_encrypted_response_enc: Option<String>,
+    userinfo_encrypted_response_alg: Option<String>,
+    userinfo_encrypted_response_enc: Option<String>,
     initiate_login_uri: Option<String>,
+    post_logout_redirect_uris: Vec<String>,
+    backchannel_logout_uri: Option<String>,
+    backchannel_logout_session_required: bool,
+    require_pushed_authorization_requests: bool,
+    tls_client_auth_subject_dn: Option<String>,
+    tls_client_certificate_bound_access_tokens: bool,
 }
 
 impl TryInto<Client> for OAuth2ClientLookup {
@@ -130,6 +145,12 @@ impl TryInto<Client> for OAuth2ClientLookup {
         if self.grant_type_client_credentials {
             grant_types.push(GrantType::ClientCredentials);
         }
+        if self.grant_type_device_code {
+            grant_types.push(GrantType::DeviceCode);
+        }
+        if self.grant_type_token_exchange {
+            grant_types.push(GrantType::TokenExchange);
+        }
 
         let logo_uri = self.logo_uri.map(|s| s.parse()).transpose().map_err(|e| {
             DatabaseInconsistencyError::on("oauth2_clients")
@@ -211,6 +232,61 @@ impl TryInto<Client> for OAuth2ClientLookup {
                     .source(e)
             })?;
 
+        let request_object_signing_alg = self
+            .request_object_signing_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("request_object_signing_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let id_token_encrypted_response_alg = self
+            .id_token_encrypted_response_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("id_token_encrypted_response_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let id_token_encrypted_response_enc = self
+            .id_token_encrypted_response_enc
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("id_token_encrypted_response_enc")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let userinfo_encrypted_response_alg = self
+            .userinfo_encrypted_response_alg
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("userinfo_encrypted_response_alg")
+                    .row(id)
+                    .source(e)
+            })?;
+
+        let userinfo_encrypted_response_enc = self
+            .userinfo_encrypted_response_enc
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("userinfo_encrypted_response_enc")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let initiate_login_uri = self
             .initiate_login_uri
             .map(|s| s.parse())
@@ -222,6 +298,38 @@ impl TryInto<Client> for OAuth2ClientLookup {
                     .source(e)
             })?;
 
+        let post_logout_redirect_uris: Result<Vec<Url>, _> = self
+            .post_logout_redirect_uris
+            .iter()
+            .map(|s| s.parse())
+            .collect();
+        let post_logout_redirect_uris = post_logout_redirect_uris.map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_clients")
+                .column("post_logout_redirect_uris")
+                .row(id)
+                .source(e)
+        })?;
+
+        let request_uris: Result<Vec<Url>, _> =
+            self.request_uris.iter().map(|s| s.parse()).collect();
+        let request_uris = request_uris.map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_clients")
+                .column("request_uris")
+                .row(id)
+                .source(e)
+        })?;
+
+        let backchannel_logout_uri = self
+            .backchannel_logout_uri
+            .map(|s| s.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_clients")
+                    .column("backchannel_logout_uri")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let jwks = match (self.jwks, self.jwks_uri) {
             (None, None) => None,
             (Some(jwks), None) => {
@@ -269,7 +377,20 @@ impl TryInto<Client> for OAuth2ClientLookup {
             userinfo_signed_response_alg,
             token_endpoint_auth_method,
             token_endpoint_auth_signing_alg,
+            request_object_signing_alg,
+            request_uris,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
             initiate_login_uri,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required: self.backchannel_logout_session_required,
+            require_pushed_authorization_requests: self.require_pushed_authorization_requests,
+            tls_client_auth_subject_dn: self.tls_client_auth_subject_dn,
+            tls_client_certificate_bound_access_tokens: self
+                .tls_client_certificate_bound_access_tokens,
         })
     }
 }
@@ -298,6 +419,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_authorization_code
                      , grant_type_refresh_token
                      , grant_type_client_credentials
+                     , grant_type_device_code
+                     , grant_type_token_exchange
                      , contacts
                      , client_name
                      , logo_uri
@@ -310,7 +433,19 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , userinfo_signed_response_alg
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
+                     , request_object_signing_alg
+                     , request_uris
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , tls_client_auth_subject_dn
+                     , tls_client_certificate_bound_access_tokens
                 FROM oauth2_clients c
 
                 WHERE oauth2_client_id = $1
@@ -349,6 +484,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_authorization_code
                      , grant_type_refresh_token
                      , grant_type_client_credentials
+                     , grant_type_device_code
+                     , grant_type_token_exchange
                      , contacts
                      , client_name
                      , logo_uri
@@ -361,7 +498,19 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , userinfo_signed_response_alg
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
+                     , request_object_signing_alg
+                     , request_uris
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , tls_client_auth_subject_dn
+                     , tls_client_certificate_bound_access_tokens
                 FROM oauth2_clients c
 
                 WHERE oauth2_client_id = ANY($1::uuid[])
@@ -412,7 +561,16 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
         userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
     ) -> Result<Client, Self::Error> {
         let now = clock.now();
         let id = Ulid::from_datetime_with_source(now.into(), rng);
@@ -425,6 +583,11 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             .map_err(DatabaseError::to_invalid_operation)?;
 
         let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let request_uris_array = request_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let post_logout_redirect_uris_array = post_logout_redirect_uris
+            .iter()
+            .map(Url::to_string)
+            .collect::<Vec<_>>();
 
         sqlx::query!(
             r#"
@@ -436,6 +599,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     , grant_type_authorization_code
                     , grant_type_refresh_token
                     , grant_type_client_credentials
+                    , grant_type_device_code
+                    , grant_type_token_exchange
                     , client_name
                     , logo_uri
                     , client_uri
@@ -447,11 +612,20 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     , userinfo_signed_response_alg
                     , token_endpoint_auth_method
                     , token_endpoint_auth_signing_alg
+                    , request_object_signing_alg
+                    , request_uris
+                    , id_token_encrypted_response_alg
+                    , id_token_encrypted_response_enc
+                    , userinfo_encrypted_response_alg
+                    , userinfo_encrypted_response_enc
                     , initiate_login_uri
+                    , post_logout_redirect_uris
+                    , backchannel_logout_uri
+                    , backchannel_logout_session_required
                     , is_static
                     )
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, FALSE)
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, FALSE)
             "#,
             Uuid::from(id),
             encrypted_client_secret,
@@ -460,6 +634,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             grant_types.contains(&GrantType::AuthorizationCode),
             grant_types.contains(&GrantType::RefreshToken),
             grant_types.contains(&GrantType::ClientCredentials),
+            grant_types.contains(&GrantType::DeviceCode),
+            grant_types.contains(&GrantType::TokenExchange),
             client_name,
             logo_uri.as_ref().map(Url::as_str),
             client_uri.as_ref().map(Url::as_str),
@@ -477,7 +653,24 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             token_endpoint_auth_signing_alg
                 .as_ref()
                 .map(ToString::to_string),
+            request_object_signing_alg.as_ref().map(ToString::to_string),
+            &request_uris_array,
+            id_token_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            id_token_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
             initiate_login_uri.as_ref().map(Url::as_str),
+            &post_logout_redirect_uris_array,
+            backchannel_logout_uri.as_ref().map(Url::as_str),
+            backchannel_logout_session_required,
         )
         .traced()
         .execute(&mut *self.conn)
@@ -513,10 +706,297 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             userinfo_signed_response_alg,
             token_endpoint_auth_method,
             token_endpoint_auth_signing_alg,
+            request_object_signing_alg,
+            request_uris,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
             initiate_login_uri,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests: false,
+            tls_client_auth_subject_dn: None,
+            tls_client_certificate_bound_access_tokens: false,
         })
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_client.update",
+        skip_all,
+        fields(
+            db.statement,
+            %client.id,
+            client.name = client_name
+        ),
+        err,
+    )]
+    #[allow(clippy::too_many_lines)]
+    async fn update(
+        &mut self,
+        client: Client,
+        redirect_uris: Vec<Url>,
+        encrypted_client_secret: Option<String>,
+        application_type: Option<ApplicationType>,
+        grant_types: Vec<GrantType>,
+        contacts: Vec<String>,
+        client_name: Option<String>,
+        logo_uri: Option<Url>,
+        client_uri: Option<Url>,
+        policy_uri: Option<Url>,
+        tos_uri: Option<Url>,
+        jwks_uri: Option<Url>,
+        jwks: Option<PublicJsonWebKeySet>,
+        id_token_signed_response_alg: Option<JsonWebSignatureAlg>,
+        userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
+        token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+    ) -> Result<Client, Self::Error> {
+        let jwks_json = jwks
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let request_uris_array = request_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let post_logout_redirect_uris_array = post_logout_redirect_uris
+            .iter()
+            .map(Url::to_string)
+            .collect::<Vec<_>>();
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_clients
+                SET encrypted_client_secret = $2
+                  , application_type = $3
+                  , redirect_uris = $4
+                  , grant_type_authorization_code = $5
+                  , grant_type_refresh_token = $6
+                  , grant_type_client_credentials = $7
+                  , grant_type_device_code = $8
+                  , grant_type_token_exchange = $9
+                  , contacts = $10
+                  , client_name = $11
+                  , logo_uri = $12
+                  , client_uri = $13
+                  , policy_uri = $14
+                  , tos_uri = $15
+                  , jwks_uri = $16
+                  , jwks = $17
+                  , id_token_signed_response_alg = $18
+                  , userinfo_signed_response_alg = $19
+                  , token_endpoint_auth_method = $20
+                  , token_endpoint_auth_signing_alg = $21
+                  , request_object_signing_alg = $22
+                  , request_uris = $23
+                  , id_token_encrypted_response_alg = $24
+                  , id_token_encrypted_response_enc = $25
+                  , userinfo_encrypted_response_alg = $26
+                  , userinfo_encrypted_response_enc = $27
+                  , initiate_login_uri = $28
+                  , post_logout_redirect_uris = $29
+                  , backchannel_logout_uri = $30
+                  , backchannel_logout_session_required = $31
+                WHERE oauth2_client_id = $1
+                  AND is_static = FALSE
+            "#,
+            Uuid::from(client.id),
+            encrypted_client_secret,
+            application_type.as_ref().map(ToString::to_string),
+            &redirect_uris_array,
+            grant_types.contains(&GrantType::AuthorizationCode),
+            grant_types.contains(&GrantType::RefreshToken),
+            grant_types.contains(&GrantType::ClientCredentials),
+            grant_types.contains(&GrantType::DeviceCode),
+            grant_types.contains(&GrantType::TokenExchange),
+            &contacts,
+            client_name,
+            logo_uri.as_ref().map(Url::as_str),
+            client_uri.as_ref().map(Url::as_str),
+            policy_uri.as_ref().map(Url::as_str),
+            tos_uri.as_ref().map(Url::as_str),
+            jwks_uri.as_ref().map(Url::as_str),
+            jwks_json,
+            id_token_signed_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_signed_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            token_endpoint_auth_method.as_ref().map(ToString::to_string),
+            token_endpoint_auth_signing_alg
+                .as_ref()
+                .map(ToString::to_string),
+            request_object_signing_alg.as_ref().map(ToString::to_string),
+            &request_uris_array,
+            id_token_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            id_token_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_alg
+                .as_ref()
+                .map(ToString::to_string),
+            userinfo_encrypted_response_enc
+                .as_ref()
+                .map(ToString::to_string),
+            initiate_login_uri.as_ref().map(Url::as_str),
+            &post_logout_redirect_uris_array,
+            backchannel_logout_uri.as_ref().map(Url::as_str),
+            backchannel_logout_session_required,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        let jwks = match (jwks, jwks_uri) {
+            (None, None) => None,
+            (Some(jwks), None) => Some(JwksOrJwksUri::Jwks(jwks)),
+            (None, Some(jwks_uri)) => Some(JwksOrJwksUri::JwksUri(jwks_uri)),
+            _ => return Err(DatabaseError::invalid_operation()),
+        };
+
+        Ok(Client {
+            encrypted_client_secret,
+            application_type,
+            redirect_uris,
+            grant_types,
+            contacts,
+            client_name,
+            logo_uri,
+            client_uri,
+            policy_uri,
+            tos_uri,
+            jwks,
+            id_token_signed_response_alg,
+            userinfo_signed_response_alg,
+            token_endpoint_auth_method,
+            token_endpoint_auth_signing_alg,
+            request_object_signing_alg,
+            request_uris,
+            id_token_encrypted_response_alg,
+            id_token_encrypted_response_enc,
+            userinfo_encrypted_response_alg,
+            userinfo_encrypted_response_enc,
+            initiate_login_uri,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
+            ..client
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.set_registration_access_token_hash",
+        skip_all,
+        fields(
+            db.statement,
+            %client.id,
+        ),
+        err,
+    )]
+    async fn set_registration_access_token_hash(
+        &mut self,
+        client: &Client,
+        registration_access_token_hash: &str,
+    ) -> Result<(), Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_clients
+                SET registration_access_token_hash = $2
+                WHERE oauth2_client_id = $1
+                  AND is_static = FALSE
+            "#,
+            Uuid::from(client.id),
+            registration_access_token_hash,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.find_by_registration_access_token_hash",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_registration_access_token_hash(
+        &mut self,
+        registration_access_token_hash: &str,
+    ) -> Result<Option<Client>, Self::Error> {
+        let res = sqlx::query_as!(
+            OAuth2ClientLookup,
+            r#"
+                SELECT oauth2_client_id
+                     , encrypted_client_secret
+                     , application_type
+                     , redirect_uris
+                     , grant_type_authorization_code
+                     , grant_type_refresh_token
+                     , grant_type_client_credentials
+                     , grant_type_device_code
+                     , grant_type_token_exchange
+                     , contacts
+                     , client_name
+                     , logo_uri
+                     , client_uri
+                     , policy_uri
+                     , tos_uri
+                     , jwks_uri
+                     , jwks
+                     , id_token_signed_response_alg
+                     , userinfo_signed_response_alg
+                     , token_endpoint_auth_method
+                     , token_endpoint_auth_signing_alg
+                     , request_object_signing_alg
+                     , request_uris
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
+                     , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , tls_client_auth_subject_dn
+                     , tls_client_certificate_bound_access_tokens
+                FROM oauth2_clients c
+
+                WHERE registration_access_token_hash = $1
+                  AND is_static = FALSE
+            "#,
+            registration_access_token_hash,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_client.upsert_static",
         skip_all,
@@ -534,6 +1014,12 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        tls_client_auth_subject_dn: Option<String>,
+        tls_client_certificate_bound_access_tokens: bool,
     ) -> Result<Client, Self::Error> {
         let jwks_json = jwks
             .as_ref()
@@ -543,6 +1029,10 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
 
         let client_auth_method = client_auth_method.to_string();
         let redirect_uris_array = redirect_uris.iter().map(Url::to_string).collect::<Vec<_>>();
+        let post_logout_redirect_uris_array = post_logout_redirect_uris
+            .iter()
+            .map(Url::to_string)
+            .collect::<Vec<_>>();
 
         sqlx::query!(
             r#"
@@ -550,36 +1040,60 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                     ( oauth2_client_id
                     , encrypted_client_secret
                     , redirect_uris
+                    , post_logout_redirect_uris
+                    , backchannel_logout_uri
+                    , backchannel_logout_session_required
                     , grant_type_authorization_code
                     , grant_type_refresh_token
                     , grant_type_client_credentials
+                    , grant_type_device_code
+                    , grant_type_token_exchange
                     , token_endpoint_auth_method
                     , jwks
                     , jwks_uri
+                    , require_pushed_authorization_requests
+                    , tls_client_auth_subject_dn
+                    , tls_client_certificate_bound_access_tokens
                     , is_static
                     )
                 VALUES
-                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, TRUE)
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, TRUE)
                 ON CONFLICT (oauth2_client_id)
                 DO
                     UPDATE SET encrypted_client_secret = EXCLUDED.encrypted_client_secret
+                             , post_logout_redirect_uris = EXCLUDED.post_logout_redirect_uris
+                             , backchannel_logout_uri = EXCLUDED.backchannel_logout_uri
+                             , backchannel_logout_session_required = EXCLUDED.backchannel_logout_session_required
                              , grant_type_authorization_code = EXCLUDED.grant_type_authorization_code
                              , grant_type_refresh_token = EXCLUDED.grant_type_refresh_token
                              , grant_type_client_credentials = EXCLUDED.grant_type_client_credentials
+                             , grant_type_device_code = EXCLUDED.grant_type_device_code
+                             , grant_type_token_exchange = EXCLUDED.grant_type_token_exchange
                              , token_endpoint_auth_method = EXCLUDED.token_endpoint_auth_method
                              , jwks = EXCLUDED.jwks
                              , jwks_uri = EXCLUDED.jwks_uri
+                             , require_pushed_authorization_requests = EXCLUDED.require_pushed_authorization_requests
+                             , tls_client_auth_subject_dn = EXCLUDED.tls_client_auth_subject_dn
+                             , tls_client_certificate_bound_access_tokens = EXCLUDED.tls_client_certificate_bound_access_tokens
                              , is_static = TRUE
             "#,
             Uuid::from(client_id),
             encrypted_client_secret,
             &redirect_uris_array,
+            &post_logout_redirect_uris_array,
+            backchannel_logout_uri.as_ref().map(Url::as_str),
+            backchannel_logout_session_required,
+            true,
+            true,
             true,
             true,
             true,
             client_auth_method,
             jwks_json,
             jwks_uri.as_ref().map(Url::as_str),
+            require_pushed_authorization_requests,
+            tls_client_auth_subject_dn.as_deref(),
+            tls_client_certificate_bound_access_tokens,
         )
         .traced()
         .execute(&mut *self.conn)
@@ -607,6 +1121,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                 GrantType::AuthorizationCode,
                 GrantType::RefreshToken,
                 GrantType::ClientCredentials,
+                GrantType::DeviceCode,
+                GrantType::TokenExchange,
             ],
             contacts: Vec::new(),
             client_name: None,
@@ -619,7 +1135,19 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             userinfo_signed_response_alg: None,
             token_endpoint_auth_method: None,
             token_endpoint_auth_signing_alg: None,
+            request_object_signing_alg: None,
+            request_uris: Vec::new(),
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: None,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: None,
             initiate_login_uri: None,
+            post_logout_redirect_uris,
+            backchannel_logout_uri,
+            backchannel_logout_session_required,
+            require_pushed_authorization_requests,
+            tls_client_auth_subject_dn,
+            tls_client_certificate_bound_access_tokens,
         })
     }
 
@@ -642,6 +1170,8 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , grant_type_authorization_code
                      , grant_type_refresh_token
                      , grant_type_client_credentials
+                     , grant_type_device_code
+                     , grant_type_token_exchange
                      , contacts
                      , client_name
                      , logo_uri
@@ -654,7 +1184,19 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
                      , userinfo_signed_response_alg
                      , token_endpoint_auth_method
                      , token_endpoint_auth_signing_alg
+                     , request_object_signing_alg
+                     , request_uris
+                     , id_token_encrypted_response_alg
+                     , id_token_encrypted_response_enc
+                     , userinfo_encrypted_response_alg
+                     , userinfo_encrypted_response_enc
                      , initiate_login_uri
+                     , post_logout_redirect_uris
+                     , backchannel_logout_uri
+                     , backchannel_logout_session_required
+                     , require_pushed_authorization_requests
+                     , tls_client_auth_subject_dn
+                     , tls_client_certificate_bound_access_tokens
                 FROM oauth2_clients c
                 WHERE is_static = TRUE
             "#,
@@ -789,6 +1331,46 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
             .await?;
         }
 
+        // Delete the device code grants
+        {
+            let span = info_span!(
+                "db.oauth2_client.delete_by_id.device_code_grants",
+                db.statement = tracing::field::Empty,
+            );
+
+            sqlx::query!(
+                r#"
+                    DELETE FROM oauth2_device_code_grants
+                    WHERE oauth2_client_id = $1
+                "#,
+                Uuid::from(id),
+            )
+            .record(&span)
+            .execute(&mut *self.conn)
+            .instrument(span)
+            .await?;
+        }
+
+        // Delete the pushed authorization requests
+        {
+            let span = info_span!(
+                "db.oauth2_client.delete_by_id.pushed_authorization_requests",
+                db.statement = tracing::field::Empty,
+            );
+
+            sqlx::query!(
+                r#"
+                    DELETE FROM oauth2_pushed_authorization_requests
+                    WHERE oauth2_client_id = $1
+                "#,
+                Uuid::from(id),
+            )
+            .record(&span)
+            .execute(&mut *self.conn)
+            .instrument(span)
+            .await?;
+        }
+
         // Delete the user consents
         {
             let span = info_span!(
@@ -889,4 +1471,90 @@ impl<'c> OAuth2ClientRepository for PgOAuth2ClientRepository<'c> {
 
         DatabaseError::ensure_affected_rows(&res, 1)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_client.cleanup_stale",
+        skip_all,
+        fields(
+            db.statement,
+            %inactive_since,
+        ),
+        err,
+    )]
+    async fn cleanup_stale(
+        &mut self,
+        clock: &dyn Clock,
+        inactive_since: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error> {
+        // Client IDs are ULIDs, so all the clients with an ID lower than this one
+        // were registered before the threshold
+        let registered_before = Ulid::from_parts(
+            u64::try_from(inactive_since.timestamp_millis()).unwrap_or_default(),
+            0,
+        );
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+
+        // Record the stale clients in the audit table first, so that we know which
+        // ones to delete afterwards
+        let ids: Vec<Uuid> = sqlx::query_scalar!(
+            r#"
+                INSERT INTO oauth2_client_deletions
+                    ( oauth2_client_id
+                    , client_name
+                    , redirect_uris
+                    , last_active_at
+                    , deleted_at
+                    )
+                SELECT c.oauth2_client_id
+                     , c.client_name
+                     , c.redirect_uris
+                     , ( SELECT MAX(GREATEST(s.created_at, s.last_active_at, s.finished_at))
+                         FROM oauth2_sessions s
+                         WHERE s.oauth2_client_id = c.oauth2_client_id
+                       )
+                     , $4
+                FROM oauth2_clients c
+                WHERE c.is_static = FALSE
+                  AND c.oauth2_client_id < $1
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM oauth2_sessions s
+                      WHERE s.oauth2_client_id = c.oauth2_client_id
+                        AND ( s.finished_at IS NULL
+                           OR GREATEST(s.created_at, s.last_active_at, s.finished_at) >= $2
+                            )
+                  )
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM oauth2_authorization_grants g
+                      WHERE g.oauth2_client_id = c.oauth2_client_id
+                        AND g.created_at >= $2
+                  )
+                  AND NOT EXISTS (
+                      SELECT 1
+                      FROM oauth2_device_code_grants g
+                      WHERE g.oauth2_client_id = c.oauth2_client_id
+                        AND g.created_at >= $2
+                  )
+                ORDER BY c.oauth2_client_id
+                LIMIT $3
+                RETURNING oauth2_client_id
+            "#,
+            Uuid::from(registered_before),
+            inactive_since,
+            limit,
+            clock.now(),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        let count = ids.len();
+        for id in ids {
+            self.delete_by_id(id.into()).await?;
+        }
+
+        Ok(count)
+    }
 }
@@ -18,32 +18,43 @@
 mod access_token;
 mod authorization_grant;
 mod client;
+mod device_code_grant;
+mod pushed_authorization_request;
 mod refresh_token;
 mod session;
 
 pub use self::{
     access_token::PgOAuth2AccessTokenRepository,
     authorization_grant::PgOAuth2AuthorizationGrantRepository, client::PgOAuth2ClientRepository,
+    device_code_grant::PgOAuth2DeviceCodeGrantRepository,
+    pushed_authorization_request::PgOAuth2PushedAuthorizationRequestRepository,
     refresh_token::PgOAuth2RefreshTokenRepository, session::PgOAuth2SessionRepository,
 };
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use chrono::Duration;
     use mas_data_model::AuthorizationCode;
+    use mas_iana::{
+        jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+        oauth::OAuthClientAuthenticationMethod,
+    };
     use mas_storage::{
         clock::MockClock,
-        oauth2::{OAuth2SessionFilter, OAuth2SessionRepository},
+        oauth2::{AuthorizationGrantFunnel, OAuth2SessionFilter, OAuth2SessionRepository},
         Clock, Pagination, Repository,
     };
     use oauth2_types::{
-        requests::{GrantType, ResponseMode},
+        requests::{ClaimsRequest, GrantType, ResponseMode},
         scope::{Scope, EMAIL, OPENID, PROFILE},
     };
     use rand::SeedableRng;
     use rand_chacha::ChaChaRng;
     use sqlx::PgPool;
     use ulid::Ulid;
+    use url::Url;
 
     use crate::PgRepository;
 
@@ -88,7 +99,16 @@ mod tests {
                 None,
                 None,
                 None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
                 Some("https://example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
             )
             .await
             .unwrap();
@@ -143,13 +163,29 @@ mod tests {
                 Some("state".to_owned()),
                 Some("nonce".to_owned()),
                 None,
+                vec!["urn:example:acr".to_owned()],
+                Some(ClaimsRequest {
+                    userinfo: Some([("email".to_owned(), None)].into()),
+                    id_token: None,
+                }),
                 ResponseMode::Query,
                 true,
                 false,
+                false,
+                Some("https://api.example.com/".parse().unwrap()),
             )
             .await
             .unwrap();
         assert!(grant.is_pending());
+        assert_eq!(
+            grant.resource.as_ref().map(url::Url::as_str),
+            Some("https://api.example.com/")
+        );
+        assert_eq!(grant.acr_values, vec!["urn:example:acr".to_owned()]);
+        assert!(grant
+            .claims
+            .as_ref()
+            .is_some_and(|claims| claims.requests_userinfo_claim("email")));
 
         // Lookup the same grant by id
         let grant_lookup = repo
@@ -356,19 +392,205 @@ mod tests {
             .unwrap();
         assert!(!access_token.is_valid(clock.now()));
 
-        // Mark the refresh token as consumed
+        // Link the refresh token to another access token
+        let other_access_token = repo
+            .oauth2_access_token()
+            .add(
+                &mut rng,
+                &clock,
+                &session,
+                "ddeeff".to_owned(),
+                Some(Duration::minutes(5)),
+            )
+            .await
+            .unwrap();
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .set_access_token(refresh_token, &other_access_token)
+            .await
+            .unwrap();
+        assert_eq!(refresh_token.access_token_id, Some(other_access_token.id));
+        let refresh_token_lookup = repo
+            .oauth2_refresh_token()
+            .lookup(refresh_token.id)
+            .await
+            .unwrap()
+            .expect("refresh token not found");
+        assert_eq!(refresh_token, refresh_token_lookup);
+
+        // Mark the refresh token as consumed, replaced by a new one
         assert!(refresh_token.is_valid());
+        let next_refresh_token = repo
+            .oauth2_refresh_token()
+            .add(
+                &mut rng,
+                &clock,
+                &session,
+                &other_access_token,
+                "gghhii".to_owned(),
+            )
+            .await
+            .unwrap();
         let refresh_token = repo
             .oauth2_refresh_token()
-            .consume(&clock, refresh_token)
+            .consume(&clock, refresh_token, &next_refresh_token)
             .await
             .unwrap();
         assert!(!refresh_token.is_valid());
+        assert_eq!(
+            refresh_token.next_refresh_token_id_in_grace(clock.now(), Duration::seconds(10)),
+            Some(next_refresh_token.id)
+        );
+
+        // Accept it again during the grace period, which only works once
+        let refresh_token = repo
+            .oauth2_refresh_token()
+            .use_grace(&clock, refresh_token)
+            .await
+            .unwrap();
+        let refresh_token_lookup = repo
+            .oauth2_refresh_token()
+            .lookup(refresh_token.id)
+            .await
+            .unwrap()
+            .expect("refresh token not found");
+        assert_eq!(refresh_token, refresh_token_lookup);
+        assert_eq!(
+            refresh_token.next_refresh_token_id_in_grace(clock.now(), Duration::seconds(10)),
+            None
+        );
+        assert!(repo
+            .oauth2_refresh_token()
+            .use_grace(&clock, refresh_token.clone())
+            .await
+            .is_err());
+
+        // Record it being used again
+        repo.oauth2_refresh_token()
+            .record_reuse(&mut rng, &clock, &refresh_token, true)
+            .await
+            .unwrap();
+
+        // Bind the session to a DPoP key
+        assert!(!session.is_dpop_bound());
+        let session = repo
+            .oauth2_session()
+            .bind_dpop_key(session, "some-thumbprint".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(session.dpop_jkt.as_deref(), Some("some-thumbprint"));
+
+        // Record the resource indicator of the session
+        assert_eq!(session.resource, None);
+        let session = repo
+            .oauth2_session()
+            .set_resource(session, "https://api.example.com/".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            session.resource.as_ref().map(url::Url::as_str),
+            Some("https://api.example.com/")
+        );
+
+        let session_lookup = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session not found");
+        assert_eq!(session, session_lookup);
+
+        // Bind the session to a client certificate
+        assert!(!session.is_certificate_bound());
+        let session = repo
+            .oauth2_session()
+            .bind_certificate(session, "some-certificate-thumbprint".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            session.certificate_thumbprint.as_deref(),
+            Some("some-certificate-thumbprint")
+        );
+
+        let session_lookup = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session not found");
+        assert_eq!(session, session_lookup);
+
+        // Name the session, and unset the name
+        assert_eq!(session.human_name, None);
+        let session = repo
+            .oauth2_session()
+            .set_human_name(session, Some("Work laptop".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(session.human_name.as_deref(), Some("Work laptop"));
+
+        let session_lookup = repo
+            .oauth2_session()
+            .lookup(session.id)
+            .await
+            .unwrap()
+            .expect("session not found");
+        assert_eq!(session, session_lookup);
+
+        let session = repo
+            .oauth2_session()
+            .set_human_name(session, None)
+            .await
+            .unwrap();
+        assert_eq!(session.human_name, None);
+
+        // Derive a session from it through a token exchange
+        let scope = Scope::from_iter([OPENID]);
+        let derived_session = repo
+            .oauth2_session()
+            .add_from_token_exchange(&mut rng, &clock, &client, &session, scope.clone())
+            .await
+            .unwrap();
+        assert!(derived_session.is_derived());
+        assert_eq!(derived_session.parent_session_id, Some(session.id));
+        assert_eq!(derived_session.user_id, session.user_id);
+        assert_eq!(derived_session.scope, scope);
+
+        // Sessions derived from a derived session are attached to the root session
+        let nested_session = repo
+            .oauth2_session()
+            .add_from_token_exchange(&mut rng, &clock, &client, &derived_session, scope)
+            .await
+            .unwrap();
+        assert_eq!(nested_session.parent_session_id, Some(session.id));
+
+        // Both can be listed from the root session
+        let filter = OAuth2SessionFilter::new().derived_from(&session);
+        let page = repo
+            .oauth2_session()
+            .list(filter, Pagination::first(10))
+            .await
+            .unwrap();
+        assert_eq!(page.edges.len(), 2);
+        assert!(page.edges.contains(&derived_session));
+        assert!(page.edges.contains(&nested_session));
+        assert_eq!(repo.oauth2_session().count(filter).await.unwrap(), 2);
 
         // Mark the session as finished
         assert!(session.is_valid());
         let session = repo.oauth2_session().finish(&clock, session).await.unwrap();
         assert!(!session.is_valid());
+
+        // The derived sessions should be finished as well
+        for id in [derived_session.id, nested_session.id] {
+            let derived_session = repo
+                .oauth2_session()
+                .lookup(id)
+                .await
+                .unwrap()
+                .expect("session not found");
+            assert!(derived_session.is_finished());
+        }
     }
 
     /// Test the [`OAuth2SessionRepository::list`] and
@@ -425,7 +647,16 @@ mod tests {
                 None,
                 None,
                 None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
                 Some("https://first.example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
             )
             .await
             .unwrap();
@@ -451,7 +682,16 @@ mod tests {
                 None,
                 None,
                 None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
                 Some("https://second.example.com/login".parse().unwrap()),
+                Vec::new(),
+                None,
+                false,
             )
             .await
             .unwrap();
@@ -534,6 +774,20 @@ mod tests {
 
         assert_eq!(repo.oauth2_session().count(filter).await.unwrap(), 2);
 
+        // Filter for only one browser session
+        let filter = OAuth2SessionFilter::new().for_browser_session(&user2_session);
+        let list = repo
+            .oauth2_session()
+            .list(filter, pagination)
+            .await
+            .unwrap();
+        assert!(!list.has_next_page);
+        assert_eq!(list.edges.len(), 2);
+        assert_eq!(list.edges[0], session12);
+        assert_eq!(list.edges[1], session22);
+
+        assert_eq!(repo.oauth2_session().count(filter).await.unwrap(), 2);
+
         // Filter for only one client
         let filter = OAuth2SessionFilter::new().for_client(&client1);
         let list = repo
@@ -690,4 +944,676 @@ mod tests {
         assert_eq!(list.edges[0], session11);
         assert_eq!(repo.oauth2_session().count(filter).await.unwrap(), 1);
     }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_cleanup_stale_clients(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        // Create two clients, one of them with an active session
+        let mut clients = Vec::new();
+        for name in ["Unused client", "Used client"] {
+            let client = repo
+                .oauth2_client()
+                .add(
+                    &mut rng,
+                    &clock,
+                    vec!["https://example.com/redirect".parse().unwrap()],
+                    None,
+                    None,
+                    vec![GrantType::AuthorizationCode],
+                    Vec::new(),
+                    Some(name.to_owned()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                    None,
+                    false,
+                )
+                .await
+                .unwrap();
+            clients.push(client);
+        }
+        let [unused_client, used_client] = <[_; 2]>::try_from(clients).unwrap();
+
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &clock,
+                &used_client,
+                &browser_session,
+                Scope::from_iter([OPENID]),
+            )
+            .await
+            .unwrap();
+
+        // Nothing is stale yet
+        let count = repo
+            .oauth2_client()
+            .cleanup_stale(&clock, clock.now() - Duration::days(90), 100)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // After a while, only the unused client gets deleted, as the other one still
+        // has an active session
+        clock.advance(Duration::days(100));
+        let count = repo
+            .oauth2_client()
+            .cleanup_stale(&clock, clock.now() - Duration::days(90), 100)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(repo
+            .oauth2_client()
+            .lookup(unused_client.id)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Ending the session counts as activity
+        repo.oauth2_session().finish(&clock, session).await.unwrap();
+        let count = repo
+            .oauth2_client()
+            .cleanup_stale(&clock, clock.now() - Duration::days(90), 100)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // Until it is too old
+        clock.advance(Duration::days(100));
+        let count = repo
+            .oauth2_client()
+            .cleanup_stale(&clock, clock.now() - Duration::days(90), 100)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(repo
+            .oauth2_client()
+            .lookup(used_client.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_client_registration_management(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                vec!["https://example.com/redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode],
+                Vec::new(),
+                Some("Test client".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // No client has a registration access token yet
+        assert!(repo
+            .oauth2_client()
+            .find_by_registration_access_token_hash("hash")
+            .await
+            .unwrap()
+            .is_none());
+
+        repo.oauth2_client()
+            .set_registration_access_token_hash(&client, "hash")
+            .await
+            .unwrap();
+
+        let found = repo
+            .oauth2_client()
+            .find_by_registration_access_token_hash("hash")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, client);
+
+        // Update the client metadata
+        let client = repo
+            .oauth2_client()
+            .update(
+                client,
+                vec!["https://example.com/other-redirect".parse().unwrap()],
+                None,
+                None,
+                vec![GrantType::AuthorizationCode, GrantType::RefreshToken],
+                vec!["hello@example.com".to_owned()],
+                Some("Renamed client".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(JsonWebSignatureAlg::Rs256),
+                vec!["https://example.com/request.jwt".parse().unwrap()],
+                Some(JsonWebEncryptionAlg::RsaOaep256),
+                Some(JsonWebEncryptionEnc::A256Gcm),
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(client.client_name.as_deref(), Some("Renamed client"));
+        assert_eq!(
+            client.request_object_signing_alg,
+            Some(JsonWebSignatureAlg::Rs256)
+        );
+        assert_eq!(
+            client.request_uris,
+            vec!["https://example.com/request.jwt".parse::<Url>().unwrap()]
+        );
+        assert_eq!(
+            client.id_token_encrypted_response(),
+            Some((
+                &JsonWebEncryptionAlg::RsaOaep256,
+                &JsonWebEncryptionEnc::A256Gcm
+            ))
+        );
+        assert_eq!(client.userinfo_encrypted_response(), None);
+
+        // The update is persisted and the token still points to the client
+        let found = repo
+            .oauth2_client()
+            .find_by_registration_access_token_hash("hash")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, client);
+
+        // Replacing the token invalidates the previous one
+        repo.oauth2_client()
+            .set_registration_access_token_hash(&client, "other-hash")
+            .await
+            .unwrap();
+        assert!(repo
+            .oauth2_client()
+            .find_by_registration_access_token_hash("hash")
+            .await
+            .unwrap()
+            .is_none());
+
+        repo.oauth2_client().delete(client).await.unwrap();
+        assert!(repo
+            .oauth2_client()
+            .find_by_registration_access_token_hash("other-hash")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_device_code_grant_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "alice".to_owned())
+            .await
+            .unwrap();
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+
+        let client = repo
+            .oauth2_client()
+            .add(
+                &mut rng,
+                &clock,
+                Vec::new(),
+                None,
+                None,
+                vec![GrantType::DeviceCode],
+                Vec::new(),
+                Some("TV client".to_owned()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Vec::new(),
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let grant = repo
+            .oauth2_device_code_grant()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                Scope::from_iter([OPENID]),
+                "device-code".to_owned(),
+                "USERCODE".to_owned(),
+                Duration::minutes(10),
+                Some("192.0.2.1".parse().unwrap()),
+                Some("TV/1.0".to_owned()),
+            )
+            .await
+            .unwrap();
+        assert!(grant.is_pending());
+        assert_eq!(grant.expires_at, clock.now() + Duration::minutes(10));
+
+        // Lookup it by its ID and by both codes
+        let lookup = repo
+            .oauth2_device_code_grant()
+            .lookup(grant.id)
+            .await
+            .unwrap()
+            .expect("grant not found");
+        assert_eq!(lookup, grant);
+
+        let lookup = repo
+            .oauth2_device_code_grant()
+            .find_by_device_code("device-code")
+            .await
+            .unwrap()
+            .expect("grant not found");
+        assert_eq!(lookup, grant);
+
+        let lookup = repo
+            .oauth2_device_code_grant()
+            .find_by_user_code("USERCODE")
+            .await
+            .unwrap()
+            .expect("grant not found");
+        assert_eq!(lookup, grant);
+
+        assert!(repo
+            .oauth2_device_code_grant()
+            .find_by_user_code("OTHERCODE")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Exchanging a pending grant is not allowed
+        let session = repo
+            .oauth2_session()
+            .add_from_browser_session(
+                &mut rng,
+                &clock,
+                &client,
+                &browser_session,
+                grant.scope.clone(),
+            )
+            .await
+            .unwrap();
+        assert!(repo
+            .oauth2_device_code_grant()
+            .exchange(&clock, grant.clone(), &session)
+            .await
+            .is_err());
+
+        // Fulfill it
+        let grant = repo
+            .oauth2_device_code_grant()
+            .fulfill(&clock, grant, &browser_session)
+            .await
+            .unwrap();
+        assert!(grant.is_fulfilled());
+
+        // It can't be rejected anymore
+        assert!(repo
+            .oauth2_device_code_grant()
+            .reject(&clock, grant.clone(), &browser_session)
+            .await
+            .is_err());
+
+        // Exchange it
+        let grant = repo
+            .oauth2_device_code_grant()
+            .exchange(&clock, grant, &session)
+            .await
+            .unwrap();
+        assert!(grant.is_exchanged());
+
+        let lookup = repo
+            .oauth2_device_code_grant()
+            .lookup(grant.id)
+            .await
+            .unwrap()
+            .expect("grant not found");
+        assert_eq!(lookup, grant);
+
+        // Reject another grant
+        let grant = repo
+            .oauth2_device_code_grant()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                Scope::from_iter([OPENID]),
+                "other-device-code".to_owned(),
+                "OTHERCODE".to_owned(),
+                Duration::minutes(10),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let grant = repo
+            .oauth2_device_code_grant()
+            .reject(&clock, grant, &browser_session)
+            .await
+            .unwrap();
+        assert!(grant.is_rejected());
+
+        let lookup = repo
+            .oauth2_device_code_grant()
+            .lookup(grant.id)
+            .await
+            .unwrap()
+            .expect("grant not found");
+        assert_eq!(lookup, grant);
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_pushed_authorization_request_repository(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_datetime_with_source(clock.now().into(), &mut rng),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec!["https://example.com/callback".parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                true,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(client.require_pushed_authorization_requests);
+
+        // The flag is persisted
+        let lookup = repo
+            .oauth2_client()
+            .lookup(client.id)
+            .await
+            .unwrap()
+            .expect("client not found");
+        assert!(lookup.require_pushed_authorization_requests);
+
+        let parameters = BTreeMap::from([
+            ("response_type".to_owned(), "code".to_owned()),
+            ("scope".to_owned(), "openid".to_owned()),
+        ]);
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .add(
+                &mut rng,
+                &clock,
+                &client,
+                parameters.clone(),
+                Duration::seconds(60),
+            )
+            .await
+            .unwrap();
+        assert_eq!(request.client_id, client.id);
+        assert_eq!(request.parameters, parameters);
+        assert_eq!(request.expires_at, clock.now() + Duration::seconds(60));
+        assert!(request.is_valid(clock.now()));
+
+        let lookup = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(request.id)
+            .await
+            .unwrap()
+            .expect("request not found");
+        assert_eq!(lookup, request);
+
+        // Consume it
+        let request = repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, request)
+            .await
+            .unwrap();
+        assert!(!request.is_valid(clock.now()));
+
+        let lookup = repo
+            .oauth2_pushed_authorization_request()
+            .lookup(request.id)
+            .await
+            .unwrap()
+            .expect("request not found");
+        assert_eq!(lookup, request);
+
+        // It can't be consumed twice
+        assert!(repo
+            .oauth2_pushed_authorization_request()
+            .consume(&clock, request)
+            .await
+            .is_err());
+
+        // Deleting the client also deletes its pushed requests
+        repo.oauth2_client().delete(client).await.unwrap();
+        assert!(repo
+            .oauth2_pushed_authorization_request()
+            .lookup(lookup.id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_authorization_grant_funnel(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+        let client = repo
+            .oauth2_client()
+            .upsert_static(
+                Ulid::from_datetime_with_source(clock.now().into(), &mut rng),
+                OAuthClientAuthenticationMethod::None,
+                None,
+                None,
+                None,
+                vec!["https://example.com/callback".parse().unwrap()],
+                Vec::new(),
+                None,
+                false,
+                false,
+                None,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let started_since = clock.now();
+
+        // Start three grants
+        let mut grants = Vec::new();
+        for _ in 0..3 {
+            let grant = repo
+                .oauth2_authorization_grant()
+                .add(
+                    &mut rng,
+                    &clock,
+                    &client,
+                    "https://example.com/callback".parse().unwrap(),
+                    Scope::from_iter([OPENID]),
+                    None,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                    None,
+                    ResponseMode::Query,
+                    false,
+                    false,
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+            grants.push(grant);
+        }
+
+        // The first one shows the login and consent screens, twice
+        for _ in 0..2 {
+            repo.oauth2_authorization_grant()
+                .record_login_shown(&clock, &grants[0])
+                .await
+                .unwrap();
+            repo.oauth2_authorization_grant()
+                .record_consent_shown(&clock, &grants[0])
+                .await
+                .unwrap();
+        }
+
+        // The second one errors out
+        repo.oauth2_authorization_grant()
+            .record_login_shown(&clock, &grants[1])
+            .await
+            .unwrap();
+        repo.oauth2_authorization_grant()
+            .record_error(&clock, &grants[1])
+            .await
+            .unwrap();
+
+        // Nothing is old enough to be abandoned yet
+        let abandoned = repo
+            .oauth2_authorization_grant()
+            .mark_abandoned(&clock, started_since)
+            .await
+            .unwrap();
+        assert!(abandoned.is_empty());
+
+        clock.advance(Duration::hours(2));
+        let abandoned = repo
+            .oauth2_authorization_grant()
+            .mark_abandoned(&clock, clock.now() - Duration::hours(1))
+            .await
+            .unwrap();
+        // The errored grant is not considered abandoned
+        assert_eq!(abandoned, vec![client.id, client.id]);
+
+        // Running it again doesn't mark them twice
+        let abandoned = repo
+            .oauth2_authorization_grant()
+            .mark_abandoned(&clock, clock.now() - Duration::hours(1))
+            .await
+            .unwrap();
+        assert!(abandoned.is_empty());
+
+        let funnel = repo
+            .oauth2_authorization_grant()
+            .funnel(started_since)
+            .await
+            .unwrap();
+        assert_eq!(
+            funnel,
+            vec![AuthorizationGrantFunnel {
+                client_id: client.id,
+                started: 3,
+                login_shown: 2,
+                consent_shown: 1,
+                completed: 0,
+                abandoned: 2,
+                errored: 1,
+            }]
+        );
+
+        // Grants started before the window are not counted
+        let funnel = repo
+            .oauth2_authorization_grant()
+            .funnel(clock.now())
+            .await
+            .unwrap();
+        assert!(funnel.is_empty());
+    }
 }
@@ -0,0 +1,216 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{Client, PushedAuthorizationRequest};
+use mas_storage::{oauth2::OAuth2PushedAuthorizationRequestRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError, DatabaseInconsistencyError};
+
+/// An implementation of [`OAuth2PushedAuthorizationRequestRepository`] for a
+/// PostgreSQL connection
+pub struct PgOAuth2PushedAuthorizationRequestRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgOAuth2PushedAuthorizationRequestRepository<'c> {
+    /// Create a new [`PgOAuth2PushedAuthorizationRequestRepository`] from an
+    /// active PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct PushedAuthorizationRequestLookup {
+    oauth2_pushed_authorization_request_id: Uuid,
+    oauth2_client_id: Uuid,
+    parameters: serde_json::Value,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<PushedAuthorizationRequestLookup> for PushedAuthorizationRequest {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: PushedAuthorizationRequestLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.oauth2_pushed_authorization_request_id);
+        let parameters = serde_json::from_value(value.parameters).map_err(|e| {
+            DatabaseInconsistencyError::on("oauth2_pushed_authorization_requests")
+                .column("parameters")
+                .row(id)
+                .source(e)
+        })?;
+
+        Ok(PushedAuthorizationRequest {
+            id,
+            client_id: value.oauth2_client_id.into(),
+            parameters,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            consumed_at: value.consumed_at,
+        })
+    }
+}
+
+#[async_trait]
+impl<'c> OAuth2PushedAuthorizationRequestRepository
+    for PgOAuth2PushedAuthorizationRequestRepository<'c>
+{
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.add",
+        skip_all,
+        fields(
+            db.statement,
+            oauth2_pushed_authorization_request.id,
+            oauth2_client.id = %client.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error> {
+        let created_at = clock.now();
+        let expires_at = created_at + expires_in;
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "oauth2_pushed_authorization_request.id",
+            tracing::field::display(id),
+        );
+
+        let parameters_json =
+            serde_json::to_value(&parameters).map_err(DatabaseError::to_invalid_operation)?;
+
+        sqlx::query!(
+            r#"
+                INSERT INTO oauth2_pushed_authorization_requests
+                    ( oauth2_pushed_authorization_request_id
+                    , oauth2_client_id
+                    , parameters
+                    , created_at
+                    , expires_at
+                    )
+                VALUES
+                    ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(client.id),
+            parameters_json,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(PushedAuthorizationRequest {
+            id,
+            client_id: client.id,
+            parameters,
+            created_at,
+            expires_at,
+            consumed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            oauth2_pushed_authorization_request.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(
+        &mut self,
+        id: Ulid,
+    ) -> Result<Option<PushedAuthorizationRequest>, Self::Error> {
+        let res = sqlx::query_as!(
+            PushedAuthorizationRequestLookup,
+            r#"
+                SELECT oauth2_pushed_authorization_request_id
+                     , oauth2_client_id
+                     , parameters
+                     , created_at
+                     , expires_at
+                     , consumed_at
+                FROM oauth2_pushed_authorization_requests
+
+                WHERE oauth2_pushed_authorization_request_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_pushed_authorization_request.consume",
+        skip_all,
+        fields(
+            db.statement,
+            oauth2_pushed_authorization_request.id = %request.id,
+            oauth2_client.id = %request.client_id,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<PushedAuthorizationRequest, Self::Error> {
+        let consumed_at = clock.now();
+        let request = request
+            .consume(consumed_at)
+            .map_err(DatabaseError::to_invalid_operation)?;
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_pushed_authorization_requests
+                SET consumed_at = $1
+                WHERE oauth2_pushed_authorization_request_id = $2
+                  AND consumed_at IS NULL
+            "#,
+            consumed_at,
+            Uuid::from(request.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(request)
+    }
+}
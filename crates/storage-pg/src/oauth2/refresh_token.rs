@@ -42,6 +42,8 @@ struct OAuth2RefreshTokenLookup {
     refresh_token: String,
     created_at: DateTime<Utc>,
     consumed_at: Option<DateTime<Utc>>,
+    next_oauth2_refresh_token_id: Option<Uuid>,
+    grace_used_at: Option<DateTime<Utc>>,
     oauth2_access_token_id: Option<Uuid>,
     oauth2_session_id: Uuid,
 }
@@ -50,7 +52,11 @@ impl From<OAuth2RefreshTokenLookup> for RefreshToken {
     fn from(value: OAuth2RefreshTokenLookup) -> Self {
         let state = match value.consumed_at {
             None => RefreshTokenState::Valid,
-            Some(consumed_at) => RefreshTokenState::Consumed { consumed_at },
+            Some(consumed_at) => RefreshTokenState::Consumed {
+                consumed_at,
+                next_refresh_token_id: value.next_oauth2_refresh_token_id.map(Ulid::from),
+                grace_used_at: value.grace_used_at,
+            },
         };
 
         RefreshToken {
@@ -85,6 +91,8 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
                      , refresh_token
                      , created_at
                      , consumed_at
+                     , next_oauth2_refresh_token_id
+                     , grace_used_at
                      , oauth2_access_token_id
                      , oauth2_session_id
                 FROM oauth2_refresh_tokens
@@ -120,6 +128,8 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
                      , refresh_token
                      , created_at
                      , consumed_at
+                     , next_oauth2_refresh_token_id
+                     , grace_used_at
                      , oauth2_access_token_id
                      , oauth2_session_id
                 FROM oauth2_refresh_tokens
@@ -137,6 +147,48 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
         Ok(Some(res.into()))
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_refresh_token.find_by_token_prefix",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<RefreshToken>, Self::Error> {
+        let pattern = format!("{}%", crate::escape_like(prefix));
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let res = sqlx::query_as!(
+            OAuth2RefreshTokenLookup,
+            r#"
+                SELECT oauth2_refresh_token_id
+                     , refresh_token
+                     , created_at
+                     , consumed_at
+                     , next_oauth2_refresh_token_id
+                     , grace_used_at
+                     , oauth2_access_token_id
+                     , oauth2_session_id
+                FROM oauth2_refresh_tokens
+
+                WHERE refresh_token LIKE $1
+
+                LIMIT $2
+            "#,
+            pattern,
+            limit,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_refresh_token.add",
         skip_all,
@@ -202,16 +254,19 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
         &mut self,
         clock: &dyn Clock,
         refresh_token: RefreshToken,
+        replaced_by: &RefreshToken,
     ) -> Result<RefreshToken, Self::Error> {
         let consumed_at = clock.now();
         let res = sqlx::query!(
             r#"
                 UPDATE oauth2_refresh_tokens
                 SET consumed_at = $2
+                  , next_oauth2_refresh_token_id = $3
                 WHERE oauth2_refresh_token_id = $1
             "#,
             Uuid::from(refresh_token.id),
             consumed_at,
+            Uuid::from(replaced_by.id),
         )
         .execute(&mut *self.conn)
         .await?;
@@ -219,7 +274,125 @@ impl<'c> OAuth2RefreshTokenRepository for PgOAuth2RefreshTokenRepository<'c> {
         DatabaseError::ensure_affected_rows(&res, 1)?;
 
         refresh_token
-            .consume(consumed_at)
+            .consume(consumed_at, replaced_by)
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_refresh_token.use_grace",
+        skip_all,
+        fields(
+            db.statement,
+            %refresh_token.id,
+            session.id = %refresh_token.session_id,
+        ),
+        err,
+    )]
+    async fn use_grace(
+        &mut self,
+        clock: &dyn Clock,
+        refresh_token: RefreshToken,
+    ) -> Result<RefreshToken, Self::Error> {
+        let used_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_refresh_tokens
+                SET grace_used_at = $2
+                WHERE oauth2_refresh_token_id = $1
+                  AND consumed_at IS NOT NULL
+                  AND grace_used_at IS NULL
+            "#,
+            Uuid::from(refresh_token.id),
+            used_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        refresh_token
+            .use_grace(used_at)
             .map_err(DatabaseError::to_invalid_operation)
     }
+
+    #[tracing::instrument(
+        name = "db.oauth2_refresh_token.set_access_token",
+        skip_all,
+        fields(
+            db.statement,
+            %refresh_token.id,
+            session.id = %refresh_token.session_id,
+            %access_token.id,
+        ),
+        err,
+    )]
+    async fn set_access_token(
+        &mut self,
+        mut refresh_token: RefreshToken,
+        access_token: &AccessToken,
+    ) -> Result<RefreshToken, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_refresh_tokens
+                SET oauth2_access_token_id = $2
+                WHERE oauth2_refresh_token_id = $1
+            "#,
+            Uuid::from(refresh_token.id),
+            Uuid::from(access_token.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        refresh_token.access_token_id = Some(access_token.id);
+        Ok(refresh_token)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_refresh_token.record_reuse",
+        skip_all,
+        fields(
+            db.statement,
+            %refresh_token.id,
+            session.id = %refresh_token.session_id,
+            session_revoked,
+        ),
+        err,
+    )]
+    async fn record_reuse(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        refresh_token: &RefreshToken,
+        session_revoked: bool,
+    ) -> Result<(), Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+
+        sqlx::query!(
+            r#"
+                INSERT INTO oauth2_refresh_token_reuses
+                    ( oauth2_refresh_token_reuse_id
+                    , oauth2_refresh_token_id
+                    , oauth2_session_id
+                    , session_revoked
+                    , created_at
+                    )
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(refresh_token.id),
+            Uuid::from(refresh_token.session_id),
+            session_revoked,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
 }
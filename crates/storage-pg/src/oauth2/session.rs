@@ -21,12 +21,16 @@ use mas_storage::{
     oauth2::{OAuth2SessionFilter, OAuth2SessionRepository},
     Clock, Page, Pagination,
 };
-use oauth2_types::scope::{Scope, ScopeToken};
+use oauth2_types::{
+    requests::ClaimsRequest,
+    scope::{Scope, ScopeToken},
+};
 use rand::RngCore;
 use sea_query::{enum_def, extension::postgres::PgExpr, Expr, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
-use sqlx::PgConnection;
+use sqlx::{types::Json, PgConnection};
 use ulid::Ulid;
+use url::Url;
 use uuid::Uuid;
 
 use crate::{
@@ -61,6 +65,12 @@ struct OAuthSessionLookup {
     finished_at: Option<DateTime<Utc>>,
     last_active_at: Option<DateTime<Utc>>,
     last_active_ip: Option<IpAddr>,
+    dpop_jkt: Option<String>,
+    certificate_thumbprint: Option<String>,
+    parent_oauth2_session_id: Option<Uuid>,
+    resource: Option<String>,
+    claims: Option<Json<ClaimsRequest>>,
+    human_name: Option<String>,
 }
 
 impl TryFrom<OAuthSessionLookup> for Session {
@@ -80,6 +90,17 @@ impl TryFrom<OAuthSessionLookup> for Session {
                 .source(e)
         })?;
 
+        let resource = value
+            .resource
+            .map(|resource| resource.parse())
+            .transpose()
+            .map_err(|e| {
+                DatabaseInconsistencyError::on("oauth2_sessions")
+                    .column("resource")
+                    .row(id)
+                    .source(e)
+            })?;
+
         let state = match value.finished_at {
             None => SessionState::Valid,
             Some(finished_at) => SessionState::Finished { finished_at },
@@ -95,6 +116,12 @@ impl TryFrom<OAuthSessionLookup> for Session {
             scope,
             last_active_at: value.last_active_at,
             last_active_ip: value.last_active_ip,
+            dpop_jkt: value.dpop_jkt,
+            certificate_thumbprint: value.certificate_thumbprint,
+            parent_session_id: value.parent_oauth2_session_id.map(Ulid::from),
+            resource,
+            claims: value.claims.map(|Json(claims)| claims),
+            human_name: value.human_name,
         })
     }
 }
@@ -125,6 +152,12 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                      , finished_at
                      , last_active_at
                      , last_active_ip as "last_active_ip: IpAddr"
+                     , dpop_jkt
+                     , certificate_thumbprint
+                     , parent_oauth2_session_id
+                     , resource
+                     , claims as "claims: Json<ClaimsRequest>"
+                     , human_name
                 FROM oauth2_sessions
 
                 WHERE oauth2_session_id = $1
@@ -199,6 +232,90 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
             scope,
             last_active_at: None,
             last_active_ip: None,
+            dpop_jkt: None,
+            certificate_thumbprint: None,
+            parent_session_id: None,
+            resource: None,
+            claims: None,
+            human_name: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.add_from_token_exchange",
+        skip_all,
+        fields(
+            db.statement,
+            %client.id,
+            %parent.id,
+            session.id,
+            session.scope = %scope,
+        ),
+        err,
+    )]
+    async fn add_from_token_exchange(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parent: &Session,
+        scope: Scope,
+    ) -> Result<Session, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("session.id", tracing::field::display(id));
+
+        // Sessions derived from a derived session are attached to the root session,
+        // so that finishing it finishes all of them at once
+        let parent_session_id = parent.parent_session_id.unwrap_or(parent.id);
+
+        let scope_list: Vec<String> = scope.iter().map(|s| s.as_str().to_owned()).collect();
+
+        sqlx::query!(
+            r#"
+                INSERT INTO oauth2_sessions
+                    ( oauth2_session_id
+                    , user_id
+                    , user_session_id
+                    , oauth2_client_id
+                    , scope_list
+                    , created_at
+                    , parent_oauth2_session_id
+                    , resource
+                    , claims
+                    )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            Uuid::from(id),
+            parent.user_id.map(Uuid::from),
+            parent.user_session_id.map(Uuid::from),
+            Uuid::from(client.id),
+            &scope_list,
+            created_at,
+            Uuid::from(parent_session_id),
+            parent.resource.as_ref().map(Url::as_str),
+            parent.claims.as_ref().map(Json) as _,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Session {
+            id,
+            state: SessionState::Valid,
+            created_at,
+            user_id: parent.user_id,
+            user_session_id: parent.user_session_id,
+            client_id: client.id,
+            scope,
+            last_active_at: None,
+            last_active_ip: None,
+            dpop_jkt: None,
+            certificate_thumbprint: None,
+            parent_session_id: Some(parent_session_id),
+            resource: parent.resource.clone(),
+            claims: parent.claims.clone(),
+            human_name: None,
         })
     }
 
@@ -234,11 +351,200 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
 
         DatabaseError::ensure_affected_rows(&res, 1)?;
 
+        // Also finish the sessions which were derived from this one
+        sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET finished_at = $2
+                WHERE parent_oauth2_session_id = $1
+                  AND finished_at IS NULL
+            "#,
+            Uuid::from(session.id),
+            finished_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
         session
             .finish(finished_at)
             .map_err(DatabaseError::to_invalid_operation)
     }
 
+    #[tracing::instrument(
+        name = "db.oauth2_session.bind_dpop_key",
+        skip_all,
+        fields(
+            db.statement,
+            %session.id,
+            client.id = %session.client_id,
+            session.dpop_jkt = %jkt,
+        ),
+        err,
+    )]
+    async fn bind_dpop_key(
+        &mut self,
+        mut session: Session,
+        jkt: String,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET dpop_jkt = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            &jkt,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        session.dpop_jkt = Some(jkt);
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.bind_certificate",
+        skip_all,
+        fields(
+            db.statement,
+            %session.id,
+            client.id = %session.client_id,
+            session.certificate_thumbprint = %thumbprint,
+        ),
+        err,
+    )]
+    async fn bind_certificate(
+        &mut self,
+        mut session: Session,
+        thumbprint: String,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET certificate_thumbprint = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            &thumbprint,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        session.certificate_thumbprint = Some(thumbprint);
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.set_resource",
+        skip_all,
+        fields(
+            db.statement,
+            %session.id,
+            client.id = %session.client_id,
+            session.resource = %resource,
+        ),
+        err,
+    )]
+    async fn set_resource(
+        &mut self,
+        mut session: Session,
+        resource: Url,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET resource = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            resource.as_str(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        session.resource = Some(resource);
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.set_claims",
+        skip_all,
+        fields(
+            db.statement,
+            %session.id,
+            client.id = %session.client_id,
+        ),
+        err,
+    )]
+    async fn set_claims(
+        &mut self,
+        mut session: Session,
+        claims: ClaimsRequest,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET claims = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            Json(&claims) as _,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        session.claims = Some(claims);
+        Ok(session)
+    }
+
+    #[tracing::instrument(
+        name = "db.oauth2_session.set_human_name",
+        skip_all,
+        fields(
+            db.statement,
+            %session.id,
+            client.id = %session.client_id,
+            session.human_name = human_name,
+        ),
+        err,
+    )]
+    async fn set_human_name(
+        &mut self,
+        mut session: Session,
+        human_name: Option<String>,
+    ) -> Result<Session, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET human_name = $2
+                WHERE oauth2_session_id = $1
+            "#,
+            Uuid::from(session.id),
+            human_name.as_deref(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        session.human_name = human_name;
+        Ok(session)
+    }
+
     #[tracing::instrument(
         name = "db.oauth2_session.list",
         skip_all,
@@ -289,10 +595,38 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveIp)),
                 OAuthSessionLookupIden::LastActiveIp,
             )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::DpopJkt)),
+                OAuthSessionLookupIden::DpopJkt,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::CertificateThumbprint)),
+                OAuthSessionLookupIden::CertificateThumbprint,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ParentOAuth2SessionId)),
+                OAuthSessionLookupIden::ParentOauth2SessionId,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::Resource)),
+                OAuthSessionLookupIden::Resource,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::Claims)),
+                OAuthSessionLookupIden::Claims,
+            )
+            .expr_as(
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::HumanName)),
+                OAuthSessionLookupIden::HumanName,
+            )
             .from(OAuth2Sessions::Table)
             .and_where_option(filter.user().map(|user| {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::UserId)).eq(Uuid::from(user.id))
             }))
+            .and_where_option(filter.browser_session().map(|browser_session| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::UserSessionId))
+                    .eq(Uuid::from(browser_session.id))
+            }))
             .and_where_option(filter.client().map(|client| {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::OAuth2ClientId))
                     .eq(Uuid::from(client.id))
@@ -308,6 +642,18 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                 let scope: Vec<String> = scope.iter().map(|s| s.as_str().to_owned()).collect();
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ScopeList)).contains(scope)
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                    .gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                    .lt(last_active_before)
+            }))
+            .and_where_option(filter.parent().map(|parent| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ParentOAuth2SessionId))
+                    .eq(Uuid::from(parent.id))
+            }))
             .generate_pagination(
                 (OAuth2Sessions::Table, OAuth2Sessions::OAuth2SessionId),
                 pagination,
@@ -354,6 +700,10 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                         .take(),
                 )
             }))
+            .and_where_option(filter.browser_session().map(|browser_session| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::UserSessionId))
+                    .eq(Uuid::from(browser_session.id))
+            }))
             .and_where_option(filter.client().map(|client| {
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::OAuth2ClientId))
                     .eq(Uuid::from(client.id))
@@ -369,6 +719,18 @@ impl<'c> OAuth2SessionRepository for PgOAuth2SessionRepository<'c> {
                 let scope: Vec<String> = scope.iter().map(|s| s.as_str().to_owned()).collect();
                 Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ScopeList)).contains(scope)
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                    .gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::LastActiveAt))
+                    .lt(last_active_before)
+            }))
+            .and_where_option(filter.parent().map(|parent| {
+                Expr::col((OAuth2Sessions::Table, OAuth2Sessions::ParentOAuth2SessionId))
+                    .eq(Uuid::from(parent.id))
+            }))
             .build_sqlx(PostgresQueryBuilder);
 
         let count: i64 = sqlx::query_scalar_with(&sql, arguments)
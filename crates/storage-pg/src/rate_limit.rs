@@ -0,0 +1,206 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! A module containing the PostgreSQL implementation of the
+//! [`RateLimitRepository`].
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_storage::{rate_limit::RateLimitRepository, Clock};
+use sqlx::PgConnection;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`RateLimitRepository`] for a PostgreSQL connection
+pub struct PgRateLimitRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgRateLimitRepository<'c> {
+    /// Create a new [`PgRateLimitRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl<'c> RateLimitRepository for PgRateLimitRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.rate_limit.take",
+        skip_all,
+        fields(
+            db.statement,
+            rate_limit.bucket = key,
+            rate_limit.capacity = capacity,
+        ),
+        err,
+    )]
+    async fn take(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        capacity: u32,
+        replenish_interval: Duration,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error> {
+        let now = clock.now();
+        let interval = replenish_interval
+            .to_std()
+            .map_err(DatabaseError::to_invalid_operation)?
+            .as_secs_f64();
+        let burst = f64::from(capacity) * interval;
+
+        // Taking a token pushes back the time at which the bucket is full by one
+        // interval. The bucket is empty if that time would end up more than
+        // `capacity` intervals in the future, in which case the row is left
+        // untouched and nothing is returned.
+        let taken = sqlx::query_scalar!(
+            r#"
+                INSERT INTO rate_limit_buckets AS bucket (bucket_key, full_at)
+                VALUES ($1, $2::TIMESTAMPTZ + make_interval(secs => $3))
+                ON CONFLICT (bucket_key) DO UPDATE
+                SET full_at = GREATEST(bucket.full_at, $2) + make_interval(secs => $3)
+                WHERE GREATEST(bucket.full_at, $2) + make_interval(secs => $3)
+                    <= $2 + make_interval(secs => $4)
+                RETURNING full_at
+            "#,
+            key,
+            now,
+            interval,
+            burst,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        if taken.is_some() {
+            return Ok(None);
+        }
+
+        let full_at = sqlx::query_scalar!(
+            r#"
+                SELECT full_at
+                FROM rate_limit_buckets
+                WHERE bucket_key = $1
+            "#,
+            key,
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        let capacity = i32::try_from(capacity).unwrap_or(i32::MAX);
+        Ok(Some(
+            full_at + replenish_interval - replenish_interval * capacity,
+        ))
+    }
+
+    #[tracing::instrument(
+        name = "db.rate_limit.cleanup",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn cleanup(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM rate_limit_buckets
+                WHERE full_at < $1
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use mas_storage::{clock::MockClock, Clock, Repository, RepositoryAccess};
+    use sqlx::PgPool;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_rate_limit_repo(pool: PgPool) {
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let interval = Duration::seconds(10);
+
+        // The bucket starts full, so three tokens can be taken at once
+        for _ in 0..3 {
+            let retry_at = repo
+                .rate_limit()
+                .take(&clock, "test", 3, interval)
+                .await
+                .unwrap();
+            assert_eq!(retry_at, None);
+        }
+
+        // The bucket is now empty, a token is available in 10 seconds
+        let retry_at = repo
+            .rate_limit()
+            .take(&clock, "test", 3, interval)
+            .await
+            .unwrap();
+        assert_eq!(retry_at, Some(clock.now() + interval));
+
+        // Other buckets are not affected
+        let retry_at = repo
+            .rate_limit()
+            .take(&clock, "other", 3, interval)
+            .await
+            .unwrap();
+        assert_eq!(retry_at, None);
+
+        // After 10 seconds, one token is available again
+        clock.advance(interval);
+        let retry_at = repo
+            .rate_limit()
+            .take(&clock, "test", 3, interval)
+            .await
+            .unwrap();
+        assert_eq!(retry_at, None);
+        let retry_at = repo
+            .rate_limit()
+            .take(&clock, "test", 3, interval)
+            .await
+            .unwrap();
+        assert_eq!(retry_at, Some(clock.now() + interval));
+
+        // None of the buckets are full yet
+        assert_eq!(repo.rate_limit().cleanup(&clock).await.unwrap(), 0);
+
+        // After a while, both buckets are full again and get cleaned up
+        clock.advance(Duration::minutes(1));
+        assert_eq!(repo.rate_limit().cleanup(&clock).await.unwrap(), 2);
+
+        // Which means they start full again
+        let retry_at = repo
+            .rate_limit()
+            .take(&clock, "test", 3, interval)
+            .await
+            .unwrap();
+        assert_eq!(retry_at, None);
+
+        repo.save().await.unwrap();
+    }
+}
@@ -17,20 +17,31 @@ use std::ops::{Deref, DerefMut};
 use futures_util::{future::BoxFuture, FutureExt, TryFutureExt};
 use mas_storage::{
     app_session::AppSessionRepository,
+    background_migration::BackgroundMigrationRepository,
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
     },
     job::JobRepository,
+    login_failure::LoginFailureRepository,
     oauth2::{
         OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
+        OAuth2DeviceCodeGrantRepository, OAuth2PushedAuthorizationRequestRepository,
         OAuth2RefreshTokenRepository, OAuth2SessionRepository,
     },
+    rate_limit::RateLimitRepository,
+    statistics::StatisticsRepository,
     upstream_oauth2::{
         UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
         UpstreamOAuthSessionRepository,
     },
-    user::{BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository},
+    user::{
+        BrowserSessionRepository, UserDataExportRepository, UserEmailRepository,
+        UserGroupRepository, UserLoginLinkRepository, UserPasswordRepository,
+        UserRecoveryCodeRepository, UserRecoveryRepository, UserRegistrationRepository,
+        UserRepository, UserSignInNotificationRepository, UserTermsRepository,
+        WebauthnCredentialRepository,
+    },
     Repository, RepositoryAccess, RepositoryTransaction,
 };
 use sqlx::{PgConnection, PgPool, Postgres, Transaction};
@@ -38,22 +49,31 @@ use tracing::Instrument;
 
 use crate::{
     app_session::PgAppSessionRepository,
+    background_migration::PgBackgroundMigrationRepository,
     compat::{
         PgCompatAccessTokenRepository, PgCompatRefreshTokenRepository, PgCompatSessionRepository,
         PgCompatSsoLoginRepository,
     },
     job::PgJobRepository,
+    login_failure::PgLoginFailureRepository,
     oauth2::{
         PgOAuth2AccessTokenRepository, PgOAuth2AuthorizationGrantRepository,
-        PgOAuth2ClientRepository, PgOAuth2RefreshTokenRepository, PgOAuth2SessionRepository,
+        PgOAuth2ClientRepository, PgOAuth2DeviceCodeGrantRepository,
+        PgOAuth2PushedAuthorizationRequestRepository, PgOAuth2RefreshTokenRepository,
+        PgOAuth2SessionRepository,
     },
+    rate_limit::PgRateLimitRepository,
+    statistics::PgStatisticsRepository,
     upstream_oauth2::{
         PgUpstreamOAuthLinkRepository, PgUpstreamOAuthProviderRepository,
         PgUpstreamOAuthSessionRepository,
     },
     user::{
-        PgBrowserSessionRepository, PgUserEmailRepository, PgUserPasswordRepository,
-        PgUserRepository,
+        PgBrowserSessionRepository, PgUserDataExportRepository, PgUserEmailRepository,
+        PgUserGroupRepository, PgUserLoginLinkRepository, PgUserPasswordRepository,
+        PgUserRecoveryCodeRepository, PgUserRecoveryRepository, PgUserRegistrationRepository,
+        PgUserRepository, PgUserSignInNotificationRepository, PgUserTermsRepository,
+        PgWebauthnCredentialRepository,
     },
     DatabaseError,
 };
@@ -178,6 +198,56 @@ where
         Box::new(PgUserPasswordRepository::new(self.conn.as_mut()))
     }
 
+    fn user_registration<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserRegistrationRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserRegistrationRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_recovery<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserRecoveryRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserRecoveryRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_sign_in_notification<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserSignInNotificationRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserSignInNotificationRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_recovery_code<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserRecoveryCodeRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserRecoveryCodeRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_group<'c>(&'c mut self) -> Box<dyn UserGroupRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserGroupRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_terms<'c>(&'c mut self) -> Box<dyn UserTermsRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserTermsRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_login_link<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserLoginLinkRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserLoginLinkRepository::new(self.conn.as_mut()))
+    }
+
+    fn user_data_export<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserDataExportRepository<Error = Self::Error> + 'c> {
+        Box::new(PgUserDataExportRepository::new(self.conn.as_mut()))
+    }
+
+    fn webauthn_credential<'c>(
+        &'c mut self,
+    ) -> Box<dyn WebauthnCredentialRepository<Error = Self::Error> + 'c> {
+        Box::new(PgWebauthnCredentialRepository::new(self.conn.as_mut()))
+    }
+
     fn browser_session<'c>(
         &'c mut self,
     ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -202,6 +272,20 @@ where
         ))
     }
 
+    fn oauth2_device_code_grant<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2DeviceCodeGrantRepository<Error = Self::Error> + 'c> {
+        Box::new(PgOAuth2DeviceCodeGrantRepository::new(self.conn.as_mut()))
+    }
+
+    fn oauth2_pushed_authorization_request<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+        Box::new(PgOAuth2PushedAuthorizationRequestRepository::new(
+            self.conn.as_mut(),
+        ))
+    }
+
     fn oauth2_session<'c>(
         &'c mut self,
     ) -> Box<dyn OAuth2SessionRepository<Error = Self::Error> + 'c> {
@@ -247,4 +331,24 @@ where
     fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
         Box::new(PgJobRepository::new(self.conn.as_mut()))
     }
+
+    fn rate_limit<'c>(&'c mut self) -> Box<dyn RateLimitRepository<Error = Self::Error> + 'c> {
+        Box::new(PgRateLimitRepository::new(self.conn.as_mut()))
+    }
+
+    fn login_failure<'c>(
+        &'c mut self,
+    ) -> Box<dyn LoginFailureRepository<Error = Self::Error> + 'c> {
+        Box::new(PgLoginFailureRepository::new(self.conn.as_mut()))
+    }
+
+    fn background_migration<'c>(
+        &'c mut self,
+    ) -> Box<dyn BackgroundMigrationRepository<Error = Self::Error> + 'c> {
+        Box::new(PgBackgroundMigrationRepository::new(self.conn.as_mut()))
+    }
+
+    fn statistics<'c>(&'c mut self) -> Box<dyn StatisticsRepository<Error = Self::Error> + 'c> {
+        Box::new(PgStatisticsRepository::new(self.conn.as_mut()))
+    }
 }
@@ -0,0 +1,196 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Check the version of the database schema against the migrations embedded
+//! in this binary
+
+use sqlx::{migrate::Migration, PgConnection};
+
+use crate::MIGRATOR;
+
+/// The state of the database schema, compared to the migrations embedded in
+/// this binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// All the migrations known by this binary were applied, and nothing more
+    UpToDate,
+
+    /// Some migrations known by this binary were not applied yet
+    Pending {
+        /// How many migrations are yet to be applied
+        count: usize,
+    },
+
+    /// The database has migrations applied which are newer than the latest
+    /// one known by this binary, most likely because it was migrated by a
+    /// newer version of the service
+    Newer {
+        /// The latest migration version known by this binary
+        latest_known: i64,
+
+        /// The latest migration version applied to the database
+        latest_applied: i64,
+    },
+}
+
+/// The latest migration version embedded in this binary
+#[must_use]
+pub fn latest_known_schema_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+/// List the migration versions successfully applied to the database
+async fn applied_migrations(conn: &mut PgConnection) -> Result<Vec<i64>, sqlx::Error> {
+    // The migrations table is created by the first migration run, so it might
+    // not exist yet on a fresh database
+    let has_migrations_table: bool =
+        sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+            .fetch_one(&mut *conn)
+            .await?;
+
+    if !has_migrations_table {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+        .fetch_all(&mut *conn)
+        .await
+}
+
+/// The latest migration version applied to the database, or [`None`] if no
+/// migration was applied yet
+///
+/// # Errors
+///
+/// Returns an error if the database could not be queried
+#[tracing::instrument(name = "db.schema_version.applied", skip_all, err)]
+pub async fn applied_schema_version(conn: &mut PgConnection) -> Result<Option<i64>, sqlx::Error> {
+    let applied = applied_migrations(conn).await?;
+    Ok(applied.into_iter().max())
+}
+
+/// List the migrations embedded in this binary which were not applied to the
+/// database yet, in the order they would be applied
+///
+/// # Errors
+///
+/// Returns an error if the database could not be queried
+#[tracing::instrument(name = "db.schema_version.pending", skip_all, err)]
+pub async fn pending_migrations(
+    conn: &mut PgConnection,
+) -> Result<Vec<&'static Migration>, sqlx::Error> {
+    let applied = applied_migrations(conn).await?;
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+/// Compare the migrations applied to the database with the ones embedded in
+/// this binary
+///
+/// # Errors
+///
+/// Returns an error if the database could not be queried
+#[tracing::instrument(name = "db.schema_version.check", skip_all, err)]
+pub async fn check_schema_version(conn: &mut PgConnection) -> Result<SchemaVersion, sqlx::Error> {
+    let applied = applied_migrations(conn).await?;
+    let latest_known = latest_known_schema_version();
+
+    // Some old migrations were removed from the binary (see `MIGRATOR`), so
+    // unknown applied migrations are only a problem if they are more recent
+    // than anything we know about
+    if let Some(latest_applied) = applied.iter().copied().max() {
+        if latest_applied > latest_known {
+            return Ok(SchemaVersion::Newer {
+                latest_known,
+                latest_applied,
+            });
+        }
+    }
+
+    let count = MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .count();
+
+    if count > 0 {
+        Ok(SchemaVersion::Pending { count })
+    } else {
+        Ok(SchemaVersion::UpToDate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_fresh_database(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let version = check_schema_version(&mut conn).await.unwrap();
+        assert_eq!(
+            version,
+            SchemaVersion::Pending {
+                count: MIGRATOR.iter().count()
+            }
+        );
+        assert_eq!(applied_schema_version(&mut conn).await.unwrap(), None);
+        assert_eq!(
+            pending_migrations(&mut conn).await.unwrap().len(),
+            MIGRATOR.iter().count()
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_newer_schema(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let version = check_schema_version(&mut conn).await.unwrap();
+        assert_eq!(version, SchemaVersion::UpToDate);
+        assert_eq!(
+            applied_schema_version(&mut conn).await.unwrap(),
+            Some(latest_known_schema_version())
+        );
+        assert!(pending_migrations(&mut conn).await.unwrap().is_empty());
+
+        // Pretend a newer version of the service applied another migration
+        let latest_known = MIGRATOR.iter().map(|m| m.version).max().unwrap();
+        let latest_applied = latest_known + 1;
+        sqlx::query(
+            r"
+                INSERT INTO _sqlx_migrations
+                    (version, description, success, checksum, execution_time)
+                VALUES ($1, 'from the future', TRUE, '\x00', 0)
+            ",
+        )
+        .bind(latest_applied)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let version = check_schema_version(&mut conn).await.unwrap();
+        assert_eq!(
+            version,
+            SchemaVersion::Newer {
+                latest_known,
+                latest_applied,
+            }
+        );
+    }
+}
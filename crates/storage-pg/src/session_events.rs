@@ -0,0 +1,179 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A listener for the events which happen to the sessions of users, sent
+//! through PostgreSQL notifications by triggers on the session tables
+
+use mas_data_model::{SessionEvent, SessionEventKind, SessionEventTarget};
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::DatabaseError;
+
+/// The channel on which the session events are notified
+const CHANNEL: &str = "mas::session_events";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Created,
+    Ended,
+    AuthenticationAdded,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SessionType {
+    Browser,
+    #[serde(rename = "oauth2")]
+    OAuth2,
+    Compat,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    kind: Kind,
+    session_type: SessionType,
+    session_id: String,
+    user_id: String,
+}
+
+fn parse_payload(payload: &str) -> Option<SessionEvent> {
+    let payload: Payload = serde_json::from_str(payload).ok()?;
+    let session_id = Ulid::from(Uuid::parse_str(&payload.session_id).ok()?);
+    let user_id = Ulid::from(Uuid::parse_str(&payload.user_id).ok()?);
+
+    let kind = match payload.kind {
+        Kind::Created => SessionEventKind::Created,
+        Kind::Ended => SessionEventKind::Ended,
+        Kind::AuthenticationAdded => SessionEventKind::AuthenticationAdded,
+    };
+
+    let session = match payload.session_type {
+        SessionType::Browser => SessionEventTarget::Browser(session_id),
+        SessionType::OAuth2 => SessionEventTarget::OAuth2(session_id),
+        SessionType::Compat => SessionEventTarget::Compat(session_id),
+    };
+
+    Some(SessionEvent {
+        kind,
+        session,
+        user_id,
+    })
+}
+
+/// Listens to the events which happen to the sessions of all users
+///
+/// Events are only sent once the transaction which caused them is committed.
+/// They may be lost if the connection to the database drops.
+pub struct PgSessionEventListener {
+    listener: PgListener,
+}
+
+impl PgSessionEventListener {
+    /// Start listening to the session events, on a dedicated connection
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection could not be established
+    pub async fn connect(pool: &PgPool) -> Result<Self, DatabaseError> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(CHANNEL).await?;
+        Ok(Self { listener })
+    }
+
+    /// Wait for the next session event
+    ///
+    /// If the connection dropped, this tries to reconnect first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the connection dropped and could not be
+    /// re-established
+    pub async fn recv(&mut self) -> Result<SessionEvent, DatabaseError> {
+        loop {
+            let notification = self.listener.recv().await?;
+            if let Some(event) = parse_payload(notification.payload()) {
+                return Ok(event);
+            }
+
+            tracing::warn!(
+                payload = notification.payload(),
+                "Ignoring invalid session event"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mas_data_model::{SessionEvent, SessionEventKind, SessionEventTarget};
+    use mas_storage::{clock::MockClock, Repository, RepositoryAccess};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+
+    use super::PgSessionEventListener;
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_session_events(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut listener = PgSessionEventListener::connect(&pool).await.unwrap();
+
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let user = repo
+            .user()
+            .add(&mut rng, &clock, "john".to_owned())
+            .await
+            .unwrap();
+        let session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &user, None)
+            .await
+            .unwrap();
+        let password = repo
+            .user_password()
+            .add(&mut rng, &clock, &user, 1, "hash".to_owned(), None)
+            .await
+            .unwrap();
+        repo.browser_session()
+            .authenticate_with_password(&mut rng, &clock, &session, &password)
+            .await
+            .unwrap();
+        repo.browser_session()
+            .finish(&clock, session.clone())
+            .await
+            .unwrap();
+        repo.save().await.unwrap();
+
+        for kind in [
+            SessionEventKind::Created,
+            SessionEventKind::AuthenticationAdded,
+            SessionEventKind::Ended,
+        ] {
+            assert_eq!(
+                listener.recv().await.unwrap(),
+                SessionEvent {
+                    kind,
+                    session: SessionEventTarget::Browser(session.id),
+                    user_id: user.id,
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A module containing the PostgreSQL implementation of the
+//! [`StatisticsRepository`].
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use mas_storage::{
+    statistics::{DailyStatistics, StatisticsRepository},
+    Clock,
+};
+use sqlx::PgConnection;
+
+use crate::{DatabaseError, ExecuteExt};
+
+/// An implementation of [`StatisticsRepository`] for a PostgreSQL connection
+pub struct PgStatisticsRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgStatisticsRepository<'c> {
+    /// Create a new [`PgStatisticsRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct DailyStatisticsLookup {
+    day: NaiveDate,
+    new_users: i64,
+    active_users: i64,
+    active_sessions: i64,
+    tokens_issued: i64,
+    computed_at: DateTime<Utc>,
+}
+
+impl From<DailyStatisticsLookup> for DailyStatistics {
+    fn from(value: DailyStatisticsLookup) -> Self {
+        // Counts can't be negative
+        let count = |count: i64| u64::try_from(count).unwrap_or_default();
+        DailyStatistics {
+            day: value.day,
+            new_users: count(value.new_users),
+            active_users: count(value.active_users),
+            active_sessions: count(value.active_sessions),
+            tokens_issued: count(value.tokens_issued),
+            computed_at: value.computed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> StatisticsRepository for PgStatisticsRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.statistics.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            statistics.day = %day,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, day: NaiveDate) -> Result<Option<DailyStatistics>, Self::Error> {
+        let res = sqlx::query_as!(
+            DailyStatisticsLookup,
+            r#"
+                SELECT day
+                     , new_users
+                     , active_users
+                     , active_sessions
+                     , tokens_issued
+                     , computed_at
+                FROM daily_statistics
+                WHERE day = $1
+            "#,
+            day,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.statistics.compute",
+        skip_all,
+        fields(
+            db.statement,
+            statistics.day = %day,
+        ),
+        err,
+    )]
+    async fn compute(
+        &mut self,
+        clock: &dyn Clock,
+        day: NaiveDate,
+    ) -> Result<DailyStatistics, Self::Error> {
+        let start = Utc.from_utc_datetime(&day.and_time(NaiveTime::MIN));
+        let end = start + Duration::days(1);
+
+        // Sessions only record the last time they were active, so the ones
+        // active on that day are the ones active since it started
+        let res = sqlx::query_as!(
+            DailyStatisticsLookup,
+            r#"
+                INSERT INTO daily_statistics
+                    ( day
+                    , new_users
+                    , active_users
+                    , active_sessions
+                    , tokens_issued
+                    , computed_at
+                    )
+                VALUES
+                    ( $1
+                    , (SELECT COUNT(*) FROM users WHERE created_at >= $2 AND created_at < $3)
+                    , (
+                        SELECT COUNT(DISTINCT user_id)
+                        FROM (
+                            SELECT user_id FROM user_sessions WHERE last_active_at >= $2
+                            UNION ALL
+                            SELECT user_id FROM oauth2_sessions WHERE last_active_at >= $2
+                            UNION ALL
+                            SELECT user_id FROM compat_sessions WHERE last_active_at >= $2
+                        ) AS active_sessions
+                      )
+                    , (SELECT COUNT(*) FROM oauth2_sessions WHERE last_active_at >= $2)
+                      + (SELECT COUNT(*) FROM compat_sessions WHERE last_active_at >= $2)
+                    , (
+                        SELECT COUNT(*) FROM oauth2_refresh_tokens
+                        WHERE created_at >= $2 AND created_at < $3
+                      )
+                      + (
+                        SELECT COUNT(*) FROM compat_access_tokens
+                        WHERE created_at >= $2 AND created_at < $3
+                      )
+                    , $4
+                    )
+                ON CONFLICT (day) DO UPDATE
+                SET new_users = EXCLUDED.new_users
+                  , active_users = EXCLUDED.active_users
+                  , active_sessions = EXCLUDED.active_sessions
+                  , tokens_issued = EXCLUDED.tokens_issued
+                  , computed_at = EXCLUDED.computed_at
+                RETURNING day
+                        , new_users
+                        , active_users
+                        , active_sessions
+                        , tokens_issued
+                        , computed_at
+            "#,
+            day,
+            start,
+            end,
+            clock.now(),
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        Ok(res.into())
+    }
+
+    #[tracing::instrument(
+        name = "db.statistics.list",
+        skip_all,
+        fields(
+            db.statement,
+            statistics.since = %since,
+        ),
+        err,
+    )]
+    async fn list(&mut self, since: NaiveDate) -> Result<Vec<DailyStatistics>, Self::Error> {
+        let res = sqlx::query_as!(
+            DailyStatisticsLookup,
+            r#"
+                SELECT day
+                     , new_users
+                     , active_users
+                     , active_sessions
+                     , tokens_issued
+                     , computed_at
+                FROM daily_statistics
+                WHERE day >= $1
+                ORDER BY day
+            "#,
+            since,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use mas_data_model::Device;
+    use mas_storage::{clock::MockClock, Clock, Repository, RepositoryAccess};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use sqlx::PgPool;
+
+    use crate::PgRepository;
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn test_statistics_repo(pool: PgPool) {
+        let mut rng = ChaChaRng::seed_from_u64(42);
+        let clock = MockClock::default();
+        let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+        let today = clock.now().date_naive();
+
+        // Nothing was computed yet
+        assert!(repo.statistics().lookup(today).await.unwrap().is_none());
+
+        let alice = repo
+            .user()
+            .add(&mut rng, &clock, "alice".to_owned())
+            .await
+            .unwrap();
+        repo.user()
+            .add(&mut rng, &clock, "bob".to_owned())
+            .await
+            .unwrap();
+
+        // Alice uses the web UI and a client, Bob never logs in
+        let browser_session = repo
+            .browser_session()
+            .add(&mut rng, &clock, &alice, None)
+            .await
+            .unwrap();
+        repo.browser_session()
+            .record_batch_activity(vec![(browser_session.id, clock.now(), None)])
+            .await
+            .unwrap();
+
+        let device = Device::generate(&mut rng);
+        let compat_session = repo
+            .compat_session()
+            .add(&mut rng, &clock, &alice, device, false)
+            .await
+            .unwrap();
+        repo.compat_session()
+            .record_batch_activity(vec![(compat_session.id, clock.now(), None)])
+            .await
+            .unwrap();
+        repo.compat_access_token()
+            .add(&mut rng, &clock, &compat_session, "token".to_owned(), None)
+            .await
+            .unwrap();
+
+        let statistics = repo.statistics().compute(&clock, today).await.unwrap();
+        assert_eq!(statistics.day, today);
+        assert_eq!(statistics.new_users, 2);
+        assert_eq!(statistics.active_users, 1);
+        assert_eq!(statistics.active_sessions, 1);
+        assert_eq!(statistics.tokens_issued, 1);
+        assert_eq!(statistics.computed_at, clock.now());
+
+        // Nothing happened the day before
+        let yesterday = today.pred_opt().unwrap();
+        let statistics = repo.statistics().compute(&clock, yesterday).await.unwrap();
+        assert_eq!(statistics.new_users, 0);
+        assert_eq!(statistics.tokens_issued, 0);
+
+        // Computing again replaces the previous statistics
+        clock.advance(Duration::minutes(1));
+        repo.user()
+            .add(&mut rng, &clock, "carol".to_owned())
+            .await
+            .unwrap();
+        let statistics = repo.statistics().compute(&clock, today).await.unwrap();
+        assert_eq!(statistics.new_users, 3);
+        assert_eq!(
+            repo.statistics().lookup(today).await.unwrap(),
+            Some(statistics.clone())
+        );
+
+        let list = repo.statistics().list(yesterday).await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].day, yesterday);
+        assert_eq!(list[1], statistics);
+
+        let list = repo.statistics().list(today).await.unwrap();
+        assert_eq!(list, vec![statistics]);
+
+        repo.save().await.unwrap();
+    }
+}
@@ -12,7 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use tracing::Span;
+use std::time::{Duration, Instant};
+
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Event, Metadata, Span, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
 /// An extension trait for [`sqlx::Execute`] that records the SQL statement as
 /// `db.statement` in a tracing span
@@ -38,3 +45,146 @@ where
         self
     }
 }
+
+/// The target of the events emitted by [`QueryTimingLayer`]
+const QUERY_TIMING_TARGET: &str = "mas_storage_pg::query";
+
+/// The target sqlx uses to log individual statements
+const SQLX_QUERY_TARGET: &str = "sqlx::query";
+
+/// Check whether a span is one of the spans wrapping a repository method.
+///
+/// Those are all named `db.<repository>.<method>`, which is what gets logged,
+/// instead of the raw SQL statements.
+fn is_query_span(metadata: &Metadata<'_>) -> bool {
+    metadata.target().starts_with("mas_storage_pg") && metadata.name().starts_with("db.")
+}
+
+/// Timing information attached to the query spans
+struct QueryTiming {
+    started_at: Instant,
+    rows_affected: u64,
+    rows_returned: u64,
+}
+
+/// Collects the row counts out of the statements logged by sqlx
+#[derive(Default)]
+struct RowCountVisitor {
+    rows_affected: u64,
+    rows_returned: u64,
+}
+
+impl Visit for RowCountVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "rows_affected" => self.rows_affected = value,
+            "rows_returned" => self.rows_returned = value,
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if let Ok(value) = value.try_into() {
+            self.record_u64(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// A [`Layer`] which times the repository methods, and logs how long each of
+/// them took, along with the number of rows they touched.
+///
+/// Queries are logged at the `DEBUG` level, unless they took longer than the
+/// configured threshold, in which case they are logged at the `WARN` level.
+///
+/// The row counts are collected from the statements logged by sqlx, so they
+/// are only available if those are enabled.
+pub struct QueryTimingLayer {
+    slow_threshold: Duration,
+}
+
+impl QueryTimingLayer {
+    /// Create a new [`QueryTimingLayer`], warning about queries which took
+    /// longer than the given threshold
+    #[must_use]
+    pub fn new(slow_threshold: Duration) -> Self {
+        Self { slow_threshold }
+    }
+}
+
+impl<S> Layer<S> for QueryTimingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !is_query_span(attrs.metadata()) {
+            return;
+        }
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(QueryTiming {
+                started_at: Instant::now(),
+                rows_affected: 0,
+                rows_returned: 0,
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != SQLX_QUERY_TARGET {
+            return;
+        }
+
+        let Some(span) = ctx
+            .event_scope(event)
+            .and_then(|mut scope| scope.find(|span| is_query_span(span.metadata())))
+        else {
+            return;
+        };
+
+        let mut visitor = RowCountVisitor::default();
+        event.record(&mut visitor);
+
+        if let Some(timing) = span.extensions_mut().get_mut::<QueryTiming>() {
+            timing.rows_affected += visitor.rows_affected;
+            timing.rows_returned += visitor.rows_returned;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let query = span.name();
+        let Some(timing) = span.extensions_mut().remove::<QueryTiming>() else {
+            return;
+        };
+        drop(span);
+
+        let elapsed = timing.started_at.elapsed();
+        let elapsed_ms = elapsed.as_secs_f64() * 1000.;
+        let rows_affected = timing.rows_affected;
+        let rows_returned = timing.rows_returned;
+
+        if elapsed >= self.slow_threshold {
+            tracing::warn!(
+                target: QUERY_TIMING_TARGET,
+                query,
+                elapsed_ms,
+                rows_affected,
+                rows_returned,
+                "Slow database query",
+            );
+        } else {
+            tracing::debug!(
+                target: QUERY_TIMING_TARGET,
+                query,
+                elapsed_ms,
+                rows_affected,
+                rows_returned,
+                "Database query",
+            );
+        }
+    }
+}
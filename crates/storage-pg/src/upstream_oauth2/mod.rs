@@ -64,6 +64,7 @@ mod tests {
                     issuer: "https://example.com/".to_owned(),
                     human_name: None,
                     brand_name: None,
+                    icon: None,
                     scope: Scope::from_iter([OPENID]),
                     token_endpoint_auth_method:
                         mas_iana::oauth::OAuthClientAuthenticationMethod::None,
@@ -247,6 +248,7 @@ mod tests {
                         issuer: ISSUER.to_owned(),
                         human_name: None,
                         brand_name: None,
+                        icon: None,
                         scope: scope.clone(),
                         token_endpoint_auth_method:
                             mas_iana::oauth::OAuthClientAuthenticationMethod::None,
@@ -55,6 +55,7 @@ struct ProviderLookup {
     issuer: String,
     human_name: Option<String>,
     brand_name: Option<String>,
+    icon: Option<String>,
     scope: String,
     client_id: String,
     encrypted_client_secret: Option<String>,
@@ -148,6 +149,7 @@ impl TryFrom<ProviderLookup> for UpstreamOAuthProvider {
             issuer: value.issuer,
             human_name: value.human_name,
             brand_name: value.brand_name,
+            icon: value.icon,
             scope,
             client_id: value.client_id,
             encrypted_client_secret: value.encrypted_client_secret,
@@ -186,6 +188,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     issuer,
                     human_name,
                     brand_name,
+                    icon,
                     scope,
                     client_id,
                     encrypted_client_secret,
@@ -243,6 +246,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 issuer,
                 human_name,
                 brand_name,
+                icon,
                 scope,
                 token_endpoint_auth_method,
                 token_endpoint_signing_alg,
@@ -256,12 +260,13 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 pkce_mode,
                 created_at
             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
-                      $10, $11, $12, $13, $14, $15, $16)
+                      $10, $11, $12, $13, $14, $15, $16, $17)
         "#,
             Uuid::from(id),
             &params.issuer,
             params.human_name.as_deref(),
             params.brand_name.as_deref(),
+            params.icon.as_deref(),
             params.scope.to_string(),
             params.token_endpoint_auth_method.to_string(),
             params
@@ -293,6 +298,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             issuer: params.issuer,
             human_name: params.human_name,
             brand_name: params.brand_name,
+            icon: params.icon,
             scope: params.scope,
             client_id: params.client_id,
             encrypted_client_secret: params.encrypted_client_secret,
@@ -400,6 +406,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     issuer,
                     human_name,
                     brand_name,
+                    icon,
                     scope,
                     token_endpoint_auth_method,
                     token_endpoint_signing_alg,
@@ -413,13 +420,14 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     pkce_mode,
                     created_at
                 ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
-                          $10, $11, $12, $13, $14, $15, $16)
+                          $10, $11, $12, $13, $14, $15, $16, $17)
                 ON CONFLICT (upstream_oauth_provider_id) 
                     DO UPDATE
                     SET
                         issuer = EXCLUDED.issuer,
                         human_name = EXCLUDED.human_name,
                         brand_name = EXCLUDED.brand_name,
+                        icon = EXCLUDED.icon,
                         scope = EXCLUDED.scope,
                         token_endpoint_auth_method = EXCLUDED.token_endpoint_auth_method,
                         token_endpoint_signing_alg = EXCLUDED.token_endpoint_signing_alg,
@@ -437,6 +445,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             &params.issuer,
             params.human_name.as_deref(),
             params.brand_name.as_deref(),
+            params.icon.as_deref(),
             params.scope.to_string(),
             params.token_endpoint_auth_method.to_string(),
             params
@@ -468,6 +477,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
             issuer: params.issuer,
             human_name: params.human_name,
             brand_name: params.brand_name,
+            icon: params.icon,
             scope: params.scope,
             client_id: params.client_id,
             encrypted_client_secret: params.encrypted_client_secret,
@@ -526,6 +536,10 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                 )),
                 ProviderLookupIden::BrandName,
             )
+            .expr_as(
+                Expr::col((UpstreamOAuthProviders::Table, UpstreamOAuthProviders::Icon)),
+                ProviderLookupIden::Icon,
+            )
             .expr_as(
                 Expr::col((UpstreamOAuthProviders::Table, UpstreamOAuthProviders::Scope)),
                 ProviderLookupIden::Scope,
@@ -680,6 +694,7 @@ impl<'c> UpstreamOAuthProviderRepository for PgUpstreamOAuthProviderRepository<'
                     issuer,
                     human_name,
                     brand_name,
+                    icon,
                     scope,
                     client_id,
                     encrypted_client_secret,
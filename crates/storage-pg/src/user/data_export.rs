@@ -0,0 +1,305 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{User, UserDataExport};
+use mas_storage::{user::UserDataExportRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserDataExportRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserDataExportRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserDataExportRepository<'c> {
+    /// Create a new [`PgUserDataExportRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserDataExportLookup {
+    user_data_export_id: Uuid,
+    user_id: Uuid,
+    token: String,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserDataExportLookup> for UserDataExport {
+    fn from(value: UserDataExportLookup) -> Self {
+        UserDataExport {
+            id: value.user_data_export_id.into(),
+            user_id: value.user_id.into(),
+            token: value.token,
+            created_at: value.created_at,
+            completed_at: value.completed_at,
+            expires_at: value.expires_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserDataExportRepository for PgUserDataExportRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_data_export.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            user_data_export.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserDataExport>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserDataExportLookup,
+            r#"
+                SELECT user_data_export_id
+                     , user_id
+                     , token
+                     , created_at
+                     , completed_at
+                     , expires_at
+                FROM user_data_exports
+                WHERE user_data_export_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.find_by_token",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserDataExport>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserDataExportLookup,
+            r#"
+                SELECT user_data_export_id
+                     , user_id
+                     , token
+                     , created_at
+                     , completed_at
+                     , expires_at
+                FROM user_data_exports
+                WHERE token = $1
+            "#,
+            token,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.latest",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn latest(&mut self, user: &User) -> Result<Option<UserDataExport>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserDataExportLookup,
+            r#"
+                SELECT user_data_export_id
+                     , user_id
+                     , token
+                     , created_at
+                     , completed_at
+                     , expires_at
+                FROM user_data_exports
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT 1
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.add",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user_data_export.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        token: String,
+    ) -> Result<UserDataExport, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_data_export.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_data_exports
+                    ( user_data_export_id
+                    , user_id
+                    , token
+                    , created_at
+                    )
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            &token,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserDataExport {
+            id,
+            user_id: user.id,
+            token,
+            created_at,
+            completed_at: None,
+            expires_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.complete",
+        skip_all,
+        fields(
+            db.statement,
+            %user_data_export.id,
+        ),
+        err,
+    )]
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_data_export: UserDataExport,
+        archive: serde_json::Value,
+        ttl: Duration,
+    ) -> Result<UserDataExport, Self::Error> {
+        let completed_at = clock.now();
+        let expires_at = completed_at + ttl;
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_data_exports
+                SET archive = $2
+                  , completed_at = $3
+                  , expires_at = $4
+                WHERE user_data_export_id = $1
+                  AND completed_at IS NULL
+            "#,
+            Uuid::from(user_data_export.id),
+            archive,
+            completed_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_data_export.completed_at = Some(completed_at);
+        user_data_export.expires_at = Some(expires_at);
+        Ok(user_data_export)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.archive",
+        skip_all,
+        fields(
+            db.statement,
+            %user_data_export.id,
+        ),
+        err,
+    )]
+    async fn archive(
+        &mut self,
+        user_data_export: &UserDataExport,
+    ) -> Result<Option<serde_json::Value>, Self::Error> {
+        let res = sqlx::query_scalar!(
+            r#"
+                SELECT archive
+                FROM user_data_exports
+                WHERE user_data_export_id = $1
+            "#,
+            Uuid::from(user_data_export.id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.flatten())
+    }
+
+    #[tracing::instrument(
+        name = "db.user_data_export.cleanup_expired",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_data_exports
+                SET archive = NULL
+                WHERE expires_at <= $1
+                  AND archive IS NOT NULL
+            "#,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(res.rows_affected().try_into().unwrap_or(usize::MAX))
+    }
+}
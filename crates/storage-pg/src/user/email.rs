@@ -16,7 +16,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{User, UserEmail, UserEmailVerification, UserEmailVerificationState};
 use mas_storage::{
-    user::{UserEmailFilter, UserEmailRepository},
+    user::{UserEmailFilter, UserEmailInUseError, UserEmailRepository},
     Clock, Page, Pagination,
 };
 use rand::RngCore;
@@ -269,6 +269,11 @@ impl<'c> UserEmailRepository for PgUserEmailRepository<'c> {
             .and_where_option(filter.user().map(|user| {
                 Expr::col((UserEmails::Table, UserEmails::UserId)).eq(Uuid::from(user.id))
             }))
+            .and_where_option(
+                filter
+                    .email()
+                    .map(|email| Expr::col((UserEmails::Table, UserEmails::Email)).eq(email)),
+            )
             .and_where_option(filter.state().map(|state| {
                 if state.is_verified() {
                     Expr::col((UserEmails::Table, UserEmails::ConfirmedAt)).is_not_null()
@@ -304,6 +309,11 @@ impl<'c> UserEmailRepository for PgUserEmailRepository<'c> {
             .and_where_option(filter.user().map(|user| {
                 Expr::col((UserEmails::Table, UserEmails::UserId)).eq(Uuid::from(user.id))
             }))
+            .and_where_option(
+                filter
+                    .email()
+                    .map(|email| Expr::col((UserEmails::Table, UserEmails::Email)).eq(email)),
+            )
             .and_where_option(filter.state().map(|state| {
                 if state.is_verified() {
                     Expr::col((UserEmails::Table, UserEmails::ConfirmedAt)).is_not_null()
@@ -428,7 +438,16 @@ impl<'c> UserEmailRepository for PgUserEmailRepository<'c> {
             confirmed_at,
         )
         .execute(&mut *self.conn)
-        .await?;
+        .await
+        .map_err(|e| match &e {
+            // A verified address can only belong to one user
+            sqlx::Error::Database(error)
+                if error.constraint() == Some("user_emails_email_verified_unique") =>
+            {
+                DatabaseError::to_invalid_operation(UserEmailInUseError)
+            }
+            _ => e.into(),
+        })?;
 
         user_email.confirmed_at = Some(confirmed_at);
         Ok(user_email)
@@ -0,0 +1,123 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::User;
+use mas_storage::{user::UserGroupRepository, Clock};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserGroupRepository`] for a PostgreSQL connection
+pub struct PgUserGroupRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserGroupRepository<'c> {
+    /// Create a new [`PgUserGroupRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl<'c> UserGroupRepository for PgUserGroupRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_group.list",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn list(&mut self, user: &User) -> Result<Vec<String>, Self::Error> {
+        let groups = sqlx::query_scalar!(
+            r#"
+                SELECT group_name
+                FROM user_groups
+                WHERE user_id = $1
+                ORDER BY group_name ASC
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(groups)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_group.add",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        groups: &[String],
+    ) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                INSERT INTO user_groups (user_id, group_name, created_at)
+                SELECT $1, group_name, $3 FROM UNNEST($2::text[]) u(group_name)
+                ON CONFLICT (user_id, group_name) DO NOTHING
+            "#,
+            Uuid::from(user.id),
+            groups,
+            clock.now(),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "db.user_group.remove",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn remove(&mut self, user: &User, groups: &[String]) -> Result<(), Self::Error> {
+        sqlx::query!(
+            r#"
+                DELETE FROM user_groups
+                WHERE user_id = $1
+                  AND group_name = ANY($2::text[])
+            "#,
+            Uuid::from(user.id),
+            groups,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(())
+    }
+}
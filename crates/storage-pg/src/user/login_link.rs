@@ -0,0 +1,231 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{UserEmail, UserLoginLink};
+use mas_storage::{user::UserLoginLinkRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserLoginLinkRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserLoginLinkRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserLoginLinkRepository<'c> {
+    /// Create a new [`PgUserLoginLinkRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserLoginLinkLookup {
+    user_login_link_id: Uuid,
+    user_id: Uuid,
+    user_email_id: Uuid,
+    token: String,
+    post_auth_action: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserLoginLinkLookup> for UserLoginLink {
+    fn from(value: UserLoginLinkLookup) -> Self {
+        UserLoginLink {
+            id: value.user_login_link_id.into(),
+            user_id: value.user_id.into(),
+            user_email_id: value.user_email_id.into(),
+            token: value.token,
+            post_auth_action: value.post_auth_action,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            consumed_at: value.consumed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserLoginLinkRepository for PgUserLoginLinkRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_login_link.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            user_login_link.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserLoginLink>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserLoginLinkLookup,
+            r#"
+                SELECT user_login_link_id
+                     , user_id
+                     , user_email_id
+                     , token
+                     , post_auth_action
+                     , created_at
+                     , expires_at
+                     , consumed_at
+                FROM user_login_links
+                WHERE user_login_link_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_login_link.add",
+        skip_all,
+        fields(
+            db.statement,
+            %user_email.user_id,
+            %user_email.id,
+            user_login_link.id,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_email: &UserEmail,
+        token: String,
+        post_auth_action: Option<serde_json::Value>,
+        ttl: Duration,
+    ) -> Result<UserLoginLink, Self::Error> {
+        let created_at = clock.now();
+        let expires_at = created_at + ttl;
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_login_link.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_login_links
+                    ( user_login_link_id
+                    , user_id
+                    , user_email_id
+                    , token
+                    , post_auth_action
+                    , created_at
+                    , expires_at
+                    )
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_email.user_id),
+            Uuid::from(user_email.id),
+            &token,
+            post_auth_action,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserLoginLink {
+            id,
+            user_id: user_email.user_id,
+            user_email_id: user_email.id,
+            token,
+            post_auth_action,
+            created_at,
+            expires_at,
+            consumed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_login_link.find_by_token",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserLoginLink>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserLoginLinkLookup,
+            r#"
+                SELECT user_login_link_id
+                     , user_id
+                     , user_email_id
+                     , token
+                     , post_auth_action
+                     , created_at
+                     , expires_at
+                     , consumed_at
+                FROM user_login_links
+                WHERE token = $1
+            "#,
+            token,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_login_link.consume",
+        skip_all,
+        fields(
+            db.statement,
+            %login_link.id,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        mut login_link: UserLoginLink,
+    ) -> Result<UserLoginLink, Self::Error> {
+        let consumed_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_login_links
+                SET consumed_at = $2
+                WHERE user_login_link_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(login_link.id),
+            consumed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        login_link.consumed_at = Some(consumed_at);
+        Ok(login_link)
+    }
+}
@@ -18,24 +18,49 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::User;
-use mas_storage::{user::UserRepository, Clock};
+use mas_storage::{
+    user::{UserFilter, UserRepository},
+    Clock, Page, Pagination,
+};
 use rand::RngCore;
+use sea_query::{
+    enum_def, extension::postgres::PgExpr, Expr, PostgresQueryBuilder, Query, SimpleExpr,
+};
+use sea_query_binder::SqlxBinder;
 use sqlx::PgConnection;
 use ulid::Ulid;
 use uuid::Uuid;
 
-use crate::{tracing::ExecuteExt, DatabaseError};
+use crate::{
+    iden::{UserEmails, Users},
+    pagination::QueryBuilderExt,
+    tracing::ExecuteExt,
+    DatabaseError,
+};
 
+mod data_export;
 mod email;
+mod group;
+mod login_link;
 mod password;
+mod recovery;
+mod recovery_code;
+mod registration;
 mod session;
+mod sign_in_notification;
+mod terms;
+mod webauthn;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::{
-    email::PgUserEmailRepository, password::PgUserPasswordRepository,
-    session::PgBrowserSessionRepository,
+    data_export::PgUserDataExportRepository, email::PgUserEmailRepository,
+    group::PgUserGroupRepository, login_link::PgUserLoginLinkRepository,
+    password::PgUserPasswordRepository, recovery::PgUserRecoveryRepository,
+    recovery_code::PgUserRecoveryCodeRepository, registration::PgUserRegistrationRepository,
+    session::PgBrowserSessionRepository, sign_in_notification::PgUserSignInNotificationRepository,
+    terms::PgUserTermsRepository, webauthn::PgWebauthnCredentialRepository,
 };
 
 /// An implementation of [`UserRepository`] for a PostgreSQL connection
@@ -50,7 +75,8 @@ impl<'c> PgUserRepository<'c> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, sqlx::FromRow)]
+#[enum_def]
 struct UserLookup {
     user_id: Uuid,
     username: String,
@@ -58,6 +84,12 @@ struct UserLookup {
     created_at: DateTime<Utc>,
     locked_at: Option<DateTime<Utc>>,
     can_request_admin: bool,
+    is_service_account: bool,
+    locale: Option<String>,
+    password_reset_required_at: Option<DateTime<Utc>>,
+    deleted_at: Option<DateTime<Utc>>,
+    purged_at: Option<DateTime<Utc>>,
+    compat_password_deprecated_at: Option<DateTime<Utc>>,
 }
 
 impl From<UserLookup> for User {
@@ -71,10 +103,36 @@ impl From<UserLookup> for User {
             created_at: value.created_at,
             locked_at: value.locked_at,
             can_request_admin: value.can_request_admin,
+            is_service_account: value.is_service_account,
+            locale: value.locale,
+            password_reset_required_at: value.password_reset_required_at,
+            deleted_at: value.deleted_at,
+            purged_at: value.purged_at,
+            compat_password_deprecated_at: value.compat_password_deprecated_at,
         }
     }
 }
 
+/// Condition matching the users whose username or one of their email
+/// addresses contains the given text, ignoring case
+fn search_condition(search: &str) -> SimpleExpr {
+    let pattern = format!("%{}%", crate::escape_like(search));
+
+    Expr::col((Users::Table, Users::Username))
+        .ilike(pattern.clone())
+        .or(Expr::exists(
+            Query::select()
+                .expr(Expr::cust("1"))
+                .from(UserEmails::Table)
+                .and_where(
+                    Expr::col((UserEmails::Table, UserEmails::UserId))
+                        .equals((Users::Table, Users::UserId)),
+                )
+                .and_where(Expr::col((UserEmails::Table, UserEmails::Email)).ilike(pattern))
+                .take(),
+        ))
+}
+
 #[async_trait]
 impl<'c> UserRepository for PgUserRepository<'c> {
     type Error = DatabaseError;
@@ -98,6 +156,12 @@ impl<'c> UserRepository for PgUserRepository<'c> {
                      , created_at
                      , locked_at
                      , can_request_admin
+                     , is_service_account
+                     , locale
+                     , password_reset_required_at
+                     , deleted_at
+                     , purged_at
+                     , compat_password_deprecated_at
                 FROM users
                 WHERE user_id = $1
             "#,
@@ -131,6 +195,12 @@ impl<'c> UserRepository for PgUserRepository<'c> {
                      , created_at
                      , locked_at
                      , can_request_admin
+                     , is_service_account
+                     , locale
+                     , password_reset_required_at
+                     , deleted_at
+                     , purged_at
+                     , compat_password_deprecated_at
                 FROM users
                 WHERE username = $1
             "#,
@@ -191,6 +261,12 @@ impl<'c> UserRepository for PgUserRepository<'c> {
             created_at,
             locked_at: None,
             can_request_admin: false,
+            is_service_account: false,
+            locale: None,
+            password_reset_required_at: None,
+            deleted_at: None,
+            purged_at: None,
+            compat_password_deprecated_at: None,
         })
     }
 
@@ -321,4 +397,695 @@ impl<'c> UserRepository for PgUserRepository<'c> {
 
         Ok(user)
     }
+
+    #[tracing::instrument(
+        name = "db.user.set_service_account",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user.is_service_account = is_service_account,
+        ),
+        err,
+    )]
+    async fn set_service_account(
+        &mut self,
+        mut user: User,
+        is_service_account: bool,
+    ) -> Result<User, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET is_service_account = $2
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+            is_service_account,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.is_service_account = is_service_account;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.set_locale",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user.locale = locale,
+        ),
+        err,
+    )]
+    async fn set_locale(&mut self, mut user: User, locale: String) -> Result<User, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET locale = $2
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+            &locale,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.locale = Some(locale);
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.require_password_reset",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn require_password_reset(
+        &mut self,
+        clock: &dyn Clock,
+        mut user: User,
+    ) -> Result<User, Self::Error> {
+        let password_reset_required_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET password_reset_required_at = $2
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+            password_reset_required_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.password_reset_required_at = Some(password_reset_required_at);
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.clear_password_reset",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn clear_password_reset(&mut self, mut user: User) -> Result<User, Self::Error> {
+        if user.password_reset_required_at.is_none() {
+            return Ok(user);
+        }
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET password_reset_required_at = NULL
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.password_reset_required_at = None;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.deprecate_compat_password",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn deprecate_compat_password(
+        &mut self,
+        clock: &dyn Clock,
+        mut user: User,
+    ) -> Result<User, Self::Error> {
+        // Don't restart the grace period if the deprecation was already started
+        if user.compat_password_deprecated_at.is_some() {
+            return Ok(user);
+        }
+
+        let compat_password_deprecated_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET compat_password_deprecated_at = $2
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+            compat_password_deprecated_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.compat_password_deprecated_at = Some(compat_password_deprecated_at);
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.clear_compat_password_deprecation",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn clear_compat_password_deprecation(
+        &mut self,
+        mut user: User,
+    ) -> Result<User, Self::Error> {
+        if user.compat_password_deprecated_at.is_none() {
+            return Ok(user);
+        }
+
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET compat_password_deprecated_at = NULL
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.compat_password_deprecated_at = None;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.soft_delete",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn soft_delete(
+        &mut self,
+        clock: &dyn Clock,
+        mut user: User,
+    ) -> Result<User, Self::Error> {
+        if user.deleted_at.is_some() {
+            return Ok(user);
+        }
+
+        let deleted_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET deleted_at = $2
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+            deleted_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.deleted_at = Some(deleted_at);
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.restore",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn restore(&mut self, mut user: User) -> Result<User, Self::Error> {
+        if user.deleted_at.is_none() {
+            return Ok(user);
+        }
+
+        // Purged users can't be restored, so this only affects users which
+        // are still in their grace period
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET deleted_at = NULL
+                WHERE user_id = $1
+                  AND purged_at IS NULL
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user.deleted_at = None;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.list_purgeable",
+        skip_all,
+        fields(
+            db.statement,
+            user.deleted_before = %deleted_before,
+        ),
+        err,
+    )]
+    async fn list_purgeable(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<User>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserLookup,
+            r#"
+                SELECT user_id
+                     , username
+                     , primary_user_email_id
+                     , created_at
+                     , locked_at
+                     , can_request_admin
+                     , is_service_account
+                     , locale
+                     , password_reset_required_at
+                     , deleted_at
+                     , purged_at
+                     , compat_password_deprecated_at
+                FROM users
+                WHERE deleted_at < $1
+                  AND purged_at IS NULL
+                ORDER BY deleted_at ASC
+                LIMIT $2
+            "#,
+            deleted_before,
+            i64::try_from(limit).unwrap_or(i64::MAX),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
+    #[tracing::instrument(
+        name = "db.user.purge",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn purge(&mut self, clock: &dyn Clock, mut user: User) -> Result<User, Self::Error> {
+        if user.purged_at.is_some() {
+            return Ok(user);
+        }
+
+        let user_id = Uuid::from(user.id);
+        let purged_at = clock.now();
+
+        // The primary email must be unset before the emails can be deleted
+        let res = sqlx::query!(
+            r#"
+                UPDATE users
+                SET purged_at = $2
+                  , primary_user_email_id = NULL
+                  , locale = NULL
+                WHERE user_id = $1
+                  AND deleted_at IS NOT NULL
+            "#,
+            user_id,
+            purged_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_email_confirmation_codes
+                WHERE user_email_id IN (
+                    SELECT user_email_id
+                    FROM user_emails
+                    WHERE user_id = $1
+                )
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_emails
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        // The authentications reference the passwords and passkeys, so they
+        // have to go first
+        sqlx::query!(
+            r#"
+                DELETE FROM user_session_authentications
+                WHERE user_session_id IN (
+                    SELECT user_session_id
+                    FROM user_sessions
+                    WHERE user_id = $1
+                )
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_passwords
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM webauthn_credentials
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_recovery_codes
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_recovery_tickets
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_sign_in_notifications
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM oauth2_consents
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_groups
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                DELETE FROM user_data_exports
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        // Unlinking the upstream accounts lets them be used again to register
+        sqlx::query!(
+            r#"
+                UPDATE upstream_oauth_links
+                SET user_id = NULL
+                WHERE user_id = $1
+            "#,
+            user_id,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        // End all the sessions, forgetting where they were used from
+        sqlx::query!(
+            r#"
+                UPDATE user_sessions
+                SET finished_at = COALESCE(finished_at, $2)
+                  , user_agent = NULL
+                  , last_active_ip = NULL
+                WHERE user_id = $1
+            "#,
+            user_id,
+            purged_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                UPDATE compat_sessions
+                SET finished_at = COALESCE(finished_at, $2)
+                  , last_active_ip = NULL
+                WHERE user_id = $1
+            "#,
+            user_id,
+            purged_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        sqlx::query!(
+            r#"
+                UPDATE oauth2_sessions
+                SET finished_at = COALESCE(finished_at, $2)
+                  , last_active_ip = NULL
+                WHERE user_id = $1
+            "#,
+            user_id,
+            purged_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        user.purged_at = Some(purged_at);
+        user.primary_user_email_id = None;
+        user.locale = None;
+
+        Ok(user)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.list",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn list(
+        &mut self,
+        filter: UserFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<User>, Self::Error> {
+        let (sql, arguments) =
+            Query::select()
+                .expr_as(
+                    Expr::col((Users::Table, Users::UserId)),
+                    UserLookupIden::UserId,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::Username)),
+                    UserLookupIden::Username,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::PrimaryUserEmailId)),
+                    UserLookupIden::PrimaryUserEmailId,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::CreatedAt)),
+                    UserLookupIden::CreatedAt,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::LockedAt)),
+                    UserLookupIden::LockedAt,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::CanRequestAdmin)),
+                    UserLookupIden::CanRequestAdmin,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::IsServiceAccount)),
+                    UserLookupIden::IsServiceAccount,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::Locale)),
+                    UserLookupIden::Locale,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::PasswordResetRequiredAt)),
+                    UserLookupIden::PasswordResetRequiredAt,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::DeletedAt)),
+                    UserLookupIden::DeletedAt,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::PurgedAt)),
+                    UserLookupIden::PurgedAt,
+                )
+                .expr_as(
+                    Expr::col((Users::Table, Users::CompatPasswordDeprecatedAt)),
+                    UserLookupIden::CompatPasswordDeprecatedAt,
+                )
+                .from(Users::Table)
+                .and_where_option(filter.state().map(|state| {
+                    if state.is_locked() {
+                        Expr::col((Users::Table, Users::LockedAt)).is_not_null()
+                    } else {
+                        Expr::col((Users::Table, Users::LockedAt)).is_null()
+                    }
+                }))
+                .and_where_option(filter.search().map(search_condition))
+                .and_where_option(filter.created_after().map(|created_after| {
+                    Expr::col((Users::Table, Users::CreatedAt)).gt(created_after)
+                }))
+                .and_where_option(filter.created_before().map(|created_before| {
+                    Expr::col((Users::Table, Users::CreatedAt)).lt(created_before)
+                }))
+                .generate_pagination((Users::Table, Users::UserId), pagination)
+                .build_sqlx(PostgresQueryBuilder);
+
+        let edges: Vec<UserLookup> = sqlx::query_as_with(&sql, arguments)
+            .traced()
+            .fetch_all(&mut *self.conn)
+            .await?;
+
+        let page = pagination.process(edges).map(User::from);
+
+        Ok(page)
+    }
+
+    #[tracing::instrument(
+        name = "db.user.count",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn count(&mut self, filter: UserFilter<'_>) -> Result<usize, Self::Error> {
+        let (sql, arguments) =
+            Query::select()
+                .expr(Expr::col((Users::Table, Users::UserId)).count())
+                .from(Users::Table)
+                .and_where_option(filter.state().map(|state| {
+                    if state.is_locked() {
+                        Expr::col((Users::Table, Users::LockedAt)).is_not_null()
+                    } else {
+                        Expr::col((Users::Table, Users::LockedAt)).is_null()
+                    }
+                }))
+                .and_where_option(filter.search().map(search_condition))
+                .and_where_option(filter.created_after().map(|created_after| {
+                    Expr::col((Users::Table, Users::CreatedAt)).gt(created_after)
+                }))
+                .and_where_option(filter.created_before().map(|created_before| {
+                    Expr::col((Users::Table, Users::CreatedAt)).lt(created_before)
+                }))
+                .build_sqlx(PostgresQueryBuilder);
+
+        let count: i64 = sqlx::query_scalar_with(&sql, arguments)
+            .traced()
+            .fetch_one(&mut *self.conn)
+            .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
 }
@@ -154,4 +154,45 @@ impl<'c> UserPasswordRepository for PgUserPasswordRepository<'c> {
             created_at,
         })
     }
+
+    #[tracing::instrument(
+        name = "db.user_password.count_active_by_version",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn count_active_by_version(&mut self) -> Result<Vec<(u16, usize)>, Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                SELECT version AS "version!"
+                     , COUNT(*) AS "count!"
+                FROM (
+                    SELECT DISTINCT ON (user_id) version
+                    FROM user_passwords
+                    ORDER BY user_id, created_at DESC
+                ) active_passwords
+                GROUP BY version
+            "#,
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        res.into_iter()
+            .map(|row| {
+                let version = row.version.try_into().map_err(|e| {
+                    DatabaseInconsistencyError::on("user_passwords")
+                        .column("version")
+                        .source(e)
+                })?;
+                let count = row
+                    .count
+                    .try_into()
+                    .map_err(DatabaseError::to_invalid_operation)?;
+                Ok((version, count))
+            })
+            .collect()
+    }
 }
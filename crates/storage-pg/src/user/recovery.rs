@@ -0,0 +1,186 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{User, UserRecoveryTicket};
+use mas_storage::{user::UserRecoveryRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserRecoveryRepository`] for a PostgreSQL connection
+pub struct PgUserRecoveryRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserRecoveryRepository<'c> {
+    /// Create a new [`PgUserRecoveryRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserRecoveryTicketLookup {
+    user_recovery_ticket_id: Uuid,
+    user_id: Uuid,
+    ticket: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserRecoveryTicketLookup> for UserRecoveryTicket {
+    fn from(value: UserRecoveryTicketLookup) -> Self {
+        UserRecoveryTicket {
+            id: value.user_recovery_ticket_id.into(),
+            user_id: value.user_id.into(),
+            ticket: value.ticket,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            consumed_at: value.consumed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserRecoveryRepository for PgUserRecoveryRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_recovery.add_ticket",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user_recovery_ticket.id,
+        ),
+        err,
+    )]
+    async fn add_ticket(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        ticket: String,
+        ttl: Duration,
+    ) -> Result<UserRecoveryTicket, Self::Error> {
+        let created_at = clock.now();
+        let expires_at = created_at + ttl;
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_recovery_ticket.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_recovery_tickets
+                    ( user_recovery_ticket_id
+                    , user_id
+                    , ticket
+                    , created_at
+                    , expires_at
+                    )
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            &ticket,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserRecoveryTicket {
+            id,
+            user_id: user.id,
+            ticket,
+            created_at,
+            expires_at,
+            consumed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_recovery.find_ticket",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_ticket(
+        &mut self,
+        ticket: &str,
+    ) -> Result<Option<UserRecoveryTicket>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserRecoveryTicketLookup,
+            r#"
+                SELECT user_recovery_ticket_id
+                     , user_id
+                     , ticket
+                     , created_at
+                     , expires_at
+                     , consumed_at
+                FROM user_recovery_tickets
+                WHERE ticket = $1
+            "#,
+            ticket,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_recovery.consume_ticket",
+        skip_all,
+        fields(
+            db.statement,
+            %ticket.id,
+        ),
+        err,
+    )]
+    async fn consume_ticket(
+        &mut self,
+        clock: &dyn Clock,
+        mut ticket: UserRecoveryTicket,
+    ) -> Result<UserRecoveryTicket, Self::Error> {
+        let consumed_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_recovery_tickets
+                SET consumed_at = $2
+                WHERE user_recovery_ticket_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(ticket.id),
+            consumed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        ticket.consumed_at = Some(consumed_at);
+        Ok(ticket)
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{User, UserRecoveryCode};
+use mas_storage::{user::UserRecoveryCodeRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserRecoveryCodeRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserRecoveryCodeRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserRecoveryCodeRepository<'c> {
+    /// Create a new [`PgUserRecoveryCodeRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserRecoveryCodeLookup {
+    user_recovery_code_id: Uuid,
+    user_id: Uuid,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserRecoveryCodeLookup> for UserRecoveryCode {
+    fn from(value: UserRecoveryCodeLookup) -> Self {
+        UserRecoveryCode {
+            id: value.user_recovery_code_id.into(),
+            user_id: value.user_id.into(),
+            created_at: value.created_at,
+            consumed_at: value.consumed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserRecoveryCodeRepository for PgUserRecoveryCodeRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_recovery_code.count_remaining",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn count_remaining(&mut self, user: &User) -> Result<usize, Self::Error> {
+        let count = sqlx::query_scalar!(
+            r#"
+                SELECT COUNT(*) AS "count!"
+                FROM user_recovery_codes
+                WHERE user_id = $1
+                  AND consumed_at IS NULL
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_one(&mut *self.conn)
+        .await?;
+
+        count
+            .try_into()
+            .map_err(DatabaseError::to_invalid_operation)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_recovery_code.replace",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn replace(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        code_hashes: Vec<String>,
+    ) -> Result<Vec<UserRecoveryCode>, Self::Error> {
+        sqlx::query!(
+            r#"
+                DELETE FROM user_recovery_codes
+                WHERE user_id = $1
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        let created_at = clock.now();
+        let ids: Vec<Ulid> = code_hashes
+            .iter()
+            .map(|_| Ulid::from_datetime_with_source(created_at.into(), rng))
+            .collect();
+        let uuids: Vec<Uuid> = ids.iter().copied().map(Uuid::from).collect();
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_recovery_codes
+                    (user_recovery_code_id, user_id, code_hash, created_at)
+                SELECT id, $2, code_hash, $4 FROM UNNEST($1::uuid[], $3::text[]) u(id, code_hash)
+            "#,
+            &uuids,
+            Uuid::from(user.id),
+            &code_hashes,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(ids
+            .into_iter()
+            .map(|id| UserRecoveryCode {
+                id,
+                user_id: user.id,
+                created_at,
+                consumed_at: None,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(
+        name = "db.user_recovery_code.consume",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user_recovery_code.id,
+        ),
+        err,
+    )]
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        code_hash: &str,
+    ) -> Result<Option<UserRecoveryCode>, Self::Error> {
+        let consumed_at = clock.now();
+
+        // Checking and consuming the code in the same statement makes sure it
+        // can't be used twice by concurrent requests
+        let res = sqlx::query_as!(
+            UserRecoveryCodeLookup,
+            r#"
+                UPDATE user_recovery_codes
+                SET consumed_at = $3
+                WHERE user_id = $1
+                  AND code_hash = $2
+                  AND consumed_at IS NULL
+                RETURNING user_recovery_code_id
+                        , user_id
+                        , created_at
+                        , consumed_at
+            "#,
+            Uuid::from(user.id),
+            code_hash,
+            consumed_at,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else {
+            return Ok(None);
+        };
+
+        let code = UserRecoveryCode::from(res);
+        tracing::Span::current().record("user_recovery_code.id", tracing::field::display(code.id));
+
+        Ok(Some(code))
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2022, 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::UserRegistration;
+use mas_storage::{user::UserRegistrationRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`UserRegistrationRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserRegistrationRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserRegistrationRepository<'c> {
+    /// Create a new [`PgUserRegistrationRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserRegistrationLookup {
+    user_registration_id: Uuid,
+    email: String,
+    code: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    verified_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl From<UserRegistrationLookup> for UserRegistration {
+    fn from(value: UserRegistrationLookup) -> Self {
+        UserRegistration {
+            id: value.user_registration_id.into(),
+            email: value.email,
+            code: value.code,
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            verified_at: value.verified_at,
+            completed_at: value.completed_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> UserRegistrationRepository for PgUserRegistrationRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_registration.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            user_registration.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserRegistration>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserRegistrationLookup,
+            r#"
+                SELECT user_registration_id
+                     , email
+                     , code
+                     , created_at
+                     , expires_at
+                     , verified_at
+                     , completed_at
+                FROM user_registrations
+                WHERE user_registration_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_registration.add",
+        skip_all,
+        fields(
+            db.statement,
+            user_registration.id,
+            user_registration.email = email,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        email: String,
+        code: String,
+        max_age: Duration,
+    ) -> Result<UserRegistration, Self::Error> {
+        let created_at = clock.now();
+        let expires_at = created_at + max_age;
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("user_registration.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_registrations
+                    (user_registration_id, email, code, created_at, expires_at)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            &email,
+            &code,
+            created_at,
+            expires_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserRegistration {
+            id,
+            email,
+            code,
+            created_at,
+            expires_at,
+            verified_at: None,
+            completed_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.user_registration.verify",
+        skip_all,
+        fields(
+            db.statement,
+            %user_registration.id,
+        ),
+        err,
+    )]
+    async fn verify(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error> {
+        let verified_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_registrations
+                SET verified_at = $2
+                WHERE user_registration_id = $1
+            "#,
+            Uuid::from(user_registration.id),
+            verified_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_registration.verified_at = Some(verified_at);
+        Ok(user_registration)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_registration.complete",
+        skip_all,
+        fields(
+            db.statement,
+            %user_registration.id,
+        ),
+        err,
+    )]
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        mut user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error> {
+        let completed_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_registrations
+                SET completed_at = $2
+                WHERE user_registration_id = $1
+                  AND completed_at IS NULL
+            "#,
+            Uuid::from(user_registration.id),
+            completed_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        user_registration.completed_at = Some(completed_at);
+        Ok(user_registration)
+    }
+}
@@ -18,7 +18,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{
     Authentication, AuthenticationMethod, BrowserSession, Password,
-    UpstreamOAuthAuthorizationSession, User,
+    UpstreamOAuthAuthorizationSession, User, UserLoginLink, UserRecoveryCode, WebauthnCredential,
 };
 use mas_storage::{user::BrowserSessionRepository, Clock, Page, Pagination};
 use rand::RngCore;
@@ -64,6 +64,12 @@ struct SessionLookup {
     user_created_at: DateTime<Utc>,
     user_locked_at: Option<DateTime<Utc>>,
     user_can_request_admin: bool,
+    user_is_service_account: bool,
+    user_locale: Option<String>,
+    user_password_reset_required_at: Option<DateTime<Utc>>,
+    user_deleted_at: Option<DateTime<Utc>>,
+    user_purged_at: Option<DateTime<Utc>>,
+    user_compat_password_deprecated_at: Option<DateTime<Utc>>,
 }
 
 impl TryFrom<SessionLookup> for BrowserSession {
@@ -79,6 +85,12 @@ impl TryFrom<SessionLookup> for BrowserSession {
             created_at: value.user_created_at,
             locked_at: value.user_locked_at,
             can_request_admin: value.user_can_request_admin,
+            is_service_account: value.user_is_service_account,
+            locale: value.user_locale,
+            password_reset_required_at: value.user_password_reset_required_at,
+            deleted_at: value.user_deleted_at,
+            purged_at: value.user_purged_at,
+            compat_password_deprecated_at: value.user_compat_password_deprecated_at,
         };
 
         Ok(BrowserSession {
@@ -98,6 +110,9 @@ struct AuthenticationLookup {
     created_at: DateTime<Utc>,
     user_password_id: Option<Uuid>,
     upstream_oauth_authorization_session_id: Option<Uuid>,
+    webauthn_credential_id: Option<Uuid>,
+    user_recovery_code_id: Option<Uuid>,
+    user_login_link_id: Option<Uuid>,
 }
 
 impl TryFrom<AuthenticationLookup> for Authentication {
@@ -110,12 +125,32 @@ impl TryFrom<AuthenticationLookup> for Authentication {
             value
                 .upstream_oauth_authorization_session_id
                 .map(Into::into),
+            value.webauthn_credential_id.map(Into::into),
+            value.user_recovery_code_id.map(Into::into),
+            value.user_login_link_id.map(Into::into),
         ) {
-            (Some(user_password_id), None) => AuthenticationMethod::Password { user_password_id },
-            (None, Some(upstream_oauth2_session_id)) => AuthenticationMethod::UpstreamOAuth2 {
-                upstream_oauth2_session_id,
-            },
-            (None, None) => AuthenticationMethod::Unknown,
+            (Some(user_password_id), None, None, None, None) => {
+                AuthenticationMethod::Password { user_password_id }
+            }
+            (None, Some(upstream_oauth2_session_id), None, None, None) => {
+                AuthenticationMethod::UpstreamOAuth2 {
+                    upstream_oauth2_session_id,
+                }
+            }
+            (None, None, Some(webauthn_credential_id), None, None) => {
+                AuthenticationMethod::Webauthn {
+                    webauthn_credential_id,
+                }
+            }
+            (None, None, None, Some(user_recovery_code_id), None) => {
+                AuthenticationMethod::RecoveryCode {
+                    user_recovery_code_id,
+                }
+            }
+            (None, None, None, None, Some(user_login_link_id)) => {
+                AuthenticationMethod::LoginLink { user_login_link_id }
+            }
+            (None, None, None, None, None) => AuthenticationMethod::Unknown,
             _ => {
                 return Err(DatabaseInconsistencyError::on("user_session_authentications").row(id));
             }
@@ -158,6 +193,12 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                      , u.created_at            AS "user_created_at"
                      , u.locked_at             AS "user_locked_at"
                      , u.can_request_admin     AS "user_can_request_admin"
+                     , u.is_service_account    AS "user_is_service_account"
+                     , u.locale                AS "user_locale"
+                     , u.password_reset_required_at AS "user_password_reset_required_at"
+                     , u.deleted_at            AS "user_deleted_at"
+                     , u.purged_at             AS "user_purged_at"
+                     , u.compat_password_deprecated_at AS "user_compat_password_deprecated_at"
                 FROM user_sessions s
                 INNER JOIN users u
                     USING (user_id)
@@ -320,6 +361,30 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                 Expr::col((Users::Table, Users::CanRequestAdmin)),
                 SessionLookupIden::UserCanRequestAdmin,
             )
+            .expr_as(
+                Expr::col((Users::Table, Users::IsServiceAccount)),
+                SessionLookupIden::UserIsServiceAccount,
+            )
+            .expr_as(
+                Expr::col((Users::Table, Users::Locale)),
+                SessionLookupIden::UserLocale,
+            )
+            .expr_as(
+                Expr::col((Users::Table, Users::PasswordResetRequiredAt)),
+                SessionLookupIden::UserPasswordResetRequiredAt,
+            )
+            .expr_as(
+                Expr::col((Users::Table, Users::DeletedAt)),
+                SessionLookupIden::UserDeletedAt,
+            )
+            .expr_as(
+                Expr::col((Users::Table, Users::PurgedAt)),
+                SessionLookupIden::UserPurgedAt,
+            )
+            .expr_as(
+                Expr::col((Users::Table, Users::CompatPasswordDeprecatedAt)),
+                SessionLookupIden::UserCompatPasswordDeprecatedAt,
+            )
             .from(UserSessions::Table)
             .inner_join(
                 Users::Table,
@@ -338,6 +403,12 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                     Expr::col((UserSessions::Table, UserSessions::FinishedAt)).is_not_null()
                 }
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((UserSessions::Table, UserSessions::LastActiveAt)).gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((UserSessions::Table, UserSessions::LastActiveAt)).lt(last_active_before)
+            }))
             .generate_pagination(
                 (UserSessions::Table, UserSessions::UserSessionId),
                 pagination,
@@ -381,6 +452,12 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                     Expr::col((UserSessions::Table, UserSessions::FinishedAt)).is_not_null()
                 }
             }))
+            .and_where_option(filter.last_active_after().map(|last_active_after| {
+                Expr::col((UserSessions::Table, UserSessions::LastActiveAt)).gt(last_active_after)
+            }))
+            .and_where_option(filter.last_active_before().map(|last_active_before| {
+                Expr::col((UserSessions::Table, UserSessions::LastActiveAt)).lt(last_active_before)
+            }))
             .build_sqlx(PostgresQueryBuilder);
 
         let count: i64 = sqlx::query_scalar_with(&sql, arguments)
@@ -491,6 +568,153 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
         })
     }
 
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_webauthn",
+        skip_all,
+        fields(
+            db.statement,
+            %user_session.id,
+            %webauthn_credential.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_webauthn(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        webauthn_credential: &WebauthnCredential,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, webauthn_credential_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(webauthn_credential.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::Webauthn {
+                webauthn_credential_id: webauthn_credential.id,
+            },
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_recovery_code",
+        skip_all,
+        fields(
+            db.statement,
+            %user_session.id,
+            %user_recovery_code.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_recovery_code: &UserRecoveryCode,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_recovery_code_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_recovery_code.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::RecoveryCode {
+                user_recovery_code_id: user_recovery_code.id,
+            },
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.browser_session.authenticate_with_login_link",
+        skip_all,
+        fields(
+            db.statement,
+            %user_session.id,
+            %user_login_link.id,
+            user_session_authentication.id,
+        ),
+        err,
+    )]
+    async fn authenticate_with_login_link(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_login_link: &UserLoginLink,
+    ) -> Result<Authentication, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record(
+            "user_session_authentication.id",
+            tracing::field::display(id),
+        );
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_session_authentications
+                    (user_session_authentication_id, user_session_id, created_at, user_login_link_id)
+                VALUES ($1, $2, $3, $4)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_session.id),
+            created_at,
+            Uuid::from(user_login_link.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(Authentication {
+            id,
+            created_at,
+            authentication_method: AuthenticationMethod::LoginLink {
+                user_login_link_id: user_login_link.id,
+            },
+        })
+    }
+
     #[tracing::instrument(
         name = "db.browser_session.get_last_authentication",
         skip_all,
@@ -511,6 +735,9 @@ impl<'c> BrowserSessionRepository for PgBrowserSessionRepository<'c> {
                      , created_at
                      , user_password_id
                      , upstream_oauth_authorization_session_id
+                     , webauthn_credential_id
+                     , user_recovery_code_id
+                     , user_login_link_id
                 FROM user_session_authentications
                 WHERE user_session_id = $1
                 ORDER BY created_at DESC
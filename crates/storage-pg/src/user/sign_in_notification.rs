@@ -0,0 +1,287 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{BrowserSession, CompatSession, SignInSession, UserSignInNotification};
+use mas_storage::{user::UserSignInNotificationRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError, DatabaseInconsistencyError};
+
+/// An implementation of [`UserSignInNotificationRepository`] for a PostgreSQL
+/// connection
+pub struct PgUserSignInNotificationRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserSignInNotificationRepository<'c> {
+    /// Create a new [`PgUserSignInNotificationRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserSignInNotificationLookup {
+    user_sign_in_notification_id: Uuid,
+    user_id: Uuid,
+    user_session_id: Option<Uuid>,
+    compat_session_id: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    acknowledged_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<UserSignInNotificationLookup> for UserSignInNotification {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: UserSignInNotificationLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.user_sign_in_notification_id);
+        let session = match (value.user_session_id, value.compat_session_id) {
+            (Some(user_session_id), None) => SignInSession::Browser(user_session_id.into()),
+            (None, Some(compat_session_id)) => SignInSession::Compat(compat_session_id.into()),
+            _ => return Err(DatabaseInconsistencyError::on("user_sign_in_notifications").row(id)),
+        };
+
+        Ok(UserSignInNotification {
+            id,
+            user_id: value.user_id.into(),
+            session,
+            created_at: value.created_at,
+            acknowledged_at: value.acknowledged_at,
+        })
+    }
+}
+
+impl<'c> PgUserSignInNotificationRepository<'c> {
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_id: Ulid,
+        session: SignInSession,
+    ) -> Result<UserSignInNotification, DatabaseError> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current()
+            .record("user_sign_in_notification.id", tracing::field::display(id));
+
+        let (user_session_id, compat_session_id) = match session {
+            SignInSession::Browser(id) => (Some(Uuid::from(id)), None),
+            SignInSession::Compat(id) => (None, Some(Uuid::from(id))),
+        };
+
+        sqlx::query!(
+            r#"
+                INSERT INTO user_sign_in_notifications
+                    ( user_sign_in_notification_id
+                    , user_id
+                    , user_session_id
+                    , compat_session_id
+                    , created_at
+                    )
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user_id),
+            user_session_id,
+            compat_session_id,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(UserSignInNotification {
+            id,
+            user_id,
+            session,
+            created_at,
+            acknowledged_at: None,
+        })
+    }
+}
+
+#[async_trait]
+impl<'c> UserSignInNotificationRepository for PgUserSignInNotificationRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_sign_in_notification.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            user_sign_in_notification.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserSignInNotification>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserSignInNotificationLookup,
+            r#"
+                SELECT user_sign_in_notification_id
+                     , user_id
+                     , user_session_id
+                     , compat_session_id
+                     , created_at
+                     , acknowledged_at
+                FROM user_sign_in_notifications
+                WHERE user_sign_in_notification_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_sign_in_notification.add_for_browser_session",
+        skip_all,
+        fields(
+            db.statement,
+            user_sign_in_notification.id,
+            %browser_session.id,
+            %browser_session.user.id,
+        ),
+        err,
+    )]
+    async fn add_for_browser_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        browser_session: &BrowserSession,
+    ) -> Result<UserSignInNotification, Self::Error> {
+        self.add(
+            rng,
+            clock,
+            browser_session.user.id,
+            SignInSession::Browser(browser_session.id),
+        )
+        .await
+    }
+
+    #[tracing::instrument(
+        name = "db.user_sign_in_notification.add_for_compat_session",
+        skip_all,
+        fields(
+            db.statement,
+            user_sign_in_notification.id,
+            %compat_session.id,
+            %compat_session.user_id,
+        ),
+        err,
+    )]
+    async fn add_for_compat_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        compat_session: &CompatSession,
+    ) -> Result<UserSignInNotification, Self::Error> {
+        self.add(
+            rng,
+            clock,
+            compat_session.user_id,
+            SignInSession::Compat(compat_session.id),
+        )
+        .await
+    }
+
+    #[tracing::instrument(
+        name = "db.user_sign_in_notification.list_unacknowledged",
+        skip_all,
+        fields(
+            db.statement,
+            %browser_session.id,
+            %browser_session.user.id,
+        ),
+        err,
+    )]
+    async fn list_unacknowledged(
+        &mut self,
+        browser_session: &BrowserSession,
+    ) -> Result<Vec<UserSignInNotification>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserSignInNotificationLookup,
+            r#"
+                SELECT user_sign_in_notification_id
+                     , user_id
+                     , user_session_id
+                     , compat_session_id
+                     , created_at
+                     , acknowledged_at
+                FROM user_sign_in_notifications
+                WHERE user_id = $1
+                  AND acknowledged_at IS NULL
+                  AND created_at >= $2
+                  AND user_session_id IS DISTINCT FROM $3
+                ORDER BY user_sign_in_notification_id ASC
+            "#,
+            Uuid::from(browser_session.user.id),
+            browser_session.created_at,
+            Uuid::from(browser_session.id),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        let notifications = res
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?;
+
+        Ok(notifications)
+    }
+
+    #[tracing::instrument(
+        name = "db.user_sign_in_notification.acknowledge",
+        skip_all,
+        fields(
+            db.statement,
+            %notification.id,
+        ),
+        err,
+    )]
+    async fn acknowledge(
+        &mut self,
+        clock: &dyn Clock,
+        mut notification: UserSignInNotification,
+    ) -> Result<UserSignInNotification, Self::Error> {
+        let acknowledged_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE user_sign_in_notifications
+                SET acknowledged_at = $2
+                WHERE user_sign_in_notification_id = $1
+            "#,
+            Uuid::from(notification.id),
+            acknowledged_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        notification.acknowledged_at = Some(acknowledged_at);
+        Ok(notification)
+    }
+}
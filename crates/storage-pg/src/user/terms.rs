@@ -0,0 +1,175 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{TermsOfService, User, UserTermsAcceptance};
+use mas_storage::{user::UserTermsRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError, DatabaseInconsistencyError};
+
+/// An implementation of [`UserTermsRepository`] for a PostgreSQL connection
+pub struct PgUserTermsRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgUserTermsRepository<'c> {
+    /// Create a new [`PgUserTermsRepository`] from an active PostgreSQL
+    /// connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct UserTermsLookup {
+    user_terms_id: Uuid,
+    user_id: Uuid,
+    version: String,
+    url: String,
+    accepted_at: DateTime<Utc>,
+}
+
+impl TryFrom<UserTermsLookup> for UserTermsAcceptance {
+    type Error = DatabaseInconsistencyError;
+
+    fn try_from(value: UserTermsLookup) -> Result<Self, Self::Error> {
+        let id = Ulid::from(value.user_terms_id);
+        let url = value.url.parse().map_err(|e| {
+            DatabaseInconsistencyError::on("user_terms")
+                .column("url")
+                .row(id)
+                .source(e)
+        })?;
+
+        Ok(UserTermsAcceptance {
+            id,
+            user_id: value.user_id.into(),
+            version: value.version,
+            url,
+            accepted_at: value.accepted_at,
+        })
+    }
+}
+
+#[async_trait]
+impl<'c> UserTermsRepository for PgUserTermsRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.user_terms.find",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user_terms.version = version,
+        ),
+        err,
+    )]
+    async fn find(
+        &mut self,
+        user: &User,
+        version: &str,
+    ) -> Result<Option<UserTermsAcceptance>, Self::Error> {
+        let res = sqlx::query_as!(
+            UserTermsLookup,
+            r#"
+                SELECT user_terms_id
+                     , user_id
+                     , version
+                     , url
+                     , accepted_at
+                FROM user_terms
+                WHERE user_id = $1
+                  AND version = $2
+            "#,
+            Uuid::from(user.id),
+            version,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        let Some(res) = res else { return Ok(None) };
+
+        Ok(Some(res.try_into()?))
+    }
+
+    #[tracing::instrument(
+        name = "db.user_terms.accept",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            user_terms.id,
+            user_terms.version = terms.version,
+        ),
+        err,
+    )]
+    async fn accept(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        terms: &TermsOfService,
+    ) -> Result<UserTermsAcceptance, Self::Error> {
+        let accepted_at = clock.now();
+        let id = Ulid::from_datetime_with_source(accepted_at.into(), rng);
+        tracing::Span::current().record("user_terms.id", tracing::field::display(id));
+
+        let res = sqlx::query!(
+            r#"
+                INSERT INTO user_terms
+                    ( user_terms_id
+                    , user_id
+                    , version
+                    , url
+                    , accepted_at
+                    )
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (user_id, version) DO NOTHING
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            &terms.version,
+            terms.url.as_str(),
+            accepted_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            // This version was already accepted, keep the first acceptance
+            return self
+                .find(user, &terms.version)
+                .await?
+                .ok_or(DatabaseError::RowsAffected {
+                    expected: 1,
+                    actual: 0,
+                });
+        }
+
+        Ok(UserTermsAcceptance {
+            id,
+            user_id: user.id,
+            version: terms.version.clone(),
+            url: terms.url.clone(),
+            accepted_at,
+        })
+    }
+}
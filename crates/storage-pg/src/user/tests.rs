@@ -13,13 +13,17 @@
 // limitations under the License.
 
 use chrono::Duration;
+use mas_data_model::{AuthenticationMethod, SignInSession, TermsOfService, UserRecoveryCode};
 use mas_storage::{
     clock::MockClock,
     user::{
-        BrowserSessionFilter, BrowserSessionRepository, UserEmailFilter, UserEmailRepository,
-        UserPasswordRepository, UserRepository,
+        BrowserSessionFilter, BrowserSessionRepository, UserDataExportRepository, UserEmailFilter,
+        UserEmailInUseError, UserEmailRepository, UserFilter, UserGroupRepository,
+        UserLoginLinkRepository, UserPasswordRepository, UserRecoveryCodeRepository,
+        UserRecoveryRepository, UserRegistrationRepository, UserRepository,
+        UserSignInNotificationRepository, UserTermsRepository, WebauthnCredentialRepository,
     },
-    Pagination, Repository, RepositoryAccess,
+    Clock, Pagination, Repository, RepositoryAccess,
 };
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
@@ -95,6 +99,58 @@ async fn test_user_repo(pool: PgPool) {
     let user = repo.user().unlock(user).await.unwrap();
     assert!(user.is_valid());
 
+    // Require a password reset
+    assert!(!user.password_reset_required());
+    let user = repo
+        .user()
+        .require_password_reset(&clock, user)
+        .await
+        .unwrap();
+    assert!(user.password_reset_required());
+
+    // Check that the property is retrieved on lookup
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(user.password_reset_required());
+
+    // Clear the requirement
+    let user = repo.user().clear_password_reset(user).await.unwrap();
+    assert!(!user.password_reset_required());
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(!user.password_reset_required());
+
+    // Deprecate the compatibility password login
+    assert!(!user.compat_password_deprecated());
+    let user = repo
+        .user()
+        .deprecate_compat_password(&clock, user)
+        .await
+        .unwrap();
+    assert!(user.compat_password_deprecated());
+    let deprecated_at = user.compat_password_deprecated_at;
+
+    // Deprecating it again doesn't restart the grace period
+    clock.advance(Duration::minutes(1));
+    let user = repo
+        .user()
+        .deprecate_compat_password(&clock, user)
+        .await
+        .unwrap();
+    assert_eq!(user.compat_password_deprecated_at, deprecated_at);
+
+    // Check that the property is retrieved on lookup
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert_eq!(user.compat_password_deprecated_at, deprecated_at);
+
+    // Clear the deprecation
+    let user = repo
+        .user()
+        .clear_compat_password_deprecation(user)
+        .await
+        .unwrap();
+    assert!(!user.compat_password_deprecated());
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(!user.compat_password_deprecated());
+
     // Set the can_request_admin flag
     let user = repo.user().set_can_request_admin(user, true).await.unwrap();
     assert!(user.can_request_admin);
@@ -115,9 +171,257 @@ async fn test_user_repo(pool: PgPool) {
     let user = repo.user().lookup(user.id).await.unwrap().unwrap();
     assert!(!user.can_request_admin);
 
+    // Users are not service accounts by default
+    assert!(!user.is_service_account);
+    assert!(user.can_login_interactively());
+
+    // Mark the user as a service account
+    let user = repo.user().set_service_account(user, true).await.unwrap();
+    assert!(user.is_service_account);
+    assert!(!user.can_login_interactively());
+
+    // Check that the property is retrieved on lookup and by username
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(user.is_service_account);
+    let user = repo
+        .user()
+        .find_by_username(USERNAME)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user.is_service_account);
+
+    // Turn it back into a regular user
+    let user = repo.user().set_service_account(user, false).await.unwrap();
+    assert!(!user.is_service_account);
+
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(!user.is_service_account);
+
+    // Users don't have a preferred language by default
+    assert_eq!(user.locale, None);
+
+    // Set the preferred language
+    let user = repo
+        .user()
+        .set_locale(user, "fr-CA".to_owned())
+        .await
+        .unwrap();
+    assert_eq!(user.locale.as_deref(), Some("fr-CA"));
+
+    // Check that it is retrieved on lookup
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert_eq!(user.locale.as_deref(), Some("fr-CA"));
+
+    repo.save().await.unwrap();
+}
+
+/// Test listing, searching and counting users
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_list(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let alice = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+
+    clock.advance(Duration::minutes(1));
+    let bob = repo
+        .user()
+        .add(&mut rng, &clock, "bob".to_owned())
+        .await
+        .unwrap();
+    let bob = repo.user().lock(&clock, bob).await.unwrap();
+
+    clock.advance(Duration::minutes(1));
+    let carol = repo
+        .user()
+        .add(&mut rng, &clock, "carol".to_owned())
+        .await
+        .unwrap();
+    repo.user_email()
+        .add(&mut rng, &clock, &carol, "Bob.Fan@example.com".to_owned())
+        .await
+        .unwrap();
+
+    let all = UserFilter::new();
+    assert_eq!(repo.user().count(all).await.unwrap(), 3);
+    let page = repo.user().list(all, Pagination::first(10)).await.unwrap();
+    assert!(!page.has_next_page);
+    assert_eq!(page.edges, vec![alice.clone(), bob.clone(), carol.clone()]);
+
+    let page = repo.user().list(all, Pagination::first(2)).await.unwrap();
+    assert!(page.has_next_page);
+    assert_eq!(page.edges, vec![alice.clone(), bob.clone()]);
+
+    // Filter on the lock state
+    let locked = UserFilter::new().locked_only();
+    assert_eq!(repo.user().count(locked).await.unwrap(), 1);
+    let page = repo
+        .user()
+        .list(locked, Pagination::first(10))
+        .await
+        .unwrap();
+    assert_eq!(page.edges, vec![bob.clone()]);
+
+    let active = UserFilter::new().active_only();
+    assert_eq!(repo.user().count(active).await.unwrap(), 2);
+
+    // Search on the username and the email addresses, ignoring case
+    let search = UserFilter::new().matching("BOB");
+    assert_eq!(repo.user().count(search).await.unwrap(), 2);
+    let page = repo
+        .user()
+        .list(search, Pagination::first(10))
+        .await
+        .unwrap();
+    assert_eq!(page.edges, vec![bob.clone(), carol.clone()]);
+
+    // Wildcards are matched literally
+    let search = UserFilter::new().matching("b_b");
+    assert_eq!(repo.user().count(search).await.unwrap(), 0);
+
+    // Filter on the creation date
+    let recent = UserFilter::new().with_created_after(alice.created_at);
+    assert_eq!(repo.user().count(recent).await.unwrap(), 2);
+    let old = UserFilter::new().with_created_before(carol.created_at);
+    let page = repo.user().list(old, Pagination::first(10)).await.unwrap();
+    assert_eq!(page.edges, vec![alice, bob]);
+
     repo.save().await.unwrap();
 }
 
+/// Test the soft-deletion of users, their restoration and their purge
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_soft_deletion(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+    let user_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &user, "john@example.com".to_owned())
+        .await
+        .unwrap();
+    repo.user_email().set_as_primary(&user_email).await.unwrap();
+    let password = repo
+        .user_password()
+        .add(&mut rng, &clock, &user, 1, "hashed".to_owned(), None)
+        .await
+        .unwrap();
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_password(&mut rng, &clock, &session, &password)
+        .await
+        .unwrap();
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+
+    // Nothing to purge yet
+    assert!(repo
+        .user()
+        .list_purgeable(clock.now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Deleting the user makes it invalid
+    assert!(!user.is_deleted());
+    let user = repo.user().soft_delete(&clock, user).await.unwrap();
+    assert!(user.is_deleted());
+    assert!(!user.is_valid());
+
+    // Check that the property is retrieved on lookup
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(user.is_deleted());
+
+    // Deleting a second time should not fail
+    let user = repo.user().soft_delete(&clock, user).await.unwrap();
+    assert!(user.is_deleted());
+
+    // The user is only purgeable once it was deleted before the given date
+    assert!(repo
+        .user()
+        .list_purgeable(clock.now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+    clock.advance(Duration::minutes(1));
+    assert_eq!(
+        repo.user()
+            .list_purgeable(clock.now(), 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Restoring the user makes it valid again
+    let user = repo.user().restore(user).await.unwrap();
+    assert!(!user.is_deleted());
+    assert!(user.is_valid());
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(user.is_valid());
+    assert!(repo
+        .user()
+        .list_purgeable(clock.now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Delete it again, and purge it
+    let user = repo.user().soft_delete(&clock, user).await.unwrap();
+    clock.advance(Duration::minutes(1));
+    let users = repo.user().list_purgeable(clock.now(), 10).await.unwrap();
+    assert_eq!(users, vec![user.clone()]);
+
+    let user = repo.user().purge(&clock, user).await.unwrap();
+    assert!(user.is_purged());
+    assert!(user.primary_user_email_id.is_none());
+
+    // Its personal data is gone, but the username stays reserved
+    let user = repo.user().lookup(user.id).await.unwrap().unwrap();
+    assert!(user.is_purged());
+    assert!(user.primary_user_email_id.is_none());
+    assert!(repo.user().exists("john").await.unwrap());
+    assert_eq!(
+        repo.user_email()
+            .count(UserEmailFilter::new().for_user(&user))
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(repo.user_password().active(&user).await.unwrap().is_none());
+    assert_eq!(
+        repo.browser_session()
+            .count(BrowserSessionFilter::new().for_user(&user).active_only())
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(repo
+        .user()
+        .list_purgeable(clock.now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // A purged user can't be restored
+    assert!(repo.user().restore(user).await.is_err());
+}
+
 /// Test the user email repository, by trying out most of its methods
 #[sqlx::test(migrator = "crate::MIGRATOR")]
 async fn test_user_email_repo(pool: PgPool) {
@@ -242,6 +546,14 @@ async fn test_user_email_repo(pool: PgPool) {
     assert_eq!(repo.user_email().count(pending).await.unwrap(), 0);
     assert_eq!(repo.user_email().count(verified).await.unwrap(), 1);
 
+    // Look for the verified address across all users
+    let by_email = UserEmailFilter::new().for_email(EMAIL).verified_only();
+    assert_eq!(repo.user_email().count(by_email).await.unwrap(), 1);
+    let by_email = UserEmailFilter::new()
+        .for_email("other@example.com")
+        .verified_only();
+    assert_eq!(repo.user_email().count(by_email).await.unwrap(), 0);
+
     // Reload the user_email
     let user_email = repo
         .user_email()
@@ -346,6 +658,51 @@ async fn test_user_email_repo(pool: PgPool) {
     repo.save().await.unwrap();
 }
 
+/// Test that a verified email address can only belong to one user
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_email_verified_unique(pool: PgPool) {
+    const EMAIL: &str = "john@example.com";
+
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let john = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+    let alice = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+
+    // Both users can add the same address
+    let john_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &john, EMAIL.to_owned())
+        .await
+        .unwrap();
+    let alice_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &alice, EMAIL.to_owned())
+        .await
+        .unwrap();
+
+    // But only one of them can verify it
+    repo.user_email()
+        .mark_as_verified(&clock, john_email)
+        .await
+        .unwrap();
+    let error = repo
+        .user_email()
+        .mark_as_verified(&clock, alice_email)
+        .await
+        .unwrap_err();
+    assert!(UserEmailInUseError::is_cause_of(&error));
+}
+
 /// Test the user password repository implementation.
 #[sqlx::test(migrator = "crate::MIGRATOR")]
 async fn test_user_password_repo(pool: PgPool) {
@@ -365,6 +722,12 @@ async fn test_user_password_repo(pool: PgPool) {
 
     // User should have no active password
     assert!(repo.user_password().active(&user).await.unwrap().is_none());
+    assert!(repo
+        .user_password()
+        .count_active_by_version()
+        .await
+        .unwrap()
+        .is_empty());
 
     // Insert a first password
     let first_password = repo
@@ -426,6 +789,112 @@ async fn test_user_password_repo(pool: PgPool) {
         Some(first_password.id)
     );
 
+    // Only the active password is counted
+    assert_eq!(
+        repo.user_password()
+            .count_active_by_version()
+            .await
+            .unwrap(),
+        vec![(2, 1)]
+    );
+
+    repo.save().await.unwrap();
+}
+
+/// Test the user registration repository
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_registration_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let registration = repo
+        .user_registration()
+        .add(
+            &mut rng,
+            &clock,
+            "alice@example.com".to_owned(),
+            "123456".to_owned(),
+            Duration::hours(1),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(registration.email, "alice@example.com");
+    assert_eq!(registration.code, "123456");
+    assert!(registration.is_valid(clock.now()));
+    assert!(!registration.is_verified());
+
+    // Lookup the registration
+    let lookup = repo
+        .user_registration()
+        .lookup(registration.id)
+        .await
+        .unwrap()
+        .expect("registration should be found");
+    assert_eq!(lookup, registration);
+
+    // Verify the email address
+    clock.advance(Duration::minutes(1));
+    let registration = repo
+        .user_registration()
+        .verify(&clock, registration)
+        .await
+        .unwrap();
+    assert_eq!(registration.verified_at, Some(clock.now()));
+
+    let lookup = repo
+        .user_registration()
+        .lookup(registration.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(lookup.is_verified());
+
+    // Complete the registration
+    let registration = repo
+        .user_registration()
+        .complete(&clock, registration)
+        .await
+        .unwrap();
+    assert!(!registration.is_valid(clock.now()));
+
+    // It can't be completed twice
+    let lookup = repo
+        .user_registration()
+        .lookup(registration.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(repo
+        .user_registration()
+        .complete(&clock, lookup)
+        .await
+        .is_err());
+
+    // Registrations expire
+    let other = repo
+        .user_registration()
+        .add(
+            &mut rng,
+            &clock,
+            "bob@example.com".to_owned(),
+            "654321".to_owned(),
+            Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    clock.advance(Duration::hours(2));
+    assert!(!other.is_valid(clock.now()));
+
+    // Unknown registrations are not found
+    assert!(repo
+        .user_registration()
+        .lookup(ulid::Ulid::nil())
+        .await
+        .unwrap()
+        .is_none());
+
     repo.save().await.unwrap();
 }
 
@@ -516,4 +985,827 @@ async fn test_user_session(pool: PgPool) {
     assert_eq!(session_lookup.user.id, user.id);
     // This time the session is finished
     assert!(session_lookup.finished_at.is_some());
+
+    // Filter the sessions on their last activity. Sessions which were never
+    // active don't match either bound
+    let recently_active = all.with_last_active_after(clock.now() - Duration::hours(1));
+    let inactive = all.with_last_active_before(clock.now() - Duration::hours(1));
+    assert_eq!(
+        repo.browser_session().count(recently_active).await.unwrap(),
+        0
+    );
+    assert_eq!(repo.browser_session().count(inactive).await.unwrap(), 0);
+
+    repo.browser_session()
+        .record_batch_activity(vec![(session.id, clock.now(), None)])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        repo.browser_session().count(recently_active).await.unwrap(),
+        1
+    );
+    assert_eq!(repo.browser_session().count(inactive).await.unwrap(), 0);
+    let session_list = repo
+        .browser_session()
+        .list(recently_active, Pagination::first(10))
+        .await
+        .unwrap();
+    assert_eq!(session_list.edges.len(), 1);
+    assert_eq!(session_list.edges[0].id, session.id);
+
+    clock.advance(Duration::hours(2));
+    let inactive = all.with_last_active_before(clock.now() - Duration::hours(1));
+    assert_eq!(repo.browser_session().count(inactive).await.unwrap(), 1);
+}
+
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_sign_in_notification_repo(pool: PgPool) {
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+
+    // The user is signed in a first browser
+    let first_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+
+    clock.advance(Duration::minutes(1));
+
+    // Then signs in a second browser
+    let second_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+
+    let notification = repo
+        .user_sign_in_notification()
+        .add_for_browser_session(&mut rng, &clock, &second_session)
+        .await
+        .unwrap();
+    assert_eq!(notification.user_id, user.id);
+    assert_eq!(
+        notification.session,
+        SignInSession::Browser(second_session.id)
+    );
+    assert!(!notification.is_acknowledged());
+
+    let lookup = repo
+        .user_sign_in_notification()
+        .lookup(notification.id)
+        .await
+        .unwrap()
+        .expect("notification not found");
+    assert_eq!(lookup, notification);
+
+    // The notification shows up in the first session, but not in the one which
+    // triggered it
+    let list = repo
+        .user_sign_in_notification()
+        .list_unacknowledged(&first_session)
+        .await
+        .unwrap();
+    assert_eq!(list, vec![notification.clone()]);
+
+    let list = repo
+        .user_sign_in_notification()
+        .list_unacknowledged(&second_session)
+        .await
+        .unwrap();
+    assert!(list.is_empty());
+
+    // Sessions started after the sign-in don't see it either
+    clock.advance(Duration::minutes(1));
+    let third_session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    let list = repo
+        .user_sign_in_notification()
+        .list_unacknowledged(&third_session)
+        .await
+        .unwrap();
+    assert!(list.is_empty());
+
+    // Once acknowledged, it doesn't show up anymore
+    let notification = repo
+        .user_sign_in_notification()
+        .acknowledge(&clock, notification)
+        .await
+        .unwrap();
+    assert!(notification.is_acknowledged());
+
+    let list = repo
+        .user_sign_in_notification()
+        .list_unacknowledged(&first_session)
+        .await
+        .unwrap();
+    assert!(list.is_empty());
+
+    // Unknown notifications are not found
+    assert!(repo
+        .user_sign_in_notification()
+        .lookup(ulid::Ulid::nil())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Test the user recovery repository, by issuing and consuming tickets
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_recovery_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    // Unknown tickets are not found
+    assert!(repo
+        .user_recovery()
+        .find_ticket("unknown")
+        .await
+        .unwrap()
+        .is_none());
+
+    let ticket = repo
+        .user_recovery()
+        .add_ticket(
+            &mut rng,
+            &clock,
+            &user,
+            "secret".to_owned(),
+            Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    assert_eq!(ticket.user_id, user.id);
+    assert!(ticket.is_valid(clock.now()));
+
+    let found = repo
+        .user_recovery()
+        .find_ticket("secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found, ticket);
+
+    // Tickets expire
+    clock.advance(Duration::hours(2));
+    assert!(!found.is_valid(clock.now()));
+
+    // Consuming a ticket makes it invalid, and can only happen once
+    let ticket = repo
+        .user_recovery()
+        .consume_ticket(&clock, found)
+        .await
+        .unwrap();
+    assert!(ticket.consumed_at.is_some());
+
+    let found = repo
+        .user_recovery()
+        .find_ticket("secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(found.consumed_at.is_some());
+
+    let mut stale = found.clone();
+    stale.consumed_at = None;
+    assert!(repo
+        .user_recovery()
+        .consume_ticket(&clock, stale)
+        .await
+        .is_err());
+}
+
+/// Test the login link repository, and authenticating browser sessions with
+/// those links
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_login_link_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+    let user_email = repo
+        .user_email()
+        .add(&mut rng, &clock, &user, "john@example.com".to_owned())
+        .await
+        .unwrap();
+
+    // Unknown links are not found
+    assert!(repo
+        .user_login_link()
+        .find_by_token("unknown")
+        .await
+        .unwrap()
+        .is_none());
+
+    let action = serde_json::json!({
+        "kind": "continue_authorization_grant",
+        "id": "01FSHN9AG0MZAA6S4AF7CTV32E",
+    });
+    let link = repo
+        .user_login_link()
+        .add(
+            &mut rng,
+            &clock,
+            &user_email,
+            "secret".to_owned(),
+            Some(action.clone()),
+            Duration::minutes(15),
+        )
+        .await
+        .unwrap();
+    assert_eq!(link.user_id, user.id);
+    assert_eq!(link.user_email_id, user_email.id);
+    assert!(link.is_valid(clock.now()));
+
+    let found = repo
+        .user_login_link()
+        .find_by_token("secret")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found, link);
+    assert_eq!(found.post_auth_action, Some(action));
+
+    let found = repo
+        .user_login_link()
+        .lookup(link.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found, link);
+
+    // Links expire
+    clock.advance(Duration::minutes(20));
+    assert!(!found.is_valid(clock.now()));
+
+    // Consuming a link makes it invalid, and can only happen once
+    let link = repo.user_login_link().consume(&clock, found).await.unwrap();
+    assert!(link.consumed_at.is_some());
+
+    let mut stale = link.clone();
+    stale.consumed_at = None;
+    assert!(repo.user_login_link().consume(&clock, stale).await.is_err());
+
+    // Authenticate a browser session with the consumed link
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_login_link(&mut rng, &clock, &session, &link)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::LoginLink {
+            user_login_link_id: link.id
+        }
+    );
+
+    // Removing the email address revokes the links sent to it
+    repo.user_email().remove(user_email).await.unwrap();
+    assert!(repo
+        .user_login_link()
+        .find_by_token("secret")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Test the WebAuthn credential repository, and authenticating browser
+/// sessions with those credentials
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_webauthn_credential_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    assert!(repo
+        .webauthn_credential()
+        .all(&user)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let credential = repo
+        .webauthn_credential()
+        .add(
+            &mut rng,
+            &clock,
+            &user,
+            "Laptop".to_owned(),
+            "Y3JlZGVudGlhbA".to_owned(),
+            serde_json::json!({ "counter": 0 }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(credential.user_id, user.id);
+    assert!(credential.last_used_at.is_none());
+    repo.save().await.unwrap();
+
+    // The same credential can't be registered twice
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    assert!(repo
+        .webauthn_credential()
+        .add(
+            &mut rng,
+            &clock,
+            &user,
+            "Laptop again".to_owned(),
+            "Y3JlZGVudGlhbA".to_owned(),
+            serde_json::json!({ "counter": 0 }),
+        )
+        .await
+        .is_err());
+
+    // The failed insert aborted the transaction, start a new one
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+
+    let found = repo
+        .webauthn_credential()
+        .find_by_credential_id("Y3JlZGVudGlhbA")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found, credential);
+
+    let found = repo
+        .webauthn_credential()
+        .lookup(credential.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found, credential);
+
+    // Using the credential updates its counters
+    clock.advance(Duration::minutes(1));
+    let credential = repo
+        .webauthn_credential()
+        .record_use(&clock, credential, serde_json::json!({ "counter": 1 }))
+        .await
+        .unwrap();
+    assert_eq!(credential.last_used_at, Some(clock.now()));
+
+    let found = repo
+        .webauthn_credential()
+        .lookup(credential.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.passkey, serde_json::json!({ "counter": 1 }));
+
+    // Authenticate a browser session with it
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_webauthn(&mut rng, &clock, &session, &credential)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Webauthn {
+            webauthn_credential_id: credential.id
+        }
+    );
+
+    let all = repo.webauthn_credential().all(&user).await.unwrap();
+    assert_eq!(all, vec![credential.clone()]);
+
+    // Removing the credential keeps the authentication, with an unknown method
+    repo.webauthn_credential().remove(credential).await.unwrap();
+    assert!(repo
+        .webauthn_credential()
+        .all(&user)
+        .await
+        .unwrap()
+        .is_empty());
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::Unknown
+    );
+}
+
+/// Test the recovery code repository, and authenticating browser sessions with
+/// those codes
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_recovery_code_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        repo.user_recovery_code()
+            .count_remaining(&user)
+            .await
+            .unwrap(),
+        0
+    );
+
+    let codes = ["aaaa-bbbb-cccc", "dddd-eeee-ffff", "gggg-hhhh-jjjj"];
+    let created = repo
+        .user_recovery_code()
+        .replace(
+            &mut rng,
+            &clock,
+            &user,
+            codes
+                .iter()
+                .map(|code| UserRecoveryCode::hash(code))
+                .collect(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 3);
+    assert!(created.iter().all(|code| code.user_id == user.id));
+    assert_eq!(
+        repo.user_recovery_code()
+            .count_remaining(&user)
+            .await
+            .unwrap(),
+        3
+    );
+
+    // An unknown code can't be consumed
+    assert!(repo
+        .user_recovery_code()
+        .consume(&clock, &user, &UserRecoveryCode::hash("kkkk-mmmm-nnnn"))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Consume a code, typed slightly differently
+    clock.advance(Duration::minutes(1));
+    let consumed = repo
+        .user_recovery_code()
+        .consume(&clock, &user, &UserRecoveryCode::hash("AAAA BBBB CCCC"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(created.iter().any(|code| code.id == consumed.id));
+    assert_eq!(consumed.consumed_at, Some(clock.now()));
+    assert_eq!(
+        repo.user_recovery_code()
+            .count_remaining(&user)
+            .await
+            .unwrap(),
+        2
+    );
+
+    // It can't be used twice
+    assert!(repo
+        .user_recovery_code()
+        .consume(&clock, &user, &UserRecoveryCode::hash(codes[0]))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Another user can't use those codes
+    let other = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+    assert!(repo
+        .user_recovery_code()
+        .consume(&clock, &other, &UserRecoveryCode::hash(codes[1]))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Authenticate a browser session with the consumed code
+    let session = repo
+        .browser_session()
+        .add(&mut rng, &clock, &user, None)
+        .await
+        .unwrap();
+    repo.browser_session()
+        .authenticate_with_recovery_code(&mut rng, &clock, &session, &consumed)
+        .await
+        .unwrap();
+    let authentication = repo
+        .browser_session()
+        .get_last_authentication(&session)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        authentication.authentication_method,
+        AuthenticationMethod::RecoveryCode {
+            user_recovery_code_id: consumed.id
+        }
+    );
+
+    // Regenerating the codes invalidates the old ones
+    repo.user_recovery_code()
+        .replace(
+            &mut rng,
+            &clock,
+            &user,
+            vec![UserRecoveryCode::hash("pppp-qqqq-rrrr")],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        repo.user_recovery_code()
+            .count_remaining(&user)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(repo
+        .user_recovery_code()
+        .consume(&clock, &user, &UserRecoveryCode::hash(codes[1]))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_group_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+    let other = repo
+        .user()
+        .add(&mut rng, &clock, "alice".to_owned())
+        .await
+        .unwrap();
+
+    assert!(repo.user_group().list(&user).await.unwrap().is_empty());
+
+    repo.user_group()
+        .add(&clock, &user, &["staff".to_owned(), "admins".to_owned()])
+        .await
+        .unwrap();
+    repo.user_group()
+        .add(&clock, &other, &["staff".to_owned()])
+        .await
+        .unwrap();
+
+    // Groups are sorted by name
+    assert_eq!(
+        repo.user_group().list(&user).await.unwrap(),
+        vec!["admins".to_owned(), "staff".to_owned()]
+    );
+
+    // Adding a group twice is a no-op
+    repo.user_group()
+        .add(&clock, &user, &["admins".to_owned(), "devs".to_owned()])
+        .await
+        .unwrap();
+    assert_eq!(
+        repo.user_group().list(&user).await.unwrap(),
+        vec!["admins".to_owned(), "devs".to_owned(), "staff".to_owned()]
+    );
+
+    // Removing groups only affects the given user
+    repo.user_group()
+        .remove(&user, &["staff".to_owned(), "unknown".to_owned()])
+        .await
+        .unwrap();
+    assert_eq!(
+        repo.user_group().list(&user).await.unwrap(),
+        vec!["admins".to_owned(), "devs".to_owned()]
+    );
+    assert_eq!(
+        repo.user_group().list(&other).await.unwrap(),
+        vec!["staff".to_owned()]
+    );
+}
+
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_terms_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    let terms = TermsOfService {
+        version: "2023-12".to_owned(),
+        url: "https://example.com/terms/2023-12".parse().unwrap(),
+    };
+
+    let acceptance = repo.user_terms().find(&user, &terms.version).await.unwrap();
+    assert!(acceptance.is_none());
+    assert!(!terms.is_accepted(acceptance.as_ref()));
+
+    let acceptance = repo
+        .user_terms()
+        .accept(&mut rng, &clock, &user, &terms)
+        .await
+        .unwrap();
+    assert_eq!(acceptance.user_id, user.id);
+    assert_eq!(acceptance.version, terms.version);
+    assert_eq!(acceptance.url, terms.url);
+    assert_eq!(acceptance.accepted_at, clock.now());
+
+    let found = repo
+        .user_terms()
+        .find(&user, &terms.version)
+        .await
+        .unwrap()
+        .expect("acceptance not found");
+    assert_eq!(found, acceptance);
+    assert!(terms.is_accepted(Some(&found)));
+
+    // Accepting the same version again keeps the first acceptance
+    clock.advance(Duration::days(1));
+    let again = repo
+        .user_terms()
+        .accept(&mut rng, &clock, &user, &terms)
+        .await
+        .unwrap();
+    assert_eq!(again, acceptance);
+
+    // A new version needs to be accepted again
+    let new_terms = TermsOfService {
+        version: "2024-06".to_owned(),
+        url: "https://example.com/terms/2024-06".parse().unwrap(),
+    };
+    let acceptance = repo
+        .user_terms()
+        .find(&user, &new_terms.version)
+        .await
+        .unwrap();
+    assert!(!new_terms.is_accepted(acceptance.as_ref()));
+}
+
+/// Test the user data export repository
+#[sqlx::test(migrator = "crate::MIGRATOR")]
+async fn test_user_data_export_repo(pool: PgPool) {
+    let mut repo = PgRepository::from_pool(&pool).await.unwrap().boxed();
+    let mut rng = ChaChaRng::seed_from_u64(42);
+    let clock = MockClock::default();
+
+    let user = repo
+        .user()
+        .add(&mut rng, &clock, "john".to_owned())
+        .await
+        .unwrap();
+
+    assert!(repo
+        .user_data_export()
+        .latest(&user)
+        .await
+        .unwrap()
+        .is_none());
+
+    let export = repo
+        .user_data_export()
+        .add(&mut rng, &clock, &user, "secret".to_owned())
+        .await
+        .unwrap();
+    assert!(export.is_pending());
+    assert!(!export.is_available(clock.now()));
+
+    let found = repo
+        .user_data_export()
+        .find_by_token("secret")
+        .await
+        .unwrap()
+        .expect("export not found");
+    assert_eq!(found, export);
+    assert!(repo
+        .user_data_export()
+        .find_by_token("other")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        repo.user_data_export().latest(&user).await.unwrap(),
+        Some(export.clone())
+    );
+
+    // There is no archive until the export is done
+    assert!(repo
+        .user_data_export()
+        .archive(&export)
+        .await
+        .unwrap()
+        .is_none());
+
+    let archive = serde_json::json!({ "username": "john" });
+    let export = repo
+        .user_data_export()
+        .complete(&clock, export, archive.clone(), Duration::days(1))
+        .await
+        .unwrap();
+    assert!(!export.is_pending());
+    assert!(export.is_available(clock.now()));
+    assert_eq!(
+        repo.user_data_export().archive(&export).await.unwrap(),
+        Some(archive)
+    );
+
+    // Completing it again fails
+    assert!(repo
+        .user_data_export()
+        .complete(
+            &clock,
+            export.clone(),
+            serde_json::json!({}),
+            Duration::days(1)
+        )
+        .await
+        .is_err());
+
+    // Once expired, the archive gets cleaned up
+    assert_eq!(
+        repo.user_data_export()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap(),
+        0
+    );
+    clock.advance(Duration::days(1));
+    assert!(!export.is_available(clock.now()));
+    assert_eq!(
+        repo.user_data_export()
+            .cleanup_expired(&clock)
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(repo
+        .user_data_export()
+        .archive(&export)
+        .await
+        .unwrap()
+        .is_none());
+
+    let found = repo
+        .user_data_export()
+        .lookup(export.id)
+        .await
+        .unwrap()
+        .expect("export not found");
+    assert_eq!(found, export);
 }
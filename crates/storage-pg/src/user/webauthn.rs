@@ -0,0 +1,288 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mas_data_model::{User, WebauthnCredential};
+use mas_storage::{user::WebauthnCredentialRepository, Clock};
+use rand::RngCore;
+use sqlx::PgConnection;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::{tracing::ExecuteExt, DatabaseError};
+
+/// An implementation of [`WebauthnCredentialRepository`] for a PostgreSQL
+/// connection
+pub struct PgWebauthnCredentialRepository<'c> {
+    conn: &'c mut PgConnection,
+}
+
+impl<'c> PgWebauthnCredentialRepository<'c> {
+    /// Create a new [`PgWebauthnCredentialRepository`] from an active
+    /// PostgreSQL connection
+    pub fn new(conn: &'c mut PgConnection) -> Self {
+        Self { conn }
+    }
+}
+
+struct WebauthnCredentialLookup {
+    webauthn_credential_id: Uuid,
+    user_id: Uuid,
+    credential_id: String,
+    name: String,
+    passkey: serde_json::Value,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<WebauthnCredentialLookup> for WebauthnCredential {
+    fn from(value: WebauthnCredentialLookup) -> Self {
+        WebauthnCredential {
+            id: value.webauthn_credential_id.into(),
+            user_id: value.user_id.into(),
+            name: value.name,
+            credential_id: value.credential_id,
+            passkey: value.passkey,
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
+
+#[async_trait]
+impl<'c> WebauthnCredentialRepository for PgWebauthnCredentialRepository<'c> {
+    type Error = DatabaseError;
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.lookup",
+        skip_all,
+        fields(
+            db.statement,
+            webauthn_credential.id = %id,
+        ),
+        err,
+    )]
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebauthnCredential>, Self::Error> {
+        let res = sqlx::query_as!(
+            WebauthnCredentialLookup,
+            r#"
+                SELECT webauthn_credential_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , passkey
+                     , created_at
+                     , last_used_at
+                FROM webauthn_credentials
+                WHERE webauthn_credential_id = $1
+            "#,
+            Uuid::from(id),
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.find_by_credential_id",
+        skip_all,
+        fields(
+            db.statement,
+        ),
+        err,
+    )]
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, Self::Error> {
+        let res = sqlx::query_as!(
+            WebauthnCredentialLookup,
+            r#"
+                SELECT webauthn_credential_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , passkey
+                     , created_at
+                     , last_used_at
+                FROM webauthn_credentials
+                WHERE credential_id = $1
+            "#,
+            credential_id,
+        )
+        .traced()
+        .fetch_optional(&mut *self.conn)
+        .await?;
+
+        Ok(res.map(Into::into))
+    }
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.all",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+        ),
+        err,
+    )]
+    async fn all(&mut self, user: &User) -> Result<Vec<WebauthnCredential>, Self::Error> {
+        let res = sqlx::query_as!(
+            WebauthnCredentialLookup,
+            r#"
+                SELECT webauthn_credential_id
+                     , user_id
+                     , credential_id
+                     , name
+                     , passkey
+                     , created_at
+                     , last_used_at
+                FROM webauthn_credentials
+                WHERE user_id = $1
+                ORDER BY created_at ASC
+            "#,
+            Uuid::from(user.id),
+        )
+        .traced()
+        .fetch_all(&mut *self.conn)
+        .await?;
+
+        Ok(res.into_iter().map(Into::into).collect())
+    }
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.add",
+        skip_all,
+        fields(
+            db.statement,
+            %user.id,
+            webauthn_credential.id,
+            webauthn_credential.name = name,
+        ),
+        err,
+    )]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error> {
+        let created_at = clock.now();
+        let id = Ulid::from_datetime_with_source(created_at.into(), rng);
+        tracing::Span::current().record("webauthn_credential.id", tracing::field::display(id));
+
+        sqlx::query!(
+            r#"
+                INSERT INTO webauthn_credentials
+                    ( webauthn_credential_id
+                    , user_id
+                    , credential_id
+                    , name
+                    , passkey
+                    , created_at
+                    )
+                VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            Uuid::from(id),
+            Uuid::from(user.id),
+            &credential_id,
+            &name,
+            &passkey,
+            created_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        Ok(WebauthnCredential {
+            id,
+            user_id: user.id,
+            name,
+            credential_id,
+            passkey,
+            created_at,
+            last_used_at: None,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.record_use",
+        skip_all,
+        fields(
+            db.statement,
+            %credential.id,
+        ),
+        err,
+    )]
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        mut credential: WebauthnCredential,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error> {
+        let last_used_at = clock.now();
+        let res = sqlx::query!(
+            r#"
+                UPDATE webauthn_credentials
+                SET passkey = $2
+                  , last_used_at = $3
+                WHERE webauthn_credential_id = $1
+            "#,
+            Uuid::from(credential.id),
+            &passkey,
+            last_used_at,
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        credential.passkey = passkey;
+        credential.last_used_at = Some(last_used_at);
+        Ok(credential)
+    }
+
+    #[tracing::instrument(
+        name = "db.webauthn_credential.remove",
+        skip_all,
+        fields(
+            db.statement,
+            %credential.id,
+        ),
+        err,
+    )]
+    async fn remove(&mut self, credential: WebauthnCredential) -> Result<(), Self::Error> {
+        let res = sqlx::query!(
+            r#"
+                DELETE FROM webauthn_credentials
+                WHERE webauthn_credential_id = $1
+            "#,
+            Uuid::from(credential.id),
+        )
+        .traced()
+        .execute(&mut *self.conn)
+        .await?;
+
+        DatabaseError::ensure_affected_rows(&res, 1)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Repository to drive the data migrations running in the background
+
+use async_trait::async_trait;
+use mas_data_model::BackgroundMigration;
+
+use crate::{repository_impl, Clock};
+
+/// A [`BackgroundMigrationRepository`] helps running the data migrations which
+/// are too big to run as part of a regular schema migration
+///
+/// Those migrations are registered by the regular schema migrations, and then
+/// processed in small batches by the workers, each batch recording its
+/// progress, so that they can be interrupted and resumed at any time.
+#[async_trait]
+pub trait BackgroundMigrationRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// List all the registered background migrations, in the order they were
+    /// registered
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(&mut self) -> Result<Vec<BackgroundMigration>, Self::Error>;
+
+    /// Run a batch of the oldest incomplete background migration which isn't
+    /// already being processed elsewhere, and record its progress
+    ///
+    /// Returns the state of the migration after the batch, or `None` if there
+    /// is no migration to run
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    /// * `batch_size`: The maximum number of rows to process in the batch
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn run_next_batch(
+        &mut self,
+        clock: &dyn Clock,
+        batch_size: usize,
+    ) -> Result<Option<BackgroundMigration>, Self::Error>;
+}
+
+repository_impl!(BackgroundMigrationRepository:
+    async fn list(&mut self) -> Result<Vec<BackgroundMigration>, Self::Error>;
+
+    async fn run_next_batch(
+        &mut self,
+        clock: &dyn Clock,
+        batch_size: usize,
+    ) -> Result<Option<BackgroundMigration>, Self::Error>;
+);
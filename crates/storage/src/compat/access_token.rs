@@ -56,6 +56,24 @@ pub trait CompatAccessTokenRepository: Send + Sync {
         access_token: &str,
     ) -> Result<Option<CompatAccessToken>, Self::Error>;
 
+    /// Find the compat access tokens whose token starts with the given prefix
+    ///
+    /// Returns at most `limit` compat access tokens
+    ///
+    /// # Parameters
+    ///
+    /// * `prefix`: The start of the token
+    /// * `limit`: The maximum number of compat access tokens to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatAccessToken>, Self::Error>;
+
     /// Add a new compat access token to the database
     ///
     /// Returns the newly created compat access token
@@ -104,6 +122,12 @@ repository_impl!(CompatAccessTokenRepository:
         access_token: &str,
     ) -> Result<Option<CompatAccessToken>, Self::Error>;
 
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatAccessToken>, Self::Error>;
+
     async fn add(
         &mut self,
         rng: &mut (dyn RngCore + Send),
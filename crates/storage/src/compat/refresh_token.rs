@@ -55,6 +55,24 @@ pub trait CompatRefreshTokenRepository: Send + Sync {
         refresh_token: &str,
     ) -> Result<Option<CompatRefreshToken>, Self::Error>;
 
+    /// Find the compat refresh tokens whose token starts with the given prefix
+    ///
+    /// Returns at most `limit` compat refresh tokens
+    ///
+    /// # Parameters
+    ///
+    /// * `prefix`: The start of the token
+    /// * `limit`: The maximum number of compat refresh tokens to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatRefreshToken>, Self::Error>;
+
     /// Add a new compat refresh token to the database
     ///
     /// Returns the newly created compat refresh token
@@ -104,6 +122,12 @@ repository_impl!(CompatRefreshTokenRepository:
         refresh_token: &str,
     ) -> Result<Option<CompatRefreshToken>, Self::Error>;
 
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<CompatRefreshToken>, Self::Error>;
+
     async fn add(
         &mut self,
         rng: &mut (dyn RngCore + Send),
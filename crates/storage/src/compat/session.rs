@@ -68,6 +68,8 @@ pub struct CompatSessionFilter<'a> {
     user: Option<&'a User>,
     state: Option<CompatSessionState>,
     auth_type: Option<CompatSessionType>,
+    last_active_after: Option<DateTime<Utc>>,
+    last_active_before: Option<DateTime<Utc>>,
 }
 
 impl<'a> CompatSessionFilter<'a> {
@@ -129,6 +131,36 @@ impl<'a> CompatSessionFilter<'a> {
     pub fn auth_type(&self) -> Option<CompatSessionType> {
         self.auth_type
     }
+
+    /// Only return sessions last active after the given time
+    #[must_use]
+    pub fn with_last_active_after(mut self, last_active_after: DateTime<Utc>) -> Self {
+        self.last_active_after = Some(last_active_after);
+        self
+    }
+
+    /// Get the last active after filter
+    ///
+    /// Returns [`None`] if no lower bound was set on the last activity
+    #[must_use]
+    pub fn last_active_after(&self) -> Option<DateTime<Utc>> {
+        self.last_active_after
+    }
+
+    /// Only return sessions last active before the given time
+    #[must_use]
+    pub fn with_last_active_before(mut self, last_active_before: DateTime<Utc>) -> Self {
+        self.last_active_before = Some(last_active_before);
+        self
+    }
+
+    /// Get the last active before filter
+    ///
+    /// Returns [`None`] if no upper bound was set on the last activity
+    #[must_use]
+    pub fn last_active_before(&self) -> Option<DateTime<Utc>> {
+        self.last_active_before
+    }
 }
 
 /// A [`CompatSessionRepository`] helps interacting with
@@ -153,7 +185,9 @@ pub trait CompatSessionRepository: Send + Sync {
 
     /// Find a compatibility session by its device ID
     ///
-    /// Returns the compat session if it exists, `None` otherwise
+    /// Returns the compat session if it exists, `None` otherwise. If multiple
+    /// sessions used this device, the active one is returned, or else the most
+    /// recently created one.
     ///
     /// # Parameters
     ///
@@ -211,6 +245,24 @@ pub trait CompatSessionRepository: Send + Sync {
         compat_session: CompatSession,
     ) -> Result<CompatSession, Self::Error>;
 
+    /// Set the human-readable name of the device of a compat session
+    ///
+    /// Returns the updated compat session
+    ///
+    /// # Parameters
+    ///
+    /// * `compat_session`: The compat session to update
+    /// * `human_name`: The new name of the device, or `None` to unset it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_human_name(
+        &mut self,
+        compat_session: CompatSession,
+        human_name: Option<String>,
+    ) -> Result<CompatSession, Self::Error>;
+
     /// List [`CompatSession`] with the given filter and pagination
     ///
     /// Returns a page of compat sessions, with the associated SSO logins if any
@@ -280,6 +332,12 @@ repository_impl!(CompatSessionRepository:
         compat_session: CompatSession,
     ) -> Result<CompatSession, Self::Error>;
 
+    async fn set_human_name(
+        &mut self,
+        compat_session: CompatSession,
+        human_name: Option<String>,
+    ) -> Result<CompatSession, Self::Error>;
+
     async fn list(
         &mut self,
         filter: CompatSessionFilter<'_>,
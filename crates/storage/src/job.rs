@@ -231,7 +231,11 @@ where
 mod jobs {
     // XXX: Move this somewhere else?
     use apalis_core::job::Job;
-    use mas_data_model::{Device, User, UserEmail};
+    use chrono::{DateTime, Utc};
+    use mas_data_model::{
+        BrowserSession, CompatSession, Device, Session, SignInSession, User, UserDataExport,
+        UserEmail, UserLoginLink, UserRegistration,
+    };
     use serde::{Deserialize, Serialize};
     use ulid::Ulid;
 
@@ -276,6 +280,48 @@ mod jobs {
         const NAME: &'static str = "verify-email";
     }
 
+    /// A job to send the verification code of a registration which verifies
+    /// the email address before creating the account.
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendRegistrationCodeJob {
+        user_registration_id: Ulid,
+        language: Option<String>,
+    }
+
+    impl SendRegistrationCodeJob {
+        /// Create a new job to send the verification code of a registration.
+        #[must_use]
+        pub fn new(user_registration: &UserRegistration) -> Self {
+            Self {
+                user_registration_id: user_registration.id,
+                language: None,
+            }
+        }
+
+        /// Set the language to use for the email.
+        #[must_use]
+        pub fn with_language(mut self, language: String) -> Self {
+            self.language = Some(language);
+            self
+        }
+
+        /// The language to use for the email.
+        #[must_use]
+        pub fn language(&self) -> Option<&str> {
+            self.language.as_deref()
+        }
+
+        /// The ID of the registration.
+        #[must_use]
+        pub fn user_registration_id(&self) -> Ulid {
+            self.user_registration_id
+        }
+    }
+
+    impl Job for SendRegistrationCodeJob {
+        const NAME: &'static str = "send-registration-code";
+    }
+
     /// A job to provision the user on the homeserver.
     #[derive(Serialize, Deserialize, Debug, Clone)]
     pub struct ProvisionUserJob {
@@ -331,6 +377,10 @@ mod jobs {
     pub struct ProvisionDeviceJob {
         user_id: Ulid,
         device_id: String,
+        #[serde(default)]
+        set_display_name: Option<String>,
+        #[serde(default)]
+        replace_existing: bool,
     }
 
     impl ProvisionDeviceJob {
@@ -340,9 +390,38 @@ mod jobs {
             Self {
                 user_id: user.id,
                 device_id: device.as_str().to_owned(),
+                set_display_name: None,
+                replace_existing: false,
             }
         }
 
+        /// Set the display name of the device.
+        #[must_use]
+        pub fn set_display_name(mut self, display_name: String) -> Self {
+            self.set_display_name = Some(display_name);
+            self
+        }
+
+        /// Get the display name to be set on the device.
+        #[must_use]
+        pub fn display_name_to_set(&self) -> Option<&str> {
+            self.set_display_name.as_deref()
+        }
+
+        /// Replace the device if it already exists on the homeserver, dropping
+        /// everything associated with it, instead of reusing it.
+        #[must_use]
+        pub fn replace_existing(mut self) -> Self {
+            self.replace_existing = true;
+            self
+        }
+
+        /// Whether an existing device should be replaced
+        #[must_use]
+        pub fn replaces_existing(&self) -> bool {
+            self.replace_existing
+        }
+
         /// The ID of the user to provision the device for.
         #[must_use]
         pub fn user_id(&self) -> Ulid {
@@ -432,8 +511,355 @@ mod jobs {
     impl Job for DeactivateUserJob {
         const NAME: &'static str = "deactivate-user";
     }
+
+    /// A job to end all the sessions of a user who has been flagged as
+    /// requiring a password reset, and to send them a link to choose a new
+    /// password
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ForcePasswordResetJob {
+        user_id: Ulid,
+    }
+
+    impl ForcePasswordResetJob {
+        /// Create a new job to force a user to reset their password
+        ///
+        /// # Parameters
+        ///
+        /// * `user` - The user who must reset their password
+        #[must_use]
+        pub fn new(user: &User) -> Self {
+            Self { user_id: user.id }
+        }
+
+        /// The ID of the user who must reset their password
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+    }
+
+    impl Job for ForcePasswordResetJob {
+        const NAME: &'static str = "force-password-reset";
+    }
+
+    /// A job to end all the sessions of a user, e.g. after they chose a new
+    /// password through the account recovery flow
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct EndUserSessionsJob {
+        user_id: Ulid,
+        #[serde(default)]
+        keep_sessions_since: Option<DateTime<Utc>>,
+    }
+
+    impl EndUserSessionsJob {
+        /// Create a new job to end all the sessions of a user
+        ///
+        /// # Parameters
+        ///
+        /// * `user` - The user whose sessions should be ended
+        #[must_use]
+        pub fn new(user: &User) -> Self {
+            Self {
+                user_id: user.id,
+                keep_sessions_since: None,
+            }
+        }
+
+        /// Keep the sessions created at or after the given time, e.g. the
+        /// session which was started along with this job
+        #[must_use]
+        pub fn keep_sessions_since(mut self, since: DateTime<Utc>) -> Self {
+            self.keep_sessions_since = Some(since);
+            self
+        }
+
+        /// The ID of the user whose sessions should be ended
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+
+        /// Sessions created at or after this time are kept
+        #[must_use]
+        pub fn kept_sessions_since(&self) -> Option<DateTime<Utc>> {
+            self.keep_sessions_since
+        }
+    }
+
+    impl Job for EndUserSessionsJob {
+        const NAME: &'static str = "end-user-sessions";
+    }
+
+    /// A job to send a link to choose a new password to a user
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendPasswordResetEmailJob {
+        user_id: Ulid,
+        #[serde(default)]
+        user_email_id: Option<Ulid>,
+        language: Option<String>,
+    }
+
+    impl SendPasswordResetEmailJob {
+        /// Create a new job to send a password reset link to a user, who must
+        /// reset their password. The link is sent to their primary email
+        /// address.
+        #[must_use]
+        pub fn new(user: &User) -> Self {
+            Self {
+                user_id: user.id,
+                user_email_id: None,
+                language: None,
+            }
+        }
+
+        /// Create a new job to send a password reset link to the given email
+        /// address, on which the user asked to recover their account
+        #[must_use]
+        pub fn requested_for(user_email: &UserEmail) -> Self {
+            Self {
+                user_id: user_email.user_id,
+                user_email_id: Some(user_email.id),
+                language: None,
+            }
+        }
+
+        /// The ID of the email address on which the user asked to recover
+        /// their account, if they did
+        #[must_use]
+        pub fn user_email_id(&self) -> Option<Ulid> {
+            self.user_email_id
+        }
+
+        /// Set the language to use for the email.
+        #[must_use]
+        pub fn with_language(mut self, language: String) -> Self {
+            self.language = Some(language);
+            self
+        }
+
+        /// The language to use for the email.
+        #[must_use]
+        pub fn language(&self) -> Option<&str> {
+            self.language.as_deref()
+        }
+
+        /// The ID of the user to send the link to
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+    }
+
+    impl Job for SendPasswordResetEmailJob {
+        const NAME: &'static str = "send-password-reset-email";
+    }
+
+    /// A job to send a login link to a user, who asked to sign in without a
+    /// password
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendLoginLinkEmailJob {
+        user_login_link_id: Ulid,
+        language: Option<String>,
+    }
+
+    impl SendLoginLinkEmailJob {
+        /// Create a new job to send the given login link
+        #[must_use]
+        pub fn new(login_link: &UserLoginLink) -> Self {
+            Self {
+                user_login_link_id: login_link.id,
+                language: None,
+            }
+        }
+
+        /// Set the language to use for the email.
+        #[must_use]
+        pub fn with_language(mut self, language: String) -> Self {
+            self.language = Some(language);
+            self
+        }
+
+        /// The language to use for the email.
+        #[must_use]
+        pub fn language(&self) -> Option<&str> {
+            self.language.as_deref()
+        }
+
+        /// The ID of the login link to send
+        #[must_use]
+        pub fn user_login_link_id(&self) -> Ulid {
+            self.user_login_link_id
+        }
+    }
+
+    impl Job for SendLoginLinkEmailJob {
+        const NAME: &'static str = "send-login-link-email";
+    }
+
+    /// A job to gather the data held about a user into an export they can
+    /// download
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct ExportUserDataJob {
+        user_data_export_id: Ulid,
+        language: Option<String>,
+    }
+
+    impl ExportUserDataJob {
+        /// Create a new job to fill the given data export
+        #[must_use]
+        pub fn new(user_data_export: &UserDataExport) -> Self {
+            Self {
+                user_data_export_id: user_data_export.id,
+                language: None,
+            }
+        }
+
+        /// Set the language to use for the email.
+        #[must_use]
+        pub fn with_language(mut self, language: String) -> Self {
+            self.language = Some(language);
+            self
+        }
+
+        /// The language to use for the email.
+        #[must_use]
+        pub fn language(&self) -> Option<&str> {
+            self.language.as_deref()
+        }
+
+        /// The ID of the data export to fill
+        #[must_use]
+        pub fn user_data_export_id(&self) -> Ulid {
+            self.user_data_export_id
+        }
+    }
+
+    impl Job for ExportUserDataJob {
+        const NAME: &'static str = "export-user-data";
+    }
+
+    /// A job to warn a user that apps will soon no longer be able to log in
+    /// with their password on the compatibility layer
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendCompatPasswordDeprecationEmailJob {
+        user_id: Ulid,
+        blocked_at: DateTime<Utc>,
+    }
+
+    impl SendCompatPasswordDeprecationEmailJob {
+        /// Create a new job to warn the given user that their password login
+        /// on the compatibility layer will be blocked at the given time. The
+        /// warning is sent to their primary email address.
+        #[must_use]
+        pub fn new(user: &User, blocked_at: DateTime<Utc>) -> Self {
+            Self {
+                user_id: user.id,
+                blocked_at,
+            }
+        }
+
+        /// The ID of the user to warn
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+
+        /// When the password login of the user will be blocked
+        #[must_use]
+        pub fn blocked_at(&self) -> DateTime<Utc> {
+            self.blocked_at
+        }
+    }
+
+    impl Job for SendCompatPasswordDeprecationEmailJob {
+        const NAME: &'static str = "send-compat-password-deprecation-email";
+    }
+
+    /// A job to notify a client that one of its sessions ended, through the
+    /// OIDC back-channel logout mechanism
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct SendBackchannelLogoutJob {
+        oauth2_session_id: Ulid,
+    }
+
+    impl SendBackchannelLogoutJob {
+        /// Create a new job to notify the client of the given OAuth 2.0 session
+        /// that the session ended
+        #[must_use]
+        pub fn new(session: &Session) -> Self {
+            Self {
+                oauth2_session_id: session.id,
+            }
+        }
+
+        /// The ID of the OAuth 2.0 session which ended
+        #[must_use]
+        pub fn oauth2_session_id(&self) -> Ulid {
+            self.oauth2_session_id
+        }
+    }
+
+    impl Job for SendBackchannelLogoutJob {
+        const NAME: &'static str = "send-backchannel-logout";
+    }
+
+    /// A job to notify the other sessions of a user that they signed in
+    /// somewhere else
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct NotifyNewSignInJob {
+        user_id: Ulid,
+        user_session_id: Option<Ulid>,
+        compat_session_id: Option<Ulid>,
+    }
+
+    impl NotifyNewSignInJob {
+        /// Create a new job to notify of a sign-in which started a browser
+        /// session
+        #[must_use]
+        pub fn for_browser_session(browser_session: &BrowserSession) -> Self {
+            Self {
+                user_id: browser_session.user.id,
+                user_session_id: Some(browser_session.id),
+                compat_session_id: None,
+            }
+        }
+
+        /// Create a new job to notify of a sign-in which started a
+        /// compatibility session
+        #[must_use]
+        pub fn for_compat_session(compat_session: &CompatSession) -> Self {
+            Self {
+                user_id: compat_session.user_id,
+                user_session_id: None,
+                compat_session_id: Some(compat_session.id),
+            }
+        }
+
+        /// The ID of the user who signed in
+        #[must_use]
+        pub fn user_id(&self) -> Ulid {
+            self.user_id
+        }
+
+        /// The session started by the sign-in
+        #[must_use]
+        pub fn session(&self) -> Option<SignInSession> {
+            match (self.user_session_id, self.compat_session_id) {
+                (Some(id), None) => Some(SignInSession::Browser(id)),
+                (None, Some(id)) => Some(SignInSession::Compat(id)),
+                _ => None,
+            }
+        }
+    }
+
+    impl Job for NotifyNewSignInJob {
+        const NAME: &'static str = "notify-new-sign-in";
+    }
 }
 
 pub use self::jobs::{
-    DeactivateUserJob, DeleteDeviceJob, ProvisionDeviceJob, ProvisionUserJob, VerifyEmailJob,
+    DeactivateUserJob, DeleteDeviceJob, EndUserSessionsJob, ExportUserDataJob,
+    ForcePasswordResetJob, NotifyNewSignInJob, ProvisionDeviceJob, ProvisionUserJob,
+    SendBackchannelLogoutJob, SendCompatPasswordDeprecationEmailJob, SendLoginLinkEmailJob,
+    SendPasswordResetEmailJob, SendRegistrationCodeJob, VerifyEmailJob,
 };
@@ -142,9 +142,13 @@ pub(crate) mod repository;
 mod utils;
 
 pub mod app_session;
+pub mod background_migration;
 pub mod compat;
 pub mod job;
+pub mod login_failure;
 pub mod oauth2;
+pub mod rate_limit;
+pub mod statistics;
 pub mod upstream_oauth2;
 pub mod user;
 
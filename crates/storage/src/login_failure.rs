@@ -0,0 +1,150 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Repository to track failed login attempts, to temporarily lock out the
+//! users and IP addresses which fail too often
+
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::User;
+
+use crate::{repository_impl, Clock};
+
+/// The key of the counter of failed login attempts on a user account
+#[must_use]
+pub fn user_key(user: &User) -> String {
+    format!("user:{}", user.id)
+}
+
+/// The key of the counter of failed login attempts from an IP address
+#[must_use]
+pub fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{ip}")
+}
+
+/// A [`LoginFailureRepository`] helps interacting with the failed login
+/// counters saved in the storage backend
+///
+/// Counters are identified by a key, like `user:<id>` or `ip:<address>`. They
+/// count the failed attempts since the counter was last reset, and hold the
+/// time until which the key is locked out, if any.
+#[async_trait]
+pub trait LoginFailureRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Get the time until which a key is locked out, if it is currently locked
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    /// * `key`: The key identifying the counter
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn locked_until(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error>;
+
+    /// Record a failed attempt, and return the number of failed attempts
+    /// recorded so far, including this one
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    /// * `key`: The key identifying the counter
+    /// * `forget_after`: Previous failures are forgotten if the last one is
+    ///   older than this
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        forget_after: Duration,
+    ) -> Result<u32, Self::Error>;
+
+    /// Lock out a key until the given time
+    ///
+    /// # Parameters
+    ///
+    /// * `key`: The key identifying the counter
+    /// * `locked_until`: When the lockout ends
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lock(&mut self, key: &str, locked_until: DateTime<Utc>) -> Result<(), Self::Error>;
+
+    /// Reset the counter of a key, lifting its lockout
+    ///
+    /// # Parameters
+    ///
+    /// * `key`: The key identifying the counter
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn reset(&mut self, key: &str) -> Result<(), Self::Error>;
+
+    /// Delete the counters which are not locked and whose last failure is
+    /// older than `forget_after`, as they are equivalent to a reset counter
+    ///
+    /// Returns the number of counters deleted
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    /// * `forget_after`: How long failures are remembered
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup(
+        &mut self,
+        clock: &dyn Clock,
+        forget_after: Duration,
+    ) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(LoginFailureRepository:
+    async fn locked_until(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error>;
+
+    async fn record(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        forget_after: Duration,
+    ) -> Result<u32, Self::Error>;
+
+    async fn lock(&mut self, key: &str, locked_until: DateTime<Utc>) -> Result<(), Self::Error>;
+
+    async fn reset(&mut self, key: &str) -> Result<(), Self::Error>;
+
+    async fn cleanup(
+        &mut self,
+        clock: &dyn Clock,
+        forget_after: Duration,
+    ) -> Result<usize, Self::Error>;
+);
@@ -56,6 +56,24 @@ pub trait OAuth2AccessTokenRepository: Send + Sync {
         access_token: &str,
     ) -> Result<Option<AccessToken>, Self::Error>;
 
+    /// Find the access tokens whose token starts with the given prefix
+    ///
+    /// Returns at most `limit` access tokens
+    ///
+    /// # Parameters
+    ///
+    /// * `prefix`: The start of the token
+    /// * `limit`: The maximum number of access tokens to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AccessToken>, Self::Error>;
+
     /// Add a new access token to the database
     ///
     /// Returns the newly created access token
@@ -106,11 +124,16 @@ pub trait OAuth2AccessTokenRepository: Send + Sync {
     /// # Parameters
     ///
     /// * `clock`: The clock used to get the current time
+    /// * `limit`: The maximum number of access tokens to delete
     ///
     /// # Errors
     ///
     /// Returns [`Self::Error`] if the underlying repository fails
-    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+    async fn cleanup_expired(
+        &mut self,
+        clock: &dyn Clock,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2AccessTokenRepository:
@@ -121,6 +144,12 @@ repository_impl!(OAuth2AccessTokenRepository:
         access_token: &str,
     ) -> Result<Option<AccessToken>, Self::Error>;
 
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AccessToken>, Self::Error>;
+
     async fn add(
         &mut self,
         rng: &mut (dyn RngCore + Send),
@@ -136,5 +165,9 @@ repository_impl!(OAuth2AccessTokenRepository:
         access_token: AccessToken,
     ) -> Result<AccessToken, Self::Error>;
 
-    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+    async fn cleanup_expired(
+        &mut self,
+        clock: &dyn Clock,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 );
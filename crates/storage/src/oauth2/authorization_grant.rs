@@ -15,14 +15,44 @@
 use std::num::NonZeroU32;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{AuthorizationCode, AuthorizationGrant, Client, Session};
-use oauth2_types::{requests::ResponseMode, scope::Scope};
+use oauth2_types::{
+    requests::{ClaimsRequest, ResponseMode},
+    scope::Scope,
+};
 use rand_core::RngCore;
 use ulid::Ulid;
 use url::Url;
 
 use crate::{repository_impl, Clock};
 
+/// How far the authorization grants of a client went in the authorization
+/// flow
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizationGrantFunnel {
+    /// The ID of the client which started the grants
+    pub client_id: Ulid,
+
+    /// How many grants were started
+    pub started: u64,
+
+    /// How many grants showed the login page
+    pub login_shown: u64,
+
+    /// How many grants showed the consent page
+    pub consent_shown: u64,
+
+    /// How many grants were completed
+    pub completed: u64,
+
+    /// How many grants were abandoned before being completed
+    pub abandoned: u64,
+
+    /// How many grants ended with an error sent back to the client
+    pub errored: u64,
+}
+
 /// An [`OAuth2AuthorizationGrantRepository`] helps interacting with
 /// [`AuthorizationGrant`] saved in the storage backend
 #[async_trait]
@@ -47,10 +77,17 @@ pub trait OAuth2AuthorizationGrantRepository: Send + Sync {
     /// * `nonce`: The nonce the client sent, if set
     /// * `max_age`: The maximum age since the user last authenticated, if asked
     ///   by the client
+    /// * `acr_values`: The Authentication Context Class References the client
+    ///   asked for, in order of preference
+    /// * `claims`: The claims the client asked for with the `claims` parameter,
+    ///   if set
     /// * `response_mode`: The response mode the client requested
     /// * `response_type_id_token`: Whether the `id_token` `response_type` was
     ///   requested
     /// * `requires_consent`: Whether the client explicitly requested consent
+    /// * `requires_reauthentication`: Whether the client explicitly requested
+    ///   the user to authenticate again
+    /// * `resource`: The resource indicator the client sent, if set
     ///
     /// # Errors
     ///
@@ -67,9 +104,13 @@ pub trait OAuth2AuthorizationGrantRepository: Send + Sync {
         state: Option<String>,
         nonce: Option<String>,
         max_age: Option<NonZeroU32>,
+        acr_values: Vec<String>,
+        claims: Option<ClaimsRequest>,
         response_mode: ResponseMode,
         response_type_id_token: bool,
         requires_consent: bool,
+        requires_reauthentication: bool,
+        resource: Option<Url>,
     ) -> Result<AuthorizationGrant, Self::Error>;
 
     /// Lookup an authorization grant by its ID
@@ -153,6 +194,92 @@ pub trait OAuth2AuthorizationGrantRepository: Send + Sync {
         &mut self,
         authorization_grant: AuthorizationGrant,
     ) -> Result<AuthorizationGrant, Self::Error>;
+
+    /// Record that the user was asked to log in to continue an authorization
+    /// grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `authorization_grant`: The authorization grant to update
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_login_shown(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    /// Record that the user was asked for consent to continue an
+    /// authorization grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `authorization_grant`: The authorization grant to update
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_consent_shown(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    /// Record that an authorization grant ended with an error sent back to the
+    /// client
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `authorization_grant`: The authorization grant to update
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_error(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    /// Mark the pending authorization grants started before the given date as
+    /// abandoned
+    ///
+    /// Returns the client IDs of the grants which were marked as abandoned,
+    /// once per grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `started_before`: Only grants started before this date are marked
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn mark_abandoned(
+        &mut self,
+        clock: &dyn Clock,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<Ulid>, Self::Error>;
+
+    /// Count how far the authorization grants started since the given date
+    /// went, for each client
+    ///
+    /// # Parameters
+    ///
+    /// * `started_since`: Only grants started after this date are counted
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn funnel(
+        &mut self,
+        started_since: DateTime<Utc>,
+    ) -> Result<Vec<AuthorizationGrantFunnel>, Self::Error>;
 }
 
 repository_impl!(OAuth2AuthorizationGrantRepository:
@@ -167,9 +294,13 @@ repository_impl!(OAuth2AuthorizationGrantRepository:
         state: Option<String>,
         nonce: Option<String>,
         max_age: Option<NonZeroU32>,
+        acr_values: Vec<String>,
+        claims: Option<ClaimsRequest>,
         response_mode: ResponseMode,
         response_type_id_token: bool,
         requires_consent: bool,
+        requires_reauthentication: bool,
+        resource: Option<Url>,
     ) -> Result<AuthorizationGrant, Self::Error>;
 
     async fn lookup(&mut self, id: Ulid) -> Result<Option<AuthorizationGrant>, Self::Error>;
@@ -194,4 +325,33 @@ repository_impl!(OAuth2AuthorizationGrantRepository:
         &mut self,
         authorization_grant: AuthorizationGrant,
     ) -> Result<AuthorizationGrant, Self::Error>;
+
+    async fn record_login_shown(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    async fn record_consent_shown(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    async fn record_error(
+        &mut self,
+        clock: &dyn Clock,
+        authorization_grant: &AuthorizationGrant,
+    ) -> Result<(), Self::Error>;
+
+    async fn mark_abandoned(
+        &mut self,
+        clock: &dyn Clock,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<Ulid>, Self::Error>;
+
+    async fn funnel(
+        &mut self,
+        started_since: DateTime<Utc>,
+    ) -> Result<Vec<AuthorizationGrantFunnel>, Self::Error>;
 );
@@ -15,8 +15,12 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::{Client, User};
-use mas_iana::{jose::JsonWebSignatureAlg, oauth::OAuthClientAuthenticationMethod};
+use mas_iana::{
+    jose::{JsonWebEncryptionAlg, JsonWebEncryptionEnc, JsonWebSignatureAlg},
+    oauth::OAuthClientAuthenticationMethod,
+};
 use mas_jose::jwk::PublicJsonWebKeySet;
 use oauth2_types::{oidc::ApplicationType, requests::GrantType, scope::Scope};
 use rand_core::RngCore;
@@ -99,7 +103,24 @@ pub trait OAuth2ClientRepository: Send + Sync {
     /// * `token_endpoint_auth_signing_alg`: The algorithm used to sign the JWT
     ///   when using the `client_secret_jwt` or `private_key_jwt` authentication
     ///   methods
+    /// * `request_object_signing_alg`: The algorithm the client must use to
+    ///   sign its request objects, if any
+    /// * `request_uris`: The `request_uri` values the client may use at the
+    ///   authorization endpoint
+    /// * `id_token_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   content encryption key of the ID tokens, if they are encrypted
+    /// * `id_token_encrypted_response_enc`: The algorithm used to encrypt the
+    ///   content of the ID tokens, if they are encrypted
+    /// * `userinfo_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   content encryption key of the user info, if it is encrypted
+    /// * `userinfo_encrypted_response_enc`: The algorithm used to encrypt the
+    ///   content of the user info, if it is encrypted
     /// * `initiate_login_uri`: The URI used to initiate a login, if given
+    /// * `post_logout_redirect_uris`: The list of URIs the user can be
+    ///   redirected to after logging out
+    /// * `backchannel_logout_uri`: The URI to send logout tokens to, if given
+    /// * `backchannel_logout_session_required`: Whether the logout tokens must
+    ///   include the `sid` claim
     ///
     /// # Errors
     ///
@@ -125,9 +146,138 @@ pub trait OAuth2ClientRepository: Send + Sync {
         userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
     ) -> Result<Client, Self::Error>;
 
+    /// Update the metadata of a dynamically registered client
+    ///
+    /// Returns the updated client
+    ///
+    /// # Parameters
+    ///
+    /// * `client`: The client to update
+    /// * `redirect_uris`: The list of redirect URIs used by this client
+    /// * `encrypted_client_secret`: The encrypted client secret, if any
+    /// * `application_type`: The application type of this client
+    /// * `grant_types`: The list of grant types this client can use
+    /// * `contacts`: The list of contacts for this client
+    /// * `client_name`: The human-readable name of this client, if given
+    /// * `logo_uri`: The URI of the logo of this client, if given
+    /// * `client_uri`: The URI of a website of this client, if given
+    /// * `policy_uri`: The URI of the privacy policy of this client, if given
+    /// * `tos_uri`: The URI of the terms of service of this client, if given
+    /// * `jwks_uri`: The URI of the JWKS of this client, if given
+    /// * `jwks`: The JWKS of this client, if given
+    /// * `id_token_signed_response_alg`: The algorithm used to sign the ID
+    ///   token
+    /// * `userinfo_signed_response_alg`: The algorithm used to sign the user
+    ///   info. If none, the user info endpoint will not sign the response
+    /// * `token_endpoint_auth_method`: The authentication method used by this
+    ///   client when calling the token endpoint
+    /// * `token_endpoint_auth_signing_alg`: The algorithm used to sign the JWT
+    ///   when using the `client_secret_jwt` or `private_key_jwt` authentication
+    ///   methods
+    /// * `request_object_signing_alg`: The algorithm the client must use to
+    ///   sign its request objects, if any
+    /// * `request_uris`: The `request_uri` values the client may use at the
+    ///   authorization endpoint
+    /// * `id_token_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   content encryption key of the ID tokens, if they are encrypted
+    /// * `id_token_encrypted_response_enc`: The algorithm used to encrypt the
+    ///   content of the ID tokens, if they are encrypted
+    /// * `userinfo_encrypted_response_alg`: The algorithm used to encrypt the
+    ///   content encryption key of the user info, if it is encrypted
+    /// * `userinfo_encrypted_response_enc`: The algorithm used to encrypt the
+    ///   content of the user info, if it is encrypted
+    /// * `initiate_login_uri`: The URI used to initiate a login, if given
+    /// * `post_logout_redirect_uris`: The list of URIs the user can be
+    ///   redirected to after logging out
+    /// * `backchannel_logout_uri`: The URI to send logout tokens to, if given
+    /// * `backchannel_logout_session_required`: Whether the logout tokens must
+    ///   include the `sid` claim
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// client does not exist or is a static client
+    #[allow(clippy::too_many_arguments)]
+    async fn update(
+        &mut self,
+        client: Client,
+        redirect_uris: Vec<Url>,
+        encrypted_client_secret: Option<String>,
+        application_type: Option<ApplicationType>,
+        grant_types: Vec<GrantType>,
+        contacts: Vec<String>,
+        client_name: Option<String>,
+        logo_uri: Option<Url>,
+        client_uri: Option<Url>,
+        policy_uri: Option<Url>,
+        tos_uri: Option<Url>,
+        jwks_uri: Option<Url>,
+        jwks: Option<PublicJsonWebKeySet>,
+        id_token_signed_response_alg: Option<JsonWebSignatureAlg>,
+        userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
+        token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+    ) -> Result<Client, Self::Error>;
+
+    /// Set the hash of the registration access token of a dynamically
+    /// registered client, replacing any previous one
+    ///
+    /// # Parameters
+    ///
+    /// * `client`: The client to set the registration access token for
+    /// * `registration_access_token_hash`: The hash of the registration access
+    ///   token
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// client does not exist or is a static client
+    async fn set_registration_access_token_hash(
+        &mut self,
+        client: &Client,
+        registration_access_token_hash: &str,
+    ) -> Result<(), Self::Error>;
+
+    /// Find a dynamically registered client by the hash of its registration
+    /// access token
+    ///
+    /// Returns `None` if no client has this registration access token
+    ///
+    /// # Parameters
+    ///
+    /// * `registration_access_token_hash`: The hash of the registration access
+    ///   token
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_registration_access_token_hash(
+        &mut self,
+        registration_access_token_hash: &str,
+    ) -> Result<Option<Client>, Self::Error>;
+
     /// Add or replace a static client
     ///
     /// Returns the client that was added or replaced
@@ -140,6 +290,17 @@ pub trait OAuth2ClientRepository: Send + Sync {
     /// * `jwks`: The client JWKS, if any
     /// * `jwks_uri`: The client JWKS URI, if any
     /// * `redirect_uris`: The list of redirect URIs used by this client
+    /// * `post_logout_redirect_uris`: The list of URIs the user can be
+    ///   redirected to after logging out
+    /// * `backchannel_logout_uri`: The URI to send logout tokens to, if given
+    /// * `backchannel_logout_session_required`: Whether the logout tokens must
+    ///   include the `sid` claim
+    /// * `require_pushed_authorization_requests`: Whether this client must use
+    ///   pushed authorization requests
+    /// * `tls_client_auth_subject_dn`: The subject of the certificate this
+    ///   client authenticates with, when using `tls_client_auth`
+    /// * `tls_client_certificate_bound_access_tokens`: Whether the access
+    ///   tokens issued to this client are bound to its certificate
     ///
     /// # Errors
     ///
@@ -153,6 +314,12 @@ pub trait OAuth2ClientRepository: Send + Sync {
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        tls_client_auth_subject_dn: Option<String>,
+        tls_client_certificate_bound_access_tokens: bool,
     ) -> Result<Client, Self::Error>;
 
     /// List all static clients
@@ -226,6 +393,29 @@ pub trait OAuth2ClientRepository: Send + Sync {
     /// Returns [`Self::Error`] if the underlying repository fails, or if the
     /// client does not exist
     async fn delete_by_id(&mut self, id: Ulid) -> Result<(), Self::Error>;
+
+    /// Delete dynamically-registered clients which have no active session and
+    /// no activity since the given threshold, keeping an audit record of each
+    /// deleted client
+    ///
+    /// Returns the number of clients deleted
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `inactive_since`: Clients with no activity after this time are
+    ///   considered stale
+    /// * `limit`: The maximum number of clients to delete
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_stale(
+        &mut self,
+        clock: &dyn Clock,
+        inactive_since: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(OAuth2ClientRepository:
@@ -256,9 +446,60 @@ repository_impl!(OAuth2ClientRepository:
         userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
         token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
         token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
         initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
     ) -> Result<Client, Self::Error>;
 
+    async fn update(
+        &mut self,
+        client: Client,
+        redirect_uris: Vec<Url>,
+        encrypted_client_secret: Option<String>,
+        application_type: Option<ApplicationType>,
+        grant_types: Vec<GrantType>,
+        contacts: Vec<String>,
+        client_name: Option<String>,
+        logo_uri: Option<Url>,
+        client_uri: Option<Url>,
+        policy_uri: Option<Url>,
+        tos_uri: Option<Url>,
+        jwks_uri: Option<Url>,
+        jwks: Option<PublicJsonWebKeySet>,
+        id_token_signed_response_alg: Option<JsonWebSignatureAlg>,
+        userinfo_signed_response_alg: Option<JsonWebSignatureAlg>,
+        token_endpoint_auth_method: Option<OAuthClientAuthenticationMethod>,
+        token_endpoint_auth_signing_alg: Option<JsonWebSignatureAlg>,
+        request_object_signing_alg: Option<JsonWebSignatureAlg>,
+        request_uris: Vec<Url>,
+        id_token_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        id_token_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        userinfo_encrypted_response_alg: Option<JsonWebEncryptionAlg>,
+        userinfo_encrypted_response_enc: Option<JsonWebEncryptionEnc>,
+        initiate_login_uri: Option<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+    ) -> Result<Client, Self::Error>;
+
+    async fn set_registration_access_token_hash(
+        &mut self,
+        client: &Client,
+        registration_access_token_hash: &str,
+    ) -> Result<(), Self::Error>;
+
+    async fn find_by_registration_access_token_hash(
+        &mut self,
+        registration_access_token_hash: &str,
+    ) -> Result<Option<Client>, Self::Error>;
+
     async fn upsert_static(
         &mut self,
         client_id: Ulid,
@@ -267,6 +508,12 @@ repository_impl!(OAuth2ClientRepository:
         jwks: Option<PublicJsonWebKeySet>,
         jwks_uri: Option<Url>,
         redirect_uris: Vec<Url>,
+        post_logout_redirect_uris: Vec<Url>,
+        backchannel_logout_uri: Option<Url>,
+        backchannel_logout_session_required: bool,
+        require_pushed_authorization_requests: bool,
+        tls_client_auth_subject_dn: Option<String>,
+        tls_client_certificate_bound_access_tokens: bool,
     ) -> Result<Client, Self::Error>;
 
     async fn all_static(&mut self) -> Result<Vec<Client>, Self::Error>;
@@ -275,6 +522,13 @@ repository_impl!(OAuth2ClientRepository:
 
     async fn delete_by_id(&mut self, id: Ulid) -> Result<(), Self::Error>;
 
+    async fn cleanup_stale(
+        &mut self,
+        clock: &dyn Clock,
+        inactive_since: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, Self::Error>;
+
     async fn get_consent_for_user(
         &mut self,
         client: &Client,
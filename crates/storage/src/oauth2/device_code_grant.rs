@@ -0,0 +1,221 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{BrowserSession, Client, DeviceCodeGrant, Session};
+use oauth2_types::scope::Scope;
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// An [`OAuth2DeviceCodeGrantRepository`] helps interacting with
+/// [`DeviceCodeGrant`] saved in the storage backend
+#[async_trait]
+pub trait OAuth2DeviceCodeGrantRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Create a new device code grant
+    ///
+    /// Returns the newly created device code grant
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: A random number generator
+    /// * `clock`: The clock used to generate timestamps
+    /// * `client`: The client that requested the device code grant
+    /// * `scope`: The scope the client requested
+    /// * `device_code`: The code the device will use to poll the token endpoint
+    /// * `user_code`: The code the user will enter on the verification page
+    /// * `expires_in`: How long the device code grant is valid for
+    /// * `ip_address`: The IP address of the device, if known
+    /// * `user_agent`: The user agent of the device, if known
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    #[allow(clippy::too_many_arguments)]
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        scope: Scope,
+        device_code: String,
+        user_code: String,
+        expires_in: Duration,
+        ip_address: Option<IpAddr>,
+        user_agent: Option<String>,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    /// Lookup a device code grant by its ID
+    ///
+    /// Returns the device code grant if found, `None` otherwise
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the device code grant to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    /// Find a device code grant by its device code
+    ///
+    /// Returns the device code grant if found, `None` otherwise
+    ///
+    /// # Parameters
+    ///
+    /// * `device_code`: The device code of the device code grant to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_device_code(
+        &mut self,
+        device_code: &str,
+    ) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    /// Find a device code grant by its user code
+    ///
+    /// Returns the device code grant if found, `None` otherwise
+    ///
+    /// # Parameters
+    ///
+    /// * `user_code`: The user code of the device code grant to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_user_code(
+        &mut self,
+        user_code: &str,
+    ) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    /// Mark a device code grant as approved by the user
+    ///
+    /// Returns the updated device code grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `device_code_grant`: The device code grant to fulfill
+    /// * `browser_session`: The browser session which approved the grant
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// device code grant is not pending
+    async fn fulfill(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        browser_session: &BrowserSession,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    /// Mark a device code grant as rejected by the user
+    ///
+    /// Returns the updated device code grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `device_code_grant`: The device code grant to reject
+    /// * `browser_session`: The browser session which rejected the grant
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// device code grant is not pending
+    async fn reject(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        browser_session: &BrowserSession,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    /// Mark a device code grant as exchanged for a session
+    ///
+    /// Returns the updated device code grant
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `device_code_grant`: The device code grant to mark as exchanged
+    /// * `session`: The OAuth 2.0 session created from the grant
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// device code grant is not fulfilled
+    async fn exchange(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        session: &Session,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+}
+
+repository_impl!(OAuth2DeviceCodeGrantRepository:
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        scope: Scope,
+        device_code: String,
+        user_code: String,
+        expires_in: Duration,
+        ip_address: Option<IpAddr>,
+        user_agent: Option<String>,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    async fn find_by_device_code(
+        &mut self,
+        device_code: &str,
+    ) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    async fn find_by_user_code(
+        &mut self,
+        user_code: &str,
+    ) -> Result<Option<DeviceCodeGrant>, Self::Error>;
+
+    async fn fulfill(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        browser_session: &BrowserSession,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    async fn reject(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        browser_session: &BrowserSession,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+
+    async fn exchange(
+        &mut self,
+        clock: &dyn Clock,
+        device_code_grant: DeviceCodeGrant,
+        session: &Session,
+    ) -> Result<DeviceCodeGrant, Self::Error>;
+);
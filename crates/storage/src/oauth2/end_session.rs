@@ -0,0 +1,101 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use mas_data_model::{Device, Session};
+
+use crate::{
+    job::{DeleteDeviceJob, JobRepositoryExt, SendBackchannelLogoutJob},
+    oauth2::OAuth2SessionFilter,
+    Clock, Pagination, RepositoryAccess,
+};
+
+/// Schedule the back-channel logout notifications for an OAuth 2.0 session
+/// which is about to be finished
+///
+/// The sessions derived from it through a token exchange get finished along
+/// with it, so their clients are notified as well.
+///
+/// # Errors
+///
+/// Returns an error if the underlying repository fails
+pub async fn schedule_backchannel_logout<R>(repo: &mut R, session: &Session) -> Result<(), R::Error>
+where
+    R: RepositoryAccess + ?Sized,
+{
+    repo.job()
+        .schedule_job(SendBackchannelLogoutJob::new(session))
+        .await?;
+
+    let filter = OAuth2SessionFilter::new()
+        .derived_from(session)
+        .active_only();
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.oauth2_session().list(filter, pagination).await?;
+
+        for derived_session in &page.edges {
+            repo.job()
+                .schedule_job(SendBackchannelLogoutJob::new(derived_session))
+                .await?;
+        }
+
+        match page.edges.last() {
+            Some(last) if page.has_next_page => pagination = pagination.after(last.id),
+            _ => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// End an OAuth 2.0 session
+///
+/// This schedules the deletion of the devices the session holds on the
+/// homeserver, notifies the clients of the session and of the sessions derived
+/// from it, and marks them all as finished.
+///
+/// Sessions derived through a token exchange share the devices of their parent
+/// session, so ending one of them doesn't delete any device.
+///
+/// # Errors
+///
+/// Returns an error if the underlying repository fails
+pub async fn end_session<R>(
+    repo: &mut R,
+    clock: &dyn Clock,
+    session: Session,
+) -> Result<Session, R::Error>
+where
+    R: RepositoryAccess + ?Sized,
+{
+    if let Some(user_id) = session.user_id.filter(|_| !session.is_derived()) {
+        // The user can't be missing, as sessions reference it
+        if let Some(user) = repo.user().lookup(user_id).await? {
+            // XXX: this might not be the right semantic, but it's the best we
+            // can do for now, since we're not explicitly storing devices for
+            // OAuth2 sessions.
+            for scope in &*session.scope {
+                if let Some(device) = Device::from_scope_token(scope) {
+                    repo.job()
+                        .schedule_job(DeleteDeviceJob::new(&user, &device))
+                        .await?;
+                }
+            }
+        }
+    }
+
+    schedule_backchannel_logout(repo, &session).await?;
+
+    repo.oauth2_session().finish(clock, session).await
+}
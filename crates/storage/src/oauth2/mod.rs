@@ -17,13 +17,19 @@
 mod access_token;
 mod authorization_grant;
 mod client;
+mod device_code_grant;
+mod end_session;
+mod pushed_authorization_request;
 mod refresh_token;
 mod session;
 
 pub use self::{
     access_token::OAuth2AccessTokenRepository,
-    authorization_grant::OAuth2AuthorizationGrantRepository,
+    authorization_grant::{AuthorizationGrantFunnel, OAuth2AuthorizationGrantRepository},
     client::OAuth2ClientRepository,
+    device_code_grant::OAuth2DeviceCodeGrantRepository,
+    end_session::{end_session, schedule_backchannel_logout},
+    pushed_authorization_request::OAuth2PushedAuthorizationRequestRepository,
     refresh_token::OAuth2RefreshTokenRepository,
     session::{OAuth2SessionFilter, OAuth2SessionRepository},
 };
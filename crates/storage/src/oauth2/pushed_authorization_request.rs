@@ -0,0 +1,107 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{Client, PushedAuthorizationRequest};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// An [`OAuth2PushedAuthorizationRequestRepository`] helps interacting with
+/// [`PushedAuthorizationRequest`] saved in the storage backend
+#[async_trait]
+pub trait OAuth2PushedAuthorizationRequestRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Save a new pushed authorization request
+    ///
+    /// Returns the newly saved pushed authorization request
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: A random number generator
+    /// * `clock`: The clock used to generate timestamps
+    /// * `client`: The client which pushed the request
+    /// * `parameters`: The parameters of the authorization request
+    /// * `expires_in`: How long the request can be used for
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+
+    /// Lookup a pushed authorization request by its ID
+    ///
+    /// Returns the pushed authorization request if found, `None` otherwise
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the pushed authorization request to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid)
+        -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    /// Mark a pushed authorization request as used
+    ///
+    /// Returns the updated pushed authorization request
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `request`: The pushed authorization request to consume
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// request was already used
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+}
+
+repository_impl!(OAuth2PushedAuthorizationRequestRepository:
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parameters: BTreeMap<String, String>,
+        expires_in: Duration,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<PushedAuthorizationRequest>, Self::Error>;
+
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        request: PushedAuthorizationRequest,
+    ) -> Result<PushedAuthorizationRequest, Self::Error>;
+);
@@ -55,6 +55,24 @@ pub trait OAuth2RefreshTokenRepository: Send + Sync {
         refresh_token: &str,
     ) -> Result<Option<RefreshToken>, Self::Error>;
 
+    /// Find the refresh tokens whose token starts with the given prefix
+    ///
+    /// Returns at most `limit` refresh tokens
+    ///
+    /// # Parameters
+    ///
+    /// * `prefix`: The start of the token
+    /// * `limit`: The maximum number of refresh tokens to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<RefreshToken>, Self::Error>;
+
     /// Add a new refresh token to the database
     ///
     /// Returns the newly created [`RefreshToken`]
@@ -88,6 +106,8 @@ pub trait OAuth2RefreshTokenRepository: Send + Sync {
     ///
     /// * `clock`: The clock used to generate timestamps
     /// * `refresh_token`: The [`RefreshToken`] to consume
+    /// * `replaced_by`: The [`RefreshToken`] issued in place of the consumed
+    ///   one
     ///
     /// # Errors
     ///
@@ -97,7 +117,68 @@ pub trait OAuth2RefreshTokenRepository: Send + Sync {
         &mut self,
         clock: &dyn Clock,
         refresh_token: RefreshToken,
+        replaced_by: &RefreshToken,
+    ) -> Result<RefreshToken, Self::Error>;
+
+    /// Record that a consumed refresh token was accepted again during the
+    /// reuse grace period, so that it can't be accepted a second time
+    ///
+    /// Returns the updated [`RefreshToken`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `refresh_token`: The consumed [`RefreshToken`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// token already was accepted again
+    async fn use_grace(
+        &mut self,
+        clock: &dyn Clock,
+        refresh_token: RefreshToken,
+    ) -> Result<RefreshToken, Self::Error>;
+
+    /// Link a refresh token to the last access token issued with it, when
+    /// refresh tokens are not rotated
+    ///
+    /// Returns the updated [`RefreshToken`]
+    ///
+    /// # Parameters
+    ///
+    /// * `refresh_token`: The [`RefreshToken`] to update
+    /// * `access_token`: The [`AccessToken`] issued with it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_access_token(
+        &mut self,
+        refresh_token: RefreshToken,
+        access_token: &AccessToken,
     ) -> Result<RefreshToken, Self::Error>;
+
+    /// Keep an audit record of an already consumed refresh token being used
+    /// again
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `refresh_token`: The [`RefreshToken`] which was replayed
+    /// * `session_revoked`: Whether the session was ended because of it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_reuse(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        refresh_token: &RefreshToken,
+        session_revoked: bool,
+    ) -> Result<(), Self::Error>;
 }
 
 repository_impl!(OAuth2RefreshTokenRepository:
@@ -108,6 +189,12 @@ repository_impl!(OAuth2RefreshTokenRepository:
         refresh_token: &str,
     ) -> Result<Option<RefreshToken>, Self::Error>;
 
+    async fn find_by_token_prefix(
+        &mut self,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<RefreshToken>, Self::Error>;
+
     async fn add(
         &mut self,
         rng: &mut (dyn RngCore + Send),
@@ -121,5 +208,26 @@ repository_impl!(OAuth2RefreshTokenRepository:
         &mut self,
         clock: &dyn Clock,
         refresh_token: RefreshToken,
+        replaced_by: &RefreshToken,
     ) -> Result<RefreshToken, Self::Error>;
+
+    async fn use_grace(
+        &mut self,
+        clock: &dyn Clock,
+        refresh_token: RefreshToken,
+    ) -> Result<RefreshToken, Self::Error>;
+
+    async fn set_access_token(
+        &mut self,
+        refresh_token: RefreshToken,
+        access_token: &AccessToken,
+    ) -> Result<RefreshToken, Self::Error>;
+
+    async fn record_reuse(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        refresh_token: &RefreshToken,
+        session_revoked: bool,
+    ) -> Result<(), Self::Error>;
 );
@@ -17,9 +17,10 @@ use std::net::IpAddr;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{BrowserSession, Client, Session, User};
-use oauth2_types::scope::Scope;
+use oauth2_types::{requests::ClaimsRequest, scope::Scope};
 use rand_core::RngCore;
 use ulid::Ulid;
+use url::Url;
 
 use crate::{pagination::Page, repository_impl, Clock, Pagination};
 
@@ -43,9 +44,13 @@ impl OAuth2SessionState {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub struct OAuth2SessionFilter<'a> {
     user: Option<&'a User>,
+    browser_session: Option<&'a BrowserSession>,
     client: Option<&'a Client>,
     state: Option<OAuth2SessionState>,
     scope: Option<&'a Scope>,
+    last_active_after: Option<DateTime<Utc>>,
+    last_active_before: Option<DateTime<Utc>>,
+    parent: Option<&'a Session>,
 }
 
 impl<'a> OAuth2SessionFilter<'a> {
@@ -70,6 +75,21 @@ impl<'a> OAuth2SessionFilter<'a> {
         self.user
     }
 
+    /// List sessions started from a specific browser session
+    #[must_use]
+    pub fn for_browser_session(mut self, browser_session: &'a BrowserSession) -> Self {
+        self.browser_session = Some(browser_session);
+        self
+    }
+
+    /// Get the browser session filter
+    ///
+    /// Returns [`None`] if no browser session filter was set
+    #[must_use]
+    pub fn browser_session(&self) -> Option<&BrowserSession> {
+        self.browser_session
+    }
+
     /// List sessions for a specific client
     #[must_use]
     pub fn for_client(mut self, client: &'a Client) -> Self {
@@ -121,6 +141,51 @@ impl<'a> OAuth2SessionFilter<'a> {
     pub fn scope(&self) -> Option<&Scope> {
         self.scope
     }
+
+    /// Only return sessions last active after the given time
+    #[must_use]
+    pub fn with_last_active_after(mut self, last_active_after: DateTime<Utc>) -> Self {
+        self.last_active_after = Some(last_active_after);
+        self
+    }
+
+    /// Get the last active after filter
+    ///
+    /// Returns [`None`] if no lower bound was set on the last activity
+    #[must_use]
+    pub fn last_active_after(&self) -> Option<DateTime<Utc>> {
+        self.last_active_after
+    }
+
+    /// Only return sessions last active before the given time
+    #[must_use]
+    pub fn with_last_active_before(mut self, last_active_before: DateTime<Utc>) -> Self {
+        self.last_active_before = Some(last_active_before);
+        self
+    }
+
+    /// Get the last active before filter
+    ///
+    /// Returns [`None`] if no upper bound was set on the last activity
+    #[must_use]
+    pub fn last_active_before(&self) -> Option<DateTime<Utc>> {
+        self.last_active_before
+    }
+
+    /// List sessions derived from the given session through a token exchange
+    #[must_use]
+    pub fn derived_from(mut self, parent: &'a Session) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Get the parent session filter
+    ///
+    /// Returns [`None`] if no parent session filter was set
+    #[must_use]
+    pub fn parent(&self) -> Option<&Session> {
+        self.parent
+    }
 }
 
 /// An [`OAuth2SessionRepository`] helps interacting with [`Session`]
@@ -230,7 +295,41 @@ pub trait OAuth2SessionRepository: Send + Sync {
         self.add(rng, clock, client, None, None, scope).await
     }
 
-    /// Mark a [`Session`] as finished
+    /// Create a new [`Session`] for a [`Client`] using the token exchange
+    /// grant, derived from an existing [`Session`]
+    ///
+    /// The new session is attached to the same user and browser session as
+    /// the parent session, and gets finished when the parent session is.
+    ///
+    /// Returns the newly created [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `client`: The [`Client`] which exchanged the token
+    /// * `parent`: The [`Session`] of the token which was exchanged
+    /// * `scope`: The [`Scope`] of the [`Session`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add_from_token_exchange(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parent: &Session,
+        scope: Scope,
+    ) -> Result<Session, Self::Error>;
+
+    /// Mark a [`Session`] as finished, along with the sessions derived from
+    /// it
+    ///
+    /// This doesn't notify the clients of those sessions: use
+    /// [`end_session`](super::end_session) or
+    /// [`schedule_backchannel_logout`](super::schedule_backchannel_logout) for
+    /// that.
     ///
     /// Returns the updated [`Session`]
     ///
@@ -245,6 +344,98 @@ pub trait OAuth2SessionRepository: Send + Sync {
     async fn finish(&mut self, clock: &dyn Clock, session: Session)
         -> Result<Session, Self::Error>;
 
+    /// Bind a [`Session`] to a DPoP key
+    ///
+    /// Returns the updated [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to bind
+    /// * `jkt`: The JWK SHA-256 Thumbprint of the DPoP key
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn bind_dpop_key(
+        &mut self,
+        session: Session,
+        jkt: String,
+    ) -> Result<Session, Self::Error>;
+
+    /// Bind a [`Session`] to a client certificate
+    ///
+    /// Returns the updated [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to bind
+    /// * `thumbprint`: The SHA-256 thumbprint of the client certificate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn bind_certificate(
+        &mut self,
+        session: Session,
+        thumbprint: String,
+    ) -> Result<Session, Self::Error>;
+
+    /// Record the resource indicator of a [`Session`], which is the intended
+    /// audience of the tokens issued for it
+    ///
+    /// Returns the updated [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to update
+    /// * `resource`: The resource indicator the client asked for
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_resource(
+        &mut self,
+        session: Session,
+        resource: Url,
+    ) -> Result<Session, Self::Error>;
+
+    /// Record the claims the client asked for with the `claims` parameter and
+    /// is allowed to receive
+    ///
+    /// Returns the updated [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to update
+    /// * `claims`: The requested claims
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_claims(
+        &mut self,
+        session: Session,
+        claims: ClaimsRequest,
+    ) -> Result<Session, Self::Error>;
+
+    /// Set the human-readable name the user gave to a session
+    ///
+    /// Returns the updated [`Session`]
+    ///
+    /// # Parameters
+    ///
+    /// * `session`: The [`Session`] to update
+    /// * `human_name`: The new name of the session, or `None` to unset it
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_human_name(
+        &mut self,
+        session: Session,
+        human_name: Option<String>,
+    ) -> Result<Session, Self::Error>;
+
     /// List [`Session`]s matching the given filter and pagination parameters
     ///
     /// # Parameters
@@ -318,9 +509,36 @@ repository_impl!(OAuth2SessionRepository:
         scope: Scope,
     ) -> Result<Session, Self::Error>;
 
+    async fn add_from_token_exchange(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        client: &Client,
+        parent: &Session,
+        scope: Scope,
+    ) -> Result<Session, Self::Error>;
+
     async fn finish(&mut self, clock: &dyn Clock, session: Session)
         -> Result<Session, Self::Error>;
 
+    async fn bind_dpop_key(&mut self, session: Session, jkt: String)
+        -> Result<Session, Self::Error>;
+
+    async fn bind_certificate(&mut self, session: Session, thumbprint: String)
+        -> Result<Session, Self::Error>;
+
+    async fn set_resource(&mut self, session: Session, resource: Url)
+        -> Result<Session, Self::Error>;
+
+    async fn set_claims(&mut self, session: Session, claims: ClaimsRequest)
+        -> Result<Session, Self::Error>;
+
+    async fn set_human_name(
+        &mut self,
+        session: Session,
+        human_name: Option<String>,
+    ) -> Result<Session, Self::Error>;
+
     async fn list(
         &mut self,
         filter: OAuth2SessionFilter<'_>,
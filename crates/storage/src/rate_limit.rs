@@ -0,0 +1,83 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Repository to share the state of the rate limiters between replicas
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{repository_impl, Clock};
+
+/// A [`RateLimitRepository`] helps interacting with the rate limiting token
+/// buckets saved in the storage backend
+///
+/// Buckets hold up to `capacity` tokens and get a new token every
+/// `replenish_interval`. Instead of the number of tokens, they only store the
+/// time at which they will be full again, from which the number of tokens
+/// available can be derived.
+#[async_trait]
+pub trait RateLimitRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Atomically take a token from a bucket, creating it full if it doesn't
+    /// exist
+    ///
+    /// Returns `None` if a token was taken, or the time at which the next
+    /// token will be available if the bucket is empty
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    /// * `key`: The key identifying the bucket
+    /// * `capacity`: The maximum number of tokens in the bucket, must be at
+    ///   least one
+    /// * `replenish_interval`: How often a token is added to the bucket
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn take(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        capacity: u32,
+        replenish_interval: Duration,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error>;
+
+    /// Delete the buckets which are full again, as they are equivalent to a
+    /// bucket which doesn't exist
+    ///
+    /// Returns the number of buckets deleted
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(RateLimitRepository:
+    async fn take(
+        &mut self,
+        clock: &dyn Clock,
+        key: &str,
+        capacity: u32,
+        replenish_interval: Duration,
+    ) -> Result<Option<DateTime<Utc>>, Self::Error>;
+
+    async fn cleanup(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+);
@@ -17,20 +17,31 @@ use thiserror::Error;
 
 use crate::{
     app_session::AppSessionRepository,
+    background_migration::BackgroundMigrationRepository,
     compat::{
         CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
         CompatSsoLoginRepository,
     },
     job::JobRepository,
+    login_failure::LoginFailureRepository,
     oauth2::{
         OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
+        OAuth2DeviceCodeGrantRepository, OAuth2PushedAuthorizationRequestRepository,
         OAuth2RefreshTokenRepository, OAuth2SessionRepository,
     },
+    rate_limit::RateLimitRepository,
+    statistics::StatisticsRepository,
     upstream_oauth2::{
         UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
         UpstreamOAuthSessionRepository,
     },
-    user::{BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository},
+    user::{
+        BrowserSessionRepository, UserDataExportRepository, UserEmailRepository,
+        UserGroupRepository, UserLoginLinkRepository, UserPasswordRepository,
+        UserRecoveryCodeRepository, UserRecoveryRepository, UserRegistrationRepository,
+        UserRepository, UserSignInNotificationRepository, UserTermsRepository,
+        WebauthnCredentialRepository,
+    },
     MapErr,
 };
 
@@ -146,6 +157,46 @@ pub trait RepositoryAccess: Send {
     fn user_password<'c>(&'c mut self)
         -> Box<dyn UserPasswordRepository<Error = Self::Error> + 'c>;
 
+    /// Get an [`UserRegistrationRepository`]
+    fn user_registration<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserRegistrationRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserRecoveryRepository`]
+    fn user_recovery<'c>(&'c mut self)
+        -> Box<dyn UserRecoveryRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserSignInNotificationRepository`]
+    fn user_sign_in_notification<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserSignInNotificationRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserRecoveryCodeRepository`]
+    fn user_recovery_code<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserRecoveryCodeRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserGroupRepository`]
+    fn user_group<'c>(&'c mut self) -> Box<dyn UserGroupRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserTermsRepository`]
+    fn user_terms<'c>(&'c mut self) -> Box<dyn UserTermsRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserLoginLinkRepository`]
+    fn user_login_link<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserLoginLinkRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`UserDataExportRepository`]
+    fn user_data_export<'c>(
+        &'c mut self,
+    ) -> Box<dyn UserDataExportRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`WebauthnCredentialRepository`]
+    fn webauthn_credential<'c>(
+        &'c mut self,
+    ) -> Box<dyn WebauthnCredentialRepository<Error = Self::Error> + 'c>;
+
     /// Get a [`BrowserSessionRepository`]
     fn browser_session<'c>(
         &'c mut self,
@@ -163,6 +214,16 @@ pub trait RepositoryAccess: Send {
         &'c mut self,
     ) -> Box<dyn OAuth2AuthorizationGrantRepository<Error = Self::Error> + 'c>;
 
+    /// Get an [`OAuth2DeviceCodeGrantRepository`]
+    fn oauth2_device_code_grant<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2DeviceCodeGrantRepository<Error = Self::Error> + 'c>;
+
+    /// Get an [`OAuth2PushedAuthorizationRequestRepository`]
+    fn oauth2_pushed_authorization_request<'c>(
+        &'c mut self,
+    ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c>;
+
     /// Get an [`OAuth2SessionRepository`]
     fn oauth2_session<'c>(
         &'c mut self,
@@ -200,6 +261,21 @@ pub trait RepositoryAccess: Send {
 
     /// Get a [`JobRepository`]
     fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`RateLimitRepository`]
+    fn rate_limit<'c>(&'c mut self) -> Box<dyn RateLimitRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`LoginFailureRepository`]
+    fn login_failure<'c>(&'c mut self)
+        -> Box<dyn LoginFailureRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`BackgroundMigrationRepository`]
+    fn background_migration<'c>(
+        &'c mut self,
+    ) -> Box<dyn BackgroundMigrationRepository<Error = Self::Error> + 'c>;
+
+    /// Get a [`StatisticsRepository`]
+    fn statistics<'c>(&'c mut self) -> Box<dyn StatisticsRepository<Error = Self::Error> + 'c>;
 }
 
 /// Implementations of the [`RepositoryAccess`], [`RepositoryTransaction`] and
@@ -210,21 +286,31 @@ mod impls {
     use super::RepositoryAccess;
     use crate::{
         app_session::AppSessionRepository,
+        background_migration::BackgroundMigrationRepository,
         compat::{
             CompatAccessTokenRepository, CompatRefreshTokenRepository, CompatSessionRepository,
             CompatSsoLoginRepository,
         },
         job::JobRepository,
+        login_failure::LoginFailureRepository,
         oauth2::{
             OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository,
-            OAuth2ClientRepository, OAuth2RefreshTokenRepository, OAuth2SessionRepository,
+            OAuth2ClientRepository, OAuth2DeviceCodeGrantRepository,
+            OAuth2PushedAuthorizationRequestRepository, OAuth2RefreshTokenRepository,
+            OAuth2SessionRepository,
         },
+        rate_limit::RateLimitRepository,
+        statistics::StatisticsRepository,
         upstream_oauth2::{
             UpstreamOAuthLinkRepository, UpstreamOAuthProviderRepository,
             UpstreamOAuthSessionRepository,
         },
         user::{
-            BrowserSessionRepository, UserEmailRepository, UserPasswordRepository, UserRepository,
+            BrowserSessionRepository, UserDataExportRepository, UserEmailRepository,
+            UserGroupRepository, UserLoginLinkRepository, UserPasswordRepository,
+            UserRecoveryCodeRepository, UserRecoveryRepository, UserRegistrationRepository,
+            UserRepository, UserSignInNotificationRepository, UserTermsRepository,
+            WebauthnCredentialRepository,
         },
         MapErr, Repository, RepositoryTransaction,
     };
@@ -309,6 +395,68 @@ mod impls {
             Box::new(MapErr::new(self.inner.user_password(), &mut self.mapper))
         }
 
+        fn user_registration<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRegistrationRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.user_registration(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn user_recovery<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRecoveryRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_recovery(), &mut self.mapper))
+        }
+
+        fn user_sign_in_notification<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserSignInNotificationRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.user_sign_in_notification(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn user_recovery_code<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRecoveryCodeRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.user_recovery_code(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn user_group<'c>(&'c mut self) -> Box<dyn UserGroupRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_group(), &mut self.mapper))
+        }
+
+        fn user_terms<'c>(&'c mut self) -> Box<dyn UserTermsRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_terms(), &mut self.mapper))
+        }
+
+        fn user_login_link<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserLoginLinkRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_login_link(), &mut self.mapper))
+        }
+
+        fn user_data_export<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserDataExportRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.user_data_export(), &mut self.mapper))
+        }
+
+        fn webauthn_credential<'c>(
+            &'c mut self,
+        ) -> Box<dyn WebauthnCredentialRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.webauthn_credential(),
+                &mut self.mapper,
+            ))
+        }
+
         fn browser_session<'c>(
             &'c mut self,
         ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -336,6 +484,24 @@ mod impls {
             ))
         }
 
+        fn oauth2_device_code_grant<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2DeviceCodeGrantRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.oauth2_device_code_grant(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn oauth2_pushed_authorization_request<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.oauth2_pushed_authorization_request(),
+                &mut self.mapper,
+            ))
+        }
+
         fn oauth2_session<'c>(
             &'c mut self,
         ) -> Box<dyn OAuth2SessionRepository<Error = Self::Error> + 'c> {
@@ -393,6 +559,29 @@ mod impls {
         fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
             Box::new(MapErr::new(self.inner.job(), &mut self.mapper))
         }
+
+        fn rate_limit<'c>(&'c mut self) -> Box<dyn RateLimitRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.rate_limit(), &mut self.mapper))
+        }
+
+        fn login_failure<'c>(
+            &'c mut self,
+        ) -> Box<dyn LoginFailureRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.login_failure(), &mut self.mapper))
+        }
+
+        fn background_migration<'c>(
+            &'c mut self,
+        ) -> Box<dyn BackgroundMigrationRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(
+                self.inner.background_migration(),
+                &mut self.mapper,
+            ))
+        }
+
+        fn statistics<'c>(&'c mut self) -> Box<dyn StatisticsRepository<Error = Self::Error> + 'c> {
+            Box::new(MapErr::new(self.inner.statistics(), &mut self.mapper))
+        }
     }
 
     impl<R: RepositoryAccess + ?Sized> RepositoryAccess for Box<R> {
@@ -430,6 +619,56 @@ mod impls {
             (**self).user_password()
         }
 
+        fn user_registration<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRegistrationRepository<Error = Self::Error> + 'c> {
+            (**self).user_registration()
+        }
+
+        fn user_recovery<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRecoveryRepository<Error = Self::Error> + 'c> {
+            (**self).user_recovery()
+        }
+
+        fn user_sign_in_notification<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserSignInNotificationRepository<Error = Self::Error> + 'c> {
+            (**self).user_sign_in_notification()
+        }
+
+        fn user_recovery_code<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserRecoveryCodeRepository<Error = Self::Error> + 'c> {
+            (**self).user_recovery_code()
+        }
+
+        fn user_group<'c>(&'c mut self) -> Box<dyn UserGroupRepository<Error = Self::Error> + 'c> {
+            (**self).user_group()
+        }
+
+        fn user_terms<'c>(&'c mut self) -> Box<dyn UserTermsRepository<Error = Self::Error> + 'c> {
+            (**self).user_terms()
+        }
+
+        fn user_login_link<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserLoginLinkRepository<Error = Self::Error> + 'c> {
+            (**self).user_login_link()
+        }
+
+        fn user_data_export<'c>(
+            &'c mut self,
+        ) -> Box<dyn UserDataExportRepository<Error = Self::Error> + 'c> {
+            (**self).user_data_export()
+        }
+
+        fn webauthn_credential<'c>(
+            &'c mut self,
+        ) -> Box<dyn WebauthnCredentialRepository<Error = Self::Error> + 'c> {
+            (**self).webauthn_credential()
+        }
+
         fn browser_session<'c>(
             &'c mut self,
         ) -> Box<dyn BrowserSessionRepository<Error = Self::Error> + 'c> {
@@ -454,6 +693,18 @@ mod impls {
             (**self).oauth2_authorization_grant()
         }
 
+        fn oauth2_device_code_grant<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2DeviceCodeGrantRepository<Error = Self::Error> + 'c> {
+            (**self).oauth2_device_code_grant()
+        }
+
+        fn oauth2_pushed_authorization_request<'c>(
+            &'c mut self,
+        ) -> Box<dyn OAuth2PushedAuthorizationRequestRepository<Error = Self::Error> + 'c> {
+            (**self).oauth2_pushed_authorization_request()
+        }
+
         fn oauth2_session<'c>(
             &'c mut self,
         ) -> Box<dyn OAuth2SessionRepository<Error = Self::Error> + 'c> {
@@ -499,5 +750,25 @@ mod impls {
         fn job<'c>(&'c mut self) -> Box<dyn JobRepository<Error = Self::Error> + 'c> {
             (**self).job()
         }
+
+        fn rate_limit<'c>(&'c mut self) -> Box<dyn RateLimitRepository<Error = Self::Error> + 'c> {
+            (**self).rate_limit()
+        }
+
+        fn login_failure<'c>(
+            &'c mut self,
+        ) -> Box<dyn LoginFailureRepository<Error = Self::Error> + 'c> {
+            (**self).login_failure()
+        }
+
+        fn background_migration<'c>(
+            &'c mut self,
+        ) -> Box<dyn BackgroundMigrationRepository<Error = Self::Error> + 'c> {
+            (**self).background_migration()
+        }
+
+        fn statistics<'c>(&'c mut self) -> Box<dyn StatisticsRepository<Error = Self::Error> + 'c> {
+            (**self).statistics()
+        }
     }
 }
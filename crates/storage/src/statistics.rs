@@ -0,0 +1,111 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Repository to compute and retrieve the daily usage statistics
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{repository_impl, Clock};
+
+/// Usage statistics of a single day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DailyStatistics {
+    /// The day, in UTC
+    pub day: NaiveDate,
+
+    /// How many users were created on that day
+    pub new_users: u64,
+
+    /// How many users had a session active on that day or since
+    pub active_users: u64,
+
+    /// How many OAuth 2.0 and compatibility sessions were active on that day
+    /// or since
+    pub active_sessions: u64,
+
+    /// How many OAuth 2.0 refresh tokens and compatibility access tokens were
+    /// issued on that day
+    pub tokens_issued: u64,
+
+    /// When the statistics were computed
+    pub computed_at: DateTime<Utc>,
+}
+
+/// A [`StatisticsRepository`] helps computing the daily usage statistics and
+/// retrieving them
+///
+/// The activity of sessions is only known through the last time they were
+/// active, so the statistics of a day have to be computed on that day, or
+/// shortly after, to be accurate.
+#[async_trait]
+pub trait StatisticsRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup the statistics of a day
+    ///
+    /// Returns `None` if they were never computed
+    ///
+    /// # Parameters
+    ///
+    /// * `day`: The day to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, day: NaiveDate) -> Result<Option<DailyStatistics>, Self::Error>;
+
+    /// Compute the statistics of a day, and save them, replacing the ones
+    /// previously computed for that day
+    ///
+    /// Returns the computed statistics
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `day`: The day to compute the statistics of
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn compute(
+        &mut self,
+        clock: &dyn Clock,
+        day: NaiveDate,
+    ) -> Result<DailyStatistics, Self::Error>;
+
+    /// List the statistics of the days since the given one, oldest first
+    ///
+    /// # Parameters
+    ///
+    /// * `since`: The first day to list
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(&mut self, since: NaiveDate) -> Result<Vec<DailyStatistics>, Self::Error>;
+}
+
+repository_impl!(StatisticsRepository:
+    async fn lookup(&mut self, day: NaiveDate) -> Result<Option<DailyStatistics>, Self::Error>;
+
+    async fn compute(
+        &mut self,
+        clock: &dyn Clock,
+        day: NaiveDate,
+    ) -> Result<DailyStatistics, Self::Error>;
+
+    async fn list(&mut self, since: NaiveDate) -> Result<Vec<DailyStatistics>, Self::Error>;
+);
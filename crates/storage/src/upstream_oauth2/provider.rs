@@ -39,6 +39,9 @@ pub struct UpstreamOAuthProviderParams {
     /// A brand identifier, e.g. "apple" or "google"
     pub brand_name: Option<String>,
 
+    /// An icon for the provider, as a Matrix Content (`mxc://`) URI
+    pub icon: Option<String>,
+
     /// The scope to request during the authorization flow
     pub scope: Scope,
 
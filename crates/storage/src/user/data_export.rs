@@ -0,0 +1,176 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{User, UserDataExport};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserDataExportRepository`] helps interacting with [`UserDataExport`]
+/// saved in the storage backend
+#[async_trait]
+pub trait UserDataExportRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup an [`UserDataExport`] by its ID
+    ///
+    /// Returns `None` if no [`UserDataExport`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserDataExport`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserDataExport>, Self::Error>;
+
+    /// Find an [`UserDataExport`] by the secret token of its download link
+    ///
+    /// Returns `None` if no [`UserDataExport`] was found. Pending and expired
+    /// exports are still returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `token`: The secret token of the [`UserDataExport`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserDataExport>, Self::Error>;
+
+    /// Get the most recent [`UserDataExport`] of a [`User`]
+    ///
+    /// Returns `None` if the user never asked for an export
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] for whom to lookup the export
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn latest(&mut self, user: &User) -> Result<Option<UserDataExport>, Self::Error>;
+
+    /// Start a new [`UserDataExport`] for a [`User`]
+    ///
+    /// Returns the newly created, pending [`UserDataExport`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] whose data is exported
+    /// * `token`: The secret part of the download link
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        token: String,
+    ) -> Result<UserDataExport, Self::Error>;
+
+    /// Save the archive of a pending [`UserDataExport`], which can then be
+    /// downloaded until it expires
+    ///
+    /// Returns the completed [`UserDataExport`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_data_export`: The [`UserDataExport`] to complete
+    /// * `archive`: The exported data
+    /// * `ttl`: How long the archive can be downloaded
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// export was already completed
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        user_data_export: UserDataExport,
+        archive: serde_json::Value,
+        ttl: Duration,
+    ) -> Result<UserDataExport, Self::Error>;
+
+    /// Get the archive of a completed [`UserDataExport`]
+    ///
+    /// Returns `None` if the export is still pending or if its archive was
+    /// cleaned up after it expired
+    ///
+    /// # Parameters
+    ///
+    /// * `user_data_export`: The [`UserDataExport`] to get the archive of
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn archive(
+        &mut self,
+        user_data_export: &UserDataExport,
+    ) -> Result<Option<serde_json::Value>, Self::Error>;
+
+    /// Delete the archives of the exports which expired
+    ///
+    /// Returns the number of archives deleted
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to get the current time
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+}
+
+repository_impl!(UserDataExportRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserDataExport>, Self::Error>;
+
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserDataExport>, Self::Error>;
+
+    async fn latest(&mut self, user: &User) -> Result<Option<UserDataExport>, Self::Error>;
+
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        token: String,
+    ) -> Result<UserDataExport, Self::Error>;
+
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        user_data_export: UserDataExport,
+        archive: serde_json::Value,
+        ttl: Duration,
+    ) -> Result<UserDataExport, Self::Error>;
+
+    async fn archive(
+        &mut self,
+        user_data_export: &UserDataExport,
+    ) -> Result<Option<serde_json::Value>, Self::Error>;
+
+    async fn cleanup_expired(&mut self, clock: &dyn Clock) -> Result<usize, Self::Error>;
+);
@@ -15,6 +15,7 @@
 use async_trait::async_trait;
 use mas_data_model::{User, UserEmail, UserEmailVerification};
 use rand_core::RngCore;
+use thiserror::Error;
 use ulid::Ulid;
 
 use crate::{pagination::Page, repository_impl, Clock, Pagination};
@@ -37,10 +38,33 @@ impl UserEmailState {
     }
 }
 
+/// Error caused by marking an email address as verified while the same
+/// address is already verified by another [`UserEmail`]
+#[derive(Debug, Error)]
+#[error("the email address is already verified by another user")]
+pub struct UserEmailInUseError;
+
+impl UserEmailInUseError {
+    /// Returns `true` if this error is in the chain of sources of the given
+    /// error
+    #[must_use]
+    pub fn is_cause_of(error: &(dyn std::error::Error + 'static)) -> bool {
+        let mut source = Some(error);
+        while let Some(error) = source {
+            if error.is::<Self>() {
+                return true;
+            }
+            source = error.source();
+        }
+        false
+    }
+}
+
 /// Filter parameters for listing user emails
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub struct UserEmailFilter<'a> {
     user: Option<&'a User>,
+    email: Option<&'a str>,
     state: Option<UserEmailState>,
 }
 
@@ -66,6 +90,21 @@ impl<'a> UserEmailFilter<'a> {
         self.user
     }
 
+    /// Filter for emails with a specific address
+    #[must_use]
+    pub fn for_email(mut self, email: &'a str) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// Get the email filter
+    ///
+    /// Returns [`None`] if no email filter is set
+    #[must_use]
+    pub fn email(&self) -> Option<&str> {
+        self.email
+    }
+
     /// Filter for emails that are verified
     #[must_use]
     pub fn verified_only(mut self) -> Self {
@@ -218,7 +257,8 @@ pub trait UserEmailRepository: Send + Sync {
     ///
     /// # Errors
     ///
-    /// Returns [`Self::Error`] if the underlying repository fails
+    /// Returns [`Self::Error`] if the underlying repository fails, caused by a
+    /// [`UserEmailInUseError`] if the same address is already verified
     async fn mark_as_verified(
         &mut self,
         clock: &dyn Clock,
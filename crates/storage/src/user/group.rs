@@ -0,0 +1,81 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::User;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserGroupRepository`] helps interacting with the groups a [`User`]
+/// belongs to, as imported from the upstream providers
+#[async_trait]
+pub trait UserGroupRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// List the groups a [`User`] belongs to, sorted by name
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] for whom to list the groups
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(&mut self, user: &User) -> Result<Vec<String>, Self::Error>;
+
+    /// Add a [`User`] to the given groups. Groups the user already belongs to
+    /// are left untouched.
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to add to the groups
+    /// * `groups`: The names of the groups
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        groups: &[String],
+    ) -> Result<(), Self::Error>;
+
+    /// Remove a [`User`] from the given groups
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to remove from the groups
+    /// * `groups`: The names of the groups
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn remove(&mut self, user: &User, groups: &[String]) -> Result<(), Self::Error>;
+}
+
+repository_impl!(UserGroupRepository:
+    async fn list(&mut self, user: &User) -> Result<Vec<String>, Self::Error>;
+
+    async fn add(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        groups: &[String],
+    ) -> Result<(), Self::Error>;
+
+    async fn remove(&mut self, user: &User, groups: &[String]) -> Result<(), Self::Error>;
+);
@@ -0,0 +1,123 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{UserEmail, UserLoginLink};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserLoginLinkRepository`] helps interacting with [`UserLoginLink`]
+/// saved in the storage backend
+#[async_trait]
+pub trait UserLoginLinkRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup an [`UserLoginLink`] by its ID
+    ///
+    /// Returns `None` if no [`UserLoginLink`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserLoginLink`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserLoginLink>, Self::Error>;
+
+    /// Issue a new [`UserLoginLink`], to be sent to the given [`UserEmail`]
+    ///
+    /// Returns the newly created [`UserLoginLink`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_email`: The verified [`UserEmail`] the link will be sent to
+    /// * `token`: The secret part of the link
+    /// * `post_auth_action`: Where to send the user once they are logged in
+    /// * `ttl`: How long the link can be used
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_email: &UserEmail,
+        token: String,
+        post_auth_action: Option<serde_json::Value>,
+        ttl: Duration,
+    ) -> Result<UserLoginLink, Self::Error>;
+
+    /// Find a [`UserLoginLink`] by its secret token
+    ///
+    /// Returns `None` if no [`UserLoginLink`] was found. Expired or consumed
+    /// links are still returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `token`: The secret token of the [`UserLoginLink`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserLoginLink>, Self::Error>;
+
+    /// Consume a [`UserLoginLink`], so that it can't be used again
+    ///
+    /// Returns the consumed [`UserLoginLink`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `login_link`: The [`UserLoginLink`] to consume
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// link was already consumed
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        login_link: UserLoginLink,
+    ) -> Result<UserLoginLink, Self::Error>;
+}
+
+repository_impl!(UserLoginLinkRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserLoginLink>, Self::Error>;
+
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_email: &UserEmail,
+        token: String,
+        post_auth_action: Option<serde_json::Value>,
+        ttl: Duration,
+    ) -> Result<UserLoginLink, Self::Error>;
+
+    async fn find_by_token(&mut self, token: &str) -> Result<Option<UserLoginLink>, Self::Error>;
+
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        login_link: UserLoginLink,
+    ) -> Result<UserLoginLink, Self::Error>;
+);
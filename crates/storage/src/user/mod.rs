@@ -15,22 +15,145 @@
 //! Repositories to interact with entities related to user accounts
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use mas_data_model::User;
 use rand_core::RngCore;
 use ulid::Ulid;
 
-use crate::{repository_impl, Clock};
+use crate::{pagination::Page, repository_impl, Clock, Pagination};
 
+mod data_export;
 mod email;
+mod group;
+mod login_link;
 mod password;
+mod recovery;
+mod recovery_code;
+mod registration;
 mod session;
+mod sign_in_notification;
+mod terms;
+mod webauthn;
 
 pub use self::{
-    email::{UserEmailFilter, UserEmailRepository},
+    data_export::UserDataExportRepository,
+    email::{UserEmailFilter, UserEmailInUseError, UserEmailRepository},
+    group::UserGroupRepository,
+    login_link::UserLoginLinkRepository,
     password::UserPasswordRepository,
+    recovery::UserRecoveryRepository,
+    recovery_code::UserRecoveryCodeRepository,
+    registration::UserRegistrationRepository,
     session::{BrowserSessionFilter, BrowserSessionRepository},
+    sign_in_notification::UserSignInNotificationRepository,
+    terms::UserTermsRepository,
+    webauthn::WebauthnCredentialRepository,
 };
 
+/// The state of a user account, used to filter the list of users
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserState {
+    Active,
+    Locked,
+}
+
+impl UserState {
+    /// Returns true if the filter should only return active users
+    pub fn is_active(self) -> bool {
+        matches!(self, Self::Active)
+    }
+
+    /// Returns true if the filter should only return locked users
+    pub fn is_locked(self) -> bool {
+        matches!(self, Self::Locked)
+    }
+}
+
+/// Filter parameters for listing users
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct UserFilter<'a> {
+    state: Option<UserState>,
+    search: Option<&'a str>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+impl<'a> UserFilter<'a> {
+    /// Create a new [`UserFilter`] with default values
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter for users which are not locked
+    #[must_use]
+    pub fn active_only(mut self) -> Self {
+        self.state = Some(UserState::Active);
+        self
+    }
+
+    /// Filter for users which are locked
+    #[must_use]
+    pub fn locked_only(mut self) -> Self {
+        self.state = Some(UserState::Locked);
+        self
+    }
+
+    /// Get the state filter
+    ///
+    /// Returns [`None`] if no state filter is set
+    #[must_use]
+    pub fn state(&self) -> Option<UserState> {
+        self.state
+    }
+
+    /// Filter for users whose username or one of their email addresses
+    /// contains the given text, ignoring case
+    #[must_use]
+    pub fn matching(mut self, search: &'a str) -> Self {
+        self.search = Some(search);
+        self
+    }
+
+    /// Get the search filter
+    ///
+    /// Returns [`None`] if no search filter is set
+    #[must_use]
+    pub fn search(&self) -> Option<&str> {
+        self.search
+    }
+
+    /// Filter for users created after the given date
+    #[must_use]
+    pub fn with_created_after(mut self, created_after: DateTime<Utc>) -> Self {
+        self.created_after = Some(created_after);
+        self
+    }
+
+    /// Get the created after filter
+    ///
+    /// Returns [`None`] if no created after filter is set
+    #[must_use]
+    pub fn created_after(&self) -> Option<DateTime<Utc>> {
+        self.created_after
+    }
+
+    /// Filter for users created before the given date
+    #[must_use]
+    pub fn with_created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    /// Get the created before filter
+    ///
+    /// Returns [`None`] if no created before filter is set
+    #[must_use]
+    pub fn created_before(&self) -> Option<DateTime<Utc>> {
+        self.created_before
+    }
+}
+
 /// A [`UserRepository`] helps interacting with [`User`] saved in the storage
 /// backend
 #[async_trait]
@@ -140,6 +263,203 @@ pub trait UserRepository: Send + Sync {
         user: User,
         can_request_admin: bool,
     ) -> Result<User, Self::Error>;
+
+    /// Set whether a [`User`] is a service account
+    ///
+    /// Service accounts are meant for bots and bridges: they can't log in
+    /// interactively and only authenticate with tokens issued to them.
+    ///
+    /// Returns the [`User`] with the new `is_service_account` value
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to update
+    /// * `is_service_account`: Whether the user is a service account
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_service_account(
+        &mut self,
+        user: User,
+        is_service_account: bool,
+    ) -> Result<User, Self::Error>;
+
+    /// Set the preferred language of a [`User`]
+    ///
+    /// Returns the [`User`] with the new `locale` value
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to update
+    /// * `locale`: The BCP 47 language tag of the preferred language
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn set_locale(&mut self, user: User, locale: String) -> Result<User, Self::Error>;
+
+    /// Flag a [`User`] as requiring a password reset
+    ///
+    /// Passwords set before this point can no longer be used to log in, until
+    /// the flag is cleared by setting a new password.
+    ///
+    /// Returns the flagged [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to flag
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn require_password_reset(
+        &mut self,
+        clock: &dyn Clock,
+        user: User,
+    ) -> Result<User, Self::Error>;
+
+    /// Clear the password reset requirement of a [`User`]
+    ///
+    /// Returns the [`User`] without the flag
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to update
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn clear_password_reset(&mut self, user: User) -> Result<User, Self::Error>;
+
+    /// Start deprecating the password login of a [`User`] on the
+    /// compatibility layer
+    ///
+    /// The user is first warned, then blocked from logging in with
+    /// `m.login.password` once the grace period is over, as decided by the
+    /// policy.
+    ///
+    /// Returns the flagged [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to flag
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn deprecate_compat_password(
+        &mut self,
+        clock: &dyn Clock,
+        user: User,
+    ) -> Result<User, Self::Error>;
+
+    /// Clear the compatibility password login deprecation of a [`User`]
+    ///
+    /// Returns the [`User`] without the flag
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to update
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn clear_compat_password_deprecation(&mut self, user: User) -> Result<User, Self::Error>;
+
+    /// Soft-delete a [`User`]
+    ///
+    /// The user can't be used anymore, but can be restored until it is
+    /// purged.
+    ///
+    /// Returns the deleted [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to delete
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn soft_delete(&mut self, clock: &dyn Clock, user: User) -> Result<User, Self::Error>;
+
+    /// Restore a soft-deleted [`User`]
+    ///
+    /// Returns the restored [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to restore. It must not have been purged yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn restore(&mut self, user: User) -> Result<User, Self::Error>;
+
+    /// List the soft-deleted [`User`]s which were not purged yet, and were
+    /// deleted before the given date
+    ///
+    /// # Parameters
+    ///
+    /// * `deleted_before`: Only list users deleted before this date
+    /// * `limit`: The maximum number of users to return
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list_purgeable(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<User>, Self::Error>;
+
+    /// Purge a soft-deleted [`User`]
+    ///
+    /// This erases the personal data of the user: its email addresses,
+    /// credentials, and the details of its sessions. The user itself is kept
+    /// as a tombstone, so that its username can't be reused.
+    ///
+    /// Returns the purged [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] to purge
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn purge(&mut self, clock: &dyn Clock, user: User) -> Result<User, Self::Error>;
+
+    /// List [`User`]s matching the given filter with the given pagination
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    /// * `pagination`: The pagination parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list(
+        &mut self,
+        filter: UserFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<User>, Self::Error>;
+
+    /// Count the [`User`]s matching the given filter
+    ///
+    /// # Parameters
+    ///
+    /// * `filter`: The filter parameters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count(&mut self, filter: UserFilter<'_>) -> Result<usize, Self::Error>;
 }
 
 repository_impl!(UserRepository:
@@ -159,4 +479,37 @@ repository_impl!(UserRepository:
         user: User,
         can_request_admin: bool,
     ) -> Result<User, Self::Error>;
+    async fn set_service_account(
+        &mut self,
+        user: User,
+        is_service_account: bool,
+    ) -> Result<User, Self::Error>;
+    async fn set_locale(&mut self, user: User, locale: String) -> Result<User, Self::Error>;
+    async fn require_password_reset(
+        &mut self,
+        clock: &dyn Clock,
+        user: User,
+    ) -> Result<User, Self::Error>;
+    async fn clear_password_reset(&mut self, user: User) -> Result<User, Self::Error>;
+    async fn deprecate_compat_password(
+        &mut self,
+        clock: &dyn Clock,
+        user: User,
+    ) -> Result<User, Self::Error>;
+    async fn clear_compat_password_deprecation(&mut self, user: User)
+        -> Result<User, Self::Error>;
+    async fn soft_delete(&mut self, clock: &dyn Clock, user: User) -> Result<User, Self::Error>;
+    async fn restore(&mut self, user: User) -> Result<User, Self::Error>;
+    async fn list_purgeable(
+        &mut self,
+        deleted_before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Vec<User>, Self::Error>;
+    async fn purge(&mut self, clock: &dyn Clock, user: User) -> Result<User, Self::Error>;
+    async fn list(
+        &mut self,
+        filter: UserFilter<'_>,
+        pagination: Pagination,
+    ) -> Result<Page<User>, Self::Error>;
+    async fn count(&mut self, filter: UserFilter<'_>) -> Result<usize, Self::Error>;
 );
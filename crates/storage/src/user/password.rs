@@ -63,6 +63,17 @@ pub trait UserPasswordRepository: Send + Sync {
         hashed_password: String,
         upgraded_from: Option<&Password>,
     ) -> Result<Password, Self::Error>;
+
+    /// Count the active passwords of all users, grouped by the version of the
+    /// hashing scheme they were hashed with
+    ///
+    /// This is used to track how many passwords still have to be upgraded to
+    /// the current hashing scheme
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if underlying repository fails
+    async fn count_active_by_version(&mut self) -> Result<Vec<(u16, usize)>, Self::Error>;
 }
 
 repository_impl!(UserPasswordRepository:
@@ -76,4 +87,5 @@ repository_impl!(UserPasswordRepository:
         hashed_password: String,
         upgraded_from: Option<&Password>,
     ) -> Result<Password, Self::Error>;
+    async fn count_active_by_version(&mut self) -> Result<Vec<(u16, usize)>, Self::Error>;
 );
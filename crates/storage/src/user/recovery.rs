@@ -0,0 +1,110 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::{User, UserRecoveryTicket};
+use rand_core::RngCore;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserRecoveryRepository`] helps interacting with [`UserRecoveryTicket`]
+/// saved in the storage backend
+#[async_trait]
+pub trait UserRecoveryRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Issue a new [`UserRecoveryTicket`] for a [`User`]
+    ///
+    /// Returns the newly created [`UserRecoveryTicket`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] who will be able to reset their password
+    /// * `ticket`: The secret sent to the user
+    /// * `ttl`: How long the ticket can be used
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add_ticket(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        ticket: String,
+        ttl: Duration,
+    ) -> Result<UserRecoveryTicket, Self::Error>;
+
+    /// Find a [`UserRecoveryTicket`] by its secret
+    ///
+    /// Returns `None` if no [`UserRecoveryTicket`] was found. Expired or
+    /// consumed tickets are still returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `ticket`: The secret of the [`UserRecoveryTicket`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_ticket(
+        &mut self,
+        ticket: &str,
+    ) -> Result<Option<UserRecoveryTicket>, Self::Error>;
+
+    /// Consume a [`UserRecoveryTicket`], so that it can't be used again
+    ///
+    /// Returns the consumed [`UserRecoveryTicket`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `ticket`: The [`UserRecoveryTicket`] to consume
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails, or if the
+    /// ticket was already consumed
+    async fn consume_ticket(
+        &mut self,
+        clock: &dyn Clock,
+        ticket: UserRecoveryTicket,
+    ) -> Result<UserRecoveryTicket, Self::Error>;
+}
+
+repository_impl!(UserRecoveryRepository:
+    async fn add_ticket(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        ticket: String,
+        ttl: Duration,
+    ) -> Result<UserRecoveryTicket, Self::Error>;
+
+    async fn find_ticket(
+        &mut self,
+        ticket: &str,
+    ) -> Result<Option<UserRecoveryTicket>, Self::Error>;
+
+    async fn consume_ticket(
+        &mut self,
+        clock: &dyn Clock,
+        ticket: UserRecoveryTicket,
+    ) -> Result<UserRecoveryTicket, Self::Error>;
+);
@@ -0,0 +1,103 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::{User, UserRecoveryCode};
+use rand_core::RngCore;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserRecoveryCodeRepository`] helps interacting with
+/// [`UserRecoveryCode`] saved in the storage backend
+#[async_trait]
+pub trait UserRecoveryCodeRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Count the [`UserRecoveryCode`] of a [`User`] which were not used yet
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] for whom to count the [`UserRecoveryCode`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn count_remaining(&mut self, user: &User) -> Result<usize, Self::Error>;
+
+    /// Replace all the [`UserRecoveryCode`] of a [`User`] with new ones
+    ///
+    /// Returns the newly created [`UserRecoveryCode`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] for whom to replace the [`UserRecoveryCode`]
+    /// * `code_hashes`: The hashes of the new codes, as computed by
+    ///   [`UserRecoveryCode::hash`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn replace(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        code_hashes: Vec<String>,
+    ) -> Result<Vec<UserRecoveryCode>, Self::Error>;
+
+    /// Mark the [`UserRecoveryCode`] of a [`User`] matching the given hash as
+    /// used
+    ///
+    /// Returns the consumed [`UserRecoveryCode`], or `None` if no unused code
+    /// matched
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] who is trying to use the code
+    /// * `code_hash`: The hash of the code, as computed by
+    ///   [`UserRecoveryCode::hash`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        code_hash: &str,
+    ) -> Result<Option<UserRecoveryCode>, Self::Error>;
+}
+
+repository_impl!(UserRecoveryCodeRepository:
+    async fn count_remaining(&mut self, user: &User) -> Result<usize, Self::Error>;
+
+    async fn replace(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        code_hashes: Vec<String>,
+    ) -> Result<Vec<UserRecoveryCode>, Self::Error>;
+
+    async fn consume(
+        &mut self,
+        clock: &dyn Clock,
+        user: &User,
+        code_hash: &str,
+    ) -> Result<Option<UserRecoveryCode>, Self::Error>;
+);
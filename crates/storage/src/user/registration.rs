@@ -0,0 +1,128 @@
+// Copyright 2022, 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use chrono::Duration;
+use mas_data_model::UserRegistration;
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserRegistrationRepository`] helps interacting with
+/// [`UserRegistration`] saved in the storage backend
+#[async_trait]
+pub trait UserRegistrationRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup a [`UserRegistration`] by its ID
+    ///
+    /// Returns `None` if no [`UserRegistration`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserRegistration`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserRegistration>, Self::Error>;
+
+    /// Start a new registration by sending a verification code to an email
+    /// address
+    ///
+    /// Returns the newly created [`UserRegistration`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `email`: The email address to verify
+    /// * `code`: The verification code sent to the email address
+    /// * `max_age`: How long the registration can be used for
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        email: String,
+        code: String,
+        max_age: Duration,
+    ) -> Result<UserRegistration, Self::Error>;
+
+    /// Mark the email address of a [`UserRegistration`] as verified
+    ///
+    /// Returns the updated [`UserRegistration`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_registration`: The [`UserRegistration`] to mark as verified
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn verify(
+        &mut self,
+        clock: &dyn Clock,
+        user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error>;
+
+    /// Mark a [`UserRegistration`] as completed, once the account was created
+    ///
+    /// Returns the updated [`UserRegistration`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_registration`: The [`UserRegistration`] to mark as completed
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error>;
+}
+
+repository_impl!(UserRegistrationRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserRegistration>, Self::Error>;
+
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        email: String,
+        code: String,
+        max_age: Duration,
+    ) -> Result<UserRegistration, Self::Error>;
+
+    async fn verify(
+        &mut self,
+        clock: &dyn Clock,
+        user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error>;
+
+    async fn complete(
+        &mut self,
+        clock: &dyn Clock,
+        user_registration: UserRegistration,
+    ) -> Result<UserRegistration, Self::Error>;
+);
@@ -18,6 +18,7 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mas_data_model::{
     Authentication, BrowserSession, Password, UpstreamOAuthAuthorizationSession, User,
+    UserLoginLink, UserRecoveryCode, WebauthnCredential,
 };
 use rand_core::RngCore;
 use ulid::Ulid;
@@ -45,6 +46,8 @@ impl BrowserSessionState {
 pub struct BrowserSessionFilter<'a> {
     user: Option<&'a User>,
     state: Option<BrowserSessionState>,
+    last_active_after: Option<DateTime<Utc>>,
+    last_active_before: Option<DateTime<Utc>>,
 }
 
 impl<'a> BrowserSessionFilter<'a> {
@@ -86,6 +89,36 @@ impl<'a> BrowserSessionFilter<'a> {
     pub fn state(&self) -> Option<BrowserSessionState> {
         self.state
     }
+
+    /// Only return sessions last active after the given time
+    #[must_use]
+    pub fn with_last_active_after(mut self, last_active_after: DateTime<Utc>) -> Self {
+        self.last_active_after = Some(last_active_after);
+        self
+    }
+
+    /// Get the last active after filter
+    ///
+    /// Returns [`None`] if no lower bound was set on the last activity
+    #[must_use]
+    pub fn last_active_after(&self) -> Option<DateTime<Utc>> {
+        self.last_active_after
+    }
+
+    /// Only return sessions last active before the given time
+    #[must_use]
+    pub fn with_last_active_before(mut self, last_active_before: DateTime<Utc>) -> Self {
+        self.last_active_before = Some(last_active_before);
+        self
+    }
+
+    /// Get the last active before filter
+    ///
+    /// Returns [`None`] if no upper bound was set on the last activity
+    #[must_use]
+    pub fn last_active_before(&self) -> Option<DateTime<Utc>> {
+        self.last_active_before
+    }
 }
 
 /// A [`BrowserSessionRepository`] helps interacting with [`BrowserSession`]
@@ -217,6 +250,68 @@ pub trait BrowserSessionRepository: Send + Sync {
         upstream_oauth_session: &UpstreamOAuthAuthorizationSession,
     ) -> Result<Authentication, Self::Error>;
 
+    /// Authenticate a [`BrowserSession`] with the given
+    /// [`WebauthnCredential`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `webauthn_credential`: The WebAuthn credential which was used to
+    ///   authenticate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_webauthn(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        webauthn_credential: &WebauthnCredential,
+    ) -> Result<Authentication, Self::Error>;
+
+    /// Authenticate a [`BrowserSession`] with the given [`UserRecoveryCode`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_recovery_code`: The recovery code which was used to authenticate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_recovery_code: &UserRecoveryCode,
+    ) -> Result<Authentication, Self::Error>;
+
+    /// Authenticate a [`BrowserSession`] with the given [`UserLoginLink`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user_session`: The session to authenticate
+    /// * `user_login_link`: The login link which was used to authenticate
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn authenticate_with_login_link(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_login_link: &UserLoginLink,
+    ) -> Result<Authentication, Self::Error>;
+
     /// Get the last successful authentication for a [`BrowserSession`]
     ///
     /// # Params
@@ -286,6 +381,30 @@ repository_impl!(BrowserSessionRepository:
         upstream_oauth_session: &UpstreamOAuthAuthorizationSession,
     ) -> Result<Authentication, Self::Error>;
 
+    async fn authenticate_with_webauthn(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        webauthn_credential: &WebauthnCredential,
+    ) -> Result<Authentication, Self::Error>;
+
+    async fn authenticate_with_recovery_code(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_recovery_code: &UserRecoveryCode,
+    ) -> Result<Authentication, Self::Error>;
+
+    async fn authenticate_with_login_link(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user_session: &BrowserSession,
+        user_login_link: &UserLoginLink,
+    ) -> Result<Authentication, Self::Error>;
+
     async fn get_last_authentication(
         &mut self,
         user_session: &BrowserSession,
@@ -0,0 +1,150 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::{BrowserSession, CompatSession, UserSignInNotification};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserSignInNotificationRepository`] helps interacting with
+/// [`UserSignInNotification`] saved in the storage backend
+#[async_trait]
+pub trait UserSignInNotificationRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup a [`UserSignInNotification`] by its ID
+    ///
+    /// Returns `None` if no [`UserSignInNotification`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`UserSignInNotification`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserSignInNotification>, Self::Error>;
+
+    /// Notify the other sessions of a user of a sign-in which started a new
+    /// [`BrowserSession`]
+    ///
+    /// Returns the newly created [`UserSignInNotification`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `browser_session`: The [`BrowserSession`] started by the sign-in
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add_for_browser_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        browser_session: &BrowserSession,
+    ) -> Result<UserSignInNotification, Self::Error>;
+
+    /// Notify the other sessions of a user of a sign-in which started a new
+    /// [`CompatSession`]
+    ///
+    /// Returns the newly created [`UserSignInNotification`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `compat_session`: The [`CompatSession`] started by the sign-in
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add_for_compat_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        compat_session: &CompatSession,
+    ) -> Result<UserSignInNotification, Self::Error>;
+
+    /// List the unacknowledged notifications to show in a [`BrowserSession`]
+    ///
+    /// Those are the notifications of the sign-ins of the same user which
+    /// happened after the session started, excluding the one which started
+    /// it, oldest first.
+    ///
+    /// # Parameters
+    ///
+    /// * `browser_session`: The [`BrowserSession`] in which the notifications
+    ///   are shown
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn list_unacknowledged(
+        &mut self,
+        browser_session: &BrowserSession,
+    ) -> Result<Vec<UserSignInNotification>, Self::Error>;
+
+    /// Mark a [`UserSignInNotification`] as acknowledged, so that it doesn't
+    /// show up anymore in any session
+    ///
+    /// Returns the updated [`UserSignInNotification`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `notification`: The [`UserSignInNotification`] to acknowledge
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn acknowledge(
+        &mut self,
+        clock: &dyn Clock,
+        notification: UserSignInNotification,
+    ) -> Result<UserSignInNotification, Self::Error>;
+}
+
+repository_impl!(UserSignInNotificationRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<UserSignInNotification>, Self::Error>;
+
+    async fn add_for_browser_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        browser_session: &BrowserSession,
+    ) -> Result<UserSignInNotification, Self::Error>;
+
+    async fn add_for_compat_session(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        compat_session: &CompatSession,
+    ) -> Result<UserSignInNotification, Self::Error>;
+
+    async fn list_unacknowledged(
+        &mut self,
+        browser_session: &BrowserSession,
+    ) -> Result<Vec<UserSignInNotification>, Self::Error>;
+
+    async fn acknowledge(
+        &mut self,
+        clock: &dyn Clock,
+        notification: UserSignInNotification,
+    ) -> Result<UserSignInNotification, Self::Error>;
+);
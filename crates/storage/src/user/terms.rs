@@ -0,0 +1,85 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::{TermsOfService, User, UserTermsAcceptance};
+use rand_core::RngCore;
+
+use crate::{repository_impl, Clock};
+
+/// A [`UserTermsRepository`] helps interacting with the versions of the
+/// terms of service a [`User`] accepted
+#[async_trait]
+pub trait UserTermsRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Find when a [`User`] accepted the given version of the terms of
+    /// service
+    ///
+    /// Returns `None` if the user never accepted this version
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] to look for
+    /// * `version`: The version of the terms of service
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find(
+        &mut self,
+        user: &User,
+        version: &str,
+    ) -> Result<Option<UserTermsAcceptance>, Self::Error>;
+
+    /// Record that a [`User`] accepted the given terms of service. Accepting
+    /// the same version again keeps the first acceptance.
+    ///
+    /// Returns the acceptance of this version
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] who accepted the terms
+    /// * `terms`: The [`TermsOfService`] they accepted
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn accept(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        terms: &TermsOfService,
+    ) -> Result<UserTermsAcceptance, Self::Error>;
+}
+
+repository_impl!(UserTermsRepository:
+    async fn find(
+        &mut self,
+        user: &User,
+        version: &str,
+    ) -> Result<Option<UserTermsAcceptance>, Self::Error>;
+
+    async fn accept(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        terms: &TermsOfService,
+    ) -> Result<UserTermsAcceptance, Self::Error>;
+);
@@ -0,0 +1,156 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use mas_data_model::{User, WebauthnCredential};
+use rand_core::RngCore;
+use ulid::Ulid;
+
+use crate::{repository_impl, Clock};
+
+/// A [`WebauthnCredentialRepository`] helps interacting with
+/// [`WebauthnCredential`] saved in the storage backend
+#[async_trait]
+pub trait WebauthnCredentialRepository: Send + Sync {
+    /// The error type returned by the repository
+    type Error;
+
+    /// Lookup a [`WebauthnCredential`] by its ID
+    ///
+    /// Returns `None` if no [`WebauthnCredential`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `id`: The ID of the [`WebauthnCredential`] to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebauthnCredential>, Self::Error>;
+
+    /// Find a [`WebauthnCredential`] by the credential ID sent by the
+    /// authenticator
+    ///
+    /// Returns `None` if no [`WebauthnCredential`] was found
+    ///
+    /// # Parameters
+    ///
+    /// * `credential_id`: The credential ID, in URL-safe base64
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, Self::Error>;
+
+    /// Get all the [`WebauthnCredential`] of a [`User`]
+    ///
+    /// # Parameters
+    ///
+    /// * `user`: The [`User`] for whom to lookup the [`WebauthnCredential`]
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn all(&mut self, user: &User) -> Result<Vec<WebauthnCredential>, Self::Error>;
+
+    /// Register a new [`WebauthnCredential`] for a [`User`]
+    ///
+    /// Returns the newly created [`WebauthnCredential`]
+    ///
+    /// # Parameters
+    ///
+    /// * `rng`: The random number generator to use
+    /// * `clock`: The clock used to generate timestamps
+    /// * `user`: The [`User`] who registered the credential
+    /// * `name`: A human-readable name for the credential
+    /// * `credential_id`: The credential ID, in URL-safe base64
+    /// * `passkey`: The serialized public key and counters of the credential
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error>;
+
+    /// Record that a [`WebauthnCredential`] was used to authenticate
+    ///
+    /// Returns the updated [`WebauthnCredential`]
+    ///
+    /// # Parameters
+    ///
+    /// * `clock`: The clock used to generate timestamps
+    /// * `credential`: The [`WebauthnCredential`] which was used
+    /// * `passkey`: The serialized credential, with its updated counters
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        credential: WebauthnCredential,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error>;
+
+    /// Remove a [`WebauthnCredential`]
+    ///
+    /// # Parameters
+    ///
+    /// * `credential`: The [`WebauthnCredential`] to remove
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Self::Error`] if the underlying repository fails
+    async fn remove(&mut self, credential: WebauthnCredential) -> Result<(), Self::Error>;
+}
+
+repository_impl!(WebauthnCredentialRepository:
+    async fn lookup(&mut self, id: Ulid) -> Result<Option<WebauthnCredential>, Self::Error>;
+
+    async fn find_by_credential_id(
+        &mut self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, Self::Error>;
+
+    async fn all(&mut self, user: &User) -> Result<Vec<WebauthnCredential>, Self::Error>;
+
+    async fn add(
+        &mut self,
+        rng: &mut (dyn RngCore + Send),
+        clock: &dyn Clock,
+        user: &User,
+        name: String,
+        credential_id: String,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error>;
+
+    async fn record_use(
+        &mut self,
+        clock: &dyn Clock,
+        credential: WebauthnCredential,
+        passkey: serde_json::Value,
+    ) -> Result<WebauthnCredential, Self::Error>;
+
+    async fn remove(&mut self, credential: WebauthnCredential) -> Result<(), Self::Error>;
+);
@@ -0,0 +1,188 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Adaptive pacing of the jobs which delete rows in batches, so that a large
+//! cleanup doesn't starve the interactive workload of database resources
+
+use std::time::{Duration, Instant};
+
+use sqlx::{Pool, Postgres};
+use tracing::debug;
+
+use crate::BatchingSettings;
+
+/// Share of the connection pool in use above which the database is considered
+/// busy
+const BUSY_POOL_USAGE: f64 = 0.75;
+
+/// Pauses shorter than this are not worth sleeping for
+const MIN_PAUSE: Duration = Duration::from_millis(10);
+
+/// Share of the connections of the pool currently in use, between 0 and 1
+fn pool_usage(pool: &Pool<Postgres>) -> f64 {
+    let max = pool.options().get_max_connections();
+    if max == 0 {
+        return 0.0;
+    }
+
+    let idle = u32::try_from(pool.num_idle()).unwrap_or(u32::MAX);
+    let used = pool.size().saturating_sub(idle);
+    f64::from(used) / f64::from(max)
+}
+
+/// Decides how many rows the next batch should process and how long to wait
+/// before running it.
+///
+/// It starts with the smallest batches and grows them as long as they are
+/// fast and the pool has spare connections. As soon as a batch takes longer
+/// than the target duration, or the pool gets busy, it shrinks the batches
+/// and pauses between them.
+pub(crate) struct Batcher {
+    min_batch_size: usize,
+    max_batch_size: usize,
+    target_batch_duration: Duration,
+    max_pause: Duration,
+    batch_size: usize,
+    pause: Duration,
+}
+
+impl Batcher {
+    pub fn new(settings: &BatchingSettings) -> Self {
+        let min_batch_size = settings.min_batch_size.max(1);
+        Self {
+            min_batch_size,
+            max_batch_size: settings.max_batch_size.max(min_batch_size),
+            target_batch_duration: settings.target_batch_duration,
+            max_pause: settings.max_pause,
+            batch_size: min_batch_size,
+            pause: Duration::ZERO,
+        }
+    }
+
+    /// How many rows the next batch should process
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Adjust the size of the next batch and the pause before it, given how
+    /// long the last batch took and the share of the connection pool in use
+    fn record(&mut self, elapsed: Duration, pool_usage: f64) {
+        if elapsed > self.target_batch_duration || pool_usage >= BUSY_POOL_USAGE {
+            // Back off: pause at least as long as the batch took, so that
+            // the cleanup uses at most half of the time
+            self.batch_size = (self.batch_size / 2).max(self.min_batch_size);
+            self.pause = self
+                .pause
+                .saturating_mul(2)
+                .max(elapsed)
+                .min(self.max_pause);
+        } else if elapsed < self.target_batch_duration / 2 {
+            self.batch_size = self.batch_size.saturating_mul(2).min(self.max_batch_size);
+            self.pause /= 2;
+            if self.pause < MIN_PAUSE {
+                self.pause = Duration::ZERO;
+            }
+        }
+    }
+
+    /// Record the batch which started at `started_at`, and wait before the
+    /// next one if the database needs some rest
+    pub async fn pace(&mut self, started_at: Instant, pool: &Pool<Postgres>) {
+        let elapsed = started_at.elapsed();
+        let pool_usage = pool_usage(pool);
+        self.record(elapsed, pool_usage);
+
+        debug!(
+            elapsed.ms = elapsed.as_millis(),
+            pool_usage,
+            batch_size = self.batch_size,
+            pause.ms = self.pause.as_millis(),
+            "paced cleanup batch"
+        );
+
+        if !self.pause.is_zero() {
+            tokio::time::sleep(self.pause).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> BatchingSettings {
+        BatchingSettings {
+            min_batch_size: 10,
+            max_batch_size: 100,
+            target_batch_duration: Duration::from_millis(500),
+            max_pause: Duration::from_secs(2),
+        }
+    }
+
+    #[test]
+    fn test_batcher_grows_when_fast() {
+        let mut batcher = Batcher::new(&settings());
+        assert_eq!(batcher.batch_size(), 10);
+
+        batcher.record(Duration::from_millis(50), 0.1);
+        assert_eq!(batcher.batch_size(), 20);
+        assert_eq!(batcher.pause, Duration::ZERO);
+
+        for _ in 0..10 {
+            batcher.record(Duration::from_millis(50), 0.1);
+        }
+        assert_eq!(batcher.batch_size(), 100);
+
+        // Batches close to the target keep their size
+        batcher.record(Duration::from_millis(400), 0.1);
+        assert_eq!(batcher.batch_size(), 100);
+    }
+
+    #[test]
+    fn test_batcher_backs_off_when_slow_or_busy() {
+        let mut batcher = Batcher::new(&settings());
+        for _ in 0..4 {
+            batcher.record(Duration::from_millis(50), 0.1);
+        }
+        assert_eq!(batcher.batch_size(), 100);
+
+        // A slow batch halves the size, and pauses as long as the batch took
+        batcher.record(Duration::from_millis(800), 0.1);
+        assert_eq!(batcher.batch_size(), 50);
+        assert_eq!(batcher.pause, Duration::from_millis(800));
+
+        // A busy pool backs off even if the batch was fast, up to the caps
+        for _ in 0..10 {
+            batcher.record(Duration::from_millis(50), 0.9);
+        }
+        assert_eq!(batcher.batch_size(), 10);
+        assert_eq!(batcher.pause, Duration::from_secs(2));
+
+        // Once the database calms down, pauses shrink again
+        batcher.record(Duration::from_millis(50), 0.1);
+        assert_eq!(batcher.batch_size(), 20);
+        assert_eq!(batcher.pause, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_batcher_inconsistent_settings() {
+        let mut batcher = Batcher::new(&BatchingSettings {
+            min_batch_size: 200,
+            ..settings()
+        });
+        assert_eq!(batcher.batch_size(), 200);
+        batcher.record(Duration::from_millis(50), 0.1);
+        assert_eq!(batcher.batch_size(), 200);
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Gather the data held about a user into an export they can download
+
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use chrono::{DateTime, Duration, Utc};
+use mas_data_model::{Authentication, CompatSession, Session, UpstreamOAuthLink, User, UserEmail};
+use mas_email::{Address, Mailbox};
+use mas_i18n::locale;
+use mas_router::AccountDataExportDownload;
+use mas_storage::{
+    compat::{CompatSessionFilter, CompatSessionRepository},
+    job::{ExportUserDataJob, JobWithSpanContext},
+    oauth2::{OAuth2ClientRepository, OAuth2SessionFilter, OAuth2SessionRepository},
+    upstream_oauth2::{UpstreamOAuthLinkFilter, UpstreamOAuthLinkRepository},
+    user::{
+        BrowserSessionFilter, BrowserSessionRepository, UserDataExportRepository,
+        UserEmailRepository, UserRepository,
+    },
+    BoxRepository, Pagination, RepositoryAccess,
+};
+use mas_templates::{EmailDataExportContext, TemplateContext};
+use serde::Serialize;
+use tracing::info;
+use ulid::Ulid;
+
+use crate::{storage::PostgresStorageFactory, JobContextExt, State};
+
+#[derive(Serialize)]
+struct Archive {
+    exported_at: DateTime<Utc>,
+    user: User,
+    emails: Vec<UserEmail>,
+    browser_sessions: Vec<ArchivedBrowserSession>,
+    oauth2_sessions: Vec<Session>,
+    compat_sessions: Vec<CompatSession>,
+    consents: Vec<ArchivedConsent>,
+    upstream_oauth_links: Vec<UpstreamOAuthLink>,
+}
+
+/// A browser session, without the user it belongs to, which is already at the
+/// top of the archive
+#[derive(Serialize)]
+struct ArchivedBrowserSession {
+    id: Ulid,
+    created_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    user_agent: Option<String>,
+    last_active_at: Option<DateTime<Utc>>,
+    last_active_ip: Option<std::net::IpAddr>,
+    last_authentication: Option<Authentication>,
+}
+
+#[derive(Serialize)]
+struct ArchivedConsent {
+    client_id: String,
+    client_name: Option<String>,
+    scope: String,
+}
+
+async fn browser_sessions(
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<Vec<ArchivedBrowserSession>, anyhow::Error> {
+    let mut sessions = Vec::new();
+    let filter = BrowserSessionFilter::new().for_user(user);
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.browser_session().list(filter, pagination).await?;
+        for browser_session in page.edges {
+            pagination = pagination.after(browser_session.id);
+            let last_authentication = repo
+                .browser_session()
+                .get_last_authentication(&browser_session)
+                .await?;
+            sessions.push(ArchivedBrowserSession {
+                id: browser_session.id,
+                created_at: browser_session.created_at,
+                finished_at: browser_session.finished_at,
+                user_agent: browser_session.user_agent,
+                last_active_at: browser_session.last_active_at,
+                last_active_ip: browser_session.last_active_ip,
+                last_authentication,
+            });
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(sessions)
+}
+
+async fn oauth2_sessions(
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<Vec<Session>, anyhow::Error> {
+    let mut sessions = Vec::new();
+    let filter = OAuth2SessionFilter::new().for_user(user);
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.oauth2_session().list(filter, pagination).await?;
+        for session in page.edges {
+            pagination = pagination.after(session.id);
+            sessions.push(session);
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(sessions)
+}
+
+async fn compat_sessions(
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<Vec<CompatSession>, anyhow::Error> {
+    let mut sessions = Vec::new();
+    let filter = CompatSessionFilter::new().for_user(user);
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.compat_session().list(filter, pagination).await?;
+        for (session, _) in page.edges {
+            pagination = pagination.after(session.id);
+            sessions.push(session);
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(sessions)
+}
+
+async fn upstream_oauth_links(
+    repo: &mut BoxRepository,
+    user: &User,
+) -> Result<Vec<UpstreamOAuthLink>, anyhow::Error> {
+    let mut links = Vec::new();
+    let filter = UpstreamOAuthLinkFilter::new().for_user(user);
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo.upstream_oauth_link().list(filter, pagination).await?;
+        for link in page.edges {
+            pagination = pagination.after(link.id);
+            links.push(link);
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(links)
+}
+
+/// Consents are given per client, so look at the clients the user had a
+/// session with
+async fn consents(
+    repo: &mut BoxRepository,
+    user: &User,
+    oauth2_sessions: &[Session],
+) -> Result<Vec<ArchivedConsent>, anyhow::Error> {
+    let client_ids: BTreeSet<Ulid> = oauth2_sessions
+        .iter()
+        .map(|session| session.client_id)
+        .collect();
+
+    let mut consents = Vec::new();
+    for client_id in client_ids {
+        let Some(client) = repo.oauth2_client().lookup(client_id).await? else {
+            continue;
+        };
+
+        let scope = repo
+            .oauth2_client()
+            .get_consent_for_user(&client, user)
+            .await?;
+        if scope.is_empty() {
+            continue;
+        }
+
+        consents.push(ArchivedConsent {
+            client_id: client.client_id,
+            client_name: client.client_name,
+            scope: scope.to_string(),
+        });
+    }
+
+    Ok(consents)
+}
+
+#[tracing::instrument(
+    name = "job.export_user_data",
+    fields(user_data_export.id = %job.user_data_export_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn export_user_data(
+    job: JobWithSpanContext<ExportUserDataJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let mut repo = state.repository().await?;
+    let mailer = state.mailer();
+    let clock = state.clock();
+
+    let user_data_export = repo
+        .user_data_export()
+        .lookup(job.user_data_export_id())
+        .await?
+        .context("User data export not found")?;
+
+    if !user_data_export.is_pending() {
+        info!("User data export was already completed");
+        return Ok(());
+    }
+
+    let user = repo
+        .user()
+        .lookup(user_data_export.user_id)
+        .await?
+        .context("User not found")?;
+
+    let emails = repo.user_email().all(&user).await?;
+    let browser_sessions = browser_sessions(&mut repo, &user).await?;
+    let oauth2_sessions = oauth2_sessions(&mut repo, &user).await?;
+    let compat_sessions = compat_sessions(&mut repo, &user).await?;
+    let consents = consents(&mut repo, &user, &oauth2_sessions).await?;
+    let upstream_oauth_links = upstream_oauth_links(&mut repo, &user).await?;
+
+    let archive = Archive {
+        exported_at: clock.now(),
+        user: user.clone(),
+        emails,
+        browser_sessions,
+        oauth2_sessions,
+        compat_sessions,
+        consents,
+        upstream_oauth_links,
+    };
+    let archive = serde_json::to_value(archive)?;
+
+    // The email sent to the user says the link works for 7 days
+    let user_data_export = repo
+        .user_data_export()
+        .complete(&clock, user_data_export, archive, Duration::days(7))
+        .await?;
+
+    let primary_email = if let Some(primary_user_email_id) = user.primary_user_email_id {
+        repo.user_email().lookup(primary_user_email_id).await?
+    } else {
+        None
+    };
+
+    // Save the archive before telling the user about it
+    repo.save().await?;
+
+    info!("User data export completed");
+
+    let Some(user_email) = primary_email else {
+        info!("User has no primary email address, not sending the download link");
+        return Ok(());
+    };
+
+    // The preferred language of the user takes precedence over the language of
+    // the request which triggered the job
+    let language = user
+        .locale
+        .as_deref()
+        .or(job.language())
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
+    let link = state
+        .url_builder()
+        .absolute_url_for(&AccountDataExportDownload::new(
+            user_data_export.token.clone(),
+        ));
+
+    let address: Address = user_email.email.parse()?;
+    let mailbox = Mailbox::new(Some(user.username.clone()), address);
+
+    let context = EmailDataExportContext::new(user, link).with_language(language);
+
+    mailer.send_data_export_email(mailbox, &context).await?;
+
+    info!("Data export email sent");
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+    storage_factory: &PostgresStorageFactory,
+) -> Monitor<TokioExecutor> {
+    let export_user_data_worker =
+        crate::build!(ExportUserDataJob => export_user_data, suffix, state, storage_factory);
+
+    monitor.register(export_user_data_worker)
+}
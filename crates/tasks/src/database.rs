@@ -14,7 +14,12 @@
 
 //! Database-related tasks
 
-use std::str::FromStr;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
 
 use apalis_core::{
     builder::{WorkerBuilder, WorkerFactoryFn},
@@ -25,11 +30,27 @@ use apalis_core::{
     utils::timer::TokioTimer,
 };
 use apalis_cron::CronStream;
-use chrono::{DateTime, Utc};
-use mas_storage::{oauth2::OAuth2AccessTokenRepository, RepositoryAccess};
-use tracing::{debug, info};
+use chrono::{DateTime, Duration, Utc};
+use mas_storage::{
+    background_migration::BackgroundMigrationRepository,
+    job::{DeactivateUserJob, JobRepositoryExt},
+    oauth2::{
+        OAuth2AccessTokenRepository, OAuth2AuthorizationGrantRepository, OAuth2ClientRepository,
+    },
+    rate_limit::RateLimitRepository,
+    statistics::{DailyStatistics, StatisticsRepository},
+    user::{UserDataExportRepository, UserPasswordRepository, UserRepository},
+    RepositoryAccess,
+};
+use opentelemetry::{
+    metrics::{Counter, Unit},
+    Key, KeyValue,
+};
+use tracing::{debug, info, warn};
+use ulid::Ulid;
 
 use crate::{
+    batch::Batcher,
     utils::{metrics_layer, trace_layer, TracedJob},
     JobContextExt, State,
 };
@@ -58,10 +79,43 @@ pub async fn cleanup_expired_tokens(
     debug!("cleanup expired tokens job scheduled at {}", job.scheduled);
 
     let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
     let clock = state.clock();
+
+    // Expired access tokens can pile up, so they are deleted in batches paced to
+    // leave room for the interactive workload
+    let mut batcher = Batcher::new(&state.settings().batching);
+    let mut count = 0;
+    loop {
+        let started_at = Instant::now();
+        let batch_size = batcher.batch_size();
+        let mut repo = state.repository().await?;
+        let deleted = repo
+            .oauth2_access_token()
+            .cleanup_expired(&clock, batch_size)
+            .await?;
+        repo.save().await?;
+
+        count += deleted;
+        if deleted < batch_size {
+            break;
+        }
+
+        batcher.pace(started_at, state.pool()).await;
+    }
+
     let mut repo = state.repository().await?;
 
-    let count = repo.oauth2_access_token().cleanup_expired(&clock).await?;
+    // Drop the archives of the data exports which can't be downloaded anymore
+    let exports = repo.user_data_export().cleanup_expired(&clock).await?;
+    if exports > 0 {
+        info!(count = exports, "cleaned up expired data exports");
+    }
+
     repo.save().await?;
 
     if count == 0 {
@@ -73,6 +127,559 @@ pub async fn cleanup_expired_tokens(
     Ok(())
 }
 
+#[derive(Default, Clone)]
+pub struct CleanupStaleClientsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for CleanupStaleClientsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for CleanupStaleClientsJob {
+    const NAME: &'static str = "cleanup-stale-clients";
+}
+
+impl TracedJob for CleanupStaleClientsJob {}
+
+pub async fn cleanup_stale_clients(
+    job: CleanupStaleClientsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("cleanup stale clients job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let Some(inactivity_period) = state.settings().stale_clients_inactivity else {
+        return Ok(());
+    };
+
+    let clock = state.clock();
+    let inactive_since = clock.now() - inactivity_period;
+
+    let mut batcher = Batcher::new(&state.settings().batching);
+    let mut total = 0;
+    loop {
+        let started_at = Instant::now();
+        let batch_size = batcher.batch_size();
+        let mut repo = state.repository().await?;
+        let count = repo
+            .oauth2_client()
+            .cleanup_stale(&clock, inactive_since, batch_size)
+            .await?;
+        repo.save().await?;
+
+        total += count;
+        if count < batch_size {
+            break;
+        }
+
+        batcher.pace(started_at, state.pool()).await;
+    }
+
+    if total == 0 {
+        debug!("no stale client to clean up");
+    } else {
+        info!(count = total, "cleaned up stale clients");
+    }
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+pub struct CleanupRateLimitBucketsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for CleanupRateLimitBucketsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for CleanupRateLimitBucketsJob {
+    const NAME: &'static str = "cleanup-rate-limit-buckets";
+}
+
+impl TracedJob for CleanupRateLimitBucketsJob {}
+
+pub async fn cleanup_rate_limit_buckets(
+    job: CleanupRateLimitBucketsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!(
+        "cleanup rate limit buckets job scheduled at {}",
+        job.scheduled
+    );
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let clock = state.clock();
+    let mut repo = state.repository().await?;
+
+    let count = repo.rate_limit().cleanup(&clock).await?;
+
+    // The counters of failed login attempts are forgotten after a while as well
+    let failures = if let Some(retention) = state.settings().login_failures_retention {
+        repo.login_failure().cleanup(&clock, retention).await?
+    } else {
+        0
+    };
+
+    repo.save().await?;
+
+    if count == 0 {
+        debug!("no rate limit bucket to clean up");
+    } else {
+        info!(count, "cleaned up full rate limit buckets");
+    }
+
+    if failures > 0 {
+        info!(count = failures, "cleaned up stale failed login counters");
+    }
+
+    Ok(())
+}
+
+const CLIENT_ID: Key = Key::from_static_str("client.id");
+
+fn abandoned_grants_counter() -> &'static Counter<u64> {
+    static COUNTER: OnceLock<Counter<u64>> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        let meter = opentelemetry::global::meter_with_version(
+            env!("CARGO_PKG_NAME"),
+            Some(env!("CARGO_PKG_VERSION")),
+            None,
+            None,
+        );
+        meter
+            .u64_counter("mas.oauth2.authorization_grants.abandoned")
+            .with_description("Number of authorization grants abandoned before completion")
+            .with_unit(Unit::new("{grants}"))
+            .init()
+    })
+}
+
+#[derive(Default, Clone)]
+pub struct MarkAbandonedAuthorizationGrantsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for MarkAbandonedAuthorizationGrantsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for MarkAbandonedAuthorizationGrantsJob {
+    const NAME: &'static str = "mark-abandoned-authorization-grants";
+}
+
+impl TracedJob for MarkAbandonedAuthorizationGrantsJob {}
+
+pub async fn mark_abandoned_authorization_grants(
+    job: MarkAbandonedAuthorizationGrantsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!(
+        "mark abandoned authorization grants job scheduled at {}",
+        job.scheduled
+    );
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let clock = state.clock();
+    let mut repo = state.repository().await?;
+
+    // Grants which are still pending after an hour are considered abandoned
+    let started_before = clock.now() - Duration::hours(1);
+    let client_ids = repo
+        .oauth2_authorization_grant()
+        .mark_abandoned(&clock, started_before)
+        .await?;
+    repo.save().await?;
+
+    if client_ids.is_empty() {
+        debug!("no abandoned authorization grant");
+        return Ok(());
+    }
+
+    info!(
+        count = client_ids.len(),
+        "marked authorization grants as abandoned"
+    );
+
+    let mut per_client: HashMap<Ulid, u64> = HashMap::new();
+    for client_id in client_ids {
+        *per_client.entry(client_id).or_default() += 1;
+    }
+
+    let counter = abandoned_grants_counter();
+    for (client_id, count) in per_client {
+        counter.add(count, &[CLIENT_ID.string(client_id.to_string())]);
+    }
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+pub struct PurgeDeletedUsersJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for PurgeDeletedUsersJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for PurgeDeletedUsersJob {
+    const NAME: &'static str = "purge-deleted-users";
+}
+
+impl TracedJob for PurgeDeletedUsersJob {}
+
+pub async fn purge_deleted_users(
+    job: PurgeDeletedUsersJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("purge deleted users job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let Some(grace_period) = state.settings().deleted_users_grace_period else {
+        return Ok(());
+    };
+
+    let clock = state.clock();
+    let deleted_before = clock.now() - grace_period;
+
+    let mut batcher = Batcher::new(&state.settings().batching);
+    let mut total = 0;
+    loop {
+        let started_at = Instant::now();
+        let batch_size = batcher.batch_size();
+        let mut repo = state.repository().await?;
+        let users = repo
+            .user()
+            .list_purgeable(deleted_before, batch_size)
+            .await?;
+        let count = users.len();
+
+        for user in users {
+            info!(user.id = %user.id, user.username = %user.username, "Purging deleted user");
+            let user = repo.user().purge(&clock, user).await?;
+
+            // Erase the user on the homeserver as well
+            repo.job()
+                .schedule_job(DeactivateUserJob::new(&user, true))
+                .await?;
+        }
+
+        repo.save().await?;
+
+        total += count;
+        if count < batch_size {
+            break;
+        }
+
+        batcher.pace(started_at, state.pool()).await;
+    }
+
+    if total == 0 {
+        debug!("no deleted user to purge");
+    } else {
+        info!(count = total, "purged deleted users");
+    }
+
+    Ok(())
+}
+
+#[derive(Default, Clone)]
+pub struct RunBackgroundMigrationsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for RunBackgroundMigrationsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for RunBackgroundMigrationsJob {
+    const NAME: &'static str = "run-background-migrations";
+}
+
+impl TracedJob for RunBackgroundMigrationsJob {}
+
+/// How many rows a background migration processes in a single transaction
+const BACKGROUND_MIGRATION_BATCH_SIZE: usize = 1000;
+
+/// How many batches to run each time the job is scheduled, to avoid hogging
+/// the database
+const BACKGROUND_MIGRATION_MAX_BATCHES: usize = 10;
+
+pub async fn run_background_migrations(
+    job: RunBackgroundMigrationsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!(
+        "run background migrations job scheduled at {}",
+        job.scheduled
+    );
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let clock = state.clock();
+
+    for _ in 0..BACKGROUND_MIGRATION_MAX_BATCHES {
+        let mut repo = state.repository().await?;
+        let migration = repo
+            .background_migration()
+            .run_next_batch(&clock, BACKGROUND_MIGRATION_BATCH_SIZE)
+            .await?;
+
+        let Some(migration) = migration else {
+            repo.cancel().await?;
+            debug!("no background migration to run");
+            break;
+        };
+
+        repo.save().await?;
+
+        if migration.is_completed() {
+            info!(
+                name = %migration.name,
+                processed_rows = migration.processed_rows,
+                "background migration completed"
+            );
+        } else {
+            debug!(
+                name = %migration.name,
+                processed_rows = migration.processed_rows,
+                "background migration progressed"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Latest count of active passwords per hashing scheme version, exposed by
+/// the `mas.user_password.active` gauge
+static ACTIVE_PASSWORDS: Mutex<Vec<(u16, usize)>> = Mutex::new(Vec::new());
+
+#[derive(Default, Clone)]
+pub struct CountPasswordSchemesJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for CountPasswordSchemesJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for CountPasswordSchemesJob {
+    const NAME: &'static str = "count-password-schemes";
+}
+
+impl TracedJob for CountPasswordSchemesJob {}
+
+pub async fn count_password_schemes(
+    job: CountPasswordSchemesJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("count password schemes job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let mut repo = state.repository().await?;
+    let counts = repo.user_password().count_active_by_version().await?;
+    repo.cancel().await?;
+
+    debug!(?counts, "counted active passwords per hashing scheme");
+    *ACTIVE_PASSWORDS.lock().unwrap() = counts;
+
+    Ok(())
+}
+
+fn register_active_passwords_gauge() {
+    let meter = opentelemetry::global::meter_with_version(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None,
+        None,
+    );
+    let gauge = meter
+        .u64_observable_gauge("mas.user_password.active")
+        .with_description("Number of active passwords, per hashing scheme version")
+        .with_unit(Unit::new("{passwords}"))
+        .init();
+    let res = meter.register_callback(&[gauge.as_any()], move |observer| {
+        let counts = ACTIVE_PASSWORDS.lock().unwrap();
+        for (version, count) in counts.iter() {
+            observer.observe_u64(
+                &gauge,
+                *count as u64,
+                &[KeyValue::new("version", i64::from(*version))],
+            );
+        }
+    });
+    if let Err(e) = res {
+        warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to register the active passwords metric"
+        );
+    }
+}
+
+/// Latest statistics of the current day, exposed by the `mas.daily.*` gauges
+static TODAY_STATISTICS: Mutex<Option<DailyStatistics>> = Mutex::new(None);
+
+#[derive(Default, Clone)]
+pub struct ComputeDailyStatisticsJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for ComputeDailyStatisticsJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for ComputeDailyStatisticsJob {
+    const NAME: &'static str = "compute-daily-statistics";
+}
+
+impl TracedJob for ComputeDailyStatisticsJob {}
+
+pub async fn compute_daily_statistics(
+    job: ComputeDailyStatisticsJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!(
+        "compute daily statistics job scheduled at {}",
+        job.scheduled
+    );
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let clock = state.clock();
+    let today = clock.now().date_naive();
+    let mut repo = state.repository().await?;
+
+    // The statistics of the previous day are computed one last time once it
+    // ended, to account for the activity of its last hour
+    if let Some(yesterday) = today.pred_opt() {
+        let previous = repo.statistics().lookup(yesterday).await?;
+        let finalized =
+            previous.is_some_and(|previous| previous.computed_at.date_naive() > yesterday);
+        if !finalized {
+            let statistics = repo.statistics().compute(&clock, yesterday).await?;
+            info!(
+                day = %statistics.day,
+                new_users = statistics.new_users,
+                active_users = statistics.active_users,
+                active_sessions = statistics.active_sessions,
+                tokens_issued = statistics.tokens_issued,
+                "computed the statistics of the previous day"
+            );
+        }
+    }
+
+    let statistics = repo.statistics().compute(&clock, today).await?;
+    repo.save().await?;
+
+    debug!(?statistics, "computed the statistics of the day");
+    *TODAY_STATISTICS.lock().unwrap() = Some(statistics);
+
+    Ok(())
+}
+
+fn register_daily_statistics_gauges() {
+    let meter = opentelemetry::global::meter_with_version(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None,
+        None,
+    );
+    let new_users = meter
+        .u64_observable_gauge("mas.daily.new_users")
+        .with_description("Number of users created today")
+        .with_unit(Unit::new("{users}"))
+        .init();
+    let active_users = meter
+        .u64_observable_gauge("mas.daily.active_users")
+        .with_description("Number of users with a session active today")
+        .with_unit(Unit::new("{users}"))
+        .init();
+    let active_sessions = meter
+        .u64_observable_gauge("mas.daily.active_sessions")
+        .with_description("Number of OAuth 2.0 and compatibility sessions active today")
+        .with_unit(Unit::new("{sessions}"))
+        .init();
+    let tokens_issued = meter
+        .u64_observable_gauge("mas.daily.tokens_issued")
+        .with_description("Number of tokens issued today")
+        .with_unit(Unit::new("{tokens}"))
+        .init();
+    let res = meter.register_callback(
+        &[
+            new_users.as_any(),
+            active_users.as_any(),
+            active_sessions.as_any(),
+            tokens_issued.as_any(),
+        ],
+        move |observer| {
+            let statistics = TODAY_STATISTICS.lock().unwrap();
+            let Some(statistics) = statistics.as_ref() else {
+                return;
+            };
+            observer.observe_u64(&new_users, statistics.new_users, &[]);
+            observer.observe_u64(&active_users, statistics.active_users, &[]);
+            observer.observe_u64(&active_sessions, statistics.active_sessions, &[]);
+            observer.observe_u64(&tokens_issued, statistics.tokens_issued, &[]);
+        },
+    );
+    if let Err(e) = res {
+        warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to register the daily statistics metrics"
+        );
+    }
+}
+
 pub(crate) fn register(
     suffix: &str,
     monitor: Monitor<TokioExecutor>,
@@ -87,5 +694,101 @@ pub(crate) fn register(
         .layer(trace_layer())
         .build_fn(cleanup_expired_tokens);
 
+    let monitor = monitor.register(worker);
+
+    // Buckets of the rate limiters are only stored in the database when using
+    // the PostgreSQL backend, but cleaning up an empty table is cheap
+    let schedule = apalis_cron::Schedule::from_str("0 */5 * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = CleanupRateLimitBucketsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(cleanup_rate_limit_buckets);
+
+    let monitor = monitor.register(worker);
+
+    // Background migrations make progress every few seconds, a few batches at a
+    // time
+    let schedule = apalis_cron::Schedule::from_str("*/10 * * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = RunBackgroundMigrationsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(run_background_migrations);
+
+    let monitor = monitor.register(worker);
+
+    let schedule = apalis_cron::Schedule::from_str("0 */5 * * * *").unwrap();
+    let worker_name = format!(
+        "{job}-{suffix}",
+        job = MarkAbandonedAuthorizationGrantsJob::NAME
+    );
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(mark_abandoned_authorization_grants);
+
+    let monitor = monitor.register(worker);
+
+    // Deleted users past their grace period are purged once an hour
+    let schedule = apalis_cron::Schedule::from_str("0 30 * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = PurgeDeletedUsersJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(purge_deleted_users);
+
+    let monitor = monitor.register(worker);
+
+    // Count which hashing schemes are still in use, to know when an old scheme
+    // can be removed from the configuration
+    register_active_passwords_gauge();
+    let schedule = apalis_cron::Schedule::from_str("0 45 * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = CountPasswordSchemesJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(count_password_schemes);
+
+    let monitor = monitor.register(worker);
+
+    // Usage statistics are computed every hour, and the ones of the previous
+    // day a last time after midnight
+    register_daily_statistics_gauges();
+    let schedule = apalis_cron::Schedule::from_str("0 5 * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = ComputeDailyStatisticsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(compute_daily_statistics);
+
+    let monitor = monitor.register(worker);
+
+    if state.settings().stale_clients_inactivity.is_none() {
+        return monitor;
+    }
+
+    // Stale clients are checked once an hour
+    let schedule = apalis_cron::Schedule::from_str("0 0 * * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = CleanupStaleClientsJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(cleanup_stale_clients);
+
     monitor.register(worker)
 }
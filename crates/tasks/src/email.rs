@@ -17,9 +17,19 @@ use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor
 use chrono::Duration;
 use mas_email::{Address, Mailbox};
 use mas_i18n::locale;
-use mas_storage::job::{JobWithSpanContext, VerifyEmailJob};
-use mas_templates::{EmailVerificationContext, TemplateContext};
-use rand::{distributions::Uniform, Rng};
+use mas_router::{LoginLinkFinish, RecoveryFinish};
+use mas_storage::job::{
+    JobWithSpanContext, SendCompatPasswordDeprecationEmailJob, SendLoginLinkEmailJob,
+    SendPasswordResetEmailJob, SendRegistrationCodeJob, VerifyEmailJob,
+};
+use mas_templates::{
+    EmailCompatPasswordDeprecationContext, EmailLoginLinkContext, EmailPasswordResetContext,
+    EmailRegistrationContext, EmailVerificationContext, TemplateContext,
+};
+use rand::{
+    distributions::{Alphanumeric, DistString, Uniform},
+    Rng,
+};
 use tracing::info;
 
 use crate::{storage::PostgresStorageFactory, JobContextExt, State};
@@ -40,11 +50,6 @@ async fn verify_email(
     let mailer = state.mailer();
     let clock = state.clock();
 
-    let language = job
-        .language()
-        .and_then(|l| l.parse().ok())
-        .unwrap_or(locale!("en").into());
-
     // Lookup the user email
     let user_email = repo
         .user_email()
@@ -59,6 +64,15 @@ async fn verify_email(
         .await?
         .context("User not found")?;
 
+    // The preferred language of the user takes precedence over the language of
+    // the request which triggered the job
+    let language = user
+        .locale
+        .as_deref()
+        .or(job.language())
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
     // Generate a verification code
     let range = Uniform::<u32>::from(0..1_000_000);
     let code = rng.sample(range);
@@ -90,6 +104,295 @@ async fn verify_email(
     Ok(())
 }
 
+#[tracing::instrument(
+    name = "job.send_registration_code",
+    fields(user_registration.id = %job.user_registration_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_registration_code(
+    job: JobWithSpanContext<SendRegistrationCodeJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let mut repo = state.repository().await?;
+    let mailer = state.mailer();
+    let clock = state.clock();
+
+    let language = job
+        .language()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
+    let registration = repo
+        .user_registration()
+        .lookup(job.user_registration_id())
+        .await?
+        .context("User registration not found")?;
+
+    if !registration.is_valid(clock.now()) {
+        info!("User registration is not valid anymore, not sending the code");
+        return Ok(());
+    }
+
+    let address: Address = registration.email.parse()?;
+    let mailbox = Mailbox::new(None, address);
+
+    let context = EmailRegistrationContext::new(registration.clone()).with_language(language);
+
+    mailer.send_registration_email(mailbox, &context).await?;
+
+    info!(
+        user_registration.id = %registration.id,
+        "Registration code sent"
+    );
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "job.send_password_reset_email",
+    fields(user.id = %job.user_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_password_reset_email(
+    job: JobWithSpanContext<SendPasswordResetEmailJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let mut repo = state.repository().await?;
+    let mut rng = state.rng();
+    let mailer = state.mailer();
+    let clock = state.clock();
+
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    if !user.is_valid() {
+        info!("User is locked, not sending a password reset link");
+        return Ok(());
+    }
+
+    // Users asking to recover their account get the link on the address they
+    // gave, as long as it still belongs to them and is verified
+    let requested = job.user_email_id().is_some();
+    let user_email = if let Some(user_email_id) = job.user_email_id() {
+        let user_email = repo.user_email().lookup(user_email_id).await?;
+        match user_email {
+            Some(user_email)
+                if user_email.user_id == user.id && user_email.confirmed_at.is_some() =>
+            {
+                user_email
+            }
+            _ => {
+                info!("User email is gone or not verified, not sending a password reset link");
+                return Ok(());
+            }
+        }
+    } else {
+        let Some(primary_user_email_id) = user.primary_user_email_id else {
+            info!("User has no primary email address, not sending a password reset link");
+            return Ok(());
+        };
+
+        repo.user_email()
+            .lookup(primary_user_email_id)
+            .await?
+            .context("User email not found")?
+    };
+
+    // The preferred language of the user takes precedence over the language of
+    // the request which triggered the job
+    let language = user
+        .locale
+        .as_deref()
+        .or(job.language())
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
+    let ticket = Alphanumeric.sample_string(&mut rng, 32);
+    let ticket = repo
+        .user_recovery()
+        .add_ticket(&mut rng, &clock, &user, ticket, Duration::hours(1))
+        .await?;
+
+    let link = state
+        .url_builder()
+        .absolute_url_for(&RecoveryFinish::new(ticket.ticket.clone()));
+
+    let address: Address = user_email.email.parse()?;
+    let mailbox = Mailbox::new(Some(user.username.clone()), address);
+
+    let context = EmailPasswordResetContext::new(user, ticket.clone(), link);
+    let context = if requested {
+        context.requested_by_user()
+    } else {
+        context
+    };
+    let context = context.with_language(language);
+
+    mailer.send_password_reset_email(mailbox, &context).await?;
+
+    info!(
+        user_recovery_ticket.id = %ticket.id,
+        "Password reset email sent"
+    );
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "job.send_login_link_email",
+    fields(user_login_link.id = %job.user_login_link_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_login_link_email(
+    job: JobWithSpanContext<SendLoginLinkEmailJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let mut repo = state.repository().await?;
+    let mailer = state.mailer();
+    let clock = state.clock();
+
+    // The link is gone if the email address was removed in the meantime
+    let Some(login_link) = repo
+        .user_login_link()
+        .lookup(job.user_login_link_id())
+        .await?
+    else {
+        info!("Login link not found, not sending it");
+        return Ok(());
+    };
+
+    if !login_link.is_valid(clock.now()) {
+        info!("Login link was already used or expired, not sending it");
+        return Ok(());
+    }
+
+    let user = repo
+        .user()
+        .lookup(login_link.user_id)
+        .await?
+        .context("User not found")?;
+
+    if !user.is_valid() {
+        info!("User is locked, not sending a login link");
+        return Ok(());
+    }
+
+    let user_email = repo
+        .user_email()
+        .lookup(login_link.user_email_id)
+        .await?
+        .context("User email not found")?;
+
+    // The preferred language of the user takes precedence over the language of
+    // the request which triggered the job
+    let language = user
+        .locale
+        .as_deref()
+        .or(job.language())
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
+    let link = state
+        .url_builder()
+        .absolute_url_for(&LoginLinkFinish::new(login_link.token.clone()));
+
+    let address: Address = user_email.email.parse()?;
+    let mailbox = Mailbox::new(Some(user.username.clone()), address);
+
+    let context = EmailLoginLinkContext::new(user, login_link, link).with_language(language);
+
+    mailer.send_login_link_email(mailbox, &context).await?;
+
+    info!("Login link email sent");
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "job.send_compat_password_deprecation_email",
+    fields(user.id = %job.user_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_compat_password_deprecation_email(
+    job: JobWithSpanContext<SendCompatPasswordDeprecationEmailJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let mut repo = state.repository().await?;
+    let mailer = state.mailer();
+    let clock = state.clock();
+
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    if !user.is_valid() {
+        info!("User is locked, not sending a password login deprecation warning");
+        return Ok(());
+    }
+
+    // The deprecation may have been cleared before the job ran
+    if !user.compat_password_deprecated() {
+        info!("Password login is no longer deprecated, not sending a warning");
+        return Ok(());
+    }
+
+    let Some(primary_user_email_id) = user.primary_user_email_id else {
+        info!(
+            "User has no primary email address, not sending a password login deprecation warning"
+        );
+        return Ok(());
+    };
+
+    let user_email = repo
+        .user_email()
+        .lookup(primary_user_email_id)
+        .await?
+        .context("User email not found")?;
+
+    let language = user
+        .locale
+        .as_deref()
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(locale!("en").into());
+
+    // Round up, so that users are never told they have more time than they do
+    let seconds_left = (job.blocked_at() - clock.now()).num_seconds().max(0);
+    let days_left = (seconds_left + 86_399) / 86_400;
+
+    let address: Address = user_email.email.parse()?;
+    let mailbox = Mailbox::new(Some(user.username.clone()), address);
+
+    let context =
+        EmailCompatPasswordDeprecationContext::new(user, days_left).with_language(language);
+
+    mailer
+        .send_compat_password_deprecation_email(mailbox, &context)
+        .await?;
+
+    info!("Password login deprecation warning sent");
+
+    Ok(())
+}
+
 pub(crate) fn register(
     suffix: &str,
     monitor: Monitor<TokioExecutor>,
@@ -98,6 +401,15 @@ pub(crate) fn register(
 ) -> Monitor<TokioExecutor> {
     let verify_email_worker =
         crate::build!(VerifyEmailJob => verify_email, suffix, state, storage_factory);
+    let send_registration_code_worker = crate::build!(SendRegistrationCodeJob => send_registration_code, suffix, state, storage_factory);
+    let send_password_reset_email_worker = crate::build!(SendPasswordResetEmailJob => send_password_reset_email, suffix, state, storage_factory);
+    let send_login_link_email_worker = crate::build!(SendLoginLinkEmailJob => send_login_link_email, suffix, state, storage_factory);
+    let send_compat_password_deprecation_email_worker = crate::build!(SendCompatPasswordDeprecationEmailJob => send_compat_password_deprecation_email, suffix, state, storage_factory);
 
-    monitor.register(verify_email_worker)
+    monitor
+        .register(verify_email_worker)
+        .register(send_registration_code_worker)
+        .register(send_password_reset_email_worker)
+        .register(send_login_link_email_worker)
+        .register(send_compat_password_deprecation_email_worker)
 }
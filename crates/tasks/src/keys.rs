@@ -0,0 +1,130 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Monitoring of the signing keys expiration
+
+use std::{str::FromStr, sync::Arc};
+
+use apalis_core::{
+    builder::{WorkerBuilder, WorkerFactoryFn},
+    context::JobContext,
+    executor::TokioExecutor,
+    job::Job,
+    monitor::Monitor,
+    utils::timer::TokioTimer,
+};
+use apalis_cron::CronStream;
+use chrono::{DateTime, Utc};
+use mas_storage::{Clock, SystemClock};
+use opentelemetry::{metrics::Unit, KeyValue};
+use tracing::{debug, error, warn};
+
+use crate::{
+    utils::{metrics_layer, trace_layer, TracedJob},
+    JobContextExt, State,
+};
+
+#[derive(Default, Clone)]
+pub struct CheckKeyExpiryJob {
+    scheduled: DateTime<Utc>,
+}
+
+impl From<DateTime<Utc>> for CheckKeyExpiryJob {
+    fn from(scheduled: DateTime<Utc>) -> Self {
+        Self { scheduled }
+    }
+}
+
+impl Job for CheckKeyExpiryJob {
+    const NAME: &'static str = "check-key-expiry";
+}
+
+impl TracedJob for CheckKeyExpiryJob {}
+
+pub async fn check_key_expiry(
+    job: CheckKeyExpiryJob,
+    ctx: JobContext,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+    debug!("check key expiry job scheduled at {}", job.scheduled);
+
+    let state = ctx.state();
+    if !state.is_leader() {
+        debug!("not the leader, skipping");
+        return Ok(());
+    }
+
+    let Some(settings) = &state.settings().key_expiry else {
+        return Ok(());
+    };
+
+    let now = state.clock().now();
+    for (kid, expires_at) in &settings.expirations {
+        if *expires_at <= now {
+            error!(kid = kid.as_str(), %expires_at, "Signing key has expired");
+        } else if *expires_at - settings.warning_period <= now {
+            warn!(kid = kid.as_str(), %expires_at, "Signing key is about to expire");
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+) -> Monitor<TokioExecutor> {
+    let Some(settings) = &state.settings().key_expiry else {
+        return monitor;
+    };
+
+    // Expose the time left before each key expires
+    let meter = opentelemetry::global::meter_with_version(
+        env!("CARGO_PKG_NAME"),
+        Some(env!("CARGO_PKG_VERSION")),
+        None,
+        None,
+    );
+    let gauge = meter
+        .i64_observable_gauge("mas.secrets.key.expires_in")
+        .with_description("The time left before the signing key expires")
+        .with_unit(Unit::new("s"))
+        .init();
+    let expirations = Arc::new(settings.expirations.clone());
+    let res = meter.register_callback(&[gauge.as_any()], move |observer| {
+        let now = SystemClock::default().now();
+        for (kid, expires_at) in expirations.iter() {
+            let expires_in = (*expires_at - now).num_seconds();
+            observer.observe_i64(&gauge, expires_in, &[KeyValue::new("kid", kid.clone())]);
+        }
+    });
+    if let Err(e) = res {
+        warn!(
+            error = &e as &dyn std::error::Error,
+            "Failed to register the key expiry metric"
+        );
+    }
+
+    // Keys are checked once a day
+    let schedule = apalis_cron::Schedule::from_str("0 0 0 * * *").unwrap();
+    let worker_name = format!("{job}-{suffix}", job = CheckKeyExpiryJob::NAME);
+    let worker = WorkerBuilder::new(worker_name)
+        .stream(CronStream::new(schedule).timer(TokioTimer).to_stream())
+        .layer(state.inject())
+        .layer(metrics_layer())
+        .layer(trace_layer())
+        .build_fn(check_key_expiry);
+
+    monitor.register(worker)
+}
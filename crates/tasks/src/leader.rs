@@ -0,0 +1,131 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Leader election between workers, backed by a PostgreSQL advisory lock
+//!
+//! When multiple workers are running, scheduled jobs would otherwise run once
+//! per worker. The worker holding the advisory lock is considered the leader,
+//! and is the only one running the singleton scheduled jobs. Jobs consuming
+//! the queue are not affected and still run on every worker.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use sqlx::{PgConnection, PgPool};
+use tracing::{debug, info, warn};
+
+/// Arbitrary key identifying the leader lock. This is "mas_lead" in ASCII.
+const LEADER_LOCK_KEY: i64 = 0x6d61_735f_6c65_6164;
+
+/// How often a follower tries to become the leader
+const ACQUIRE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How often the leader checks that its connection, and therefore the lock, is
+/// still alive
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Tracks whether this worker is currently the leader
+#[derive(Clone, Default)]
+pub struct LeaderElection {
+    is_leader: Arc<AtomicBool>,
+}
+
+impl LeaderElection {
+    /// Start the leader election in the background
+    pub fn spawn(pool: PgPool) -> Self {
+        let election = Self::default();
+        tokio::spawn(election.clone().run(pool));
+        election
+    }
+
+    /// Whether this worker is currently the leader
+    pub fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::Relaxed)
+    }
+
+    async fn run(self, pool: PgPool) {
+        loop {
+            if let Err(e) = self.campaign(&pool).await {
+                if self.is_leader() {
+                    warn!(error = &e as &dyn std::error::Error, "Lost the leader lock");
+                } else {
+                    warn!(
+                        error = &e as &dyn std::error::Error,
+                        "Failed to acquire the leader lock"
+                    );
+                }
+            }
+
+            self.is_leader.store(false, Ordering::Relaxed);
+            tokio::time::sleep(ACQUIRE_INTERVAL).await;
+        }
+    }
+
+    /// Try to acquire the lock, and hold it for as long as the underlying
+    /// connection is alive
+    ///
+    /// The advisory lock is bound to the database session, so we take a
+    /// connection out of the pool to make sure it is not released or shared
+    /// with other queries.
+    async fn campaign(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut conn = pool.acquire().await?.detach();
+
+        while !try_lock(&mut conn).await? {
+            debug!("Another worker is the leader");
+            tokio::time::sleep(ACQUIRE_INTERVAL).await;
+        }
+
+        info!("This worker is now the leader, it will run the scheduled jobs");
+        self.is_leader.store(true, Ordering::Relaxed);
+
+        // If the connection goes away, PostgreSQL releases the lock, so we check
+        // regularly that it is still alive
+        loop {
+            tokio::time::sleep(KEEPALIVE_INTERVAL).await;
+            sqlx::query("SELECT 1").execute(&mut conn).await?;
+        }
+    }
+}
+
+async fn try_lock(conn: &mut PgConnection) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+        .bind(LEADER_LOCK_KEY)
+        .fetch_one(conn)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_lite::FutureExt;
+
+    use super::*;
+
+    #[sqlx::test(migrations = false)]
+    async fn test_single_leader(pool: PgPool) {
+        let first = LeaderElection::default();
+        let second = LeaderElection::default();
+
+        // Campaigns only return on errors, so let both compete for a while
+        let campaigns = first.campaign(&pool).or(second.campaign(&pool));
+        let res = tokio::time::timeout(Duration::from_millis(500), campaigns).await;
+        assert!(res.is_err(), "A campaign stopped early: {res:?}");
+
+        assert!(first.is_leader() != second.is_leader());
+    }
+}
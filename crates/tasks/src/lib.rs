@@ -15,43 +15,129 @@
 use std::sync::Arc;
 
 use apalis_core::{executor::TokioExecutor, layers::extensions::Extension, monitor::Monitor};
+use mas_axum_utils::http_client_factory::HttpClientFactory;
 use mas_email::Mailer;
+use mas_keystore::Keystore;
 use mas_matrix::HomeserverConnection;
+use mas_router::UrlBuilder;
 use mas_storage::{BoxClock, BoxRepository, Repository, SystemClock};
 use mas_storage_pg::{DatabaseError, PgRepository};
 use rand::SeedableRng;
 use sqlx::{Pool, Postgres};
 use tracing::debug;
+use url::Url;
 
-use crate::storage::PostgresStorageFactory;
+use crate::{leader::LeaderElection, storage::PostgresStorageFactory};
 
+mod batch;
+mod data_export;
 mod database;
 mod email;
+mod keys;
+mod leader;
 mod matrix;
+mod oauth2;
 mod storage;
 mod user;
 mod utils;
 
+/// Settings of the background tasks
+#[derive(Debug, Clone, Default)]
+pub struct TasksSettings {
+    /// How long a dynamically-registered client has to be inactive before it
+    /// gets deleted. `None` disables the cleanup of stale clients.
+    pub stale_clients_inactivity: Option<chrono::Duration>,
+
+    /// Monitoring of the signing keys expiration. `None` if no signing key
+    /// has an expiration time.
+    pub key_expiry: Option<KeyExpirySettings>,
+
+    /// How long a soft-deleted user can be restored before it gets purged.
+    /// `None` disables the purge of deleted users.
+    pub deleted_users_grace_period: Option<chrono::Duration>,
+
+    /// How long failed login attempts are remembered. `None` if the login
+    /// lockout is disabled.
+    pub login_failures_retention: Option<chrono::Duration>,
+
+    /// Pace of the jobs deleting rows in batches
+    pub batching: BatchingSettings,
+}
+
+/// Settings of the monitoring of the signing keys expiration
+#[derive(Debug, Clone)]
+pub struct KeyExpirySettings {
+    /// Expiration time of the signing keys, by key ID
+    pub expirations: Vec<(String, chrono::DateTime<chrono::Utc>)>,
+
+    /// How long before a key expires to start warning about it
+    pub warning_period: chrono::Duration,
+}
+
+/// Limits within which the cleanup jobs adapt the size of their batches and
+/// the pause between them
+#[derive(Debug, Clone)]
+pub struct BatchingSettings {
+    /// Smallest number of rows deleted in a single batch
+    pub min_batch_size: usize,
+
+    /// Largest number of rows deleted in a single batch
+    pub max_batch_size: usize,
+
+    /// How long a single batch should take
+    pub target_batch_duration: std::time::Duration,
+
+    /// Longest pause between two batches
+    pub max_pause: std::time::Duration,
+}
+
+impl Default for BatchingSettings {
+    fn default() -> Self {
+        Self {
+            min_batch_size: 10,
+            max_batch_size: 1000,
+            target_batch_duration: std::time::Duration::from_millis(500),
+            max_pause: std::time::Duration::from_secs(10),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct State {
     pool: Pool<Postgres>,
     mailer: Mailer,
     clock: SystemClock,
     homeserver: Arc<dyn HomeserverConnection<Error = anyhow::Error>>,
+    settings: Arc<TasksSettings>,
+    leader: LeaderElection,
+    key_store: Keystore,
+    url_builder: UrlBuilder,
+    http_client_factory: HttpClientFactory,
 }
 
 impl State {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pool: Pool<Postgres>,
         clock: SystemClock,
         mailer: Mailer,
         homeserver: impl HomeserverConnection<Error = anyhow::Error> + 'static,
+        settings: TasksSettings,
+        leader: LeaderElection,
+        key_store: Keystore,
+        url_builder: UrlBuilder,
+        http_client_factory: HttpClientFactory,
     ) -> Self {
         Self {
             pool,
             mailer,
             clock,
             homeserver: Arc::new(homeserver),
+            settings: Arc::new(settings),
+            leader,
+            key_store,
+            url_builder,
+            http_client_factory,
         }
     }
 
@@ -89,6 +175,31 @@ impl State {
     pub fn matrix_connection(&self) -> &dyn HomeserverConnection<Error = anyhow::Error> {
         self.homeserver.as_ref()
     }
+
+    pub fn settings(&self) -> &TasksSettings {
+        &self.settings
+    }
+
+    /// Whether this worker should run the singleton scheduled jobs
+    pub fn is_leader(&self) -> bool {
+        self.leader.is_leader()
+    }
+
+    pub fn key_store(&self) -> &Keystore {
+        &self.key_store
+    }
+
+    pub fn issuer(&self) -> Url {
+        self.url_builder.oidc_issuer()
+    }
+
+    pub fn url_builder(&self) -> &UrlBuilder {
+        &self.url_builder
+    }
+
+    pub fn http_client_factory(&self) -> &HttpClientFactory {
+        &self.http_client_factory
+    }
 }
 
 trait JobContextExt {
@@ -118,10 +229,12 @@ macro_rules! build {
             .layer(crate::utils::trace_layer())
             .layer(crate::utils::metrics_layer());
 
+        // Workers are woken up as soon as jobs of their type are inserted, so this
+        // only has to catch jobs scheduled in the future and retries
         let builder = ::apalis_core::storage::builder::WithStorage::with_storage_config(
             builder,
             storage,
-            |c| c.fetch_interval(std::time::Duration::from_secs(1)),
+            |c| c.fetch_interval(std::time::Duration::from_secs(10)),
         );
         ::apalis_core::builder::WorkerFactory::build(builder, ::apalis_core::job_fn::job_fn($fn))
     }};
@@ -134,23 +247,36 @@ pub(crate) use build;
 /// # Errors
 ///
 /// This function can fail if the database connection fails.
+#[allow(clippy::too_many_arguments)]
 pub async fn init(
     name: &str,
     pool: &Pool<Postgres>,
     mailer: &Mailer,
     homeserver: impl HomeserverConnection<Error = anyhow::Error> + 'static,
+    settings: TasksSettings,
+    key_store: &Keystore,
+    url_builder: &UrlBuilder,
+    http_client_factory: &HttpClientFactory,
 ) -> Result<Monitor<TokioExecutor>, sqlx::Error> {
     let state = State::new(
         pool.clone(),
         SystemClock::default(),
         mailer.clone(),
         homeserver,
+        settings,
+        LeaderElection::spawn(pool.clone()),
+        key_store.clone(),
+        url_builder.clone(),
+        http_client_factory.clone(),
     );
     let factory = PostgresStorageFactory::new(pool.clone());
     let monitor = Monitor::new().executor(TokioExecutor::new());
     let monitor = self::database::register(name, monitor, &state);
+    let monitor = self::data_export::register(name, monitor, &state, &factory);
     let monitor = self::email::register(name, monitor, &state, &factory);
+    let monitor = self::keys::register(name, monitor, &state);
     let monitor = self::matrix::register(name, monitor, &state, &factory);
+    let monitor = self::oauth2::register(name, monitor, &state, &factory);
     let monitor = self::user::register(name, monitor, &state, &factory);
     // TODO: we might want to grab the join handle here
     factory.listen().await?;
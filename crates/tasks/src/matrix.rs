@@ -48,14 +48,17 @@ async fn provision_user(
         .context("User not found")?;
 
     let mxid = matrix.mxid(&user.username);
-    let emails = repo
+    // Only verified addresses are bound on the homeserver, with the primary
+    // one first
+    let mut emails: Vec<_> = repo
         .user_email()
         .all(&user)
         .await?
         .into_iter()
-        .filter(|email| email.confirmed_at.is_some())
-        .map(|email| email.email)
+        .filter(|email| email.is_verified())
         .collect();
+    emails.sort_by_key(|email| !user.is_primary_email(email));
+    let emails = emails.into_iter().map(|email| email.email).collect();
 
     repo.cancel().await?;
 
@@ -104,8 +107,20 @@ async fn provision_device(
 
     let mxid = matrix.mxid(&user.username);
 
-    matrix.create_device(&mxid, job.device_id()).await?;
-    info!(%user.id, %mxid, device.id = job.device_id(), "Device created");
+    if job.replaces_existing() {
+        matrix.replace_device(&mxid, job.device_id()).await?;
+        info!(%user.id, %mxid, device.id = job.device_id(), "Device replaced");
+    } else {
+        matrix.create_device(&mxid, job.device_id()).await?;
+        info!(%user.id, %mxid, device.id = job.device_id(), "Device created");
+    }
+
+    if let Some(display_name) = job.display_name_to_set() {
+        matrix
+            .update_device_display_name(&mxid, job.device_id(), display_name)
+            .await?;
+        info!(%user.id, %mxid, device.id = job.device_id(), "Device display name set");
+    }
 
     Ok(())
 }
@@ -0,0 +1,155 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use anyhow::Context;
+use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use bytes::Bytes;
+use http::{header::CONTENT_TYPE, Method, Request};
+use mas_iana::jose::JsonWebSignatureAlg;
+use mas_jose::logout::LogoutToken;
+use mas_storage::{
+    job::{JobWithSpanContext, SendBackchannelLogoutJob},
+    oauth2::{OAuth2ClientRepository, OAuth2SessionRepository},
+    user::UserRepository,
+    RepositoryAccess,
+};
+use tower::{Service, ServiceExt};
+use tracing::info;
+use url::form_urlencoded;
+
+use crate::{storage::PostgresStorageFactory, JobContextExt, State};
+
+/// Job to notify a client that one of its sessions ended, by sending a logout
+/// token to its back-channel logout URI.
+///
+/// A non-successful response from the client makes the job fail, so that it
+/// gets retried later.
+#[tracing::instrument(
+    name = "job.send_backchannel_logout"
+    fields(oauth2_session.id = %job.oauth2_session_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn send_backchannel_logout(
+    job: JobWithSpanContext<SendBackchannelLogoutJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut rng = state.rng();
+    let mut repo = state.repository().await?;
+
+    let session = repo
+        .oauth2_session()
+        .lookup(job.oauth2_session_id())
+        .await?
+        .context("OAuth 2.0 session not found")?;
+
+    let client = repo
+        .oauth2_client()
+        .lookup(session.client_id)
+        .await?
+        .context("Client not found")?;
+
+    let Some(backchannel_logout_uri) = client.backchannel_logout_uri.clone() else {
+        info!(%client.id, "Client has no back-channel logout URI, skipping");
+        return Ok(());
+    };
+
+    let Some(user_id) = session.user_id else {
+        info!(%client.id, "Session has no user, skipping");
+        return Ok(());
+    };
+
+    let user = repo
+        .user()
+        .lookup(user_id)
+        .await?
+        .context("User not found")?;
+
+    repo.cancel().await?;
+
+    let mut logout_token = LogoutToken::new(
+        state.issuer().as_str(),
+        client.client_id.clone(),
+        clock.now(),
+    )
+    .with_subject(user.sub.clone());
+
+    if let Some(user_session_id) = session.user_session_id {
+        logout_token = logout_token.with_session_id(user_session_id.to_string());
+    } else if client.backchannel_logout_session_required {
+        // The client wants a `sid` claim, but this session is not tied to a
+        // browser session: there is no way we can notify it
+        info!(%client.id, "Client requires a session ID but the session has none, skipping");
+        return Ok(());
+    }
+
+    let alg = client
+        .id_token_signed_response_alg
+        .clone()
+        .unwrap_or(JsonWebSignatureAlg::Rs256);
+    let key = state
+        .key_store()
+        .signing_key_for_algorithm(&alg)
+        .context("No signing key available for the algorithm")?;
+    let signer = key.params().signing_key_for_alg(&alg)?;
+    let kid = key.kid().map(ToOwned::to_owned);
+
+    let logout_token = logout_token.sign_with_rng(&mut rng, alg, kid, &signer)?;
+
+    let body = form_urlencoded::Serializer::new(String::new())
+        .append_pair("logout_token", logout_token.as_str())
+        .finish();
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(backchannel_logout_uri.as_str())
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Bytes::from(body))?;
+
+    let mut http_service = state
+        .http_client_factory()
+        .http_service("oauth2.backchannel_logout");
+
+    let response = http_service
+        .ready()
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?
+        .call(request)
+        .await
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "Client responded with status {} to the back-channel logout request",
+            response.status()
+        );
+    }
+
+    info!(%client.id, %user.id, "Back-channel logout notification sent");
+
+    Ok(())
+}
+
+pub(crate) fn register(
+    suffix: &str,
+    monitor: Monitor<TokioExecutor>,
+    state: &State,
+    storage_factory: &PostgresStorageFactory,
+) -> Monitor<TokioExecutor> {
+    let send_backchannel_logout_worker = crate::build!(SendBackchannelLogoutJob => send_backchannel_logout, suffix, state, storage_factory);
+
+    monitor.register(send_backchannel_logout_worker)
+}
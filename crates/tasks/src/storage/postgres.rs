@@ -12,7 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{convert::TryInto, marker::PhantomData, ops::Add, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    marker::PhantomData,
+    ops::Add,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use apalis_core::{
     error::JobStreamError,
@@ -32,16 +39,21 @@ use tokio::task::JoinHandle;
 
 use super::SqlJobRequest;
 
+/// How long to wait before listening again to job notifications after the
+/// connection dropped
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
 pub struct StorageFactory {
     pool: PgPool,
-    event: Arc<Event>,
+    /// One event per job type, notified when jobs of that type are inserted
+    events: Arc<Mutex<HashMap<&'static str, Arc<Event>>>>,
 }
 
 impl StorageFactory {
     pub fn new(pool: Pool<Postgres>) -> Self {
         StorageFactory {
             pool,
-            event: Arc::new(Event::new()),
+            events: Arc::default(),
         }
     }
 
@@ -51,19 +63,50 @@ impl StorageFactory {
 
         let handle = tokio::spawn(async move {
             loop {
-                let notification = listener.recv().await.expect("Failed to poll notification");
-                self.event.notify(usize::MAX);
-                tracing::debug!(?notification, "Broadcast notification");
+                match listener.recv().await {
+                    Ok(notification) => {
+                        // The payload is the type of the inserted job, so only the workers of
+                        // that type wake up
+                        let events = self.events.lock().expect("lock poisoned");
+                        if let Some(event) = events.get(notification.payload()) {
+                            event.notify(usize::MAX);
+                        }
+                        tracing::debug!(?notification, "Broadcast notification");
+                    }
+
+                    Err(e) => {
+                        tracing::error!(
+                            error = &e as &dyn std::error::Error,
+                            "Failed to receive job notifications, retrying in 5 seconds"
+                        );
+
+                        // Notifications might have been missed while disconnected, so wake up
+                        // all the workers
+                        for event in self.events.lock().expect("lock poisoned").values() {
+                            event.notify(usize::MAX);
+                        }
+
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
             }
         });
 
         Ok(handle)
     }
 
-    pub fn build<T>(&self) -> Storage<T> {
+    pub fn build<T: Job>(&self) -> Storage<T> {
+        let event = self
+            .events
+            .lock()
+            .expect("lock poisoned")
+            .entry(T::NAME)
+            .or_insert_with(|| Arc::new(Event::new()))
+            .clone();
+
         Storage {
             pool: self.pool.clone(),
-            event: self.event.clone(),
+            event,
             job_type: PhantomData,
         }
     }
@@ -14,10 +14,20 @@
 
 use anyhow::Context;
 use apalis_core::{context::JobContext, executor::TokioExecutor, monitor::Monitor};
+use chrono::{DateTime, Utc};
+use mas_data_model::{SignInSession, User};
 use mas_storage::{
-    job::{DeactivateUserJob, JobWithSpanContext},
-    user::UserRepository,
-    RepositoryAccess,
+    compat::{CompatSessionFilter, CompatSessionRepository},
+    job::{
+        DeactivateUserJob, DeleteDeviceJob, EndUserSessionsJob, ForcePasswordResetJob,
+        JobRepositoryExt, JobWithSpanContext, NotifyNewSignInJob, SendPasswordResetEmailJob,
+    },
+    oauth2::{end_session, OAuth2SessionFilter, OAuth2SessionRepository},
+    user::{
+        BrowserSessionFilter, BrowserSessionRepository, UserRepository,
+        UserSignInNotificationRepository,
+    },
+    BoxRepository, Clock, Pagination, RepositoryAccess,
 };
 use tracing::info;
 
@@ -52,7 +62,8 @@ async fn deactivate_user(
         .await
         .context("Failed to lock user")?;
 
-    // TODO: delete the sessions & access tokens
+    // End all the sessions, which also revokes their tokens
+    end_user_sessions(&mut repo, &clock, &user, None).await?;
 
     // Before calling back to the homeserver, commit the changes to the database
     repo.save().await?;
@@ -64,6 +75,250 @@ async fn deactivate_user(
     Ok(())
 }
 
+/// End the sessions of a user, along with their devices on the homeserver.
+///
+/// If `keep_since` is set, the sessions created at or after that time are
+/// kept.
+async fn end_user_sessions(
+    repo: &mut BoxRepository,
+    clock: &dyn Clock,
+    user: &User,
+    keep_since: Option<DateTime<Utc>>,
+) -> Result<(), anyhow::Error> {
+    let should_end =
+        |created_at: DateTime<Utc>| keep_since.map_or(true, |since| created_at < since);
+
+    // Kept sessions stay in the list, so the cursor has to move past them
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo
+            .compat_session()
+            .list(
+                CompatSessionFilter::new().for_user(user).active_only(),
+                pagination,
+            )
+            .await?;
+
+        for (compat_session, _) in page.edges {
+            pagination = pagination.after(compat_session.id);
+            if !should_end(compat_session.created_at) {
+                continue;
+            }
+
+            info!(%compat_session.id, %compat_session.device, "Ending compat session");
+            repo.job()
+                .schedule_job(DeleteDeviceJob::new(user, &compat_session.device))
+                .await?;
+            repo.compat_session().finish(clock, compat_session).await?;
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo
+            .oauth2_session()
+            .list(
+                OAuth2SessionFilter::new().for_user(user).active_only(),
+                pagination,
+            )
+            .await?;
+
+        for oauth2_session in page.edges {
+            pagination = pagination.after(oauth2_session.id);
+            if !should_end(oauth2_session.created_at) {
+                continue;
+            }
+
+            // Derived sessions end along with their parent session, which was
+            // created before them
+            if oauth2_session.is_derived() {
+                continue;
+            }
+
+            info!(%oauth2_session.id, %oauth2_session.scope, "Ending OAuth 2.0 session");
+            end_session(repo, clock, oauth2_session).await?;
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    let mut pagination = Pagination::first(100);
+    loop {
+        let page = repo
+            .browser_session()
+            .list(
+                BrowserSessionFilter::new().for_user(user).active_only(),
+                pagination,
+            )
+            .await?;
+
+        for browser_session in page.edges {
+            pagination = pagination.after(browser_session.id);
+            if !should_end(browser_session.created_at) {
+                continue;
+            }
+
+            info!(%browser_session.id, "Ending browser session");
+            repo.browser_session()
+                .finish(clock, browser_session)
+                .await?;
+        }
+
+        if !page.has_next_page {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Job to end all the sessions of a user who must reset their password, and
+/// send them a link to choose a new one.
+#[tracing::instrument(
+    name = "job.force_password_reset"
+    fields(user.id = %job.user_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn force_password_reset(
+    job: JobWithSpanContext<ForcePasswordResetJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut repo = state.repository().await?;
+
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    end_user_sessions(&mut repo, &clock, &user, None).await?;
+
+    // Now that the user is signed out everywhere, send them a link to choose a
+    // new password
+    repo.job()
+        .schedule_job(SendPasswordResetEmailJob::new(&user))
+        .await?;
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+/// Job to end all the sessions of a user, except the ones created after it was
+/// scheduled.
+#[tracing::instrument(
+    name = "job.end_user_sessions"
+    fields(user.id = %job.user_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn end_user_sessions_job(
+    job: JobWithSpanContext<EndUserSessionsJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut repo = state.repository().await?;
+
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    end_user_sessions(&mut repo, &clock, &user, job.kept_sessions_since()).await?;
+
+    repo.save().await?;
+
+    Ok(())
+}
+
+/// Job to notify the other sessions of a user that they signed in somewhere
+/// else. The notification is shown in the other browser sessions, so nothing
+/// is recorded if there is none.
+#[tracing::instrument(
+    name = "job.notify_new_sign_in"
+    fields(user.id = %job.user_id()),
+    skip_all,
+    err(Debug),
+)]
+async fn notify_new_sign_in(
+    job: JobWithSpanContext<NotifyNewSignInJob>,
+    ctx: JobContext,
+) -> Result<(), anyhow::Error> {
+    let state = ctx.state();
+    let clock = state.clock();
+    let mut rng = state.rng();
+    let mut repo = state.repository().await?;
+
+    let session = job.session().context("Job has no session")?;
+    let user = repo
+        .user()
+        .lookup(job.user_id())
+        .await?
+        .context("User not found")?;
+
+    let active_browser_sessions = repo
+        .browser_session()
+        .count(BrowserSessionFilter::new().for_user(&user).active_only())
+        .await?;
+
+    // A new browser session is one of the active ones
+    let other_browser_sessions = match session {
+        SignInSession::Browser(_) => active_browser_sessions.saturating_sub(1),
+        SignInSession::Compat(_) => active_browser_sessions,
+    };
+
+    if other_browser_sessions == 0 {
+        info!("No other browser session to notify of the new sign-in");
+        return Ok(());
+    }
+
+    let notification = match session {
+        SignInSession::Browser(id) => {
+            let browser_session = repo
+                .browser_session()
+                .lookup(id)
+                .await?
+                .context("Browser session not found")?;
+
+            repo.user_sign_in_notification()
+                .add_for_browser_session(&mut rng, &clock, &browser_session)
+                .await?
+        }
+
+        SignInSession::Compat(id) => {
+            let compat_session = repo
+                .compat_session()
+                .lookup(id)
+                .await?
+                .context("Compat session not found")?;
+
+            repo.user_sign_in_notification()
+                .add_for_compat_session(&mut rng, &clock, &compat_session)
+                .await?
+        }
+    };
+
+    info!(
+        user_sign_in_notification.id = %notification.id,
+        "Notifying {other_browser_sessions} other browser sessions of the new sign-in"
+    );
+
+    repo.save().await?;
+
+    Ok(())
+}
+
 pub(crate) fn register(
     suffix: &str,
     monitor: Monitor<TokioExecutor>,
@@ -73,5 +328,17 @@ pub(crate) fn register(
     let deactivate_user_worker =
         crate::build!(DeactivateUserJob => deactivate_user, suffix, state, storage_factory);
 
-    monitor.register(deactivate_user_worker)
+    let force_password_reset_worker = crate::build!(ForcePasswordResetJob => force_password_reset, suffix, state, storage_factory);
+
+    let end_user_sessions_worker =
+        crate::build!(EndUserSessionsJob => end_user_sessions_job, suffix, state, storage_factory);
+
+    let notify_new_sign_in_worker =
+        crate::build!(NotifyNewSignInJob => notify_new_sign_in, suffix, state, storage_factory);
+
+    monitor
+        .register(deactivate_user_worker)
+        .register(force_password_reset_worker)
+        .register(end_user_sessions_worker)
+        .register(notify_new_sign_in_worker)
 }
@@ -16,13 +16,15 @@
 
 mod branding;
 
-use std::fmt::Formatter;
+use std::{collections::BTreeMap, fmt::Formatter};
 
 use chrono::{DateTime, Utc};
 use http::{Method, Uri, Version};
 use mas_data_model::{
-    AuthorizationGrant, BrowserSession, Client, CompatSsoLogin, CompatSsoLoginState,
-    UpstreamOAuthLink, UpstreamOAuthProvider, User, UserEmail, UserEmailVerification,
+    AuthorizationGrant, BrowserSession, CaptchaConfig, CaptchaService, Client, CompatSsoLogin,
+    CompatSsoLoginState, DeviceCodeGrant, DeviceCodeGrantState, TermsOfService, UpstreamOAuthLink,
+    UpstreamOAuthProvider, User, UserDataExport, UserEmail, UserEmailVerification, UserLoginLink,
+    UserRecoveryCode, UserRecoveryTicket, UserRegistration, WebauthnCredential,
 };
 use mas_i18n::DataLocale;
 use mas_router::{Account, GraphQL, PostAuthAction, UrlBuilder};
@@ -32,7 +34,7 @@ use ulid::Ulid;
 use url::Url;
 
 pub use self::branding::SiteBranding;
-use crate::{FieldError, FormField, FormState};
+use crate::{FieldError, FormError, FormField, FormState};
 
 /// Helper trait to construct context wrappers
 pub trait TemplateContext: Serialize {
@@ -283,6 +285,8 @@ impl TemplateContext for IndexContext {
 pub struct AppConfig {
     root: String,
     graphql_endpoint: String,
+    embedded: bool,
+    embedding_origins: Vec<String>,
 }
 
 /// Context used by the `app.html` template
@@ -301,9 +305,19 @@ impl AppContext {
             app_config: AppConfig {
                 root,
                 graphql_endpoint,
+                embedded: false,
+                embedding_origins: Vec::new(),
             },
         }
     }
+
+    /// Render the app to be embedded in a frame by one of the given origins
+    #[must_use]
+    pub fn with_embedding_origins(mut self, origins: Vec<String>) -> Self {
+        self.app_config.embedded = true;
+        self.app_config.embedding_origins = origins;
+        self
+    }
 }
 
 impl TemplateContext for AppContext {
@@ -312,7 +326,11 @@ impl TemplateContext for AppContext {
         Self: Sized,
     {
         let url_builder = UrlBuilder::new("https://example.com/".parse().unwrap(), None, None);
-        vec![Self::from_url_builder(&url_builder)]
+        vec![
+            Self::from_url_builder(&url_builder),
+            Self::from_url_builder(&url_builder)
+                .with_embedding_origins(vec!["https://client.example.com".to_owned()]),
+        ]
     }
 }
 
@@ -356,6 +374,15 @@ pub enum PostAuthContextInner {
     /// Change the account password
     ChangePassword,
 
+    /// Manage the email addresses of the account
+    ManageEmails,
+
+    /// Manage the WebAuthn credentials of the account
+    ManageWebauthn,
+
+    /// Manage the recovery codes of the account
+    ManageRecoveryCodes,
+
     /// Link an upstream account
     LinkUpstream {
         /// The upstream provider
@@ -367,6 +394,12 @@ pub enum PostAuthContextInner {
 
     /// Go to the account management page
     ManageAccount,
+
+    /// Continue a device code grant
+    ContinueDeviceCodeGrant {
+        /// The device code grant that will be continued after authentication
+        grant: Box<DeviceCodeGrant>,
+    },
 }
 
 /// Context used in login and reauth screens, for the post-auth action to do
@@ -386,7 +419,18 @@ pub struct LoginContext {
     form: FormState<LoginFormField>,
     next: Option<PostAuthContext>,
     password_disabled: bool,
+    login_link_enabled: bool,
     providers: Vec<UpstreamOAuthProvider>,
+    captcha: Option<CaptchaConfig>,
+}
+
+/// A CAPTCHA configuration used in the samples of the forms it protects
+fn sample_captcha() -> CaptchaConfig {
+    CaptchaConfig {
+        service: CaptchaService::HCaptcha,
+        site_key: "10000000-ffff-ffff-ffff-000000000001".to_owned(),
+        secret_key: "0x0000000000000000000000000000000000000000".to_owned(),
+    }
 }
 
 impl TemplateContext for LoginContext {
@@ -400,13 +444,33 @@ impl TemplateContext for LoginContext {
                 form: FormState::default(),
                 next: None,
                 password_disabled: true,
+                login_link_enabled: false,
+                providers: Vec::new(),
+                captcha: None,
+            },
+            LoginContext {
+                form: FormState::default(),
+                next: None,
+                password_disabled: false,
+                login_link_enabled: false,
+                providers: Vec::new(),
+                captcha: None,
+            },
+            LoginContext {
+                form: FormState::default(),
+                next: None,
+                password_disabled: false,
+                login_link_enabled: false,
                 providers: Vec::new(),
+                captcha: Some(sample_captcha()),
             },
             LoginContext {
                 form: FormState::default(),
                 next: None,
                 password_disabled: false,
+                login_link_enabled: true,
                 providers: Vec::new(),
+                captcha: None,
             },
             LoginContext {
                 form: FormState::default()
@@ -419,14 +483,18 @@ impl TemplateContext for LoginContext {
                     ),
                 next: None,
                 password_disabled: false,
+                login_link_enabled: false,
                 providers: Vec::new(),
+                captcha: None,
             },
             LoginContext {
                 form: FormState::default()
                     .with_error_on_field(LoginFormField::Username, FieldError::Exists),
                 next: None,
                 password_disabled: false,
+                login_link_enabled: false,
                 providers: Vec::new(),
+                captcha: None,
             },
         ]
     }
@@ -442,6 +510,15 @@ impl LoginContext {
         }
     }
 
+    /// Set whether users can ask for a login link by email
+    #[must_use]
+    pub fn with_login_link(self, enabled: bool) -> Self {
+        Self {
+            login_link_enabled: enabled,
+            ..self
+        }
+    }
+
     /// Set the form state
     #[must_use]
     pub fn with_form_state(self, form: FormState<LoginFormField>) -> Self {
@@ -454,6 +531,12 @@ impl LoginContext {
         Self { providers, ..self }
     }
 
+    /// Set the CAPTCHA protecting the password form
+    #[must_use]
+    pub fn with_captcha(self, captcha: Option<CaptchaConfig>) -> Self {
+        Self { captcha, ..self }
+    }
+
     /// Add a post authentication action to the context
     #[must_use]
     pub fn with_post_action(self, context: PostAuthContext) -> Self {
@@ -464,6 +547,155 @@ impl LoginContext {
     }
 }
 
+/// Fields of the recovery code login form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryCodeLoginFormField {
+    /// The username field
+    Username,
+
+    /// The recovery code field
+    Code,
+}
+
+impl FormField for RecoveryCodeLoginFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Username => true,
+            Self::Code => false,
+        }
+    }
+}
+
+/// Context used by the `pages/recovery_code_login.html` template
+#[derive(Serialize, Default)]
+pub struct RecoveryCodeLoginContext {
+    form: FormState<RecoveryCodeLoginFormField>,
+    next: Option<PostAuthContext>,
+}
+
+impl TemplateContext for RecoveryCodeLoginContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::default().with_form_state(
+                FormState::default().with_error_on_form(FormError::InvalidCredentials),
+            ),
+        ]
+    }
+}
+
+impl RecoveryCodeLoginContext {
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<RecoveryCodeLoginFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+}
+
+/// Fields of the form to ask for a login link
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginLinkFormField {
+    /// The email field
+    Email,
+}
+
+impl FormField for LoginLinkFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Email => true,
+        }
+    }
+}
+
+/// Context used by the `pages/login_link/start.html` template
+#[derive(Serialize, Default)]
+pub struct LoginLinkContext {
+    form: FormState<LoginLinkFormField>,
+    next: Option<PostAuthContext>,
+
+    /// Whether the form was submitted, and the user should now check their
+    /// inbox
+    sent: bool,
+}
+
+impl TemplateContext for LoginLinkContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::default().with_form_state(
+                FormState::default()
+                    .with_error_on_field(LoginLinkFormField::Email, FieldError::Invalid),
+            ),
+            Self::default().sent(),
+        ]
+    }
+}
+
+impl LoginLinkContext {
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<LoginLinkFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+
+    /// Tell the user that the link was sent, if the address belongs to an
+    /// account
+    #[must_use]
+    pub fn sent(self) -> Self {
+        Self { sent: true, ..self }
+    }
+}
+
+/// Context used by the `pages/login_link/finish.html` template
+#[derive(Serialize)]
+pub struct LoginLinkFinishContext {
+    user: User,
+}
+
+impl LoginLinkFinishContext {
+    /// Constructs a context for the page confirming the sign in with a login
+    /// link
+    #[must_use]
+    pub fn new(user: User) -> Self {
+        Self { user }
+    }
+}
+
+impl TemplateContext for LoginLinkFinishContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng).into_iter().map(Self::new).collect()
+    }
+}
+
 /// Fields of the registration form
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -479,13 +711,16 @@ pub enum RegisterFormField {
 
     /// The password confirmation field
     PasswordConfirm,
+
+    /// The checkbox to accept the terms of service
+    AcceptTerms,
 }
 
 impl FormField for RegisterFormField {
     fn keep(&self) -> bool {
         match self {
             Self::Username | Self::Email => true,
-            Self::Password | Self::PasswordConfirm => false,
+            Self::Password | Self::PasswordConfirm | Self::AcceptTerms => false,
         }
     }
 }
@@ -495,6 +730,26 @@ impl FormField for RegisterFormField {
 pub struct RegisterContext {
     form: FormState<RegisterFormField>,
     next: Option<PostAuthContext>,
+
+    /// Only ask for the email address, which gets verified before the rest of
+    /// the form is shown
+    email_only: bool,
+
+    /// The email address which was verified earlier in the registration
+    verified_email: Option<String>,
+
+    /// The CAPTCHA protecting the form, if any
+    captcha: Option<CaptchaConfig>,
+
+    /// The terms of service the user has to accept, if any
+    terms: Option<TermsOfService>,
+}
+
+fn sample_terms() -> TermsOfService {
+    TermsOfService {
+        version: "2023-12".to_owned(),
+        url: Url::parse("https://example.com/terms").unwrap(),
+    }
 }
 
 impl TemplateContext for RegisterContext {
@@ -503,10 +758,16 @@ impl TemplateContext for RegisterContext {
         Self: Sized,
     {
         // TODO: samples with errors
-        vec![RegisterContext {
-            form: FormState::default(),
-            next: None,
-        }]
+        vec![
+            RegisterContext::default(),
+            RegisterContext::default().with_email_only(),
+            RegisterContext::default().with_verified_email("alice@example.com".to_owned()),
+            RegisterContext::default().with_captcha(Some(sample_captcha())),
+            RegisterContext::default()
+                .with_email_only()
+                .with_captcha(Some(sample_captcha())),
+            RegisterContext::default().with_terms(Some(sample_terms())),
+        ]
     }
 }
 
@@ -525,60 +786,109 @@ impl RegisterContext {
             ..self
         }
     }
+
+    /// Only ask for the email address, as the first step of a registration
+    /// which verifies it before creating the account
+    #[must_use]
+    pub fn with_email_only(self) -> Self {
+        Self {
+            email_only: true,
+            ..self
+        }
+    }
+
+    /// Set the email address which was verified earlier in the registration
+    #[must_use]
+    pub fn with_verified_email(self, email: String) -> Self {
+        Self {
+            verified_email: Some(email),
+            ..self
+        }
+    }
+
+    /// Set the CAPTCHA protecting the form
+    #[must_use]
+    pub fn with_captcha(self, captcha: Option<CaptchaConfig>) -> Self {
+        Self { captcha, ..self }
+    }
+
+    /// Set the terms of service the user has to accept
+    #[must_use]
+    pub fn with_terms(self, terms: Option<TermsOfService>) -> Self {
+        Self { terms, ..self }
+    }
 }
 
-/// Context used by the `consent.html` template
+/// Fields of the form to accept the terms of service
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AcceptTermsFormField {
+    /// The checkbox to accept the terms of service
+    AcceptTerms,
+}
+
+impl FormField for AcceptTermsFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::AcceptTerms => false,
+        }
+    }
+}
+
+/// Context used by the `pages/accept_terms.html` template
 #[derive(Serialize)]
-pub struct ConsentContext {
-    grant: AuthorizationGrant,
-    client: Client,
-    action: PostAuthAction,
+pub struct AcceptTermsContext {
+    form: FormState<AcceptTermsFormField>,
+    next: Option<PostAuthContext>,
+    terms: TermsOfService,
 }
 
-impl TemplateContext for ConsentContext {
-    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+impl TemplateContext for AcceptTermsContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
     {
-        Client::samples(now, rng)
-            .into_iter()
-            .map(|client| {
-                let mut grant = AuthorizationGrant::sample(now, rng);
-                let action = PostAuthAction::continue_grant(grant.id);
-                // XXX
-                grant.client_id = client.id;
-                Self {
-                    grant,
-                    client,
-                    action,
-                }
-            })
-            .collect()
+        vec![AcceptTermsContext::new(sample_terms())]
     }
 }
 
-impl ConsentContext {
-    /// Constructs a context for the client consent page
+impl AcceptTermsContext {
+    /// Constructs a context for the page asking to accept the given terms
     #[must_use]
-    pub fn new(grant: AuthorizationGrant, client: Client) -> Self {
-        let action = PostAuthAction::continue_grant(grant.id);
+    pub fn new(terms: TermsOfService) -> Self {
         Self {
-            grant,
-            client,
-            action,
+            form: FormState::default(),
+            next: None,
+            terms,
+        }
+    }
+
+    /// Add an error on the form
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<AcceptTermsFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
         }
     }
 }
 
-/// Context used by the `policy_violation.html` template
+/// Context used by the `consent.html` template
 #[derive(Serialize)]
-pub struct PolicyViolationContext {
+pub struct ConsentContext {
     grant: AuthorizationGrant,
     client: Client,
     action: PostAuthAction,
+    scope_descriptions: BTreeMap<String, String>,
 }
 
-impl TemplateContext for PolicyViolationContext {
+impl TemplateContext for ConsentContext {
     fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
@@ -594,270 +904,1249 @@ impl TemplateContext for PolicyViolationContext {
                     grant,
                     client,
                     action,
+                    scope_descriptions: BTreeMap::new(),
                 }
             })
             .collect()
     }
 }
 
-impl PolicyViolationContext {
-    /// Constructs a context for the policy violation page
+impl ConsentContext {
+    /// Constructs a context for the client consent page
     #[must_use]
-    pub const fn new(grant: AuthorizationGrant, client: Client) -> Self {
+    pub fn new(grant: AuthorizationGrant, client: Client) -> Self {
         let action = PostAuthAction::continue_grant(grant.id);
         Self {
             grant,
             client,
             action,
+            scope_descriptions: BTreeMap::new(),
+        }
+    }
+
+    /// Set the descriptions of the custom scopes the client may request, keyed
+    /// by scope token
+    #[must_use]
+    pub fn with_scope_descriptions(self, scope_descriptions: BTreeMap<String, String>) -> Self {
+        Self {
+            scope_descriptions,
+            ..self
+        }
+    }
+}
+
+/// Fields of the device code link form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceLinkFormField {
+    /// The user code field
+    Code,
+}
+
+impl FormField for DeviceLinkFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Code => true,
         }
     }
 }
 
-/// Fields of the reauthentication form
+/// Context used by the `device_link.html` template
+#[derive(Serialize, Default)]
+pub struct DeviceLinkContext {
+    form: FormState<DeviceLinkFormField>,
+}
+
+impl DeviceLinkContext {
+    /// Constructs a new context with an existing form state
+    #[must_use]
+    pub fn with_form_state(mut self, form: FormState<DeviceLinkFormField>) -> Self {
+        self.form = form;
+        self
+    }
+}
+
+impl TemplateContext for DeviceLinkContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::default().with_form_state(
+                FormState::default()
+                    .with_error_on_field(DeviceLinkFormField::Code, FieldError::Required),
+            ),
+            Self::default().with_form_state(
+                FormState::default()
+                    .with_error_on_field(DeviceLinkFormField::Code, FieldError::Invalid),
+            ),
+        ]
+    }
+}
+
+/// Context used by the `device_consent.html` template
+#[derive(Serialize)]
+pub struct DeviceConsentContext {
+    grant: DeviceCodeGrant,
+    client: Client,
+    action: PostAuthAction,
+    scope_descriptions: BTreeMap<String, String>,
+}
+
+impl DeviceConsentContext {
+    /// Constructs a context for the device consent page
+    #[must_use]
+    pub fn new(grant: DeviceCodeGrant, client: Client) -> Self {
+        let action = PostAuthAction::continue_device_code_grant(grant.id);
+        Self {
+            grant,
+            client,
+            action,
+            scope_descriptions: BTreeMap::new(),
+        }
+    }
+
+    /// Set the descriptions of the custom scopes the client may request, keyed
+    /// by scope token
+    #[must_use]
+    pub fn with_scope_descriptions(self, scope_descriptions: BTreeMap<String, String>) -> Self {
+        Self {
+            scope_descriptions,
+            ..self
+        }
+    }
+}
+
+impl TemplateContext for DeviceConsentContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let browser_session_id = Ulid::from_datetime_with_source(now.into(), rng);
+        let states = [
+            DeviceCodeGrantState::Pending,
+            DeviceCodeGrantState::Fulfilled {
+                browser_session_id,
+                fulfilled_at: now,
+            },
+            DeviceCodeGrantState::Rejected {
+                browser_session_id,
+                rejected_at: now,
+            },
+        ];
+
+        let mut samples = Vec::new();
+        for client in Client::samples(now, rng) {
+            for state in states.clone() {
+                let grant = DeviceCodeGrant {
+                    id: Ulid::from_datetime_with_source(now.into(), rng),
+                    state,
+                    client_id: client.id,
+                    scope: [oauth2_types::scope::OPENID].into_iter().collect(),
+                    user_code: "ABCDEFGH".to_owned(),
+                    device_code: "abcdefghijklmnopqrstuvwxyz012345".to_owned(),
+                    created_at: now - chrono::Duration::minutes(5),
+                    expires_at: now + chrono::Duration::minutes(25),
+                    ip_address: Some(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)),
+                    user_agent: Some("Mozilla/5.0".to_owned()),
+                };
+                samples.push(Self::new(grant, client.clone()));
+            }
+        }
+
+        samples
+    }
+}
+
+/// Context used by the `policy_violation.html` template
+#[derive(Serialize)]
+pub struct PolicyViolationContext {
+    grant: AuthorizationGrant,
+    client: Client,
+    action: PostAuthAction,
+}
+
+impl TemplateContext for PolicyViolationContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        Client::samples(now, rng)
+            .into_iter()
+            .map(|client| {
+                let mut grant = AuthorizationGrant::sample(now, rng);
+                let action = PostAuthAction::continue_grant(grant.id);
+                // XXX
+                grant.client_id = client.id;
+                Self {
+                    grant,
+                    client,
+                    action,
+                }
+            })
+            .collect()
+    }
+}
+
+impl PolicyViolationContext {
+    /// Constructs a context for the policy violation page
+    #[must_use]
+    pub const fn new(grant: AuthorizationGrant, client: Client) -> Self {
+        let action = PostAuthAction::continue_grant(grant.id);
+        Self {
+            grant,
+            client,
+            action,
+        }
+    }
+}
+
+/// Context used by the `end_session.html` template
+#[derive(Serialize)]
+pub struct EndSessionContext {
+    client: Option<Client>,
+    post_logout_redirect_uri: Option<Url>,
+    state: Option<String>,
+}
+
+impl TemplateContext for EndSessionContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let mut samples: Vec<Self> = Client::samples(now, rng)
+            .into_iter()
+            .map(|client| {
+                let post_logout_redirect_uri = client.post_logout_redirect_uris.first().cloned();
+                Self::new(
+                    Some(client),
+                    post_logout_redirect_uri,
+                    Some("state".to_owned()),
+                )
+            })
+            .collect();
+
+        samples.push(Self::new(None, None, None));
+        samples
+    }
+}
+
+impl EndSessionContext {
+    /// Constructs a context for the RP-initiated logout confirmation page
+    #[must_use]
+    pub const fn new(
+        client: Option<Client>,
+        post_logout_redirect_uri: Option<Url>,
+        state: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            post_logout_redirect_uri,
+            state,
+        }
+    }
+}
+
+/// Fields of the reauthentication form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReauthFormField {
+    /// The password field
+    Password,
+}
+
+impl FormField for ReauthFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Password => false,
+        }
+    }
+}
+
+/// Context used by the `reauth.html` template
+#[derive(Serialize, Default)]
+pub struct ReauthContext {
+    form: FormState<ReauthFormField>,
+    next: Option<PostAuthContext>,
+    password_disabled: bool,
+    webauthn: bool,
+}
+
+impl TemplateContext for ReauthContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        // TODO: samples with errors
+        vec![
+            ReauthContext {
+                form: FormState::default(),
+                next: None,
+                password_disabled: false,
+                webauthn: false,
+            },
+            ReauthContext {
+                form: FormState::default(),
+                next: None,
+                password_disabled: true,
+                webauthn: true,
+            },
+        ]
+    }
+}
+
+impl ReauthContext {
+    /// Set whether password reauthentication is enabled or not
+    #[must_use]
+    pub fn with_password_login(self, enabled: bool) -> Self {
+        Self {
+            password_disabled: !enabled,
+            ..self
+        }
+    }
+
+    /// Set whether the user can reauthenticate with a WebAuthn credential
+    #[must_use]
+    pub fn with_webauthn(self, webauthn: bool) -> Self {
+        Self { webauthn, ..self }
+    }
+
+    /// Add an error on the reauthentication form
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<ReauthFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+}
+
+/// Context used by the `sso.html` template
+#[derive(Serialize)]
+pub struct CompatSsoContext {
+    login: CompatSsoLogin,
+    action: PostAuthAction,
+}
+
+impl TemplateContext for CompatSsoContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let id = Ulid::from_datetime_with_source(now.into(), rng);
+        vec![CompatSsoContext::new(CompatSsoLogin {
+            id,
+            redirect_uri: Url::parse("https://app.element.io/").unwrap(),
+            login_token: "abcdefghijklmnopqrstuvwxyz012345".into(),
+            created_at: now,
+            state: CompatSsoLoginState::Pending,
+        })]
+    }
+}
+
+impl CompatSsoContext {
+    /// Constructs a context for the legacy SSO login page
+    #[must_use]
+    pub fn new(login: CompatSsoLogin) -> Self
+where {
+        let action = PostAuthAction::continue_compat_sso_login(login.id);
+        Self { login, action }
+    }
+}
+
+/// Context used by the `emails/verification.{txt,html,subject}` templates
+#[derive(Serialize)]
+pub struct EmailVerificationContext {
+    user: User,
+    verification: UserEmailVerification,
+}
+
+impl EmailVerificationContext {
+    /// Constructs a context for the verification email
+    #[must_use]
+    pub fn new(user: User, verification: UserEmailVerification) -> Self {
+        Self { user, verification }
+    }
+
+    /// Get the user to which this email is being sent
+    #[must_use]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Get the verification code being sent
+    #[must_use]
+    pub fn verification(&self) -> &UserEmailVerification {
+        &self.verification
+    }
+}
+
+impl TemplateContext for EmailVerificationContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .map(|user| {
+                let email = UserEmail {
+                    id: Ulid::from_datetime_with_source(now.into(), rng),
+                    user_id: user.id,
+                    email: "foobar@example.com".to_owned(),
+                    created_at: now,
+                    confirmed_at: None,
+                };
+
+                let verification = UserEmailVerification {
+                    id: Ulid::from_datetime_with_source(now.into(), rng),
+                    user_email_id: email.id,
+                    code: "123456".to_owned(),
+                    created_at: now,
+                    state: mas_data_model::UserEmailVerificationState::Valid,
+                };
+
+                Self { user, verification }
+            })
+            .collect()
+    }
+}
+
+/// Fields of the email verification form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailVerificationFormField {
+    /// The code field
+    Code,
+}
+
+impl FormField for EmailVerificationFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Code => true,
+        }
+    }
+}
+
+/// Context used by the `pages/account/verify.html` templates
+#[derive(Serialize)]
+pub struct EmailVerificationPageContext {
+    form: FormState<EmailVerificationFormField>,
+    email: UserEmail,
+}
+
+impl EmailVerificationPageContext {
+    /// Constructs a context for the email verification page
+    #[must_use]
+    pub fn new(email: UserEmail) -> Self {
+        Self {
+            form: FormState::default(),
+            email,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<EmailVerificationFormField>) -> Self {
+        Self { form, ..self }
+    }
+}
+
+impl TemplateContext for EmailVerificationPageContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let email = UserEmail {
+            id: Ulid::from_datetime_with_source(now.into(), rng),
+            user_id: Ulid::from_datetime_with_source(now.into(), rng),
+            email: "foobar@example.com".to_owned(),
+            created_at: now,
+            confirmed_at: None,
+        };
+
+        vec![Self {
+            form: FormState::default(),
+            email,
+        }]
+    }
+}
+
+/// Context used by the `pages/register/verify.html` template
+#[derive(Serialize)]
+pub struct RegisterVerifyContext {
+    form: FormState<EmailVerificationFormField>,
+    email: String,
+    next: Option<PostAuthContext>,
+}
+
+impl RegisterVerifyContext {
+    /// Constructs a context for the registration email verification page
+    #[must_use]
+    pub fn new(email: String) -> Self {
+        Self {
+            form: FormState::default(),
+            email,
+            next: None,
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<EmailVerificationFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Add a post authentication action to the context
+    #[must_use]
+    pub fn with_post_action(self, next: PostAuthContext) -> Self {
+        Self {
+            next: Some(next),
+            ..self
+        }
+    }
+}
+
+impl TemplateContext for RegisterVerifyContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![Self::new("foobar@example.com".to_owned())]
+    }
+}
+
+/// Context used by the `emails/registration.{txt,html,subject}` templates
+#[derive(Serialize)]
+pub struct EmailRegistrationContext {
+    registration: UserRegistration,
+}
+
+impl EmailRegistrationContext {
+    /// Constructs a context for the registration verification email
+    #[must_use]
+    pub fn new(registration: UserRegistration) -> Self {
+        Self { registration }
+    }
+
+    /// Get the registration for which this email is being sent
+    #[must_use]
+    pub fn registration(&self) -> &UserRegistration {
+        &self.registration
+    }
+}
+
+impl TemplateContext for EmailRegistrationContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        UserRegistration::samples(now, rng)
+            .into_iter()
+            .map(Self::new)
+            .collect()
+    }
+}
+
+/// Context used by the `emails/password_reset.{txt,html,subject}` templates
+#[derive(Serialize)]
+pub struct EmailPasswordResetContext {
+    user: User,
+    ticket: UserRecoveryTicket,
+    link: Url,
+    requested: bool,
+}
+
+impl EmailPasswordResetContext {
+    /// Constructs a context for the email sent to a user who must reset their
+    /// password
+    #[must_use]
+    pub fn new(user: User, ticket: UserRecoveryTicket, link: Url) -> Self {
+        Self {
+            user,
+            ticket,
+            link,
+            requested: false,
+        }
+    }
+
+    /// Mark this email as sent because the user asked to recover their
+    /// account, rather than because they were forced to reset their password
+    #[must_use]
+    pub fn requested_by_user(mut self) -> Self {
+        self.requested = true;
+        self
+    }
+
+    /// Get the user to which this email is being sent
+    #[must_use]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Get the recovery ticket being sent
+    #[must_use]
+    pub fn ticket(&self) -> &UserRecoveryTicket {
+        &self.ticket
+    }
+}
+
+impl TemplateContext for EmailPasswordResetContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .zip(UserRecoveryTicket::samples(now, rng))
+            .flat_map(|(user, ticket)| {
+                let link = Url::parse("https://example.com/recover/")
+                    .unwrap()
+                    .join(&ticket.ticket)
+                    .unwrap();
+                [
+                    Self::new(user.clone(), ticket.clone(), link.clone()),
+                    Self::new(user, ticket, link).requested_by_user(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Context used by the `emails/login_link.{txt,html,subject}` templates
+#[derive(Serialize)]
+pub struct EmailLoginLinkContext {
+    user: User,
+    login_link: UserLoginLink,
+    link: Url,
+}
+
+impl EmailLoginLinkContext {
+    /// Constructs a context for the email sent to a user who asked for a
+    /// login link
+    #[must_use]
+    pub fn new(user: User, login_link: UserLoginLink, link: Url) -> Self {
+        Self {
+            user,
+            login_link,
+            link,
+        }
+    }
+
+    /// Get the user to which this email is being sent
+    #[must_use]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Get the login link being sent
+    #[must_use]
+    pub fn login_link(&self) -> &UserLoginLink {
+        &self.login_link
+    }
+}
+
+impl TemplateContext for EmailLoginLinkContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .zip(UserLoginLink::samples(now, rng))
+            .map(|(user, login_link)| {
+                let link = Url::parse("https://example.com/login/link/")
+                    .unwrap()
+                    .join(&login_link.token)
+                    .unwrap();
+                Self::new(user, login_link, link)
+            })
+            .collect()
+    }
+}
+
+/// Context used by the `emails/data_export.{txt,html,subject}` templates
+#[derive(Serialize)]
+pub struct EmailDataExportContext {
+    user: User,
+    link: Url,
+}
+
+impl EmailDataExportContext {
+    /// Constructs a context for the email sent to a user once their data
+    /// export is ready
+    #[must_use]
+    pub fn new(user: User, link: Url) -> Self {
+        Self { user, link }
+    }
+
+    /// Get the user to which this email is being sent
+    #[must_use]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+}
+
+impl TemplateContext for EmailDataExportContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .map(|user| {
+                let link = Url::parse("https://example.com/account/export/")
+                    .unwrap()
+                    .join("Hq7mBnHCfVbyAnUMtlcXFxOGaqo3V2Ci")
+                    .unwrap();
+                Self::new(user, link)
+            })
+            .collect()
+    }
+}
+
+/// Context used by the `emails/compat_password_deprecation.{txt,html,subject}`
+/// templates
+#[derive(Serialize)]
+pub struct EmailCompatPasswordDeprecationContext {
+    user: User,
+    days_left: i64,
+}
+
+impl EmailCompatPasswordDeprecationContext {
+    /// Constructs a context for the email warning a user that they will no
+    /// longer be able to log in with their password in the given number of
+    /// days
+    #[must_use]
+    pub fn new(user: User, days_left: i64) -> Self {
+        Self { user, days_left }
+    }
+
+    /// Get the user to which this email is being sent
+    #[must_use]
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+}
+
+impl TemplateContext for EmailCompatPasswordDeprecationContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .flat_map(|user| [Self::new(user.clone(), 1), Self::new(user, 14)])
+            .collect()
+    }
+}
+
+/// Fields of the form to start recovering an account
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryStartFormField {
+    /// The email field
+    Email,
+}
+
+impl FormField for RecoveryStartFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Email => true,
+        }
+    }
+}
+
+/// Context used by the `pages/recovery/start.html` template
+#[derive(Serialize, Default)]
+pub struct RecoveryStartContext {
+    form: FormState<RecoveryStartFormField>,
+
+    /// Whether the form was submitted, and the user should now check their
+    /// inbox
+    sent: bool,
+}
+
+impl TemplateContext for RecoveryStartContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::default().with_form_state(
+                FormState::default()
+                    .with_error_on_field(RecoveryStartFormField::Email, FieldError::Required),
+            ),
+            Self::default().sent(),
+        ]
+    }
+}
+
+impl RecoveryStartContext {
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<RecoveryStartFormField>) -> Self {
+        Self { form, ..self }
+    }
+
+    /// Tell the user that a recovery link was sent, if the address belongs to
+    /// an account
+    #[must_use]
+    pub fn sent(self) -> Self {
+        Self { sent: true, ..self }
+    }
+}
+
+/// Fields of the password recovery form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryFinishFormField {
+    /// The new password field
+    NewPassword,
+
+    /// The new password confirmation field
+    NewPasswordConfirm,
+}
+
+impl FormField for RecoveryFinishFormField {
+    fn keep(&self) -> bool {
+        false
+    }
+}
+
+/// Context used by the `pages/recovery/finish.html` template
+#[derive(Serialize)]
+pub struct RecoveryFinishContext {
+    user: User,
+    form: FormState<RecoveryFinishFormField>,
+}
+
+impl RecoveryFinishContext {
+    /// Constructs a context for the form to choose a new password
+    #[must_use]
+    pub fn new(user: User) -> Self {
+        Self {
+            user,
+            form: FormState::default(),
+        }
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(self, form: FormState<RecoveryFinishFormField>) -> Self {
+        Self { form, ..self }
+    }
+}
+
+impl TemplateContext for RecoveryFinishContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        User::samples(now, rng)
+            .into_iter()
+            .flat_map(|user| {
+                vec![
+                    Self::new(user.clone()),
+                    Self::new(user).with_form_state(
+                        FormState::default()
+                            .with_error_on_field(
+                                RecoveryFinishFormField::NewPassword,
+                                FieldError::Invalid,
+                            )
+                            .with_error_on_form(FormError::PasswordMismatch),
+                    ),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Context used by the `sms/verification.txt` template
+#[derive(Serialize)]
+pub struct SmsVerificationContext {
+    code: String,
+}
+
+impl SmsVerificationContext {
+    /// Constructs a context for a text message with a verification code
+    #[must_use]
+    pub fn new(code: String) -> Self {
+        Self { code }
+    }
+
+    /// Get the verification code being sent
+    #[must_use]
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+impl TemplateContext for SmsVerificationContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![Self::new("123456".to_owned())]
+    }
+}
+
+/// Fields of the account email add form
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailAddFormField {
+    /// The email
+    Email,
+}
+
+impl FormField for EmailAddFormField {
+    fn keep(&self) -> bool {
+        match self {
+            Self::Email => true,
+        }
+    }
+}
+
+/// Context used by the `pages/account/emails/index.html` template
+#[derive(Serialize)]
+pub struct AccountEmailsContext {
+    emails: Vec<UserEmail>,
+}
+
+impl AccountEmailsContext {
+    /// Constructs a context for the email addresses management page
+    #[must_use]
+    pub fn new(emails: Vec<UserEmail>) -> Self {
+        Self { emails }
+    }
+}
+
+impl TemplateContext for AccountEmailsContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::new(Vec::new()),
+            Self::new(UserEmail::samples(now, rng)),
+        ]
+    }
+}
+
+/// Context used by the `pages/account/verify.html` templates
+#[derive(Serialize, Default)]
+pub struct EmailAddContext {
+    form: FormState<EmailAddFormField>,
+}
+
+impl EmailAddContext {
+    /// Constructs a context for the email add page
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the form state
+    #[must_use]
+    pub fn with_form_state(form: FormState<EmailAddFormField>) -> Self {
+        Self { form }
+    }
+}
+
+impl TemplateContext for EmailAddContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![Self::default()]
+    }
+}
+
+/// Fields of the WebAuthn credential registration form
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-pub enum ReauthFormField {
-    /// The password field
-    Password,
+#[serde(rename_all = "snake_case")]
+pub enum AccountWebauthnFormField {
+    /// The name given to the credential
+    Name,
 }
 
-impl FormField for ReauthFormField {
+impl FormField for AccountWebauthnFormField {
     fn keep(&self) -> bool {
         match self {
-            Self::Password => false,
+            Self::Name => true,
         }
     }
 }
 
-/// Context used by the `reauth.html` template
+/// Context used by the `pages/account/webauthn.html` template
 #[derive(Serialize, Default)]
-pub struct ReauthContext {
-    form: FormState<ReauthFormField>,
-    next: Option<PostAuthContext>,
+pub struct AccountWebauthnContext {
+    credentials: Vec<WebauthnCredential>,
+    form: FormState<AccountWebauthnFormField>,
 }
 
-impl TemplateContext for ReauthContext {
-    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
-    where
-        Self: Sized,
-    {
-        // TODO: samples with errors
-        vec![ReauthContext {
+impl AccountWebauthnContext {
+    /// Constructs a context for the WebAuthn credentials management page
+    #[must_use]
+    pub fn new(credentials: Vec<WebauthnCredential>) -> Self {
+        Self {
+            credentials,
             form: FormState::default(),
-            next: None,
-        }]
+        }
     }
-}
 
-impl ReauthContext {
-    /// Add an error on the reauthentication form
+    /// Set the form state
     #[must_use]
-    pub fn with_form_state(self, form: FormState<ReauthFormField>) -> Self {
+    pub fn with_form_state(self, form: FormState<AccountWebauthnFormField>) -> Self {
         Self { form, ..self }
     }
-
-    /// Add a post authentication action to the context
-    #[must_use]
-    pub fn with_post_action(self, next: PostAuthContext) -> Self {
-        Self {
-            next: Some(next),
-            ..self
-        }
-    }
 }
 
-/// Context used by the `sso.html` template
-#[derive(Serialize)]
-pub struct CompatSsoContext {
-    login: CompatSsoLogin,
-    action: PostAuthAction,
-}
-
-impl TemplateContext for CompatSsoContext {
+impl TemplateContext for AccountWebauthnContext {
     fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
     {
-        let id = Ulid::from_datetime_with_source(now.into(), rng);
-        vec![CompatSsoContext::new(CompatSsoLogin {
-            id,
-            redirect_uri: Url::parse("https://app.element.io/").unwrap(),
-            login_token: "abcdefghijklmnopqrstuvwxyz012345".into(),
-            created_at: now,
-            state: CompatSsoLoginState::Pending,
-        })]
-    }
-}
-
-impl CompatSsoContext {
-    /// Constructs a context for the legacy SSO login page
-    #[must_use]
-    pub fn new(login: CompatSsoLogin) -> Self
-where {
-        let action = PostAuthAction::continue_compat_sso_login(login.id);
-        Self { login, action }
+        vec![
+            Self::default(),
+            Self::new(WebauthnCredential::samples(now, rng)).with_form_state(
+                FormState::default()
+                    .with_error_on_field(AccountWebauthnFormField::Name, FieldError::Required),
+            ),
+        ]
     }
 }
 
-/// Context used by the `emails/verification.{txt,html,subject}` templates
-#[derive(Serialize)]
-pub struct EmailVerificationContext {
-    user: User,
-    verification: UserEmailVerification,
+/// Context used by the `pages/account/recovery_codes.html` template
+#[derive(Serialize, Default)]
+pub struct AccountRecoveryCodesContext {
+    remaining: usize,
+    codes: Option<Vec<String>>,
 }
 
-impl EmailVerificationContext {
-    /// Constructs a context for the verification email
-    #[must_use]
-    pub fn new(user: User, verification: UserEmailVerification) -> Self {
-        Self { user, verification }
-    }
-
-    /// Get the user to which this email is being sent
+impl AccountRecoveryCodesContext {
+    /// Constructs a context for the recovery codes page, with the number of
+    /// codes which were not used yet
     #[must_use]
-    pub fn user(&self) -> &User {
-        &self.user
+    pub fn new(remaining: usize) -> Self {
+        Self {
+            remaining,
+            codes: None,
+        }
     }
 
-    /// Get the verification code being sent
+    /// Show freshly generated codes. They are only ever shown once, right
+    /// after being generated
     #[must_use]
-    pub fn verification(&self) -> &UserEmailVerification {
-        &self.verification
+    pub fn with_codes(self, codes: Vec<String>) -> Self {
+        Self {
+            remaining: codes.len(),
+            codes: Some(codes),
+        }
     }
 }
 
-impl TemplateContext for EmailVerificationContext {
-    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+impl TemplateContext for AccountRecoveryCodesContext {
+    fn sample(_now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
     {
-        User::samples(now, rng)
-            .into_iter()
-            .map(|user| {
-                let email = UserEmail {
-                    id: Ulid::from_datetime_with_source(now.into(), rng),
-                    user_id: user.id,
-                    email: "foobar@example.com".to_owned(),
-                    created_at: now,
-                    confirmed_at: None,
-                };
+        let codes = (0..UserRecoveryCode::COUNT)
+            .map(|_| UserRecoveryCode::generate(rng))
+            .collect();
 
-                let verification = UserEmailVerification {
-                    id: Ulid::from_datetime_with_source(now.into(), rng),
-                    user_email_id: email.id,
-                    code: "123456".to_owned(),
-                    created_at: now,
-                    state: mas_data_model::UserEmailVerificationState::Valid,
-                };
-
-                Self { user, verification }
-            })
-            .collect()
+        vec![
+            Self::new(0),
+            Self::new(3),
+            Self::default().with_codes(codes),
+        ]
     }
 }
 
-/// Fields of the email verification form
+/// Fields of the profile forms
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-pub enum EmailVerificationFormField {
-    /// The code field
-    Code,
+pub enum AccountProfileFormField {
+    /// The display name of the user
+    DisplayName,
+
+    /// The uploaded avatar
+    Avatar,
 }
 
-impl FormField for EmailVerificationFormField {
+impl FormField for AccountProfileFormField {
     fn keep(&self) -> bool {
         match self {
-            Self::Code => true,
+            Self::DisplayName => true,
+            Self::Avatar => false,
         }
     }
 }
 
-/// Context used by the `pages/account/verify.html` templates
-#[derive(Serialize)]
-pub struct EmailVerificationPageContext {
-    form: FormState<EmailVerificationFormField>,
-    email: UserEmail,
+/// Context used by the `pages/account/profile.html` template
+#[derive(Serialize, Default)]
+pub struct AccountProfileContext {
+    form: FormState<AccountProfileFormField>,
+
+    /// The display name currently set on the homeserver
+    display_name: Option<String>,
+
+    /// The URL of the avatar currently set on the homeserver
+    avatar_url: Option<String>,
+
+    /// Whether avatar uploads are enabled
+    avatar_uploads: bool,
 }
 
-impl EmailVerificationPageContext {
-    /// Constructs a context for the email verification page
+impl AccountProfileContext {
+    /// Constructs a context for the profile page
     #[must_use]
-    pub fn new(email: UserEmail) -> Self {
+    pub fn new(
+        display_name: Option<String>,
+        avatar_url: Option<String>,
+        avatar_uploads: bool,
+    ) -> Self {
         Self {
             form: FormState::default(),
-            email,
+            display_name,
+            avatar_url,
+            avatar_uploads,
         }
     }
 
-    /// Set the form state
+    /// Add an error on the profile forms
     #[must_use]
-    pub fn with_form_state(self, form: FormState<EmailVerificationFormField>) -> Self {
+    pub fn with_form_state(self, form: FormState<AccountProfileFormField>) -> Self {
         Self { form, ..self }
     }
 }
 
-impl TemplateContext for EmailVerificationPageContext {
-    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+impl TemplateContext for AccountProfileContext {
+    fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
     {
-        let email = UserEmail {
-            id: Ulid::from_datetime_with_source(now.into(), rng),
-            user_id: Ulid::from_datetime_with_source(now.into(), rng),
-            email: "foobar@example.com".to_owned(),
-            created_at: now,
-            confirmed_at: None,
-        };
-
-        vec![Self {
-            form: FormState::default(),
-            email,
-        }]
+        vec![
+            Self::new(None, None, false),
+            Self::new(
+                Some("Alice".to_owned()),
+                Some("https://example.com/avatars/01H2Q3K2PZ6JBZ4T3TNV8S2VJ9.png".to_owned()),
+                true,
+            ),
+        ]
     }
 }
 
-/// Fields of the account email add form
+/// Fields of the account deactivation form
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
-pub enum EmailAddFormField {
-    /// The email
-    Email,
+#[serde(rename_all = "kebab-case")]
+pub enum AccountDeactivateFormField {
+    /// The username, typed again to confirm
+    Username,
+
+    /// The password of the user
+    Password,
 }
 
-impl FormField for EmailAddFormField {
+impl FormField for AccountDeactivateFormField {
     fn keep(&self) -> bool {
         match self {
-            Self::Email => true,
+            Self::Username | Self::Password => false,
         }
     }
 }
 
-/// Context used by the `pages/account/verify.html` templates
+/// Context used by the `pages/account/deactivate.html` template
 #[derive(Serialize, Default)]
-pub struct EmailAddContext {
-    form: FormState<EmailAddFormField>,
+pub struct AccountDeactivateContext {
+    form: FormState<AccountDeactivateFormField>,
+
+    /// Whether the user has to enter their password to confirm
+    password: bool,
 }
 
-impl EmailAddContext {
-    /// Constructs a context for the email add page
+impl AccountDeactivateContext {
+    /// Constructs a context for the account deactivation page
     #[must_use]
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(password: bool) -> Self {
+        Self {
+            form: FormState::default(),
+            password,
+        }
     }
 
-    /// Set the form state
+    /// Add an error on the deactivation form
     #[must_use]
-    pub fn with_form_state(form: FormState<EmailAddFormField>) -> Self {
-        Self { form }
+    pub fn with_form_state(self, form: FormState<AccountDeactivateFormField>) -> Self {
+        Self { form, ..self }
     }
 }
 
-impl TemplateContext for EmailAddContext {
+impl TemplateContext for AccountDeactivateContext {
     fn sample(_now: chrono::DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
     where
         Self: Sized,
     {
-        vec![Self::default()]
+        vec![Self::new(true), Self::new(false)]
+    }
+}
+
+/// Context used by the `pages/account/export.html` template
+#[derive(Serialize, Default)]
+pub struct AccountDataExportContext {
+    /// The latest export requested by the user, if any
+    export: Option<UserDataExport>,
+
+    /// Where to download the latest export, if it is ready and hasn't expired
+    download_link: Option<Url>,
+}
+
+impl AccountDataExportContext {
+    /// Constructs a context for the data export page
+    #[must_use]
+    pub fn new(export: Option<UserDataExport>, download_link: Option<Url>) -> Self {
+        Self {
+            export,
+            download_link,
+        }
+    }
+}
+
+impl TemplateContext for AccountDataExportContext {
+    fn sample(now: chrono::DateTime<Utc>, rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        let pending = UserDataExport {
+            id: Ulid::from_datetime_with_source(now.into(), rng),
+            user_id: Ulid::from_datetime_with_source(now.into(), rng),
+            token: "Hq7mBnHCfVbyAnUMtlcXFxOGaqo3V2Ci".to_owned(),
+            created_at: now,
+            completed_at: None,
+            expires_at: None,
+        };
+        let ready = UserDataExport {
+            completed_at: Some(now),
+            expires_at: Some(now + chrono::Duration::days(7)),
+            ..pending.clone()
+        };
+        let link = Url::parse("https://example.com/account/export/")
+            .unwrap()
+            .join(&ready.token)
+            .unwrap();
+
+        vec![
+            Self::default(),
+            Self::new(Some(pending), None),
+            Self::new(Some(ready), Some(link)),
+        ]
     }
 }
 
@@ -1188,3 +2477,82 @@ impl TemplateContext for NotFoundContext {
         ]
     }
 }
+
+/// Context used by the maintenance (`maintenance.html`) template
+#[derive(Serialize, Default)]
+pub struct MaintenanceContext {
+    message: Option<String>,
+}
+
+impl MaintenanceContext {
+    /// Constructs a context for the maintenance page
+    #[must_use]
+    pub fn new(message: Option<String>) -> Self {
+        Self { message }
+    }
+}
+
+impl TemplateContext for MaintenanceContext {
+    fn sample(_now: DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::new(Some("The database is being upgraded".to_owned())),
+        ]
+    }
+}
+
+/// An email listed on the development mailbox page
+#[derive(Serialize, Debug, Clone)]
+pub struct DevMailboxEmail {
+    /// When the email was sent
+    pub sent_at: DateTime<Utc>,
+
+    /// The sender of the email
+    pub from: Option<String>,
+
+    /// The recipients of the email
+    pub to: Vec<String>,
+
+    /// The subject of the email
+    pub subject: Option<String>,
+
+    /// The full message, as it would have been sent on the wire
+    pub message: String,
+}
+
+/// Context used by the development mailbox (`pages/dev/mailbox.html`) template
+#[derive(Serialize, Default)]
+pub struct DevMailboxContext {
+    emails: Vec<DevMailboxEmail>,
+}
+
+impl DevMailboxContext {
+    /// Constructs a context for the development mailbox page, with the emails
+    /// sorted from the most recent
+    #[must_use]
+    pub fn new(emails: Vec<DevMailboxEmail>) -> Self {
+        Self { emails }
+    }
+}
+
+impl TemplateContext for DevMailboxContext {
+    fn sample(now: DateTime<Utc>, _rng: &mut impl Rng) -> Vec<Self>
+    where
+        Self: Sized,
+    {
+        vec![
+            Self::default(),
+            Self::new(vec![DevMailboxEmail {
+                sent_at: now,
+                from: Some("root@localhost".to_owned()),
+                to: vec!["alice@example.com".to_owned()],
+                subject: Some("Verify your email address".to_owned()),
+                message: "Subject: Verify your email address\r\n\r\nYour code is 123456\r\n"
+                    .to_owned(),
+            }]),
+        ]
+    }
+}
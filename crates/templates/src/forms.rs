@@ -44,6 +44,12 @@ pub enum FieldError {
         /// Message for this policy violation
         message: String,
     },
+
+    /// The password appeared in a known data breach
+    Breached,
+
+    /// The uploaded file is larger than what the server accepts
+    TooLarge,
 }
 
 /// An error on the whole form
@@ -64,6 +70,28 @@ pub enum FormError {
         /// Message for this policy violation
         message: String,
     },
+
+    /// Too many attempts were made recently
+    RateLimitExceeded,
+
+    /// The password of the user must be reset before they can log in
+    PasswordResetRequired,
+
+    /// The WebAuthn ceremony failed, or the credential is unknown
+    WebauthnFailed,
+
+    /// The CAPTCHA challenge was not solved
+    Captcha,
+
+    /// The account or the IP address is temporarily locked out after too many
+    /// failed login attempts
+    LoginLocked,
+
+    /// Too many emails were sent recently to this address or by this user
+    EmailCooldown {
+        /// How many seconds to wait before requesting another email
+        retry_after: u64,
+    },
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -42,12 +42,23 @@ mod macros;
 
 pub use self::{
     context::{
-        AppContext, CompatSsoContext, ConsentContext, EmailAddContext, EmailVerificationContext,
-        EmailVerificationPageContext, EmptyContext, ErrorContext, FormPostContext, IndexContext,
-        LoginContext, LoginFormField, NotFoundContext, PolicyViolationContext, PostAuthContext,
-        PostAuthContextInner, ReauthContext, ReauthFormField, RegisterContext, RegisterFormField,
-        SiteBranding, TemplateContext, UpstreamExistingLinkContext, UpstreamRegister,
-        UpstreamRegisterFormField, UpstreamSuggestLink, WithCsrf, WithLanguage,
+        AcceptTermsContext, AcceptTermsFormField, AccountDataExportContext,
+        AccountDeactivateContext, AccountDeactivateFormField, AccountEmailsContext,
+        AccountProfileContext, AccountProfileFormField, AccountRecoveryCodesContext,
+        AccountWebauthnContext, AccountWebauthnFormField, AppContext, CompatSsoContext,
+        ConsentContext, DevMailboxContext, DevMailboxEmail, DeviceConsentContext,
+        DeviceLinkContext, DeviceLinkFormField, EmailAddContext,
+        EmailCompatPasswordDeprecationContext, EmailDataExportContext, EmailLoginLinkContext,
+        EmailPasswordResetContext, EmailRegistrationContext, EmailVerificationContext,
+        EmailVerificationFormField, EmailVerificationPageContext, EmptyContext, EndSessionContext,
+        ErrorContext, FormPostContext, IndexContext, LoginContext, LoginFormField,
+        LoginLinkContext, LoginLinkFinishContext, LoginLinkFormField, MaintenanceContext,
+        NotFoundContext, PolicyViolationContext, PostAuthContext, PostAuthContextInner,
+        ReauthContext, ReauthFormField, RecoveryCodeLoginContext, RecoveryCodeLoginFormField,
+        RecoveryFinishContext, RecoveryFinishFormField, RecoveryStartContext,
+        RecoveryStartFormField, RegisterContext, RegisterFormField, RegisterVerifyContext,
+        SiteBranding, SmsVerificationContext, TemplateContext, UpstreamExistingLinkContext,
+        UpstreamRegister, UpstreamRegisterFormField, UpstreamSuggestLink, WithCsrf, WithLanguage,
         WithOptionalSession, WithSession,
     },
     forms::{FieldError, FormError, FormField, FormState, ToFormState},
@@ -309,21 +320,51 @@ register_templates! {
     /// Render the not found fallback page
     pub fn render_not_found(WithLanguage<NotFoundContext>) { "pages/404.html" }
 
+    /// Render the maintenance page
+    pub fn render_maintenance(WithLanguage<MaintenanceContext>) { "pages/maintenance.html" }
+
+    /// Render the development mailbox page
+    pub fn render_dev_mailbox(WithLanguage<DevMailboxContext>) { "pages/dev/mailbox.html" }
+
     /// Render the frontend app
     pub fn render_app(WithLanguage<AppContext>) { "app.html" }
 
     /// Render the login page
     pub fn render_login(WithLanguage<WithCsrf<LoginContext>>) { "pages/login.html" }
 
+    /// Render the login page to sign in with a recovery code
+    pub fn render_recovery_code_login(WithLanguage<WithCsrf<RecoveryCodeLoginContext>>) { "pages/recovery_code_login.html" }
+
+    /// Render the form to ask for a login link by email
+    pub fn render_login_link(WithLanguage<WithCsrf<LoginLinkContext>>) { "pages/login_link/start.html" }
+
+    /// Render the page confirming the sign in with a login link
+    pub fn render_login_link_finish(WithLanguage<WithCsrf<LoginLinkFinishContext>>) { "pages/login_link/finish.html" }
+
+    /// Render the page shown when a login link is expired or was already used
+    pub fn render_login_link_expired(WithLanguage<EmptyContext>) { "pages/login_link/expired.html" }
+
     /// Render the registration page
     pub fn render_register(WithLanguage<WithCsrf<RegisterContext>>) { "pages/register.html" }
 
+    /// Render the email verification step of the registration
+    pub fn render_register_verify(WithLanguage<WithCsrf<RegisterVerifyContext>>) { "pages/register/verify.html" }
+
     /// Render the client consent page
     pub fn render_consent(WithLanguage<WithCsrf<WithSession<ConsentContext>>>) { "pages/consent.html" }
 
+    /// Render the device code link page
+    pub fn render_device_link(WithLanguage<WithCsrf<WithOptionalSession<DeviceLinkContext>>>) { "pages/device_link.html" }
+
+    /// Render the device code consent page
+    pub fn render_device_consent(WithLanguage<WithCsrf<WithSession<DeviceConsentContext>>>) { "pages/device_consent.html" }
+
     /// Render the policy violation page
     pub fn render_policy_violation(WithLanguage<WithCsrf<WithSession<PolicyViolationContext>>>) { "pages/policy_violation.html" }
 
+    /// Render the RP-initiated logout confirmation page
+    pub fn render_end_session(WithLanguage<WithCsrf<WithSession<EndSessionContext>>>) { "pages/end_session.html" }
+
     /// Render the legacy SSO login consent page
     pub fn render_sso_login(WithLanguage<WithCsrf<WithSession<CompatSsoContext>>>) { "pages/sso.html" }
 
@@ -339,9 +380,39 @@ register_templates! {
     /// Render the email verification page
     pub fn render_account_add_email(WithLanguage<WithCsrf<WithSession<EmailAddContext>>>) { "pages/account/emails/add.html" }
 
+    /// Render the email addresses management page
+    pub fn render_account_emails(WithLanguage<WithCsrf<WithSession<AccountEmailsContext>>>) { "pages/account/emails/index.html" }
+
+    /// Render the WebAuthn credentials management page
+    pub fn render_account_webauthn(WithLanguage<WithCsrf<WithSession<AccountWebauthnContext>>>) { "pages/account/webauthn.html" }
+
+    /// Render the recovery codes management page
+    pub fn render_account_recovery_codes(WithLanguage<WithCsrf<WithSession<AccountRecoveryCodesContext>>>) { "pages/account/recovery_codes.html" }
+
+    /// Render the profile page
+    pub fn render_account_profile(WithLanguage<WithCsrf<WithSession<AccountProfileContext>>>) { "pages/account/profile.html" }
+
+    /// Render the account deactivation page
+    pub fn render_account_deactivate(WithLanguage<WithCsrf<WithSession<AccountDeactivateContext>>>) { "pages/account/deactivate.html" }
+
+    /// Render the data export page
+    pub fn render_account_data_export(WithLanguage<WithCsrf<WithSession<AccountDataExportContext>>>) { "pages/account/export.html" }
+
+    /// Render the form to ask for a password recovery link
+    pub fn render_recovery_start(WithLanguage<WithCsrf<RecoveryStartContext>>) { "pages/recovery/start.html" }
+
+    /// Render the form to choose a new password with a recovery ticket
+    pub fn render_recovery_finish(WithLanguage<WithCsrf<RecoveryFinishContext>>) { "pages/recovery/finish.html" }
+
+    /// Render the page shown when a recovery ticket is expired or was already used
+    pub fn render_recovery_expired(WithLanguage<EmptyContext>) { "pages/recovery/expired.html" }
+
     /// Render the re-authentication form
     pub fn render_reauth(WithLanguage<WithCsrf<WithSession<ReauthContext>>>) { "pages/reauth.html" }
 
+    /// Render the page asking to accept the terms of service
+    pub fn render_accept_terms(WithLanguage<WithCsrf<WithSession<AcceptTermsContext>>>) { "pages/accept_terms.html" }
+
     /// Render the form used by the form_post response mode
     pub fn render_form_post<T: Serialize>(FormPostContext<T>) { "form_post.html" }
 
@@ -357,6 +428,54 @@ register_templates! {
     /// Render the email verification subject
     pub fn render_email_verification_subject(WithLanguage<EmailVerificationContext>) { "emails/verification.subject" }
 
+    /// Render the registration verification email (plain text variant)
+    pub fn render_email_registration_txt(WithLanguage<EmailRegistrationContext>) { "emails/registration.txt" }
+
+    /// Render the registration verification email (HTML text variant)
+    pub fn render_email_registration_html(WithLanguage<EmailRegistrationContext>) { "emails/registration.html" }
+
+    /// Render the registration verification email subject
+    pub fn render_email_registration_subject(WithLanguage<EmailRegistrationContext>) { "emails/registration.subject" }
+
+    /// Render the password reset email (plain text variant)
+    pub fn render_email_password_reset_txt(WithLanguage<EmailPasswordResetContext>) { "emails/password_reset.txt" }
+
+    /// Render the password reset email (HTML text variant)
+    pub fn render_email_password_reset_html(WithLanguage<EmailPasswordResetContext>) { "emails/password_reset.html" }
+
+    /// Render the password reset email subject
+    pub fn render_email_password_reset_subject(WithLanguage<EmailPasswordResetContext>) { "emails/password_reset.subject" }
+
+    /// Render the login link email (plain text variant)
+    pub fn render_email_login_link_txt(WithLanguage<EmailLoginLinkContext>) { "emails/login_link.txt" }
+
+    /// Render the login link email (HTML text variant)
+    pub fn render_email_login_link_html(WithLanguage<EmailLoginLinkContext>) { "emails/login_link.html" }
+
+    /// Render the login link email subject
+    pub fn render_email_login_link_subject(WithLanguage<EmailLoginLinkContext>) { "emails/login_link.subject" }
+
+    /// Render the data export email (plain text variant)
+    pub fn render_email_data_export_txt(WithLanguage<EmailDataExportContext>) { "emails/data_export.txt" }
+
+    /// Render the data export email (HTML text variant)
+    pub fn render_email_data_export_html(WithLanguage<EmailDataExportContext>) { "emails/data_export.html" }
+
+    /// Render the data export email subject
+    pub fn render_email_data_export_subject(WithLanguage<EmailDataExportContext>) { "emails/data_export.subject" }
+
+    /// Render the compatibility password login deprecation email (plain text variant)
+    pub fn render_email_compat_password_deprecation_txt(WithLanguage<EmailCompatPasswordDeprecationContext>) { "emails/compat_password_deprecation.txt" }
+
+    /// Render the compatibility password login deprecation email (HTML text variant)
+    pub fn render_email_compat_password_deprecation_html(WithLanguage<EmailCompatPasswordDeprecationContext>) { "emails/compat_password_deprecation.html" }
+
+    /// Render the compatibility password login deprecation email subject
+    pub fn render_email_compat_password_deprecation_subject(WithLanguage<EmailCompatPasswordDeprecationContext>) { "emails/compat_password_deprecation.subject" }
+
+    /// Render the text message with a verification code
+    pub fn render_sms_verification(WithLanguage<SmsVerificationContext>) { "sms/verification.txt" }
+
     /// Render the upstream link mismatch message
     pub fn render_upstream_oauth2_link_mismatch(WithLanguage<WithCsrf<WithSession<UpstreamExistingLinkContext>>>) { "pages/upstream_oauth2/link_mismatch.html" }
 
@@ -380,22 +499,58 @@ impl Templates {
         rng: &mut impl Rng,
     ) -> anyhow::Result<()> {
         check::render_not_found(self, now, rng)?;
+        check::render_maintenance(self, now, rng)?;
+        check::render_dev_mailbox(self, now, rng)?;
         check::render_app(self, now, rng)?;
         check::render_login(self, now, rng)?;
+        check::render_recovery_code_login(self, now, rng)?;
+        check::render_login_link(self, now, rng)?;
+        check::render_login_link_finish(self, now, rng)?;
+        check::render_login_link_expired(self, now, rng)?;
         check::render_register(self, now, rng)?;
+        check::render_register_verify(self, now, rng)?;
         check::render_consent(self, now, rng)?;
+        check::render_device_link(self, now, rng)?;
+        check::render_device_consent(self, now, rng)?;
         check::render_policy_violation(self, now, rng)?;
+        check::render_end_session(self, now, rng)?;
         check::render_sso_login(self, now, rng)?;
         check::render_index(self, now, rng)?;
         check::render_account_password(self, now, rng)?;
         check::render_account_add_email(self, now, rng)?;
+        check::render_account_emails(self, now, rng)?;
         check::render_account_verify_email(self, now, rng)?;
+        check::render_account_webauthn(self, now, rng)?;
+        check::render_account_recovery_codes(self, now, rng)?;
+        check::render_account_profile(self, now, rng)?;
+        check::render_account_deactivate(self, now, rng)?;
+        check::render_account_data_export(self, now, rng)?;
+        check::render_recovery_start(self, now, rng)?;
+        check::render_recovery_finish(self, now, rng)?;
+        check::render_recovery_expired(self, now, rng)?;
         check::render_reauth(self, now, rng)?;
+        check::render_accept_terms(self, now, rng)?;
         check::render_form_post::<EmptyContext>(self, now, rng)?;
         check::render_error(self, now, rng)?;
         check::render_email_verification_txt(self, now, rng)?;
         check::render_email_verification_html(self, now, rng)?;
         check::render_email_verification_subject(self, now, rng)?;
+        check::render_email_registration_txt(self, now, rng)?;
+        check::render_email_registration_html(self, now, rng)?;
+        check::render_email_registration_subject(self, now, rng)?;
+        check::render_email_password_reset_txt(self, now, rng)?;
+        check::render_email_password_reset_html(self, now, rng)?;
+        check::render_email_password_reset_subject(self, now, rng)?;
+        check::render_email_login_link_txt(self, now, rng)?;
+        check::render_email_login_link_html(self, now, rng)?;
+        check::render_email_login_link_subject(self, now, rng)?;
+        check::render_email_data_export_txt(self, now, rng)?;
+        check::render_email_data_export_html(self, now, rng)?;
+        check::render_email_data_export_subject(self, now, rng)?;
+        check::render_email_compat_password_deprecation_txt(self, now, rng)?;
+        check::render_email_compat_password_deprecation_html(self, now, rng)?;
+        check::render_email_compat_password_deprecation_subject(self, now, rng)?;
+        check::render_sms_verification(self, now, rng)?;
         check::render_upstream_oauth2_link_mismatch(self, now, rng)?;
         check::render_upstream_oauth2_suggest_link(self, now, rng)?;
         check::render_upstream_oauth2_do_register(self, now, rng)?;
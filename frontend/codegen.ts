@@ -27,6 +27,7 @@ const config: CodegenConfig = {
         scalars: {
           DateTime: "string",
           Url: "string",
+          Upload: "File",
         },
       },
     },
@@ -27,6 +27,7 @@ export const FRAGMENT = graphql(/* GraphQL */ `
     id
     createdAt
     deviceId
+    humanName
     finishedAt
     lastActiveIp
     lastActiveAt
@@ -105,7 +106,7 @@ const CompatSession: React.FC<{
   return (
     <Session
       id={data.id}
-      name={data.deviceId}
+      name={data.humanName || data.deviceId}
       createdAt={createdAt}
       finishedAt={finishedAt}
       clientName={clientName}
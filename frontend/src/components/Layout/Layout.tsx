@@ -35,8 +35,9 @@ const Layout: React.FC<{ children?: React.ReactNode }> = ({ children }) => {
 
   if (isErr(result)) return <GraphQLError error={unwrapErr(result)} />;
 
-  // Hide the nav bar & user greeting on the verify-email page
-  const shouldHideNavBar = route.type === "verify-email";
+  // Hide the nav bar & user greeting on the verify-email page, and when
+  // embedded in a client, which has its own navigation
+  const shouldHideNavBar = route.type === "verify-email" || appConfig.embedded;
 
   const userId = unwrapOk(result);
   if (userId === null)
@@ -65,11 +66,13 @@ const Layout: React.FC<{ children?: React.ReactNode }> = ({ children }) => {
 
       {children}
 
-      <Footer
-        imprint={appConfig.branding?.imprint}
-        tosUri={appConfig.branding?.tosUri}
-        policyUri={appConfig.branding?.policyUri}
-      />
+      {appConfig.embedded ? null : (
+        <Footer
+          imprint={appConfig.branding?.imprint}
+          tosUri={appConfig.branding?.tosUri}
+          policyUri={appConfig.branding?.policyUri}
+        />
+      )}
     </div>
   );
 };
@@ -16,6 +16,7 @@ import { Button } from "@vector-im/compound-web";
 import { useState } from "react";
 import { useTranslation } from "react-i18next";
 
+import { notifyEmbedder } from "../../utils/embedding";
 import ConfirmationModal from "../ConfirmationModal/ConfirmationModal";
 import LoadingSpinner from "../LoadingSpinner/LoadingSpinner";
 
@@ -33,6 +34,7 @@ const EndSessionButton: React.FC<{ endSession: () => Promise<void> }> = ({
     setInProgress(true);
     try {
       await endSession();
+      notifyEmbedder("session_ended");
     } catch (error) {
       console.error("Failed to end session", error);
     }
@@ -32,6 +32,7 @@ export const FRAGMENT = graphql(/* GraphQL */ `
     id
     createdAt
     deviceId
+    humanName
     finishedAt
     lastActiveIp
     lastActiveAt
@@ -113,7 +114,7 @@ const CompatSessionDetail: React.FC<Props> = ({ session }) => {
           type: "sessions-overview",
         }}
       >
-        {data.deviceId || data.id}
+        {data.humanName || data.deviceId || data.id}
       </SessionHeader>
       <SessionDetails
         title={t("frontend.compat_session_detail.session_details_title")}
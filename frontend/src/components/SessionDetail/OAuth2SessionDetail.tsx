@@ -37,6 +37,7 @@ export const FRAGMENT = graphql(/* GraphQL */ `
     finishedAt
     lastActiveIp
     lastActiveAt
+    expiresAt
     client {
       id
       clientId
@@ -86,6 +87,18 @@ const OAuth2SessionDetail: React.FC<Props> = ({ session }) => {
       ]
     : [];
 
+  // Only shown when the server has refresh token lifetimes configured, so that
+  // users know when they will have to sign in again
+  const expiresAt =
+    data.expiresAt && !data.finishedAt
+      ? [
+          {
+            label: "Expires",
+            value: <DateTime datetime={parseISO(data.expiresAt)} />,
+          },
+        ]
+      : [];
+
   const sessionDetails = [
     { label: "ID", value: <code>{data.id}</code> },
     { label: "Device ID", value: <code>{deviceId}</code> },
@@ -93,6 +106,7 @@ const OAuth2SessionDetail: React.FC<Props> = ({ session }) => {
     ...finishedAt,
     ...lastActiveAt,
     ...lastActiveIp,
+    ...expiresAt,
     {
       label: "Scopes",
       value: (
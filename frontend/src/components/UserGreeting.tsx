@@ -31,6 +31,7 @@ const QUERY = graphql(/* GraphQL */ `
       matrix {
         mxid
         displayName
+        avatarUrl
       }
 
       ...UnverifiedEmailAlert
@@ -38,6 +39,10 @@ const QUERY = graphql(/* GraphQL */ `
   }
 `);
 
+// Avatars set from Matrix clients are `mxc://` URIs, which browsers can't load
+const avatarSrc = (avatarUrl?: string | null): string | undefined =>
+  avatarUrl && /^https?:\/\//.test(avatarUrl) ? avatarUrl : undefined;
+
 export const userGreetingFamily = atomFamily((userId: string) => {
   const userGreeting = atomWithQuery({
     query: QUERY,
@@ -60,6 +65,7 @@ const UserGreeting: React.FC<{ userId: string }> = ({ userId }) => {
             size="var(--cpd-space-24x)"
             id={user.matrix.mxid}
             name={user.matrix.displayName || user.matrix.mxid}
+            src={avatarSrc(user.matrix.avatarUrl)}
           />
           <Heading size="xl" weight="semibold">
             {user.matrix.displayName || user.username}
@@ -19,12 +19,14 @@ import { useRef, useTransition } from "react";
 import { useTranslation } from "react-i18next";
 
 import { graphql } from "../../gql";
+import { useCountdown } from "../../utils/useCountdown";
 
 const ADD_EMAIL_MUTATION = graphql(/* GraphQL */ `
   mutation AddEmail($userId: ID!, $email: String!) {
     addEmail(input: { userId: $userId, email: $email }) {
       status
       violations
+      retryAfter
       email {
         id
         ...UserEmail_email
@@ -74,7 +76,13 @@ const AddEmailForm: React.FC<{
   const emailExists = status === "EXISTS";
   const emailInvalid = status === "INVALID";
   const emailDenied = status === "DENIED";
+  const emailInUse = status === "IN_USE";
   const violations = addEmailResult.data?.addEmail.violations ?? [];
+  const cooldown = useCountdown(
+    addEmailResult.data?.addEmail.retryAfter,
+    addEmailResult.data,
+  );
+  const rateLimited = status === "RATE_LIMITED" && cooldown > 0;
 
   return (
     <>
@@ -97,6 +105,26 @@ const AddEmailForm: React.FC<{
           </Alert>
         )}
 
+        {emailInUse && (
+          <Alert
+            type="critical"
+            title={t("frontend.add_email_form.email_in_use_alert.title")}
+          >
+            {t("frontend.add_email_form.email_in_use_alert.text")}
+          </Alert>
+        )}
+
+        {rateLimited && (
+          <Alert
+            type="critical"
+            title={t("frontend.add_email_form.rate_limited_alert.title")}
+          >
+            {t("frontend.add_email_form.rate_limited_alert.text", {
+              count: cooldown,
+            })}
+          </Alert>
+        )}
+
         {emailDenied && (
           <Alert
             type="critical"
@@ -113,13 +141,15 @@ const AddEmailForm: React.FC<{
 
         <Form.Field
           name="email"
-          serverInvalid={emailInvalid || emailExists || emailDenied}
+          serverInvalid={
+            emailInvalid || emailExists || emailDenied || emailInUse
+          }
         >
           <Form.Label>
             {t("frontend.add_email_form.email_field_label")}
           </Form.Label>
           <Form.TextControl
-            disabled={pending}
+            disabled={pending || rateLimited}
             type="email"
             autoComplete="email"
             ref={fieldRef}
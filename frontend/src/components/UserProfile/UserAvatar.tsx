@@ -0,0 +1,122 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import { Alert, Form } from "@vector-im/compound-web";
+import { useAtom, atom } from "jotai";
+import { atomFamily } from "jotai/utils";
+import { atomWithMutation } from "jotai-urql";
+import { useState, ChangeEventHandler } from "react";
+import { useTranslation } from "react-i18next";
+
+import { graphql } from "../../gql";
+import LoadingSpinner from "../LoadingSpinner/LoadingSpinner";
+import { userGreetingFamily } from "../UserGreeting";
+
+const UPLOAD_AVATAR_MUTATION = graphql(/* GraphQL */ `
+  mutation UploadAvatar($userId: ID!, $file: Upload!) {
+    uploadAvatar(input: { userId: $userId, file: $file }) {
+      status
+      user {
+        id
+        matrix {
+          avatarUrl
+        }
+      }
+    }
+  }
+`);
+
+const uploadAvatarFamily = atomFamily((userId: string) => {
+  const uploadAvatar = atomWithMutation(UPLOAD_AVATAR_MUTATION);
+
+  // A proxy atom which pre-sets the id variable in the mutation
+  const uploadAvatarAtom = atom(
+    (get) => get(uploadAvatar),
+    (get, set, file: File) => set(uploadAvatar, { userId, file }),
+  );
+
+  return uploadAvatarAtom;
+});
+
+const UserAvatar: React.FC<{ userId: string }> = ({ userId }) => {
+  const [, refreshUserGreeting] = useAtom(userGreetingFamily(userId));
+  const [uploadAvatarResult, uploadAvatar] = useAtom(
+    uploadAvatarFamily(userId),
+  );
+  const [inProgress, setInProgress] = useState(false);
+
+  const { t } = useTranslation();
+
+  const onChange: ChangeEventHandler<HTMLInputElement> = (event): void => {
+    const input = event.currentTarget;
+    const file = input.files?.[0];
+    if (!file) {
+      return;
+    }
+
+    setInProgress(true);
+    uploadAvatar(file).then((result) => {
+      if (!result.data) {
+        console.error("Failed to upload avatar", result.error);
+      } else if (result.data.uploadAvatar.status === "UPLOADED") {
+        // refresh the user greeting to show the new avatar
+        refreshUserGreeting({
+          requestPolicy: "network-only",
+        });
+      }
+
+      input.value = "";
+      setInProgress(false);
+    });
+  };
+
+  let errorMessage: string | undefined;
+  if (uploadAvatarResult.error) {
+    errorMessage = t("frontend.user_avatar.error_alert");
+  } else {
+    switch (uploadAvatarResult.data?.uploadAvatar.status) {
+      case "TOO_LARGE":
+        errorMessage = t("frontend.user_avatar.too_large_alert");
+        break;
+      case "INVALID_TYPE":
+        errorMessage = t("frontend.user_avatar.invalid_type_alert");
+        break;
+      case "DISABLED":
+        errorMessage = t("frontend.user_avatar.disabled_alert");
+        break;
+    }
+  }
+
+  return (
+    <Form.Root onSubmit={(event): void => event.preventDefault()}>
+      <Form.Field name="avatar" serverInvalid={!inProgress && !!errorMessage}>
+        <Form.Label>{t("frontend.user_avatar.field_label")}</Form.Label>
+        <Form.Control
+          type="file"
+          accept="image/png,image/jpeg,image/gif,image/webp"
+          disabled={inProgress}
+          onChange={onChange}
+        />
+      </Form.Field>
+      {!!inProgress && <LoadingSpinner inline />}
+      {!inProgress && errorMessage && (
+        <Alert type="critical" title={t("common.error")}>
+          {errorMessage}
+        </Alert>
+      )}
+    </Form.Root>
+  );
+};
+
+export default UserAvatar;
@@ -17,6 +17,7 @@ import { Separator } from "@vector-im/compound-web";
 import BlockList from "../BlockList/BlockList";
 
 import CrossSigningReset from "./CrossSigningReset";
+import UserAvatar from "./UserAvatar";
 import UserEmailList from "./UserEmailList";
 import UserName from "./UserName";
 
@@ -24,6 +25,7 @@ const UserProfile: React.FC<{ userId: string }> = ({ userId }) => {
   return (
     <BlockList>
       <UserName userId={userId} />
+      <UserAvatar userId={userId} />
       <UserEmailList userId={userId} />
       <Separator />
       <CrossSigningReset userId={userId} />
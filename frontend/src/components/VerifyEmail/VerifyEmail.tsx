@@ -23,6 +23,8 @@ import { Trans, useTranslation } from "react-i18next";
 
 import { FragmentType, graphql, useFragment } from "../../gql";
 import { routeAtom, useNavigationLink } from "../../routing";
+import { notifyEmbedder } from "../../utils/embedding";
+import { useCountdown } from "../../utils/useCountdown";
 
 import styles from "./VerifyEmail.module.css";
 
@@ -57,6 +59,7 @@ const RESEND_VERIFICATION_EMAIL_MUTATION = graphql(/* GraphQL */ `
   mutation ResendVerificationEmail($id: ID!) {
     sendVerificationEmail(input: { userEmailId: $id }) {
       status
+      retryAfter
 
       user {
         id
@@ -142,6 +145,7 @@ const VerifyEmail: React.FC<{
         form.reset();
 
         if (result.data?.verifyEmail.status === "VERIFIED") {
+          notifyEmbedder("email_verified");
           setRoute({ type: "profile" });
         }
       });
@@ -160,6 +164,14 @@ const VerifyEmail: React.FC<{
     resendVerificationEmailResult.data?.sendVerificationEmail.status === "SENT";
   const invalidCode =
     verifyEmailResult.data?.verifyEmail.status === "INVALID_CODE";
+  const emailInUse = verifyEmailResult.data?.verifyEmail.status === "IN_USE";
+  const cooldown = useCountdown(
+    resendVerificationEmailResult.data?.sendVerificationEmail.retryAfter,
+    resendVerificationEmailResult.data,
+  );
+  const rateLimited =
+    resendVerificationEmailResult.data?.sendVerificationEmail.status ===
+      "RATE_LIMITED" && cooldown > 0;
   const { email: codeEmail } = data;
 
   return (
@@ -185,6 +197,24 @@ const VerifyEmail: React.FC<{
             {t("frontend.verify_email.email_sent_alert.description")}
           </Alert>
         )}
+        {rateLimited && (
+          <Alert
+            type="critical"
+            title={t("frontend.verify_email.rate_limited_alert.title")}
+          >
+            {t("frontend.verify_email.rate_limited_alert.description", {
+              count: cooldown,
+            })}
+          </Alert>
+        )}
+        {emailInUse && (
+          <Alert
+            type="critical"
+            title={t("frontend.verify_email.email_in_use_alert.title")}
+          >
+            {t("frontend.verify_email.email_in_use_alert.description")}
+          </Alert>
+        )}
         {invalidCode && (
           <Alert
             type="critical"
@@ -218,10 +248,12 @@ const VerifyEmail: React.FC<{
         <Button
           type="button"
           kind="secondary"
-          disabled={pending}
+          disabled={pending || rateLimited}
           onClick={onResendClick}
         >
-          {t("frontend.verify_email.resend_code")}
+          {rateLimited
+            ? t("frontend.verify_email.resend_code_in", { count: cooldown })
+            : t("frontend.verify_email.resend_code")}
         </Button>
         <BackButton />
       </Form.Root>
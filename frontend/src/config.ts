@@ -15,6 +15,8 @@
 export type AppConfig = {
   root: string;
   graphqlEndpoint: string;
+  embedded?: boolean;
+  embeddingOrigins?: string[];
   branding?: {
     tosUri?: string;
     policyUri?: string;
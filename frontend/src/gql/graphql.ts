@@ -33,10 +33,33 @@ export type Scalars = {
    * The input/output is a string in RFC3339 format.
    */
   DateTime: { input: string; output: string };
+  Upload: { input: File; output: File };
   /** URL is a String implementing the [URL Standard](http://url.spec.whatwg.org/) */
   Url: { input: string; output: string };
 };
 
+/** The input of the `acknowledgeSignIn` mutation. */
+export type AcknowledgeSignInInput = {
+  /** The ID of the sign-in notification to acknowledge. */
+  signInNotificationId: Scalars["ID"]["input"];
+};
+
+export type AcknowledgeSignInPayload = {
+  __typename?: "AcknowledgeSignInPayload";
+  /** Returns the acknowledged sign-in notification. */
+  signInNotification?: Maybe<SignInNotification>;
+  /** The status of the mutation. */
+  status: AcknowledgeSignInStatus;
+};
+
+/** The status of the `acknowledgeSignIn` mutation. */
+export enum AcknowledgeSignInStatus {
+  /** The sign-in was acknowledged. */
+  Acknowledged = "ACKNOWLEDGED",
+  /** The sign-in notification was not found. */
+  NotFound = "NOT_FOUND",
+}
+
 /** The input for the `addEmail` mutation */
 export type AddEmailInput = {
   /** The email address to add */
@@ -54,6 +77,11 @@ export type AddEmailPayload = {
   __typename?: "AddEmailPayload";
   /** The email address that was added */
   email?: Maybe<UserEmail>;
+  /**
+   * How many seconds to wait before trying again, if the rate limit was
+   * exceeded
+   */
+  retryAfter?: Maybe<Scalars["Int"]["output"]>;
   /** Status of the operation */
   status: AddEmailStatus;
   /** The user to whom the email address was added */
@@ -70,12 +98,21 @@ export enum AddEmailStatus {
   Denied = "DENIED",
   /** The email address already exists */
   Exists = "EXISTS",
+  /** The email address is already in use by another account */
+  InUse = "IN_USE",
   /** The email address is invalid */
   Invalid = "INVALID",
+  /** Too many emails were sent recently, try again later */
+  RateLimited = "RATE_LIMITED",
 }
 
 /** The input for the `addUser` mutation. */
 export type AddUserInput = {
+  /**
+   * Whether the user is a service account, which can't log in
+   * interactively. Defaults to `false`.
+   */
+  serviceAccount?: InputMaybe<Scalars["Boolean"]["input"]>;
   /** The username of the user to add. */
   username: Scalars["String"]["input"];
 };
@@ -172,6 +209,11 @@ export type BrowserSession = CreationEvent &
     lastAuthentication?: Maybe<Authentication>;
     /** The state of the session. */
     state: SessionState;
+    /**
+     * The sign-ins of the user which happened after this session started and
+     * were not acknowledged yet, oldest first.
+     */
+    unacknowledgedSignIns: Array<SignInNotification>;
     /** The user logged in this session. */
     user: User;
     /** The user-agent string with which the session was created. */
@@ -212,6 +254,11 @@ export type CompatSession = CreationEvent &
     deviceId: Scalars["String"]["output"];
     /** When the session ended. */
     finishedAt?: Maybe<Scalars["DateTime"]["output"]>;
+    /**
+     * A human-readable name for the session, supplied by the client when
+     * logging in, derived from its user agent, or set by the user.
+     */
+    humanName?: Maybe<Scalars["String"]["output"]>;
     /** ID of the object. */
     id: Scalars["ID"]["output"];
     /** The last time the session was active. */
@@ -326,6 +373,58 @@ export type CreationEvent = {
   createdAt: Scalars["DateTime"]["output"];
 };
 
+/** Usage statistics of a single day. */
+export type DailyStatistics = {
+  __typename?: "DailyStatistics";
+  /** Number of OAuth 2.0 and compatibility sessions active on that day. */
+  activeSessions: Scalars["Int"]["output"];
+  /** Number of users with a session active on that day. */
+  activeUsers: Scalars["Int"]["output"];
+  /**
+   * When the statistics were last computed. The statistics of the current
+   * day are updated every hour.
+   */
+  computedAt: Scalars["DateTime"]["output"];
+  /** The start of the day, at midnight UTC. */
+  day: Scalars["DateTime"]["output"];
+  /** Number of users created on that day. */
+  newUsers: Scalars["Int"]["output"];
+  /**
+   * Number of OAuth 2.0 refresh tokens and compatibility access tokens
+   * issued on that day.
+   */
+  tokensIssued: Scalars["Int"]["output"];
+};
+
+/** The input for the `deleteUser` mutation. */
+export type DeleteUserInput = {
+  /**
+   * Deactivate the user on the homeserver right away, instead of when it
+   * gets purged. The user can still be restored, but not on the
+   * homeserver.
+   */
+  deactivate?: InputMaybe<Scalars["Boolean"]["input"]>;
+  /** The ID of the user to delete. */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload for the `deleteUser` mutation. */
+export type DeleteUserPayload = {
+  __typename?: "DeleteUserPayload";
+  /** Status of the operation */
+  status: DeleteUserStatus;
+  /** The user that was deleted. */
+  user?: Maybe<User>;
+};
+
+/** The status of the `deleteUser` mutation. */
+export enum DeleteUserStatus {
+  /** The user was deleted. */
+  Deleted = "DELETED",
+  /** The user was not found. */
+  NotFound = "NOT_FOUND",
+}
+
 /** The input of the `endBrowserSession` mutation. */
 export type EndBrowserSessionInput = {
   /** The ID of the session to end. */
@@ -370,6 +469,32 @@ export enum EndCompatSessionStatus {
   NotFound = "NOT_FOUND",
 }
 
+/** The input of the `endOauth2ClientSessions` mutation. */
+export type EndOAuth2ClientSessionsInput = {
+  /** The ID of the client to revoke access from. */
+  oauth2ClientId: Scalars["ID"]["input"];
+  /** The ID of the user whose sessions should be ended. */
+  userId: Scalars["ID"]["input"];
+};
+
+export type EndOAuth2ClientSessionsPayload = {
+  __typename?: "EndOAuth2ClientSessionsPayload";
+  /** The number of sessions which were ended. */
+  endedSessions: Scalars["Int"]["output"];
+  /** The client which lost access to the account. */
+  oauth2Client?: Maybe<Oauth2Client>;
+  /** The status of the mutation. */
+  status: EndOAuth2ClientSessionsStatus;
+};
+
+/** The status of the `endOauth2ClientSessions` mutation. */
+export enum EndOAuth2ClientSessionsStatus {
+  /** The sessions of the client were ended. */
+  Ended = "ENDED",
+  /** The user or the client was not found. */
+  NotFound = "NOT_FOUND",
+}
+
 /** The input of the `endOauth2Session` mutation. */
 export type EndOAuth2SessionInput = {
   /** The ID of the session to end. */
@@ -392,6 +517,29 @@ export enum EndOAuth2SessionStatus {
   NotFound = "NOT_FOUND",
 }
 
+/** The input for the `forcePasswordReset` mutation. */
+export type ForcePasswordResetInput = {
+  /** The ID of the user who must reset their password. */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload for the `forcePasswordReset` mutation. */
+export type ForcePasswordResetPayload = {
+  __typename?: "ForcePasswordResetPayload";
+  /** Status of the operation */
+  status: ForcePasswordResetStatus;
+  /** The user who must reset their password. */
+  user?: Maybe<User>;
+};
+
+/** The status of the `forcePasswordReset` mutation. */
+export enum ForcePasswordResetStatus {
+  /** The user was not found. */
+  NotFound = "NOT_FOUND",
+  /** The user must now reset their password. */
+  Required = "REQUIRED",
+}
+
 /** The input for the `lockUser` mutation. */
 export type LockUserInput = {
   /** Permanently lock the user. */
@@ -430,6 +578,11 @@ export type MatrixUser = {
 /** The mutations root of the GraphQL interface. */
 export type Mutation = {
   __typename?: "Mutation";
+  /**
+   * Acknowledge a new sign-in of the user, so that it stops being shown in
+   * their other sessions.
+   */
+  acknowledgeSignIn: AcknowledgeSignInPayload;
   /** Add an email address to the specified user */
   addEmail: AddEmailPayload;
   /** Add a user. This is only available to administrators. */
@@ -442,13 +595,44 @@ export type Mutation = {
    * Only available for administrators.
    */
   createOauth2Session: CreateOAuth2SessionPayload;
+  /**
+   * Delete a user. The user can't be used anymore, but can be restored
+   * until the end of the grace period, after which its data is erased.
+   * This is only available to administrators.
+   */
+  deleteUser: DeleteUserPayload;
   endBrowserSession: EndBrowserSessionPayload;
   endCompatSession: EndCompatSessionPayload;
+  /**
+   * End all the active sessions of a user with a client, revoking the
+   * access this client has to their account.
+   */
+  endOauth2ClientSessions: EndOAuth2ClientSessionsPayload;
   endOauth2Session: EndOAuth2SessionPayload;
+  /**
+   * Require a user to choose a new password, e.g. after a suspected
+   * credential leak. All their sessions are ended, and they get an email
+   * with a link to choose a new password. This is only available to
+   * administrators.
+   */
+  forcePasswordReset: ForcePasswordResetPayload;
   /** Lock a user. This is only available to administrators. */
   lockUser: LockUserPayload;
+  /**
+   * Generate a new set of recovery codes for a user, invalidating the
+   * previous ones. This is only available to the user themselves, as the
+   * codes are returned in the response.
+   */
+  regenerateRecoveryCodes: RegenerateRecoveryCodesPayload;
+  /** Remove the avatar of a user */
+  removeAvatar: RemoveAvatarPayload;
   /** Remove an email address */
   removeEmail: RemoveEmailPayload;
+  /**
+   * Restore a deleted user, as long as it wasn't purged yet. This is only
+   * available to administrators.
+   */
+  restoreUser: RestoreUserPayload;
   /** Send a verification code for an email address */
   sendVerificationEmail: SendVerificationEmailPayload;
   /**
@@ -456,14 +640,36 @@ export type Mutation = {
    * administrators.
    */
   setCanRequestAdmin: SetCanRequestAdminPayload;
+  /**
+   * Rename a compatibility session, for example to "Work laptop". The name
+   * is also used as the display name of the device on the homeserver.
+   */
+  setCompatSessionName: SetCompatSessionNamePayload;
   /** Set the display name of a user */
   setDisplayName: SetDisplayNamePayload;
+  /**
+   * Rename an OAuth 2.0 session, for example to "Work laptop". The name is
+   * also used as the display name of its device on the homeserver.
+   */
+  setOauth2SessionName: SetOAuth2SessionNamePayload;
+  /**
+   * Set the preferred language of a user, used for the emails sent to them
+   * and the pages they see.
+   */
+  setPreferredLanguage: SetPreferredLanguagePayload;
   /** Set an email address as primary */
   setPrimaryEmail: SetPrimaryEmailPayload;
+  /** Upload an image and set it as the avatar of a user */
+  uploadAvatar: UploadAvatarPayload;
   /** Submit a verification code for an email address */
   verifyEmail: VerifyEmailPayload;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationAcknowledgeSignInArgs = {
+  input: AcknowledgeSignInInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationAddEmailArgs = {
   input: AddEmailInput;
@@ -484,6 +690,11 @@ export type MutationCreateOauth2SessionArgs = {
   input: CreateOAuth2SessionInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationDeleteUserArgs = {
+  input: DeleteUserInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationEndBrowserSessionArgs = {
   input: EndBrowserSessionInput;
@@ -494,21 +705,46 @@ export type MutationEndCompatSessionArgs = {
   input: EndCompatSessionInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationEndOauth2ClientSessionsArgs = {
+  input: EndOAuth2ClientSessionsInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationEndOauth2SessionArgs = {
   input: EndOAuth2SessionInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationForcePasswordResetArgs = {
+  input: ForcePasswordResetInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationLockUserArgs = {
   input: LockUserInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationRegenerateRecoveryCodesArgs = {
+  input: RegenerateRecoveryCodesInput;
+};
+
+/** The mutations root of the GraphQL interface. */
+export type MutationRemoveAvatarArgs = {
+  input: RemoveAvatarInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationRemoveEmailArgs = {
   input: RemoveEmailInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationRestoreUserArgs = {
+  input: RestoreUserInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationSendVerificationEmailArgs = {
   input: SendVerificationEmailInput;
@@ -519,16 +755,36 @@ export type MutationSetCanRequestAdminArgs = {
   input: SetCanRequestAdminInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationSetCompatSessionNameArgs = {
+  input: SetCompatSessionNameInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationSetDisplayNameArgs = {
   input: SetDisplayNameInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationSetOauth2SessionNameArgs = {
+  input: SetOAuth2SessionNameInput;
+};
+
+/** The mutations root of the GraphQL interface. */
+export type MutationSetPreferredLanguageArgs = {
+  input: SetPreferredLanguageInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationSetPrimaryEmailArgs = {
   input: SetPrimaryEmailInput;
 };
 
+/** The mutations root of the GraphQL interface. */
+export type MutationUploadAvatarArgs = {
+  input: UploadAvatarInput;
+};
+
 /** The mutations root of the GraphQL interface. */
 export type MutationVerifyEmailArgs = {
   input: VerifyEmailInput;
@@ -548,6 +804,28 @@ export enum Oauth2ApplicationType {
   Web = "WEB",
 }
 
+/**
+ * How far the authorization grants of a client went in the authorization
+ * flow.
+ */
+export type Oauth2AuthorizationGrantFunnel = {
+  __typename?: "Oauth2AuthorizationGrantFunnel";
+  /** Number of authorization grants which were abandoned by the user. */
+  abandoned: Scalars["Int"]["output"];
+  /** OAuth 2.0 client which started the authorization grants. */
+  client: Oauth2Client;
+  /** Number of authorization grants which were completed. */
+  completed: Scalars["Int"]["output"];
+  /** Number of authorization grants which showed the consent screen. */
+  consentShown: Scalars["Int"]["output"];
+  /** Number of authorization grants which ended with an error. */
+  errored: Scalars["Int"]["output"];
+  /** Number of authorization grants which sent the user to a login page. */
+  loginShown: Scalars["Int"]["output"];
+  /** Number of authorization grants started. */
+  started: Scalars["Int"]["output"];
+};
+
 /** An OAuth 2.0 client */
 export type Oauth2Client = Node & {
   __typename?: "Oauth2Client";
@@ -586,8 +864,15 @@ export type Oauth2Session = CreationEvent &
     client: Oauth2Client;
     /** When the object was created. */
     createdAt: Scalars["DateTime"]["output"];
+    /**
+     * When the session will expire if it isn't used anymore, according to
+     * the refresh token lifetimes configured on the server for its client.
+     */
+    expiresAt?: Maybe<Scalars["DateTime"]["output"]>;
     /** When the session ended. */
     finishedAt?: Maybe<Scalars["DateTime"]["output"]>;
+    /** A human-readable name the user gave to the session. */
+    humanName?: Maybe<Scalars["String"]["output"]>;
     /** ID of the object. */
     id: Scalars["ID"]["output"];
     /** The last time the session was active. */
@@ -651,8 +936,22 @@ export type Query = {
    * @deprecated Use `viewer` instead.
    */
   currentUser?: Maybe<User>;
+  /**
+   * Get the usage statistics of each day since the given date, oldest
+   * first.
+   *
+   * This is only available to administrators.
+   */
+  dailyStatistics: Array<DailyStatistics>;
   /** Fetches an object given its ID. */
   node?: Maybe<Node>;
+  /**
+   * Get how far the authorization grants started since the given date went
+   * in the authorization flow, per client.
+   *
+   * This is only available to administrators.
+   */
+  oauth2AuthorizationGrantFunnel: Array<Oauth2AuthorizationGrantFunnel>;
   /** Fetch an OAuth 2.0 client by its ID. */
   oauth2Client?: Maybe<Oauth2Client>;
   /** Lookup a compat or OAuth 2.0 session */
@@ -669,6 +968,12 @@ export type Query = {
   userByUsername?: Maybe<User>;
   /** Fetch a user email by its ID. */
   userEmail?: Maybe<UserEmail>;
+  /**
+   * Get a list of users, chronologically sorted.
+   *
+   * Only available for administrators.
+   */
+  users: UserConnection;
   /** Get the viewer */
   viewer: Viewer;
   /** Get the viewer's session */
@@ -680,11 +985,21 @@ export type QueryBrowserSessionArgs = {
   id: Scalars["ID"]["input"];
 };
 
+/** The query root of the GraphQL interface. */
+export type QueryDailyStatisticsArgs = {
+  since: Scalars["DateTime"]["input"];
+};
+
 /** The query root of the GraphQL interface. */
 export type QueryNodeArgs = {
   id: Scalars["ID"]["input"];
 };
 
+/** The query root of the GraphQL interface. */
+export type QueryOauth2AuthorizationGrantFunnelArgs = {
+  since: Scalars["DateTime"]["input"];
+};
+
 /** The query root of the GraphQL interface. */
 export type QueryOauth2ClientArgs = {
   id: Scalars["ID"]["input"];
@@ -729,6 +1044,67 @@ export type QueryUserEmailArgs = {
   id: Scalars["ID"]["input"];
 };
 
+/** The query root of the GraphQL interface. */
+export type QueryUsersArgs = {
+  after?: InputMaybe<Scalars["String"]["input"]>;
+  before?: InputMaybe<Scalars["String"]["input"]>;
+  createdAfter?: InputMaybe<Scalars["DateTime"]["input"]>;
+  createdBefore?: InputMaybe<Scalars["DateTime"]["input"]>;
+  first?: InputMaybe<Scalars["Int"]["input"]>;
+  last?: InputMaybe<Scalars["Int"]["input"]>;
+  search?: InputMaybe<Scalars["String"]["input"]>;
+  state?: InputMaybe<UserState>;
+};
+
+/** The input for the `regenerateRecoveryCodes` mutation. */
+export type RegenerateRecoveryCodesInput = {
+  /** The ID of the user to regenerate the recovery codes for. */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload for the `regenerateRecoveryCodes` mutation. */
+export type RegenerateRecoveryCodesPayload = {
+  __typename?: "RegenerateRecoveryCodesPayload";
+  /**
+   * The new recovery codes. They replace the previous ones, and can't be
+   * retrieved again later.
+   */
+  codes?: Maybe<Array<Scalars["String"]["output"]>>;
+  /** Status of the operation */
+  status: RegenerateRecoveryCodesStatus;
+  /** The user whose recovery codes were regenerated. */
+  user?: Maybe<User>;
+};
+
+/** The status of the `regenerateRecoveryCodes` mutation. */
+export enum RegenerateRecoveryCodesStatus {
+  /** The user was not found. */
+  NotFound = "NOT_FOUND",
+  /** New recovery codes were generated. */
+  Regenerated = "REGENERATED",
+}
+
+/** The input for the `removeAvatar` mutation */
+export type RemoveAvatarInput = {
+  /** The ID of the user to remove the avatar of */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload of the `removeAvatar` mutation */
+export type RemoveAvatarPayload = {
+  __typename?: "RemoveAvatarPayload";
+  /** Status of the operation */
+  status: RemoveAvatarStatus;
+  /** The user that was updated */
+  user?: Maybe<User>;
+};
+
+/** The status of the `removeAvatar` mutation */
+export enum RemoveAvatarStatus {
+  /** The avatar was removed */
+  Removed = "REMOVED",
+}
+
 /** The input for the `removeEmail` mutation */
 export type RemoveEmailInput = {
   /** The ID of the email address to remove */
@@ -756,6 +1132,31 @@ export enum RemoveEmailStatus {
   Removed = "REMOVED",
 }
 
+/** The input for the `restoreUser` mutation. */
+export type RestoreUserInput = {
+  /** The ID of the user to restore. */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload for the `restoreUser` mutation. */
+export type RestoreUserPayload = {
+  __typename?: "RestoreUserPayload";
+  /** Status of the operation */
+  status: RestoreUserStatus;
+  /** The user that was restored. */
+  user?: Maybe<User>;
+};
+
+/** The status of the `restoreUser` mutation. */
+export enum RestoreUserStatus {
+  /** The user was not found. */
+  NotFound = "NOT_FOUND",
+  /** The user was already purged, and can't be restored anymore. */
+  Purged = "PURGED",
+  /** The user was restored. */
+  Restored = "RESTORED",
+}
+
 /** The input for the `sendVerificationEmail` mutation */
 export type SendVerificationEmailInput = {
   /** The ID of the email address to verify */
@@ -767,6 +1168,11 @@ export type SendVerificationEmailPayload = {
   __typename?: "SendVerificationEmailPayload";
   /** The email address to which the verification email was sent */
   email: UserEmail;
+  /**
+   * How many seconds to wait before trying again, if the rate limit was
+   * exceeded
+   */
+  retryAfter?: Maybe<Scalars["Int"]["output"]>;
   /** Status of the operation */
   status: SendVerificationEmailStatus;
   /** The user to whom the email address belongs */
@@ -777,6 +1183,8 @@ export type SendVerificationEmailPayload = {
 export enum SendVerificationEmailStatus {
   /** The email address is already verified */
   AlreadyVerified = "ALREADY_VERIFIED",
+  /** Too many emails were sent recently, try again later */
+  RateLimited = "RATE_LIMITED",
   /** The verification email was sent */
   Sent = "SENT",
 }
@@ -784,6 +1192,30 @@ export enum SendVerificationEmailStatus {
 /** A client session, either compat or OAuth 2.0 */
 export type Session = CompatSession | Oauth2Session;
 
+/** Something which happened to one of the sessions of the user */
+export type SessionEvent = {
+  __typename?: "SessionEvent";
+  /** What happened to the session */
+  kind: SessionEventKind;
+  /**
+   * The session, in its current state. Is `null` if it does not exist
+   * anymore.
+   */
+  session?: Maybe<Node>;
+  /** ID of the session */
+  sessionId: Scalars["ID"]["output"];
+};
+
+/** What happened to a session */
+export enum SessionEventKind {
+  /** The user authenticated again in the session */
+  AuthenticationAdded = "AUTHENTICATION_ADDED",
+  /** The session was started */
+  Created = "CREATED",
+  /** The session was ended */
+  Ended = "ENDED",
+}
+
 /** The state of a session */
 export enum SessionState {
   /** The session is active. */
@@ -807,6 +1239,32 @@ export type SetCanRequestAdminPayload = {
   user?: Maybe<User>;
 };
 
+/** The input of the `setCompatSessionName` mutation. */
+export type SetCompatSessionNameInput = {
+  /** The ID of the session to rename. */
+  compatSessionId: Scalars["ID"]["input"];
+  /** The new name of the session. If `None`, the name will be removed. */
+  humanName?: InputMaybe<Scalars["String"]["input"]>;
+};
+
+export type SetCompatSessionNamePayload = {
+  __typename?: "SetCompatSessionNamePayload";
+  /** The session with the new name. */
+  compatSession?: Maybe<CompatSession>;
+  /** The status of the mutation. */
+  status: SetCompatSessionNameStatus;
+};
+
+/** The status of the `setCompatSessionName` mutation. */
+export enum SetCompatSessionNameStatus {
+  /** The name is invalid. */
+  Invalid = "INVALID",
+  /** The session was not found. */
+  NotFound = "NOT_FOUND",
+  /** The session was renamed. */
+  Updated = "UPDATED",
+}
+
 /** The input for the `addEmail` mutation */
 export type SetDisplayNameInput = {
   /** The display name to set. If `None`, the display name will be removed. */
@@ -832,6 +1290,59 @@ export enum SetDisplayNameStatus {
   Set = "SET",
 }
 
+/** The input of the `setOauth2SessionName` mutation. */
+export type SetOAuth2SessionNameInput = {
+  /** The ID of the session to rename. */
+  oauth2SessionId: Scalars["ID"]["input"];
+  /** The new name of the session. If `None`, the name will be removed. */
+  humanName?: InputMaybe<Scalars["String"]["input"]>;
+};
+
+export type SetOAuth2SessionNamePayload = {
+  __typename?: "SetOAuth2SessionNamePayload";
+  /** The session with the new name. */
+  oauth2Session?: Maybe<Oauth2Session>;
+  /** The status of the mutation. */
+  status: SetOAuth2SessionNameStatus;
+};
+
+/** The status of the `setOauth2SessionName` mutation. */
+export enum SetOAuth2SessionNameStatus {
+  /** The name is invalid. */
+  Invalid = "INVALID",
+  /** The session was not found. */
+  NotFound = "NOT_FOUND",
+  /** The session was renamed. */
+  Updated = "UPDATED",
+}
+
+/** The input for the `setPreferredLanguage` mutation. */
+export type SetPreferredLanguageInput = {
+  /** The preferred language of the user, as a BCP 47 language tag. */
+  locale: Scalars["String"]["input"];
+  /** The ID of the user to update. */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload for the `setPreferredLanguage` mutation. */
+export type SetPreferredLanguagePayload = {
+  __typename?: "SetPreferredLanguagePayload";
+  /** Status of the operation */
+  status: SetPreferredLanguageStatus;
+  /** The user that was updated. */
+  user?: Maybe<User>;
+};
+
+/** The status of the `setPreferredLanguage` mutation. */
+export enum SetPreferredLanguageStatus {
+  /** The language tag is invalid. */
+  Invalid = "INVALID",
+  /** The user was not found. */
+  NotFound = "NOT_FOUND",
+  /** The preferred language was updated. */
+  Updated = "UPDATED",
+}
+
 /** The input for the `setPrimaryEmail` mutation */
 export type SetPrimaryEmailInput = {
   /** The ID of the email address to set as primary */
@@ -856,6 +1367,66 @@ export enum SetPrimaryEmailStatus {
   Unverified = "UNVERIFIED",
 }
 
+/**
+ * A notification of a new sign-in of the user, shown in their other sessions
+ * until it is acknowledged.
+ */
+export type SignInNotification = CreationEvent &
+  Node & {
+    __typename?: "SignInNotification";
+    /** When the object was created. */
+    createdAt: Scalars["DateTime"]["output"];
+    /** ID of the object. */
+    id: Scalars["ID"]["output"];
+    /**
+     * The session started by the sign-in, which can be ended if the sign-in
+     * wasn't expected.
+     */
+    session: SignInSession;
+  };
+
+/** The session started by a sign-in. */
+export type SignInSession = BrowserSession | CompatSession;
+
+/** The subscription root of the GraphQL interface. */
+export type Subscription = {
+  __typename?: "Subscription";
+  /**
+   * Get the events which happen to the sessions of the current user: when
+   * they start, end, or when the user authenticates again in one of them.
+   */
+  sessionEvents: SessionEvent;
+};
+
+/** The input for the `uploadAvatar` mutation */
+export type UploadAvatarInput = {
+  /** The image to use as avatar */
+  file: Scalars["Upload"]["input"];
+  /** The ID of the user to set the avatar of */
+  userId: Scalars["ID"]["input"];
+};
+
+/** The payload of the `uploadAvatar` mutation */
+export type UploadAvatarPayload = {
+  __typename?: "UploadAvatarPayload";
+  /** Status of the operation */
+  status: UploadAvatarStatus;
+  /** The user that was updated */
+  user?: Maybe<User>;
+};
+
+/** The status of the `uploadAvatar` mutation */
+export enum UploadAvatarStatus {
+  /** Avatar uploads are disabled on this server */
+  Disabled = "DISABLED",
+  /** The file is not a PNG, JPEG, GIF or WebP image */
+  InvalidType = "INVALID_TYPE",
+  /** The file is larger than what the server accepts */
+  TooLarge = "TOO_LARGE",
+  /** The avatar was uploaded and set */
+  Uploaded = "UPLOADED",
+}
+
 export type UpstreamOAuth2Link = CreationEvent &
   Node & {
     __typename?: "UpstreamOAuth2Link";
@@ -944,18 +1515,48 @@ export type User = Node & {
   compatSsoLogins: CompatSsoLoginConnection;
   /** When the object was created. */
   createdAt: Scalars["DateTime"]["output"];
+  /**
+   * When the user was deleted. Deleted users can be restored until they
+   * are purged.
+   */
+  deletedAt?: Maybe<Scalars["DateTime"]["output"]>;
   /** Get the list of emails, chronologically sorted */
   emails: UserEmailConnection;
   /** ID of the object. */
   id: Scalars["ID"]["output"];
+  /**
+   * Whether the user is a service account, which can't log in
+   * interactively.
+   */
+  isServiceAccount: Scalars["Boolean"]["output"];
+  /** The preferred language of the user, as a BCP 47 language tag. */
+  locale?: Maybe<Scalars["String"]["output"]>;
   /** When the user was locked out. */
   lockedAt?: Maybe<Scalars["DateTime"]["output"]>;
+  /**
+   * Until when the user is temporarily locked out after too many failed
+   * login attempts.
+   */
+  loginLockedUntil?: Maybe<Scalars["DateTime"]["output"]>;
   /** Access to the user's Matrix account information. */
   matrix: MatrixUser;
   /** Get the list of OAuth 2.0 sessions, chronologically sorted */
   oauth2Sessions: Oauth2SessionConnection;
+  /** When an administrator required the user to choose a new password. */
+  passwordResetRequiredAt?: Maybe<Scalars["DateTime"]["output"]>;
   /** Primary email address of the user. */
   primaryEmail?: Maybe<UserEmail>;
+  /** When the data of the deleted user was erased. */
+  purgedAt?: Maybe<Scalars["DateTime"]["output"]>;
+  /** Number of recovery codes of the user which were not used yet. */
+  recoveryCodesRemaining: Scalars["Int"]["output"];
+  /**
+   * Whether the user accepted the current version of the terms of
+   * service. Always true if no terms of service are configured.
+   */
+  termsAccepted: Scalars["Boolean"]["output"];
+  /** When the user accepted the current version of the terms of service. */
+  termsAcceptedAt?: Maybe<Scalars["DateTime"]["output"]>;
   /** Get the list of upstream OAuth 2.0 links */
   upstreamOauth2Links: UpstreamOAuth2LinkConnection;
   /** Username chosen by the user. */
@@ -978,6 +1579,8 @@ export type UserBrowserSessionsArgs = {
   before?: InputMaybe<Scalars["String"]["input"]>;
   first?: InputMaybe<Scalars["Int"]["input"]>;
   last?: InputMaybe<Scalars["Int"]["input"]>;
+  lastActiveAfter?: InputMaybe<Scalars["DateTime"]["input"]>;
+  lastActiveBefore?: InputMaybe<Scalars["DateTime"]["input"]>;
   state?: InputMaybe<SessionState>;
 };
 
@@ -987,6 +1590,8 @@ export type UserCompatSessionsArgs = {
   before?: InputMaybe<Scalars["String"]["input"]>;
   first?: InputMaybe<Scalars["Int"]["input"]>;
   last?: InputMaybe<Scalars["Int"]["input"]>;
+  lastActiveAfter?: InputMaybe<Scalars["DateTime"]["input"]>;
+  lastActiveBefore?: InputMaybe<Scalars["DateTime"]["input"]>;
   state?: InputMaybe<SessionState>;
   type?: InputMaybe<CompatSessionType>;
 };
@@ -1015,6 +1620,8 @@ export type UserOauth2SessionsArgs = {
   client?: InputMaybe<Scalars["ID"]["input"]>;
   first?: InputMaybe<Scalars["Int"]["input"]>;
   last?: InputMaybe<Scalars["Int"]["input"]>;
+  lastActiveAfter?: InputMaybe<Scalars["DateTime"]["input"]>;
+  lastActiveBefore?: InputMaybe<Scalars["DateTime"]["input"]>;
   state?: InputMaybe<SessionState>;
 };
 
@@ -1026,6 +1633,27 @@ export type UserUpstreamOauth2LinksArgs = {
   last?: InputMaybe<Scalars["Int"]["input"]>;
 };
 
+export type UserConnection = {
+  __typename?: "UserConnection";
+  /** A list of edges. */
+  edges: Array<UserEdge>;
+  /** A list of nodes. */
+  nodes: Array<User>;
+  /** Information to aid in pagination. */
+  pageInfo: PageInfo;
+  /** Identifies the total count of items in the connection. */
+  totalCount: Scalars["Int"]["output"];
+};
+
+/** An edge in a connection. */
+export type UserEdge = {
+  __typename?: "UserEdge";
+  /** A cursor for use in pagination */
+  cursor: Scalars["String"]["output"];
+  /** The item at the end of the edge */
+  node: User;
+};
+
 /** A user email address */
 export type UserEmail = CreationEvent &
   Node & {
@@ -1072,6 +1700,14 @@ export enum UserEmailState {
   Pending = "PENDING",
 }
 
+/** The state of a user account. */
+export enum UserState {
+  /** The user can use their account. */
+  Active = "ACTIVE",
+  /** The user was locked out of their account by an administrator. */
+  Locked = "LOCKED",
+}
+
 /** The input for the `verifyEmail` mutation */
 export type VerifyEmailInput = {
   /** The verification code */
@@ -1095,6 +1731,8 @@ export type VerifyEmailPayload = {
 export enum VerifyEmailStatus {
   /** The email address was already verified before */
   AlreadyVerified = "ALREADY_VERIFIED",
+  /** The email address is already verified by another account */
+  InUse = "IN_USE",
   /** The verification code is invalid */
   InvalidCode = "INVALID_CODE",
   /** The email address was just verified */
@@ -1216,6 +1854,7 @@ export type CompatSession_SessionFragment = {
   id: string;
   createdAt: string;
   deviceId: string;
+  humanName?: string | null;
   finishedAt?: string | null;
   lastActiveIp?: string | null;
   lastActiveAt?: string | null;
@@ -1301,6 +1940,7 @@ export type CompatSession_DetailFragment = {
   id: string;
   createdAt: string;
   deviceId: string;
+  humanName?: string | null;
   finishedAt?: string | null;
   lastActiveIp?: string | null;
   lastActiveAt?: string | null;
@@ -1319,6 +1959,7 @@ export type OAuth2Session_DetailFragment = {
   finishedAt?: string | null;
   lastActiveIp?: string | null;
   lastActiveAt?: string | null;
+  expiresAt?: string | null;
   client: {
     __typename?: "Oauth2Client";
     id: string;
@@ -1408,6 +2049,7 @@ export type UserGreetingQuery = {
           __typename?: "MatrixUser";
           mxid: string;
           displayName?: string | null;
+          avatarUrl?: string | null;
         };
       } & {
         " $fragmentRefs"?: {
@@ -1428,6 +2070,7 @@ export type AddEmailMutation = {
     __typename?: "AddEmailPayload";
     status: AddEmailStatus;
     violations?: Array<string> | null;
+    retryAfter?: number | null;
     email?:
       | ({ __typename?: "UserEmail"; id: string } & {
           " $fragmentRefs"?: {
@@ -1450,6 +2093,24 @@ export type AllowCrossSigningResetMutation = {
   };
 };
 
+export type UploadAvatarMutationVariables = Exact<{
+  userId: Scalars["ID"]["input"];
+  file: Scalars["Upload"]["input"];
+}>;
+
+export type UploadAvatarMutation = {
+  __typename?: "Mutation";
+  uploadAvatar: {
+    __typename?: "UploadAvatarPayload";
+    status: UploadAvatarStatus;
+    user?: {
+      __typename?: "User";
+      id: string;
+      matrix: { __typename?: "MatrixUser"; avatarUrl?: string | null };
+    } | null;
+  };
+};
+
 export type UserEmailListQueryQueryVariables = Exact<{
   userId: Scalars["ID"]["input"];
   first?: InputMaybe<Scalars["Int"]["input"]>;
@@ -1609,6 +2270,7 @@ export type ResendVerificationEmailMutation = {
   sendVerificationEmail: {
     __typename?: "SendVerificationEmailPayload";
     status: SendVerificationEmailStatus;
+    retryAfter?: number | null;
     user: {
       __typename?: "User";
       id: string;
@@ -1757,6 +2419,7 @@ export const CompatSession_SessionFragmentDoc = {
           { kind: "Field", name: { kind: "Name", value: "id" } },
           { kind: "Field", name: { kind: "Name", value: "createdAt" } },
           { kind: "Field", name: { kind: "Name", value: "deviceId" } },
+          { kind: "Field", name: { kind: "Name", value: "humanName" } },
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
@@ -1879,6 +2542,7 @@ export const CompatSession_DetailFragmentDoc = {
           { kind: "Field", name: { kind: "Name", value: "id" } },
           { kind: "Field", name: { kind: "Name", value: "createdAt" } },
           { kind: "Field", name: { kind: "Name", value: "deviceId" } },
+          { kind: "Field", name: { kind: "Name", value: "humanName" } },
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
@@ -1917,6 +2581,7 @@ export const OAuth2Session_DetailFragmentDoc = {
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
+          { kind: "Field", name: { kind: "Name", value: "expiresAt" } },
           {
             kind: "Field",
             name: { kind: "Name", value: "client" },
@@ -2770,6 +3435,7 @@ export const SessionQueryDocument = {
           { kind: "Field", name: { kind: "Name", value: "id" } },
           { kind: "Field", name: { kind: "Name", value: "createdAt" } },
           { kind: "Field", name: { kind: "Name", value: "deviceId" } },
+          { kind: "Field", name: { kind: "Name", value: "humanName" } },
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
@@ -2803,6 +3469,7 @@ export const SessionQueryDocument = {
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
+          { kind: "Field", name: { kind: "Name", value: "expiresAt" } },
           {
             kind: "Field",
             name: { kind: "Name", value: "client" },
@@ -3018,6 +3685,10 @@ export const UserGreetingDocument = {
                         kind: "Field",
                         name: { kind: "Name", value: "displayName" },
                       },
+                      {
+                        kind: "Field",
+                        name: { kind: "Name", value: "avatarUrl" },
+                      },
                     ],
                   },
                 },
@@ -3142,6 +3813,7 @@ export const AddEmailDocument = {
               selections: [
                 { kind: "Field", name: { kind: "Name", value: "status" } },
                 { kind: "Field", name: { kind: "Name", value: "violations" } },
+                { kind: "Field", name: { kind: "Name", value: "retryAfter" } },
                 {
                   kind: "Field",
                   name: { kind: "Name", value: "email" },
@@ -3249,6 +3921,105 @@ export const AllowCrossSigningResetDocument = {
   AllowCrossSigningResetMutation,
   AllowCrossSigningResetMutationVariables
 >;
+export const UploadAvatarDocument = {
+  kind: "Document",
+  definitions: [
+    {
+      kind: "OperationDefinition",
+      operation: "mutation",
+      name: { kind: "Name", value: "UploadAvatar" },
+      variableDefinitions: [
+        {
+          kind: "VariableDefinition",
+          variable: {
+            kind: "Variable",
+            name: { kind: "Name", value: "userId" },
+          },
+          type: {
+            kind: "NonNullType",
+            type: { kind: "NamedType", name: { kind: "Name", value: "ID" } },
+          },
+        },
+        {
+          kind: "VariableDefinition",
+          variable: { kind: "Variable", name: { kind: "Name", value: "file" } },
+          type: {
+            kind: "NonNullType",
+            type: { kind: "NamedType", name: { kind: "Name", value: "Upload" } },
+          },
+        },
+      ],
+      selectionSet: {
+        kind: "SelectionSet",
+        selections: [
+          {
+            kind: "Field",
+            name: { kind: "Name", value: "uploadAvatar" },
+            arguments: [
+              {
+                kind: "Argument",
+                name: { kind: "Name", value: "input" },
+                value: {
+                  kind: "ObjectValue",
+                  fields: [
+                    {
+                      kind: "ObjectField",
+                      name: { kind: "Name", value: "userId" },
+                      value: {
+                        kind: "Variable",
+                        name: { kind: "Name", value: "userId" },
+                      },
+                    },
+                    {
+                      kind: "ObjectField",
+                      name: { kind: "Name", value: "file" },
+                      value: {
+                        kind: "Variable",
+                        name: { kind: "Name", value: "file" },
+                      },
+                    },
+                  ],
+                },
+              },
+            ],
+            selectionSet: {
+              kind: "SelectionSet",
+              selections: [
+                { kind: "Field", name: { kind: "Name", value: "status" } },
+                {
+                  kind: "Field",
+                  name: { kind: "Name", value: "user" },
+                  selectionSet: {
+                    kind: "SelectionSet",
+                    selections: [
+                      { kind: "Field", name: { kind: "Name", value: "id" } },
+                      {
+                        kind: "Field",
+                        name: { kind: "Name", value: "matrix" },
+                        selectionSet: {
+                          kind: "SelectionSet",
+                          selections: [
+                            {
+                              kind: "Field",
+                              name: { kind: "Name", value: "avatarUrl" },
+                            },
+                          ],
+                        },
+                      },
+                    ],
+                  },
+                },
+              ],
+            },
+          },
+        ],
+      },
+    },
+  ],
+} as unknown as DocumentNode<
+  UploadAvatarMutation,
+  UploadAvatarMutationVariables
+>;
 export const UserEmailListQueryDocument = {
   kind: "Document",
   definitions: [
@@ -3829,6 +4600,7 @@ export const AppSessionListDocument = {
           { kind: "Field", name: { kind: "Name", value: "id" } },
           { kind: "Field", name: { kind: "Name", value: "createdAt" } },
           { kind: "Field", name: { kind: "Name", value: "deviceId" } },
+          { kind: "Field", name: { kind: "Name", value: "humanName" } },
           { kind: "Field", name: { kind: "Name", value: "finishedAt" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveIp" } },
           { kind: "Field", name: { kind: "Name", value: "lastActiveAt" } },
@@ -4056,6 +4828,7 @@ export const ResendVerificationEmailDocument = {
               kind: "SelectionSet",
               selections: [
                 { kind: "Field", name: { kind: "Name", value: "status" } },
+                { kind: "Field", name: { kind: "Name", value: "retryAfter" } },
                 {
                   kind: "Field",
                   name: { kind: "Name", value: "user" },
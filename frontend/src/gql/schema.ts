@@ -7,8 +7,37 @@ export default {
     mutationType: {
       name: "Mutation",
     },
-    subscriptionType: null,
+    subscriptionType: {
+      name: "Subscription",
+    },
     types: [
+      {
+        kind: "OBJECT",
+        name: "AcknowledgeSignInPayload",
+        fields: [
+          {
+            name: "signInNotification",
+            type: {
+              kind: "OBJECT",
+              name: "SignInNotification",
+              ofType: null,
+            },
+            args: [],
+          },
+          {
+            name: "status",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
       {
         kind: "OBJECT",
         name: "AddEmailPayload",
@@ -22,6 +51,14 @@ export default {
             },
             args: [],
           },
+          {
+            name: "retryAfter",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "status",
             type: {
@@ -343,6 +380,24 @@ export default {
             },
             args: [],
           },
+          {
+            name: "unacknowledgedSignIns",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "SignInNotification",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [],
+          },
           {
             name: "user",
             type: {
@@ -505,6 +560,14 @@ export default {
             },
             args: [],
           },
+          {
+            name: "humanName",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "id",
             type: {
@@ -910,6 +973,10 @@ export default {
             kind: "OBJECT",
             name: "Oauth2Session",
           },
+          {
+            kind: "OBJECT",
+            name: "SignInNotification",
+          },
           {
             kind: "OBJECT",
             name: "UpstreamOAuth2Link",
@@ -924,6 +991,106 @@ export default {
           },
         ],
       },
+      {
+        kind: "OBJECT",
+        name: "DailyStatistics",
+        fields: [
+          {
+            name: "activeSessions",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "activeUsers",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "computedAt",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "day",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "newUsers",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "tokensIssued",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "DeleteUserPayload",
+        fields: [
+          {
+            name: "status",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "user",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
       {
         kind: "OBJECT",
         name: "EndBrowserSessionPayload",
@@ -978,6 +1145,44 @@ export default {
         ],
         interfaces: [],
       },
+      {
+        kind: "OBJECT",
+        name: "EndOAuth2ClientSessionsPayload",
+        fields: [
+          {
+            name: "endedSessions",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "oauth2Client",
+            type: {
+              kind: "OBJECT",
+              name: "Oauth2Client",
+              ofType: null,
+            },
+            args: [],
+          },
+          {
+            name: "status",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
       {
         kind: "OBJECT",
         name: "EndOAuth2SessionPayload",
@@ -1005,6 +1210,33 @@ export default {
         ],
         interfaces: [],
       },
+      {
+        kind: "OBJECT",
+        name: "ForcePasswordResetPayload",
+        fields: [
+          {
+            name: "status",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "user",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
       {
         kind: "OBJECT",
         name: "LockUserPayload",
@@ -1071,12 +1303,12 @@ export default {
         name: "Mutation",
         fields: [
           {
-            name: "addEmail",
+            name: "acknowledgeSignIn",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "AddEmailPayload",
+                name: "AcknowledgeSignInPayload",
                 ofType: null,
               },
             },
@@ -1094,12 +1326,12 @@ export default {
             ],
           },
           {
-            name: "addUser",
+            name: "addEmail",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "AddUserPayload",
+                name: "AddEmailPayload",
                 ofType: null,
               },
             },
@@ -1117,12 +1349,12 @@ export default {
             ],
           },
           {
-            name: "allowUserCrossSigningReset",
+            name: "addUser",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "AllowUserCrossSigningResetPayload",
+                name: "AddUserPayload",
                 ofType: null,
               },
             },
@@ -1140,12 +1372,12 @@ export default {
             ],
           },
           {
-            name: "createOauth2Session",
+            name: "allowUserCrossSigningReset",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "CreateOAuth2SessionPayload",
+                name: "AllowUserCrossSigningResetPayload",
                 ofType: null,
               },
             },
@@ -1163,12 +1395,12 @@ export default {
             ],
           },
           {
-            name: "endBrowserSession",
+            name: "createOauth2Session",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "EndBrowserSessionPayload",
+                name: "CreateOAuth2SessionPayload",
                 ofType: null,
               },
             },
@@ -1186,12 +1418,12 @@ export default {
             ],
           },
           {
-            name: "endCompatSession",
+            name: "deleteUser",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "EndCompatSessionPayload",
+                name: "DeleteUserPayload",
                 ofType: null,
               },
             },
@@ -1209,12 +1441,12 @@ export default {
             ],
           },
           {
-            name: "endOauth2Session",
+            name: "endBrowserSession",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "EndOAuth2SessionPayload",
+                name: "EndBrowserSessionPayload",
                 ofType: null,
               },
             },
@@ -1232,12 +1464,12 @@ export default {
             ],
           },
           {
-            name: "lockUser",
+            name: "endCompatSession",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "LockUserPayload",
+                name: "EndCompatSessionPayload",
                 ofType: null,
               },
             },
@@ -1255,12 +1487,12 @@ export default {
             ],
           },
           {
-            name: "removeEmail",
+            name: "endOauth2ClientSessions",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "RemoveEmailPayload",
+                name: "EndOAuth2ClientSessionsPayload",
                 ofType: null,
               },
             },
@@ -1278,12 +1510,12 @@ export default {
             ],
           },
           {
-            name: "sendVerificationEmail",
+            name: "endOauth2Session",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "SendVerificationEmailPayload",
+                name: "EndOAuth2SessionPayload",
                 ofType: null,
               },
             },
@@ -1301,12 +1533,12 @@ export default {
             ],
           },
           {
-            name: "setCanRequestAdmin",
+            name: "forcePasswordReset",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "SetCanRequestAdminPayload",
+                name: "ForcePasswordResetPayload",
                 ofType: null,
               },
             },
@@ -1324,12 +1556,12 @@ export default {
             ],
           },
           {
-            name: "setDisplayName",
+            name: "lockUser",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "SetDisplayNamePayload",
+                name: "LockUserPayload",
                 ofType: null,
               },
             },
@@ -1347,12 +1579,12 @@ export default {
             ],
           },
           {
-            name: "setPrimaryEmail",
+            name: "regenerateRecoveryCodes",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "SetPrimaryEmailPayload",
+                name: "RegenerateRecoveryCodesPayload",
                 ofType: null,
               },
             },
@@ -1370,12 +1602,265 @@ export default {
             ],
           },
           {
-            name: "verifyEmail",
+            name: "removeAvatar",
             type: {
               kind: "NON_NULL",
               ofType: {
                 kind: "OBJECT",
-                name: "VerifyEmailPayload",
+                name: "RemoveAvatarPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "removeEmail",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "RemoveEmailPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "restoreUser",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "RestoreUserPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "sendVerificationEmail",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SendVerificationEmailPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setCanRequestAdmin",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetCanRequestAdminPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setCompatSessionName",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetCompatSessionNamePayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setDisplayName",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetDisplayNamePayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setOauth2SessionName",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetOAuth2SessionNamePayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setPreferredLanguage",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetPreferredLanguagePayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "setPrimaryEmail",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "SetPrimaryEmailPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "uploadAvatar",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "UploadAvatarPayload",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "input",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "verifyEmail",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "VerifyEmailPayload",
                 ofType: null,
               },
             },
@@ -1441,6 +1926,10 @@ export default {
             kind: "OBJECT",
             name: "Oauth2Session",
           },
+          {
+            kind: "OBJECT",
+            name: "SignInNotification",
+          },
           {
             kind: "OBJECT",
             name: "UpstreamOAuth2Link",
@@ -1461,18 +1950,103 @@ export default {
       },
       {
         kind: "OBJECT",
-        name: "Oauth2Client",
+        name: "Oauth2AuthorizationGrantFunnel",
         fields: [
           {
-            name: "applicationType",
-            type: {
-              kind: "SCALAR",
-              name: "Any",
-            },
-            args: [],
-          },
-          {
-            name: "clientId",
+            name: "abandoned",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "client",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "Oauth2Client",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+          {
+            name: "completed",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "consentShown",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "errored",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "loginShown",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "started",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "Oauth2Client",
+        fields: [
+          {
+            name: "applicationType",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "clientId",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -1589,38 +2163,690 @@ export default {
             args: [],
           },
           {
-            name: "client",
+            name: "client",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "Oauth2Client",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+          {
+            name: "createdAt",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "expiresAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "finishedAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "humanName",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "id",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "lastActiveAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "lastActiveIp",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "scope",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "state",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "user",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [],
+          },
+        ],
+        interfaces: [
+          {
+            kind: "INTERFACE",
+            name: "CreationEvent",
+          },
+          {
+            kind: "INTERFACE",
+            name: "Node",
+          },
+        ],
+      },
+      {
+        kind: "OBJECT",
+        name: "Oauth2SessionConnection",
+        fields: [
+          {
+            name: "edges",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "Oauth2SessionEdge",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [],
+          },
+          {
+            name: "nodes",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "Oauth2Session",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [],
+          },
+          {
+            name: "pageInfo",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "PageInfo",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+          {
+            name: "totalCount",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "Oauth2SessionEdge",
+        fields: [
+          {
+            name: "cursor",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "node",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "Oauth2Session",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "PageInfo",
+        fields: [
+          {
+            name: "endCursor",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "hasNextPage",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "hasPreviousPage",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "startCursor",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "Query",
+        fields: [
+          {
+            name: "browserSession",
+            type: {
+              kind: "OBJECT",
+              name: "BrowserSession",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "currentBrowserSession",
+            type: {
+              kind: "OBJECT",
+              name: "BrowserSession",
+              ofType: null,
+            },
+            args: [],
+          },
+          {
+            name: "currentUser",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [],
+          },
+          {
+            name: "dailyStatistics",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "DailyStatistics",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [
+              {
+                name: "since",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "node",
+            type: {
+              kind: "INTERFACE",
+              name: "Node",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "oauth2AuthorizationGrantFunnel",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "Oauth2AuthorizationGrantFunnel",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [
+              {
+                name: "since",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "oauth2Client",
+            type: {
+              kind: "OBJECT",
+              name: "Oauth2Client",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "session",
+            type: {
+              kind: "UNION",
+              name: "Session",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "deviceId",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+              {
+                name: "userId",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "upstreamOauth2Link",
+            type: {
+              kind: "OBJECT",
+              name: "UpstreamOAuth2Link",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "upstreamOauth2Provider",
+            type: {
+              kind: "OBJECT",
+              name: "UpstreamOAuth2Provider",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "upstreamOauth2Providers",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "UpstreamOAuth2ProviderConnection",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "after",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "before",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "first",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "last",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+            ],
+          },
+          {
+            name: "user",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "userByUsername",
+            type: {
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "username",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "userEmail",
+            type: {
+              kind: "OBJECT",
+              name: "UserEmail",
+              ofType: null,
+            },
+            args: [
+              {
+                name: "id",
+                type: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "SCALAR",
+                    name: "Any",
+                  },
+                },
+              },
+            ],
+          },
+          {
+            name: "users",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "UserConnection",
+                ofType: null,
+              },
+            },
+            args: [
+              {
+                name: "after",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "before",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "createdAfter",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "createdBefore",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "first",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "last",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "search",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "state",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+            ],
+          },
+          {
+            name: "viewer",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "OBJECT",
-                name: "Oauth2Client",
+                kind: "UNION",
+                name: "Viewer",
                 ofType: null,
               },
             },
             args: [],
           },
           {
-            name: "createdAt",
+            name: "viewerSession",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "SCALAR",
-                name: "Any",
+                kind: "UNION",
+                name: "ViewerSession",
+                ofType: null,
               },
             },
             args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "RegenerateRecoveryCodesPayload",
+        fields: [
           {
-            name: "finishedAt",
+            name: "codes",
             type: {
-              kind: "SCALAR",
-              name: "Any",
+              kind: "LIST",
+              ofType: {
+                kind: "NON_NULL",
+                ofType: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
             },
             args: [],
           },
           {
-            name: "id",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -1631,34 +2857,23 @@ export default {
             args: [],
           },
           {
-            name: "lastActiveAt",
-            type: {
-              kind: "SCALAR",
-              name: "Any",
-            },
-            args: [],
-          },
-          {
-            name: "lastActiveIp",
-            type: {
-              kind: "SCALAR",
-              name: "Any",
-            },
-            args: [],
-          },
-          {
-            name: "scope",
+            name: "user",
             type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "SCALAR",
-                name: "Any",
-              },
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
             },
             args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "RemoveAvatarPayload",
+        fields: [
           {
-            name: "state",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -1678,77 +2893,38 @@ export default {
             args: [],
           },
         ],
-        interfaces: [
-          {
-            kind: "INTERFACE",
-            name: "CreationEvent",
-          },
-          {
-            kind: "INTERFACE",
-            name: "Node",
-          },
-        ],
+        interfaces: [],
       },
       {
         kind: "OBJECT",
-        name: "Oauth2SessionConnection",
+        name: "RemoveEmailPayload",
         fields: [
           {
-            name: "edges",
-            type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "LIST",
-                ofType: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "OBJECT",
-                    name: "Oauth2SessionEdge",
-                    ofType: null,
-                  },
-                },
-              },
-            },
-            args: [],
-          },
-          {
-            name: "nodes",
+            name: "email",
             type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "LIST",
-                ofType: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "OBJECT",
-                    name: "Oauth2Session",
-                    ofType: null,
-                  },
-                },
-              },
+              kind: "OBJECT",
+              name: "UserEmail",
+              ofType: null,
             },
             args: [],
           },
           {
-            name: "pageInfo",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "OBJECT",
-                name: "PageInfo",
-                ofType: null,
+                kind: "SCALAR",
+                name: "Any",
               },
             },
             args: [],
           },
           {
-            name: "totalCount",
+            name: "user",
             type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "SCALAR",
-                name: "Any",
-              },
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
             },
             args: [],
           },
@@ -1757,10 +2933,10 @@ export default {
       },
       {
         kind: "OBJECT",
-        name: "Oauth2SessionEdge",
+        name: "RestoreUserPayload",
         fields: [
           {
-            name: "cursor",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -1771,14 +2947,11 @@ export default {
             args: [],
           },
           {
-            name: "node",
+            name: "user",
             type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "OBJECT",
-                name: "Oauth2Session",
-                ofType: null,
-              },
+              kind: "OBJECT",
+              name: "User",
+              ofType: null,
             },
             args: [],
           },
@@ -1787,29 +2960,30 @@ export default {
       },
       {
         kind: "OBJECT",
-        name: "PageInfo",
+        name: "SendVerificationEmailPayload",
         fields: [
           {
-            name: "endCursor",
+            name: "email",
             type: {
-              kind: "SCALAR",
-              name: "Any",
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "UserEmail",
+                ofType: null,
+              },
             },
             args: [],
           },
           {
-            name: "hasNextPage",
+            name: "retryAfter",
             type: {
-              kind: "NON_NULL",
-              ofType: {
-                kind: "SCALAR",
-                name: "Any",
-              },
+              kind: "SCALAR",
+              name: "Any",
             },
             args: [],
           },
           {
-            name: "hasPreviousPage",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -1820,10 +2994,14 @@ export default {
             args: [],
           },
           {
-            name: "startCursor",
+            name: "user",
             type: {
-              kind: "SCALAR",
-              name: "Any",
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "User",
+                ofType: null,
+              },
             },
             args: [],
           },
@@ -1831,298 +3009,185 @@ export default {
         interfaces: [],
       },
       {
-        kind: "OBJECT",
-        name: "Query",
-        fields: [
-          {
-            name: "browserSession",
-            type: {
-              kind: "OBJECT",
-              name: "BrowserSession",
-              ofType: null,
-            },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
-          },
-          {
-            name: "currentBrowserSession",
-            type: {
-              kind: "OBJECT",
-              name: "BrowserSession",
-              ofType: null,
-            },
-            args: [],
-          },
+        kind: "UNION",
+        name: "Session",
+        possibleTypes: [
           {
-            name: "currentUser",
-            type: {
-              kind: "OBJECT",
-              name: "User",
-              ofType: null,
-            },
-            args: [],
+            kind: "OBJECT",
+            name: "CompatSession",
           },
           {
-            name: "node",
-            type: {
-              kind: "INTERFACE",
-              name: "Node",
-              ofType: null,
-            },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
+            kind: "OBJECT",
+            name: "Oauth2Session",
           },
+        ],
+      },
+      {
+        kind: "OBJECT",
+        name: "SessionEvent",
+        fields: [
           {
-            name: "oauth2Client",
+            name: "kind",
             type: {
-              kind: "OBJECT",
-              name: "Oauth2Client",
-              ofType: null,
-            },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
               },
-            ],
+            },
+            args: [],
           },
           {
             name: "session",
             type: {
-              kind: "UNION",
-              name: "Session",
+              kind: "INTERFACE",
+              name: "Node",
               ofType: null,
             },
-            args: [
-              {
-                name: "deviceId",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-              {
-                name: "userId",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
+            args: [],
+          },
+          {
+            name: "sessionId",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
               },
-            ],
+            },
+            args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetCanRequestAdminPayload",
+        fields: [
           {
-            name: "upstreamOauth2Link",
+            name: "user",
             type: {
               kind: "OBJECT",
-              name: "UpstreamOAuth2Link",
+              name: "User",
               ofType: null,
             },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
+            args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetCompatSessionNamePayload",
+        fields: [
           {
-            name: "upstreamOauth2Provider",
+            name: "compatSession",
             type: {
               kind: "OBJECT",
-              name: "UpstreamOAuth2Provider",
+              name: "CompatSession",
               ofType: null,
             },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
+            args: [],
           },
           {
-            name: "upstreamOauth2Providers",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "OBJECT",
-                name: "UpstreamOAuth2ProviderConnection",
-                ofType: null,
+                kind: "SCALAR",
+                name: "Any",
               },
             },
-            args: [
-              {
-                name: "after",
-                type: {
-                  kind: "SCALAR",
-                  name: "Any",
-                },
-              },
-              {
-                name: "before",
-                type: {
-                  kind: "SCALAR",
-                  name: "Any",
-                },
-              },
-              {
-                name: "first",
-                type: {
-                  kind: "SCALAR",
-                  name: "Any",
-                },
-              },
-              {
-                name: "last",
-                type: {
-                  kind: "SCALAR",
-                  name: "Any",
-                },
-              },
-            ],
+            args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetDisplayNamePayload",
+        fields: [
           {
-            name: "user",
+            name: "status",
             type: {
-              kind: "OBJECT",
-              name: "User",
-              ofType: null,
-            },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
               },
-            ],
+            },
+            args: [],
           },
           {
-            name: "userByUsername",
+            name: "user",
             type: {
               kind: "OBJECT",
               name: "User",
               ofType: null,
             },
-            args: [
-              {
-                name: "username",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
+            args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetOAuth2SessionNamePayload",
+        fields: [
           {
-            name: "userEmail",
+            name: "oauth2Session",
             type: {
               kind: "OBJECT",
-              name: "UserEmail",
+              name: "Oauth2Session",
               ofType: null,
             },
-            args: [
-              {
-                name: "id",
-                type: {
-                  kind: "NON_NULL",
-                  ofType: {
-                    kind: "SCALAR",
-                    name: "Any",
-                  },
-                },
-              },
-            ],
+            args: [],
           },
           {
-            name: "viewer",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "UNION",
-                name: "Viewer",
-                ofType: null,
+                kind: "SCALAR",
+                name: "Any",
               },
             },
             args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetPreferredLanguagePayload",
+        fields: [
           {
-            name: "viewerSession",
+            name: "status",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "UNION",
-                name: "ViewerSession",
-                ofType: null,
+                kind: "SCALAR",
+                name: "Any",
               },
             },
             args: [],
           },
-        ],
-        interfaces: [],
-      },
-      {
-        kind: "OBJECT",
-        name: "RemoveEmailPayload",
-        fields: [
           {
-            name: "email",
+            name: "user",
             type: {
               kind: "OBJECT",
-              name: "UserEmail",
+              name: "User",
               ofType: null,
             },
             args: [],
           },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "SetPrimaryEmailPayload",
+        fields: [
           {
             name: "status",
             type: {
@@ -2148,22 +3213,21 @@ export default {
       },
       {
         kind: "OBJECT",
-        name: "SendVerificationEmailPayload",
+        name: "SignInNotification",
         fields: [
           {
-            name: "email",
+            name: "createdAt",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "OBJECT",
-                name: "UserEmail",
-                ofType: null,
+                kind: "SCALAR",
+                name: "Any",
               },
             },
             args: [],
           },
           {
-            name: "status",
+            name: "id",
             type: {
               kind: "NON_NULL",
               ofType: {
@@ -2174,80 +3238,65 @@ export default {
             args: [],
           },
           {
-            name: "user",
+            name: "session",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "OBJECT",
-                name: "User",
+                kind: "UNION",
+                name: "SignInSession",
                 ofType: null,
               },
             },
             args: [],
           },
         ],
-        interfaces: [],
+        interfaces: [
+          {
+            kind: "INTERFACE",
+            name: "CreationEvent",
+          },
+          {
+            kind: "INTERFACE",
+            name: "Node",
+          },
+        ],
       },
       {
         kind: "UNION",
-        name: "Session",
+        name: "SignInSession",
         possibleTypes: [
           {
             kind: "OBJECT",
-            name: "CompatSession",
+            name: "BrowserSession",
           },
           {
             kind: "OBJECT",
-            name: "Oauth2Session",
-          },
-        ],
-      },
-      {
-        kind: "OBJECT",
-        name: "SetCanRequestAdminPayload",
-        fields: [
-          {
-            name: "user",
-            type: {
-              kind: "OBJECT",
-              name: "User",
-              ofType: null,
-            },
-            args: [],
+            name: "CompatSession",
           },
         ],
-        interfaces: [],
       },
       {
         kind: "OBJECT",
-        name: "SetDisplayNamePayload",
+        name: "Subscription",
         fields: [
           {
-            name: "status",
+            name: "sessionEvents",
             type: {
               kind: "NON_NULL",
               ofType: {
-                kind: "SCALAR",
-                name: "Any",
+                kind: "OBJECT",
+                name: "SessionEvent",
+                ofType: null,
               },
             },
             args: [],
           },
-          {
-            name: "user",
-            type: {
-              kind: "OBJECT",
-              name: "User",
-              ofType: null,
-            },
-            args: [],
-          },
         ],
         interfaces: [],
       },
       {
         kind: "OBJECT",
-        name: "SetPrimaryEmailPayload",
+        name: "UploadAvatarPayload",
         fields: [
           {
             name: "status",
@@ -2692,6 +3741,20 @@ export default {
                   name: "Any",
                 },
               },
+              {
+                name: "lastActiveAfter",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "lastActiveBefore",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
               {
                 name: "state",
                 type: {
@@ -2751,6 +3814,20 @@ export default {
                   name: "Any",
                 },
               },
+              {
+                name: "lastActiveAfter",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "lastActiveBefore",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
               {
                 name: "state",
                 type: {
@@ -2819,6 +3896,14 @@ export default {
             },
             args: [],
           },
+          {
+            name: "deletedAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "emails",
             type: {
@@ -2878,6 +3963,25 @@ export default {
             },
             args: [],
           },
+          {
+            name: "isServiceAccount",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "locale",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "lockedAt",
             type: {
@@ -2886,6 +3990,14 @@ export default {
             },
             args: [],
           },
+          {
+            name: "loginLockedUntil",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "matrix",
             type: {
@@ -2944,6 +4056,20 @@ export default {
                   name: "Any",
                 },
               },
+              {
+                name: "lastActiveAfter",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
+              {
+                name: "lastActiveBefore",
+                type: {
+                  kind: "SCALAR",
+                  name: "Any",
+                },
+              },
               {
                 name: "state",
                 type: {
@@ -2953,6 +4079,14 @@ export default {
               },
             ],
           },
+          {
+            name: "passwordResetRequiredAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "primaryEmail",
             type: {
@@ -2962,6 +4096,44 @@ export default {
             },
             args: [],
           },
+          {
+            name: "purgedAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
+          {
+            name: "recoveryCodesRemaining",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "termsAccepted",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "termsAcceptedAt",
+            type: {
+              kind: "SCALAR",
+              name: "Any",
+            },
+            args: [],
+          },
           {
             name: "upstreamOauth2Links",
             type: {
@@ -3022,6 +4194,102 @@ export default {
           },
         ],
       },
+      {
+        kind: "OBJECT",
+        name: "UserConnection",
+        fields: [
+          {
+            name: "edges",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "UserEdge",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [],
+          },
+          {
+            name: "nodes",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "LIST",
+                ofType: {
+                  kind: "NON_NULL",
+                  ofType: {
+                    kind: "OBJECT",
+                    name: "User",
+                    ofType: null,
+                  },
+                },
+              },
+            },
+            args: [],
+          },
+          {
+            name: "pageInfo",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "PageInfo",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+          {
+            name: "totalCount",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
+      {
+        kind: "OBJECT",
+        name: "UserEdge",
+        fields: [
+          {
+            name: "cursor",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "SCALAR",
+                name: "Any",
+              },
+            },
+            args: [],
+          },
+          {
+            name: "node",
+            type: {
+              kind: "NON_NULL",
+              ofType: {
+                kind: "OBJECT",
+                name: "User",
+                ofType: null,
+              },
+            },
+            args: [],
+          },
+        ],
+        interfaces: [],
+      },
       {
         kind: "OBJECT",
         name: "UserEmail",
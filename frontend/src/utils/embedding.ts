@@ -0,0 +1,31 @@
+// Copyright 2024 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import appConfig from "../config";
+
+/** Actions which, once completed, are reported to the embedding client */
+export type CompletedAction = "session_ended" | "email_verified";
+
+/**
+ * Tell the client embedding the app in a frame, if any, that the user
+ * completed an action. The message is only sent to the origins allowed to
+ * embed the app.
+ */
+export const notifyEmbedder = (action: CompletedAction): void => {
+  if (!appConfig.embedded || window.parent === window) return;
+
+  for (const origin of appConfig.embeddingOrigins ?? []) {
+    window.parent.postMessage({ type: "mas.completed", action }, origin);
+  }
+};
@@ -0,0 +1,45 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import { useEffect, useState } from "react";
+
+/**
+ * Count down from the given number of seconds, one second at a time,
+ * restarting whenever the `key` changes, e.g. with each new mutation result
+ */
+export const useCountdown = (
+  seconds: number | null | undefined,
+  key?: unknown,
+): number => {
+  const [remaining, setRemaining] = useState(seconds ?? 0);
+
+  useEffect(() => {
+    setRemaining(seconds ?? 0);
+    if (!seconds) return;
+
+    const interval = setInterval(() => {
+      setRemaining((value) => {
+        if (value <= 1) {
+          clearInterval(interval);
+          return 0;
+        }
+        return value - 1;
+      });
+    }, 1000);
+
+    return (): void => clearInterval(interval);
+  }, [seconds, key]);
+
+  return remaining;
+};
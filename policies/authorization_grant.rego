@@ -23,6 +23,25 @@ can_request_admin(user) {
 	user.can_request_admin
 }
 
+# 3. They are in one of the admin_groups, e.g. imported from an upstream provider
+can_request_admin(_) {
+	some group in input.groups
+	group in data.admin_groups
+}
+
+# Grants where the user is present to give their consent
+interactive_grant_type("authorization_code") = true
+
+interactive_grant_type("urn:ietf:params:oauth:grant-type:device_code") = true
+
+# Grants acting on behalf of a user, either directly or by exchanging a token
+# the user consented to
+user_grant_type(grant_type) {
+	interactive_grant_type(grant_type)
+}
+
+user_grant_type("urn:ietf:params:oauth:grant-type:token-exchange") = true
+
 # Special case to make empty scope work
 allowed_scope("") = true
 
@@ -32,8 +51,8 @@ allowed_scope("email") = true
 
 # This grants access to Synapse's admin API endpoints
 allowed_scope("urn:synapse:admin:*") {
-	# Synapse doesn't support user-less tokens yet, so access to the admin API can only be used with an authorization_code grant as the user is present
-	input.grant_type == "authorization_code"
+	# Synapse doesn't support user-less tokens yet, so access to the admin API can only be used with an interactive grant as the user is present
+	interactive_grant_type(input.grant_type)
 	can_request_admin(input.user)
 }
 
@@ -42,7 +61,7 @@ allowed_scope("urn:mas:graphql:*") = true
 
 # This makes it possible to query and do anything in the GraphQL API as an admin
 allowed_scope("urn:mas:admin") {
-	input.grant_type == "authorization_code"
+	interactive_grant_type(input.grant_type)
 	can_request_admin(input.user)
 }
 
@@ -55,15 +74,59 @@ allowed_scope("urn:mas:admin") {
 
 allowed_scope(scope) {
 	# Grant access to the C-S API only if there is a user
-	input.grant_type == "authorization_code"
+	user_grant_type(input.grant_type)
 	regex.match("urn:matrix:org.matrix.msc2967.client:device:[A-Za-z0-9-]{10,}", scope)
 }
 
 allowed_scope("urn:matrix:org.matrix.msc2967.client:api:*") {
 	# Grant access to the C-S API only if there is a user
-	input.grant_type == "authorization_code"
+	user_grant_type(input.grant_type)
+}
+
+# Custom scopes declared by the operator in the `scopes` configuration section
+allowed_scope(scope) {
+	some custom_scope in data.custom_scopes
+	custom_scope.name == scope
+	custom_scope_grant_allowed(custom_scope)
+	custom_scope_client_allowed(custom_scope)
+}
+
+# Custom scopes can always be requested through the client credentials grant
+custom_scope_grant_allowed(_) {
+	input.grant_type == "client_credentials"
+}
+
+# ...but users can only grant the ones marked as consentable
+custom_scope_grant_allowed(custom_scope) {
+	user_grant_type(input.grant_type)
+	custom_scope.user_consentable
+}
+
+# If no list of clients is set, any client can request the scope
+custom_scope_client_allowed(custom_scope) {
+	not custom_scope.clients
 }
 
+custom_scope_client_allowed(custom_scope) {
+	some client in custom_scope.clients
+	input.client.id == client
+}
+
+# Claims clients can ask for with the `claims` parameter
+allowed_claim("sub") = true
+
+allowed_claim("auth_time") = true
+
+allowed_claim("acr") = true
+
+allowed_claim("amr") = true
+
+allowed_claim("username") = true
+
+allowed_claim("email") = true
+
+allowed_claim("email_verified") = true
+
 violation[{"msg": msg}] {
 	some scope in split(input.scope, " ")
 	not allowed_scope(scope)
@@ -74,3 +137,23 @@ violation[{"msg": "only one device scope is allowed at a time"}] {
 	scope_list := split(input.scope, " ")
 	count({key | scope_list[key]; startswith(scope_list[key], "urn:matrix:org.matrix.msc2967.client:device:")}) > 1
 }
+
+# Only the clients listed in the policy data can exchange tokens
+violation[{"msg": "client not allowed to exchange tokens"}] {
+	input.grant_type == "urn:ietf:params:oauth:grant-type:token-exchange"
+	not token_exchange_client_allowed
+}
+
+# Violations on requested claims don't deny the grant: those claims are only
+# left out of what the client receives
+violation[{"msg": msg, "field": field}] {
+	some claim in input.claims
+	not allowed_claim(claim)
+	msg := sprintf("claim '%s' not allowed", [claim])
+	field := sprintf("claims.%s", [claim])
+}
+
+token_exchange_client_allowed {
+	some client in data.token_exchange_clients
+	input.client.id == client
+}
@@ -37,6 +37,15 @@ test_matrix_scopes {
 		with input.client as client
 		with input.grant_type as "authorization_code"
 		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+
+	allow with input.user as user
+		with input.client as client
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:device_code"
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+
+	not allow with input.client as client
+		with input.grant_type as "client_credentials"
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
 }
 
 test_device_scopes {
@@ -110,6 +119,28 @@ test_synapse_admin_scopes {
 		with data.admin_users as []
 		with input.grant_type as "authorization_code"
 		with input.scope as "urn:synapse:admin:*"
+
+	allow with input.user as user
+		with input.client as client
+		with data.admin_users as ["john"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:device_code"
+		with input.scope as "urn:synapse:admin:*"
+
+	allow with input.user as user
+		with input.groups as ["staff", "admins"]
+		with input.client as client
+		with data.admin_users as []
+		with data.admin_groups as ["admins"]
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:synapse:admin:*"
+
+	not allow with input.user as user
+		with input.groups as ["staff"]
+		with input.client as client
+		with data.admin_users as []
+		with data.admin_groups as ["admins"]
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:synapse:admin:*"
 }
 
 test_mas_scopes {
@@ -129,3 +160,146 @@ test_mas_scopes {
 		with input.grant_type as "authorization_code"
 		with input.scope as "urn:mas:admin"
 }
+
+test_custom_scopes {
+	custom_scopes := [
+		{"name": "urn:example:read", "user_consentable": true},
+		{"name": "urn:example:bot", "user_consentable": false},
+		{"name": "urn:example:restricted", "user_consentable": true, "clients": ["other"]},
+	]
+
+	allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "authorization_code"
+		with input.scope as "openid urn:example:read"
+
+	allow with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "client_credentials"
+		with input.scope as "urn:example:read"
+
+	# Not declared
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:example:write"
+
+	# Users can't grant scopes which are not consentable
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:example:bot"
+
+	allow with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "client_credentials"
+		with input.scope as "urn:example:bot"
+
+	# Restricted to other clients
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:example:restricted"
+
+	allow with input.user as user
+		with input.client as {"id": "other"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "authorization_code"
+		with input.scope as "urn:example:restricted"
+
+	not allow with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "client_credentials"
+		with input.scope as "urn:example:restricted"
+
+	# The same rules apply to the device code grant and token exchanges
+	allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:device_code"
+		with input.scope as "urn:example:read"
+
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:device_code"
+		with input.scope as "urn:example:bot"
+
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:device_code"
+		with input.scope as "urn:example:write"
+
+	allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:example:read"
+
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:example:bot"
+
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.custom_scopes as custom_scopes
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:example:restricted"
+}
+
+test_token_exchange {
+	# Clients need to be explicitly allowed to exchange tokens
+	not allow with input.user as user
+		with input.client as {"id": "client"}
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+
+	allow with input.user as user
+		with input.client as {"id": "client"}
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+
+	not allow with input.user as user
+		with input.client as {"id": "other"}
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:matrix:org.matrix.msc2967.client:api:*"
+
+	# Admin scopes can't be exchanged
+	not allow with input.user as {"username": "john", "can_request_admin": true}
+		with input.client as {"id": "client"}
+		with data.token_exchange_clients as ["client"]
+		with input.grant_type as "urn:ietf:params:oauth:grant-type:token-exchange"
+		with input.scope as "urn:synapse:admin:*"
+}
+
+test_claims {
+	allow with input.user as user
+		with input.client as client
+		with input.grant_type as "authorization_code"
+		with input.scope as "openid"
+		with input.claims as ["email", "email_verified", "acr"]
+
+	not allow with input.user as user
+		with input.client as client
+		with input.grant_type as "authorization_code"
+		with input.scope as "openid"
+		with input.claims as ["email", "phone_number"]
+
+	violation[{"msg": "claim 'phone_number' not allowed", "field": "claims.phone_number"}] with input.user as user
+		with input.client as client
+		with input.grant_type as "authorization_code"
+		with input.scope as "openid"
+		with input.claims as ["phone_number"]
+}
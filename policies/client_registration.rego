@@ -96,6 +96,16 @@ violation[{"msg": "logo_uri not on the same host as the client_uri"}] {
 	not host_matches_client_uri(input.client_metadata.logo_uri)
 }
 
+violation[{"msg": "invalid backchannel_logout_uri"}] {
+	input.client_metadata.backchannel_logout_uri
+	not secure_url(input.client_metadata.backchannel_logout_uri)
+}
+
+violation[{"msg": "backchannel_logout_uri not on the same host as the client_uri"}] {
+	input.client_metadata.backchannel_logout_uri
+	not host_matches_client_uri(input.client_metadata.backchannel_logout_uri)
+}
+
 violation[{"msg": "missing contacts"}] {
 	not data.client_registration.allow_missing_contacts
 	not input.client_metadata.contacts
@@ -138,6 +148,11 @@ violation[{"msg": "client_credentials grant_type requires some form of client au
 	is_public_client
 }
 
+violation[{"msg": "token-exchange grant_type requires some form of client authentication"}] {
+	uses_grant_type("urn:ietf:params:oauth:grant-type:token-exchange")
+	is_public_client
+}
+
 violation[{"msg": "missing redirect_uris"}] {
 	requires_redirect_uris
 	not input.client_metadata.redirect_uris
@@ -230,3 +245,8 @@ violation[{"msg": "invalid redirect_uri", "redirect_uri": redirect_uri}] {
 	some redirect_uri in input.client_metadata.redirect_uris
 	not valid_redirect_uri(redirect_uri)
 }
+
+violation[{"msg": "invalid post_logout_redirect_uri", "post_logout_redirect_uri": redirect_uri}] {
+	some redirect_uri in input.client_metadata.post_logout_redirect_uris
+	not valid_redirect_uri(redirect_uri)
+}
@@ -434,6 +434,49 @@ test_client_credentials_grant {
 	}
 }
 
+test_token_exchange_grant {
+	# Allowed for confidential clients
+	allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+		"token_endpoint_auth_method": "client_secret_basic",
+		"client_uri": "https://example.com/",
+		"contacts": ["contact@example.com"],
+	}
+
+	# Disallowed for public clients
+	not allow with input.client_metadata as {
+		"grant_types": ["urn:ietf:params:oauth:grant-type:token-exchange"],
+		"token_endpoint_auth_method": "none",
+		"client_uri": "https://example.com/",
+		"contacts": ["contact@example.com"],
+	}
+}
+
+test_post_logout_redirect_uris {
+	allow with input.client_metadata as {
+		"client_uri": "https://example.com/",
+		"redirect_uris": ["https://example.com/callback"],
+		"post_logout_redirect_uris": ["https://example.com/logged-out"],
+		"contacts": ["contact@example.com"],
+	}
+
+	# Insecure URL
+	not allow with input.client_metadata as {
+		"client_uri": "https://example.com/",
+		"redirect_uris": ["https://example.com/callback"],
+		"post_logout_redirect_uris": ["http://example.com/logged-out"],
+		"contacts": ["contact@example.com"],
+	}
+
+	# Host mismatch
+	not allow with input.client_metadata as {
+		"client_uri": "https://example.com/",
+		"redirect_uris": ["https://example.com/callback"],
+		"post_logout_redirect_uris": ["https://example.org/logged-out"],
+		"contacts": ["contact@example.com"],
+	}
+}
+
 test_is_subdomain {
 	is_subdomain("example.com", "example.com")
 	is_subdomain("example.com", "app.example.com")
@@ -447,3 +490,28 @@ test_reverse_dns_match {
 	not reverse_dns_match("example.com", "org.example")
 	not reverse_dns_match("test.com", "com.example")
 }
+
+test_backchannel_logout_uri {
+	allow with input.client_metadata as {
+		"grant_types": [],
+		"client_uri": "https://example.com/",
+		"backchannel_logout_uri": "https://example.com/logout",
+		"contacts": ["contact@example.com"],
+	}
+
+	# Insecure
+	not allow with input.client_metadata as {
+		"grant_types": [],
+		"client_uri": "https://example.com/",
+		"backchannel_logout_uri": "http://example.com/logout",
+		"contacts": ["contact@example.com"],
+	}
+
+	# Host mismatch
+	not allow with input.client_metadata as {
+		"grant_types": [],
+		"client_uri": "https://example.com/",
+		"backchannel_logout_uri": "https://example.org/logout",
+		"contacts": ["contact@example.com"],
+	}
+}
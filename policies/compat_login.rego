@@ -0,0 +1,17 @@
+# METADATA
+# schemas:
+#   - input: schema["compat_login_input"]
+package compat_login
+
+default allow := false
+
+allow {
+	count(violation) == 0
+}
+
+# Block password logins once the grace period of their deprecation is over.
+# During the grace period, users are only warned by email.
+violation[{"msg": "password login is no longer allowed, sign in through the browser instead"}] {
+	input.login_type == "m.login.password"
+	input.deprecated_for >= input.grace_period
+}
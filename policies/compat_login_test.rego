@@ -0,0 +1,22 @@
+package compat_login
+
+test_not_deprecated {
+	allow with input.login_type as "m.login.password"
+		with input.grace_period as 3600
+}
+
+test_grace_period {
+	allow with input.login_type as "m.login.password"
+		with input.deprecated_for as 60
+		with input.grace_period as 3600
+
+	not allow with input.login_type as "m.login.password"
+		with input.deprecated_for as 3600
+		with input.grace_period as 3600
+}
+
+test_token_login {
+	allow with input.login_type as "m.login.token"
+		with input.deprecated_for as 7200
+		with input.grace_period as 3600
+}